@@ -70,6 +70,12 @@ import { User } from "trailbase";
 
 export const assets = new Map<OAuthProviderId, string>([
   [OAuthProviderId.OIDC0, openIdConnect],
+  [OAuthProviderId.OIDC1, openIdConnect],
+  [OAuthProviderId.OIDC2, openIdConnect],
+  [OAuthProviderId.OIDC3, openIdConnect],
+  [OAuthProviderId.OIDC4, openIdConnect],
+  [OAuthProviderId.OIDC5, openIdConnect],
+  [OAuthProviderId.OIDC6, openIdConnect],
   [OAuthProviderId.APPLE, apple],
   [OAuthProviderId.DISCORD, discord],
   [OAuthProviderId.FACEBOOK, facebook],
@@ -81,6 +87,10 @@ export const assets = new Map<OAuthProviderId, string>([
   [OAuthProviderId.YANDEX, yandex],
 ]);
 
+function isOidcProvider(id: number): boolean {
+  return id >= OAuthProviderId.OIDC0 && id <= OAuthProviderId.OIDC6;
+}
+
 // Using a proxy struct for oauth providers, since tanstack only deals with arrays and not maps.
 // And rather than trying to hack it an converting on the fly, we're converting
 // once upfront from config to proxy and back on submission.
@@ -241,7 +251,21 @@ function ProviderSettingsSubForm(props: {
             })}
           </props.form.Field>
 
-          <Show when={props.provider.id === OAuthProviderId.OIDC0}>
+          <Show when={isOidcProvider(props.provider.id)}>
+            <props.form.Field
+              name={`namedOAuthProviders[${props.index}].state.issuerUrl`}
+            >
+              {buildOptionalTextFormField({
+                label: () => <L>Issuer URL</L>,
+                info: (
+                  <p>
+                    Discovers the endpoints below and validates ID tokens, e.g.
+                    for Keycloak, Okta or Entra ID.
+                  </p>
+                ),
+              })}
+            </props.form.Field>
+
             <props.form.Field
               name={`namedOAuthProviders[${props.index}].state.authUrl`}
             >
@@ -1,7 +1,11 @@
-import { Switch, Match, Index } from "solid-js";
+import { Switch, Match, Index, Show } from "solid-js";
 import { createForm } from "@tanstack/solid-form";
 import { useQueryClient, useQuery } from "@tanstack/solid-query";
-import { TbOutlinePlayerPlay, TbOutlineInfoCircle } from "solid-icons/tb";
+import {
+  TbOutlinePlayerPlay,
+  TbOutlinePlayerPause,
+  TbOutlineInfoCircle,
+} from "solid-icons/tb";
 
 import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardHeader } from "@/components/ui/card";
@@ -25,7 +29,7 @@ import {
 import { type FieldApiT, FieldInfo } from "@/components/FormFields";
 import { Config, JobsConfig, SystemJob } from "@proto/config";
 import { createConfigQuery, setConfig } from "@/lib/api/config";
-import { listJobs, runJob } from "@/lib/api/jobs";
+import { listJobs, pauseJob, resumeJob, runJob } from "@/lib/api/jobs";
 import type { Job } from "@bindings/Job";
 
 const cronRegex =
@@ -109,6 +113,11 @@ function extractConfig(proxy: FormProxy): JobsConfig {
   const systemJobs: SystemJob[] = [];
 
   for (const entry of proxy.jobs) {
+    // User-defined jobs are persisted in the database rather than the config.
+    if (entry.job?.query) {
+      continue;
+    }
+
     // Only add entries that were part of the original config or have changed from the initial default.
     if (entry.default === false) {
       systemJobs.push(entry.config);
@@ -349,6 +358,36 @@ function JobSettingsImpl(props: {
                               >
                                 <TbOutlinePlayerPlay />
                               </IconButton>
+
+                              <IconButton
+                                tooltip={
+                                  proxy().job?.enabled ? "Pause" : "Resume"
+                                }
+                                type="button"
+                                onClick={() => {
+                                  const job = proxy().job;
+                                  if (job) {
+                                    (async () => {
+                                      try {
+                                        if (job.enabled) {
+                                          await pauseJob({ id: job.id });
+                                        } else {
+                                          await resumeJob({ id: job.id });
+                                        }
+                                      } finally {
+                                        props.refetchJobs();
+                                      }
+                                    })();
+                                  }
+                                }}
+                              >
+                                <Show
+                                  when={proxy().job?.enabled}
+                                  fallback={<TbOutlinePlayerPlay />}
+                                >
+                                  <TbOutlinePlayerPause />
+                                </Show>
+                              </IconButton>
                             </div>
                           </TableCell>
                         </TableRow>
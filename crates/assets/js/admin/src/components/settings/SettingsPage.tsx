@@ -325,6 +325,11 @@ function SystemInformation(props: { systemInfo: InfoResponse }) {
           {info().command_line_arguments?.join(" ")}
         </span>
 
+        <TextFieldLabel class={width}>Statement Cache:</TextFieldLabel>
+        <span>
+          {`${info().statement_cache_hits} hits / ${info().statement_cache_misses} misses`}
+        </span>
+
         <Show when={props.systemInfo.postgres}>
           <TextFieldLabel class={width}>Postgres:</TextFieldLabel>
           <span>enabled</span>
@@ -49,6 +49,7 @@ export function CreateAlterIndexForm(props: {
       ascending: false,
       // Sqlite doesn't support nulls_first, i.e. this parameter must be "null".
       nulls_first: null,
+      expression: false,
     };
   };
 
@@ -246,7 +246,7 @@ function removeRecordApiConfig(
 
   while (true) {
     const index = newConfig.recordApis.findIndex(
-      (api) => api.tableName === tableName && api.name === apiName,
+      (api) => recordApiTableName(api) === tableName && api.name === apiName,
     );
     if (index < 0) {
       break;
@@ -273,12 +273,21 @@ function ConflictResolutionStrategyToString(
   }
 }
 
+// Referenced table, qualified with the API's database if set.
+function recordApiTableName(api: RecordApiConfig): string {
+  const db = api.database;
+  if (db && db !== "main" && !api.tableName?.includes(".")) {
+    return `${db}.${api.tableName}`;
+  }
+  return api.tableName ?? "";
+}
+
 export function getRecordApis(
   config: Config | undefined,
   tableName: QualifiedName,
 ): RecordApiConfig[] {
   return (config?.recordApis ?? []).filter(
-    (api) => api.tableName === prettyFormatQualifiedName(tableName),
+    (api) => recordApiTableName(api) === prettyFormatQualifiedName(tableName),
   );
 }
 
@@ -288,7 +297,7 @@ export function hasRecordApis(
 ): boolean {
   return (
     (config?.recordApis ?? []).findIndex(
-      (api) => api.tableName === prettyFormatQualifiedName(tableName),
+      (api) => recordApiTableName(api) === prettyFormatQualifiedName(tableName),
     ) !== -1
   );
 }
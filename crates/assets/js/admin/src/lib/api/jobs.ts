@@ -1,6 +1,11 @@
 import { adminFetch } from "@/lib/fetch";
 
+import type { CreateJobRequest } from "@bindings/CreateJobRequest";
+import type { CreateJobResponse } from "@bindings/CreateJobResponse";
+import type { DeleteJobRequest } from "@bindings/DeleteJobRequest";
+import type { ListJobRunsResponse } from "@bindings/ListJobRunsResponse";
 import type { ListJobsResponse } from "@bindings/ListJobsResponse";
+import type { PauseJobRequest } from "@bindings/PauseJobRequest";
 import type { RunJobRequest } from "@bindings/RunJobRequest";
 import type { RunJobResponse } from "@bindings/RunJobResponse";
 
@@ -18,3 +23,52 @@ export async function runJob(request: RunJobRequest): Promise<RunJobResponse> {
   });
   return await response.json();
 }
+
+export async function createJob(
+  request: CreateJobRequest,
+): Promise<CreateJobResponse> {
+  const response = await adminFetch("/job", {
+    method: "POST",
+    body: JSON.stringify(request),
+  });
+  return await response.json();
+}
+
+export async function deleteJob(request: DeleteJobRequest) {
+  await adminFetch("/job", {
+    method: "DELETE",
+    body: JSON.stringify(request),
+  });
+}
+
+export async function pauseJob(request: PauseJobRequest) {
+  await adminFetch("/job/pause", {
+    method: "POST",
+    body: JSON.stringify(request),
+  });
+}
+
+export async function resumeJob(request: PauseJobRequest) {
+  await adminFetch("/job/resume", {
+    method: "POST",
+    body: JSON.stringify(request),
+  });
+}
+
+export async function listJobRuns(
+  job?: string,
+  limit?: number,
+): Promise<ListJobRunsResponse> {
+  const params = new URLSearchParams();
+  if (job) {
+    params.set("job", job);
+  }
+  if (limit) {
+    params.set("limit", limit.toString());
+  }
+
+  const response = await adminFetch(`/job/runs?${params}`, {
+    method: "GET",
+  });
+  return await response.json();
+}
@@ -1,7 +1,12 @@
 import { adminFetch } from "@/lib/fetch";
 import { buildListSearchParams } from "@/lib/list";
 
+import type { EmailIdRequest } from "@bindings/EmailIdRequest";
+import type { ListEmailLogResponse } from "@bindings/ListEmailLogResponse";
+import type { ListEmailQueueResponse } from "@bindings/ListEmailQueueResponse";
 import type { ListLogsResponse } from "@bindings/ListLogsResponse";
+import type { ListSlowQueriesResponse } from "@bindings/ListSlowQueriesResponse";
+import type { LogAnalyticsResponse } from "@bindings/LogAnalyticsResponse";
 import type { StatsResponse } from "@bindings/StatsResponse";
 
 export async function fetchLogs(
@@ -31,3 +36,72 @@ export async function fetchStats(filter?: string): Promise<StatsResponse> {
   const response = await adminFetch(`/logs/stats?${params}`);
   return await response.json();
 }
+
+export async function fetchLogAnalytics(opts: {
+  since?: number;
+  until?: number;
+  intervalSec?: number;
+  limit?: number;
+}): Promise<LogAnalyticsResponse> {
+  const params = new URLSearchParams();
+  if (opts.since !== undefined) params.set("since", opts.since.toString());
+  if (opts.until !== undefined) params.set("until", opts.until.toString());
+  if (opts.intervalSec !== undefined) {
+    params.set("interval_sec", opts.intervalSec.toString());
+  }
+  if (opts.limit !== undefined) params.set("limit", opts.limit.toString());
+
+  const response = await adminFetch(`/logs/analytics?${params}`);
+  return await response.json();
+}
+
+export async function fetchSlowQueries(opts: {
+  minDurationMs?: number;
+  cursor?: bigint;
+  limit?: number;
+}): Promise<ListSlowQueriesResponse> {
+  const params = new URLSearchParams();
+  if (opts.minDurationMs !== undefined) {
+    params.set("min_duration_ms", opts.minDurationMs.toString());
+  }
+  if (opts.cursor !== undefined) params.set("cursor", opts.cursor.toString());
+  if (opts.limit !== undefined) params.set("limit", opts.limit.toString());
+
+  const response = await adminFetch(`/logs/slow_queries?${params}`);
+  return await response.json();
+}
+
+export async function fetchEmailLog(opts: {
+  status?: "sent" | "failed";
+  recipient?: string;
+  cursor?: bigint;
+  limit?: number;
+}): Promise<ListEmailLogResponse> {
+  const params = new URLSearchParams();
+  if (opts.status !== undefined) params.set("status", opts.status);
+  if (opts.recipient !== undefined) params.set("recipient", opts.recipient);
+  if (opts.cursor !== undefined) params.set("cursor", opts.cursor.toString());
+  if (opts.limit !== undefined) params.set("limit", opts.limit.toString());
+
+  const response = await adminFetch(`/email/log?${params}`);
+  return await response.json();
+}
+
+export async function fetchEmailQueue(opts: {
+  status?: "queued" | "failed" | "bounced";
+  limit?: number;
+}): Promise<ListEmailQueueResponse> {
+  const params = new URLSearchParams();
+  if (opts.status !== undefined) params.set("status", opts.status);
+  if (opts.limit !== undefined) params.set("limit", opts.limit.toString());
+
+  const response = await adminFetch(`/email/queue?${params}`);
+  return await response.json();
+}
+
+export async function requeueEmail(request: EmailIdRequest) {
+  await adminFetch("/email/queue/requeue", {
+    method: "POST",
+    body: JSON.stringify(request),
+  });
+}
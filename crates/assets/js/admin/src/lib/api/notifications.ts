@@ -0,0 +1,33 @@
+import { adminFetch } from "@/lib/fetch";
+
+import type { ListPushDeliveriesResponse } from "@bindings/ListPushDeliveriesResponse";
+import type { SendNotificationRequest } from "@bindings/SendNotificationRequest";
+import type { SendNotificationResponse } from "@bindings/SendNotificationResponse";
+
+export async function sendNotification(
+  request: SendNotificationRequest,
+): Promise<SendNotificationResponse> {
+  const response = await adminFetch("/notifications/send", {
+    method: "POST",
+    body: JSON.stringify(request),
+  });
+  return await response.json();
+}
+
+export async function fetchPushDeliveries(opts: {
+  notification?: string;
+  status?: "sent" | "failed" | "unregistered";
+  cursor?: bigint;
+  limit?: number;
+}): Promise<ListPushDeliveriesResponse> {
+  const params = new URLSearchParams();
+  if (opts.notification !== undefined) {
+    params.set("notification", opts.notification);
+  }
+  if (opts.status !== undefined) params.set("status", opts.status);
+  if (opts.cursor !== undefined) params.set("cursor", opts.cursor.toString());
+  if (opts.limit !== undefined) params.set("limit", opts.limit.toString());
+
+  const response = await adminFetch(`/notifications/deliveries?${params}`);
+  return await response.json();
+}
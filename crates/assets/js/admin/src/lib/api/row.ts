@@ -11,6 +11,8 @@ import type { Column } from "@bindings/Column";
 import type { InsertRowRequest } from "@bindings/InsertRowRequest";
 import type { UpdateRowRequest } from "@bindings/UpdateRowRequest";
 import type { DeleteRowsRequest } from "@bindings/DeleteRowsRequest";
+import type { SeedRowsRequest } from "@bindings/SeedRowsRequest";
+import type { SeedRowsResponse } from "@bindings/SeedRowsResponse";
 import type { ListRowsResponse } from "@bindings/ListRowsResponse";
 import type { QualifiedName } from "@bindings/QualifiedName";
 import type { SqlValue } from "@bindings/SqlValue";
@@ -35,6 +37,21 @@ export async function insertRow(table: Table, row: Record) {
   return await response.text();
 }
 
+export async function seedRows(
+  table: Table,
+  count: number,
+): Promise<SeedRowsResponse> {
+  const request: SeedRowsRequest = { count };
+
+  const tableName: string = prettyFormatQualifiedName(table.name);
+  const response = await adminFetch(`/table/${tableName}/seed`, {
+    method: "POST",
+    body: JSON.stringify(request),
+  });
+
+  return await response.json();
+}
+
 export async function updateRow(table: Table, row: Record) {
   return await updateRowInternal(table.name, table.columns, row);
 }
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Permission } from "./Permission";
+
+export type ApiKeyJson = { id: string, name: string, record_apis: Array<string>, permissions: Array<Permission>, created: bigint, expires: bigint | null, tenant: string | null, };
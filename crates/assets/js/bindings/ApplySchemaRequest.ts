@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Request for applying a declarative schema, i.e. a list of `CREATE TABLE`, `CREATE VIEW` and
+ * `CREATE INDEX` statements describing the desired state of a database.
+ */
+export type ApplySchemaRequest = { 
+/**
+ * Desired schema as SQL.
+ */
+schema: string, 
+/**
+ * Database to apply the schema to. Defaults to "main".
+ */
+database: string | null, 
+/**
+ * Drop tables, views and indexes, which are not part of the desired schema. Tables, views and
+ * indexes managed by TrailBase, i.e. prefixed with "_", are never dropped.
+ */
+prune: boolean | null, dry_run: boolean | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ApplySeedsRequest = { 
+/**
+ * Re-run all seeds including already applied ones. Only available in dev mode.
+ */
+rerun: boolean | null, };
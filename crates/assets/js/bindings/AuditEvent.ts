@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type AuditEvent = { id: bigint, 
+/**
+ * User, who performed the action. None for actions performed by the server, e.g. on config
+ * file changes.
+ */
+actor_id: string | null, actor_email: string | null, 
+/**
+ * Action name, e.g. "table.altered".
+ */
+action: string, 
+/**
+ * Affected entity, e.g. a table name or a user id.
+ */
+target: string, 
+/**
+ * Action-specific details, e.g. a diff of the changed schema.
+ */
+payload: JsonValue | null, created: bigint, };
@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RecordChange } from "./RecordChange";
+
+export type ChangesResponse = { 
+/**
+ * Changes in the order they happened.
+ */
+changes: Array<RecordChange>, 
+/**
+ * Cursor to pass as `since` to the next request.
+ */
+cursor: bigint, 
+/**
+ * Whether there are further changes.
+ */
+has_more: boolean, 
+/**
+ * The cursor predates the retained changes, i.e. clients have to re-list all records.
+ */
+expired: boolean, };
@@ -1,3 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type ColumnOrder = { column_name: string, ascending: boolean | null, nulls_first: boolean | null, };
+export type ColumnOrder = { 
+/**
+ * Column name or, for expression indexes, the SQL expression.
+ */
+column_name: string, ascending: boolean | null, nulls_first: boolean | null, 
+/**
+ * Whether `column_name` is an expression, e.g. `lower(email)`, rather than a column name.
+ */
+expression: boolean, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ConfigVersion = { id: bigint, hash: string, 
+/**
+ * Hash of the config this version replaced.
+ */
+parent_hash: string | null, 
+/**
+ * Admin user, who applied the change. None for changes applied by the server, e.g. on config
+ * file changes.
+ */
+user_id: string | null, user_email: string | null, created: bigint, };
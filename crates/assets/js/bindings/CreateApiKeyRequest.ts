@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Permission } from "./Permission";
+
+export type CreateApiKeyRequest = { name: string, 
+/**
+ * Names of the record APIs the key grants access to.
+ */
+record_apis: Array<string>, permissions: Array<Permission>, 
+/**
+ * Time-to-live in seconds. The key never expires if absent.
+ */
+ttl_sec: bigint | null, 
+/**
+ * Tenant to bind the key to. Only keys bound to a tenant may access its data.
+ */
+tenant: string | null, };
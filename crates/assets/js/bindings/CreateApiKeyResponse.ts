@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CreateApiKeyResponse = { id: string, 
+/**
+ * The key's secret. It is only revealed once and cannot be recovered later.
+ */
+secret: string, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CreateJobRequest = { name: string, 
+/**
+ * 6/7-component cron spec, e.g. "0 0 * * * *".
+ */
+schedule: string, 
+/**
+ * SQL executed against the main database on every run.
+ */
+query: string, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Subject and bodies of an email, either as template or rendered.
+ */
+export type EmailContent = { subject: string, html: string, 
+/**
+ * Plain-text alternative of the HTML body.
+ */
+text: string | null, };
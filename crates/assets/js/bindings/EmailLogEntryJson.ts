@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type EmailLogEntryJson = { id: bigint, 
+/**
+ * Seconds since epoch with fractional millisecond resolution.
+ */
+created: number, provider: string, sender: string, recipient: string, subject: string, 
+/**
+ * Either "sent" or "failed".
+ */
+status: string, attempts: bigint, 
+/**
+ * Error of the last delivery attempt, if failed.
+ */
+error: string | null, };
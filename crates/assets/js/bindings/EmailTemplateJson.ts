@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EmailContent } from "./EmailContent";
+import type { EmailTemplateName } from "./EmailTemplateName";
+
+export type EmailTemplateJson = { name: EmailTemplateName, 
+/**
+ * Variables available to the template, e.g. "APP_NAME".
+ */
+variables: Array<string>, 
+/**
+ * Effective template, i.e. the stored override falling back to the config and defaults.
+ */
+template: EmailContent, 
+/**
+ * Whether the template has been overridden via the admin API.
+ */
+customized: boolean, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type EmailTemplateName = "user_verification" | "change_email" | "change_email_old_address" | "password_reset" | "otp" | "magic_link";
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type EnqueueTaskRequest = { queue: string, payload: JsonValue, 
+/**
+ * Delay in seconds before the first attempt.
+ */
+delay_sec: bigint | null, 
+/**
+ * Overrides the configured maximum number of attempts.
+ */
+max_attempts: number | null, };
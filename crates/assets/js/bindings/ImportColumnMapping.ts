@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ImportColumnMapping = { 
+/**
+ * Column in the imported file.
+ */
+source: string, 
+/**
+ * Column of the table the source column is imported into. Unmapped columns are skipped.
+ */
+target: string | null, };
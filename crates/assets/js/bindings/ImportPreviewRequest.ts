@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportFormat } from "./ImportFormat";
+
+export type ImportPreviewRequest = { format: ImportFormat, 
+/**
+ * Content of the file to be imported.
+ */
+data: string, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportColumnMapping } from "./ImportColumnMapping";
+import type { ImportRowError } from "./ImportRowError";
+import type { SqlValue } from "./SqlValue";
+
+export type ImportPreviewResponse = { 
+/**
+ * Inferred mapping of source columns onto the table's columns.
+ */
+mapping: Array<ImportColumnMapping>, 
+/**
+ * The first few rows converted according to the mapping, ordered like `mapping`.
+ */
+sample: Array<Array<SqlValue>>, total_rows: number, 
+/**
+ * Rows that cannot be converted to the target columns' types.
+ */
+errors: Array<ImportRowError>, };
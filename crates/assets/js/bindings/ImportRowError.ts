@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ImportRowError = { 
+/**
+ * 1-based index of the data row in the imported file, i.e. not counting the CSV header.
+ */
+row: number, error: string, };
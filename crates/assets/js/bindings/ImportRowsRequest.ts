@@ -0,0 +1,25 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportColumnMapping } from "./ImportColumnMapping";
+import type { ImportFormat } from "./ImportFormat";
+
+export type ImportRowsRequest = { format: ImportFormat, 
+/**
+ * Content of the file to be imported.
+ */
+data: string, 
+/**
+ * Mapping of source columns onto the table's columns. Inferred, if absent.
+ */
+mapping: Array<ImportColumnMapping> | null, 
+/**
+ * Update existing rows on primary key conflicts rather than failing them.
+ */
+upsert: boolean | null, 
+/**
+ * Number of rows imported per transaction. Defaults to 500.
+ */
+chunk_size: number | null, 
+/**
+ * Validates the import without committing any rows.
+ */
+dry_run: boolean | null, };
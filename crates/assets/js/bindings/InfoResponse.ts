@@ -20,4 +20,8 @@ start_time: bigint,
 /**
  * Experimental Postgres mode
  */
-postgres: boolean, };
+postgres: boolean, 
+/**
+ * Prepared statement cache lookups across all connections since start.
+ */
+statement_cache_hits: bigint, statement_cache_misses: bigint, };
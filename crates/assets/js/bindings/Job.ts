@@ -5,4 +5,8 @@ export type Job = { id: number, name: string, schedule: string, enabled: boolean
  * Optional metadata from latest run: start timestamp in seconds since epoch, duration in
  * milliseconds and error output.
  */
-latest: [bigint, bigint, string | null] | null, };
+latest: [bigint, bigint, string | null] | null, 
+/**
+ * SQL executed by user-defined jobs. None for system and WASM jobs.
+ */
+query: string | null, };
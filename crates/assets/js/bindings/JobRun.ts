@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type JobRun = { id: bigint, job: string, 
+/**
+ * Start timestamp in seconds since epoch.
+ */
+started: bigint, duration_ms: bigint, success: boolean, 
+/**
+ * Error output of failed runs.
+ */
+output: string | null, };
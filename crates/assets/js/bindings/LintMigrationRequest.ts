@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type LintMigrationRequest = { 
+/**
+ * Candidate migration SQL.
+ */
+sql: string, 
+/**
+ * Database to lint the migration against. Defaults to "main".
+ */
+database: string | null, };
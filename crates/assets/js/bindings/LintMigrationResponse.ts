@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MigrationLint } from "./MigrationLint";
+
+export type LintMigrationResponse = { 
+/**
+ * Error applying the migration to a copy of the database, if any.
+ */
+error: string | null, 
+/**
+ * Tables created, altered or dropped by the migration, or getting new indexes.
+ */
+affected_tables: Array<string>, lints: Array<MigrationLint>, };
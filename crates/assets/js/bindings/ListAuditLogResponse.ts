@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AuditEvent } from "./AuditEvent";
+
+export type ListAuditLogResponse = { events: Array<AuditEvent>, 
+/**
+ * Cursor for fetching the next page of older events, if any.
+ */
+cursor: bigint | null, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RestorePoint } from "./RestorePoint";
+
+export type ListBackupsResponse = { 
+/**
+ * Available backups, newest first.
+ */
+backups: Array<RestorePoint>, };
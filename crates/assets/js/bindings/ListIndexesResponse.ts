@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TableIndex } from "./TableIndex";
+
+export type ListIndexesResponse = { 
+/**
+ * Indexes on the table and their `CREATE INDEX` statements.
+ */
+indexes: Array<[TableIndex, string]>, };
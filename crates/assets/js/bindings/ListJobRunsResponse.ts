@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JobRun } from "./JobRun";
+
+export type ListJobRunsResponse = { 
+/**
+ * Most recent runs first.
+ */
+runs: Array<JobRun>, };
@@ -1,6 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Column } from "./Column";
 import type { SqlValue } from "./SqlValue";
+import type { TableIndex } from "./TableIndex";
 
 export type ListRowsResponse = { 
 /**
@@ -11,6 +12,10 @@ columns: Array<Column>,
  * Actual row data.
  */
 rows: Array<Array<SqlValue>>, 
+/**
+ * Indexes on the table, empty for views.
+ */
+indexes: Array<TableIndex>, 
 /**
  * Total number of records.
  */
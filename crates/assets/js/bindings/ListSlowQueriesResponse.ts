@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SlowQueryJson } from "./SlowQueryJson";
+
+export type ListSlowQueriesResponse = { slow_queries: Array<SlowQueryJson>, 
+/**
+ * Cursor for fetching the next page of older entries, if any.
+ */
+cursor: bigint | null, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RequestBucket } from "./RequestBucket";
+import type { RouteLatency } from "./RouteLatency";
+import type { TopEntry } from "./TopEntry";
+
+export type LogAnalyticsResponse = { 
+/**
+ * Request and error counts per bucket, oldest first. Buckets w/o requests are omitted.
+ */
+series: Array<RequestBucket>, 
+/**
+ * Latency percentiles of the busiest routes.
+ */
+routes: Array<RouteLatency>, top_user_agents: Array<TopEntry>, top_client_ips: Array<TopEntry>, };
@@ -1,8 +1,13 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { GeoipCity } from "./GeoipCity";
+import type { JsonValue } from "./serde_json/JsonValue";
 
 export type LogJson = { id: bigint, created: number, status: number, method: string, url: string, latency_ms: number, client_ip: string, 
 /**
  * Optional two-letter country code.
  */
-client_geoip_cc: string | null, client_geoip_city: GeoipCity | null, referer: string, user_agent: string, user_id: string | null, };
+client_geoip_cc: string | null, client_geoip_city: GeoipCity | null, referer: string, user_agent: string, user_id: string | null, 
+/**
+ * Extra data, e.g. the matched route or captured fields like error details.
+ */
+data: JsonValue | null, };
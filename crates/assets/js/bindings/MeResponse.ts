@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type MeResponse = { 
+/**
+ * Url-safe Base64 encoded id of the current user.
+ */
+id: string, email: string | null, username: string | null, verified: boolean, 
+/**
+ * Custom profile fields as configured via `auth.user_profile`.
+ */
+profile: { [key in string]?: JsonValue }, };
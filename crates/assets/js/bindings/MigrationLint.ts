@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LintLevel } from "./LintLevel";
+
+export type MigrationLint = { level: LintLevel, 
+/**
+ * Index of the statement the lint refers to, if specific to one.
+ */
+statement: number | null, message: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Notification = { title: string, body: string, 
+/**
+ * Custom key-value pairs passed along to the app.
+ */
+data: { [key in string]: string } | null, };
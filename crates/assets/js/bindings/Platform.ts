@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Push service a device is registered with: Firebase Cloud Messaging, e.g. Android, Apple Push
+ * Notification service, e.g. iOS, or Web Push for browsers.
+ */
+export type Platform = "fcm" | "apns" | "web_push";
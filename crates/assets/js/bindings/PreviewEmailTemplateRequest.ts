@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EmailTemplateName } from "./EmailTemplateName";
+
+export type PreviewEmailTemplateRequest = { name: EmailTemplateName, 
+/**
+ * Unsaved edits to preview. Unset fields fall back to the effective template.
+ */
+subject: string | null, html: string | null, text: string | null, };
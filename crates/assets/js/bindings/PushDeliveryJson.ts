@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type PushDeliveryJson = { id: bigint, 
+/**
+ * Seconds since epoch with fractional millisecond resolution.
+ */
+created: number, notification: string, user_id: string, device: bigint, platform: string, title: string, 
+/**
+ * One of "sent", "failed" or "unregistered", i.e. the device was removed.
+ */
+status: string, error: string | null, };
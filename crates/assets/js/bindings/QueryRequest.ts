@@ -1,3 +1,20 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SqlValue } from "./SqlValue";
 
-export type QueryRequest = { query: string, attached_databases: Array<string> | null, };
+export type QueryRequest = { query: string, attached_databases: Array<string> | null, 
+/**
+ * Positional parameters bound to the query, e.g. `$1` or `?1`. Requires a single statement.
+ */
+params: Array<SqlValue> | null, 
+/**
+ * Returns the query plan rather than executing the query. Requires a single statement.
+ */
+explain: boolean | null, 
+/**
+ * Maximum number of rows returned. Paging requires a single SELECT statement.
+ */
+limit: number | null, offset: number | null, 
+/**
+ * Aborts execution after the given duration. Defaults to 30s.
+ */
+timeout_ms: bigint | null, };
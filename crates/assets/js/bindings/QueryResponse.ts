@@ -2,4 +2,8 @@
 import type { Column } from "./Column";
 import type { SqlValue } from "./SqlValue";
 
-export type QueryResponse = { columns: Array<Column> | null, rows: Array<Array<SqlValue>>, };
+export type QueryResponse = { columns: Array<Column> | null, rows: Array<Array<SqlValue>>, 
+/**
+ * Whether there are more rows past the requested page. Only set when paging.
+ */
+has_more: boolean, };
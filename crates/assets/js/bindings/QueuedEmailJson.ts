@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type QueuedEmailJson = { id: bigint, 
+/**
+ * One of "queued", "failed", i.e. ran out of attempts, or "bounced", i.e. rejected
+ * permanently.
+ */
+status: string, attempts: bigint, next_attempt: bigint, last_error: string | null, sender: string, recipient: string, subject: string, created: bigint, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type QueuedTaskJson = { id: bigint, queue: string, payload: string, 
+/**
+ * 0: pending, 2: failed, i.e. dead-lettered.
+ */
+status: bigint, attempts: bigint, max_attempts: bigint | null, next_attempt: bigint, last_error: string | null, created: bigint, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type QueuedWebhookEventJson = { id: bigint, webhook: string, event: string, payload: string, 
+/**
+ * 0: pending, 2: failed.
+ */
+status: bigint, attempts: bigint, next_attempt: bigint, last_error: string | null, created: bigint, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RecordChangeOp } from "./RecordChangeOp";
+
+export type RecordChange = { op: RecordChangeOp, 
+/**
+ * Record id, i.e. an integer or url-safe base64 encoded UUID.
+ */
+id: string, 
+/**
+ * Seconds since epoch.
+ */
+created: bigint, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Platform } from "./Platform";
+
+export type RegisterDeviceRequest = { platform: Platform, 
+/**
+ * FCM registration token, APNs device token or, for Web Push, the subscription's endpoint.
+ */
+token: string, 
+/**
+ * Keys of Web Push subscriptions, i.e. `PushSubscription.toJSON().keys`.
+ */
+p256dh: string | null, auth: string | null, };
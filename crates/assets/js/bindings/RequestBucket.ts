@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Requests in a time bucket.
+ */
+export type RequestBucket = { 
+/**
+ * Start of the bucket in seconds since epoch.
+ */
+timestamp: bigint, requests: bigint, 
+/**
+ * Requests per minute.
+ */
+rate: number, 
+/**
+ * Requests with a 4XX status.
+ */
+client_errors: bigint, 
+/**
+ * Requests with a 5XX status.
+ */
+server_errors: bigint, 
+/**
+ * Share of requests with a 5XX status in [0, 1].
+ */
+error_rate: number, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RestoreBackupRequest = { 
+/**
+ * Name of the backup to restore, e.g. "main-20250101T000000Z.db".
+ */
+name: string, };
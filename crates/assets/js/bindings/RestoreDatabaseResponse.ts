@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RestoreDatabaseResponse = { 
+/**
+ * Snapshot of the database taken right before the restore, relative to the backup directory.
+ */
+pre_restore_backup: string, };
@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RestorePoint = { 
+/**
+ * Name of the backup, e.g. "main-20250101T000000Z.db".
+ */
+name: string, 
+/**
+ * Backed up database, i.e. "main" or "logs".
+ */
+database: string, 
+/**
+ * Unix timestamp in seconds of when the backup was taken.
+ */
+created: bigint, 
+/**
+ * Whether the backup is available in the local backup directory.
+ */
+local: boolean, 
+/**
+ * Whether the backup is available in the object store.
+ */
+remote: boolean, };
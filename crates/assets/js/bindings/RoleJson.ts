@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RoleJson = { name: string, description: string | null, created: bigint, 
+/**
+ * Number of users the role is assigned to.
+ */
+users: bigint, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RollbackMigrationsRequest = { 
+/**
+ * Database to roll back migrations for. Defaults to "main".
+ */
+database: string | null, dry_run: boolean | null, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RollbackMigrationsResponse = { 
+/**
+ * Rolled back migrations, most recent first.
+ */
+migrations: Array<string>, 
+/**
+ * The applied down migrations.
+ */
+sql: string, };
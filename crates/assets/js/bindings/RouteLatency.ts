@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Latency percentiles of a route.
+ */
+export type RouteLatency = { method: string, 
+/**
+ * Matched route, e.g. "/api/records/v1/{name}". Falls back to the request path for requests
+ * w/o a matched route, e.g. 404s or logs written before routes were recorded.
+ */
+route: string, requests: bigint, p50_ms: number, p95_ms: number, p99_ms: number, };
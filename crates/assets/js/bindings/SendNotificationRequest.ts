@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Notification } from "./Notification";
+
+export type SendNotificationRequest = { 
+/**
+ * Users to notify on all their registered devices. Mutually exclusive with `topic`.
+ */
+user_ids: Array<string> | null, 
+/**
+ * Notify all users subscribed to the given topic.
+ */
+topic: string | null, notification: Notification, };
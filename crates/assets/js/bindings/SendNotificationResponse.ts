@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SendNotificationResponse = { 
+/**
+ * Id shared by all deliveries of the notification.
+ */
+notification_id: string, sent: number, failed: number, 
+/**
+ * Devices that are no longer registered with their push service and have been removed.
+ */
+unregistered: number, };
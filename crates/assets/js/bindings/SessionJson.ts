@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SessionJson = { id: bigint, user_agent: string | null, client_ip: string | null, created: bigint, 
+/**
+ * Last time the session was refreshed, or its creation time.
+ */
+last_seen: bigint, expires: bigint, 
+/**
+ * Whether this is the session of the requesting client.
+ */
+current: boolean, };
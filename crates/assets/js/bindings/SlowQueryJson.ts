@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SlowQueryJson = { id: bigint, 
+/**
+ * Seconds since epoch with fractional millisecond resolution.
+ */
+created: number, 
+/**
+ * Path of the database file, None for in-memory databases.
+ */
+database: string | null, sql: string, duration_ms: number, 
+/**
+ * Output of "EXPLAIN QUERY PLAN" with nested steps indented.
+ */
+query_plan: string, };
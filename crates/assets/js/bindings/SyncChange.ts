@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncOp } from "./SyncOp";
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type SyncChange = { op: SyncOp, 
+/**
+ * Url-safe base64 encoded id of the record.
+ */
+id: string, 
+/**
+ * Current state of upserted records.
+ */
+record: JsonValue | null, };
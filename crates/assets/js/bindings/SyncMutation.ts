@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncOp } from "./SyncOp";
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type SyncMutation = { op: SyncOp, 
+/**
+ * Url-safe base64 encoded, client-generated UUIDv7 id of the record.
+ */
+id: string, 
+/**
+ * Record contents for upserts. Partial records are merged into existing ones.
+ */
+record: JsonValue | null, };
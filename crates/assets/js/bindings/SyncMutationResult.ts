@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncMutationStatus } from "./SyncMutationStatus";
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type SyncMutationResult = { id: string, status: SyncMutationStatus, 
+/**
+ * Server's version of the record in case of a conflict, absent if it was deleted.
+ */
+record: JsonValue | null, 
+/**
+ * Reason a mutation was rejected, e.g. insufficient permissions.
+ */
+error: string | null, };
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncChange } from "./SyncChange";
+
+export type SyncPullResponse = { 
+/**
+ * Changes in the order they happened. Records the user is not allowed to read (any longer) are
+ * reported as deleted.
+ */
+changes: Array<SyncChange>, 
+/**
+ * Cursor to pass to the next pull and push.
+ */
+cursor: bigint, 
+/**
+ * Whether there are further changes to pull.
+ */
+has_more: boolean, };
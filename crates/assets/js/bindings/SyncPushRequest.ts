@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncMutation } from "./SyncMutation";
+
+export type SyncPushRequest = { 
+/**
+ * Cursor of the client's last pull. Mutations of records changed since are conflicts.
+ */
+cursor: bigint | null, 
+/**
+ * Mutations in the order they were made offline.
+ */
+mutations: Array<SyncMutation>, };
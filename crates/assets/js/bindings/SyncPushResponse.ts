@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncMutationResult } from "./SyncMutationResult";
+
+export type SyncPushResponse = { 
+/**
+ * A result per mutation in request order.
+ */
+results: Array<SyncMutationResult>, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type TenantJson = { name: string, created: bigint, 
+/**
+ * Number of users assigned to the tenant.
+ */
+users: bigint, };
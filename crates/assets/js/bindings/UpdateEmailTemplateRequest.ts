@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EmailTemplateName } from "./EmailTemplateName";
+
+export type UpdateEmailTemplateRequest = { name: EmailTemplateName, 
+/**
+ * Unset fields fall back to the config and then the built-in defaults.
+ */
+subject: string | null, html: string | null, text: string | null, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type UpdateProfileRequest = { 
+/**
+ * Profile fields to update. Fields not present remain unchanged, `null` unsets a field.
+ */
+profile: { [key in string]?: JsonValue }, };
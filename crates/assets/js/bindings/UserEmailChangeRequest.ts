@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type UserEmailChangeRequest = { 
+/**
+ * Id of the user with a pending email change.
+ */
+id: string, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type VapidPublicKeyResponse = { 
+/**
+ * URL-safe base64 encoded key to pass as `applicationServerKey` to
+ * `PushManager.subscribe()`.
+ */
+public_key: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type WebhookDeliveryJson = { id: bigint, webhook: string, event: string, payload: string, 
+/**
+ * 0: pending, 1: delivered, 2: failed.
+ */
+status: bigint, attempts: bigint, last_error: string | null, created: bigint, updated: bigint, };
@@ -30,8 +30,14 @@ import type { MfaTokenResponse } from "@bindings/MfaTokenResponse";
 import type { PromoteAnonymousRequest } from "@bindings/PromoteAnonymousRequest";
 import type { RefreshRequest } from "@bindings/RefreshRequest";
 import type { RefreshResponse } from "@bindings/RefreshResponse";
+import type { RegisterDeviceRequest } from "@bindings/RegisterDeviceRequest";
 import type { RegisterTotpResponse } from "@bindings/RegisterTotpResponse";
 import type { RequestOtpRequest } from "@bindings/RequestOtpRequest";
+import type { SyncPullResponse } from "@bindings/SyncPullResponse";
+import type { SyncPushRequest } from "@bindings/SyncPushRequest";
+import type { SyncPushResponse } from "@bindings/SyncPushResponse";
+import type { UnregisterDeviceRequest } from "@bindings/UnregisterDeviceRequest";
+import type { VapidPublicKeyResponse } from "@bindings/VapidPublicKeyResponse";
 
 export type User = {
   id: string;
@@ -208,6 +214,17 @@ export interface Client {
   confirmTOTP(totpUrl: string, totp: string): Promise<void>;
   unregisterTOTP(totp: string): Promise<void>;
 
+  /// Registers a device of the current user for push notifications, e.g. using
+  /// an FCM token or the endpoint and keys of a Web Push subscription.
+  registerPushDevice(device: RegisterDeviceRequest): Promise<void>;
+  unregisterPushDevice(token: string): Promise<void>;
+  /// Subscribes the current user to notifications sent to the given topic.
+  subscribeTopic(topic: string): Promise<void>;
+  unsubscribeTopic(topic: string): Promise<void>;
+  /// VAPID public key to pass as `applicationServerKey` when subscribing
+  /// browsers to Web Push.
+  vapidPublicKey(): Promise<string>;
+
   /// Promote an anonymous user to "proper" user. If an email is provided, a verification
   /// email will be sent out.
   promoteAnonymous(opts: PromotionOptions): Promise<void>;
@@ -230,6 +247,17 @@ export interface Client {
     operations: (CreateOperation | UpdateOperation | DeleteOperation)[],
     transaction?: boolean,
   ): Promise<RecordId[]>;
+
+  /// Pull changes of a sync-enabled Record API since the given cursor.
+  syncPull(
+    apiName: string,
+    opts?: { cursor?: bigint; limit?: number },
+  ): Promise<SyncPullResponse>;
+  /// Push offline mutations of a sync-enabled Record API.
+  syncPush(
+    apiName: string,
+    request: SyncPushRequest,
+  ): Promise<SyncPushResponse>;
 }
 
 /// Client for interacting with TrailBase auth and record APIs.
@@ -302,6 +330,35 @@ class ClientImpl implements Client {
     return parseJSON(await response.text()).ids;
   }
 
+  public async syncPull(
+    apiName: string,
+    opts?: { cursor?: bigint; limit?: number },
+  ): Promise<SyncPullResponse> {
+    const params = new URLSearchParams();
+    if (opts?.cursor !== undefined) {
+      params.append("cursor", opts.cursor.toString());
+    }
+    if (opts?.limit !== undefined) {
+      params.append("limit", opts.limit.toString());
+    }
+
+    const response = await this.fetch(
+      `${syncApiBasePath}/${apiName}/pull?${params}`,
+    );
+    return parseJSON(await response.text());
+  }
+
+  public async syncPush(
+    apiName: string,
+    request: SyncPushRequest,
+  ): Promise<SyncPushResponse> {
+    const response = await this.fetch(`${syncApiBasePath}/${apiName}/push`, {
+      method: "POST",
+      body: JSON.stringify(request),
+    });
+    return parseJSON(await response.text());
+  }
+
   public avatarUrl(userId?: string): string | undefined {
     const id = userId ?? this.user()?.id;
     if (id) {
@@ -493,6 +550,48 @@ class ClientImpl implements Client {
     await this.refreshAuthToken({ force: true });
   }
 
+  public async registerPushDevice(
+    device: RegisterDeviceRequest,
+  ): Promise<void> {
+    await this.fetch(`${notificationsApiBasePath}/devices`, {
+      method: "POST",
+      body: JSON.stringify(device),
+    });
+  }
+
+  public async unregisterPushDevice(token: string): Promise<void> {
+    await this.fetch(`${notificationsApiBasePath}/devices`, {
+      method: "DELETE",
+      body: JSON.stringify({ token } as UnregisterDeviceRequest),
+    });
+  }
+
+  public async subscribeTopic(topic: string): Promise<void> {
+    await this.fetch(
+      `${notificationsApiBasePath}/topics/${encodeURIComponent(topic)}`,
+      {
+        method: "POST",
+      },
+    );
+  }
+
+  public async unsubscribeTopic(topic: string): Promise<void> {
+    await this.fetch(
+      `${notificationsApiBasePath}/topics/${encodeURIComponent(topic)}`,
+      {
+        method: "DELETE",
+      },
+    );
+  }
+
+  public async vapidPublicKey(): Promise<string> {
+    const response = await this.fetch(
+      `${notificationsApiBasePath}/web_push/vapid_public_key`,
+    );
+    const parsed: VapidPublicKeyResponse = await response.json();
+    return parsed.public_key;
+  }
+
   /// This will call the status endpoint, which validates any provided tokens
   /// but also hoists any tokens provided as cookies into a JSON response.
   private async checkAuthStatus(): Promise<Tokens | undefined> {
@@ -678,4 +777,6 @@ function headers(tokens?: Tokens): HeadersInit {
 }
 
 const authApiBasePath = "/api/auth/v1";
+const notificationsApiBasePath = "/api/notifications/v1";
+const syncApiBasePath = "/api/sync/v1";
 const transactionApiBasePath = "/api/transaction/v1/execute";
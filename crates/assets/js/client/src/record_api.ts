@@ -5,6 +5,7 @@ import { isDev, jsonContentTypeHeader } from "./constants";
 import { parseJSON } from "./json";
 import { Client } from "./client";
 
+import type { ChangesResponse } from "@bindings/ChangesResponse";
 import type { WsProtocol } from "@bindings/WsProtocol";
 
 export interface FileUpload {
@@ -364,6 +365,13 @@ export interface RecordApi<T = Record<string, unknown>> {
   update(id: RecordId, record: Partial<T>): Promise<void>;
   updateOp(id: RecordId, record: Partial<T>): UpdateOperation;
 
+  // Atomically adds the given deltas to numeric columns. Returns the updated
+  // values.
+  increment(
+    id: RecordId,
+    deltas: Partial<Record<keyof T, number>>,
+  ): Promise<Partial<Record<keyof T, number>>>;
+
   delete(id: RecordId): Promise<void>;
   deleteOp(id: RecordId): DeleteOperation;
 
@@ -374,6 +382,10 @@ export interface RecordApi<T = Record<string, unknown>> {
   subscribeAll(
     opts?: SubscribeOpts & SubscribeFilterOpts,
   ): Promise<ReadableStream<ChangeEvent>>;
+
+  // Requires change tracking to be enabled for the API. Without `since` only
+  // the current cursor is returned.
+  changes(opts?: { since?: bigint; limit?: number }): Promise<ChangesResponse>;
 }
 
 /// Provides CRUD access to records through TrailBase's record API.
@@ -446,6 +458,21 @@ export class RecordApiImpl<
     return new UpdateOperation<T>(this.client, this.name, id, record);
   }
 
+  public async increment(
+    id: RecordId,
+    deltas: Partial<Record<keyof T, number>>,
+  ): Promise<Partial<Record<keyof T, number>>> {
+    const response = await this.client.fetch(
+      `${recordApiBasePath}/${this.name}/${id}/increment`,
+      {
+        method: "PATCH",
+        body: JSON.stringify(deltas),
+        headers: jsonContentTypeHeader,
+      },
+    );
+    return parseJSON(await response.text());
+  }
+
   public async delete(id: RecordId): Promise<void> {
     return new DeleteOperation(this.client, this.name, id).query();
   }
@@ -467,6 +494,24 @@ export class RecordApiImpl<
     return await this.subscribeImpl("*", opts);
   }
 
+  public async changes(opts?: {
+    since?: bigint;
+    limit?: number;
+  }): Promise<ChangesResponse> {
+    const params = new URLSearchParams();
+    if (opts?.since !== undefined) {
+      params.append("since", opts.since.toString());
+    }
+    if (opts?.limit !== undefined) {
+      params.append("limit", opts.limit.toString());
+    }
+
+    const response = await this.client.fetch(
+      `${recordApiBasePath}/${this.name}/changes?${params}`,
+    );
+    return parseJSON(await response.text());
+  }
+
   private async subscribeImpl(
     id: RecordId,
     opts?: SubscribeOpts & SubscribeFilterOpts,
@@ -4,8 +4,12 @@
 
 mod assets;
 pub mod email;
+mod static_site;
 
 pub use assets::AssetService;
+pub use static_site::{
+  DirSource, OpenFuture, ReadFuture, StaticFile, StaticSiteService, StaticSource,
+};
 
 use rust_embed::RustEmbed;
 
@@ -0,0 +1,606 @@
+use axum::body::{Body, Bytes};
+use axum::http::{self, HeaderMap, HeaderValue, Request, StatusCode, header};
+use axum::response::Response;
+use log::*;
+use std::convert::Infallible;
+use std::future::Future;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock};
+use std::task::Poll;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tower_service::Service;
+
+pub type ReadFuture = Pin<Box<dyn Future<Output = Result<Body, std::io::Error>> + Send>>;
+
+/// A file served by [`StaticSiteService`]. The contents are only read once needed, i.e. not for
+/// `HEAD` or conditional requests.
+pub struct StaticFile {
+  /// Size in bytes.
+  pub len: u64,
+  /// Opaque validator, e.g. derived from the modification time and size. Quoted.
+  pub etag: Option<String>,
+  /// Streams the given byte range of the contents.
+  pub read: Box<dyn FnOnce(Range<u64>) -> ReadFuture + Send>,
+}
+
+impl StaticFile {
+  /// An in-memory file.
+  pub fn from_bytes(data: Bytes, etag: Option<String>) -> Self {
+    return Self {
+      len: data.len() as u64,
+      etag,
+      read: Box::new(move |range: Range<u64>| -> ReadFuture {
+        return Box::pin(async move {
+          return Ok(Body::from(
+            data.slice(range.start as usize..range.end as usize),
+          ));
+        });
+      }),
+    };
+  }
+}
+
+pub type OpenFuture<'a> =
+  Pin<Box<dyn Future<Output = Result<Option<StaticFile>, std::io::Error>> + Send + 'a>>;
+
+/// Source of static files, e.g. a local directory or an object store.
+pub trait StaticSource: Send + Sync {
+  /// Opens the file at the given relative path, e.g. "assets/index.js". Returns `None` if the file
+  /// doesn't exist.
+  fn open<'a>(&'a self, path: &'a str) -> OpenFuture<'a>;
+}
+
+/// Serves files from a local directory.
+pub struct DirSource {
+  root: PathBuf,
+}
+
+impl DirSource {
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    return Self { root: root.into() };
+  }
+}
+
+impl StaticSource for DirSource {
+  fn open<'a>(&'a self, path: &'a str) -> OpenFuture<'a> {
+    return Box::pin(async move {
+      let path = self.root.join(path);
+      let metadata = match tokio::fs::metadata(&path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => return Ok(None),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+      };
+
+      let etag = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|modified| format!("\"{:x}-{:x}\"", modified.as_secs(), metadata.len()));
+
+      return Ok(Some(StaticFile {
+        len: metadata.len(),
+        etag,
+        read: Box::new(move |range: Range<u64>| -> ReadFuture {
+          return Box::pin(async move {
+            let mut file = tokio::fs::File::open(&path).await?;
+            if range.start > 0 {
+              file.seek(std::io::SeekFrom::Start(range.start)).await?;
+            }
+            let reader = file.take(range.end - range.start);
+            return Ok(Body::from_stream(tokio_util::io::ReaderStream::new(reader)));
+          });
+        }),
+      }));
+    });
+  }
+}
+
+/// Serves a static site, e.g. a user-provided SPA, at the HTTP root.
+///
+/// Requests for "/" or directories are served their "index.html". Pre-compressed variants, i.e.
+/// "<file>.br" and "<file>.gz", are served if present and accepted by the client. Hashed assets,
+/// e.g. "index-B4x2Cn9a.js", are cached indefinitely, everything else is revalidated using ETags.
+/// Files are streamed and single-range requests are supported, e.g. for media playback.
+#[derive(Clone)]
+pub struct StaticSiteService {
+  source: Arc<dyn StaticSource>,
+  spa: bool,
+}
+
+impl StaticSiteService {
+  pub fn new(source: impl StaticSource + 'static) -> Self {
+    return Self {
+      source: Arc::new(source),
+      spa: false,
+    };
+  }
+
+  /// Enables the SPA fallback: requests for paths w/o file extension, which don't match a file,
+  /// are served "index.html" to support client-side routing.
+  pub fn with_spa_fallback(mut self, spa: bool) -> Self {
+    self.spa = spa;
+    return self;
+  }
+}
+
+impl Service<Request<Body>> for StaticSiteService {
+  type Response = Response<Body>;
+  type Error = Infallible;
+  type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, Infallible>> + Send>>;
+
+  fn poll_ready(&mut self, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn call(&mut self, req: Request<Body>) -> Self::Future {
+    let source = self.source.clone();
+    let spa = self.spa;
+
+    return Box::pin(async move {
+      return Ok(
+        serve(source.as_ref(), spa, &req)
+          .await
+          .unwrap_or_else(|err| {
+            warn!("Failed to serve static file '{}': {err}", req.uri().path());
+            Response::builder()
+              .status(StatusCode::INTERNAL_SERVER_ERROR)
+              .body(Body::empty())
+              .unwrap_or_default()
+          }),
+      );
+    });
+  }
+}
+
+async fn serve(
+  source: &dyn StaticSource,
+  spa: bool,
+  req: &Request<Body>,
+) -> Result<Response<Body>, std::io::Error> {
+  let head = match *req.method() {
+    http::Method::GET => false,
+    http::Method::HEAD => true,
+    _ => {
+      return Ok(
+        Response::builder()
+          .status(StatusCode::METHOD_NOT_ALLOWED)
+          .header(header::ALLOW, "GET, HEAD")
+          .body(Body::empty())
+          .unwrap_or_default(),
+      );
+    }
+  };
+
+  let Some(mut path) = sanitize_path(req.uri().path()) else {
+    return Ok(not_found());
+  };
+  if path.is_empty() || path.ends_with('/') {
+    path.push_str("index.html");
+  }
+
+  let (path, encoding, file) = match open(source, &path, req.headers()).await? {
+    Some((encoding, file)) => (path, encoding, file),
+    None if !has_extension(&path) => {
+      // Redirect directories requested w/o trailing slash, so relative links resolve correctly.
+      if open(source, &format!("{path}/index.html"), req.headers())
+        .await?
+        .is_some()
+      {
+        let query = req
+          .uri()
+          .query()
+          .map(|q| format!("?{q}"))
+          .unwrap_or_default();
+        return Ok(
+          Response::builder()
+            .status(StatusCode::TEMPORARY_REDIRECT)
+            .header(header::LOCATION, format!("/{}/{query}", encode_path(&path)))
+            .body(Body::empty())
+            .unwrap_or_default(),
+        );
+      }
+
+      // Otherwise fall back to the SPA's "index.html" to support client-side routing.
+      let index = "index.html".to_string();
+      let found = if spa {
+        open(source, &index, req.headers()).await?
+      } else {
+        None
+      };
+      let Some((encoding, file)) = found else {
+        return Ok(not_found());
+      };
+      (index, encoding, file)
+    }
+    None => return Ok(not_found()),
+  };
+
+  let cache_control = if is_hashed_asset(&path) {
+    "public, max-age=31536000, immutable"
+  } else {
+    "no-cache"
+  };
+
+  let mut builder = Response::builder()
+    .header(header::CACHE_CONTROL, cache_control)
+    .header(header::VARY, "accept-encoding")
+    .header(header::ACCEPT_RANGES, "bytes");
+  if let Some(ref etag) = file.etag {
+    if req
+      .headers()
+      .get(header::IF_NONE_MATCH)
+      .and_then(|v| v.to_str().ok())
+      .is_some_and(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"))
+    {
+      return Ok(
+        builder
+          .status(StatusCode::NOT_MODIFIED)
+          .header(header::ETAG, etag)
+          .body(Body::empty())
+          .unwrap_or_default(),
+      );
+    }
+    builder = builder.header(header::ETAG, etag);
+  }
+
+  // Following RFC 9110, ranges are ignored if `If-Range` doesn't match the current ETag.
+  let range_header = req
+    .headers()
+    .get(header::RANGE)
+    .and_then(|v| v.to_str().ok())
+    .filter(|_| {
+      return req
+        .headers()
+        .get(header::IF_RANGE)
+        .is_none_or(|v| file.etag.as_deref().is_some_and(|etag| v == etag));
+    });
+  let range = match range_header.and_then(|v| parse_range(v, file.len)) {
+    Some(Ok(range)) => Some(range),
+    Some(Err(())) => {
+      return Ok(
+        builder
+          .status(StatusCode::RANGE_NOT_SATISFIABLE)
+          .header(header::CONTENT_RANGE, format!("bytes */{}", file.len))
+          .body(Body::empty())
+          .unwrap_or_default(),
+      );
+    }
+    None => None,
+  };
+
+  let mime = mime_guess::from_path(&path).first_or_octet_stream();
+  builder = builder.header(header::CONTENT_TYPE, mime.as_ref());
+  if let Some(encoding) = encoding {
+    builder = builder.header(header::CONTENT_ENCODING, encoding);
+  }
+
+  let range = match range {
+    Some(range) => {
+      builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+        header::CONTENT_RANGE,
+        format!("bytes {}-{}/{}", range.start, range.end - 1, file.len),
+      );
+      range
+    }
+    None => 0..file.len,
+  };
+  builder = builder.header(header::CONTENT_LENGTH, range.end - range.start);
+
+  let body = if head {
+    Body::empty()
+  } else {
+    (file.read)(range).await?
+  };
+  return Ok(builder.body(body).unwrap_or_default());
+}
+
+/// Opens the file or, if accepted by the client, its pre-compressed variant.
+async fn open(
+  source: &dyn StaticSource,
+  path: &str,
+  headers: &HeaderMap,
+) -> Result<Option<(Option<&'static str>, StaticFile)>, std::io::Error> {
+  for (encoding, suffix) in [("br", "br"), ("gzip", "gz")] {
+    if accepts_encoding(headers, encoding)
+      && let Some(file) = source.open(&format!("{path}.{suffix}")).await?
+    {
+      return Ok(Some((Some(encoding), file)));
+    }
+  }
+  return Ok(source.open(path).await?.map(|file| (None, file)));
+}
+
+fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+  return headers
+    .get_all(header::ACCEPT_ENCODING)
+    .iter()
+    .filter_map(|v: &HeaderValue| v.to_str().ok())
+    .flat_map(|v| v.split(','))
+    .any(|entry| {
+      let mut parts = entry.split(';').map(str::trim);
+      if parts.next() != Some(encoding) {
+        return false;
+      }
+      // Explicitly rejected, e.g. "br;q=0".
+      return !parts.any(|p| p.strip_prefix("q=").is_some_and(|q| q.parse() == Ok(0.0)));
+    });
+}
+
+/// Decodes the request path and strips the leading slash. Returns `None` for paths trying to
+/// escape the root, e.g. "/../secret".
+fn sanitize_path(path: &str) -> Option<String> {
+  let decoded = percent_encoding::percent_decode_str(path)
+    .decode_utf8()
+    .ok()?;
+  let path = decoded.trim_start_matches('/');
+  if path
+    .split('/')
+    .any(|segment| segment == ".." || segment.contains('\\'))
+  {
+    return None;
+  }
+  return Some(path.to_string());
+}
+
+/// Parses a single-range `Range` header. Returns `None` for anything unsupported, e.g. multiple
+/// ranges, in which case the entire file is served, and an error if the range cannot be
+/// satisfied.
+fn parse_range(value: &str, size: u64) -> Option<Result<Range<u64>, ()>> {
+  let spec = value.trim().strip_prefix("bytes=")?;
+  if spec.contains(',') {
+    return None;
+  }
+  let (start, end) = spec.split_once('-')?;
+  let (start, end) = (start.trim(), end.trim());
+
+  if start.is_empty() {
+    // Suffix range, i.e. the last N bytes.
+    let suffix: u64 = end.parse().ok()?;
+    if suffix == 0 || size == 0 {
+      return Some(Err(()));
+    }
+    return Some(Ok(size.saturating_sub(suffix)..size));
+  }
+
+  let start: u64 = start.parse().ok()?;
+  let end: Option<u64> = match end {
+    "" => None,
+    end => Some(end.parse().ok()?),
+  };
+  if end.is_some_and(|end| end < start) {
+    return None;
+  }
+  if start >= size {
+    return Some(Err(()));
+  }
+
+  return Some(Ok(start..end.map_or(size, |end| (end + 1).min(size))));
+}
+
+/// Re-encodes a path returned by [sanitize_path] for use in a `Location` header.
+fn encode_path(path: &str) -> String {
+  const PATH: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+  return percent_encoding::utf8_percent_encode(path, PATH).to_string();
+}
+
+fn has_extension(path: &str) -> bool {
+  static SUFFIX_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"[.]\w+$").expect("const"));
+  return SUFFIX_RE.is_match(path);
+}
+
+/// Whether the file name contains a content hash, e.g. "index-B4x2Cn9a.js" as emitted by vite or
+/// "main.3f2a1b9c.css" as emitted by webpack.
+fn is_hashed_asset(path: &str) -> bool {
+  static HASHED_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"[.-]([0-9A-Za-z_]{8,})[.]\w+$").expect("const"));
+  return HASHED_RE
+    .captures(path)
+    .and_then(|c| c.get(1))
+    .is_some_and(|hash| hash.as_str().bytes().any(|b| b.is_ascii_digit()));
+}
+
+fn not_found() -> Response<Body> {
+  return Response::builder()
+    .status(StatusCode::NOT_FOUND)
+    .body(Body::from("Not found"))
+    .unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashMap;
+
+  struct MapSource(HashMap<&'static str, &'static str>);
+
+  impl StaticSource for MapSource {
+    fn open<'a>(&'a self, path: &'a str) -> OpenFuture<'a> {
+      return Box::pin(async move {
+        return Ok(self.0.get(path).map(|data| {
+          StaticFile::from_bytes(
+            Bytes::from_static(data.as_bytes()),
+            Some(format!("\"{}\"", data.len())),
+          )
+        }));
+      });
+    }
+  }
+
+  async fn get(service: &mut StaticSiteService, path: &str, headers: &[(&str, &str)]) -> Response {
+    let mut builder = Request::get(path);
+    for (name, value) in headers {
+      builder = builder.header(*name, *value);
+    }
+    return service
+      .call(builder.body(Body::empty()).expect("valid"))
+      .await
+      .expect("infallible");
+  }
+
+  async fn body(response: Response) -> String {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .expect("body");
+    return String::from_utf8(bytes.to_vec()).expect("utf8");
+  }
+
+  #[tokio::test]
+  async fn test_static_site_service() {
+    let source = MapSource(HashMap::from([
+      ("index.html", "index"),
+      ("assets/index-B4x2Cn9a.js", "js"),
+      ("assets/index-B4x2Cn9a.js.br", "brotli"),
+      ("docs/index.html", "docs"),
+      ("my docs/index.html", "my docs"),
+    ]));
+    let mut service = StaticSiteService::new(source).with_spa_fallback(true);
+
+    let response = get(&mut service, "/", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+    assert_eq!(body(response).await, "index");
+
+    let response = get(&mut service, "/assets/index-B4x2Cn9a.js", &[]).await;
+    assert_eq!(
+      response.headers()[header::CACHE_CONTROL],
+      "public, max-age=31536000, immutable"
+    );
+    assert!(
+      response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap_or_default()
+        .ends_with("javascript")
+    );
+    assert_eq!(body(response).await, "js");
+
+    // Pre-compressed variant.
+    let response = get(
+      &mut service,
+      "/assets/index-B4x2Cn9a.js",
+      &[("accept-encoding", "gzip, br")],
+    )
+    .await;
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+    assert!(
+      response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap_or_default()
+        .ends_with("javascript")
+    );
+    assert_eq!(body(response).await, "brotli");
+
+    // Conditional request.
+    let response = get(&mut service, "/index.html", &[("if-none-match", "\"5\"")]).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // Directory index.
+    assert_eq!(body(get(&mut service, "/docs/", &[]).await).await, "docs");
+    let response = get(&mut service, "/docs?a=b", &[]).await;
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(response.headers()[header::LOCATION], "/docs/?a=b");
+    // Must not turn into a protocol-relative URL.
+    let response = get(&mut service, "//docs", &[]).await;
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(response.headers()[header::LOCATION], "/docs/");
+    let response = get(&mut service, "/my%20docs", &[]).await;
+    assert_eq!(response.headers()[header::LOCATION], "/my%20docs/");
+
+    // SPA fallback for routes but not for files.
+    assert_eq!(
+      body(get(&mut service, "/users/123", &[]).await).await,
+      "index"
+    );
+    assert_eq!(
+      get(&mut service, "/favicon.ico", &[]).await.status(),
+      StatusCode::NOT_FOUND
+    );
+
+    assert_eq!(
+      get(&mut service, "/../secret", &[]).await.status(),
+      StatusCode::NOT_FOUND
+    );
+  }
+
+  #[tokio::test]
+  async fn test_static_site_range() {
+    let source = MapSource(HashMap::from([("video.mp4", "0123456789")]));
+    let mut service = StaticSiteService::new(source);
+
+    let response = get(&mut service, "/video.mp4", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+    assert_eq!(response.headers()[header::CONTENT_LENGTH], "10");
+
+    let response = get(&mut service, "/video.mp4", &[("range", "bytes=2-4")]).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-4/10");
+    assert_eq!(response.headers()[header::CONTENT_LENGTH], "3");
+    assert_eq!(body(response).await, "234");
+
+    let response = get(&mut service, "/video.mp4", &[("range", "bytes=-3")]).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(body(response).await, "789");
+
+    let response = get(&mut service, "/video.mp4", &[("range", "bytes=10-")]).await;
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+
+    // Stale `If-Range` serves the entire file.
+    let response = get(
+      &mut service,
+      "/video.mp4",
+      &[("range", "bytes=2-4"), ("if-range", "\"stale\"")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(response).await, "0123456789");
+  }
+
+  #[tokio::test]
+  async fn test_dir_source() {
+    let dir = std::env::temp_dir().join(format!("static_site_{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.expect("mkdir");
+    tokio::fs::write(dir.join("data.txt"), "0123456789")
+      .await
+      .expect("write");
+
+    let source = DirSource::new(&dir);
+    let file = source
+      .open("data.txt")
+      .await
+      .expect("open")
+      .expect("exists");
+    assert_eq!(file.len, 10);
+    assert!(file.etag.is_some());
+    let bytes = axum::body::to_bytes((file.read)(3..6).await.expect("read"), usize::MAX)
+      .await
+      .expect("body");
+    assert_eq!(&bytes[..], b"345");
+
+    assert!(source.open("missing.txt").await.expect("open").is_none());
+
+    tokio::fs::remove_dir_all(&dir).await.expect("cleanup");
+  }
+
+  #[test]
+  fn test_is_hashed_asset() {
+    assert!(is_hashed_asset("assets/index-B4x2Cn9a.js"));
+    assert!(is_hashed_asset("main.3f2a1b9c.css"));
+    assert!(!is_hashed_asset("index.html"));
+    assert!(!is_hashed_asset("assets/component-library.js"));
+  }
+}
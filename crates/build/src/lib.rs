@@ -21,6 +21,7 @@ pub fn build_protos(proto_path: impl AsRef<Path>) -> Result<()> {
     PathBuf::from(format!("{path}/config.proto")),
     PathBuf::from(format!("{path}/config_api.proto")),
     PathBuf::from(format!("{path}/metadata.proto")),
+    PathBuf::from(format!("{path}/records.proto")),
     PathBuf::from(format!("{path}/vault.proto")),
   ];
 
@@ -1,7 +1,7 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
 
-use trailbase::DataDir;
 use trailbase::api::JsonSchemaMode;
+use trailbase::{DataDir, OtelProtocol};
 
 #[derive(ValueEnum, Clone, Copy, Debug)]
 pub enum JsonSchemaModeArg {
@@ -23,6 +23,23 @@ impl From<JsonSchemaModeArg> for JsonSchemaMode {
   }
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OtelProtocolArg {
+  /// OTLP over gRPC.
+  Grpc,
+  /// OTLP over HTTP with protobuf payloads.
+  Http,
+}
+
+impl From<OtelProtocolArg> for OtelProtocol {
+  fn from(value: OtelProtocolArg) -> Self {
+    match value {
+      OtelProtocolArg::Grpc => Self::Grpc,
+      OtelProtocolArg::Http => Self::HttpProtobuf,
+    }
+  }
+}
+
 /// Command line arguments for TrailBase's CLI.
 ///
 /// NOTE: a good rule of thumb for thinking of proto config vs CLI options: if it requires a
@@ -67,11 +84,46 @@ pub enum SubCommands {
     /// Optional database name
     db: Option<String>,
   },
+  /// Applies a declarative schema, i.e. a file of CREATE TABLE, VIEW and INDEX statements, by
+  /// generating and applying the migration needed to get from the current to the desired schema.
+  ApplySchema {
+    /// SQL file with the desired schema.
+    file: std::path::PathBuf,
+    /// Database to apply the schema to (Default: main).
+    #[arg(long)]
+    db: Option<String>,
+    /// Drop tables, views and indexes not part of the desired schema.
+    #[arg(long, default_value_t = false)]
+    prune: bool,
+    /// Only print the migration w/o applying it.
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+  },
+  /// Rolls back the most recently applied batch of migrations using their down migrations, i.e.
+  /// paired `U<timestamp>__<name>.down.sql` files, and removes the migration files.
+  RollbackMigrations {
+    /// Database to roll back migrations for (Default: main).
+    #[arg(long)]
+    db: Option<String>,
+    /// Only print the down migrations w/o applying them.
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+  },
   /// Manage admin users (list, demote, promote).
   Admin {
     #[command(subcommand)]
     cmd: Option<AdminSubCommands>,
   },
+  /// List and restore backups taken by the backup job.
+  Backup {
+    #[command(subcommand)]
+    cmd: Option<BackupSubCommands>,
+  },
+  /// List and restore replicas of the main database in the object store.
+  Replica {
+    #[command(subcommand)]
+    cmd: Option<ReplicaSubCommands>,
+  },
   /// Manage users. Unlike the admin UI this will also let you change admin users.
   User {
     #[command(subcommand)]
@@ -84,6 +136,13 @@ pub enum SubCommands {
     #[command(subcommand)]
     cmd: Option<ComponentSubCommands>,
   },
+  /// Run declarative API contract tests from JSON fixtures against an ephemeral instance using
+  /// the data directory's config and migrations.
+  Test {
+    /// Fixture files to run.
+    #[arg(required = true)]
+    fixtures: Vec<std::path::PathBuf>,
+  },
 }
 
 #[derive(Args, Clone, Debug)]
@@ -102,10 +161,15 @@ pub struct ServerArgs {
 
   /// Enable SPA fallback: serve index.html for routes (paths without file extensions).
   /// File requests (e.g., /favicon.ico) will still return 404 if not found.
-  /// Use with --public-dir.
+  /// Use with --public-dir or --public-object-store-prefix.
   #[arg(long, env, default_value_t = false)]
   pub spa: bool,
 
+  /// Optional prefix of the configured object store, e.g. "public/", to serve static assets from
+  /// at the HTTP root instead of --public-dir.
+  #[arg(long, env, conflicts_with = "public_dir")]
+  pub public_object_store_prefix: Option<String>,
+
   /// Optional path to sandboxed FS root for WASM runtime.
   #[arg(long, env)]
   pub runtime_root_fs: Option<String>,
@@ -136,6 +200,71 @@ pub struct ServerArgs {
 
   #[arg(long, env)]
   pub experimental_pg: Option<String>,
+
+  /// When set, the Record and Auth APIs will also be served via gRPC on this address. Requires
+  /// the "grpc" feature.
+  #[arg(long, env)]
+  pub grpc_address: Option<String>,
+
+  /// When set, Prometheus metrics will be served under `/metrics` on this address.
+  #[arg(long, env)]
+  pub metrics_address: Option<String>,
+
+  /// When set, request traces will be exported to this OTLP collector endpoint. Requires the
+  /// "otel" feature.
+  #[arg(long, env)]
+  pub otel_endpoint: Option<String>,
+
+  /// Transport used to export traces.
+  #[arg(long, env, default_value = "grpc")]
+  pub otel_protocol: OtelProtocolArg,
+
+  /// Ratio of traces to sample in [0, 1] (Default: 1). Requests with a `traceparent` header
+  /// follow the caller's sampling decision.
+  #[arg(long, env)]
+  pub otel_sample_ratio: Option<f64>,
+
+  /// Additional resource attributes of exported traces, e.g. "deployment.environment=prod".
+  #[arg(long, env, value_delimiter = ',', value_parser = parse_key_value)]
+  pub otel_resource_attributes: Vec<(String, String)>,
+
+  /// When set, TLS certificates for these domains are provisioned and renewed automatically via
+  /// ACME, e.g. Let's Encrypt. Requires the "acme" feature.
+  #[arg(long, env, value_delimiter = ',')]
+  pub acme_domains: Vec<String>,
+
+  /// Contact emails registered with the ACME account, e.g. for expiry notices.
+  #[arg(long, env, value_delimiter = ',')]
+  pub acme_contact: Vec<String>,
+
+  /// Use Let's Encrypt's production environment. Defaults to staging, whose certificates aren't
+  /// trusted by browsers.
+  #[arg(long, env, default_value_t = false)]
+  pub acme_production: bool,
+
+  /// Custom ACME directory URL, e.g. of another CA.
+  #[arg(long, env)]
+  pub acme_directory_url: Option<String>,
+
+  /// When set, ACME challenges are answered via HTTP-01 on this address, e.g. "0.0.0.0:80".
+  /// Defaults to TLS-ALPN-01 on the main address.
+  #[arg(long, env)]
+  pub acme_http01_address: Option<String>,
+
+  /// Number of read-only connections serving record reads and listings, which don't contend
+  /// with writes. Disabled by default.
+  #[arg(long, env)]
+  pub read_replica_threads: Option<usize>,
+
+  /// Serve record reads and listings from a replicated copy of the main database, e.g.
+  /// maintained by LiteFS, instead of the main database.
+  #[arg(long, env)]
+  pub read_replica_path: Option<String>,
+
+  /// Watch `config.textproto` and `secrets/secrets.textproto` and apply changes without restarting.
+  /// Invalid edits are rejected and logged.
+  #[arg(long, default_value_t = false)]
+  pub watch_config: bool,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -191,6 +320,31 @@ pub enum AdminSubCommands {
   },
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum BackupSubCommands {
+  /// Lists available backups, local and uploaded to the object store.
+  List,
+  /// Restores a backup. The current state is backed up first. Stop the server before restoring.
+  Restore {
+    /// Name of the backup, e.g. main-20250101T000000Z.db.
+    name: String,
+  },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ReplicaSubCommands {
+  /// Lists replication generations in the object store.
+  Generations,
+  /// Restores the replica to a new database file, e.g. to replace traildepot/data/main.db.
+  Restore {
+    /// Path of the restored database. Must not exist.
+    output: std::path::PathBuf,
+    /// Point in time to restore, e.g. 2025-01-01T00:00:00Z. Defaults to the latest state.
+    #[arg(long)]
+    timestamp: Option<String>,
+  },
+}
+
 // TODO: Add "create user" (low priority since users can be created via the UI).
 #[derive(Subcommand, Debug, Clone)]
 pub enum UserSubCommands {
@@ -256,6 +410,13 @@ pub enum UserSubCommands {
   },
 }
 
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+  let Some((key, value)) = s.split_once('=') else {
+    return Err(format!("Expected <key>=<value>, got: {s}"));
+  };
+  return Ok((key.trim().to_string(), value.trim().to_string()));
+}
+
 #[derive(Clone, Debug)]
 pub enum ComponentReference {
   Path(std::path::PathBuf),
@@ -9,7 +9,10 @@ use itertools::Itertools;
 use serde::Deserialize;
 use std::io::Write;
 use trailbase::api::{self, Email, InitArgs, JsonSchemaMode, init_app_state};
-use trailbase::{DataDir, Server, ServerOptions, constants::USER_TABLE};
+use trailbase::{
+  AcmeOptions, DataDir, OtelOptions, ReadReplicaOptions, Server, ServerOptions,
+  constants::USER_TABLE,
+};
 use trailbase_cli::wasm::{
   download_component, find_component, find_component_by_filename, install_wasm_component,
   list_installed_wasm_components, repo,
@@ -17,8 +20,8 @@ use trailbase_cli::wasm::{
 use utoipa::OpenApi;
 
 use trailbase_cli::{
-  AdminSubCommands, CommandLineArgs, ComponentReference, ComponentSubCommands, OpenApiSubCommands,
-  SubCommands, UserSubCommands,
+  AdminSubCommands, BackupSubCommands, CommandLineArgs, ComponentReference, ComponentSubCommands,
+  OpenApiSubCommands, ReplicaSubCommands, SubCommands, UserSubCommands,
 };
 
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
@@ -70,6 +73,7 @@ async fn async_main(
         admin_address: cmd.admin_address,
         public_dir: cmd.public_dir.map(|p| p.into()),
         public_dir_spa: cmd.spa,
+        public_object_store_prefix: cmd.public_object_store_prefix,
         runtime_root_fs: cmd.runtime_root_fs.map(|p| p.into()),
         geoip_db_path: cmd.geoip_db_path.map(|p| p.into()),
         log_responses: cmd.dev || cmd.stderr_logging,
@@ -79,7 +83,42 @@ async fn async_main(
         wasm_tokio_runtime,
         tls_key: None,
         tls_cert: None,
+        // NOTE: Any ACME option enables ACME, such that incomplete options get rejected rather
+        // than silently ignored.
+        acme: (!cmd.acme_domains.is_empty()
+          || !cmd.acme_contact.is_empty()
+          || cmd.acme_production
+          || cmd.acme_directory_url.is_some()
+          || cmd.acme_http01_address.is_some())
+        .then(|| AcmeOptions {
+          domains: cmd.acme_domains,
+          contact: cmd.acme_contact,
+          production: cmd.acme_production,
+          directory_url: cmd.acme_directory_url,
+          http01_address: cmd.acme_http01_address,
+          cache_dir: None,
+        }),
         pg_uri: cmd.experimental_pg,
+        grpc_address: cmd.grpc_address,
+        metrics_address: cmd.metrics_address,
+        otel: cmd.otel_endpoint.map(|endpoint| OtelOptions {
+          endpoint: Some(endpoint),
+          protocol: cmd.otel_protocol.into(),
+          sample_ratio: cmd.otel_sample_ratio,
+          resource_attributes: cmd.otel_resource_attributes,
+        }),
+        read_replica: (cmd.read_replica_threads.is_some() || cmd.read_replica_path.is_some()).then(
+          || ReadReplicaOptions {
+            path: cmd.read_replica_path.map(|p| p.into()),
+            num_threads: cmd.read_replica_threads,
+          },
+        ),
+        record_hooks: vec![],
+        file_key_provider: None,
+        upload_scanners: vec![],
+        custom_routers: vec![],
+        sqlite_functions: vec![],
+        watch_config: cmd.watch_config,
       })
       .await?;
 
@@ -140,6 +179,61 @@ async fn async_main(
 
       println!("Created empty migration file: {path:?}");
     }
+    SubCommands::ApplySchema {
+      file,
+      db,
+      prune,
+      dry_run,
+    } => {
+      let (_new_db, state) = init_app_state(InitArgs {
+        data_dir,
+        public_url,
+        ..Default::default()
+      })
+      .await?;
+
+      let response = api::apply_schema(
+        &state,
+        api::ApplySchemaRequest {
+          schema: std::fs::read_to_string(&file)?,
+          database: db,
+          prune: Some(prune),
+          dry_run: Some(dry_run),
+        },
+      )
+      .await?;
+
+      if response.sql.is_empty() {
+        println!("Schema is up-to-date");
+      } else if dry_run {
+        println!("{}", response.sql);
+      } else {
+        println!("Applied migration:\n{}", response.sql);
+      }
+    }
+    SubCommands::RollbackMigrations { db, dry_run } => {
+      let (_new_db, state) = init_app_state(InitArgs {
+        data_dir,
+        public_url,
+        ..Default::default()
+      })
+      .await?;
+
+      let response = api::rollback_migrations(
+        &state,
+        api::RollbackMigrationsRequest {
+          database: db,
+          dry_run: Some(dry_run),
+        },
+      )
+      .await?;
+
+      if dry_run {
+        println!("{}", response.sql);
+      } else {
+        println!("Rolled back: {}", response.migrations.join(", "));
+      }
+    }
     SubCommands::Admin { cmd } => {
       let (_new_db, state) = init_app_state(InitArgs {
         data_dir,
@@ -186,6 +280,84 @@ async fn async_main(
         }
       };
     }
+    SubCommands::Backup { cmd } => {
+      let (_new_db, state) = init_app_state(InitArgs {
+        data_dir,
+        public_url,
+        ..Default::default()
+      })
+      .await?;
+
+      match cmd {
+        Some(BackupSubCommands::List) => {
+          println!("name\tcreated\tlocal\tremote");
+          for backup in api::list_backups(&state).await? {
+            println!(
+              "{}\t{created:?}\t{}\t{}",
+              backup.name,
+              backup.local,
+              backup.remote,
+              created = chrono::Utc.timestamp_opt(backup.created, 0),
+            );
+          }
+        }
+        Some(BackupSubCommands::Restore { name }) => {
+          let previous = api::restore_backup(&state, &name).await?;
+
+          println!("Restored '{name}'. Previous state backed up as '{previous}'");
+        }
+        None => {
+          CommandLineArgs::command()
+            .find_subcommand_mut("backup")
+            .map(|cmd| cmd.print_help());
+        }
+      };
+    }
+    SubCommands::Replica { cmd } => {
+      let (_new_db, state) = init_app_state(InitArgs {
+        data_dir,
+        public_url,
+        ..Default::default()
+      })
+      .await?;
+
+      match cmd {
+        Some(ReplicaSubCommands::Generations) => {
+          println!("generation\tsnapshot\tlatest\tsegments");
+          for generation in api::list_replica_generations(&state).await? {
+            println!(
+              "{}\t{snapshot:?}\t{latest:?}\t{}",
+              generation.id,
+              generation.segments,
+              snapshot = generation
+                .snapshot
+                .and_then(chrono::DateTime::from_timestamp_millis),
+              latest = generation
+                .latest
+                .and_then(chrono::DateTime::from_timestamp_millis),
+            );
+          }
+        }
+        Some(ReplicaSubCommands::Restore { output, timestamp }) => {
+          let timestamp = timestamp
+            .map(|ts| chrono::DateTime::parse_from_rfc3339(&ts))
+            .transpose()?
+            .map(|ts| ts.to_utc());
+          let restored = api::restore_replica(&state, timestamp, &output).await?;
+
+          println!(
+            "Restored generation '{}' as of {:?} to {output:?}",
+            restored.generation,
+            chrono::DateTime::from_timestamp_millis(restored.timestamp),
+          );
+        }
+        None => {
+          CommandLineArgs::command()
+            .find_subcommand_mut("replica")
+            .map(|cmd| cmd.print_help());
+        }
+      };
+    }
     SubCommands::User { cmd } => {
       let (_new_db, state) = init_app_state(InitArgs {
         data_dir,
@@ -398,6 +570,30 @@ async fn async_main(
         }
       };
     }
+    SubCommands::Test { fixtures } => {
+      // Copy config and migrations from the data directory if present.
+      let source = data_dir.root().exists().then_some(&data_dir);
+
+      let mut failures = 0;
+      for path in fixtures {
+        let fixture = trailbase::contract::Fixture::from_path(&path)?;
+        let report = trailbase::contract::run_fixture(fixture, source).await?;
+
+        for result in report.results {
+          match result.failure {
+            None => println!("PASS {path:?}: {}", result.name),
+            Some(failure) => {
+              failures += 1;
+              println!("FAIL {path:?}: {}\n\t{failure}", result.name);
+            }
+          };
+        }
+      }
+
+      if failures > 0 {
+        return Err(format!("{failures} contract test(s) failed").into());
+      }
+    }
   }
 
   return Ok(());
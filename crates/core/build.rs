@@ -9,5 +9,77 @@ fn main() -> std::io::Result<()> {
   trailbase_build::setup_version_info!();
   trailbase_build::build_protos("./proto")?;
 
+  #[cfg(feature = "grpc")]
+  build_grpc_services();
+
   return Ok(());
 }
+
+/// Generates tonic service stubs for the messages in `proto/records.proto`, which are compiled
+/// into `DESCRIPTOR_POOL` alongside the other protos.
+#[cfg(feature = "grpc")]
+fn build_grpc_services() {
+  use tonic_build::manual::{Builder, Method, Service};
+
+  let method = |name: &str, route_name: &str, input: &str, output: &str| {
+    return Method::builder()
+      .name(name)
+      .route_name(route_name)
+      .input_type(format!("crate::grpc::proto::{input}"))
+      .output_type(format!("crate::grpc::proto::{output}"))
+      .codec_path("tonic_prost::ProstCodec")
+      .build();
+  };
+
+  let record_service = Service::builder()
+    .name("RecordService")
+    .package("records")
+    .method(method(
+      "create_record",
+      "CreateRecord",
+      "CreateRecordRequest",
+      "CreateRecordResponse",
+    ))
+    .method(method(
+      "read_record",
+      "ReadRecord",
+      "ReadRecordRequest",
+      "ReadRecordResponse",
+    ))
+    .method(method(
+      "update_record",
+      "UpdateRecord",
+      "UpdateRecordRequest",
+      "UpdateRecordResponse",
+    ))
+    .method(method(
+      "delete_record",
+      "DeleteRecord",
+      "DeleteRecordRequest",
+      "DeleteRecordResponse",
+    ))
+    .method(method(
+      "list_records",
+      "ListRecords",
+      "ListRecordsRequest",
+      "ListRecordsResponse",
+    ))
+    .build();
+
+  let auth_service = Service::builder()
+    .name("AuthService")
+    .package("records")
+    .method(method("login", "Login", "LoginRequest", "LoginResponse"))
+    .method(method(
+      "refresh",
+      "Refresh",
+      "RefreshRequest",
+      "RefreshResponse",
+    ))
+    .build();
+
+  Builder::new()
+    .build_client(false)
+    .build_transport(false)
+    .compile(&[record_service, auth_service]);
+}
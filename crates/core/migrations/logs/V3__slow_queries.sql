@@ -0,0 +1,16 @@
+-- Statements exceeding the configured slow query threshold.
+CREATE TABLE IF NOT EXISTS _slow_queries (
+  id                           INTEGER PRIMARY KEY,
+
+  -- Timestamp in seconds with fractional millisecond resolution.
+  created                      REAL DEFAULT (UNIXEPOCH('subsec')) NOT NULL,
+
+  -- Path of the database file, NULL for in-memory databases.
+  database                     TEXT,
+  sql                          TEXT NOT NULL,
+  duration_ms                  REAL NOT NULL,
+  -- Output of "EXPLAIN QUERY PLAN".
+  query_plan                   TEXT NOT NULL
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS __slow_queries__created_index ON _slow_queries (created);
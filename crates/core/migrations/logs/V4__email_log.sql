@@ -0,0 +1,20 @@
+-- Outcome of outgoing emails, e.g. to debug deliverability.
+CREATE TABLE IF NOT EXISTS _email_log (
+  id                           INTEGER PRIMARY KEY,
+
+  -- Timestamp in seconds with fractional millisecond resolution.
+  created                      REAL DEFAULT (UNIXEPOCH('subsec')) NOT NULL,
+
+  -- Name of the provider, e.g. "smtp" or "ses".
+  provider                     TEXT NOT NULL,
+  sender                       TEXT NOT NULL,
+  recipient                    TEXT NOT NULL,
+  subject                      TEXT NOT NULL,
+  status                       TEXT CHECK(status IN ('sent', 'failed')) NOT NULL,
+  attempts                     INTEGER NOT NULL,
+  -- Error of the last attempt for failed deliveries.
+  error                        TEXT
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS __email_log__created_index ON _email_log (created);
+CREATE INDEX IF NOT EXISTS __email_log__recipient_index ON _email_log (recipient);
@@ -0,0 +1,20 @@
+-- Outcome of push notifications per device.
+CREATE TABLE IF NOT EXISTS _push_deliveries (
+  id                           INTEGER PRIMARY KEY,
+
+  -- Timestamp in seconds with fractional millisecond resolution.
+  created                      REAL DEFAULT (UNIXEPOCH('subsec')) NOT NULL,
+
+  -- Id shared by all deliveries of the same notification.
+  notification                 TEXT NOT NULL,
+  user                         BLOB NOT NULL,
+  device                       INTEGER NOT NULL,
+  platform                     TEXT NOT NULL,
+  title                        TEXT NOT NULL,
+  -- "unregistered": the device token is no longer valid and was removed.
+  status                       TEXT CHECK(status IN ('sent', 'failed', 'unregistered')) NOT NULL,
+  error                        TEXT
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS __push_deliveries__created_index ON _push_deliveries (created);
+CREATE INDEX IF NOT EXISTS __push_deliveries__notification_index ON _push_deliveries (notification);
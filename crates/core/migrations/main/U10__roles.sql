@@ -0,0 +1,17 @@
+--
+-- Roles, which can be assigned to users. Assigned roles are embedded into auth
+-- tokens and can be checked in record API access rules, e.g.
+-- `_USER_.has_role('editor')`.
+--
+CREATE TABLE _roles (
+  name                             TEXT PRIMARY KEY NOT NULL,
+  description                      TEXT,
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE TABLE _user_roles (
+  user                             BLOB NOT NULL REFERENCES _user(id) ON DELETE CASCADE,
+  role                             TEXT NOT NULL REFERENCES _roles(name) ON DELETE CASCADE ON UPDATE CASCADE,
+
+  PRIMARY KEY (user, role)
+) STRICT;
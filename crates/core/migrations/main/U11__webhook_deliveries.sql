@@ -0,0 +1,20 @@
+--
+-- Deliveries of auth event webhooks. Failed deliveries are kept around for
+-- inspection and manual redelivery.
+--
+CREATE TABLE _webhook_deliveries (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  -- Name of the webhook in the config.
+  webhook                          TEXT NOT NULL,
+  event                            TEXT NOT NULL,
+  payload                          TEXT NOT NULL CHECK(json_valid(payload)),
+  -- 0: pending, 1: delivered, 2: failed.
+  status                           INTEGER DEFAULT 0 NOT NULL,
+  attempts                         INTEGER DEFAULT 0 NOT NULL,
+  last_error                       TEXT,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  updated                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __webhook_deliveries__status_index ON _webhook_deliveries (status);
@@ -0,0 +1,21 @@
+--
+-- Durable queue of record change events for outgoing webhooks. Delivered
+-- events are removed, failed ones are kept around for inspection and retries.
+--
+CREATE TABLE _webhook_queue (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  -- Name of the webhook in the config.
+  webhook                          TEXT NOT NULL,
+  event                            TEXT NOT NULL,
+  payload                          TEXT NOT NULL CHECK(json_valid(payload)),
+  -- 0: pending, 2: failed.
+  status                           INTEGER DEFAULT 0 NOT NULL,
+  attempts                         INTEGER DEFAULT 0 NOT NULL,
+  -- Earliest time of the next delivery attempt.
+  next_attempt                     INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  last_error                       TEXT,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __webhook_queue__status_next_attempt_index ON _webhook_queue (status, next_attempt);
@@ -0,0 +1,16 @@
+--
+-- Outbox of captured row changes, which have yet to be published to their
+-- CDC sinks. Published changes are removed.
+--
+CREATE TABLE _cdc_outbox (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  -- Name of the sink in the config.
+  sink                             TEXT NOT NULL,
+  envelope                         TEXT NOT NULL CHECK(json_valid(envelope)),
+  attempts                         INTEGER DEFAULT 0 NOT NULL,
+  last_error                       TEXT,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __cdc_outbox__sink_index ON _cdc_outbox (sink, id);
@@ -0,0 +1,17 @@
+--
+-- Audit log of statements executed through the admin SQL query console.
+--
+CREATE TABLE _admin_query_log (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  -- Id of the admin user, who executed the query.
+  user                             BLOB CHECK(is_uuid(user)),
+  query                            TEXT NOT NULL,
+  params                           TEXT CHECK(params IS NULL OR json_valid(params)),
+  explain_query_plan               INTEGER DEFAULT FALSE NOT NULL,
+  duration_ms                      INTEGER NOT NULL,
+  error                            TEXT,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __admin_query_log__created_index ON _admin_query_log (created);
@@ -0,0 +1,16 @@
+--
+-- Catalog of tenants in multi-tenant mode. Each tenant's data lives in its own
+-- database: `<traildepot>/data/tenants/<name>.db`.
+--
+CREATE TABLE _tenant (
+  name                             TEXT PRIMARY KEY NOT NULL,
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+-- Assignment of users to tenants. The assigned tenant is embedded into auth
+-- tokens, which is used for claim-based routing and to keep users from
+-- accessing other tenants.
+CREATE TABLE _tenant_user (
+  user                             BLOB PRIMARY KEY NOT NULL REFERENCES _user(id) ON DELETE CASCADE,
+  tenant                           TEXT NOT NULL REFERENCES _tenant(name) ON DELETE CASCADE
+) STRICT;
@@ -0,0 +1,28 @@
+--
+-- Append-only history of applied config changes. Secrets are redacted.
+--
+CREATE TABLE _config_history (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  -- Hash of the applied config and the config it replaced.
+  hash                             TEXT NOT NULL,
+  parent_hash                      TEXT,
+  -- Id of the admin user, who applied the change. NULL for changes applied
+  -- by the server, e.g. on config file changes.
+  user                             BLOB CHECK(is_uuid(user)),
+  -- Text-proto encoded config with secrets redacted.
+  config                           TEXT NOT NULL,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __config_history__hash_index ON _config_history (hash);
+
+CREATE TRIGGER __config_history__no_update BEFORE UPDATE ON _config_history
+BEGIN
+  SELECT RAISE(ABORT, 'config history is append-only');
+END;
+
+CREATE TRIGGER __config_history__no_delete BEFORE DELETE ON _config_history
+BEGIN
+  SELECT RAISE(ABORT, 'config history is append-only');
+END;
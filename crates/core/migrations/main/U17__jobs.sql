@@ -0,0 +1,28 @@
+--
+-- Definitions of registered cron jobs and their run history.
+--
+CREATE TABLE _jobs (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  name                             TEXT NOT NULL UNIQUE,
+  schedule                         TEXT NOT NULL,
+  -- SQL executed by user-defined jobs. NULL for system and WASM jobs, which
+  -- are registered at runtime.
+  query                            TEXT,
+  paused                           INTEGER DEFAULT FALSE NOT NULL,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  updated                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE TABLE _job_runs (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  job                              TEXT NOT NULL,
+  -- Start timestamp in seconds and duration in milliseconds.
+  started                          INTEGER NOT NULL,
+  duration_ms                      INTEGER NOT NULL,
+  success                          INTEGER NOT NULL,
+  -- Error output of failed runs.
+  output                           TEXT
+) STRICT;
+
+CREATE INDEX __job_runs__job_index ON _job_runs (job, started);
@@ -0,0 +1,23 @@
+--
+-- Durable queue of background tasks. Completed tasks are removed, tasks that
+-- ran out of attempts are kept around as dead letters for inspection and
+-- requeueing.
+--
+CREATE TABLE _task_queue (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  -- Name of the queue, which determines the handler.
+  queue                            TEXT NOT NULL,
+  payload                          TEXT NOT NULL CHECK(json_valid(payload)),
+  -- 0: pending, 2: failed, i.e. dead-lettered.
+  status                           INTEGER DEFAULT 0 NOT NULL,
+  attempts                         INTEGER DEFAULT 0 NOT NULL,
+  -- NULL falls back to the configured default.
+  max_attempts                     INTEGER,
+  -- Earliest time of the next attempt.
+  next_attempt                     INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  last_error                       TEXT,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __task_queue__status_next_attempt_index ON _task_queue (status, next_attempt);
@@ -0,0 +1,32 @@
+--
+-- Append-only audit log of semantic admin and auth actions, e.g. altered
+-- tables, config changes or deleted users. Unlike the request logs, entries
+-- aren't subject to retention.
+--
+CREATE TABLE _audit_log (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  -- Id of the user, who performed the action. NULL for actions performed by
+  -- the server, e.g. on config file changes.
+  actor                            BLOB CHECK(is_uuid(actor)),
+  -- Action name, e.g. "table.altered".
+  action                           TEXT NOT NULL,
+  -- Affected entity, e.g. a table name or a user id.
+  target                           TEXT NOT NULL,
+  -- Action-specific details, e.g. a diff of the changed schema.
+  payload                          TEXT CHECK(payload IS NULL OR json_valid(payload)),
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __audit_log__created_index ON _audit_log (created);
+CREATE INDEX __audit_log__action_index ON _audit_log (action);
+
+CREATE TRIGGER __audit_log__no_update BEFORE UPDATE ON _audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'audit log is append-only');
+END;
+
+CREATE TRIGGER __audit_log__no_delete BEFORE DELETE ON _audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'audit log is append-only');
+END;
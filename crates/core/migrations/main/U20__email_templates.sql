@@ -0,0 +1,14 @@
+--
+-- Email templates edited via the admin UI. Take precedence over templates in
+-- the config and the built-in defaults. NULL fields fall back individually.
+--
+CREATE TABLE _email_templates (
+  -- Template name, e.g. "password_reset".
+  name                             TEXT PRIMARY KEY NOT NULL,
+  subject                          TEXT,
+  html                             TEXT,
+  -- Optional plain-text alternative of the HTML body.
+  text                             TEXT,
+
+  updated                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
@@ -0,0 +1,23 @@
+-- Durable queue of outbound emails drained by a background worker. Delivered
+-- emails are removed and recorded in the email log, undeliverable ones are kept
+-- around for inspection and requeueing.
+--
+CREATE TABLE _email_queue (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  -- "failed": ran out of attempts, "bounced": permanently rejected.
+  status                           TEXT DEFAULT 'queued' NOT NULL CHECK(status IN ('queued', 'failed', 'bounced')),
+  attempts                         INTEGER DEFAULT 0 NOT NULL,
+  -- Earliest time of the next attempt.
+  next_attempt                     INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  last_error                       TEXT,
+
+  sender                           TEXT NOT NULL,
+  recipient                        TEXT NOT NULL,
+  subject                          TEXT NOT NULL,
+  html                             TEXT NOT NULL,
+  text                             TEXT,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __email_queue__status_next_attempt_index ON _email_queue (status, next_attempt);
@@ -0,0 +1,28 @@
+-- Devices registered by users for receiving push notifications.
+CREATE TABLE _push_devices (
+  id                               INTEGER PRIMARY KEY NOT NULL,
+  user                             BLOB NOT NULL REFERENCES _user(id) ON DELETE CASCADE,
+  platform                         TEXT NOT NULL CHECK(platform IN ('fcm', 'apns', 'web_push')),
+  -- FCM registration token, APNs device token or WebPush endpoint.
+  token                            TEXT NOT NULL,
+  -- Keys of WebPush subscriptions for encrypting payloads.
+  p256dh                           TEXT,
+  auth                             TEXT,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  updated                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE UNIQUE INDEX __push_devices__token_index ON _push_devices (token);
+CREATE INDEX __push_devices__user_index ON _push_devices (user);
+
+-- Topics users subscribed to, e.g. "news". Notifications sent to a topic are
+-- delivered to all devices of all subscribed users.
+CREATE TABLE _push_topics (
+  topic                            TEXT NOT NULL,
+  user                             BLOB NOT NULL REFERENCES _user(id) ON DELETE CASCADE,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+
+  PRIMARY KEY (topic, user)
+) STRICT;
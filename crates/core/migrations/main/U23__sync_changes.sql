@@ -0,0 +1,14 @@
+-- Latest change per record of sync-enabled record APIs. Clients pull changes
+-- with a sequence number greater than their cursor. Updating a record bumps
+-- its sequence number, deletions are kept as tombstones.
+CREATE TABLE _sync_changes (
+  seq                              INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  table_name                       TEXT NOT NULL,
+  record_id                        BLOB NOT NULL,
+  op                               TEXT NOT NULL CHECK(op IN ('upsert', 'delete')),
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE UNIQUE INDEX __sync_changes__record_index ON _sync_changes (table_name, record_id);
+CREATE INDEX __sync_changes__seq_index ON _sync_changes (table_name, seq);
@@ -0,0 +1,17 @@
+--
+-- Ordered log of record changes of record APIs with change tracking enabled,
+-- recorded by triggers on the respective tables. Entries are kept for a
+-- limited time, see the RECORD_CHANGES_CLEANER system job.
+--
+CREATE TABLE _record_changes (
+  seq                              INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  table_name                       TEXT NOT NULL,
+  -- Primary key of the record, i.e. an integer or UUID.
+  record_id                        ANY NOT NULL,
+  op                               TEXT NOT NULL CHECK(op IN ('create', 'update', 'delete')),
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __record_changes__table_name_seq_index ON _record_changes (table_name, seq);
+CREATE INDEX __record_changes__created_index ON _record_changes (created);
@@ -0,0 +1,5 @@
+--
+-- Binds API keys to a tenant in multi-tenant mode. Keys w/o tenant may not
+-- access any tenant's data.
+--
+ALTER TABLE _api_keys ADD COLUMN tenant TEXT REFERENCES _tenant(name) ON DELETE CASCADE;
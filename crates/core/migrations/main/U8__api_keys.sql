@@ -0,0 +1,20 @@
+--
+-- Long-lived API keys for service-to-service access to record APIs.
+--
+CREATE TABLE _api_keys (
+  id                               BLOB PRIMARY KEY NOT NULL CHECK(is_uuid(id)) DEFAULT (uuid_v4()),
+  name                             TEXT NOT NULL,
+  -- SHA-256 hash of the secret. The secret itself is only revealed once, when
+  -- the key is minted.
+  secret_hash                      BLOB NOT NULL,
+  -- JSON array of record API names the key grants access to.
+  record_apis                      TEXT NOT NULL CHECK(json_valid(record_apis)),
+  -- Bitmask of granted permissions, see `PermissionFlag`.
+  permissions                      INTEGER NOT NULL,
+
+  created                          INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  -- Optional expiration, NULL means the key doesn't expire.
+  expires                          INTEGER
+) STRICT;
+
+CREATE UNIQUE INDEX __api_keys__secret_hash_index ON _api_keys (secret_hash);
@@ -0,0 +1,13 @@
+--
+-- Pending email address changes. Changes only take effect once confirmed via
+-- both the old and the new address.
+--
+CREATE TABLE _email_change (
+  user                             BLOB PRIMARY KEY NOT NULL REFERENCES _user(id) ON DELETE CASCADE,
+  -- NULL for users without an email address, e.g. username-only users.
+  old_email                        TEXT,
+  new_email                        TEXT NOT NULL CHECK(is_email(new_email)),
+  old_email_confirmed              INTEGER DEFAULT FALSE NOT NULL,
+  new_email_confirmed              INTEGER DEFAULT FALSE NOT NULL,
+  expires                          INTEGER NOT NULL
+) STRICT;
@@ -0,0 +1,18 @@
+--
+-- Idempotency keys for safely retrying record creation.
+--
+CREATE TABLE _idempotency (
+  id                           INTEGER PRIMARY KEY NOT NULL,
+  api                          TEXT NOT NULL,
+  user                         BLOB,
+  idempotency_key              TEXT NOT NULL,
+  -- Hash of the request, used to detect key reuse across different requests.
+  fingerprint                  TEXT NOT NULL,
+  -- JSON response. NULL while the original request is still in flight.
+  response                     TEXT,
+  created                      INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  expires                      INTEGER NOT NULL
+) STRICT;
+
+-- Keys are scoped by API and user. Anonymous requests share a scope.
+CREATE UNIQUE INDEX __idempotency__key ON _idempotency (api, IFNULL(user, X''), idempotency_key);
@@ -0,0 +1,14 @@
+--
+-- Nonces of pending magic links for password-less login. Links are single-use,
+-- i.e. the nonce is deleted upon login.
+--
+CREATE TABLE _magic_link (
+  id                           INTEGER PRIMARY KEY NOT NULL,
+  user                         BLOB NOT NULL,
+  nonce                        TEXT NOT NULL,
+  created                      INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  expires                      INTEGER NOT NULL
+) STRICT;
+
+-- Main nonce lookup.
+CREATE UNIQUE INDEX __magic_link__nonce ON _magic_link (nonce);
@@ -0,0 +1,11 @@
+--
+-- Pending SAML authentication requests. Consumed when the identity provider's
+-- response arrives, which ties responses to requests and prevents replays.
+--
+CREATE TABLE _saml_request (
+  id                           TEXT PRIMARY KEY NOT NULL,
+  provider                     TEXT NOT NULL,
+  redirect_uri                 TEXT,
+  created                      INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  expires                      INTEGER NOT NULL
+) STRICT;
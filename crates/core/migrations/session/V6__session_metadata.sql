@@ -0,0 +1,7 @@
+--
+-- Client metadata for sessions, allowing users to tell their devices apart.
+--
+ALTER TABLE _session ADD COLUMN user_agent TEXT;
+ALTER TABLE _session ADD COLUMN client_ip TEXT;
+-- Last refresh of the session's auth token. NULL if never refreshed.
+ALTER TABLE _session ADD COLUMN last_seen INTEGER;
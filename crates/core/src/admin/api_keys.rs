@@ -0,0 +1,287 @@
+use axum::{
+  Json,
+  extract::State,
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::auth::api_key::{hash_api_key_secret, new_api_key_secret};
+use crate::constants::API_KEY_TABLE;
+use crate::records::Permission;
+use crate::tenants::{TenantError, tenant_exists};
+
+const ALL_PERMISSIONS: [Permission; 5] = [
+  Permission::Create,
+  Permission::Read,
+  Permission::Update,
+  Permission::Delete,
+  Permission::Schema,
+];
+
+#[derive(Debug, Serialize, TS)]
+pub struct ApiKeyJson {
+  pub id: String,
+  pub name: String,
+  pub record_apis: Vec<String>,
+  pub permissions: Vec<Permission>,
+  pub created: i64,
+  pub expires: Option<i64>,
+  pub tenant: Option<String>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListApiKeysResponse {
+  keys: Vec<ApiKeyJson>,
+}
+
+#[derive(Deserialize)]
+struct DbApiKey {
+  id: [u8; 16],
+  name: String,
+  record_apis: String,
+  permissions: i64,
+  created: i64,
+  expires: Option<i64>,
+  tenant: Option<String>,
+}
+
+pub async fn list_api_keys_handler(
+  State(state): State<AppState>,
+) -> Result<Json<ListApiKeysResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "SELECT id, name, record_apis, permissions, created, expires, tenant FROM '{API_KEY_TABLE}' ORDER BY created DESC"
+  );
+
+  let keys = state
+    .conn()
+    .read_query_values::<DbApiKey>(QUERY, ())
+    .await?
+    .into_iter()
+    .map(|key| {
+      return Ok(ApiKeyJson {
+        id: Uuid::from_bytes(key.id).to_string(),
+        name: key.name,
+        record_apis: serde_json::from_str(&key.record_apis)?,
+        permissions: ALL_PERMISSIONS
+          .into_iter()
+          .filter(|p| (key.permissions & (*p as i64)) > 0)
+          .collect(),
+        created: key.created,
+        expires: key.expires,
+        tenant: key.tenant,
+      });
+    })
+    .collect::<Result<Vec<_>, Error>>()?;
+
+  return Ok(Json(ListApiKeysResponse { keys }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct CreateApiKeyRequest {
+  pub name: String,
+  /// Names of the record APIs the key grants access to.
+  pub record_apis: Vec<String>,
+  pub permissions: Vec<Permission>,
+  /// Time-to-live in seconds. The key never expires if absent.
+  pub ttl_sec: Option<i64>,
+  /// Tenant to bind the key to. Only keys bound to a tenant may access its data.
+  pub tenant: Option<String>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct CreateApiKeyResponse {
+  pub id: String,
+  /// The key's secret. It is only revealed once and cannot be recovered later.
+  pub secret: String,
+}
+
+pub async fn create_api_key_handler(
+  State(state): State<AppState>,
+  Json(request): Json<CreateApiKeyRequest>,
+) -> Result<Json<CreateApiKeyResponse>, Error> {
+  if request.name.trim().is_empty() {
+    return Err(Error::BadRequest("missing name".into()));
+  }
+  if request.record_apis.is_empty() || request.permissions.is_empty() {
+    return Err(Error::BadRequest(
+      "API keys require at least one record API and permission".into(),
+    ));
+  }
+  for api_name in &request.record_apis {
+    if state.lookup_base_record_api(api_name).is_none() {
+      return Err(Error::BadRequest(
+        format!("unknown record API: {api_name}").into(),
+      ));
+    }
+  }
+
+  if let Some(ref tenant) = request.tenant
+    && !tenant_exists(&state, tenant).await?
+  {
+    return Err(TenantError::NotFound.into());
+  }
+
+  let expires = match request.ttl_sec {
+    Some(ttl) if ttl <= 0 => return Err(Error::BadRequest("invalid ttl".into())),
+    Some(ttl) => Some((Utc::now() + Duration::seconds(ttl)).timestamp()),
+    None => None,
+  };
+  let permissions = request
+    .permissions
+    .iter()
+    .fold(0_i64, |acc, p| acc | (*p as i64));
+
+  let secret = new_api_key_secret();
+
+  const INSERT_QUERY: &str = formatcp!(
+    "\
+      INSERT INTO '{API_KEY_TABLE}' (name, secret_hash, record_apis, permissions, expires, tenant) \
+      VALUES ($1, $2, $3, $4, $5, $6) \
+      RETURNING id \
+    "
+  );
+  let id: [u8; 16] = state
+    .conn()
+    .write_query_row_get(
+      INSERT_QUERY,
+      params!(
+        request.name,
+        hash_api_key_secret(&secret),
+        serde_json::to_string(&request.record_apis)?,
+        permissions,
+        expires,
+        request.tenant,
+      ),
+      0,
+    )
+    .await?
+    .ok_or_else(|| Error::Internal("insert failed".into()))?;
+
+  return Ok(Json(CreateApiKeyResponse {
+    id: Uuid::from_bytes(id).to_string(),
+    secret,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct DeleteApiKeyRequest {
+  id: Uuid,
+}
+
+/// Revokes an API key.
+pub async fn delete_api_key_handler(
+  State(state): State<AppState>,
+  Json(request): Json<DeleteApiKeyRequest>,
+) -> Result<Response, Error> {
+  const DELETE_QUERY: &str = formatcp!("DELETE FROM '{API_KEY_TABLE}' WHERE id = $1");
+  state
+    .conn()
+    .execute(DELETE_QUERY, params!(request.id.into_bytes()))
+    .await?;
+
+  return Ok((StatusCode::OK, "deleted").into_response());
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::{Request, header};
+  use axum::{Json, extract::State};
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::auth::User;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::test_utils::add_record_api_config;
+
+  #[tokio::test]
+  async fn test_api_key_lifecycle() {
+    let state = test_state(None).await.unwrap();
+
+    state
+      .conn()
+      .execute_batch("CREATE TABLE message (id INTEGER PRIMARY KEY, text TEXT) STRICT;")
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("message".to_string()),
+        table_name: Some("message".to_string()),
+        acl_authenticated: [PermissionFlag::Read as i32, PermissionFlag::Create as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let response = create_api_key_handler(
+      State(state.clone()),
+      Json(CreateApiKeyRequest {
+        name: "reader".to_string(),
+        record_apis: vec!["message".to_string()],
+        permissions: vec![Permission::Read],
+        ttl_sec: None,
+        tenant: None,
+      }),
+    )
+    .await
+    .unwrap();
+
+    let keys = list_api_keys_handler(State(state.clone())).await.unwrap();
+    assert_eq!(keys.keys.len(), 1);
+    assert_eq!(keys.keys[0].permissions, vec![Permission::Read]);
+
+    let extract_user = async |secret: &str| {
+      let (mut parts, _) = Request::builder()
+        .header(header::AUTHORIZATION, format!("Bearer {secret}"))
+        .body(())
+        .unwrap()
+        .into_parts();
+      return <User as axum::extract::FromRequestParts<AppState>>::from_request_parts(
+        &mut parts, &state,
+      )
+      .await;
+    };
+
+    let user = extract_user(&response.secret).await.unwrap();
+    let api = state.lookup_record_api("message").unwrap();
+    assert!(
+      api
+        .check_table_level_access(Permission::Read, Some(&user))
+        .is_ok()
+    );
+    // Allowed by the API's ACL but not by the key's scope.
+    assert!(
+      api
+        .check_table_level_access(Permission::Create, Some(&user))
+        .is_err()
+    );
+
+    assert!(extract_user("tb_invalid").await.is_err());
+
+    delete_api_key_handler(
+      State(state.clone()),
+      Json(DeleteApiKeyRequest {
+        id: Uuid::parse_str(&response.id).unwrap(),
+      }),
+    )
+    .await
+    .unwrap();
+
+    assert!(extract_user(&response.secret).await.is_err());
+  }
+}
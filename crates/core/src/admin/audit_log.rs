@@ -0,0 +1,204 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::constants::{AUDIT_LOG_TABLE, USER_TABLE};
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct AuditEvent {
+  pub id: i64,
+  /// User, who performed the action. None for actions performed by the server, e.g. on config
+  /// file changes.
+  pub actor_id: Option<String>,
+  pub actor_email: Option<String>,
+  /// Action name, e.g. "table.altered".
+  pub action: String,
+  /// Affected entity, e.g. a table name or a user id.
+  pub target: String,
+  /// Action-specific details, e.g. a diff of the changed schema.
+  pub payload: Option<serde_json::Value>,
+  pub created: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListAuditLogResponse {
+  events: Vec<AuditEvent>,
+  /// Cursor for fetching the next page of older events, if any.
+  cursor: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListAuditLogQuery {
+  action: Option<String>,
+  actor: Option<Uuid>,
+  target: Option<String>,
+  /// Only list events created at or after the given UNIX timestamp.
+  since: Option<i64>,
+  /// Only list events created before the given UNIX timestamp.
+  until: Option<i64>,
+  /// Only list events older than the given event id.
+  cursor: Option<i64>,
+  limit: Option<usize>,
+}
+
+/// Lists audit events, most recent first.
+pub async fn list_audit_log_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListAuditLogQuery>,
+) -> Result<Json<ListAuditLogResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT a.id, a.actor, u.email, a.action, a.target, a.payload, a.created \
+      FROM '{AUDIT_LOG_TABLE}' AS a LEFT JOIN '{USER_TABLE}' AS u ON a.actor = u.id \
+      WHERE \
+        ($1 IS NULL OR a.action = $1) AND \
+        ($2 IS NULL OR a.actor = $2) AND \
+        ($3 IS NULL OR a.target = $3) AND \
+        ($4 IS NULL OR a.created >= $4) AND \
+        ($5 IS NULL OR a.created < $5) AND \
+        ($6 IS NULL OR a.id < $6) \
+      ORDER BY a.id DESC LIMIT $7 \
+    "
+  );
+
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  let rows = state
+    .conn()
+    .read_query_rows(
+      QUERY,
+      params!(
+        query.action,
+        query.actor.map(|id| id.into_bytes().to_vec()),
+        query.target,
+        query.since,
+        query.until,
+        query.cursor,
+        limit as i64,
+      ),
+    )
+    .await?;
+
+  let events = rows
+    .iter()
+    .map(|row| -> Result<AuditEvent, Error> {
+      let actor: Option<[u8; 16]> = row.get(1)?;
+      let payload: Option<String> = row.get(5)?;
+      return Ok(AuditEvent {
+        id: row.get(0)?,
+        actor_id: actor.map(|id| Uuid::from_bytes(id).to_string()),
+        actor_email: row.get(2)?,
+        action: row.get(3)?,
+        target: row.get(4)?,
+        payload: payload.and_then(|p| serde_json::from_str(&p).ok()),
+        created: row.get(6)?,
+      });
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let cursor = if events.len() == limit {
+    events.last().map(|e| e.id)
+  } else {
+    None
+  };
+
+  return Ok(Json(ListAuditLogResponse { events, cursor }));
+}
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::audit::{AuditAction, record_audit_event};
+
+  #[tokio::test]
+  async fn test_list_audit_log() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let admin_id = crate::admin::user::create_user_for_test(&state, "admin@test.org", "Secret!1!!")
+      .await
+      .unwrap();
+
+    for table in ["a", "b", "c"] {
+      record_audit_event(
+        &state,
+        Some(admin_id),
+        AuditAction::TableDropped,
+        table,
+        Some(serde_json::json!({ "type": "table" })),
+      )
+      .await;
+    }
+    record_audit_event(&state, None, AuditAction::UserDeleted, "some-user", None).await;
+
+    let list = async |query: ListAuditLogQuery| {
+      return list_audit_log_handler(State(state.clone()), Query(query))
+        .await
+        .unwrap()
+        .0;
+    };
+
+    let response = list(ListAuditLogQuery {
+      action: Some("table.dropped".to_string()),
+      limit: Some(2),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(
+      response
+        .events
+        .iter()
+        .map(|e| e.target.as_str())
+        .collect::<Vec<_>>(),
+      ["c", "b"]
+    );
+    assert_eq!(response.events[0].actor_id, Some(admin_id.to_string()));
+    assert_eq!(
+      response.events[0].actor_email.as_deref(),
+      Some("admin@test.org")
+    );
+    assert_eq!(
+      response.events[0].payload,
+      Some(serde_json::json!({ "type": "table" }))
+    );
+
+    let next = list(ListAuditLogQuery {
+      action: Some("table.dropped".to_string()),
+      cursor: response.cursor,
+      limit: Some(2),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(next.events.len(), 1);
+    assert_eq!(next.events[0].target, "a");
+    assert_eq!(next.cursor, None);
+
+    let by_target = list(ListAuditLogQuery {
+      target: Some("some-user".to_string()),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(by_target.events.len(), 1);
+    assert_eq!(by_target.events[0].action, "user.deleted");
+    assert_eq!(by_target.events[0].actor_id, None);
+
+    // The audit log is append-only.
+    assert!(
+      state
+        .conn()
+        .execute(format!("DELETE FROM '{AUDIT_LOG_TABLE}'"), ())
+        .await
+        .is_err()
+    );
+  }
+}
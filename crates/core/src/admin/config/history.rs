@@ -0,0 +1,198 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::auth::User;
+use crate::config::proto::{Config, Vault, hash_config};
+use crate::config::{merge_vault_and_env, redact_secrets};
+use crate::constants::{CONFIG_HISTORY_TABLE, USER_TABLE};
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ConfigVersion {
+  pub id: i64,
+  pub hash: String,
+  /// Hash of the config this version replaced.
+  pub parent_hash: Option<String>,
+  /// Admin user, who applied the change. None for changes applied by the server, e.g. on config
+  /// file changes.
+  pub user_id: Option<String>,
+  pub user_email: Option<String>,
+  pub created: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListConfigHistoryResponse {
+  versions: Vec<ConfigVersion>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListConfigHistoryQuery {
+  limit: Option<usize>,
+}
+
+/// Lists applied config versions, most recent first.
+pub async fn list_config_history_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListConfigHistoryQuery>,
+) -> Result<Json<ListConfigHistoryResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT h.id, h.hash, h.parent_hash, h.user, u.email, h.created \
+      FROM '{CONFIG_HISTORY_TABLE}' AS h LEFT JOIN '{USER_TABLE}' AS u ON h.user = u.id \
+      ORDER BY h.id DESC LIMIT $1 \
+    "
+  );
+
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+  let rows = state
+    .conn()
+    .read_query_rows(QUERY, params!(limit as i64))
+    .await?;
+
+  let versions = rows
+    .iter()
+    .map(|row| -> Result<ConfigVersion, Error> {
+      let user: Option<[u8; 16]> = row.get(3)?;
+      return Ok(ConfigVersion {
+        id: row.get(0)?,
+        hash: row.get(1)?,
+        parent_hash: row.get(2)?,
+        user_id: user.map(|id| Uuid::from_bytes(id).to_string()),
+        user_email: row.get(4)?,
+        created: row.get(5)?,
+      });
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+  return Ok(Json(ListConfigHistoryResponse { versions }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct RollbackConfigRequest {
+  /// Hash of the config version to roll back to.
+  pub hash: String,
+}
+
+/// Re-applies a prior config version. Secrets are merged in from the current config, since
+/// they're redacted in the history. The rollback itself is recorded as a new version.
+pub async fn rollback_config_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<RollbackConfigRequest>,
+) -> Result<Response, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  const QUERY: &str = formatcp!(
+    "SELECT config FROM '{CONFIG_HISTORY_TABLE}' WHERE hash = $1 ORDER BY id DESC LIMIT 1"
+  );
+  let Some(text) = state
+    .conn()
+    .read_query_row_get::<String>(QUERY, params!(request.hash), 0)
+    .await?
+  else {
+    return Err(Error::Precondition("Unknown config version".into()));
+  };
+
+  let current = state.get_config();
+  let (_, secrets) = redact_secrets(&current)?;
+  let config = merge_vault_and_env(Config::from_text(&text)?, Vault { secrets })?;
+
+  state
+    .validate_and_update_config_as(config, Some(hash_config(&current)), Some(user.uuid))
+    .await?;
+
+  return Ok((StatusCode::OK, "Config rolled back").into_response());
+}
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_config_history_and_rollback() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let user_id = crate::admin::user::create_user_for_test(&state, "admin@test.org", "Secret!1!!")
+      .await
+      .unwrap();
+    let user = User::from_unverified(user_id, Some("admin@test.org"), None);
+
+    let update = async |name: &str| {
+      let current = state.get_config();
+      let mut config = (*current).clone();
+      config.server.application_name = Some(name.to_string());
+      state
+        .validate_and_update_config_as(config, Some(hash_config(&current)), Some(user_id))
+        .await
+        .unwrap();
+      return hash_config(&state.get_config());
+    };
+
+    let first = update("First").await;
+    let second = update("Second").await;
+
+    let versions = list_config_history_handler(State(state.clone()), Query(Default::default()))
+      .await
+      .unwrap()
+      .0
+      .versions;
+    assert_eq!(versions[0].hash, second);
+    assert_eq!(versions[0].parent_hash.as_deref(), Some(first.as_str()));
+    assert_eq!(versions[0].user_id, Some(user_id.to_string()));
+    assert_eq!(versions[0].user_email.as_deref(), Some("admin@test.org"));
+    assert_eq!(versions[1].hash, first);
+
+    // History is append-only.
+    assert!(
+      state
+        .conn()
+        .execute(format!("DELETE FROM '{CONFIG_HISTORY_TABLE}'"), ())
+        .await
+        .is_err()
+    );
+
+    rollback_config_handler(
+      State(state.clone()),
+      user.clone(),
+      Json(RollbackConfigRequest {
+        hash: first.clone(),
+      }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(hash_config(&state.get_config()), first);
+    assert_eq!(
+      state.get_config().server.application_name.as_deref(),
+      Some("First")
+    );
+
+    assert!(
+      rollback_config_handler(
+        State(state.clone()),
+        user,
+        Json(RollbackConfigRequest {
+          hash: "unknown".to_string(),
+        }),
+      )
+      .await
+      .is_err()
+    );
+  }
+}
@@ -1,5 +1,7 @@
 mod get_config;
+mod history;
 mod update_config;
 
 pub use get_config::get_config_handler;
+pub use history::{list_config_history_handler, rollback_config_handler};
 pub use update_config::update_config_handler;
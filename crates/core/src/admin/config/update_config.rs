@@ -4,12 +4,14 @@ use axum::response::IntoResponse;
 
 use crate::admin::AdminError as Error;
 use crate::app_state::AppState;
+use crate::auth::User;
 use crate::config::proto::{UpdateConfigRequest, Vault};
 use crate::config::{merge_vault_and_env, redact_secrets};
 use crate::extract::protobuf::Protobuf;
 
 pub async fn update_config_handler(
   State(state): State<AppState>,
+  user: User,
   Protobuf(request): Protobuf<UpdateConfigRequest>,
 ) -> Result<impl IntoResponse, Error> {
   if state.demo_mode() {
@@ -27,7 +29,9 @@ pub async fn update_config_handler(
 
   let merged = merge_vault_and_env(config, Vault { secrets })?;
 
-  state.validate_and_update_config(merged, Some(hash)).await?;
+  state
+    .validate_and_update_config_as(merged, Some(hash), Some(user.uuid))
+    .await?;
 
   return Ok((StatusCode::OK, "Config updated"));
 }
@@ -0,0 +1,234 @@
+use axum::Json;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::backup::{RestorePoint, list_backups, restore_backup, restore_main};
+
+/// Every SQLite database file starts with this header.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct RestoreDatabaseResponse {
+  /// Snapshot of the database taken right before the restore, relative to the backup directory.
+  pub pre_restore_backup: String,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListBackupsResponse {
+  /// Available backups, newest first.
+  pub backups: Vec<RestorePoint>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct RestoreBackupRequest {
+  /// Name of the backup to restore, e.g. "main-20250101T000000Z.db".
+  pub name: String,
+}
+
+/// Downloads a consistent snapshot of the main database.
+pub async fn export_database_handler(State(state): State<AppState>) -> Result<Response, Error> {
+  let path = temp_path(&state, "export").await?;
+  let result = state.conn().backup(&path).await;
+  let contents = match result {
+    Ok(()) => tokio::fs::read(&path).await,
+    Err(err) => {
+      remove_file(&path).await;
+      return Err(err.into());
+    }
+  };
+  remove_file(&path).await;
+
+  let contents = contents.map_err(|err| Error::Internal(err.into()))?;
+  let filename = format!("main-{}.db", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"));
+
+  return Ok(
+    (
+      [
+        (header::CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+        (
+          header::CONTENT_DISPOSITION,
+          format!("attachment; filename=\"{filename}\""),
+        ),
+      ],
+      contents,
+    )
+      .into_response(),
+  );
+}
+
+/// Replaces the main database with an uploaded snapshot.
+///
+/// The current database is backed up first. Afterwards migrations are applied and the schema
+/// metadata as well as config-dependent state, e.g. record APIs, are rebuilt.
+///
+/// NOTE: Uploads are subject to the server's request size limit.
+pub async fn restore_database_handler(
+  State(state): State<AppState>,
+  body: Bytes,
+) -> Result<Json<RestoreDatabaseResponse>, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  if !body.starts_with(SQLITE_HEADER) {
+    return Err(Error::BadRequest("Not a SQLite database".into()));
+  }
+
+  let upload = temp_path(&state, "restore").await?;
+  let result = restore(&state, &upload, &body).await;
+  remove_file(&upload).await;
+
+  return Ok(Json(result?));
+}
+
+async fn restore(
+  state: &AppState,
+  upload: &Path,
+  contents: &[u8],
+) -> Result<RestoreDatabaseResponse, Error> {
+  tokio::fs::write(upload, contents)
+    .await
+    .map_err(|err| Error::Internal(err.into()))?;
+
+  check_integrity(upload.to_path_buf()).await?;
+
+  return Ok(RestoreDatabaseResponse {
+    pre_restore_backup: restore_main(state, upload).await?,
+  });
+}
+
+/// Lists backups taken by the backup job, both local and uploaded to the object store.
+pub async fn list_backups_handler(
+  State(state): State<AppState>,
+) -> Result<Json<ListBackupsResponse>, Error> {
+  return Ok(Json(ListBackupsResponse {
+    backups: list_backups(&state).await?,
+  }));
+}
+
+/// Restores a backup taken by the backup job.
+pub async fn restore_backup_handler(
+  State(state): State<AppState>,
+  Json(request): Json<RestoreBackupRequest>,
+) -> Result<Json<RestoreDatabaseResponse>, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  return Ok(Json(RestoreDatabaseResponse {
+    pre_restore_backup: restore_backup(&state, &request.name).await?,
+  }));
+}
+
+/// Rejects corrupted uploads before they replace the live database.
+async fn check_integrity(path: PathBuf) -> Result<(), Error> {
+  return tokio::task::spawn_blocking(move || -> Result<(), Error> {
+    let conn =
+      rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let result: String = conn.query_row("PRAGMA quick_check", (), |row| row.get(0))?;
+    if result != "ok" {
+      return Err(Error::BadRequest(
+        format!("Integrity check failed: {result}").into(),
+      ));
+    }
+    return Ok(());
+  })
+  .await
+  .map_err(|err| Error::Internal(err.into()))?;
+}
+
+pub(super) async fn temp_path(state: &AppState, prefix: &str) -> Result<PathBuf, Error> {
+  let dir = state.data_dir().backup_path();
+  tokio::fs::create_dir_all(&dir)
+    .await
+    .map_err(|err| Error::Internal(err.into()))?;
+  return Ok(dir.join(format!(".{prefix}-{}.db", uuid::Uuid::now_v7())));
+}
+
+pub(super) async fn remove_file(path: &Path) {
+  if let Err(err) = tokio::fs::remove_file(path).await {
+    warn!("Failed to remove temporary file {path:?}: {err}");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use http_body_util::BodyExt;
+
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[tokio::test]
+  async fn test_export_and_restore_database() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE snapshot (id INTEGER PRIMARY KEY, value TEXT) STRICT; \
+          INSERT INTO snapshot (value) VALUES ('a'), ('b'); \
+        ",
+      )
+      .await
+      .unwrap();
+
+    let response = export_database_handler(State(state.clone())).await.unwrap();
+    let snapshot = response.into_body().collect().await.unwrap().to_bytes();
+    assert!(snapshot.starts_with(SQLITE_HEADER));
+
+    state
+      .conn()
+      .execute_batch("DROP TABLE snapshot; CREATE TABLE after_export (id INTEGER PRIMARY KEY)")
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    assert!(
+      restore_database_handler(State(state.clone()), Bytes::from_static(b"garbage"))
+        .await
+        .is_err()
+    );
+
+    let Json(response) = restore_database_handler(State(state.clone()), snapshot)
+      .await
+      .unwrap();
+    assert!(
+      state
+        .data_dir()
+        .backup_path()
+        .join(&response.pre_restore_backup)
+        .exists()
+    );
+
+    let count: i64 = state
+      .conn()
+      .read_query_row_get("SELECT COUNT(*) FROM snapshot", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(count, 2);
+    let dropped: Result<Option<i64>, _> = state
+      .conn()
+      .read_query_row_get("SELECT COUNT(*) FROM after_export", (), 0)
+      .await;
+    assert!(dropped.is_err());
+
+    let metadata = state.connection_manager().main_entry().metadata;
+    assert!(
+      metadata
+        .get_table(&trailbase_schema::QualifiedName::parse("snapshot").unwrap())
+        .is_some()
+    );
+  }
+}
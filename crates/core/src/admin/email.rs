@@ -1,10 +1,16 @@
-use axum::{Json, extract::State};
+use axum::{
+  Json,
+  extract::{Query, State},
+};
+use const_format::formatcp;
 use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
 use ts_rs::TS;
 
 use crate::admin::AdminError as Error;
 use crate::app_state::AppState;
 use crate::auth::util::validate_and_normalize_email_address;
+use crate::constants::{EMAIL_LOG_TABLE, EMAIL_QUEUE_TABLE};
 use crate::email::Email;
 
 /// Request the delivery of a test email.
@@ -31,7 +37,299 @@ pub async fn test_email_handler(
     "This is a test. Do not reply".to_string(),
   )?;
 
-  email.send().await?;
+  email.send_now().await?;
 
   return Ok(());
 }
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct EmailLogEntryJson {
+  pub id: i64,
+  /// Seconds since epoch with fractional millisecond resolution.
+  pub created: f64,
+  pub provider: String,
+  pub sender: String,
+  pub recipient: String,
+  pub subject: String,
+  /// Either "sent" or "failed".
+  pub status: String,
+  pub attempts: i64,
+  /// Error of the last delivery attempt, if failed.
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListEmailLogResponse {
+  entries: Vec<EmailLogEntryJson>,
+  /// Cursor for fetching the next page of older entries, if any.
+  cursor: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListEmailLogQuery {
+  /// Only list entries with the given status, i.e. "sent" or "failed".
+  status: Option<String>,
+  recipient: Option<String>,
+  /// Only list entries older than the given id.
+  cursor: Option<i64>,
+  limit: Option<usize>,
+}
+
+/// Lists the outcome of sent emails, most recent first.
+pub async fn list_email_log_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListEmailLogQuery>,
+) -> Result<Json<ListEmailLogResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, created, provider, sender, recipient, subject, status, attempts, error \
+      FROM '{EMAIL_LOG_TABLE}' \
+      WHERE \
+        ($1 IS NULL OR status = $1) AND \
+        ($2 IS NULL OR recipient = $2) AND \
+        ($3 IS NULL OR id < $3) \
+      ORDER BY id DESC LIMIT $4 \
+    "
+  );
+
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  let entries = state
+    .logs_conn()
+    .read_query_values::<EmailLogEntryJson>(
+      QUERY,
+      params!(query.status, query.recipient, query.cursor, limit as i64),
+    )
+    .await?;
+
+  let cursor = if entries.len() == limit {
+    entries.last().map(|e| e.id)
+  } else {
+    None
+  };
+
+  return Ok(Json(ListEmailLogResponse { entries, cursor }));
+}
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct QueuedEmailJson {
+  pub id: i64,
+  /// One of "queued", "failed", i.e. ran out of attempts, or "bounced", i.e. rejected
+  /// permanently.
+  pub status: String,
+  pub attempts: i64,
+  pub next_attempt: i64,
+  pub last_error: Option<String>,
+  pub sender: String,
+  pub recipient: String,
+  pub subject: String,
+  pub created: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListEmailQueueResponse {
+  emails: Vec<QueuedEmailJson>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListEmailQueueQuery {
+  /// Only list emails with the given status.
+  status: Option<String>,
+  limit: Option<usize>,
+}
+
+/// Lists queued and undeliverable emails. Delivered emails are removed from the queue.
+///
+/// NOTE: Like for the email log, bodies are deliberately not exposed since they may contain
+/// secrets, e.g. verification tokens.
+pub async fn list_email_queue_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListEmailQueueQuery>,
+) -> Result<Json<ListEmailQueueResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, status, attempts, next_attempt, last_error, sender, recipient, subject, created \
+      FROM '{EMAIL_QUEUE_TABLE}' \
+      WHERE $1 IS NULL OR status = $1 \
+      ORDER BY id DESC LIMIT $2 \
+    "
+  );
+
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  return Ok(Json(ListEmailQueueResponse {
+    emails: state
+      .conn()
+      .read_query_values::<QueuedEmailJson>(QUERY, params!(query.status, limit as i64))
+      .await?,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct EmailIdRequest {
+  pub id: i64,
+}
+
+/// Resets the given email, e.g. a bounced one, for immediate delivery.
+pub async fn requeue_email_handler(
+  State(state): State<AppState>,
+  Json(request): Json<EmailIdRequest>,
+) -> Result<(), Error> {
+  if !state.email_queue().requeue(request.id).await? {
+    return Err(Error::BadRequest("unknown email".into()));
+  }
+  return Ok(());
+}
+
+pub async fn delete_queued_email_handler(
+  State(state): State<AppState>,
+  Json(request): Json<EmailIdRequest>,
+) -> Result<(), Error> {
+  const QUERY: &str = formatcp!("DELETE FROM '{EMAIL_QUEUE_TABLE}' WHERE id = $1");
+
+  if state.conn().execute(QUERY, params!(request.id)).await? == 0 {
+    return Err(Error::BadRequest("unknown email".into()));
+  }
+  return Ok(());
+}
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_list_email_log() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+
+    for (recipient, status) in [
+      ("a@test.org", "sent"),
+      ("b@test.org", "failed"),
+      ("a@test.org", "sent"),
+    ] {
+      state
+        .logs_conn()
+        .execute(
+          format!(
+            "INSERT INTO '{EMAIL_LOG_TABLE}' (provider, sender, recipient, subject, status, attempts) VALUES ('smtp', 'noreply@test.org', $1, 'subject', $2, 1)"
+          ),
+          params!(recipient, status),
+        )
+        .await
+        .unwrap();
+    }
+
+    let list = async |query: ListEmailLogQuery| {
+      return list_email_log_handler(State(state.clone()), Query(query))
+        .await
+        .unwrap()
+        .0;
+    };
+
+    let response = list(ListEmailLogQuery {
+      limit: Some(2),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(
+      response
+        .entries
+        .iter()
+        .map(|e| e.recipient.as_str())
+        .collect::<Vec<_>>(),
+      ["a@test.org", "b@test.org"]
+    );
+
+    let next = list(ListEmailLogQuery {
+      cursor: response.cursor,
+      limit: Some(2),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(next.entries.len(), 1);
+    assert_eq!(next.cursor, None);
+
+    let failed = list(ListEmailLogQuery {
+      status: Some("failed".to_string()),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(failed.entries.len(), 1);
+    assert_eq!(failed.entries[0].recipient, "b@test.org");
+
+    let by_recipient = list(ListEmailLogQuery {
+      recipient: Some("a@test.org".to_string()),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(by_recipient.entries.len(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_email_queue() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+
+    for (recipient, status) in [("a@test.org", "queued"), ("b@test.org", "bounced")] {
+      state
+        .conn()
+        .execute(
+          format!(
+            "INSERT INTO '{EMAIL_QUEUE_TABLE}' (sender, recipient, subject, html, status, attempts) VALUES ('noreply@test.org', $1, 'subject', 'body', $2, 1)"
+          ),
+          params!(recipient, status),
+        )
+        .await
+        .unwrap();
+    }
+
+    let list = async |status: Option<&str>| {
+      return list_email_queue_handler(
+        State(state.clone()),
+        Query(ListEmailQueueQuery {
+          status: status.map(|s| s.to_string()),
+          ..Default::default()
+        }),
+      )
+      .await
+      .unwrap()
+      .0
+      .emails;
+    };
+
+    assert_eq!(list(None).await.len(), 2);
+    let bounced = list(Some("bounced")).await;
+    assert_eq!(bounced.len(), 1);
+    assert_eq!(bounced[0].recipient, "b@test.org");
+
+    requeue_email_handler(
+      State(state.clone()),
+      Json(EmailIdRequest { id: bounced[0].id }),
+    )
+    .await
+    .unwrap();
+    assert!(list(Some("bounced")).await.is_empty());
+    let queued = list(Some("queued")).await;
+    assert_eq!(queued.len(), 2);
+    assert_eq!(queued[0].recipient, "b@test.org");
+    assert_eq!(queued[0].attempts, 0);
+
+    assert!(
+      requeue_email_handler(State(state.clone()), Json(EmailIdRequest { id: 1000 }))
+        .await
+        .is_err()
+    );
+
+    delete_queued_email_handler(
+      State(state.clone()),
+      Json(EmailIdRequest { id: bounced[0].id }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(list(None).await.len(), 1);
+  }
+}
@@ -0,0 +1,332 @@
+use axum::{
+  Json,
+  extract::State,
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use const_format::formatcp;
+use minijinja::Environment;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::audit::{AuditAction, record_audit_event};
+use crate::auth::User;
+use crate::config::proto::EmailTemplate;
+use crate::config::validate_email_template;
+use crate::constants::EMAIL_TEMPLATES_TABLE;
+use crate::email::{
+  EmailContent, EmailTemplateName, StoredEmailTemplate, get_site_url, load_stored_template,
+  resolve_template,
+};
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct EmailTemplateJson {
+  pub name: EmailTemplateName,
+  /// Variables available to the template, e.g. "APP_NAME".
+  pub variables: Vec<String>,
+  /// Effective template, i.e. the stored override falling back to the config and defaults.
+  pub template: EmailContent,
+  /// Whether the template has been overridden via the admin API.
+  pub customized: bool,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListEmailTemplatesResponse {
+  templates: Vec<EmailTemplateJson>,
+}
+
+pub async fn list_email_templates_handler(
+  State(state): State<AppState>,
+) -> Result<Json<ListEmailTemplatesResponse>, Error> {
+  let config = state.get_config();
+
+  let mut templates = Vec::with_capacity(EmailTemplateName::ALL.len());
+  for name in EmailTemplateName::ALL {
+    let stored = load_stored_template(state.user_conn(), name).await?;
+    templates.push(EmailTemplateJson {
+      name,
+      variables: name.variables().iter().map(|v| v.to_string()).collect(),
+      customized: stored.is_some(),
+      template: resolve_template(name, stored, &config.email),
+    });
+  }
+
+  return Ok(Json(ListEmailTemplatesResponse { templates }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct UpdateEmailTemplateRequest {
+  pub name: EmailTemplateName,
+  /// Unset fields fall back to the config and then the built-in defaults.
+  pub subject: Option<String>,
+  pub html: Option<String>,
+  pub text: Option<String>,
+}
+
+/// Creates or replaces the override of an email template.
+pub async fn update_email_template_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<UpdateEmailTemplateRequest>,
+) -> Result<Response, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  for template in [&request.subject, &request.html, &request.text]
+    .into_iter()
+    .flatten()
+  {
+    if let Err(err) = Environment::empty().template_from_str(template) {
+      return Err(Error::BadRequest(format!("Invalid template: {err}").into()));
+    }
+  }
+  validate_email_template(
+    Some(&EmailTemplate {
+      subject: None,
+      body: request.html.clone(),
+    }),
+    request.name.required_variables(),
+  )
+  .map_err(|err| Error::BadRequest(err.into()))?;
+
+  const QUERY: &str = formatcp!(
+    "\
+      INSERT INTO '{EMAIL_TEMPLATES_TABLE}' (name, subject, html, text) VALUES ($1, $2, $3, $4) \
+      ON CONFLICT (name) DO UPDATE SET \
+        subject = excluded.subject, html = excluded.html, text = excluded.text, \
+        updated = UNIXEPOCH() \
+    "
+  );
+  state
+    .user_conn()
+    .execute(
+      QUERY,
+      params!(
+        request.name.name().to_string(),
+        request.subject,
+        request.html,
+        request.text,
+      ),
+    )
+    .await?;
+
+  record_audit_event(
+    &state,
+    Some(user.uuid),
+    AuditAction::EmailTemplateChanged,
+    request.name.name(),
+    None,
+  )
+  .await;
+
+  return Ok((StatusCode::OK, "updated").into_response());
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct DeleteEmailTemplateRequest {
+  pub name: EmailTemplateName,
+}
+
+/// Removes the override of an email template, i.e. reverts to the config or built-in default.
+pub async fn delete_email_template_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<DeleteEmailTemplateRequest>,
+) -> Result<Response, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  const QUERY: &str = formatcp!("DELETE FROM '{EMAIL_TEMPLATES_TABLE}' WHERE name = $1");
+  state
+    .user_conn()
+    .execute(QUERY, params!(request.name.name().to_string()))
+    .await?;
+
+  record_audit_event(
+    &state,
+    Some(user.uuid),
+    AuditAction::EmailTemplateChanged,
+    request.name.name(),
+    Some(serde_json::json!({ "reset": true })),
+  )
+  .await;
+
+  return Ok((StatusCode::OK, "deleted").into_response());
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct PreviewEmailTemplateRequest {
+  pub name: EmailTemplateName,
+  /// Unsaved edits to preview. Unset fields fall back to the effective template.
+  pub subject: Option<String>,
+  pub html: Option<String>,
+  pub text: Option<String>,
+}
+
+/// Renders an email template with sample data.
+pub async fn preview_email_template_handler(
+  State(state): State<AppState>,
+  Json(request): Json<PreviewEmailTemplateRequest>,
+) -> Result<Json<EmailContent>, Error> {
+  let config = state.get_config();
+  let name = request.name;
+
+  let stored = load_stored_template(state.user_conn(), name)
+    .await?
+    .unwrap_or_default();
+  let template = resolve_template(
+    name,
+    Some(StoredEmailTemplate {
+      subject: request.subject.or(stored.subject),
+      html: request.html.or(stored.html),
+      text: request.text.or(stored.text),
+    }),
+    &config.email,
+  );
+
+  let rendered = template
+    .render(name.sample_context(
+      config.server.application_name.as_deref(),
+      &get_site_url(&state),
+    ))
+    .map_err(|err| Error::BadRequest(err.into()))?;
+
+  return Ok(Json(rendered));
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::admin::user::create_user_for_test;
+  use crate::app_state::{TestStateOptions, test_state};
+  use crate::auth::util::{UserIdentifier, login_with_password_for_test};
+  use crate::email::{Email, SmtpMailer, testing::TestAsyncSmtpTransport};
+
+  #[tokio::test]
+  async fn test_email_templates() {
+    let mailer = TestAsyncSmtpTransport::new();
+    let state = test_state(Some(TestStateOptions {
+      mailer: Some(Arc::new(SmtpMailer::new(mailer.clone()))),
+      ..Default::default()
+    }))
+    .await
+    .unwrap();
+    let email = "admin@test.org";
+    let password = "Secret!1!!";
+    create_user_for_test(&state, email, password).await.unwrap();
+    let tokens =
+      login_with_password_for_test(&state, UserIdentifier::Email(email.to_string()), password)
+        .await
+        .unwrap()
+        .unwrap();
+    let user = User::from_auth_token(&state, &tokens.auth_token).unwrap();
+
+    let otp = |templates: &ListEmailTemplatesResponse| -> (EmailContent, bool) {
+      let t = templates
+        .templates
+        .iter()
+        .find(|t| t.name == EmailTemplateName::Otp)
+        .unwrap();
+      return (t.template.clone(), t.customized);
+    };
+
+    let defaults = list_email_templates_handler(State(state.clone()))
+      .await
+      .unwrap()
+      .0;
+    assert_eq!(defaults.templates.len(), EmailTemplateName::ALL.len());
+    let (default_otp, customized) = otp(&defaults);
+    assert!(!customized);
+
+    // Templates must reference the required variables.
+    let response = update_email_template_handler(
+      State(state.clone()),
+      user.clone(),
+      Json(UpdateEmailTemplateRequest {
+        name: EmailTemplateName::Otp,
+        subject: None,
+        html: Some("No code".to_string()),
+        text: None,
+      }),
+    )
+    .await;
+    assert!(matches!(response, Err(Error::BadRequest(_))));
+
+    update_email_template_handler(
+      State(state.clone()),
+      user.clone(),
+      Json(UpdateEmailTemplateRequest {
+        name: EmailTemplateName::Otp,
+        subject: Some("Your {{ APP_NAME }} code".to_string()),
+        html: Some("<b>{{ CODE }}</b>".to_string()),
+        text: Some("Code: {{ CODE }}".to_string()),
+      }),
+    )
+    .await
+    .unwrap();
+
+    let preview = preview_email_template_handler(
+      State(state.clone()),
+      Json(PreviewEmailTemplateRequest {
+        name: EmailTemplateName::Otp,
+        subject: None,
+        html: None,
+        text: None,
+      }),
+    )
+    .await
+    .unwrap()
+    .0;
+    assert_eq!(
+      preview,
+      EmailContent {
+        subject: "Your TrailBase code".to_string(),
+        html: "<b>12345678</b>".to_string(),
+        text: Some("Code: 12345678".to_string()),
+      }
+    );
+
+    // Stored templates are used for sending, including the plain-text alternative.
+    Email::otp_email(&state, "foo@bar.org", "87654321", None)
+      .await
+      .unwrap()
+      .send()
+      .await
+      .unwrap();
+    let logs = mailer.get_logs();
+    assert_eq!(logs.len(), 1);
+    let raw = &logs[0].1;
+    assert!(raw.contains("Subject: Your TrailBase code"), "{raw}");
+    assert!(raw.contains("<b>87654321</b>"), "{raw}");
+    assert!(raw.contains("Code: 87654321"), "{raw}");
+    assert!(raw.contains("multipart/alternative"), "{raw}");
+
+    delete_email_template_handler(
+      State(state.clone()),
+      user,
+      Json(DeleteEmailTemplateRequest {
+        name: EmailTemplateName::Otp,
+      }),
+    )
+    .await
+    .unwrap();
+
+    let reset = list_email_templates_handler(State(state.clone()))
+      .await
+      .unwrap()
+      .0;
+    assert_eq!(otp(&reset), (default_otp, false));
+  }
+}
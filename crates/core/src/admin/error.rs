@@ -3,6 +3,8 @@ use axum::http::{StatusCode, header::CONTENT_TYPE};
 use axum::response::{IntoResponse, Response};
 use thiserror::Error;
 
+use crate::logging::with_error_fields;
+
 // FIXME: Admin APIs also deserve more explicit error handling eventually.
 #[derive(Debug, Error)]
 pub enum AdminError {
@@ -58,6 +60,12 @@ pub enum AdminError {
   File(#[from] crate::records::files::FileError),
   #[error("SqlValueDecode: {0}")]
   SqlValueDecode(#[from] trailbase_sqlvalue::DecodeError),
+  #[error("Backup: {0}")]
+  Backup(#[from] crate::backup::BackupError),
+  #[error("Tenant: {0}")]
+  Tenant(#[from] crate::tenants::TenantError),
+  #[error("Seed: {0}")]
+  Seed(#[from] crate::seeds::SeedError),
 }
 
 impl IntoResponse for AdminError {
@@ -72,15 +80,36 @@ impl IntoResponse for AdminError {
       Self::BadRequest(err) => (StatusCode::BAD_REQUEST, err.to_string()),
       Self::Internal(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
       Self::AlreadyExists(_) => (StatusCode::CONFLICT, self.to_string()),
+      Self::Backup(crate::backup::BackupError::NotFound(_)) => {
+        (StatusCode::NOT_FOUND, self.to_string())
+      }
+      Self::Tenant(crate::tenants::TenantError::NotFound) => {
+        (StatusCode::NOT_FOUND, self.to_string())
+      }
+      Self::Tenant(crate::tenants::TenantError::AlreadyExists) => {
+        (StatusCode::CONFLICT, self.to_string())
+      }
+      Self::Tenant(crate::tenants::TenantError::InvalidName(_)) => {
+        (StatusCode::BAD_REQUEST, self.to_string())
+      }
+      Self::Transaction(crate::transaction_recorder::TransactionError::Rollback(_)) => {
+        (StatusCode::PRECONDITION_FAILED, self.to_string())
+      }
       // NOTE: We can almost always leak the internal error (except for permission errors) since
       // these are errors for the admin apis.
       err => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
     };
 
-    return Response::builder()
+    let error = status.is_server_error().then(|| msg.clone());
+    let response = Response::builder()
       .status(status)
       .header(CONTENT_TYPE, "text/plain")
       .body(Body::new(msg))
       .unwrap_or_default();
+
+    return match error {
+      Some(err) => with_error_fields(response, err),
+      None => response,
+    };
   }
 }
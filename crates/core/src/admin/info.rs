@@ -22,6 +22,9 @@ pub struct InfoResponse {
   start_time: u64,
   /// Experimental Postgres mode
   postgres: bool,
+  /// Prepared statement cache lookups across all connections since start.
+  statement_cache_hits: u64,
+  statement_cache_misses: u64,
 }
 
 pub async fn info_handler(State(state): State<AppState>) -> Result<Json<InfoResponse>, Error> {
@@ -30,6 +33,7 @@ pub async fn info_handler(State(state): State<AppState>) -> Result<Json<InfoResp
 
 fn build_info_response(state: &AppState) -> InfoResponse {
   let version_info = state.version();
+  let statement_cache = trailbase_sqlite::statement_cache_stats();
   let git_version = version_info
     .git_version()
     .map(|v| (v.tag(), v.commits_since.unwrap_or(0) as usize));
@@ -54,5 +58,7 @@ fn build_info_response(state: &AppState) -> InfoResponse {
         .connection_type(),
       ConnectionType::Pg
     ),
+    statement_cache_hits: statement_cache.hits,
+    statement_cache_misses: statement_cache.misses,
   };
 }
@@ -0,0 +1,66 @@
+use axum::{Json, extract::State};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use ts_rs::TS;
+
+use crate::AppState;
+use crate::admin::AdminError as Error;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct CreateJobRequest {
+  pub name: String,
+  /// 6/7-component cron spec, e.g. "0 0 * * * *".
+  pub schedule: String,
+  /// SQL executed against the main database on every run.
+  pub query: String,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct CreateJobResponse {
+  pub id: i32,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct DeleteJobRequest {
+  pub id: i32,
+}
+
+/// Registers a user-defined job, which is persisted and survives restarts.
+pub async fn create_job_handler(
+  State(state): State<AppState>,
+  Json(request): Json<CreateJobRequest>,
+) -> Result<Json<CreateJobResponse>, Error> {
+  if request.name.trim().is_empty() {
+    return Err(Error::BadRequest("Missing job name".into()));
+  }
+  if request.query.trim().is_empty() {
+    return Err(Error::BadRequest("Missing job query".into()));
+  }
+  let schedule =
+    Schedule::from_str(&request.schedule).map_err(|err| Error::BadRequest(err.into()))?;
+
+  let Some(job) = state
+    .jobs()
+    .create_query_job(request.name, schedule, request.query)
+    .await?
+  else {
+    return Err(Error::AlreadyExists("job"));
+  };
+
+  return Ok(Json(CreateJobResponse { id: job.id }));
+}
+
+/// Deletes a user-defined job. System and WASM jobs cannot be deleted.
+pub async fn delete_job_handler(
+  State(state): State<AppState>,
+  Json(request): Json<DeleteJobRequest>,
+) -> Result<(), Error> {
+  if state.jobs().delete_query_job(request.id).await?.is_none() {
+    return Err(Error::Precondition("User-defined job not found".into()));
+  }
+  return Ok(());
+}
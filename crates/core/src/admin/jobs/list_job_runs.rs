@@ -0,0 +1,185 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::AppState;
+use crate::admin::AdminError as Error;
+use crate::constants::JOB_RUNS_TABLE;
+
+#[derive(Debug, Serialize, TS)]
+pub struct JobRun {
+  pub id: i64,
+  pub job: String,
+  /// Start timestamp in seconds since epoch.
+  pub started: i64,
+  pub duration_ms: i64,
+  pub success: bool,
+  /// Error output of failed runs.
+  pub output: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct ListJobRunsQuery {
+  /// Only list runs of the job with the given name.
+  pub job: Option<String>,
+  pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListJobRunsResponse {
+  /// Most recent runs first.
+  pub runs: Vec<JobRun>,
+}
+
+pub async fn list_job_runs_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListJobRunsQuery>,
+) -> Result<Json<ListJobRunsResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, job, started, duration_ms, success, output FROM {JOB_RUNS_TABLE} \
+      WHERE $1 IS NULL OR job = $1 \
+      ORDER BY id DESC LIMIT $2 \
+    "
+  );
+
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+  let rows = state
+    .conn()
+    .read_query_rows(QUERY, trailbase_sqlite::params!(query.job, limit as i64))
+    .await?;
+
+  let runs = rows
+    .iter()
+    .map(|row| {
+      return Ok(JobRun {
+        id: row.get(0)?,
+        job: row.get(1)?,
+        started: row.get(2)?,
+        duration_ms: row.get(3)?,
+        success: row.get(4)?,
+        output: row.get(5)?,
+      });
+    })
+    .collect::<Result<Vec<_>, Error>>()?;
+
+  return Ok(Json(ListJobRunsResponse { runs }));
+}
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::admin::jobs::create_job::{
+    CreateJobRequest, DeleteJobRequest, create_job_handler, delete_job_handler,
+  };
+  use crate::admin::jobs::pause_job::{PauseJobRequest, pause_job_handler, resume_job_handler};
+
+  #[tokio::test]
+  async fn test_job_management() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch("CREATE TABLE counter (id INTEGER PRIMARY KEY) STRICT")
+      .await
+      .unwrap();
+
+    let Json(created) = create_job_handler(
+      State(state.clone()),
+      Json(CreateJobRequest {
+        name: "count".to_string(),
+        schedule: "@yearly".to_string(),
+        query: "INSERT INTO counter DEFAULT VALUES".to_string(),
+      }),
+    )
+    .await
+    .unwrap();
+
+    // Names are unique.
+    assert!(
+      create_job_handler(
+        State(state.clone()),
+        Json(CreateJobRequest {
+          name: "count".to_string(),
+          schedule: "@daily".to_string(),
+          query: "SELECT 1".to_string(),
+        }),
+      )
+      .await
+      .is_err()
+    );
+
+    let job = state.jobs().get_job(created.id).unwrap();
+    assert!(job.running());
+
+    pause_job_handler(
+      State(state.clone()),
+      Json(PauseJobRequest { id: created.id }),
+    )
+    .await
+    .unwrap();
+    assert!(!job.running());
+
+    // Paused state and user-defined jobs are restored, e.g. after a config change.
+    let registry = std::sync::Arc::new(crate::scheduler::JobRegistry::with_history(
+      state.conn().clone(),
+    ));
+    crate::scheduler::restore_persisted_jobs(registry.clone())
+      .await
+      .unwrap();
+    let restored = registry.find_job("count").unwrap();
+    assert!(!restored.running());
+    assert_eq!(
+      restored.query().as_deref(),
+      Some("INSERT INTO counter DEFAULT VALUES")
+    );
+    drop(registry);
+
+    resume_job_handler(
+      State(state.clone()),
+      Json(PauseJobRequest { id: created.id }),
+    )
+    .await
+    .unwrap();
+    assert!(job.running());
+
+    // Manual trigger is recorded in the run history.
+    state.jobs().run_job(created.id).await.unwrap().unwrap();
+
+    let Json(response) = list_job_runs_handler(
+      State(state.clone()),
+      Query(ListJobRunsQuery {
+        job: Some("count".to_string()),
+        limit: None,
+      }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.runs.len(), 1);
+    assert!(response.runs[0].success);
+
+    let count: i64 = state
+      .conn()
+      .read_query_row_get("SELECT COUNT(*) FROM counter", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(count, 1);
+
+    delete_job_handler(
+      State(state.clone()),
+      Json(DeleteJobRequest { id: created.id }),
+    )
+    .await
+    .unwrap();
+    assert!(state.jobs().get_job(created.id).is_none());
+  }
+}
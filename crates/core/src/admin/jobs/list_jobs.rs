@@ -16,6 +16,8 @@ pub struct Job {
   /// Optional metadata from latest run: start timestamp in seconds since epoch, duration in
   /// milliseconds and error output.
   pub latest: Option<(i64, i64, Option<String>)>,
+  /// SQL executed by user-defined jobs. None for system and WASM jobs.
+  pub query: Option<String>,
 }
 
 #[derive(Debug, Serialize, TS)]
@@ -46,6 +48,7 @@ pub async fn list_jobs_handler(
         enabled,
         next: job.next_run().map(|t| t.timestamp()),
         latest,
+        query: job.query(),
       };
     })
     .collect();
@@ -1,5 +1,11 @@
+mod create_job;
+mod list_job_runs;
 mod list_jobs;
+mod pause_job;
 mod run_job;
 
+pub use create_job::{create_job_handler, delete_job_handler};
+pub use list_job_runs::list_job_runs_handler;
 pub use list_jobs::list_jobs_handler;
+pub use pause_job::{pause_job_handler, resume_job_handler};
 pub use run_job::run_job_handler;
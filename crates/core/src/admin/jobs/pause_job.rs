@@ -0,0 +1,37 @@
+use axum::{Json, extract::State};
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::AppState;
+use crate::admin::AdminError as Error;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct PauseJobRequest {
+  pub id: i32,
+}
+
+/// Pauses a job until resumed. The state is persisted across restarts and config changes.
+pub async fn pause_job_handler(
+  State(state): State<AppState>,
+  Json(request): Json<PauseJobRequest>,
+) -> Result<(), Error> {
+  return set_paused(&state, request.id, true).await;
+}
+
+pub async fn resume_job_handler(
+  State(state): State<AppState>,
+  Json(request): Json<PauseJobRequest>,
+) -> Result<(), Error> {
+  return set_paused(&state, request.id, false).await;
+}
+
+async fn set_paused(state: &AppState, id: i32, paused: bool) -> Result<(), Error> {
+  let jobs = state.jobs();
+  let Some(job) = jobs.get_job(id) else {
+    return Err(Error::Precondition("Job not found".into()));
+  };
+
+  jobs.set_paused(&job, paused).await?;
+  return Ok(());
+}
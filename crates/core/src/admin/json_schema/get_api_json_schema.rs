@@ -18,7 +18,7 @@ pub async fn get_api_json_schema_handler(
   Path(record_api_name): Path<String>,
   Query(query): Query<GetTableSchemaParams>,
 ) -> Result<Response, Error> {
-  let Some(api) = state.lookup_record_api(&record_api_name) else {
+  let Some(api) = state.lookup_base_record_api(&record_api_name) else {
     return Err(Error::Precondition(format!(
       "API {record_api_name} not found"
     )));
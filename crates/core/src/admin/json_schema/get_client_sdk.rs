@@ -0,0 +1,454 @@
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use std::io::Write;
+use trailbase_schema::codegen::{Language, SWIFT_JSON_VALUE, json_schema_to_model};
+use trailbase_schema::json_schema::JsonSchemaMode;
+use trailbase_schema::typescript::{json_schema_to_typescript, to_type_name};
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::records::RecordApi;
+use crate::records::json_schema::build_api_json_schema;
+
+const HEADER: &str = "Generated by TrailBase from the Record API schemas. Do not edit manually.";
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientLanguage {
+  TypeScript,
+  Dart,
+  Kotlin,
+  Swift,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetClientSdkParams {
+  lang: ClientLanguage,
+  /// Package declaration for Kotlin sources.
+  package: Option<String>,
+}
+
+/// Serves a zip archive with typed models and thin wrappers around the respective client's
+/// `RecordApi` for every configured Record API. For every API `foo` there's a `Foo` model for
+/// reads, `FooInsert` and `FooUpdate` models for writes of TABLE-based APIs, and a `FooApi`
+/// wrapper.
+pub async fn get_client_sdk_handler(
+  State(state): State<AppState>,
+  Query(query): Query<GetClientSdkParams>,
+) -> Result<Response, Error> {
+  let mut api_names: Vec<String> = state
+    .get_config()
+    .record_apis
+    .iter()
+    .filter_map(|config| config.name.clone())
+    .collect();
+  api_names.sort();
+
+  let mut files: Vec<(String, String)> = vec![];
+  for api_name in api_names {
+    // NOTE: Tenant-scoped APIs are generated based on the tenant template's schema.
+    let Some(api) = state.lookup_base_record_api(&api_name) else {
+      continue;
+    };
+    files.push(build_api_source(&state, &api, &query)?);
+  }
+
+  if query.lang == ClientLanguage::Swift {
+    files.push(("JSONValue.swift".to_string(), SWIFT_JSON_VALUE.to_string()));
+  }
+
+  let archive = build_zip(files).map_err(|err| Error::Internal(err.into()))?;
+
+  let mut response = archive.into_response();
+  response.headers_mut().insert(
+    header::CONTENT_TYPE,
+    header::HeaderValue::from_static("application/zip"),
+  );
+  response.headers_mut().insert(
+    header::CONTENT_DISPOSITION,
+    header::HeaderValue::from_static(match query.lang {
+      ClientLanguage::TypeScript => "attachment; filename=\"trailbase_typescript.zip\"",
+      ClientLanguage::Dart => "attachment; filename=\"trailbase_dart.zip\"",
+      ClientLanguage::Kotlin => "attachment; filename=\"trailbase_kotlin.zip\"",
+      ClientLanguage::Swift => "attachment; filename=\"trailbase_swift.zip\"",
+    }),
+  );
+  return Ok(response);
+}
+
+/// Returns file name and contents of the models and wrapper for the given API.
+fn build_api_source(
+  state: &AppState,
+  api: &RecordApi,
+  query: &GetClientSdkParams,
+) -> Result<(String, String), Error> {
+  let name = api.api_name();
+  let type_name = to_type_name(name);
+
+  // VIEW-based APIs are read-only.
+  let mut modes = vec![(JsonSchemaMode::Select, type_name.clone())];
+  if api.is_table() {
+    modes.push((JsonSchemaMode::Insert, format!("{type_name}Insert")));
+    modes.push((JsonSchemaMode::Update, format!("{type_name}Update")));
+  }
+
+  let mut models: Vec<String> = vec![];
+  for (mode, model_name) in modes {
+    let schema =
+      build_api_json_schema(state, api, Some(mode)).map_err(|err| Error::Internal(err.into()))?;
+    models.push(match query.lang {
+      ClientLanguage::TypeScript => json_schema_to_typescript(&model_name, &schema),
+      ClientLanguage::Dart => json_schema_to_model(Language::Dart, &model_name, &schema),
+      ClientLanguage::Kotlin => json_schema_to_model(Language::Kotlin, &model_name, &schema),
+      ClientLanguage::Swift => json_schema_to_model(Language::Swift, &model_name, &schema),
+    });
+  }
+
+  // API names are restricted to identifier characters, thus safe to use in string literals.
+  let is_table = api.is_table();
+  let (file_name, prelude, wrapper) = match query.lang {
+    ClientLanguage::TypeScript => (
+      format!("{name}.ts"),
+      format!(
+        "// {HEADER}\n\n\
+          import type {{ Client, ListOpts, ListResponse, ReadOpts, RecordApi, RecordId }} \
+          from \"trailbase\";\n"
+      ),
+      typescript_wrapper(name, &type_name, is_table),
+    ),
+    ClientLanguage::Dart => (
+      format!("{name}.dart"),
+      format!("// {HEADER}\n\nimport 'package:trailbase/trailbase.dart';\n"),
+      dart_wrapper(name, &type_name, is_table),
+    ),
+    ClientLanguage::Kotlin => (
+      format!("{type_name}.kt"),
+      format!(
+        "// {HEADER}\n\n{}\
+          import io.trailbase.client.*\n\
+          import kotlinx.serialization.SerialName\n\
+          import kotlinx.serialization.Serializable\n\
+          import kotlinx.serialization.json.JsonElement\n",
+        query
+          .package
+          .as_ref()
+          .map_or_else(String::new, |package| format!("package {package}\n\n")),
+      ),
+      kotlin_wrapper(name, &type_name, is_table),
+    ),
+    ClientLanguage::Swift => (
+      format!("{type_name}.swift"),
+      format!("// {HEADER}\n\nimport Foundation\nimport TrailBase\n"),
+      swift_wrapper(name, &type_name, is_table),
+    ),
+  };
+
+  let mut out = prelude;
+  for model in models {
+    out.push('\n');
+    out.push_str(&model);
+  }
+  out.push('\n');
+  out.push_str(&wrapper);
+  return Ok((file_name, out));
+}
+
+fn typescript_wrapper(name: &str, t: &str, is_table: bool) -> String {
+  let mut out = format!(
+    r#"export class {t}Api {{
+  private readonly api: RecordApi;
+
+  constructor(client: Client) {{
+    this.api = client.records("{name}");
+  }}
+
+  list(opts?: ListOpts): Promise<ListResponse<{t}>> {{
+    return this.api.list(opts) as Promise<ListResponse<{t}>>;
+  }}
+
+  read(id: RecordId, opts?: ReadOpts): Promise<{t}> {{
+    return this.api.read(id, opts) as Promise<{t}>;
+  }}
+"#
+  );
+  if is_table {
+    out.push_str(&format!(
+      r#"
+  create(record: {t}Insert): Promise<RecordId> {{
+    return this.api.create(record);
+  }}
+
+  update(id: RecordId, record: {t}Update): Promise<void> {{
+    return this.api.update(id, record);
+  }}
+
+  delete(id: RecordId): Promise<void> {{
+    return this.api.delete(id);
+  }}
+"#
+    ));
+  }
+  out.push_str("}\n");
+  return out;
+}
+
+fn dart_wrapper(name: &str, t: &str, is_table: bool) -> String {
+  let mut out = format!(
+    r#"class {t}Api {{
+  final RecordApi _api;
+
+  {t}Api(Client client) : _api = client.records('{name}');
+
+  Future<({{List<{t}> records, String? cursor, int? totalCount}})> list({{
+    Pagination? pagination,
+    List<String>? order,
+    List<FilterBase>? filters,
+    bool? count,
+    List<String>? expand,
+  }}) async {{
+    final response = await _api.list(
+      pagination: pagination,
+      order: order,
+      filters: filters,
+      count: count,
+      expand: expand,
+    );
+    return (
+      records: response.records.map({t}.fromJson).toList(),
+      cursor: response.cursor,
+      totalCount: response.totalCount,
+    );
+  }}
+
+  Future<{t}> read(RecordId id, {{List<String>? expand}}) async =>
+      {t}.fromJson(await _api.read(id, expand: expand));
+"#
+  );
+  if is_table {
+    out.push_str(&format!(
+      r#"
+  Future<RecordId> create({t}Insert record) => _api.create(record.toJson());
+
+  Future<void> update(RecordId id, {t}Update record) =>
+      _api.update(id, record.toJson());
+
+  Future<void> delete(RecordId id) => _api.delete(id);
+"#
+    ));
+  }
+  out.push_str("}\n");
+  return out;
+}
+
+fn kotlin_wrapper(name: &str, t: &str, is_table: bool) -> String {
+  let mut out = format!(
+    r#"class {t}Api(client: Client) {{
+    private val api = client.records("{name}")
+
+    suspend fun list(
+        pagination: Pagination? = null,
+        order: List<String>? = null,
+        filters: List<FilterBase>? = null,
+        count: Boolean = false,
+        expand: List<String>? = null,
+    ): ListResponse<{t}> = api.list(pagination, order, filters, count, expand)
+
+    suspend fun read(id: RecordId, expand: List<String>? = null): {t} = api.read(id, expand)
+"#
+  );
+  if is_table {
+    out.push_str(&format!(
+      r#"
+    suspend fun create(record: {t}Insert): RecordId = api.create(record)
+
+    suspend fun update(id: RecordId, record: {t}Update) = api.update(id, record)
+
+    suspend fun delete(id: RecordId) = api.delete(id)
+"#
+    ));
+  }
+  out.push_str("}\n");
+  return out;
+}
+
+fn swift_wrapper(name: &str, t: &str, is_table: bool) -> String {
+  let mut out = format!(
+    r#"public struct {t}Api {{
+  private let api: RecordApi
+
+  public init(client: Client) {{
+    self.api = client.records("{name}")
+  }}
+
+  public func list(
+    pagination: Pagination? = nil,
+    order: [String]? = nil,
+    filters: [Filter]? = nil,
+    expand: [String]? = nil,
+    count: Bool = false
+  ) async throws -> ListResponse<{t}> {{
+    return try await api.list(
+      pagination: pagination, order: order, filters: filters, expand: expand, count: count)
+  }}
+
+  public func read(recordId: RecordId, expand: [String]? = nil) async throws -> {t} {{
+    return try await api.read(recordId: recordId, expand: expand)
+  }}
+"#
+  );
+  if is_table {
+    out.push_str(&format!(
+      r#"
+  public func create(record: {t}Insert) async throws -> RecordId {{
+    return try await api.create(record: record)
+  }}
+
+  public func update(recordId: RecordId, record: {t}Update) async throws {{
+    try await api.update(recordId: recordId, record: record)
+  }}
+
+  public func delete(recordId: RecordId) async throws {{
+    try await api.delete(recordId: recordId)
+  }}
+"#
+    ));
+  }
+  out.push_str("}\n");
+  return out;
+}
+
+fn build_zip(files: Vec<(String, String)>) -> Result<Vec<u8>, zip::result::ZipError> {
+  let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::<u8>::new()));
+  let options =
+    zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+  for (name, contents) in files {
+    writer.start_file(name, options)?;
+    writer.write_all(contents.as_bytes())?;
+  }
+  return Ok(writer.finish()?.into_inner());
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Read;
+
+  use super::*;
+  use crate::config::proto::RecordApiConfig;
+  use crate::records::test_utils::add_record_api_config;
+
+  async fn get_sdk(state: &AppState, lang: ClientLanguage) -> Vec<(String, String)> {
+    let response = get_client_sdk_handler(
+      State(state.clone()),
+      Query(GetClientSdkParams {
+        lang,
+        package: Some("com.example".to_string()),
+      }),
+    )
+    .await
+    .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+    let mut files = vec![];
+    for i in 0..archive.len() {
+      let mut file = archive.by_index(i).unwrap();
+      let mut contents = String::new();
+      file.read_to_string(&mut contents).unwrap();
+      files.push((file.name().to_string(), contents));
+    }
+    return files;
+  }
+
+  #[tokio::test]
+  async fn test_get_client_sdk() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE movie_list (id INTEGER PRIMARY KEY, name TEXT NOT NULL, rating REAL) STRICT; \
+          CREATE VIEW movie_names AS SELECT id, name FROM movie_list; \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    for (name, table) in [("movie_list", "movie_list"), ("movie_names", "movie_names")] {
+      add_record_api_config(
+        &state,
+        RecordApiConfig {
+          name: Some(name.to_string()),
+          table_name: Some(table.to_string()),
+          ..Default::default()
+        },
+      )
+      .await
+      .unwrap();
+    }
+
+    let files = get_sdk(&state, ClientLanguage::TypeScript).await;
+    assert_eq!(
+      files
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>(),
+      ["movie_list.ts", "movie_names.ts"]
+    );
+    let (_, movie_list) = &files[0];
+    assert!(
+      movie_list.contains("export type MovieListInsert = {\n  id?: number;\n  name: string;\n"),
+      "{movie_list}"
+    );
+    assert!(
+      movie_list.contains("  create(record: MovieListInsert): Promise<RecordId> {"),
+      "{movie_list}"
+    );
+    // VIEWs are read-only.
+    let (_, movie_names) = &files[1];
+    assert!(
+      movie_names.contains("export class MovieNamesApi {"),
+      "{movie_names}"
+    );
+    assert!(!movie_names.contains("MovieNamesInsert"), "{movie_names}");
+    assert!(!movie_names.contains("create("), "{movie_names}");
+
+    let files = get_sdk(&state, ClientLanguage::Dart).await;
+    let (_, movie_list) = &files[0];
+    assert!(
+      movie_list.contains("  final double? rating;\n"),
+      "{movie_list}"
+    );
+    assert!(
+      movie_list.contains("  MovieListApi(Client client) : _api = client.records('movie_list');"),
+      "{movie_list}"
+    );
+
+    let files = get_sdk(&state, ClientLanguage::Kotlin).await;
+    assert_eq!(files[0].0, "MovieList.kt");
+    let (_, movie_list) = &files[0];
+    assert!(movie_list.contains("package com.example\n"), "{movie_list}");
+    assert!(
+      movie_list.contains("data class MovieListUpdate(\n    val id: Long? = null,\n"),
+      "{movie_list}"
+    );
+
+    let files = get_sdk(&state, ClientLanguage::Swift).await;
+    assert_eq!(
+      files
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>(),
+      ["MovieList.swift", "MovieNames.swift", "JSONValue.swift"]
+    );
+    let (_, movie_list) = &files[0];
+    assert!(
+      movie_list.contains("public struct MovieList: Codable, Sendable {\n  public var id: Int64\n"),
+      "{movie_list}"
+    );
+  }
+}
@@ -0,0 +1,134 @@
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use trailbase_schema::QualifiedName;
+use trailbase_schema::json_schema::{JsonSchemaMode, build_json_schema};
+use trailbase_schema::typescript::{json_schema_to_typescript, to_type_name};
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+
+/// Serves TypeScript definitions for all user TABLEs and VIEWs, e.g. for typed record access from
+/// WASM components. For every TABLE `foo` there's a `Foo` type for reads as well as `FooInsert`
+/// and `FooUpdate` for writes. VIEWs are read-only.
+pub async fn get_typescript_types_handler(
+  State(state): State<AppState>,
+) -> Result<Response, Error> {
+  let metadata = state.connection_manager().main_entry().metadata;
+  let registry = state.json_schema_registry().read();
+
+  let mut tables: Vec<_> = metadata
+    .tables
+    .values()
+    .filter(|t| !is_reserved(t.name()) && !t.schema.virtual_table)
+    .collect();
+  tables.sort_by_key(|t| t.name().escaped_string());
+
+  let mut views: Vec<_> = metadata
+    .views
+    .values()
+    .filter(|v| !is_reserved(v.name()))
+    .collect();
+  views.sort_by_key(|v| v.name().escaped_string());
+
+  let mut out =
+    "// Generated by TrailBase from the database schema. Do not edit this file manually.\n"
+      .to_string();
+
+  for table in tables {
+    for (mode, suffix) in [
+      (JsonSchemaMode::Select, ""),
+      (JsonSchemaMode::Insert, "Insert"),
+      (JsonSchemaMode::Update, "Update"),
+    ] {
+      let (_validator, schema) =
+        build_json_schema(&registry, &table.name().name, &table.column_metadata, mode)?;
+      append_type(&mut out, table.name(), suffix, &schema);
+    }
+  }
+
+  for view in views {
+    // Columns of complex VIEWs may not be inferable.
+    let Some(columns) = view.columns() else {
+      continue;
+    };
+    let (_validator, schema) = build_json_schema(
+      &registry,
+      &view.name().name,
+      columns,
+      JsonSchemaMode::Select,
+    )?;
+    append_type(&mut out, view.name(), "", &schema);
+  }
+
+  let mut response = out.into_response();
+  response.headers_mut().insert(
+    header::CONTENT_TYPE,
+    header::HeaderValue::from_static("application/typescript"),
+  );
+  return Ok(response);
+}
+
+fn append_type(out: &mut String, name: &QualifiedName, suffix: &str, schema: &serde_json::Value) {
+  out.push('\n');
+  out.push_str(&json_schema_to_typescript(
+    &format!("{}{suffix}", type_name(name)),
+    schema,
+  ));
+}
+
+fn is_reserved(name: &QualifiedName) -> bool {
+  return name.name.starts_with('_') || name.name.starts_with("sqlite_");
+}
+
+/// Prefixes types of attached databases with the database's name to avoid collisions.
+fn type_name(name: &QualifiedName) -> String {
+  return match name.database_schema.as_deref() {
+    None | Some("main") => to_type_name(&name.name),
+    Some(db) => to_type_name(&format!("{db}_{}", name.name)),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_get_typescript_types() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE movie_list (id INTEGER PRIMARY KEY, name TEXT NOT NULL, rating REAL) STRICT; \
+          CREATE VIEW movie_names AS SELECT id, name FROM movie_list; \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    let response = get_typescript_types_handler(State(state)).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let types = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(
+      types.contains(
+        "export type MovieList = {\n  id: number;\n  name: string;\n  rating?: null | number;\n};"
+      ),
+      "{types}"
+    );
+    assert!(
+      types.contains("export type MovieListInsert = {\n  id?: number;\n  name: string;\n"),
+      "{types}"
+    );
+    assert!(
+      types.contains("export type MovieListUpdate = {\n  id?: number;\n  name?: string;\n"),
+      "{types}"
+    );
+    assert!(types.contains("export type MovieNames = {"), "{types}");
+    assert!(!types.contains("_user"), "{types}");
+  }
+}
@@ -1,6 +1,12 @@
 mod get_api_json_schema;
+mod get_client_sdk;
+mod get_typescript_types;
+mod update_json_schema;
 
 pub(super) use get_api_json_schema::get_api_json_schema_handler;
+pub(super) use get_client_sdk::get_client_sdk_handler;
+pub(super) use get_typescript_types::get_typescript_types_handler;
+pub(super) use update_json_schema::{delete_json_schema_handler, update_json_schema_handler};
 
 use axum::extract::{Json, State};
 use serde::Serialize;
@@ -0,0 +1,327 @@
+use axum::extract::{Json, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use trailbase_schema::QualifiedName;
+use trailbase_schema::metadata::JsonColumnMetadata;
+use trailbase_schema::registry::build_json_schema_registry;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::auth::User;
+use crate::config::proto::{JsonSchemaConfig, hash_config};
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct UpdateJsonSchemaRequest {
+  pub name: String,
+  /// The JSON schema definition.
+  pub schema: String,
+}
+
+/// Creates or updates a custom JSON schema.
+///
+/// Schemas are persisted in the config. Updates are rejected if existing values of columns
+/// constrained by the schema, i.e. `CHECK(jsonschema('<name>', col))`, don't conform to the new
+/// definition.
+pub async fn update_json_schema_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<UpdateJsonSchemaRequest>,
+) -> Result<Response, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+  if request.name.is_empty() {
+    return Err(Error::BadRequest("Missing schema name".into()));
+  }
+  check_not_builtin(&state, &request.name)?;
+
+  let mut config = (*state.get_config()).clone();
+  let old_config_hash = hash_config(&config);
+
+  match config
+    .schemas
+    .iter_mut()
+    .find(|s| s.name.as_ref() == Some(&request.name))
+  {
+    Some(existing) => existing.schema = Some(request.schema),
+    None => config.schemas.push(JsonSchemaConfig {
+      name: Some(request.name.clone()),
+      schema: Some(request.schema),
+    }),
+  };
+
+  // Validate existing values against the new definition before it's applied.
+  let columns = referencing_columns(&state, &request.name);
+  if !columns.is_empty() {
+    let schemas = config
+      .schemas
+      .iter()
+      .filter_map(|s| {
+        let (Some(name), Some(schema)) = (&s.name, &s.schema) else {
+          return None;
+        };
+        return Some(
+          serde_json::from_str::<serde_json::Value>(schema).map(|schema| (name.clone(), schema)),
+        );
+      })
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|err| Error::BadRequest(err.into()))?;
+    let registry =
+      build_json_schema_registry(schemas).map_err(|err| Error::BadRequest(err.into()))?;
+    let metadata = JsonColumnMetadata::SchemaName(request.name.clone());
+
+    for (table_name, column_name) in columns {
+      let escaped_column_name = column_name.replace('"', "\"\"");
+      let rows = state
+        .conn()
+        .read_query_rows(
+          format!(
+            r#"SELECT "{escaped_column_name}" FROM {table} WHERE "{escaped_column_name}" IS NOT NULL"#,
+            table = table_name.escaped_string()
+          ),
+          (),
+        )
+        .await?;
+
+      for row in rows.iter() {
+        let valid = serde_json::from_str::<serde_json::Value>(&row.get::<String>(0)?)
+          .is_ok_and(|value| metadata.validate(&registry, &value).is_ok());
+        if !valid {
+          return Err(Error::Precondition(format!(
+            "Existing values of {}.{column_name} violate the new schema",
+            table_name.escaped_string()
+          )));
+        }
+      }
+    }
+  }
+
+  // NOTE: Applying the config re-registers the schemas and rebuilds the schema metadata.
+  state
+    .validate_and_update_config_as(config, Some(old_config_hash), Some(user.uuid))
+    .await?;
+
+  return Ok((StatusCode::OK, "updated").into_response());
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct DeleteJsonSchemaRequest {
+  pub name: String,
+}
+
+/// Deletes a custom JSON schema. Schemas still referenced by column constraints cannot be deleted.
+pub async fn delete_json_schema_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<DeleteJsonSchemaRequest>,
+) -> Result<Response, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+  check_not_builtin(&state, &request.name)?;
+
+  if let Some((table_name, column_name)) = referencing_columns(&state, &request.name).first() {
+    return Err(Error::Precondition(format!(
+      "Schema '{}' is still used by {}.{column_name}",
+      request.name,
+      table_name.escaped_string()
+    )));
+  }
+
+  let mut config = (*state.get_config()).clone();
+  let old_config_hash = hash_config(&config);
+
+  let len = config.schemas.len();
+  config
+    .schemas
+    .retain(|s| s.name.as_ref() != Some(&request.name));
+  if config.schemas.len() == len {
+    return Err(Error::Precondition(format!(
+      "Schema '{}' not found",
+      request.name
+    )));
+  }
+
+  state
+    .validate_and_update_config_as(config, Some(old_config_hash), Some(user.uuid))
+    .await?;
+
+  return Ok((StatusCode::OK, "deleted").into_response());
+}
+
+fn check_not_builtin(state: &AppState, name: &str) -> Result<(), Error> {
+  if let Some(schema) = state.json_schema_registry().read().get_schema(name)
+    && schema.builtin
+  {
+    return Err(Error::Precondition(format!(
+      "Cannot modify builtin schema: {name}"
+    )));
+  }
+  return Ok(());
+}
+
+/// Columns constrained by the given schema, i.e. `CHECK(jsonschema('<name>', col))`.
+fn referencing_columns(state: &AppState, name: &str) -> Vec<(QualifiedName, String)> {
+  let metadata = state.connection_manager().main_entry().metadata;
+
+  let mut columns: Vec<(QualifiedName, String)> = metadata
+    .tables
+    .values()
+    .flat_map(|table| {
+      return table
+        .column_metadata
+        .iter()
+        .filter(|meta| matches!(&meta.json, Some(JsonColumnMetadata::SchemaName(n)) if n == name))
+        .map(|meta| (table.name().clone(), meta.column.name.clone()));
+    })
+    .collect();
+  columns
+    .sort_by_key(|(table_name, column_name)| (table_name.escaped_string(), column_name.clone()));
+  return columns;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+
+  fn admin_user() -> User {
+    return User::from_unverified(uuid::Uuid::now_v7(), Some("admin@test.org"), None);
+  }
+
+  async fn update(state: &AppState, name: &str, schema: serde_json::Value) -> Result<(), Error> {
+    return update_json_schema_handler(
+      State(state.clone()),
+      admin_user(),
+      Json(UpdateJsonSchemaRequest {
+        name: name.to_string(),
+        schema: schema.to_string(),
+      }),
+    )
+    .await
+    .map(|_| ());
+  }
+
+  async fn delete(state: &AppState, name: &str) -> Result<(), Error> {
+    return delete_json_schema_handler(
+      State(state.clone()),
+      admin_user(),
+      Json(DeleteJsonSchemaRequest {
+        name: name.to_string(),
+      }),
+    )
+    .await
+    .map(|_| ());
+  }
+
+  #[tokio::test]
+  async fn test_json_schema_lifecycle() {
+    let state = test_state(None).await.unwrap();
+
+    update(
+      &state,
+      "point",
+      serde_json::json!({
+        "type": "object",
+        "properties": { "x": { "type": "number" } },
+        "required": ["x"],
+      }),
+    )
+    .await
+    .unwrap();
+    assert!(
+      state
+        .get_config()
+        .schemas
+        .iter()
+        .any(|s| s.name.as_deref() == Some("point"))
+    );
+
+    // Registered schemas are immediately available to constraints.
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE points (
+            id    INTEGER PRIMARY KEY,
+            data  TEXT CHECK(jsonschema('point', data))
+          ) STRICT;
+          INSERT INTO points (data) VALUES ('{"x": 1}');
+        "#,
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    // Existing values violate the new definition.
+    assert!(matches!(
+      update(
+        &state,
+        "point",
+        serde_json::json!({
+          "type": "object",
+          "required": ["x", "y"],
+        }),
+      )
+      .await,
+      Err(Error::Precondition(_))
+    ));
+
+    // Compatible update.
+    update(
+      &state,
+      "point",
+      serde_json::json!({
+        "type": "object",
+        "properties": { "x": { "type": "number" }, "y": { "type": "number" } },
+        "required": ["x"],
+      }),
+    )
+    .await
+    .unwrap();
+    assert!(
+      state
+        .conn()
+        .execute(
+          r#"INSERT INTO points (data) VALUES ('{"x": 1, "y": "a"}')"#,
+          ()
+        )
+        .await
+        .is_err()
+    );
+
+    // Builtins and referenced schemas cannot be removed.
+    assert!(matches!(
+      delete(&state, "std.FileUpload").await,
+      Err(Error::Precondition(_))
+    ));
+    assert!(matches!(
+      delete(&state, "point").await,
+      Err(Error::Precondition(_))
+    ));
+
+    state
+      .conn()
+      .execute_batch("DROP TABLE points")
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    delete(&state, "point").await.unwrap();
+    assert!(
+      state
+        .json_schema_registry()
+        .read()
+        .get_schema("point")
+        .is_none()
+    );
+    assert!(matches!(
+      delete(&state, "point").await,
+      Err(Error::Precondition(_))
+    ));
+  }
+}
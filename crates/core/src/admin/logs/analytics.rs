@@ -0,0 +1,341 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use trailbase_sqlite::Value;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::constants::LOGS_TABLE;
+
+/// Requests in a time bucket.
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct RequestBucket {
+  /// Start of the bucket in seconds since epoch.
+  timestamp: i64,
+  requests: i64,
+  /// Requests per minute.
+  rate: f64,
+  /// Requests with a 4XX status.
+  client_errors: i64,
+  /// Requests with a 5XX status.
+  server_errors: i64,
+  /// Share of requests with a 5XX status in [0, 1].
+  error_rate: f64,
+}
+
+/// Latency percentiles of a route.
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct RouteLatency {
+  method: String,
+  /// Matched route, e.g. "/api/records/v1/{name}". Falls back to the request path for requests
+  /// w/o a matched route, e.g. 404s or logs written before routes were recorded.
+  route: String,
+  requests: i64,
+  p50_ms: f64,
+  p95_ms: f64,
+  p99_ms: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct TopEntry {
+  value: String,
+  requests: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct LogAnalyticsResponse {
+  /// Request and error counts per bucket, oldest first. Buckets w/o requests are omitted.
+  series: Vec<RequestBucket>,
+  /// Latency percentiles of the busiest routes.
+  routes: Vec<RouteLatency>,
+  top_user_agents: Vec<TopEntry>,
+  top_client_ips: Vec<TopEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogAnalyticsQuery {
+  /// Start of the analyzed time range as UNIX timestamp. Default: 24h before `until`.
+  since: Option<i64>,
+  /// End of the analyzed time range as UNIX timestamp. Default: now.
+  until: Option<i64>,
+  /// Bucket width of the time series in seconds. Default: 60s.
+  interval_sec: Option<i64>,
+  /// Max number of routes, user agents and client IPs. Default: 10.
+  limit: Option<usize>,
+}
+
+/// Aggregates the request logs server-side, e.g. request rates, error rates and latency
+/// percentiles, to avoid shipping raw logs to the dashboard.
+pub async fn fetch_log_analytics_handler(
+  State(state): State<AppState>,
+  Query(query): Query<LogAnalyticsQuery>,
+) -> Result<Json<LogAnalyticsResponse>, Error> {
+  let until = query
+    .until
+    .unwrap_or_else(|| chrono::Utc::now().timestamp());
+  let since = query.since.unwrap_or(until - 24 * 3600);
+  let interval_sec = query.interval_sec.unwrap_or(60);
+
+  if since >= until {
+    return Err(Error::BadRequest("Expected 'since' < 'until'".into()));
+  }
+  if interval_sec <= 0 || (until - since) / interval_sec > MAX_BUCKETS {
+    return Err(Error::BadRequest(
+      format!("Invalid 'interval_sec', at most {MAX_BUCKETS} buckets supported").into(),
+    ));
+  }
+
+  return Ok(Json(
+    fetch_log_analytics(
+      state.logs_conn(),
+      AnalyticsArgs {
+        since,
+        until,
+        interval_sec,
+        limit: query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+      },
+    )
+    .await?,
+  ));
+}
+
+#[derive(Debug)]
+struct AnalyticsArgs {
+  since: i64,
+  until: i64,
+  interval_sec: i64,
+  limit: usize,
+}
+
+async fn fetch_log_analytics(
+  conn: &trailbase_sqlite::Connection,
+  args: AnalyticsArgs,
+) -> Result<LogAnalyticsResponse, Error> {
+  let params = |name: &'static str, value: i64| -> Vec<(Cow<'static, str>, Value)> {
+    return Vec::from([
+      (Cow::Borrowed(":since"), Value::Integer(args.since)),
+      (Cow::Borrowed(":until"), Value::Integer(args.until)),
+      (Cow::Borrowed(name), Value::Integer(value)),
+    ]);
+  };
+
+  let limit = args.limit as i64;
+
+  // NOTE: Buckets are aligned with the epoch rather than `since` to get stable buckets when
+  // polling with a sliding window.
+  let series_query = format!(
+    "\
+      SELECT \
+        CAST(created / :interval_sec AS INTEGER) * :interval_sec AS timestamp, \
+        COUNT(*) AS requests, \
+        COUNT(*) * 60.0 / :interval_sec AS rate, \
+        SUM(status >= 400 AND status < 500) AS client_errors, \
+        SUM(status >= 500) AS server_errors, \
+        SUM(status >= 500) * 1.0 / COUNT(*) AS error_rate \
+      FROM '{LOGS_TABLE}' \
+      WHERE created >= :since AND created < :until \
+      GROUP BY timestamp \
+      ORDER BY timestamp ASC \
+    "
+  );
+
+  // Percentiles use the nearest-rank method, i.e. the p-th percentile is the smallest latency,
+  // which is greater or equal to p% of all latencies: rank = ceil(p * n / 100).
+  let routes_query = format!(
+    "\
+      SELECT \
+        method, \
+        route, \
+        requests, \
+        MAX(CASE WHEN idx = (50 * requests + 99) / 100 THEN latency END) AS p50_ms, \
+        MAX(CASE WHEN idx = (95 * requests + 99) / 100 THEN latency END) AS p95_ms, \
+        MAX(CASE WHEN idx = (99 * requests + 99) / 100 THEN latency END) AS p99_ms \
+      FROM ( \
+        SELECT \
+          method, \
+          route, \
+          latency, \
+          ROW_NUMBER() OVER (PARTITION BY method, route ORDER BY latency) AS idx, \
+          COUNT(*) OVER (PARTITION BY method, route) AS requests \
+        FROM ( \
+          SELECT \
+            method, \
+            COALESCE( \
+              data ->> '$.route', \
+              IIF(INSTR(url, '?') > 0, SUBSTR(url, 1, INSTR(url, '?') - 1), url) \
+            ) AS route, \
+            latency \
+          FROM '{LOGS_TABLE}' \
+          WHERE created >= :since AND created < :until \
+        ) \
+      ) \
+      GROUP BY method, route \
+      ORDER BY requests DESC, route ASC \
+      LIMIT :limit \
+    "
+  );
+
+  let top_query = |column: &str| {
+    return format!(
+      "\
+        SELECT {column} AS value, COUNT(*) AS requests \
+        FROM '{LOGS_TABLE}' \
+        WHERE created >= :since AND created < :until AND {column} != '' \
+        GROUP BY {column} \
+        ORDER BY requests DESC, value ASC \
+        LIMIT :limit \
+      "
+    );
+  };
+
+  return Ok(LogAnalyticsResponse {
+    series: conn
+      .read_query_values::<RequestBucket>(series_query, params(":interval_sec", args.interval_sec))
+      .await?,
+    routes: conn
+      .read_query_values::<RouteLatency>(routes_query, params(":limit", limit))
+      .await?,
+    top_user_agents: conn
+      .read_query_values::<TopEntry>(top_query("user_agent"), params(":limit", limit))
+      .await?,
+    top_client_ips: conn
+      .read_query_values::<TopEntry>(top_query("client_ip"), params(":limit", limit))
+      .await?,
+  });
+}
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 1024;
+const MAX_BUCKETS: i64 = 10_000;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::migrations::apply_logs_migrations;
+
+  #[tokio::test]
+  async fn test_log_analytics() {
+    let conn = trailbase_sqlite::Connection::with_opts(
+      move || -> Result<_, trailbase_sqlite::Error> {
+        let mut conn_sync =
+          crate::connection::connect_rusqlite_without_default_extensions_and_schemas(None).unwrap();
+        apply_logs_migrations(&mut conn_sync).unwrap();
+        return Ok(conn_sync);
+      },
+      Default::default(),
+    )
+    .unwrap();
+
+    // (created, status, method, url, latency, client_ip, user_agent, data)
+    #[allow(clippy::type_complexity)]
+    let logs: [(i64, i64, &str, &str, f64, &str, &str, Option<&str>); 6] = [
+      // Outside the analyzed range.
+      (0, 200, "GET", "/api/healthcheck", 1.0, "", "", None),
+      (60, 200, "GET", "/a?x=1", 10.0, "1.1.1.1", "curl", None),
+      (70, 404, "GET", "/a?x=2", 20.0, "1.1.1.1", "curl", None),
+      (80, 500, "GET", "/a", 30.0, "2.2.2.2", "firefox", None),
+      (
+        130,
+        200,
+        "POST",
+        "/api/records/v1/movies",
+        5.0,
+        "1.1.1.1",
+        "curl",
+        Some(r#"{"route":"/api/records/v1/{name}"}"#),
+      ),
+      (
+        140,
+        200,
+        "POST",
+        "/api/records/v1/books",
+        7.0,
+        "1.1.1.1",
+        "",
+        Some(r#"{"route":"/api/records/v1/{name}"}"#),
+      ),
+    ];
+    for (created, status, method, url, latency, client_ip, user_agent, data) in logs {
+      conn
+        .execute(
+          format!(
+            "INSERT INTO '{LOGS_TABLE}' (created, status, method, url, latency, client_ip, user_agent, data) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+          ),
+          trailbase_sqlite::params!(
+            created as f64,
+            status,
+            method,
+            url,
+            latency,
+            client_ip,
+            user_agent,
+            data.map(str::to_string),
+          ),
+        )
+        .await
+        .unwrap();
+    }
+
+    let analytics = fetch_log_analytics(
+      &conn,
+      AnalyticsArgs {
+        since: 60,
+        until: 180,
+        interval_sec: 60,
+        limit: 10,
+      },
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(analytics.series.len(), 2);
+    let bucket = &analytics.series[0];
+    assert_eq!(bucket.timestamp, 60);
+    assert_eq!(bucket.requests, 3);
+    assert_eq!(bucket.rate, 3.0);
+    assert_eq!(bucket.client_errors, 1);
+    assert_eq!(bucket.server_errors, 1);
+    assert_eq!(bucket.error_rate, 1.0 / 3.0);
+    assert_eq!(analytics.series[1].timestamp, 120);
+    assert_eq!(analytics.series[1].requests, 2);
+    assert_eq!(analytics.series[1].error_rate, 0.0);
+
+    assert_eq!(analytics.routes.len(), 2);
+    let route = &analytics.routes[0];
+    assert_eq!((route.method.as_str(), route.route.as_str()), ("GET", "/a"));
+    assert_eq!(route.requests, 3);
+    assert_eq!(
+      (route.p50_ms, route.p95_ms, route.p99_ms),
+      (20.0, 30.0, 30.0)
+    );
+    let route = &analytics.routes[1];
+    assert_eq!(route.route, "/api/records/v1/{name}");
+    assert_eq!((route.p50_ms, route.p99_ms), (5.0, 7.0));
+
+    assert_eq!(
+      analytics
+        .top_user_agents
+        .iter()
+        .map(|e| (e.value.as_str(), e.requests))
+        .collect::<Vec<_>>(),
+      [("curl", 3), ("firefox", 1)]
+    );
+    assert_eq!(
+      analytics
+        .top_client_ips
+        .iter()
+        .map(|e| (e.value.as_str(), e.requests))
+        .collect::<Vec<_>>(),
+      [("1.1.1.1", 4), ("2.2.2.2", 1)]
+    );
+  }
+}
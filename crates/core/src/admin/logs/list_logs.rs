@@ -224,6 +224,8 @@ pub struct LogJson {
   pub referer: String,
   pub user_agent: String,
   pub user_id: Option<String>,
+  /// Extra data, e.g. the matched route or captured fields like error details.
+  pub data: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -246,7 +248,7 @@ struct LogEntry {
   referer: String,
   user_agent: String,
   user_id: Option<[u8; 16]>,
-  // data: Option<Vec<u8>>,
+  data: Option<String>,
 }
 
 impl LogEntry {
@@ -260,6 +262,7 @@ impl LogEntry {
     replace_if_set(&mut self.client_ip);
     replace_if_set(&mut self.referer);
     replace_if_set(&mut self.user_agent);
+    self.data = None;
   }
 }
 
@@ -290,6 +293,7 @@ impl From<LogEntry> for LogJson {
       referer: value.referer,
       user_agent: value.user_agent,
       user_id: value.user_id.map(|blob| Uuid::from_bytes(blob).to_string()),
+      data: value.data.and_then(|data| serde_json::from_str(&data).ok()),
     };
   }
 }
@@ -1,2 +1,4 @@
+pub mod analytics;
 pub mod list_logs;
+pub mod slow_queries;
 pub mod stats;
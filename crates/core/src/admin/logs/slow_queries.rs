@@ -0,0 +1,146 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::constants::SLOW_QUERIES_TABLE;
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct SlowQueryJson {
+  pub id: i64,
+  /// Seconds since epoch with fractional millisecond resolution.
+  pub created: f64,
+  /// Path of the database file, None for in-memory databases.
+  pub database: Option<String>,
+  pub sql: String,
+  pub duration_ms: f64,
+  /// Output of "EXPLAIN QUERY PLAN" with nested steps indented.
+  pub query_plan: String,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListSlowQueriesResponse {
+  slow_queries: Vec<SlowQueryJson>,
+  /// Cursor for fetching the next page of older entries, if any.
+  cursor: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListSlowQueriesQuery {
+  /// Only list statements, which took at least the given duration.
+  min_duration_ms: Option<f64>,
+  /// Only list entries older than the given id.
+  cursor: Option<i64>,
+  limit: Option<usize>,
+}
+
+/// Lists statements recorded in the slow query log, most recent first.
+pub async fn list_slow_queries_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListSlowQueriesQuery>,
+) -> Result<Json<ListSlowQueriesResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, created, database, sql, duration_ms, query_plan \
+      FROM '{SLOW_QUERIES_TABLE}' \
+      WHERE \
+        ($1 IS NULL OR duration_ms >= $1) AND \
+        ($2 IS NULL OR id < $2) \
+      ORDER BY id DESC LIMIT $3 \
+    "
+  );
+
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  let slow_queries = state
+    .logs_conn()
+    .read_query_values::<SlowQueryJson>(
+      QUERY,
+      params!(query.min_duration_ms, query.cursor, limit as i64),
+    )
+    .await?;
+
+  let cursor = if slow_queries.len() == limit {
+    slow_queries.last().map(|q| q.id)
+  } else {
+    None
+  };
+
+  return Ok(Json(ListSlowQueriesResponse {
+    slow_queries,
+    cursor,
+  }));
+}
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_list_slow_queries() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+
+    for (sql, duration_ms) in [("SELECT 1", 10.0), ("SELECT 2", 200.0), ("SELECT 3", 300.0)] {
+      state
+        .logs_conn()
+        .execute(
+          format!(
+            "INSERT INTO '{SLOW_QUERIES_TABLE}' (sql, duration_ms, query_plan) VALUES ($1, $2, $3)"
+          ),
+          params!(sql, duration_ms, "SCAN t"),
+        )
+        .await
+        .unwrap();
+    }
+
+    let list = async |query: ListSlowQueriesQuery| {
+      return list_slow_queries_handler(State(state.clone()), Query(query))
+        .await
+        .unwrap()
+        .0;
+    };
+
+    let response = list(ListSlowQueriesQuery {
+      limit: Some(2),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(
+      response
+        .slow_queries
+        .iter()
+        .map(|q| q.sql.as_str())
+        .collect::<Vec<_>>(),
+      ["SELECT 3", "SELECT 2"]
+    );
+    assert_eq!(response.slow_queries[0].query_plan, "SCAN t");
+    assert_eq!(response.slow_queries[0].database, None);
+
+    let next = list(ListSlowQueriesQuery {
+      cursor: response.cursor,
+      limit: Some(2),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(next.slow_queries.len(), 1);
+    assert_eq!(next.slow_queries[0].sql, "SELECT 1");
+    assert_eq!(next.cursor, None);
+
+    let slow = list(ListSlowQueriesQuery {
+      min_duration_ms: Some(100.0),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(slow.slow_queries.len(), 2);
+  }
+}
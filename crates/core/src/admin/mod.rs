@@ -1,18 +1,29 @@
+mod api_keys;
+mod audit_log;
 mod config;
+mod database;
 mod email;
+mod email_templates;
 mod error;
 mod info;
 mod jobs;
 mod json_schema;
 mod jwt;
 mod logs;
+mod notifications;
 mod oauth_providers;
 mod parse;
 mod query;
+mod queue;
+mod roles;
 pub(crate) mod rows;
-mod table;
+mod seeds;
+pub(crate) mod table;
+mod tenants;
 pub(crate) mod user;
 mod util;
+mod wasm;
+mod webhooks;
 
 pub use error::AdminError;
 
@@ -34,6 +45,23 @@ pub fn router() -> Router<AppState> {
     .route("/table/{table_name}", patch(rows::update_row_handler))
     .route("/table/{table_name}", post(rows::insert_row_handler))
     .route("/table/{table_name}", delete(rows::delete_row_handler))
+    .route("/table/{table_name}/seed", post(rows::seed_rows_handler))
+    .route(
+      "/table/{table_name}/import/preview",
+      post(rows::import_preview_handler),
+    )
+    .route(
+      "/table/{table_name}/import",
+      post(rows::import_rows_handler),
+    )
+    .route(
+      "/table/{table_name}/indexes",
+      get(table::list_table_indexes_handler).post(table::create_table_index_handler),
+    )
+    .route(
+      "/table/{table_name}/indexes/{index_name}",
+      delete(table::drop_table_index_handler),
+    )
     // Index actions.
     .route("/index", post(table::create_index_handler))
     .route("/index", patch(table::alter_index_handler))
@@ -44,24 +72,110 @@ pub fn router() -> Router<AppState> {
     .route("/table", patch(table::alter_table_handler))
     // Table & Index actions.
     .route("/tables", get(table::list_tables_handler))
+    .route("/tables/apply", post(table::apply_schema_handler))
+    .route("/migrations/lint", post(table::lint_migration_handler))
+    .route(
+      "/migrations/rollback",
+      post(table::rollback_migrations_handler),
+    )
+    // Seed data
+    .route("/seeds/apply", post(seeds::apply_seeds_handler))
+    // Database snapshots
+    .route("/database/export", get(database::export_database_handler))
+    .route(
+      "/database/restore",
+      post(database::restore_database_handler),
+    )
+    .route("/database/backups", get(database::list_backups_handler))
+    .route(
+      "/database/backups/restore",
+      post(database::restore_backup_handler),
+    )
     // Config actions
     .route("/config", get(config::get_config_handler))
     .route("/config", post(config::update_config_handler))
+    .route("/config/history", get(config::list_config_history_handler))
+    .route("/config/rollback", post(config::rollback_config_handler))
+    // Audit log
+    .route("/audit_log", get(audit_log::list_audit_log_handler))
     // User actions
     .route("/user", get(user::list_users_handler))
     .route("/user", post(user::create_user_handler))
     .route("/user", patch(user::update_user_handler))
     .route("/user", delete(user::delete_user_handler))
+    .route("/user/sessions", get(user::list_user_sessions_handler))
+    .route("/user/sessions", delete(user::delete_user_session_handler))
+    .route(
+      "/user/email_change",
+      post(user::confirm_user_email_change_handler),
+    )
+    .route(
+      "/user/email_change",
+      delete(user::cancel_user_email_change_handler),
+    )
+    .route("/user/roles", get(roles::list_user_roles_handler))
+    .route("/user/roles", post(roles::assign_user_role_handler))
+    .route("/user/roles", delete(roles::unassign_user_role_handler))
+    // Roles
+    .route("/roles", get(roles::list_roles_handler))
+    .route("/roles", post(roles::create_role_handler))
+    .route("/roles", delete(roles::delete_role_handler))
+    // Tenants
+    .route("/tenants", get(tenants::list_tenants_handler))
+    .route("/tenants", post(tenants::create_tenant_handler))
+    .route("/tenants", delete(tenants::delete_tenant_handler))
+    .route("/user/tenant", post(tenants::assign_user_tenant_handler))
+    // Webhooks
+    .route(
+      "/webhook/deliveries",
+      get(webhooks::list_webhook_deliveries_handler),
+    )
+    .route(
+      "/webhook/redeliver",
+      post(webhooks::redeliver_webhook_handler),
+    )
+    .route("/webhook/queue", get(webhooks::list_webhook_queue_handler))
+    .route(
+      "/webhook/queue/retry",
+      post(webhooks::retry_webhook_event_handler),
+    )
+    // Task queue
+    .route("/queue", get(queue::list_tasks_handler))
+    .route("/queue", post(queue::enqueue_task_handler))
+    .route("/queue", delete(queue::delete_task_handler))
+    .route("/queue/requeue", post(queue::requeue_task_handler))
+    // API keys
+    .route("/api_key", get(api_keys::list_api_keys_handler))
+    .route("/api_key", post(api_keys::create_api_key_handler))
+    .route("/api_key", delete(api_keys::delete_api_key_handler))
     // Schema actions
     .route("/schema", get(json_schema::list_schemas_handler))
+    .route("/schema", post(json_schema::update_json_schema_handler))
+    .route("/schema", delete(json_schema::delete_json_schema_handler))
+    .route(
+      "/schema/types.d.ts",
+      get(json_schema::get_typescript_types_handler),
+    )
+    .route(
+      "/schema/client_sdk.zip",
+      get(json_schema::get_client_sdk_handler),
+    )
     .route(
       "/schema/{record_api_name}/schema.json",
       get(json_schema::get_api_json_schema_handler),
     )
     // Logs
     .route("/logs/list", get(logs::list_logs::list_logs_handler))
+    .route(
+      "/logs/slow_queries",
+      get(logs::slow_queries::list_slow_queries_handler),
+    )
     // Stats
     .route("/logs/stats", get(logs::stats::fetch_stats_handler))
+    .route(
+      "/logs/analytics",
+      get(logs::analytics::fetch_log_analytics_handler),
+    )
     // Query execution handler for the UI editor
     .route("/query", post(query::query_handler))
     // Parse handler for UI validation.
@@ -75,5 +189,43 @@ pub fn router() -> Router<AppState> {
     .route("/info", get(info::info_handler))
     .route("/jobs", get(jobs::list_jobs_handler))
     .route("/job/run", post(jobs::run_job_handler))
+    .route("/job", post(jobs::create_job_handler))
+    .route("/job", delete(jobs::delete_job_handler))
+    .route("/job/pause", post(jobs::pause_job_handler))
+    .route("/job/resume", post(jobs::resume_job_handler))
+    .route("/job/runs", get(jobs::list_job_runs_handler))
     .route("/email/test", post(email::test_email_handler))
+    .route("/email/log", get(email::list_email_log_handler))
+    .route("/email/queue", get(email::list_email_queue_handler))
+    .route("/email/queue", delete(email::delete_queued_email_handler))
+    .route("/email/queue/requeue", post(email::requeue_email_handler))
+    .route(
+      "/email/templates",
+      get(email_templates::list_email_templates_handler),
+    )
+    .route(
+      "/email/templates",
+      post(email_templates::update_email_template_handler),
+    )
+    .route(
+      "/email/templates",
+      delete(email_templates::delete_email_template_handler),
+    )
+    .route(
+      "/email/templates/preview",
+      post(email_templates::preview_email_template_handler),
+    )
+    .route(
+      "/notifications/send",
+      post(notifications::send_notification_handler),
+    )
+    .route(
+      "/notifications/deliveries",
+      get(notifications::list_push_deliveries_handler),
+    )
+    .route("/wasm", get(wasm::list_wasm_components_handler))
+    .route(
+      "/wasm/enabled",
+      post(wasm::set_wasm_component_enabled_handler),
+    )
 }
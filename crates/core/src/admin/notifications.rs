@@ -0,0 +1,160 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::audit::{AuditAction, record_audit_event};
+use crate::auth::User;
+use crate::constants::PUSH_DELIVERIES_TABLE;
+use crate::notifications::{Notification, Recipients, SendNotificationResponse, send_notification};
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct SendNotificationRequest {
+  /// Users to notify on all their registered devices. Mutually exclusive with `topic`.
+  pub user_ids: Option<Vec<Uuid>>,
+  /// Notify all users subscribed to the given topic.
+  pub topic: Option<String>,
+  pub notification: Notification,
+}
+
+/// Sends a push notification and waits for the push services' responses.
+pub async fn send_notification_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<SendNotificationRequest>,
+) -> Result<Json<SendNotificationResponse>, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  let recipients = match (request.user_ids, request.topic) {
+    (Some(user_ids), None) => Recipients::Users(user_ids),
+    (None, Some(topic)) => Recipients::Topic(topic),
+    _ => {
+      return Err(Error::BadRequest(
+        "expected either user ids or a topic".into(),
+      ));
+    }
+  };
+  if request.notification.title.is_empty() {
+    return Err(Error::BadRequest("missing title".into()));
+  }
+
+  let response = send_notification(&state, recipients.clone(), &request.notification).await?;
+
+  let target = match recipients {
+    Recipients::Users(ref user_ids) => format!("{} users", user_ids.len()),
+    Recipients::Topic(ref topic) => format!("topic:{topic}"),
+  };
+  record_audit_event(
+    &state,
+    Some(user.uuid),
+    AuditAction::NotificationSent,
+    &target,
+    Some(serde_json::json!({
+      "notification": response.notification_id,
+      "title": request.notification.title,
+    })),
+  )
+  .await;
+
+  return Ok(Json(response));
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct PushDeliveryJson {
+  pub id: i64,
+  /// Seconds since epoch with fractional millisecond resolution.
+  pub created: f64,
+  pub notification: String,
+  pub user_id: String,
+  pub device: i64,
+  pub platform: String,
+  pub title: String,
+  /// One of "sent", "failed" or "unregistered", i.e. the device was removed.
+  pub status: String,
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListPushDeliveriesResponse {
+  deliveries: Vec<PushDeliveryJson>,
+  /// Cursor for fetching the next page of older entries, if any.
+  cursor: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListPushDeliveriesQuery {
+  /// Only list deliveries of the given notification.
+  notification: Option<String>,
+  status: Option<String>,
+  /// Only list entries older than the given id.
+  cursor: Option<i64>,
+  limit: Option<usize>,
+}
+
+/// Lists the outcome of push notifications per device, most recent first.
+pub async fn list_push_deliveries_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListPushDeliveriesQuery>,
+) -> Result<Json<ListPushDeliveriesResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, created, notification, user, device, platform, title, status, error \
+      FROM '{PUSH_DELIVERIES_TABLE}' \
+      WHERE \
+        ($1 IS NULL OR notification = $1) AND \
+        ($2 IS NULL OR status = $2) AND \
+        ($3 IS NULL OR id < $3) \
+      ORDER BY id DESC LIMIT $4 \
+    "
+  );
+
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  let rows = state
+    .logs_conn()
+    .read_query_rows(
+      QUERY,
+      params!(query.notification, query.status, query.cursor, limit as i64),
+    )
+    .await?;
+
+  let deliveries = rows
+    .iter()
+    .map(|row| -> Result<PushDeliveryJson, Error> {
+      let user: [u8; 16] = row.get(3)?;
+      return Ok(PushDeliveryJson {
+        id: row.get(0)?,
+        created: row.get(1)?,
+        notification: row.get(2)?,
+        user_id: Uuid::from_bytes(user).to_string(),
+        device: row.get(4)?,
+        platform: row.get(5)?,
+        title: row.get(6)?,
+        status: row.get(7)?,
+        error: row.get(8)?,
+      });
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let cursor = if deliveries.len() == limit {
+    deliveries.last().map(|d| d.id)
+  } else {
+    None
+  };
+
+  return Ok(Json(ListPushDeliveriesResponse { deliveries, cursor }));
+}
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1024;
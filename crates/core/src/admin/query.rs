@@ -1,14 +1,27 @@
 use axum::{Json, extract::State};
+use const_format::formatcp;
+use log::*;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use trailbase_schema::parse::parse_into_statements;
 use trailbase_schema::sqlite::Column;
+use trailbase_sqlite::{ConnectionType, Value, params};
 use trailbase_sqlvalue::SqlValue;
 use ts_rs::TS;
 
 use crate::AppState;
 use crate::admin::AdminError as Error;
 use crate::admin::util::{rows_to_columns, rows_to_sql_value_rows};
+use crate::auth::User;
 use crate::connection::{BuildOptions, ConnectionEntry};
+use crate::constants::ADMIN_QUERY_LOG_TABLE;
+
+const AUDIT_TARGET: &str = "admin_audit";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_TIMEOUT: Duration = Duration::from_secs(600);
 
 #[derive(Debug, Default, Serialize, TS)]
 #[ts(export)]
@@ -16,27 +29,67 @@ pub struct QueryResponse {
   columns: Option<Vec<Column>>,
 
   rows: Vec<Vec<SqlValue>>,
+
+  /// Whether there are more rows past the requested page. Only set when paging.
+  has_more: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, TS)]
+#[derive(Debug, Default, Deserialize, Serialize, TS)]
 #[ts(export)]
 pub struct QueryRequest {
   query: String,
   attached_databases: Option<Vec<String>>,
+
+  /// Positional parameters bound to the query, e.g. `$1` or `?1`. Requires a single statement.
+  params: Option<Vec<SqlValue>>,
+  /// Returns the query plan rather than executing the query. Requires a single statement.
+  explain: Option<bool>,
+
+  /// Maximum number of rows returned. Paging requires a single SELECT statement.
+  limit: Option<usize>,
+  offset: Option<usize>,
+
+  /// Aborts execution after the given duration. Defaults to 30s.
+  timeout_ms: Option<u64>,
 }
 
 pub async fn query_handler(
   State(state): State<AppState>,
+  user: User,
   Json(request): Json<QueryRequest>,
 ) -> Result<Json<QueryResponse>, Error> {
+  let start = Instant::now();
+  let result = execute_query(&state, &request).await;
+
+  audit_query(
+    &state,
+    &user,
+    &request,
+    start.elapsed(),
+    result.as_ref().err(),
+  )
+  .await;
+
+  return result.map(Json);
+}
+
+async fn execute_query(state: &AppState, request: &QueryRequest) -> Result<QueryResponse, Error> {
   // Check the statements are correct before executing anything, just to be sure.
   let statements =
     parse_into_statements(&request.query).map_err(|err| Error::BadRequest(err.into()))?;
 
+  let explain = request.explain.unwrap_or(false);
+  let paging = request.limit.is_some() || request.offset.is_some();
+  if (explain || paging || request.params.is_some()) && statements.len() != 1 {
+    return Err(Error::BadRequest(
+      "Parameters, EXPLAIN and paging require a single statement".into(),
+    ));
+  }
+
   let mut must_invalidate_schema_cache = false;
   let mut mutation = true;
 
-  for stmt in statements {
+  for stmt in &statements {
     use sqlite3_parser::ast::Stmt;
 
     match stmt {
@@ -59,10 +112,22 @@ pub async fn query_handler(
       Stmt::Select { .. } => {
         mutation = false;
       }
-      _ => {}
+      _ => {
+        if paging {
+          return Err(Error::BadRequest(
+            "Paging requires a SELECT statement".into(),
+          ));
+        }
+      }
     }
   }
 
+  if explain {
+    // Only plans the query, nothing is executed.
+    mutation = false;
+    must_invalidate_schema_cache = false;
+  }
+
   if state.demo_mode() && mutation {
     return Err(Error::Precondition(
       "Demo disallows mutation queries".into(),
@@ -77,25 +142,309 @@ pub async fn query_handler(
     .connection_manager()
     .build(BuildOptions {
       is_main: true,
-      attached_databases: request.attached_databases.map(|v| v.into_iter().collect()),
+      attached_databases: request
+        .attached_databases
+        .clone()
+        .map(|v| v.into_iter().collect()),
       num_threads: Some(1),
     })
     .await?;
 
-  let batched_rows_result = trailbase_sqlite::execute_batch(&conn, request.query).await;
+  let timeout = request
+    .timeout_ms
+    .map_or(DEFAULT_TIMEOUT, Duration::from_millis)
+    .min(MAX_TIMEOUT);
+  let timed_out = Arc::new(AtomicBool::new(false));
+  let _interrupt_on_drop = if matches!(conn.connection_type(), ConnectionType::Sqlite) {
+    let deadline = Instant::now() + timeout;
+    let timed_out = timed_out.clone();
+
+    let lock = conn
+      .write_lock()
+      .map_err(|err| Error::Internal(err.into()))?;
+    lock.progress_handler(
+      1000,
+      Some(move || {
+        if Instant::now() > deadline {
+          timed_out.store(true, Ordering::Relaxed);
+          return true;
+        }
+        return false;
+      }),
+    )?;
+
+    // Stops execution when the request is dropped, e.g. because the client disconnected.
+    Some(InterruptOnDrop(lock.get_interrupt_handle()))
+  } else {
+    None
+  };
+
+  let query = request.query.trim().trim_end_matches(';');
+  let (limit, offset) = (request.limit, request.offset.unwrap_or(0));
+
+  let rows_result = if explain {
+    execute_statement(&conn, format!("EXPLAIN QUERY PLAN {query}"), request).await
+  } else if paging {
+    // Fetch one extra row to tell whether there's more.
+    let limit = limit.map_or(-1, |l| l as i64 + 1);
+    execute_statement(
+      &conn,
+      format!("SELECT * FROM (\n{query}\n) LIMIT {limit} OFFSET {offset}"),
+      request,
+    )
+    .await
+  } else if request.params.is_some() {
+    execute_statement(&conn, query.to_string(), request).await
+  } else {
+    trailbase_sqlite::execute_batch(&conn, request.query.clone())
+      .await
+      .map_err(|err| Error::BadRequest(err.into()))
+  };
 
   // In the fallback case we always need to invalidate the cache.
   if must_invalidate_schema_cache {
     state.rebuild_connection_metadata().await?;
   }
 
-  let batched_rows = batched_rows_result.map_err(|err| Error::BadRequest(err.into()))?;
-  if let Some(rows) = batched_rows {
-    return Ok(Json(QueryResponse {
-      columns: Some(rows_to_columns(&rows)),
-      rows: rows_to_sql_value_rows(&rows)?,
-    }));
+  if timed_out.load(Ordering::Relaxed) {
+    return Err(Error::BadRequest(
+      format!("Query exceeded timeout of {}ms", timeout.as_millis()).into(),
+    ));
   }
 
-  return Ok(Json(QueryResponse::default()));
+  let Some(rows) = rows_result? else {
+    return Ok(QueryResponse::default());
+  };
+
+  let mut values = rows_to_sql_value_rows(&rows)?;
+  let has_more = match limit {
+    Some(limit) if paging && values.len() > limit => {
+      values.truncate(limit);
+      true
+    }
+    _ => false,
+  };
+
+  return Ok(QueryResponse {
+    columns: Some(rows_to_columns(&rows)),
+    rows: values,
+    has_more,
+  });
+}
+
+struct InterruptOnDrop(rusqlite::InterruptHandle);
+
+impl Drop for InterruptOnDrop {
+  fn drop(&mut self) {
+    // NOTE: Has no effect if there are no running statements, e.g. when execution completed.
+    self.0.interrupt();
+  }
+}
+
+async fn execute_statement(
+  conn: &trailbase_sqlite::Connection,
+  sql: String,
+  request: &QueryRequest,
+) -> Result<Option<trailbase_sqlite::Rows>, Error> {
+  let params = request
+    .params
+    .clone()
+    .unwrap_or_default()
+    .into_iter()
+    .map(Value::try_from)
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let rows = conn
+    .write_query_rows(sql, params)
+    .await
+    .map_err(|err| Error::BadRequest(err.into()))?;
+
+  return Ok(if rows.column_count() > 0 {
+    Some(rows)
+  } else {
+    None
+  });
+}
+
+/// Records executed statements for auditing, independent of whether they succeeded.
+async fn audit_query(
+  state: &AppState,
+  user: &User,
+  request: &QueryRequest,
+  duration: Duration,
+  error: Option<&Error>,
+) {
+  const QUERY: &str = formatcp!(
+    "INSERT INTO '{ADMIN_QUERY_LOG_TABLE}' \
+       (user, query, params, explain_query_plan, duration_ms, error) \
+     VALUES ($1, $2, $3, $4, $5, $6)"
+  );
+
+  info!(
+    target: AUDIT_TARGET,
+    "Admin '{user}' executed query: {query}",
+    user = user.email.as_deref().unwrap_or(&user.id),
+    query = request.query,
+  );
+
+  let params = request
+    .params
+    .as_ref()
+    .and_then(|p| serde_json::to_string(p).ok());
+
+  if let Err(err) = state
+    .conn()
+    .execute(
+      QUERY,
+      params!(
+        user.uuid.into_bytes().to_vec(),
+        request.query.clone(),
+        params,
+        request.explain.unwrap_or(false),
+        duration.as_millis() as i64,
+        error.map(|err| err.to_string()),
+      ),
+    )
+    .await
+  {
+    warn!("Failed to write admin query audit log: {err}");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+
+  fn admin() -> User {
+    let uuid = uuid::Uuid::now_v7();
+    return User {
+      id: crate::util::uuid_to_b64(&uuid),
+      email: Some("admin@localhost".to_string()),
+      username: None,
+      uuid,
+      csrf_token: "csrf".to_string(),
+      roles: vec![],
+      api_key: None,
+    };
+  }
+
+  async fn query(state: &AppState, request: QueryRequest) -> Result<QueryResponse, Error> {
+    return query_handler(State(state.clone()), admin(), Json(request))
+      .await
+      .map(|response| response.0);
+  }
+
+  #[tokio::test]
+  async fn test_query_console() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT) STRICT; \
+          INSERT INTO item (name) VALUES ('a'), ('b'), ('c'); \
+        ",
+      )
+      .await
+      .unwrap();
+
+    // Parameter binding.
+    let response = query(
+      &state,
+      QueryRequest {
+        query: "SELECT name FROM item WHERE id = $1".to_string(),
+        params: Some(vec![SqlValue::Integer(2)]),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.rows, vec![vec![SqlValue::Text("b".to_string())]]);
+    assert_eq!(response.columns.unwrap()[0].name, "name");
+
+    // Paging.
+    let response = query(
+      &state,
+      QueryRequest {
+        query: "SELECT id FROM item ORDER BY id;".to_string(),
+        limit: Some(2),
+        offset: Some(0),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.rows.len(), 2);
+    assert!(response.has_more);
+
+    let response = query(
+      &state,
+      QueryRequest {
+        query: "SELECT id FROM item ORDER BY id".to_string(),
+        limit: Some(2),
+        offset: Some(2),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.rows, vec![vec![SqlValue::Integer(3)]]);
+    assert!(!response.has_more);
+
+    // EXPLAIN.
+    let response = query(
+      &state,
+      QueryRequest {
+        query: "SELECT * FROM item WHERE id = 1".to_string(),
+        explain: Some(true),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+    assert!(response.columns.unwrap().iter().any(|c| c.name == "detail"));
+
+    // Timeout.
+    assert!(
+      query(
+        &state,
+        QueryRequest {
+          query: "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) \
+                  SELECT COUNT(*) FROM c"
+            .to_string(),
+          timeout_ms: Some(50),
+          ..Default::default()
+        },
+      )
+      .await
+      .is_err()
+    );
+
+    // Multiple statements cannot be parameterized.
+    assert!(
+      query(
+        &state,
+        QueryRequest {
+          query: "SELECT 1; SELECT $1".to_string(),
+          params: Some(vec![SqlValue::Integer(1)]),
+          ..Default::default()
+        },
+      )
+      .await
+      .is_err()
+    );
+
+    let logged: i64 = state
+      .conn()
+      .read_query_row_get(
+        format!("SELECT COUNT(*) FROM '{ADMIN_QUERY_LOG_TABLE}'"),
+        (),
+        0,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(logged, 6);
+  }
 }
@@ -0,0 +1,141 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use trailbase_sqlite::params;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::constants::TASK_QUEUE_TABLE;
+use crate::queue::{EnqueueOptions, TaskStatus};
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct QueuedTaskJson {
+  pub id: i64,
+  pub queue: String,
+  pub payload: String,
+  /// 0: pending, 2: failed, i.e. dead-lettered.
+  pub status: i64,
+  pub attempts: i64,
+  pub max_attempts: Option<i64>,
+  pub next_attempt: i64,
+  pub last_error: Option<String>,
+  pub created: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListTasksQuery {
+  /// Only list tasks of the given queue.
+  queue: Option<String>,
+  /// Only list dead-lettered tasks.
+  failed: Option<bool>,
+  limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListTasksResponse {
+  tasks: Vec<QueuedTaskJson>,
+}
+
+/// Lists pending and dead-lettered tasks. Completed tasks are removed from the queue.
+pub async fn list_tasks_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListTasksQuery>,
+) -> Result<Json<ListTasksResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, queue, payload, status, attempts, max_attempts, next_attempt, last_error, created \
+      FROM '{TASK_QUEUE_TABLE}' \
+      WHERE ($1 IS NULL OR status = $1) AND ($2 IS NULL OR queue = $2) \
+      ORDER BY id DESC LIMIT $3 \
+    "
+  );
+
+  let status = query
+    .failed
+    .unwrap_or(false)
+    .then_some(TaskStatus::Failed as i64);
+  let limit = query.limit.unwrap_or(100).min(1024) as i64;
+
+  return Ok(Json(ListTasksResponse {
+    tasks: state
+      .conn()
+      .read_query_values::<QueuedTaskJson>(QUERY, params!(status, query.queue, limit))
+      .await?,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct EnqueueTaskRequest {
+  pub queue: String,
+  pub payload: serde_json::Value,
+  /// Delay in seconds before the first attempt.
+  pub delay_sec: Option<u64>,
+  /// Overrides the configured maximum number of attempts.
+  pub max_attempts: Option<u32>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct EnqueueTaskResponse {
+  pub id: i64,
+}
+
+pub async fn enqueue_task_handler(
+  State(state): State<AppState>,
+  Json(request): Json<EnqueueTaskRequest>,
+) -> Result<Json<EnqueueTaskResponse>, Error> {
+  if request.queue.is_empty() {
+    return Err(Error::BadRequest("Missing queue".into()));
+  }
+
+  let id = state
+    .task_queue()
+    .enqueue(
+      &request.queue,
+      request.payload,
+      EnqueueOptions {
+        delay: request.delay_sec.map(Duration::from_secs),
+        max_attempts: request.max_attempts,
+      },
+    )
+    .await?;
+
+  return Ok(Json(EnqueueTaskResponse { id }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct TaskIdRequest {
+  pub id: i64,
+}
+
+/// Resets the given task, e.g. a dead-lettered one, for immediate processing.
+pub async fn requeue_task_handler(
+  State(state): State<AppState>,
+  Json(request): Json<TaskIdRequest>,
+) -> Result<(), Error> {
+  if !state.task_queue().requeue(request.id).await? {
+    return Err(Error::BadRequest("unknown task".into()));
+  }
+  return Ok(());
+}
+
+pub async fn delete_task_handler(
+  State(state): State<AppState>,
+  Json(request): Json<TaskIdRequest>,
+) -> Result<(), Error> {
+  const QUERY: &str = formatcp!("DELETE FROM '{TASK_QUEUE_TABLE}' WHERE id = $1");
+
+  if state.conn().execute(QUERY, params!(request.id)).await? == 0 {
+    return Err(Error::BadRequest("unknown task".into()));
+  }
+  return Ok(());
+}
@@ -0,0 +1,353 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::auth::util::get_user_roles;
+use crate::constants::{ROLES_TABLE, USER_ROLES_TABLE};
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct RoleJson {
+  pub name: String,
+  pub description: Option<String>,
+  pub created: i64,
+  /// Number of users the role is assigned to.
+  pub users: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListRolesResponse {
+  roles: Vec<RoleJson>,
+}
+
+pub async fn list_roles_handler(
+  State(state): State<AppState>,
+) -> Result<Json<ListRolesResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT \
+        r.name, r.description, r.created, \
+        (SELECT COUNT(*) FROM '{USER_ROLES_TABLE}' WHERE role = r.name) AS users \
+      FROM '{ROLES_TABLE}' AS r \
+      ORDER BY r.name \
+    "
+  );
+
+  return Ok(Json(ListRolesResponse {
+    roles: state
+      .user_conn()
+      .read_query_values::<RoleJson>(QUERY, ())
+      .await?,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct CreateRoleRequest {
+  /// Role name. May only contain alphanumeric characters, '_' and '-'.
+  pub name: String,
+  pub description: Option<String>,
+}
+
+pub async fn create_role_handler(
+  State(state): State<AppState>,
+  Json(request): Json<CreateRoleRequest>,
+) -> Result<Response, Error> {
+  validate_role_name(&request.name)?;
+
+  const QUERY: &str = formatcp!(
+    "INSERT INTO '{ROLES_TABLE}' (name, description) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+  );
+  let rows_affected = state
+    .user_conn()
+    .execute(QUERY, params!(request.name, request.description))
+    .await?;
+  if rows_affected == 0 {
+    return Err(Error::AlreadyExists("role"));
+  }
+
+  return Ok((StatusCode::OK, "created").into_response());
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct DeleteRoleRequest {
+  pub name: String,
+}
+
+/// Deletes a role and unassigns it from all users.
+pub async fn delete_role_handler(
+  State(state): State<AppState>,
+  Json(request): Json<DeleteRoleRequest>,
+) -> Result<Response, Error> {
+  const QUERY: &str = formatcp!("DELETE FROM '{ROLES_TABLE}' WHERE name = $1");
+  state
+    .user_conn()
+    .execute(QUERY, params!(request.name))
+    .await?;
+
+  return Ok((StatusCode::OK, "deleted").into_response());
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListUserRolesQuery {
+  user_id: Uuid,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListUserRolesResponse {
+  roles: Vec<String>,
+}
+
+pub async fn list_user_roles_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListUserRolesQuery>,
+) -> Result<Json<ListUserRolesResponse>, Error> {
+  return Ok(Json(ListUserRolesResponse {
+    roles: get_user_roles(state.user_conn(), query.user_id.as_bytes()).await?,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct UserRoleRequest {
+  pub user_id: String,
+  pub role: String,
+}
+
+/// Assigns a role to a user. Takes effect once the user's auth token gets refreshed.
+pub async fn assign_user_role_handler(
+  State(state): State<AppState>,
+  Json(request): Json<UserRoleRequest>,
+) -> Result<Response, Error> {
+  let user_id = Uuid::parse_str(&request.user_id).map_err(|err| Error::BadRequest(err.into()))?;
+
+  const QUERY: &str = formatcp!(
+    "\
+      INSERT INTO '{USER_ROLES_TABLE}' (user, role) \
+      SELECT $1, name FROM '{ROLES_TABLE}' WHERE name = $2 \
+      ON CONFLICT DO NOTHING \
+    "
+  );
+  let rows_affected = state
+    .user_conn()
+    .execute(QUERY, params!(user_id.into_bytes(), request.role.clone()))
+    .await?;
+
+  if rows_affected == 0 && !role_exists(&state, request.role).await? {
+    return Err(Error::BadRequest("unknown role".into()));
+  }
+
+  return Ok((StatusCode::OK, "assigned").into_response());
+}
+
+/// Unassigns a role from a user. Takes effect once the user's auth token gets refreshed.
+pub async fn unassign_user_role_handler(
+  State(state): State<AppState>,
+  Json(request): Json<UserRoleRequest>,
+) -> Result<Response, Error> {
+  let user_id = Uuid::parse_str(&request.user_id).map_err(|err| Error::BadRequest(err.into()))?;
+
+  const QUERY: &str = formatcp!("DELETE FROM '{USER_ROLES_TABLE}' WHERE user = $1 AND role = $2");
+  state
+    .user_conn()
+    .execute(QUERY, params!(user_id.into_bytes(), request.role))
+    .await?;
+
+  return Ok((StatusCode::OK, "unassigned").into_response());
+}
+
+async fn role_exists(state: &AppState, name: String) -> Result<bool, Error> {
+  const QUERY: &str = formatcp!("SELECT EXISTS(SELECT 1 FROM '{ROLES_TABLE}' WHERE name = $1)");
+  return Ok(
+    state
+      .user_conn()
+      .read_query_row_get::<bool>(QUERY, params!(name), 0)
+      .await?
+      .unwrap_or(false),
+  );
+}
+
+fn validate_role_name(name: &str) -> Result<(), Error> {
+  if name.is_empty() || name.len() > 64 {
+    return Err(Error::BadRequest("invalid role name length".into()));
+  }
+  if !name
+    .chars()
+    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+  {
+    return Err(Error::BadRequest(
+      "role names may only contain alphanumeric characters, '_' and '-'".into(),
+    ));
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::{Request, header};
+
+  use super::*;
+  use crate::auth::User;
+  use crate::auth::jwt::AuthTokenClaims;
+  use crate::auth::tokens::reauth_with_refresh_token;
+  use crate::auth::util::{UserIdentifier, login_with_password_for_test};
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::Permission;
+  use crate::records::params::LazyParams;
+  use crate::records::test_utils::add_record_api_config;
+
+  #[tokio::test]
+  async fn test_roles() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+
+    state
+      .conn()
+      .execute_batch("CREATE TABLE article (id INTEGER PRIMARY KEY, text TEXT) STRICT;")
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("article".to_string()),
+        table_name: Some("article".to_string()),
+        acl_authenticated: [PermissionFlag::Create as i32].into(),
+        create_access_rule: Some("_USER_.has_role('editor')".to_string()),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let password = "Secret!1!!";
+    let user_id = crate::admin::user::create_user_for_test(&state, "editor@test.org", password)
+      .await
+      .unwrap();
+
+    assert!(validate_role_name("bad role").is_err());
+
+    create_role_handler(
+      State(state.clone()),
+      Json(CreateRoleRequest {
+        name: "editor".to_string(),
+        description: Some("Can write articles".to_string()),
+      }),
+    )
+    .await
+    .unwrap();
+    assert!(
+      create_role_handler(
+        State(state.clone()),
+        Json(CreateRoleRequest {
+          name: "editor".to_string(),
+          description: None,
+        }),
+      )
+      .await
+      .is_err()
+    );
+
+    let assign = async |role: &str| {
+      return assign_user_role_handler(
+        State(state.clone()),
+        Json(UserRoleRequest {
+          user_id: user_id.to_string(),
+          role: role.to_string(),
+        }),
+      )
+      .await;
+    };
+    assert!(assign("unknown").await.is_err());
+    assign("editor").await.unwrap();
+    // Idempotent.
+    assign("editor").await.unwrap();
+
+    let roles = list_roles_handler(State(state.clone())).await.unwrap();
+    assert_eq!(roles.roles.len(), 1);
+    assert_eq!(roles.roles[0].users, 1);
+
+    // Roles are embedded into newly minted tokens.
+    let tokens = login_with_password_for_test(
+      &state,
+      UserIdentifier::Email("editor@test.org".to_string()),
+      password,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let claims = AuthTokenClaims::from_auth_token(state.jwt(), &tokens.auth_token).unwrap();
+    assert_eq!(claims.roles, vec!["editor".to_string()]);
+
+    let extract_user = async |auth_token: &str| {
+      let (mut parts, _) = Request::builder()
+        .header(header::AUTHORIZATION, format!("Bearer {auth_token}"))
+        .body(())
+        .unwrap()
+        .into_parts();
+      return <User as axum::extract::FromRequestParts<AppState>>::from_request_parts(
+        &mut parts, &state,
+      )
+      .await
+      .unwrap();
+    };
+
+    let api = state.lookup_record_api("article").unwrap();
+    let check_create_access = async |user: &User| {
+      let mut lazy_params = LazyParams::for_insert(
+        &api,
+        state.json_schema_registry().clone(),
+        serde_json::json!({"text": "foo"})
+          .as_object()
+          .unwrap()
+          .clone(),
+        None,
+      );
+      return api
+        .check_record_level_access(Permission::Create, None, Some(&mut lazy_params), Some(user))
+        .await;
+    };
+
+    let user = extract_user(&tokens.auth_token).await;
+    assert!(check_create_access(&user).await.is_ok());
+
+    // Role changes take effect on refresh.
+    unassign_user_role_handler(
+      State(state.clone()),
+      Json(UserRoleRequest {
+        user_id: user_id.to_string(),
+        role: "editor".to_string(),
+      }),
+    )
+    .await
+    .unwrap();
+    assert!(
+      list_user_roles_handler(State(state.clone()), Query(ListUserRolesQuery { user_id }))
+        .await
+        .unwrap()
+        .roles
+        .is_empty()
+    );
+
+    let (claims, _) = reauth_with_refresh_token(&state, tokens.refresh_token)
+      .await
+      .unwrap();
+    assert!(claims.roles.is_empty());
+
+    let user = User::from_token_claims(claims).unwrap();
+    assert!(check_create_access(&user).await.is_err());
+  }
+}
@@ -70,7 +70,7 @@ pub(crate) async fn delete_row(
 
   run_delete_query(
     &conn,
-    state.objectstore(),
+    &state.objectstore(),
     &QualifiedNameEscaped::from(&table_metadata.schema.name),
     pk_col,
     pk_value.try_into()?,
@@ -149,6 +149,7 @@ mod tests {
 
     let _ = create_table_handler(
       State(state.clone()),
+      crate::auth::User::from_unverified(Uuid::now_v7(), None, None),
       Json(CreateTableRequest {
         schema: Table {
           name: QualifiedName::parse(&table_name).unwrap(),
@@ -0,0 +1,660 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use trailbase_schema::json::{flat_json_to_value, parse_string_to_sqlite_value};
+use trailbase_schema::metadata::TableMetadata;
+use trailbase_schema::sqlite::{Column, ColumnDataType};
+use trailbase_schema::{QualifiedName, QualifiedNameEscaped};
+use trailbase_sqlite::Value;
+use trailbase_sqlite::traits::{SyncConnection, SyncTransaction};
+use trailbase_sqlvalue::SqlValue;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::connection::ConnectionEntry;
+
+const DEFAULT_CHUNK_SIZE: usize = 500;
+const MAX_CHUNK_SIZE: usize = 10_000;
+const PREVIEW_ROWS: usize = 10;
+/// Caps the number of reported row errors, the counts remain accurate.
+const MAX_REPORTED_ERRORS: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum ImportFormat {
+  /// Comma-separated values with a header row.
+  #[default]
+  Csv,
+  /// Newline-delimited JSON objects.
+  Ndjson,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportColumnMapping {
+  /// Column in the imported file.
+  pub source: String,
+  /// Column of the table the source column is imported into. Unmapped columns are skipped.
+  pub target: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportRowError {
+  /// 1-based index of the data row in the imported file, i.e. not counting the CSV header.
+  pub row: usize,
+  pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportPreviewRequest {
+  pub format: ImportFormat,
+  /// Content of the file to be imported.
+  pub data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportPreviewResponse {
+  /// Inferred mapping of source columns onto the table's columns.
+  pub mapping: Vec<ImportColumnMapping>,
+  /// The first few rows converted according to the mapping, ordered like `mapping`.
+  pub sample: Vec<Vec<SqlValue>>,
+  pub total_rows: usize,
+  /// Rows that cannot be converted to the target columns' types.
+  pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportRowsRequest {
+  pub format: ImportFormat,
+  /// Content of the file to be imported.
+  pub data: String,
+
+  /// Mapping of source columns onto the table's columns. Inferred, if absent.
+  pub mapping: Option<Vec<ImportColumnMapping>>,
+  /// Update existing rows on primary key conflicts rather than failing them.
+  pub upsert: Option<bool>,
+  /// Number of rows imported per transaction. Defaults to 500.
+  pub chunk_size: Option<usize>,
+  /// Validates the import without committing any rows.
+  pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportRowsResponse {
+  pub imported: usize,
+  pub failed: usize,
+  pub errors: Vec<ImportRowError>,
+}
+
+/// Infers how an uploaded CSV or NDJSON file maps onto a table and previews the first rows.
+pub async fn import_preview_handler(
+  State(state): State<AppState>,
+  Path(table_name): Path<String>,
+  Json(request): Json<ImportPreviewRequest>,
+) -> Result<Json<ImportPreviewResponse>, Error> {
+  let table_name = QualifiedName::parse(&table_name)?;
+  let ConnectionEntry { metadata, .. } = state
+    .connection_manager()
+    .get_entry_for_qn(&table_name)
+    .await?;
+  let Some(table_metadata) = metadata.get_table(&table_name) else {
+    return Err(Error::Precondition(format!(
+      "Table {table_name:?} not found"
+    )));
+  };
+
+  let source = parse_source(request.format, &request.data)?;
+  let mapping = infer_mapping(&source.columns, table_metadata);
+  let targets = resolve_targets(&mapping, &source.columns, table_metadata)?;
+
+  let mut sample = vec![];
+  let mut errors = vec![];
+  for (i, record) in source.rows.into_iter().enumerate() {
+    match convert_row(request.format, &targets, record) {
+      Ok(values) => {
+        if sample.len() < PREVIEW_ROWS {
+          sample.push(mapping_order(&mapping, &targets, values));
+        }
+      }
+      Err(error) => {
+        if errors.len() < MAX_REPORTED_ERRORS {
+          errors.push(ImportRowError { row: i + 1, error });
+        }
+      }
+    }
+  }
+
+  return Ok(Json(ImportPreviewResponse {
+    mapping,
+    sample,
+    total_rows: source.total_rows,
+    errors,
+  }));
+}
+
+/// Imports a CSV or NDJSON file into a table.
+///
+/// Rows are inserted in chunks, each in its own transaction. Rows failing conversion or
+/// constraints are skipped and reported, without affecting the remaining rows.
+pub async fn import_rows_handler(
+  State(state): State<AppState>,
+  Path(table_name): Path<String>,
+  Json(request): Json<ImportRowsRequest>,
+) -> Result<Json<ImportRowsResponse>, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  let chunk_size = request.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+  if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+    return Err(Error::BadRequest(
+      format!("chunk_size must be between 1 and {MAX_CHUNK_SIZE}").into(),
+    ));
+  }
+
+  let table_name = QualifiedName::parse(&table_name)?;
+  let ConnectionEntry {
+    connection: conn,
+    metadata,
+  } = state
+    .connection_manager()
+    .get_entry_for_qn(&table_name)
+    .await?;
+  let Some(table_metadata) = metadata.get_table(&table_name) else {
+    return Err(Error::Precondition(format!(
+      "Table {table_name:?} not found"
+    )));
+  };
+
+  let source = parse_source(request.format, &request.data)?;
+  let mapping = match request.mapping {
+    Some(mapping) => mapping,
+    None => infer_mapping(&source.columns, table_metadata),
+  };
+  let targets = resolve_targets(&mapping, &source.columns, table_metadata)?;
+  if targets.is_empty() {
+    return Err(Error::BadRequest("No columns mapped".into()));
+  }
+
+  let query = build_insert_query(table_metadata, &targets, request.upsert.unwrap_or(false))?;
+  let dry_run = request.dry_run.unwrap_or(false);
+
+  let mut response = ImportRowsResponse::default();
+  let mut rows = source.rows.into_iter().enumerate().peekable();
+  while rows.peek().is_some() {
+    let mut chunk: Vec<(usize, Vec<Value>)> = vec![];
+    for (i, record) in rows.by_ref().take(chunk_size) {
+      match convert_row(request.format, &targets, record) {
+        Ok(values) => chunk.push((i + 1, values)),
+        Err(err) => response.report(i + 1, err),
+      }
+    }
+
+    let query = query.clone();
+    let results = conn
+      .transaction(
+        move |mut tx| -> Result<Vec<(usize, Option<String>)>, trailbase_sqlite::Error> {
+          let mut results = Vec::with_capacity(chunk.len());
+          for (row, values) in chunk {
+            // Isolate every row, so that a failing row doesn't abort the entire chunk.
+            tx.execute_batch("SAVEPOINT import_row")?;
+            match tx.execute(&query, values) {
+              Ok(_) => {
+                tx.execute_batch("RELEASE SAVEPOINT import_row")?;
+                results.push((row, None));
+              }
+              Err(err) => {
+                tx.execute_batch("ROLLBACK TO SAVEPOINT import_row; RELEASE SAVEPOINT import_row")?;
+                results.push((row, Some(err.to_string())));
+              }
+            }
+          }
+
+          if dry_run {
+            tx.rollback()?;
+          } else {
+            tx.commit()?;
+          }
+          return Ok(results);
+        },
+      )
+      .await?;
+
+    for (row, error) in results {
+      match error {
+        None => response.imported += 1,
+        Some(err) => response.report(row, err),
+      }
+    }
+  }
+
+  return Ok(Json(response));
+}
+
+impl ImportRowsResponse {
+  fn report(&mut self, row: usize, error: String) {
+    self.failed += 1;
+    if self.errors.len() < MAX_REPORTED_ERRORS {
+      self.errors.push(ImportRowError { row, error });
+    }
+  }
+}
+
+struct Source {
+  columns: Vec<String>,
+  rows: Vec<Vec<serde_json::Value>>,
+  total_rows: usize,
+}
+
+/// Parses the imported file into rows of values ordered like `columns`. CSV values are always
+/// strings, NDJSON values are whatever the JSON holds.
+fn parse_source(format: ImportFormat, data: &str) -> Result<Source, Error> {
+  return match format {
+    ImportFormat::Csv => {
+      let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+
+      let columns: Vec<String> = reader
+        .headers()
+        .map_err(|err| Error::BadRequest(err.into()))?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+
+      let rows = reader
+        .records()
+        .map(|record| {
+          let record = record.map_err(|err| Error::BadRequest(err.into()))?;
+          return Ok(
+            (0..columns.len())
+              .map(|i| match record.get(i) {
+                Some(value) => serde_json::Value::String(value.to_string()),
+                None => serde_json::Value::Null,
+              })
+              .collect(),
+          );
+        })
+        .collect::<Result<Vec<Vec<_>>, Error>>()?;
+
+      let total_rows = rows.len();
+      Ok(Source {
+        columns,
+        rows,
+        total_rows,
+      })
+    }
+    ImportFormat::Ndjson => {
+      let objects = data
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+          return match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(serde_json::Value::Object(map)) => Ok(map),
+            Ok(_) => Err(Error::BadRequest(
+              format!("Row {}: expected JSON object", i + 1).into(),
+            )),
+            Err(err) => Err(Error::BadRequest(format!("Row {}: {err}", i + 1).into())),
+          };
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+      // Columns in order of first appearance.
+      let mut columns: Vec<String> = vec![];
+      for object in &objects {
+        for key in object.keys() {
+          if !columns.contains(key) {
+            columns.push(key.clone());
+          }
+        }
+      }
+
+      let rows: Vec<Vec<serde_json::Value>> = objects
+        .into_iter()
+        .map(|mut object| {
+          return columns
+            .iter()
+            .map(|c| object.remove(c).unwrap_or(serde_json::Value::Null))
+            .collect();
+        })
+        .collect();
+
+      let total_rows = rows.len();
+      Ok(Source {
+        columns,
+        rows,
+        total_rows,
+      })
+    }
+  };
+}
+
+/// Matches source columns to table columns ignoring case and non-alphanumeric characters, e.g.
+/// "First Name" maps onto "first_name".
+fn infer_mapping(columns: &[String], table_metadata: &TableMetadata) -> Vec<ImportColumnMapping> {
+  fn normalize(name: &str) -> String {
+    return name
+      .chars()
+      .filter(|c| c.is_alphanumeric())
+      .flat_map(char::to_lowercase)
+      .collect();
+  }
+
+  let mut used: Vec<&str> = vec![];
+  return columns
+    .iter()
+    .map(|source| {
+      let target = table_metadata
+        .column_metadata
+        .iter()
+        .map(|meta| meta.column.name.as_str())
+        .find(|name| *name == source.as_str())
+        .or_else(|| {
+          let normalized = normalize(source);
+          return table_metadata
+            .column_metadata
+            .iter()
+            .map(|meta| meta.column.name.as_str())
+            .find(|name| !used.contains(name) && normalize(name) == normalized);
+        })
+        .filter(|name| !used.contains(name));
+
+      if let Some(target) = target {
+        used.push(target);
+      }
+
+      return ImportColumnMapping {
+        source: source.clone(),
+        target: target.map(str::to_string),
+      };
+    })
+    .collect();
+}
+
+/// Resolves the mapping into (source index, target column) pairs.
+fn resolve_targets(
+  mapping: &[ImportColumnMapping],
+  columns: &[String],
+  table_metadata: &TableMetadata,
+) -> Result<Vec<(usize, Column)>, Error> {
+  let source_index: HashMap<&str, usize> = columns
+    .iter()
+    .enumerate()
+    .map(|(i, c)| (c.as_str(), i))
+    .collect();
+
+  let mut targets: Vec<(usize, Column)> = vec![];
+  for ImportColumnMapping { source, target } in mapping {
+    let Some(target) = target else {
+      continue;
+    };
+    let Some(index) = source_index.get(source.as_str()) else {
+      return Err(Error::BadRequest(
+        format!("Source column '{source}' not found").into(),
+      ));
+    };
+    let Some(meta) = table_metadata.column_by_name(target) else {
+      return Err(Error::BadRequest(
+        format!("Target column '{target}' not found").into(),
+      ));
+    };
+    if targets.iter().any(|(_, c)| c.name == meta.column.name) {
+      return Err(Error::BadRequest(
+        format!("Target column '{target}' mapped more than once").into(),
+      ));
+    }
+    targets.push((*index, meta.column.clone()));
+  }
+
+  return Ok(targets);
+}
+
+/// Converts a source row into values for the target columns.
+fn convert_row(
+  format: ImportFormat,
+  targets: &[(usize, Column)],
+  mut record: Vec<serde_json::Value>,
+) -> Result<Vec<Value>, String> {
+  return targets
+    .iter()
+    .map(|(index, column)| {
+      let value = std::mem::take(&mut record[*index]);
+      return convert_value(format, column, value)
+        .map_err(|err| format!("Column '{}': {err}", column.name));
+    })
+    .collect();
+}
+
+fn convert_value(
+  format: ImportFormat,
+  column: &Column,
+  value: serde_json::Value,
+) -> Result<Value, trailbase_schema::json::JsonError> {
+  let data_type = column.data_type;
+  return match (format, value) {
+    // CSV cannot distinguish NULL from empty strings. Only text columns keep the empty string.
+    (ImportFormat::Csv, serde_json::Value::String(s))
+      if s.is_empty() && data_type != ColumnDataType::Text =>
+    {
+      Ok(Value::Null)
+    }
+    (ImportFormat::Csv, serde_json::Value::String(s)) => match data_type {
+      ColumnDataType::Integer | ColumnDataType::Real => {
+        parse_string_to_sqlite_value(data_type, s.trim().to_string())
+      }
+      _ => parse_string_to_sqlite_value(data_type, s),
+    },
+    // Nested objects and arrays are stored as JSON text, e.g. for JSON columns.
+    (
+      ImportFormat::Ndjson,
+      value @ (serde_json::Value::Object(_) | serde_json::Value::Array(_)),
+    ) if matches!(data_type, ColumnDataType::Text | ColumnDataType::Any) => {
+      Ok(Value::Text(value.to_string()))
+    }
+    (_, value) => flat_json_to_value(data_type, value),
+  };
+}
+
+/// Orders converted values like the mapping, unmapped source columns are NULL.
+fn mapping_order(
+  mapping: &[ImportColumnMapping],
+  targets: &[(usize, Column)],
+  values: Vec<Value>,
+) -> Vec<SqlValue> {
+  let mut values: Vec<Option<Value>> = values.into_iter().map(Some).collect();
+  return mapping
+    .iter()
+    .map(|m| {
+      return m
+        .target
+        .as_ref()
+        .and_then(|target| targets.iter().position(|(_, c)| &c.name == target))
+        .and_then(|i| values[i].take())
+        .map_or(SqlValue::Null, SqlValue::from);
+    })
+    .collect();
+}
+
+fn build_insert_query(
+  table_metadata: &TableMetadata,
+  targets: &[(usize, Column)],
+  upsert: bool,
+) -> Result<String, Error> {
+  let column_names = targets
+    .iter()
+    .map(|(_, c)| format!(r#""{}""#, c.name))
+    .collect::<Vec<_>>()
+    .join(", ");
+  let placeholders = (1..=targets.len())
+    .map(|i| format!("${i}"))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  let mut query = format!(
+    "INSERT INTO {table} ({column_names}) VALUES ({placeholders})",
+    table = QualifiedNameEscaped::new(&table_metadata.schema.name),
+  );
+
+  if upsert {
+    let pk_columns: Vec<&str> = table_metadata
+      .schema
+      .columns
+      .iter()
+      .filter(|c| c.is_primary())
+      .map(|c| c.name.as_str())
+      .collect();
+    if pk_columns.is_empty() {
+      return Err(Error::BadRequest("Upsert requires a primary key".into()));
+    }
+
+    let updates: Vec<String> = targets
+      .iter()
+      .filter(|(_, c)| !pk_columns.contains(&c.name.as_str()))
+      .map(|(_, c)| format!(r#""{name}" = excluded."{name}""#, name = c.name))
+      .collect();
+
+    let conflict_target = pk_columns
+      .iter()
+      .map(|c| format!(r#""{c}""#))
+      .collect::<Vec<_>>()
+      .join(", ");
+    if updates.is_empty() {
+      query.push_str(&format!(" ON CONFLICT ({conflict_target}) DO NOTHING"));
+    } else {
+      query.push_str(&format!(
+        " ON CONFLICT ({conflict_target}) DO UPDATE SET {}",
+        updates.join(", ")
+      ));
+    }
+  }
+
+  return Ok(query);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[tokio::test]
+  async fn test_import_rows() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE contact ( \
+            id          INTEGER PRIMARY KEY, \
+            first_name  TEXT NOT NULL CHECK(first_name <> ''), \
+            age         INTEGER, \
+            meta        TEXT \
+          ) STRICT; \
+          INSERT INTO contact (id, first_name) VALUES (1, 'Old'); \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    let csv = "ID,First Name,Age,ignored\n1,Alice,30,x\n2,Bob,,y\n3,Carl,old,z\n,,5,w\n";
+
+    let Json(preview) = import_preview_handler(
+      State(state.clone()),
+      Path("contact".to_string()),
+      Json(ImportPreviewRequest {
+        format: ImportFormat::Csv,
+        data: csv.to_string(),
+      }),
+    )
+    .await
+    .unwrap();
+    let targets: Vec<_> = preview
+      .mapping
+      .iter()
+      .map(|m| m.target.as_deref())
+      .collect();
+    assert_eq!(
+      targets,
+      vec![Some("id"), Some("first_name"), Some("age"), None]
+    );
+    assert_eq!(preview.total_rows, 4);
+    assert_eq!(
+      preview.sample[1],
+      vec![
+        SqlValue::Integer(2),
+        SqlValue::Text("Bob".to_string()),
+        SqlValue::Null,
+        SqlValue::Null
+      ]
+    );
+    assert_eq!(preview.errors.len(), 1);
+    assert_eq!(preview.errors[0].row, 3);
+
+    let import = async |data: &str, format: ImportFormat, upsert: bool| {
+      return import_rows_handler(
+        State(state.clone()),
+        Path("contact".to_string()),
+        Json(ImportRowsRequest {
+          format,
+          data: data.to_string(),
+          mapping: None,
+          upsert: Some(upsert),
+          chunk_size: Some(2),
+          dry_run: None,
+        }),
+      )
+      .await
+      .unwrap()
+      .0;
+    };
+
+    // Row 1 conflicts with the existing row, row 3 fails conversion and row 4 the CHECK
+    // constraint. Conversion errors are reported before the chunk is executed.
+    let response = import(csv, ImportFormat::Csv, false).await;
+    assert_eq!(response.imported, 1);
+    assert_eq!(response.failed, 3);
+    assert_eq!(
+      response.errors.iter().map(|e| e.row).collect::<Vec<_>>(),
+      vec![1, 3, 4]
+    );
+
+    let response = import(csv, ImportFormat::Csv, true).await;
+    assert_eq!(response.imported, 2);
+    assert_eq!(response.failed, 2);
+
+    let name: String = state
+      .conn()
+      .read_query_row_get("SELECT first_name FROM contact WHERE id = 1", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(name, "Alice");
+
+    let response = import(
+      "{\"id\": 10, \"first_name\": \"Dora\", \"meta\": {\"vip\": true}}\n\n{\"first_name\": 5}\n",
+      ImportFormat::Ndjson,
+      false,
+    )
+    .await;
+    assert_eq!(response.imported, 1);
+    assert_eq!(response.failed, 1);
+
+    let meta: String = state
+      .conn()
+      .read_query_row_get("SELECT meta FROM contact WHERE id = 10", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(meta, r#"{"vip":true}"#);
+  }
+}
@@ -46,7 +46,7 @@ pub async fn insert_row_handler(
 
   let rowid_value = run_insert_or_replace_query(
     &conn,
-    state.objectstore(),
+    &state.objectstore(),
     &QualifiedNameEscaped::new(&table_metadata.schema.name),
     &table_metadata.column_metadata,
     crate::config::proto::ConflictResolutionStrategy::Abort,
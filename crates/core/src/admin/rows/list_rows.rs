@@ -4,11 +4,13 @@ use serde::Serialize;
 use std::borrow::Cow;
 use trailbase_qs::{Cursor, CursorType, Order, OrderPrecedent, Query};
 use trailbase_schema::QualifiedName;
-use trailbase_schema::sqlite::{Column, ColumnDataType};
+use trailbase_schema::sqlite::{Column, ColumnDataType, TableIndex};
+use trailbase_sqlite::ConnectionType;
 use trailbase_sqlvalue::SqlValue;
 use ts_rs::TS;
 
 use crate::admin::AdminError as Error;
+use crate::admin::table::list_indexes;
 use crate::admin::util::rows_to_sql_value_rows;
 use crate::app_state::AppState;
 use crate::connection::ConnectionEntry;
@@ -23,6 +25,9 @@ pub struct ListRowsResponse {
   /// Actual row data.
   pub rows: Vec<Vec<SqlValue>>,
 
+  /// Indexes on the table, empty for views.
+  pub indexes: Vec<TableIndex>,
+
   /// Total number of records.
   pub total_row_count: i64,
   /// Next cursor for pagination.
@@ -84,13 +89,19 @@ pub async fn list_rows_handler(
     }
   };
 
+  // Rows are read from the read replica, if configured, to not contend with writes.
+  let reader = state
+    .connection_manager()
+    .get_reader_for_qn(&qualified_name)
+    .await?;
+
   let total_row_count: i64 = {
     let where_clause = &filter_where_clause.clause;
     let count_query = format!(
       "SELECT COUNT(*) FROM {fq_name} AS _ROW_ WHERE {where_clause}",
       fq_name = qualified_name.escaped_string()
     );
-    conn
+    reader
       .read_query_row_get(count_query, filter_where_clause.params.clone(), 0)
       .await?
       .unwrap_or(-1)
@@ -102,7 +113,7 @@ pub async fn list_rows_handler(
     _ => None,
   };
   let (rows, columns) = fetch_rows(
-    &conn,
+    &reader,
     &qualified_name,
     filter_where_clause,
     &order,
@@ -132,7 +143,17 @@ pub async fn list_rows_handler(
     None
   };
 
+  let indexes = match conn.connection_type() {
+    ConnectionType::Sqlite => list_indexes(&state, &qualified_name)
+      .await?
+      .into_iter()
+      .map(|(index, _sql)| index)
+      .collect(),
+    ConnectionType::Pg => vec![],
+  };
+
   return Ok(Json(ListRowsResponse {
+    indexes,
     total_row_count,
     cursor: next_cursor,
     // NOTE: in the view case we don't have a good way of extracting the columns from the "CREATE
@@ -253,7 +274,6 @@ fn parse_cursor(cursor: &str, pk_col: &Column) -> Result<Cursor, Error> {
 #[cfg(test)]
 mod tests {
   use base64::prelude::*;
-  use trailbase_sqlite::ConnectionType;
   use trailbase_sqlvalue::Blob;
 
   use super::*;
@@ -1,11 +1,15 @@
 mod delete_rows;
+mod import_rows;
 mod insert_row;
 mod list_rows;
 mod read_files;
+mod seed_rows;
 mod update_row;
 
 pub(super) use delete_rows::{delete_row, delete_row_handler, delete_rows_handler};
+pub(super) use import_rows::{import_preview_handler, import_rows_handler};
 pub(super) use insert_row::insert_row_handler;
 pub(super) use list_rows::list_rows_handler;
 pub(super) use read_files::read_files_handler;
+pub(super) use seed_rows::seed_rows_handler;
 pub(super) use update_row::update_row_handler;
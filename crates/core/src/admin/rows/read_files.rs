@@ -1,5 +1,6 @@
 use axum::{
   extract::{Path, Query, State},
+  http::HeaderMap,
   response::Response,
 };
 use serde::Deserialize;
@@ -80,8 +81,8 @@ pub async fn read_files_handler(
       return Err(Error::Precondition(format!("File '{filename}' not found")));
     };
 
-    Ok(read_file_into_response(&state, file).await?)
+    Ok(read_file_into_response(&state, file, &HeaderMap::new()).await?)
   } else {
-    Ok(read_file_into_response(&state, file_uploads.remove(0)).await?)
+    Ok(read_file_into_response(&state, file_uploads.remove(0), &HeaderMap::new()).await?)
   };
 }
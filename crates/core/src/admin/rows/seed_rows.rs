@@ -0,0 +1,422 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use trailbase_schema::metadata::{ColumnMetadata, ConnectionMetadata, JsonColumnMetadata};
+use trailbase_schema::registry::JsonSchemaRegistry;
+use trailbase_schema::sqlite::{ColumnDataType, ColumnOption};
+use trailbase_schema::{QualifiedName, QualifiedNameEscaped};
+use trailbase_sqlite::Connection;
+use trailbase_sqlvalue::{Blob, SqlValue};
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::config::proto::ConflictResolutionStrategy;
+use crate::connection::ConnectionEntry;
+use crate::rand::random_alphanumeric;
+use crate::records::params::Params;
+use crate::records::write_queries::{WriteQuery, run_queries};
+use crate::util::row_id_column;
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SeedRowsRequest {
+  /// Number of fake rows to insert.
+  pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SeedRowsResponse {
+  pub row_count: usize,
+}
+
+/// Populates a table with random rows, e.g. for testing or development.
+///
+/// Values respect the columns' types, NOT NULL, JSON schemas and foreign keys, i.e. references are
+/// drawn from rows already present in the referenced tables. Columns with defaults or generated
+/// values are left to the database. Other CHECK constraints are not taken into account and may
+/// lead to the insertion failing.
+pub async fn seed_rows_handler(
+  State(state): State<AppState>,
+  Path(table_name): Path<String>,
+  Json(request): Json<SeedRowsRequest>,
+) -> Result<Json<SeedRowsResponse>, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  if request.count == 0 || request.count > MAX_SEED_ROWS {
+    return Err(Error::BadRequest(
+      format!("count must be between 1 and {MAX_SEED_ROWS}").into(),
+    ));
+  }
+
+  let table_name = QualifiedName::parse(&table_name)?;
+  let ConnectionEntry {
+    connection: conn,
+    metadata,
+  } = state
+    .connection_manager()
+    .get_entry_for_qn(&table_name)
+    .await?;
+
+  let Some(table_metadata) = metadata.get_table(&table_name) else {
+    return Err(Error::Precondition(format!(
+      "Table {table_name:?} not found"
+    )));
+  };
+
+  let mut generators: Vec<(&ColumnMetadata, ValueGenerator)> = vec![];
+  for meta in &table_metadata.column_metadata {
+    if let Some(generator) = ValueGenerator::new(&conn, &metadata, meta).await? {
+      generators.push((meta, generator));
+    }
+  }
+
+  let queries = {
+    let registry = state.json_schema_registry().read();
+    let mut rng = rand::rng();
+
+    (0..request.count)
+      .map(|_| {
+        let row = generators
+          .iter()
+          .map(|(meta, generator)| {
+            return Ok((
+              meta.column.name.clone(),
+              generator.generate(&mut rng, &registry, meta)?,
+            ));
+          })
+          .collect::<Result<indexmap::IndexMap<_, _>, Error>>()?;
+
+        let (query, _files) = WriteQuery::new_insert_or_replace(
+          conn.connection_type(),
+          &QualifiedNameEscaped::new(&table_metadata.schema.name),
+          &table_metadata.column_metadata,
+          row_id_column(&conn),
+          ConflictResolutionStrategy::Abort,
+          Params::for_admin_insert(table_metadata, row)?,
+        )?;
+
+        return Ok((query, None));
+      })
+      .collect::<Result<Vec<_>, Error>>()?
+  };
+
+  let row_count = run_queries(&conn, &state.objectstore(), queries)
+    .await?
+    .len();
+
+  return Ok(Json(SeedRowsResponse { row_count }));
+}
+
+enum ValueGenerator {
+  Random,
+  /// Picks one of the values already present in the referenced table.
+  ForeignKey(Vec<SqlValue>),
+  Json(JsonColumnMetadata),
+}
+
+impl ValueGenerator {
+  /// Returns `None` for columns that should be populated by the database.
+  async fn new(
+    conn: &Connection,
+    metadata: &ConnectionMetadata,
+    meta: &ColumnMetadata,
+  ) -> Result<Option<Self>, Error> {
+    let column = &meta.column;
+    let rowid_alias = column.data_type == ColumnDataType::Integer && column.is_primary();
+    let generated = column
+      .options
+      .iter()
+      .any(|opt| matches!(opt, ColumnOption::Generated { .. }));
+    if rowid_alias || generated || column.has_default() {
+      return Ok(None);
+    }
+
+    if let Some((foreign_table, foreign_column)) = foreign_key(metadata, meta) {
+      let values: Vec<SqlValue> = conn
+        .read_query_rows(
+          format!(
+            r#"SELECT "{foreign_column}" FROM {table} ORDER BY RANDOM() LIMIT {MAX_SEED_ROWS}"#,
+            table = QualifiedNameEscaped::new(&foreign_table)
+          ),
+          (),
+        )
+        .await?
+        .iter()
+        .filter_map(|row| row.get_value(0).map(SqlValue::from))
+        .collect();
+
+      if values.is_empty() {
+        if column.is_not_null() {
+          return Err(Error::Precondition(format!(
+            "Cannot seed '{}': referenced table {foreign_table:?} is empty",
+            column.name
+          )));
+        }
+        return Ok(None);
+      }
+      return Ok(Some(Self::ForeignKey(values)));
+    }
+
+    if meta.is_file || meta.is_geometry {
+      if column.is_not_null() {
+        return Err(Error::Precondition(format!(
+          "Cannot seed '{}': unsupported column type",
+          column.name
+        )));
+      }
+      return Ok(None);
+    }
+
+    if let Some(ref json) = meta.json {
+      return Ok(Some(Self::Json(json.clone())));
+    }
+
+    return Ok(Some(Self::Random));
+  }
+
+  fn generate(
+    &self,
+    rng: &mut impl rand::Rng,
+    registry: &JsonSchemaRegistry,
+    meta: &ColumnMetadata,
+  ) -> Result<SqlValue, Error> {
+    return match self {
+      Self::ForeignKey(values) => Ok(values[rng.random_range(0..values.len())].clone()),
+      Self::Json(json) => {
+        let schema = match json {
+          JsonColumnMetadata::SchemaName(name) => registry
+            .get_schema(name)
+            .map(|entry| entry.schema.clone())
+            .ok_or_else(|| Error::Precondition(format!("JSON schema '{name}' not found")))?,
+          JsonColumnMetadata::Pattern(pattern) => pattern.clone(),
+        };
+
+        let value = fake_json(rng, &schema, 0);
+        json.validate(registry, &value).map_err(|_err| {
+          Error::Precondition(format!(
+            "Cannot seed '{}': failed to generate value matching JSON schema",
+            meta.column.name
+          ))
+        })?;
+
+        Ok(SqlValue::Text(value.to_string()))
+      }
+      Self::Random => Ok(match meta.column.data_type {
+        ColumnDataType::Integer => SqlValue::Integer(rng.random_range(0..1_000_000)),
+        ColumnDataType::Real => SqlValue::Real(rng.random_range(0.0..1_000_000.0)),
+        // UUIDv7s satisfy `is_uuid` as well as `is_uuid_v7` checks.
+        ColumnDataType::Blob => {
+          SqlValue::Blob(Blob::Array(uuid::Uuid::now_v7().into_bytes().to_vec()))
+        }
+        ColumnDataType::Text | ColumnDataType::Any => {
+          SqlValue::Text(format!("{}_{}", meta.column.name, random_alphanumeric(8)))
+        }
+      }),
+    };
+  }
+}
+
+/// Returns the referenced table and column if `meta` is a foreign key column.
+fn foreign_key(
+  metadata: &ConnectionMetadata,
+  meta: &ColumnMetadata,
+) -> Option<(QualifiedName, String)> {
+  return meta.column.options.iter().find_map(|opt| {
+    let ColumnOption::ForeignKey {
+      foreign_table,
+      referred_columns,
+      ..
+    } = opt
+    else {
+      return None;
+    };
+
+    let foreign_table = QualifiedName::parse(foreign_table).ok()?;
+    let foreign_column = match referred_columns.first() {
+      Some(column) => column.clone(),
+      None => {
+        // Implicitly references the primary key.
+        let foreign_metadata = metadata.get_table(&foreign_table)?;
+        foreign_metadata
+          .schema
+          .columns
+          .iter()
+          .find(|column| column.is_primary())?
+          .name
+          .clone()
+      }
+    };
+
+    return Some((foreign_table, foreign_column));
+  });
+}
+
+/// Generates a random JSON value for the given JSON schema on a best-effort basis.
+fn fake_json(
+  rng: &mut impl rand::Rng,
+  schema: &serde_json::Value,
+  depth: usize,
+) -> serde_json::Value {
+  use serde_json::{Value, json};
+
+  let Value::Object(schema) = schema else {
+    return Value::Null;
+  };
+
+  if let Some(value) = schema.get("const") {
+    return value.clone();
+  }
+  if let Some(Value::Array(values)) = schema.get("enum")
+    && !values.is_empty()
+  {
+    return values[rng.random_range(0..values.len())].clone();
+  }
+
+  let r#type = match schema.get("type") {
+    Some(Value::String(t)) => t.as_str(),
+    Some(Value::Array(types)) => types.first().and_then(|t| t.as_str()).unwrap_or("null"),
+    _ => "object",
+  };
+
+  let number = |key: &str| schema.get(key).and_then(|v| v.as_f64());
+
+  return match r#type {
+    "null" => Value::Null,
+    "boolean" => Value::Bool(rng.random_bool(0.5)),
+    "integer" => {
+      let min = number("minimum").unwrap_or(0.0) as i64;
+      let max = number("maximum").map_or(min + 1000, |max| max as i64);
+      json!(rng.random_range(min..=max.max(min)))
+    }
+    "number" => {
+      let min = number("minimum").unwrap_or(0.0);
+      let max = number("maximum").unwrap_or(min + 1000.0).max(min);
+      json!(rng.random_range(min..=max))
+    }
+    "string" => {
+      let min = number("minLength").unwrap_or(1.0) as usize;
+      let max = number("maxLength").map_or(min.max(12), |max| max as usize);
+      Value::String(random_alphanumeric(min.max(max.min(12))))
+    }
+    "array" => {
+      if depth >= MAX_JSON_DEPTH {
+        return Value::Array(vec![]);
+      }
+      let len = number("minItems").map_or(1, |min| min as usize);
+      Value::Array(
+        (0..len)
+          .map(|_| fake_json(rng, schema.get("items").unwrap_or(&Value::Null), depth + 1))
+          .collect(),
+      )
+    }
+    _ => {
+      let mut object = serde_json::Map::new();
+      if depth < MAX_JSON_DEPTH
+        && let Some(Value::Object(properties)) = schema.get("properties")
+      {
+        for (name, property) in properties {
+          object.insert(name.clone(), fake_json(rng, property, depth + 1));
+        }
+      }
+      Value::Object(object)
+    }
+  };
+}
+
+const MAX_SEED_ROWS: usize = 10000;
+const MAX_JSON_DEPTH: usize = 8;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::test_utils::*;
+
+  // NOTE: Relies on SQLite's jsonschema extension functions.
+  #[cfg(not(feature = "pg-test"))]
+  #[tokio::test]
+  async fn test_seed_rows() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE authors (
+            id      INTEGER PRIMARY KEY,
+            name    TEXT NOT NULL
+          ) {strict};
+
+          CREATE TABLE books (
+            id        INTEGER PRIMARY KEY,
+            title     TEXT NOT NULL,
+            pages     INTEGER NOT NULL,
+            author    INTEGER NOT NULL REFERENCES authors(id),
+            meta      TEXT CHECK(jsonschema_matches('{{
+              "type": "object",
+              "properties": {{
+                "rating": {{ "type": "integer", "minimum": 1, "maximum": 5 }},
+                "tags": {{ "type": "array", "items": {{ "type": "string" }} }}
+              }},
+              "required": ["rating"]
+            }}', meta))
+          ) {strict};
+        "#,
+        strict = strict(conn),
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    let seed = async |table: &str, count: usize| {
+      return seed_rows_handler(
+        State(state.clone()),
+        Path(table.to_string()),
+        Json(SeedRowsRequest { count }),
+      )
+      .await;
+    };
+
+    // Books reference authors, which are still empty.
+    assert!(matches!(
+      seed("books", 10).await,
+      Err(Error::Precondition(_))
+    ));
+
+    let Json(response) = seed("authors", 5).await.unwrap();
+    assert_eq!(5, response.row_count);
+
+    let Json(response) = seed("books", 20).await.unwrap();
+    assert_eq!(20, response.row_count);
+
+    let orphans: i64 = conn
+      .read_query_row_get(
+        "SELECT COUNT(*) FROM books WHERE author NOT IN (SELECT id FROM authors)",
+        (),
+        0,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(0, orphans);
+
+    let ratings: i64 = conn
+      .read_query_row_get(
+        "SELECT COUNT(*) FROM books WHERE meta->>'rating' BETWEEN 1 AND 5",
+        (),
+        0,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(20, ratings);
+
+    assert!(matches!(seed("books", 0).await, Err(Error::BadRequest(_))));
+  }
+}
@@ -66,7 +66,7 @@ pub async fn update_row_handler(
 
   run_update_query(
     &conn,
-    state.objectstore(),
+    &state.objectstore(),
     &QualifiedNameEscaped::new(&table_metadata.schema.name),
     Params::for_admin_update(
       table_metadata,
@@ -128,7 +128,7 @@ mod tests {
       name: Some("foo0".to_string()),
       filename: Some("bar0.png".to_string()),
       content_type: None,
-      data: FileUploadData(bytes0.clone()),
+      data: FileUploadData::Bytes(bytes0.clone()),
     };
 
     update_row_handler(
@@ -0,0 +1,75 @@
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::AppState;
+use crate::admin::AdminError as Error;
+use crate::seeds::apply_seeds;
+
+#[derive(Debug, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct ApplySeedsRequest {
+  /// Re-run all seeds including already applied ones. Only available in dev mode.
+  pub rerun: Option<bool>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ApplySeedsResponse {
+  /// File names of the applied seeds.
+  pub applied: Vec<String>,
+}
+
+pub async fn apply_seeds_handler(
+  State(state): State<AppState>,
+  Json(request): Json<ApplySeedsRequest>,
+) -> Result<Json<ApplySeedsResponse>, Error> {
+  let rerun = request.rerun.unwrap_or(false);
+  if rerun && !state.dev_mode() {
+    return Err(Error::Precondition(
+      "Re-running seeds is only available in dev mode".into(),
+    ));
+  }
+
+  let applied = apply_seeds(state.conn(), state.data_dir().seeds_path(), rerun).await?;
+
+  return Ok(Json(ApplySeedsResponse { applied }));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_apply_seeds_handler() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let seeds_path = state.data_dir().seeds_path();
+    std::fs::create_dir_all(&seeds_path).unwrap();
+    std::fs::write(
+      seeds_path.join("roles.sql"),
+      "CREATE TABLE IF NOT EXISTS role (name TEXT PRIMARY KEY NOT NULL) STRICT; INSERT OR IGNORE INTO role (name) VALUES ('editor');",
+    )
+    .unwrap();
+
+    let Json(response) =
+      apply_seeds_handler(State(state.clone()), Json(ApplySeedsRequest::default()))
+        .await
+        .unwrap();
+    assert_eq!(response.applied, vec!["roles.sql"]);
+
+    let Json(response) =
+      apply_seeds_handler(State(state.clone()), Json(ApplySeedsRequest::default()))
+        .await
+        .unwrap();
+    assert!(response.applied.is_empty());
+
+    // Test state runs in dev mode.
+    let Json(response) = apply_seeds_handler(
+      State(state.clone()),
+      Json(ApplySeedsRequest { rerun: Some(true) }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.applied, vec!["roles.sql"]);
+  }
+}
@@ -7,6 +7,8 @@ use ts_rs::TS;
 
 use crate::admin::AdminError as Error;
 use crate::app_state::AppState;
+use crate::audit::{AuditAction, diff_json, record_audit_event, table_target};
+use crate::auth::User;
 use crate::config::proto::hash_config;
 use crate::transaction_recorder::{TransactionLog, TransactionRecorder};
 
@@ -41,6 +43,7 @@ pub struct AlterTableResponse {
 /// the data over, see https://sqlite.org/lang_altertable.html.
 pub async fn alter_table_handler(
   State(state): State<AppState>,
+  user: User,
   Json(request): Json<AlterTableRequest>,
 ) -> Result<Json<AlterTableResponse>, Error> {
   if state.demo_mode() {
@@ -80,6 +83,11 @@ pub async fn alter_table_handler(
     .unwrap_or(&ephemeral_table_schema.name)
     .clone();
 
+  let target_table_schema = Table {
+    name: target_table_name.clone(),
+    ..ephemeral_table_schema.clone()
+  };
+
   let tx_log = {
     let unqualified_source_table_name = source_table_schema.name.name.clone();
     let unqualified_ephemeral_table_rename =
@@ -101,49 +109,15 @@ pub async fn alter_table_handler(
           // In which case `PRAGMA legacy_alter_table=ON;` (and OFF afterwards) would be needed.
           tx.execute("PRAGMA defer_foreign_keys = ON", ())?;
 
-          // Create new table
-          let sql = ephemeral_table_schema.create_table_statement();
-          tx.execute(sql.clone(), ()).map_err(|err| {
-            warn!("Failed creating ephemeral table, likely invalid operations: {sql}\n\t{err}");
-            return err;
-          })?;
-
-          // Copy
-          let unqualified_ephemeral_table_name = ephemeral_table_schema.name.name;
-          let insert_data_query = format!(
-            r#"
-            INSERT INTO
-              "{unqualified_ephemeral_table_name}" ({target_columns})
-            SELECT
-              {source_columns}
-            FROM
-              "{unqualified_source_table_name}"
-          "#,
-            source_columns = escape_and_join_column_names(&source_columns),
-            target_columns = escape_and_join_column_names(&target_columns),
-          );
-          tx.execute(insert_data_query, ())?;
-
-          tx.execute(
-            format!("DROP TABLE \"{unqualified_source_table_name}\""),
-            (),
+          recreate_table(
+            &mut tx,
+            &unqualified_source_table_name,
+            &ephemeral_table_schema,
+            unqualified_ephemeral_table_rename.as_deref(),
+            &source_columns,
+            &target_columns,
           )?;
 
-          if let Some(unqualified_target_name) = unqualified_ephemeral_table_rename {
-            // NOTE: w/o the `legacy_alter_table = ON` the following `RENAME TO` would fail, since
-            // `ALTER TABLE` otherwise does a schema consistency-check and realize that any views
-            // referencing this table are no longer valid (even though may be again after the
-            // rename).
-            tx.execute("PRAGMA legacy_alter_table = ON", ())?;
-            tx.execute(
-              format!(
-                "ALTER TABLE \"{unqualified_ephemeral_table_name}\" RENAME TO \"{unqualified_target_name}\""
-              ),
-              (),
-            )?;
-            tx.execute("PRAGMA legacy_alter_table = OFF", ())?;
-          }
-
           return tx
             .rollback()
             .map_err(|err| trailbase_sqlite::Error::Other(err.into()));
@@ -171,10 +145,10 @@ pub async fn alter_table_handler(
       let old_config_hash = hash_config(&config);
 
       for api in &mut config.record_apis {
-        if let Some(ref name) = api.table_name
-          && QualifiedName::parse(name)? == source_table_schema.name
-        {
-          if let Some(ref db) = target_table_name.database_schema {
+        if api.table_name.is_some() && api.qualified_table_name()? == source_table_schema.name {
+          if api.database.is_some() {
+            api.table_name = Some(target_table_name.name.clone());
+          } else if let Some(ref db) = target_table_name.database_schema {
             api.table_name = Some(format!("{}.{}", db, target_table_name.name));
           } else {
             api.table_name = Some(target_table_name.name.clone());
@@ -183,11 +157,25 @@ pub async fn alter_table_handler(
       }
 
       state
-        .validate_and_update_config(config, Some(old_config_hash))
+        .validate_and_update_config_as(config, Some(old_config_hash), Some(user.uuid))
         .await?;
     }
 
     state.rebuild_connection_metadata().await?;
+
+    if let (Ok(source), Ok(target)) = (
+      serde_json::to_value(&source_table_schema),
+      serde_json::to_value(&target_table_schema),
+    ) {
+      record_audit_event(
+        &state,
+        Some(user.uuid),
+        AuditAction::TableAltered,
+        &table_target(&source_table_schema.name),
+        Some(diff_json(&source, &target)),
+      )
+      .await;
+    }
   }
 
   return Ok(Json(AlterTableResponse {
@@ -195,6 +183,64 @@ pub async fn alter_table_handler(
   }));
 }
 
+/// Creates the (ephemeral) target table, copies the data of the mapped columns over, drops the
+/// source table and, if necessary, renames the target table.
+///
+/// Expects foreign key checks to be deferred, i.e. `PRAGMA defer_foreign_keys = ON`.
+pub(super) fn recreate_table(
+  tx: &mut TransactionRecorder<'_>,
+  unqualified_source_table_name: &str,
+  ephemeral_table_schema: &Table,
+  unqualified_ephemeral_table_rename: Option<&str>,
+  source_columns: &[String],
+  target_columns: &[String],
+) -> Result<(), trailbase_sqlite::Error> {
+  // Create new table
+  let sql = ephemeral_table_schema.create_table_statement();
+  tx.execute(sql.clone(), ()).map_err(|err| {
+    warn!("Failed creating ephemeral table, likely invalid operations: {sql}\n\t{err}");
+    return err;
+  })?;
+
+  // Copy
+  let unqualified_ephemeral_table_name = &ephemeral_table_schema.name.name;
+  let insert_data_query = format!(
+    r#"
+    INSERT INTO
+      "{unqualified_ephemeral_table_name}" ({target_columns})
+    SELECT
+      {source_columns}
+    FROM
+      "{unqualified_source_table_name}"
+  "#,
+    source_columns = escape_and_join_column_names(source_columns),
+    target_columns = escape_and_join_column_names(target_columns),
+  );
+  tx.execute(insert_data_query, ())?;
+
+  tx.execute(
+    format!("DROP TABLE \"{unqualified_source_table_name}\""),
+    (),
+  )?;
+
+  if let Some(unqualified_target_name) = unqualified_ephemeral_table_rename {
+    // NOTE: w/o the `legacy_alter_table = ON` the following `RENAME TO` would fail, since
+    // `ALTER TABLE` otherwise does a schema consistency-check and realize that any views
+    // referencing this table are no longer valid (even though may be again after the
+    // rename).
+    tx.execute("PRAGMA legacy_alter_table = ON", ())?;
+    tx.execute(
+      format!(
+        "ALTER TABLE \"{unqualified_ephemeral_table_name}\" RENAME TO \"{unqualified_target_name}\""
+      ),
+      (),
+    )?;
+    tx.execute("PRAGMA legacy_alter_table = OFF", ())?;
+  }
+
+  return Ok(());
+}
+
 struct TargetSchema {
   ephemeral_table_schema: Table,
   ephemeral_table_rename: Option<QualifiedName>,
@@ -319,7 +365,7 @@ fn build_ephemeral_target_schema(
   });
 }
 
-fn check_column_removals_invalidating_config(
+pub(super) fn check_column_removals_invalidating_config(
   state: &AppState,
   source_schema: &Table,
   operations: &[AlterTableOperation],
@@ -342,7 +388,7 @@ fn check_column_removals_invalidating_config(
   let config = state.get_config();
   for api in &config.record_apis {
     let api_name = api.name();
-    let api_table = QualifiedName::parse(api.table_name.as_deref().unwrap_or_default())?;
+    let api_table = api.qualified_table_name()?;
     if api_table != source_schema.name {
       continue;
     }
@@ -417,6 +463,10 @@ mod tests {
   use crate::records::read_record::{ReadRecordQuery, read_record_handler};
   use crate::records::test_utils::*;
 
+  fn admin_user() -> User {
+    return User::from_unverified(uuid::Uuid::now_v7(), Some("admin@test.org"), None);
+  }
+
   fn parse_create_table(create_table_sql: &str) -> Table {
     let create_table_statement = parse_into_statement(create_table_sql).unwrap().unwrap();
     return create_table_statement.try_into().unwrap();
@@ -620,9 +670,13 @@ mod tests {
       "Create Table: {}",
       create_table_request.schema.create_table_statement()
     );
-    let _ = create_table_handler(State(state.clone()), Json(create_table_request.clone()))
-      .await
-      .unwrap();
+    let _ = create_table_handler(
+      State(state.clone()),
+      admin_user(),
+      Json(create_table_request.clone()),
+    )
+    .await
+    .unwrap();
 
     conn
       .read_query_rows(format!("SELECT {pk_col} FROM foo"), ())
@@ -637,10 +691,13 @@ mod tests {
         dry_run: None,
       };
 
-      let Json(response) =
-        alter_table_handler(State(state.clone()), Json(alter_table_request.clone()))
-          .await
-          .unwrap();
+      let Json(response) = alter_table_handler(
+        State(state.clone()),
+        admin_user(),
+        Json(alter_table_request.clone()),
+      )
+      .await
+      .unwrap();
       assert_eq!(response.sql, "");
 
       conn
@@ -668,10 +725,13 @@ mod tests {
         dry_run: None,
       };
 
-      let Json(response) =
-        alter_table_handler(State(state.clone()), Json(alter_table_request.clone()))
-          .await
-          .unwrap();
+      let Json(response) = alter_table_handler(
+        State(state.clone()),
+        admin_user(),
+        Json(alter_table_request.clone()),
+      )
+      .await
+      .unwrap();
       assert!(response.sql.contains("new"));
 
       conn
@@ -690,10 +750,13 @@ mod tests {
         dry_run: None,
       };
 
-      let Json(response) =
-        alter_table_handler(State(state.clone()), Json(alter_table_request.clone()))
-          .await
-          .unwrap();
+      let Json(response) = alter_table_handler(
+        State(state.clone()),
+        admin_user(),
+        Json(alter_table_request.clone()),
+      )
+      .await
+      .unwrap();
       assert!(response.sql.contains("bar"));
 
       assert!(conn.read_query_rows("SELECT * FROM foo", ()).await.is_err());
@@ -701,6 +764,18 @@ mod tests {
         .read_query_rows(format!("SELECT {pk_col} FROM bar"), ())
         .await
         .unwrap();
+
+      let payload: String = conn
+        .read_query_row_get(
+          "SELECT payload FROM _audit_log WHERE action = 'table.altered' AND target = 'foo' ORDER BY id DESC",
+          (),
+          0,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+      let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+      assert_eq!(payload["name"]["name"]["new"], "bar");
     }
   }
 
@@ -793,10 +868,13 @@ mod tests {
       dry_run: None,
     };
 
-    let Json(response) =
-      alter_table_handler(State(state.clone()), Json(alter_table_request.clone()))
-        .await
-        .unwrap();
+    let Json(response) = alter_table_handler(
+      State(state.clone()),
+      admin_user(),
+      Json(alter_table_request.clone()),
+    )
+    .await
+    .unwrap();
     assert!(response.sql.contains("new"));
 
     assert_eq!(
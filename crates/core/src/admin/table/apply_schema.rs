@@ -0,0 +1,597 @@
+use axum::extract::{Json, State};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use trailbase_schema::parse::parse_into_statements;
+use trailbase_schema::sqlite::{QualifiedName, Table, TableIndex, View};
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::admin::table::alter_table::{
+  AlterTableOperation, check_column_removals_invalidating_config, recreate_table,
+};
+use crate::app_state::AppState;
+use crate::constants::SQLITE_SCHEMA_TABLE;
+use crate::transaction_recorder::{TransactionLog, TransactionRecorder};
+
+/// Request for applying a declarative schema, i.e. a list of `CREATE TABLE`, `CREATE VIEW` and
+/// `CREATE INDEX` statements describing the desired state of a database.
+#[derive(Clone, Debug, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct ApplySchemaRequest {
+  /// Desired schema as SQL.
+  pub schema: String,
+  /// Database to apply the schema to. Defaults to "main".
+  pub database: Option<String>,
+  /// Drop tables, views and indexes, which are not part of the desired schema. Tables, views and
+  /// indexes managed by TrailBase, i.e. prefixed with "_", are never dropped.
+  pub prune: Option<bool>,
+
+  pub dry_run: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ApplySchemaResponse {
+  /// The migration applied, empty if the schema is already up-to-date.
+  pub sql: String,
+}
+
+/// Admin-only handler for applying a declarative schema.
+///
+/// The desired schema is diffed against the current schema and the minimal set of changes is
+/// applied as a single migration. Tables are altered by recreating them and copying over the data
+/// of columns present in both, see `alter_table_handler`. Column renames therefore show up as
+/// dropping and adding a column.
+pub async fn apply_schema_handler(
+  State(state): State<AppState>,
+  Json(request): Json<ApplySchemaRequest>,
+) -> Result<Json<ApplySchemaResponse>, Error> {
+  return Ok(Json(apply_schema(&state, request).await?));
+}
+
+pub async fn apply_schema(
+  state: &AppState,
+  request: ApplySchemaRequest,
+) -> Result<ApplySchemaResponse, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  let db = request
+    .database
+    .clone()
+    .filter(|db| db != "main")
+    .unwrap_or_else(|| "main".to_string());
+  let prune = request.prune.unwrap_or(false);
+  let dry_run = request.dry_run.unwrap_or(false);
+
+  let desired = parse_desired_schema(&request.schema, &db)?;
+
+  let (conn, migration_path) =
+    super::get_conn_and_migration_path(state, if db == "main" { None } else { Some(db.clone()) })?;
+
+  let current = {
+    let entry = state
+      .connection_manager()
+      .get_entry_for_qn(&QualifiedName {
+        name: String::new(),
+        database_schema: Some(db.clone()),
+      })
+      .await?;
+    let in_db = |name: &QualifiedName| name.database_schema.as_deref().unwrap_or("main") == db;
+
+    CurrentSchema {
+      tables: entry
+        .metadata
+        .tables()
+        .into_iter()
+        .filter(|t| in_db(&t.name) && !is_reserved(&t.name.name) && !t.virtual_table)
+        .map(|t| (t.name.name.clone(), unqualified(t.clone())))
+        .collect(),
+      views: entry
+        .metadata
+        .views()
+        .into_iter()
+        .filter(|v| in_db(&v.name) && !is_reserved(&v.name.name))
+        .map(|v| (v.name.name.clone(), v.query.clone()))
+        .collect(),
+      indexes: list_current_indexes(&conn).await?,
+    }
+  };
+
+  let steps = plan(state, &desired, &current, prune)?;
+  if steps.is_empty() {
+    return Ok(ApplySchemaResponse { sql: String::new() });
+  }
+
+  let tx_log = conn
+    .transaction(
+      move |tx| -> Result<Option<TransactionLog>, trailbase_sqlite::Error> {
+        let mut tx = TransactionRecorder::new(tx);
+
+        // Defer any foreign key checks until transaction is being committed.
+        tx.execute("PRAGMA defer_foreign_keys = ON", ())?;
+
+        for step in steps {
+          match step {
+            Step::Execute(sql) => {
+              debug!("Applying schema: {sql}");
+              tx.execute(sql, ())?;
+            }
+            Step::Recreate {
+              source,
+              target,
+              columns,
+            } => {
+              let mut ephemeral = target;
+              ephemeral.name.name = format!("__alter_table_{source}");
+              recreate_table(
+                &mut tx,
+                &source,
+                &ephemeral,
+                Some(source.as_str()),
+                &columns,
+                &columns,
+              )?;
+            }
+          }
+        }
+
+        return tx
+          .rollback()
+          .map_err(|err| trailbase_sqlite::Error::Other(err.into()));
+      },
+    )
+    .await?;
+
+  // Take transaction log, write a migration file and apply.
+  if !dry_run && let Some(ref log) = tx_log {
+    let filename = QualifiedName {
+      name: "schema".to_string(),
+      database_schema: None,
+    }
+    .migration_filename("apply");
+
+    let report = log
+      .apply_as_migration(&conn, migration_path, &filename)
+      .await?;
+    debug!("Migration report: {report:?}");
+
+    state.rebuild_connection_metadata().await?;
+  }
+
+  return Ok(ApplySchemaResponse {
+    sql: tx_log.map(|l| l.build_sql()).unwrap_or_default(),
+  });
+}
+
+#[derive(Debug, Default)]
+struct DesiredSchema {
+  tables: Vec<Table>,
+  /// Views and their queries.
+  views: Vec<(String, String)>,
+  indexes: Vec<TableIndex>,
+}
+
+struct CurrentSchema {
+  tables: HashMap<String, Table>,
+  /// Views and their queries.
+  views: HashMap<String, String>,
+  /// Indexes and their `CREATE INDEX` statements.
+  indexes: HashMap<String, (TableIndex, String)>,
+}
+
+#[derive(Debug)]
+enum Step {
+  Execute(String),
+  /// Recreates the table with the target schema, copying over the given columns.
+  Recreate {
+    source: String,
+    target: Table,
+    columns: Vec<String>,
+  },
+}
+
+fn parse_desired_schema(sql: &str, db: &str) -> Result<DesiredSchema, Error> {
+  use sqlite3_parser::ast::Stmt;
+
+  let statements = parse_into_statements(sql).map_err(|err| Error::BadRequest(err.into()))?;
+
+  let mut desired = DesiredSchema::default();
+  let mut names = HashSet::<String>::new();
+
+  let mut check_name = |name: &QualifiedName| -> Result<(), Error> {
+    if let Some(ref schema) = name.database_schema
+      && schema != db
+    {
+      return Err(Error::BadRequest(
+        format!("'{name}' not in database '{db}'").into(),
+      ));
+    }
+    if is_reserved(&name.name) {
+      return Err(Error::BadRequest(
+        format!("'{name}' uses reserved prefix '_'").into(),
+      ));
+    }
+    if !names.insert(name.name.clone()) {
+      return Err(Error::BadRequest(format!("'{name}' defined twice").into()));
+    }
+    return Ok(());
+  };
+
+  for stmt in statements {
+    match stmt {
+      Stmt::CreateTable { .. } | Stmt::CreateVirtualTable { .. } => {
+        let table: Table = stmt.try_into()?;
+        check_name(&table.name)?;
+        if table.virtual_table || table.temporary {
+          return Err(Error::BadRequest(
+            format!("Virtual and temporary tables not supported: {}", table.name).into(),
+          ));
+        }
+        desired.tables.push(unqualified(table));
+      }
+      Stmt::CreateView { .. } => {
+        let view = View::from(stmt, &desired.tables)?;
+        check_name(&view.name)?;
+        if view.temporary {
+          return Err(Error::BadRequest(
+            format!("Temporary views not supported: {}", view.name).into(),
+          ));
+        }
+        desired.views.push((view.name.name, view.query));
+      }
+      Stmt::CreateIndex { .. } => {
+        let mut index: TableIndex = stmt.try_into()?;
+        check_name(&index.name)?;
+        index.name.database_schema = None;
+        index.if_not_exists = false;
+        desired.indexes.push(index);
+      }
+      stmt => {
+        return Err(Error::BadRequest(
+          format!("Expected CREATE TABLE, VIEW or INDEX, got: {stmt:?}").into(),
+        ));
+      }
+    }
+  }
+
+  return Ok(desired);
+}
+
+/// Diffs the desired against the current schema and returns the steps needed to get there.
+fn plan(
+  state: &AppState,
+  desired: &DesiredSchema,
+  current: &CurrentSchema,
+  prune: bool,
+) -> Result<Vec<Step>, Error> {
+  let mut steps: Vec<Step> = vec![];
+
+  let desired_tables: HashSet<&str> = desired
+    .tables
+    .iter()
+    .map(|t| t.name.name.as_str())
+    .collect();
+
+  // Tables.
+  let mut recreated_tables = HashSet::<String>::new();
+  let mut table_steps: Vec<Step> = vec![];
+  for table in &desired.tables {
+    let name = &table.name.name;
+    match current.tables.get(name) {
+      None => table_steps.push(Step::Execute(table.create_table_statement())),
+      Some(current_table) if current_table != table => {
+        let dropped_columns: Vec<AlterTableOperation> = current_table
+          .columns
+          .iter()
+          .filter(|c| !table.columns.iter().any(|d| d.name == c.name))
+          .map(|c| AlterTableOperation::DropColumn {
+            name: c.name.clone(),
+          })
+          .collect();
+        check_column_removals_invalidating_config(state, current_table, &dropped_columns)?;
+
+        table_steps.push(Step::Recreate {
+          source: name.clone(),
+          target: table.clone(),
+          columns: table
+            .columns
+            .iter()
+            .filter(|c| current_table.columns.iter().any(|s| s.name == c.name))
+            .map(|c| c.name.clone())
+            .collect(),
+        });
+        recreated_tables.insert(name.clone());
+      }
+      Some(_) => {}
+    }
+  }
+
+  let mut dropped_tables: Vec<&str> = vec![];
+  if prune {
+    let config = state.get_config();
+    for name in current.tables.keys() {
+      if desired_tables.contains(name.as_str()) {
+        continue;
+      }
+
+      for api in &config.record_apis {
+        if api.table_name.is_some() && api.qualified_table_name()?.name == *name {
+          return Err(Error::BadRequest(
+            format!("Cannot drop table {name} referenced by API: {}", api.name()).into(),
+          ));
+        }
+      }
+      dropped_tables.push(name);
+    }
+    dropped_tables.sort();
+  }
+
+  // Views: dropped first and re-created last, since they may depend on tables being altered.
+  let mut view_steps: Vec<Step> = vec![];
+  for (name, query) in &desired.views {
+    match current.views.get(name) {
+      Some(current_query) if current_query == query => {}
+      existing => {
+        if existing.is_some() {
+          steps.push(Step::Execute(format!("DROP VIEW \"{name}\"")));
+        }
+        view_steps.push(Step::Execute(format!("CREATE VIEW \"{name}\" AS {query}")));
+      }
+    }
+  }
+  if prune {
+    let mut dropped_views: Vec<&String> = current
+      .views
+      .keys()
+      .filter(|name| !desired.views.iter().any(|(n, _)| n == *name))
+      .collect();
+    dropped_views.sort();
+    for name in dropped_views {
+      steps.push(Step::Execute(format!("DROP VIEW \"{name}\"")));
+    }
+  }
+
+  // Indexes: indexes of recreated tables are dropped alongside the table and need to be
+  // re-created even if unchanged.
+  let mut index_steps: Vec<Step> = vec![];
+  for index in &desired.indexes {
+    let name = &index.name.name;
+    match current.indexes.get(name) {
+      Some((current_index, _))
+        if current_index == index && !recreated_tables.contains(&index.table_name) => {}
+      existing => {
+        if existing.is_some() && !recreated_tables.contains(&index.table_name) {
+          steps.push(Step::Execute(format!("DROP INDEX \"{name}\"")));
+        }
+        index_steps.push(Step::Execute(index.create_index_statement()));
+      }
+    }
+  }
+  let mut remaining_indexes: Vec<&String> = current
+    .indexes
+    .keys()
+    .filter(|name| !desired.indexes.iter().any(|i| i.name.name == **name))
+    .collect();
+  remaining_indexes.sort();
+  for name in remaining_indexes {
+    let (index, sql) = &current.indexes[name];
+    if dropped_tables.contains(&index.table_name.as_str()) {
+      continue;
+    }
+
+    if prune {
+      if !recreated_tables.contains(&index.table_name) {
+        steps.push(Step::Execute(format!("DROP INDEX \"{name}\"")));
+      }
+    } else if recreated_tables.contains(&index.table_name) {
+      index_steps.push(Step::Execute(sql.clone()));
+    }
+  }
+
+  for name in dropped_tables {
+    steps.push(Step::Execute(format!("DROP TABLE \"{name}\"")));
+  }
+
+  steps.extend(table_steps);
+  steps.extend(view_steps);
+  steps.extend(index_steps);
+
+  return Ok(steps);
+}
+
+async fn list_current_indexes(
+  conn: &trailbase_sqlite::Connection,
+) -> Result<HashMap<String, (TableIndex, String)>, Error> {
+  let rows = conn
+    .read_query_rows(
+      format!(
+        r#"
+          SELECT sql FROM main."{SQLITE_SCHEMA_TABLE}"
+            WHERE type = 'index' AND sql IS NOT NULL AND tbl_name NOT LIKE '\_%' ESCAPE '\'
+        "#
+      ),
+      (),
+    )
+    .await?;
+
+  let mut indexes = HashMap::<String, (TableIndex, String)>::new();
+  for row in rows.iter() {
+    let sql: String = row.get(0)?;
+    let Some(stmt) = trailbase_schema::parse::parse_into_statement(&sql)
+      .map_err(|err| Error::Internal(err.into()))?
+    else {
+      continue;
+    };
+
+    let mut index: TableIndex = stmt.try_into()?;
+    if is_reserved(&index.name.name) {
+      continue;
+    }
+    index.name.database_schema = None;
+    index.if_not_exists = false;
+    indexes.insert(index.name.name.clone(), (index, sql));
+  }
+
+  return Ok(indexes);
+}
+
+fn unqualified(mut table: Table) -> Table {
+  table.name.database_schema = None;
+  return table;
+}
+
+/// Tables, views and indexes managed by TrailBase, as well as SQLite's own.
+fn is_reserved(name: &str) -> bool {
+  return name.starts_with('_') || name.starts_with("sqlite_");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[tokio::test]
+  async fn test_apply_schema() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    let apply = async |schema: &str, prune: bool| {
+      return apply_schema(
+        &state,
+        ApplySchemaRequest {
+          schema: schema.to_string(),
+          prune: Some(prune),
+          ..Default::default()
+        },
+      )
+      .await;
+    };
+
+    let schema = r#"
+      CREATE TABLE movies (
+        id       INTEGER PRIMARY KEY,
+        title    TEXT NOT NULL
+      ) STRICT;
+      CREATE INDEX _movies_title ON movies (title);
+    "#;
+    // Reserved names are rejected.
+    assert!(apply(schema, false).await.is_err());
+
+    let schema = r#"
+      CREATE TABLE movies (
+        id       INTEGER PRIMARY KEY,
+        title    TEXT NOT NULL
+      ) STRICT;
+      CREATE INDEX movies_title ON movies (title);
+      CREATE VIEW movie_titles AS SELECT id, title FROM movies;
+    "#;
+    let response = apply(schema, false).await.unwrap();
+    assert!(response.sql.contains("CREATE TABLE"), "{}", response.sql);
+
+    conn
+      .execute(
+        "INSERT INTO movies (id, title) VALUES (1, 'Casablanca')",
+        (),
+      )
+      .await
+      .unwrap();
+
+    // Re-applying the same schema is a no-op.
+    assert_eq!(apply(schema, false).await.unwrap().sql, "");
+
+    // Add a column. Data and indexes are retained.
+    let schema = r#"
+      CREATE TABLE movies (
+        id       INTEGER PRIMARY KEY,
+        title    TEXT NOT NULL,
+        year     INTEGER NOT NULL DEFAULT 0
+      ) STRICT;
+      CREATE INDEX movies_title ON movies (title);
+      CREATE VIEW movie_titles AS SELECT id, title FROM movies;
+      CREATE TABLE actors (
+        id       INTEGER PRIMARY KEY,
+        name     TEXT NOT NULL
+      ) STRICT;
+    "#;
+    apply(schema, false).await.unwrap();
+    assert_eq!(apply(schema, false).await.unwrap().sql, "");
+
+    assert_eq!(
+      "Casablanca",
+      conn
+        .read_query_row_get::<String>("SELECT title FROM movie_titles WHERE id = 1", (), 0)
+        .await
+        .unwrap()
+        .unwrap()
+    );
+    assert_eq!(
+      1,
+      conn
+        .read_query_row_get::<i64>(
+          "SELECT COUNT(*) FROM sqlite_schema WHERE name = 'movies_title'",
+          (),
+          0
+        )
+        .await
+        .unwrap()
+        .unwrap()
+    );
+
+    // Dry runs don't apply changes.
+    let schema = r#"
+      CREATE TABLE movies (
+        id       INTEGER PRIMARY KEY,
+        title    TEXT NOT NULL
+      ) STRICT;
+    "#;
+    let response = apply_schema(
+      &state,
+      ApplySchemaRequest {
+        schema: schema.to_string(),
+        prune: Some(true),
+        dry_run: Some(true),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+    assert!(
+      response.sql.contains("DROP TABLE \"actors\""),
+      "{}",
+      response.sql
+    );
+    conn
+      .read_query_rows("SELECT year FROM movies", ())
+      .await
+      .unwrap();
+
+    // Prune everything not part of the schema.
+    apply(schema, true).await.unwrap();
+    assert!(
+      conn
+        .read_query_rows("SELECT year FROM movies", ())
+        .await
+        .is_err()
+    );
+    assert!(
+      conn
+        .read_query_rows("SELECT * FROM actors", ())
+        .await
+        .is_err()
+    );
+    assert!(
+      conn
+        .read_query_rows("SELECT * FROM movie_titles", ())
+        .await
+        .is_err()
+    );
+    // Internal tables are left alone.
+    conn
+      .read_query_rows("SELECT * FROM _user", ())
+      .await
+      .unwrap();
+  }
+}
@@ -24,6 +24,13 @@ pub async fn create_index_handler(
   State(state): State<AppState>,
   Json(request): Json<CreateIndexRequest>,
 ) -> Result<Json<CreateIndexResponse>, Error> {
+  return Ok(Json(create_index(&state, request).await?));
+}
+
+pub(super) async fn create_index(
+  state: &AppState,
+  request: CreateIndexRequest,
+) -> Result<CreateIndexResponse, Error> {
   let dry_run = request.dry_run.unwrap_or(false);
   let (db, index_schema) = {
     let mut schema = request.schema.clone();
@@ -31,7 +38,7 @@ pub async fn create_index_handler(
     (schema.name.database_schema.take(), schema)
   };
 
-  let (conn, migration_path) = super::get_conn_and_migration_path(&state, db)?;
+  let (conn, migration_path) = super::get_conn_and_migration_path(state, db)?;
 
   // This builds the `CREATE INDEX` SQL statement.
   let create_index_query = index_schema.create_index_statement();
@@ -56,7 +63,7 @@ pub async fn create_index_handler(
       .await?;
   }
 
-  return Ok(Json(CreateIndexResponse {
+  return Ok(CreateIndexResponse {
     sql: tx_log.map(|l| l.build_sql()).unwrap_or_default(),
-  }));
+  });
 }
@@ -5,6 +5,8 @@ use ts_rs::TS;
 
 use crate::admin::AdminError as Error;
 use crate::app_state::AppState;
+use crate::audit::{AuditAction, record_audit_event, table_target};
+use crate::auth::User;
 use crate::transaction_recorder::TransactionRecorder;
 
 #[derive(Clone, Debug, Deserialize, TS)]
@@ -22,6 +24,7 @@ pub struct CreateTableResponse {
 
 pub async fn create_table_handler(
   State(state): State<AppState>,
+  user: User,
   Json(request): Json<CreateTableRequest>,
 ) -> Result<Json<CreateTableResponse>, Error> {
   if request.schema.columns.is_empty() {
@@ -62,6 +65,15 @@ pub async fn create_table_handler(
       .await?;
 
     state.rebuild_connection_metadata().await?;
+
+    record_audit_event(
+      &state,
+      Some(user.uuid),
+      AuditAction::TableCreated,
+      &table_target(&request.schema.name),
+      serde_json::to_value(&request.schema).ok(),
+    )
+    .await;
   }
 
   return Ok(Json(CreateTableResponse {
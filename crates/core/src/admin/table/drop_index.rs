@@ -25,6 +25,13 @@ pub async fn drop_index_handler(
   State(state): State<AppState>,
   Json(request): Json<DropIndexRequest>,
 ) -> Result<Json<DropIndexResponse>, Error> {
+  return Ok(Json(drop_index(&state, request).await?));
+}
+
+pub(super) async fn drop_index(
+  state: &AppState,
+  request: DropIndexRequest,
+) -> Result<DropIndexResponse, Error> {
   if state.demo_mode() {
     return Err(Error::Precondition("Disallowed in demo".into()));
   }
@@ -35,7 +42,7 @@ pub async fn drop_index_handler(
     database_schema,
   } = QualifiedName::parse(&request.name)?;
 
-  let (conn, migration_path) = super::get_conn_and_migration_path(&state, database_schema)?;
+  let (conn, migration_path) = super::get_conn_and_migration_path(state, database_schema)?;
 
   let tx_log = {
     let unqualified_index_name = unqualified_index_name.clone();
@@ -67,7 +74,7 @@ pub async fn drop_index_handler(
       .await?;
   }
 
-  return Ok(Json(DropIndexResponse {
+  return Ok(DropIndexResponse {
     sql: tx_log.map(|l| l.build_sql()).unwrap_or_default(),
-  }));
+  });
 }
@@ -6,6 +6,8 @@ use ts_rs::TS;
 
 use crate::admin::AdminError as Error;
 use crate::app_state::AppState;
+use crate::audit::{AuditAction, record_audit_event, table_target};
+use crate::auth::User;
 use crate::config::proto::hash_config;
 use crate::constants::SQLITE_SCHEMA_TABLE;
 use crate::transaction_recorder::TransactionRecorder;
@@ -26,6 +28,7 @@ pub struct DropTableResponse {
 
 pub async fn drop_table_handler(
   State(state): State<AppState>,
+  user: User,
   Json(request): Json<DropTableRequest>,
 ) -> Result<Json<DropTableResponse>, Error> {
   if state.demo_mode() {
@@ -101,19 +104,28 @@ pub async fn drop_table_handler(
       let old_config_hash = hash_config(&config);
 
       config.record_apis.retain(|c| {
-        if let Some(ref name) = c.table_name
-          && let Ok(name) = QualifiedName::parse(name)
+        if c.table_name.is_some()
+          && let Ok(name) = c.qualified_table_name()
         {
           return name != table_name;
         }
         return true;
       });
       state
-        .validate_and_update_config(config, Some(old_config_hash))
+        .validate_and_update_config_as(config, Some(old_config_hash), Some(user.uuid))
         .await?;
     }
 
     state.rebuild_connection_metadata().await?;
+
+    record_audit_event(
+      &state,
+      Some(user.uuid),
+      AuditAction::TableDropped,
+      &table_target(&table_name),
+      Some(serde_json::json!({ "type": entity_type.to_lowercase() })),
+    )
+    .await;
   }
 
   return Ok(Json(DropTableResponse {
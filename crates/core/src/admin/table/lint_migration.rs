@@ -0,0 +1,455 @@
+use axum::extract::{Json, State};
+use log::*;
+use serde::{Deserialize, Serialize};
+use sqlite3_parser::ast::{Stmt, fmt::ToTokens};
+use trailbase_schema::parse::{parse_into_statement, parse_into_statements};
+use trailbase_schema::sqlite::{QualifiedName, Table};
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::admin::database::{remove_file, temp_path};
+use crate::admin::table::alter_table::{
+  AlterTableOperation, check_column_removals_invalidating_config,
+};
+use crate::app_state::AppState;
+use crate::transaction_recorder::{SchemaEntry, snapshot_schema};
+
+#[derive(Clone, Debug, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct LintMigrationRequest {
+  /// Candidate migration SQL.
+  pub sql: String,
+  /// Database to lint the migration against. Defaults to "main".
+  pub database: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, TS)]
+pub enum LintLevel {
+  Info,
+  Warning,
+  Error,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct MigrationLint {
+  pub level: LintLevel,
+  /// Index of the statement the lint refers to, if specific to one.
+  pub statement: Option<usize>,
+  pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct LintMigrationResponse {
+  /// Error applying the migration to a copy of the database, if any.
+  pub error: Option<String>,
+  /// Tables created, altered or dropped by the migration, or getting new indexes.
+  pub affected_tables: Vec<String>,
+  pub lints: Vec<MigrationLint>,
+}
+
+/// Admin-only handler for linting a candidate migration before applying it.
+///
+/// The migration is applied to a temporary copy of the database inside a transaction, which is
+/// rolled back. The resulting schema changes are checked for data loss, locking implications and
+/// conflicts with record APIs.
+pub async fn lint_migration_handler(
+  State(state): State<AppState>,
+  Json(request): Json<LintMigrationRequest>,
+) -> Result<Json<LintMigrationResponse>, Error> {
+  let statements = parse_into_statements(&request.sql)
+    .map_err(|err| Error::BadRequest(err.into()))?
+    .into_iter()
+    .map(|stmt| StmtFormatter(&stmt).to_string())
+    .collect::<Vec<_>>();
+  if statements.is_empty() {
+    return Err(Error::BadRequest("Empty migration".into()));
+  }
+
+  let db = request
+    .database
+    .clone()
+    .filter(|db| db != "main")
+    .unwrap_or_else(|| "main".to_string());
+  let (conn, _migration_path) =
+    super::get_conn_and_migration_path(&state, if db == "main" { None } else { Some(db.clone()) })?;
+
+  // Apply to a temporary copy.
+  let path = temp_path(&state, "lint").await?;
+  let result = dry_run(&state, &conn, &path, statements.clone()).await;
+  remove_file(&path).await;
+  let DryRun {
+    before,
+    after,
+    error,
+  } = result?;
+
+  let mut lints = vec![MigrationLint {
+    level: LintLevel::Info,
+    statement: None,
+    message: format!(
+      "Migrations are applied in a single transaction, blocking all other writes to '{db}' until committed"
+    ),
+  }];
+
+  for (idx, sql) in statements.iter().enumerate() {
+    let Ok(Some(stmt)) = parse_into_statement(sql) else {
+      continue;
+    };
+    match stmt {
+      Stmt::Update { .. } | Stmt::Delete { .. } | Stmt::Insert { .. } => {
+        lints.push(MigrationLint {
+          level: LintLevel::Info,
+          statement: Some(idx),
+          message: "Data migration, locking time scales with the number of affected records"
+            .to_string(),
+        })
+      }
+      Stmt::Pragma { .. } => lints.push(MigrationLint {
+        level: LintLevel::Warning,
+        statement: Some(idx),
+        message: "Some PRAGMAs have no effect within a transaction".to_string(),
+      }),
+      Stmt::Attach { .. } | Stmt::Detach { .. } | Stmt::Vacuum { .. } => {
+        lints.push(MigrationLint {
+          level: LintLevel::Error,
+          statement: Some(idx),
+          message: "Not allowed in migrations".to_string(),
+        })
+      }
+      _ => {}
+    }
+  }
+
+  if let Some((idx, ref err)) = error {
+    lints.push(MigrationLint {
+      level: LintLevel::Error,
+      statement: Some(idx),
+      message: format!("Failed to apply: {err}"),
+    });
+  }
+
+  let find = |entries: &[SchemaEntry], r#type: &str, name: &str| -> Option<SchemaEntry> {
+    return entries
+      .iter()
+      .find(|e| e.r#type == r#type && e.name == name)
+      .cloned();
+  };
+  let count_records = async |table: &str| -> Option<i64> {
+    return conn
+      .read_query_row_get::<i64>(format!("SELECT COUNT(*) FROM \"{table}\""), (), 0)
+      .await
+      .ok()
+      .flatten();
+  };
+
+  let mut affected_tables: Vec<String> = vec![];
+  let config = state.get_config();
+  let api_names_for_table = |name: &str| -> Vec<String> {
+    return config
+      .record_apis
+      .iter()
+      .filter(|api| {
+        api
+          .qualified_table_name()
+          .is_ok_and(|qn| qn.name == name && qn.database_schema.as_deref().unwrap_or("main") == db)
+      })
+      .map(|api| api.name().to_string())
+      .collect();
+  };
+
+  // Dropped or changed tables and views.
+  for entry in before
+    .iter()
+    .filter(|e| e.r#type == "table" || e.r#type == "view")
+  {
+    let name = &entry.name;
+    match find(&after, &entry.r#type, name) {
+      None => {
+        if entry.r#type == "table" {
+          affected_tables.push(name.clone());
+          let records = count_records(name).await.unwrap_or(0);
+          lints.push(MigrationLint {
+            level: LintLevel::Warning,
+            statement: None,
+            message: format!("Drops table '{name}' and its {records} records"),
+          });
+        }
+
+        for api in api_names_for_table(name) {
+          lints.push(MigrationLint {
+            level: LintLevel::Error,
+            statement: None,
+            message: format!("'{name}' is dropped or renamed but used by record API '{api}'"),
+          });
+        }
+      }
+      Some(a) if a.sql != entry.sql && entry.r#type == "table" => {
+        affected_tables.push(name.clone());
+
+        let (Some(before_table), Some(after_table)) =
+          (parse_table(&entry.sql), parse_table(&a.sql))
+        else {
+          continue;
+        };
+
+        let dropped: Vec<AlterTableOperation> = before_table
+          .columns
+          .iter()
+          .filter(|c| !after_table.columns.iter().any(|o| o.name == c.name))
+          .map(|c| AlterTableOperation::DropColumn {
+            name: c.name.clone(),
+          })
+          .collect();
+        if dropped.is_empty() {
+          continue;
+        }
+
+        let records = count_records(name).await.unwrap_or(0);
+        lints.push(MigrationLint {
+          level: LintLevel::Warning,
+          statement: None,
+          message: format!(
+            "Drops columns of '{name}', losing their data and rewriting its {records} records"
+          ),
+        });
+
+        let mut source = before_table;
+        source.name = QualifiedName {
+          name: name.clone(),
+          database_schema: if db == "main" { None } else { Some(db.clone()) },
+        };
+        if let Err(err) = check_column_removals_invalidating_config(&state, &source, &dropped) {
+          lints.push(MigrationLint {
+            level: LintLevel::Error,
+            statement: None,
+            message: err.to_string(),
+          });
+        }
+      }
+      Some(_) => {}
+    }
+  }
+
+  // Created tables and indexes.
+  for entry in &after {
+    if find(&before, &entry.r#type, &entry.name).is_some() {
+      continue;
+    }
+
+    match entry.r#type.as_str() {
+      "table" => affected_tables.push(entry.name.clone()),
+      "index" => {
+        let table = &entry.tbl_name;
+        if find(&before, "table", table).is_none() {
+          continue;
+        }
+        if !affected_tables.contains(table) {
+          affected_tables.push(table.clone());
+        }
+
+        let records = count_records(table).await.unwrap_or(0);
+        lints.push(MigrationLint {
+          level: if records > LARGE_TABLE {
+            LintLevel::Warning
+          } else {
+            LintLevel::Info
+          },
+          statement: None,
+          message: format!(
+            "Building index '{}' scans table '{table}' with {records} records",
+            entry.name
+          ),
+        });
+      }
+      _ => {}
+    }
+  }
+
+  return Ok(Json(LintMigrationResponse {
+    error: error.map(|(_, err)| err),
+    affected_tables,
+    lints,
+  }));
+}
+
+struct DryRun {
+  before: Vec<SchemaEntry>,
+  after: Vec<SchemaEntry>,
+  /// Index of the failing statement and its error.
+  error: Option<(usize, String)>,
+}
+
+async fn dry_run(
+  state: &AppState,
+  conn: &trailbase_sqlite::Connection,
+  path: &std::path::Path,
+  statements: Vec<String>,
+) -> Result<DryRun, Error> {
+  conn.backup(path).await?;
+
+  let copy = {
+    let path = path.to_path_buf();
+    let json_registry = state.json_schema_registry().clone();
+    let functions = state.connection_manager().sqlite_functions().to_vec();
+    trailbase_sqlite::Connection::with_opts(
+      move || {
+        return trailbase_extension::connect_sqlite_with_functions(
+          Some(path.clone()),
+          Some(json_registry.clone()),
+          &functions,
+        )
+        .map_err(|err| trailbase_sqlite::Error::Other(err.into()));
+      },
+      Default::default(),
+    )?
+  };
+
+  let result = copy
+    .transaction(move |mut tx| -> Result<DryRun, trailbase_sqlite::Error> {
+      use trailbase_sqlite::traits::{SyncConnection, SyncTransaction};
+
+      let before = snapshot_schema(&mut tx)?;
+      let mut error = None;
+      for (idx, sql) in statements.into_iter().enumerate() {
+        if let Err(err) = tx.execute_batch(sql) {
+          error = Some((idx, err.to_string()));
+          break;
+        }
+      }
+      let after = snapshot_schema(&mut tx)?;
+      tx.rollback()?;
+
+      return Ok(DryRun {
+        before,
+        after,
+        error,
+      });
+    })
+    .await;
+
+  if let Err(err) = copy.close().await {
+    warn!("Failed to close temporary copy: {err}");
+  }
+
+  return Ok(result?);
+}
+
+fn parse_table(sql: &str) -> Option<Table> {
+  return parse_into_statement(sql).ok()??.try_into().ok();
+}
+
+struct StmtFormatter<'a>(&'a Stmt);
+
+impl std::fmt::Display for StmtFormatter<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    self.0.to_fmt(f)
+  }
+}
+
+/// Number of records above which index builds are flagged.
+const LARGE_TABLE: i64 = 100_000;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::RecordApiConfig;
+  use crate::records::test_utils::add_record_api_config;
+
+  #[tokio::test]
+  async fn test_lint_migration() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(
+        r#"
+          CREATE TABLE movies (id INTEGER PRIMARY KEY, title TEXT NOT NULL, year INTEGER) STRICT;
+          INSERT INTO movies (title, year) VALUES ('Casablanca', 1942), ('Vertigo', 1958);
+        "#,
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("movies_api".to_string()),
+        table_name: Some("movies".to_string()),
+        excluded_columns: vec!["year".to_string()],
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let lint = async |sql: &str| {
+      return lint_migration_handler(
+        State(state.clone()),
+        Json(LintMigrationRequest {
+          sql: sql.to_string(),
+          database: None,
+        }),
+      )
+      .await
+      .map(|r| r.0);
+    };
+
+    assert!(lint("NOT SQL").await.is_err());
+
+    let response = lint("CREATE INDEX movies_title ON movies (title);")
+      .await
+      .unwrap();
+    assert_eq!(response.error, None);
+    assert_eq!(response.affected_tables, vec!["movies".to_string()]);
+    assert!(
+      response
+        .lints
+        .iter()
+        .any(|l| l.message.contains("2 records")),
+      "{response:?}"
+    );
+
+    let response = lint("ALTER TABLE movies DROP COLUMN year;").await.unwrap();
+    assert_eq!(response.error, None);
+    assert!(
+      response
+        .lints
+        .iter()
+        .any(|l| l.level == LintLevel::Error && l.message.contains("movies_api")),
+      "{response:?}"
+    );
+
+    let response = lint("DROP TABLE movies;").await.unwrap();
+    assert!(
+      response
+        .lints
+        .iter()
+        .any(|l| l.level == LintLevel::Error && l.message.contains("movies_api")),
+      "{response:?}"
+    );
+
+    let response =
+      lint("CREATE TABLE foo (id INTEGER PRIMARY KEY); INSERT INTO missing VALUES (1);")
+        .await
+        .unwrap();
+    assert!(response.error.is_some());
+    assert!(
+      response
+        .lints
+        .iter()
+        .any(|l| l.level == LintLevel::Error && l.statement == Some(1)),
+      "{response:?}"
+    );
+
+    // Nothing was applied.
+    conn
+      .read_query_rows("SELECT year FROM movies", ())
+      .await
+      .unwrap();
+    assert!(conn.read_query_rows("SELECT * FROM foo", ()).await.is_err());
+  }
+}
@@ -2,10 +2,15 @@
 mod alter_index;
 mod create_index;
 mod drop_index;
+mod table_indexes;
 
 pub(super) use alter_index::alter_index_handler;
 pub(super) use create_index::create_index_handler;
 pub(super) use drop_index::drop_index_handler;
+pub(crate) use table_indexes::list_indexes;
+pub(super) use table_indexes::{
+  create_table_index_handler, drop_table_index_handler, list_table_indexes_handler,
+};
 
 // Tables
 mod alter_table;
@@ -22,6 +27,23 @@ mod list_tables;
 
 pub(crate) use list_tables::list_tables_handler;
 
+// Declarative schema for Tables, Views and Indexes
+mod apply_schema;
+
+pub(crate) use apply_schema::apply_schema_handler;
+
+// Migrations
+mod lint_migration;
+mod rollback_migrations;
+
+pub(crate) use lint_migration::lint_migration_handler;
+
+pub use apply_schema::{ApplySchemaRequest, ApplySchemaResponse, apply_schema};
+pub(crate) use rollback_migrations::rollback_migrations_handler;
+pub use rollback_migrations::{
+  RollbackMigrationsRequest, RollbackMigrationsResponse, rollback_migrations,
+};
+
 /// Builds dedicated connection for database with given name.
 ///
 /// NOTE: We cannot use the ConnectionManager's facilities since migrations require DBs to be
@@ -36,14 +58,16 @@ fn get_conn_and_migration_path(
       let db_path = state.data_dir().data_path().join(format!("{db}.db"));
       let migration_path = state.data_dir().migrations_path().join(&db);
       let json_registry = state.json_schema_registry().clone();
+      let functions = state.connection_manager().sqlite_functions().to_vec();
 
       Ok((
         trailbase_sqlite::Connection::with_opts(
           move || {
             // TODO: We should load WASM SQLite functions, since migrations may depend on them.
-            return trailbase_extension::connect_sqlite(
+            return trailbase_extension::connect_sqlite_with_functions(
               Some(db_path.clone()),
               Some(json_registry.clone()),
+              &functions,
             )
             .map_err(|err| trailbase_sqlite::Error::Other(err.into()));
           },
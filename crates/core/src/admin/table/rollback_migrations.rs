@@ -0,0 +1,173 @@
+use axum::extract::{Json, State};
+use log::*;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::migrations::rollback_last_migration_batch;
+
+#[derive(Clone, Debug, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct RollbackMigrationsRequest {
+  /// Database to roll back migrations for. Defaults to "main".
+  pub database: Option<String>,
+  pub dry_run: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct RollbackMigrationsResponse {
+  /// Rolled back migrations, most recent first.
+  pub migrations: Vec<String>,
+  /// The applied down migrations.
+  pub sql: String,
+}
+
+/// Admin-only handler rolling back the most recently applied batch of migrations.
+pub async fn rollback_migrations_handler(
+  State(state): State<AppState>,
+  Json(request): Json<RollbackMigrationsRequest>,
+) -> Result<Json<RollbackMigrationsResponse>, Error> {
+  return Ok(Json(rollback_migrations(&state, request).await?));
+}
+
+pub async fn rollback_migrations(
+  state: &AppState,
+  request: RollbackMigrationsRequest,
+) -> Result<RollbackMigrationsResponse, Error> {
+  if state.demo_mode() {
+    return Err(Error::Precondition("Disallowed in demo".into()));
+  }
+
+  let dry_run = request.dry_run.unwrap_or(false);
+  let (conn, migration_path) = super::get_conn_and_migration_path(state, request.database)?;
+
+  let rolled_back = rollback_last_migration_batch(&conn, migration_path, dry_run).await?;
+  if !dry_run {
+    info!("Rolled back migrations: {:?}", rolled_back.migrations);
+    state.rebuild_connection_metadata().await?;
+  }
+
+  return Ok(RollbackMigrationsResponse {
+    migrations: rolled_back.migrations,
+    sql: rolled_back.sql,
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::admin::table::{ApplySchemaRequest, apply_schema};
+  use crate::app_state::test_state;
+
+  #[tokio::test]
+  async fn test_rollback_migrations() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    let rollback = async |dry_run: bool| {
+      return rollback_migrations(
+        &state,
+        RollbackMigrationsRequest {
+          database: None,
+          dry_run: Some(dry_run),
+        },
+      )
+      .await;
+    };
+
+    // Nothing to roll back yet, embedded migrations cannot be rolled back.
+    assert!(rollback(false).await.is_err());
+
+    let apply = async |schema: &str| {
+      apply_schema(
+        &state,
+        ApplySchemaRequest {
+          schema: schema.to_string(),
+          ..Default::default()
+        },
+      )
+      .await
+      .unwrap();
+    };
+
+    apply(
+      r#"
+        CREATE TABLE movies (id INTEGER PRIMARY KEY, title TEXT NOT NULL) STRICT;
+        CREATE INDEX movies_title ON movies (title);
+      "#,
+    )
+    .await;
+    conn
+      .execute(
+        "INSERT INTO movies (id, title) VALUES (1, 'Casablanca')",
+        (),
+      )
+      .await
+      .unwrap();
+
+    apply(
+      r#"
+        CREATE TABLE movies (
+          id INTEGER PRIMARY KEY,
+          title TEXT NOT NULL,
+          year INTEGER NOT NULL DEFAULT 0
+        ) STRICT;
+        CREATE INDEX movies_title ON movies (title);
+      "#,
+    )
+    .await;
+    conn
+      .read_query_rows("SELECT year FROM movies", ())
+      .await
+      .unwrap();
+
+    // Dry runs don't change anything.
+    let response = rollback(true).await.unwrap();
+    assert_eq!(response.migrations.len(), 1);
+    conn
+      .read_query_rows("SELECT year FROM movies", ())
+      .await
+      .unwrap();
+
+    // Revert the added column, retaining data and indexes.
+    rollback(false).await.unwrap();
+    assert!(
+      conn
+        .read_query_rows("SELECT year FROM movies", ())
+        .await
+        .is_err()
+    );
+    assert_eq!(
+      "Casablanca",
+      conn
+        .read_query_row_get::<String>("SELECT title FROM movies WHERE id = 1", (), 0)
+        .await
+        .unwrap()
+        .unwrap()
+    );
+    assert!(
+      conn
+        .read_query_row_get::<bool>(
+          "SELECT EXISTS(SELECT 1 FROM sqlite_schema WHERE name = 'movies_title')",
+          (),
+          0
+        )
+        .await
+        .unwrap()
+        .unwrap()
+    );
+
+    // Revert the table creation.
+    rollback(false).await.unwrap();
+    assert!(
+      conn
+        .read_query_rows("SELECT * FROM movies", ())
+        .await
+        .is_err()
+    );
+
+    assert!(rollback(false).await.is_err());
+  }
+}
@@ -0,0 +1,248 @@
+use axum::extract::{Json, Path, Query, State};
+use serde::{Deserialize, Serialize};
+use trailbase_schema::parse::parse_into_statement;
+use trailbase_schema::sqlite::{QualifiedName, TableIndex};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::admin::table::create_index::{CreateIndexRequest, CreateIndexResponse, create_index};
+use crate::admin::table::drop_index::{DropIndexRequest, DropIndexResponse, drop_index};
+use crate::app_state::AppState;
+use crate::connection::ConnectionEntry;
+use crate::constants::SQLITE_SCHEMA_TABLE;
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListIndexesResponse {
+  /// Indexes on the table and their `CREATE INDEX` statements.
+  pub indexes: Vec<(TableIndex, String)>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct DropTableIndexQuery {
+  pub dry_run: Option<bool>,
+}
+
+/// Lists the indexes of the given table. Implicit indexes, e.g. for UNIQUE constraints, are
+/// skipped since they're part of the table's schema.
+pub(crate) async fn list_indexes(
+  state: &AppState,
+  table_name: &QualifiedName,
+) -> Result<Vec<(TableIndex, String)>, Error> {
+  let ConnectionEntry {
+    connection: conn, ..
+  } = state
+    .connection_manager()
+    .get_entry_for_qn(table_name)
+    .await?;
+
+  let db = table_name.database_schema.as_deref().unwrap_or("main");
+  let rows = conn
+    .read_query_rows(
+      format!(
+        r#"
+          SELECT sql FROM "{db}"."{SQLITE_SCHEMA_TABLE}"
+            WHERE type = 'index' AND tbl_name = $1 AND sql IS NOT NULL
+            ORDER BY name
+        "#
+      ),
+      params!(table_name.name.clone()),
+    )
+    .await?;
+
+  let mut indexes = vec![];
+  for row in rows.iter() {
+    let sql: String = row.get(0)?;
+    let Some(stmt) = parse_into_statement(&sql).map_err(|err| Error::Internal(err.into()))? else {
+      continue;
+    };
+
+    let mut index: TableIndex = stmt.try_into()?;
+    index.name.database_schema = table_name.database_schema.clone();
+    indexes.push((index, sql));
+  }
+
+  return Ok(indexes);
+}
+
+pub async fn list_table_indexes_handler(
+  State(state): State<AppState>,
+  Path(table_name): Path<String>,
+) -> Result<Json<ListIndexesResponse>, Error> {
+  let table_name = QualifiedName::parse(&table_name)?;
+  return Ok(Json(ListIndexesResponse {
+    indexes: list_indexes(&state, &table_name).await?,
+  }));
+}
+
+pub async fn create_table_index_handler(
+  State(state): State<AppState>,
+  Path(table_name): Path<String>,
+  Json(mut request): Json<CreateIndexRequest>,
+) -> Result<Json<CreateIndexResponse>, Error> {
+  let table_name = QualifiedName::parse(&table_name)?;
+
+  // Indexes live in the same database as their table.
+  if request.schema.name.database_schema.is_some()
+    && request.schema.name.database_schema != table_name.database_schema
+  {
+    return Err(Error::BadRequest(
+      "Index and table must be in the same database".into(),
+    ));
+  }
+  request.schema.name.database_schema = table_name.database_schema;
+  request.schema.table_name = table_name.name;
+
+  return Ok(Json(create_index(&state, request).await?));
+}
+
+pub async fn drop_table_index_handler(
+  State(state): State<AppState>,
+  Path((table_name, index_name)): Path<(String, String)>,
+  Query(query): Query<DropTableIndexQuery>,
+) -> Result<Json<DropIndexResponse>, Error> {
+  let table_name = QualifiedName::parse(&table_name)?;
+
+  let Some((index, _sql)) = list_indexes(&state, &table_name)
+    .await?
+    .into_iter()
+    .find(|(index, _)| index.name.name == index_name)
+  else {
+    return Err(Error::Precondition(format!(
+      "Index '{index_name}' not found on table '{}'",
+      table_name.name
+    )));
+  };
+
+  return Ok(Json(
+    drop_index(
+      &state,
+      DropIndexRequest {
+        name: index.name.escaped_string(),
+        dry_run: query.dry_run,
+      },
+    )
+    .await?,
+  ));
+}
+
+#[cfg(test)]
+mod tests {
+  use trailbase_schema::sqlite::ColumnOrder;
+
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[tokio::test]
+  async fn test_table_index_lifecycle() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "CREATE TABLE person (id INTEGER PRIMARY KEY, email TEXT NOT NULL, deleted INTEGER) STRICT",
+      )
+      .await
+      .unwrap();
+
+    let request = || CreateIndexRequest {
+      schema: TableIndex {
+        name: QualifiedName::parse("_person__email_index").unwrap(),
+        table_name: "ignored".to_string(),
+        columns: vec![ColumnOrder {
+          column_name: "lower(email)".to_string(),
+          ascending: None,
+          nulls_first: None,
+          expression: true,
+        }],
+        unique: true,
+        predicate: Some("deleted IS NULL".to_string()),
+        if_not_exists: false,
+      },
+      dry_run: None,
+    };
+
+    let response = create_table_index_handler(
+      State(state.clone()),
+      Path("person".to_string()),
+      Json(CreateIndexRequest {
+        dry_run: Some(true),
+        ..request()
+      }),
+    )
+    .await
+    .unwrap();
+    assert!(response.sql.contains("lower(email)"), "{}", response.sql);
+    assert!(
+      list_indexes(&state, &QualifiedName::parse("person").unwrap())
+        .await
+        .unwrap()
+        .is_empty()
+    );
+
+    create_table_index_handler(
+      State(state.clone()),
+      Path("person".to_string()),
+      Json(request()),
+    )
+    .await
+    .unwrap();
+
+    let indexes = list_table_indexes_handler(State(state.clone()), Path("person".to_string()))
+      .await
+      .unwrap()
+      .0
+      .indexes;
+    assert_eq!(indexes.len(), 1);
+    let (index, _sql) = &indexes[0];
+    assert_eq!(index.table_name, "person");
+    assert!(index.unique);
+    assert!(index.columns[0].expression);
+    assert!(index.predicate.is_some());
+
+    // Uniqueness is enforced on the expression for non-deleted rows only.
+    state
+      .conn()
+      .execute_batch(
+        "\
+          INSERT INTO person (email) VALUES ('a@b.c'); \
+          INSERT INTO person (email, deleted) VALUES ('A@b.c', 1); \
+        ",
+      )
+      .await
+      .unwrap();
+    assert!(
+      state
+        .conn()
+        .execute_batch("INSERT INTO person (email) VALUES ('A@B.C')")
+        .await
+        .is_err()
+    );
+
+    assert!(
+      drop_table_index_handler(
+        State(state.clone()),
+        Path(("person".to_string(), "missing".to_string())),
+        Query(DropTableIndexQuery::default()),
+      )
+      .await
+      .is_err()
+    );
+
+    drop_table_index_handler(
+      State(state.clone()),
+      Path(("person".to_string(), "_person__email_index".to_string())),
+      Query(DropTableIndexQuery::default()),
+    )
+    .await
+    .unwrap();
+
+    assert!(
+      list_indexes(&state, &QualifiedName::parse("person").unwrap())
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
+}
@@ -0,0 +1,129 @@
+use axum::{
+  Json,
+  extract::State,
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::constants::{TENANT_TABLE, TENANT_USER_TABLE};
+use crate::tenants::{TenantError, register_tenant, unregister_tenant};
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct TenantJson {
+  pub name: String,
+  pub created: i64,
+  /// Number of users assigned to the tenant.
+  pub users: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListTenantsResponse {
+  tenants: Vec<TenantJson>,
+}
+
+pub async fn list_tenants_handler(
+  State(state): State<AppState>,
+) -> Result<Json<ListTenantsResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT \
+        t.name, t.created, \
+        (SELECT COUNT(*) FROM '{TENANT_USER_TABLE}' WHERE tenant = t.name) AS users \
+      FROM '{TENANT_TABLE}' AS t \
+      ORDER BY t.name \
+    "
+  );
+
+  return Ok(Json(ListTenantsResponse {
+    tenants: state
+      .user_conn()
+      .read_query_values::<TenantJson>(QUERY, ())
+      .await?,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct CreateTenantRequest {
+  /// Tenant name. May only contain lowercase alphanumeric characters, '_' and '-'.
+  pub name: String,
+}
+
+/// Registers a tenant and creates its database.
+pub async fn create_tenant_handler(
+  State(state): State<AppState>,
+  Json(request): Json<CreateTenantRequest>,
+) -> Result<Response, Error> {
+  register_tenant(&state, &request.name).await?;
+
+  return Ok((StatusCode::OK, "created").into_response());
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct DeleteTenantRequest {
+  pub name: String,
+}
+
+/// Unregisters a tenant and unassigns all its users. The tenant's database file is kept.
+pub async fn delete_tenant_handler(
+  State(state): State<AppState>,
+  Json(request): Json<DeleteTenantRequest>,
+) -> Result<Response, Error> {
+  unregister_tenant(&state, &request.name).await?;
+
+  return Ok((StatusCode::OK, "deleted").into_response());
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct UserTenantRequest {
+  pub user_id: String,
+  /// Tenant to assign the user to or none to unassign.
+  pub tenant: Option<String>,
+}
+
+/// Assigns a user to a tenant. Takes effect immediately for tenant routing, while the `tenant` claim
+/// is updated once the user's auth token gets refreshed.
+pub async fn assign_user_tenant_handler(
+  State(state): State<AppState>,
+  Json(request): Json<UserTenantRequest>,
+) -> Result<Response, Error> {
+  let user_id = Uuid::parse_str(&request.user_id).map_err(|err| Error::BadRequest(err.into()))?;
+
+  let Some(tenant) = request.tenant else {
+    const QUERY: &str = formatcp!("DELETE FROM '{TENANT_USER_TABLE}' WHERE user = $1");
+    state
+      .user_conn()
+      .execute(QUERY, params!(user_id.into_bytes()))
+      .await?;
+
+    return Ok((StatusCode::OK, "unassigned").into_response());
+  };
+
+  const QUERY: &str = formatcp!(
+    "\
+      INSERT INTO '{TENANT_USER_TABLE}' (user, tenant) \
+      SELECT $1, name FROM '{TENANT_TABLE}' WHERE name = $2 \
+      ON CONFLICT DO UPDATE SET tenant = excluded.tenant \
+    "
+  );
+  let rows_affected = state
+    .user_conn()
+    .execute(QUERY, params!(user_id.into_bytes(), tenant))
+    .await?;
+  if rows_affected == 0 {
+    return Err(TenantError::NotFound.into());
+  }
+
+  return Ok((StatusCode::OK, "assigned").into_response());
+}
@@ -8,9 +8,10 @@ use uuid::Uuid;
 use crate::admin::AdminError as Error;
 use crate::app_state::AppState;
 use crate::auth::jwt::EmailVerificationTokenClaims;
-use crate::auth::password::{hash_password, validate_password_policy};
+use crate::auth::password::{hash_password, validate_password};
 use crate::auth::user::DbUser;
 use crate::auth::util::{user_exists, validate_and_normalize_email_address};
+use crate::auth::webhooks::emit_user_created;
 use crate::constants::USER_TABLE;
 use crate::email::Email;
 
@@ -35,11 +36,12 @@ pub async fn create_user_handler(
   let normalized_email = validate_and_normalize_email_address(&request.email)?;
 
   let auth_options = state.auth_options();
-  validate_password_policy(
+  validate_password(
     &request.password,
     &request.password,
     auth_options.password_options(),
-  )?;
+  )
+  .await?;
 
   if user_exists(&state, &normalized_email).await {
     return Err(Error::AlreadyExists("user"));
@@ -73,6 +75,8 @@ pub async fn create_user_handler(
     return Err(Error::Precondition("Internal".into()));
   };
 
+  emit_user_created(&state, &user, "admin");
+
   // Send an email
   if let Some(ref email) = user.email
     && !request.verified
@@ -87,7 +91,8 @@ pub async fn create_user_handler(
 
     // NOTE: We cannot pass a valid redirect_uri, since we cannot be sure if auth UI is
     // installed.
-    Email::verification_email(&state, email, &token, None)?
+    Email::verification_email(&state, email, &token, None)
+      .await?
       .send()
       .await?;
   }
@@ -12,6 +12,8 @@ use ts_rs::TS;
 use crate::admin::AdminError as Error;
 use crate::admin::rows::delete_row;
 use crate::app_state::AppState;
+use crate::audit::{AuditAction, record_audit_event};
+use crate::auth::User;
 use crate::auth::util::is_admin;
 use crate::util::uuid_to_b64;
 
@@ -23,6 +25,7 @@ pub struct DeleteUserRequest {
 
 pub async fn delete_user_handler(
   State(state): State<AppState>,
+  user: User,
   Json(request): Json<DeleteUserRequest>,
 ) -> Result<Response, Error> {
   if is_admin(&state, &request.id).await {
@@ -42,5 +45,14 @@ pub async fn delete_user_handler(
   )
   .await?;
 
+  record_audit_event(
+    &state,
+    Some(user.uuid),
+    AuditAction::UserDeleted,
+    &request.id.to_string(),
+    None,
+  )
+  .await;
+
   return Ok((StatusCode::OK, "deleted").into_response());
 }
@@ -0,0 +1,61 @@
+use axum::{
+  Json,
+  extract::State,
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use const_format::formatcp;
+use serde::Deserialize;
+use trailbase_sqlite::params;
+use trailbase_sqlite::traits::SyncTransaction;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::auth::api::change_email::apply_pending_email_change;
+use crate::constants::EMAIL_CHANGE_TABLE;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct UserEmailChangeRequest {
+  /// Id of the user with a pending email change.
+  id: uuid::Uuid,
+}
+
+/// Applies a user's pending email change without waiting for confirmation via email, e.g. when the
+/// old address is no longer accessible.
+pub async fn confirm_user_email_change_handler(
+  State(state): State<AppState>,
+  Json(request): Json<UserEmailChangeRequest>,
+) -> Result<Response, Error> {
+  let user_id = request.id;
+  let applied = state
+    .user_conn()
+    .transaction(move |mut tx| -> Result<bool, trailbase_sqlite::Error> {
+      if !apply_pending_email_change(&mut tx, user_id)? {
+        return Ok(false);
+      }
+      tx.commit()?;
+      return Ok(true);
+    })
+    .await?;
+
+  if !applied {
+    return Ok((StatusCode::NOT_FOUND, "no pending email change").into_response());
+  }
+  return Ok((StatusCode::OK, "email changed").into_response());
+}
+
+/// Cancels a user's pending email change.
+pub async fn cancel_user_email_change_handler(
+  State(state): State<AppState>,
+  Json(request): Json<UserEmailChangeRequest>,
+) -> Result<Response, Error> {
+  const QUERY: &str = formatcp!("DELETE FROM '{EMAIL_CHANGE_TABLE}' WHERE user = $1");
+  state
+    .user_conn()
+    .execute(QUERY, params!(request.id.into_bytes()))
+    .await?;
+
+  return Ok((StatusCode::OK, "cancelled").into_response());
+}
@@ -1,11 +1,17 @@
 mod create_user;
 mod delete_user;
+mod email_change;
 mod list_users;
+mod sessions;
 mod update_user;
 
 pub use create_user::{CreateUserRequest, create_user_handler};
 pub(super) use delete_user::delete_user_handler;
+pub(super) use email_change::{
+  cancel_user_email_change_handler, confirm_user_email_change_handler,
+};
 pub(super) use list_users::list_users_handler;
+pub(super) use sessions::{delete_user_session_handler, list_user_sessions_handler};
 pub(super) use update_user::update_user_handler;
 
 #[cfg(test)]
@@ -21,7 +27,7 @@ mod tests {
   use crate::app_state::{TestStateOptions, test_state};
   use crate::auth::util::user_by_email;
   use crate::constants::USER_TABLE;
-  use crate::email::{Mailer, testing::TestAsyncSmtpTransport};
+  use crate::email::{SmtpMailer, testing::TestAsyncSmtpTransport};
 
   use super::create_user::*;
 
@@ -33,7 +39,7 @@ mod tests {
 
     let mailer = TestAsyncSmtpTransport::new();
     let state = test_state(Some(TestStateOptions {
-      mailer: Some(Mailer::Smtp(Arc::new(mailer.clone()))),
+      mailer: Some(Arc::new(SmtpMailer::new(mailer.clone()))),
       ..Default::default()
     }))
     .await
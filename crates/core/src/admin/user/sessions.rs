@@ -0,0 +1,54 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::auth::api::sessions::{SessionJson, list_sessions_for_user};
+use crate::constants::SESSION_TABLE;
+
+#[derive(Debug, Deserialize)]
+pub struct ListUserSessionsQuery {
+  user_id: uuid::Uuid,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListUserSessionsResponse {
+  sessions: Vec<SessionJson>,
+}
+
+pub async fn list_user_sessions_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListUserSessionsQuery>,
+) -> Result<Json<ListUserSessionsResponse>, Error> {
+  return Ok(Json(ListUserSessionsResponse {
+    sessions: list_sessions_for_user(state.session_conn(), query.user_id, None).await?,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct DeleteUserSessionRequest {
+  id: i64,
+}
+
+pub async fn delete_user_session_handler(
+  State(state): State<AppState>,
+  Json(request): Json<DeleteUserSessionRequest>,
+) -> Result<Response, Error> {
+  const QUERY: &str = formatcp!("DELETE FROM '{SESSION_TABLE}' WHERE id = $1");
+  state
+    .session_conn()
+    .execute(QUERY, params!(request.id))
+    .await?;
+
+  return Ok((StatusCode::OK, "deleted").into_response());
+}
@@ -12,6 +12,8 @@ use ts_rs::TS;
 
 use crate::admin::AdminError as Error;
 use crate::app_state::AppState;
+use crate::audit::{AuditAction, record_audit_event};
+use crate::auth::User;
 use crate::auth::password::hash_password;
 use crate::auth::util::is_admin;
 use crate::auth::util::validate_and_normalize_username;
@@ -36,6 +38,7 @@ pub struct UpdateUserRequest {
 
 pub async fn update_user_handler(
   State(state): State<AppState>,
+  user: User,
   Json(request): Json<UpdateUserRequest>,
 ) -> Result<Response, Error> {
   let UpdateUserRequest {
@@ -52,6 +55,18 @@ pub async fn update_user_handler(
     ));
   }
 
+  // NOTE: Passwords are never logged, only the fact that they were changed.
+  let mut changes = serde_json::Map::new();
+  if let Some(ref email) = email {
+    changes.insert("email".to_string(), email.clone().into());
+  }
+  if let Some(ref username) = username {
+    changes.insert("username".to_string(), username.clone().into());
+  }
+  if let Some(verified) = verified {
+    changes.insert("verified".to_string(), verified.into());
+  }
+
   let user_id_bytes: [u8; 16] = user_id.into_bytes();
   let hashed_password = match password {
     Some(ref pw) => Some(hash_password(pw)?),
@@ -78,7 +93,7 @@ pub async fn update_user_handler(
     "
   );
 
-  return match state
+  let rows_affected = state
     .user_conn()
     .execute(
       UPDATE_QUERY,
@@ -102,10 +117,35 @@ pub async fn update_user_handler(
           ":verified": verified.map_or(Value::Null, |v| Value::Integer(if v {1} else {0})),
       },
     )
-    .await?
-  {
+    .await?;
+
+  return match rows_affected {
     0 => Ok((StatusCode::NOT_FOUND, "race?").into_response()),
-    1 => Ok((StatusCode::OK, "updated").into_response()),
+    1 => {
+      let target = user_id.to_string();
+      if !changes.is_empty() {
+        record_audit_event(
+          &state,
+          Some(user.uuid),
+          AuditAction::UserUpdated,
+          &target,
+          Some(changes.into()),
+        )
+        .await;
+      }
+      if password.is_some() {
+        record_audit_event(
+          &state,
+          Some(user.uuid),
+          AuditAction::PasswordResetForced,
+          &target,
+          None,
+        )
+        .await;
+      }
+
+      Ok((StatusCode::OK, "updated").into_response())
+    }
     _ => {
       unreachable!("user id must be unique");
     }
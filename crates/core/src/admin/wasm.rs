@@ -0,0 +1,69 @@
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct WasmComponentJson {
+  pub path: String,
+  pub enabled: bool,
+  pub in_flight: usize,
+  pub calls: u64,
+  pub errors: u64,
+  pub deadlines_exceeded: u64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListWasmComponentsResponse {
+  pub components: Vec<WasmComponentJson>,
+}
+
+/// Lists loaded WASM components alongside their invocation metrics.
+pub async fn list_wasm_components_handler(
+  State(state): State<AppState>,
+) -> Result<Json<ListWasmComponentsResponse>, Error> {
+  let mut components = vec![];
+  for runtime in state.wasm_runtimes() {
+    let runtime = runtime.read().await;
+    let metrics = runtime.metrics();
+
+    components.push(WasmComponentJson {
+      path: runtime.component_path().to_string_lossy().to_string(),
+      enabled: runtime.enabled(),
+      in_flight: metrics.in_flight,
+      calls: metrics.calls,
+      errors: metrics.errors,
+      deadlines_exceeded: metrics.deadlines_exceeded,
+    });
+  }
+
+  return Ok(Json(ListWasmComponentsResponse { components }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct SetWasmComponentEnabledRequest {
+  pub path: String,
+  pub enabled: bool,
+}
+
+/// Kill switch for misbehaving components. In-flight invocations of disabled components, e.g. HTTP
+/// requests or job runs, are aborted and subsequent ones fail right away.
+pub async fn set_wasm_component_enabled_handler(
+  State(state): State<AppState>,
+  Json(request): Json<SetWasmComponentEnabledRequest>,
+) -> Result<(), Error> {
+  for runtime in state.wasm_runtimes() {
+    let runtime = runtime.read().await;
+    if runtime.component_path().to_string_lossy() == request.path {
+      runtime.set_enabled(request.enabled);
+      return Ok(());
+    }
+  }
+
+  return Err(Error::BadRequest("unknown component".into()));
+}
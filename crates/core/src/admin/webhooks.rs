@@ -0,0 +1,156 @@
+use axum::{
+  Json,
+  extract::{Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::params;
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::auth::webhooks::{DeliveryStatus, redeliver};
+use crate::constants::{WEBHOOK_DELIVERIES_TABLE, WEBHOOK_QUEUE_TABLE};
+use crate::records::webhooks::{QueueStatus, retry};
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct WebhookDeliveryJson {
+  pub id: i64,
+  pub webhook: String,
+  pub event: String,
+  pub payload: String,
+  /// 0: pending, 1: delivered, 2: failed.
+  pub status: i64,
+  pub attempts: i64,
+  pub last_error: Option<String>,
+  pub created: i64,
+  pub updated: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListWebhookDeliveriesQuery {
+  /// Only list failed deliveries.
+  failed: Option<bool>,
+  limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListWebhookDeliveriesResponse {
+  deliveries: Vec<WebhookDeliveryJson>,
+}
+
+/// Lists the most recent webhook deliveries.
+pub async fn list_webhook_deliveries_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListWebhookDeliveriesQuery>,
+) -> Result<Json<ListWebhookDeliveriesResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, webhook, event, payload, status, attempts, last_error, created, updated \
+      FROM '{WEBHOOK_DELIVERIES_TABLE}' \
+      WHERE $1 IS NULL OR status = $1 \
+      ORDER BY id DESC LIMIT $2 \
+    "
+  );
+
+  let status = query
+    .failed
+    .unwrap_or(false)
+    .then_some(DeliveryStatus::Failed as i64);
+  let limit = query.limit.unwrap_or(100).min(1024) as i64;
+
+  return Ok(Json(ListWebhookDeliveriesResponse {
+    deliveries: state
+      .user_conn()
+      .read_query_values::<WebhookDeliveryJson>(QUERY, params!(status, limit))
+      .await?,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct RedeliverWebhookRequest {
+  pub id: i64,
+}
+
+/// Resets the given delivery, e.g. a failed one, and delivers it again in the background.
+pub async fn redeliver_webhook_handler(
+  State(state): State<AppState>,
+  Json(request): Json<RedeliverWebhookRequest>,
+) -> Result<Response, Error> {
+  redeliver(&state, request.id).await?;
+
+  return Ok((StatusCode::OK, "scheduled").into_response());
+}
+
+#[derive(Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct QueuedWebhookEventJson {
+  pub id: i64,
+  pub webhook: String,
+  pub event: String,
+  pub payload: String,
+  /// 0: pending, 2: failed.
+  pub status: i64,
+  pub attempts: i64,
+  pub next_attempt: i64,
+  pub last_error: Option<String>,
+  pub created: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListWebhookQueueResponse {
+  events: Vec<QueuedWebhookEventJson>,
+}
+
+/// Lists record change events, which have not been delivered yet. Delivered events are removed
+/// from the queue.
+pub async fn list_webhook_queue_handler(
+  State(state): State<AppState>,
+  Query(query): Query<ListWebhookDeliveriesQuery>,
+) -> Result<Json<ListWebhookQueueResponse>, Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, webhook, event, payload, status, attempts, next_attempt, last_error, created \
+      FROM '{WEBHOOK_QUEUE_TABLE}' \
+      WHERE $1 IS NULL OR status = $1 \
+      ORDER BY id DESC LIMIT $2 \
+    "
+  );
+
+  let status = query
+    .failed
+    .unwrap_or(false)
+    .then_some(QueueStatus::Failed as i64);
+  let limit = query.limit.unwrap_or(100).min(1024) as i64;
+
+  return Ok(Json(ListWebhookQueueResponse {
+    events: state
+      .user_conn()
+      .read_query_values::<QueuedWebhookEventJson>(QUERY, params!(status, limit))
+      .await?,
+  }));
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct RetryWebhookEventRequest {
+  pub id: i64,
+}
+
+/// Resets the given queued event, e.g. a failed one, and delivers it again in the background.
+pub async fn retry_webhook_event_handler(
+  State(state): State<AppState>,
+  Json(request): Json<RetryWebhookEventRequest>,
+) -> Result<Response, Error> {
+  if !retry(&state, request.id).await? {
+    return Err(Error::BadRequest("unknown event".into()));
+  }
+
+  return Ok((StatusCode::OK, "scheduled").into_response());
+}
@@ -1,3 +1,4 @@
+use const_format::formatcp;
 use log::*;
 use object_store::ObjectStore;
 use std::collections::HashMap;
@@ -5,23 +6,43 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use trailbase_auth_config::{AuthConfig, LoginIdentifier, OAuthProvider, RegistrationIdentifier};
+use trailbase_extension::crypto::EncryptionKey;
 use trailbase_extension::jsonschema::JsonSchemaRegistry;
 use trailbase_reactive::{AsyncReactive, DeriveInput, Reactive};
+use trailbase_sqlite::ConnectionType;
+use trailbase_wasm_common::RecordHookOperation;
 
+use crate::audit::{AuditAction, diff_lines, record_audit_event};
 use crate::auth::jwt::JwtHelper;
 use crate::auth::options::AuthOptions;
 use crate::config::proto::{
-  Config, JsonSchemaConfig, RecordApiConfig, S3StorageConfig, UserIdentifier, hash_config,
+  Config, JsonSchemaConfig, ObjectStoreConfig, RecordApiConfig, S3StorageConfig, ServerConfig,
+  UserIdentifier, hash_config,
+};
+use crate::config::{
+  ConfigError, redact_secrets, validate_config, write_config_and_vault_textproto,
 };
-use crate::config::{ConfigError, validate_config, write_config_and_vault_textproto};
 use crate::connection::{BuildOptions, ConnectionEntry, ConnectionError, ConnectionManager};
+use crate::constants::CONFIG_HISTORY_TABLE;
 use crate::data_dir::DataDir;
-use crate::email::Mailer;
-use crate::records::RecordApi;
+use crate::email::{EmailQueue, Mailer, build_mailer};
+use crate::logging::{
+  LogSink, LogsFlusher, build_log_sinks, install_slow_query_recorder, spawn_slow_query_writer,
+};
+use crate::notifications::{PushProviders, build_push_providers};
+use crate::queue::TaskQueue;
+use crate::rate_limit::RateLimiter;
+use crate::records::file_encryption::build_file_key_provider;
+use crate::records::hooks::AsyncRecordHook;
+use crate::records::scanner::build_upload_scanner;
 use crate::records::subscribe::manager::SubscriptionManager;
-use crate::scheduler::{JobRegistry, build_job_registry_from_config};
+use crate::records::{FileKeyProvider, RecordApi, RecordHooks, UploadScanner};
+use crate::scheduler::{JobRegistry, build_job_registry_from_config, restore_persisted_jobs};
+use crate::tenants::Tenant;
 use crate::wasm::Runtime;
 
+type AsyncRecordHooks = HashMap<(String, RecordHookOperation), Vec<Arc<dyn AsyncRecordHook>>>;
+
 /// The app's internal state. AppState needs to be clonable which puts unnecessary constraints on
 /// the internals. Thus rather arc once than many times.
 struct InternalState {
@@ -35,10 +56,22 @@ struct InternalState {
   demo: bool,
 
   auth: Reactive<Arc<AuthOptions>>,
+  auth_rate_limiters: Reactive<Arc<HashMap<String, RateLimiter>>>,
   jobs: Reactive<Arc<JobRegistry>>,
-  mailer: Reactive<Mailer>,
+  mailer: Reactive<Arc<dyn Mailer>>,
+  push_providers: Reactive<Arc<PushProviders>>,
   config: Reactive<Config>,
   json_schema_registry: Arc<parking_lot::RwLock<JsonSchemaRegistry>>,
+  record_hooks: parking_lot::RwLock<Arc<Vec<Arc<dyn RecordHooks>>>>,
+  async_record_hooks: parking_lot::RwLock<Arc<AsyncRecordHooks>>,
+  file_key_provider: Reactive<Option<Arc<dyn FileKeyProvider>>>,
+  custom_file_key_provider: parking_lot::RwLock<Option<Arc<dyn FileKeyProvider>>>,
+  upload_scanner: Reactive<Option<Arc<dyn UploadScanner>>>,
+  custom_upload_scanners: parking_lot::RwLock<Arc<Vec<Arc<dyn UploadScanner>>>>,
+  log_sinks: Reactive<Arc<Vec<Arc<dyn LogSink>>>>,
+  logs_flusher: parking_lot::RwLock<Option<LogsFlusher>>,
+  task_queue: Arc<TaskQueue>,
+  email_queue: Arc<EmailQueue>,
 
   // TODO: Maybe remove main `conn` in favor of connection manager. Note that this is currently
   // also used for the state.user_conn().
@@ -50,8 +83,11 @@ struct InternalState {
   jwt: JwtHelper,
 
   record_apis: AsyncReactive<HashMap<String, RecordApi>>,
+  /// Instances of tenant-scoped record APIs keyed by (tenant, API name) alongside the base API
+  /// they were built from.
+  tenant_record_apis: quick_cache::sync::Cache<(String, String), (RecordApi, RecordApi)>,
   subscription_manager: SubscriptionManager,
-  object_store: Arc<dyn ObjectStore>,
+  object_store: Reactive<Arc<dyn ObjectStore>>,
 
   /// Actual WASM runtimes.
   wasm_runtimes: Vec<Arc<RwLock<Runtime>>>,
@@ -118,7 +154,23 @@ impl AppState {
     .await;
 
     let main_conn = args.connection_manager.main_entry().connection;
-    let object_store: Arc<dyn ObjectStore> = args.object_store.into();
+    let object_store: Reactive<Arc<dyn ObjectStore>> = Reactive::new(args.object_store.into());
+    {
+      // Rebuild the object store whenever the storage config changes. Note that this observer
+      // needs to be registered before the jobs', which capture the current object store.
+      let data_dir = args.data_dir.clone();
+      let object_store = object_store.clone();
+      config
+        .derive(|c| objectstore_config(&c.server))
+        .add_observer(move |c| {
+          debug!("(re-)building object store from config");
+
+          match build_objectstore(&data_dir, c.as_ref().as_ref()) {
+            Ok(store) => object_store.set(store.into()),
+            Err(err) => error!("Failed to build object store, keeping previous: {err}"),
+          };
+        });
+    }
     let jobs_input = (
       args.data_dir.clone(),
       args.connection_manager.clone(),
@@ -152,10 +204,35 @@ impl AppState {
       args.wasm_tokio_runtime,
       args.runtime_root_fs.clone(),
       Some(shared_kv_store),
+      &config,
       args.dev,
     )
     .expect("startup");
 
+    {
+      // Record slow statements across all connections into the logs DB.
+      let sender = spawn_slow_query_writer(args.logs_conn.clone());
+      let threshold = config.derive(|c| c.server.slow_query_threshold_ms);
+      install_slow_query_recorder(&sender, threshold.value());
+      threshold.add_observer(move |threshold_ms| {
+        install_slow_query_recorder(&sender, **threshold_ms);
+      });
+    }
+
+    {
+      // Key for the `encrypt()`/`decrypt()` SQL functions, which is shared by all connections.
+      let key = config.derive(|c| c.server.sql_encryption_key.clone());
+      install_sql_encryption_key(key.value().as_deref());
+      key.add_observer(move |key| install_sql_encryption_key(key.as_deref()));
+    }
+
+    let log_sinks = {
+      let data_dir = args.data_dir.clone();
+      config
+        .derive(|c| c.server.log_sinks.clone())
+        .derive_unchecked(move |c| build_log_sinks(&data_dir, c))
+    };
+
     AppState {
       state: Arc::new(InternalState {
         data_dir: args.data_dir,
@@ -166,35 +243,63 @@ impl AppState {
         dev: args.dev,
         demo: args.demo,
         auth: config.derive_unchecked(|c| Arc::new(AuthOptions::from_config(c.auth.clone()))),
+        auth_rate_limiters: config.derive_unchecked(build_auth_rate_limiters),
         jobs: config.derive_unchecked(move |c| {
           debug!("(re-)building jobs from config");
 
           let (data_dir, conn_mgr, logs_conn, session_conn, object_store) = &jobs_input;
 
-          return Arc::new(
+          let jobs = Arc::new(
             build_job_registry_from_config(
               c,
               data_dir,
               conn_mgr,
               logs_conn,
               session_conn,
-              object_store.clone(),
+              object_store.value(),
             )
             .unwrap_or_else(|err| {
               error!("Failed to build JobRegistry for cron jobs: {err}");
               return JobRegistry::new();
             }),
           );
+
+          // Register user-defined jobs and apply persisted pause states.
+          tokio::spawn({
+            let jobs = jobs.clone();
+            async move {
+              if let Err(err) = restore_persisted_jobs(jobs).await {
+                error!("Failed to restore persisted jobs: {err}");
+              }
+            }
+          });
+
+          return jobs;
         }),
-        mailer: config.derive_unchecked(Mailer::new_from_config),
+        mailer: config.derive_unchecked(build_mailer),
+        push_providers: config
+          .derive_unchecked(|c| Arc::new(build_push_providers(c.push.as_ref()))),
         config,
         json_schema_registry: args.json_schema_registry,
+        record_hooks: Default::default(),
+        async_record_hooks: Default::default(),
+        file_key_provider: config
+          .derive_unchecked(|c| build_file_key_provider(c.server.file_encryption.as_ref())),
+        custom_file_key_provider: Default::default(),
+        upload_scanner: config
+          .derive_unchecked(|c| build_upload_scanner(c.server.upload_scan.as_ref())),
+        custom_upload_scanners: Default::default(),
+        log_sinks,
+        logs_flusher: Default::default(),
+        task_queue: Arc::new(TaskQueue::new((*main_conn).clone())),
+        email_queue: Arc::new(EmailQueue::new((*main_conn).clone())),
         conn: (*main_conn).clone(),
         session_conn: args.session_conn,
         logs_conn: args.logs_conn,
         connection_manager: args.connection_manager,
         jwt: args.jwt,
         record_apis: record_apis.clone(),
+        tenant_record_apis: quick_cache::sync::Cache::new(256),
         subscription_manager: SubscriptionManager::new(record_apis),
         object_store,
         wasm_runtimes: wasm_runtimes_builder()
@@ -242,11 +347,145 @@ impl AppState {
     return &self.state.json_schema_registry;
   }
 
+  /// Register hooks transforming Record API requests and responses, see [RecordHooks].
+  pub fn register_record_hooks(&self, hooks: Arc<dyn RecordHooks>) {
+    let mut lock = self.state.record_hooks.write();
+    let mut all = (**lock).clone();
+    all.push(hooks);
+    *lock = Arc::new(all);
+  }
+
+  pub(crate) fn record_hooks(&self) -> Arc<Vec<Arc<dyn RecordHooks>>> {
+    return self.state.record_hooks.read().clone();
+  }
+
+  /// Register an asynchronous hook for the given Record API and operation, see [AsyncRecordHook].
+  pub(crate) fn register_async_record_hook(
+    &self,
+    api_name: &str,
+    operation: RecordHookOperation,
+    hook: Arc<dyn AsyncRecordHook>,
+  ) {
+    let mut lock = self.state.async_record_hooks.write();
+    let mut all = (**lock).clone();
+    all
+      .entry((api_name.to_string(), operation))
+      .or_default()
+      .push(hook);
+    *lock = Arc::new(all);
+  }
+
+  pub(crate) fn async_record_hooks(
+    &self,
+    api_name: &str,
+    operation: RecordHookOperation,
+  ) -> Option<Vec<Arc<dyn AsyncRecordHook>>> {
+    return self
+      .state
+      .async_record_hooks
+      .read()
+      .get(&(api_name.to_string(), operation))
+      .cloned();
+  }
+
+  /// Register a custom provider for wrapping the data keys of encrypted files, e.g. backed by an
+  /// external KMS, see [FileKeyProvider]. Takes precedence over the configured master key.
+  pub fn register_file_key_provider(&self, provider: Arc<dyn FileKeyProvider>) {
+    *self.state.custom_file_key_provider.write() = Some(provider);
+  }
+
+  pub(crate) fn file_key_provider(&self) -> Option<Arc<dyn FileKeyProvider>> {
+    if let Some(ref provider) = *self.state.custom_file_key_provider.read() {
+      return Some(provider.clone());
+    }
+    return self.state.file_key_provider.value();
+  }
+
+  /// Register a scanner for uploaded files, e.g. to reject malware, see [UploadScanner].
+  pub fn register_upload_scanner(&self, scanner: Arc<dyn UploadScanner>) {
+    let mut lock = self.state.custom_upload_scanners.write();
+    let mut all = (**lock).clone();
+    all.push(scanner);
+    *lock = Arc::new(all);
+  }
+
+  /// Durable queue of background tasks. Handlers for custom queues can be registered using
+  /// [TaskQueue::register_handler].
+  pub fn task_queue(&self) -> Arc<TaskQueue> {
+    return self.state.task_queue.clone();
+  }
+
+  pub(crate) fn upload_scanners(&self) -> Arc<Vec<Arc<dyn UploadScanner>>> {
+    let custom = self.state.custom_upload_scanners.read().clone();
+    return match self.state.upload_scanner.value() {
+      Some(scanner) => Arc::new(
+        std::iter::once(scanner)
+          .chain(custom.iter().cloned())
+          .collect(),
+      ),
+      None => custom,
+    };
+  }
+
+  /// Log sinks configured in the config, e.g. syslog or Loki.
+  pub(crate) fn log_sinks(&self) -> Arc<Vec<Arc<dyn LogSink>>> {
+    return self.state.log_sinks.value();
+  }
+
+  pub(crate) fn set_logs_flusher(&self, flusher: LogsFlusher) {
+    *self.state.logs_flusher.write() = Some(flusher);
+  }
+
+  /// Quiesces background work before exiting, i.e. after in-flight requests have completed:
+  /// stops jobs at safe points, flushes buffered logs and checkpoints the WAL, so that neither
+  /// logs nor job runs are lost or cut short. Jobs still running after `timeout` are aborted.
+  pub async fn shutdown(&self, timeout: std::time::Duration) {
+    self.jobs().shutdown(timeout).await;
+
+    let flusher = self.state.logs_flusher.read().clone();
+    if let Some(flusher) = flusher
+      && tokio::time::timeout(timeout, flusher.flush())
+        .await
+        .is_err()
+    {
+      warn!("Timed out flushing logs");
+    }
+
+    for (name, conn) in [
+      ("main", self.conn()),
+      ("logs", self.logs_conn()),
+      ("session", self.session_conn()),
+    ] {
+      if !matches!(conn.connection_type(), ConnectionType::Sqlite) {
+        continue;
+      }
+      if let Err(err) = conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)").await {
+        warn!("Failed to checkpoint {name} DB: {err}");
+      }
+    }
+  }
+
+  pub(crate) fn file_encryption_enabled(&self) -> bool {
+    return self.access_config(|c| {
+      c.server
+        .file_encryption
+        .as_ref()
+        .and_then(|e| e.enabled)
+        .unwrap_or(false)
+    });
+  }
+
   #[cfg(test)]
   pub fn conn(&self) -> &trailbase_sqlite::Connection {
     return &self.state.conn;
   }
 
+  /// Connection for read-only queries against the main database. Is a read replica, if
+  /// configured.
+  pub fn read_conn(&self) -> Arc<trailbase_sqlite::Connection> {
+    return self.state.connection_manager.main_reader();
+  }
+
   pub fn user_conn(&self) -> &trailbase_sqlite::Connection {
     return &self.state.conn;
   }
@@ -305,8 +544,8 @@ impl AppState {
     return Ok(());
   }
 
-  pub(crate) fn objectstore(&self) -> &Arc<dyn ObjectStore> {
-    return &self.state.object_store;
+  pub(crate) fn objectstore(&self) -> Arc<dyn ObjectStore> {
+    return self.state.object_store.value();
   }
 
   pub(crate) fn jobs(&self) -> Arc<JobRegistry> {
@@ -317,22 +556,103 @@ impl AppState {
     return self.state.auth.value();
   }
 
+  pub(crate) fn auth_rate_limiters(&self) -> Arc<HashMap<String, RateLimiter>> {
+    return self.state.auth_rate_limiters.value();
+  }
+
   pub fn site_url(&self) -> Arc<Option<url::Url>> {
     return self.state.site_url.value();
   }
 
-  pub(crate) fn mailer(&self) -> Mailer {
+  pub(crate) fn mailer(&self) -> Arc<dyn Mailer> {
     return self.state.mailer.value();
   }
 
+  pub(crate) fn push_providers(&self) -> Arc<PushProviders> {
+    return self.state.push_providers.value();
+  }
+
+  pub(crate) fn email_queue(&self) -> Arc<EmailQueue> {
+    return self.state.email_queue.clone();
+  }
+
   pub(crate) fn jwt(&self) -> &JwtHelper {
     return &self.state.jwt;
   }
 
+  /// Looks up a record API by name. Tenant-scoped APIs are only available in the scope of a
+  /// request routed to a tenant and serve from that tenant's database.
   pub fn lookup_record_api(&self, name: &str) -> Option<RecordApi> {
+    let api = self.state.record_apis.snapshot().get(name).cloned()?;
+    if !api.tenant_scoped() {
+      return Some(api);
+    }
+
+    let tenant = crate::tenants::current_tenant()?;
+    return self.tenant_record_api(&tenant, api);
+  }
+
+  /// Looks up a record API by name including tenant-scoped APIs outside of a tenant's scope, which
+  /// are backed by the schema-only tenant template. Useful for admin APIs.
+  pub(crate) fn lookup_base_record_api(&self, name: &str) -> Option<RecordApi> {
     return self.state.record_apis.snapshot().get(name).cloned();
   }
 
+  fn tenant_record_api(&self, tenant: &Tenant, base: RecordApi) -> Option<RecordApi> {
+    let key = (tenant.name.clone(), base.api_name().to_string());
+
+    // Re-use the cached instance unless either the base API or the tenant's schema changed.
+    if let Some((cached_base, api)) = self.state.tenant_record_apis.get(&key)
+      && cached_base.ptr_eq(&base)
+      && Arc::ptr_eq(api.connection_metadata(), &tenant.entry.metadata)
+    {
+      return Some(api);
+    }
+
+    let config = self
+      .get_config()
+      .record_apis
+      .iter()
+      .find(|c| c.name() == base.api_name())?
+      .clone();
+
+    let api = match RecordApi::build(
+      tenant.entry.connection.clone(),
+      tenant.entry.connection.clone(),
+      tenant.entry.metadata.clone(),
+      config,
+    ) {
+      Ok(api) => api,
+      Err(err) => {
+        warn!(
+          "Failed to build record API {} for tenant {}: {err}",
+          base.api_name(),
+          tenant.name
+        );
+        return None;
+      }
+    };
+
+    self
+      .state
+      .tenant_record_apis
+      .insert(key, (base, api.clone()));
+    return Some(api);
+  }
+
+  /// Drop all cached tenant-scoped record API instances of the given tenant.
+  pub(crate) fn invalidate_tenant_record_apis(&self, tenant: &str) {
+    let keys: Vec<_> = self
+      .state
+      .tenant_record_apis
+      .iter()
+      .filter_map(|(key, _)| (key.0 == tenant).then_some(key))
+      .collect();
+    for key in keys {
+      self.state.tenant_record_apis.remove(&key);
+    }
+  }
+
   pub fn get_config(&self) -> Arc<Config> {
     return self.state.config.ptr();
   }
@@ -348,14 +668,28 @@ impl AppState {
     &self,
     config: Config,
     hash: Option<String>,
+  ) -> Result<(), ConfigError> {
+    return self.validate_and_update_config_as(config, hash, None).await;
+  }
+
+  /// Like [Self::validate_and_update_config] but records the given admin user as the author of
+  /// the change in the config history.
+  pub(crate) async fn validate_and_update_config_as(
+    &self,
+    config: Config,
+    hash: Option<String>,
+    user: Option<uuid::Uuid>,
   ) -> Result<(), ConfigError> {
     let connection_manager = self.connection_manager();
     validate_config(&connection_manager, &config).await?;
 
+    let old_config = self.get_config();
+    let mut parent_hash: Option<String> = None;
     match hash {
       Some(hash) => {
         let mut error: Option<ConfigError> = None;
         let err = &mut error;
+        let parent = &mut parent_hash;
         self.state.config.update(move |old| {
           if hash_config(old) != hash {
             let _ = err.insert(ConfigError::Update(
@@ -365,6 +699,7 @@ impl AppState {
             return old.clone();
           }
 
+          let _ = parent.insert(hash);
           return config;
         });
 
@@ -373,7 +708,10 @@ impl AppState {
         }
       }
       None => {
-        self.state.config.update(|_old| config);
+        self.state.config.update(|old| {
+          parent_hash = Some(hash_config(old));
+          return config;
+        });
       }
     };
 
@@ -391,6 +729,23 @@ impl AppState {
     // Write new config to the file system.
     write_config_and_vault_textproto(self.data_dir(), &connection_manager, &new_config).await?;
 
+    if let Err(err) =
+      record_config_history(self.conn(), &new_config, parent_hash.as_deref(), user).await
+    {
+      warn!("Failed to record config history: {err}");
+    }
+
+    if hash_config(&old_config) != hash_config(&new_config) {
+      record_audit_event(
+        self,
+        user,
+        AuditAction::ConfigChanged,
+        "config",
+        config_diff(&old_config, &new_config).ok(),
+      )
+      .await;
+    }
+
     // After updating the config we need to poll record apis to make sure they're up-to-date.
     let _wait_for_snapshot_update = self.state.record_apis.ptr().await;
 
@@ -422,8 +777,11 @@ impl AppState {
         continue;
       };
 
-      // Swap out old with new WASM runtime for the given component.
-      *old_rt.write().await = new_runtimes.remove(index);
+      // Swap out old with new WASM runtime for the given component, retaining the kill switch.
+      let mut old_rt = old_rt.write().await;
+      let new_rt = new_runtimes.remove(index);
+      new_rt.set_enabled(old_rt.enabled());
+      *old_rt = new_rt;
     }
 
     for new_rt in new_runtimes {
@@ -438,6 +796,18 @@ impl AppState {
 }
 
 /// Returns true if schemas were registered.
+fn install_sql_encryption_key(key: Option<&str>) {
+  // NOTE: Invalid keys are rejected by config validation.
+  let key = key.and_then(|key| match EncryptionKey::from_base64(key) {
+    Ok(key) => Some(key),
+    Err(err) => {
+      error!("Invalid SQL encryption key: {err}");
+      None
+    }
+  });
+  trailbase_extension::crypto::set_encryption_key(key);
+}
+
 pub(crate) fn update_json_schema_registry(
   config: &[JsonSchemaConfig],
   registry: &parking_lot::RwLock<JsonSchemaRegistry>,
@@ -475,9 +845,64 @@ pub(crate) fn update_json_schema_registry(
     return Ok(true);
   }
 
+  // Drop previously registered custom schemas, e.g. after the last one was removed.
+  if registry
+    .read()
+    .entries()
+    .iter()
+    .any(|(_, schema)| !schema.builtin)
+  {
+    registry.write().swap(
+      trailbase_schema::registry::build_json_schema_registry(vec![]).map_err(|err| {
+        return ConfigError::Update(format!("Update of JSON schema registry failed: {err}"));
+      })?,
+    );
+    return Ok(true);
+  }
+
   return Ok(false);
 }
 
+/// Line-based diff of the text-proto encoded configs with secrets redacted.
+fn config_diff(old: &Config, new: &Config) -> Result<serde_json::Value, ConfigError> {
+  let (old, _) = redact_secrets(old)?;
+  let (new, _) = redact_secrets(new)?;
+  return Ok(diff_lines(&old.to_text()?, &new.to_text()?));
+}
+
+/// Appends the config to the config history unless unchanged.
+async fn record_config_history(
+  conn: &trailbase_sqlite::Connection,
+  config: &Config,
+  parent_hash: Option<&str>,
+  user: Option<uuid::Uuid>,
+) -> Result<(), ConfigError> {
+  const QUERY: &str = formatcp!(
+    "INSERT INTO '{CONFIG_HISTORY_TABLE}' (hash, parent_hash, user, config) VALUES ($1, $2, $3, $4)"
+  );
+
+  let hash = hash_config(config);
+  if parent_hash == Some(hash.as_str()) {
+    return Ok(());
+  }
+
+  let (stripped, _secrets) = redact_secrets(config)?;
+  conn
+    .execute(
+      QUERY,
+      trailbase_sqlite::params!(
+        hash,
+        parent_hash.map(|h| h.to_string()),
+        user.map(|u| u.into_bytes().to_vec()),
+        stripped.to_text()?,
+      ),
+    )
+    .await
+    .map_err(|err| ConfigError::Update(err.to_string()))?;
+
+  return Ok(());
+}
+
 async fn build_record_apis(
   connection_manager: ConnectionManager,
   record_api_configs: Reactive<Vec<RecordApiConfig>>,
@@ -494,47 +919,73 @@ async fn build_record_apis_impl(
   prev: Option<Arc<HashMap<String, RecordApi>>>,
   record_api_configs: Arc<Vec<RecordApiConfig>>,
 ) -> HashMap<String, RecordApi> {
+  let main_conn = connection_manager.main_entry().connection;
+
   // Re-use existing connection when possible to keep subscriptions alive.
   //
   // WARN: We need to be very careful to how we rebuild RecordAPIs, since long-lived
   // subscriptions may be tied to specific connections. So we need to keep connection alive
   // whenever possible, e.g. an ACL changing for one API isn't a good reason to drop
   // subscriptions on all APIs.
-  let get_conn =
-    async move |api_name: &str, attached_databases: &[String]| -> Result<_, ConnectionError> {
-      let ConnectionEntry {
-        connection: conn,
-        metadata,
-      } = if attached_databases.is_empty() {
-        connection_manager.main_entry()
-      } else {
-        connection_manager
-          .get_entry(BuildOptions {
-            is_main: true,
-            attached_databases: Some(attached_databases.iter().cloned().collect()),
-            ..Default::default()
-          })
-          .await?
-      };
+  let get_conn = async move |api_name: &str,
+                             attached_databases: &[String],
+                             tenant_scoped: bool|
+              -> Result<_, ConnectionError> {
+    // Tenant-scoped APIs are built against the schema-only template and instantiated per tenant
+    // on lookup.
+    let ConnectionEntry {
+      connection: conn,
+      metadata,
+    } = if tenant_scoped {
+      connection_manager.tenant_template_entry().await?
+    } else if attached_databases.is_empty() {
+      connection_manager.main_entry()
+    } else {
+      connection_manager
+        .get_entry(BuildOptions {
+          is_main: true,
+          attached_databases: Some(attached_databases.iter().cloned().collect()),
+          ..Default::default()
+        })
+        .await?
+    };
 
-      if let Some((_, candidate)) =
-        prev
-          .as_ref()
-          .and_then(|prev: &Arc<HashMap<String, RecordApi>>| {
-            return prev.iter().find(|(_name, api)| api.api_name() == api_name);
-          })
-        && candidate.attached_databases() == attached_databases
-      {
-        // NOTE: We must use latest metadata to work recorrectly on schema changes.
-        return Ok((candidate.conn().clone(), metadata));
-      };
+    if let Some((_, candidate)) =
+      prev
+        .as_ref()
+        .and_then(|prev: &Arc<HashMap<String, RecordApi>>| {
+          return prev.iter().find(|(_name, api)| api.api_name() == api_name);
+        })
+      && candidate.attached_databases() == attached_databases
+      && candidate.tenant_scoped() == tenant_scoped
+    {
+      // NOTE: We must use latest metadata to work recorrectly on schema changes.
+      return Ok((
+        candidate.conn().clone(),
+        candidate.read_conn().clone(),
+        metadata,
+      ));
+    };
 
-      return Ok((conn, metadata));
+    // Read replicas only cover the main database.
+    let read_conn = if attached_databases.is_empty() && !tenant_scoped {
+      connection_manager.main_reader()
+    } else {
+      conn.clone()
     };
 
+    return Ok((conn, read_conn, metadata));
+  };
+
   let mut next: HashMap<String, RecordApi> = HashMap::new();
   for config in record_api_configs.iter() {
-    let (conn, metadata) = match get_conn(config.name(), &config.attached_databases).await {
+    let (conn, read_conn, metadata) = match get_conn(
+      config.name(),
+      &config.databases_to_attach(),
+      config.tenant_scoped.unwrap_or(false),
+    )
+    .await
+    {
       Ok(x) => x,
       Err(err) => {
         log::error!("Failed to get conn for record API {}: {err}", config.name());
@@ -542,7 +993,7 @@ async fn build_record_apis_impl(
       }
     };
 
-    match RecordApi::build(conn, metadata, config.clone()) {
+    match RecordApi::build(conn, read_conn, metadata, config.clone()) {
       Ok(api) => {
         next.insert(api.api_name().to_string(), api);
       }
@@ -552,33 +1003,56 @@ async fn build_record_apis_impl(
     };
   }
 
+  // NOTE: Triggers are (re-)installed on config and schema changes, e.g. after a table was
+  // re-created by an ALTER TABLE.
+  if let Err(err) =
+    crate::records::changes::update_change_tracking_triggers(&main_conn, &next).await
+  {
+    log::error!("Failed to update change tracking triggers: {err}");
+  }
+
   return next;
 }
 
+/// Effective object store configuration, taking the deprecated `s3_storage_config` into account.
+pub(crate) fn objectstore_config(config: &ServerConfig) -> Option<ObjectStoreConfig> {
+  if let Some(ref object_store) = config.object_store {
+    return Some(object_store.clone());
+  }
+
+  return config
+    .s3_storage_config
+    .as_ref()
+    .map(|s3| ObjectStoreConfig {
+      s3: Some(s3.clone()),
+      ..Default::default()
+    });
+}
+
 pub(crate) fn build_objectstore(
   data_dir: &DataDir,
-  config: Option<&S3StorageConfig>,
+  config: Option<&ObjectStoreConfig>,
 ) -> Result<Box<dyn ObjectStore>, object_store::Error> {
-  if let Some(config) = config {
+  let allow_http = |endpoint: &str| {
+    return object_store::ClientOptions::default().with_allow_http(endpoint.starts_with("http://"));
+  };
+
+  if let Some(config) = config.and_then(|c| c.s3.as_ref()) {
     let mut builder = object_store::aws::AmazonS3Builder::from_env();
 
     if let Some(ref endpoint) = config.endpoint {
-      builder = builder.with_endpoint(endpoint);
-
-      if endpoint.starts_with("http://") {
-        builder =
-          builder.with_client_options(object_store::ClientOptions::default().with_allow_http(true))
-      }
+      builder = builder
+        .with_endpoint(endpoint)
+        .with_client_options(allow_http(endpoint));
     }
 
     if let Some(ref region) = config.region {
       builder = builder.with_region(region);
     }
 
-    let Some(ref bucket_name) = config.bucket_name else {
-      panic!("S3StorageConfig missing 'bucket_name'.");
-    };
-    builder = builder.with_bucket_name(bucket_name);
+    if let Some(ref bucket_name) = config.bucket_name {
+      builder = builder.with_bucket_name(bucket_name);
+    }
 
     if let Some(ref access_key) = config.access_key {
       builder = builder.with_access_key_id(access_key);
@@ -591,8 +1065,65 @@ pub(crate) fn build_objectstore(
     return Ok(Box::new(builder.build()?));
   }
 
+  if let Some(config) = config.and_then(|c| c.gcs.as_ref()) {
+    let mut builder = object_store::gcp::GoogleCloudStorageBuilder::from_env();
+
+    if let Some(ref bucket_name) = config.bucket_name {
+      builder = builder.with_bucket_name(bucket_name);
+    }
+
+    if let Some(ref service_account_path) = config.service_account_path {
+      builder = builder.with_service_account_path(service_account_path);
+    }
+
+    if let Some(ref service_account_key) = config.service_account_key {
+      builder = builder.with_service_account_key(service_account_key);
+    }
+
+    return Ok(Box::new(builder.build()?));
+  }
+
+  if let Some(config) = config.and_then(|c| c.azure.as_ref()) {
+    let mut builder = object_store::azure::MicrosoftAzureBuilder::from_env();
+
+    if let Some(ref account) = config.account {
+      builder = builder.with_account(account);
+    }
+
+    if let Some(ref container_name) = config.container_name {
+      builder = builder.with_container_name(container_name);
+    }
+
+    if let Some(ref access_key) = config.access_key {
+      builder = builder.with_access_key(access_key);
+    }
+
+    if let Some(ref endpoint) = config.endpoint {
+      builder = builder
+        .with_endpoint(endpoint.clone())
+        .with_client_options(allow_http(endpoint));
+    }
+
+    return Ok(Box::new(builder.build()?));
+  }
+
+  let path = match config
+    .and_then(|c| c.local.as_ref())
+    .and_then(|l| l.path.as_ref())
+  {
+    Some(path) => {
+      let path = data_dir.root().join(path);
+      std::fs::create_dir_all(&path).map_err(|err| object_store::Error::Generic {
+        store: "LocalFileSystem",
+        source: err.into(),
+      })?;
+      path
+    }
+    None => data_dir.uploads_path(),
+  };
+
   return Ok(Box::new(
-    object_store::local::LocalFileSystem::new_with_prefix(data_dir.uploads_path())?,
+    object_store::local::LocalFileSystem::new_with_prefix(path)?,
   ));
 }
 
@@ -604,6 +1135,19 @@ fn build_site_url(c: &Config) -> Result<Option<url::Url>, url::ParseError> {
   return Ok(None);
 }
 
+fn build_auth_rate_limiters(config: &Config) -> Arc<HashMap<String, RateLimiter>> {
+  return Arc::new(
+    config
+      .auth
+      .endpoint_rate_limits
+      .iter()
+      .filter_map(|(endpoint, rate_limit)| {
+        return Some((endpoint.clone(), RateLimiter::new(rate_limit)?));
+      })
+      .collect(),
+  );
+}
+
 fn build_auth_config(config: &Config) -> AuthConfig {
   let oauth_providers: Vec<_> = config
     .auth
@@ -707,7 +1251,7 @@ mod test_utils {
   pub struct TestStateOptions {
     pub config: Option<Config>,
     pub json_schema_registry: Option<JsonSchemaRegistry>,
-    pub(crate) mailer: Option<Mailer>,
+    pub(crate) mailer: Option<Arc<dyn Mailer>>,
   }
 
   pub async fn test_state(options: Option<TestStateOptions>) -> anyhow::Result<AppState> {
@@ -802,7 +1346,7 @@ mod test_utils {
     let config = config.unwrap_or_else(test_config);
     update_json_schema_registry(&config.schemas, &json_schema_registry).unwrap();
 
-    let logs_conn = crate::connection::init_logs_db(None)?;
+    let logs_conn = crate::connection::init_logs_db(None, &[])?;
     let session_conn = crate::connection::init_session_db(None)?;
 
     let connection_manager = ConnectionManager::new_for_test(
@@ -813,24 +1357,28 @@ mod test_utils {
     )
     .await;
 
-    let object_store = if std::env::var("TEST_S3_OBJECT_STORE").map_or(false, |v| v == "TRUE") {
-      info!("Use S3 Storage for tests");
-
-      build_objectstore(
-        &data_dir,
-        Some(&S3StorageConfig {
-          endpoint: Some("http://127.0.0.1:9000".to_string()),
-          region: None,
-          bucket_name: Some("test".to_string()),
-          access_key: Some("minioadmin".to_string()),
-          secret_access_key: Some("minioadmin".to_string()),
-        }),
-      )
-      .unwrap()
-      .into()
-    } else {
-      build_objectstore(&data_dir, None).unwrap().into()
-    };
+    let object_store: Arc<dyn ObjectStore> =
+      if std::env::var("TEST_S3_OBJECT_STORE").map_or(false, |v| v == "TRUE") {
+        info!("Use S3 Storage for tests");
+
+        build_objectstore(
+          &data_dir,
+          Some(&ObjectStoreConfig {
+            s3: Some(S3StorageConfig {
+              endpoint: Some("http://127.0.0.1:9000".to_string()),
+              region: None,
+              bucket_name: Some("test".to_string()),
+              access_key: Some("minioadmin".to_string()),
+              secret_access_key: Some("minioadmin".to_string()),
+            }),
+            ..Default::default()
+          }),
+        )
+        .unwrap()
+        .into()
+      } else {
+        build_objectstore(&data_dir, None).unwrap().into()
+      };
 
     let config = Reactive::new(config);
 
@@ -840,6 +1388,13 @@ mod test_utils {
     )
     .await;
 
+    let log_sinks = {
+      let data_dir = data_dir.clone();
+      config
+        .derive(|c| c.server.log_sinks.clone())
+        .derive_unchecked(move |c| build_log_sinks(&data_dir, c))
+    };
+
     return Ok(AppState {
       state: Arc::new(InternalState {
         data_dir,
@@ -850,21 +1405,44 @@ mod test_utils {
         dev: true,
         demo: false,
         auth: config.derive_unchecked(|c| Arc::new(AuthOptions::from_config(c.auth.clone()))),
-        jobs: config.derive_unchecked(|_c| Arc::new(JobRegistry::new())),
+        auth_rate_limiters: config.derive_unchecked(build_auth_rate_limiters),
+        jobs: {
+          let conn = (*connection_manager.main_entry().connection).clone();
+          config.derive_unchecked(move |_c| Arc::new(JobRegistry::with_history(conn.clone())))
+        },
         mailer: mailer.map_or_else(
-          || config.derive_unchecked(Mailer::new_from_config),
+          || config.derive_unchecked(build_mailer),
           |m| Reactive::new(m),
         ),
+        push_providers: config
+          .derive_unchecked(|c| Arc::new(build_push_providers(c.push.as_ref()))),
         config,
         json_schema_registry,
+        record_hooks: Default::default(),
+        async_record_hooks: Default::default(),
+        file_key_provider: config
+          .derive_unchecked(|c| build_file_key_provider(c.server.file_encryption.as_ref())),
+        custom_file_key_provider: Default::default(),
+        upload_scanner: config
+          .derive_unchecked(|c| build_upload_scanner(c.server.upload_scan.as_ref())),
+        custom_upload_scanners: Default::default(),
+        log_sinks,
+        logs_flusher: Default::default(),
+        task_queue: Arc::new(TaskQueue::new(
+          (*connection_manager.main_entry().connection).clone(),
+        )),
+        email_queue: Arc::new(EmailQueue::new(
+          (*connection_manager.main_entry().connection).clone(),
+        )),
         conn: (*connection_manager.main_entry().connection).clone(),
         session_conn,
         logs_conn,
         connection_manager,
         jwt: crate::auth::jwt::test_jwt_helper(),
         record_apis: record_apis.clone(),
+        tenant_record_apis: quick_cache::sync::Cache::new(256),
         subscription_manager: SubscriptionManager::new(record_apis),
-        object_store,
+        object_store: Reactive::new(object_store),
         wasm_runtimes: vec![],
         wasm_runtimes_builder: Box::new(|| Ok(vec![])),
         pg_uri,
@@ -0,0 +1,171 @@
+//! Audit log of semantic admin and auth actions, e.g. altered tables or deleted users.
+//!
+//! Unlike the request logs, see `logging.rs`, audit events describe *what* changed and are kept
+//! in the append-only `_audit_log` table of the main database.
+
+use const_format::formatcp;
+use log::*;
+use serde_json::{Map, Value};
+use trailbase_schema::QualifiedName;
+use trailbase_sqlite::params;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::constants::AUDIT_LOG_TABLE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AuditAction {
+  TableCreated,
+  TableAltered,
+  TableDropped,
+  ConfigChanged,
+  UserUpdated,
+  UserDeleted,
+  /// An admin set a new password for a user.
+  PasswordResetForced,
+  /// A user reset their password, e.g. using a reset email.
+  PasswordReset,
+  /// An admin edited or reset an email template.
+  EmailTemplateChanged,
+  /// An admin sent a push notification.
+  NotificationSent,
+}
+
+impl AuditAction {
+  pub(crate) fn name(self) -> &'static str {
+    return match self {
+      Self::TableCreated => "table.created",
+      Self::TableAltered => "table.altered",
+      Self::TableDropped => "table.dropped",
+      Self::ConfigChanged => "config.changed",
+      Self::UserUpdated => "user.updated",
+      Self::UserDeleted => "user.deleted",
+      Self::PasswordResetForced => "user.password_reset_forced",
+      Self::PasswordReset => "user.password_reset",
+      Self::EmailTemplateChanged => "email_template.changed",
+      Self::NotificationSent => "notification.sent",
+    };
+  }
+}
+
+/// Appends an event to the audit log.
+///
+/// Failures are logged rather than returned, since the audited action already happened.
+pub(crate) async fn record_audit_event(
+  state: &AppState,
+  actor: Option<Uuid>,
+  action: AuditAction,
+  target: &str,
+  payload: Option<Value>,
+) {
+  const QUERY: &str = formatcp!(
+    "INSERT INTO '{AUDIT_LOG_TABLE}' (actor, action, target, payload) VALUES ($1, $2, $3, $4)"
+  );
+
+  if let Err(err) = state
+    .conn()
+    .execute(
+      QUERY,
+      params!(
+        actor.map(|id| id.into_bytes().to_vec()),
+        action.name(),
+        target.to_string(),
+        payload.map(|p| p.to_string()),
+      ),
+    )
+    .await
+  {
+    warn!("Failed to record '{}' audit event: {err}", action.name());
+  }
+}
+
+/// Unquoted audit target for tables and views, e.g. "movies" or "other_db.movies".
+pub(crate) fn table_target(name: &QualifiedName) -> String {
+  return match name.database_schema {
+    Some(ref db) => format!("{db}.{}", name.name),
+    None => name.name.clone(),
+  };
+}
+
+/// Builds a diff of two JSON values, where every changed leaf maps to its `old` and `new` value.
+/// Arrays are compared as a whole.
+pub(crate) fn diff_json(old: &Value, new: &Value) -> Value {
+  let (Value::Object(old), Value::Object(new)) = (old, new) else {
+    return serde_json::json!({ "old": old, "new": new });
+  };
+
+  let mut diff = Map::new();
+  for (key, old_value) in old {
+    match new.get(key) {
+      Some(new_value) if new_value == old_value => {}
+      Some(new_value) => {
+        diff.insert(key.clone(), diff_json(old_value, new_value));
+      }
+      None => {
+        diff.insert(key.clone(), diff_json(old_value, &Value::Null));
+      }
+    }
+  }
+  for (key, new_value) in new {
+    if !old.contains_key(key) {
+      diff.insert(key.clone(), diff_json(&Value::Null, new_value));
+    }
+  }
+  return Value::Object(diff);
+}
+
+/// Builds a line-based diff of two texts, e.g. text-proto encoded configs.
+pub(crate) fn diff_lines(old: &str, new: &str) -> Value {
+  let old_lines: Vec<&str> = old.lines().collect();
+  let new_lines: Vec<&str> = new.lines().collect();
+
+  return serde_json::json!({
+    "removed": old_lines.iter().filter(|l| !new_lines.contains(l)).collect::<Vec<_>>(),
+    "added": new_lines.iter().filter(|l| !old_lines.contains(l)).collect::<Vec<_>>(),
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_diff_json() {
+    let old = serde_json::json!({
+      "name": "movies",
+      "strict": true,
+      "columns": ["id"],
+      "options": { "a": 1, "b": 2 },
+    });
+    let new = serde_json::json!({
+      "name": "films",
+      "strict": true,
+      "columns": ["id", "title"],
+      "options": { "a": 1, "c": 3 },
+    });
+
+    assert_eq!(
+      diff_json(&old, &new),
+      serde_json::json!({
+        "name": { "old": "movies", "new": "films" },
+        "columns": { "old": ["id"], "new": ["id", "title"] },
+        "options": {
+          "b": { "old": 2, "new": null },
+          "c": { "old": null, "new": 3 },
+        },
+      })
+    );
+    assert_eq!(diff_json(&old, &old), serde_json::json!({}));
+  }
+
+  #[test]
+  fn test_diff_lines() {
+    assert_eq!(
+      diff_lines("a: 1\nb: 2\n", "a: 1\nb: 3\nc: 4\n"),
+      serde_json::json!({
+        "removed": ["b: 2"],
+        "added": ["b: 3", "c: 4"],
+      })
+    );
+  }
+}
@@ -1,4 +1,5 @@
 use axum::extract::{Path, State};
+use axum::http::HeaderMap;
 use axum::response::Response;
 use const_format::formatcp;
 use std::sync::LazyLock;
@@ -12,8 +13,10 @@ use crate::config::proto::ConflictResolutionStrategy;
 use crate::constants::AVATAR_TABLE;
 use crate::extract::Either;
 use crate::records::RecordError;
+use crate::records::file_encryption::encrypt_files;
 use crate::records::params::{JsonRow, LazyParams};
 use crate::records::read_queries::run_get_file_query;
+use crate::records::scanner::{UploadScanError, scan_files};
 use crate::records::write_queries::run_insert_or_replace_query;
 use crate::util::uuid_to_b64;
 
@@ -26,6 +29,7 @@ use crate::util::uuid_to_b64;
 pub async fn get_avatar_handler(
   State(state): State<AppState>,
   Path(b64_user_id): Path<String>,
+  headers: HeaderMap,
 ) -> Result<Response, AuthError> {
   let Ok(user_id) = crate::util::b64_to_uuid(&b64_user_id) else {
     return Err(AuthError::BadRequest("Invalid user id"));
@@ -45,7 +49,7 @@ pub async fn get_avatar_handler(
     _ => AuthError::Internal(err.into()),
   })?;
 
-  return crate::records::files::read_file_into_response(&state, file_upload)
+  return crate::records::files::read_file_into_response(&state, file_upload, &headers)
     .await
     .map_err(|err| AuthError::Internal(err.into()));
 }
@@ -96,13 +100,20 @@ pub async fn create_avatar_handler(
     record,
     Some(files),
   );
-  let params = lazy_params
+  let mut params = lazy_params
     .consume()
     .map_err(|_| AuthError::BadRequest("parameter conversion"))?;
+  scan_files(&state, &params).await.map_err(|err| match err {
+    UploadScanError::Rejected { .. } => AuthError::BadRequest("File rejected"),
+    err => AuthError::Internal(err.into()),
+  })?;
+  encrypt_files(&state, &mut params)
+    .await
+    .map_err(|err| AuthError::Internal(err.into()))?;
 
   let _user_id_value = run_insert_or_replace_query(
     conn,
-    state.objectstore(),
+    &state.objectstore(),
     &trailbase_schema::QualifiedNameEscaped::new(&AVATAR_TABLE_NAME),
     &AVATAR_TABLE_METADATA.column_metadata,
     ConflictResolutionStrategy::Replace,
@@ -269,9 +280,13 @@ mod tests {
   }
 
   async fn download_avatar(state: &AppState, record_id: &[u8; 16]) -> Response {
-    return get_avatar_handler(State(state.clone()), Path(id_to_b64(record_id)))
-      .await
-      .unwrap();
+    return get_avatar_handler(
+      State(state.clone()),
+      Path(id_to_b64(record_id)),
+      HeaderMap::new(),
+    )
+    .await
+    .unwrap();
   }
 
   #[tokio::test]
@@ -295,10 +310,13 @@ mod tests {
       .unwrap()
       .unwrap();
 
-    let missing_profile_response =
-      get_avatar_handler(State(state.clone()), Path(id_to_b64(&db_user.id)))
-        .await
-        .err();
+    let missing_profile_response = get_avatar_handler(
+      State(state.clone()),
+      Path(id_to_b64(&db_user.id)),
+      HeaderMap::new(),
+    )
+    .await
+    .err();
     assert!(
       matches!(missing_profile_response, Some(AuthError::NotFound)),
       "{missing_profile_response:?}"
@@ -345,9 +363,13 @@ mod tests {
 
     assert!(non_img_result.is_err(), "{rows:?}");
 
-    let response = get_avatar_handler(State(state.clone()), Path(id_to_b64(&db_user.id)))
-      .await
-      .unwrap();
+    let response = get_avatar_handler(
+      State(state.clone()),
+      Path(id_to_b64(&db_user.id)),
+      HeaderMap::new(),
+    )
+    .await
+    .unwrap();
     assert_eq!(
       axum::body::to_bytes(response.into_body(), usize::MAX)
         .await
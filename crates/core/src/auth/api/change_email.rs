@@ -6,18 +6,20 @@ use axum::{
 use const_format::formatcp;
 use serde::Deserialize;
 use trailbase_sqlite::params;
+use trailbase_sqlite::traits::{SyncConnection, SyncTransaction};
 use ts_rs::TS;
 use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 
 use crate::app_state::AppState;
 use crate::auth::jwt::EmailChangeTokenClaims;
 use crate::auth::util::{user_by_id, validate_and_normalize_email_address, validate_redirect};
 use crate::auth::{AuthError, User};
 use crate::config::proto::UserIdentifier;
-use crate::constants::USER_TABLE;
+use crate::constants::{EMAIL_CHANGE_TABLE, USER_TABLE};
 use crate::email::Email;
 use crate::extract::Either;
-use crate::util::urlencode;
+use crate::util::{b64_to_uuid, urlencode};
 
 #[derive(Debug, Default, Deserialize, IntoParams, ToSchema, TS)]
 pub struct ChangeEmailParams {
@@ -130,7 +132,7 @@ pub async fn change_email_request_handler(
     return Err(AuthError::Forbidden);
   };
 
-  let old_email = db_user.email.as_deref().unwrap_or_default();
+  let old_email = db_user.email.as_deref().filter(|e| !e.is_empty());
 
   // NOTE: Require `old_email` in form-mode. This is pretty arbitrary, we could do away with this
   // entirely :shrug:.
@@ -146,7 +148,7 @@ pub async fn change_email_request_handler(
       return Err(AuthError::BadRequest(MSG));
     };
 
-    if validate_and_normalize_email_address(old_email_req)? != old_email {
+    if Some(validate_and_normalize_email_address(old_email_req)?.as_str()) != old_email {
       const MSG: &str = "`old_email` does not match";
       if let Some(ref redirect_uri) = err_redirect_uri.or(redirect_uri) {
         return Ok(
@@ -158,26 +160,73 @@ pub async fn change_email_request_handler(
     }
   }
 
-  let claims = EmailChangeTokenClaims::new(
-    &db_user.uuid(),
-    old_email.to_string(),
-    new_email.clone(),
-    chrono::Duration::hours(4),
+  // Track the pending change. Any previously pending change is replaced.
+  const PENDING_QUERY: &str = formatcp!(
+    "\
+      INSERT OR REPLACE INTO '{EMAIL_CHANGE_TABLE}' \
+        (user, old_email, new_email, old_email_confirmed, expires) \
+      VALUES ($1, $2, $3, $4, $5) \
+    "
   );
-  let token = state
-    .jwt()
-    .encode(&claims)
-    .map_err(|err| AuthError::Internal(err.into()))?;
+  let ttl = chrono::Duration::hours(4);
+  state
+    .user_conn()
+    .execute(
+      PENDING_QUERY,
+      params!(
+        db_user.id,
+        old_email.map(|e| e.to_string()),
+        new_email.clone(),
+        // Users without an address have nothing to confirm.
+        old_email.is_none(),
+        (chrono::Utc::now() + ttl).timestamp(),
+      ),
+    )
+    .await?;
+
+  let encode = |confirms_old_email: bool| {
+    let claims = EmailChangeTokenClaims::new(
+      &db_user.uuid(),
+      old_email.unwrap_or_default().to_string(),
+      new_email.clone(),
+      confirms_old_email,
+      ttl,
+    );
+    return state
+      .jwt()
+      .encode(&claims)
+      .map_err(|err| AuthError::Internal(err.into()));
+  };
 
   let email =
-    Email::change_email_address_email(&state, &new_email, &token, redirect_uri.as_deref())
+    Email::change_email_address_email(&state, &new_email, &encode(false)?, redirect_uri.as_deref())
+      .await
       .map_err(|err| AuthError::Internal(err.into()))?;
   email
     .send()
     .await
     .map_err(|err| AuthError::Internal(err.into()))?;
 
-  let msg = format!("Verification mail sent to {new_email}.");
+  if let Some(old_email) = old_email {
+    let email = Email::change_email_old_address_email(
+      &state,
+      old_email,
+      &new_email,
+      &encode(true)?,
+      redirect_uri.as_deref(),
+    )
+    .await
+    .map_err(|err| AuthError::Internal(err.into()))?;
+    email
+      .send()
+      .await
+      .map_err(|err| AuthError::Internal(err.into()))?;
+  }
+
+  let msg = match old_email {
+    Some(old_email) => format!("Verification mails sent to {old_email} and {new_email}."),
+    None => format!("Verification mail sent to {new_email}."),
+  };
   if !json && let Some(ref redirect_uri) = redirect_uri {
     return Ok(
       Redirect::to(&format!(
@@ -197,6 +246,8 @@ pub(crate) struct ChangeEmailConfigParams {
 }
 
 /// Confirm a change of email address.
+///
+/// Changes only take effect once confirmed via the links sent to both the old and the new address.
 #[utoipa::path(
   get,
   path = "/change_email/confirm/:email_verification_code",
@@ -219,26 +270,66 @@ pub async fn change_email_confirm_handler(
   let redirect_uri = validate_redirect(&state, query.redirect_uri)?;
   let claims = EmailChangeTokenClaims::decode(state.jwt(), &email_verification_token)
     .map_err(|_err| AuthError::BadRequest("Invalid token"))?;
+  let user_id = b64_to_uuid(&claims.sub).map_err(|_err| AuthError::BadRequest("Invalid token"))?;
+  let old_email = (!claims.old_email.is_empty()).then_some(claims.old_email);
 
-  const QUERY: &str = formatcp!(
+  const CONFIRM_OLD_QUERY: &str = formatcp!(
     "\
-      UPDATE \"{USER_TABLE}\" \
-      SET \
-        email = $1, \
-        verified = TRUE \
-      WHERE \
-        email = $2 \
+      UPDATE '{EMAIL_CHANGE_TABLE}' SET old_email_confirmed = TRUE \
+      WHERE user = $1 AND new_email = $2 AND old_email IS $3 AND expires > UNIXEPOCH() \
+      RETURNING old_email_confirmed AND new_email_confirmed \
+    "
+  );
+  const CONFIRM_NEW_QUERY: &str = formatcp!(
+    "\
+      UPDATE '{EMAIL_CHANGE_TABLE}' SET new_email_confirmed = TRUE \
+      WHERE user = $1 AND new_email = $2 AND old_email IS $3 AND expires > UNIXEPOCH() \
+      RETURNING old_email_confirmed AND new_email_confirmed \
     "
   );
 
-  let rows_affected = state
+  let confirms_old_email = claims.confirms_old_email;
+  let new_email = claims.new_email;
+  let outcome = state
     .user_conn()
-    .execute(QUERY, params!(claims.new_email, claims.old_email))
+    .transaction(move |mut tx| -> Result<_, trailbase_sqlite::Error> {
+      let Some(row) = tx.query_row(
+        if confirms_old_email {
+          CONFIRM_OLD_QUERY
+        } else {
+          CONFIRM_NEW_QUERY
+        },
+        params!(user_id.into_bytes(), new_email, old_email),
+      )?
+      else {
+        // Expired or superseded by a more recent request.
+        return Ok(None);
+      };
+
+      let complete = row
+        .get::<i64>(0)
+        .map_err(|err| trailbase_sqlite::Error::Other(err.into()))?
+        != 0;
+      if complete && !apply_pending_email_change(&mut tx, user_id)? {
+        return Ok(None);
+      }
+
+      tx.commit()?;
+      return Ok(Some(complete));
+    })
     .await?;
 
-  return match rows_affected {
-    0 => Err(AuthError::Conflict),
-    1 => {
+  return match outcome {
+    None => Err(AuthError::Conflict),
+    Some(false) => {
+      const MSG: &str = "Confirmed. Please also follow the link sent to your other address.";
+      if let Some(redirect) = redirect_uri {
+        Ok(Redirect::to(&format!("{redirect}?alert={msg}", msg = urlencode(MSG))).into_response())
+      } else {
+        Ok((StatusCode::OK, MSG).into_response())
+      }
+    }
+    Some(true) => {
       if let Some(redirect) = redirect_uri {
         Ok(Redirect::to(&redirect).into_response())
       } else if state.public_dir().is_some() {
@@ -247,6 +338,37 @@ pub async fn change_email_confirm_handler(
         Ok((StatusCode::OK, "email changed").into_response())
       }
     }
-    _ => panic!("emails updated for multiple users at once: {rows_affected}"),
   };
 }
+
+/// Applies the given user's pending email change, regardless of confirmations, and marks the new
+/// address as verified. Returns false if there's no pending change or the user's address changed
+/// in the meantime.
+///
+/// NOTE: Expected to run within a transaction.
+pub(crate) fn apply_pending_email_change(
+  tx: &mut impl SyncConnection,
+  user_id: Uuid,
+) -> Result<bool, trailbase_sqlite::Error> {
+  const UPDATE_QUERY: &str = formatcp!(
+    "\
+      UPDATE \"{USER_TABLE}\" \
+      SET \
+        email = pending.new_email, \
+        verified = TRUE \
+      FROM \
+        (SELECT old_email, new_email FROM '{EMAIL_CHANGE_TABLE}' WHERE user = $1 AND expires > UNIXEPOCH()) AS pending \
+      WHERE \
+        id = $1 AND email IS pending.old_email \
+    "
+  );
+  const DELETE_QUERY: &str = formatcp!("DELETE FROM '{EMAIL_CHANGE_TABLE}' WHERE user = $1");
+
+  let rows_affected = tx.execute(UPDATE_QUERY, params!(user_id.into_bytes()))?;
+  if rows_affected != 1 {
+    return Ok(false);
+  }
+  tx.execute(DELETE_QUERY, params!(user_id.into_bytes()))?;
+
+  return Ok(true);
+}
@@ -8,7 +8,7 @@ use ts_rs::TS;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::app_state::AppState;
-use crate::auth::password::{check_user_password, hash_password, validate_password_policy};
+use crate::auth::password::{check_user_password, hash_password, validate_password};
 use crate::auth::util::{user_by_id, validate_redirect};
 use crate::auth::{AuthError, User};
 use crate::constants::USER_TABLE;
@@ -69,14 +69,16 @@ pub async fn change_password_handler(
     query.err_redirect_uri.or(request.params.err_redirect_uri),
   )?;
 
-  if let Err(err) = validate_password_policy(
+  if let Err(err) = validate_password(
     &request.new_password,
     request
       .new_password_repeat
       .as_ref()
       .unwrap_or(&request.new_password),
     state.auth_options().password_options(),
-  ) {
+  )
+  .await
+  {
     if !json && let Some(redirect_uri) = err_redirect_uri.or(redirect_uri) {
       return Ok(
         Redirect::to(&format!(
@@ -94,7 +96,13 @@ pub async fn change_password_handler(
   // Optionally validate old password.
   // TODO: It would probably be good practice to check TOTP as well for users of multi-factor
   // auth.
-  if let Err(_err) = check_user_password(&db_user, &request.old_password, state.demo_mode()) {
+  if let Err(_err) = check_user_password(
+    &db_user,
+    &request.old_password,
+    None,
+    state.auth_options().lockout_options(),
+    state.demo_mode(),
+  ) {
     const MSG: &str = "invalid `old_password`";
     if !json && let Some(redirect_uri) = err_redirect_uri.or(redirect_uri) {
       return Ok(
@@ -14,12 +14,14 @@ use crate::auth::AuthError;
 use crate::auth::jwt::PendingAuthTokenClaims;
 use crate::auth::login_params::{LoginInputParams, LoginParams, build_and_validate_input_params};
 use crate::auth::password::check_user_password;
+use crate::auth::tokens::SessionMetadata;
 use crate::auth::totp::new_totp;
 use crate::auth::user::DbUser;
 use crate::auth::util::{
   new_cookie, remove_cookie, user_by_email, user_by_id, user_by_username,
   validate_and_normalize_email_address, validate_and_normalize_username,
 };
+use crate::auth::webhooks::{AuthEvent, emit_auth_event};
 use crate::constants::{
   AUTHORIZATION_CODE_TABLE, COOKIE_AUTH_TOKEN, COOKIE_REFRESH_TOKEN,
   DEFAULT_AUTHORIZATION_CODE_TTL, DEFAULT_MFA_TOKEN_TTL, VERIFICATION_CODE_LENGTH,
@@ -93,6 +95,7 @@ pub(crate) async fn login_handler(
   State(state): State<AppState>,
   Query(query_login_input): Query<LoginInputParams>,
   cookies: Cookies,
+  session: SessionMetadata,
   either_request: Either<LoginRequest>,
 ) -> Result<Response, AuthError> {
   let (request, json) = match either_request {
@@ -141,12 +144,18 @@ pub(crate) async fn login_handler(
     ),
   };
 
+  let (identifier_kind, identifier) = match user_identifier {
+    UserIdentifier::Email(ref email) => ("email", email.clone()),
+    UserIdentifier::Username(ref username) => ("username", username.clone()),
+  };
+
   type CheckFuture = futures_util::future::BoxFuture<'static, Result<DbUser, AuthError>>;
   type CheckFn = Box<dyn FnOnce() -> CheckFuture + Send>;
 
   let check_credentials: CheckFn = match user_identifier {
     UserIdentifier::Email(normalized_email) => {
       let state = state.clone();
+      let client_ip = session.client_ip.clone();
       Box::new(move || -> CheckFuture {
         return Box::pin(async move {
           let db_user: DbUser = user_by_email(&state, &normalized_email)
@@ -156,8 +165,14 @@ pub(crate) async fn login_handler(
               return AuthError::Unauthorized;
             })?;
 
-          // Check password and rate limits attempts.
-          check_user_password(&db_user, &password, state.demo_mode())?;
+          // Check password and lock out repeated failures.
+          check_user_password(
+            &db_user,
+            &password,
+            client_ip.as_deref(),
+            state.auth_options().lockout_options(),
+            state.demo_mode(),
+          )?;
 
           Ok(db_user)
         });
@@ -165,6 +180,7 @@ pub(crate) async fn login_handler(
     }
     UserIdentifier::Username(username) => {
       let state = state.clone();
+      let client_ip = session.client_ip.clone();
       Box::new(|| -> CheckFuture {
         return Box::pin(async move {
           let db_user: DbUser = user_by_username(&state, &username).await.map_err(|_| {
@@ -172,8 +188,14 @@ pub(crate) async fn login_handler(
             return AuthError::Unauthorized;
           })?;
 
-          // Check password and rate limits attempts.
-          check_user_password(&db_user, &password, state.demo_mode())?;
+          // Check password and lock out repeated failures.
+          check_user_password(
+            &db_user,
+            &password,
+            client_ip.as_deref(),
+            state.auth_options().lockout_options(),
+            state.demo_mode(),
+          )?;
 
           Ok(db_user)
         });
@@ -197,6 +219,16 @@ pub(crate) async fn login_handler(
   // Check credentials.
   let db_user = match check_credentials().await {
     Err(err) => {
+      emit_auth_event(
+        &state,
+        AuthEvent::LoginFailed,
+        serde_json::json!({
+          identifier_kind: identifier,
+          "client_ip": session.client_ip,
+          "reason": err.to_string(),
+        }),
+      );
+
       if !json && let Some(redirect_uri) = params.redirect_uri.as_deref() {
         return Ok(auth_error_to_response(err, &cookies, Some(redirect_uri)));
       }
@@ -231,7 +263,7 @@ pub(crate) async fn login_handler(
   return match login_params {
     // Auth-token flow.
     LoginParams::Password { redirect_uri } => {
-      build_auth_token_flow_response(&state, &db_user, &cookies, redirect_uri, json).await
+      build_auth_token_flow_response(&state, &db_user, &cookies, &session, redirect_uri, json).await
     }
     // Authorization-code flow.
     LoginParams::AuthorizationCodeFlowWithPkce {
@@ -263,14 +295,17 @@ pub(crate) async fn build_auth_token_flow_response_with_ttl(
   state: &AppState,
   db_user: &DbUser,
   cookies: &Cookies,
+  session: &SessionMetadata,
   redirect: Option<String>,
   is_json: bool,
   (auth_token_ttl, refresh_token_ttl): (Duration, Duration),
 ) -> Result<Response, AuthError> {
   let build_new_tokens = async || {
     let tokens = crate::auth::tokens::mint_new_tokens(
+      state.user_conn(),
       state.session_conn(),
       db_user,
+      session,
       &auth_token_ttl,
       &refresh_token_ttl,
     )
@@ -319,6 +354,7 @@ pub(crate) async fn build_auth_token_flow_response(
   state: &AppState,
   db_user: &DbUser,
   cookies: &Cookies,
+  session: &SessionMetadata,
   redirect: Option<String>,
   is_json: bool,
 ) -> Result<Response, AuthError> {
@@ -326,6 +362,7 @@ pub(crate) async fn build_auth_token_flow_response(
     state,
     db_user,
     cookies,
+    session,
     redirect,
     is_json,
     state.access_config(|c| c.auth.token_ttls()),
@@ -421,6 +458,7 @@ pub(crate) async fn login_mfa_handler(
   State(state): State<AppState>,
   Query(query_login_input): Query<LoginInputParams>,
   cookies: Cookies,
+  session: SessionMetadata,
   either_request: Either<LoginMfaRequest>,
 ) -> Result<Response, AuthError> {
   let (
@@ -487,7 +525,7 @@ pub(crate) async fn login_mfa_handler(
   return match params {
     // Auth-token flow.
     LoginParams::Password { redirect_uri } => {
-      build_auth_token_flow_response(&state, &db_user, &cookies, redirect_uri, json).await
+      build_auth_token_flow_response(&state, &db_user, &cookies, &session, redirect_uri, json).await
     }
     // Authorization-code flow.
     LoginParams::AuthorizationCodeFlowWithPkce {
@@ -11,6 +11,7 @@ use utoipa::ToSchema;
 use crate::app_state::AppState;
 use crate::auth::AuthError;
 use crate::auth::api::register::RegisterUserParams;
+use crate::auth::tokens::SessionMetadata;
 use crate::auth::user::DbUser;
 use crate::auth::util::validate_redirect;
 use crate::constants::{DEFAULT_ANONYMOUS_REFRESH_TOKEN_TTL, DEFAULT_AUTH_TOKEN_TTL, USER_TABLE};
@@ -23,7 +24,7 @@ pub struct LoginAnonymousRequest {
   pub params: RegisterUserParams,
 }
 
-/// Registers a new user with email and password.
+/// Signs in as a new anonymous user, i.e. w/o email or password. Also mounted at `/anonymous`.
 #[utoipa::path(
   post,
   path = "/login_anonymous",
@@ -39,6 +40,7 @@ pub async fn login_anonymous_user_handler(
   State(state): State<AppState>,
   Query(query): Query<RegisterUserParams>,
   cookies: Cookies,
+  session: SessionMetadata,
   either_request: Either<LoginAnonymousRequest>,
 ) -> Result<Response, AuthError> {
   let (enabled, auth_token_ttl) = state.access_config(|c| {
@@ -95,6 +97,7 @@ pub async fn login_anonymous_user_handler(
           &state,
           &user,
           &cookies,
+          &session,
           redirect_uri,
           json,
           // TODO: Separate config setting for anonymous token TTLs. Folks may want this to be
@@ -0,0 +1,218 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Redirect, Response};
+use chrono::Utc;
+use const_format::formatcp;
+use mini_moka::sync::Cache;
+use serde::Deserialize;
+use std::sync::LazyLock;
+use tower_cookies::Cookies;
+use trailbase_sqlite::params;
+use ts_rs::TS;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::app_state::AppState;
+use crate::auth::AuthError;
+use crate::auth::api::login::build_auth_token_flow_response;
+use crate::auth::jwt::MagicLinkTokenClaims;
+use crate::auth::tokens::SessionMetadata;
+use crate::auth::util::{
+  get_user_by_id, user_by_email, validate_and_normalize_email_address, validate_redirect,
+};
+use crate::constants::{MAGIC_LINK_TABLE, USER_TABLE, VERIFICATION_CODE_LENGTH};
+use crate::email::Email;
+use crate::extract::Either;
+use crate::rand::random_alphanumeric;
+use crate::util::{b64_to_uuid, urlencode};
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct RequestMagicLinkParams {
+  pub redirect_uri: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct RequestMagicLinkRequest {
+  pub email: String,
+  pub redirect_uri: Option<String>,
+}
+
+/// Request a single-use sign-in link via email.
+#[utoipa::path(
+  post,
+  path = "/magic_link",
+  tag = "auth",
+  params(RequestMagicLinkParams),
+  request_body = RequestMagicLinkRequest,
+  responses(
+    (status = 200, description = "Link sent or user not found, when redirect_uri not present."),
+    (status = 303, description = "Link sent or user not found, when redirect_uri present."),
+    (status = 400, description = "Bad request"),
+    (status = 429, description = "Too many attempts"),
+  )
+)]
+pub async fn request_magic_link_handler(
+  State(state): State<AppState>,
+  Query(query): Query<RequestMagicLinkParams>,
+  either_request: Either<RequestMagicLinkRequest>,
+) -> Result<Response, AuthError> {
+  if !state.access_config(|c| c.auth.enable_magic_link_signin()) {
+    return Err(AuthError::MethodNotAllowed);
+  }
+
+  let request = match either_request {
+    Either::Json(req) => req,
+    Either::Multipart(req, _) => req,
+    Either::Form(req) => req,
+  };
+
+  let redirect_uri = validate_redirect(&state, query.redirect_uri.or(request.redirect_uri))?;
+  let normalized_email = validate_and_normalize_email_address(&request.email)?;
+
+  rate_limit_magic_link_requests(normalized_email.clone())?;
+
+  let success_response = || {
+    const MSG: &str = "Sign-in link sent";
+    if let Some(ref redirect) = redirect_uri {
+      Redirect::to(&format!("{redirect}?alert={msg}", msg = urlencode(MSG))).into_response()
+    } else {
+      (StatusCode::OK, MSG).into_response()
+    }
+  };
+
+  let Ok(db_user) = user_by_email(&state, &normalized_email).await else {
+    // In case we don't find a user we still reply with a success to avoid leaking
+    // users' email addresses.
+    return Ok(success_response());
+  };
+
+  if db_user.totp_secret.is_some() {
+    // Like for OTP, a link-only login would circumvent the user's second factor.
+    #[cfg(debug_assertions)]
+    log::debug!("Skipping magic link request for user with two-factor auth enabled.");
+
+    return Ok(success_response());
+  }
+
+  let ttl = state.access_config(|c| c.auth.magic_link_ttl());
+  let nonce = random_alphanumeric(VERIFICATION_CODE_LENGTH);
+
+  const INSERT_NONCE_QUERY: &str =
+    formatcp!("INSERT INTO '{MAGIC_LINK_TABLE}' (user, nonce, expires) VALUES ($1, $2, $3)");
+  state
+    .session_conn()
+    .execute(
+      INSERT_NONCE_QUERY,
+      params!(db_user.id, nonce.clone(), (Utc::now() + ttl).timestamp()),
+    )
+    .await?;
+
+  let claims = MagicLinkTokenClaims::new(&db_user.uuid(), normalized_email.clone(), nonce, ttl);
+  let token = state
+    .jwt()
+    .encode(&claims)
+    .map_err(|err| AuthError::Internal(err.into()))?;
+
+  let email = Email::magic_link_email(&state, &normalized_email, &token, redirect_uri.as_deref())
+    .await
+    .map_err(|err| AuthError::Internal(err.into()))?;
+  email
+    .send()
+    .await
+    .map_err(|err| AuthError::Internal(err.into()))?;
+
+  return Ok(success_response());
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct MagicLinkLoginParams {
+  pub redirect_uri: Option<String>,
+}
+
+/// Exchange a magic link for a session. Sets auth cookies and redirects.
+#[utoipa::path(
+  get,
+  path = "/magic_link/login/:magic_link_token",
+  tag = "auth",
+  params(MagicLinkLoginParams),
+  responses(
+    (status = 200, description = "Logged in, when redirect_uri not present."),
+    (status = 303, description = "Logged in, when redirect_uri present."),
+    (status = 401, description = "Invalid, expired or already used link."),
+  )
+)]
+pub async fn login_magic_link_handler(
+  State(state): State<AppState>,
+  cookies: Cookies,
+  session: SessionMetadata,
+  Path(magic_link_token): Path<String>,
+  Query(query): Query<MagicLinkLoginParams>,
+) -> Result<Response, AuthError> {
+  if !state.access_config(|c| c.auth.enable_magic_link_signin()) {
+    return Err(AuthError::MethodNotAllowed);
+  }
+
+  let redirect_uri = validate_redirect(&state, query.redirect_uri)?;
+
+  let claims = MagicLinkTokenClaims::decode(state.jwt(), &magic_link_token)
+    .map_err(|_err| AuthError::Unauthorized)?;
+  let user_id = b64_to_uuid(&claims.sub).map_err(|_err| AuthError::Unauthorized)?;
+
+  // Consume the nonce to make sure the link can only be used once.
+  const CONSUME_NONCE_QUERY: &str = formatcp!(
+    "\
+      DELETE FROM '{MAGIC_LINK_TABLE}' \
+      WHERE nonce = $1 AND user = $2 AND UNIXEPOCH() < expires \
+      RETURNING user \
+    "
+  );
+  let _: [u8; 16] = state
+    .session_conn()
+    .write_query_row_get(
+      CONSUME_NONCE_QUERY,
+      params!(claims.nonce, user_id.into_bytes()),
+      0,
+    )
+    .await?
+    .ok_or(AuthError::Unauthorized)?;
+
+  // Following the link proves control over the address, i.e. it also verifies it.
+  const VERIFY_QUERY: &str =
+    formatcp!("UPDATE '{USER_TABLE}' SET verified = TRUE WHERE id = $1 AND email = $2");
+  state
+    .user_conn()
+    .execute(VERIFY_QUERY, params!(user_id.into_bytes(), claims.email))
+    .await?;
+
+  let db_user = get_user_by_id(state.user_conn(), &user_id).await?;
+
+  return build_auth_token_flow_response(
+    &state,
+    &db_user,
+    &cookies,
+    &session,
+    redirect_uri.map(|uri| uri.to_string()),
+    false,
+  )
+  .await;
+}
+
+const RATE_LIMIT_SEC: u64 = 60;
+
+// Track attempts to request magic links for abuse prevention.
+fn rate_limit_magic_link_requests(id: String) -> Result<(), AuthError> {
+  static REQUEST_ATTEMPTS: LazyLock<Cache<String, ()>> = LazyLock::new(|| {
+    Cache::builder()
+      .time_to_live(std::time::Duration::from_secs(RATE_LIMIT_SEC))
+      .max_capacity(2048)
+      .build()
+  });
+
+  if REQUEST_ATTEMPTS.get(&id).is_some() {
+    return Err(AuthError::TooManyRequests);
+  }
+
+  REQUEST_ATTEMPTS.insert(id, ());
+
+  return Ok(());
+}
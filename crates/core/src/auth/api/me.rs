@@ -0,0 +1,89 @@
+use axum::extract::{Json, State};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+use crate::app_state::AppState;
+use crate::auth::profile::{Profile, read_profile, validate_profile, write_profile};
+use crate::auth::util::user_by_id;
+use crate::auth::{AuthError, User};
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct MeResponse {
+  /// Url-safe Base64 encoded id of the current user.
+  pub id: String,
+  pub email: Option<String>,
+  pub username: Option<String>,
+  pub verified: bool,
+  /// Custom profile fields as configured via `auth.user_profile`.
+  #[schema(value_type = Object)]
+  pub profile: Profile,
+}
+
+/// Get the current user, including custom profile fields.
+#[utoipa::path(
+  get,
+  path = "/me",
+  tag = "auth",
+  responses(
+    (status = 200, description = "Current user.", body = MeResponse),
+    (status = 401, description = "Unauthorized."),
+  )
+)]
+pub async fn get_me_handler(
+  State(state): State<AppState>,
+  user: User,
+) -> Result<Json<MeResponse>, AuthError> {
+  if user.api_key.is_some() {
+    return Err(AuthError::Forbidden);
+  }
+
+  let db_user = user_by_id(&state, &user.uuid).await?;
+
+  return Ok(Json(MeResponse {
+    id: user.id,
+    email: db_user.email,
+    username: db_user.username,
+    verified: db_user.verified,
+    profile: read_profile(&state, user.uuid).await?,
+  }));
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema, TS)]
+#[ts(export)]
+pub struct UpdateProfileRequest {
+  /// Profile fields to update. Fields not present remain unchanged, `null` unsets a field.
+  #[schema(value_type = Object)]
+  pub profile: Profile,
+}
+
+/// Update the current user's custom profile fields.
+#[utoipa::path(
+  patch,
+  path = "/me",
+  tag = "auth",
+  request_body = UpdateProfileRequest,
+  responses(
+    (status = 200, description = "Updated user.", body = MeResponse),
+    (status = 400, description = "Unknown, read-only or invalid profile field."),
+    (status = 401, description = "Unauthorized."),
+  )
+)]
+pub async fn update_me_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<UpdateProfileRequest>,
+) -> Result<Json<MeResponse>, AuthError> {
+  if state.demo_mode() {
+    return Err(AuthError::BadRequest("Disallowed in demo"));
+  }
+  if user.api_key.is_some() {
+    return Err(AuthError::Forbidden);
+  }
+
+  let values = validate_profile(&state, request.profile, false)?;
+  write_profile(&state, user.uuid, values).await?;
+
+  return get_me_handler(State(state), user).await;
+}
@@ -1,16 +1,19 @@
 pub(super) mod avatar;
-pub(super) mod change_email;
+pub(crate) mod change_email;
 pub(super) mod change_password;
 pub(super) mod change_username;
 pub(super) mod delete;
-pub(super) mod login;
+pub(crate) mod login;
 pub(super) mod login_anonymous;
 pub(super) mod logout;
+pub(super) mod magic_link;
+pub(super) mod me;
 pub(super) mod otp;
 pub(super) mod promote_anonymous;
 pub(super) mod refresh;
 pub(super) mod register;
 pub(super) mod reset_password;
+pub(crate) mod sessions;
 pub(super) mod status;
 pub(super) mod token;
 pub(super) mod totp;
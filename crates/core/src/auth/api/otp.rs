@@ -15,6 +15,7 @@ use uuid::Uuid;
 use crate::app_state::AppState;
 use crate::auth::AuthError;
 use crate::auth::api::login::{LoginResponse, build_auth_token_flow_response};
+use crate::auth::tokens::SessionMetadata;
 use crate::auth::user::DbUser;
 use crate::auth::util::{
   get_user_by_id, user_by_email, user_by_username, validate_and_normalize_email_address,
@@ -201,6 +202,7 @@ pub async fn request_otp_handler(
   }
 
   let email = Email::otp_email(&state, normalized_email, &otp_code, redirect_uri.as_deref())
+    .await
     .map_err(|err| AuthError::Internal(err.into()))?;
   email
     .send()
@@ -240,6 +242,7 @@ pub struct LoginOtpRequest {
 pub async fn login_otp_handler(
   State(state): State<AppState>,
   cookies: Cookies,
+  session: SessionMetadata,
   Query(query): Query<LoginOtpParams>,
   either_request: Either<LoginOtpRequest>,
 ) -> Result<Response, AuthError> {
@@ -334,6 +337,7 @@ pub async fn login_otp_handler(
     &state,
     &db_user,
     &cookies,
+    &session,
     redirect_uri.map(|uri| uri.to_string()),
     json,
   )
@@ -9,7 +9,7 @@ use utoipa::{IntoParams, ToSchema};
 
 use crate::app_state::AppState;
 use crate::auth::jwt::EmailVerificationTokenClaims;
-use crate::auth::password::{hash_password, validate_password_policy};
+use crate::auth::password::{hash_password, validate_password};
 use crate::auth::util::{user_by_id, validate_redirect};
 use crate::auth::{AuthError, User};
 use crate::constants::USER_TABLE;
@@ -82,14 +82,16 @@ pub async fn promote_anonymous_user_handler(
     request.new_username.as_deref(),
   )?;
 
-  if let Err(err) = validate_password_policy(
+  if let Err(err) = validate_password(
     &request.new_password,
     request
       .new_password_repeat
       .as_ref()
       .unwrap_or(&request.new_password),
     state.auth_options().password_options(),
-  ) {
+  )
+  .await
+  {
     if !json && let Some(redirect_uri) = err_redirect_uri.or(redirect_uri) {
       return Ok(
         Redirect::to(&format!(
@@ -103,7 +105,7 @@ pub async fn promote_anonymous_user_handler(
   }
 
   let db_user = user_by_id(&state, &user.uuid).await?;
-  if db_user.password_hash.is_some() || db_user.provider_id > 0 {
+  if !db_user.is_anonymous() {
     return Err(AuthError::FailedDependency("not an anonymous user".into()));
   }
 
@@ -148,6 +150,7 @@ pub async fn promote_anonymous_user_handler(
           .map_err(|err| AuthError::Internal(err.into()))?;
 
         let email = Email::verification_email(&state, email, &token, redirect_uri.as_deref())
+          .await
           .map_err(|err| AuthError::Internal(err.into()))?;
 
         email
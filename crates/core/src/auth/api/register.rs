@@ -10,11 +10,13 @@ use utoipa::{IntoParams, ToSchema};
 use crate::app_state::AppState;
 use crate::auth::AuthError;
 use crate::auth::jwt::EmailVerificationTokenClaims;
-use crate::auth::password::{hash_password, validate_password_policy};
+use crate::auth::password::{hash_password, validate_password};
+use crate::auth::profile::{Profile, validate_profile, write_profile};
 use crate::auth::user::DbUser;
 use crate::auth::util::{
   validate_and_normalize_email_address, validate_and_normalize_username, validate_redirect,
 };
+use crate::auth::webhooks::emit_user_created;
 use crate::config::proto::UserIdentifier;
 use crate::constants::USER_TABLE;
 use crate::email::Email;
@@ -33,6 +35,11 @@ pub struct RegisterUserRequest {
   pub password: String,
   pub password_repeat: String,
 
+  /// Custom profile fields as configured via `auth.user_profile`. Only supported for JSON
+  /// requests.
+  #[schema(value_type = Option<Object>)]
+  pub profile: Option<Profile>,
+
   #[serde(flatten)]
   pub params: RegisterUserParams,
 }
@@ -78,11 +85,13 @@ pub async fn register_user_handler(
   )?;
 
   let auth_options = state.auth_options();
-  if let Err(err) = validate_password_policy(
+  if let Err(err) = validate_password(
     &request.password,
     &request.password_repeat,
     auth_options.password_options(),
-  ) {
+  )
+  .await
+  {
     if !json && let Some(redirect_uri) = redirect_uri {
       return Ok(
         Redirect::to(&format!(
@@ -96,6 +105,8 @@ pub async fn register_user_handler(
     return Err(err);
   }
 
+  let profile = validate_profile(&state, request.profile.unwrap_or_default(), true)?;
+
   let success_response = {
     let normalized_email = normalized_email.clone();
     let redirect_uri = redirect_uri.clone();
@@ -150,6 +161,9 @@ pub async fn register_user_handler(
     }
   };
 
+  write_profile(&state, user.uuid(), profile).await?;
+  emit_user_created(&state, &user, "register");
+
   if let Some(ref email) = user.email {
     let claims =
       EmailVerificationTokenClaims::new(&user.uuid(), email.clone(), chrono::Duration::hours(4));
@@ -159,6 +173,7 @@ pub async fn register_user_handler(
       .map_err(|err| AuthError::Internal(err.into()))?;
 
     let email = Email::verification_email(&state, email, &token, redirect_uri.as_deref())
+      .await
       .map_err(|err| AuthError::Internal(err.into()))?;
 
     email
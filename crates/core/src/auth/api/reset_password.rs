@@ -9,15 +9,18 @@ use std::sync::LazyLock;
 use trailbase_sqlite::params;
 use ts_rs::TS;
 use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 
 use crate::app_state::AppState;
+use crate::audit::{AuditAction, record_audit_event};
 use crate::auth::AuthError;
 use crate::auth::jwt::PasswordResetTokenClaims;
-use crate::auth::password::{hash_password, validate_password_policy};
+use crate::auth::password::{hash_password, validate_password};
 use crate::auth::util::{
   user_by_email, user_by_username, validate_and_normalize_email_address,
   validate_and_normalize_username, validate_redirect,
 };
+use crate::auth::webhooks::{AuthEvent, emit_auth_event};
 use crate::constants::USER_TABLE;
 use crate::email::Email;
 use crate::extract::Either;
@@ -125,6 +128,7 @@ pub async fn reset_password_request_handler(
     .map_err(|err| AuthError::Internal(err.into()))?;
 
   let email = Email::password_reset_email(&state, &normalized_email, &token)
+    .await
     .map_err(|err| AuthError::Internal(err.into()))?;
   email
     .send()
@@ -180,11 +184,12 @@ pub async fn reset_password_update_handler(
   let redirect_uri = validate_redirect(&state, query.redirect_uri.or(request.params.redirect_uri))?;
 
   let auth_options = state.auth_options();
-  validate_password_policy(
+  validate_password(
     &request.password,
     &request.password_repeat,
     auth_options.password_options(),
-  )?;
+  )
+  .await?;
 
   let password_reset_claims =
     PasswordResetTokenClaims::from_password_reset_token(state.jwt(), &request.password_reset_token)
@@ -196,20 +201,38 @@ pub async fn reset_password_update_handler(
       UPDATE \"{USER_TABLE}\" \
       SET password_hash = $1 \
       WHERE email = $2 \
+      RETURNING id \
     "
   );
 
-  let rows_affected = state
+  let user_id: Option<[u8; 16]> = state
     .user_conn()
-    .execute(
+    .write_query_row_get(
       UPDATE_PASSWORD_QUERY,
-      params!(hashed_password, password_reset_claims.sub),
+      params!(hashed_password, password_reset_claims.sub.clone()),
+      0,
     )
     .await?;
 
-  return match rows_affected {
-    0 => Err(AuthError::Unauthorized),
-    1 => {
+  return match user_id {
+    None => Err(AuthError::Unauthorized),
+    Some(user_id) => {
+      let user_id = Uuid::from_bytes(user_id);
+      record_audit_event(
+        &state,
+        Some(user_id),
+        AuditAction::PasswordReset,
+        &user_id.to_string(),
+        None,
+      )
+      .await;
+
+      emit_auth_event(
+        &state,
+        AuthEvent::PasswordReset,
+        serde_json::json!({ "email": password_reset_claims.sub }),
+      );
+
       if let Some(redirect) = redirect_uri {
         Ok(
           Redirect::to(&format!(
@@ -222,9 +245,6 @@ pub async fn reset_password_update_handler(
         Ok((StatusCode::OK, "Password reset").into_response())
       }
     }
-    _ => {
-      panic!("multiple users with same verification code.");
-    }
   };
 }
 
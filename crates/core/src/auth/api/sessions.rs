@@ -0,0 +1,113 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::{Connection, params};
+use ts_rs::TS;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::auth::AuthError;
+use crate::auth::tokens::Tokens;
+use crate::auth::user::User;
+use crate::constants::SESSION_TABLE;
+use crate::util::b64_to_uuid;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SessionJson {
+  pub id: i64,
+  pub user_agent: Option<String>,
+  pub client_ip: Option<String>,
+  pub created: i64,
+  /// Last time the session was refreshed, or its creation time.
+  pub last_seen: i64,
+  pub expires: i64,
+  /// Whether this is the session of the requesting client.
+  pub current: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct ListSessionsResponse {
+  pub sessions: Vec<SessionJson>,
+}
+
+/// List the current user's active sessions, i.e. signed-in devices.
+#[utoipa::path(
+  get,
+  path = "/sessions",
+  tag = "auth",
+  responses(
+    (status = 200, description = "Active sessions.", body = ListSessionsResponse),
+    (status = 401, description = "Unauthorized."),
+  )
+)]
+pub async fn list_sessions_handler(
+  State(state): State<AppState>,
+  tokens: Tokens,
+) -> Result<Json<ListSessionsResponse>, AuthError> {
+  let user_id = b64_to_uuid(&tokens.auth_token_claims.sub)
+    .map_err(|_err| AuthError::BadRequest("invalid user id"))?;
+
+  return Ok(Json(ListSessionsResponse {
+    sessions: list_sessions_for_user(state.session_conn(), user_id, tokens.refresh_token).await?,
+  }));
+}
+
+/// Revoke one of the current user's sessions, e.g. to sign out a lost device.
+///
+/// Already issued auth tokens remain valid until they expire, however they can no longer be
+/// refreshed.
+#[utoipa::path(
+  delete,
+  path = "/sessions/{session_id}",
+  tag = "auth",
+  responses(
+    (status = 200, description = "Session revoked."),
+    (status = 401, description = "Unauthorized."),
+    (status = 404, description = "Session not found."),
+  )
+)]
+pub async fn revoke_session_handler(
+  State(state): State<AppState>,
+  Path(session_id): Path<i64>,
+  user: User,
+) -> Result<Response, AuthError> {
+  const QUERY: &str = formatcp!("DELETE FROM '{SESSION_TABLE}' WHERE id = $1 AND user = $2");
+
+  let rows_affected = state
+    .session_conn()
+    .execute(QUERY, params!(session_id, user.uuid.into_bytes()))
+    .await?;
+  if rows_affected == 0 {
+    return Err(AuthError::NotFound);
+  }
+
+  return Ok((StatusCode::OK, "revoked").into_response());
+}
+
+pub(crate) async fn list_sessions_for_user(
+  session_conn: &Connection,
+  user_id: Uuid,
+  current_refresh_token: Option<String>,
+) -> Result<Vec<SessionJson>, AuthError> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT \
+        id, user_agent, client_ip, created, COALESCE(last_seen, created) AS last_seen, expires, \
+        (refresh_token IS $2) AS current \
+      FROM '{SESSION_TABLE}' \
+      WHERE user = $1 AND expires > UNIXEPOCH() \
+      ORDER BY last_seen DESC \
+    "
+  );
+
+  return Ok(
+    session_conn
+      .read_query_values::<SessionJson>(QUERY, params!(user_id.into_bytes(), current_refresh_token))
+      .await?,
+  );
+}
@@ -8,7 +8,7 @@ use uuid::Uuid;
 
 use crate::app_state::AppState;
 use crate::auth::AuthError;
-use crate::auth::tokens::mint_new_tokens;
+use crate::auth::tokens::{SessionMetadata, mint_new_tokens};
 use crate::auth::util::{derive_pkce_code_challenge, get_user_by_id};
 use crate::constants::{AUTHORIZATION_CODE_TABLE, VERIFICATION_CODE_LENGTH};
 
@@ -41,6 +41,7 @@ pub struct TokenResponse {
 )]
 pub(crate) async fn auth_code_to_token_handler(
   State(state): State<AppState>,
+  session: SessionMetadata,
   Json(request): Json<AuthCodeToTokenRequest>,
 ) -> Result<Json<TokenResponse>, AuthError> {
   let authorization_code = match request.authorization_code {
@@ -82,8 +83,10 @@ pub(crate) async fn auth_code_to_token_handler(
   let (auth_token_ttl, refresh_token_ttl) = state.access_config(|c| c.auth.token_ttls());
 
   let tokens = mint_new_tokens(
+    state.user_conn(),
     state.session_conn(),
     &db_user,
+    &session,
     &auth_token_ttl,
     &refresh_token_ttl,
   )
@@ -73,6 +73,7 @@ pub async fn request_email_verification_handler(
     .map_err(|err| AuthError::Internal(err.into()))?;
 
   let email = Email::verification_email(&state, &normalized_email, &token, redirect_uri.as_deref())
+    .await
     .map_err(|err| AuthError::Internal(err.into()))?;
   email
     .send()
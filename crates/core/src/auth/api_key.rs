@@ -0,0 +1,126 @@
+use axum::http::header;
+use const_format::formatcp;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use trailbase_sqlite::params;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::AuthError;
+use crate::constants::{API_KEY_SECRET_LENGTH, API_KEY_TABLE};
+use crate::rand::random_alphanumeric;
+use crate::records::Permission;
+use crate::util::get_header;
+
+/// Prefix distinguishing API keys from JWT auth tokens, which are both passed as bearer tokens.
+pub(crate) const API_KEY_PREFIX: &str = "tb_";
+
+/// Scope of an API key, i.e. which record APIs it may access with which permissions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiKeyScope {
+  pub record_apis: Vec<String>,
+  /// Bitmask of [Permission]s.
+  pub permissions: u8,
+  /// Tenant the key is bound to in multi-tenant mode.
+  pub tenant: Option<String>,
+}
+
+impl ApiKeyScope {
+  pub fn allows(&self, api_name: &str, p: Permission) -> bool {
+    return (self.permissions & (p as u8)) > 0 && self.record_apis.iter().any(|a| a == api_name);
+  }
+}
+
+/// A freshly minted secret. Only its hash gets persisted.
+pub(crate) fn new_api_key_secret() -> String {
+  return format!(
+    "{API_KEY_PREFIX}{}",
+    random_alphanumeric(API_KEY_SECRET_LENGTH)
+  );
+}
+
+pub(crate) fn hash_api_key_secret(secret: &str) -> Vec<u8> {
+  return Sha256::digest(secret.as_bytes()).to_vec();
+}
+
+/// Returns the API key passed as `Authorization: Bearer tb_...` header, if any.
+pub(crate) fn extract_api_key_from_headers(headers: &header::HeaderMap) -> Option<&str> {
+  return get_header(headers, header::AUTHORIZATION)
+    .and_then(|v| v.strip_prefix("Bearer "))
+    .filter(|token| token.starts_with(API_KEY_PREFIX));
+}
+
+/// Looks up a non-expired API key by its secret.
+pub(crate) async fn lookup_api_key(
+  state: &AppState,
+  secret: &str,
+) -> Result<(Uuid, ApiKeyScope), AuthError> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT id, record_apis, permissions, tenant FROM '{API_KEY_TABLE}' \
+      WHERE secret_hash = $1 AND (expires IS NULL OR UNIXEPOCH() < expires) \
+    "
+  );
+
+  let Some(key) = state
+    .user_conn()
+    .read_query_value::<DbApiKey>(QUERY, params!(hash_api_key_secret(secret)))
+    .await?
+  else {
+    return Err(AuthError::Unauthorized);
+  };
+
+  return Ok((
+    Uuid::from_bytes(key.id),
+    ApiKeyScope {
+      record_apis: serde_json::from_str(&key.record_apis)
+        .map_err(|err| AuthError::Internal(err.into()))?,
+      permissions: key.permissions as u8,
+      tenant: key.tenant,
+    },
+  ));
+}
+
+#[derive(Deserialize)]
+struct DbApiKey {
+  id: [u8; 16],
+  record_apis: String,
+  permissions: i64,
+  tenant: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_api_key_scope() {
+    let scope = ApiKeyScope {
+      record_apis: vec!["messages".to_string()],
+      permissions: Permission::Read as u8 | Permission::Create as u8,
+      tenant: None,
+    };
+
+    assert!(scope.allows("messages", Permission::Read));
+    assert!(scope.allows("messages", Permission::Create));
+    assert!(!scope.allows("messages", Permission::Delete));
+    assert!(!scope.allows("other", Permission::Read));
+  }
+
+  #[test]
+  fn test_extract_api_key() {
+    let secret = new_api_key_secret();
+    let mut headers = header::HeaderMap::new();
+    headers.insert(
+      header::AUTHORIZATION,
+      format!("Bearer {secret}").parse().unwrap(),
+    );
+    assert_eq!(
+      extract_api_key_from_headers(&headers),
+      Some(secret.as_str())
+    );
+
+    headers.insert(header::AUTHORIZATION, "Bearer eyJhbGciOi".parse().unwrap());
+    assert_eq!(extract_api_key_from_headers(&headers), None);
+  }
+}
@@ -25,6 +25,7 @@ use crate::auth::api::login::{
 };
 use crate::auth::api::login_anonymous::{LoginAnonymousRequest, login_anonymous_user_handler};
 use crate::auth::api::logout::{LogoutParams, logout_handler};
+use crate::auth::api::magic_link;
 use crate::auth::api::otp;
 use crate::auth::api::promote_anonymous::{
   PromoteAnonymousRequest, promote_anonymous_user_handler,
@@ -35,16 +36,20 @@ use crate::auth::api::reset_password::{
   ResetPasswordRequest, ResetPasswordUpdateRequest, reset_password_request_handler,
   reset_password_update_handler,
 };
+use crate::auth::api::sessions;
 use crate::auth::api::token::{AuthCodeToTokenRequest, TokenResponse, auth_code_to_token_handler};
 use crate::auth::api::totp;
 use crate::auth::api::verify_email::{VerifyEmailParams, verify_email_handler};
 use crate::auth::jwt::PasswordResetTokenClaims;
 use crate::auth::login_params::{LoginInputParams, ResponseType};
+use crate::auth::tokens::{
+  FreshTokens, SessionMetadata, Tokens, mint_new_tokens, reauth_with_refresh_token,
+};
 use crate::auth::user::{DbUser, User};
-use crate::auth::util::{login_with_password, login_with_password_for_test};
+use crate::auth::util::{login_with_password, login_with_password_for_test, user_by_id};
 use crate::config::proto::{Config, EmailTemplate, UserIdentifier};
 use crate::constants::*;
-use crate::email::{Mailer, testing::TestAsyncSmtpTransport};
+use crate::email::{SmtpMailer, testing::TestAsyncSmtpTransport};
 use crate::extract::Either;
 
 fn build_test_config_with_trivial_tokens() -> Config {
@@ -57,6 +62,10 @@ fn build_test_config_with_trivial_tokens() -> Config {
     subject: None,
     body: Some("{{ TOKEN }}".to_string()),
   });
+  config.email.change_email_old_address_template = Some(EmailTemplate {
+    subject: None,
+    body: Some("{{ TOKEN }}".to_string()),
+  });
   config.email.user_verification_template = Some(EmailTemplate {
     subject: None,
     body: Some("{{ TOKEN }}".to_string()),
@@ -76,7 +85,7 @@ async fn setup_state_and_test_user(
   let mailer = TestAsyncSmtpTransport::new();
 
   let state = test_state(Some(TestStateOptions {
-    mailer: Some(Mailer::Smtp(Arc::new(mailer.clone()))),
+    mailer: Some(Arc::new(SmtpMailer::new(mailer.clone()))),
     config: Some({
       let mut config = config.unwrap_or_else(build_test_config_with_trivial_tokens);
 
@@ -174,6 +183,7 @@ async fn register_test_user(
         State(state.clone()),
         Query(LoginInputParams::default()),
         Cookies::default(),
+        SessionMetadata::default(),
         Either::Json(match identifier {
           Identifier::Email(ref email) | Identifier::EmailAndUsername(ref email, _) =>
             LoginRequest::Email {
@@ -249,6 +259,7 @@ async fn test_auth_password_login_flow_with_pkce() {
       State(state.clone()),
       Query(LoginInputParams::default()),
       Cookies::default(),
+      SessionMetadata::default(),
       request,
     )
     .await;
@@ -348,6 +359,7 @@ async fn test_auth_password_login_flow_with_pkce() {
   // And now upgrade to tokens, i.e. complete log-in.
   let Json(token_response): Json<TokenResponse> = auth_code_to_token_handler(
     State(state.clone()),
+    SessionMetadata::default(),
     Json(AuthCodeToTokenRequest {
       authorization_code: Some(auth_code.as_str().to_string()),
       pkce_code_verifier: Some(pkce_code_verifier.secret().to_string()),
@@ -393,6 +405,7 @@ async fn test_auth_password_login_flow_without_pkce() {
       State(state.clone()),
       Query(LoginInputParams::default()),
       Cookies::default(),
+      SessionMetadata::default(),
       request,
     )
     .await;
@@ -501,6 +514,7 @@ async fn test_auth_password_login_flow_with_totp() {
       State(state.clone()),
       Query(LoginInputParams::default()),
       Cookies::default(),
+      SessionMetadata::default(),
       request,
     )
     .await;
@@ -559,6 +573,7 @@ async fn test_auth_password_login_flow_with_totp() {
     State(state.clone()),
     Query(Default::default()),
     Cookies::default(),
+    SessionMetadata::default(),
     Either::Json(LoginMfaRequest {
       mfa_token,
       totp: Some(t.generate_current().unwrap()),
@@ -775,48 +790,76 @@ async fn test_auth_change_email_flow() {
   .await
   .unwrap();
 
-  // Assert that a change-email email was sent.
-  assert_eq!(mailer.get_logs().len(), 2);
+  // Assert that change-email emails were sent to both the new and the old address.
+  assert_eq!(mailer.get_logs().len(), 3);
 
-  // Steal the change email verification code.
-  let change_email_body: String = String::from_utf8_lossy(
-    &quoted_printable::decode(
-      mailer.get_logs().get(1).unwrap().1.as_bytes(),
-      quoted_printable::ParseMode::Robust,
+  // Steal the change email verification codes.
+  let steal_token = |index: usize| -> String {
+    let body: String = String::from_utf8_lossy(
+      &quoted_printable::decode(
+        mailer.get_logs().get(index).unwrap().1.as_bytes(),
+        quoted_printable::ParseMode::Robust,
+      )
+      .unwrap(),
     )
-    .unwrap(),
-  )
-  .to_string();
-
-  let change_email_re = Regex::new(r"\n(ey.*)$").unwrap();
-  let change_email_token: String = change_email_re
-    .captures(&change_email_body)
-    .unwrap()
-    .get(1)
-    .unwrap()
-    .as_str()
     .to_string();
 
+    let change_email_re = Regex::new(r"\n(ey.*)$").unwrap();
+    return change_email_re
+      .captures(&body)
+      .unwrap()
+      .get(1)
+      .unwrap()
+      .as_str()
+      .to_string();
+  };
+  let new_email_token = steal_token(1);
+  let old_email_token = steal_token(2);
+  assert_ne!(new_email_token, old_email_token);
+
+  let read_email = async || -> String {
+    return state
+      .user_conn()
+      .read_query_row_get(
+        format!(r#"SELECT email FROM "{USER_TABLE}" WHERE id = $1"#),
+        params!(user.uuid.into_bytes()),
+        0,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+  };
+
+  // Confirming only the new address is not sufficient.
   let _ = change_email::change_email_confirm_handler(
     State(state.clone()),
-    Path(change_email_token.clone()),
+    Path(new_email_token.clone()),
     Query(ChangeEmailConfigParams { redirect_uri: None }),
   )
   .await
-  .expect(&format!("CODE: '{change_email_token}'"));
+  .expect(&format!("CODE: '{new_email_token}'"));
+  assert_eq!(email, read_email().await);
 
-  let db_email: String = state
-    .user_conn()
-    .read_query_row_get(
-      format!(r#"SELECT email FROM "{USER_TABLE}" WHERE id = $1"#),
-      params!(user.uuid.into_bytes()),
-      0,
+  let _ = change_email::change_email_confirm_handler(
+    State(state.clone()),
+    Path(old_email_token.clone()),
+    Query(ChangeEmailConfigParams { redirect_uri: None }),
+  )
+  .await
+  .expect(&format!("CODE: '{old_email_token}'"));
+
+  // Tokens are single-use.
+  assert!(
+    change_email::change_email_confirm_handler(
+      State(state.clone()),
+      Path(old_email_token),
+      Query(ChangeEmailConfigParams { redirect_uri: None }),
     )
     .await
-    .unwrap()
-    .unwrap();
+    .is_err()
+  );
 
-  assert_eq!(new_email, db_email);
+  assert_eq!(new_email, read_email().await);
 
   assert!(
     login_with_password(&state, &email, &password)
@@ -928,7 +971,7 @@ async fn test_auth_register_handle_only() {
 
   let mailer = TestAsyncSmtpTransport::new();
   let state = test_state(Some(TestStateOptions {
-    mailer: Some(Mailer::Smtp(Arc::new(mailer.clone()))),
+    mailer: Some(Arc::new(SmtpMailer::new(mailer.clone()))),
     config: Some({
       let mut config = build_test_config_with_trivial_tokens();
       config.auth.user_identifier = Some(UserIdentifier::OnlyUsername.into());
@@ -970,6 +1013,7 @@ async fn test_auth_register_handle_only() {
     State(state.clone()),
     Query(LoginInputParams::default()),
     Cookies::default(),
+    SessionMetadata::default(),
     Either::Json(LoginRequest::Username {
       username: username.clone(),
       password: password.to_string(),
@@ -990,7 +1034,7 @@ async fn test_auth_change_username_and_unset_email_flow() {
 
   let mailer = TestAsyncSmtpTransport::new();
   let state = test_state(Some(TestStateOptions {
-    mailer: Some(Mailer::Smtp(Arc::new(mailer.clone()))),
+    mailer: Some(Arc::new(SmtpMailer::new(mailer.clone()))),
     config: Some({
       let mut config = build_test_config_with_trivial_tokens();
       config.auth.user_identifier = Some(UserIdentifier::RequireUsername.into());
@@ -1146,6 +1190,7 @@ async fn test_auth_otp_flow_using_email() {
     otp::login_otp_handler(
       State(state.clone()),
       Cookies::default(),
+      SessionMetadata::default(),
       Query(Default::default()),
       Either::Form(otp::LoginOtpRequest {
         params: otp::LoginOtpParams {
@@ -1173,6 +1218,7 @@ async fn test_auth_otp_flow_using_email() {
   let response = otp::login_otp_handler(
     State(state.clone()),
     Cookies::default(),
+    SessionMetadata::default(),
     Query(Default::default()),
     Either::Json(otp::LoginOtpRequest {
       params: otp::LoginOtpParams {
@@ -1194,6 +1240,88 @@ async fn test_auth_otp_flow_using_email() {
   let _login_response: LoginResponse = serde_json::from_slice(&body).unwrap();
 }
 
+#[tokio::test]
+async fn test_auth_magic_link_flow() {
+  let email = "user@test.org".to_string();
+  let password = "secret123".to_string();
+
+  let (state, mailer, _user) = setup_state_and_test_user(
+    &email,
+    &password,
+    Some({
+      let mut config = build_test_config_with_trivial_tokens();
+      config.auth.enable_magic_link_signin = Some(true);
+      config
+    }),
+  )
+  .await;
+
+  // NOTE: We return a success response on unknown user to avoid leaks.
+  magic_link::request_magic_link_handler(
+    State(state.clone()),
+    Query(Default::default()),
+    Either::Form(magic_link::RequestMagicLinkRequest {
+      email: "unknown@user.org".to_string(),
+      redirect_uri: None,
+    }),
+  )
+  .await
+  .unwrap();
+
+  // Only verify-email email for "user@test.org"
+  assert_eq!(mailer.get_logs().len(), 1, "{:?}", mailer.get_logs());
+
+  magic_link::request_magic_link_handler(
+    State(state.clone()),
+    Query(Default::default()),
+    Either::Json(magic_link::RequestMagicLinkRequest {
+      email: email.clone(),
+      redirect_uri: None,
+    }),
+  )
+  .await
+  .unwrap();
+
+  assert_eq!(mailer.get_logs().len(), 2);
+
+  let body: String = String::from_utf8_lossy(
+    &quoted_printable::decode(
+      mailer.get_logs()[1].1.as_bytes(),
+      quoted_printable::ParseMode::Robust,
+    )
+    .unwrap(),
+  )
+  .to_string();
+
+  let token: String = Regex::new(r#"magic_link/login/([^"?\s]+)"#)
+    .unwrap()
+    .captures(&body)
+    .expect(&body)
+    .get(1)
+    .unwrap()
+    .as_str()
+    .to_string();
+
+  let login = async |token: &str| {
+    return magic_link::login_magic_link_handler(
+      State(state.clone()),
+      Cookies::default(),
+      SessionMetadata::default(),
+      Path(token.to_string()),
+      Query(Default::default()),
+    )
+    .await;
+  };
+
+  assert!(login("invalid").await.is_err());
+
+  let response = login(&token).await.unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+
+  // Links are single-use.
+  assert!(matches!(login(&token).await, Err(AuthError::Unauthorized)));
+}
+
 #[tokio::test]
 async fn test_auth_otp_flow_using_username() {
   let email = "user@test.org".to_string();
@@ -1202,7 +1330,7 @@ async fn test_auth_otp_flow_using_username() {
 
   let mailer = TestAsyncSmtpTransport::new();
   let state = test_state(Some(TestStateOptions {
-    mailer: Some(Mailer::Smtp(Arc::new(mailer.clone()))),
+    mailer: Some(Arc::new(SmtpMailer::new(mailer.clone()))),
     config: Some({
       let mut config = build_test_config_with_trivial_tokens();
       config.auth.user_identifier = Some(UserIdentifier::RequireUsername.into());
@@ -1268,6 +1396,7 @@ async fn test_auth_otp_flow_using_username() {
     otp::login_otp_handler(
       State(state.clone()),
       Cookies::default(),
+      SessionMetadata::default(),
       Query(Default::default()),
       Either::Form(otp::LoginOtpRequest {
         params: otp::LoginOtpParams {
@@ -1295,6 +1424,7 @@ async fn test_auth_otp_flow_using_username() {
   let response = otp::login_otp_handler(
     State(state.clone()),
     Cookies::default(),
+    SessionMetadata::default(),
     Query(Default::default()),
     Either::Json(otp::LoginOtpRequest {
       params: otp::LoginOtpParams {
@@ -1322,7 +1452,7 @@ async fn test_auth_annonymous_signin() {
 
   let mailer = TestAsyncSmtpTransport::new();
   let state = test_state(Some(TestStateOptions {
-    mailer: Some(Mailer::Smtp(Arc::new(mailer.clone()))),
+    mailer: Some(Arc::new(SmtpMailer::new(mailer.clone()))),
     config: Some({
       let mut config = build_test_config_with_trivial_tokens();
       config.auth.user_identifier = Some(UserIdentifier::RequireUsername.into());
@@ -1338,6 +1468,7 @@ async fn test_auth_annonymous_signin() {
     State(state.clone()),
     Query(Default::default()),
     Cookies::default(),
+    SessionMetadata::default(),
     Either::Json(LoginAnonymousRequest {
       params: Default::default(),
     }),
@@ -1386,6 +1517,7 @@ async fn test_auth_annonymous_signin() {
       State(state.clone()),
       Query(LoginInputParams::default()),
       Cookies::default(),
+      SessionMetadata::default(),
       Either::Json(LoginRequest::Username {
         username: new_username.clone(),
         password: password.clone(),
@@ -1410,6 +1542,7 @@ async fn test_auth_annonymous_signin() {
     State(state.clone()),
     Query(LoginInputParams::default()),
     Cookies::default(),
+    SessionMetadata::default(),
     Either::Json(LoginRequest::Username {
       username: new_username.clone(),
       password: password.clone(),
@@ -1422,6 +1555,82 @@ async fn test_auth_annonymous_signin() {
   .unwrap();
 }
 
+#[tokio::test]
+async fn test_list_and_revoke_sessions() {
+  let (state, _mailer, user) =
+    setup_state_and_test_user("sessions@test.org", "Secret!1!!", None).await;
+  let db_user = user_by_id(&state, &user.uuid).await.unwrap();
+  let (auth_token_ttl, refresh_token_ttl) = state.access_config(|c| c.auth.token_ttls());
+
+  let mut tokens = vec![];
+  for user_agent in ["laptop", "phone"] {
+    tokens.push(
+      mint_new_tokens(
+        state.user_conn(),
+        state.session_conn(),
+        &db_user,
+        &SessionMetadata {
+          user_agent: Some(user_agent.to_string()),
+          client_ip: Some("127.0.0.1".to_string()),
+        },
+        &auth_token_ttl,
+        &refresh_token_ttl,
+      )
+      .await
+      .unwrap(),
+    );
+  }
+
+  let list = async |t: &FreshTokens| {
+    return sessions::list_sessions_handler(
+      State(state.clone()),
+      Tokens {
+        auth_token_claims: t.auth_token_claims.clone(),
+        refresh_token: Some(t.refresh_token.clone()),
+      },
+    )
+    .await
+    .unwrap()
+    .0
+    .sessions;
+  };
+
+  let sessions = list(&tokens[0]).await;
+  let laptop = sessions
+    .iter()
+    .find(|s| s.user_agent.as_deref() == Some("laptop"))
+    .unwrap();
+  let phone = sessions
+    .iter()
+    .find(|s| s.user_agent.as_deref() == Some("phone"))
+    .unwrap();
+  assert!(laptop.current);
+  assert!(!phone.current);
+  assert_eq!(phone.client_ip.as_deref(), Some("127.0.0.1"));
+
+  // Other users cannot revoke the session.
+  let other = User::from_unverified(Uuid::now_v7(), None, None);
+  assert!(matches!(
+    sessions::revoke_session_handler(State(state.clone()), Path(phone.id), other).await,
+    Err(AuthError::NotFound)
+  ));
+
+  sessions::revoke_session_handler(State(state.clone()), Path(phone.id), user.clone())
+    .await
+    .unwrap();
+
+  let sessions = list(&tokens[0]).await;
+  assert!(sessions.iter().all(|s| s.id != phone.id));
+  assert!(sessions.iter().any(|s| s.id == laptop.id));
+
+  // The revoked session can no longer be refreshed.
+  assert!(
+    reauth_with_refresh_token(&state, tokens[1].refresh_token.clone())
+      .await
+      .is_err()
+  );
+}
+
 async fn session_exists(state: &AppState, user_id: Uuid) -> bool {
   return state
     .session_conn()
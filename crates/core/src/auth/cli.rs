@@ -7,7 +7,7 @@ use uuid::Uuid;
 use crate::DataDir;
 use crate::auth::AuthError;
 use crate::auth::password::hash_password;
-use crate::auth::tokens::mint_new_tokens;
+use crate::auth::tokens::{SessionMetadata, mint_new_tokens};
 use crate::auth::user::DbUser;
 use crate::auth::util::{
   get_user_by_email, get_user_by_id, validate_and_normalize_email_address,
@@ -171,7 +171,12 @@ pub async fn mint_auth_token(
   session_conn: &trailbase_sqlite::Connection,
   user: UserReference,
 ) -> Result<String, AuthError> {
-  let jwt = crate::api::JwtHelper::init_from_path(data_dir)
+  let algorithm = crate::config::maybe_load_config_textproto_unverified(data_dir)
+    .map_err(|err| AuthError::FailedDependency(err.into()))?
+    .map_or_else(Default::default, |config| {
+      config.auth.jwt_algorithm().into()
+    });
+  let jwt = crate::api::JwtHelper::init_from_path(data_dir, algorithm)
     .await
     .map_err(|err| AuthError::FailedDependency(err.into()))?;
   let db_user = user.lookup_user(user_conn).await?;
@@ -179,7 +184,15 @@ pub async fn mint_auth_token(
   // NOTE: we just discard the refresh token.
   let auth_token_ttl = chrono::Duration::hours(12);
   let refresh_token_ttl = chrono::Duration::hours(12);
-  let tokens = mint_new_tokens(session_conn, &db_user, &auth_token_ttl, &refresh_token_ttl).await?;
+  let tokens = mint_new_tokens(
+    user_conn,
+    session_conn,
+    &db_user,
+    &SessionMetadata::default(),
+    &auth_token_ttl,
+    &refresh_token_ttl,
+  )
+  .await?;
 
   let auth_token = jwt
     .encode(&tokens.auth_token_claims)
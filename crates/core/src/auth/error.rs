@@ -3,6 +3,8 @@ use axum::http::{StatusCode, header::CONTENT_TYPE};
 use axum::response::{IntoResponse, Response};
 use thiserror::Error;
 
+use crate::logging::with_error_fields;
+
 #[derive(Debug, Error)]
 pub enum AuthError {
   // Unauthorized means: "not authenticated".
@@ -59,8 +61,20 @@ impl From<trailbase_sqlite::Error> for AuthError {
   }
 }
 
+impl From<trailbase_sqlite::from_sql::FromSqlError> for AuthError {
+  fn from(err: trailbase_sqlite::from_sql::FromSqlError) -> Self {
+    return Self::Internal(err.into());
+  }
+}
+
 impl IntoResponse for AuthError {
   fn into_response(self) -> Response {
+    // Internal errors aren't exposed to clients in release builds but should still be logged.
+    let error = match self {
+      Self::Internal(ref err) => Some(err.to_string()),
+      _ => None,
+    };
+
     let (status, body) = match self {
       Self::Unauthorized => (StatusCode::UNAUTHORIZED, None),
       Self::Forbidden => (StatusCode::FORBIDDEN, None),
@@ -80,18 +94,23 @@ impl IntoResponse for AuthError {
       Self::Internal(_err) => (StatusCode::INTERNAL_SERVER_ERROR, None),
     };
 
-    if let Some(body) = body {
-      return Response::builder()
+    let response = if let Some(body) = body {
+      Response::builder()
         .status(status)
         .header(CONTENT_TYPE, "text/plain")
         .body(Body::new(body))
-        .unwrap_or_default();
-    }
+        .unwrap_or_default()
+    } else {
+      Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap_or_default()
+    };
 
-    return Response::builder()
-      .status(status)
-      .body(Body::empty())
-      .unwrap_or_default();
+    return match error {
+      Some(err) => with_error_fields(response, err),
+      None => response,
+    };
   }
 }
 
@@ -1,11 +1,14 @@
 use crate::auth::user::DbUser;
+use crate::config::proto;
 use crate::rand::random_alphanumeric;
 use crate::util::{id_to_b64, uuid_to_b64};
 use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
 use ed25519_dalek::pkcs8::{EncodePrivateKey, EncodePublicKey};
 use ed25519_dalek::{SigningKey, VerifyingKey};
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, errors::Error as JwtError};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 use tokio::fs;
@@ -23,6 +26,8 @@ pub enum JwtHelperError {
   PKCS8(#[from] ed25519_dalek::pkcs8::Error),
   #[error("PKCS8 SPKI error: {0}")]
   PKCS8Spki(#[from] ed25519_dalek::pkcs8::spki::Error),
+  #[error("RSA error: {0}")]
+  Rsa(#[from] rsa::Error),
 }
 
 #[repr(u8)]
@@ -34,6 +39,7 @@ enum TokenType {
   ResetPassword,
   ChangeEmail,
   VerifyEmail,
+  MagicLink,
 }
 
 /// The actual "AuthToken" used for signed-in users.
@@ -69,13 +75,27 @@ pub struct AuthTokenClaims {
   /// Optional username.
   pub username: Option<String>,
 
+  /// Names of the roles assigned to the user at the time of minting.
+  #[serde(default)]
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub roles: Vec<String>,
+
+  /// Tenant the user is assigned to in multi-tenant mode.
+  #[serde(default)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tenant: Option<String>,
+
   /// CSRF random token. Requiring that the client echos this random token back on a non-cookie,
   /// non-auto-attach channel can be used to protect from CSRF.
   pub csrf_token: String,
 }
 
 impl AuthTokenClaims {
-  pub(crate) fn new(db_user: &DbUser, auth_token_ttl: &chrono::Duration) -> Self {
+  pub(crate) fn new(
+    db_user: &DbUser,
+    roles: Vec<String>,
+    auth_token_ttl: &chrono::Duration,
+  ) -> Self {
     assert!(db_user.email.is_none() || db_user.verified);
 
     let now = chrono::Utc::now();
@@ -89,6 +109,8 @@ impl AuthTokenClaims {
       provider: db_user.provider_id as u8,
       email: db_user.email.clone(),
       username: db_user.username.clone(),
+      roles,
+      tenant: None,
       csrf_token: random_alphanumeric(20),
     };
   }
@@ -221,6 +243,10 @@ pub struct EmailChangeTokenClaims {
 
   pub old_email: String,
   pub new_email: String,
+
+  /// Whether the token was sent to and thus confirms the old rather than the new address.
+  #[serde(default)]
+  pub confirms_old_email: bool,
 }
 
 impl EmailChangeTokenClaims {
@@ -228,6 +254,7 @@ impl EmailChangeTokenClaims {
     user_id: &uuid::Uuid,
     old_email: String,
     new_email: String,
+    confirms_old_email: bool,
     expires_in: chrono::Duration,
   ) -> Self {
     let now = chrono::Utc::now();
@@ -238,6 +265,7 @@ impl EmailChangeTokenClaims {
       r#type: TokenType::ChangeEmail as u8,
       old_email,
       new_email,
+      confirms_old_email,
     };
   }
 
@@ -248,68 +276,217 @@ impl EmailChangeTokenClaims {
   }
 }
 
+// Magic link token for password-less login.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MagicLinkTokenClaims {
+  /// Url-safe Base64 encoded id of the current user.
+  pub sub: String,
+  /// Expiration timestamp
+  pub exp: i64,
+
+  // Token type.
+  pub r#type: u8,
+
+  pub email: String,
+
+  /// Random nonce, which is consumed on login to make the link single-use.
+  pub nonce: String,
+}
+
+impl MagicLinkTokenClaims {
+  pub fn new(
+    user_id: &uuid::Uuid,
+    email: String,
+    nonce: String,
+    expires_in: chrono::Duration,
+  ) -> Self {
+    let now = chrono::Utc::now();
+
+    return Self {
+      sub: uuid_to_b64(user_id),
+      exp: (now + expires_in).timestamp(),
+      r#type: TokenType::MagicLink as u8,
+      email,
+      nonce,
+    };
+  }
+
+  pub fn decode(jwt: &JwtHelper, token: &str) -> Result<Self, JwtError> {
+    let claims = jwt.decode::<Self>(token)?;
+    if claims.r#type != TokenType::MagicLink as u8 {
+      return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
+    }
+    return Ok(claims);
+  }
+}
+
+/// Signature algorithm used for minting and validating JWTs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+  /// Ed25519 signatures.
+  #[default]
+  EdDSA,
+  /// RSASSA-PKCS1-v1_5 using SHA-256, e.g. for verifiers lacking Ed25519 support.
+  RS256,
+}
+
+impl JwtAlgorithm {
+  /// Names of the private and public key files. Keys are stored separately per algorithm, such
+  /// that switching back and forth doesn't discard existing keys.
+  fn key_files(self) -> (&'static str, &'static str) {
+    return match self {
+      Self::EdDSA => (PRIVATE_KEY_FILE, PUBLIC_KEY_FILE),
+      Self::RS256 => (RSA_PRIVATE_KEY_FILE, RSA_PUBLIC_KEY_FILE),
+    };
+  }
+}
+
+impl From<proto::JwtAlgorithm> for JwtAlgorithm {
+  fn from(algorithm: proto::JwtAlgorithm) -> Self {
+    return match algorithm {
+      proto::JwtAlgorithm::Undefined | proto::JwtAlgorithm::Eddsa => Self::EdDSA,
+      proto::JwtAlgorithm::Rs256 => Self::RS256,
+    };
+  }
+}
+
+impl From<JwtAlgorithm> for jsonwebtoken::Algorithm {
+  fn from(algorithm: JwtAlgorithm) -> Self {
+    return match algorithm {
+      JwtAlgorithm::EdDSA => jsonwebtoken::Algorithm::EdDSA,
+      JwtAlgorithm::RS256 => jsonwebtoken::Algorithm::RS256,
+    };
+  }
+}
+
+/// Validates tokens offline given only the public key.
+///
+/// Intended for downstream services, e.g. Rust services embedding this crate or sharing its
+/// user base, to authenticate requests without a round-trip to the auth APIs. The public key can
+/// be found in `<data_dir>/secrets/keys` or fetched via the admin API.
+#[derive(Clone)]
+pub struct TokenVerifier {
+  validation: Validation,
+  decoding_key: DecodingKey,
+}
+
+impl TokenVerifier {
+  pub fn new(algorithm: JwtAlgorithm, public_key_pem: &[u8]) -> Result<Self, JwtHelperError> {
+    let decoding_key = match algorithm {
+      JwtAlgorithm::EdDSA => DecodingKey::from_ed_pem(public_key_pem)?,
+      JwtAlgorithm::RS256 => DecodingKey::from_rsa_pem(public_key_pem)?,
+    };
+
+    return Ok(Self {
+      validation: Validation::new(algorithm.into()),
+      decoding_key,
+    });
+  }
+
+  /// Validates signature and expiration of an auth token and returns its claims.
+  pub fn verify_auth_token(&self, auth_token: &str) -> Result<AuthTokenClaims, JwtError> {
+    let claims = self.decode::<AuthTokenClaims>(auth_token)?;
+    if claims.r#type != TokenType::Auth as u8 {
+      return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
+    }
+    return Ok(claims);
+  }
+
+  pub fn decode<T: DeserializeOwned + Clone>(&self, token: &str) -> Result<T, JwtError> {
+    // Note: we don't need to expose the token headers.
+    return jsonwebtoken::decode::<T>(token, &self.decoding_key, &self.validation)
+      .map(|data| data.claims);
+  }
+}
+
 pub struct JwtHelper {
   header: Header,
-  validation: Validation,
 
   // The private key used for minting new JWTs.
   encoding_key: EncodingKey,
 
   // The public key used for validating provided JWTs.
-  decoding_key: DecodingKey,
+  verifier: TokenVerifier,
   public_key: String,
+
+  // Digest of the private key, from which auxiliary keys are derived, see [JwtHelper::derive_key].
+  key_seed: [u8; 32],
 }
 
 impl JwtHelper {
-  pub fn new(private_key: Vec<u8>, public_key: Vec<u8>) -> Result<Self, JwtHelperError> {
+  pub fn new(
+    algorithm: JwtAlgorithm,
+    private_key: Vec<u8>,
+    public_key: Vec<u8>,
+  ) -> Result<Self, JwtHelperError> {
+    let encoding_key = match algorithm {
+      JwtAlgorithm::EdDSA => EncodingKey::from_ed_pem(&private_key)?,
+      JwtAlgorithm::RS256 => EncodingKey::from_rsa_pem(&private_key)?,
+    };
+
     return Ok(JwtHelper {
-      header: Header::new(jsonwebtoken::Algorithm::EdDSA),
-      validation: Validation::new(jsonwebtoken::Algorithm::EdDSA),
-      encoding_key: EncodingKey::from_ed_pem(&private_key)?,
-      decoding_key: DecodingKey::from_ed_pem(&public_key)?,
+      header: Header::new(algorithm.into()),
+      encoding_key,
+      verifier: TokenVerifier::new(algorithm, &public_key)?,
       public_key: String::from_utf8_lossy(&public_key).to_string(),
+      key_seed: Sha256::digest(&private_key).into(),
     });
   }
 
-  pub async fn init_from_path(data_dir: &DataDir) -> Result<Self, JwtHelperError> {
+  pub async fn init_from_path(
+    data_dir: &DataDir,
+    algorithm: JwtAlgorithm,
+  ) -> Result<Self, JwtHelperError> {
     let key_path = data_dir.key_path();
+    let (private_key_file, public_key_file) = algorithm.key_files();
 
-    async fn open_key_files(key_path: &Path) -> std::io::Result<(fs::File, fs::File)> {
+    let open_key_files = async || -> std::io::Result<(fs::File, fs::File)> {
       Ok((
-        fs::File::open(key_path.join(PRIVATE_KEY_FILE)).await?,
-        fs::File::open(key_path.join(PUBLIC_KEY_FILE)).await?,
+        fs::File::open(key_path.join(private_key_file)).await?,
+        fs::File::open(key_path.join(public_key_file)).await?,
       ))
-    }
+    };
 
-    let (private_key, public_key) = match open_key_files(&key_path).await {
+    let (private_key, public_key) = match open_key_files().await {
       Ok((priv_key_file, pub_key_file)) => (
         read_file(priv_key_file).await?,
         read_file(pub_key_file).await?,
       ),
       Err(err) => match err.kind() {
-        std::io::ErrorKind::NotFound => write_new_pem_keys(&key_path).await?,
+        std::io::ErrorKind::NotFound => write_new_pem_keys(&key_path, algorithm).await?,
         _ => {
           return Err(err.into());
         }
       },
     };
 
-    return Self::new(private_key, public_key);
+    return Self::new(algorithm, private_key, public_key);
   }
 
   pub fn public_key(&self) -> String {
     return self.public_key.clone();
   }
 
+  /// Returns a verifier for validating tokens minted by this helper.
+  pub fn verifier(&self) -> TokenVerifier {
+    return self.verifier.clone();
+  }
+
   pub fn decode<T: DeserializeOwned + Clone>(&self, token: &str) -> Result<T, JwtError> {
-    // Note: we don't need to expose the token headers.
-    return jsonwebtoken::decode::<T>(token, &self.decoding_key, &self.validation)
-      .map(|data| data.claims);
+    return self.verifier.decode(token);
   }
 
   pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, JwtError> {
     return jsonwebtoken::encode::<T>(&self.header, claims, &self.encoding_key);
   }
+
+  /// Derives a symmetric key for the given purpose, e.g. signing file URLs, from the persisted
+  /// private key. Derived keys are thus stable across restarts and distinct per purpose.
+  pub(crate) fn derive_key(&self, purpose: &str) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&self.key_seed).expect("any key length");
+    mac.update(purpose.as_bytes());
+    return mac.finalize().into_bytes().into();
+  }
 }
 
 fn generate_new_key_pair() -> (SigningKey, VerifyingKey) {
@@ -320,15 +497,41 @@ fn generate_new_key_pair() -> (SigningKey, VerifyingKey) {
   return (signing_key, verifying_key);
 }
 
-async fn write_new_pem_keys(key_path: &Path) -> Result<(Vec<u8>, Vec<u8>), JwtHelperError> {
-  let (signing_key, verifying_key) = generate_new_key_pair();
-
+/// Returns new PEM-encoded (private, public) keys.
+fn generate_new_pem_keys(algorithm: JwtAlgorithm) -> Result<(Vec<u8>, Vec<u8>), JwtHelperError> {
   let le = LineEnding::default();
-  let priv_key = signing_key.to_pkcs8_pem(le)?.as_bytes().to_vec();
-  let pub_key = verifying_key.to_public_key_pem(le)?.into_bytes();
 
-  write_new_file(key_path.join(PRIVATE_KEY_FILE), &priv_key).await?;
-  write_new_file(key_path.join(PUBLIC_KEY_FILE), &pub_key).await?;
+  return Ok(match algorithm {
+    JwtAlgorithm::EdDSA => {
+      let (signing_key, verifying_key) = generate_new_key_pair();
+      (
+        signing_key.to_pkcs8_pem(le)?.as_bytes().to_vec(),
+        verifying_key.to_public_key_pem(le)?.into_bytes(),
+      )
+    }
+    JwtAlgorithm::RS256 => {
+      let mut csprng = argon2::password_hash::rand_core::OsRng;
+      let private_key = rsa::RsaPrivateKey::new(&mut csprng, RSA_KEY_BITS)?;
+      (
+        private_key.to_pkcs8_pem(le)?.as_bytes().to_vec(),
+        private_key
+          .to_public_key()
+          .to_public_key_pem(le)?
+          .into_bytes(),
+      )
+    }
+  });
+}
+
+async fn write_new_pem_keys(
+  key_path: &Path,
+  algorithm: JwtAlgorithm,
+) -> Result<(Vec<u8>, Vec<u8>), JwtHelperError> {
+  let (priv_key, pub_key) = generate_new_pem_keys(algorithm)?;
+
+  let (private_key_file, public_key_file) = algorithm.key_files();
+  write_new_file(key_path.join(private_key_file), &priv_key).await?;
+  write_new_file(key_path.join(public_key_file), &pub_key).await?;
 
   Ok((priv_key, pub_key))
 }
@@ -346,21 +549,8 @@ async fn write_new_file(path: PathBuf, bytes: &[u8]) -> std::io::Result<()> {
 
 #[cfg(test)]
 pub(crate) fn test_jwt_helper() -> JwtHelper {
-  let (signing_key, verifying_key) = generate_new_key_pair();
-
-  let private_key = signing_key
-    .to_pkcs8_pem(LineEnding::default())
-    .unwrap()
-    .as_bytes()
-    .to_vec();
-
-  let public_key = verifying_key
-    .to_public_key_pem(LineEnding::default())
-    .unwrap()
-    .as_bytes()
-    .to_vec();
-
-  return JwtHelper::new(private_key, public_key).unwrap();
+  let (private_key, public_key) = generate_new_pem_keys(JwtAlgorithm::EdDSA).unwrap();
+  return JwtHelper::new(JwtAlgorithm::EdDSA, private_key, public_key).unwrap();
 }
 
 #[cfg(test)]
@@ -379,7 +569,11 @@ mod tests {
       ..Default::default()
     };
 
-    let claims = AuthTokenClaims::new(&db_user, &crate::constants::DEFAULT_AUTH_TOKEN_TTL);
+    let claims = AuthTokenClaims::new(
+      &db_user,
+      vec!["editor".to_string()],
+      &crate::constants::DEFAULT_AUTH_TOKEN_TTL,
+    );
     let token = jwt.encode(&claims).unwrap();
 
     assert_eq!(claims, jwt.decode(&token).unwrap());
@@ -400,7 +594,61 @@ mod tests {
     );
     assert!(AuthTokenClaims::from_auth_token(&jwt, &pending_auth_token).is_err())
   }
+
+  #[test]
+  fn test_token_verifier() {
+    let db_user = DbUser {
+      id: uuid::Uuid::new_v4().into_bytes(),
+      email: Some("foo@bar.com".to_string()),
+      verified: true,
+      ..Default::default()
+    };
+    let claims = AuthTokenClaims::new(&db_user, vec![], &crate::constants::DEFAULT_AUTH_TOKEN_TTL);
+
+    for algorithm in [JwtAlgorithm::EdDSA, JwtAlgorithm::RS256] {
+      let (private_key, public_key) = generate_new_pem_keys(algorithm).unwrap();
+      let jwt = JwtHelper::new(algorithm, private_key, public_key.clone()).unwrap();
+      let token = jwt.encode(&claims).unwrap();
+
+      // Downstream services only need the public key.
+      let verifier = TokenVerifier::new(algorithm, &public_key).unwrap();
+      assert_eq!(claims, verifier.verify_auth_token(&token).unwrap());
+
+      // Tokens of other types are rejected.
+      let reset_token = jwt
+        .encode(&PasswordResetTokenClaims::new(
+          "foo@bar.com",
+          chrono::Duration::minutes(5),
+        ))
+        .unwrap();
+      assert!(verifier.verify_auth_token(&reset_token).is_err());
+
+      // As are tokens signed by other keys.
+      let other = test_jwt_helper();
+      assert!(
+        verifier
+          .verify_auth_token(&other.encode(&claims).unwrap())
+          .is_err()
+      );
+    }
+  }
+
+  #[test]
+  fn test_derive_key() {
+    let (private_key, public_key) = generate_new_pem_keys(JwtAlgorithm::EdDSA).unwrap();
+    let jwt = JwtHelper::new(JwtAlgorithm::EdDSA, private_key.clone(), public_key.clone()).unwrap();
+
+    // Stable for the same private key, e.g. across restarts.
+    let reloaded = JwtHelper::new(JwtAlgorithm::EdDSA, private_key, public_key).unwrap();
+    assert_eq!(jwt.derive_key("foo"), reloaded.derive_key("foo"));
+
+    assert_ne!(jwt.derive_key("foo"), jwt.derive_key("bar"));
+    assert_ne!(jwt.derive_key("foo"), test_jwt_helper().derive_key("foo"));
+  }
 }
 
 const PRIVATE_KEY_FILE: &str = "private_key.pem";
 const PUBLIC_KEY_FILE: &str = "public_key.pem";
+const RSA_PRIVATE_KEY_FILE: &str = "rsa_private_key.pem";
+const RSA_PUBLIC_KEY_FILE: &str = "rsa_public_key.pem";
+const RSA_KEY_BITS: usize = 2048;
@@ -0,0 +1,329 @@
+use log::*;
+use mini_moka::sync::Cache;
+use sha2::{Digest, Sha256};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use crate::auth::AuthError;
+
+/// Log target for security-relevant auth events, e.g. to route them to a dedicated sink via
+/// `RUST_LOG=auth_audit=info`. Account identifiers and client IPs are logged as [pseudonym]s.
+pub(crate) const AUDIT_TARGET: &str = "auth_audit";
+
+/// Keyed hash of an account identifier or client IP, which allows correlating log lines w/o
+/// logging PII. The key is random per process, i.e. pseudonyms can't be reversed by hashing
+/// candidate emails.
+fn pseudonym(value: &str) -> String {
+  static KEY: LazyLock<[u8; 32]> = LazyLock::new(rand::random);
+
+  let digest = Sha256::new()
+    .chain_update(KEY.as_slice())
+    .chain_update(value.as_bytes())
+    .finalize();
+  return digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+}
+
+// HACK: Increase limit in tests to avoid limits.
+#[cfg(test)]
+const DEFAULT_MAX_FAILED_ATTEMPTS: u32 = 10;
+
+#[cfg(not(test))]
+const DEFAULT_MAX_FAILED_ATTEMPTS: u32 = 3;
+
+#[derive(Clone, Debug)]
+pub struct LockoutOptions {
+  /// Failed attempts per account before it gets locked.
+  pub max_failed_attempts: u32,
+  /// Failed attempts per client IP before it gets locked. Zero disables per-IP tracking.
+  pub max_failed_attempts_per_ip: u32,
+  /// Duration of the first lockout. Doubles with every subsequent failure.
+  pub lockout: Duration,
+  /// Upper bound for lockouts. Failures are forgotten after this long without new failures.
+  pub max_lockout: Duration,
+}
+
+impl Default for LockoutOptions {
+  fn default() -> Self {
+    return LockoutOptions {
+      max_failed_attempts: DEFAULT_MAX_FAILED_ATTEMPTS,
+      max_failed_attempts_per_ip: 10 * DEFAULT_MAX_FAILED_ATTEMPTS,
+      lockout: Duration::from_secs(60),
+      max_lockout: Duration::from_secs(3600),
+    };
+  }
+}
+
+#[derive(Clone, Debug)]
+struct FailedAttempts {
+  failures: u32,
+  last_failure: Instant,
+}
+
+impl FailedAttempts {
+  /// Returns the time until which further attempts are rejected, if any.
+  fn locked_until(&self, threshold: u32, opts: &LockoutOptions) -> Option<Instant> {
+    if threshold == 0 || self.failures < threshold {
+      return None;
+    }
+
+    // Exponential backoff: lockout * 2^(failures - threshold), capped at max_lockout.
+    let exponent = (self.failures - threshold).min(31);
+    let delay = opts
+      .lockout
+      .saturating_mul(1 << exponent)
+      .min(opts.max_lockout);
+    return Some(self.last_failure + delay);
+  }
+
+  fn is_stale(&self, now: Instant, opts: &LockoutOptions) -> bool {
+    return now.saturating_duration_since(self.last_failure) > opts.max_lockout;
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum LockoutKey {
+  Account(String),
+  Ip(String),
+}
+
+impl std::fmt::Display for LockoutKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return match self {
+      Self::Account(account) => write!(f, "account {}", pseudonym(account)),
+      Self::Ip(ip) => write!(f, "IP {}", pseudonym(ip)),
+    };
+  }
+}
+
+struct LoginLockout {
+  attempts: Cache<LockoutKey, FailedAttempts>,
+}
+
+impl LoginLockout {
+  fn new() -> Self {
+    return Self {
+      attempts: Cache::builder()
+        .time_to_idle(Duration::from_secs(24 * 3600))
+        .max_capacity(16 * 1024)
+        .build(),
+    };
+  }
+
+  fn get(&self, key: &LockoutKey, now: Instant, opts: &LockoutOptions) -> Option<FailedAttempts> {
+    return self
+      .attempts
+      .get(key)
+      .filter(|attempts| !attempts.is_stale(now, opts));
+  }
+
+  fn check(
+    &self,
+    account: &str,
+    client_ip: Option<&str>,
+    now: Instant,
+    opts: &LockoutOptions,
+  ) -> Result<(), AuthError> {
+    let mut keys = vec![(
+      LockoutKey::Account(account.to_string()),
+      opts.max_failed_attempts,
+    )];
+    if let Some(ip) = client_ip {
+      keys.push((
+        LockoutKey::Ip(ip.to_string()),
+        opts.max_failed_attempts_per_ip,
+      ));
+    }
+
+    for (key, threshold) in keys {
+      if let Some(attempts) = self.get(&key, now, opts)
+        && let Some(until) = attempts.locked_until(threshold, opts)
+        && now < until
+      {
+        warn!(
+          target: AUDIT_TARGET,
+          "Rejected login for account {account} from IP {ip}: {key} locked for another {secs}s",
+          account = pseudonym(account),
+          ip = client_ip.map_or_else(|| "unknown".to_string(), pseudonym),
+          secs = (until - now).as_secs(),
+        );
+        return Err(AuthError::TooManyRequests);
+      }
+    }
+
+    return Ok(());
+  }
+
+  fn record_failure(
+    &self,
+    account: &str,
+    client_ip: Option<&str>,
+    now: Instant,
+    opts: &LockoutOptions,
+  ) {
+    info!(
+      target: AUDIT_TARGET,
+      "Failed login for account {account} from IP {ip}",
+      account = pseudonym(account),
+      ip = client_ip.map_or_else(|| "unknown".to_string(), pseudonym),
+    );
+
+    let mut keys = vec![(
+      LockoutKey::Account(account.to_string()),
+      opts.max_failed_attempts,
+    )];
+    if let Some(ip) = client_ip
+      && opts.max_failed_attempts_per_ip > 0
+    {
+      keys.push((
+        LockoutKey::Ip(ip.to_string()),
+        opts.max_failed_attempts_per_ip,
+      ));
+    }
+
+    for (key, threshold) in keys {
+      let attempts = FailedAttempts {
+        failures: self
+          .get(&key, now, opts)
+          .map_or(1, |attempts| attempts.failures.saturating_add(1)),
+        last_failure: now,
+      };
+
+      if let Some(until) = attempts.locked_until(threshold, opts) {
+        warn!(
+          target: AUDIT_TARGET,
+          "Locked {key} for {secs}s after {failures} failed login attempts",
+          secs = (until - now).as_secs(),
+          failures = attempts.failures,
+        );
+      }
+
+      self.attempts.insert(key, attempts);
+    }
+  }
+
+  /// Successful logins reset the account's failures but not the IP's, since otherwise attackers
+  /// could reset their budget by logging into an account of their own.
+  fn record_success(&self, account: &str) {
+    self
+      .attempts
+      .invalidate(&LockoutKey::Account(account.to_string()));
+  }
+}
+
+static LOCKOUT: LazyLock<LoginLockout> = LazyLock::new(LoginLockout::new);
+
+/// Rejects login attempts for accounts or client IPs, which are currently locked out.
+pub(crate) fn check_lockout(
+  account: &str,
+  client_ip: Option<&str>,
+  opts: &LockoutOptions,
+) -> Result<(), AuthError> {
+  return LOCKOUT.check(account, client_ip, Instant::now(), opts);
+}
+
+pub(crate) fn record_failed_login(account: &str, client_ip: Option<&str>, opts: &LockoutOptions) {
+  LOCKOUT.record_failure(account, client_ip, Instant::now(), opts);
+}
+
+pub(crate) fn record_successful_login(account: &str) {
+  LOCKOUT.record_success(account);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pseudonym() {
+    let email = "alice@example.com";
+    assert_eq!(pseudonym(email), pseudonym(email));
+    assert_ne!(pseudonym(email), pseudonym("bob@example.com"));
+    assert_eq!(pseudonym(email).len(), 16);
+
+    let key = LockoutKey::Account(email.to_string()).to_string();
+    assert!(!key.contains("alice"), "{key}");
+  }
+
+  #[test]
+  fn test_exponential_backoff() {
+    let lockout = LoginLockout::new();
+    let opts = LockoutOptions {
+      max_failed_attempts: 2,
+      max_failed_attempts_per_ip: 0,
+      lockout: Duration::from_secs(10),
+      max_lockout: Duration::from_secs(30),
+    };
+    let start = Instant::now();
+
+    lockout.record_failure("foo", None, start, &opts);
+    assert!(lockout.check("foo", None, start, &opts).is_ok());
+
+    // Second failure locks the account for 10s.
+    lockout.record_failure("foo", None, start, &opts);
+    assert!(lockout.check("foo", None, start, &opts).is_err());
+    assert!(lockout.check("bar", None, start, &opts).is_ok());
+
+    let t = start + Duration::from_secs(11);
+    assert!(lockout.check("foo", None, t, &opts).is_ok());
+
+    // Third failure doubles the lockout to 20s.
+    lockout.record_failure("foo", None, t, &opts);
+    assert!(
+      lockout
+        .check("foo", None, t + Duration::from_secs(19), &opts)
+        .is_err()
+    );
+    assert!(
+      lockout
+        .check("foo", None, t + Duration::from_secs(21), &opts)
+        .is_ok()
+    );
+
+    // Capped at max_lockout.
+    let t = t + Duration::from_secs(21);
+    lockout.record_failure("foo", None, t, &opts);
+    assert!(
+      lockout
+        .check("foo", None, t + Duration::from_secs(29), &opts)
+        .is_err()
+    );
+    assert!(
+      lockout
+        .check("foo", None, t + Duration::from_secs(31), &opts)
+        .is_ok()
+    );
+
+    // Failures are forgotten past max_lockout.
+    let t = t + Duration::from_secs(31);
+    lockout.record_failure("foo", None, t, &opts);
+    assert!(lockout.check("foo", None, t, &opts).is_ok());
+
+    lockout.record_failure("foo", None, t, &opts);
+    assert!(lockout.check("foo", None, t, &opts).is_err());
+    lockout.record_success("foo");
+    assert!(lockout.check("foo", None, t, &opts).is_ok());
+  }
+
+  #[test]
+  fn test_ip_lockout() {
+    let lockout = LoginLockout::new();
+    let opts = LockoutOptions {
+      max_failed_attempts: 10,
+      max_failed_attempts_per_ip: 2,
+      ..Default::default()
+    };
+    let now = Instant::now();
+    let ip = Some("1.2.3.4");
+
+    lockout.record_failure("a", ip, now, &opts);
+    lockout.record_failure("b", ip, now, &opts);
+
+    // Different accounts but same IP.
+    assert!(lockout.check("c", ip, now, &opts).is_err());
+    assert!(lockout.check("c", Some("5.6.7.8"), now, &opts).is_ok());
+
+    // Success doesn't reset the IP's failures.
+    lockout.record_success("a");
+    assert!(lockout.check("a", ip, now, &opts).is_err());
+  }
+}
@@ -4,17 +4,22 @@ use axum::{
 };
 use utoipa::OpenApi;
 
+pub mod api_key;
 pub mod cli;
 pub mod jwt;
 pub mod user;
 
 pub(crate) mod api;
+pub(crate) mod lockout;
 pub(crate) mod login_params;
 pub(crate) mod oauth;
 pub(crate) mod options;
 pub(crate) mod password;
+pub(crate) mod profile;
+pub(crate) mod saml;
 pub(crate) mod tokens;
 pub(crate) mod util;
+pub(crate) mod webhooks;
 
 mod error;
 
@@ -49,6 +54,8 @@ use api::*;
     login::login_mfa_handler,
     otp::request_otp_handler,
     otp::login_otp_handler,
+    magic_link::request_magic_link_handler,
+    magic_link::login_magic_link_handler,
     totp::register_totp_request_handler,
     totp::register_totp_confirm_handler,
     totp::unregister_totp_handler,
@@ -56,6 +63,10 @@ use api::*;
     status::login_status_handler,
     logout::logout_handler,
     logout::post_logout_handler,
+    sessions::list_sessions_handler,
+    sessions::revoke_session_handler,
+    me::get_me_handler,
+    me::update_me_handler,
     avatar::get_avatar_handler,
     avatar::create_avatar_handler,
     avatar::delete_avatar_handler,
@@ -63,6 +74,7 @@ use api::*;
   ),
   nest(
      (path = "/oauth", api = oauth::OAuthApi),
+     (path = "/saml", api = saml::SamlApi),
   ),
 )]
 pub(super) struct AuthApi;
@@ -176,6 +188,20 @@ pub(super) fn router(config: &Config) -> Router<crate::AppState> {
       &format!("/{AUTH_API_PATH}/logout"),
       post(api::logout::post_logout_handler),
     )
+    // Sessions: list and revoke the current user's signed-in devices.
+    .route(
+      &format!("/{AUTH_API_PATH}/sessions"),
+      get(api::sessions::list_sessions_handler),
+    )
+    .route(
+      &format!("/{AUTH_API_PATH}/sessions/{{session_id}}"),
+      delete(api::sessions::revoke_session_handler),
+    )
+    // Current user including custom profile fields.
+    .route(
+      &format!("/{AUTH_API_PATH}/me"),
+      get(api::me::get_me_handler).patch(api::me::update_me_handler),
+    )
     // Get a user's avatar.
     .route(
       &format!("/{AUTH_API_PATH}/avatar/{{b64_user_id}}"),
@@ -195,7 +221,9 @@ pub(super) fn router(config: &Config) -> Router<crate::AppState> {
       delete(api::delete::delete_handler),
     )
     // OAuth flows: list providers, login+callback
-    .nest(&format!("/{AUTH_API_PATH}/oauth"), oauth::oauth_router());
+    .nest(&format!("/{AUTH_API_PATH}/oauth"), oauth::oauth_router())
+    // SAML flows: SP metadata, login+assertion consumer service
+    .nest(&format!("/{AUTH_API_PATH}/saml"), saml::saml_router());
 
   if config.auth.enable_anonymous_signin() {
     router = router
@@ -203,6 +231,10 @@ pub(super) fn router(config: &Config) -> Router<crate::AppState> {
         &format!("/{AUTH_API_PATH}/login_anonymous"),
         post(api::login_anonymous::login_anonymous_user_handler),
       )
+      .route(
+        &format!("/{AUTH_API_PATH}/anonymous"),
+        post(api::login_anonymous::login_anonymous_user_handler),
+      )
       .route(
         &format!("/{AUTH_API_PATH}/promote_anonymous"),
         post(api::promote_anonymous::promote_anonymous_user_handler),
@@ -222,6 +254,19 @@ pub(super) fn router(config: &Config) -> Router<crate::AppState> {
       );
   }
 
+  if config.auth.enable_magic_link_signin() {
+    router = router
+      // Magic link flow
+      .route(
+        &format!("/{AUTH_API_PATH}/magic_link"),
+        post(api::magic_link::request_magic_link_handler),
+      )
+      .route(
+        &format!("/{AUTH_API_PATH}/magic_link/login/{{magic_link_token}}"),
+        get(api::magic_link::login_magic_link_handler),
+      );
+  }
+
   return router;
 }
 
@@ -7,23 +7,26 @@ use serde::Deserialize;
 use tower_cookies::Cookies;
 use trailbase_sqlite::{named_params, params};
 use utoipa::IntoParams;
+use uuid::Uuid;
 
 use crate::AppState;
 use crate::auth::AuthError;
 use crate::auth::oauth::OAuthUser;
 use crate::auth::oauth::providers::OAuthProviderType;
 use crate::auth::oauth::state::{OAuthStateClaims, ResponseType};
-use crate::auth::tokens::{FreshTokens, mint_new_tokens};
+use crate::auth::tokens::{FreshTokens, SessionMetadata, mint_new_tokens};
 use crate::auth::user::DbUser;
 use crate::auth::util::{
   new_cookie, remove_cookie, validate_and_normalize_username, validate_redirect,
 };
+use crate::auth::webhooks::emit_user_created;
 use crate::config::proto::{OAuthProviderId, UserIdentifier};
 use crate::constants::{
   AUTHORIZATION_CODE_TABLE, COOKIE_AUTH_TOKEN, COOKIE_OAUTH_STATE, COOKIE_REFRESH_TOKEN,
   DEFAULT_AUTHORIZATION_CODE_TTL, USER_TABLE, VERIFICATION_CODE_LENGTH,
 };
 use crate::rand::random_alphanumeric;
+use crate::util::b64_to_uuid;
 
 #[derive(Debug, Deserialize, IntoParams)]
 pub struct AuthQuery {
@@ -47,6 +50,7 @@ pub(crate) async fn callback_from_external_auth_provider(
   Path(provider): Path<String>,
   Query(query): Query<AuthQuery>,
   cookies: Cookies,
+  session: SessionMetadata,
 ) -> Result<Response, AuthError> {
   let auth_options = state.auth_options();
   let Some(provider) = auth_options.lookup_oauth_provider(&provider) else {
@@ -60,6 +64,7 @@ pub(crate) async fn callback_from_external_auth_provider(
     user_pkce_code_challenge,
     response_type,
     redirect_uri,
+    anonymous_user_id,
     exp: _,
   } = state
     .jwt()
@@ -82,6 +87,11 @@ pub(crate) async fn callback_from_external_auth_provider(
   // NOTE: This was already validated in the login-handler, we're just pedantic.
   let redirect_uri = validate_redirect(&state, redirect_uri)?;
 
+  let anonymous_user_id = anonymous_user_id
+    .map(|id| b64_to_uuid(&id))
+    .transpose()
+    .map_err(|_err| AuthError::BadRequest("invalid state"))?;
+
   return match response_type {
     Some(ResponseType::Code) => {
       callback_from_oauth_provider_using_auth_code_flow(
@@ -92,6 +102,7 @@ pub(crate) async fn callback_from_external_auth_provider(
         query.code,
         pkce_code_verifier,
         user_pkce_code_challenge,
+        anonymous_user_id,
       )
       .await
     }
@@ -99,10 +110,12 @@ pub(crate) async fn callback_from_external_auth_provider(
       callback_from_oauth_provider_setting_token_cookies(
         &state,
         &cookies,
+        &session,
         provider,
         redirect_uri,
         query.code,
         pkce_code_verifier,
+        anonymous_user_id,
       )
       .await
     }
@@ -113,12 +126,21 @@ pub(crate) async fn callback_from_external_auth_provider(
 async fn callback_from_oauth_provider_setting_token_cookies(
   state: &AppState,
   cookies: &Cookies,
+  session: &SessionMetadata,
   provider: &OAuthProviderType,
   redirect: Option<String>,
   auth_code: String,
   server_pkce_code_verifier: String,
+  anonymous_user_id: Option<Uuid>,
 ) -> Result<Response, AuthError> {
-  let db_user = get_or_create_user(state, provider, auth_code, server_pkce_code_verifier).await?;
+  let db_user = get_or_create_user(
+    state,
+    provider,
+    auth_code,
+    server_pkce_code_verifier,
+    anonymous_user_id,
+  )
+  .await?;
 
   // Mint user token and start a session.
   let (auth_token_ttl, refresh_token_ttl) = state.access_config(|c| c.auth.token_ttls());
@@ -128,8 +150,10 @@ async fn callback_from_oauth_provider_setting_token_cookies(
     refresh_token,
     ..
   } = mint_new_tokens(
+    state.user_conn(),
     state.session_conn(),
     &db_user,
+    session,
     &auth_token_ttl,
     &refresh_token_ttl,
   )
@@ -183,6 +207,7 @@ async fn callback_from_oauth_provider_using_auth_code_flow(
   auth_code: String,
   server_pkce_code_verifier: String,
   user_pkce_code_challenge: Option<String>,
+  anonymous_user_id: Option<Uuid>,
 ) -> Result<Response, AuthError> {
   let (Some(redirect), Some(user_pkce_code_challenge)) = (redirect, user_pkce_code_challenge)
   else {
@@ -192,7 +217,14 @@ async fn callback_from_oauth_provider_using_auth_code_flow(
     return Err(AuthError::BadRequest("invalid state"));
   };
 
-  let db_user = get_or_create_user(state, provider, auth_code, server_pkce_code_verifier).await?;
+  let db_user = get_or_create_user(
+    state,
+    provider,
+    auth_code,
+    server_pkce_code_verifier,
+    anonymous_user_id,
+  )
+  .await?;
 
   // For the auth_code flow we generate a random code.
   let authorization_code = random_alphanumeric(VERIFICATION_CODE_LENGTH);
@@ -237,13 +269,27 @@ async fn get_or_create_user(
   provider: &OAuthProviderType,
   auth_code: String,
   server_pkce_code_verifier: String,
+  anonymous_user_id: Option<Uuid>,
 ) -> Result<DbUser, AuthError> {
+  provider.discover().await?;
+
   let token_response = provider
     .get_token(state, auth_code, server_pkce_code_verifier)
     .await?;
 
   // Call provider's USER_INFO endpoint with the tokens acquired above.
   let oauth_user = provider.get_user(&token_response).await?;
+
+  return get_or_create_external_user(state, oauth_user, anonymous_user_id).await;
+}
+
+/// Looks up the local user for an external identity, e.g. from an OAuth or SAML provider, or
+/// creates one if needed.
+pub(crate) async fn get_or_create_external_user(
+  state: &AppState,
+  oauth_user: OAuthUser,
+  anonymous_user_id: Option<Uuid>,
+) -> Result<DbUser, AuthError> {
   if !oauth_user.verified {
     return Err(AuthError::BadRequest("External OAuth user unverified"));
   }
@@ -268,9 +314,24 @@ async fn get_or_create_user(
     .and_then(|ui| ui.try_into().ok())
     .unwrap_or(UserIdentifier::Undefined);
 
-  // Otherwise, create a new user and return that.
-  let db_user =
-    create_user_for_external_provider(state.user_conn(), user_identifier, oauth_user).await?;
+  // Otherwise, link a signed-in anonymous user, if any, or create a new user and return that.
+  let db_user = match anonymous_user_id {
+    Some(user_id) => {
+      link_anonymous_user_to_external_provider(
+        state.user_conn(),
+        &user_id,
+        user_identifier,
+        oauth_user,
+      )
+      .await?
+    }
+    None => {
+      let db_user =
+        create_user_for_external_provider(state.user_conn(), user_identifier, oauth_user).await?;
+      emit_user_created(state, &db_user, "external");
+      db_user
+    }
+  };
 
   // This should never happen. We only ever create a new local user here for verified users above.
   if !db_user.verified {
@@ -300,6 +361,98 @@ async fn create_user_for_external_provider(
     return Err(AuthError::Unauthorized);
   }
 
+  let username = derive_username(conn, user_identifier, username).await?;
+
+  const QUERY: &str = formatcp!(
+    "\
+      INSERT INTO \"{USER_TABLE}\" ( \
+        provider_id, provider_user_id, verified, email, username, provider_avatar_url \
+      ) VALUES ( \
+        :provider_id, :provider_user_id, :verified, :email, :username, :avatar \
+      ) RETURNING * \
+    "
+  );
+
+  let db_user: DbUser = conn
+    .write_query_value(
+      QUERY,
+      named_params! {
+          ":provider_id": provider_id as i64,
+          ":provider_user_id": provider_user_id,
+          ":verified": verified as i64,
+          ":email": email,
+          ":username": username,
+          ":avatar": avatar,
+      },
+    )
+    .await?
+    .ok_or_else(|| AuthError::Internal("insertion issue".into()))?;
+
+  return Ok(db_user);
+}
+
+/// Turns an anonymous user into a regular user of the external provider, preserving the user's id
+/// and thus ownership of any records.
+async fn link_anonymous_user_to_external_provider(
+  conn: &trailbase_sqlite::Connection,
+  user_id: &Uuid,
+  user_identifier: UserIdentifier,
+  user: OAuthUser,
+) -> Result<DbUser, AuthError> {
+  let OAuthUser {
+    provider_user_id,
+    provider_id,
+    email,
+    username,
+    verified,
+    avatar,
+  } = user;
+
+  if !verified {
+    return Err(AuthError::Unauthorized);
+  }
+
+  let username = derive_username(conn, user_identifier, username).await?;
+
+  // NOTE: Re-check anonymity as part of the update to prevent races with concurrent promotions.
+  const QUERY: &str = formatcp!(
+    "\
+      UPDATE \"{USER_TABLE}\" \
+      SET \
+        provider_id = :provider_id, \
+        provider_user_id = :provider_user_id, \
+        verified = :verified, \
+        email = :email, \
+        username = :username, \
+        provider_avatar_url = :avatar \
+      WHERE \
+        id = :user_id AND password_hash IS NULL AND provider_id = 0 \
+      RETURNING * \
+    "
+  );
+
+  return conn
+    .write_query_value::<DbUser>(
+      QUERY,
+      named_params! {
+          ":user_id": user_id.into_bytes().to_vec(),
+          ":provider_id": provider_id as i64,
+          ":provider_user_id": provider_user_id,
+          ":verified": verified as i64,
+          ":email": email,
+          ":username": username,
+          ":avatar": avatar,
+      },
+    )
+    .await?
+    .ok_or(AuthError::Conflict);
+}
+
+async fn derive_username(
+  conn: &trailbase_sqlite::Connection,
+  user_identifier: UserIdentifier,
+  username: Option<String>,
+) -> Result<Option<String>, AuthError> {
   let mut username: Option<String> = match (user_identifier, username) {
     (UserIdentifier::OnlyEmail | UserIdentifier::Undefined, _) => None,
     (
@@ -344,32 +497,7 @@ async fn create_user_for_external_provider(
     debug_assert!(validate_and_normalize_username(username).is_ok());
   }
 
-  const QUERY: &str = formatcp!(
-    "\
-      INSERT INTO \"{USER_TABLE}\" ( \
-        provider_id, provider_user_id, verified, email, username, provider_avatar_url \
-      ) VALUES ( \
-        :provider_id, :provider_user_id, :verified, :email, :username, :avatar \
-      ) RETURNING * \
-    "
-  );
-
-  let db_user: DbUser = conn
-    .write_query_value(
-      QUERY,
-      named_params! {
-          ":provider_id": provider_id as i64,
-          ":provider_user_id": provider_user_id,
-          ":verified": verified as i64,
-          ":email": email,
-          ":username": username,
-          ":avatar": avatar,
-      },
-    )
-    .await?
-    .ok_or_else(|| AuthError::Internal("insertion issue".into()))?;
-
-  return Ok(db_user);
+  return Ok(username);
 }
 
 async fn user_by_provider_id(
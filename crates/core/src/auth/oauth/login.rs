@@ -7,10 +7,10 @@ use oauth2::{CsrfToken, PkceCodeChallenge, Scope};
 use tower_cookies::Cookies;
 
 use crate::AppState;
-use crate::auth::AuthError;
 use crate::auth::login_params::{LoginInputParams, LoginParams, build_and_validate_input_params};
 use crate::auth::oauth::state::{OAuthStateClaims, ResponseType};
-use crate::auth::util::{new_cookie_opts, secure_tls_only};
+use crate::auth::util::{new_cookie_opts, secure_tls_only, user_by_id};
+use crate::auth::{AuthError, User};
 use crate::constants::COOKIE_OAUTH_STATE;
 
 /// Log in via external OAuth provider.
@@ -28,6 +28,7 @@ pub(crate) async fn login_with_external_auth_provider(
   Path(provider): Path<String>,
   Query(login_input_query): Query<LoginInputParams>,
   cookies: Cookies,
+  user: Option<User>,
 ) -> Result<Redirect, AuthError> {
   let auth_options = state.auth_options();
   let Some(provider) = auth_options.lookup_oauth_provider(&provider) else {
@@ -35,6 +36,21 @@ pub(crate) async fn login_with_external_auth_provider(
   };
   let login_params = build_and_validate_input_params(&state, login_input_query)?;
 
+  // Anonymous users signing in get their account linked to the external identity, thus preserving
+  // their user id and ownership of existing records.
+  let anonymous_user_id = match user {
+    Some(user)
+      if user_by_id(&state, &user.uuid)
+        .await
+        .is_ok_and(|u| u.is_anonymous()) =>
+    {
+      Some(user.id)
+    }
+    _ => None,
+  };
+
+  provider.discover().await?;
+
   // Also use PKCE between TrailBase and the external auth provider. Is is independent from PKCE
   // between the client and TrailBase.
   let (server_pkce_code_challenge, server_pkce_code_verifier) =
@@ -61,6 +77,7 @@ pub(crate) async fn login_with_external_auth_provider(
       redirect_uri,
       response_type: None,
       user_pkce_code_challenge: None,
+      anonymous_user_id,
     },
     LoginParams::AuthorizationCodeFlowWithPkce {
       redirect_uri,
@@ -73,6 +90,7 @@ pub(crate) async fn login_with_external_auth_provider(
       user_pkce_code_challenge: Some(pkce_code_challenge),
       response_type: Some(ResponseType::Code),
       redirect_uri: Some(redirect_uri),
+      anonymous_user_id,
     },
   };
 
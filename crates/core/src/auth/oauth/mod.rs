@@ -1,7 +1,7 @@
 pub(crate) mod provider;
 pub(crate) mod providers;
 
-mod callback;
+pub(crate) mod callback;
 mod list_providers;
 mod login;
 mod reqwest_client;
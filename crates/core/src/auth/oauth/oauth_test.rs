@@ -12,6 +12,7 @@ use uuid::Uuid;
 
 use crate::api::AuthTokenClaims;
 use crate::app_state::{AppState, TestStateOptions, test_state};
+use crate::auth::User;
 use crate::auth::api::token::{
   AuthCodeToTokenRequest, TokenResponse as TokenHandlerResponse, auth_code_to_token_handler,
 };
@@ -19,6 +20,7 @@ use crate::auth::login_params::{LoginInputParams, ResponseType};
 use crate::auth::oauth::providers::test::{TestOAuthProvider, TestUser};
 use crate::auth::oauth::state::OAuthStateClaims;
 use crate::auth::oauth::{callback, list_providers, login};
+use crate::auth::tokens::SessionMetadata;
 use crate::auth::user::DbUser;
 use crate::auth::util::derive_pkce_code_challenge;
 use crate::config::proto::{Config, OAuthProviderConfig, OAuthProviderId};
@@ -163,6 +165,7 @@ async fn test_oauth_login_flow_without_pkce() {
       pkce_code_challenge: None,
     }),
     cookies.clone(),
+    None,
   )
   .await
   .unwrap();
@@ -214,6 +217,7 @@ async fn test_oauth_login_flow_without_pkce() {
       code: auth_query.code_challenge.clone(),
     }),
     cookies.clone(),
+    SessionMetadata::default(),
   )
   .await
   .unwrap();
@@ -268,6 +272,7 @@ async fn test_oauth_login_flow_with_pkce() {
       pkce_code_challenge: Some(pkce_code_challenge.as_str().to_string()),
     }),
     cookies.clone(),
+    None,
   )
   .await
   .unwrap();
@@ -319,6 +324,7 @@ async fn test_oauth_login_flow_with_pkce() {
       code: auth_query.code_challenge.clone(),
     }),
     cookies.clone(),
+    SessionMetadata::default(),
   )
   .await
   .unwrap();
@@ -350,6 +356,7 @@ async fn test_oauth_login_flow_with_pkce() {
   // Upgrade to tokens, i.e. complete log-in.
   let Json(token_response): Json<TokenHandlerResponse> = auth_code_to_token_handler(
     State(state.clone()),
+    SessionMetadata::default(),
     Json(AuthCodeToTokenRequest {
       authorization_code: Some(auth_code.as_str().to_string()),
       pkce_code_verifier: Some(pkce_code_verifier.secret().to_string()),
@@ -378,6 +385,75 @@ async fn test_oauth_login_flow_with_pkce() {
   );
 }
 
+#[tokio::test]
+async fn test_oauth_login_links_anonymous_user() {
+  let site_url = "https://bar.org";
+  let (_server, state) = setup_fake_oauth_server(site_url).await;
+
+  let anonymous_user = state
+    .user_conn()
+    .write_query_value::<DbUser>(
+      format!("INSERT INTO {USER_TABLE} (username) VALUES ('anon123') RETURNING *"),
+      (),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(anonymous_user.is_anonymous());
+
+  let cookies = Cookies::default();
+  let external_redirect: Redirect = login::login_with_external_auth_provider(
+    State(state.clone()),
+    Path(TestOAuthProvider::NAME.to_string()),
+    Query(LoginInputParams::default()),
+    cookies.clone(),
+    Some(User::from_unverified(
+      anonymous_user.uuid(),
+      None,
+      anonymous_user.username.as_deref(),
+    )),
+  )
+  .await
+  .unwrap();
+
+  let auth_query: AuthQuery = reqwest::get(&get_redirect_location(external_redirect).unwrap())
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+
+  callback::callback_from_external_auth_provider(
+    State(state.clone()),
+    Path(TestOAuthProvider::NAME.to_string()),
+    Query(callback::AuthQuery {
+      state: auth_query.state.clone(),
+      code: auth_query.code_challenge.clone(),
+    }),
+    cookies.clone(),
+    SessionMetadata::default(),
+  )
+  .await
+  .unwrap();
+
+  // The anonymous user was upgraded in place, i.e. its id is preserved.
+  let db_user = state
+    .user_conn()
+    .read_query_value::<DbUser>(
+      format!("SELECT * FROM {USER_TABLE} WHERE provider_user_id = $1"),
+      (EXTERNAL_USER_ID,),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(anonymous_user.id, db_user.id);
+  assert_eq!(EXTERNAL_USER_EMAIL, db_user.email.as_deref().unwrap());
+  assert!(db_user.verified);
+  assert!(!db_user.is_anonymous());
+
+  assert!(session_exists(&state, db_user.uuid()).await);
+}
+
 fn get_redirect_location<T: IntoResponse>(response: T) -> Option<String> {
   return response
     .into_response()
@@ -115,6 +115,12 @@ pub trait OAuthProvider {
 
   fn oauth_scopes(&self) -> Vec<&'static str>;
 
+  /// Fetches remote provider metadata, e.g. via OpenID Connect discovery, ahead of
+  /// [Self::settings] being called. No-op for providers with static endpoints.
+  async fn discover(&self) -> Result<(), AuthError> {
+    return Ok(());
+  }
+
   async fn get_token(
     &self,
     state: &AppState,
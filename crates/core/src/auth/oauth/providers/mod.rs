@@ -36,13 +36,19 @@ pub(crate) struct OAuthProviderFactory {
 }
 
 pub(crate) fn oauth_providers_static_registry() -> &'static [OAuthProviderFactory] {
-  const N: usize = if cfg!(test) { 11 } else { 10 };
+  const N: usize = if cfg!(test) { 17 } else { 16 };
   static REGISTRY: LazyLock<[OAuthProviderFactory; N]> = LazyLock::new(|| {
     [
       #[cfg(test)]
       test::TestOAuthProvider::factory(),
-      // NOTE: In the future we might want to have more than one OIDC factory.
+      // Generic OpenID Connect providers, e.g. Keycloak, Okta, Entra ID, ... .
       oidc::OidcProvider::factory(0),
+      oidc::OidcProvider::factory(1),
+      oidc::OidcProvider::factory(2),
+      oidc::OidcProvider::factory(3),
+      oidc::OidcProvider::factory(4),
+      oidc::OidcProvider::factory(5),
+      oidc::OidcProvider::factory(6),
       // "Social" OAuth providers.
       apple::AppleOAuthProvider::factory(),
       discord::DiscordOAuthProvider::factory(),
@@ -1,78 +1,159 @@
 use async_trait::async_trait;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
 use oauth2::TokenResponse as _;
-use serde::{Deserialize, Serialize};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use url::Url;
 
 use crate::auth::AuthError;
 use crate::auth::oauth::provider::TokenResponse;
 use crate::auth::oauth::providers::{OAuthProviderError, OAuthProviderFactory};
 use crate::auth::oauth::{OAuthClientSettings, OAuthProvider, OAuthUser};
-use crate::config::proto::{OAuthProviderConfig, OAuthProviderId};
+use crate::config::proto::{OAuthProviderConfig, OAuthProviderId, OidcClaimMapping};
 
-// TODO: Add name/display name and this would already be a generic CustomOAuthProvider.
+/// Number of generic OpenID Connect provider slots, i.e. OIDC0 through OIDC6.
+const NUM_OIDC_PROVIDERS: u64 = 7;
+
+/// How long discovered metadata and keys are cached before being re-fetched.
+const DISCOVERY_TTL: Duration = Duration::from_secs(60 * 60);
+/// Minimum interval between key re-fetches triggered by unknown key ids, e.g. after key rotation.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Generic OpenID Connect provider, e.g. Keycloak, Okta or Entra ID.
+///
+/// Endpoints can either be configured explicitly or discovered from the issuer. In the latter
+/// case, ID tokens are validated against the issuer's JSON Web Key Set (JWKS).
 pub struct OidcProvider {
+  id: OAuthProviderId,
   name: String,
   display_name: String,
   client_id: String,
   client_secret: String,
 
-  auth_url: String,
-  token_url: String,
-  user_api_url: String,
+  auth_url: Option<String>,
+  token_url: Option<String>,
+  user_api_url: Option<String>,
+
+  issuer_url: Option<String>,
+  claim_mapping: OidcClaimMapping,
+
+  discovery: Mutex<Option<Arc<Discovery>>>,
+}
+
+/// Reference: https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata
+#[derive(Clone, Debug, Deserialize)]
+struct ProviderMetadata {
+  issuer: String,
+  authorization_endpoint: String,
+  token_endpoint: String,
+  userinfo_endpoint: Option<String>,
+  jwks_uri: String,
+}
+
+#[derive(Debug)]
+struct Discovery {
+  metadata: ProviderMetadata,
+  jwks: JwkSet,
+  fetched: Instant,
 }
 
 impl OidcProvider {
   pub fn factory(index: u64) -> OAuthProviderFactory {
-    let (id, factory_name, factory_display_name) = match index {
-      0 => (OAuthProviderId::Oidc0, "oidc0", "OpenID Connect"),
-      _ => panic!("Multiple OIDC provider not implemented"),
+    let (id, factory_name) = match index {
+      0 => (OAuthProviderId::Oidc0, "oidc0"),
+      1 => (OAuthProviderId::Oidc1, "oidc1"),
+      2 => (OAuthProviderId::Oidc2, "oidc2"),
+      3 => (OAuthProviderId::Oidc3, "oidc3"),
+      4 => (OAuthProviderId::Oidc4, "oidc4"),
+      5 => (OAuthProviderId::Oidc5, "oidc5"),
+      6 => (OAuthProviderId::Oidc6, "oidc6"),
+      _ => panic!("Only {NUM_OIDC_PROVIDERS} OIDC providers supported"),
     };
+    const FACTORY_DISPLAY_NAME: &str = "OpenID Connect";
 
     OAuthProviderFactory {
       id,
       factory_name,
-      factory_display_name,
-      factory: Box::new(|name: &str, config: &OAuthProviderConfig| {
+      factory_display_name: FACTORY_DISPLAY_NAME,
+      factory: Box::new(move |name: &str, config: &OAuthProviderConfig| {
         // NOTE: Below errors should not trigger, since already checked by config validation.
-        let Some(auth_url) = config.auth_url.clone() else {
-          return Err(OAuthProviderError::Missing("Auth url missing".into()));
-        };
-        let Some(token_url) = config.token_url.clone() else {
-          return Err(OAuthProviderError::Missing("Token url missing".into()));
-        };
-        let Some(user_api_url) = config.user_api_url.clone() else {
-          return Err(OAuthProviderError::Missing("User-API url missing".into()));
-        };
+        if config.issuer_url.is_none() {
+          if config.auth_url.is_none() {
+            return Err(OAuthProviderError::Missing("Auth url missing".into()));
+          }
+          if config.token_url.is_none() {
+            return Err(OAuthProviderError::Missing("Token url missing".into()));
+          }
+          if config.user_api_url.is_none() {
+            return Err(OAuthProviderError::Missing("User-API url missing".into()));
+          }
+        }
 
         Ok(Box::new(OidcProvider {
+          id,
           name: name.to_string(),
           display_name: config
             .display_name
             .as_deref()
-            .unwrap_or(factory_display_name)
+            .unwrap_or(FACTORY_DISPLAY_NAME)
             .to_string(),
           client_id: config.client_id.clone().expect("startup"),
           client_secret: config.client_secret.clone().expect("startup"),
 
-          auth_url,
-          token_url,
-          user_api_url,
+          auth_url: config.auth_url.clone(),
+          token_url: config.token_url.clone(),
+          user_api_url: config.user_api_url.clone(),
+
+          issuer_url: config.issuer_url.clone(),
+          claim_mapping: config.claim_mapping.clone().unwrap_or_default(),
+
+          discovery: Mutex::new(None),
         }))
       }),
     }
   }
-}
 
-// Reference: https://openid.net/specs/openid-connect-core-1_0.html#StandardClaims
-#[derive(Default, Debug, Deserialize, Serialize)]
-pub struct OidcUser {
-  pub sub: String,
-  pub email: String,
-  pub email_verified: Option<bool>,
-
-  pub preferred_username: Option<String>,
-  // pub name: Option<String>,
-  pub picture: Option<String>,
+  fn discovered(&self) -> Option<Arc<Discovery>> {
+    return self.discovery.lock().clone();
+  }
+
+  async fn validate_id_token(
+    &self,
+    discovery: Arc<Discovery>,
+    id_token: &str,
+  ) -> Result<Map<String, Value>, AuthError> {
+    let header = jsonwebtoken::decode_header(id_token)
+      .map_err(|err| AuthError::FailedDependency(err.into()))?;
+
+    let discovery = match header.kid {
+      Some(ref kid)
+        if discovery.jwks.find(kid).is_none()
+          && discovery.fetched.elapsed() > JWKS_REFRESH_INTERVAL =>
+      {
+        // Unknown key id, the provider may have rotated its keys.
+        let jwks = fetch_jwks(&discovery.metadata.jwks_uri).await?;
+        let refreshed = Arc::new(Discovery {
+          metadata: discovery.metadata.clone(),
+          jwks,
+          fetched: Instant::now(),
+        });
+        *self.discovery.lock() = Some(refreshed.clone());
+        refreshed
+      }
+      _ => discovery,
+    };
+
+    return decode_id_token(
+      &discovery.jwks,
+      id_token,
+      &discovery.metadata.issuer,
+      &self.client_id,
+    );
+  }
 }
 
 #[async_trait]
@@ -81,16 +162,30 @@ impl OAuthProvider for OidcProvider {
     return &self.name;
   }
   fn provider(&self) -> OAuthProviderId {
-    OAuthProviderId::Oidc0
+    self.id
   }
   fn display_name(&self) -> &str {
     return &self.display_name;
   }
 
   fn settings(&self) -> Result<OAuthClientSettings, AuthError> {
+    let discovery = self.discovered();
+    let metadata = discovery.as_ref().map(|d| &d.metadata);
+
+    let auth_url = self
+      .auth_url
+      .as_deref()
+      .or(metadata.map(|m| m.authorization_endpoint.as_str()))
+      .ok_or_else(|| AuthError::Internal("OIDC discovery pending".into()))?;
+    let token_url = self
+      .token_url
+      .as_deref()
+      .or(metadata.map(|m| m.token_endpoint.as_str()))
+      .ok_or_else(|| AuthError::Internal("OIDC discovery pending".into()))?;
+
     return Ok(OAuthClientSettings {
-      auth_url: Url::parse(&self.auth_url).map_err(|err| AuthError::Internal(err.into()))?,
-      token_url: Url::parse(&self.token_url).map_err(|err| AuthError::Internal(err.into()))?,
+      auth_url: Url::parse(auth_url).map_err(|err| AuthError::Internal(err.into()))?,
+      token_url: Url::parse(token_url).map_err(|err| AuthError::Internal(err.into()))?,
       client_id: self.client_id.clone(),
       client_secret: self.client_secret.clone(),
     });
@@ -100,6 +195,29 @@ impl OAuthProvider for OidcProvider {
     return vec!["openid", "email", "profile"];
   }
 
+  async fn discover(&self) -> Result<(), AuthError> {
+    let Some(ref issuer_url) = self.issuer_url else {
+      return Ok(());
+    };
+
+    if let Some(discovery) = self.discovered()
+      && discovery.fetched.elapsed() < DISCOVERY_TTL
+    {
+      return Ok(());
+    }
+
+    let metadata = fetch_provider_metadata(issuer_url).await?;
+    let jwks = fetch_jwks(&metadata.jwks_uri).await?;
+
+    *self.discovery.lock() = Some(Arc::new(Discovery {
+      metadata,
+      jwks,
+      fetched: Instant::now(),
+    }));
+
+    return Ok(());
+  }
+
   async fn get_user(&self, token_response: &TokenResponse) -> Result<OAuthUser, AuthError> {
     if *token_response.token_type() != oauth2::basic::BasicTokenType::Bearer {
       return Err(AuthError::Internal(
@@ -107,25 +225,314 @@ impl OAuthProvider for OidcProvider {
       ));
     }
 
-    let response = reqwest::Client::new()
-      .get(&self.user_api_url)
-      .bearer_auth(token_response.access_token().secret())
-      .send()
-      .await
-      .map_err(|err| AuthError::FailedDependency(err.into()))?;
+    let discovery = self.discovered();
 
-    let user = response
-      .json::<OidcUser>()
-      .await
-      .map_err(|err| AuthError::FailedDependency(err.into()))?;
+    let mut claims = match (&discovery, &token_response.extra_fields().id_token) {
+      (Some(discovery), Some(id_token)) => {
+        self.validate_id_token(discovery.clone(), id_token).await?
+      }
+      (Some(_), None) => {
+        return Err(AuthError::FailedDependency("Missing ID token".into()));
+      }
+      // W/o discovery there are no keys to validate ID tokens against, rely on the user API.
+      (None, _) => Map::new(),
+    };
 
-    return Ok(OAuthUser {
-      provider_user_id: user.sub,
-      provider_id: OAuthProviderId::Oidc0,
-      email: user.email,
-      username: user.preferred_username,
-      verified: user.email_verified.unwrap_or(true),
-      avatar: user.picture,
-    });
+    let user_api_url = self.user_api_url.as_deref().or(
+      discovery
+        .as_ref()
+        .and_then(|d| d.metadata.userinfo_endpoint.as_deref()),
+    );
+
+    if let Some(user_api_url) = user_api_url {
+      let response = reqwest::Client::new()
+        .get(user_api_url)
+        .bearer_auth(token_response.access_token().secret())
+        .send()
+        .await
+        .map_err(|err| AuthError::FailedDependency(err.into()))?;
+
+      let user_info = response
+        .json::<Map<String, Value>>()
+        .await
+        .map_err(|err| AuthError::FailedDependency(err.into()))?;
+
+      // The user info must describe the same subject as the validated ID token.
+      if let (Some(sub), Some(user_info_sub)) = (claims.get("sub"), user_info.get("sub"))
+        && sub != user_info_sub
+      {
+        return Err(AuthError::FailedDependency("Subject mismatch".into()));
+      }
+
+      for (key, value) in user_info {
+        claims.entry(key).or_insert(value);
+      }
+    }
+
+    return map_claims(self.id, &self.claim_mapping, &claims);
+  }
+}
+
+async fn fetch_provider_metadata(issuer_url: &str) -> Result<ProviderMetadata, AuthError> {
+  let issuer = issuer_url.trim_end_matches('/');
+
+  let metadata = reqwest::Client::new()
+    .get(format!("{issuer}/.well-known/openid-configuration"))
+    .send()
+    .await
+    .map_err(|err| AuthError::FailedDependency(err.into()))?
+    .json::<ProviderMetadata>()
+    .await
+    .map_err(|err| AuthError::FailedDependency(err.into()))?;
+
+  // The spec requires the advertised issuer to match the one used for discovery exactly.
+  if metadata.issuer.trim_end_matches('/') != issuer {
+    return Err(AuthError::FailedDependency(
+      format!("OIDC issuer mismatch: {}", metadata.issuer).into(),
+    ));
+  }
+
+  return Ok(metadata);
+}
+
+async fn fetch_jwks(jwks_uri: &str) -> Result<JwkSet, AuthError> {
+  return reqwest::Client::new()
+    .get(jwks_uri)
+    .send()
+    .await
+    .map_err(|err| AuthError::FailedDependency(err.into()))?
+    .json::<JwkSet>()
+    .await
+    .map_err(|err| AuthError::FailedDependency(err.into()));
+}
+
+fn decode_id_token(
+  jwks: &JwkSet,
+  id_token: &str,
+  issuer: &str,
+  client_id: &str,
+) -> Result<Map<String, Value>, AuthError> {
+  let header =
+    jsonwebtoken::decode_header(id_token).map_err(|err| AuthError::FailedDependency(err.into()))?;
+
+  // Symmetric algorithms would use the client secret, which we don't support. Rejecting them
+  // explicitly also rules out algorithm confusion.
+  if matches!(
+    header.alg,
+    Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+  ) {
+    return Err(AuthError::FailedDependency(
+      format!("Unsupported ID token algorithm: {:?}", header.alg).into(),
+    ));
+  }
+
+  let jwk = match header.kid {
+    Some(ref kid) => jwks.find(kid),
+    None if jwks.keys.len() == 1 => jwks.keys.first(),
+    None => None,
+  }
+  .ok_or_else(|| AuthError::FailedDependency("Unknown ID token key".into()))?;
+
+  let key = DecodingKey::from_jwk(jwk).map_err(|err| AuthError::FailedDependency(err.into()))?;
+
+  let mut validation = Validation::new(header.alg);
+  validation.set_audience(&[client_id]);
+  validation.set_issuer(&[issuer]);
+
+  return Ok(
+    jsonwebtoken::decode::<Map<String, Value>>(id_token, &key, &validation)
+      .map_err(|_err| AuthError::Unauthorized)?
+      .claims,
+  );
+}
+
+// Reference: https://openid.net/specs/openid-connect-core-1_0.html#StandardClaims
+fn map_claims(
+  provider_id: OAuthProviderId,
+  mapping: &OidcClaimMapping,
+  claims: &Map<String, Value>,
+) -> Result<OAuthUser, AuthError> {
+  let string_claim = |name: &str| claims.get(name).and_then(|v| v.as_str()).map(String::from);
+
+  let Some(sub) = string_claim("sub") else {
+    return Err(AuthError::FailedDependency("Missing 'sub' claim".into()));
+  };
+  let Some(email) = string_claim(mapping.email.as_deref().unwrap_or("email")) else {
+    return Err(AuthError::FailedDependency("Missing email claim".into()));
+  };
+
+  // Some providers send booleans as strings.
+  let verified = match claims.get(
+    mapping
+      .email_verified
+      .as_deref()
+      .unwrap_or("email_verified"),
+  ) {
+    Some(Value::Bool(verified)) => *verified,
+    Some(Value::String(verified)) => verified == "true",
+    _ => true,
+  };
+
+  return Ok(OAuthUser {
+    provider_user_id: sub,
+    provider_id,
+    email,
+    username: string_claim(mapping.username.as_deref().unwrap_or("preferred_username")),
+    verified,
+    avatar: string_claim(mapping.avatar_url.as_deref().unwrap_or("picture")),
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use base64::prelude::*;
+  use ed25519_dalek::SigningKey;
+  use ed25519_dalek::pkcs8::EncodePrivateKey;
+  use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+  use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, OctetKeyPairParameters,
+    OctetKeyPairType,
+  };
+  use jsonwebtoken::{EncodingKey, Header};
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn test_oidc_claim_mapping() {
+    let Value::Object(claims) = json!({
+      "sub": "subject",
+      "email": "foo@bar.org",
+      "email_verified": "false",
+      "preferred_username": "foo",
+      "upn": "foo@corp.example",
+    }) else {
+      panic!("expected object");
+    };
+
+    let user = map_claims(
+      OAuthProviderId::Oidc1,
+      &OidcClaimMapping::default(),
+      &claims,
+    )
+    .unwrap();
+    assert_eq!(user.provider_user_id, "subject");
+    assert_eq!(user.provider_id, OAuthProviderId::Oidc1);
+    assert_eq!(user.email, "foo@bar.org");
+    assert_eq!(user.username.as_deref(), Some("foo"));
+    assert!(!user.verified);
+    assert_eq!(user.avatar, None);
+
+    let user = map_claims(
+      OAuthProviderId::Oidc1,
+      &OidcClaimMapping {
+        email: Some("upn".to_string()),
+        ..Default::default()
+      },
+      &claims,
+    )
+    .unwrap();
+    assert_eq!(user.email, "foo@corp.example");
+
+    let mut claims = claims;
+    claims.remove("sub");
+    assert!(
+      map_claims(
+        OAuthProviderId::Oidc1,
+        &OidcClaimMapping::default(),
+        &claims
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn test_oidc_id_token_validation() {
+    let signing_key = SigningKey::generate(&mut argon2::password_hash::rand_core::OsRng);
+    let encoding_key = EncodingKey::from_ed_pem(
+      signing_key
+        .to_pkcs8_pem(LineEnding::default())
+        .unwrap()
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let jwks = JwkSet {
+      keys: vec![Jwk {
+        common: CommonParameters {
+          key_id: Some("key0".to_string()),
+          ..Default::default()
+        },
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+          key_type: OctetKeyPairType::OctetKeyPair,
+          curve: EllipticCurve::Ed25519,
+          x: BASE64_URL_SAFE_NO_PAD.encode(signing_key.verifying_key().to_bytes()),
+        }),
+      }],
+    };
+
+    const ISSUER: &str = "https://issuer.example";
+    const CLIENT_ID: &str = "client";
+
+    let sign = |kid: &str, claims: Value| {
+      let mut header = Header::new(Algorithm::EdDSA);
+      header.kid = Some(kid.to_string());
+      return jsonwebtoken::encode(&header, &claims, &encoding_key).unwrap();
+    };
+    let exp = chrono::Utc::now().timestamp() + 60;
+
+    let claims = decode_id_token(
+      &jwks,
+      &sign(
+        "key0",
+        json!({"iss": ISSUER, "aud": CLIENT_ID, "exp": exp, "sub": "subject"}),
+      ),
+      ISSUER,
+      CLIENT_ID,
+    )
+    .unwrap();
+    assert_eq!(claims.get("sub").unwrap(), "subject");
+
+    // Wrong audience.
+    assert!(
+      decode_id_token(
+        &jwks,
+        &sign(
+          "key0",
+          json!({"iss": ISSUER, "aud": "other", "exp": exp, "sub": "subject"}),
+        ),
+        ISSUER,
+        CLIENT_ID,
+      )
+      .is_err()
+    );
+
+    // Wrong issuer.
+    assert!(
+      decode_id_token(
+        &jwks,
+        &sign(
+          "key0",
+          json!({"iss": "https://evil.example", "aud": CLIENT_ID, "exp": exp, "sub": "subject"}),
+        ),
+        ISSUER,
+        CLIENT_ID,
+      )
+      .is_err()
+    );
+
+    // Unknown key.
+    assert!(
+      decode_id_token(
+        &jwks,
+        &sign(
+          "key1",
+          json!({"iss": ISSUER, "aud": CLIENT_ID, "exp": exp, "sub": "subject"}),
+        ),
+        ISSUER,
+        CLIENT_ID,
+      )
+      .is_err()
+    );
   }
 }
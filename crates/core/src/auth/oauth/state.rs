@@ -43,6 +43,11 @@ pub(crate) struct OAuthStateClaims {
 
   /// Redirect target.
   pub redirect_uri: Option<String>,
+
+  /// Url-safe Base64 encoded id of a signed-in anonymous user. If present, the anonymous account
+  /// is linked to the external identity rather than creating a new user.
+  #[serde(rename = "link", default, skip_serializing_if = "Option::is_none")]
+  pub anonymous_user_id: Option<String>,
 }
 
 #[cfg(test)]
@@ -61,6 +66,7 @@ mod tests {
       user_pkce_code_challenge: Some("client challenge".to_string()),
       response_type: Some(ResponseType::Code),
       redirect_uri: Some("custom-sheme://test".to_string()),
+      anonymous_user_id: None,
     };
 
     let encoded = state.jwt().encode(&oauth_state).unwrap();
@@ -1,7 +1,9 @@
 use indexmap::IndexMap;
 use itertools::Itertools;
 use log::*;
+use std::time::Duration;
 
+use crate::auth::lockout::LockoutOptions;
 use crate::auth::oauth::providers::{
   OAuthProviderError, OAuthProviderType, oauth_providers_static_registry,
 };
@@ -11,6 +13,7 @@ use crate::config::proto::AuthConfig;
 #[derive(Default)]
 pub struct AuthOptions {
   password_options: PasswordOptions,
+  lockout_options: LockoutOptions,
   oauth_providers: IndexMap<String, OAuthProviderType>,
 }
 
@@ -22,6 +25,9 @@ pub struct OAuthProvider {
 
 impl AuthOptions {
   pub fn from_config(config: AuthConfig) -> Self {
+    let lockout_config = config.login_lockout.clone().unwrap_or_default();
+    let lockout_defaults = LockoutOptions::default();
+
     return Self {
       password_options: PasswordOptions {
         min_length: config.password_minimal_length.unwrap_or(8) as usize,
@@ -33,6 +39,28 @@ impl AuthOptions {
         must_contain_special_characters: config
           .password_must_contain_special_characters
           .unwrap_or(false),
+        deny_list: config
+          .password_deny_list
+          .iter()
+          .map(|p| p.to_lowercase())
+          .collect(),
+        check_breached: config.password_check_breached.unwrap_or(false),
+      },
+      lockout_options: LockoutOptions {
+        max_failed_attempts: lockout_config
+          .max_failed_attempts
+          .unwrap_or(lockout_defaults.max_failed_attempts),
+        max_failed_attempts_per_ip: lockout_config
+          .max_failed_attempts_per_ip
+          .unwrap_or(lockout_defaults.max_failed_attempts_per_ip),
+        lockout: lockout_config
+          .lockout_sec
+          .map_or(lockout_defaults.lockout, |s| Duration::from_secs(s as u64)),
+        max_lockout: lockout_config
+          .max_lockout_sec
+          .map_or(lockout_defaults.max_lockout, |s| {
+            Duration::from_secs(s as u64)
+          }),
       },
       oauth_providers: build_oauth_providers_from_config(config).unwrap_or_else(|err| {
         error!("Failed to derive configured OAuth providers from config: {err}");
@@ -45,6 +73,10 @@ impl AuthOptions {
     return &self.password_options;
   }
 
+  pub fn lockout_options(&self) -> &LockoutOptions {
+    return &self.lockout_options;
+  }
+
   pub fn lookup_oauth_provider(&self, name: &str) -> Option<&OAuthProviderType> {
     if let Some(entry) = self.oauth_providers.get(name) {
       return Some(entry);
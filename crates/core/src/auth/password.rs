@@ -1,7 +1,9 @@
-use mini_moka::sync::Cache;
-use std::sync::LazyLock;
+use log::*;
 
 use crate::auth::AuthError;
+use crate::auth::lockout::{
+  LockoutOptions, check_lockout, record_failed_login, record_successful_login,
+};
 use crate::auth::user::DbUser;
 
 #[derive(Clone, Debug)]
@@ -12,6 +14,11 @@ pub struct PasswordOptions {
   pub must_contain_upper_and_lower_case: bool,
   pub must_contain_digits: bool,
   pub must_contain_special_characters: bool,
+
+  /// Lower-case passwords that are rejected.
+  pub deny_list: Vec<String>,
+  /// Whether to reject passwords that appeared in known data breaches.
+  pub check_breached: bool,
 }
 
 impl Default for PasswordOptions {
@@ -22,6 +29,8 @@ impl Default for PasswordOptions {
       must_contain_upper_and_lower_case: false,
       must_contain_digits: false,
       must_contain_special_characters: false,
+      deny_list: vec![],
+      check_breached: false,
     };
   }
 }
@@ -62,27 +71,97 @@ pub fn validate_password_policy(
     return Err(AuthError::BadRequest("Must contain special characters"));
   }
 
+  if !opts.deny_list.is_empty() {
+    let lower = password.to_lowercase();
+    if opts.deny_list.iter().any(|denied| *denied == lower) {
+      return Err(AuthError::BadRequest("Password too common"));
+    }
+  }
+
   return Ok(());
 }
 
-#[derive(Clone)]
-struct FailedAttempt {
-  tries: usize,
-}
+/// Like `validate_password_policy` but additionally checks the password against known data breaches
+/// if configured.
+pub async fn validate_password(
+  password: &str,
+  password_repeat: &str,
+  opts: &PasswordOptions,
+) -> Result<(), AuthError> {
+  validate_password_policy(password, password_repeat, opts)?;
 
-impl Default for FailedAttempt {
-  fn default() -> Self {
-    return Self { tries: 1 };
+  if opts.check_breached && is_breached_password(password).await {
+    return Err(AuthError::BadRequest("Password appeared in a data breach"));
   }
+
+  return Ok(());
 }
 
-// Track login attempts for abuse prevention.
-static ATTEMPTS: LazyLock<Cache<String, FailedAttempt>> = LazyLock::new(|| {
-  Cache::builder()
-    .time_to_live(std::time::Duration::from_secs(60))
-    .max_capacity(1024)
-    .build()
-});
+/// Checks the password against the "Have I Been Pwned" range API using k-anonymity, i.e. only the
+/// first five hex characters of the password's SHA-1 hash leave the server.
+///
+/// Fails open: if the API cannot be reached in time, the password is accepted.
+#[cfg(feature = "hibp")]
+async fn is_breached_password(password: &str) -> bool {
+  use sha1::{Digest, Sha1};
+
+  const HIBP_RANGE_URL: &str = "https://api.pwnedpasswords.com/range";
+  const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+  let hash: String = Sha1::digest(password.as_bytes())
+    .iter()
+    .map(|b| format!("{b:02X}"))
+    .collect();
+  let (prefix, suffix) = hash.split_at(5);
+
+  let client = match reqwest::Client::builder().timeout(TIMEOUT).build() {
+    Ok(client) => client,
+    Err(err) => {
+      warn!("Failed to build HIBP client: {err}");
+      return false;
+    }
+  };
+
+  let body = match client
+    .get(format!("{HIBP_RANGE_URL}/{prefix}"))
+    // Pads the response with fake entries to obscure the number of matches.
+    .header("Add-Padding", "true")
+    .send()
+    .await
+    .and_then(|response| response.error_for_status())
+  {
+    Ok(response) => match response.text().await {
+      Ok(body) => body,
+      Err(err) => {
+        warn!("Failed to read HIBP response: {err}");
+        return false;
+      }
+    },
+    Err(err) => {
+      warn!("HIBP breach check failed: {err}");
+      return false;
+    }
+  };
+
+  return range_contains_suffix(&body, suffix);
+}
+
+#[cfg(not(feature = "hibp"))]
+async fn is_breached_password(_password: &str) -> bool {
+  warn!("Breached password check configured but TrailBase was built without the 'hibp' feature");
+  return false;
+}
+
+/// Parses a range response, i.e. lines of "<SUFFIX>:<COUNT>". Padding entries have a count of zero.
+#[cfg(any(feature = "hibp", test))]
+fn range_contains_suffix(body: &str, suffix: &str) -> bool {
+  return body.lines().any(|line| {
+    let Some((candidate, count)) = line.trim().split_once(':') else {
+      return false;
+    };
+    return candidate.eq_ignore_ascii_case(suffix) && count.parse::<u64>().is_ok_and(|c| c > 0);
+  });
+}
 
 pub fn hash_password(password: &str) -> Result<String, AuthError> {
   return trailbase_extension::password::hash_password(password).map_err(|err| {
@@ -92,10 +171,13 @@ pub fn hash_password(password: &str) -> Result<String, AuthError> {
 }
 
 /// Checks the given password against a known user. Will further ensure that the email was verified
-/// and rate limit attempts to protect against brute-force attacks.
+/// and lock out accounts and client IPs with repeated failures to protect against brute-force
+/// attacks.
 pub fn check_user_password(
   db_user: &DbUser,
   password: &str,
+  client_ip: Option<&str>,
+  lockout: &LockoutOptions,
   is_demo: bool,
 ) -> Result<(), AuthError> {
   if db_user.email.is_some() && !db_user.verified {
@@ -113,38 +195,27 @@ pub fn check_user_password(
     .unwrap_or_default()
     .to_string();
 
-  let attempts = ATTEMPTS.get(&account);
-
-  if !is_demo && attempts.as_ref().map(|a| a.tries).unwrap_or(0) >= LOGIN_RATE_LIMIT {
-    return Err(AuthError::TooManyRequests);
+  if !is_demo {
+    check_lockout(&account, client_ip, lockout)?;
   }
 
   trailbase_extension::password::verify_password(password.as_bytes(), password_hash).map_err(
     |err| {
-      ATTEMPTS.insert(
-        account,
-        attempts
-          .map(|a| FailedAttempt { tries: a.tries + 1 })
-          .unwrap_or_default(),
-      );
-
       return match err {
-        trailbase_extension::password::PasswordError::InvalidPassword => AuthError::Unauthorized,
+        trailbase_extension::password::PasswordError::InvalidPassword => {
+          record_failed_login(&account, client_ip, lockout);
+          AuthError::Unauthorized
+        }
         err => AuthError::Internal(err.to_string().into()),
       };
     },
   )?;
 
+  record_successful_login(&account);
+
   return Ok(());
 }
 
-// HACK: Increase limit in tests to avoid limits.
-#[cfg(test)]
-const LOGIN_RATE_LIMIT: usize = 10;
-
-#[cfg(not(test))]
-const LOGIN_RATE_LIMIT: usize = 3;
-
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -154,16 +225,19 @@ mod tests {
     let password = "0123456789.";
     let db_user = DbUser::new_for_test("foo@test.org", password);
 
-    assert!(check_user_password(&db_user, password, false).is_ok());
+    let lockout = LockoutOptions::default();
+    let ip = Some("127.0.0.1");
+
+    assert!(check_user_password(&db_user, password, ip, &lockout, false).is_ok());
 
-    // Lock-out/rate-limit after 10 (3 in prod) failed attempts.
+    // Lock-out after 10 (3 in prod) failed attempts.
     for _ in 0..10 {
-      assert!(check_user_password(&db_user, "mismatch", false).is_err());
+      assert!(check_user_password(&db_user, "mismatch", ip, &lockout, false).is_err());
     }
-    assert!(check_user_password(&db_user, password, false).is_err());
+    assert!(check_user_password(&db_user, password, ip, &lockout, false).is_err());
 
     // By-pass lock-out in demo mode.
-    assert!(check_user_password(&db_user, password, true).is_ok());
+    assert!(check_user_password(&db_user, password, ip, &lockout, true).is_ok());
   }
 
   #[test]
@@ -228,5 +302,38 @@ mod tests {
       assert!(test("a2", &options).is_err());
       assert!(test("2.", &options).is_ok());
     }
+
+    {
+      // Deny-list
+      let options = PasswordOptions {
+        min_length: 2,
+        deny_list: vec!["password".to_string()],
+        ..Default::default()
+      };
+
+      assert!(test("PassWord", &options).is_err());
+      assert!(test("password1", &options).is_ok());
+    }
+  }
+
+  #[test]
+  fn test_breach_range_response() {
+    let body = "0018A45C4D1DEF81644B54AB7F969B88D65:3\r\n\
+                1E4C9B93F3F0682250B6CF8331B7EE68FD8:0\r\n\
+                00D4F6E8FA6EECAD2A3AA415EEC418D38EC:2";
+
+    assert!(range_contains_suffix(
+      body,
+      "00d4f6e8fa6eecad2a3aa415eec418d38ec"
+    ));
+    // Padding entries don't count.
+    assert!(!range_contains_suffix(
+      body,
+      "1E4C9B93F3F0682250B6CF8331B7EE68FD8"
+    ));
+    assert!(!range_contains_suffix(
+      body,
+      "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"
+    ));
   }
 }
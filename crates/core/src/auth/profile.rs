@@ -0,0 +1,290 @@
+use base64::prelude::*;
+use itertools::Itertools;
+use serde_json::Map;
+use trailbase_sqlite::Value;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::AuthError;
+use crate::constants::USER_TABLE;
+
+/// Custom profile fields keyed by column name.
+pub type Profile = Map<String, serde_json::Value>;
+
+/// Built-in `_user` columns, which must not be exposed as profile fields.
+pub(crate) const RESERVED_USER_COLUMNS: &[&str] = &[
+  "id",
+  "user",
+  "email",
+  "username",
+  "password_hash",
+  "verified",
+  "admin",
+  "created",
+  "updated",
+  "totp_secret",
+  "email_verification_code",
+  "email_verification_code_sent_at",
+  "pending_email",
+  "password_reset_code",
+  "password_reset_code_sent_at",
+  "authorization_code",
+  "authorization_code_sent_at",
+  "pkce_code_challenge",
+  "provider_id",
+  "provider_user_id",
+  "provider_avatar_url",
+];
+
+/// Validates user-provided profile values against the configured fields and their JSON schemas.
+///
+/// When `registering`, required fields must be present.
+pub(crate) fn validate_profile(
+  state: &AppState,
+  profile: Profile,
+  registering: bool,
+) -> Result<Vec<(String, Value)>, AuthError> {
+  let config = state.access_config(|c| c.auth.user_profile.clone().unwrap_or_default());
+
+  if registering {
+    for field in &config.fields {
+      if field.required.unwrap_or(false)
+        && let Some(ref name) = field.name
+        && profile.get(name).is_none_or(|v| v.is_null())
+      {
+        return Err(AuthError::BadRequest("Missing required profile field"));
+      }
+    }
+  }
+
+  let registry = state.json_schema_registry().read();
+  return profile
+    .into_iter()
+    .map(|(name, value)| {
+      let Some(field) = config
+        .fields
+        .iter()
+        .find(|f| f.name.as_deref() == Some(name.as_str()))
+      else {
+        return Err(AuthError::BadRequest("Unknown profile field"));
+      };
+      if field.read_only.unwrap_or(false) {
+        return Err(AuthError::BadRequest("Read-only profile field"));
+      }
+
+      if let Some(ref schema_name) = field.json_schema
+        && !value.is_null()
+      {
+        let Some(schema) = registry.get_schema(schema_name) else {
+          return Err(AuthError::Internal(
+            format!("Missing JSON schema: {schema_name}").into(),
+          ));
+        };
+        if !schema.validator.is_valid(&value) {
+          return Err(AuthError::BadRequest("Invalid profile field"));
+        }
+      }
+
+      return Ok((name, json_to_sql(value)));
+    })
+    .collect();
+}
+
+/// Reads the given user's profile. Fields without a value are returned as `null`.
+pub(crate) async fn read_profile(state: &AppState, user_id: Uuid) -> Result<Profile, AuthError> {
+  let config = state.access_config(|c| c.auth.user_profile.clone().unwrap_or_default());
+  let names: Vec<String> = config.fields.into_iter().filter_map(|f| f.name).collect();
+  if names.is_empty() {
+    return Ok(Profile::new());
+  }
+
+  let columns = names.iter().map(|n| format!("\"{n}\"")).join(", ");
+  let query = match config.table_name {
+    Some(table_name) => format!("SELECT {columns} FROM \"{table_name}\" WHERE user = $1"),
+    None => format!("SELECT {columns} FROM \"{USER_TABLE}\" WHERE id = $1"),
+  };
+
+  let row = state
+    .user_conn()
+    .read_query_row(query, trailbase_sqlite::params!(user_id.into_bytes()))
+    .await?;
+
+  return Ok(
+    names
+      .into_iter()
+      .enumerate()
+      .map(|(idx, name)| {
+        let value = row
+          .as_ref()
+          .and_then(|row| row.get_value(idx))
+          .map_or(serde_json::Value::Null, sql_to_json);
+        return (name, value);
+      })
+      .collect(),
+  );
+}
+
+/// Writes previously validated profile values for the given user.
+pub(crate) async fn write_profile(
+  state: &AppState,
+  user_id: Uuid,
+  values: Vec<(String, Value)>,
+) -> Result<(), AuthError> {
+  if values.is_empty() {
+    return Ok(());
+  }
+
+  let table_name = state.access_config(|c| {
+    c.auth
+      .user_profile
+      .as_ref()
+      .and_then(|p| p.table_name.clone())
+  });
+
+  let (names, mut params): (Vec<String>, Vec<Value>) = values.into_iter().unzip();
+  params.insert(0, Value::Blob(user_id.into_bytes().to_vec()));
+
+  let query = match table_name {
+    Some(table_name) => format!(
+      "INSERT INTO \"{table_name}\" (user, {columns}) VALUES ($1, {placeholders}) \
+       ON CONFLICT (user) DO UPDATE SET {updates}",
+      columns = names.iter().map(|n| format!("\"{n}\"")).join(", "),
+      placeholders = (0..names.len()).map(|i| format!("${}", i + 2)).join(", "),
+      updates = names
+        .iter()
+        .map(|n| format!("\"{n}\" = excluded.\"{n}\""))
+        .join(", "),
+    ),
+    None => format!(
+      "UPDATE \"{USER_TABLE}\" SET {updates} WHERE id = $1",
+      updates = names
+        .iter()
+        .enumerate()
+        .map(|(i, n)| format!("\"{n}\" = ${}", i + 2))
+        .join(", "),
+    ),
+  };
+
+  state.user_conn().execute(query, params).await?;
+
+  return Ok(());
+}
+
+fn json_to_sql(value: serde_json::Value) -> Value {
+  return match value {
+    serde_json::Value::Null => Value::Null,
+    serde_json::Value::Bool(b) => Value::Integer(b as i64),
+    serde_json::Value::Number(n) => n
+      .as_i64()
+      .map(Value::Integer)
+      .unwrap_or_else(|| Value::Real(n.as_f64().unwrap_or_default())),
+    serde_json::Value::String(s) => Value::Text(s),
+    v @ (serde_json::Value::Array(_) | serde_json::Value::Object(_)) => Value::Text(v.to_string()),
+  };
+}
+
+fn sql_to_json(value: &Value) -> serde_json::Value {
+  return match value {
+    Value::Null => serde_json::Value::Null,
+    Value::Integer(i) => serde_json::Value::from(*i),
+    Value::Real(r) => serde_json::Value::from(*r),
+    Value::Text(s) => serde_json::Value::String(s.clone()),
+    Value::Blob(b) => serde_json::Value::String(BASE64_URL_SAFE.encode(b)),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+  use crate::app_state::{TestStateOptions, test_state};
+  use crate::config::proto::{UserProfileConfig, UserProfileField};
+
+  #[tokio::test]
+  async fn test_profile_fields() {
+    let mut config = crate::app_state::test_config();
+    config.auth.user_profile = Some(UserProfileConfig {
+      table_name: None,
+      fields: vec![
+        UserProfileField {
+          name: Some("display_name".to_string()),
+          required: Some(true),
+          ..Default::default()
+        },
+        UserProfileField {
+          name: Some("age".to_string()),
+          json_schema: Some("age".to_string()),
+          ..Default::default()
+        },
+        UserProfileField {
+          name: Some("plan".to_string()),
+          read_only: Some(true),
+          ..Default::default()
+        },
+      ],
+    });
+
+    let state = test_state(Some(TestStateOptions {
+      config: Some(config),
+      json_schema_registry: Some(
+        trailbase_schema::registry::build_json_schema_registry(vec![(
+          "age".to_string(),
+          json!({ "type": "integer", "minimum": 0 }),
+        )])
+        .unwrap(),
+      ),
+      ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    state
+      .user_conn()
+      .execute_batch(
+        "\
+          ALTER TABLE _user ADD COLUMN display_name TEXT; \
+          ALTER TABLE _user ADD COLUMN age INTEGER; \
+          ALTER TABLE _user ADD COLUMN plan TEXT DEFAULT 'free'; \
+        ",
+      )
+      .await
+      .unwrap();
+
+    let user_id = crate::admin::user::create_user_for_test(&state, "foo@bar.org", "Secret!1!!")
+      .await
+      .unwrap();
+
+    let profile = |value: serde_json::Value| -> Profile {
+      return value.as_object().unwrap().clone();
+    };
+
+    // Required fields.
+    assert!(validate_profile(&state, profile(json!({"age": 5})), true).is_err());
+    // Schema validation.
+    assert!(
+      validate_profile(
+        &state,
+        profile(json!({"display_name": "Foo", "age": -1})),
+        false
+      )
+      .is_err()
+    );
+    // Read-only and unknown fields.
+    assert!(validate_profile(&state, profile(json!({"plan": "pro"})), false).is_err());
+    assert!(validate_profile(&state, profile(json!({"admin": true})), false).is_err());
+
+    let values = validate_profile(
+      &state,
+      profile(json!({"display_name": "Foo", "age": 42})),
+      true,
+    )
+    .unwrap();
+    write_profile(&state, user_id, values).await.unwrap();
+
+    assert_eq!(
+      read_profile(&state, user_id).await.unwrap(),
+      profile(json!({"display_name": "Foo", "age": 42, "plan": "free"}))
+    );
+  }
+}
@@ -0,0 +1,464 @@
+use axum::extract::{Form, Path, State};
+use axum::response::Response;
+use base64::prelude::*;
+use chrono::{DateTime, Duration, Utc};
+use const_format::formatcp;
+use log::*;
+use roxmltree::{Document, Node};
+use rsa::RsaPublicKey;
+use serde::Deserialize;
+use std::collections::HashSet;
+use thiserror::Error;
+use tower_cookies::Cookies;
+use trailbase_sqlite::params;
+use utoipa::ToSchema;
+
+use crate::AppState;
+use crate::auth::AuthError;
+use crate::auth::api::login::build_auth_token_flow_response;
+use crate::auth::oauth::OAuthUser;
+use crate::auth::oauth::callback::get_or_create_external_user;
+use crate::auth::saml::SamlProvider;
+use crate::auth::saml::signature::{SignatureError, verify_enveloped_signature};
+use crate::auth::tokens::SessionMetadata;
+use crate::config::proto::OAuthProviderId;
+use crate::constants::SAML_REQUEST_TABLE;
+
+const PROTOCOL_NS: &str = "urn:oasis:names:tc:SAML:2.0:protocol";
+const ASSERTION_NS: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+
+const STATUS_SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+const BEARER: &str = "urn:oasis:names:tc:SAML:2.0:cm:bearer";
+
+/// Tolerated clock skew between IdP and SP.
+const CLOCK_SKEW: Duration = Duration::minutes(2);
+
+const DEFAULT_EMAIL_ATTRIBUTES: &[&str] = &[
+  "email",
+  "mail",
+  "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress",
+];
+
+#[derive(Debug, Error)]
+pub(crate) enum SamlResponseError {
+  #[error("Malformed response: {0}")]
+  Malformed(&'static str),
+  #[error("Invalid response: {0}")]
+  Invalid(&'static str),
+  #[error("Signature: {0}")]
+  Signature(#[from] SignatureError),
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct SamlAcsRequest {
+  #[serde(rename = "SAMLResponse")]
+  pub saml_response: String,
+  #[serde(rename = "RelayState")]
+  pub relay_state: Option<String>,
+}
+
+/// Assertion consumer service (ACS) receiving the IdP's response via HTTP-POST binding.
+#[utoipa::path(
+  post,
+  path = "/{provider}/acs",
+  tag = "saml",
+  request_body = SamlAcsRequest,
+  responses(
+    (status = 200, description = "Logged in, when redirect_uri not present."),
+    (status = 303, description = "Logged in, when redirect_uri present."),
+    (status = 401, description = "Invalid, expired or unsolicited response."),
+  )
+)]
+pub(crate) async fn saml_acs_handler(
+  State(state): State<AppState>,
+  Path(provider): Path<String>,
+  cookies: Cookies,
+  session: SessionMetadata,
+  Form(request): Form<SamlAcsRequest>,
+) -> Result<Response, AuthError> {
+  let provider = SamlProvider::lookup(&state, &provider)?;
+  let key = provider.idp_public_key()?;
+
+  let encoded: String = request
+    .saml_response
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .collect();
+  let xml = BASE64_STANDARD
+    .decode(encoded)
+    .ok()
+    .and_then(|bytes| String::from_utf8(bytes).ok())
+    .ok_or(AuthError::BadRequest("invalid SAMLResponse"))?;
+
+  let assertion = validate_response(&provider, &key, &xml, Utc::now()).map_err(|err| {
+    debug!("Rejected SAML response from '{}': {err}", provider.name);
+    AuthError::Unauthorized
+  })?;
+
+  if let Some(relay_state) = request.relay_state
+    && relay_state != assertion.in_response_to
+  {
+    return Err(AuthError::Unauthorized);
+  }
+
+  // Consume the pending request. This rejects unsolicited responses and replays.
+  const CONSUME_REQUEST_QUERY: &str = formatcp!(
+    "\
+      DELETE FROM '{SAML_REQUEST_TABLE}' \
+      WHERE id = $1 AND provider = $2 AND UNIXEPOCH() < expires \
+      RETURNING redirect_uri \
+    "
+  );
+  let redirect_uri: Option<String> = state
+    .session_conn()
+    .write_query_row_get(
+      CONSUME_REQUEST_QUERY,
+      params!(assertion.in_response_to, provider.name.clone()),
+      0,
+    )
+    .await?
+    .ok_or(AuthError::Unauthorized)?;
+
+  let Some(email) = assertion.email else {
+    return Err(AuthError::BadRequest("SAML assertion lacks email"));
+  };
+
+  let db_user = get_or_create_external_user(
+    &state,
+    OAuthUser {
+      provider_user_id: format!("{}:{}", provider.name, assertion.name_id),
+      provider_id: OAuthProviderId::Saml,
+      email,
+      username: assertion.username,
+      // The IdP vouches for its users.
+      verified: true,
+      avatar: None,
+    },
+    None,
+  )
+  .await?;
+
+  return build_auth_token_flow_response(&state, &db_user, &cookies, &session, redirect_uri, false)
+    .await;
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct SamlAssertion {
+  pub name_id: String,
+  pub in_response_to: String,
+  pub email: Option<String>,
+  pub username: Option<String>,
+}
+
+/// Validates a SAML response and extracts the asserted identity.
+pub(crate) fn validate_response(
+  provider: &SamlProvider,
+  key: &RsaPublicKey,
+  xml: &str,
+  now: DateTime<Utc>,
+) -> Result<SamlAssertion, SamlResponseError> {
+  // NOTE: roxmltree rejects DTDs by default, i.e. there's no entity expansion.
+  let doc = Document::parse(xml).map_err(|_err| SamlResponseError::Malformed("xml"))?;
+  let response = doc.root_element();
+  if !response.has_tag_name((PROTOCOL_NS, "Response")) {
+    return Err(SamlResponseError::Malformed("expected Response"));
+  }
+
+  // Duplicate IDs are a prerequisite for signature wrapping attacks.
+  let mut ids = HashSet::new();
+  for id in doc.descendants().filter_map(|n| n.attribute("ID")) {
+    if !ids.insert(id) {
+      return Err(SamlResponseError::Invalid("duplicate ID"));
+    }
+  }
+
+  if let Some(destination) = response.attribute("Destination")
+    && destination != provider.acs_url
+  {
+    return Err(SamlResponseError::Invalid("destination"));
+  }
+
+  let status_code = child(response, PROTOCOL_NS, "Status")
+    .and_then(|status| child(status, PROTOCOL_NS, "StatusCode"))
+    .and_then(|code| code.attribute("Value"));
+  if status_code != Some(STATUS_SUCCESS) {
+    return Err(SamlResponseError::Invalid("status"));
+  }
+
+  if child(response, ASSERTION_NS, "EncryptedAssertion").is_some() {
+    return Err(SamlResponseError::Invalid(
+      "encrypted assertions unsupported",
+    ));
+  }
+  let mut assertions = response
+    .children()
+    .filter(|n| n.has_tag_name((ASSERTION_NS, "Assertion")));
+  let (Some(assertion), None) = (assertions.next(), assertions.next()) else {
+    return Err(SamlResponseError::Invalid("expected exactly one assertion"));
+  };
+
+  // Either the entire response or the assertion must be signed.
+  match verify_enveloped_signature(response, key) {
+    Ok(()) => {}
+    Err(SignatureError::Missing) => verify_enveloped_signature(assertion, key)?,
+    Err(err) => return Err(err.into()),
+  };
+
+  let issuer = child(assertion, ASSERTION_NS, "Issuer").and_then(|n| n.text());
+  if issuer.is_none() || issuer != provider.config.idp_entity_id.as_deref() {
+    return Err(SamlResponseError::Invalid("issuer"));
+  }
+
+  let subject =
+    child(assertion, ASSERTION_NS, "Subject").ok_or(SamlResponseError::Malformed("Subject"))?;
+  let name_id = child(subject, ASSERTION_NS, "NameID")
+    .and_then(|n| n.text())
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .ok_or(SamlResponseError::Malformed("NameID"))?;
+
+  let in_response_to = subject
+    .children()
+    .filter(|n| {
+      n.has_tag_name((ASSERTION_NS, "SubjectConfirmation")) && n.attribute("Method") == Some(BEARER)
+    })
+    .filter_map(|confirmation| child(confirmation, ASSERTION_NS, "SubjectConfirmationData"))
+    .find_map(|data| {
+      if data.attribute("Recipient") != Some(provider.acs_url.as_str()) {
+        return None;
+      }
+      if !parse_time(data.attribute("NotOnOrAfter")).is_some_and(|t| now < t + CLOCK_SKEW) {
+        return None;
+      }
+      return data.attribute("InResponseTo");
+    })
+    .ok_or(SamlResponseError::Invalid("subject confirmation"))?
+    .to_string();
+
+  let conditions = child(assertion, ASSERTION_NS, "Conditions")
+    .ok_or(SamlResponseError::Malformed("Conditions"))?;
+  if let Some(not_before) = conditions.attribute("NotBefore")
+    && !parse_time(Some(not_before)).is_some_and(|t| t - CLOCK_SKEW <= now)
+  {
+    return Err(SamlResponseError::Invalid("not yet valid"));
+  }
+  if let Some(not_on_or_after) = conditions.attribute("NotOnOrAfter")
+    && !parse_time(Some(not_on_or_after)).is_some_and(|t| now < t + CLOCK_SKEW)
+  {
+    return Err(SamlResponseError::Invalid("expired"));
+  }
+
+  let mut audience_restrictions = conditions
+    .children()
+    .filter(|n| n.has_tag_name((ASSERTION_NS, "AudienceRestriction")))
+    .peekable();
+  if audience_restrictions.peek().is_none() {
+    return Err(SamlResponseError::Invalid("missing audience"));
+  }
+  for restriction in audience_restrictions {
+    let matches = restriction
+      .children()
+      .filter(|n| n.has_tag_name((ASSERTION_NS, "Audience")))
+      .any(|n| n.text().map(str::trim) == Some(provider.sp_entity_id.as_str()));
+    if !matches {
+      return Err(SamlResponseError::Invalid("audience"));
+    }
+  }
+
+  let mapping = provider.config.attribute_mapping.as_ref();
+  let email = match mapping.and_then(|m| m.email.as_deref()) {
+    Some(name) => attribute(assertion, name),
+    None => DEFAULT_EMAIL_ATTRIBUTES
+      .iter()
+      .find_map(|name| attribute(assertion, name))
+      .or_else(|| name_id.contains('@').then(|| name_id.clone())),
+  };
+  let username = mapping
+    .and_then(|m| m.username.as_deref())
+    .and_then(|name| attribute(assertion, name));
+
+  return Ok(SamlAssertion {
+    name_id,
+    in_response_to,
+    email,
+    username,
+  });
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, ns: &str, name: &str) -> Option<Node<'a, 'input>> {
+  return node.children().find(|n| n.has_tag_name((ns, name)));
+}
+
+/// Returns the first value of the named attribute in the assertion's attribute statements.
+fn attribute(assertion: Node, name: &str) -> Option<String> {
+  return assertion
+    .children()
+    .filter(|n| n.has_tag_name((ASSERTION_NS, "AttributeStatement")))
+    .flat_map(|statement| statement.children())
+    .filter(|n| n.has_tag_name((ASSERTION_NS, "Attribute")) && n.attribute("Name") == Some(name))
+    .flat_map(|attribute| attribute.children())
+    .filter(|n| n.has_tag_name((ASSERTION_NS, "AttributeValue")))
+    .find_map(|value| {
+      value
+        .text()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+    });
+}
+
+fn parse_time(value: Option<&str>) -> Option<DateTime<Utc>> {
+  return DateTime::parse_from_rfc3339(value?)
+    .ok()
+    .map(|t| t.with_timezone(&Utc));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::auth::saml::signature::parse_certificate;
+  use crate::auth::saml::signature::tests::{
+    IDP_CERTIFICATE, SIGNED_ASSERTION, SIGNED_RESPONSE, sign_element, test_key,
+  };
+  use crate::config::proto::SamlProviderConfig;
+
+  const ACS_URL: &str = "https://sp.test/api/auth/v1/saml/idp/acs";
+  const SP_ENTITY_ID: &str = "https://sp.test/api/auth/v1/saml/idp/metadata";
+
+  fn provider() -> SamlProvider {
+    return SamlProvider {
+      name: "idp".to_string(),
+      config: SamlProviderConfig {
+        idp_entity_id: Some("https://idp.test".to_string()),
+        ..Default::default()
+      },
+      sp_entity_id: SP_ENTITY_ID.to_string(),
+      acs_url: ACS_URL.to_string(),
+    };
+  }
+
+  fn response(now: DateTime<Utc>, audience: &str) -> String {
+    let not_on_or_after = (now + Duration::minutes(5)).to_rfc3339();
+    let not_before = (now - Duration::minutes(1)).to_rfc3339();
+    return format!(
+      r#"<samlp:Response xmlns:samlp="{PROTOCOL_NS}" xmlns:saml="{ASSERTION_NS}" ID="_resp" Version="2.0" Destination="{ACS_URL}" InResponseTo="_req"><saml:Issuer>https://idp.test</saml:Issuer><samlp:Status><samlp:StatusCode Value="{STATUS_SUCCESS}"/></samlp:Status><saml:Assertion ID="_assertion" Version="2.0"><saml:Issuer>https://idp.test</saml:Issuer><saml:Subject><saml:NameID>user@idp.test</saml:NameID><saml:SubjectConfirmation Method="{BEARER}"><saml:SubjectConfirmationData InResponseTo="_req" Recipient="{ACS_URL}" NotOnOrAfter="{not_on_or_after}"/></saml:SubjectConfirmation></saml:Subject><saml:Conditions NotBefore="{not_before}" NotOnOrAfter="{not_on_or_after}"><saml:AudienceRestriction><saml:Audience>{audience}</saml:Audience></saml:AudienceRestriction></saml:Conditions><saml:AttributeStatement><saml:Attribute Name="mail"><saml:AttributeValue>mail@idp.test</saml:AttributeValue></saml:Attribute><saml:Attribute Name="uid"><saml:AttributeValue>jdoe</saml:AttributeValue></saml:Attribute></saml:AttributeStatement></saml:Assertion></samlp:Response>"#
+    );
+  }
+
+  #[test]
+  fn test_validate_response() {
+    let key = test_key();
+    let public_key = key.to_public_key();
+    let now = Utc::now();
+
+    let mut provider = provider();
+    let signed_assertion = sign_element(&response(now, SP_ENTITY_ID), "_assertion", &key);
+    let signed_response = sign_element(&response(now, SP_ENTITY_ID), "_resp", &key);
+
+    for xml in [&signed_assertion, &signed_response] {
+      assert_eq!(
+        validate_response(&provider, &public_key, xml, now).unwrap(),
+        SamlAssertion {
+          name_id: "user@idp.test".to_string(),
+          in_response_to: "_req".to_string(),
+          email: Some("mail@idp.test".to_string()),
+          username: None,
+        }
+      );
+    }
+
+    // Attribute mapping.
+    provider.config.attribute_mapping = Some(crate::config::proto::SamlAttributeMapping {
+      email: Some("missing".to_string()),
+      username: Some("uid".to_string()),
+    });
+    let assertion = validate_response(&provider, &public_key, &signed_assertion, now).unwrap();
+    assert_eq!(assertion.email, None);
+    assert_eq!(assertion.username.as_deref(), Some("jdoe"));
+
+    // Unsigned.
+    assert!(matches!(
+      validate_response(&provider, &public_key, &response(now, SP_ENTITY_ID), now),
+      Err(SamlResponseError::Signature(SignatureError::Missing))
+    ));
+
+    // Signed by someone else.
+    assert!(
+      validate_response(
+        &provider,
+        &test_key().to_public_key(),
+        &signed_assertion,
+        now
+      )
+      .is_err()
+    );
+
+    // Expired.
+    assert!(matches!(
+      validate_response(
+        &provider,
+        &public_key,
+        &signed_assertion,
+        now + Duration::minutes(10)
+      ),
+      Err(SamlResponseError::Invalid(_))
+    ));
+
+    // Wrong audience.
+    let other_audience = sign_element(&response(now, "https://other.test"), "_assertion", &key);
+    assert!(matches!(
+      validate_response(&provider, &public_key, &other_audience, now),
+      Err(SamlResponseError::Invalid("audience"))
+    ));
+
+    // Wrong issuer.
+    provider.config.idp_entity_id = Some("https://other-idp.test".to_string());
+    assert!(matches!(
+      validate_response(&provider, &public_key, &signed_assertion, now),
+      Err(SamlResponseError::Invalid("issuer"))
+    ));
+  }
+
+  #[test]
+  fn test_validate_interop_fixtures() {
+    let public_key = parse_certificate(IDP_CERTIFICATE).unwrap();
+    let now = DateTime::parse_from_rfc3339("2026-01-01T12:01:00Z")
+      .unwrap()
+      .with_timezone(&Utc);
+
+    let mut provider = provider();
+    assert_eq!(
+      validate_response(&provider, &public_key, SIGNED_RESPONSE, now).unwrap(),
+      SamlAssertion {
+        name_id: "user@idp.test".to_string(),
+        in_response_to: "_req".to_string(),
+        email: Some("mail@idp.test".to_string()),
+        username: None,
+      }
+    );
+
+    provider.config.attribute_mapping = Some(crate::config::proto::SamlAttributeMapping {
+      email: None,
+      username: Some("uid".to_string()),
+    });
+    assert_eq!(
+      validate_response(&provider, &public_key, SIGNED_ASSERTION, now).unwrap(),
+      SamlAssertion {
+        name_id: "jdoe".to_string(),
+        in_response_to: "_req".to_string(),
+        email: Some("jdoe@idp.test".to_string()),
+        username: Some("jdoe".to_string()),
+      }
+    );
+
+    // Expired.
+    assert!(matches!(
+      validate_response(
+        &provider,
+        &public_key,
+        SIGNED_ASSERTION,
+        now + Duration::minutes(10)
+      ),
+      Err(SamlResponseError::Invalid(_))
+    ));
+  }
+}
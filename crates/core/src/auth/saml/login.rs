@@ -0,0 +1,96 @@
+use axum::extract::{Path, Query, State};
+use axum::response::Redirect;
+use base64::prelude::*;
+use chrono::{SecondsFormat, Utc};
+use const_format::formatcp;
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use serde::Deserialize;
+use std::io::Write;
+use trailbase_sqlite::params;
+use utoipa::IntoParams;
+
+use crate::AppState;
+use crate::auth::AuthError;
+use crate::auth::saml::{SamlProvider, xml_escape};
+use crate::auth::util::validate_redirect;
+use crate::constants::{DEFAULT_SAML_REQUEST_TTL, SAML_REQUEST_TABLE};
+use crate::rand::random_alphanumeric;
+use crate::util::urlencode;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub(crate) struct SamlLoginParams {
+  pub redirect_uri: Option<String>,
+}
+
+/// Log in via external SAML identity provider.
+#[utoipa::path(
+  get,
+  path = "/{provider}/login",
+  tag = "saml",
+  params(SamlLoginParams),
+  responses(
+    (status = 303, description = "Redirect to identity provider."),
+    (status = 404, description = "Unknown provider."),
+  )
+)]
+pub(crate) async fn saml_login_handler(
+  State(state): State<AppState>,
+  Path(provider): Path<String>,
+  Query(query): Query<SamlLoginParams>,
+) -> Result<Redirect, AuthError> {
+  let provider = SamlProvider::lookup(&state, &provider)?;
+  let redirect_uri = validate_redirect(&state, query.redirect_uri)?;
+
+  let Some(ref idp_sso_url) = provider.config.idp_sso_url else {
+    return Err(AuthError::Internal("Missing SAML IdP SSO URL".into()));
+  };
+
+  // IDs must not start with a digit (xsd:ID).
+  let request_id = format!("_{}", random_alphanumeric(32));
+
+  const INSERT_REQUEST_QUERY: &str = formatcp!(
+    "INSERT INTO '{SAML_REQUEST_TABLE}' (id, provider, redirect_uri, expires) VALUES ($1, $2, $3, $4)"
+  );
+  state
+    .session_conn()
+    .execute(
+      INSERT_REQUEST_QUERY,
+      params!(
+        request_id.clone(),
+        provider.name.clone(),
+        redirect_uri,
+        (Utc::now() + DEFAULT_SAML_REQUEST_TTL).timestamp(),
+      ),
+    )
+    .await?;
+
+  let authn_request = build_authn_request(&provider, &request_id, idp_sso_url);
+
+  // HTTP-Redirect binding: raw deflate, base64 and url encoding.
+  let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+  encoder
+    .write_all(authn_request.as_bytes())
+    .map_err(|err| AuthError::Internal(err.into()))?;
+  let deflated = encoder
+    .finish()
+    .map_err(|err| AuthError::Internal(err.into()))?;
+
+  let separator = if idp_sso_url.contains('?') { '&' } else { '?' };
+  return Ok(Redirect::to(&format!(
+    "{idp_sso_url}{separator}SAMLRequest={request}&RelayState={relay_state}",
+    request = urlencode(&BASE64_STANDARD.encode(deflated)),
+    relay_state = urlencode(&request_id),
+  )));
+}
+
+fn build_authn_request(provider: &SamlProvider, request_id: &str, idp_sso_url: &str) -> String {
+  return format!(
+    r#"<samlp:AuthnRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" ID="{id}" Version="2.0" IssueInstant="{instant}" Destination="{destination}" ProtocolBinding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" AssertionConsumerServiceURL="{acs_url}"><saml:Issuer>{issuer}</saml:Issuer><samlp:NameIDPolicy AllowCreate="true"/></samlp:AuthnRequest>"#,
+    id = request_id,
+    instant = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    destination = xml_escape(idp_sso_url),
+    acs_url = xml_escape(&provider.acs_url),
+    issuer = xml_escape(&provider.sp_entity_id),
+  );
+}
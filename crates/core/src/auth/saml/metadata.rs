@@ -0,0 +1,45 @@
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+
+use crate::AppState;
+use crate::auth::AuthError;
+use crate::auth::saml::{SamlProvider, xml_escape};
+
+/// SAML service provider metadata to be registered with the identity provider.
+#[utoipa::path(
+  get,
+  path = "/{provider}/metadata",
+  tag = "saml",
+  responses(
+    (status = 200, description = "SP metadata XML."),
+    (status = 404, description = "Unknown provider."),
+  )
+)]
+pub(crate) async fn saml_metadata_handler(
+  State(state): State<AppState>,
+  Path(provider): Path<String>,
+) -> Result<Response, AuthError> {
+  let provider = SamlProvider::lookup(&state, &provider)?;
+
+  let metadata = format!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<md:EntityDescriptor xmlns:md="urn:oasis:names:tc:SAML:2.0:metadata" entityID="{entity_id}">
+  <md:SPSSODescriptor AuthnRequestsSigned="false" WantAssertionsSigned="true" protocolSupportEnumeration="urn:oasis:names:tc:SAML:2.0:protocol">
+    <md:NameIDFormat>urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress</md:NameIDFormat>
+    <md:AssertionConsumerService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" Location="{acs_url}" index="0" isDefault="true"/>
+  </md:SPSSODescriptor>
+</md:EntityDescriptor>
+"#,
+    entity_id = xml_escape(&provider.sp_entity_id),
+    acs_url = xml_escape(&provider.acs_url),
+  );
+
+  return Ok(
+    (
+      [(header::CONTENT_TYPE, "application/samlmetadata+xml")],
+      metadata,
+    )
+      .into_response(),
+  );
+}
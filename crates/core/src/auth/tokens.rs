@@ -11,11 +11,12 @@ use crate::app_state::AppState;
 use crate::auth::AuthError;
 use crate::auth::jwt::AuthTokenClaims;
 use crate::auth::user::DbUser;
-use crate::auth::util::new_cookie;
+use crate::auth::util::{get_user_roles, get_user_tenant, new_cookie};
 use crate::constants::{
   COOKIE_AUTH_TOKEN, COOKIE_REFRESH_TOKEN, HEADER_REFRESH_TOKEN, REFRESH_TOKEN_LENGTH,
   SESSION_TABLE, USER_TABLE,
 };
+use crate::extract::ip::extract_ip_from_parts;
 use crate::rand::random_alphanumeric;
 use crate::util::get_header;
 
@@ -159,6 +160,27 @@ fn extract_tokens_from_cookies(cookies: &Cookies) -> Option<CookieTokens> {
   };
 }
 
+/// Client metadata persisted alongside a session, helping users to tell their devices apart.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SessionMetadata {
+  pub user_agent: Option<String>,
+  pub client_ip: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for SessionMetadata
+where
+  S: Send + Sync,
+{
+  type Rejection = std::convert::Infallible;
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    return Ok(SessionMetadata {
+      user_agent: get_header(&parts.headers, header::USER_AGENT).map(|ua| ua.to_string()),
+      client_ip: extract_ip_from_parts(&parts.headers, &parts.extensions).map(|ip| ip.to_string()),
+    });
+  }
+}
+
 /// Only difference to Tokens above, refresh token presence is guaranteed.
 pub struct FreshTokens {
   pub auth_token_claims: AuthTokenClaims,
@@ -166,8 +188,10 @@ pub struct FreshTokens {
 }
 
 pub(crate) async fn mint_new_tokens(
+  user_conn: &Connection,
   session_conn: &Connection,
   db_user: &DbUser,
+  session: &SessionMetadata,
   auth_token_ttl: &Duration,
   refresh_token_ttl: &Duration,
 ) -> Result<FreshTokens, AuthError> {
@@ -177,12 +201,18 @@ pub(crate) async fn mint_new_tokens(
     ));
   }
 
-  let claims = AuthTokenClaims::new(db_user, auth_token_ttl);
+  let roles = get_user_roles(user_conn, &db_user.id).await?;
+  let mut claims = AuthTokenClaims::new(db_user, roles, auth_token_ttl);
+  claims.tenant = get_user_tenant(user_conn, &db_user.id).await?;
 
   // Unlike JWT auth tokens, refresh tokens are opaque.
   let refresh_token = random_alphanumeric(REFRESH_TOKEN_LENGTH);
-  const QUERY: &str =
-    formatcp!("INSERT INTO '{SESSION_TABLE}' (user, refresh_token, expires) VALUES ($1, $2, $3)");
+  const QUERY: &str = formatcp!(
+    "\
+      INSERT INTO '{SESSION_TABLE}' (user, refresh_token, expires, user_agent, client_ip) \
+      VALUES ($1, $2, $3, $4, $5) \
+    "
+  );
 
   session_conn
     .execute(
@@ -191,6 +221,8 @@ pub(crate) async fn mint_new_tokens(
         db_user.id,
         refresh_token.clone(),
         (chrono::Utc::now() + *refresh_token_ttl).timestamp(),
+        session.user_agent.clone(),
+        session.client_ip.clone(),
       ),
     )
     .await?;
@@ -207,18 +239,20 @@ pub(crate) async fn reauth_with_refresh_token(
 ) -> Result<(AuthTokenClaims, chrono::Duration), AuthError> {
   let (auth_token_ttl, _refresh_token_ttl) = state.access_config(|c| c.auth.token_ttls());
 
+  // Refreshing also marks the session as recently used.
   const SESSION_QUERY: &str = formatcp!(
     "\
-      SELECT user \
-      FROM '{SESSION_TABLE}' \
+      UPDATE '{SESSION_TABLE}' \
+      SET last_seen = UNIXEPOCH() \
       WHERE \
         refresh_token = $1 AND expires > UNIXEPOCH() \
+      RETURNING user \
     "
   );
 
   let Some(user_id) = state
     .session_conn()
-    .read_query_row_get::<[u8; 16]>(SESSION_QUERY, params!(refresh_token), 0)
+    .write_query_row_get::<[u8; 16]>(SESSION_QUERY, params!(refresh_token), 0)
     .await?
   else {
     // Row not found case, typically expected in one of 4 cases:
@@ -255,8 +289,10 @@ pub(crate) async fn reauth_with_refresh_token(
     "unverified user, should have been caught by above query"
   );
 
-  return Ok((
-    AuthTokenClaims::new(&db_user, &auth_token_ttl),
-    auth_token_ttl,
-  ));
+  // Roles are re-read on refresh, thus role changes take effect with the next refresh.
+  let roles = get_user_roles(state.user_conn(), &db_user.id).await?;
+  let mut claims = AuthTokenClaims::new(&db_user, roles, &auth_token_ttl);
+  claims.tenant = get_user_tenant(state.user_conn(), &db_user.id).await?;
+
+  return Ok((claims, auth_token_ttl));
 }
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::auth::AuthError;
+use crate::auth::api_key::{ApiKeyScope, extract_api_key_from_headers, lookup_api_key};
 use crate::auth::jwt::AuthTokenClaims;
 use crate::auth::tokens::extract_tokens_from_request_parts;
 use crate::{app_state::AppState, util::b64_to_uuid};
@@ -39,6 +40,11 @@ impl DbUser {
     return uuid;
   }
 
+  /// Anonymous users have neither a password nor an external OAuth identity.
+  pub(crate) fn is_anonymous(&self) -> bool {
+    return self.password_hash.is_none() && self.provider_id == 0;
+  }
+
   #[cfg(test)]
   pub fn new_for_test(email: &str, password: &str) -> Self {
     let now = std::time::SystemTime::now();
@@ -81,6 +87,15 @@ pub struct User {
 
   /// The "expected" CSRF token as included in the auth token claims [User] was constructed from.
   pub csrf_token: String,
+
+  /// Names of the user's roles as included in the auth token claims.
+  #[serde(default)]
+  pub roles: Vec<String>,
+
+  /// Set when authenticated via API key rather than as an actual user. Restricts access to the
+  /// key's scope.
+  #[serde(skip)]
+  pub api_key: Option<ApiKeyScope>,
 }
 
 impl PartialEq for User {
@@ -101,9 +116,38 @@ impl User {
       username: claims.username,
       uuid,
       csrf_token: claims.csrf_token,
+      roles: claims.roles,
+      api_key: None,
     });
   }
 
+  /// Construct a synthetic principal for an API key, which isn't backed by a `_user` entry.
+  pub(crate) fn from_api_key(key_id: Uuid, scope: ApiKeyScope) -> Self {
+    return Self {
+      id: crate::util::uuid_to_b64(&key_id),
+      email: None,
+      username: None,
+      uuid: key_id,
+      // API keys aren't subject to CSRF, still don't leave an empty, i.e. guessable, token.
+      csrf_token: crate::rand::random_alphanumeric(20),
+      roles: vec![],
+      api_key: Some(scope),
+    };
+  }
+
+  async fn from_request_parts_impl(state: &AppState, parts: &Parts) -> Result<Self, AuthError> {
+    if let Some(secret) = extract_api_key_from_headers(&parts.headers) {
+      let (key_id, scope) = lookup_api_key(state, secret).await?;
+      return Ok(Self::from_api_key(key_id, scope));
+    }
+
+    return User::from_token_claims(
+      extract_tokens_from_request_parts(state, parts)
+        .await?
+        .auth_token_claims,
+    );
+  }
+
   #[cfg(test)]
   pub(crate) fn from_auth_token(state: &AppState, auth_token: &str) -> Option<Self> {
     Some(
@@ -124,6 +168,8 @@ impl User {
       username: username.map(|s| s.to_string()),
       uuid: user_id,
       csrf_token: crate::rand::random_alphanumeric(20),
+      roles: vec![],
+      api_key: None,
     };
   }
 }
@@ -136,11 +182,7 @@ where
   type Rejection = AuthError;
 
   async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-    let user = User::from_token_claims(
-      extract_tokens_from_request_parts(&AppState::from_ref(state), parts)
-        .await?
-        .auth_token_claims,
-    )?;
+    let user = User::from_request_parts_impl(&AppState::from_ref(state), parts).await?;
 
     tracing::Span::current().record("user_id", user.uuid.to_u128_le());
 
@@ -159,7 +201,18 @@ where
     parts: &mut Parts,
     state: &S,
   ) -> Result<Option<Self>, Self::Rejection> {
-    if let Ok(tokens) = extract_tokens_from_request_parts(&AppState::from_ref(state), parts).await {
+    let state = AppState::from_ref(state);
+    if let Some(secret) = extract_api_key_from_headers(&parts.headers) {
+      // Like for invalid auth tokens, invalid keys fall back to unauthenticated access.
+      return Ok(
+        lookup_api_key(&state, secret)
+          .await
+          .ok()
+          .map(|(key_id, scope)| User::from_api_key(key_id, scope)),
+      );
+    }
+
+    if let Ok(tokens) = extract_tokens_from_request_parts(&state, parts).await {
       let user = User::from_token_claims(tokens.auth_token_claims)?;
 
       tracing::Span::current().record("user_id", user.uuid.to_u128_le());
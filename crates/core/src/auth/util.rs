@@ -15,7 +15,8 @@ use crate::AppState;
 use crate::auth::AuthError;
 use crate::auth::user::DbUser;
 use crate::constants::{
-  COOKIE_AUTH_TOKEN, COOKIE_OAUTH_STATE, COOKIE_REFRESH_TOKEN, SESSION_TABLE, USER_TABLE,
+  COOKIE_AUTH_TOKEN, COOKIE_OAUTH_STATE, COOKIE_REFRESH_TOKEN, SESSION_TABLE, TENANT_USER_TABLE,
+  USER_ROLES_TABLE, USER_TABLE,
 };
 
 /// Strips plus-addressing, e.g. foo+spam@test.org becomes foo@test.org.
@@ -183,13 +184,21 @@ pub async fn login_with_password_for_test(
   })?;
   let user_id = db_user.uuid();
 
-  // Validates password and rate limits attempts.
-  crate::auth::password::check_user_password(&db_user, password, state.demo_mode())?;
+  // Validates password and locks out repeated failures.
+  crate::auth::password::check_user_password(
+    &db_user,
+    password,
+    None,
+    state.auth_options().lockout_options(),
+    state.demo_mode(),
+  )?;
 
   let (auth_token_ttl, refresh_token_ttl) = state.access_config(|c| c.auth.token_ttls());
   let tokens = crate::auth::tokens::mint_new_tokens(
+    state.user_conn(),
     state.session_conn(),
     &db_user,
+    &crate::auth::tokens::SessionMetadata::default(),
     &auth_token_ttl,
     &refresh_token_ttl,
   )
@@ -360,6 +369,36 @@ pub async fn get_user_by_id(
   return db_user.ok_or_else(|| AuthError::NotFound);
 }
 
+/// Names of the roles assigned to the given user.
+pub(crate) async fn get_user_roles(
+  user_conn: &trailbase_sqlite::Connection,
+  user_id: &[u8; 16],
+) -> Result<Vec<String>, AuthError> {
+  const QUERY: &str =
+    formatcp!(r#"SELECT role FROM "{USER_ROLES_TABLE}" WHERE user = $1 ORDER BY role"#);
+
+  return user_conn
+    .read_query_rows(QUERY, params!(*user_id))
+    .await?
+    .into_iter()
+    .map(|row| Ok(row.get::<String>(0)?))
+    .collect();
+}
+
+/// Name of the tenant the given user is assigned to, if any.
+pub(crate) async fn get_user_tenant(
+  user_conn: &trailbase_sqlite::Connection,
+  user_id: &[u8; 16],
+) -> Result<Option<String>, AuthError> {
+  const QUERY: &str = formatcp!(r#"SELECT tenant FROM "{TENANT_USER_TABLE}" WHERE user = $1"#);
+
+  return Ok(
+    user_conn
+      .read_query_row_get::<String>(QUERY, params!(*user_id), 0)
+      .await?,
+  );
+}
+
 pub async fn user_exists(state: &AppState, email: &str) -> bool {
   const QUERY: &str = formatcp!(r#"SELECT EXISTS(SELECT 1 FROM "{USER_TABLE}" WHERE email = $1)"#);
 
@@ -0,0 +1,413 @@
+use const_format::formatcp;
+use hmac::{Hmac, Mac};
+use log::*;
+use sha2::Sha256;
+use std::sync::LazyLock;
+use std::time::Duration;
+use trailbase_sqlite::params;
+
+use crate::app_state::AppState;
+use crate::auth::AuthError;
+use crate::auth::user::DbUser;
+use crate::constants::WEBHOOK_DELIVERIES_TABLE;
+use crate::util::id_to_b64;
+
+/// Auth lifecycle events, which can be delivered to webhooks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AuthEvent {
+  UserCreated,
+  LoginFailed,
+  PasswordReset,
+}
+
+impl AuthEvent {
+  pub(crate) const ALL: [AuthEvent; 3] = [
+    AuthEvent::UserCreated,
+    AuthEvent::LoginFailed,
+    AuthEvent::PasswordReset,
+  ];
+
+  pub(crate) fn name(self) -> &'static str {
+    return match self {
+      Self::UserCreated => "user.created",
+      Self::LoginFailed => "login.failed",
+      Self::PasswordReset => "password.reset",
+    };
+  }
+}
+
+#[repr(i64)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DeliveryStatus {
+  Pending = 0,
+  Delivered = 1,
+  Failed = 2,
+}
+
+const MAX_ATTEMPTS: i64 = 5;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// HACK: Speed up retries in tests.
+#[cfg(test)]
+const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+
+#[cfg(not(test))]
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+  return reqwest::Client::builder()
+    .timeout(TIMEOUT)
+    .build()
+    .unwrap_or_default();
+});
+
+/// Emits an auth event to all subscribed webhooks and counts it for metrics.
+///
+/// Deliveries are persisted and happen in the background, i.e. they never fail or delay the
+/// triggering request.
+pub(crate) fn emit_auth_event(state: &AppState, event: AuthEvent, data: serde_json::Value) {
+  crate::metrics::record_auth_event(event);
+
+  let webhooks: Vec<String> = state.access_config(|c| {
+    return c
+      .auth
+      .webhooks
+      .iter()
+      .filter(|(_, webhook)| {
+        webhook.events.is_empty() || webhook.events.iter().any(|e| e == event.name())
+      })
+      .map(|(name, _)| name.clone())
+      .collect();
+  });
+  if webhooks.is_empty() {
+    return;
+  }
+
+  let payload = serde_json::json!({
+    "event": event.name(),
+    "timestamp": chrono::Utc::now().timestamp(),
+    "data": data,
+  })
+  .to_string();
+
+  let state = state.clone();
+  tokio::spawn(async move {
+    for webhook in webhooks {
+      match insert_delivery(&state, &webhook, event, &payload).await {
+        Ok(id) => {
+          tokio::spawn(deliver_with_retries(state.clone(), id));
+        }
+        Err(err) => {
+          warn!(
+            "Failed to persist '{}' delivery for {webhook}: {err}",
+            event.name()
+          );
+        }
+      }
+    }
+  });
+}
+
+/// Emits `user.created` for a newly inserted user, where `method` describes how the user was
+/// created, e.g. "register".
+pub(crate) fn emit_user_created(state: &AppState, user: &DbUser, method: &str) {
+  emit_auth_event(
+    state,
+    AuthEvent::UserCreated,
+    serde_json::json!({
+      "id": id_to_b64(&user.id),
+      "email": user.email,
+      "username": user.username,
+      "method": method,
+    }),
+  );
+}
+
+/// Resets a delivery, e.g. a failed one, and delivers it again in the background.
+pub(crate) async fn redeliver(state: &AppState, id: i64) -> Result<(), AuthError> {
+  const QUERY: &str = formatcp!(
+    "UPDATE '{WEBHOOK_DELIVERIES_TABLE}' SET status = $2, attempts = 0, updated = UNIXEPOCH() WHERE id = $1"
+  );
+
+  let rows_affected = state
+    .user_conn()
+    .execute(QUERY, params!(id, DeliveryStatus::Pending as i64))
+    .await?;
+  if rows_affected == 0 {
+    return Err(AuthError::NotFound);
+  }
+
+  tokio::spawn(deliver_with_retries(state.clone(), id));
+
+  return Ok(());
+}
+
+async fn insert_delivery(
+  state: &AppState,
+  webhook: &str,
+  event: AuthEvent,
+  payload: &str,
+) -> Result<i64, AuthError> {
+  const QUERY: &str = formatcp!(
+    "INSERT INTO '{WEBHOOK_DELIVERIES_TABLE}' (webhook, event, payload) VALUES ($1, $2, $3) RETURNING id"
+  );
+
+  return state
+    .user_conn()
+    .write_query_row_get::<i64>(
+      QUERY,
+      params!(
+        webhook.to_string(),
+        event.name().to_string(),
+        payload.to_string()
+      ),
+      0,
+    )
+    .await?
+    .ok_or_else(|| AuthError::Internal("insert failed".into()));
+}
+
+/// Attempts delivery with exponential backoff until it either succeeds or runs out of attempts.
+pub(crate) async fn deliver_with_retries(state: AppState, id: i64) {
+  let mut backoff = INITIAL_BACKOFF;
+  loop {
+    match attempt_delivery(&state, id).await {
+      Ok(DeliveryStatus::Pending) => {
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+      }
+      Ok(_) => return,
+      Err(err) => {
+        warn!("Webhook delivery {id} failed: {err}");
+        return;
+      }
+    }
+  }
+}
+
+/// Attempts a single delivery and persists the outcome.
+async fn attempt_delivery(state: &AppState, id: i64) -> Result<DeliveryStatus, AuthError> {
+  const SELECT_QUERY: &str = formatcp!(
+    "SELECT webhook, event, payload, attempts FROM '{WEBHOOK_DELIVERIES_TABLE}' WHERE id = $1"
+  );
+
+  let Some(row) = state
+    .user_conn()
+    .read_query_row(SELECT_QUERY, params!(id))
+    .await?
+  else {
+    return Err(AuthError::NotFound);
+  };
+  let webhook: String = row.get(0)?;
+  let event: String = row.get(1)?;
+  let payload: String = row.get(2)?;
+  let attempts = row.get::<i64>(3)? + 1;
+
+  let (status, error) = match state.access_config(|c| c.auth.webhooks.get(&webhook).cloned()) {
+    Some(config) => match post_signed(
+      config.url.as_deref(),
+      config.secret.as_deref(),
+      &event,
+      &payload,
+    )
+    .await
+    {
+      Ok(()) => (DeliveryStatus::Delivered, None),
+      Err(err) if attempts < MAX_ATTEMPTS => (DeliveryStatus::Pending, Some(err)),
+      Err(err) => (DeliveryStatus::Failed, Some(err)),
+    },
+    None => (
+      DeliveryStatus::Failed,
+      Some(format!("Webhook '{webhook}' no longer configured")),
+    ),
+  };
+
+  if status == DeliveryStatus::Failed {
+    warn!("Giving up on webhook delivery {id} to '{webhook}' after {attempts} attempts");
+  }
+
+  const UPDATE_QUERY: &str = formatcp!(
+    "\
+      UPDATE '{WEBHOOK_DELIVERIES_TABLE}' \
+      SET status = $2, attempts = $3, last_error = $4, updated = UNIXEPOCH() \
+      WHERE id = $1 \
+    "
+  );
+  state
+    .user_conn()
+    .execute(UPDATE_QUERY, params!(id, status as i64, attempts, error))
+    .await?;
+
+  return Ok(status);
+}
+
+/// POSTs the JSON payload to the given URL and, if a secret is given, signs it.
+pub(crate) async fn post_signed(
+  url: Option<&str>,
+  secret: Option<&str>,
+  event: &str,
+  payload: &str,
+) -> Result<(), String> {
+  let Some(url) = url else {
+    return Err("Missing URL".to_string());
+  };
+
+  let mut request = CLIENT
+    .post(url)
+    .header(reqwest::header::CONTENT_TYPE, "application/json")
+    .header("X-Webhook-Event", event);
+  if let Some(secret) = secret {
+    request = request.header(
+      "X-Webhook-Signature",
+      sign_payload(secret, chrono::Utc::now().timestamp(), payload),
+    );
+  }
+
+  request
+    .body(payload.to_string())
+    .send()
+    .await
+    .and_then(|response| response.error_for_status())
+    .map_err(|err| err.to_string())?;
+
+  return Ok(());
+}
+
+/// Signs `<timestamp>.<payload>`. Including the timestamp lets receivers reject replays.
+fn sign_payload(secret: &str, timestamp: i64, payload: &str) -> String {
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+  mac.update(timestamp.to_string().as_bytes());
+  mac.update(b".");
+  mac.update(payload.as_bytes());
+
+  let signature: String = mac
+    .finalize()
+    .into_bytes()
+    .iter()
+    .map(|b| format!("{b:02x}"))
+    .collect();
+
+  return format!("t={timestamp},v1={signature}");
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::{HeaderMap, StatusCode};
+  use axum::{Router, extract::State, routing::post};
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  use super::*;
+  use crate::app_state::{TestStateOptions, test_state};
+  use crate::config::proto::AuthWebhookConfig;
+
+  #[derive(Clone, Default)]
+  struct Receiver {
+    calls: Arc<AtomicUsize>,
+    received: Arc<parking_lot::Mutex<Vec<(HeaderMap, String)>>>,
+  }
+
+  async fn receive(
+    State(receiver): State<Receiver>,
+    headers: HeaderMap,
+    body: String,
+  ) -> StatusCode {
+    // Fail the first attempt to exercise retries.
+    if receiver.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+      return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    receiver.received.lock().push((headers, body));
+    return StatusCode::OK;
+  }
+
+  async fn delivery_status(state: &AppState, id: i64) -> (i64, i64) {
+    let row = state
+      .user_conn()
+      .read_query_row(
+        format!("SELECT status, attempts FROM '{WEBHOOK_DELIVERIES_TABLE}' WHERE id = $1"),
+        params!(id),
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    return (row.get(0).unwrap(), row.get(1).unwrap());
+  }
+
+  #[tokio::test]
+  async fn test_webhook_delivery() {
+    let receiver = Receiver::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = Router::new()
+      .route("/hook", post(receive))
+      .with_state(receiver.clone());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let mut config = crate::app_state::test_config();
+    config.auth.webhooks.insert(
+      "test".to_string(),
+      AuthWebhookConfig {
+        url: Some(format!("http://{addr}/hook")),
+        secret: Some("secret".to_string()),
+        events: vec![AuthEvent::UserCreated.name().to_string()],
+      },
+    );
+    config.auth.webhooks.insert(
+      "unreachable".to_string(),
+      AuthWebhookConfig {
+        // Port 9 (discard) is expected to refuse connections.
+        url: Some("http://127.0.0.1:9/hook".to_string()),
+        ..Default::default()
+      },
+    );
+
+    let state = test_state(Some(TestStateOptions {
+      config: Some(config),
+      ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    let payload = serde_json::json!({"event": "user.created"}).to_string();
+    let id = insert_delivery(&state, "test", AuthEvent::UserCreated, &payload)
+      .await
+      .unwrap();
+    deliver_with_retries(state.clone(), id).await;
+
+    assert_eq!(
+      delivery_status(&state, id).await,
+      (DeliveryStatus::Delivered as i64, 2)
+    );
+
+    let (headers, body) = receiver.received.lock().pop().unwrap();
+    assert_eq!(body, payload);
+    assert_eq!(headers.get("X-Webhook-Event").unwrap(), "user.created");
+
+    let signature = headers
+      .get("X-Webhook-Signature")
+      .unwrap()
+      .to_str()
+      .unwrap();
+    let timestamp: i64 = signature
+      .strip_prefix("t=")
+      .and_then(|s| s.split(',').next())
+      .unwrap()
+      .parse()
+      .unwrap();
+    assert_eq!(signature, sign_payload("secret", timestamp, &payload));
+    assert_ne!(signature, sign_payload("other", timestamp, &payload));
+
+    // Unreachable endpoints fail after exhausting all attempts and can be redelivered.
+    let id = insert_delivery(&state, "unreachable", AuthEvent::LoginFailed, &payload)
+      .await
+      .unwrap();
+    deliver_with_retries(state.clone(), id).await;
+    assert_eq!(
+      delivery_status(&state, id).await,
+      (DeliveryStatus::Failed as i64, MAX_ATTEMPTS)
+    );
+
+    redeliver(&state, id).await.unwrap();
+    assert!(redeliver(&state, id + 100).await.is_err());
+  }
+}
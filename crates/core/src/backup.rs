@@ -0,0 +1,362 @@
+//! Online backups of the main and logs databases.
+//!
+//! Backups are written to `<traildepot>/backups/<db>-<timestamp>.db` and, if configured, uploaded
+//! to the object store under `backups/`. Only the most recent backups are retained.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures_util::StreamExt;
+use log::*;
+use object_store::ObjectStore;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+use trailbase_sqlite::Connection;
+use ts_rs::TS;
+
+use crate::DataDir;
+use crate::app_state::AppState;
+use crate::config::proto::BackupConfig;
+use crate::metrics::{ObjectStoreOp, record_object_store_op};
+use crate::migrations::apply_main_migrations;
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+  #[error("IO: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("TrailbaseSqlite: {0}")]
+  TrailbaseSqlite(#[from] trailbase_sqlite::Error),
+  #[error("ObjectStore: {0}")]
+  ObjectStore(#[from] object_store::Error),
+  #[error("Migration: {0}")]
+  Migration(#[from] trailbase_refinery::Error),
+  #[error("Connection: {0}")]
+  Connection(#[from] crate::connection::ConnectionError),
+  #[error("Not found: {0}")]
+  NotFound(String),
+}
+
+const DEFAULT_RETENTION: usize = 7;
+const OBJECT_STORE_PREFIX: &str = "backups";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Databases included in backups.
+const DATABASES: [&str; 2] = ["main", "logs"];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RestorePoint {
+  /// Name of the backup, e.g. "main-20250101T000000Z.db".
+  pub name: String,
+  /// Backed up database, i.e. "main" or "logs".
+  pub database: String,
+  /// Unix timestamp in seconds of when the backup was taken.
+  pub created: i64,
+  /// Whether the backup is available in the local backup directory.
+  pub local: bool,
+  /// Whether the backup is available in the object store.
+  pub remote: bool,
+}
+
+/// Backs up the main and logs databases, uploads the backups if configured and applies the
+/// retention policy. Returns the names of the new backups.
+pub(crate) async fn create_backups(
+  data_dir: &DataDir,
+  main_conn: &Connection,
+  logs_conn: &Connection,
+  object_store: &Arc<dyn ObjectStore>,
+  config: &BackupConfig,
+) -> Result<Vec<String>, BackupError> {
+  let mut names = vec![];
+  for (database, conn) in [("main", main_conn), ("logs", logs_conn)] {
+    let name = backup_local(data_dir, database, conn).await?;
+
+    if config.upload.unwrap_or(false) {
+      let contents = tokio::fs::read(data_dir.backup_path().join(&name)).await?;
+      record_object_store_op(ObjectStoreOp::Put);
+      object_store
+        .put(&object_path(&name), contents.into())
+        .await?;
+    }
+
+    names.push(name);
+  }
+
+  apply_retention(
+    data_dir,
+    object_store,
+    config
+      .retention
+      .map_or(DEFAULT_RETENTION, |r| r as usize)
+      .max(1),
+  )
+  .await?;
+
+  return Ok(names);
+}
+
+/// Writes a consistent snapshot of the given database to the local backup directory.
+async fn backup_local(
+  data_dir: &DataDir,
+  database: &str,
+  conn: &Connection,
+) -> Result<String, BackupError> {
+  let dir = data_dir.backup_path();
+  tokio::fs::create_dir_all(&dir).await?;
+
+  // Never overwrite existing backups, e.g. when backing up twice within a second.
+  let mut timestamp = Utc::now();
+  loop {
+    let name = format!("{database}-{}.db", timestamp.format(TIMESTAMP_FORMAT));
+    let path = dir.join(&name);
+    if !tokio::fs::try_exists(&path).await? {
+      conn.backup(&path).await?;
+      return Ok(name);
+    }
+    timestamp += chrono::Duration::seconds(1);
+  }
+}
+
+/// Lists available backups, local and remote, newest first.
+pub async fn list_backups(state: &AppState) -> Result<Vec<RestorePoint>, BackupError> {
+  return list_restore_points(state.data_dir(), &state.objectstore()).await;
+}
+
+async fn list_restore_points(
+  data_dir: &DataDir,
+  object_store: &Arc<dyn ObjectStore>,
+) -> Result<Vec<RestorePoint>, BackupError> {
+  let mut points: Vec<RestorePoint> = vec![];
+
+  let dir = data_dir.backup_path();
+  if tokio::fs::try_exists(&dir).await? {
+    let mut entries = tokio::fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+      if let Some(mut point) = entry.file_name().to_str().and_then(parse_name) {
+        point.local = true;
+        points.push(point);
+      }
+    }
+  }
+
+  let mut objects = object_store.list(Some(&object_store::path::Path::from(OBJECT_STORE_PREFIX)));
+  while let Some(meta) = objects.next().await {
+    let meta = meta?;
+    let Some(point) = meta.location.filename().and_then(parse_name) else {
+      continue;
+    };
+
+    match points.iter_mut().find(|p| p.name == point.name) {
+      Some(existing) => existing.remote = true,
+      None => points.push(RestorePoint {
+        remote: true,
+        ..point
+      }),
+    }
+  }
+
+  points.sort_by(|a, b| b.created.cmp(&a.created).then(a.name.cmp(&b.name)));
+  return Ok(points);
+}
+
+/// Restores the named backup, downloading it from the object store if not available locally.
+///
+/// The current state of the database is backed up first. Returns the name of that backup.
+pub async fn restore_backup(state: &AppState, name: &str) -> Result<String, BackupError> {
+  let Some(point) = parse_name(name) else {
+    return Err(BackupError::NotFound(name.to_string()));
+  };
+
+  let local = state.data_dir().backup_path().join(&point.name);
+  let (path, downloaded) = if tokio::fs::try_exists(&local).await? {
+    (local, false)
+  } else {
+    record_object_store_op(ObjectStoreOp::Get);
+    let contents = match state.objectstore().get(&object_path(&point.name)).await {
+      Ok(result) => result.bytes().await?,
+      Err(object_store::Error::NotFound { .. }) => {
+        return Err(BackupError::NotFound(name.to_string()));
+      }
+      Err(err) => return Err(err.into()),
+    };
+
+    let path = state
+      .data_dir()
+      .backup_path()
+      .join(format!(".download-{}", point.name));
+    tokio::fs::write(&path, contents).await?;
+    (path, true)
+  };
+
+  let result = match point.database.as_str() {
+    "main" => restore_main(state, &path).await,
+    _ => restore_logs(state, &path).await,
+  };
+
+  if downloaded && let Err(err) = tokio::fs::remove_file(&path).await {
+    warn!("Failed to remove downloaded backup {path:?}: {err}");
+  }
+
+  return result;
+}
+
+/// Replaces the main database with the database at the given path.
+///
+/// The current database is backed up first. Afterwards migrations are applied, e.g. to bring an
+/// older snapshot up to date, and the schema metadata as well as config-dependent state, e.g.
+/// record APIs, are rebuilt.
+pub(crate) async fn restore_main(state: &AppState, path: &Path) -> Result<String, BackupError> {
+  let conn = state.conn();
+  let pre_restore_backup = backup_local(state.data_dir(), "main", conn).await?;
+
+  conn.restore(path).await?;
+  info!("Restored main database. Previous state backed up as: {pre_restore_backup}");
+
+  apply_main_migrations(conn, Some(state.data_dir().migrations_path())).await?;
+  state.rebuild_connection_metadata().await?;
+
+  return Ok(pre_restore_backup);
+}
+
+async fn restore_logs(state: &AppState, path: &Path) -> Result<String, BackupError> {
+  let conn = state.logs_conn();
+  let pre_restore_backup = backup_local(state.data_dir(), "logs", conn).await?;
+
+  conn.restore(path).await?;
+  info!("Restored logs database. Previous state backed up as: {pre_restore_backup}");
+
+  return Ok(pre_restore_backup);
+}
+
+/// Deletes all but the `retention` most recent backups per database, locally and remotely.
+async fn apply_retention(
+  data_dir: &DataDir,
+  object_store: &Arc<dyn ObjectStore>,
+  retention: usize,
+) -> Result<(), BackupError> {
+  let points = list_restore_points(data_dir, object_store).await?;
+
+  for database in DATABASES {
+    for point in points
+      .iter()
+      .filter(|p| p.database == database)
+      .skip(retention)
+    {
+      if point.local {
+        tokio::fs::remove_file(data_dir.backup_path().join(&point.name)).await?;
+      }
+      if point.remote {
+        record_object_store_op(ObjectStoreOp::Delete);
+        object_store.delete(&object_path(&point.name)).await?;
+      }
+      debug!("Deleted expired backup: {}", point.name);
+    }
+  }
+
+  return Ok(());
+}
+
+fn object_path(name: &str) -> object_store::path::Path {
+  return object_store::path::Path::from_iter([OBJECT_STORE_PREFIX, name]);
+}
+
+/// Parses backup names, i.e. "<db>-<timestamp>.db". Other files are ignored.
+fn parse_name(name: &str) -> Option<RestorePoint> {
+  let (database, timestamp) = name.strip_suffix(".db")?.split_once('-')?;
+  if !DATABASES.contains(&database) {
+    return None;
+  }
+
+  let created: DateTime<Utc> = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+    .ok()?
+    .and_utc();
+
+  return Some(RestorePoint {
+    name: name.to_string(),
+    database: database.to_string(),
+    created: created.timestamp(),
+    local: false,
+    remote: false,
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[test]
+  fn test_parse_name() {
+    let point = parse_name("main-20250102T030405Z.db").unwrap();
+    assert_eq!(point.database, "main");
+    assert_eq!(
+      point.created,
+      chrono::DateTime::parse_from_rfc3339("2025-01-02T03:04:05Z")
+        .unwrap()
+        .timestamp()
+    );
+
+    assert!(parse_name("backup.db").is_none());
+    assert!(parse_name("other-20250102T030405Z.db").is_none());
+    assert!(parse_name(".export-0192.db").is_none());
+  }
+
+  #[tokio::test]
+  async fn test_backup_and_restore() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch("CREATE TABLE backed_up (id INTEGER PRIMARY KEY) STRICT")
+      .await
+      .unwrap();
+
+    let object_store = state.objectstore();
+    let config = BackupConfig {
+      retention: Some(1),
+      upload: Some(true),
+    };
+
+    let names = create_backups(
+      state.data_dir(),
+      state.conn(),
+      state.logs_conn(),
+      &object_store,
+      &config,
+    )
+    .await
+    .unwrap();
+    assert_eq!(names.len(), 2);
+
+    let points = list_restore_points(state.data_dir(), &object_store)
+      .await
+      .unwrap();
+    assert_eq!(points.len(), 2, "{points:?}");
+    assert!(points.iter().all(|p| p.local && p.remote));
+
+    // Remote backups are restored, when missing locally.
+    let main_backup = names[0].clone();
+    tokio::fs::remove_file(state.data_dir().backup_path().join(&main_backup))
+      .await
+      .unwrap();
+
+    state
+      .conn()
+      .execute_batch("DROP TABLE backed_up")
+      .await
+      .unwrap();
+
+    restore_backup(&state, &main_backup).await.unwrap();
+    let count: i64 = state
+      .conn()
+      .read_query_row_get("SELECT COUNT(*) FROM backed_up", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(count, 0);
+
+    assert!(matches!(
+      restore_backup(&state, "main-20000101T000000Z.db").await,
+      Err(BackupError::NotFound(_))
+    ));
+  }
+}
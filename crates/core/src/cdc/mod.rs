@@ -0,0 +1,382 @@
+//! Change Data Capture (CDC): streams row changes to external sinks like Kafka, NATS or Redis.
+//!
+//! Changes are captured using SQLite's preupdate hook and only forwarded once their transaction
+//! commits. They're first persisted in an outbox table, from which they're published to every
+//! sink in order. Publishing is retried until it succeeds, i.e. delivery is at-least-once.
+
+mod sinks;
+
+use const_format::formatcp;
+use log::*;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Notify;
+use trailbase_schema::QualifiedName;
+use trailbase_schema::json::value_to_flat_json;
+use trailbase_sqlite::{Connection, params};
+
+use crate::app_state::AppState;
+use crate::config::proto::CdcConfig;
+use crate::constants::CDC_OUTBOX_TABLE;
+use crate::records::subscribe::hook::{
+  HookListener, PreupdateHookEvent, RecordAction, TransactionOutcome, add_listener,
+  add_transaction_listener,
+};
+
+pub(crate) use sinks::{CdcSink, build_sink};
+
+#[derive(Debug, Error)]
+pub enum CdcError {
+  #[error("IO: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("Protocol: {0}")]
+  Protocol(String),
+  #[error("Sink: {0}")]
+  Sink(Box<dyn std::error::Error + Send + Sync>),
+  #[error("Hook: {0}")]
+  Hook(Box<dyn std::error::Error + Send + Sync>),
+  #[error("TrailbaseSqlite: {0}")]
+  TrailbaseSqlite(#[from] trailbase_sqlite::Error),
+}
+
+const BATCH_SIZE: i64 = 128;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Starts capturing changes and publishing them to the configured sinks, if any.
+///
+/// NOTE: Config changes only take effect after a restart.
+pub(crate) fn start(state: &AppState) -> Result<(), CdcError> {
+  let Some(config) = state.get_config().cdc else {
+    return Ok(());
+  };
+  if config.sinks.is_empty() {
+    return Ok(());
+  }
+
+  let sinks = config
+    .sinks
+    .iter()
+    .map(|(name, sink)| Ok((name.clone(), build_sink(sink)?)))
+    .collect::<Result<Vec<_>, CdcError>>()?;
+
+  return start_with_sinks(state, &config, sinks);
+}
+
+fn start_with_sinks(
+  state: &AppState,
+  config: &CdcConfig,
+  sinks: Vec<(String, Arc<dyn CdcSink>)>,
+) -> Result<(), CdcError> {
+  let (sender, receiver) = flume::unbounded::<Vec<PreupdateHookEvent>>();
+  install_capture(state.conn(), config.tables.clone(), sender)?;
+
+  let notifiers: Vec<Arc<Notify>> = sinks.iter().map(|_| Arc::new(Notify::new())).collect();
+
+  tokio::spawn(write_outbox(
+    state.clone(),
+    receiver,
+    sinks.iter().map(|(name, _)| name.clone()).collect(),
+    notifiers.clone(),
+  ));
+
+  for ((name, sink), notify) in sinks.into_iter().zip(notifiers) {
+    tokio::spawn(publish_loop(state.conn().clone(), name, sink, notify));
+  }
+
+  return Ok(());
+}
+
+/// Buffers changes to captured tables and forwards them once their transaction commits.
+fn install_capture(
+  conn: &Connection,
+  tables: Vec<String>,
+  sender: flume::Sender<Vec<PreupdateHookEvent>>,
+) -> Result<(), CdcError> {
+  let pending: Arc<Mutex<Vec<PreupdateHookEvent>>> = Default::default();
+
+  add_listener(
+    conn,
+    HookListener::Cdc,
+    Box::new({
+      let pending = pending.clone();
+      let sender = sender.clone();
+      move |event: &PreupdateHookEvent| {
+        if captured(&tables, &event.table_name) {
+          pending.lock().push(event.clone());
+        }
+        return !sender.is_disconnected();
+      }
+    }),
+  )
+  .map_err(|err| CdcError::Hook(err.into()))?;
+
+  add_transaction_listener(
+    conn,
+    HookListener::Cdc,
+    Box::new(move |outcome: TransactionOutcome| {
+      let events = std::mem::take(&mut *pending.lock());
+      if outcome == TransactionOutcome::Commit && !events.is_empty() && sender.send(events).is_err()
+      {
+        warn!("CDC outbox writer gone. Dropping changes");
+      }
+      return true;
+    }),
+  )
+  .map_err(|err| CdcError::Hook(err.into()))?;
+
+  return Ok(());
+}
+
+fn captured(tables: &[String], table_name: &QualifiedName) -> bool {
+  if table_name.name.starts_with('_') {
+    return false;
+  }
+  if tables.is_empty() {
+    return true;
+  }
+
+  let name = match table_name.database_schema {
+    Some(ref db) => format!("{db}.{}", table_name.name),
+    None => table_name.name.clone(),
+  };
+  return tables.contains(&name);
+}
+
+/// Persists committed changes in the outbox, once per sink.
+async fn write_outbox(
+  state: AppState,
+  receiver: flume::Receiver<Vec<PreupdateHookEvent>>,
+  sinks: Vec<String>,
+  notifiers: Vec<Arc<Notify>>,
+) {
+  const QUERY: &str =
+    formatcp!("INSERT INTO '{CDC_OUTBOX_TABLE}' (sink, envelope) VALUES ($1, $2)");
+
+  while let Ok(events) = receiver.recv_async().await {
+    for event in events {
+      let envelope = build_envelope(&state, event).to_string();
+      for sink in &sinks {
+        if let Err(err) = state
+          .conn()
+          .execute(QUERY, params!(sink.clone(), envelope.clone()))
+          .await
+        {
+          error!("Failed to write CDC outbox for sink '{sink}': {err}");
+        }
+      }
+    }
+
+    for notify in &notifiers {
+      notify.notify_one();
+    }
+  }
+}
+
+/// Builds the published envelope, i.e. `{"op", "table", "row_id", "record", "timestamp"}`. For
+/// updates the record holds the new values, for deletes the old ones.
+fn build_envelope(state: &AppState, event: PreupdateHookEvent) -> serde_json::Value {
+  let PreupdateHookEvent {
+    action,
+    table_name,
+    row_id,
+    record,
+  } = event;
+
+  let metadata = state.connection_manager().main_entry().metadata;
+  let record = match metadata.get_table(&table_name) {
+    Some(table_metadata) => serde_json::Value::Object(
+      record
+        .iter()
+        .zip(&table_metadata.schema.columns)
+        .filter_map(|(value, column)| {
+          return value_to_flat_json(value)
+            .ok()
+            .map(|v| (column.name.clone(), v));
+        })
+        .collect(),
+    ),
+    None => {
+      warn!("CDC: missing metadata for {table_name:?}");
+      serde_json::Value::Null
+    }
+  };
+
+  return serde_json::json!({
+    "op": match action {
+      RecordAction::Insert => "insert",
+      RecordAction::Update => "update",
+      RecordAction::Delete => "delete",
+    },
+    "table": match table_name.database_schema {
+      Some(ref db) => format!("{db}.{}", table_name.name),
+      None => table_name.name,
+    },
+    "row_id": row_id,
+    "record": record,
+    "timestamp": chrono::Utc::now().timestamp(),
+  });
+}
+
+async fn publish_loop(conn: Connection, name: String, sink: Arc<dyn CdcSink>, notify: Arc<Notify>) {
+  let mut backoff = INITIAL_BACKOFF;
+  loop {
+    match publish_pending(&conn, &name, sink.as_ref()).await {
+      Ok(more) => {
+        backoff = INITIAL_BACKOFF;
+        if !more {
+          tokio::select! {
+            _ = notify.notified() => {},
+            _ = tokio::time::sleep(POLL_INTERVAL) => {},
+          }
+        }
+      }
+      Err(err) => {
+        warn!("Failed to publish changes to CDC sink '{name}': {err}");
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+      }
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct OutboxEntry {
+  id: i64,
+  envelope: String,
+}
+
+/// Publishes the next batch of changes in order. Stops at the first failure to preserve order.
+/// Returns whether there may be more changes pending.
+async fn publish_pending(
+  conn: &Connection,
+  sink_name: &str,
+  sink: &dyn CdcSink,
+) -> Result<bool, CdcError> {
+  const SELECT_QUERY: &str = formatcp!(
+    "SELECT id, envelope FROM '{CDC_OUTBOX_TABLE}' WHERE sink = $1 ORDER BY id LIMIT {BATCH_SIZE}"
+  );
+  const DELETE_QUERY: &str = formatcp!("DELETE FROM '{CDC_OUTBOX_TABLE}' WHERE id = $1");
+  const FAILED_QUERY: &str = formatcp!(
+    "UPDATE '{CDC_OUTBOX_TABLE}' SET attempts = attempts + 1, last_error = $2 WHERE id = $1"
+  );
+
+  let entries: Vec<OutboxEntry> = conn
+    .read_query_values(SELECT_QUERY, params!(sink_name.to_string()))
+    .await?;
+  let more = entries.len() as i64 == BATCH_SIZE;
+
+  for entry in entries {
+    if let Err(err) = sink.publish(&entry.envelope).await {
+      conn
+        .execute(FAILED_QUERY, params!(entry.id, err.to_string()))
+        .await?;
+      return Err(err);
+    }
+    conn.execute(DELETE_QUERY, params!(entry.id)).await?;
+  }
+
+  return Ok(more);
+}
+
+#[cfg(test)]
+mod tests {
+  use async_trait::async_trait;
+
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[derive(Default)]
+  struct MemorySink {
+    published: Mutex<Vec<serde_json::Value>>,
+  }
+
+  #[async_trait]
+  impl CdcSink for MemorySink {
+    async fn publish(&self, envelope: &str) -> Result<(), CdcError> {
+      self
+        .published
+        .lock()
+        .push(serde_json::from_str(envelope).unwrap());
+      return Ok(());
+    }
+  }
+
+  #[tokio::test]
+  async fn test_cdc_capture_and_publish() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE captured (id INTEGER PRIMARY KEY, text TEXT) STRICT; \
+          CREATE TABLE ignored (id INTEGER PRIMARY KEY) STRICT; \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    let sink = Arc::new(MemorySink::default());
+    start_with_sinks(
+      &state,
+      &CdcConfig {
+        tables: vec!["captured".to_string()],
+        ..Default::default()
+      },
+      vec![("memory".to_string(), sink.clone())],
+    )
+    .unwrap();
+
+    state
+      .conn()
+      .execute_batch(
+        "\
+          INSERT INTO captured (id, text) VALUES (1, 'a'); \
+          INSERT INTO ignored (id) VALUES (1); \
+          BEGIN; INSERT INTO captured (id, text) VALUES (2, 'rolled back'); ROLLBACK; \
+          UPDATE captured SET text = 'b' WHERE id = 1; \
+          DELETE FROM captured WHERE id = 1; \
+        ",
+      )
+      .await
+      .unwrap();
+
+    for _ in 0..500 {
+      if sink.published.lock().len() >= 3 {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let published = sink.published.lock().clone();
+    assert_eq!(published.len(), 3, "{published:?}");
+    assert_eq!(published[0]["op"], "insert");
+    assert_eq!(published[0]["table"], "captured");
+    assert_eq!(published[0]["record"]["text"], "a");
+    assert_eq!(published[1]["op"], "update");
+    assert_eq!(published[1]["record"]["text"], "b");
+    assert_eq!(published[2]["op"], "delete");
+    assert_eq!(published[2]["row_id"], 1);
+
+    // Published changes are removed from the outbox.
+    let mut count: i64 = -1;
+    for _ in 0..500 {
+      count = state
+        .conn()
+        .read_query_row_get(format!("SELECT COUNT(*) FROM '{CDC_OUTBOX_TABLE}'"), (), 0)
+        .await
+        .unwrap()
+        .unwrap();
+      if count == 0 {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(count, 0);
+  }
+}
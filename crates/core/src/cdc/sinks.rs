@@ -0,0 +1,349 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+
+use crate::cdc::CdcError;
+use crate::config::proto::{CdcSinkConfig, CdcSinkType};
+
+/// Destination for captured changes.
+#[async_trait]
+pub(crate) trait CdcSink: Send + Sync {
+  /// Publishes a single JSON change envelope. Must only return once the broker accepted it.
+  async fn publish(&self, envelope: &str) -> Result<(), CdcError>;
+}
+
+pub(crate) fn build_sink(config: &CdcSinkConfig) -> Result<Arc<dyn CdcSink>, CdcError> {
+  let (Some(address), Some(topic)) = (config.address.as_ref(), config.topic.as_ref()) else {
+    return Err(CdcError::Protocol("missing address or topic".to_string()));
+  };
+
+  return match config.r#type() {
+    CdcSinkType::Undefined => Err(CdcError::Protocol("undefined sink type".to_string())),
+    CdcSinkType::Redis => Ok(Arc::new(RedisSink::new(address, topic.clone())?)),
+    CdcSinkType::Nats => Ok(Arc::new(NatsSink::new(address, topic.clone())?)),
+    #[cfg(feature = "kafka")]
+    CdcSinkType::Kafka => Ok(Arc::new(kafka::KafkaSink::new(address, topic.clone()))),
+    #[cfg(not(feature = "kafka"))]
+    CdcSinkType::Kafka => Err(CdcError::Protocol(
+      "Kafka sink requires the 'kafka' feature".to_string(),
+    )),
+  };
+}
+
+/// Host, port and optional credentials of a broker URL.
+struct Endpoint {
+  address: String,
+  username: Option<String>,
+  password: Option<String>,
+  path: Option<String>,
+}
+
+impl Endpoint {
+  fn parse(url: &str, scheme: &str, default_port: u16) -> Result<Self, CdcError> {
+    let url = url::Url::parse(url).map_err(|err| CdcError::Protocol(err.to_string()))?;
+    if url.scheme() != scheme {
+      return Err(CdcError::Protocol(format!("expected '{scheme}://' URL")));
+    }
+    let Some(host) = url.host_str() else {
+      return Err(CdcError::Protocol("missing host".to_string()));
+    };
+
+    return Ok(Self {
+      address: format!("{host}:{}", url.port().unwrap_or(default_port)),
+      username: Some(url.username())
+        .filter(|u| !u.is_empty())
+        .map(str::to_string),
+      password: url.password().map(str::to_string),
+      path: Some(url.path().trim_start_matches('/'))
+        .filter(|p| !p.is_empty())
+        .map(str::to_string),
+    });
+  }
+}
+
+/// Appends changes to a Redis stream using XADD over the plain RESP protocol.
+///
+/// See: https://redis.io/docs/latest/develop/reference/protocol-spec/
+struct RedisSink {
+  endpoint: Endpoint,
+  key: String,
+  conn: tokio::sync::Mutex<Option<BufStream<TcpStream>>>,
+}
+
+impl RedisSink {
+  fn new(url: &str, key: String) -> Result<Self, CdcError> {
+    return Ok(Self {
+      endpoint: Endpoint::parse(url, "redis", 6379)?,
+      key,
+      conn: tokio::sync::Mutex::new(None),
+    });
+  }
+
+  async fn connect(&self) -> Result<BufStream<TcpStream>, CdcError> {
+    let mut conn = BufStream::new(TcpStream::connect(&self.endpoint.address).await?);
+
+    if let Some(ref password) = self.endpoint.password {
+      let mut args = vec!["AUTH"];
+      if let Some(ref username) = self.endpoint.username {
+        args.push(username);
+      }
+      args.push(password);
+      redis_command(&mut conn, &args).await?;
+    }
+    if let Some(ref db) = self.endpoint.path {
+      redis_command(&mut conn, &["SELECT", db]).await?;
+    }
+
+    return Ok(conn);
+  }
+}
+
+#[async_trait]
+impl CdcSink for RedisSink {
+  async fn publish(&self, envelope: &str) -> Result<(), CdcError> {
+    let mut lock = self.conn.lock().await;
+    let mut conn = match lock.take() {
+      Some(conn) => conn,
+      None => self.connect().await?,
+    };
+
+    redis_command(&mut conn, &["XADD", &self.key, "*", "envelope", envelope]).await?;
+
+    // Only keep healthy connections around.
+    *lock = Some(conn);
+    return Ok(());
+  }
+}
+
+async fn redis_command(conn: &mut BufStream<TcpStream>, args: &[&str]) -> Result<(), CdcError> {
+  conn
+    .write_all(encode_redis_command(args).as_bytes())
+    .await?;
+  conn.flush().await?;
+
+  let mut line = String::new();
+  conn.read_line(&mut line).await?;
+  match line.as_bytes().first() {
+    Some(b'+') | Some(b':') => {}
+    Some(b'$') => {
+      // Bulk string, e.g. the id of the stream entry.
+      if line.trim_end() != "$-1" {
+        line.clear();
+        conn.read_line(&mut line).await?;
+      }
+    }
+    _ => {
+      return Err(CdcError::Protocol(format!("redis: {}", line.trim_end())));
+    }
+  }
+
+  return Ok(());
+}
+
+fn encode_redis_command(args: &[&str]) -> String {
+  let mut command = format!("*{}\r\n", args.len());
+  for arg in args {
+    command.push_str(&format!("${}\r\n{arg}\r\n", arg.len()));
+  }
+  return command;
+}
+
+/// Publishes changes to a NATS subject over the plain text protocol. Every publish is followed by
+/// a PING to make sure the server processed it.
+///
+/// See: https://docs.nats.io/reference/reference-protocols/nats-protocol
+struct NatsSink {
+  endpoint: Endpoint,
+  subject: String,
+  conn: tokio::sync::Mutex<Option<BufStream<TcpStream>>>,
+}
+
+impl NatsSink {
+  fn new(url: &str, subject: String) -> Result<Self, CdcError> {
+    return Ok(Self {
+      endpoint: Endpoint::parse(url, "nats", 4222)?,
+      subject,
+      conn: tokio::sync::Mutex::new(None),
+    });
+  }
+
+  async fn connect(&self) -> Result<BufStream<TcpStream>, CdcError> {
+    let mut conn = BufStream::new(TcpStream::connect(&self.endpoint.address).await?);
+
+    // The server greets with INFO.
+    let mut line = String::new();
+    conn.read_line(&mut line).await?;
+    if !line.starts_with("INFO") {
+      return Err(CdcError::Protocol(format!("nats: {}", line.trim_end())));
+    }
+
+    let options = serde_json::json!({
+      "verbose": false,
+      "pedantic": false,
+      "user": self.endpoint.username,
+      "pass": self.endpoint.password,
+    });
+    conn
+      .write_all(format!("CONNECT {options}\r\n").as_bytes())
+      .await?;
+
+    return Ok(conn);
+  }
+}
+
+#[async_trait]
+impl CdcSink for NatsSink {
+  async fn publish(&self, envelope: &str) -> Result<(), CdcError> {
+    let mut lock = self.conn.lock().await;
+    let mut conn = match lock.take() {
+      Some(conn) => conn,
+      None => self.connect().await?,
+    };
+
+    conn
+      .write_all(
+        format!(
+          "PUB {} {}\r\n{envelope}\r\nPING\r\n",
+          self.subject,
+          envelope.len()
+        )
+        .as_bytes(),
+      )
+      .await?;
+    conn.flush().await?;
+
+    loop {
+      let mut line = String::new();
+      if conn.read_line(&mut line).await? == 0 {
+        return Err(CdcError::Protocol("nats: connection closed".to_string()));
+      }
+
+      match line.trim_end() {
+        "PONG" => break,
+        "PING" => {
+          conn.write_all(b"PONG\r\n").await?;
+          conn.flush().await?;
+        }
+        l if l.starts_with("-ERR") => {
+          return Err(CdcError::Protocol(format!("nats: {l}")));
+        }
+        // E.g. +OK or updated INFO.
+        _ => {}
+      }
+    }
+
+    // Only keep healthy connections around.
+    *lock = Some(conn);
+    return Ok(());
+  }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+  use rskafka::client::ClientBuilder;
+  use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+  use rskafka::record::Record;
+
+  use super::*;
+
+  /// Produces changes to partition 0 of a Kafka topic, which preserves their order.
+  pub(crate) struct KafkaSink {
+    brokers: Vec<String>,
+    topic: String,
+    client: tokio::sync::Mutex<Option<PartitionClient>>,
+  }
+
+  impl KafkaSink {
+    pub(crate) fn new(brokers: &str, topic: String) -> Self {
+      return Self {
+        brokers: brokers.split(',').map(|b| b.trim().to_string()).collect(),
+        topic,
+        client: tokio::sync::Mutex::new(None),
+      };
+    }
+  }
+
+  #[async_trait]
+  impl CdcSink for KafkaSink {
+    async fn publish(&self, envelope: &str) -> Result<(), CdcError> {
+      let mut lock = self.client.lock().await;
+      if lock.is_none() {
+        let client = ClientBuilder::new(self.brokers.clone())
+          .build()
+          .await
+          .map_err(|err| CdcError::Sink(err.into()))?;
+        *lock = Some(
+          client
+            .partition_client(self.topic.clone(), 0, UnknownTopicHandling::Retry)
+            .await
+            .map_err(|err| CdcError::Sink(err.into()))?,
+        );
+      }
+
+      let Some(client) = lock.as_ref() else {
+        unreachable!("initialized above");
+      };
+
+      let record = Record {
+        key: None,
+        value: Some(envelope.as_bytes().to_vec()),
+        headers: Default::default(),
+        timestamp: chrono::Utc::now(),
+      };
+      if let Err(err) = client
+        .produce(vec![record], Compression::NoCompression)
+        .await
+      {
+        *lock = None;
+        return Err(CdcError::Sink(err.into()));
+      }
+
+      return Ok(());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_endpoint_parsing() {
+    let endpoint = Endpoint::parse("redis://user:pw@localhost/2", "redis", 6379).unwrap();
+    assert_eq!(endpoint.address, "localhost:6379");
+    assert_eq!(endpoint.username.as_deref(), Some("user"));
+    assert_eq!(endpoint.password.as_deref(), Some("pw"));
+    assert_eq!(endpoint.path.as_deref(), Some("2"));
+
+    assert!(Endpoint::parse("nats://localhost:4222", "redis", 6379).is_err());
+  }
+
+  #[tokio::test]
+  async fn test_redis_sink() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+      let (stream, _) = listener.accept().await.unwrap();
+      let mut stream = BufStream::new(stream);
+
+      let expected = encode_redis_command(&["XADD", "changes", "*", "envelope", "{}"]);
+      let mut received = String::new();
+      while received.len() < expected.len() {
+        stream.read_line(&mut received).await.unwrap();
+      }
+      stream
+        .write_all(b"$15\r\n1700000000000-0\r\n")
+        .await
+        .unwrap();
+      stream.flush().await.unwrap();
+
+      return received == expected;
+    });
+
+    let sink = RedisSink::new(&format!("redis://{addr}"), "changes".to_string()).unwrap();
+    sink.publish("{}").await.unwrap();
+    assert!(server.await.unwrap());
+  }
+}
@@ -3,20 +3,27 @@ use log::*;
 use prost_reflect::{
   DynamicMessage, ExtensionDescriptor, FieldDescriptor, Kind, MapKey, ReflectMessage, Value,
 };
-use proto::{EmailTemplate, OAuthProviderId, SmtpEncryption};
+use proto::{CdcSinkType, EmailProvider, EmailTemplate, OAuthProviderId, SmtpEncryption};
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fs;
 use std::str::FromStr;
 use thiserror::Error;
+use trailbase_extension::crypto::EncryptionKey;
 use trailbase_sqlite::ConnectionType;
 use validator::{ValidateEmail, ValidateUrl};
 
 use crate::DESCRIPTOR_POOL;
 use crate::auth::oauth::providers::oauth_providers_static_registry;
+use crate::auth::webhooks::AuthEvent;
 use crate::connection::ConnectionManager;
 use crate::data_dir::DataDir;
-use crate::records::validate_record_api_config;
+use crate::email::EmailTemplateName;
+use crate::records::file_encryption::MasterKeyProvider;
+use crate::records::webhooks::RecordOperation;
+use crate::records::{validate_record_api_config, validate_saved_query_config};
+
+pub mod secrets;
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -73,11 +80,13 @@ pub mod proto {
   use prost_reflect::text_format::FormatOptions;
   use prost_reflect::{DynamicMessage, MessageDescriptor, ReflectMessage};
   use std::hash::{DefaultHasher, Hash, Hasher};
+  use trailbase_schema::QualifiedName;
 
   use crate::DESCRIPTOR_POOL;
   use crate::config::ConfigError;
   use crate::constants::{
-    DEFAULT_AUTH_TOKEN_TTL, DEFAULT_REFRESH_TOKEN_TTL, LOGS_RETENTION_DEFAULT,
+    DEFAULT_AUTH_TOKEN_TTL, DEFAULT_MAGIC_LINK_TTL, DEFAULT_REFRESH_TOKEN_TTL,
+    LOGS_RETENTION_DEFAULT,
   };
 
   include!(concat!(env!("OUT_DIR"), "/config.rs"));
@@ -159,6 +168,49 @@ pub mod proto {
           .map_or(DEFAULT_REFRESH_TOKEN_TTL, Duration::seconds),
       );
     }
+
+    pub fn magic_link_ttl(&self) -> Duration {
+      return self
+        .magic_link_ttl_sec
+        .map_or(DEFAULT_MAGIC_LINK_TTL, Duration::seconds);
+    }
+  }
+
+  impl RecordApiConfig {
+    /// Referenced table or view, qualified with `database` if set.
+    pub fn qualified_table_name(&self) -> Result<QualifiedName, ConfigError> {
+      let name = QualifiedName::parse(self.table_name())?;
+      let database = match (self.database.as_deref(), name.database_schema.as_deref()) {
+        (None, _) | (Some("main"), None) => return Ok(name),
+        (Some(database), None) => database,
+        (Some(database), Some(db)) if database == db => database,
+        (Some(database), Some(db)) => {
+          return Err(ConfigError::Invalid(format!(
+            "Table '{}' is qualified with database '{db}' conflicting with '{database}'",
+            name.name
+          )));
+        }
+      };
+
+      return Ok(QualifiedName {
+        database_schema: Some(database.to_string()),
+        name: name.name,
+      });
+    }
+
+    /// Databases to attach, i.e. `attached_databases` plus the database of the referenced table
+    /// or view unless "main".
+    pub fn databases_to_attach(&self) -> Vec<String> {
+      let mut databases = self.attached_databases.clone();
+      if let Ok(name) = self.qualified_table_name()
+        && let Some(db) = name.database_schema
+        && db != "main"
+        && !databases.contains(&db)
+      {
+        databases.push(db);
+      }
+      return databases;
+    }
   }
 
   pub fn hash_config(config: &Config) -> String {
@@ -301,9 +353,171 @@ pub(crate) fn merge_vault_and_env(
 ) -> Result<proto::Config, ConfigError> {
   let mut dyn_config = config.transcode_to_dynamic();
   recursively_merge_vault_and_env(&mut dyn_config, &vault, vec![])?;
+  recursively_visit_strings(&mut dyn_config, "", &mut |path, value| {
+    if let Some(resolved) = resolve_references(value, &vault, path)? {
+      *value = resolved;
+    }
+    return Ok(());
+  })?;
   return Ok(dyn_config.transcode_to::<proto::Config>()?);
 }
 
+/// Calls `f` with the path and value of every string field, including repeated fields and map
+/// values.
+fn recursively_visit_strings(
+  msg: &mut DynamicMessage,
+  parent_path: &str,
+  f: &mut impl FnMut(&str, &mut String) -> Result<(), ConfigError>,
+) -> Result<(), ConfigError> {
+  fn visit_value(
+    value: &mut Value,
+    path: &str,
+    f: &mut impl FnMut(&str, &mut String) -> Result<(), ConfigError>,
+  ) -> Result<(), ConfigError> {
+    match value {
+      Value::String(s) => f(path, s)?,
+      Value::Message(m) => recursively_visit_strings(m, path, f)?,
+      Value::List(list) => {
+        for (index, v) in list.iter_mut().enumerate() {
+          visit_value(v, &format!("{path}[{index}]"), f)?;
+        }
+      }
+      Value::Map(map) => {
+        for (key, v) in map.iter_mut() {
+          let path = match key {
+            MapKey::String(key) => format!("{path}[{key}]"),
+            key => format!("{path}[{key:?}]"),
+          };
+          visit_value(v, &path, f)?;
+        }
+      }
+      _ => {}
+    }
+    return Ok(());
+  }
+
+  for field_descr in msg.descriptor().fields() {
+    if !msg.has_field(&field_descr) {
+      continue;
+    }
+
+    let path = if parent_path.is_empty() {
+      field_descr.name().to_string()
+    } else {
+      format!("{parent_path}.{}", field_descr.name())
+    };
+    visit_value(msg.get_field_mut(&field_descr), &path, f)?;
+  }
+
+  return Ok(());
+}
+
+/// Resolves a `secret://<name>` reference to the named vault entry and substitutes `${ENV_VAR}`
+/// references with the environment variable's value. `$${` escapes a literal `${`.
+///
+/// Returns `None` if the value doesn't contain any references.
+fn resolve_references(
+  value: &str,
+  vault: &proto::Vault,
+  path: &str,
+) -> Result<Option<String>, ConfigError> {
+  if let Some(name) = value.strip_prefix(SECRET_REFERENCE_PREFIX) {
+    let Some(secret) = vault.secrets.get(name) else {
+      return Err(ConfigError::Invalid(format!(
+        "Missing secret '{name}' referenced by '{path}'"
+      )));
+    };
+    return Ok(Some(secret.clone()));
+  }
+
+  if !value.contains("${") {
+    return Ok(None);
+  }
+
+  let mut resolved = String::with_capacity(value.len());
+  let mut rest = value;
+  while let Some(start) = rest.find("${") {
+    if let Some(prefix) = rest[..start].strip_suffix('$') {
+      resolved.push_str(prefix);
+      resolved.push_str("${");
+      rest = &rest[start + 2..];
+      continue;
+    }
+    resolved.push_str(&rest[..start]);
+
+    let Some(len) = rest[start + 2..].find('}') else {
+      return Err(ConfigError::Invalid(format!(
+        "Unterminated '${{' in '{path}'"
+      )));
+    };
+    let name = &rest[start + 2..start + 2 + len];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+      return Err(ConfigError::Invalid(format!(
+        "Invalid environment variable name '{name}' referenced by '{path}'"
+      )));
+    }
+
+    let Some(env_value) = parse_env_var::<String>(name).expect("infallible") else {
+      return Err(ConfigError::Invalid(format!(
+        "Missing environment variable '{name}' referenced by '{path}'"
+      )));
+    };
+    resolved.push_str(&env_value);
+    rest = &rest[start + 2 + len + 1..];
+  }
+  resolved.push_str(rest);
+
+  return Ok(Some(resolved));
+}
+
+/// Replaces values resolved from `${ENV_VAR}` and `secret://<name>` references in the on-disk
+/// config with the original references. This way, writing back an updated config, e.g. from the
+/// admin UI, doesn't persist resolved credentials.
+///
+/// References are restored by field path and only if the field's value is unchanged, i.e. still
+/// matches the resolved reference. Other fields, which coincidentally hold the same value, as well
+/// as fields that have been changed are left untouched.
+fn restore_references(
+  data_dir: &DataDir,
+  config: &proto::Config,
+) -> Result<proto::Config, ConfigError> {
+  let Some(raw) = maybe_load_config_textproto_unverified(data_dir)? else {
+    return Ok(config.clone());
+  };
+  let vault = load_vault_textproto_or_default(data_dir)?;
+
+  // Secret fields are stored as placeholders with the actual values, which may be references too,
+  // in the vault.
+  let mut dyn_raw = raw.transcode_to_dynamic();
+  recursively_merge_vault_and_env(&mut dyn_raw, &vault, vec![])?;
+
+  // Map from field paths to the on-disk reference and its current resolution.
+  let mut references = HashMap::<String, (String, String)>::new();
+  recursively_visit_strings(&mut dyn_raw, "", &mut |path, value| {
+    if let Ok(Some(resolved)) = resolve_references(value, &vault, path) {
+      references.insert(path.to_string(), (value.clone(), resolved));
+    }
+    return Ok(());
+  })?;
+
+  if references.is_empty() {
+    return Ok(config.clone());
+  }
+
+  let mut dyn_config = config.transcode_to_dynamic();
+  recursively_visit_strings(&mut dyn_config, "", &mut |path, value| {
+    if let Some((reference, resolved)) = references.get(path)
+      && resolved == value
+    {
+      *value = reference.clone();
+    }
+    return Ok(());
+  })?;
+  return Ok(dyn_config.transcode_to::<proto::Config>()?);
+}
+
+const SECRET_REFERENCE_PREFIX: &str = "secret://";
+
 const PLACEHOLDER: &str = "<REDACTED>";
 
 fn recursively_redact_secrets(
@@ -378,7 +592,16 @@ pub(crate) fn redact_secrets(
   return Ok((stripped, secrets));
 }
 
+/// Loads the vault including the most recently resolved external secrets.
 fn load_vault_textproto_or_default(data_dir: &DataDir) -> Result<proto::Vault, ConfigError> {
+  let mut vault = load_local_vault_textproto_or_default(data_dir)?;
+  for (name, value) in secrets::resolved_secrets() {
+    vault.secrets.entry(name).or_insert(value);
+  }
+  return Ok(vault);
+}
+
+fn load_local_vault_textproto_or_default(data_dir: &DataDir) -> Result<proto::Vault, ConfigError> {
   let vault_path = data_dir.secrets_path().join(VAULT_FILENAME);
 
   let vault = match fs::read_to_string(&vault_path) {
@@ -406,6 +629,28 @@ pub(crate) fn maybe_load_config_textproto_unverified(
   };
 }
 
+/// Loads the config and merges in secrets from the vault and env. Unlike
+/// [load_or_init_config_textproto], neither initializes a missing config nor validates.
+pub(crate) async fn load_config_textproto(
+  data_dir: &DataDir,
+) -> Result<Option<proto::Config>, ConfigError> {
+  let Some(config) = maybe_load_config_textproto_unverified(data_dir)? else {
+    return Ok(None);
+  };
+  secrets::resolve_external_secrets(&config, &load_local_vault_textproto_or_default(data_dir)?)
+    .await?;
+  let vault = load_vault_textproto_or_default(data_dir)?;
+  return Ok(Some(merge_vault_and_env(config, vault)?));
+}
+
+/// Paths of the config and vault files.
+pub(crate) fn config_file_paths(data_dir: &DataDir) -> [std::path::PathBuf; 2] {
+  return [
+    data_dir.config_path().join(CONFIG_FILENAME),
+    data_dir.secrets_path().join(VAULT_FILENAME),
+  ];
+}
+
 // TODO: Initialization order is currently borked and worked-around by rebuilding
 // ConnectionMetadata. Specifically, building SchemaMatadataCache, which contains JSON metadata,
 // requires custom JSON schemas to be built from the config and globally registered. However,
@@ -433,6 +678,8 @@ pub async fn load_or_init_config_textproto(
       }
     };
 
+    secrets::resolve_external_secrets(&config, &load_local_vault_textproto_or_default(data_dir)?)
+      .await?;
     let vault = load_vault_textproto_or_default(data_dir)?;
     merge_vault_and_env(config, vault)?
   };
@@ -460,13 +707,21 @@ pub async fn write_config_and_vault_textproto(
 ) -> Result<(), ConfigError> {
   validate_config(connection_manager, config).await?;
 
-  let (stripped_config, vault) = split_config(config)?;
+  let config = restore_references(data_dir, config)?;
+  let (stripped_config, mut vault) = split_config(&config)?;
 
   if cfg!(test) {
     debug!("Skip writing config for tests.");
     return Ok(());
   }
 
+  // Preserve named secrets, which may be referenced using `secret://<name>`.
+  for (name, value) in load_local_vault_textproto_or_default(data_dir)?.secrets {
+    if !name.starts_with("TRAIL_") {
+      vault.secrets.entry(name).or_insert(value);
+    }
+  }
+
   let config_path = data_dir.config_path().join(CONFIG_FILENAME);
   let vault_path = data_dir.secrets_path().join(VAULT_FILENAME);
   debug!("Writing config files: {config_path:?}, {vault_path:?}");
@@ -494,6 +749,188 @@ fn validate_application_name(name: &str) -> Result<(), ConfigError> {
   Ok(())
 }
 
+fn validate_object_store_config(server: &proto::ServerConfig) -> Result<(), ConfigError> {
+  let config = match (&server.s3_storage_config, &server.object_store) {
+    (Some(_), Some(_)) => {
+      return ierr("Only one of `s3_storage_config` and `object_store` may be set");
+    }
+    (Some(s3), None) => proto::ObjectStoreConfig {
+      s3: Some(s3.clone()),
+      ..Default::default()
+    },
+    (None, Some(object_store)) => object_store.clone(),
+    (None, None) => {
+      return Ok(());
+    }
+  };
+
+  let num_backends = [
+    config.local.is_some(),
+    config.s3.is_some(),
+    config.gcs.is_some(),
+    config.azure.is_some(),
+  ]
+  .into_iter()
+  .filter(|b| *b)
+  .count();
+  if num_backends > 1 {
+    return ierr("Only one object store backend may be configured");
+  }
+
+  if let Some(ref local) = config.local
+    && local.path.as_ref().is_none_or(|p| p.is_empty())
+  {
+    return ierr("Local object store requires a 'path'");
+  }
+
+  if let Some(ref s3) = config.s3
+    && s3.bucket_name.as_ref().is_none_or(|b| b.is_empty())
+  {
+    return ierr("S3 object store requires a 'bucket_name'");
+  }
+
+  if let Some(ref gcs) = config.gcs
+    && gcs.bucket_name.as_ref().is_none_or(|b| b.is_empty())
+  {
+    return ierr("GCS object store requires a 'bucket_name'");
+  }
+
+  if let Some(ref azure) = config.azure {
+    if azure.account.as_ref().is_none_or(|a| a.is_empty()) {
+      return ierr("Azure object store requires an 'account'");
+    }
+    if azure.container_name.as_ref().is_none_or(|c| c.is_empty()) {
+      return ierr("Azure object store requires a 'container_name'");
+    }
+  }
+
+  return Ok(());
+}
+
+fn validate_log_sinks_config(server: &proto::ServerConfig) -> Result<(), ConfigError> {
+  for sink in &server.log_sinks {
+    match sink {
+      proto::LogSinkConfig {
+        file: Some(file),
+        syslog: None,
+        loki: None,
+      } => {
+        if file.path.as_ref().is_none_or(|p| p.is_empty()) {
+          return ierr("File log sink requires a 'path'");
+        }
+      }
+      proto::LogSinkConfig {
+        file: None,
+        syslog: Some(syslog),
+        loki: None,
+      } => {
+        if syslog.facility.is_some_and(|f| f > 23) {
+          return ierr("Syslog facility must be in [0, 23]");
+        }
+      }
+      proto::LogSinkConfig {
+        file: None,
+        syslog: None,
+        loki: Some(loki),
+      } => {
+        let Some(ref url) = loki.url else {
+          return ierr("Loki log sink requires a 'url'");
+        };
+        if let Err(err) = url::Url::parse(url) {
+          return ierr(format!("Failed to parse Loki url '{url}': {err}"));
+        }
+      }
+      _ => {
+        return ierr("Exactly one log sink must be set per entry");
+      }
+    }
+  }
+
+  return Ok(());
+}
+
+fn validate_cors_config(server: &proto::ServerConfig) -> Result<(), ConfigError> {
+  let Some(ref cors) = server.cors else {
+    return Ok(());
+  };
+
+  for policy in [&cors.fallback, &cors.records, &cors.auth, &cors.admin]
+    .into_iter()
+    .flatten()
+  {
+    for origin in &policy.allowed_origins {
+      if origin == "*" {
+        continue;
+      }
+      // Substitute wildcards, e.g. "https://*.example.com", to validate the remainder.
+      let valid = url::Url::parse(&origin.replace('*', "x"))
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.path() == "/")
+        && !origin.ends_with('/');
+      if !valid {
+        return ierr(format!("Invalid CORS origin: '{origin}'"));
+      }
+    }
+    for header in &policy.allowed_headers {
+      if axum::http::HeaderName::try_from(header.as_str()).is_err() {
+        return ierr(format!("Invalid CORS header: '{header}'"));
+      }
+    }
+    for method in &policy.allowed_methods {
+      if axum::http::Method::try_from(method.as_str()).is_err() {
+        return ierr(format!("Invalid CORS method: '{method}'"));
+      }
+    }
+  }
+
+  return Ok(());
+}
+
+fn validate_proxy_routes_config(routes: &[proto::ProxyRouteConfig]) -> Result<(), ConfigError> {
+  const RESERVED_PREFIXES: &[&str] = &[
+    "/api/records/",
+    "/api/transaction/",
+    "/api/query/",
+    "/api/auth/",
+    "/api/_admin/",
+    "/api/healthcheck/",
+    "/_/",
+  ];
+
+  let mut prefixes = HashSet::<&str>::new();
+  for route in routes {
+    let Some(prefix) = route.path_prefix.as_deref() else {
+      return ierr("Proxy route requires a 'path_prefix'");
+    };
+    let prefix = prefix.trim_end_matches('/');
+    if !prefix.starts_with('/') || prefix.contains(['{', '}', '*']) {
+      return ierr(format!("Invalid proxy route path prefix: '{prefix}'"));
+    }
+    let with_slash = format!("{prefix}/");
+    if RESERVED_PREFIXES
+      .iter()
+      .any(|reserved| with_slash.starts_with(reserved) || reserved.starts_with(&with_slash))
+    {
+      return ierr(format!(
+        "Proxy route path prefix collides with built-in APIs: '{prefix}'"
+      ));
+    }
+    if !prefixes.insert(prefix) {
+      return ierr(format!("Duplicate proxy route path prefix: '{prefix}'"));
+    }
+
+    let Some(ref upstream_url) = route.upstream_url else {
+      return ierr(format!("Proxy route '{prefix}' requires an 'upstream_url'"));
+    };
+    if !url::Url::parse(upstream_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+      return ierr(format!(
+        "Invalid upstream url for proxy route '{prefix}': '{upstream_url}'"
+      ));
+    }
+  }
+
+  return Ok(());
+}
+
 pub async fn validate_config(
   connection_manager: &ConnectionManager,
   config: &proto::Config,
@@ -511,6 +948,47 @@ pub async fn validate_config(
     None => None,
   };
 
+  validate_object_store_config(&config.server)?;
+  validate_log_sinks_config(&config.server)?;
+  validate_cors_config(&config.server)?;
+
+  if config.server.slow_query_threshold_ms == Some(0) {
+    return ierr("Slow query threshold must be positive, unset to disable");
+  }
+
+  if let Some(ref master_key) = config
+    .server
+    .file_encryption
+    .as_ref()
+    .and_then(|e| e.master_key.as_ref())
+  {
+    MasterKeyProvider::from_base64(master_key)
+      .map_err(|err| ConfigError::Invalid(format!("File encryption: {err}")))?;
+  }
+
+  if let Some(ref key) = config.server.sql_encryption_key {
+    EncryptionKey::from_base64(key)
+      .map_err(|err| ConfigError::Invalid(format!("SQL encryption key: {err}")))?;
+  }
+
+  if let Some(ref address) = config
+    .server
+    .upload_scan
+    .as_ref()
+    .and_then(|s| s.clamav_address.as_ref())
+  {
+    if !cfg!(feature = "clamav") {
+      return ierr("Upload scanning via ClamAV requires the 'clamav' feature");
+    }
+    if address.is_empty() {
+      return ierr("Empty ClamAV address");
+    }
+  }
+
+  if let Some(ref secrets) = config.secrets {
+    secrets::build_secret_resolvers(secrets)?;
+  }
+
   let connection_type = connection_manager.main_entry().connection.connection_type();
 
   let mut db_names = HashSet::<String>::new();
@@ -542,6 +1020,20 @@ pub async fn validate_config(
     }
   }
 
+  // Check tenancy.
+  let tenancy_enabled = config
+    .tenancy
+    .as_ref()
+    .is_some_and(|t| t.enabled.unwrap_or(false));
+  if tenancy_enabled && matches!(connection_type, ConnectionType::Pg) {
+    return ierr("PG doesn't (yet) support multi-tenancy.");
+  }
+  if let Some(header) = config.tenancy.as_ref().and_then(|t| t.header.as_ref())
+    && axum::http::HeaderName::try_from(header.as_str()).is_err()
+  {
+    return ierr(format!("Invalid tenant header: {header}"));
+  }
+
   // Check RecordApis.
   //
   // Note: it is valid to declare multiple api (e.g. with different acls) over the same
@@ -550,6 +1042,12 @@ pub async fn validate_config(
   for api in &config.record_apis {
     let api_name = validate_record_api_config(connection_manager, api, &config.databases).await?;
 
+    if api.tenant_scoped.unwrap_or(false) && !tenancy_enabled {
+      return ierr(format!(
+        "API '{api_name}' is tenant-scoped but multi-tenancy is disabled"
+      ));
+    }
+
     if !api_names.insert(api_name.clone()) {
       return ierr(format!(
         "Two or more APIs have the colliding name: '{api_name}'"
@@ -557,6 +1055,61 @@ pub async fn validate_config(
     }
   }
 
+  // Check saved queries.
+  let mut saved_query_names = HashSet::<String>::new();
+  for saved_query in &config.saved_queries {
+    let name = validate_saved_query_config(connection_manager, saved_query).await?;
+
+    if !saved_query_names.insert(name.clone()) {
+      return ierr(format!(
+        "Two or more saved queries have the colliding name: '{name}'"
+      ));
+    }
+  }
+
+  // Check record webhooks.
+  for (name, webhook) in &config.webhooks {
+    if webhook
+      .url
+      .as_ref()
+      .is_none_or(|url| !url.validate_url() || !url.starts_with("http"))
+    {
+      return ierr(format!("Invalid url for webhook: {name}"));
+    }
+
+    for operation in &webhook.operations {
+      if !RecordOperation::ALL.iter().any(|o| o.name() == operation) {
+        return ierr(format!(
+          "Unknown operation '{operation}' for webhook: {name}"
+        ));
+      }
+    }
+  }
+
+  validate_proxy_routes_config(&config.proxy_routes)?;
+
+  // Check CDC.
+  if let Some(ref cdc) = config.cdc {
+    for (name, sink) in &cdc.sinks {
+      match sink.r#type() {
+        CdcSinkType::Undefined => {
+          return ierr(format!("Missing type for CDC sink: {name}"));
+        }
+        CdcSinkType::Kafka if !cfg!(feature = "kafka") => {
+          return ierr("Kafka CDC sinks require the 'kafka' feature");
+        }
+        _ => {}
+      }
+
+      if sink.address.as_ref().is_none_or(|a| a.is_empty()) {
+        return ierr(format!("Missing address for CDC sink: {name}"));
+      }
+      if sink.topic.as_ref().is_none_or(|t| t.is_empty()) {
+        return ierr(format!("Missing topic for CDC sink: {name}"));
+      }
+    }
+  }
+
   // Check OAuth.
   if !config.auth.oauth_providers.is_empty() && site_url.is_none() {
     info!(
@@ -605,30 +1158,126 @@ pub async fn validate_config(
       return ierr(format!("Missing secret for: {name}"));
     }
 
-    if provider_id == OAuthProviderId::Oidc0 {
-      if provider
-        .auth_url
-        .as_ref()
-        .as_ref()
-        .is_none_or(|url| !url.validate_url())
-      {
-        return ierr(format!("Invalid auth url for: {name}"));
+    let is_oidc = matches!(
+      provider_id,
+      OAuthProviderId::Oidc0
+        | OAuthProviderId::Oidc1
+        | OAuthProviderId::Oidc2
+        | OAuthProviderId::Oidc3
+        | OAuthProviderId::Oidc4
+        | OAuthProviderId::Oidc5
+        | OAuthProviderId::Oidc6
+    );
+
+    if is_oidc {
+      if let Some(ref issuer_url) = provider.issuer_url {
+        if !issuer_url.validate_url() {
+          return ierr(format!("Invalid issuer url for: {name}"));
+        }
+      } else {
+        if provider
+          .auth_url
+          .as_ref()
+          .as_ref()
+          .is_none_or(|url| !url.validate_url())
+        {
+          return ierr(format!("Invalid auth url for: {name}"));
+        }
+
+        if provider
+          .token_url
+          .as_ref()
+          .is_none_or(|url| !url.validate_url())
+        {
+          return ierr(format!("Invalid token url for: {name}"));
+        }
+
+        if provider
+          .user_api_url
+          .as_ref()
+          .is_none_or(|url| !url.validate_url())
+        {
+          return ierr(format!("Invalid user api url for '{name}"));
+        }
       }
+    }
+  }
 
-      if provider
-        .token_url
-        .as_ref()
-        .is_none_or(|url| !url.validate_url())
-      {
-        return ierr(format!("Invalid token url for: {name}"));
+  // Check webhooks.
+  for (name, webhook) in &config.auth.webhooks {
+    if webhook
+      .url
+      .as_ref()
+      .is_none_or(|url| !url.validate_url() || !url.starts_with("http"))
+    {
+      return ierr(format!("Invalid url for webhook: {name}"));
+    }
+
+    for event in &webhook.events {
+      if !AuthEvent::ALL.iter().any(|e| e.name() == event) {
+        return ierr(format!("Unknown event '{event}' for webhook: {name}"));
       }
+    }
+  }
 
-      if provider
-        .user_api_url
-        .as_ref()
-        .is_none_or(|url| !url.validate_url())
-      {
-        return ierr(format!("Invalid user api url for '{name}"));
+  // Check SAML.
+  if !config.auth.saml_providers.is_empty() && site_url.is_none() {
+    info!(
+      "SAML requires a public URL for the assertion consumer service but `config.server.site_url` not set. May have been provided via `--public-url` instead"
+    );
+  }
+
+  for (name, provider) in &config.auth.saml_providers {
+    if provider
+      .idp_entity_id
+      .as_ref()
+      .is_none_or(|id| id.trim().is_empty())
+    {
+      return ierr(format!("Missing IdP entity id for SAML provider: {name}"));
+    }
+
+    if provider
+      .idp_sso_url
+      .as_ref()
+      .is_none_or(|url| !url.validate_url())
+    {
+      return ierr(format!("Invalid IdP SSO url for SAML provider: {name}"));
+    }
+
+    let Some(ref certificate) = provider.idp_certificate else {
+      return ierr(format!("Missing IdP certificate for SAML provider: {name}"));
+    };
+    if let Err(err) = crate::auth::saml::signature::parse_certificate(certificate) {
+      return ierr(format!("SAML provider {name}: {err}"));
+    }
+  }
+
+  // Check user profile fields.
+  if let Some(ref profile) = config.auth.user_profile {
+    let is_identifier = |name: &str| {
+      return !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    };
+
+    if let Some(ref table_name) = profile.table_name
+      && !is_identifier(table_name)
+    {
+      return ierr(format!("Invalid user profile table: {table_name}"));
+    }
+
+    for field in &profile.fields {
+      let Some(ref name) = field.name else {
+        return ierr("Missing user profile field name");
+      };
+      if !is_identifier(name) {
+        return ierr(format!("Invalid user profile field: {name}"));
+      }
+
+      let reserved = match profile.table_name {
+        Some(_) => name == "user",
+        None => crate::auth::profile::RESERVED_USER_COLUMNS.contains(&name.as_str()),
+      };
+      if reserved {
+        return ierr(format!("Reserved user profile field: {name}"));
       }
     }
   }
@@ -659,6 +1308,11 @@ pub async fn validate_config(
   // Check email config.
   validate_email_config(&config.email)?;
 
+  // Check push notification config.
+  if let Some(ref push) = config.push {
+    validate_push_config(push)?;
+  }
+
   // Check job config.
   for job in &config.jobs.system_jobs {
     let Some(ref id) = job.id else {
@@ -677,17 +1331,102 @@ pub async fn validate_config(
   return Ok(());
 }
 
+fn validate_push_config(push: &proto::PushConfig) -> Result<(), ConfigError> {
+  let require = |value: Option<&String>, name: &str| -> Result<(), ConfigError> {
+    if value.is_none_or(|v| v.is_empty()) {
+      return ierr(format!("{name} missing."));
+    }
+    return Ok(());
+  };
+
+  if let Some(ref fcm) = push.fcm {
+    require(fcm.service_account_key.as_ref(), "FCM service account key")?;
+  }
+
+  if let Some(ref apns) = push.apns {
+    require(apns.team_id.as_ref(), "APNs team id")?;
+    require(apns.key_id.as_ref(), "APNs key id")?;
+    require(apns.private_key.as_ref(), "APNs private key")?;
+    require(apns.bundle_id.as_ref(), "APNs bundle id")?;
+  }
+
+  if let Some(ref web_push) = push.web_push {
+    require(web_push.vapid_private_key.as_ref(), "VAPID private key")?;
+    // Push services require a way to contact the sender, see RFC 8292.
+    if !web_push
+      .subject
+      .as_ref()
+      .is_some_and(|s| s.starts_with("mailto:") || s.starts_with("https://"))
+    {
+      return ierr("Web Push subject must be a 'mailto:' or 'https:' URI.");
+    }
+  }
+
+  return Ok(());
+}
+
 pub(crate) fn validate_email_config(email: &proto::EmailConfig) -> Result<(), ConfigError> {
-  validate_email_template(
-    email.user_verification_template.as_ref(),
-    &["VERIFICATION_URL", "CODE", "TOKEN"],
-  )?;
-  validate_email_template(
-    email.change_email_template.as_ref(),
-    &["VERIFICATION_URL", "CODE", "TOKEN"],
-  )?;
-  validate_email_template(email.password_reset_template.as_ref(), &["TOKEN", "CODE"])?;
-  validate_email_template(email.otp_template.as_ref(), &["CODE"])?;
+  for name in EmailTemplateName::ALL {
+    validate_email_template(name.config_template(email), name.required_variables())?;
+  }
+
+  if email.max_attempts == Some(0) {
+    return ierr("Email max attempts must be positive.");
+  }
+  if email.rate_limit_per_minute == Some(0) {
+    return ierr("Email rate limit must be positive.");
+  }
+
+  let require = |value: Option<&String>, name: &str| -> Result<(), ConfigError> {
+    if value.is_none_or(|v| v.is_empty()) {
+      return ierr(format!("{name} missing."));
+    }
+    return Ok(());
+  };
+  let validate_endpoint = |endpoint: Option<&String>| -> Result<(), ConfigError> {
+    if let Some(endpoint) = endpoint
+      && !url::Url::parse(endpoint).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+    {
+      return ierr(format!("Invalid email API endpoint: '{endpoint}'"));
+    }
+    return Ok(());
+  };
+
+  match email.provider() {
+    EmailProvider::Undefined => {}
+    EmailProvider::Smtp => require(email.smtp_host.as_ref(), "SMTP host")?,
+    EmailProvider::Sendmail => return validate_email_sender(email),
+    EmailProvider::Ses => {
+      // NOTE: Region and credentials may also be provided via the AWS environment variables.
+      validate_endpoint(email.ses.as_ref().and_then(|c| c.endpoint.as_ref()))?;
+      return validate_email_sender(email);
+    }
+    EmailProvider::Mailgun => {
+      let Some(ref mailgun) = email.mailgun else {
+        return ierr("Mailgun config missing.");
+      };
+      require(mailgun.domain.as_ref(), "Mailgun domain")?;
+      require(mailgun.api_key.as_ref(), "Mailgun API key")?;
+      validate_endpoint(mailgun.endpoint.as_ref())?;
+      return validate_email_sender(email);
+    }
+    EmailProvider::Sendgrid => {
+      let Some(ref sendgrid) = email.sendgrid else {
+        return ierr("SendGrid config missing.");
+      };
+      require(sendgrid.api_key.as_ref(), "SendGrid API key")?;
+      validate_endpoint(sendgrid.endpoint.as_ref())?;
+      return validate_email_sender(email);
+    }
+    EmailProvider::Resend => {
+      let Some(ref resend) = email.resend else {
+        return ierr("Resend config missing.");
+      };
+      require(resend.api_key.as_ref(), "Resend API key")?;
+      validate_endpoint(resend.endpoint.as_ref())?;
+      return validate_email_sender(email);
+    }
+  }
 
   let Some(host) = &email.smtp_host else {
     match (email.smtp_port, &email.smtp_username, &email.smtp_password) {
@@ -705,15 +1444,7 @@ pub(crate) fn validate_email_config(email: &proto::EmailConfig) -> Result<(), Co
     return ierr(format!("SMTP host '{host}' is invalid."));
   }
 
-  // NOTE: When no explicit sender is given, we fall back to noreply@host.
-  if let Some(ref sender_address) = email.sender_address {
-    if !sender_address.validate_email() {
-      return ierr("Invalid sender address.");
-    };
-    if email.sender_name.is_none() {
-      return ierr("Sender address but missing sender name.");
-    }
-  }
+  validate_email_sender(email)?;
 
   let _port: u16 = match email.smtp_port {
     Some(port) => {
@@ -756,7 +1487,21 @@ pub(crate) fn validate_email_config(email: &proto::EmailConfig) -> Result<(), Co
   };
 }
 
-fn validate_email_template(
+fn validate_email_sender(email: &proto::EmailConfig) -> Result<(), ConfigError> {
+  // NOTE: When no explicit sender is given, we fall back to noreply@host.
+  if let Some(ref sender_address) = email.sender_address {
+    if !sender_address.validate_email() {
+      return ierr("Invalid sender address.");
+    };
+    if email.sender_name.is_none() {
+      return ierr("Sender address but missing sender name.");
+    }
+  }
+
+  return Ok(());
+}
+
+pub(crate) fn validate_email_template(
   template: Option<&EmailTemplate>,
   acceptable_vars: &[&str],
 ) -> Result<(), ConfigError> {
@@ -839,6 +1584,242 @@ mod test {
     test_config_stripping();
     test_config_merging_from_env_and_vault();
     test_strip_and_merge();
+    test_config_references();
+  }
+
+  #[test]
+  fn test_record_api_qualified_table_name() {
+    let api = |table_name: &str, database: Option<&str>| proto::RecordApiConfig {
+      table_name: Some(table_name.to_string()),
+      database: database.map(|db| db.to_string()),
+      ..Default::default()
+    };
+
+    let name = api("posts", None).qualified_table_name().unwrap();
+    assert_eq!(name.database_schema, None);
+    assert!(api("posts", None).databases_to_attach().is_empty());
+    assert!(api("posts", Some("main")).databases_to_attach().is_empty());
+
+    for config in [api("posts", Some("other")), api("other.posts", None)] {
+      let name = config.qualified_table_name().unwrap();
+      assert_eq!(name.name, "posts");
+      assert_eq!(name.database_schema.as_deref(), Some("other"));
+      assert_eq!(config.databases_to_attach(), vec!["other".to_string()]);
+    }
+
+    assert!(
+      api("other.posts", Some("other"))
+        .qualified_table_name()
+        .is_ok()
+    );
+    assert!(
+      api("other.posts", Some("another"))
+        .qualified_table_name()
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn test_object_store_config_validation() {
+    let server = |object_store: proto::ObjectStoreConfig| proto::ServerConfig {
+      object_store: Some(object_store),
+      ..Default::default()
+    };
+
+    assert!(validate_object_store_config(&proto::ServerConfig::default()).is_ok());
+    assert!(
+      validate_object_store_config(&server(proto::ObjectStoreConfig {
+        gcs: Some(proto::GcsStorageConfig {
+          bucket_name: Some("bucket".to_string()),
+          ..Default::default()
+        }),
+        ..Default::default()
+      }))
+      .is_ok()
+    );
+
+    // Missing bucket.
+    assert!(
+      validate_object_store_config(&server(proto::ObjectStoreConfig {
+        s3: Some(proto::S3StorageConfig::default()),
+        ..Default::default()
+      }))
+      .is_err()
+    );
+
+    // Multiple backends.
+    assert!(
+      validate_object_store_config(&server(proto::ObjectStoreConfig {
+        local: Some(proto::LocalStorageConfig {
+          path: Some("files".to_string()),
+        }),
+        azure: Some(proto::AzureStorageConfig {
+          account: Some("account".to_string()),
+          container_name: Some("container".to_string()),
+          ..Default::default()
+        }),
+        ..Default::default()
+      }))
+      .is_err()
+    );
+
+    // Both, legacy and new config.
+    assert!(
+      validate_object_store_config(&proto::ServerConfig {
+        s3_storage_config: Some(proto::S3StorageConfig {
+          bucket_name: Some("bucket".to_string()),
+          ..Default::default()
+        }),
+        object_store: Some(proto::ObjectStoreConfig::default()),
+        ..Default::default()
+      })
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn test_cors_config_validation() {
+    let server = |policy: proto::CorsPolicy| proto::ServerConfig {
+      cors: Some(proto::CorsConfig {
+        records: Some(policy),
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+
+    assert!(validate_cors_config(&proto::ServerConfig::default()).is_ok());
+    assert!(
+      validate_cors_config(&server(proto::CorsPolicy {
+        allowed_origins: vec![
+          "*".to_string(),
+          "https://example.com".to_string(),
+          "https://*.example.com".to_string(),
+          "http://localhost:3000".to_string(),
+        ],
+        allowed_headers: vec!["content-type".to_string()],
+        allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+        ..Default::default()
+      }))
+      .is_ok()
+    );
+
+    for origin in [
+      "example.com",
+      "https://example.com/path",
+      "ftp://example.com",
+    ] {
+      assert!(
+        validate_cors_config(&server(proto::CorsPolicy {
+          allowed_origins: vec![origin.to_string()],
+          ..Default::default()
+        }))
+        .is_err(),
+        "{origin}"
+      );
+    }
+
+    assert!(
+      validate_cors_config(&server(proto::CorsPolicy {
+        allowed_headers: vec!["invalid header".to_string()],
+        ..Default::default()
+      }))
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn test_proxy_routes_config_validation() {
+    let route = |prefix: &str, url: &str| proto::ProxyRouteConfig {
+      path_prefix: Some(prefix.to_string()),
+      upstream_url: Some(url.to_string()),
+      ..Default::default()
+    };
+
+    assert!(validate_proxy_routes_config(&[]).is_ok());
+    assert!(
+      validate_proxy_routes_config(&[
+        route("/api/payments", "http://localhost:9000"),
+        route("/api/search/", "https://search.internal/v1"),
+      ])
+      .is_ok()
+    );
+
+    // Invalid prefixes.
+    assert!(
+      validate_proxy_routes_config(&[route("api/payments", "http://localhost:9000")]).is_err()
+    );
+    assert!(validate_proxy_routes_config(&[route("/", "http://localhost:9000")]).is_err());
+    assert!(validate_proxy_routes_config(&[route("/api", "http://localhost:9000")]).is_err());
+    assert!(
+      validate_proxy_routes_config(&[route("/api/records/v1/x", "http://localhost:9000")]).is_err()
+    );
+    assert!(validate_proxy_routes_config(&[route("/api/{id}", "http://localhost:9000")]).is_err());
+
+    // Invalid upstream.
+    assert!(validate_proxy_routes_config(&[route("/api/payments", "localhost:9000")]).is_err());
+
+    // Duplicates.
+    assert!(
+      validate_proxy_routes_config(&[
+        route("/api/payments", "http://localhost:9000"),
+        route("/api/payments/", "http://localhost:9001"),
+      ])
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn test_log_sinks_config_validation() {
+    let server = |sink: proto::LogSinkConfig| proto::ServerConfig {
+      log_sinks: vec![sink],
+      ..Default::default()
+    };
+
+    assert!(validate_log_sinks_config(&proto::ServerConfig::default()).is_ok());
+    assert!(
+      validate_log_sinks_config(&server(proto::LogSinkConfig {
+        loki: Some(proto::LokiLogSinkConfig {
+          url: Some("http://localhost:3100".to_string()),
+          ..Default::default()
+        }),
+        ..Default::default()
+      }))
+      .is_ok()
+    );
+
+    // Missing path.
+    assert!(
+      validate_log_sinks_config(&server(proto::LogSinkConfig {
+        file: Some(proto::FileLogSinkConfig::default()),
+        ..Default::default()
+      }))
+      .is_err()
+    );
+
+    // Invalid facility.
+    assert!(
+      validate_log_sinks_config(&server(proto::LogSinkConfig {
+        syslog: Some(proto::SyslogLogSinkConfig {
+          facility: Some(24),
+          ..Default::default()
+        }),
+        ..Default::default()
+      }))
+      .is_err()
+    );
+
+    // Multiple sinks in one entry.
+    assert!(
+      validate_log_sinks_config(&server(proto::LogSinkConfig {
+        file: Some(proto::FileLogSinkConfig {
+          path: Some("requests.jsonl".to_string()),
+          ..Default::default()
+        }),
+        syslog: Some(proto::SyslogLogSinkConfig::default()),
+        ..Default::default()
+      }))
+      .is_err()
+    );
   }
 
   async fn test_default_config_is_valid() {
@@ -1011,6 +1992,97 @@ mod test {
     assert_eq!(config, merged);
   }
 
+  fn test_config_references() {
+    test_env::set("SMTP_HOST", Some("smtp.test.org"));
+
+    let vault = proto::Vault {
+      secrets: HashMap::<String, String>::from([(
+        "smtp_password".to_string(),
+        "secret_password".to_string(),
+      )]),
+    };
+
+    let config = proto::Config {
+      email: EmailConfig {
+        smtp_host: Some("${SMTP_HOST}".to_string()),
+        smtp_password: Some("secret://smtp_password".to_string()),
+        sender_name: Some("$${NOT_A_REFERENCE} of ${SMTP_HOST}".to_string()),
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let merged = merge_vault_and_env(config.clone(), vault.clone()).unwrap();
+    assert_eq!(merged.email.smtp_host.as_deref(), Some("smtp.test.org"));
+    assert_eq!(
+      merged.email.smtp_password.as_deref(),
+      Some("secret_password")
+    );
+    assert_eq!(
+      merged.email.sender_name.as_deref(),
+      Some("${NOT_A_REFERENCE} of smtp.test.org")
+    );
+
+    let missing_env = proto::Config {
+      email: EmailConfig {
+        smtp_host: Some("${MISSING}".to_string()),
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+    let err = merge_vault_and_env(missing_env, vault.clone()).unwrap_err();
+    assert!(err.to_string().contains("MISSING"), "{err}");
+    assert!(err.to_string().contains("email.smtp_host"), "{err}");
+
+    let missing_secret = proto::Config {
+      email: EmailConfig {
+        smtp_password: Some("secret://missing".to_string()),
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+    assert!(merge_vault_and_env(missing_secret, vault.clone()).is_err());
+
+    // Writing back the resolved config restores the references.
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let data_dir = DataDir(temp_dir.path().to_path_buf());
+    std::fs::create_dir_all(data_dir.secrets_path()).unwrap();
+    std::fs::write(
+      data_dir.config_path().join(CONFIG_FILENAME),
+      config.to_text().unwrap(),
+    )
+    .unwrap();
+    std::fs::write(
+      data_dir.secrets_path().join(VAULT_FILENAME),
+      vault.to_text().unwrap(),
+    )
+    .unwrap();
+
+    let restored = restore_references(&data_dir, &merged).unwrap();
+
+    assert_eq!(restored.email.smtp_host, config.email.smtp_host);
+    assert_eq!(restored.email.smtp_password, config.email.smtp_password);
+
+    // Only unchanged fields with on-disk references are restored. Values that merely coincide with
+    // a resolved reference are kept as is.
+    let mut updated = merged.clone();
+    updated.email.smtp_username = Some("smtp.test.org".to_string());
+    updated.email.smtp_password = Some("new_password".to_string());
+
+    let restored = restore_references(&data_dir, &updated).unwrap();
+    test_env::clear();
+
+    assert_eq!(restored.email.smtp_host, config.email.smtp_host);
+    assert_eq!(
+      restored.email.smtp_username.as_deref(),
+      Some("smtp.test.org")
+    );
+    assert_eq!(
+      restored.email.smtp_password.as_deref(),
+      Some("new_password")
+    );
+  }
+
   #[test]
   fn test_is_valid_hostname_or_ip() {
     assert_eq!(false, is_valid_hostname_or_ip(""));
@@ -1059,4 +2131,76 @@ mod test {
     };
     validate_email_template(Some(&template), &["TOKEN"]).unwrap();
   }
+
+  #[test]
+  fn test_validate_email_provider_config() {
+    let email = |provider: EmailProvider| {
+      let mut config = proto::EmailConfig::default();
+      config.set_provider(provider);
+      return config;
+    };
+
+    assert!(validate_email_config(&email(EmailProvider::Undefined)).is_ok());
+    assert!(validate_email_config(&email(EmailProvider::Sendmail)).is_ok());
+    assert!(validate_email_config(&email(EmailProvider::Smtp)).is_err());
+    assert!(validate_email_config(&email(EmailProvider::Resend)).is_err());
+
+    let resend = |api_key: &str, endpoint: Option<&str>| proto::EmailConfig {
+      resend: Some(proto::ResendConfig {
+        api_key: Some(api_key.to_string()),
+        endpoint: endpoint.map(|e| e.to_string()),
+      }),
+      ..email(EmailProvider::Resend)
+    };
+    assert!(validate_email_config(&resend("re_key", None)).is_ok());
+    assert!(validate_email_config(&resend("", None)).is_err());
+    assert!(validate_email_config(&resend("re_key", Some("localhost:8080"))).is_err());
+
+    let mailgun = proto::EmailConfig {
+      mailgun: Some(proto::MailgunConfig {
+        api_key: Some("key".to_string()),
+        ..Default::default()
+      }),
+      ..email(EmailProvider::Mailgun)
+    };
+    assert!(validate_email_config(&mailgun).is_err());
+
+    assert!(
+      validate_email_config(&proto::EmailConfig {
+        max_attempts: Some(0),
+        ..Default::default()
+      })
+      .is_err()
+    );
+    assert!(
+      validate_email_config(&proto::EmailConfig {
+        rate_limit_per_minute: Some(0),
+        ..Default::default()
+      })
+      .is_err()
+    );
+  }
+  #[test]
+  fn test_validate_push_config() {
+    assert!(validate_push_config(&proto::PushConfig::default()).is_ok());
+
+    let web_push = |subject: &str| proto::PushConfig {
+      web_push: Some(proto::WebPushConfig {
+        vapid_private_key: Some("key".to_string()),
+        subject: Some(subject.to_string()),
+      }),
+      ..Default::default()
+    };
+    assert!(validate_push_config(&web_push("mailto:admin@test.org")).is_ok());
+    assert!(validate_push_config(&web_push("admin@test.org")).is_err());
+
+    let apns = proto::PushConfig {
+      apns: Some(proto::ApnsConfig {
+        team_id: Some("team".to_string()),
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
+    assert!(validate_push_config(&apns).is_err());
+  }
 }
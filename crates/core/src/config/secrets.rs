@@ -0,0 +1,526 @@
+//! External secrets backends.
+//!
+//! Config values can reference secrets stored in an external backend using
+//! `secret://<backend>/<name>`, e.g. `secret://vault/myapp/smtp#password`. References are resolved
+//! ahead of merging the config and the resolved values are injected into the in-memory vault, i.e.
+//! they're never written to disk. Resolution is repeated periodically to pick up rotated
+//! credentials.
+
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::app_state::AppState;
+use crate::config::proto::{self, SecretsConfig};
+use crate::config::{ConfigError, SECRET_REFERENCE_PREFIX, merge_vault_and_env};
+
+#[derive(Debug, Error)]
+pub enum SecretError {
+  #[error("Not found: {0}")]
+  NotFound(String),
+  #[error("Invalid reference: {0}")]
+  InvalidReference(String),
+  #[error("Backend: {0}")]
+  Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Resolves secrets from an external backend.
+///
+/// The name is the part of a `secret://<backend>/<name>` reference following the backend.
+#[async_trait]
+pub trait SecretResolver: Send + Sync {
+  async fn resolve(&self, name: &str) -> Result<String, SecretError>;
+}
+
+lazy_static! {
+  /// Most recently resolved external secrets keyed by reference w/o the `secret://` prefix.
+  static ref RESOLVED_SECRETS: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
+
+/// Most recently resolved external secrets, which are merged into the vault.
+pub(crate) fn resolved_secrets() -> HashMap<String, String> {
+  return RESOLVED_SECRETS.read().clone();
+}
+
+/// Builds the resolvers for the configured backends keyed by backend name.
+pub(crate) fn build_secret_resolvers(
+  config: &SecretsConfig,
+) -> Result<HashMap<&'static str, Arc<dyn SecretResolver>>, ConfigError> {
+  #[allow(unused_mut)]
+  let mut resolvers = HashMap::<&'static str, Arc<dyn SecretResolver>>::new();
+
+  if let Some(ref _vault) = config.hashicorp_vault {
+    #[cfg(not(feature = "hashicorp-vault"))]
+    return Err(ConfigError::Invalid(
+      "HashiCorp Vault secrets require the 'hashicorp-vault' feature".to_string(),
+    ));
+
+    #[cfg(feature = "hashicorp-vault")]
+    resolvers.insert(
+      HASHICORP_VAULT,
+      Arc::new(hashicorp_vault::HashiCorpVaultResolver::new(_vault)?),
+    );
+  }
+
+  if let Some(ref _aws) = config.aws_secrets_manager {
+    #[cfg(not(feature = "aws-secrets-manager"))]
+    return Err(ConfigError::Invalid(
+      "AWS Secrets Manager secrets require the 'aws-secrets-manager' feature".to_string(),
+    ));
+
+    #[cfg(feature = "aws-secrets-manager")]
+    resolvers.insert(
+      AWS_SECRETS_MANAGER,
+      Arc::new(aws_secrets_manager::AwsSecretsManagerResolver::new(_aws)?),
+    );
+  }
+
+  return Ok(resolvers);
+}
+
+/// Resolves all external secret references in the given config and the vault.
+///
+/// Secrets failing to resolve keep their previously resolved value, if any, to ride out transient
+/// backend outages.
+pub(crate) async fn resolve_external_secrets(
+  config: &proto::Config,
+  vault: &proto::Vault,
+) -> Result<(), ConfigError> {
+  let Some(ref secrets_config) = config.secrets else {
+    return Ok(());
+  };
+
+  // The backends' own settings may reference env variables and local secrets, e.g. the token.
+  let secrets_config = merge_vault_and_env(
+    proto::Config {
+      secrets: Some(secrets_config.clone()),
+      ..Default::default()
+    },
+    vault.clone(),
+  )?
+  .secrets
+  .unwrap_or_default();
+  let resolvers = build_secret_resolvers(&secrets_config)?;
+  if resolvers.is_empty() {
+    return Ok(());
+  }
+
+  let mut references = Vec::<String>::new();
+  let mut collect = |_path: &str, value: &mut String| {
+    if let Some(reference) = value.strip_prefix(SECRET_REFERENCE_PREFIX)
+      && let Some((backend, _name)) = reference.split_once('/')
+      && resolvers.contains_key(backend)
+    {
+      references.push(reference.to_string());
+    }
+    return Ok(());
+  };
+  super::recursively_visit_strings(&mut config.transcode_to_dynamic(), "", &mut collect)?;
+  for value in vault.secrets.values() {
+    collect("", &mut value.clone())?;
+  }
+
+  let previous = resolved_secrets();
+  let mut resolved = HashMap::<String, String>::new();
+  for reference in references {
+    if resolved.contains_key(&reference) {
+      continue;
+    }
+
+    let Some((backend, name)) = reference.split_once('/') else {
+      continue;
+    };
+    let resolver = &resolvers[backend];
+
+    match resolver.resolve(name).await {
+      Ok(value) => {
+        resolved.insert(reference, value);
+      }
+      Err(err) => {
+        let Some(value) = previous.get(&reference) else {
+          return Err(ConfigError::Invalid(format!(
+            "Failed to resolve secret '{reference}': {err}"
+          )));
+        };
+        warn!("Failed to refresh secret '{reference}', keeping previous value: {err}");
+        resolved.insert(reference, value.clone());
+      }
+    }
+  }
+
+  *RESOLVED_SECRETS.write() = resolved;
+
+  return Ok(());
+}
+
+fn refresh_interval(config: Option<&SecretsConfig>) -> Option<Duration> {
+  let config = config?;
+  if config.hashicorp_vault.is_none() && config.aws_secrets_manager.is_none() {
+    return None;
+  }
+
+  return match config
+    .refresh_interval_sec
+    .unwrap_or(DEFAULT_REFRESH_INTERVAL_SEC)
+  {
+    0 => None,
+    sec => Some(Duration::from_secs(sec as u64)),
+  };
+}
+
+/// Spawns a task periodically re-resolving external secrets and applying the config if any of them
+/// changed, e.g. due to rotation.
+pub(crate) fn spawn_secrets_refresher(state: AppState) -> tokio::task::JoinHandle<()> {
+  return tokio::spawn(async move {
+    loop {
+      let interval = state.access_config(|c| refresh_interval(c.secrets.as_ref()));
+      let Some(interval) = interval else {
+        // Re-check for changed settings later.
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        continue;
+      };
+      tokio::time::sleep(interval).await;
+
+      match crate::config_watcher::reload_config(&state).await {
+        Ok(crate::config_watcher::ReloadOutcome::Applied) => {
+          info!("Applied config with refreshed secrets");
+        }
+        Ok(_) => {}
+        Err(err) => error!("Failed to refresh secrets: {err}"),
+      }
+    }
+  });
+}
+
+#[cfg(feature = "hashicorp-vault")]
+mod hashicorp_vault {
+  use super::*;
+
+  /// Resolves `<path>#<key>` from a KV v2 secrets engine. The key defaults to "value".
+  pub(super) struct HashiCorpVaultResolver {
+    client: reqwest::Client,
+    address: String,
+    token: String,
+    mount: String,
+    namespace: Option<String>,
+  }
+
+  impl HashiCorpVaultResolver {
+    pub(super) fn new(config: &proto::HashiCorpVaultConfig) -> Result<Self, ConfigError> {
+      let Some(ref address) = config.address else {
+        return Err(ConfigError::Invalid(
+          "Missing HashiCorp Vault address".into(),
+        ));
+      };
+      let Some(token) = config
+        .token
+        .clone()
+        .or_else(|| std::env::var("VAULT_TOKEN").ok())
+      else {
+        return Err(ConfigError::Invalid("Missing HashiCorp Vault token".into()));
+      };
+
+      return Ok(Self {
+        client: reqwest::Client::new(),
+        address: address.trim_end_matches('/').to_string(),
+        token,
+        mount: config
+          .mount
+          .as_deref()
+          .unwrap_or("secret")
+          .trim_matches('/')
+          .to_string(),
+        namespace: config.namespace.clone(),
+      });
+    }
+  }
+
+  #[async_trait]
+  impl SecretResolver for HashiCorpVaultResolver {
+    async fn resolve(&self, name: &str) -> Result<String, SecretError> {
+      let (path, key) = name.split_once('#').unwrap_or((name, "value"));
+      if path.is_empty() || path.contains("..") {
+        return Err(SecretError::InvalidReference(name.to_string()));
+      }
+
+      let mut request = self
+        .client
+        .get(format!(
+          "{address}/v1/{mount}/data/{path}",
+          address = self.address,
+          mount = self.mount
+        ))
+        .header("X-Vault-Token", &self.token);
+      if let Some(ref namespace) = self.namespace {
+        request = request.header("X-Vault-Namespace", namespace);
+      }
+
+      let response = request
+        .send()
+        .await
+        .map_err(|err| SecretError::Backend(err.into()))?;
+      if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(SecretError::NotFound(name.to_string()));
+      }
+      let json: serde_json::Value = response
+        .error_for_status()
+        .map_err(|err| SecretError::Backend(err.into()))?
+        .json()
+        .await
+        .map_err(|err| SecretError::Backend(err.into()))?;
+
+      return match &json["data"]["data"][key] {
+        serde_json::Value::String(value) => Ok(value.clone()),
+        serde_json::Value::Null => Err(SecretError::NotFound(name.to_string())),
+        value => Ok(value.to_string()),
+      };
+    }
+  }
+}
+
+#[cfg(feature = "aws-secrets-manager")]
+mod aws_secrets_manager {
+  use hmac::{Hmac, Mac};
+  use sha2::{Digest, Sha256};
+
+  use super::*;
+
+  /// Resolves `<secret-id>[#<key>]` using GetSecretValue. With a key, the secret string is parsed
+  /// as JSON object and the key's value is returned.
+  pub(super) struct AwsSecretsManagerResolver {
+    client: reqwest::Client,
+    region: String,
+    endpoint: String,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+  }
+
+  impl AwsSecretsManagerResolver {
+    pub(super) fn new(config: &proto::AwsSecretsManagerConfig) -> Result<Self, ConfigError> {
+      let setting = |value: &Option<String>, env: &str| -> Option<String> {
+        return value.clone().or_else(|| std::env::var(env).ok());
+      };
+
+      let Some(region) = setting(&config.region, "AWS_REGION") else {
+        return Err(ConfigError::Invalid("Missing AWS region".into()));
+      };
+      let (Some(access_key_id), Some(secret_access_key)) = (
+        setting(&config.access_key_id, "AWS_ACCESS_KEY_ID"),
+        setting(&config.secret_access_key, "AWS_SECRET_ACCESS_KEY"),
+      ) else {
+        return Err(ConfigError::Invalid("Missing AWS credentials".into()));
+      };
+
+      return Ok(Self {
+        client: reqwest::Client::new(),
+        endpoint: config
+          .endpoint
+          .clone()
+          .unwrap_or_else(|| format!("https://secretsmanager.{region}.amazonaws.com")),
+        region,
+        access_key_id,
+        secret_access_key,
+        session_token: setting(&config.session_token, "AWS_SESSION_TOKEN"),
+      });
+    }
+
+    /// Signs the request using AWS Signature Version 4.
+    fn authorization(
+      &self,
+      host: &str,
+      amz_date: &str,
+      headers: &[(&str, &str)],
+      body: &str,
+    ) -> String {
+      let date = &amz_date[..8];
+      let scope = format!(
+        "{date}/{region}/{SERVICE}/aws4_request",
+        region = self.region
+      );
+
+      // NOTE: Headers must be sorted by name.
+      let mut canonical_headers: Vec<(&str, &str)> = vec![("host", host)];
+      canonical_headers.extend_from_slice(headers);
+      canonical_headers.sort_by_key(|(name, _)| *name);
+
+      let signed_headers = canonical_headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+      let canonical_request = format!(
+        "POST\n/\n\n{headers}\n{signed_headers}\n{payload}",
+        headers = canonical_headers
+          .iter()
+          .map(|(name, value)| format!("{name}:{value}\n"))
+          .collect::<String>(),
+        payload = hex(&Sha256::digest(body.as_bytes())),
+      );
+      let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{hash}",
+        hash = hex(&Sha256::digest(canonical_request.as_bytes())),
+      );
+
+      let key = [self.region.as_str(), SERVICE, "aws4_request"]
+        .into_iter()
+        .fold(
+          hmac(format!("AWS4{}", self.secret_access_key).as_bytes(), date),
+          |key, segment| hmac(&key, segment),
+        );
+
+      return format!(
+        "AWS4-HMAC-SHA256 Credential={access_key_id}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        access_key_id = self.access_key_id,
+        signature = hex(&hmac(&key, &string_to_sign)),
+      );
+    }
+  }
+
+  #[async_trait]
+  impl SecretResolver for AwsSecretsManagerResolver {
+    async fn resolve(&self, name: &str) -> Result<String, SecretError> {
+      let (secret_id, key) = match name.split_once('#') {
+        Some((secret_id, key)) => (secret_id, Some(key)),
+        None => (name, None),
+      };
+
+      let url = url::Url::parse(&self.endpoint).map_err(|err| SecretError::Backend(err.into()))?;
+      let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return Err(SecretError::Backend("invalid endpoint".into())),
+      };
+
+      let body = serde_json::json!({ "SecretId": secret_id }).to_string();
+      let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+      let mut headers = vec![
+        ("content-type", "application/x-amz-json-1.1"),
+        ("x-amz-date", amz_date.as_str()),
+        ("x-amz-target", "secretsmanager.GetSecretValue"),
+      ];
+      if let Some(ref token) = self.session_token {
+        headers.push(("x-amz-security-token", token.as_str()));
+      }
+      let authorization = self.authorization(&host, &amz_date, &headers, &body);
+
+      let mut request = self
+        .client
+        .post(url)
+        .header("authorization", authorization)
+        .body(body);
+      for (name, value) in headers {
+        request = request.header(name, value);
+      }
+
+      let response = request
+        .send()
+        .await
+        .map_err(|err| SecretError::Backend(err.into()))?;
+      let status = response.status();
+      let json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|err| SecretError::Backend(err.into()))?;
+      if !status.is_success() {
+        if json["__type"]
+          .as_str()
+          .is_some_and(|t| t.ends_with("ResourceNotFoundException"))
+        {
+          return Err(SecretError::NotFound(name.to_string()));
+        }
+        return Err(SecretError::Backend(
+          format!("GetSecretValue failed ({status}): {json}").into(),
+        ));
+      }
+
+      let Some(secret) = json["SecretString"].as_str() else {
+        return Err(SecretError::Backend("missing SecretString".into()));
+      };
+      let Some(key) = key else {
+        return Ok(secret.to_string());
+      };
+
+      let object: serde_json::Value =
+        serde_json::from_str(secret).map_err(|err| SecretError::Backend(err.into()))?;
+      return match &object[key] {
+        serde_json::Value::String(value) => Ok(value.clone()),
+        serde_json::Value::Null => Err(SecretError::NotFound(name.to_string())),
+        value => Ok(value.to_string()),
+      };
+    }
+  }
+
+  fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length");
+    mac.update(data.as_bytes());
+    return mac.finalize().into_bytes().to_vec();
+  }
+
+  fn hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|b| format!("{b:02x}")).collect();
+  }
+
+  const SERVICE: &str = "secretsmanager";
+
+  #[cfg(test)]
+  mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigv4_signing_key() {
+      // Example from the AWS Signature Version 4 documentation.
+      let key = ["us-east-1", "iam", "aws4_request"].into_iter().fold(
+        hmac(
+          "AWS4wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".as_bytes(),
+          "20150830",
+        ),
+        |key, segment| hmac(&key, segment),
+      );
+      assert_eq!(
+        hex(&key),
+        "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+      );
+    }
+  }
+}
+
+#[cfg(feature = "hashicorp-vault")]
+const HASHICORP_VAULT: &str = "vault";
+#[cfg(feature = "aws-secrets-manager")]
+const AWS_SECRETS_MANAGER: &str = "aws";
+
+const DEFAULT_REFRESH_INTERVAL_SEC: u32 = 300;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_refresh_interval() {
+    assert_eq!(refresh_interval(None), None);
+    assert_eq!(refresh_interval(Some(&SecretsConfig::default())), None);
+
+    let config = SecretsConfig {
+      hashicorp_vault: Some(proto::HashiCorpVaultConfig::default()),
+      ..Default::default()
+    };
+    assert_eq!(
+      refresh_interval(Some(&config)),
+      Some(Duration::from_secs(300))
+    );
+    assert_eq!(
+      refresh_interval(Some(&SecretsConfig {
+        refresh_interval_sec: Some(0),
+        ..config
+      })),
+      None
+    );
+  }
+}
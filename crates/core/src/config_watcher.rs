@@ -0,0 +1,123 @@
+//! Hot-reloading of the config and vault files on change.
+//!
+//! The files are polled for changes to their modification time or size. Changed configs are
+//! validated and applied at runtime, invalid edits are rejected and the current config is kept.
+
+use log::*;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::app_state::AppState;
+use crate::config::proto::hash_config;
+use crate::config::{ConfigError, config_file_paths, load_config_textproto};
+
+/// Modification time and size of a watched file, or `None` if missing.
+type Fingerprint = Vec<Option<(SystemTime, u64)>>;
+
+async fn fingerprint(paths: &[PathBuf]) -> Fingerprint {
+  let mut fingerprint = Vec::with_capacity(paths.len());
+  for path in paths {
+    fingerprint.push(
+      tokio::fs::metadata(path)
+        .await
+        .ok()
+        .and_then(|m| Some((m.modified().ok()?, m.len()))),
+    );
+  }
+  return fingerprint;
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum ReloadOutcome {
+  Applied,
+  /// The config on disk matches the current config, e.g. after an update from the admin UI was
+  /// written back.
+  Unchanged,
+  /// The config file was removed. The current config is kept.
+  Missing,
+}
+
+/// Loads the config from disk and applies it if changed.
+pub(crate) async fn reload_config(state: &AppState) -> Result<ReloadOutcome, ConfigError> {
+  let Some(config) = load_config_textproto(state.data_dir()).await? else {
+    return Ok(ReloadOutcome::Missing);
+  };
+
+  if hash_config(&config) == hash_config(&state.get_config()) {
+    return Ok(ReloadOutcome::Unchanged);
+  }
+
+  state.validate_and_update_config(config, None).await?;
+  return Ok(ReloadOutcome::Applied);
+}
+
+/// Spawns a task watching the config and vault files and applying changes at runtime.
+pub(crate) fn spawn_config_watcher(state: AppState) -> tokio::task::JoinHandle<()> {
+  return tokio::spawn(async move {
+    let paths = config_file_paths(state.data_dir());
+    let mut last = fingerprint(&paths).await;
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+      interval.tick().await;
+
+      let current = fingerprint(&paths).await;
+      if current == last {
+        continue;
+      }
+      last = current;
+
+      match reload_config(&state).await {
+        Ok(ReloadOutcome::Applied) => info!("Config change applied: {paths:?}"),
+        Ok(ReloadOutcome::Unchanged) => {}
+        Ok(ReloadOutcome::Missing) => warn!("Config file removed, keeping current config"),
+        Err(err) => error!("Rejected invalid config edit, keeping current config: {err}"),
+      }
+    }
+  });
+}
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_reload_config() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let [config_path, _vault_path] = config_file_paths(state.data_dir());
+
+    assert_eq!(reload_config(&state).await.unwrap(), ReloadOutcome::Missing);
+
+    let mut config = (*state.get_config()).clone();
+    std::fs::write(&config_path, config.to_text().unwrap()).unwrap();
+    assert_eq!(
+      reload_config(&state).await.unwrap(),
+      ReloadOutcome::Unchanged
+    );
+
+    config.server.application_name = Some("Reloaded".to_string());
+    std::fs::write(&config_path, config.to_text().unwrap()).unwrap();
+    assert_eq!(reload_config(&state).await.unwrap(), ReloadOutcome::Applied);
+    assert_eq!(
+      state.get_config().server.application_name.as_deref(),
+      Some("Reloaded")
+    );
+
+    // Invalid edits are rejected.
+    config.server.application_name = Some("Invalid/Name".to_string());
+    std::fs::write(&config_path, config.to_text().unwrap()).unwrap();
+    assert!(reload_config(&state).await.is_err());
+
+    std::fs::write(&config_path, "server { unknown_field: 1 }").unwrap();
+    assert!(reload_config(&state).await.is_err());
+
+    assert_eq!(
+      state.get_config().server.application_name.as_deref(),
+      Some("Reloaded")
+    );
+  }
+}
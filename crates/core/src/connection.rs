@@ -1,9 +1,10 @@
 use parking_lot::RwLock;
 use quick_cache::sync::GuardResult;
 use std::collections::BTreeSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use thiserror::Error;
+use trailbase_extension::SqliteFunctionRegistrar;
 use trailbase_extension::jsonschema::JsonSchemaRegistry;
 use trailbase_schema::metadata::ConnectionMetadata;
 
@@ -77,10 +78,15 @@ struct ConnectionManagerState {
   data_dir: DataDir,
   json_schema_registry: Arc<RwLock<trailbase_schema::registry::JsonSchemaRegistry>>,
   sqlite_function_runtimes: Vec<(SqliteStore, SqliteFunctions)>,
+  sqlite_functions: Vec<SqliteFunctionRegistrar>,
 
   // Properties for caching connections:
   main: RwLock<ConnectionEntry>,
+  /// Optional pool of read-only connections to the main database or a replicated copy.
+  replica: Option<Arc<Connection>>,
   connections: quick_cache::sync::Cache<ConnectionKey, ConnectionEntry>,
+  /// Lazily opened tenant databases keyed by tenant name. `None` is the schema-only template.
+  tenants: quick_cache::sync::Cache<Option<String>, ConnectionEntry>,
 
   #[allow(unused)]
   pg_uri: Option<String>,
@@ -99,7 +105,25 @@ pub struct Options {
   pub data_dir: DataDir,
   pub json_schema_registry: Arc<RwLock<trailbase_schema::registry::JsonSchemaRegistry>>,
   pub sqlite_function_runtimes: Vec<(SqliteStore, SqliteFunctions)>,
+  /// Custom SQL functions registered on every connection, e.g. by embedders.
+  pub sqlite_functions: Vec<SqliteFunctionRegistrar>,
   pub pg_uri: Option<String>,
+  pub read_replica: Option<ReadReplicaOptions>,
+}
+
+/// Pool of read-only connections serving read-only queries, e.g. record reads and listings.
+///
+/// Unlike the main connection's readers, the pool doesn't contend with writes for the main
+/// connection's lock.
+#[derive(Clone, Debug, Default)]
+pub struct ReadReplicaOptions {
+  /// Replicated copy of the main database, e.g. maintained by LiteFS. Defaults to the main
+  /// database itself.
+  ///
+  /// NOTE: Reads from copies are only eventually consistent with writes.
+  pub path: Option<PathBuf>,
+  /// Number of read-only connections. Defaults to the number of available cores.
+  pub num_threads: Option<usize>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -115,7 +139,9 @@ impl ConnectionManager {
       data_dir,
       json_schema_registry,
       sqlite_function_runtimes,
+      sqlite_functions,
       pg_uri,
+      read_replica,
     } = opts;
 
     let (main_conn, main_metadata, new_db) = if let Some(ref pg_uri) = pg_uri {
@@ -130,6 +156,7 @@ impl ConnectionManager {
           is_main_db: true,
           json_registry: &json_schema_registry,
           runtimes: &sqlite_function_runtimes,
+          functions: &sqlite_functions,
           attach: vec![],
           num_threads: None,
         },
@@ -143,23 +170,43 @@ impl ConnectionManager {
         is_main_db: true,
         json_registry: &json_schema_registry,
         runtimes: &sqlite_function_runtimes,
+        functions: &sqlite_functions,
         attach: vec![],
         num_threads: None,
       })
       .await?
     };
 
+    let replica = match read_replica {
+      Some(_) if pg_uri.is_some() => {
+        return Err(ConnectionError::InvalidSetting(
+          "Read replicas require SQLite",
+        ));
+      }
+      Some(replica) => Some(Arc::new(init_replica_sqlite(
+        replica.path.unwrap_or_else(|| data_dir.main_db_path()),
+        replica.num_threads,
+        &json_schema_registry,
+        &sqlite_function_runtimes,
+        &sqlite_functions,
+      )?)),
+      None => None,
+    };
+
     return Ok((
       Self {
         state: Arc::new(ConnectionManagerState {
           data_dir,
           json_schema_registry,
           sqlite_function_runtimes,
+          sqlite_functions,
           main: RwLock::new(ConnectionEntry {
             connection: Arc::new(main_conn),
             metadata: Arc::new(main_metadata),
           }),
+          replica,
           connections: quick_cache::sync::Cache::new(256),
+          tenants: quick_cache::sync::Cache::new(256),
           pg_uri,
         }),
       },
@@ -174,6 +221,7 @@ impl ConnectionManager {
     sqlite_function_runtimes: Vec<(SqliteStore, SqliteFunctions)>,
     pg_uri: Option<String>,
   ) -> Self {
+    let sqlite_functions: Vec<SqliteFunctionRegistrar> = vec![];
     let (main_conn, main_metadata, new_db) = cfg_select! {
     feature = "pg-test" =>
       init_db_pg(
@@ -183,6 +231,7 @@ impl ConnectionManager {
           is_main_db: true,
           json_registry: &json_schema_registry,
           runtimes: &sqlite_function_runtimes,
+          functions: &sqlite_functions,
           attach: vec![],
           num_threads: None,
         },
@@ -195,6 +244,7 @@ impl ConnectionManager {
         is_main_db: true,
         json_registry: &json_schema_registry,
         runtimes: &sqlite_function_runtimes,
+        functions: &sqlite_functions,
         attach: vec![],
         num_threads: None,
       })
@@ -211,11 +261,14 @@ impl ConnectionManager {
         data_dir,
         json_schema_registry,
         sqlite_function_runtimes,
+        sqlite_functions,
         main: RwLock::new(ConnectionEntry {
           connection: Arc::new(main_conn),
           metadata: Arc::new(main_metadata),
         }),
+        replica: None,
         connections: quick_cache::sync::Cache::new(256),
+        tenants: quick_cache::sync::Cache::new(256),
         pg_uri,
       }),
     };
@@ -225,6 +278,20 @@ impl ConnectionManager {
     return self.state.main.read().clone();
   }
 
+  /// Custom SQL functions registered on every connection.
+  pub(crate) fn sqlite_functions(&self) -> &[SqliteFunctionRegistrar] {
+    return &self.state.sqlite_functions;
+  }
+
+  /// Connection for read-only queries against the main database. Returns the read replica pool,
+  /// if configured, and the main connection otherwise.
+  pub fn main_reader(&self) -> Arc<Connection> {
+    if let Some(ref replica) = self.state.replica {
+      return replica.clone();
+    }
+    return self.state.main.read().connection.clone();
+  }
+
   pub async fn get_entry(&self, opts: BuildOptions) -> Result<ConnectionEntry, ConnectionError> {
     if opts.is_main && opts.attached_databases.is_none() {
       return Ok(self.state.main.read().clone());
@@ -268,6 +335,78 @@ impl ConnectionManager {
     };
   }
 
+  /// Connection for read-only queries against the given table or view. Uses the read replica
+  /// pool for the main database, if configured.
+  pub async fn get_reader_for_qn(
+    &self,
+    name: &trailbase_schema::QualifiedName,
+  ) -> Result<Arc<Connection>, ConnectionError> {
+    return match name.database_schema.as_deref() {
+      Some("main") | Some("public") | None => Ok(self.main_reader()),
+      Some(_) => Ok(self.get_entry_for_qn(name).await?.connection),
+    };
+  }
+
+  /// Connection to the given tenant's database. Tenant databases are opened lazily and only the
+  /// most recently used ones are kept open.
+  ///
+  /// NOTE: Doesn't check the tenant catalog, see `crate::tenants`.
+  pub(crate) async fn get_tenant_entry(
+    &self,
+    tenant: &str,
+  ) -> Result<ConnectionEntry, ConnectionError> {
+    return self
+      .get_or_init_tenant_entry(Some(tenant.to_string()))
+      .await;
+  }
+
+  /// Schema-only in-memory database with the tenant migrations applied. Used to validate and
+  /// build tenant-scoped record APIs, which are then served from the actual tenant databases.
+  pub(crate) async fn tenant_template_entry(&self) -> Result<ConnectionEntry, ConnectionError> {
+    return self.get_or_init_tenant_entry(None).await;
+  }
+
+  /// Closes the given tenant's database, if open.
+  pub(crate) fn evict_tenant(&self, tenant: &str) {
+    self.state.tenants.remove(&Some(tenant.to_string()));
+  }
+
+  async fn get_or_init_tenant_entry(
+    &self,
+    key: Option<String>,
+  ) -> Result<ConnectionEntry, ConnectionError> {
+    return match self.state.tenants.get_value_or_guard(&key, None) {
+      GuardResult::Value(entry) => Ok(entry.clone()),
+      GuardResult::Guard(placeholder) => {
+        let path = key.as_ref().map(|tenant| {
+          self
+            .state
+            .data_dir
+            .tenants_path()
+            .join(format!("{tenant}.db"))
+        });
+        let conn = init_tenant_db_sqlite(
+          path,
+          &self.state.data_dir.migrations_path(),
+          &self.state.json_schema_registry,
+          &self.state.sqlite_function_runtimes,
+          &self.state.sqlite_functions,
+        )?;
+        let metadata = build_metadata(&conn, &self.state.json_schema_registry).await?;
+
+        let entry = ConnectionEntry {
+          connection: Arc::new(conn),
+          metadata: Arc::new(metadata),
+        };
+        let _ = placeholder.insert(entry.clone());
+        Ok(entry)
+      }
+      GuardResult::Timeout => {
+        return Err(ConnectionError::Timeout);
+      }
+    };
+  }
+
   pub(crate) async fn build(&self, opts: BuildOptions) -> Result<ConnectionEntry, ConnectionError> {
     #[cfg(all(test, not(feature = "pg-test")))]
     if opts.is_main && opts.attached_databases.is_none() {
@@ -323,6 +462,7 @@ impl ConnectionManager {
           is_main_db: is_main,
           json_registry: &self.state.json_schema_registry,
           runtimes: &self.state.sqlite_function_runtimes,
+          functions: &self.state.sqlite_functions,
           attach,
           num_threads: opts.num_threads,
         },
@@ -336,6 +476,7 @@ impl ConnectionManager {
         is_main_db: is_main,
         json_registry: &self.state.json_schema_registry,
         runtimes: &self.state.sqlite_function_runtimes,
+        functions: &self.state.sqlite_functions,
         attach,
         num_threads: opts.num_threads,
       })
@@ -360,6 +501,21 @@ impl ConnectionManager {
       self.state.main.write().metadata = new_metadata;
     }
 
+    // Tenants:
+    for (key, entry) in self.state.tenants.iter() {
+      let new_metadata =
+        Arc::new(build_metadata(&entry.connection, &self.state.json_schema_registry).await?);
+
+      let _ = self.state.tenants.replace(
+        key,
+        ConnectionEntry {
+          connection: entry.connection.clone(),
+          metadata: new_metadata,
+        },
+        true,
+      );
+    }
+
     // Others:
     for (key, entry) in self.state.connections.iter() {
       let new_metadata =
@@ -385,6 +541,7 @@ struct InitDbOptions<'a> {
   is_main_db: bool,
   json_registry: &'a Arc<RwLock<JsonSchemaRegistry>>,
   runtimes: &'a Vec<(SqliteStore, SqliteFunctions)>,
+  functions: &'a [SqliteFunctionRegistrar],
   attach: Vec<AttachedDatabase>,
   num_threads: Option<usize>,
 }
@@ -435,45 +592,20 @@ async fn init_db_sqlite<'a>(
     return Err(ConnectionError::InvalidSetting("Too many databases"));
   }
 
-  fn build_connection(
-    db_path: Option<PathBuf>,
-    json_registry: Arc<RwLock<JsonSchemaRegistry>>,
-    #[allow(unused)] runtimes: &[(SqliteStore, SqliteFunctions)],
-  ) -> Result<rusqlite::Connection, ConnectionError> {
-    let conn = trailbase_extension::connect_sqlite(db_path, Some(json_registry))?;
-
-    // Apply custom connection settings, e.g. PRAGMAs and client settings.
-    {
-      // The default is just 16.
-      conn.set_prepared_statement_cache_capacity(PREPARED_STATEMENT_CACHE_CAPACITY);
-
-      // NOTE: We could consider larger memory maps and caches for the main database.
-      // Should be driven by benchmarks.
-      // conn.pragma_update(None, "mmap_size", 268435456)?;
-      // conn.pragma_update(None, "cache_size", -32768)?; // 32MB
-    }
-
-    #[cfg(any(feature = "geos", feature = "geos-static"))]
-    litegis::register(&conn).map_err(trailbase_extension::Error::Rusqlite)?;
-
-    // Install SQLite extension methods/functions registered by WASM components.
-    #[cfg(feature = "wasm")]
-    for (store, functions) in runtimes {
-      trailbase_wasm_runtime_host::functions::setup_connection(&conn, store.clone(), functions)
-        .map_err(trailbase_extension::Error::Rusqlite)?;
-    }
-
-    return Ok(conn);
-  }
-
   let conn = trailbase_sqlite::Connection::with_opts(
     {
       let data_path = opts.data_path.cloned();
       let json_registry = opts.json_registry.clone();
       let runtimes = opts.runtimes.clone();
+      let functions = opts.functions.to_vec();
 
       move || -> Result<rusqlite::Connection, ConnectionError> {
-        return build_connection(data_path.clone(), json_registry.clone(), &runtimes);
+        return build_connection(
+          data_path.clone(),
+          json_registry.clone(),
+          &runtimes,
+          &functions,
+        );
       }
     },
     trailbase_sqlite::Options {
@@ -519,6 +651,7 @@ async fn init_db_sqlite<'a>(
         Some(path.clone()),
         opts.json_registry.clone(),
         opts.runtimes,
+        opts.functions,
       )?;
 
       // Apply migrations.
@@ -536,10 +669,153 @@ async fn init_db_sqlite<'a>(
   return Ok((conn, metadata, init_schema));
 }
 
+/// Opens a pool of query-only connections. Unlike the main connection, no migrations are applied,
+/// i.e. replicated copies are expected to be kept up-to-date by the replication mechanism.
+fn init_replica_sqlite(
+  path: PathBuf,
+  num_threads: Option<usize>,
+  json_registry: &Arc<RwLock<JsonSchemaRegistry>>,
+  runtimes: &[(SqliteStore, SqliteFunctions)],
+  functions: &[SqliteFunctionRegistrar],
+) -> Result<Connection, ConnectionError> {
+  log::debug!("Opening read replica: {path:?}");
+
+  let conn = trailbase_sqlite::Connection::with_opts(
+    {
+      let json_registry = json_registry.clone();
+      let runtimes = runtimes.to_vec();
+      let functions = functions.to_vec();
+
+      move || -> Result<rusqlite::Connection, ConnectionError> {
+        let conn = build_connection(
+          Some(path.clone()),
+          json_registry.clone(),
+          &runtimes,
+          &functions,
+        )?;
+        conn
+          .pragma_update(None, "query_only", true)
+          .map_err(trailbase_extension::Error::Rusqlite)?;
+        return Ok(conn);
+      }
+    },
+    trailbase_sqlite::Options {
+      num_threads: Some(
+        num_threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(2, |n| n.get())),
+      ),
+      ..Default::default()
+    },
+  )
+  .map_err(|err| {
+    // Unpack potentially packed ConnectionError.
+    return match unpack_other_error::<ConnectionError>(err) {
+      Ok(err) => err,
+      Err(sql_err) => sql_err.into(),
+    };
+  })?;
+
+  return Ok(conn);
+}
+
+/// Opens a tenant database, or a schema-only in-memory database if `path` is `None`, and applies
+/// the base migrations as well as the tenant migrations from `<traildepot>/migrations/tenants/`.
+fn init_tenant_db_sqlite(
+  path: Option<PathBuf>,
+  migrations_path: &Path,
+  json_registry: &Arc<RwLock<JsonSchemaRegistry>>,
+  runtimes: &[(SqliteStore, SqliteFunctions)],
+  functions: &[SqliteFunctionRegistrar],
+) -> Result<Connection, ConnectionError> {
+  log::debug!("Opening tenant database: {path:?}");
+
+  if let Some(ref path) = path {
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent).map_err(|err| trailbase_sqlite::Error::Other(err.into()))?;
+    }
+
+    // Migrate upfront rather than from every connection of the pool.
+    let mut conn = build_connection(
+      Some(path.clone()),
+      json_registry.clone(),
+      runtimes,
+      functions,
+    )?;
+    apply_base_migrations(&mut conn, Some(migrations_path), TENANTS_MIGRATIONS)?;
+  }
+
+  let num_threads = if path.is_some() { 2 } else { 1 };
+  let conn = trailbase_sqlite::Connection::with_opts(
+    {
+      let json_registry = json_registry.clone();
+      let runtimes = runtimes.to_vec();
+      let functions = functions.to_vec();
+      let migrations_path = migrations_path.to_path_buf();
+
+      move || -> Result<rusqlite::Connection, ConnectionError> {
+        let mut conn =
+          build_connection(path.clone(), json_registry.clone(), &runtimes, &functions)?;
+        // In-memory databases are private to each connection.
+        if path.is_none() {
+          apply_base_migrations(&mut conn, Some(&migrations_path), TENANTS_MIGRATIONS)?;
+        }
+        return Ok(conn);
+      }
+    },
+    trailbase_sqlite::Options {
+      num_threads: Some(num_threads),
+      ..Default::default()
+    },
+  )
+  .map_err(|err| {
+    // Unpack potentially packed ConnectionError.
+    return match unpack_other_error::<ConnectionError>(err) {
+      Ok(err) => err,
+      Err(sql_err) => sql_err.into(),
+    };
+  })?;
+
+  return Ok(conn);
+}
+
+fn build_connection(
+  db_path: Option<PathBuf>,
+  json_registry: Arc<RwLock<JsonSchemaRegistry>>,
+  #[allow(unused)] runtimes: &[(SqliteStore, SqliteFunctions)],
+  functions: &[SqliteFunctionRegistrar],
+) -> Result<rusqlite::Connection, ConnectionError> {
+  let conn =
+    trailbase_extension::connect_sqlite_with_functions(db_path, Some(json_registry), functions)?;
+
+  // Apply custom connection settings, e.g. PRAGMAs and client settings.
+  {
+    // The default is just 16.
+    conn.set_prepared_statement_cache_capacity(PREPARED_STATEMENT_CACHE_CAPACITY);
+
+    // NOTE: We could consider larger memory maps and caches for the main database.
+    // Should be driven by benchmarks.
+    // conn.pragma_update(None, "mmap_size", 268435456)?;
+    // conn.pragma_update(None, "cache_size", -32768)?; // 32MB
+  }
+
+  #[cfg(any(feature = "geos", feature = "geos-static"))]
+  litegis::register(&conn).map_err(trailbase_extension::Error::Rusqlite)?;
+
+  // Install SQLite extension methods/functions registered by WASM components.
+  #[cfg(feature = "wasm")]
+  for (store, functions) in runtimes {
+    trailbase_wasm_runtime_host::functions::setup_connection(&conn, store.clone(), functions)
+      .map_err(trailbase_extension::Error::Rusqlite)?;
+  }
+
+  return Ok(conn);
+}
+
 pub(super) fn init_logs_db(
   data_dir: Option<&DataDir>,
+  functions: &[SqliteFunctionRegistrar],
 ) -> Result<Connection, trailbase_sqlite::Error> {
   let path = data_dir.map(|d| d.logs_db_path());
+  let functions = functions.to_vec();
 
   return trailbase_sqlite::Connection::with_opts(
     || -> Result<_, trailbase_sqlite::Error> {
@@ -547,6 +823,9 @@ pub(super) fn init_logs_db(
       let mut conn = connect_rusqlite_without_default_extensions_and_schemas(path.clone())?;
 
       trailbase_extension::register_all_extension_functions(&conn, None)?;
+      for registrar in &functions {
+        registrar.register(&conn)?;
+      }
 
       // Turn off secure_deletions, i.e. don't wipe the memory with zeros.
       conn.pragma_update(None, "secure_delete", "FALSE")?;
@@ -610,3 +889,63 @@ pub(crate) fn connect_rusqlite_without_default_extensions_and_schemas(
 }
 
 const PREPARED_STATEMENT_CACHE_CAPACITY: usize = 256;
+
+/// Name of the tenant migrations, i.e. `<traildepot>/migrations/tenants/`.
+const TENANTS_MIGRATIONS: &str = "tenants";
+
+#[cfg(all(test, not(feature = "pg-test")))]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_read_replica() {
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let data_dir = DataDir(temp_dir.path().to_path_buf());
+    data_dir.ensure_directory_structure().await.unwrap();
+
+    let (manager, _new_db) = ConnectionManager::new(Options {
+      data_dir,
+      json_schema_registry: Arc::new(RwLock::new(
+        trailbase_schema::registry::build_json_schema_registry(vec![]).unwrap(),
+      )),
+      sqlite_function_runtimes: vec![],
+      sqlite_functions: vec![],
+      pg_uri: None,
+      read_replica: Some(ReadReplicaOptions {
+        path: None,
+        num_threads: Some(2),
+      }),
+    })
+    .await
+    .unwrap();
+
+    let main = manager.main_entry().connection;
+    let reader = manager.main_reader();
+    assert_ne!(main.id(), reader.id());
+
+    main
+      .execute_batch("CREATE TABLE replicated (id INTEGER PRIMARY KEY) STRICT")
+      .await
+      .unwrap();
+    main
+      .execute("INSERT INTO replicated (id) VALUES (1)", ())
+      .await
+      .unwrap();
+
+    // Writes are immediately visible to the replica pool.
+    let count: i64 = reader
+      .read_query_row_get("SELECT COUNT(*) FROM replicated", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(count, 1);
+
+    // The replica pool is read-only.
+    assert!(
+      reader
+        .execute("INSERT INTO replicated (id) VALUES (2)", ())
+        .await
+        .is_err()
+    );
+  }
+}
@@ -10,13 +10,41 @@ pub static USER_TABLE_FQ: LazyLock<QualifiedName> = LazyLock::new(|| QualifiedNa
 });
 
 pub(crate) const LOGS_TABLE: &str = "_logs";
+pub(crate) const SLOW_QUERIES_TABLE: &str = "_slow_queries";
+pub(crate) const EMAIL_LOG_TABLE: &str = "_email_log";
+pub(crate) const PUSH_DELIVERIES_TABLE: &str = "_push_deliveries";
 pub(crate) const SESSION_TABLE: &str = "_session";
 pub(crate) const AVATAR_TABLE: &str = "_user_avatar";
+pub(crate) const API_KEY_TABLE: &str = "_api_keys";
+pub(crate) const EMAIL_CHANGE_TABLE: &str = "_email_change";
+pub(crate) const ROLES_TABLE: &str = "_roles";
+pub(crate) const USER_ROLES_TABLE: &str = "_user_roles";
+pub(crate) const WEBHOOK_DELIVERIES_TABLE: &str = "_webhook_deliveries";
+pub(crate) const WEBHOOK_QUEUE_TABLE: &str = "_webhook_queue";
+pub(crate) const CDC_OUTBOX_TABLE: &str = "_cdc_outbox";
+pub(crate) const ADMIN_QUERY_LOG_TABLE: &str = "_admin_query_log";
+pub(crate) const CONFIG_HISTORY_TABLE: &str = "_config_history";
+pub(crate) const AUDIT_LOG_TABLE: &str = "_audit_log";
+pub(crate) const EMAIL_TEMPLATES_TABLE: &str = "_email_templates";
+pub(crate) const EMAIL_QUEUE_TABLE: &str = "_email_queue";
+pub(crate) const PUSH_DEVICES_TABLE: &str = "_push_devices";
+pub(crate) const PUSH_TOPICS_TABLE: &str = "_push_topics";
+pub(crate) const SYNC_CHANGES_TABLE: &str = "_sync_changes";
+pub(crate) const RECORD_CHANGES_TABLE: &str = "_record_changes";
+pub(crate) const JOBS_TABLE: &str = "_jobs";
+pub(crate) const JOB_RUNS_TABLE: &str = "_job_runs";
+pub(crate) const TASK_QUEUE_TABLE: &str = "_task_queue";
 pub(crate) const AUTHORIZATION_CODE_TABLE: &str = "_authorization_code";
 pub(crate) const OTP_CODE_TABLE: &str = "_otp_code";
+pub(crate) const MAGIC_LINK_TABLE: &str = "_magic_link";
+pub(crate) const SAML_REQUEST_TABLE: &str = "_saml_request";
+pub(crate) const IDEMPOTENCY_TABLE: &str = "_idempotency";
+pub(crate) const TENANT_TABLE: &str = "_tenant";
+pub(crate) const TENANT_USER_TABLE: &str = "_tenant_user";
 
 pub(crate) const LOGS_TABLE_ID_COLUMN: &str = "id";
 pub const LOGS_RETENTION_DEFAULT: Duration = Duration::days(7);
+pub(crate) const RECORD_CHANGES_RETENTION: Duration = Duration::days(30);
 
 pub const COOKIE_AUTH_TOKEN: &str = "auth_token";
 pub const COOKIE_REFRESH_TOKEN: &str = "refresh_token";
@@ -33,15 +61,20 @@ pub const DEFAULT_AUTH_TOKEN_TTL: Duration =
 pub(crate) const DEFAULT_AUTHORIZATION_CODE_TTL: Duration = Duration::minutes(5);
 
 pub(crate) const DEFAULT_MFA_TOKEN_TTL: Duration = Duration::minutes(2);
+pub(crate) const DEFAULT_MAGIC_LINK_TTL: Duration = Duration::minutes(15);
+pub(crate) const DEFAULT_SAML_REQUEST_TTL: Duration = Duration::minutes(10);
 pub(crate) const DEFAULT_REFRESH_TOKEN_TTL: Duration = Duration::days(30);
 pub(crate) const DEFAULT_ANONYMOUS_REFRESH_TOKEN_TTL: Duration = Duration::days(90);
 
 pub(crate) const VERIFICATION_CODE_LENGTH: usize = 24;
 pub(crate) const REFRESH_TOKEN_LENGTH: usize = 32;
+pub(crate) const API_KEY_SECRET_LENGTH: usize = 40;
 
 // Public APIs
 pub const RECORD_API_PATH: &str = "api/records/v1";
 pub const TRANSACTION_API_PATH: &str = "api/transaction/v1";
 pub const QUERY_API_PATH: &str = "api/query/v1";
 pub const AUTH_API_PATH: &str = "api/auth/v1";
+pub const SYNC_API_PATH: &str = "api/sync/v1";
+pub const NOTIFICATIONS_API_PATH: &str = "api/notifications/v1";
 pub const ADMIN_API_PATH: &str = "api/_admin";
@@ -0,0 +1,565 @@
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Method, Request, header};
+use base64::prelude::*;
+use log::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tower::ServiceExt;
+use tower_cookies::CookieManagerLayer;
+use trailbase_schema::QualifiedName;
+use trailbase_sqlite::Value;
+
+use crate::app_state::AppState;
+use crate::auth::api::login::LoginResponse;
+use crate::config::proto::RecordApiConfig;
+use crate::connection::ConnectionEntry;
+use crate::constants::AUTH_API_PATH;
+use crate::data_dir::DataDir;
+use crate::server::{InitArgs, InitError, init_app_state};
+
+#[derive(Debug, Error)]
+pub enum ContractError {
+  #[error("IO error: {0}")]
+  IO(#[from] std::io::Error),
+  #[error("Fixture error: {0}")]
+  Fixture(String),
+  #[error("Init error: {0}")]
+  Init(#[from] InitError),
+  #[error("SQLite error: {0}")]
+  Sqlite(#[from] trailbase_sqlite::Error),
+  #[error("Connection error: {0}")]
+  Connection(#[from] crate::connection::ConnectionError),
+  #[error("Config error: {0}")]
+  Config(#[from] crate::config::ConfigError),
+  #[error("Auth error: {0}")]
+  Auth(#[from] crate::auth::AuthError),
+}
+
+/// Declarative description of a test scenario: a schema, seed data, users and a list of
+/// request/response assertions against record and auth APIs.
+///
+/// Strings of the form `{{user:<email>}}` in rows, paths and request bodies are substituted with
+/// the respective fixture user's id.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+  /// SQL statements executed against the main database, e.g. `CREATE TABLE ...`.
+  #[serde(default)]
+  pub tables: Vec<String>,
+  /// Users to create. Users are verified and can be referenced by email in test cases.
+  #[serde(default)]
+  pub users: Vec<FixtureUser>,
+  /// Rows to insert in order.
+  #[serde(default)]
+  pub rows: Vec<FixtureRow>,
+  /// Additional Record APIs in `config.RecordApiConfig` textproto format.
+  #[serde(default)]
+  pub record_apis: Vec<String>,
+  /// Request/response assertions.
+  #[serde(default)]
+  pub cases: Vec<TestCase>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FixtureUser {
+  pub email: String,
+  pub password: String,
+  #[serde(default)]
+  pub admin: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FixtureRow {
+  pub table: String,
+  pub values: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+  pub name: String,
+  /// Email of the fixture user to send the request as. Anonymous if absent.
+  pub user: Option<String>,
+  /// HTTP method, default: GET.
+  pub method: Option<String>,
+  /// Request path including query, e.g. "/api/records/v1/posts?limit=5".
+  pub path: String,
+  /// Optional JSON request body.
+  pub body: Option<serde_json::Value>,
+  pub expect: Expectation,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+  pub status: u16,
+  /// Expected JSON response body. Objects match if all expected keys are present with matching
+  /// values, i.e. additional keys in the response are ignored. Arrays have to match element-wise.
+  pub body: Option<serde_json::Value>,
+}
+
+#[derive(Debug)]
+pub struct CaseResult {
+  pub name: String,
+  /// Failure description or None if the case passed.
+  pub failure: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+  pub results: Vec<CaseResult>,
+}
+
+impl Report {
+  pub fn passed(&self) -> bool {
+    return self.results.iter().all(|r| r.failure.is_none());
+  }
+}
+
+impl Fixture {
+  /// Loads a fixture from a JSON file.
+  pub fn from_path(path: &Path) -> Result<Self, ContractError> {
+    let contents = std::fs::read_to_string(path)?;
+    return serde_json::from_str(&contents).map_err(|err| ContractError::Fixture(err.to_string()));
+  }
+}
+
+/// Runs the given fixture against a fresh, ephemeral TrailBase instance.
+///
+/// If `source` is provided, its `config.textproto` and migrations are copied over first. This
+/// allows testing an existing setup's schemas and access rules without touching its data.
+pub async fn run_fixture(
+  fixture: Fixture,
+  source: Option<&DataDir>,
+) -> Result<Report, ContractError> {
+  let root = std::env::temp_dir().join(format!(
+    "trailbase-contract-{}",
+    crate::rand::random_alphanumeric(12)
+  ));
+  let data_dir = DataDir(root.clone());
+
+  let result = async {
+    if let Some(source) = source {
+      copy_setup(source, &data_dir).await?;
+    }
+
+    let (_new_db, state) = init_app_state(InitArgs {
+      data_dir: data_dir.clone(),
+      ..Default::default()
+    })
+    .await?;
+
+    let router = build_router(&state);
+    let users = setup(&state, &router, &fixture).await?;
+    return run_cases(router, &users, fixture.cases).await;
+  }
+  .await;
+
+  if let Err(err) = tokio::fs::remove_dir_all(&root).await {
+    warn!("Failed to clean up {root:?}: {err}");
+  }
+
+  return result;
+}
+
+/// Copies config and migrations from `source` to `target`.
+async fn copy_setup(source: &DataDir, target: &DataDir) -> Result<(), ContractError> {
+  const CONFIG_FILENAME: &str = "config.textproto";
+
+  tokio::fs::create_dir_all(target.config_path()).await?;
+  let config = source.config_path().join(CONFIG_FILENAME);
+  if tokio::fs::try_exists(&config).await? {
+    tokio::fs::copy(config, target.config_path().join(CONFIG_FILENAME)).await?;
+  }
+
+  let migrations = source.migrations_path();
+  for entry in walkdir::WalkDir::new(&migrations) {
+    let entry = entry.map_err(|err| ContractError::IO(err.into()))?;
+    let Ok(relative) = entry.path().strip_prefix(&migrations) else {
+      continue;
+    };
+
+    let dest: PathBuf = target.migrations_path().join(relative);
+    if entry.file_type().is_dir() {
+      tokio::fs::create_dir_all(dest).await?;
+    } else {
+      tokio::fs::copy(entry.path(), dest).await?;
+    }
+  }
+
+  return Ok(());
+}
+
+async fn setup(
+  state: &AppState,
+  router: &Router,
+  fixture: &Fixture,
+) -> Result<HashMap<String, TestUser>, ContractError> {
+  let conn = state.user_conn();
+
+  for sql in &fixture.tables {
+    conn.execute_batch(sql.clone()).await?;
+  }
+  if !fixture.tables.is_empty() {
+    state.rebuild_connection_metadata().await?;
+  }
+
+  let mut users: HashMap<String, TestUser> = HashMap::new();
+  for user in &fixture.users {
+    let id = crate::auth::cli::add_user(conn, &user.email, &user.password).await?;
+    if user.admin {
+      crate::auth::cli::promote_user_to_admin(
+        conn,
+        crate::auth::cli::UserReference::Email(user.email.clone()),
+      )
+      .await?;
+    }
+
+    let auth_token = login(router, &user.email, &user.password).await?;
+
+    users.insert(user.email.clone(), TestUser { id, auth_token });
+  }
+
+  for row in &fixture.rows {
+    let table_name =
+      QualifiedName::parse(&row.table).map_err(|err| ContractError::Fixture(err.to_string()))?;
+
+    let mut columns: Vec<String> = vec![];
+    let mut params: Vec<Value> = vec![];
+    for (column, value) in &row.values {
+      if column.contains('"') {
+        return Err(ContractError::Fixture(format!("Invalid column: {column}")));
+      }
+      columns.push(format!(r#""{column}""#));
+      params.push(json_to_sql(&users, value)?);
+    }
+
+    let placeholders: Vec<String> = (1..=params.len()).map(|i| format!("?{i}")).collect();
+    conn
+      .execute(
+        format!(
+          "INSERT INTO {table} ({columns}) VALUES ({placeholders})",
+          table = table_name.escaped_string(),
+          columns = columns.join(", "),
+          placeholders = placeholders.join(", "),
+        ),
+        params,
+      )
+      .await?;
+  }
+
+  if !fixture.record_apis.is_empty() {
+    let mut config = (*state.get_config()).clone();
+    for text in &fixture.record_apis {
+      config.record_apis.push(parse_record_api_config(text)?);
+    }
+    state.validate_and_update_config(config, None).await?;
+  }
+
+  return Ok(users);
+}
+
+/// Record and auth APIs as served to clients.
+fn build_router(state: &AppState) -> Router {
+  let ConnectionEntry {
+    connection: conn, ..
+  } = state.connection_manager().main_entry();
+  let enable_transactions =
+    state.access_config(|c| c.server.enable_record_transactions.unwrap_or(false));
+
+  return Router::new()
+    .merge(crate::records::router(
+      conn.connection_type(),
+      enable_transactions,
+    ))
+    .merge(crate::auth::router(&state.get_config()))
+    .layer(CookieManagerLayer::new())
+    .with_state(state.clone());
+}
+
+/// Logs the user in through the regular password login API and returns their auth token.
+async fn login(router: &Router, email: &str, password: &str) -> Result<String, ContractError> {
+  let request = Request::builder()
+    .method(Method::POST)
+    .uri(format!("/{AUTH_API_PATH}/login"))
+    .header(header::CONTENT_TYPE, "application/json")
+    .body(Body::from(
+      serde_json::json!({ "email": email, "password": password }).to_string(),
+    ))
+    .map_err(|err| ContractError::Fixture(err.to_string()))?;
+
+  let Ok(response) = router.clone().oneshot(request).await;
+
+  let status = response.status();
+  let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+    .await
+    .map_err(|err| ContractError::IO(std::io::Error::other(err)))?;
+  if !status.is_success() {
+    return Err(ContractError::Fixture(format!(
+      "Failed to log in {email}: {status}: {}",
+      String::from_utf8_lossy(&bytes)
+    )));
+  }
+
+  let response: LoginResponse =
+    serde_json::from_slice(&bytes).map_err(|err| ContractError::Fixture(err.to_string()))?;
+  return Ok(response.auth_token);
+}
+
+async fn run_cases(
+  router: Router,
+  users: &HashMap<String, TestUser>,
+  cases: Vec<TestCase>,
+) -> Result<Report, ContractError> {
+  let mut report = Report::default();
+  for case in cases {
+    let failure = run_case(router.clone(), users, &case).await?;
+    report.results.push(CaseResult {
+      name: case.name,
+      failure,
+    });
+  }
+
+  return Ok(report);
+}
+
+async fn run_case(
+  router: Router,
+  users: &HashMap<String, TestUser>,
+  case: &TestCase,
+) -> Result<Option<String>, ContractError> {
+  let method = Method::from_bytes(case.method.as_deref().unwrap_or("GET").as_bytes())
+    .map_err(|err| ContractError::Fixture(err.to_string()))?;
+
+  let mut builder = Request::builder()
+    .method(method)
+    .uri(substitute_users(users, &case.path)?);
+  if let Some(ref email) = case.user {
+    let user = users
+      .get(email)
+      .ok_or_else(|| ContractError::Fixture(format!("Unknown user: {email}")))?;
+    builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", user.auth_token));
+  }
+
+  let body = match case.body {
+    Some(ref body) => {
+      builder = builder.header(header::CONTENT_TYPE, "application/json");
+      Body::from(substitute_users(users, &body.to_string())?)
+    }
+    None => Body::empty(),
+  };
+
+  let request = builder
+    .body(body)
+    .map_err(|err| ContractError::Fixture(err.to_string()))?;
+
+  let Ok(response) = router.oneshot(request).await;
+
+  let status = response.status().as_u16();
+  let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+    .await
+    .map_err(|err| ContractError::IO(std::io::Error::other(err)))?;
+
+  if status != case.expect.status {
+    return Ok(Some(format!(
+      "expected status {}, got {status}: {}",
+      case.expect.status,
+      String::from_utf8_lossy(&bytes)
+    )));
+  }
+
+  if let Some(ref expected) = case.expect.body {
+    let actual: serde_json::Value = serde_json::from_slice(&bytes)
+      .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&bytes).to_string()));
+
+    if !json_contains(&actual, expected) {
+      return Ok(Some(format!("expected body {expected}, got {actual}")));
+    }
+  }
+
+  return Ok(None);
+}
+
+struct TestUser {
+  id: uuid::Uuid,
+  auth_token: String,
+}
+
+fn parse_record_api_config(text: &str) -> Result<RecordApiConfig, ContractError> {
+  let descriptor = crate::DESCRIPTOR_POOL
+    .get_message_by_name("config.RecordApiConfig")
+    .ok_or_else(|| ContractError::Fixture("Missing RecordApiConfig descriptor".to_string()))?;
+
+  let message = prost_reflect::DynamicMessage::parse_text_format(descriptor, text)
+    .map_err(|err| ContractError::Fixture(err.to_string()))?;
+
+  return message
+    .transcode_to::<RecordApiConfig>()
+    .map_err(|err| ContractError::Fixture(err.to_string()));
+}
+
+/// Replaces `{{user:<email>}}` references with the user's Base64-encoded id, i.e. the
+/// representation used by record APIs.
+fn substitute_users(users: &HashMap<String, TestUser>, s: &str) -> Result<String, ContractError> {
+  let mut result = s.to_string();
+  while let Some(start) = result.find(USER_REF_PREFIX) {
+    let Some(len) = result[start..].find(USER_REF_SUFFIX) else {
+      break;
+    };
+    let email = &result[start + USER_REF_PREFIX.len()..start + len];
+    let user = users
+      .get(email)
+      .ok_or_else(|| ContractError::Fixture(format!("Unknown user: {email}")))?;
+
+    result.replace_range(
+      start..start + len + USER_REF_SUFFIX.len(),
+      &BASE64_URL_SAFE.encode(user.id.as_bytes()),
+    );
+  }
+  return Ok(result);
+}
+
+fn json_to_sql(
+  users: &HashMap<String, TestUser>,
+  value: &serde_json::Value,
+) -> Result<Value, ContractError> {
+  return Ok(match value {
+    serde_json::Value::Null => Value::Null,
+    serde_json::Value::Bool(b) => Value::Integer(*b as i64),
+    serde_json::Value::Number(n) => match n.as_i64() {
+      Some(i) => Value::Integer(i),
+      None => Value::Real(n.as_f64().unwrap_or_default()),
+    },
+    serde_json::Value::String(s) => {
+      // A full user reference is inserted as the raw id, e.g. for `_user(id)` foreign keys.
+      if let Some(email) = s
+        .strip_prefix(USER_REF_PREFIX)
+        .and_then(|s| s.strip_suffix(USER_REF_SUFFIX))
+      {
+        let user = users
+          .get(email)
+          .ok_or_else(|| ContractError::Fixture(format!("Unknown user: {email}")))?;
+        Value::Blob(user.id.as_bytes().to_vec())
+      } else {
+        Value::Text(s.clone())
+      }
+    }
+    v @ (serde_json::Value::Array(_) | serde_json::Value::Object(_)) => Value::Text(v.to_string()),
+  });
+}
+
+fn json_contains(actual: &serde_json::Value, expected: &serde_json::Value) -> bool {
+  use serde_json::Value;
+
+  return match (actual, expected) {
+    (Value::Object(actual), Value::Object(expected)) => expected
+      .iter()
+      .all(|(k, e)| actual.get(k).is_some_and(|a| json_contains(a, e))),
+    (Value::Array(actual), Value::Array(expected)) => {
+      actual.len() == expected.len()
+        && actual
+          .iter()
+          .zip(expected)
+          .all(|(a, e)| json_contains(a, e))
+    }
+    (a, e) => a == e,
+  };
+}
+
+const USER_REF_PREFIX: &str = "{{user:";
+const USER_REF_SUFFIX: &str = "}}";
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_run_fixture() {
+    let fixture: Fixture = serde_json::from_value(serde_json::json!({
+      "tables": [
+        r#"CREATE TABLE post (
+          id      INTEGER PRIMARY KEY,
+          owner   BLOB NOT NULL REFERENCES _user(id),
+          title   TEXT NOT NULL
+        ) STRICT"#,
+      ],
+      "users": [
+        { "email": "alice@test.org", "password": "Secret!1!!" },
+        { "email": "bob@test.org", "password": "Secret!1!!" },
+      ],
+      "rows": [
+        { "table": "post", "values": { "owner": "{{user:alice@test.org}}", "title": "first" } },
+      ],
+      "record_apis": [
+        r#"
+          name: "posts"
+          table_name: "post"
+          acl_authenticated: [CREATE, READ]
+          create_access_rule: "_REQ_.owner = _USER_.id"
+          read_access_rule: "_ROW_.owner = _USER_.id"
+        "#,
+      ],
+      "cases": [
+        {
+          "name": "owner can read",
+          "user": "alice@test.org",
+          "path": "/api/records/v1/posts/1",
+          "expect": { "status": 200, "body": { "title": "first" } },
+        },
+        {
+          "name": "others cannot read",
+          "user": "bob@test.org",
+          "path": "/api/records/v1/posts/1",
+          "expect": { "status": 403 },
+        },
+        {
+          "name": "anonymous cannot list",
+          "path": "/api/records/v1/posts",
+          "expect": { "status": 403 },
+        },
+        {
+          "name": "cannot create on behalf of others",
+          "user": "bob@test.org",
+          "method": "POST",
+          "path": "/api/records/v1/posts",
+          "body": { "owner": "{{user:alice@test.org}}", "title": "spoof" },
+          "expect": { "status": 403 },
+        },
+        {
+          "name": "intentionally failing",
+          "user": "alice@test.org",
+          "path": "/api/records/v1/posts/1",
+          "expect": { "status": 200, "body": { "title": "other" } },
+        },
+      ],
+    }))
+    .unwrap();
+
+    let report = run_fixture(fixture, None).await.unwrap();
+    assert_eq!(5, report.results.len());
+    assert!(!report.passed());
+
+    for result in &report.results[..4] {
+      assert!(result.failure.is_none(), "{result:?}");
+    }
+    assert!(report.results[4].failure.is_some());
+  }
+
+  #[test]
+  fn test_json_contains() {
+    use serde_json::json;
+
+    assert!(json_contains(&json!({"a": 1, "b": 2}), &json!({"a": 1})));
+    assert!(!json_contains(&json!({"a": 1}), &json!({"a": 1, "b": 2})));
+    assert!(json_contains(
+      &json!({"records": [{"a": 1, "b": 2}]}),
+      &json!({"records": [{"a": 1}]})
+    ));
+    assert!(!json_contains(&json!([1, 2]), &json!([1])));
+  }
+}
@@ -39,6 +39,10 @@ impl DataDir {
     return self.0.join("data/");
   }
 
+  pub fn tenants_path(&self) -> PathBuf {
+    return self.data_path().join("tenants/");
+  }
+
   pub fn config_path(&self) -> PathBuf {
     return self.0.clone();
   }
@@ -55,6 +59,10 @@ impl DataDir {
     return self.0.join("migrations/");
   }
 
+  pub fn seeds_path(&self) -> PathBuf {
+    return self.0.join("seeds/");
+  }
+
   pub fn uploads_path(&self) -> PathBuf {
     return self.0.join("uploads/");
   }
@@ -1,15 +1,27 @@
+use const_format::formatcp;
 use lettre::address::AddressError;
-use lettre::message::{Body, Mailbox, Message, header::ContentType};
-use lettre::transport::smtp;
-use lettre::{AsyncSendmailTransport, AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use lettre::message::{Body, Mailbox, Message, MultiPart, header::ContentType};
 use log::*;
-use minijinja::{Environment, context};
-use std::sync::Arc;
+use minijinja::context;
 use thiserror::Error;
+use trailbase_sqlite::params;
 
 use crate::AppState;
-use crate::config::proto::{Config, EmailConfig, SmtpEncryption};
-use crate::constants::AUTH_API_PATH;
+use crate::constants::{AUTH_API_PATH, EMAIL_LOG_TABLE};
+use crate::util::urlencode;
+
+mod mailer;
+mod providers;
+mod queue;
+mod templates;
+
+pub(crate) use mailer::{Mailer, SmtpMailer, build_mailer};
+pub(crate) use queue::{EmailQueue, spawn_email_worker};
+pub(crate) use templates::{
+  EmailContent, EmailTemplateName, StoredEmailTemplate, load_stored_template, resolve_template,
+};
+
+use templates::load_template;
 
 #[derive(Debug, Error)]
 pub enum EmailError {
@@ -23,21 +35,40 @@ pub enum EmailError {
   Smtp(#[from] lettre::transport::smtp::Error),
   #[error("Sendmail: {0}")]
   Sendmail(#[from] lettre::transport::sendmail::Error),
+  #[error("Http: {0}")]
+  Http(#[from] reqwest::Error),
+  #[error("Api ({status}): {message}")]
+  Api { status: u16, message: String },
+  #[error("Queue: {0}")]
+  Queue(#[from] trailbase_sqlite::Error),
   #[error("Template: {0}")]
   Template(#[from] minijinja::Error),
   #[error("Internal: {0}")]
   Internal(Box<dyn std::error::Error + Send + Sync>),
 }
 
+impl EmailError {
+  /// Whether a delivery attempt may succeed when retried, e.g. on rate limits or outages.
+  fn is_transient(&self) -> bool {
+    return match self {
+      Self::Smtp(err) => err.is_transient() || err.is_timeout(),
+      Self::Http(err) => err.is_timeout() || err.is_connect(),
+      Self::Api { status, .. } => *status == 429 || *status >= 500,
+      _ => false,
+    };
+  }
+}
+
 pub struct Email {
-  mailer: Mailer,
-  dev: bool,
+  state: AppState,
 
   from: Mailbox,
   to: Mailbox,
 
   subject: String,
   body: String,
+  /// Plain-text alternative of the HTML body.
+  text: Option<String>,
 }
 
 impl Email {
@@ -47,88 +78,137 @@ impl Email {
     subject: String,
     body: String,
   ) -> Result<Self, EmailError> {
-    return Self::new_internal(state, to.parse()?, subject, body);
+    return Self::new_internal(
+      state,
+      to.parse()?,
+      EmailContent {
+        subject,
+        html: body,
+        text: None,
+      },
+    );
   }
 
   fn new_internal(
     state: &AppState,
     to: Mailbox,
-    subject: String,
-    body: String,
+    content: EmailContent,
   ) -> Result<Self, EmailError> {
     return Ok(Self {
-      mailer: state.mailer(),
-      dev: state.dev_mode(),
+      state: state.clone(),
       from: get_sender(state)?,
       to,
-      subject,
-      body,
+      subject: content.subject,
+      body: content.html,
+      text: content.text,
     });
   }
 
+  /// Queues the email for delivery by the background worker, which retries transient failures
+  /// with exponential backoff and records the outcome in the email log.
   pub async fn send(&self) -> Result<(), EmailError> {
-    let Email {
-      mailer,
-      #[allow(unused)]
-      dev,
-      from,
-      to,
-      subject,
-      body,
-    } = self;
-
-    let email = Message::builder()
-      .to(to.clone())
-      .from(from.clone())
-      .subject(subject.clone())
-      .header(ContentType::TEXT_HTML)
-      .body(Body::new(body.clone()))?;
-
-    #[cfg(not(test))]
-    if *dev {
-      log::info!(
-        "\
-            [dev] Skip sending email:\
-            \nFROM: {from}\
-            \nTO: {to}\
-            \nSUBJECT: {subject}\
-            \nBODY: {body}\
-            "
-      );
+    if self.skip_in_dev_mode() {
       return Ok(());
     }
 
-    match mailer {
-      Mailer::Smtp(mailer) => {
-        mailer.send(email).await?;
-      }
-      Mailer::Local(mailer) => {
-        mailer.send(email).await?;
-      }
-    };
+    self.state.email_queue().enqueue(self).await?;
+
+    // Tests don't run the background worker.
+    #[cfg(test)]
+    queue::process_due(&self.state).await?;
 
     return Ok(());
   }
 
-  pub(crate) fn verification_email(
+  /// Delivers the email right away bypassing the queue and retries, e.g. to surface configuration
+  /// errors. The outcome is recorded in the email log.
+  pub(crate) async fn send_now(&self) -> Result<(), EmailError> {
+    if self.skip_in_dev_mode() {
+      return Ok(());
+    }
+
+    let mailer = self.state.mailer();
+    let result = mailer.send(self).await;
+    self
+      .record_outcome(mailer.provider(), 1, result.as_ref().err())
+      .await;
+
+    return result;
+  }
+
+  fn skip_in_dev_mode(&self) -> bool {
+    if cfg!(test) || !self.state.dev_mode() {
+      return false;
+    }
+
+    log::info!(
+      "\
+          [dev] Skip sending email:\
+          \nFROM: {from}\
+          \nTO: {to}\
+          \nSUBJECT: {subject}\
+          \nBODY: {body}\
+          ",
+      from = self.from,
+      to = self.to,
+      subject = self.subject,
+      body = self.body,
+    );
+    return true;
+  }
+
+  fn to_message(&self) -> Result<Message, EmailError> {
+    let builder = Message::builder()
+      .to(self.to.clone())
+      .from(self.from.clone())
+      .subject(self.subject.clone());
+
+    return Ok(match self.text {
+      Some(ref text) => builder.multipart(MultiPart::alternative_plain_html(
+        text.clone(),
+        self.body.clone(),
+      ))?,
+      None => builder
+        .header(ContentType::TEXT_HTML)
+        .body(Body::new(self.body.clone()))?,
+    });
+  }
+
+  async fn record_outcome(&self, provider: &str, attempts: i64, error: Option<&EmailError>) {
+    const QUERY: &str = formatcp!(
+      "INSERT INTO '{EMAIL_LOG_TABLE}' (provider, sender, recipient, subject, status, attempts, error) VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    );
+
+    let status = if error.is_some() { "failed" } else { "sent" };
+    if let Err(err) = self
+      .state
+      .logs_conn()
+      .execute(
+        QUERY,
+        params!(
+          provider.to_string(),
+          self.from.email.to_string(),
+          self.to.email.to_string(),
+          self.subject.clone(),
+          status.to_string(),
+          attempts,
+          error.map(|err| err.to_string()),
+        ),
+      )
+      .await
+    {
+      warn!("Failed to record email outcome: {err}");
+    }
+  }
+
+  pub(crate) async fn verification_email(
     state: &AppState,
     email_address: &str,
     email_verification_token: &str,
     redirect_uri: Option<&str>,
   ) -> Result<Self, EmailError> {
     let to: Mailbox = email_address.parse()?;
-
     let config = state.get_config();
-    let template = &config.email.user_verification_template;
-
-    let subject_template = template
-      .as_ref()
-      .and_then(|t| t.subject.as_deref())
-      .unwrap_or(trailbase_assets::email::DEFAULT_EMAIL_VERIFICATION_SUBJECT);
-    let body_template = template
-      .as_ref()
-      .and_then(|t| t.body.as_deref())
-      .unwrap_or(trailbase_assets::email::DEFAULT_EMAIL_VERIFICATION_BODY);
 
     let site_url = get_site_url(state);
     let verification_url = site_url
@@ -139,29 +219,26 @@ impl Email {
       })
       .map_err(|_err| EmailError::Internal("Invalid URL".into()))?;
 
-    let env = Environment::empty();
-    let subject = env
-      .template_from_named_str("subject", subject_template)?
-      .render(context! {
-        APP_NAME => &config.server.application_name,
-        EMAIL => email_address,
-      })?;
-    let body = env
-      .template_from_named_str("body", body_template)?
-      .render(context! {
-        APP_NAME => &config.server.application_name,
-        CODE => email_verification_token,
-        EMAIL => email_address,
-        REDIRECT_URI => redirect_uri,
-        SITE_URL => site_url.origin().ascii_serialization(),
-        TOKEN => email_verification_token,
-        VERIFICATION_URL => verification_url,
-      })?;
-
-    return Email::new_internal(state, to, subject, body);
+    let template = load_template(
+      state.user_conn(),
+      &config.email,
+      EmailTemplateName::UserVerification,
+    )
+    .await;
+    let content = template.render(context! {
+      APP_NAME => &config.server.application_name,
+      CODE => email_verification_token,
+      EMAIL => email_address,
+      REDIRECT_URI => redirect_uri,
+      SITE_URL => site_url.origin().ascii_serialization(),
+      TOKEN => email_verification_token,
+      VERIFICATION_URL => verification_url,
+    })?;
+
+    return Email::new_internal(state, to, content);
   }
 
-  pub(crate) fn change_email_address_email(
+  pub(crate) async fn change_email_address_email(
     state: &AppState,
     email_address: &str,
     email_verification_token: &str,
@@ -169,16 +246,6 @@ impl Email {
   ) -> Result<Self, EmailError> {
     let to: Mailbox = email_address.parse()?;
     let config = state.get_config();
-    let template = &config.email.change_email_template;
-
-    let subject_template = template
-      .as_ref()
-      .and_then(|t| t.subject.as_deref())
-      .unwrap_or(trailbase_assets::email::DEFAULT_EMAIL_CHANGE_ADDRESS_SUBJECT);
-    let body_template = template
-      .as_ref()
-      .and_then(|t| t.body.as_deref())
-      .unwrap_or(trailbase_assets::email::DEFAULT_EMAIL_CHANGE_ADDRESS_BODY);
 
     let site_url = get_site_url(state);
     let verification_url = site_url
@@ -189,71 +256,97 @@ impl Email {
       })
       .map_err(|_err| EmailError::Internal("Invalid URL".into()))?;
 
-    let env = Environment::empty();
-    let subject = env
-      .template_from_named_str("subject", subject_template)?
-      .render(context! {
-        APP_NAME => &config.server.application_name,
-        EMAIL => email_address,
-      })?;
-    let body = env
-      .template_from_named_str("body", body_template)?
-      .render(context! {
-        APP_NAME => &config.server.application_name,
-        CODE => email_verification_token,
-        EMAIL => email_address,
-        REDIRECT_URI => redirect_uri,
-        SITE_URL => site_url.origin().ascii_serialization(),
-        TOKEN => email_verification_token,
-        VERIFICATION_URL => verification_url,
-      })?;
-
-    return Email::new_internal(state, to, subject, body);
+    let template = load_template(
+      state.user_conn(),
+      &config.email,
+      EmailTemplateName::ChangeEmail,
+    )
+    .await;
+    let content = template.render(context! {
+      APP_NAME => &config.server.application_name,
+      CODE => email_verification_token,
+      EMAIL => email_address,
+      REDIRECT_URI => redirect_uri,
+      SITE_URL => site_url.origin().ascii_serialization(),
+      TOKEN => email_verification_token,
+      VERIFICATION_URL => verification_url,
+    })?;
+
+    return Email::new_internal(state, to, content);
   }
 
-  pub(crate) fn password_reset_email(
+  pub(crate) async fn change_email_old_address_email(
     state: &AppState,
     email_address: &str,
-    password_reset_token: &str,
+    new_email_address: &str,
+    email_verification_token: &str,
+    redirect_uri: Option<&str>,
   ) -> Result<Self, EmailError> {
     let to: Mailbox = email_address.parse()?;
     let config = state.get_config();
-    let template = &config.email.password_reset_template;
 
-    let subject_template = template
-      .as_ref()
-      .and_then(|t| t.subject.as_deref())
-      .unwrap_or(trailbase_assets::email::DEFAULT_EMAIL_PASSWORD_RESET_SUBJECT);
-    let body_template = template
-      .as_ref()
-      .and_then(|t| t.body.as_deref())
-      .unwrap_or(trailbase_assets::email::DEFAULT_EMAIL_PASSWORD_RESET_BODY);
+    let site_url = get_site_url(state);
+    let verification_url = site_url
+      .join(&if let Some(redirect_uri) = redirect_uri {
+        format!(
+          "/{AUTH_API_PATH}/change_email/confirm/{email_verification_token}?redirect_uri={}",
+          urlencode(redirect_uri)
+        )
+      } else {
+        format!("/{AUTH_API_PATH}/change_email/confirm/{email_verification_token}")
+      })
+      .map_err(|_err| EmailError::Internal("Invalid URL".into()))?;
+
+    let template = load_template(
+      state.user_conn(),
+      &config.email,
+      EmailTemplateName::ChangeEmailOldAddress,
+    )
+    .await;
+    let content = template.render(context! {
+      APP_NAME => &config.server.application_name,
+      CODE => email_verification_token,
+      EMAIL => email_address,
+      NEW_EMAIL => new_email_address,
+      REDIRECT_URI => redirect_uri,
+      SITE_URL => site_url.origin().ascii_serialization(),
+      TOKEN => email_verification_token,
+      VERIFICATION_URL => verification_url,
+    })?;
+
+    return Email::new_internal(state, to, content);
+  }
+
+  pub(crate) async fn password_reset_email(
+    state: &AppState,
+    email_address: &str,
+    password_reset_token: &str,
+  ) -> Result<Self, EmailError> {
+    let to: Mailbox = email_address.parse()?;
+    let config = state.get_config();
 
     // NOTE: Unlike verify_email and change_email, we're linking to page for users to input their
     // new password.
     let site_url = get_site_url(state);
 
-    let env = Environment::empty();
-    let subject = env
-      .template_from_named_str("subject", subject_template)?
-      .render(context! {
-        APP_NAME => &config.server.application_name,
-        EMAIL => email_address,
-      })?;
-    let body = env
-      .template_from_named_str("body", body_template)?
-      .render(context! {
-        APP_NAME => &config.server.application_name,
-        CODE => password_reset_token,
-        EMAIL => email_address,
-        SITE_URL => site_url.origin().ascii_serialization(),
-        TOKEN => password_reset_token,
-      })?;
-
-    return Email::new_internal(state, to, subject, body);
+    let template = load_template(
+      state.user_conn(),
+      &config.email,
+      EmailTemplateName::PasswordReset,
+    )
+    .await;
+    let content = template.render(context! {
+      APP_NAME => &config.server.application_name,
+      CODE => password_reset_token,
+      EMAIL => email_address,
+      SITE_URL => site_url.origin().ascii_serialization(),
+      TOKEN => password_reset_token,
+    })?;
+
+    return Email::new_internal(state, to, content);
   }
 
-  pub(crate) fn otp_email(
+  pub(crate) async fn otp_email(
     state: &AppState,
     email_address: &str,
     otp_code: &str,
@@ -261,35 +354,57 @@ impl Email {
   ) -> Result<Self, EmailError> {
     let to: Mailbox = email_address.parse()?;
     let config = state.get_config();
-    let template = &config.email.otp_template;
-
-    let subject_template = template
-      .as_ref()
-      .and_then(|t| t.subject.as_deref())
-      .unwrap_or(trailbase_assets::email::DEFAULT_EMAIL_OTP_SUBJECT);
-    let body_template = template
-      .as_ref()
-      .and_then(|t| t.body.as_deref())
-      .unwrap_or(trailbase_assets::email::DEFAULT_EMAIL_OTP_BODY);
     let site_url = get_site_url(state);
 
-    let env = Environment::empty();
-    let subject = env
-      .template_from_named_str("subject", subject_template)?
-      .render(context! {
-        APP_NAME => &config.server.application_name,
-        EMAIL => email_address,
-      })?;
-    let body = env
-      .template_from_named_str("body", body_template)?
-      .render(context! {
-        APP_NAME => &config.server.application_name,
-        CODE => otp_code,
-        EMAIL => email_address,
-        REDIRECT_URI => redirect_uri,
-        SITE_URL => site_url.origin().ascii_serialization(),
-      })?;
-    return Email::new_internal(state, to, subject, body);
+    let template = load_template(state.user_conn(), &config.email, EmailTemplateName::Otp).await;
+    let content = template.render(context! {
+      APP_NAME => &config.server.application_name,
+      CODE => otp_code,
+      EMAIL => email_address,
+      REDIRECT_URI => redirect_uri,
+      SITE_URL => site_url.origin().ascii_serialization(),
+    })?;
+
+    return Email::new_internal(state, to, content);
+  }
+
+  pub(crate) async fn magic_link_email(
+    state: &AppState,
+    email_address: &str,
+    magic_link_token: &str,
+    redirect_uri: Option<&str>,
+  ) -> Result<Self, EmailError> {
+    let to: Mailbox = email_address.parse()?;
+    let config = state.get_config();
+
+    let site_url = get_site_url(state);
+    let magic_link_url = site_url
+      .join(&if let Some(redirect_uri) = redirect_uri {
+        format!(
+          "/{AUTH_API_PATH}/magic_link/login/{magic_link_token}?redirect_uri={}",
+          urlencode(redirect_uri)
+        )
+      } else {
+        format!("/{AUTH_API_PATH}/magic_link/login/{magic_link_token}")
+      })
+      .map_err(|_err| EmailError::Internal("Invalid URL".into()))?;
+
+    let template = load_template(
+      state.user_conn(),
+      &config.email,
+      EmailTemplateName::MagicLink,
+    )
+    .await;
+    let content = template.render(context! {
+      APP_NAME => &config.server.application_name,
+      EMAIL => email_address,
+      MAGIC_LINK_URL => magic_link_url,
+      REDIRECT_URI => redirect_uri,
+      SITE_URL => site_url.origin().ascii_serialization(),
+      TOKEN => magic_link_token,
+    })?;
+
+    return Email::new_internal(state, to, content);
   }
 }
 
@@ -319,85 +434,7 @@ fn fallback_sender(site_url: &Option<url::Url>) -> String {
   return "noreply@localhost".to_string();
 }
 
-#[derive(Clone)]
-pub(crate) enum Mailer {
-  Smtp(Arc<dyn AsyncTransport<Ok = smtp::response::Response, Error = smtp::Error> + Send + Sync>),
-  Local(Arc<AsyncSendmailTransport<Tokio1Executor>>),
-}
-
-impl Mailer {
-  fn new_smtp(
-    host: &str,
-    port: u16,
-    user: Option<String>,
-    pass: Option<String>,
-    encryption: SmtpEncryption,
-  ) -> Result<Mailer, EmailError> {
-    let transport = match encryption {
-      SmtpEncryption::None => {
-        let mut transport =
-          AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host).port(port);
-        if let (Some(user), Some(pass)) = (user, pass) {
-          warn!("Encryption is None. SMTP password will be sent in plain text");
-          transport = transport.credentials(smtp::authentication::Credentials::new(user, pass));
-        }
-
-        transport
-      }
-      SmtpEncryption::Starttls | SmtpEncryption::Undefined => {
-        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
-          .port(port)
-          .credentials(smtp::authentication::Credentials::new(
-            user.ok_or(EmailError::Missing("SMTP username"))?,
-            pass.ok_or(EmailError::Missing("SMTP password"))?,
-          ))
-      }
-      SmtpEncryption::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
-        .port(port)
-        .credentials(smtp::authentication::Credentials::new(
-          user.ok_or(EmailError::Missing("SMTP username"))?,
-          pass.ok_or(EmailError::Missing("SMTP password"))?,
-        )),
-    };
-
-    return Ok(Mailer::Smtp(Arc::new(transport.build())));
-  }
-
-  fn new_local() -> Mailer {
-    return Mailer::Local(Arc::new(AsyncSendmailTransport::<Tokio1Executor>::new()));
-  }
-
-  pub(crate) fn new_from_config(config: &Config) -> Mailer {
-    fn smtp_from_config(email: &EmailConfig) -> Result<Mailer, EmailError> {
-      let host = email
-        .smtp_host
-        .as_deref()
-        .ok_or(EmailError::Missing("SMTP host"))?;
-      let port = email
-        .smtp_port
-        .and_then(|port| u16::try_from(port).ok())
-        .ok_or(EmailError::Missing("SMTP port"))?;
-
-      return Mailer::new_smtp(
-        host,
-        port,
-        email.smtp_username.clone(),
-        email.smtp_password.clone(),
-        email.smtp_encryption(),
-      );
-    }
-
-    return match smtp_from_config(&config.email) {
-      Ok(mailer) => mailer,
-      Err(err) => {
-        info!("Falling back to local sendmail: {err}");
-        Self::new_local()
-      }
-    };
-  }
-}
-
-fn get_site_url(state: &AppState) -> url::Url {
+pub(crate) fn get_site_url(state: &AppState) -> url::Url {
   return match *state.site_url() {
     Some(ref site_url) => site_url.clone(),
     None => {
@@ -468,7 +505,9 @@ pub mod testing {
 
     let code = "verification_code0123.";
     {
-      let email = Email::verification_email(&state, "foo@bar.org", code, Some("/target")).unwrap();
+      let email = Email::verification_email(&state, "foo@bar.org", code, Some("/target"))
+        .await
+        .unwrap();
       assert_eq!(email.subject, "Verify your Email Address for TrailBase");
       assert!(email.body.contains("Welcome foo@bar.org"));
       assert!(email.body.contains(&format!(
@@ -477,8 +516,9 @@ pub mod testing {
     }
 
     {
-      let email =
-        Email::change_email_address_email(&state, "foo@bar.org", code, Some("/target")).unwrap();
+      let email = Email::change_email_address_email(&state, "foo@bar.org", code, Some("/target"))
+        .await
+        .unwrap();
       assert_eq!(email.subject, "Change your Email Address for TrailBase");
       assert!(
         email.body.contains(&format!(
@@ -490,7 +530,33 @@ pub mod testing {
     }
 
     {
-      let email = Email::password_reset_email(&state, "foo@bar.org", code).unwrap();
+      let email = Email::change_email_old_address_email(
+        &state,
+        "foo@bar.org",
+        "new@bar.org",
+        code,
+        Some("/target"),
+      )
+      .await
+      .unwrap();
+      assert_eq!(
+        email.subject,
+        "Confirm your Email Address change for TrailBase"
+      );
+      assert!(email.body.contains("new@bar.org"), "{}", email.body);
+      assert!(
+        email.body.contains(&format!(
+          "https://test.org/api/auth/v1/change_email/confirm/{code}?redirect_uri=%2Ftarget"
+        )),
+        "{}",
+        email.body
+      );
+    }
+
+    {
+      let email = Email::password_reset_email(&state, "foo@bar.org", code)
+        .await
+        .unwrap();
       assert_eq!(email.subject, "Reset your Password for TrailBase");
       assert!(
         email.body.contains(&format!(
@@ -502,7 +568,9 @@ pub mod testing {
     }
 
     {
-      let email = Email::otp_email(&state, "foo@bar.org", "12345678", None).unwrap();
+      let email = Email::otp_email(&state, "foo@bar.org", "12345678", None)
+        .await
+        .unwrap();
       assert_eq!(email.subject, "OTP Sign-in for TrailBase");
       assert!(email.body.contains(&format!("&code=12345678")));
       assert!(!email.body.contains(&format!("redirect_uri")));
@@ -510,12 +578,28 @@ pub mod testing {
     }
 
     {
-      let email = Email::otp_email(&state, "foo@bar.org", "12345678", Some("/go/to")).unwrap();
+      let email = Email::otp_email(&state, "foo@bar.org", "12345678", Some("/go/to"))
+        .await
+        .unwrap();
       assert_eq!(email.subject, "OTP Sign-in for TrailBase");
       assert!(email.body.contains(&format!("&code=12345678")));
       assert!(email.body.contains(&format!("&redirect_uri=/go/to")));
       assert!(email.body.contains("https://test.org/_/auth/otp/login"));
     }
+
+    {
+      let email = Email::magic_link_email(&state, "foo@bar.org", code, Some("/go/to"))
+        .await
+        .unwrap();
+      assert_eq!(email.subject, "Sign in to TrailBase");
+      assert!(
+        email.body.contains(&format!(
+          "https://test.org/api/auth/v1/magic_link/login/{code}?redirect_uri=%2Fgo%2Fto"
+        )),
+        "{}",
+        email.body
+      );
+    }
   }
 
   #[test]
@@ -0,0 +1,144 @@
+use async_trait::async_trait;
+use lettre::transport::smtp;
+use lettre::{AsyncSendmailTransport, AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use log::*;
+use std::sync::Arc;
+
+use crate::config::proto::{Config, EmailConfig, EmailProvider, SmtpEncryption};
+use crate::email::providers::{MailgunMailer, ResendMailer, SendGridMailer, SesMailer};
+use crate::email::{Email, EmailError};
+
+/// Delivers emails via a specific provider, e.g. an SMTP server or an HTTP API.
+#[async_trait]
+pub(crate) trait Mailer: Send + Sync {
+  /// Name of the provider as recorded in the email log.
+  fn provider(&self) -> &'static str;
+
+  /// Makes a single delivery attempt. Retries are up to the caller.
+  async fn send(&self, email: &Email) -> Result<(), EmailError>;
+}
+
+type SmtpTransport =
+  dyn AsyncTransport<Ok = smtp::response::Response, Error = smtp::Error> + Send + Sync;
+
+pub(crate) struct SmtpMailer {
+  transport: Box<SmtpTransport>,
+}
+
+impl SmtpMailer {
+  pub(crate) fn new(
+    transport: impl AsyncTransport<Ok = smtp::response::Response, Error = smtp::Error>
+    + Send
+    + Sync
+    + 'static,
+  ) -> Self {
+    return Self {
+      transport: Box::new(transport),
+    };
+  }
+
+  fn from_config(email: &EmailConfig) -> Result<Self, EmailError> {
+    let host = email
+      .smtp_host
+      .as_deref()
+      .ok_or(EmailError::Missing("SMTP host"))?;
+    let port = email
+      .smtp_port
+      .and_then(|port| u16::try_from(port).ok())
+      .ok_or(EmailError::Missing("SMTP port"))?;
+    let user = email.smtp_username.clone();
+    let pass = email.smtp_password.clone();
+
+    let transport = match email.smtp_encryption() {
+      SmtpEncryption::None => {
+        let mut transport =
+          AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host).port(port);
+        if let (Some(user), Some(pass)) = (user, pass) {
+          warn!("Encryption is None. SMTP password will be sent in plain text");
+          transport = transport.credentials(smtp::authentication::Credentials::new(user, pass));
+        }
+
+        transport
+      }
+      SmtpEncryption::Starttls | SmtpEncryption::Undefined => {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+          .port(port)
+          .credentials(smtp::authentication::Credentials::new(
+            user.ok_or(EmailError::Missing("SMTP username"))?,
+            pass.ok_or(EmailError::Missing("SMTP password"))?,
+          ))
+      }
+      SmtpEncryption::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+        .port(port)
+        .credentials(smtp::authentication::Credentials::new(
+          user.ok_or(EmailError::Missing("SMTP username"))?,
+          pass.ok_or(EmailError::Missing("SMTP password"))?,
+        )),
+    };
+
+    return Ok(Self::new(transport.build()));
+  }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+  fn provider(&self) -> &'static str {
+    return "smtp";
+  }
+
+  async fn send(&self, email: &Email) -> Result<(), EmailError> {
+    self.transport.send(email.to_message()?).await?;
+    return Ok(());
+  }
+}
+
+pub(crate) struct SendmailMailer {
+  transport: AsyncSendmailTransport<Tokio1Executor>,
+}
+
+impl SendmailMailer {
+  fn new() -> Self {
+    return Self {
+      transport: AsyncSendmailTransport::<Tokio1Executor>::new(),
+    };
+  }
+}
+
+#[async_trait]
+impl Mailer for SendmailMailer {
+  fn provider(&self) -> &'static str {
+    return "sendmail";
+  }
+
+  async fn send(&self, email: &Email) -> Result<(), EmailError> {
+    self.transport.send(email.to_message()?).await?;
+    return Ok(());
+  }
+}
+
+/// Builds the mailer for the configured provider. Without an explicit provider, SMTP is used if
+/// configured. Falls back to the local sendmail otherwise or if the configuration is incomplete.
+pub(crate) fn build_mailer(config: &Config) -> Arc<dyn Mailer> {
+  let email = &config.email;
+
+  fn arc(mailer: impl Mailer + 'static) -> Arc<dyn Mailer> {
+    return Arc::new(mailer);
+  }
+
+  let mailer = match email.provider() {
+    EmailProvider::Undefined | EmailProvider::Smtp => SmtpMailer::from_config(email).map(arc),
+    EmailProvider::Sendmail => Ok(arc(SendmailMailer::new())),
+    EmailProvider::Ses => SesMailer::new(email.ses.as_ref()).map(arc),
+    EmailProvider::Mailgun => MailgunMailer::new(email.mailgun.as_ref()).map(arc),
+    EmailProvider::Sendgrid => SendGridMailer::new(email.sendgrid.as_ref()).map(arc),
+    EmailProvider::Resend => ResendMailer::new(email.resend.as_ref()).map(arc),
+  };
+
+  return match mailer {
+    Ok(mailer) => mailer,
+    Err(err) => {
+      info!("Falling back to local sendmail: {err}");
+      arc(SendmailMailer::new())
+    }
+  };
+}
@@ -0,0 +1,558 @@
+//! Transactional email providers offering HTTP APIs.
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+use crate::config::proto::{MailgunConfig, ResendConfig, SendGridConfig, SesConfig};
+use crate::email::{Email, EmailError, Mailer};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+fn build_client() -> Result<reqwest::Client, EmailError> {
+  return Ok(reqwest::Client::builder().timeout(TIMEOUT).build()?);
+}
+
+async fn check_response(response: reqwest::Response) -> Result<(), EmailError> {
+  let status = response.status();
+  if status.is_success() {
+    return Ok(());
+  }
+
+  return Err(EmailError::Api {
+    status: status.as_u16(),
+    message: response.text().await.unwrap_or_default(),
+  });
+}
+
+/// Amazon SES using the v2 SendEmail API.
+pub(crate) struct SesMailer {
+  client: reqwest::Client,
+  region: String,
+  endpoint: String,
+  access_key_id: String,
+  secret_access_key: String,
+  session_token: Option<String>,
+}
+
+impl SesMailer {
+  pub(crate) fn new(config: Option<&SesConfig>) -> Result<Self, EmailError> {
+    let config = config.cloned().unwrap_or_default();
+    let setting = |value: Option<String>, env: &str| -> Option<String> {
+      return value.or_else(|| std::env::var(env).ok());
+    };
+
+    let region = setting(config.region, "AWS_REGION").ok_or(EmailError::Missing("SES region"))?;
+    let access_key_id = setting(config.access_key_id, "AWS_ACCESS_KEY_ID")
+      .ok_or(EmailError::Missing("SES access key id"))?;
+    let secret_access_key = setting(config.secret_access_key, "AWS_SECRET_ACCESS_KEY")
+      .ok_or(EmailError::Missing("SES secret access key"))?;
+
+    return Ok(Self {
+      client: build_client()?,
+      endpoint: config
+        .endpoint
+        .map(|endpoint| endpoint.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| format!("https://email.{region}.amazonaws.com")),
+      region,
+      access_key_id,
+      secret_access_key,
+      session_token: setting(config.session_token, "AWS_SESSION_TOKEN"),
+    });
+  }
+
+  /// Signs the request using AWS Signature Version 4.
+  fn authorization(
+    &self,
+    host: &str,
+    amz_date: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+  ) -> String {
+    const SERVICE: &str = "ses";
+
+    let date = &amz_date[..8];
+    let scope = format!(
+      "{date}/{region}/{SERVICE}/aws4_request",
+      region = self.region
+    );
+
+    // NOTE: Headers must be sorted by name.
+    let mut canonical_headers: Vec<(&str, &str)> = vec![("host", host)];
+    canonical_headers.extend_from_slice(headers);
+    canonical_headers.sort_by_key(|(name, _)| *name);
+
+    let signed_headers = canonical_headers
+      .iter()
+      .map(|(name, _)| *name)
+      .collect::<Vec<_>>()
+      .join(";");
+    let canonical_request = format!(
+      "POST\n{SES_SEND_PATH}\n\n{headers}\n{signed_headers}\n{payload}",
+      headers = canonical_headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect::<String>(),
+      payload = hex(&Sha256::digest(body.as_bytes())),
+    );
+    let string_to_sign = format!(
+      "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{hash}",
+      hash = hex(&Sha256::digest(canonical_request.as_bytes())),
+    );
+
+    let key = [self.region.as_str(), SERVICE, "aws4_request"]
+      .into_iter()
+      .fold(
+        hmac(format!("AWS4{}", self.secret_access_key).as_bytes(), date),
+        |key, segment| hmac(&key, segment),
+      );
+
+    return format!(
+      "AWS4-HMAC-SHA256 Credential={access_key_id}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+      access_key_id = self.access_key_id,
+      signature = hex(&hmac(&key, &string_to_sign)),
+    );
+  }
+}
+
+#[async_trait]
+impl Mailer for SesMailer {
+  fn provider(&self) -> &'static str {
+    return "ses";
+  }
+
+  async fn send(&self, email: &Email) -> Result<(), EmailError> {
+    let url = url::Url::parse(&format!("{}{SES_SEND_PATH}", self.endpoint))
+      .map_err(|err| EmailError::Internal(err.into()))?;
+    let host = match (url.host_str(), url.port()) {
+      (Some(host), Some(port)) => format!("{host}:{port}"),
+      (Some(host), None) => host.to_string(),
+      (None, _) => return Err(EmailError::Internal("Invalid SES endpoint".into())),
+    };
+
+    let body = serde_json::json!({
+      "FromEmailAddress": email.from.to_string(),
+      "Destination": { "ToAddresses": [email.to.to_string()] },
+      "Content": {
+        "Simple": {
+          "Subject": { "Data": email.subject, "Charset": "UTF-8" },
+          "Body": match email.text {
+            Some(ref text) => serde_json::json!({
+              "Html": { "Data": email.body, "Charset": "UTF-8" },
+              "Text": { "Data": text, "Charset": "UTF-8" },
+            }),
+            None => serde_json::json!({ "Html": { "Data": email.body, "Charset": "UTF-8" } }),
+          },
+        },
+      },
+    })
+    .to_string();
+    let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut headers = vec![
+      ("content-type", "application/json"),
+      ("x-amz-date", amz_date.as_str()),
+    ];
+    if let Some(ref token) = self.session_token {
+      headers.push(("x-amz-security-token", token.as_str()));
+    }
+    let authorization = self.authorization(&host, &amz_date, &headers, &body);
+
+    let mut request = self
+      .client
+      .post(url)
+      .header("authorization", authorization)
+      .body(body);
+    for (name, value) in headers {
+      request = request.header(name, value);
+    }
+
+    return check_response(request.send().await?).await;
+  }
+}
+
+const SES_SEND_PATH: &str = "/v2/email/outbound-emails";
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length");
+  mac.update(data.as_bytes());
+  return mac.finalize().into_bytes().to_vec();
+}
+
+fn hex(bytes: &[u8]) -> String {
+  return bytes.iter().map(|b| format!("{b:02x}")).collect();
+}
+
+pub(crate) struct MailgunMailer {
+  client: reqwest::Client,
+  url: String,
+  api_key: String,
+}
+
+impl MailgunMailer {
+  pub(crate) fn new(config: Option<&MailgunConfig>) -> Result<Self, EmailError> {
+    let config = config.ok_or(EmailError::Missing("Mailgun config"))?;
+    let domain = config
+      .domain
+      .as_deref()
+      .ok_or(EmailError::Missing("Mailgun domain"))?;
+
+    return Ok(Self {
+      client: build_client()?,
+      url: format!(
+        "{endpoint}/v3/{domain}/messages",
+        endpoint = config
+          .endpoint
+          .as_deref()
+          .unwrap_or("https://api.mailgun.net")
+          .trim_end_matches('/'),
+      ),
+      api_key: config
+        .api_key
+        .clone()
+        .ok_or(EmailError::Missing("Mailgun API key"))?,
+    });
+  }
+}
+
+#[async_trait]
+impl Mailer for MailgunMailer {
+  fn provider(&self) -> &'static str {
+    return "mailgun";
+  }
+
+  async fn send(&self, email: &Email) -> Result<(), EmailError> {
+    let mut form = url::form_urlencoded::Serializer::new(String::new());
+    form
+      .append_pair("from", &email.from.to_string())
+      .append_pair("to", &email.to.to_string())
+      .append_pair("subject", &email.subject)
+      .append_pair("html", &email.body);
+    if let Some(ref text) = email.text {
+      form.append_pair("text", text);
+    }
+
+    let response = self
+      .client
+      .post(&self.url)
+      .basic_auth("api", Some(&self.api_key))
+      .header("content-type", "application/x-www-form-urlencoded")
+      .body(form.finish())
+      .send()
+      .await?;
+
+    return check_response(response).await;
+  }
+}
+
+pub(crate) struct SendGridMailer {
+  client: reqwest::Client,
+  url: String,
+  api_key: String,
+}
+
+impl SendGridMailer {
+  pub(crate) fn new(config: Option<&SendGridConfig>) -> Result<Self, EmailError> {
+    let config = config.ok_or(EmailError::Missing("SendGrid config"))?;
+
+    return Ok(Self {
+      client: build_client()?,
+      url: format!(
+        "{endpoint}/v3/mail/send",
+        endpoint = config
+          .endpoint
+          .as_deref()
+          .unwrap_or("https://api.sendgrid.com")
+          .trim_end_matches('/'),
+      ),
+      api_key: config
+        .api_key
+        .clone()
+        .ok_or(EmailError::Missing("SendGrid API key"))?,
+    });
+  }
+}
+
+#[async_trait]
+impl Mailer for SendGridMailer {
+  fn provider(&self) -> &'static str {
+    return "sendgrid";
+  }
+
+  async fn send(&self, email: &Email) -> Result<(), EmailError> {
+    let address = |mailbox: &lettre::message::Mailbox| {
+      return serde_json::json!({
+        "email": mailbox.email.to_string(),
+        "name": mailbox.name,
+      });
+    };
+
+    let response = self
+      .client
+      .post(&self.url)
+      .bearer_auth(&self.api_key)
+      .json(&serde_json::json!({
+        "personalizations": [{ "to": [address(&email.to)] }],
+        "from": address(&email.from),
+        "subject": email.subject,
+        // NOTE: SendGrid requires "text/plain" to precede "text/html".
+        "content": email
+          .text
+          .iter()
+          .map(|text| serde_json::json!({ "type": "text/plain", "value": text }))
+          .chain([serde_json::json!({ "type": "text/html", "value": email.body })])
+          .collect::<Vec<_>>(),
+      }))
+      .send()
+      .await?;
+
+    return check_response(response).await;
+  }
+}
+
+pub(crate) struct ResendMailer {
+  client: reqwest::Client,
+  url: String,
+  api_key: String,
+}
+
+impl ResendMailer {
+  pub(crate) fn new(config: Option<&ResendConfig>) -> Result<Self, EmailError> {
+    let config = config.ok_or(EmailError::Missing("Resend config"))?;
+
+    return Ok(Self {
+      client: build_client()?,
+      url: format!(
+        "{endpoint}/emails",
+        endpoint = config
+          .endpoint
+          .as_deref()
+          .unwrap_or("https://api.resend.com")
+          .trim_end_matches('/'),
+      ),
+      api_key: config
+        .api_key
+        .clone()
+        .ok_or(EmailError::Missing("Resend API key"))?,
+    });
+  }
+}
+
+#[async_trait]
+impl Mailer for ResendMailer {
+  fn provider(&self) -> &'static str {
+    return "resend";
+  }
+
+  async fn send(&self, email: &Email) -> Result<(), EmailError> {
+    let mut body = serde_json::json!({
+      "from": email.from.to_string(),
+      "to": [email.to.to_string()],
+      "subject": email.subject,
+      "html": email.body,
+    });
+    if let Some(ref text) = email.text {
+      body["text"] = serde_json::Value::String(text.clone());
+    }
+
+    let response = self
+      .client
+      .post(&self.url)
+      .bearer_auth(&self.api_key)
+      .json(&body)
+      .send()
+      .await?;
+
+    return check_response(response).await;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::Router;
+  use axum::extract::State;
+  use axum::http::{HeaderMap, StatusCode};
+  use axum::routing::post;
+  use parking_lot::Mutex;
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::app_state::{AppState, TestStateOptions, test_state};
+  use crate::config::proto::EmailProvider;
+  use crate::constants::{EMAIL_LOG_TABLE, EMAIL_QUEUE_TABLE};
+  use crate::email::queue::process_due;
+
+  #[derive(Clone, Default)]
+  struct Upstream {
+    requests: Arc<Mutex<Vec<(Option<String>, serde_json::Value)>>>,
+  }
+
+  async fn resend_handler(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+    body: String,
+  ) -> StatusCode {
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let authorization = headers
+      .get("authorization")
+      .and_then(|v| v.to_str().ok())
+      .map(|v| v.to_string());
+
+    let mut requests = upstream.requests.lock();
+    requests.push((authorization, json.clone()));
+
+    // Fail the first attempt transiently and reject invalid recipients permanently.
+    if requests.len() == 1 {
+      return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    if json["to"][0] == "invalid@test.org" {
+      return StatusCode::UNPROCESSABLE_ENTITY;
+    }
+    return StatusCode::OK;
+  }
+
+  async fn queue_statuses(state: &AppState) -> Vec<(String, String, i64)> {
+    return state
+      .conn()
+      .read_query_rows(
+        format!("SELECT recipient, status, attempts FROM '{EMAIL_QUEUE_TABLE}' ORDER BY id"),
+        (),
+      )
+      .await
+      .unwrap()
+      .iter()
+      .map(|row| {
+        (
+          row.get(0).unwrap(),
+          row.get(1).unwrap(),
+          row.get(2).unwrap(),
+        )
+      })
+      .collect();
+  }
+
+  #[tokio::test]
+  async fn test_resend_mailer_retries_and_logs() {
+    let upstream = Upstream::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = Router::new()
+      .route("/emails", post(resend_handler))
+      .with_state(upstream.clone());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let mut config = crate::app_state::test_config();
+    config.email.set_provider(EmailProvider::Resend);
+    config.email.max_attempts = Some(2);
+    config.email.sender_address = Some("sender@test.org".to_string());
+    config.email.resend = Some(ResendConfig {
+      api_key: Some("re_secret".to_string()),
+      endpoint: Some(format!("http://{addr}")),
+    });
+
+    let state = test_state(Some(TestStateOptions {
+      config: Some(config),
+      ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    Email::new(
+      &state,
+      "user@test.org",
+      "subject".to_string(),
+      "<p>body</p>".to_string(),
+    )
+    .unwrap()
+    .send()
+    .await
+    .unwrap();
+
+    // The first attempt failed transiently and the email remains queued with backoff.
+    assert_eq!(upstream.requests.lock().len(), 1);
+    assert_eq!(
+      queue_statuses(&state).await,
+      [("user@test.org".to_string(), "queued".to_string(), 1)]
+    );
+    assert_eq!(process_due(&state).await.unwrap(), 0);
+
+    state
+      .conn()
+      .execute(
+        format!("UPDATE '{EMAIL_QUEUE_TABLE}' SET next_attempt = 0"),
+        (),
+      )
+      .await
+      .unwrap();
+    assert_eq!(process_due(&state).await.unwrap(), 1);
+
+    {
+      let requests = upstream.requests.lock();
+      assert_eq!(requests.len(), 2);
+      let (authorization, json) = &requests[1];
+      assert_eq!(authorization.as_deref(), Some("Bearer re_secret"));
+      assert_eq!(json["from"], "sender@test.org");
+      assert_eq!(json["to"][0], "user@test.org");
+      assert_eq!(json["subject"], "subject");
+      assert_eq!(json["html"], "<p>body</p>");
+    }
+    assert_eq!(queue_statuses(&state).await, []);
+
+    // Permanent errors aren't retried.
+    Email::new(
+      &state,
+      "invalid@test.org",
+      "subject".to_string(),
+      "body".to_string(),
+    )
+    .unwrap()
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(upstream.requests.lock().len(), 3);
+    assert_eq!(
+      queue_statuses(&state).await,
+      [("invalid@test.org".to_string(), "bounced".to_string(), 1)]
+    );
+
+    let rows = state
+      .logs_conn()
+      .read_query_rows(
+        format!(
+          "SELECT provider, recipient, status, attempts FROM '{EMAIL_LOG_TABLE}' ORDER BY id ASC"
+        ),
+        (),
+      )
+      .await
+      .unwrap();
+    let rows: Vec<(String, String, String, i64)> = rows
+      .iter()
+      .map(|row| {
+        (
+          row.get(0).unwrap(),
+          row.get(1).unwrap(),
+          row.get(2).unwrap(),
+          row.get(3).unwrap(),
+        )
+      })
+      .collect();
+    assert_eq!(
+      rows,
+      [
+        (
+          "resend".to_string(),
+          "user@test.org".to_string(),
+          "sent".to_string(),
+          2
+        ),
+        (
+          "resend".to_string(),
+          "invalid@test.org".to_string(),
+          "failed".to_string(),
+          1
+        ),
+      ]
+    );
+  }
+}
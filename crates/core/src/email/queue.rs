@@ -0,0 +1,331 @@
+//! Durable queue of outbound emails.
+//!
+//! [Email::send] enqueues emails into the `_email_queue` table, which a background worker drains
+//! subject to the configured per-minute rate limit. Transient failures are retried with
+//! exponential backoff. Emails that ran out of attempts or were rejected permanently are kept as
+//! "failed" or "bounced" respectively, from where they can be requeued via the admin API.
+
+use const_format::formatcp;
+use lettre::message::Mailbox;
+use log::*;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use trailbase_sqlite::{Connection, params};
+
+use crate::app_state::AppState;
+use crate::config::proto::EmailConfig;
+use crate::constants::EMAIL_QUEUE_TABLE;
+use crate::email::{Email, EmailError};
+
+#[derive(Debug, Deserialize)]
+struct QueuedEmail {
+  id: i64,
+  attempts: i64,
+  sender: String,
+  recipient: String,
+  subject: String,
+  html: String,
+  text: Option<String>,
+}
+
+impl QueuedEmail {
+  fn into_email(self, state: &AppState) -> Result<Email, EmailError> {
+    return Ok(Email {
+      state: state.clone(),
+      from: self.sender.parse::<Mailbox>()?,
+      to: self.recipient.parse::<Mailbox>()?,
+      subject: self.subject,
+      body: self.html,
+      text: self.text,
+    });
+  }
+}
+
+pub(crate) struct EmailQueue {
+  conn: Connection,
+  /// Wakes up the worker, e.g. after enqueuing.
+  notify: Notify,
+}
+
+impl EmailQueue {
+  pub(crate) fn new(conn: Connection) -> Self {
+    return Self {
+      conn,
+      notify: Notify::new(),
+    };
+  }
+
+  /// Enqueues an email for immediate delivery and returns its id.
+  pub(crate) async fn enqueue(&self, email: &Email) -> Result<i64, trailbase_sqlite::Error> {
+    const QUERY: &str = formatcp!(
+      "INSERT INTO '{EMAIL_QUEUE_TABLE}' (sender, recipient, subject, html, text) VALUES ($1, $2, $3, $4, $5) RETURNING id"
+    );
+
+    let id: i64 = self
+      .conn
+      .write_query_row_get(
+        QUERY,
+        params!(
+          email.from.to_string(),
+          email.to.to_string(),
+          email.subject.clone(),
+          email.body.clone(),
+          email.text.clone(),
+        ),
+        0,
+      )
+      .await?
+      .ok_or_else(|| trailbase_sqlite::Error::Other("missing id".into()))?;
+
+    self.notify.notify_one();
+    return Ok(id);
+  }
+
+  /// Resets an undeliverable email for immediate delivery. Returns false if no such email exists.
+  pub(crate) async fn requeue(&self, id: i64) -> Result<bool, trailbase_sqlite::Error> {
+    const QUERY: &str = formatcp!(
+      "UPDATE '{EMAIL_QUEUE_TABLE}' SET status = 'queued', attempts = 0, next_attempt = UNIXEPOCH() WHERE id = $1"
+    );
+
+    let rows_affected = self.conn.execute(QUERY, params!(id)).await?;
+
+    self.notify.notify_one();
+    return Ok(rows_affected > 0);
+  }
+
+  /// Claims up to `limit` due emails.
+  async fn claim(&self, limit: usize) -> Result<Vec<QueuedEmail>, trailbase_sqlite::Error> {
+    // Claiming bumps `next_attempt`, which keeps emails from being sent twice. Emails left over
+    // from a crash are picked up again once the lease expires.
+    const QUERY: &str = formatcp!(
+      "\
+        UPDATE '{EMAIL_QUEUE_TABLE}' \
+        SET attempts = attempts + 1, next_attempt = UNIXEPOCH() + {LEASE_SEC} \
+        WHERE id IN ( \
+          SELECT id FROM '{EMAIL_QUEUE_TABLE}' \
+          WHERE status = 'queued' AND next_attempt <= UNIXEPOCH() \
+          ORDER BY id LIMIT $1 \
+        ) \
+        RETURNING id, attempts, sender, recipient, subject, html, text \
+      "
+    );
+
+    if limit == 0 {
+      return Ok(vec![]);
+    }
+
+    return self
+      .conn
+      .write_query_values(QUERY, params!(limit as i64))
+      .await;
+  }
+
+  /// Makes a delivery attempt for a claimed email and records the outcome.
+  async fn deliver(
+    &self,
+    state: &AppState,
+    queued: QueuedEmail,
+    max_attempts: i64,
+  ) -> Result<(), trailbase_sqlite::Error> {
+    let (id, attempts) = (queued.id, queued.attempts);
+    let email = match queued.into_email(state) {
+      Ok(email) => email,
+      Err(err) => {
+        return self.set_status(id, "bounced", &err).await;
+      }
+    };
+
+    let mailer = state.mailer();
+    let result = mailer.send(&email).await;
+    match result {
+      Ok(()) => {
+        const QUERY: &str = formatcp!("DELETE FROM '{EMAIL_QUEUE_TABLE}' WHERE id = $1");
+        self.conn.execute(QUERY, params!(id)).await?;
+      }
+      Err(ref err) if err.is_transient() && attempts < max_attempts => {
+        const QUERY: &str = formatcp!(
+          "UPDATE '{EMAIL_QUEUE_TABLE}' SET next_attempt = UNIXEPOCH() + $2, last_error = $3 WHERE id = $1"
+        );
+        let backoff = INITIAL_BACKOFF_SEC << (attempts - 1).clamp(0, 16);
+        debug!("Retrying email to {} in {backoff}s: {err}", email.to);
+        self
+          .conn
+          .execute(QUERY, params!(id, backoff, err.to_string()))
+          .await?;
+
+        // Only final outcomes are recorded in the email log.
+        return Ok(());
+      }
+      Err(ref err) => {
+        let status = if err.is_transient() {
+          "failed"
+        } else {
+          "bounced"
+        };
+        warn!(
+          "Giving up on email to {} after {attempts} attempts: {err}",
+          email.to
+        );
+        self.set_status(id, status, err).await?;
+      }
+    }
+
+    email
+      .record_outcome(mailer.provider(), attempts, result.as_ref().err())
+      .await;
+
+    return Ok(());
+  }
+
+  async fn set_status(
+    &self,
+    id: i64,
+    status: &'static str,
+    err: &EmailError,
+  ) -> Result<(), trailbase_sqlite::Error> {
+    const QUERY: &str =
+      formatcp!("UPDATE '{EMAIL_QUEUE_TABLE}' SET status = $2, last_error = $3 WHERE id = $1");
+
+    self
+      .conn
+      .execute(QUERY, params!(id, status.to_string(), err.to_string()))
+      .await?;
+    return Ok(());
+  }
+}
+
+/// Sliding window of emails sent within the last minute.
+#[derive(Default)]
+struct RateLimit {
+  sent: VecDeque<Instant>,
+}
+
+impl RateLimit {
+  /// Number of emails that may be sent right now without exceeding the per-minute limit.
+  fn budget(&mut self, now: Instant, per_minute: Option<u32>) -> usize {
+    while self
+      .sent
+      .front()
+      .is_some_and(|sent| now.duration_since(*sent) >= Duration::from_secs(60))
+    {
+      self.sent.pop_front();
+    }
+
+    return match per_minute {
+      Some(limit) => (limit as usize).saturating_sub(self.sent.len()),
+      None => usize::MAX,
+    };
+  }
+
+  fn record(&mut self, now: Instant) {
+    self.sent.push_back(now);
+  }
+}
+
+fn max_attempts(config: &EmailConfig) -> i64 {
+  return config
+    .max_attempts
+    .map_or(DEFAULT_MAX_ATTEMPTS, |m| m as i64)
+    .max(1);
+}
+
+/// Spawns the worker draining the email queue.
+pub(crate) fn spawn_email_worker(state: AppState) -> tokio::task::JoinHandle<()> {
+  return tokio::spawn(async move {
+    let queue = state.email_queue();
+    let mut rate_limit = RateLimit::default();
+
+    loop {
+      let (per_minute, max_attempts) =
+        state.access_config(|c| (c.email.rate_limit_per_minute, max_attempts(&c.email)));
+
+      let limit = rate_limit
+        .budget(Instant::now(), per_minute)
+        .min(BATCH_SIZE);
+      let claimed = match queue.claim(limit).await {
+        Ok(emails) => emails,
+        Err(err) => {
+          warn!("Failed to claim emails: {err}");
+          vec![]
+        }
+      };
+
+      let count = claimed.len();
+      for queued in claimed {
+        rate_limit.record(Instant::now());
+        if let Err(err) = queue.deliver(&state, queued, max_attempts).await {
+          warn!("Failed to record email outcome: {err}");
+        }
+      }
+
+      // Keep going while there's a backlog.
+      if count == BATCH_SIZE {
+        continue;
+      }
+
+      tokio::select! {
+        _ = queue.notify.notified() => {}
+        _ = tokio::time::sleep(POLL_INTERVAL) => {}
+      }
+    }
+  });
+}
+
+/// Delivers all due emails ignoring the rate limit. Returns the number of claimed emails.
+#[cfg(test)]
+pub(crate) async fn process_due(state: &AppState) -> Result<usize, trailbase_sqlite::Error> {
+  let queue = state.email_queue();
+  let max_attempts = max_attempts(&state.get_config().email);
+
+  let claimed = queue.claim(BATCH_SIZE).await?;
+  let count = claimed.len();
+  for queued in claimed {
+    queue.deliver(state, queued, max_attempts).await?;
+  }
+  return Ok(count);
+}
+
+const DEFAULT_MAX_ATTEMPTS: i64 = 5;
+/// Backoff after the first failed attempt in seconds. Doubles with every further attempt.
+const INITIAL_BACKOFF_SEC: i64 = 30;
+/// Claimed emails are considered abandoned, e.g. due to a crash, once the lease expires.
+const LEASE_SEC: i64 = 600;
+const BATCH_SIZE: usize = 10;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rate_limit() {
+    let mut rate_limit = RateLimit::default();
+    let start = Instant::now();
+
+    assert_eq!(rate_limit.budget(start, None), usize::MAX);
+    assert_eq!(rate_limit.budget(start, Some(2)), 2);
+
+    rate_limit.record(start);
+    rate_limit.record(start + Duration::from_secs(30));
+    assert_eq!(
+      rate_limit.budget(start + Duration::from_secs(30), Some(2)),
+      0
+    );
+    assert_eq!(
+      rate_limit.budget(start + Duration::from_secs(30), Some(3)),
+      1
+    );
+
+    // The first send drops out of the window after a minute.
+    assert_eq!(
+      rate_limit.budget(start + Duration::from_secs(60), Some(2)),
+      1
+    );
+    assert_eq!(
+      rate_limit.budget(start + Duration::from_secs(90), Some(2)),
+      2
+    );
+  }
+}
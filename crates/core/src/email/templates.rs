@@ -0,0 +1,287 @@
+//! Email templates. Admins can override templates in the database, which take precedence over
+//! templates in the config and the built-in defaults.
+
+use const_format::formatcp;
+use log::*;
+use minijinja::{Environment, Value, context};
+use serde::{Deserialize, Serialize};
+use trailbase_assets::email as defaults;
+use trailbase_sqlite::params;
+use ts_rs::TS;
+
+use crate::config::proto::{EmailConfig, EmailTemplate};
+use crate::constants::EMAIL_TEMPLATES_TABLE;
+use crate::email::EmailError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum EmailTemplateName {
+  UserVerification,
+  ChangeEmail,
+  /// Sent to a user's old address to confirm a change of email address.
+  ChangeEmailOldAddress,
+  PasswordReset,
+  Otp,
+  MagicLink,
+}
+
+impl EmailTemplateName {
+  pub const ALL: [Self; 6] = [
+    Self::UserVerification,
+    Self::ChangeEmail,
+    Self::ChangeEmailOldAddress,
+    Self::PasswordReset,
+    Self::Otp,
+    Self::MagicLink,
+  ];
+
+  pub fn name(self) -> &'static str {
+    return match self {
+      Self::UserVerification => "user_verification",
+      Self::ChangeEmail => "change_email",
+      Self::ChangeEmailOldAddress => "change_email_old_address",
+      Self::PasswordReset => "password_reset",
+      Self::Otp => "otp",
+      Self::MagicLink => "magic_link",
+    };
+  }
+
+  /// Variables available when rendering the template.
+  pub fn variables(self) -> &'static [&'static str] {
+    return match self {
+      Self::UserVerification | Self::ChangeEmail => &[
+        "APP_NAME",
+        "CODE",
+        "EMAIL",
+        "REDIRECT_URI",
+        "SITE_URL",
+        "TOKEN",
+        "VERIFICATION_URL",
+      ],
+      Self::ChangeEmailOldAddress => &[
+        "APP_NAME",
+        "CODE",
+        "EMAIL",
+        "NEW_EMAIL",
+        "REDIRECT_URI",
+        "SITE_URL",
+        "TOKEN",
+        "VERIFICATION_URL",
+      ],
+      Self::PasswordReset => &["APP_NAME", "CODE", "EMAIL", "SITE_URL", "TOKEN"],
+      Self::Otp => &["APP_NAME", "CODE", "EMAIL", "REDIRECT_URI", "SITE_URL"],
+      Self::MagicLink => &[
+        "APP_NAME",
+        "EMAIL",
+        "MAGIC_LINK_URL",
+        "REDIRECT_URI",
+        "SITE_URL",
+        "TOKEN",
+      ],
+    };
+  }
+
+  /// Variables, of which the body has to reference at least one for the email to be actionable.
+  pub(crate) fn required_variables(self) -> &'static [&'static str] {
+    return match self {
+      Self::UserVerification | Self::ChangeEmail | Self::ChangeEmailOldAddress => {
+        &["VERIFICATION_URL", "CODE", "TOKEN"]
+      }
+      Self::PasswordReset => &["TOKEN", "CODE"],
+      Self::Otp => &["CODE"],
+      Self::MagicLink => &["MAGIC_LINK_URL", "TOKEN"],
+    };
+  }
+
+  pub(crate) fn config_template(self, config: &EmailConfig) -> Option<&EmailTemplate> {
+    return match self {
+      Self::UserVerification => config.user_verification_template.as_ref(),
+      Self::ChangeEmail => config.change_email_template.as_ref(),
+      Self::ChangeEmailOldAddress => config.change_email_old_address_template.as_ref(),
+      Self::PasswordReset => config.password_reset_template.as_ref(),
+      Self::Otp => config.otp_template.as_ref(),
+      Self::MagicLink => config.magic_link_template.as_ref(),
+    };
+  }
+
+  fn defaults(self) -> (&'static str, &'static str) {
+    return match self {
+      Self::UserVerification => (
+        defaults::DEFAULT_EMAIL_VERIFICATION_SUBJECT,
+        defaults::DEFAULT_EMAIL_VERIFICATION_BODY,
+      ),
+      Self::ChangeEmail => (
+        defaults::DEFAULT_EMAIL_CHANGE_ADDRESS_SUBJECT,
+        defaults::DEFAULT_EMAIL_CHANGE_ADDRESS_BODY,
+      ),
+      Self::ChangeEmailOldAddress => (
+        defaults::DEFAULT_EMAIL_CHANGE_OLD_ADDRESS_SUBJECT,
+        defaults::DEFAULT_EMAIL_CHANGE_OLD_ADDRESS_BODY,
+      ),
+      Self::PasswordReset => (
+        defaults::DEFAULT_EMAIL_PASSWORD_RESET_SUBJECT,
+        defaults::DEFAULT_EMAIL_PASSWORD_RESET_BODY,
+      ),
+      Self::Otp => (
+        defaults::DEFAULT_EMAIL_OTP_SUBJECT,
+        defaults::DEFAULT_EMAIL_OTP_BODY,
+      ),
+      Self::MagicLink => (
+        defaults::DEFAULT_EMAIL_MAGIC_LINK_SUBJECT,
+        defaults::DEFAULT_EMAIL_MAGIC_LINK_BODY,
+      ),
+    };
+  }
+
+  /// Placeholder values for rendering previews.
+  pub(crate) fn sample_context(self, app_name: Option<&str>, site_url: &url::Url) -> Value {
+    let site = site_url.origin().ascii_serialization();
+    let token = "sample_token0123";
+    let url = |path: &str| format!("{site}{path}{token}?redirect_uri=%2Fwelcome");
+
+    return context! {
+      APP_NAME => app_name,
+      CODE => "12345678",
+      EMAIL => "user@example.com",
+      NEW_EMAIL => "new@example.com",
+      REDIRECT_URI => "/welcome",
+      SITE_URL => site,
+      TOKEN => token,
+      VERIFICATION_URL => match self {
+        Self::UserVerification => url("/api/auth/v1/verify_email/confirm/"),
+        _ => url("/api/auth/v1/change_email/confirm/"),
+      },
+      MAGIC_LINK_URL => url("/api/auth/v1/magic_link/login/"),
+    };
+  }
+}
+
+/// Subject and bodies of an email, either as template or rendered.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct EmailContent {
+  pub subject: String,
+  pub html: String,
+  /// Plain-text alternative of the HTML body.
+  pub text: Option<String>,
+}
+
+impl EmailContent {
+  pub(crate) fn render(&self, ctx: Value) -> Result<EmailContent, EmailError> {
+    let render = |name: &str, template: &str| -> Result<String, EmailError> {
+      let env = Environment::empty();
+      return Ok(env.template_from_named_str(name, template)?.render(&ctx)?);
+    };
+
+    return Ok(EmailContent {
+      subject: render("subject", &self.subject)?,
+      html: render("html", &self.html)?,
+      text: self
+        .text
+        .as_deref()
+        .map(|text| render("text", text))
+        .transpose()?,
+    });
+  }
+}
+
+/// Template overrides stored in the database. Unset fields fall back to the config and then the
+/// built-in defaults.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct StoredEmailTemplate {
+  pub subject: Option<String>,
+  pub html: Option<String>,
+  pub text: Option<String>,
+}
+
+pub(crate) async fn load_stored_template(
+  conn: &trailbase_sqlite::Connection,
+  name: EmailTemplateName,
+) -> Result<Option<StoredEmailTemplate>, trailbase_sqlite::Error> {
+  const QUERY: &str =
+    formatcp!("SELECT subject, html, text FROM '{EMAIL_TEMPLATES_TABLE}' WHERE name = $1");
+
+  return conn
+    .read_query_value::<StoredEmailTemplate>(QUERY, params!(name.name().to_string()))
+    .await;
+}
+
+/// Resolves the effective template, i.e. database overrides, then config, then defaults.
+pub(crate) fn resolve_template(
+  name: EmailTemplateName,
+  stored: Option<StoredEmailTemplate>,
+  config: &EmailConfig,
+) -> EmailContent {
+  let stored = stored.unwrap_or_default();
+  let config = name.config_template(config);
+  let (default_subject, default_html) = name.defaults();
+
+  return EmailContent {
+    subject: stored
+      .subject
+      .or_else(|| config.and_then(|t| t.subject.clone()))
+      .unwrap_or_else(|| default_subject.to_string()),
+    html: stored
+      .html
+      .or_else(|| config.and_then(|t| t.body.clone()))
+      .unwrap_or_else(|| default_html.to_string()),
+    text: stored.text,
+  };
+}
+
+pub(crate) async fn load_template(
+  conn: &trailbase_sqlite::Connection,
+  config: &EmailConfig,
+  name: EmailTemplateName,
+) -> EmailContent {
+  // NOTE: Fall back rather than fail, sending an email with the config or default template is
+  // preferable over not sending it at all.
+  let stored = load_stored_template(conn, name)
+    .await
+    .unwrap_or_else(|err| {
+      warn!("Failed to load email template '{}': {err}", name.name());
+      None
+    });
+
+  return resolve_template(name, stored, config);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_resolve_template() {
+    let name = EmailTemplateName::Otp;
+    let (default_subject, default_html) = name.defaults();
+
+    let resolved = resolve_template(name, None, &EmailConfig::default());
+    assert_eq!(resolved.subject, default_subject);
+    assert_eq!(resolved.html, default_html);
+    assert_eq!(resolved.text, None);
+
+    let config = EmailConfig {
+      otp_template: Some(EmailTemplate {
+        subject: Some("config subject".to_string()),
+        body: Some("config {{ CODE }}".to_string()),
+      }),
+      ..Default::default()
+    };
+    let resolved = resolve_template(
+      name,
+      Some(StoredEmailTemplate {
+        subject: Some("stored subject".to_string()),
+        html: None,
+        text: Some("code: {{ CODE }}".to_string()),
+      }),
+      &config,
+    );
+    assert_eq!(resolved.subject, "stored subject");
+    assert_eq!(resolved.html, "config {{ CODE }}");
+
+    let rendered = resolved.render(context! { CODE => "1234" }).unwrap();
+    assert_eq!(rendered.html, "config 1234");
+    assert_eq!(rendered.text.as_deref(), Some("code: 1234"));
+  }
+}
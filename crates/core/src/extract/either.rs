@@ -24,8 +24,24 @@ pub enum EitherRejection {
   Multipart(#[from] MultipartRejection),
 }
 
+impl EitherRejection {
+  fn is_payload_too_large(&self) -> bool {
+    let status = match self {
+      Self::Form(rejection) => rejection.status(),
+      Self::Json(rejection) => rejection.status(),
+      Self::Multipart(MultipartRejection::MultipartField(err)) => err.status(),
+      _ => return false,
+    };
+    return status == StatusCode::PAYLOAD_TOO_LARGE;
+  }
+}
+
 impl IntoResponse for EitherRejection {
   fn into_response(self) -> Response {
+    // Surface exceeded body limits, e.g. of streamed bodies w/o content length, as such.
+    if self.is_payload_too_large() {
+      return (StatusCode::PAYLOAD_TOO_LARGE, format!("{self:?}")).into_response();
+    }
     return (StatusCode::BAD_REQUEST, format!("{self:?}")).into_response();
   }
 }
@@ -1,12 +1,14 @@
 use axum::extract::ConnectInfo;
-use axum::http::Request;
+use axum::http::{Extensions, HeaderMap, Request};
 use std::net::IpAddr;
 use tower_governor::GovernorError;
 use tower_governor::key_extractor::KeyExtractor;
 
 pub fn extract_ip<T>(req: &Request<T>) -> Option<std::net::IpAddr> {
-  let headers = req.headers();
+  return extract_ip_from_parts(req.headers(), req.extensions());
+}
 
+pub fn extract_ip_from_parts(headers: &HeaderMap, extensions: &Extensions) -> Option<IpAddr> {
   // NOTE: This code is mimicking axum_client_ip's pre v1 `InsecureClientIp::from`:
   return client_ip::rightmost_x_forwarded_for(headers)
     .or_else(|_| client_ip::x_real_ip(headers))
@@ -16,8 +18,7 @@ pub fn extract_ip<T>(req: &Request<T>) -> Option<std::net::IpAddr> {
     .or_else(|_| client_ip::cloudfront_viewer_address(headers))
     .ok()
     .or_else(|| {
-      req
-        .extensions()
+      extensions
         .get::<ConnectInfo<std::net::SocketAddr>>()
         .map(|ConnectInfo(addr)| addr.ip())
     });
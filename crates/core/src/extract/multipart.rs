@@ -5,8 +5,11 @@ use axum::{
 };
 use serde::de::DeserializeOwned;
 use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
 use thiserror::Error;
-use trailbase_schema::{FileUploadData, FileUploadInput};
+use tokio::io::AsyncWriteExt;
+use trailbase_schema::{FileUploadData, FileUploadInput, SpooledFile};
 
 #[derive(Debug, Error)]
 pub enum Rejection {
@@ -18,6 +21,8 @@ pub enum Rejection {
   MultipartField(#[from] axum::extract::multipart::MultipartError),
   #[error("Failed to deserialize JSON: {0}")]
   Serde(#[from] serde_path_to_error::Error<serde_json::Error>),
+  #[error("Failed to spool file: {0}")]
+  Spool(#[from] std::io::Error),
   #[error("Precondition error: {0}")]
   Precondition(&'static str),
 }
@@ -41,13 +46,12 @@ where
       let name = field.name().map(|s| s.to_string());
       let filename = field.file_name().map(|s| s.to_string());
 
-      let mut buffer: Vec<u8> = vec![];
-      while let Some(chunk) = field.chunk().await? {
-        buffer.extend_from_slice(&chunk);
-      }
+      let data = read_file_field(&mut field).await?;
 
       // Forms submit an empty string for optional file inputs :/.
-      if buffer.is_empty() {
+      if let FileUploadData::Bytes(ref buffer) = data
+        && buffer.is_empty()
+      {
         continue;
       }
 
@@ -55,7 +59,7 @@ where
         name,
         filename,
         content_type,
-        data: FileUploadData(buffer),
+        data,
       });
     } else if let Some(name) = field.name() {
       coerce_and_push_array(&mut data, name.to_string(), json!(field.text().await?));
@@ -71,6 +75,80 @@ where
   ));
 }
 
+/// Reads a file field's contents. Small files are kept in memory, larger ones are spooled to a
+/// temporary file chunk by chunk to keep memory usage bounded regardless of upload size.
+async fn read_file_field(
+  field: &mut axum::extract::multipart::Field<'_>,
+) -> Result<FileUploadData, Rejection> {
+  let mut buffer: Vec<u8> = vec![];
+  let mut spool: Option<Spool> = None;
+
+  while let Some(chunk) = field.chunk().await? {
+    if let Some(ref mut spool) = spool {
+      spool.write(&chunk).await?;
+      continue;
+    }
+
+    buffer.extend_from_slice(&chunk);
+    if buffer.len() > SPOOL_THRESHOLD {
+      let mut s = Spool::new(&buffer[..HEAD_LENGTH]).await?;
+      s.write(&buffer).await?;
+      buffer = vec![];
+      spool = Some(s);
+    }
+  }
+
+  return match spool {
+    Some(spool) => spool.finish().await,
+    None => Ok(FileUploadData::Bytes(buffer)),
+  };
+}
+
+struct Spool {
+  writer: tokio::io::BufWriter<tokio::fs::File>,
+  sha: Sha256,
+  // NOTE: Owning the SpooledFile early makes sure the file is removed on errors.
+  spooled: SpooledFile,
+}
+
+impl Spool {
+  async fn new(head: &[u8]) -> Result<Self, std::io::Error> {
+    let path = std::env::temp_dir().join(format!("trailbase-upload-{}", uuid::Uuid::now_v7()));
+    let file = tokio::fs::File::create_new(&path).await?;
+
+    return Ok(Self {
+      writer: tokio::io::BufWriter::new(file),
+      sha: Sha256::new(),
+      spooled: SpooledFile {
+        path,
+        len: 0,
+        head: head.to_vec(),
+        digest: vec![],
+      },
+    });
+  }
+
+  async fn write(&mut self, chunk: &[u8]) -> Result<(), std::io::Error> {
+    self.writer.write_all(chunk).await?;
+    self.sha.update(chunk);
+    self.spooled.len += chunk.len() as u64;
+    return Ok(());
+  }
+
+  async fn finish(self) -> Result<FileUploadData, Rejection> {
+    let Self {
+      mut writer,
+      sha,
+      mut spooled,
+    } = self;
+
+    writer.shutdown().await?;
+    spooled.digest = sha.finalize().to_vec();
+
+    return Ok(FileUploadData::Spooled(Arc::new(spooled)));
+  }
+}
+
 /// Adds ([key], [value]) to [map], first as value and subsequently as an array, i.e.
 ///   `map[key]=[v0, v1, ...]`.
 fn coerce_and_push_array(
@@ -92,6 +170,11 @@ fn coerce_and_push_array(
   };
 }
 
+/// Files larger than this are spooled to disk rather than kept in memory.
+const SPOOL_THRESHOLD: usize = 1024 * 1024;
+/// Number of leading bytes of spooled files kept in memory, e.g. to infer mime types.
+const HEAD_LENGTH: usize = 8 * 1024;
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -149,15 +232,52 @@ mod test {
           name: Some("file1".to_string()),
           filename: Some("a.txt".to_string()),
           content_type: Some("text/plain".to_string()),
-          data: FileUploadData(Vec::from("Some text".as_bytes())),
+          data: FileUploadData::Bytes(Vec::from("Some text".as_bytes())),
         }),
         (FileUploadInput {
           name: Some("file2".to_string()),
           filename: Some("a.html".to_string()),
           content_type: Some("text/html".to_string()),
-          data: FileUploadData(Vec::from("<b>Some html</b>".as_bytes())),
+          data: FileUploadData::Bytes(Vec::from("<b>Some html</b>".as_bytes())),
         }),
       ]
     );
   }
+
+  #[tokio::test]
+  async fn parse_multipart_spools_large_files() {
+    let contents = "x".repeat(2 * SPOOL_THRESHOLD);
+    let body = format!(
+      "--fieldB\r\n\
+       Content-Disposition: form-data; name=\"file\"; filename=\"large.txt\"\r\n\
+       Content-Type: text/plain\r\n\r\n\
+       {contents}\r\n\
+       --fieldB--\r\n"
+    );
+
+    let req = axum::http::Request::builder()
+      .header("content-type", "multipart/form-data; boundary=fieldB")
+      .header("content-length", body.len())
+      .body(axum::body::Body::from(body))
+      .unwrap();
+
+    let (_value, mut files) = super::parse_multipart::<serde_json::Value>(req)
+      .await
+      .unwrap();
+    assert_eq!(1, files.len());
+
+    let FileUploadData::Spooled(spooled) = files.remove(0).data else {
+      panic!("expected spooled file");
+    };
+    assert_eq!(contents.len() as u64, spooled.len);
+    assert_eq!(HEAD_LENGTH, spooled.head.len());
+    assert_eq!(
+      contents.as_bytes(),
+      tokio::fs::read(&spooled.path).await.unwrap()
+    );
+
+    let path = spooled.path.clone();
+    drop(spooled);
+    assert!(!path.exists());
+  }
 }
@@ -0,0 +1,518 @@
+use axum::Router;
+use axum::extract::{Path, Query, RawQuery, State};
+use http_body_util::BodyExt;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status};
+
+use crate::app_state::AppState;
+use crate::auth::password::check_user_password;
+use crate::auth::tokens::{SessionMetadata, mint_new_tokens, reauth_with_refresh_token};
+use crate::auth::util::{user_by_email, validate_and_normalize_email_address};
+use crate::auth::{AuthError, AuthTokenClaims, User};
+use crate::extract::Either;
+use crate::records::RecordError;
+use crate::records::create_record::{
+  CreateRecordQuery, CreateRecordResponse, create_record_handler,
+};
+use crate::records::delete_record::delete_record_handler;
+use crate::records::list_records::{ListOrGeoJSONResponse, ListRecordsQuery, list_records_handler};
+use crate::records::read_record::{ReadRecordQuery, read_record_handler};
+use crate::records::update_record::{UpdateRecordQuery, update_record_handler};
+
+pub mod proto {
+  include!(concat!(env!("OUT_DIR"), "/records.rs"));
+}
+
+mod services {
+  include!(concat!(env!("OUT_DIR"), "/records.RecordService.rs"));
+  include!(concat!(env!("OUT_DIR"), "/records.AuthService.rs"));
+}
+
+use services::auth_service_server::{AuthService, AuthServiceServer};
+use services::record_service_server::{RecordService, RecordServiceServer};
+
+/// Builds a router serving the Record and Auth gRPC services.
+pub(crate) fn router(state: AppState) -> Router {
+  return tonic::service::Routes::new(RecordServiceServer::new(RecordGrpcService {
+    state: state.clone(),
+  }))
+  .add_service(AuthServiceServer::new(AuthGrpcService { state }))
+  .into_axum_router();
+}
+
+struct RecordGrpcService {
+  state: AppState,
+}
+
+#[tonic::async_trait]
+impl RecordService for RecordGrpcService {
+  async fn create_record(
+    &self,
+    request: Request<proto::CreateRecordRequest>,
+  ) -> Result<Response<proto::CreateRecordResponse>, Status> {
+    let user = user_from_metadata(&self.state, request.metadata())?;
+    let request = request.into_inner();
+
+    let response = create_record_handler(
+      State(self.state.clone()),
+      Path(request.api_name.unwrap_or_default()),
+      Query(CreateRecordQuery::default()),
+      user,
+      None,
+      Either::Json(parse_json(request.record_json.as_deref())?),
+    )
+    .await
+    .map_err(record_error_to_status)?;
+
+    let body = response
+      .into_body()
+      .collect()
+      .await
+      .map_err(|err| Status::internal(err.to_string()))?
+      .to_bytes();
+    let CreateRecordResponse { ids } =
+      serde_json::from_slice(&body).map_err(|err| Status::internal(err.to_string()))?;
+
+    return Ok(Response::new(proto::CreateRecordResponse { ids }));
+  }
+
+  async fn read_record(
+    &self,
+    request: Request<proto::ReadRecordRequest>,
+  ) -> Result<Response<proto::ReadRecordResponse>, Status> {
+    let user = user_from_metadata(&self.state, request.metadata())?;
+    let request = request.into_inner();
+
+    let axum::Json(record) = read_record_handler(
+      State(self.state.clone()),
+      Path((
+        request.api_name.unwrap_or_default(),
+        request.record_id.unwrap_or_default(),
+      )),
+      Query(ReadRecordQuery {
+        select: request.select,
+        ..Default::default()
+      }),
+      user,
+    )
+    .await
+    .map_err(record_error_to_status)?;
+
+    return Ok(Response::new(proto::ReadRecordResponse {
+      record_json: Some(record.to_string()),
+    }));
+  }
+
+  async fn update_record(
+    &self,
+    request: Request<proto::UpdateRecordRequest>,
+  ) -> Result<Response<proto::UpdateRecordResponse>, Status> {
+    let user = user_from_metadata(&self.state, request.metadata())?;
+    let request = request.into_inner();
+
+    let serde_json::Value::Object(record) = parse_json(request.record_json.as_deref())? else {
+      return Err(Status::invalid_argument("Expected single record"));
+    };
+
+    update_record_handler(
+      State(self.state.clone()),
+      Path((
+        request.api_name.unwrap_or_default(),
+        request.record_id.unwrap_or_default(),
+      )),
+      Query(UpdateRecordQuery::default()),
+      user,
+      Either::Json(record),
+    )
+    .await
+    .map_err(record_error_to_status)?;
+
+    return Ok(Response::new(proto::UpdateRecordResponse {}));
+  }
+
+  async fn delete_record(
+    &self,
+    request: Request<proto::DeleteRecordRequest>,
+  ) -> Result<Response<proto::DeleteRecordResponse>, Status> {
+    let user = user_from_metadata(&self.state, request.metadata())?;
+    let request = request.into_inner();
+
+    delete_record_handler(
+      State(self.state.clone()),
+      Path((
+        request.api_name.unwrap_or_default(),
+        request.record_id.unwrap_or_default(),
+      )),
+      user,
+    )
+    .await
+    .map_err(record_error_to_status)?;
+
+    return Ok(Response::new(proto::DeleteRecordResponse {}));
+  }
+
+  async fn list_records(
+    &self,
+    request: Request<proto::ListRecordsRequest>,
+  ) -> Result<Response<proto::ListRecordsResponse>, Status> {
+    let user = user_from_metadata(&self.state, request.metadata())?;
+    let request = request.into_inner();
+
+    let axum::Json(response) = list_records_handler(
+      State(self.state.clone()),
+      Path(request.api_name.unwrap_or_default()),
+      Query(ListRecordsQuery::default()),
+      RawQuery(request.query),
+      user,
+    )
+    .await
+    .map_err(record_error_to_status)?;
+
+    let list = match response {
+      ListOrGeoJSONResponse::List(list) => list,
+      #[cfg(any(feature = "geos", feature = "geos-static"))]
+      ListOrGeoJSONResponse::GeoJSON(_) => {
+        return Err(Status::unimplemented("GeoJSON"));
+      }
+    };
+
+    return Ok(Response::new(proto::ListRecordsResponse {
+      records_json: list.records.iter().map(|r| r.to_string()).collect(),
+      cursor: list.cursor,
+      total_count: list.total_count.map(|c| c as i64),
+    }));
+  }
+}
+
+struct AuthGrpcService {
+  state: AppState,
+}
+
+#[tonic::async_trait]
+impl AuthService for AuthGrpcService {
+  async fn login(
+    &self,
+    request: Request<proto::LoginRequest>,
+  ) -> Result<Response<proto::LoginResponse>, Status> {
+    let session = SessionMetadata {
+      user_agent: request
+        .metadata()
+        .get("user-agent")
+        .and_then(|ua| ua.to_str().ok())
+        .map(|ua| ua.to_string()),
+      client_ip: request.remote_addr().map(|addr| addr.ip().to_string()),
+    };
+    let request = request.into_inner();
+    let state = &self.state;
+
+    let normalized_email = validate_and_normalize_email_address(&request.email.unwrap_or_default())
+      .map_err(auth_error_to_status)?;
+
+    let db_user = user_by_email(state, &normalized_email).await.map_err(|_| {
+      // Don't leak if user wasn't found or password was wrong.
+      return Status::unauthenticated("Unauthorized");
+    })?;
+
+    // Check password and lock out repeated failures.
+    check_user_password(
+      &db_user,
+      &request.password.unwrap_or_default(),
+      session.client_ip.as_deref(),
+      state.auth_options().lockout_options(),
+      state.demo_mode(),
+    )
+    .map_err(auth_error_to_status)?;
+
+    // Multi-factor auth isn't supported via gRPC (yet).
+    if db_user.totp_secret.is_some() {
+      return Err(Status::failed_precondition("MFA required"));
+    }
+
+    let (auth_token_ttl, refresh_token_ttl) = state.access_config(|c| c.auth.token_ttls());
+    let tokens = mint_new_tokens(
+      state.user_conn(),
+      state.session_conn(),
+      &db_user,
+      &session,
+      &auth_token_ttl,
+      &refresh_token_ttl,
+    )
+    .await
+    .map_err(auth_error_to_status)?;
+
+    let auth_token = state
+      .jwt()
+      .encode(&tokens.auth_token_claims)
+      .map_err(|err| Status::internal(err.to_string()))?;
+
+    return Ok(Response::new(proto::LoginResponse {
+      auth_token: Some(auth_token),
+      refresh_token: Some(tokens.refresh_token),
+      csrf_token: Some(tokens.auth_token_claims.csrf_token),
+    }));
+  }
+
+  async fn refresh(
+    &self,
+    request: Request<proto::RefreshRequest>,
+  ) -> Result<Response<proto::RefreshResponse>, Status> {
+    let request = request.into_inner();
+
+    let (claims, _ttl) =
+      reauth_with_refresh_token(&self.state, request.refresh_token.unwrap_or_default())
+        .await
+        .map_err(auth_error_to_status)?;
+
+    let auth_token = self
+      .state
+      .jwt()
+      .encode(&claims)
+      .map_err(|err| Status::internal(err.to_string()))?;
+
+    return Ok(Response::new(proto::RefreshResponse {
+      auth_token: Some(auth_token),
+      csrf_token: Some(claims.csrf_token),
+    }));
+  }
+}
+
+/// Extracts the user from an "authorization: Bearer <auth_token>" metadata entry.
+///
+/// NOTE: Unlike the HTTP APIs, there's no cookie fallback.
+fn user_from_metadata(state: &AppState, metadata: &MetadataMap) -> Result<Option<User>, Status> {
+  let Some(value) = metadata.get("authorization") else {
+    return Ok(None);
+  };
+
+  let auth_token = value
+    .to_str()
+    .ok()
+    .and_then(|v| v.strip_prefix("Bearer "))
+    .ok_or_else(|| Status::unauthenticated("Unauthorized"))?;
+
+  let claims = AuthTokenClaims::from_auth_token(state.jwt(), auth_token)
+    .map_err(|_| Status::unauthenticated("Unauthorized"))?;
+
+  return User::from_token_claims(claims)
+    .map(Some)
+    .map_err(auth_error_to_status);
+}
+
+fn parse_json(json: Option<&str>) -> Result<serde_json::Value, Status> {
+  return serde_json::from_str(json.unwrap_or_default())
+    .map_err(|_| Status::invalid_argument("Invalid JSON"));
+}
+
+fn record_error_to_status(err: RecordError) -> Status {
+  return match err {
+    RecordError::ApiNotFound => Status::not_found("Api Not Found"),
+    RecordError::ApiRequiresTable => Status::failed_precondition("Api Requires Table"),
+    RecordError::RecordNotFound => Status::not_found("Record Not Found"),
+    RecordError::Forbidden => Status::permission_denied("Forbidden"),
+    RecordError::BadRequest(msg) => Status::invalid_argument(msg),
+    RecordError::Conflict(msg) => Status::aborted(msg),
+    RecordError::Internal(err) if cfg!(debug_assertions) => Status::internal(err.to_string()),
+    RecordError::Internal(_err) => Status::internal("Internal"),
+  };
+}
+
+fn auth_error_to_status(err: AuthError) -> Status {
+  return match err {
+    AuthError::Unauthorized => Status::unauthenticated("Unauthorized"),
+    AuthError::Forbidden => Status::permission_denied("Forbidden"),
+    AuthError::Conflict => Status::already_exists("Conflict"),
+    AuthError::NotFound | AuthError::OAuthProviderNotFound => Status::not_found("Not Found"),
+    AuthError::MethodNotAllowed => Status::unimplemented("Method Not Allowed"),
+    AuthError::BadRequest(msg) => Status::invalid_argument(msg),
+    AuthError::TooManyRequests => Status::resource_exhausted("Too Many Requests"),
+    AuthError::FailedDependency(_err) => Status::unavailable("Failed Dependency"),
+    AuthError::Internal(err) if cfg!(debug_assertions) => Status::internal(err.to_string()),
+    AuthError::Internal(_err) => Status::internal("Internal"),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::admin::user::create_user_for_test;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::test_utils::*;
+
+  #[tokio::test]
+  async fn test_grpc_record_service() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE message (
+            id      INTEGER PRIMARY KEY,
+            text    TEXT NOT NULL
+          ) {strict};
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("message".to_string()),
+        acl_authenticated: [
+          PermissionFlag::Create as i32,
+          PermissionFlag::Read as i32,
+          PermissionFlag::Update as i32,
+          PermissionFlag::Delete as i32,
+        ]
+        .into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let password = "Secret!1!!";
+    create_user_for_test(&state, "user@test.com", password)
+      .await
+      .unwrap();
+
+    let auth = AuthGrpcService {
+      state: state.clone(),
+    };
+    assert_eq!(
+      tonic::Code::Unauthenticated,
+      auth
+        .login(Request::new(proto::LoginRequest {
+          email: Some("user@test.com".to_string()),
+          password: Some("wrong".to_string()),
+        }))
+        .await
+        .unwrap_err()
+        .code()
+    );
+    let tokens = auth
+      .login(Request::new(proto::LoginRequest {
+        email: Some("user@test.com".to_string()),
+        password: Some(password.to_string()),
+      }))
+      .await
+      .unwrap()
+      .into_inner();
+
+    let records = RecordGrpcService {
+      state: state.clone(),
+    };
+    let auth_token = tokens.auth_token.unwrap();
+    fn with_token<T>(auth_token: &str, message: T) -> Request<T> {
+      let mut request = Request::new(message);
+      request.metadata_mut().insert(
+        "authorization",
+        format!("Bearer {auth_token}").parse().unwrap(),
+      );
+      return request;
+    }
+
+    // Anonymous users don't have access.
+    assert_eq!(
+      tonic::Code::PermissionDenied,
+      records
+        .create_record(Request::new(proto::CreateRecordRequest {
+          api_name: Some("api".to_string()),
+          record_json: Some(r#"{"id": 1, "text": "first"}"#.to_string()),
+        }))
+        .await
+        .unwrap_err()
+        .code()
+    );
+
+    let ids = records
+      .create_record(with_token(
+        &auth_token,
+        proto::CreateRecordRequest {
+          api_name: Some("api".to_string()),
+          record_json: Some(r#"{"id": 1, "text": "first"}"#.to_string()),
+        },
+      ))
+      .await
+      .unwrap()
+      .into_inner()
+      .ids;
+    assert_eq!(vec!["1".to_string()], ids);
+
+    records
+      .update_record(with_token(
+        &auth_token,
+        proto::UpdateRecordRequest {
+          api_name: Some("api".to_string()),
+          record_id: Some("1".to_string()),
+          record_json: Some(r#"{"text": "updated"}"#.to_string()),
+        },
+      ))
+      .await
+      .unwrap();
+
+    let record = records
+      .read_record(with_token(
+        &auth_token,
+        proto::ReadRecordRequest {
+          api_name: Some("api".to_string()),
+          record_id: Some("1".to_string()),
+          select: None,
+        },
+      ))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(
+      serde_json::json!({"id": 1, "text": "updated"}),
+      serde_json::from_str::<serde_json::Value>(&record.record_json.unwrap()).unwrap()
+    );
+
+    let list = records
+      .list_records(with_token(
+        &auth_token,
+        proto::ListRecordsRequest {
+          api_name: Some("api".to_string()),
+          query: Some("count=true".to_string()),
+        },
+      ))
+      .await
+      .unwrap()
+      .into_inner();
+    assert_eq!(Some(1), list.total_count);
+    assert_eq!(1, list.records_json.len());
+
+    records
+      .delete_record(with_token(
+        &auth_token,
+        proto::DeleteRecordRequest {
+          api_name: Some("api".to_string()),
+          record_id: Some("1".to_string()),
+        },
+      ))
+      .await
+      .unwrap();
+
+    assert_eq!(
+      tonic::Code::NotFound,
+      records
+        .read_record(with_token(
+          &auth_token,
+          proto::ReadRecordRequest {
+            api_name: Some("api".to_string()),
+            record_id: Some("1".to_string()),
+            select: None,
+          }
+        ))
+        .await
+        .unwrap_err()
+        .code()
+    );
+  }
+}
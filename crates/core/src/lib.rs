@@ -5,8 +5,10 @@
 pub mod app_state;
 pub mod config;
 pub mod constants;
+pub mod contract;
 pub mod logging;
 pub mod metadata;
+pub mod openapi;
 pub mod records;
 pub mod util;
 
@@ -14,17 +16,31 @@ pub mod util;
 pub mod test_utils;
 
 mod admin;
+mod audit;
 mod auth;
+mod backup;
+mod cdc;
+mod config_watcher;
 mod connection;
 mod data_dir;
 mod email;
 mod encryption;
 mod extract;
+#[cfg(feature = "grpc")]
+mod grpc;
 mod listing;
+mod metrics;
 mod migrations;
+mod notifications;
+mod proxy;
+mod queue;
+mod rate_limit;
+mod replication;
 mod scheduler;
 mod schema_metadata;
+mod seeds;
 mod server;
+mod tenants;
 mod transaction_recorder;
 
 #[cfg(feature = "wasm")]
@@ -57,10 +73,28 @@ mod wasm {
 
   pub(crate) struct Runtime;
 
+  #[derive(Default)]
+  pub(crate) struct RuntimeMetrics {
+    pub in_flight: usize,
+    pub calls: u64,
+    pub errors: u64,
+    pub deadlines_exceeded: u64,
+  }
+
   impl Runtime {
     pub fn component_path(&self) -> std::path::PathBuf {
       return std::path::PathBuf::default();
     }
+
+    pub fn metrics(&self) -> RuntimeMetrics {
+      return RuntimeMetrics::default();
+    }
+
+    pub fn enabled(&self) -> bool {
+      return true;
+    }
+
+    pub fn set_enabled(&self, _enabled: bool) {}
   }
 
   pub(crate) type WasmRuntimeBuilder =
@@ -72,6 +106,7 @@ mod wasm {
     _rt: Option<tokio::runtime::Handle>,
     _runtime_root_fs: Option<std::path::PathBuf>,
     _shared_kv_store: Option<KvStore>,
+    _config: &trailbase_reactive::Reactive<crate::config::proto::Config>,
     _dev: bool,
   ) -> Result<WasmRuntimeBuilder, AnyError> {
     return Ok(Box::new(|| Ok(vec![])));
@@ -106,7 +141,10 @@ mod test;
 pub use app_state::AppState;
 pub use auth::User;
 pub use data_dir::DataDir;
-pub use server::{InitError, Server, ServerOptions};
+pub use server::{
+  AcmeOptions, InitError, OtelOptions, OtelProtocol, ReadReplicaOptions, Server, ServerOptions,
+};
+pub use trailbase_extension::SqliteFunctionRegistrar;
 
 use prost_reflect::DescriptorPool;
 use std::sync::LazyLock;
@@ -118,37 +156,29 @@ static DESCRIPTOR_POOL: LazyLock<DescriptorPool> = LazyLock::new(|| {
   DescriptorPool::decode(FILE_DESCRIPTOR_SET).expect("Failed to load file descriptor set")
 });
 
-pub mod openapi {
-  use utoipa::OpenApi;
-
-  #[derive(OpenApi)]
-  #[openapi(
-        info(
-            title = "TrailBase",
-            description = "TrailBase APIs",
-        ),
-        nest(
-            (path = "/api/auth/v1", api = crate::auth::AuthApi),
-            (path = "/api/records/v1", api = crate::records::RecordOpenApi),
-        ),
-        tags(),
-    )]
-  pub struct Doc;
-}
-
 pub mod api {
+  pub use crate::admin::table::{
+    ApplySchemaRequest, ApplySchemaResponse, RollbackMigrationsRequest, RollbackMigrationsResponse,
+    apply_schema, rollback_migrations,
+  };
   pub use crate::admin::user::{CreateUserRequest, create_user_handler};
+  pub use crate::auth::jwt::{JwtAlgorithm, TokenVerifier};
   pub use crate::auth::{AuthTokenClaims, JwtHelper, cli};
+  pub use crate::backup::{BackupError, RestorePoint, list_backups, restore_backup};
   pub use crate::connection::Connection;
   pub use crate::email::{Email, EmailError};
   pub use crate::migrations::new_unique_migration_filename;
+  pub use crate::queue::{EnqueueOptions, TaskError, TaskHandler, TaskQueue};
   pub use crate::records::json_schema::build_api_json_schema;
+  pub use crate::replication::{
+    ReplicaGeneration, ReplicationError, RestoredReplica, list_replica_generations, restore_replica,
+  };
   pub use crate::schema_metadata::ConnectionMetadata;
   pub use crate::server::{
     InitArgs,
     init_app_state,
-    // TODO: Remove currently exposed for custom binary example. Should probably inject custom
-    // routes into server rather than pick it apart (see examples/custom-binary/src/main.rs).
+    // NOTE: Prefer registering custom routes via `ServerOptions::custom_routers` over picking
+    // the server apart. Kept for backwards compatibility.
     serve_impl as serve,
   };
 
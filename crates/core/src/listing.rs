@@ -13,6 +13,10 @@ pub enum WhereClauseError {
   NotImplemented(String),
   #[error("Unrecognized param error: {0}")]
   UnrecognizedParam(String),
+  #[error("Unknown column: {0}")]
+  UnknownColumn(String),
+  #[error("Unsupported operator: {0}")]
+  UnsupportedOperator(String),
 }
 
 #[derive(Debug, Clone)]
@@ -44,18 +48,24 @@ pub(crate) fn build_filter_where_clause(
       )));
     }
 
+    let Some(meta) = find_column(column_metadata, column_name) else {
+      return Err(WhereClauseError::UnknownColumn(column_name.clone()));
+    };
+
+    if !crate::records::filter::is_supported_op(meta, column_op_value.op) {
+      return Err(WhereClauseError::UnsupportedOperator(format!(
+        "{} on column {column_name} of type {:?}",
+        column_op_value.op.as_query(),
+        meta.column.data_type
+      )));
+    }
+
     return Ok(());
   })?;
 
   let (sql, params) = filter_params.into_sql(Some(table_name), |column_op_value| {
-    let Some(meta) = column_metadata
-      .iter()
-      .find(|meta| meta.column.name == column_op_value.column)
-    else {
-      return Err(WhereClauseError::UnrecognizedParam(format!(
-        "Filter on unknown column: {}",
-        column_op_value.column
-      )));
+    let Some(meta) = find_column(column_metadata, &column_op_value.column) else {
+      return Err(WhereClauseError::UnknownColumn(column_op_value.column));
     };
 
     return crate::records::filter::qs_value_to_sql_with_constraints(
@@ -74,6 +84,14 @@ pub(crate) fn build_filter_where_clause(
   });
 }
 
+#[inline]
+fn find_column<'a>(
+  column_metadata: &'a [ColumnMetadata],
+  name: &str,
+) -> Option<&'a ColumnMetadata> {
+  return column_metadata.iter().find(|meta| meta.column.name == name);
+}
+
 pub fn limit_or_default(
   limit: Option<usize>,
   hard_limit: Option<usize>,
@@ -1,12 +1,13 @@
 use axum::body::Body;
+use axum::extract::MatchedPath;
 use axum::http::{Request, header};
 use axum::response::Response;
 use const_format::formatcp;
 use flume::TrySendError;
 use serde::Serialize;
 use serde_json::json;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::AsyncWriteExt;
 use tracing::Level;
 use tracing::field::Field;
 use tracing::span::{Attributes, Id, Record, Span};
@@ -15,9 +16,17 @@ use trailbase_sqlite::SyncConnectionTrait;
 use uuid::Uuid;
 
 use crate::AppState;
+use crate::config::proto::RequestLogsConfig;
 use crate::extract::ip::extract_ip;
 use crate::util::get_header;
 
+mod sinks;
+mod slow_queries;
+
+use sinks::JsonStdoutSink;
+pub(crate) use sinks::{LogSink, build_log_sinks};
+pub(crate) use slow_queries::{install_slow_query_recorder, spawn_slow_query_writer};
+
 // NOTE: Tracing is quite sweet but also utterly decoupled. There are several moving parts.
 //
 //  * In `server/mod.rs` we install some tower/axum middleware: `tower_http::trace::TraceLayer` to
@@ -27,6 +36,8 @@ use crate::util::get_header;
 //  * Independently, we install the `SqliteLogLayer` as a tracing subscriber listening for above
 //    events, building request-response log entries and ultimately sending them to a writer task.
 //  * The writer task receives the request-response log entries writes them to the logs database.
+//  * Additionally, entries are handed to `LogSink`s, e.g. stdout or configured syslog and Loki
+//    sinks, see `logging/sinks.rs`.
 //  * Lastly, there's also a period task to wipe expired logs past their retention.
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
@@ -163,12 +174,13 @@ pub(super) fn sqlite_logger_make_span(request: &Request<Body>) -> Span {
   let headers = request.headers();
 
   // NOTE: "%" means print using fmt::Display, and "?" means fmt::Debug.
-  return tracing::span!(
+  let span = tracing::span!(
       target: EVENT_TARGET,
       LEVEL,
       SPAN_NAME,
       method = %request.method(),
       uri = %request.uri(),
+      route = request.extensions().get::<MatchedPath>().map(|p| p.as_str()),
       version = ?request.version(),
       host = get_header(headers, "host"),
       client_ip = extract_ip(request).map(|ip| ip.to_string()),
@@ -180,6 +192,18 @@ pub(super) fn sqlite_logger_make_span(request: &Request<Body>) -> Span {
       status = tracing::field::Empty,
       length = tracing::field::Empty,
   );
+
+  // Continue traces of upstream services, i.e. make the span a child of an incoming
+  // `traceparent`.
+  #[cfg(feature = "otel")]
+  {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let _ =
+      span.set_parent(tracing_opentelemetry_instrumentation_sdk::http::extract_context(headers));
+  }
+
+  return span;
 }
 
 pub(super) fn sqlite_logger_on_request(_req: &Request<Body>, _span: &Span) {
@@ -209,21 +233,70 @@ pub(super) fn sqlite_logger_on_response(response: &Response<Body>, latency: Dura
   // module_path, ?params },   &tracing::valueset! { metadata.fields(), EVENT_TARGET, EVENT_NAME
   // }, );
 
+  // Extra fields attached by handlers, e.g. error details, serialized to get them past tracing's
+  // static fields.
+  let fields = response
+    .extensions()
+    .get::<LogFields>()
+    .and_then(|fields| serde_json::to_string(&fields.0).ok());
+
   // Log the event that gets picked up by `SqilteLogLayer` and written out.
   tracing::event!(
     name: EVENT_NAME,
     target: EVENT_TARGET,
     parent: span,
     LEVEL,
-    {}
+    { fields = fields.as_deref() }
   );
 }
 
+/// Extra fields of a request log entry, which are persisted in the `data` column if enabled via
+/// the `server.request_logs` config. Can be attached to responses as an extension, e.g. by custom
+/// handlers.
+#[derive(Clone, Debug, Default)]
+pub struct LogFields(pub serde_json::Map<String, serde_json::Value>);
+
+impl LogFields {
+  pub fn error(err: impl std::fmt::Display) -> Self {
+    return Self(serde_json::Map::from_iter([(
+      "error".to_string(),
+      serde_json::Value::String(err.to_string()),
+    )]));
+  }
+}
+
+/// Attaches error details to (5XX) error responses, which may not expose the error to clients.
+pub(crate) fn with_error_fields(mut response: Response, err: impl std::fmt::Display) -> Response {
+  response.extensions_mut().insert(LogFields::error(err));
+  return response;
+}
+
+enum WriterMessage {
+  Log(LogFieldStorage),
+  /// Acknowledged once all previously sent logs have been written.
+  Flush(tokio::sync::oneshot::Sender<()>),
+}
+
+/// Handle for flushing logs buffered by the `SqliteLogLayer`'s writer, e.g. before shutting down.
+#[derive(Clone)]
+pub(crate) struct LogsFlusher(flume::Sender<WriterMessage>);
+
+impl LogsFlusher {
+  /// Waits until all logs sent before the call have been written to the logs DB.
+  pub(crate) async fn flush(&self) {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    if self.0.send(WriterMessage::Flush(sender)).is_ok() {
+      let _ = receiver.await;
+    }
+  }
+}
+
 pub struct SqliteLogLayer {
-  sender: flume::Sender<LogFieldStorage>,
+  sender: flume::Sender<WriterMessage>,
+  state: AppState,
 
-  #[allow(unused)]
-  json_stdout: bool,
+  /// Sinks enabled at startup, e.g. JSON stdout, as opposed to the ones configured in the config.
+  sinks: Vec<Arc<dyn LogSink>>,
 }
 
 impl SqliteLogLayer {
@@ -231,10 +304,11 @@ impl SqliteLogLayer {
     let conn = state.logs_conn().clone();
 
     // NOTE: If anything here becomes a performance bottleneck we could switch to a dedicated
-    // lock-free single thread writer. We also may not want to support sinks other than SQLite.
+    // lock-free single thread writer.
     // TODO: We could consider a bounded receiver to create back-pressure?
     let (sender, receiver) = flume::unbounded();
 
+    let writer_state = state.clone();
     tokio::spawn(async move {
       #[inline]
       fn new_buffer() -> Vec<LogFieldStorage> {
@@ -242,14 +316,21 @@ impl SqliteLogLayer {
       }
 
       let mut buffer = new_buffer();
+      let mut flushes: Vec<tokio::sync::oneshot::Sender<()>> = vec![];
       while let Ok(first) = receiver.recv_async().await {
-        buffer.push(first);
-        buffer.extend(receiver.try_iter());
+        for message in std::iter::once(first).chain(receiver.try_iter()) {
+          match message {
+            WriterMessage::Log(log) => buffer.push(log),
+            WriterMessage::Flush(ack) => flushes.push(ack),
+          }
+        }
         let len = buffer.len();
 
+        let options =
+          writer_state.access_config(|c| LogDataOptions::new(c.server.request_logs.as_ref()));
         buffer = conn
           .call_writer(move |mut conn| -> Result<_, trailbase_sqlite::Error> {
-            insert_logs(&mut conn, &mut buffer)?;
+            insert_logs(&mut conn, &mut buffer, &options)?;
             return Ok(buffer);
           })
           .await
@@ -261,6 +342,13 @@ impl SqliteLogLayer {
         debug_assert!(buffer.is_empty());
         debug_assert!(buffer.capacity() >= 1024);
 
+        if !flushes.is_empty() {
+          for ack in flushes.drain(..) {
+            let _ = ack.send(());
+          }
+          continue;
+        }
+
         // Didn't write so many logs this go-around, let's take a nap and batch some more.
         if len < 256 {
           tokio::time::sleep(tokio::time::Duration::from_micros(500)).await;
@@ -270,9 +358,17 @@ impl SqliteLogLayer {
       log::error!("Logs writer shut down.");
     });
 
+    let mut sinks: Vec<Arc<dyn LogSink>> = vec![];
+    if json_stdout {
+      sinks.push(Arc::new(JsonStdoutSink));
+    }
+
+    state.set_logs_flusher(LogsFlusher(sender.clone()));
+
     return SqliteLogLayer {
       sender,
-      json_stdout,
+      state: state.clone(),
+      sinks,
     };
   }
 
@@ -280,18 +376,15 @@ impl SqliteLogLayer {
   // then writes to Sqlite.
   #[inline]
   fn write_log(&self, storage: LogFieldStorage) {
-    if self.json_stdout {
+    let configured_sinks = self.state.log_sinks();
+    if !self.sinks.is_empty() || !configured_sinks.is_empty() {
       let json: JsonLog = (&storage).into();
-      tokio::spawn(async move {
-        let mut buf: Vec<u8> = Vec::with_capacity(480);
-        if serde_json::to_writer(&mut buf, &json).is_ok() {
-          buf.push(b'\n');
-          let _ = tokio::io::stdout().write_all(&buf).await;
-        }
-      });
+      for sink in self.sinks.iter().chain(configured_sinks.iter()) {
+        sink.write(&json);
+      }
     }
 
-    match self.sender.try_send(storage) {
+    match self.sender.try_send(WriterMessage::Log(storage)) {
       Ok(()) => {}
       Err(TrySendError::Full(_)) => {
         log::warn!("Back-pressure. Dropping log.");
@@ -303,26 +396,90 @@ impl SqliteLogLayer {
   }
 }
 
+/// Which extra data is persisted in the logs' `data` column.
+struct LogDataOptions {
+  capture_fields: bool,
+  /// Lower-case keys of redacted fields.
+  redacted_keys: Vec<String>,
+}
+
+impl LogDataOptions {
+  fn new(config: Option<&RequestLogsConfig>) -> Self {
+    return Self {
+      capture_fields: config.and_then(|c| c.capture_fields).unwrap_or(false),
+      redacted_keys: DEFAULT_REDACTED_KEYS
+        .iter()
+        .map(|key| key.to_string())
+        .chain(
+          config
+            .into_iter()
+            .flat_map(|c| c.redacted_keys.iter().map(|key| key.to_lowercase())),
+        )
+        .collect(),
+    };
+  }
+}
+
+const DEFAULT_REDACTED_KEYS: &[&str] = &["authorization", "cookie", "password", "secret", "token"];
+
+/// Builds the JSON stored in the `data` column: the matched route and, if enabled, extra fields.
+fn build_log_data(log: &LogFieldStorage, options: &LogDataOptions) -> Option<String> {
+  let mut data = serde_json::Map::new();
+  if options.capture_fields {
+    for (key, value) in &log.fields {
+      data.insert(key.clone(), redact(key, value, &options.redacted_keys));
+    }
+  }
+  if let Some(ref route) = log.route {
+    data.insert("route".to_string(), route.clone().into());
+  }
+
+  if data.is_empty() {
+    return None;
+  }
+  return Some(serde_json::Value::Object(data).to_string());
+}
+
+fn redact(key: &str, value: &serde_json::Value, redacted_keys: &[String]) -> serde_json::Value {
+  if redacted_keys.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+    return json!("<redacted>");
+  }
+
+  return match value {
+    serde_json::Value::Object(map) => serde_json::Value::Object(
+      map
+        .iter()
+        .map(|(k, v)| (k.clone(), redact(k, v, redacted_keys)))
+        .collect(),
+    ),
+    serde_json::Value::Array(values) => serde_json::Value::Array(
+      values
+        .iter()
+        .map(|v| redact("", v, redacted_keys))
+        .collect(),
+    ),
+    value => value.clone(),
+  };
+}
+
 fn insert_logs(
   conn: &mut trailbase_sqlite::SyncConnection,
   buffer: &mut Vec<LogFieldStorage>,
+  options: &LogDataOptions,
 ) -> Result<(), trailbase_sqlite::Error> {
   use trailbase_sqlite::Value;
 
   const QUERY: &str = formatcp!(
     "\
         INSERT INTO \
-          _logs (created, status, method, url, latency, client_ip, referer, user_agent, user_id) \
+          _logs (created, status, method, url, latency, client_ip, referer, user_agent, user_id, data) \
         VALUES \
-          ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+          ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
       "
   );
 
   for log in buffer.drain(..) {
-    #[cfg(debug_assertions)]
-    if !log.fields.is_empty() {
-      log::info!("Dangling log fields: {:?}", log.fields);
-    }
+    let data = build_log_data(&log, options);
 
     conn.execute(
       QUERY,
@@ -345,7 +502,7 @@ fn insert_logs(
         } else {
           Value::Null
         },
-        // TODO: we're not (yet) writing extra JSON data to the data field.
+        data,
       ),
     )?;
   }
@@ -392,6 +549,13 @@ where
     // Collect the remaining data from the event itself.
     event.record(&mut LogVisitor(&mut storage));
 
+    crate::metrics::record_request(
+      storage.method.as_str(),
+      storage.route.as_deref(),
+      storage.status as u16,
+      storage.latency_ms,
+    );
+
     // Then write.
     self.write_log(storage);
   }
@@ -417,6 +581,8 @@ struct LogFieldStorage {
   timestamp: chrono::DateTime<chrono::Utc>,
   method: HttpMethod,
   uri: String,
+  /// Matched route, e.g. "/api/records/v1/{name}", used for metrics and aggregating logs.
+  route: Option<String>,
   client_ip: Option<String>,
   host: String,
   referer: String,
@@ -433,9 +599,9 @@ struct LogFieldStorage {
   fields: serde_json::Map<String, serde_json::Value>,
 }
 
-/// Defines the JSON output format for stdout logging.
+/// Defines the JSON output format for logging to stdout and other `LogSink`s.
 #[derive(Debug, Default, Clone, Serialize)]
-struct JsonLog {
+pub(crate) struct JsonLog {
   /// Response timestamp in seconds since epoch with fractional milliseconds.
   timestamp: String,
   /// HTTP method (e.g. GET, POST).
@@ -525,7 +691,13 @@ impl tracing::field::Visit for LogVisitor<'_> {
 
   fn record_str(&mut self, field: &Field, s: &str) {
     match field.name() {
+      "fields" => {
+        if let Ok(serde_json::Value::Object(fields)) = serde_json::from_str(s) {
+          self.0.fields.extend(fields);
+        }
+      }
       "client_ip" => self.0.client_ip = Some(s.to_string()),
+      "route" => self.0.route = Some(s.to_string()),
       "host" => self.0.host = s.to_string(),
       "referer" => self.0.referer = s.to_string(),
       "user_agent" => self.0.user_agent = s.to_string(),
@@ -574,6 +746,85 @@ mod tests {
   use super::*;
   use std::time::Duration;
 
+  #[test]
+  fn test_build_log_data() {
+    let log = LogFieldStorage {
+      route: Some("/api/records/v1/{name}".to_string()),
+      fields: serde_json::Map::from_iter([
+        ("error".to_string(), json!("boom")),
+        (
+          "request".to_string(),
+          json!({ "Password": "secret!", "api_key": "key", "name": "foo" }),
+        ),
+      ]),
+      ..Default::default()
+    };
+    let data = |log: &LogFieldStorage, options: &LogDataOptions| {
+      return build_log_data(log, options)
+        .map(|data| serde_json::from_str::<serde_json::Value>(&data).unwrap());
+    };
+
+    // Fields aren't captured by default.
+    assert_eq!(
+      data(&log, &LogDataOptions::new(None)),
+      Some(json!({ "route": "/api/records/v1/{name}" }))
+    );
+
+    let options = LogDataOptions::new(Some(&RequestLogsConfig {
+      capture_fields: Some(true),
+      redacted_keys: vec!["API_KEY".to_string()],
+    }));
+    assert_eq!(
+      data(&log, &options),
+      Some(json!({
+        "route": "/api/records/v1/{name}",
+        "error": "boom",
+        "request": { "Password": "<redacted>", "api_key": "<redacted>", "name": "foo" },
+      }))
+    );
+
+    assert_eq!(data(&LogFieldStorage::default(), &options), None);
+  }
+
+  #[test]
+  fn test_internal_errors_attach_log_fields() {
+    use axum::response::IntoResponse;
+
+    let response = crate::records::RecordError::Internal("db on fire".into()).into_response();
+    assert_eq!(response.status(), 500);
+    assert_eq!(
+      response.extensions().get::<LogFields>().unwrap().0["error"],
+      json!("db on fire")
+    );
+
+    let response = crate::records::RecordError::RecordNotFound.into_response();
+    assert!(response.extensions().get::<LogFields>().is_none());
+  }
+
+  #[tokio::test]
+  async fn test_shutdown_flushes_logs() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let layer = SqliteLogLayer::new(&state, false);
+
+    for _ in 0..10 {
+      layer.write_log(LogFieldStorage {
+        status: 200,
+        timestamp: chrono::Utc::now(),
+        ..Default::default()
+      });
+    }
+
+    state.shutdown(Duration::from_secs(5)).await;
+
+    let count: i64 = state
+      .logs_conn()
+      .read_query_row_get("SELECT COUNT(*) FROM _logs", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(count, 10);
+  }
+
   #[test]
   fn test_as_seconds_f64() {
     let duration = chrono::Duration::new(1, 250_000_000).unwrap();
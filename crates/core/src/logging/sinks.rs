@@ -0,0 +1,482 @@
+//! Destinations for request logs in addition to the logs database, e.g. stdout, rotated files,
+//! syslog or Loki.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::proto::{
+  FileLogSinkConfig, LogSinkConfig, LokiLogSinkConfig, SyslogLogSinkConfig,
+};
+use crate::data_dir::DataDir;
+use crate::logging::JsonLog;
+
+/// A destination for request logs.
+///
+/// Sinks are invoked on the request path and must not block, i.e. writing should be deferred to
+/// a background task.
+pub(crate) trait LogSink: Send + Sync {
+  fn write(&self, log: &JsonLog);
+}
+
+/// Writes logs to stdout as line-delimited JSON.
+pub(crate) struct JsonStdoutSink;
+
+impl LogSink for JsonStdoutSink {
+  fn write(&self, log: &JsonLog) {
+    use tokio::io::AsyncWriteExt;
+
+    let Some(line) = to_json_line(log) else {
+      return;
+    };
+    tokio::spawn(async move {
+      let _ = tokio::io::stdout().write_all(&line).await;
+    });
+  }
+}
+
+/// Hands logs off to a background writer. Logs are dropped rather than blocking requests, if the
+/// writer can't keep up.
+struct ChannelSink {
+  name: &'static str,
+  sender: flume::Sender<JsonLog>,
+}
+
+impl LogSink for ChannelSink {
+  fn write(&self, log: &JsonLog) {
+    if let Err(flume::TrySendError::Full(_)) = self.sender.try_send(log.clone()) {
+      log::warn!("Back-pressure. Dropping log for {} sink.", self.name);
+    }
+  }
+}
+
+/// Builds the configured sinks. Invalid sinks are logged and skipped.
+///
+/// Background writers shut down once their sinks are dropped, e.g. when the config changes.
+pub(crate) fn build_log_sinks(
+  data_dir: &DataDir,
+  configs: &[LogSinkConfig],
+) -> Arc<Vec<Arc<dyn LogSink>>> {
+  let mut sinks: Vec<Arc<dyn LogSink>> = vec![];
+  for config in configs {
+    let sink = match config {
+      LogSinkConfig {
+        file: Some(file),
+        syslog: None,
+        loki: None,
+      } => build_file_sink(data_dir, file),
+      LogSinkConfig {
+        file: None,
+        syslog: Some(syslog),
+        loki: None,
+      } => build_syslog_sink(syslog),
+      LogSinkConfig {
+        file: None,
+        syslog: None,
+        loki: Some(loki),
+      } => build_loki_sink(loki),
+      _ => Err("exactly one sink must be set".to_string()),
+    };
+
+    match sink {
+      Ok(sink) => sinks.push(sink),
+      Err(err) => log::error!("Failed to build log sink: {err}"),
+    };
+  }
+  return Arc::new(sinks);
+}
+
+const CHANNEL_CAPACITY: usize = 16 * 1024;
+
+fn build_file_sink(
+  data_dir: &DataDir,
+  config: &FileLogSinkConfig,
+) -> Result<Arc<dyn LogSink>, String> {
+  let Some(ref path) = config.path else {
+    return Err("file sink requires a 'path'".to_string());
+  };
+
+  let mut file = RotatingFile::open(
+    data_dir.root().join(path),
+    config
+      .max_file_size_bytes
+      .unwrap_or(DEFAULT_MAX_FILE_SIZE_BYTES),
+    config.max_files.unwrap_or(DEFAULT_MAX_FILES),
+  )
+  .map_err(|err| format!("failed to open '{path}': {err}"))?;
+
+  let (sender, receiver) = flume::bounded::<JsonLog>(CHANNEL_CAPACITY);
+  std::thread::Builder::new()
+    .name("file-log-sink".to_string())
+    .spawn(move || {
+      while let Ok(first) = receiver.recv() {
+        for log in std::iter::once(first).chain(receiver.try_iter()) {
+          let Some(line) = to_json_line(&log) else {
+            continue;
+          };
+          if let Err(err) = file.write_line(&line) {
+            log::warn!("Failed to write log file: {err}");
+          }
+        }
+        if let Err(err) = file.flush() {
+          log::warn!("Failed to flush log file: {err}");
+        }
+      }
+    })
+    .map_err(|err| err.to_string())?;
+
+  return Ok(Arc::new(ChannelSink {
+    name: "file",
+    sender,
+  }));
+}
+
+const DEFAULT_MAX_FILE_SIZE_BYTES: u64 = 100 * 1024 * 1024;
+const DEFAULT_MAX_FILES: u32 = 5;
+
+/// A line-oriented file, which is rotated once it would exceed `max_size` bytes. Rotated files are
+/// kept as "<path>.1", "<path>.2", ..., where "<path>.1" is the most recent one.
+struct RotatingFile {
+  path: PathBuf,
+  max_size: u64,
+  max_files: u32,
+
+  file: std::io::BufWriter<std::fs::File>,
+  size: u64,
+}
+
+impl RotatingFile {
+  fn open(path: PathBuf, max_size: u64, max_files: u32) -> std::io::Result<Self> {
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+
+    let file = open_append(&path)?;
+    let size = file.metadata()?.len();
+    return Ok(Self {
+      path,
+      max_size,
+      max_files,
+      file: std::io::BufWriter::new(file),
+      size,
+    });
+  }
+
+  fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+    if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+      self.rotate()?;
+    }
+
+    self.file.write_all(line)?;
+    self.size += line.len() as u64;
+    return Ok(());
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    return self.file.flush();
+  }
+
+  fn rotate(&mut self) -> std::io::Result<()> {
+    self.file.flush()?;
+
+    if self.max_files == 0 {
+      std::fs::remove_file(&self.path)?;
+    } else {
+      // Drop the oldest file and shift the others, i.e. "<path>.n" becomes "<path>.n+1".
+      let _ = std::fs::remove_file(rotated_path(&self.path, self.max_files));
+      for n in (1..self.max_files).rev() {
+        let from = rotated_path(&self.path, n);
+        if from.exists() {
+          std::fs::rename(from, rotated_path(&self.path, n + 1))?;
+        }
+      }
+      std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+    }
+
+    self.file = std::io::BufWriter::new(open_append(&self.path)?);
+    self.size = 0;
+    return Ok(());
+  }
+}
+
+fn open_append(path: &Path) -> std::io::Result<std::fs::File> {
+  return std::fs::OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path);
+}
+
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+  let mut name = path.as_os_str().to_owned();
+  name.push(format!(".{n}"));
+  return name.into();
+}
+
+fn build_syslog_sink(config: &SyslogLogSinkConfig) -> Result<Arc<dyn LogSink>, String> {
+  let address = config.address.as_deref().unwrap_or("/dev/log");
+  let facility = config.facility.unwrap_or(16);
+  if facility > 23 {
+    return Err(format!("invalid syslog facility: {facility}"));
+  }
+  let app_name = config
+    .app_name
+    .clone()
+    .unwrap_or_else(|| "trailbase".to_string());
+
+  let socket = SyslogSocket::connect(address)
+    .map_err(|err| format!("failed to connect '{address}': {err}"))?;
+
+  let (sender, receiver) = flume::bounded::<JsonLog>(CHANNEL_CAPACITY);
+  std::thread::Builder::new()
+    .name("syslog-log-sink".to_string())
+    .spawn(move || {
+      let pid = std::process::id();
+      while let Ok(log) = receiver.recv() {
+        let Some(message) = format_syslog(&log, facility, &app_name, pid) else {
+          continue;
+        };
+        if let Err(err) = socket.send(&message) {
+          log::warn!("Failed to send syslog message: {err}");
+        }
+      }
+    })
+    .map_err(|err| err.to_string())?;
+
+  return Ok(Arc::new(ChannelSink {
+    name: "syslog",
+    sender,
+  }));
+}
+
+enum SyslogSocket {
+  Udp(std::net::UdpSocket),
+  #[cfg(unix)]
+  Unix(std::os::unix::net::UnixDatagram),
+}
+
+impl SyslogSocket {
+  fn connect(address: &str) -> std::io::Result<Self> {
+    #[cfg(unix)]
+    if address.starts_with('/') {
+      let socket = std::os::unix::net::UnixDatagram::unbound()?;
+      socket.connect(address)?;
+      return Ok(Self::Unix(socket));
+    }
+
+    use std::net::ToSocketAddrs;
+    let Some(addr) = address.to_socket_addrs()?.next() else {
+      return Err(std::io::Error::other("failed to resolve address"));
+    };
+    let socket = std::net::UdpSocket::bind(if addr.is_ipv4() {
+      "0.0.0.0:0"
+    } else {
+      "[::]:0"
+    })?;
+    socket.connect(addr)?;
+    return Ok(Self::Udp(socket));
+  }
+
+  fn send(&self, message: &[u8]) -> std::io::Result<usize> {
+    return match self {
+      Self::Udp(socket) => socket.send(message),
+      #[cfg(unix)]
+      Self::Unix(socket) => socket.send(message),
+    };
+  }
+}
+
+/// Formats an RFC 5424 syslog message with the JSON log as its message. The severity is derived
+/// from the response status.
+fn format_syslog(log: &JsonLog, facility: u32, app_name: &str, pid: u32) -> Option<Vec<u8>> {
+  let severity = match log.status {
+    500.. => 3,    // error
+    400..500 => 4, // warning
+    _ => 6,        // informational
+  };
+  let json = serde_json::to_string(log).ok()?;
+
+  return Some(
+    format!(
+      "<{pri}>1 {timestamp} - {app_name} {pid} http - {json}",
+      pri = facility * 8 + severity,
+      timestamp = log.timestamp,
+    )
+    .into_bytes(),
+  );
+}
+
+fn build_loki_sink(config: &LokiLogSinkConfig) -> Result<Arc<dyn LogSink>, String> {
+  let Some(ref url) = config.url else {
+    return Err("Loki sink requires a 'url'".to_string());
+  };
+  let push_url = url::Url::parse(url)
+    .and_then(|url| url.join("loki/api/v1/push"))
+    .map_err(|err| format!("invalid Loki url '{url}': {err}"))?;
+
+  let labels = if config.labels.is_empty() {
+    HashMap::from([("job".to_string(), "trailbase".to_string())])
+  } else {
+    config.labels.clone()
+  };
+  let tenant_id = config.tenant_id.clone();
+
+  let (sender, receiver) = flume::bounded::<JsonLog>(CHANNEL_CAPACITY);
+  tokio::spawn(async move {
+    let client = reqwest::Client::new();
+    while let Ok(first) = receiver.recv_async().await {
+      let logs: Vec<JsonLog> = std::iter::once(first)
+        .chain(receiver.try_iter().take(MAX_LOKI_BATCH_SIZE - 1))
+        .collect();
+
+      let mut request = client
+        .post(push_url.clone())
+        .json(&loki_push_request(&labels, &logs));
+      if let Some(ref tenant_id) = tenant_id {
+        request = request.header("X-Scope-OrgID", tenant_id);
+      }
+
+      if let Err(err) = request.send().await.and_then(|r| r.error_for_status()) {
+        log::warn!("Failed to push {} logs to Loki: {err}", logs.len());
+      }
+
+      // Batch up some more logs rather than sending a request per log.
+      if logs.len() < MAX_LOKI_BATCH_SIZE {
+        tokio::time::sleep(LOKI_BATCH_INTERVAL).await;
+      }
+    }
+  });
+
+  return Ok(Arc::new(ChannelSink {
+    name: "Loki",
+    sender,
+  }));
+}
+
+const MAX_LOKI_BATCH_SIZE: usize = 1024;
+const LOKI_BATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Builds the body of a Loki push request, see
+/// https://grafana.com/docs/loki/latest/reference/loki-http-api/#ingest-logs.
+fn loki_push_request(labels: &HashMap<String, String>, logs: &[JsonLog]) -> serde_json::Value {
+  let values: Vec<_> = logs
+    .iter()
+    .filter_map(|log| {
+      let timestamp = chrono::DateTime::parse_from_rfc3339(&log.timestamp)
+        .ok()?
+        .timestamp_nanos_opt()?;
+      return Some([timestamp.to_string(), serde_json::to_string(log).ok()?]);
+    })
+    .collect();
+
+  return serde_json::json!({
+    "streams": [{
+      "stream": labels,
+      "values": values,
+    }],
+  });
+}
+
+fn to_json_line(log: &JsonLog) -> Option<Vec<u8>> {
+  let mut buf: Vec<u8> = Vec::with_capacity(480);
+  serde_json::to_writer(&mut buf, log).ok()?;
+  buf.push(b'\n');
+  return Some(buf);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn test_log(status: i64) -> JsonLog {
+    return JsonLog {
+      timestamp: "2025-01-01T12:00:00+00:00".to_string(),
+      uri: "/api/healthcheck".to_string(),
+      status,
+      ..Default::default()
+    };
+  }
+
+  #[test]
+  fn test_rotating_file() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let path = dir.path().join("logs/requests.jsonl");
+
+    let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+    for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+      file.write_line(line.as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let read = |n: Option<u32>| {
+      let path = n.map_or_else(|| path.clone(), |n| rotated_path(&path, n));
+      return std::fs::read_to_string(path).ok();
+    };
+    assert_eq!(read(None).as_deref(), Some("fourth\n"));
+    assert_eq!(read(Some(1)).as_deref(), Some("third\n"));
+    assert_eq!(read(Some(2)).as_deref(), Some("second\n"));
+    // Only `max_files` rotated files are kept.
+    assert_eq!(read(Some(3)), None);
+
+    // Re-opening appends to the existing file.
+    let mut file = RotatingFile::open(path.clone(), 100, 2).unwrap();
+    file.write_line(b"fifth\n").unwrap();
+    file.flush().unwrap();
+    assert_eq!(read(None).as_deref(), Some("fourth\nfifth\n"));
+  }
+
+  #[test]
+  fn test_format_syslog() {
+    let message = String::from_utf8(format_syslog(&test_log(503), 16, "app", 42).unwrap()).unwrap();
+    let json = serde_json::to_string(&test_log(503)).unwrap();
+    assert_eq!(
+      message,
+      format!("<131>1 2025-01-01T12:00:00+00:00 - app 42 http - {json}")
+    );
+
+    let message = String::from_utf8(format_syslog(&test_log(200), 1, "app", 42).unwrap()).unwrap();
+    assert!(message.starts_with("<14>1 "), "{message}");
+  }
+
+  #[test]
+  fn test_loki_push_request() {
+    let labels = HashMap::from([("job".to_string(), "test".to_string())]);
+    let log = test_log(200);
+
+    assert_eq!(
+      loki_push_request(&labels, std::slice::from_ref(&log)),
+      serde_json::json!({
+        "streams": [{
+          "stream": { "job": "test" },
+          "values": [
+            ["1735732800000000000", serde_json::to_string(&log).unwrap()],
+          ],
+        }],
+      })
+    );
+  }
+
+  #[test]
+  fn test_build_log_sinks() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let data_dir = DataDir(dir.path().to_path_buf());
+
+    let sinks = build_log_sinks(
+      &data_dir,
+      &[
+        LogSinkConfig {
+          file: Some(FileLogSinkConfig {
+            path: Some("requests.jsonl".to_string()),
+            ..Default::default()
+          }),
+          ..Default::default()
+        },
+        // Invalid: no sink set.
+        LogSinkConfig::default(),
+      ],
+    );
+    assert_eq!(sinks.len(), 1);
+    assert!(dir.path().join("requests.jsonl").exists());
+  }
+}
@@ -0,0 +1,85 @@
+//! Records statements exceeding `slow_query_threshold_ms` into the logs database.
+
+use const_format::formatcp;
+use flume::TrySendError;
+use trailbase_sqlite::{SlowQuery, SyncConnectionTrait};
+
+use crate::constants::SLOW_QUERIES_TABLE;
+
+/// Max number of slow queries buffered before they're dropped.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// (Re-)installs the process-wide slow query handler. `None` disables recording.
+pub(crate) fn install_slow_query_recorder(
+  sender: &flume::Sender<SlowQuery>,
+  threshold_ms: Option<u64>,
+) {
+  let Some(threshold_ms) = threshold_ms else {
+    trailbase_sqlite::clear_slow_query_handler();
+    return;
+  };
+
+  let sender = sender.clone();
+  trailbase_sqlite::set_slow_query_handler(
+    std::time::Duration::from_millis(threshold_ms),
+    move |slow_query| {
+      // Recording slow queries must not be recorded itself, otherwise we may end up in a loop.
+      if slow_query.sql.contains(SLOW_QUERIES_TABLE) {
+        return;
+      }
+
+      match sender.try_send(slow_query) {
+        Ok(_) | Err(TrySendError::Disconnected(_)) => {}
+        Err(TrySendError::Full(_)) => {
+          log::warn!("Slow query log backed up, dropping entry");
+        }
+      }
+    },
+  );
+}
+
+/// Spawns the task writing recorded slow queries to the logs DB and returns its input.
+pub(crate) fn spawn_slow_query_writer(
+  conn: trailbase_sqlite::Connection,
+) -> flume::Sender<SlowQuery> {
+  let (sender, receiver) = flume::bounded::<SlowQuery>(CHANNEL_CAPACITY);
+
+  tokio::spawn(async move {
+    while let Ok(first) = receiver.recv_async().await {
+      let mut buffer = vec![first];
+      buffer.extend(receiver.try_iter());
+
+      if let Err(err) = conn
+        .call_writer(move |mut conn| insert_slow_queries(&mut conn, buffer))
+        .await
+      {
+        log::warn!("Failed to write slow queries: {err}");
+      }
+    }
+  });
+
+  return sender;
+}
+
+fn insert_slow_queries(
+  conn: &mut trailbase_sqlite::SyncConnection,
+  slow_queries: Vec<SlowQuery>,
+) -> Result<(), trailbase_sqlite::Error> {
+  const QUERY: &str = formatcp!(
+    "INSERT INTO '{SLOW_QUERIES_TABLE}' (database, sql, duration_ms, query_plan) VALUES ($1, $2, $3, $4)"
+  );
+
+  for slow_query in slow_queries {
+    conn.execute(
+      QUERY,
+      trailbase_sqlite::params!(
+        slow_query.database,
+        slow_query.sql,
+        slow_query.duration.as_secs_f64() * 1000.0,
+        slow_query.query_plan,
+      ),
+    )?;
+  }
+
+  return Ok(());
+}
@@ -0,0 +1,319 @@
+//! Process-wide metrics exported in the Prometheus text format.
+//!
+//! Request metrics are derived from the request tracing spans, see `logging.rs`. Metrics are only
+//! served on a dedicated listener, see [crate::ServerOptions::metrics_address].
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::app_state::AppState;
+use crate::auth::webhooks::AuthEvent;
+
+/// Upper bounds of the request latency histogram buckets in seconds.
+const LATENCY_BUCKETS: [f64; 11] = [
+  0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Route label for requests, which didn't match a route, e.g. static assets served by the
+/// fallback.
+const FALLBACK_ROUTE: &str = "fallback";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct RequestKey {
+  method: String,
+  route: String,
+  status: u16,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+  /// Non-cumulative counts per bucket.
+  buckets: [u64; LATENCY_BUCKETS.len()],
+  count: u64,
+  sum: f64,
+}
+
+static REQUESTS: LazyLock<Mutex<HashMap<RequestKey, Histogram>>> =
+  LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ObjectStoreOp {
+  Get = 0,
+  Head = 1,
+  Put = 2,
+  Delete = 3,
+}
+
+impl ObjectStoreOp {
+  const ALL: [ObjectStoreOp; 4] = [
+    ObjectStoreOp::Get,
+    ObjectStoreOp::Head,
+    ObjectStoreOp::Put,
+    ObjectStoreOp::Delete,
+  ];
+
+  fn name(self) -> &'static str {
+    return match self {
+      Self::Get => "get",
+      Self::Head => "head",
+      Self::Put => "put",
+      Self::Delete => "delete",
+    };
+  }
+}
+
+static OBJECT_STORE_OPS: [AtomicU64; ObjectStoreOp::ALL.len()] =
+  [const { AtomicU64::new(0) }; ObjectStoreOp::ALL.len()];
+
+static AUTH_EVENTS: [AtomicU64; AuthEvent::ALL.len()] =
+  [const { AtomicU64::new(0) }; AuthEvent::ALL.len()];
+
+/// Records a served request. `route` is the matched route, e.g. "/api/records/v1/{name}", rather
+/// than the requested path to keep the number of series bounded.
+pub(crate) fn record_request(method: &str, route: Option<&str>, status: u16, latency_ms: f64) {
+  let latency_sec = latency_ms / 1000.0;
+  let key = RequestKey {
+    method: method.to_string(),
+    route: route.unwrap_or(FALLBACK_ROUTE).to_string(),
+    status,
+  };
+
+  let mut requests = REQUESTS.lock();
+  let histogram = requests.entry(key).or_default();
+  if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| latency_sec <= *le) {
+    histogram.buckets[bucket] += 1;
+  }
+  histogram.count += 1;
+  histogram.sum += latency_sec;
+}
+
+pub(crate) fn record_object_store_op(op: ObjectStoreOp) {
+  OBJECT_STORE_OPS[op as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_auth_event(event: AuthEvent) {
+  if let Some(index) = AuthEvent::ALL.iter().position(|e| *e == event) {
+    AUTH_EVENTS[index].fetch_add(1, Ordering::Relaxed);
+  }
+}
+
+pub(crate) fn router() -> Router<AppState> {
+  return Router::new().route("/metrics", get(metrics_handler));
+}
+
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+  return (
+    [(
+      header::CONTENT_TYPE,
+      "text/plain; version=0.0.4; charset=utf-8",
+    )],
+    render_metrics(&state).await,
+  )
+    .into_response();
+}
+
+pub(crate) async fn render_metrics(state: &AppState) -> String {
+  let mut out = String::new();
+
+  {
+    let requests = REQUESTS.lock();
+    let mut keys: Vec<_> = requests.keys().collect();
+    keys.sort();
+
+    header(
+      &mut out,
+      "trailbase_http_request_duration_seconds",
+      "histogram",
+      "Latency of served HTTP requests by method, matched route and status.",
+    );
+    for key in keys {
+      let histogram = &requests[key];
+      let labels = format!(
+        "method=\"{}\",route=\"{}\",status=\"{}\"",
+        escape(&key.method),
+        escape(&key.route),
+        key.status
+      );
+
+      let mut cumulative = 0;
+      for (le, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+        cumulative += count;
+        let _ = writeln!(
+          out,
+          "trailbase_http_request_duration_seconds_bucket{{{labels},le=\"{le}\"}} {cumulative}"
+        );
+      }
+      let _ = writeln!(
+        out,
+        "trailbase_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+        histogram.count
+      );
+      let _ = writeln!(
+        out,
+        "trailbase_http_request_duration_seconds_sum{{{labels}}} {}",
+        histogram.sum
+      );
+      let _ = writeln!(
+        out,
+        "trailbase_http_request_duration_seconds_count{{{labels}}} {}",
+        histogram.count
+      );
+    }
+  }
+
+  header(
+    &mut out,
+    "trailbase_sqlite_busy_errors_total",
+    "counter",
+    "Operations failed with SQLITE_BUSY, e.g. due to busy timeouts.",
+  );
+  let _ = writeln!(
+    out,
+    "trailbase_sqlite_busy_errors_total {}",
+    trailbase_sqlite::busy_error_count()
+  );
+
+  let cache_stats = trailbase_sqlite::statement_cache_stats();
+  header(
+    &mut out,
+    "trailbase_sqlite_statement_cache_hits_total",
+    "counter",
+    "Prepared statements served from the statement cache.",
+  );
+  let _ = writeln!(
+    out,
+    "trailbase_sqlite_statement_cache_hits_total {}",
+    cache_stats.hits
+  );
+  header(
+    &mut out,
+    "trailbase_sqlite_statement_cache_misses_total",
+    "counter",
+    "Prepared statements missing from the statement cache.",
+  );
+  let _ = writeln!(
+    out,
+    "trailbase_sqlite_statement_cache_misses_total {}",
+    cache_stats.misses
+  );
+
+  header(
+    &mut out,
+    "trailbase_sqlite_wal_size_bytes",
+    "gauge",
+    "Size of the database's write-ahead log.",
+  );
+  let data_dir = state.data_dir();
+  for (database, path) in [
+    ("main", data_dir.main_db_path()),
+    ("logs", data_dir.logs_db_path()),
+    ("session", data_dir.session_db_path()),
+  ] {
+    // NOTE: The WAL may not exist, e.g. after a checkpoint on close.
+    let size = tokio::fs::metadata(wal_path(path))
+      .await
+      .map_or(0, |m| m.len());
+    let _ = writeln!(
+      out,
+      "trailbase_sqlite_wal_size_bytes{{database=\"{database}\"}} {size}"
+    );
+  }
+
+  header(
+    &mut out,
+    "trailbase_object_store_operations_total",
+    "counter",
+    "Object store operations, e.g. reads and writes of record files.",
+  );
+  for op in ObjectStoreOp::ALL {
+    let _ = writeln!(
+      out,
+      "trailbase_object_store_operations_total{{operation=\"{}\"}} {}",
+      op.name(),
+      OBJECT_STORE_OPS[op as usize].load(Ordering::Relaxed)
+    );
+  }
+
+  header(
+    &mut out,
+    "trailbase_auth_events_total",
+    "counter",
+    "Auth lifecycle events, e.g. failed logins.",
+  );
+  for (index, event) in AuthEvent::ALL.iter().enumerate() {
+    let _ = writeln!(
+      out,
+      "trailbase_auth_events_total{{event=\"{}\"}} {}",
+      event.name(),
+      AUTH_EVENTS[index].load(Ordering::Relaxed)
+    );
+  }
+
+  return out;
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+  let _ = writeln!(out, "# HELP {name} {help}");
+  let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn wal_path(db_path: PathBuf) -> PathBuf {
+  let mut path = db_path.into_os_string();
+  path.push("-wal");
+  return path.into();
+}
+
+/// Escapes label values as required by the text format.
+fn escape(value: &str) -> String {
+  return value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_render_metrics() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+
+    record_request("GET", Some("/metrics_test/{id}"), 200, 30.0);
+    record_request("GET", Some("/metrics_test/{id}"), 200, 3000.0);
+    record_object_store_op(ObjectStoreOp::Put);
+    record_auth_event(AuthEvent::LoginFailed);
+
+    let metrics = render_metrics(&state).await;
+
+    let labels = r#"method="GET",route="/metrics_test/{id}",status="200""#;
+    for line in [
+      format!("trailbase_http_request_duration_seconds_bucket{{{labels},le=\"0.025\"}} 0"),
+      format!("trailbase_http_request_duration_seconds_bucket{{{labels},le=\"0.05\"}} 1"),
+      format!("trailbase_http_request_duration_seconds_bucket{{{labels},le=\"5\"}} 2"),
+      format!("trailbase_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} 2"),
+      format!("trailbase_http_request_duration_seconds_count{{{labels}}} 2"),
+    ] {
+      assert!(metrics.contains(&line), "{line}\n{metrics}");
+    }
+
+    assert!(metrics.contains("# TYPE trailbase_sqlite_busy_errors_total counter"));
+    assert!(metrics.contains("trailbase_sqlite_wal_size_bytes{database=\"main\"}"));
+    assert!(!metrics.contains("trailbase_object_store_operations_total{operation=\"put\"} 0"));
+    assert!(!metrics.contains("trailbase_auth_events_total{event=\"login.failed\"} 0"));
+  }
+
+  #[test]
+  fn test_escape() {
+    assert_eq!(r#"a\"b\\c\nd"#, escape("a\"b\\c\nd"));
+  }
+}
@@ -1,14 +1,30 @@
+use const_format::formatcp;
 use itertools::Itertools;
 use log::*;
 use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 use trailbase_refinery::{Error as RefineryError, Migration};
+use trailbase_sqlite::ConnectionType;
+use trailbase_sqlite::params;
+use trailbase_sqlite::traits::{SyncConnection, SyncTransaction};
 use walkdir::{DirEntry, WalkDir};
 
+use crate::constants::SQLITE_SCHEMA_TABLE;
+use crate::transaction_recorder::TransactionError;
+
 const MIGRATION_TABLE_NAME: &str = "_schema_history";
 
+/// Tracks which user migrations were applied together, i.e. in a batch, and how to revert them.
+const MIGRATION_BATCH_TABLE_NAME: &str = "_migration_batch";
+
+/// Down migrations by version. Only user migrations, i.e. loaded from the data directory or
+/// generated by the admin UI, are tracked in batches and can be rolled back. A migration w/o down
+/// migration blocks rolling back its batch.
+pub(crate) type DownMigrations = HashMap<u32, Option<String>>;
+
 pub fn new_unique_migration_filename(suffix: &str) -> String {
   let timestamp = {
     // We use the timestamp as a version. We need to debounce it to avoid collisions.
@@ -54,16 +70,21 @@ pub(crate) async fn apply_main_migrations(
     load_embedded_migrations::<BaseMigrations>(),
     load_embedded_migrations::<MainMigrations>(),
   ];
+  let mut downs = DownMigrations::new();
 
   if let Some(path) = base_migrations_path {
     // Ignore when `<traildepot>/migrations/main/` is missing.
-    migrations.push(maybe_load_sql_migrations(path.as_ref().join("main"), true)?);
+    migrations.push(maybe_load_sql_migrations(
+      path.as_ref().join("main"),
+      true,
+      &mut downs,
+    )?);
 
     // Legacy: all *.sql files in migrations.
-    migrations.push(load_sql_migrations(path, false)?);
+    migrations.push(load_sql_migrations(path, false, &mut downs)?);
   }
 
-  return apply_migrations_async("main", conn, migrations).await;
+  return apply_migrations_async("main", conn, migrations, downs).await;
 }
 
 /// Apply migrations: embedded and from `user_migrations_path`.
@@ -76,16 +97,21 @@ pub(crate) async fn apply_pg_main_migrations(
   base_migrations_path: Option<impl AsRef<Path>>,
 ) -> Result<bool, RefineryError> {
   let mut migrations = vec![load_embedded_migrations::<PgMainMigrations>()];
+  let mut downs = DownMigrations::new();
 
   if let Some(path) = base_migrations_path {
     // Ignore when `<traildepot>/migrations/main/` is missing.
-    migrations.push(maybe_load_sql_migrations(path.as_ref().join("main"), true)?);
+    migrations.push(maybe_load_sql_migrations(
+      path.as_ref().join("main"),
+      true,
+      &mut downs,
+    )?);
 
     // Legacy: all *.sql files in migrations.
-    migrations.push(load_sql_migrations(path, false)?);
+    migrations.push(load_sql_migrations(path, false, &mut downs)?);
   }
 
-  return apply_migrations_async("main", conn, migrations).await;
+  return apply_migrations_async("main", conn, migrations, downs).await;
 }
 
 // Base migrations contains things like file deletions table shared across main and user DBs.
@@ -95,12 +121,17 @@ pub(crate) fn apply_base_migrations(
   db: &str,
 ) -> Result<bool, RefineryError> {
   let mut migrations = vec![load_embedded_migrations::<BaseMigrations>()];
+  let mut downs = DownMigrations::new();
   // TODO: Should we handle load_sql_migrations error?
   if let Some(path) = base_migrations_path {
     // Ignore when `<traildepot>/migrations/main/` is missing.
-    migrations.push(maybe_load_sql_migrations(path.as_ref().join(db), true)?);
+    migrations.push(maybe_load_sql_migrations(
+      path.as_ref().join(db),
+      true,
+      &mut downs,
+    )?);
   }
-  return apply_migrations(db, conn, migrations);
+  return apply_migrations(db, conn, migrations, downs);
 }
 
 pub(crate) fn apply_logs_migrations(
@@ -110,6 +141,7 @@ pub(crate) fn apply_logs_migrations(
     "logs",
     logs_conn,
     vec![load_embedded_migrations::<LogsMigrations>()],
+    DownMigrations::new(),
   )?;
   return Ok(());
 }
@@ -121,6 +153,7 @@ pub(crate) fn apply_session_migrations(
     "session",
     logs_conn,
     vec![load_embedded_migrations::<SessionMigrations>()],
+    DownMigrations::new(),
   )?;
   return Ok(());
 }
@@ -129,6 +162,7 @@ fn apply_migrations(
   name: &str,
   conn: &mut rusqlite::Connection,
   migrations: Vec<Vec<Migration>>,
+  downs: DownMigrations,
 ) -> Result<bool, RefineryError> {
   let migrations: Vec<Migration> = migrations.into_iter().flatten().sorted().collect();
 
@@ -144,6 +178,14 @@ fn apply_migrations(
   let applied_migrations = report.applied_migrations();
   log_migrations(name, applied_migrations);
 
+  (|| -> Result<(), trailbase_sqlite::Error> {
+    let mut tx = conn.transaction()?;
+    record_migration_batch(&mut tx, applied_migrations, &downs)?;
+    tx.commit()?;
+    return Ok(());
+  })()
+  .map_err(batch_error)?;
+
   // If we applied migration v1 we can be sure this is a fresh database.
   let new_db = applied_migrations.iter().any(|m| m.version() == 1);
 
@@ -154,6 +196,7 @@ async fn apply_migrations_async(
   name: &str,
   conn: &trailbase_sqlite::Connection,
   migrations: Vec<Vec<Migration>>,
+  downs: DownMigrations,
 ) -> Result<bool, RefineryError> {
   let migrations: Vec<Migration> = migrations.into_iter().flatten().sorted().collect();
 
@@ -170,12 +213,215 @@ async fn apply_migrations_async(
   let applied_migrations = report.applied_migrations();
   log_migrations(name, applied_migrations);
 
+  record_migration_batch_async(&conn, applied_migrations, downs).await?;
+
   // If we applied migration v1 we can be sure this is a fresh database.
   let new_db = applied_migrations.iter().any(|m| m.version() == 1);
 
   return Ok(new_db);
 }
 
+fn batch_error(err: trailbase_sqlite::Error) -> RefineryError {
+  return RefineryError::new(
+    trailbase_refinery::error::Kind::Connection(
+      "failed to record migration batch".to_string(),
+      err.into(),
+    ),
+    None,
+  );
+}
+
+/// Records the applied user migrations as a new batch.
+fn record_migration_batch(
+  conn: &mut impl SyncConnection,
+  applied_migrations: &[Migration],
+  downs: &DownMigrations,
+) -> Result<(), trailbase_sqlite::Error> {
+  const CREATE_TABLE_QUERY: &str = formatcp!(
+    "\
+      CREATE TABLE IF NOT EXISTS {MIGRATION_BATCH_TABLE_NAME} ( \
+        version    INTEGER PRIMARY KEY NOT NULL, \
+        batch      INTEGER NOT NULL, \
+        down       TEXT \
+      ) STRICT \
+    "
+  );
+  const NEXT_BATCH_QUERY: &str =
+    formatcp!("SELECT COALESCE(MAX(batch), 0) + 1 FROM {MIGRATION_BATCH_TABLE_NAME}");
+  const INSERT_QUERY: &str = formatcp!(
+    "INSERT INTO {MIGRATION_BATCH_TABLE_NAME} (version, batch, down) VALUES ($1, $2, $3)"
+  );
+
+  let user_migrations: Vec<(u32, Option<String>)> = applied_migrations
+    .iter()
+    .filter_map(|m| Some((m.version(), downs.get(&m.version())?.clone())))
+    .collect();
+  if user_migrations.is_empty() || conn.connection_type() != ConnectionType::Sqlite {
+    return Ok(());
+  }
+
+  conn.execute_batch(CREATE_TABLE_QUERY)?;
+  let batch: i64 = match conn.query_row(NEXT_BATCH_QUERY, ())? {
+    Some(row) => row.get(0)?,
+    None => 1,
+  };
+
+  for (version, down) in user_migrations {
+    conn.execute(INSERT_QUERY, params!(version as i64, batch, down))?;
+  }
+
+  return Ok(());
+}
+
+pub(crate) async fn record_migration_batch_async(
+  conn: &trailbase_sqlite::Connection,
+  applied_migrations: &[Migration],
+  downs: DownMigrations,
+) -> Result<(), RefineryError> {
+  let applied_migrations = applied_migrations.to_vec();
+  return conn
+    .transaction(move |mut tx| -> Result<(), trailbase_sqlite::Error> {
+      record_migration_batch(&mut tx, &applied_migrations, &downs)?;
+      tx.commit()?;
+      return Ok(());
+    })
+    .await
+    .map_err(batch_error);
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RolledBackBatch {
+  /// Names of the rolled back migrations, e.g. "U1700000000__create_table_foo".
+  pub migrations: Vec<String>,
+  /// The down migrations that were applied.
+  pub sql: String,
+}
+
+/// Rolls back the most recently applied batch of user migrations by applying their down
+/// migrations in reverse order.
+///
+/// Since migrations are otherwise re-applied on the next start, the corresponding migration
+/// files are removed from `migrations_path`.
+pub(crate) async fn rollback_last_migration_batch(
+  conn: &trailbase_sqlite::Connection,
+  migrations_path: impl AsRef<Path>,
+  dry_run: bool,
+) -> Result<RolledBackBatch, TransactionError> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT b.version, h.name, b.down FROM {MIGRATION_BATCH_TABLE_NAME} AS b \
+        LEFT JOIN {MIGRATION_TABLE_NAME} AS h ON b.version = h.version \
+      WHERE b.batch = (SELECT MAX(batch) FROM {MIGRATION_BATCH_TABLE_NAME}) \
+      ORDER BY b.version DESC \
+    "
+  );
+
+  let exists: bool = conn
+    .read_query_row_get(
+      formatcp!(
+        "SELECT EXISTS(SELECT 1 FROM {SQLITE_SCHEMA_TABLE} WHERE type = 'table' AND name = '{MIGRATION_BATCH_TABLE_NAME}')"
+      ),
+      (),
+      0,
+    )
+    .await?
+    .unwrap_or(false);
+  if !exists {
+    return Err(TransactionError::Rollback(
+      "No migrations to roll back".to_string(),
+    ));
+  }
+
+  let mut batch: Vec<(i64, String, String)> = vec![];
+  for row in conn.read_query_rows(QUERY, ()).await?.iter() {
+    let version: i64 = row.get(0)?;
+    let name = format!(
+      "U{version}__{}",
+      row.get::<Option<String>>(1)?.unwrap_or_default()
+    );
+    let Some(down) = row.get::<Option<String>>(2)? else {
+      return Err(TransactionError::Rollback(format!(
+        "Migration '{name}' cannot be rolled back, missing down migration"
+      )));
+    };
+    batch.push((version, name, down));
+  }
+
+  if batch.is_empty() {
+    return Err(TransactionError::Rollback(
+      "No migrations to roll back".to_string(),
+    ));
+  }
+
+  let rolled_back = RolledBackBatch {
+    migrations: batch.iter().map(|(_, name, _)| name.clone()).collect(),
+    sql: batch.iter().map(|(_, _, down)| down.as_str()).join("\n\n"),
+  };
+  if dry_run {
+    return Ok(rolled_back);
+  }
+
+  {
+    let batch = batch.clone();
+    conn
+      .transaction(move |mut tx| -> Result<(), trailbase_sqlite::Error> {
+        for (version, _name, down) in batch {
+          tx.execute_batch(down)?;
+          tx.execute(
+            formatcp!("DELETE FROM {MIGRATION_TABLE_NAME} WHERE version = $1"),
+            params!(version),
+          )?;
+          tx.execute(
+            formatcp!("DELETE FROM {MIGRATION_BATCH_TABLE_NAME} WHERE version = $1"),
+            params!(version),
+          )?;
+        }
+        tx.commit()?;
+        return Ok(());
+      })
+      .await?;
+  }
+
+  // Remove the rolled back migrations' files.
+  let names: Vec<String> = batch
+    .iter()
+    .flat_map(|(version, name, _)| {
+      let name = name.trim_start_matches(&format!("U{version}"));
+      return [
+        format!("U{version}{name}.sql"),
+        format!("U{version}{name}.down.sql"),
+        format!("V{version}{name}.sql"),
+        format!("V{version}{name}.down.sql"),
+      ];
+    })
+    .collect();
+  for entry in WalkDir::new(migrations_path)
+    .into_iter()
+    .filter_map(Result::ok)
+  {
+    if entry
+      .file_name()
+      .to_str()
+      .is_some_and(|f| names.iter().any(|n| n == f))
+    {
+      info!("Removing rolled back migration: {:?}", entry.path());
+      std::fs::remove_file(entry.path())?;
+    }
+  }
+
+  return Ok(rolled_back);
+}
+
+/// Path of the down migration for the given migration file, e.g. `U1__foo.down.sql` for
+/// `U1__foo.sql`.
+pub(crate) fn down_migration_path(path: &Path) -> PathBuf {
+  let stem = path
+    .file_stem()
+    .map(|s| s.to_string_lossy().to_string())
+    .unwrap_or_default();
+  return path.with_file_name(format!("{stem}.down.sql"));
+}
+
 fn log_migrations(db_name: &str, migrations: &[Migration]) {
   fn name(migration: &Migration) -> String {
     return format!(
@@ -211,8 +457,9 @@ fn log_migrations(db_name: &str, migrations: &[Migration]) {
 fn maybe_load_sql_migrations(
   location: impl AsRef<Path>,
   recursive: bool,
+  downs: &mut DownMigrations,
 ) -> Result<Vec<Migration>, RefineryError> {
-  return match load_sql_migrations(location, recursive) {
+  return match load_sql_migrations(location, recursive, downs) {
     Err(err)
       if matches!(
         err.kind(),
@@ -227,9 +474,12 @@ fn maybe_load_sql_migrations(
 
 /// Loads SQL migrations from a path. This enables dynamic migration discovery, as opposed to
 /// embedding. The resulting collection is ordered by version.
+///
+/// Paired down migrations, e.g. `U1__foo.down.sql` for `U1__foo.sql`, are added to `downs`.
 fn load_sql_migrations(
   location: impl AsRef<Path>,
   recursive: bool,
+  downs: &mut DownMigrations,
 ) -> Result<Vec<Migration>, RefineryError> {
   use trailbase_refinery::{Error, error::Kind};
 
@@ -250,7 +500,20 @@ fn load_sql_migrations(
         .and_then(|file| file.to_os_string().into_string().ok())
         .ok_or_else(|| RefineryError::new(Kind::InvalidName, None))?;
 
-      return Migration::unapplied(&filename, &sql);
+      let migration = Migration::unapplied(&filename, &sql)?;
+
+      let down_path = down_migration_path(&path);
+      let down = if down_path.exists() {
+        Some(
+          std::fs::read_to_string(&down_path)
+            .map_err(|e| Error::new(Kind::InvalidMigrationFile(down_path.clone(), e), None))?,
+        )
+      } else {
+        None
+      };
+      downs.insert(migration.version(), down);
+
+      return Ok(migration);
     })
     .collect::<Result<Vec<Migration>, Error>>()?;
 
@@ -262,6 +525,8 @@ fn load_sql_migrations(
 const STEM_RE: &str = r"^([U|V])(\d+(?:\.\d+)?)__(\w+)";
 static SQL_FILE_RE: LazyLock<regex::Regex> =
   LazyLock::new(|| regex::Regex::new(&format!(r"{STEM_RE}\.sql$")).expect("const"));
+static DOWN_SQL_FILE_RE: LazyLock<regex::Regex> =
+  LazyLock::new(|| regex::Regex::new(&format!(r"{STEM_RE}\.down\.sql$")).expect("const"));
 
 /// find migrations on file system recursively across directories given a location and
 /// [MigrationType]
@@ -296,6 +561,8 @@ fn find_migration_files(
     return match path.file_name().and_then(OsStr::to_str) {
       Some(_) if path.is_dir() => false,
       Some(file_name) if SQL_FILE_RE.is_match(file_name) => true,
+      // Loaded alongside their up migrations.
+      Some(file_name) if DOWN_SQL_FILE_RE.is_match(file_name) => false,
       Some(file_name) => {
         log::warn!(
           "File \"{file_name}\" does not adhere to the migration naming convention. Migrations must be named in the format [U|V]{{1}}__{{2}}.sql or [U|V]{{1}}__{{2}}.rs, where {{1}} represents the migration version and {{2}} the name."
@@ -351,9 +618,10 @@ mod tests {
 
   #[test]
   fn test_load_sql_migrations() {
-    assert!(load_sql_migrations("__non-existent-path__", true).is_err());
+    let mut downs = DownMigrations::new();
+    assert!(load_sql_migrations("__non-existent-path__", true, &mut downs).is_err());
     assert!(
-      maybe_load_sql_migrations("__non-existent-path__", true)
+      maybe_load_sql_migrations("__non-existent-path__", true, &mut downs)
         .unwrap()
         .is_empty()
     );
@@ -371,7 +639,7 @@ mod tests {
 
       return conn.read_query_row_get(
           format!(
-            "SELECT EXISTS(SELECT 1 FROM sqlite_schema WHERE type = '{schema_type}' AND name = '{name}')"
+            "SELECT EXISTS(SELECT 1 FROM {SQLITE_SCHEMA_TABLE} WHERE type = '{schema_type}' AND name = '{name}')"
           ),
           (),
           0,
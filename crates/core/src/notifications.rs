@@ -0,0 +1,740 @@
+//! Push notifications delivered to users' devices via FCM, APNs or Web Push.
+//!
+//! Users register their devices' tokens and subscribe to topics via the notifications API.
+//! Notifications are sent to users or topics via the admin API and fanned out to all matching
+//! devices. Outcomes are recorded in the `_push_deliveries` log and devices the push services
+//! reported as unregistered, e.g. uninstalled apps or revoked subscriptions, are removed.
+
+use axum::{
+  Json, Router,
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+  routing::{get, post},
+};
+use const_format::formatcp;
+use futures_util::StreamExt;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+use trailbase_sqlite::params;
+use ts_rs::TS;
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::{AuthError, User};
+use crate::constants::{
+  NOTIFICATIONS_API_PATH, PUSH_DELIVERIES_TABLE, PUSH_DEVICES_TABLE, PUSH_TOPICS_TABLE,
+};
+
+mod providers;
+mod webpush;
+
+pub(crate) use providers::{PushProviders, build_push_providers};
+
+#[derive(Debug, Error)]
+pub enum PushError {
+  #[error("Missing {0}")]
+  Missing(&'static str),
+  #[error("Config: {0}")]
+  Config(String),
+  #[error("HTTP: {0}")]
+  Http(#[from] reqwest::Error),
+  #[error("JWT: {0}")]
+  Jwt(#[from] jsonwebtoken::errors::Error),
+  #[error("API error ({status}): {message}")]
+  Api { status: u16, message: String },
+  #[error("Device unregistered")]
+  Unregistered,
+  #[error("Encryption: {0}")]
+  Encryption(&'static str),
+}
+
+/// Push service a device is registered with: Firebase Cloud Messaging, e.g. Android, Apple Push
+/// Notification service, e.g. iOS, or Web Push for browsers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Platform {
+  Fcm,
+  Apns,
+  WebPush,
+}
+
+impl Platform {
+  pub fn name(&self) -> &'static str {
+    return match self {
+      Self::Fcm => "fcm",
+      Self::Apns => "apns",
+      Self::WebPush => "web_push",
+    };
+  }
+
+  fn from_name(name: &str) -> Option<Self> {
+    return match name {
+      "fcm" => Some(Self::Fcm),
+      "apns" => Some(Self::Apns),
+      "web_push" => Some(Self::WebPush),
+      _ => None,
+    };
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct Notification {
+  pub title: String,
+  pub body: String,
+  /// Custom key-value pairs passed along to the app.
+  pub data: Option<BTreeMap<String, String>>,
+}
+
+/// A registered device as stored in `_push_devices`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Device {
+  pub id: i64,
+  pub user: [u8; 16],
+  pub platform: String,
+  pub token: String,
+  pub p256dh: Option<String>,
+  pub auth: Option<String>,
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+  register_device_handler,
+  unregister_device_handler,
+  subscribe_topic_handler,
+  unsubscribe_topic_handler,
+  vapid_public_key_handler,
+))]
+pub(super) struct NotificationsApi;
+
+/// Router for notification API endpoints, i.e. api/notifications/v?/... .
+pub(crate) fn router() -> Router<AppState> {
+  return Router::new()
+    .route(
+      &format!("/{NOTIFICATIONS_API_PATH}/devices"),
+      post(register_device_handler).delete(unregister_device_handler),
+    )
+    .route(
+      &format!("/{NOTIFICATIONS_API_PATH}/topics/{{topic}}"),
+      post(subscribe_topic_handler).delete(unsubscribe_topic_handler),
+    )
+    .route(
+      &format!("/{NOTIFICATIONS_API_PATH}/web_push/vapid_public_key"),
+      get(vapid_public_key_handler),
+    );
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct RegisterDeviceRequest {
+  pub platform: Platform,
+  /// FCM registration token, APNs device token or, for Web Push, the subscription's endpoint.
+  pub token: String,
+  /// Keys of Web Push subscriptions, i.e. `PushSubscription.toJSON().keys`.
+  pub p256dh: Option<String>,
+  pub auth: Option<String>,
+}
+
+/// Register a device of the current user for receiving push notifications.
+///
+/// Registering a token again, e.g. after signing in as a different user, moves it to the current
+/// user.
+#[utoipa::path(
+  post,
+  path = "/devices",
+  tag = "notifications",
+  request_body = RegisterDeviceRequest,
+  responses(
+    (status = 200, description = "Device registered."),
+    (status = 400, description = "Invalid device."),
+    (status = 401, description = "Unauthorized."),
+  )
+)]
+pub async fn register_device_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<RegisterDeviceRequest>,
+) -> Result<Response, AuthError> {
+  // Devices belong to people rather than services.
+  if user.api_key.is_some() {
+    return Err(AuthError::Forbidden);
+  }
+  validate_device(&request)?;
+
+  const QUERY: &str = formatcp!(
+    "\
+      INSERT INTO '{PUSH_DEVICES_TABLE}' (user, platform, token, p256dh, auth) \
+      VALUES ($1, $2, $3, $4, $5) \
+      ON CONFLICT (token) DO UPDATE SET \
+        user = excluded.user, platform = excluded.platform, p256dh = excluded.p256dh, \
+        auth = excluded.auth, updated = UNIXEPOCH() \
+    "
+  );
+
+  state
+    .user_conn()
+    .execute(
+      QUERY,
+      params!(
+        user.uuid.into_bytes(),
+        request.platform.name().to_string(),
+        request.token,
+        request.p256dh,
+        request.auth,
+      ),
+    )
+    .await?;
+
+  return Ok((StatusCode::OK, "registered").into_response());
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct UnregisterDeviceRequest {
+  pub token: String,
+}
+
+/// Unregister a device of the current user, e.g. when signing out.
+#[utoipa::path(
+  delete,
+  path = "/devices",
+  tag = "notifications",
+  request_body = UnregisterDeviceRequest,
+  responses(
+    (status = 200, description = "Device unregistered."),
+    (status = 401, description = "Unauthorized."),
+    (status = 404, description = "Device not found."),
+  )
+)]
+pub async fn unregister_device_handler(
+  State(state): State<AppState>,
+  user: User,
+  Json(request): Json<UnregisterDeviceRequest>,
+) -> Result<Response, AuthError> {
+  const QUERY: &str =
+    formatcp!("DELETE FROM '{PUSH_DEVICES_TABLE}' WHERE token = $1 AND user = $2");
+
+  let rows_affected = state
+    .user_conn()
+    .execute(QUERY, params!(request.token, user.uuid.into_bytes()))
+    .await?;
+  if rows_affected == 0 {
+    return Err(AuthError::NotFound);
+  }
+
+  return Ok((StatusCode::OK, "unregistered").into_response());
+}
+
+/// Subscribe the current user to a topic.
+#[utoipa::path(
+  post,
+  path = "/topics/{topic}",
+  tag = "notifications",
+  responses(
+    (status = 200, description = "Subscribed."),
+    (status = 400, description = "Invalid topic."),
+    (status = 401, description = "Unauthorized."),
+  )
+)]
+pub async fn subscribe_topic_handler(
+  State(state): State<AppState>,
+  Path(topic): Path<String>,
+  user: User,
+) -> Result<Response, AuthError> {
+  if user.api_key.is_some() {
+    return Err(AuthError::Forbidden);
+  }
+  validate_topic(&topic)?;
+
+  const QUERY: &str =
+    formatcp!("INSERT OR IGNORE INTO '{PUSH_TOPICS_TABLE}' (topic, user) VALUES ($1, $2)");
+
+  state
+    .user_conn()
+    .execute(QUERY, params!(topic, user.uuid.into_bytes()))
+    .await?;
+
+  return Ok((StatusCode::OK, "subscribed").into_response());
+}
+
+/// Unsubscribe the current user from a topic.
+#[utoipa::path(
+  delete,
+  path = "/topics/{topic}",
+  tag = "notifications",
+  responses(
+    (status = 200, description = "Unsubscribed."),
+    (status = 401, description = "Unauthorized."),
+  )
+)]
+pub async fn unsubscribe_topic_handler(
+  State(state): State<AppState>,
+  Path(topic): Path<String>,
+  user: User,
+) -> Result<Response, AuthError> {
+  const QUERY: &str = formatcp!("DELETE FROM '{PUSH_TOPICS_TABLE}' WHERE topic = $1 AND user = $2");
+
+  state
+    .user_conn()
+    .execute(QUERY, params!(topic, user.uuid.into_bytes()))
+    .await?;
+
+  return Ok((StatusCode::OK, "unsubscribed").into_response());
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct VapidPublicKeyResponse {
+  /// URL-safe base64 encoded key to pass as `applicationServerKey` to
+  /// `PushManager.subscribe()`.
+  pub public_key: String,
+}
+
+/// Get the VAPID public key browsers need for subscribing to Web Push.
+#[utoipa::path(
+  get,
+  path = "/web_push/vapid_public_key",
+  tag = "notifications",
+  responses(
+    (status = 200, description = "VAPID public key.", body = VapidPublicKeyResponse),
+    (status = 404, description = "Web Push not configured."),
+  )
+)]
+pub async fn vapid_public_key_handler(
+  State(state): State<AppState>,
+) -> Result<Json<VapidPublicKeyResponse>, AuthError> {
+  let Some(public_key) = state.push_providers().vapid_public_key() else {
+    return Err(AuthError::NotFound);
+  };
+  return Ok(Json(VapidPublicKeyResponse { public_key }));
+}
+
+fn validate_device(request: &RegisterDeviceRequest) -> Result<(), AuthError> {
+  if request.token.is_empty() || request.token.len() > MAX_TOKEN_LENGTH {
+    return Err(AuthError::BadRequest("invalid token"));
+  }
+
+  if request.platform == Platform::WebPush {
+    // Web Push endpoints are requested by the server, so only accept push services, i.e. public
+    // HTTPS endpoints, and not e.g. internal services.
+    let valid_endpoint = url::Url::parse(&request.token).is_ok_and(|url| {
+      return url.scheme() == "https"
+        && matches!(url.host(), Some(url::Host::Domain(domain)) if domain != "localhost");
+    });
+    if !valid_endpoint {
+      return Err(AuthError::BadRequest("invalid endpoint"));
+    }
+    if request.p256dh.is_none() || request.auth.is_none() {
+      return Err(AuthError::BadRequest("missing subscription keys"));
+    }
+  }
+
+  return Ok(());
+}
+
+fn validate_topic(topic: &str) -> Result<(), AuthError> {
+  let valid = !topic.is_empty()
+    && topic.len() <= MAX_TOPIC_LENGTH
+    && topic
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+  if !valid {
+    return Err(AuthError::BadRequest("invalid topic"));
+  }
+  return Ok(());
+}
+
+/// Who to send a notification to.
+#[derive(Clone, Debug)]
+pub(crate) enum Recipients {
+  Users(Vec<Uuid>),
+  Topic(String),
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct SendNotificationResponse {
+  /// Id shared by all deliveries of the notification.
+  pub notification_id: String,
+  pub sent: usize,
+  pub failed: usize,
+  /// Devices that are no longer registered with their push service and have been removed.
+  pub unregistered: usize,
+}
+
+/// Sends a notification to all devices of the given recipients and records the outcomes.
+pub(crate) async fn send_notification(
+  state: &AppState,
+  recipients: Recipients,
+  notification: &Notification,
+) -> Result<SendNotificationResponse, trailbase_sqlite::Error> {
+  let devices = load_devices(state, recipients).await?;
+  let providers = state.push_providers();
+
+  let outcomes: Vec<(Device, Result<(), PushError>)> = futures_util::stream::iter(devices)
+    .map(|device| {
+      let providers = &providers;
+      async move {
+        let result = match Platform::from_name(&device.platform) {
+          Some(platform) => match providers.get(platform) {
+            Some(provider) => provider.send(&device, notification).await,
+            None => Err(PushError::Missing("provider config")),
+          },
+          None => Err(PushError::Missing("platform")),
+        };
+        (device, result)
+      }
+    })
+    .buffer_unordered(CONCURRENCY)
+    .collect()
+    .await;
+
+  let mut response = SendNotificationResponse {
+    notification_id: Uuid::now_v7().to_string(),
+    ..Default::default()
+  };
+
+  for (device, result) in outcomes {
+    let status = match result {
+      Ok(()) => {
+        response.sent += 1;
+        "sent"
+      }
+      Err(PushError::Unregistered) => {
+        const QUERY: &str = formatcp!("DELETE FROM '{PUSH_DEVICES_TABLE}' WHERE id = $1");
+        state.user_conn().execute(QUERY, params!(device.id)).await?;
+
+        response.unregistered += 1;
+        "unregistered"
+      }
+      Err(ref err) => {
+        debug!("Failed to push to device {}: {err}", device.id);
+        response.failed += 1;
+        "failed"
+      }
+    };
+
+    const QUERY: &str = formatcp!(
+      "INSERT INTO '{PUSH_DELIVERIES_TABLE}' (notification, user, device, platform, title, status, error) \
+       VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    );
+    state
+      .logs_conn()
+      .execute(
+        QUERY,
+        params!(
+          response.notification_id.clone(),
+          device.user,
+          device.id,
+          device.platform,
+          notification.title.clone(),
+          status.to_string(),
+          result.err().map(|err| err.to_string()),
+        ),
+      )
+      .await?;
+  }
+
+  return Ok(response);
+}
+
+async fn load_devices(
+  state: &AppState,
+  recipients: Recipients,
+) -> Result<Vec<Device>, trailbase_sqlite::Error> {
+  const COLUMNS: &str = "id, user, platform, token, p256dh, auth";
+
+  return match recipients {
+    Recipients::Users(user_ids) => {
+      const QUERY: &str = formatcp!("SELECT {COLUMNS} FROM '{PUSH_DEVICES_TABLE}' WHERE user = $1");
+
+      let mut devices = vec![];
+      for user_id in user_ids {
+        devices.extend(
+          state
+            .user_conn()
+            .read_query_values::<Device>(QUERY, params!(user_id.into_bytes()))
+            .await?,
+        );
+      }
+      Ok(devices)
+    }
+    Recipients::Topic(topic) => {
+      const QUERY: &str = formatcp!(
+        "SELECT {COLUMNS} FROM '{PUSH_DEVICES_TABLE}' WHERE user IN (SELECT user FROM '{PUSH_TOPICS_TABLE}' WHERE topic = $1)"
+      );
+
+      state
+        .user_conn()
+        .read_query_values::<Device>(QUERY, params!(topic))
+        .await
+    }
+  };
+}
+
+const MAX_TOKEN_LENGTH: usize = 4096;
+const MAX_TOPIC_LENGTH: usize = 128;
+/// Maximum number of concurrent requests to push services.
+const CONCURRENCY: usize = 16;
+
+#[cfg(test)]
+mod tests {
+  use axum::http::HeaderMap;
+  use base64::prelude::*;
+  use p256::SecretKey;
+  use p256::elliptic_curve::sec1::ToEncodedPoint;
+  use p256::pkcs8::{EncodePrivateKey, LineEnding};
+  use parking_lot::Mutex;
+  use rand::Rng;
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::admin::user::create_user_for_test;
+  use crate::app_state::{TestStateOptions, test_state};
+  use crate::auth::util::{UserIdentifier, login_with_password_for_test};
+  use crate::config::proto::{PushConfig, WebPushConfig};
+
+  #[derive(Clone, Default)]
+  struct PushService {
+    requests: Arc<Mutex<Vec<(String, HeaderMap, usize)>>>,
+  }
+
+  async fn push_handler(
+    State(service): State<PushService>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+  ) -> StatusCode {
+    service
+      .requests
+      .lock()
+      .push((id.clone(), headers, body.len()));
+    if id == "gone" {
+      return StatusCode::GONE;
+    }
+    return StatusCode::CREATED;
+  }
+
+  fn random_key() -> SecretKey {
+    let mut bytes = [0u8; 32];
+    loop {
+      rand::rng().fill_bytes(&mut bytes);
+      if let Ok(key) = SecretKey::from_slice(&bytes) {
+        return key;
+      }
+    }
+  }
+
+  #[tokio::test]
+  async fn test_web_push_notifications() {
+    let service = PushService::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = Router::new()
+      .route("/push/{id}", post(push_handler))
+      .with_state(service.clone());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let vapid_key = random_key();
+    let mut config = crate::app_state::test_config();
+    config.push = Some(PushConfig {
+      web_push: Some(WebPushConfig {
+        vapid_private_key: Some(vapid_key.to_pkcs8_pem(LineEnding::LF).unwrap().to_string()),
+        subject: Some("mailto:admin@test.org".to_string()),
+      }),
+      ..Default::default()
+    });
+
+    let state = test_state(Some(TestStateOptions {
+      config: Some(config),
+      ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    let vapid_public_key = vapid_public_key_handler(State(state.clone()))
+      .await
+      .unwrap()
+      .0
+      .public_key;
+    assert_eq!(
+      BASE64_URL_SAFE_NO_PAD.decode(&vapid_public_key).unwrap(),
+      vapid_key.public_key().to_encoded_point(false).as_bytes()
+    );
+
+    let password = "Secret!1!!";
+    let alice = create_user_for_test(&state, "alice@test.org", password)
+      .await
+      .unwrap();
+    let bob = create_user_for_test(&state, "bob@test.org", password)
+      .await
+      .unwrap();
+    let tokens = login_with_password_for_test(
+      &state,
+      UserIdentifier::Email("alice@test.org".to_string()),
+      password,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let user = User::from_auth_token(&state, &tokens.auth_token).unwrap();
+
+    let ua_key = random_key();
+    let p256dh =
+      BASE64_URL_SAFE_NO_PAD.encode(ua_key.public_key().to_encoded_point(false).as_bytes());
+    let auth = BASE64_URL_SAFE_NO_PAD.encode([7u8; 16]);
+
+    // Endpoints must be public HTTPS push services.
+    for endpoint in [
+      "http://push.test.org/abc",
+      "https://localhost/abc",
+      "https://127.0.0.1/abc",
+    ] {
+      let response = register_device_handler(
+        State(state.clone()),
+        user.clone(),
+        Json(RegisterDeviceRequest {
+          platform: Platform::WebPush,
+          token: endpoint.to_string(),
+          p256dh: Some(p256dh.clone()),
+          auth: Some(auth.clone()),
+        }),
+      )
+      .await;
+      assert!(
+        matches!(response, Err(AuthError::BadRequest(_))),
+        "{endpoint}"
+      );
+    }
+
+    register_device_handler(
+      State(state.clone()),
+      user.clone(),
+      Json(RegisterDeviceRequest {
+        platform: Platform::WebPush,
+        token: "https://push.test.org/abc".to_string(),
+        p256dh: Some(p256dh.clone()),
+        auth: Some(auth.clone()),
+      }),
+    )
+    .await
+    .unwrap();
+    unregister_device_handler(
+      State(state.clone()),
+      user.clone(),
+      Json(UnregisterDeviceRequest {
+        token: "https://push.test.org/abc".to_string(),
+      }),
+    )
+    .await
+    .unwrap();
+
+    assert!(matches!(
+      subscribe_topic_handler(
+        State(state.clone()),
+        Path("not a topic".to_string()),
+        user.clone()
+      )
+      .await,
+      Err(AuthError::BadRequest(_))
+    ));
+    subscribe_topic_handler(State(state.clone()), Path("news".to_string()), user.clone())
+      .await
+      .unwrap();
+
+    // Bypass the endpoint validation to point devices at the local push service.
+    for id in ["ok", "gone"] {
+      state
+        .user_conn()
+        .execute(
+          format!(
+            "INSERT INTO '{PUSH_DEVICES_TABLE}' (user, platform, token, p256dh, auth) VALUES ($1, 'web_push', $2, $3, $4)"
+          ),
+          params!(
+            alice.into_bytes(),
+            format!("http://{addr}/push/{id}"),
+            p256dh.clone(),
+            auth.clone(),
+          ),
+        )
+        .await
+        .unwrap();
+    }
+
+    let notification = Notification {
+      title: "Hello".to_string(),
+      body: "World".to_string(),
+      data: None,
+    };
+
+    let response = send_notification(&state, Recipients::Topic("news".to_string()), &notification)
+      .await
+      .unwrap();
+    assert_eq!(
+      (response.sent, response.failed, response.unregistered),
+      (1, 0, 1)
+    );
+
+    {
+      let requests = service.requests.lock();
+      assert_eq!(requests.len(), 2);
+      for (_id, headers, len) in requests.iter() {
+        assert!(
+          headers["authorization"]
+            .to_str()
+            .unwrap()
+            .starts_with("vapid t=")
+        );
+        assert_eq!(headers["content-encoding"], "aes128gcm");
+        assert!(*len > 0);
+      }
+    }
+
+    // The unregistered device was removed and deliveries were logged.
+    let devices: i64 = state
+      .user_conn()
+      .read_query_row_get(
+        format!("SELECT COUNT(*) FROM '{PUSH_DEVICES_TABLE}'"),
+        (),
+        0,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(devices, 1);
+
+    let deliveries: i64 = state
+      .logs_conn()
+      .read_query_row_get(
+        format!("SELECT COUNT(*) FROM '{PUSH_DELIVERIES_TABLE}' WHERE notification = $1"),
+        params!(response.notification_id.clone()),
+        0,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(deliveries, 2);
+
+    // Bob has no devices.
+    let response = send_notification(&state, Recipients::Users(vec![bob]), &notification)
+      .await
+      .unwrap();
+    assert_eq!(
+      (response.sent, response.failed, response.unregistered),
+      (0, 0, 0)
+    );
+
+    unsubscribe_topic_handler(State(state.clone()), Path("news".to_string()), user)
+      .await
+      .unwrap();
+    let response = send_notification(&state, Recipients::Topic("news".to_string()), &notification)
+      .await
+      .unwrap();
+    assert_eq!(response.sent, 0);
+  }
+}
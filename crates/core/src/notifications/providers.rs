@@ -0,0 +1,436 @@
+//! Push notification providers: Firebase Cloud Messaging, Apple Push Notification service and
+//! Web Push.
+
+use async_trait::async_trait;
+use base64::prelude::*;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use p256::SecretKey;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::pkcs8::{DecodePrivateKey, EncodePrivateKey};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use crate::config::proto::{ApnsConfig, FcmConfig, PushConfig, WebPushConfig};
+use crate::notifications::{Device, Notification, Platform, PushError, webpush};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+fn build_client() -> Result<reqwest::Client, PushError> {
+  return Ok(reqwest::Client::builder().timeout(TIMEOUT).build()?);
+}
+
+async fn api_error(response: reqwest::Response) -> PushError {
+  return PushError::Api {
+    status: response.status().as_u16(),
+    message: response.text().await.unwrap_or_default(),
+  };
+}
+
+/// Delivers notifications to devices of a specific platform.
+#[async_trait]
+pub(crate) trait PushProvider: Send + Sync {
+  async fn send(&self, device: &Device, notification: &Notification) -> Result<(), PushError>;
+}
+
+/// Providers for all configured platforms.
+#[derive(Default)]
+pub(crate) struct PushProviders {
+  fcm: Option<FcmProvider>,
+  apns: Option<ApnsProvider>,
+  web_push: Option<WebPushProvider>,
+}
+
+impl PushProviders {
+  pub(crate) fn get(&self, platform: Platform) -> Option<&dyn PushProvider> {
+    return match platform {
+      Platform::Fcm => self.fcm.as_ref().map(|p| p as &dyn PushProvider),
+      Platform::Apns => self.apns.as_ref().map(|p| p as &dyn PushProvider),
+      Platform::WebPush => self.web_push.as_ref().map(|p| p as &dyn PushProvider),
+    };
+  }
+
+  /// VAPID public key browsers need for subscribing, if Web Push is configured.
+  pub(crate) fn vapid_public_key(&self) -> Option<String> {
+    return self
+      .web_push
+      .as_ref()
+      .map(|p| BASE64_URL_SAFE_NO_PAD.encode(&p.public_key));
+  }
+}
+
+/// Builds providers for all configured platforms. Invalid configurations are logged and skipped.
+pub(crate) fn build_push_providers(config: Option<&PushConfig>) -> PushProviders {
+  let Some(config) = config else {
+    return PushProviders::default();
+  };
+
+  fn build<T>(name: &str, result: Option<Result<T, PushError>>) -> Option<T> {
+    return match result? {
+      Ok(provider) => Some(provider),
+      Err(err) => {
+        log::error!("Failed to set up {name} push notifications: {err}");
+        None
+      }
+    };
+  }
+
+  return PushProviders {
+    fcm: build("FCM", config.fcm.as_ref().map(FcmProvider::new)),
+    apns: build("APNs", config.apns.as_ref().map(ApnsProvider::new)),
+    web_push: build(
+      "Web Push",
+      config.web_push.as_ref().map(WebPushProvider::new),
+    ),
+  };
+}
+
+/// Caches short-lived credentials, e.g. OAuth access tokens or signed JWTs.
+#[derive(Default)]
+struct CachedToken(Mutex<Option<(String, Instant)>>);
+
+impl CachedToken {
+  fn get(&self) -> Option<String> {
+    return match *self.0.lock() {
+      Some((ref token, expires)) if expires > Instant::now() => Some(token.clone()),
+      _ => None,
+    };
+  }
+
+  fn set(&self, token: &str, ttl: Duration) {
+    *self.0.lock() = Some((token.to_string(), Instant::now() + ttl));
+  }
+}
+
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+  project_id: String,
+  client_email: String,
+  private_key: String,
+  token_uri: String,
+}
+
+/// Firebase Cloud Messaging using the HTTP v1 API.
+pub(crate) struct FcmProvider {
+  client: reqwest::Client,
+  url: String,
+  client_email: String,
+  token_uri: String,
+  key: EncodingKey,
+  access_token: CachedToken,
+}
+
+impl FcmProvider {
+  fn new(config: &FcmConfig) -> Result<Self, PushError> {
+    let account: ServiceAccountKey = serde_json::from_str(
+      config
+        .service_account_key
+        .as_deref()
+        .ok_or(PushError::Missing("FCM service account key"))?,
+    )
+    .map_err(|err| PushError::Config(format!("Invalid FCM service account key: {err}")))?;
+
+    let endpoint = config
+      .endpoint
+      .as_deref()
+      .unwrap_or("https://fcm.googleapis.com")
+      .trim_end_matches('/');
+
+    return Ok(Self {
+      client: build_client()?,
+      url: format!(
+        "{endpoint}/v1/projects/{}/messages:send",
+        account.project_id
+      ),
+      key: EncodingKey::from_rsa_pem(account.private_key.as_bytes())?,
+      client_email: account.client_email,
+      token_uri: account.token_uri,
+      access_token: CachedToken::default(),
+    });
+  }
+
+  /// Exchanges a self-signed JWT for an OAuth access token, see
+  /// https://developers.google.com/identity/protocols/oauth2/service-account.
+  async fn access_token(&self) -> Result<String, PushError> {
+    if let Some(token) = self.access_token.get() {
+      return Ok(token);
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let assertion = jsonwebtoken::encode(
+      &Header::new(Algorithm::RS256),
+      &serde_json::json!({
+        "iss": self.client_email,
+        "scope": "https://www.googleapis.com/auth/firebase.messaging",
+        "aud": self.token_uri,
+        "iat": now,
+        "exp": now + 3600,
+      }),
+      &self.key,
+    )?;
+
+    let mut form = url::form_urlencoded::Serializer::new(String::new());
+    form
+      .append_pair("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer")
+      .append_pair("assertion", &assertion);
+
+    let response = self
+      .client
+      .post(&self.token_uri)
+      .header("content-type", "application/x-www-form-urlencoded")
+      .body(form.finish())
+      .send()
+      .await?;
+    if !response.status().is_success() {
+      return Err(api_error(response).await);
+    }
+
+    #[derive(Deserialize)]
+    struct TokenResponse {
+      access_token: String,
+      expires_in: u64,
+    }
+
+    let token: TokenResponse = response.json().await?;
+    // Refresh early to not race the expiry.
+    self.access_token.set(
+      &token.access_token,
+      Duration::from_secs(token.expires_in.saturating_sub(60)),
+    );
+
+    return Ok(token.access_token);
+  }
+}
+
+#[async_trait]
+impl PushProvider for FcmProvider {
+  async fn send(&self, device: &Device, notification: &Notification) -> Result<(), PushError> {
+    let mut message = serde_json::json!({
+      "token": device.token,
+      "notification": {
+        "title": notification.title,
+        "body": notification.body,
+      },
+    });
+    if let Some(ref data) = notification.data {
+      message["data"] = serde_json::json!(data);
+    }
+
+    let response = self
+      .client
+      .post(&self.url)
+      .bearer_auth(self.access_token().await?)
+      .json(&serde_json::json!({ "message": message }))
+      .send()
+      .await?;
+
+    return match response.status().as_u16() {
+      200..300 => Ok(()),
+      // UNREGISTERED, e.g. the app was uninstalled.
+      404 => Err(PushError::Unregistered),
+      _ => Err(api_error(response).await),
+    };
+  }
+}
+
+/// Apple Push Notification service using token-based authentication.
+pub(crate) struct ApnsProvider {
+  client: reqwest::Client,
+  endpoint: String,
+  team_id: String,
+  key_id: String,
+  bundle_id: String,
+  key: EncodingKey,
+  token: CachedToken,
+}
+
+impl ApnsProvider {
+  fn new(config: &ApnsConfig) -> Result<Self, PushError> {
+    let require = |value: &Option<String>, name: &'static str| -> Result<String, PushError> {
+      return value.clone().ok_or(PushError::Missing(name));
+    };
+
+    let endpoint = match config.endpoint {
+      Some(ref endpoint) => endpoint.trim_end_matches('/').to_string(),
+      None if config.sandbox.unwrap_or(false) => "https://api.sandbox.push.apple.com".to_string(),
+      None => "https://api.push.apple.com".to_string(),
+    };
+
+    return Ok(Self {
+      // NOTE: APNs requires HTTP/2, which is negotiated via ALPN.
+      client: build_client()?,
+      endpoint,
+      team_id: require(&config.team_id, "APNs team id")?,
+      key_id: require(&config.key_id, "APNs key id")?,
+      bundle_id: require(&config.bundle_id, "APNs bundle id")?,
+      key: EncodingKey::from_ec_pem(require(&config.private_key, "APNs private key")?.as_bytes())?,
+      token: CachedToken::default(),
+    });
+  }
+
+  /// Provider tokens are valid for an hour but must not be refreshed more than every 20 minutes.
+  fn token(&self) -> Result<String, PushError> {
+    if let Some(token) = self.token.get() {
+      return Ok(token);
+    }
+
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some(self.key_id.clone());
+    let token = jsonwebtoken::encode(
+      &header,
+      &serde_json::json!({
+        "iss": self.team_id,
+        "iat": chrono::Utc::now().timestamp(),
+      }),
+      &self.key,
+    )?;
+
+    self.token.set(&token, Duration::from_secs(50 * 60));
+    return Ok(token);
+  }
+}
+
+#[async_trait]
+impl PushProvider for ApnsProvider {
+  async fn send(&self, device: &Device, notification: &Notification) -> Result<(), PushError> {
+    let mut payload = serde_json::json!({
+      "aps": {
+        "alert": {
+          "title": notification.title,
+          "body": notification.body,
+        },
+        "sound": "default",
+      },
+    });
+    // Custom data goes next to the reserved "aps" key.
+    for (key, value) in notification.data.iter().flatten() {
+      if key != "aps" {
+        payload[key] = serde_json::Value::String(value.clone());
+      }
+    }
+
+    let response = self
+      .client
+      .post(format!("{}/3/device/{}", self.endpoint, device.token))
+      .bearer_auth(self.token()?)
+      .header("apns-topic", &self.bundle_id)
+      .header("apns-push-type", "alert")
+      .json(&payload)
+      .send()
+      .await?;
+
+    let status = response.status().as_u16();
+    if (200..300).contains(&status) {
+      return Ok(());
+    }
+
+    let message = response.text().await.unwrap_or_default();
+    #[derive(Deserialize)]
+    struct ErrorResponse {
+      reason: String,
+    }
+    let reason = serde_json::from_str::<ErrorResponse>(&message).map(|r| r.reason);
+
+    return match (status, reason.as_deref()) {
+      (410, _) | (400, Ok("BadDeviceToken")) => Err(PushError::Unregistered),
+      _ => Err(PushError::Api { status, message }),
+    };
+  }
+}
+
+/// Web Push with VAPID authentication, see RFC 8030 and RFC 8292.
+pub(crate) struct WebPushProvider {
+  client: reqwest::Client,
+  key: EncodingKey,
+  /// Uncompressed public key corresponding to `key`.
+  public_key: Vec<u8>,
+  subject: String,
+}
+
+impl WebPushProvider {
+  fn new(config: &WebPushConfig) -> Result<Self, PushError> {
+    let pem = config
+      .vapid_private_key
+      .as_deref()
+      .ok_or(PushError::Missing("VAPID private key"))?;
+    // Accept both PKCS#8 and SEC1 keys, e.g. as generated by `openssl ecparam`.
+    let secret = SecretKey::from_pkcs8_pem(pem)
+      .or_else(|_| SecretKey::from_sec1_pem(pem))
+      .map_err(|err| PushError::Config(format!("Invalid VAPID private key: {err}")))?;
+    let der = secret
+      .to_pkcs8_der()
+      .map_err(|err| PushError::Config(format!("Invalid VAPID private key: {err}")))?;
+
+    return Ok(Self {
+      client: build_client()?,
+      key: EncodingKey::from_ec_der(der.as_bytes()),
+      public_key: secret
+        .public_key()
+        .to_encoded_point(false)
+        .as_bytes()
+        .to_vec(),
+      subject: config
+        .subject
+        .clone()
+        .ok_or(PushError::Missing("Web Push subject"))?,
+    });
+  }
+}
+
+#[async_trait]
+impl PushProvider for WebPushProvider {
+  async fn send(&self, device: &Device, notification: &Notification) -> Result<(), PushError> {
+    let (Some(p256dh), Some(auth)) = (&device.p256dh, &device.auth) else {
+      return Err(PushError::Unregistered);
+    };
+    let decode = |key: &str| {
+      return BASE64_URL_SAFE_NO_PAD
+        .decode(key.trim_end_matches('='))
+        .map_err(|_| PushError::Unregistered);
+    };
+
+    let body = webpush::encrypt(
+      &serde_json::to_vec(notification).map_err(|err| PushError::Config(err.to_string()))?,
+      &decode(p256dh)?,
+      &decode(auth)?,
+    )?;
+
+    let endpoint = url::Url::parse(&device.token).map_err(|_| PushError::Unregistered)?;
+    let jwt = jsonwebtoken::encode(
+      &Header::new(Algorithm::ES256),
+      &serde_json::json!({
+        "aud": endpoint.origin().ascii_serialization(),
+        "exp": chrono::Utc::now().timestamp() + 12 * 3600,
+        "sub": self.subject,
+      }),
+      &self.key,
+    )?;
+
+    let response = self
+      .client
+      .post(endpoint)
+      .header(
+        "authorization",
+        format!(
+          "vapid t={jwt}, k={}",
+          BASE64_URL_SAFE_NO_PAD.encode(&self.public_key)
+        ),
+      )
+      .header("content-encoding", "aes128gcm")
+      .header("content-type", "application/octet-stream")
+      .header("ttl", TTL_SEC.to_string())
+      .body(body)
+      .send()
+      .await?;
+
+    return match response.status().as_u16() {
+      200..300 => Ok(()),
+      // The subscription expired or was revoked.
+      404 | 410 => Err(PushError::Unregistered),
+      _ => Err(api_error(response).await),
+    };
+  }
+}
+
+/// How long push services should retain undelivered notifications, e.g. for offline devices.
+const TTL_SEC: u64 = 24 * 3600;
@@ -0,0 +1,141 @@
+//! Message encryption for Web Push, see RFC 8291 and the "aes128gcm" content coding of RFC 8188.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use hmac::{Hmac, Mac};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use rand::Rng;
+use sha2::Sha256;
+
+use crate::notifications::PushError;
+
+/// Push services are only required to accept payloads up to 4096 bytes including the header,
+/// padding and authentication tag.
+const MAX_RECORD_SIZE: usize = 4096;
+const HEADER_SIZE: usize = 16 + 4 + 1 + 65;
+const TAG_SIZE: usize = 16;
+
+/// Encrypts the payload for the subscription identified by the user agent's public key and
+/// authentication secret, both as provided by `PushSubscription.getKey()`.
+pub(crate) fn encrypt(
+  payload: &[u8],
+  ua_public: &[u8],
+  auth_secret: &[u8],
+) -> Result<Vec<u8>, PushError> {
+  let mut rng = rand::rng();
+
+  let as_secret = loop {
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes);
+    // Rejects the astronomically unlikely zero or out-of-range scalars.
+    if let Ok(secret) = SecretKey::from_slice(&bytes) {
+      break secret;
+    }
+  };
+
+  let mut salt = [0u8; 16];
+  rng.fill_bytes(&mut salt);
+
+  return encrypt_with_keys(payload, ua_public, auth_secret, &as_secret, &salt);
+}
+
+fn encrypt_with_keys(
+  payload: &[u8],
+  ua_public: &[u8],
+  auth_secret: &[u8],
+  as_secret: &SecretKey,
+  salt: &[u8; 16],
+) -> Result<Vec<u8>, PushError> {
+  if HEADER_SIZE + payload.len() + 1 + TAG_SIZE > MAX_RECORD_SIZE {
+    return Err(PushError::Encryption("payload too large"));
+  }
+
+  let ua_key =
+    PublicKey::from_sec1_bytes(ua_public).map_err(|_| PushError::Encryption("invalid p256dh"))?;
+  let as_public = as_secret.public_key().to_encoded_point(false);
+  let as_public = as_public.as_bytes();
+
+  let shared_secret = p256::ecdh::diffie_hellman(as_secret.to_nonzero_scalar(), ua_key.as_affine());
+
+  let key_info = [b"WebPush: info\0".as_slice(), ua_public, as_public].concat();
+  let ikm = hkdf(auth_secret, shared_secret.raw_secret_bytes(), &key_info, 32);
+  let cek = hkdf(salt, &ikm, b"Content-Encoding: aes128gcm\0", 16);
+  let nonce = hkdf(salt, &ikm, b"Content-Encoding: nonce\0", 12);
+
+  // A single record terminated by the last-record delimiter w/o further padding.
+  let mut plaintext = Vec::with_capacity(payload.len() + 1);
+  plaintext.extend_from_slice(payload);
+  plaintext.push(2);
+
+  let cipher = Aes128Gcm::new_from_slice(&cek).map_err(|_| PushError::Encryption("key"))?;
+  let ciphertext = cipher
+    .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+    .map_err(|_| PushError::Encryption("encrypt"))?;
+
+  let mut body = Vec::with_capacity(HEADER_SIZE + ciphertext.len());
+  body.extend_from_slice(salt);
+  body.extend_from_slice(&(MAX_RECORD_SIZE as u32).to_be_bytes());
+  body.push(as_public.len() as u8);
+  body.extend_from_slice(as_public);
+  body.extend_from_slice(&ciphertext);
+
+  return Ok(body);
+}
+
+/// HKDF with SHA-256, see RFC 5869, for outputs of at most 32 bytes.
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+  debug_assert!(len <= 32);
+
+  let prk = hmac_sha256(salt, &[ikm]);
+  let mut okm = hmac_sha256(&prk, &[info, &[1]]);
+  okm.truncate(len);
+  return okm;
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length");
+  for part in parts {
+    mac.update(part);
+  }
+  return mac.finalize().into_bytes().to_vec();
+}
+
+#[cfg(test)]
+mod tests {
+  use base64::prelude::*;
+
+  use super::*;
+
+  #[test]
+  fn test_encrypt_rfc8291_example() {
+    // Example from RFC 8291, Section 5.
+    let decode = |s: &str| BASE64_URL_SAFE_NO_PAD.decode(s).unwrap();
+
+    let as_secret =
+      SecretKey::from_slice(&decode("yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw")).unwrap();
+    let ua_public = decode(
+      "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+    );
+    let auth_secret = decode("BTBZMqHH6r4Tts7J_aSIgg");
+    let salt: [u8; 16] = decode("DGv6ra1nlYgDCS1FRnbzlw").try_into().unwrap();
+
+    let body = encrypt_with_keys(
+      b"When I grow up, I want to be a watermelon",
+      &ua_public,
+      &auth_secret,
+      &as_secret,
+      &salt,
+    )
+    .unwrap();
+
+    assert_eq!(
+      BASE64_URL_SAFE_NO_PAD.encode(body),
+      "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN"
+    );
+
+    let random = encrypt(b"payload", &ua_public, &auth_secret).unwrap();
+    assert_eq!(random.len(), HEADER_SIZE + b"payload".len() + 1 + TAG_SIZE);
+    assert!(encrypt(&[0; MAX_RECORD_SIZE], &ua_public, &auth_secret).is_err());
+  }
+}
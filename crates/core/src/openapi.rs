@@ -0,0 +1,364 @@
+//! OpenAPI definitions.
+//!
+//! [Doc] describes the generic APIs independent of any deployment, e.g. for `trail openapi print`.
+//! [build_deployment_doc] additionally describes every configured Record API with concrete paths
+//! and request/response schemas derived from the API's JSON schemas, enabling accurate client
+//! code generation.
+
+use axum::{Json, extract::State};
+use trailbase_schema::json_schema::JsonSchemaMode;
+use utoipa::OpenApi;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::constants::RECORD_API_PATH;
+use crate::records::json_schema::build_user_api_json_schema;
+use crate::records::{Permission, RecordApi, RecordError};
+
+#[derive(OpenApi)]
+#[openapi(
+      info(
+          title = "TrailBase",
+          description = "TrailBase APIs",
+      ),
+      nest(
+          (path = "/api/auth/v1", api = crate::auth::AuthApi),
+          (path = "/api/records/v1", api = crate::records::RecordOpenApi),
+          (path = "/api/query/v1", api = crate::records::saved_queries::SavedQueryOpenApi),
+          (path = "/api/sync/v1", api = crate::records::sync::SyncOpenApi),
+          (path = "/api/notifications/v1", api = crate::notifications::NotificationsApi),
+      ),
+      tags(),
+  )]
+pub struct Doc;
+
+/// Serves the deployment's OpenAPI document. Only Record APIs whose schema the user may access are
+/// included.
+pub(crate) async fn openapi_handler(
+  State(state): State<AppState>,
+  user: Option<User>,
+) -> Result<Json<serde_json::Value>, RecordError> {
+  return Ok(Json(build_deployment_doc(&state, user.as_ref()).await?));
+}
+
+/// Builds the generic [Doc] augmented with paths and component schemas for each configured Record
+/// API, i.e. `<name>_select`, `<name>_insert` and `<name>_update`.
+pub async fn build_deployment_doc(
+  state: &AppState,
+  user: Option<&User>,
+) -> Result<serde_json::Value, RecordError> {
+  let mut doc =
+    serde_json::to_value(Doc::openapi()).map_err(|err| RecordError::Internal(err.into()))?;
+
+  let mut paths = serde_json::Map::new();
+  let mut schemas = serde_json::Map::new();
+  for config in &state.get_config().record_apis {
+    // NOTE: Tenant-scoped APIs are described based on the tenant template's schema.
+    let Some(api) = state.lookup_base_record_api(config.name()) else {
+      continue;
+    };
+
+    match api
+      .check_record_level_access(Permission::Schema, None, None, user)
+      .await
+    {
+      Ok(()) => {}
+      Err(RecordError::Forbidden) => continue,
+      Err(err) => return Err(err),
+    };
+
+    add_record_api(state, &api, user, &mut paths, &mut schemas)?;
+  }
+
+  if let Some(doc) = doc.as_object_mut() {
+    if let serde_json::Value::Object(existing) =
+      doc.entry("paths").or_insert_with(|| serde_json::json!({}))
+    {
+      existing.extend(paths);
+    }
+
+    let components = doc
+      .entry("components")
+      .or_insert_with(|| serde_json::json!({}));
+    if let Some(components) = components.as_object_mut()
+      && let serde_json::Value::Object(existing) = components
+        .entry("schemas")
+        .or_insert_with(|| serde_json::json!({}))
+    {
+      existing.extend(schemas);
+    }
+  }
+
+  return Ok(doc);
+}
+
+fn add_record_api(
+  state: &AppState,
+  api: &RecordApi,
+  user: Option<&User>,
+  paths: &mut serde_json::Map<String, serde_json::Value>,
+  schemas: &mut serde_json::Map<String, serde_json::Value>,
+) -> Result<(), RecordError> {
+  let name = api.api_name();
+
+  let mut component = |mode: JsonSchemaMode, suffix: &str| -> Result<String, RecordError> {
+    let component_name = format!("{name}_{suffix}");
+    let schema = build_user_api_json_schema(state, api, mode, user)?;
+    add_component(schemas, &component_name, schema);
+    return Ok(format!("#/components/schemas/{component_name}"));
+  };
+
+  let select = component(JsonSchemaMode::Select, "select")?;
+  let tags = serde_json::json!([name]);
+  let record_param = serde_json::json!({
+    "name": "record",
+    "in": "path",
+    "required": true,
+    "description": "Record id, i.e. url-safe base64 encoded UUID or integer.",
+    "schema": {"type": "string"},
+  });
+
+  let mut collection = serde_json::json!({
+    "get": {
+      "tags": tags,
+      "operationId": format!("list_{name}"),
+      "summary": format!("List {name} records."),
+      "parameters": [
+        query_param("limit", "integer", "Maximum number of records."),
+        query_param("cursor", "string", "Cursor from a previous response."),
+        query_param("offset", "integer", "Number of records to skip."),
+        query_param("order", "string", "Comma separated columns, e.g. `-created,id`."),
+        query_param("count", "boolean", "Include the total count."),
+        query_param("select", "string", "Comma separated columns to return."),
+        query_param("expand", "string", "Comma separated foreign keys to expand."),
+      ],
+      "responses": {
+        "200": {
+          "description": "Listed records.",
+          "content": {"application/json": {"schema": {
+            "type": "object",
+            "properties": {
+              "cursor": {"type": "string"},
+              "total_count": {"type": "integer"},
+              "records": {"type": "array", "items": {"$ref": select}},
+            },
+            "required": ["records"],
+          }}},
+        },
+      },
+    },
+  });
+
+  let mut record = serde_json::json!({
+    "parameters": [record_param],
+    "get": {
+      "tags": tags,
+      "operationId": format!("read_{name}"),
+      "summary": format!("Read {name} record."),
+      "responses": {
+        "200": {
+          "description": "Record contents.",
+          "content": {"application/json": {"schema": {"$ref": select}}},
+        },
+      },
+    },
+  });
+
+  // VIEW-based APIs are read-only.
+  if api.is_table() {
+    let insert = component(JsonSchemaMode::Insert, "insert")?;
+    let update = component(JsonSchemaMode::Update, "update")?;
+
+    collection["post"] = serde_json::json!({
+      "tags": tags,
+      "operationId": format!("create_{name}"),
+      "summary": format!("Create {name} record(s)."),
+      "requestBody": {
+        "required": true,
+        "content": {"application/json": {"schema": {"oneOf": [
+          {"$ref": insert},
+          {"type": "array", "items": {"$ref": insert}},
+        ]}}},
+      },
+      "responses": {
+        "200": {
+          "description": "Ids of the created records.",
+          "content": {"application/json": {"schema": {
+            "type": "object",
+            "properties": {"ids": {"type": "array", "items": {"type": "string"}}},
+            "required": ["ids"],
+          }}},
+        },
+      },
+    });
+    record["patch"] = serde_json::json!({
+      "tags": tags,
+      "operationId": format!("update_{name}"),
+      "summary": format!("Update {name} record."),
+      "requestBody": {
+        "required": true,
+        "content": {"application/json": {"schema": {"$ref": update}}},
+      },
+      "responses": {"200": {"description": "Successful update."}},
+    });
+    record["delete"] = serde_json::json!({
+      "tags": tags,
+      "operationId": format!("delete_{name}"),
+      "summary": format!("Delete {name} record."),
+      "responses": {"200": {"description": "Successful deletion."}},
+    });
+  }
+
+  paths.insert(format!("/{RECORD_API_PATH}/{name}"), collection);
+  paths.insert(format!("/{RECORD_API_PATH}/{name}/{{record}}"), record);
+
+  return Ok(());
+}
+
+fn query_param(name: &str, r#type: &str, description: &str) -> serde_json::Value {
+  return serde_json::json!({
+    "name": name,
+    "in": "query",
+    "required": false,
+    "description": description,
+    "schema": {"type": r#type},
+  });
+}
+
+/// Adds a JSON schema as component. Local definitions are hoisted into separate components, since
+/// `#/$defs/<name>` references would otherwise resolve against the root of the OpenAPI document.
+fn add_component(
+  schemas: &mut serde_json::Map<String, serde_json::Value>,
+  name: &str,
+  mut schema: serde_json::Value,
+) {
+  let prefix = format!("#/components/schemas/{name}_");
+
+  if let Some(obj) = schema.as_object_mut() {
+    obj.remove("$schema");
+    if let Some(serde_json::Value::Object(defs)) = obj.remove("$defs") {
+      for (def_name, mut def) in defs {
+        rewrite_refs(&mut def, &prefix);
+        schemas.insert(format!("{name}_{}", sanitize(&def_name)), def);
+      }
+    }
+  }
+
+  rewrite_refs(&mut schema, &prefix);
+  schemas.insert(name.to_string(), schema);
+}
+
+fn rewrite_refs(value: &mut serde_json::Value, prefix: &str) {
+  match value {
+    serde_json::Value::Object(obj) => {
+      for (key, value) in obj.iter_mut() {
+        if key == "$ref"
+          && let serde_json::Value::String(reference) = value
+          && let Some(def_name) = reference.strip_prefix("#/$defs/")
+        {
+          let new_reference = format!("{prefix}{}", sanitize(def_name));
+          *reference = new_reference;
+          continue;
+        }
+        rewrite_refs(value, prefix);
+      }
+    }
+    serde_json::Value::Array(values) => {
+      for value in values {
+        rewrite_refs(value, prefix);
+      }
+    }
+    _ => {}
+  }
+}
+
+/// Component names may only contain `[a-zA-Z0-9._-]`.
+fn sanitize(name: &str) -> String {
+  return name
+    .chars()
+    .map(|c| {
+      if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::test_utils::add_record_api_config;
+
+  #[tokio::test]
+  async fn test_deployment_doc() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE articles ( \
+            id      INTEGER PRIMARY KEY, \
+            title   TEXT NOT NULL, \
+            image   TEXT CHECK(jsonschema('std.FileUpload', image)) \
+          ) STRICT; \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    for (name, acl) in [
+      ("articles", vec![PermissionFlag::Schema as i32]),
+      ("secret", vec![]),
+    ] {
+      add_record_api_config(
+        &state,
+        RecordApiConfig {
+          name: Some(name.to_string()),
+          table_name: Some("articles".to_string()),
+          acl_world: acl,
+          ..Default::default()
+        },
+      )
+      .await
+      .unwrap();
+    }
+
+    let doc = build_deployment_doc(&state, None).await.unwrap();
+
+    // Generic routes are retained.
+    assert!(doc["paths"]["/api/records/v1/{name}"].is_object());
+
+    let paths = &doc["paths"];
+    assert_eq!(
+      paths["/api/records/v1/articles"]["post"]["operationId"],
+      "create_articles"
+    );
+    assert_eq!(
+      paths["/api/records/v1/articles/{record}"]["get"]["responses"]["200"]["content"]["application/json"]
+        ["schema"]["$ref"],
+      "#/components/schemas/articles_select"
+    );
+    // Not accessible w/o schema permission.
+    assert!(paths.get("/api/records/v1/secret").is_none());
+
+    let schemas = &doc["components"]["schemas"];
+    assert_eq!(
+      schemas["articles_insert"]["properties"]["title"]["type"],
+      "string"
+    );
+
+    // Local definitions are hoisted into resolvable components.
+    let serialized = serde_json::to_string(&doc).unwrap();
+    assert!(!serialized.contains("#/$defs/"), "{serialized}");
+    let image_ref = schemas["articles_select"]["properties"]["image"]["$ref"]
+      .as_str()
+      .unwrap();
+    let image_component = image_ref.strip_prefix("#/components/schemas/").unwrap();
+    assert!(schemas[image_component].is_object(), "{image_ref}");
+  }
+}
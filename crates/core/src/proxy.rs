@@ -0,0 +1,588 @@
+//! Config-defined routes forwarding requests to upstream services.
+
+use axum::Router;
+use axum::body::{Body, HttpBody};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::any;
+use log::*;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use crate::app_state::AppState;
+use crate::auth::User;
+use crate::config::proto::ProxyRouteConfig;
+use crate::constants::{COOKIE_AUTH_TOKEN, COOKIE_OAUTH_STATE, COOKIE_REFRESH_TOKEN};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const HEADER_USER_ID: HeaderName = HeaderName::from_static("x-trailbase-user-id");
+const HEADER_USER_EMAIL: HeaderName = HeaderName::from_static("x-trailbase-user-email");
+const HEADER_USER_ROLES: HeaderName = HeaderName::from_static("x-trailbase-user-roles");
+const CLAIM_HEADER_PREFIX: &str = "x-trailbase-user-";
+const HEADER_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const HEADER_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+const HEADER_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Cookies owned by TrailBase, which upstreams must not be able to set, e.g. to fixate sessions.
+const TRAILBASE_COOKIES: [&str; 3] = [COOKIE_AUTH_TOKEN, COOKIE_REFRESH_TOKEN, COOKIE_OAUTH_STATE];
+
+/// Headers only meaningful for a single connection, which must not be forwarded.
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+  header::CONNECTION,
+  HeaderName::from_static("keep-alive"),
+  header::PROXY_AUTHENTICATE,
+  header::PROXY_AUTHORIZATION,
+  header::TE,
+  header::TRAILER,
+  header::TRANSFER_ENCODING,
+  header::UPGRADE,
+];
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+  reqwest::Client::builder()
+    // Pass redirects through to the client rather than following them.
+    .redirect(reqwest::redirect::Policy::none())
+    .build()
+    .expect("startup")
+});
+
+struct ProxyRoute {
+  path_prefix: String,
+  upstream_url: String,
+  /// Path of `upstream_url`, which forwarded requests must not escape.
+  upstream_path: String,
+  require_auth: bool,
+  allow_api_keys: bool,
+  forward_claims: bool,
+  forward_auth_headers: bool,
+  timeout: Duration,
+}
+
+/// Builds the router for all configured proxy routes.
+pub(crate) fn router(routes: &[ProxyRouteConfig]) -> Router<AppState> {
+  let mut router = Router::new();
+
+  for config in routes {
+    let (Some(path_prefix), Some(upstream_url)) = (&config.path_prefix, &config.upstream_url)
+    else {
+      warn!("Skipping incomplete proxy route: {config:?}");
+      continue;
+    };
+
+    let upstream_url = upstream_url.trim_end_matches('/').to_string();
+    let Ok(upstream_path) = url::Url::parse(&upstream_url).map(|url| url.path().to_string()) else {
+      warn!("Skipping proxy route with invalid upstream: {config:?}");
+      continue;
+    };
+
+    let route = Arc::new(ProxyRoute {
+      path_prefix: path_prefix.trim_end_matches('/').to_string(),
+      upstream_url,
+      upstream_path: upstream_path.trim_end_matches('/').to_string(),
+      require_auth: config.require_auth.unwrap_or(false),
+      allow_api_keys: config.allow_api_keys.unwrap_or(false),
+      forward_claims: config.forward_claims.unwrap_or(true),
+      forward_auth_headers: config.forward_auth_headers.unwrap_or(false),
+      timeout: config
+        .timeout_sec
+        .map_or(DEFAULT_TIMEOUT, Duration::from_secs),
+    });
+
+    let handler = {
+      let route = route.clone();
+      any(
+        move |State(state): State<AppState>, user: Option<User>, req: Request| {
+          let route = route.clone();
+          async move { proxy_handler(&state, &route, user, req).await }
+        },
+      )
+    };
+
+    router = router
+      .route(&route.path_prefix, handler.clone())
+      .route(&format!("{}/{{*rest}}", route.path_prefix), handler);
+  }
+
+  return router;
+}
+
+async fn proxy_handler(
+  state: &AppState,
+  route: &ProxyRoute,
+  user: Option<User>,
+  req: Request,
+) -> Response {
+  if route.require_auth {
+    let Some(ref user) = user else {
+      return StatusCode::UNAUTHORIZED.into_response();
+    };
+    // API keys are scoped to specific Record APIs and aren't meant to authorize arbitrary
+    // upstreams. On public routes they're harmless, since their claims are never forwarded.
+    if !route.allow_api_keys && user.api_key.is_some() {
+      return StatusCode::FORBIDDEN.into_response();
+    }
+  }
+
+  let (parts, body) = req.into_parts();
+
+  let rest = parts
+    .uri
+    .path()
+    .strip_prefix(&route.path_prefix)
+    .unwrap_or_default();
+  let Some(url) = upstream_url(route, rest, parts.uri.query()) else {
+    return StatusCode::BAD_REQUEST.into_response();
+  };
+
+  let mut headers = parts.headers;
+  strip_hop_by_hop_headers(&mut headers);
+  if !route.forward_auth_headers {
+    headers.remove(header::AUTHORIZATION);
+    headers.remove(header::COOKIE);
+  }
+  // Never trust claims sent by clients.
+  let spoofed: Vec<HeaderName> = headers
+    .keys()
+    .filter(|name| name.as_str().starts_with(CLAIM_HEADER_PREFIX))
+    .cloned()
+    .collect();
+  for name in spoofed {
+    headers.remove(name);
+  }
+
+  if let Some(host) = headers.remove(header::HOST) {
+    headers.insert(HEADER_FORWARDED_HOST, host);
+  }
+  set_forwarded_for(&mut headers, &parts.extensions);
+  let proto = forwarded_proto(state, &parts.uri, &headers);
+  headers.insert(HEADER_FORWARDED_PROTO, HeaderValue::from_static(proto));
+
+  // NOTE: API keys aren't users. Their ids must not be mistaken for user ids upstream.
+  if route.forward_claims
+    && let Some(ref user) = user
+    && user.api_key.is_none()
+  {
+    let claims = [
+      (HEADER_USER_ID, Some(user.id.clone())),
+      (HEADER_USER_EMAIL, user.email.clone()),
+      (
+        HEADER_USER_ROLES,
+        (!user.roles.is_empty()).then(|| user.roles.join(",")),
+      ),
+    ];
+    for (name, value) in claims {
+      if let Some(value) = value.and_then(|v| HeaderValue::try_from(v).ok()) {
+        headers.insert(name, value);
+      }
+    }
+  }
+
+  let mut request = CLIENT
+    .request(parts.method, &url)
+    .headers(headers)
+    .timeout(route.timeout);
+  // Avoid sending chunked empty bodies, e.g. for GET requests.
+  if !body.is_end_stream() {
+    request = request.body(reqwest::Body::wrap_stream(body.into_data_stream()));
+  }
+
+  let result = request.send().await;
+
+  let upstream_response = match result {
+    Ok(response) => response,
+    Err(err) => {
+      warn!("Proxying to {url} failed: {err}");
+      return if err.is_timeout() {
+        StatusCode::GATEWAY_TIMEOUT.into_response()
+      } else {
+        StatusCode::BAD_GATEWAY.into_response()
+      };
+    }
+  };
+
+  let mut response = Response::builder().status(upstream_response.status());
+  if let Some(response_headers) = response.headers_mut() {
+    *response_headers = upstream_response.headers().clone();
+    strip_hop_by_hop_headers(response_headers);
+    strip_trailbase_set_cookies(response_headers);
+  }
+
+  return response
+    .body(Body::from_stream(upstream_response.bytes_stream()))
+    .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response());
+}
+
+/// Joins the remainder of the request path onto the upstream URL. Returns None for paths that
+/// could escape the upstream's base path, e.g. using (encoded) dot segments or encoded slashes.
+fn upstream_url(route: &ProxyRoute, rest: &str, query: Option<&str>) -> Option<String> {
+  for segment in rest.split('/') {
+    let segment = segment.to_ascii_lowercase();
+    if segment.contains("%2f") || segment.contains("%5c") || segment.contains('\\') {
+      return None;
+    }
+    if matches!(segment.replace("%2e", ".").as_str(), "." | "..") {
+      return None;
+    }
+  }
+
+  let url = match query {
+    Some(query) => format!("{}{rest}?{query}", route.upstream_url),
+    None => format!("{}{rest}", route.upstream_url),
+  };
+
+  // Defense in depth: make sure normalization didn't take us outside the base path.
+  let parsed = url::Url::parse(&url).ok()?;
+  let path = parsed.path();
+  if path != route.upstream_path && !path.starts_with(&format!("{}/", route.upstream_path)) {
+    return None;
+  }
+
+  return Some(url);
+}
+
+fn strip_hop_by_hop_headers(headers: &mut HeaderMap) {
+  for name in &HOP_BY_HOP_HEADERS {
+    headers.remove(name);
+  }
+}
+
+fn strip_trailbase_set_cookies(headers: &mut HeaderMap) {
+  let header::Entry::Occupied(entry) = headers.entry(header::SET_COOKIE) else {
+    return;
+  };
+
+  let (_, values) = entry.remove_entry_mult();
+  let retained: Vec<HeaderValue> = values
+    .filter(|value| {
+      let name = value
+        .to_str()
+        .ok()
+        .and_then(|v| v.split_once('='))
+        .map(|(name, _)| name.trim());
+      if let Some(name) = name
+        && !TRAILBASE_COOKIES.contains(&name)
+      {
+        return true;
+      }
+      warn!("Dropping upstream Set-Cookie: {name:?}");
+      return false;
+    })
+    .collect();
+
+  for value in retained {
+    headers.append(header::SET_COOKIE, value);
+  }
+}
+
+/// Appends the client's address to the chain of proxies in "X-Forwarded-For".
+fn set_forwarded_for(headers: &mut HeaderMap, extensions: &axum::http::Extensions) {
+  let Some(ConnectInfo(addr)) = extensions.get::<ConnectInfo<SocketAddr>>() else {
+    return;
+  };
+
+  let value = match headers
+    .get(&HEADER_FORWARDED_FOR)
+    .and_then(|v| v.to_str().ok())
+  {
+    Some(chain) => format!("{chain}, {}", addr.ip()),
+    None => addr.ip().to_string(),
+  };
+  if let Ok(value) = HeaderValue::try_from(value) {
+    headers.insert(HEADER_FORWARDED_FOR, value);
+  }
+}
+
+/// The scheme the client used to reach us. Preserves the value of a TLS-terminating reverse proxy
+/// in front of TrailBase and otherwise falls back to the configured site URL.
+fn forwarded_proto(state: &AppState, uri: &axum::http::Uri, headers: &HeaderMap) -> &'static str {
+  let is_https = match uri.scheme_str() {
+    Some(scheme) => scheme == "https",
+    None => match headers
+      .get(&HEADER_FORWARDED_PROTO)
+      .and_then(|v| v.to_str().ok())
+    {
+      Some(proto) => proto.eq_ignore_ascii_case("https"),
+      None => (*state.site_url())
+        .as_ref()
+        .is_some_and(|url| url.scheme() == "https"),
+    },
+  };
+
+  return if is_https { "https" } else { "http" };
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::Path;
+  use axum::routing::get;
+  use std::net::{IpAddr, Ipv4Addr};
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::admin::user::create_user_for_test;
+  use crate::app_state::{TestStateOptions, test_state};
+  use crate::auth::util::{UserIdentifier, login_with_password_for_test};
+
+  async fn echo(Path(rest): Path<String>, headers: HeaderMap, body: String) -> String {
+    let header = |name: &str| {
+      headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_string()
+    };
+    return format!(
+      "/{rest} id={} email={} auth={} body={body}",
+      header("x-trailbase-user-id"),
+      header("x-trailbase-user-email"),
+      header("authorization"),
+    );
+  }
+
+  async fn set_cookies() -> Response {
+    return Response::builder()
+      .header(header::SET_COOKIE, "auth_token=spoofed; Path=/")
+      .header(header::SET_COOKIE, "refresh_token=spoofed")
+      .header(header::SET_COOKIE, "session=upstream; HttpOnly")
+      .body(Body::empty())
+      .unwrap();
+  }
+
+  async fn forwarded(headers: HeaderMap) -> String {
+    let header = |name: &str| {
+      headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_string()
+    };
+    return format!(
+      "for={} proto={} host={}",
+      header("x-forwarded-for"),
+      header("x-forwarded-proto"),
+      header("x-forwarded-host"),
+    );
+  }
+
+  #[tokio::test]
+  async fn test_proxy_routes() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let upstream = Router::new()
+      .route("/v1/{*rest}", get(echo).post(echo))
+      .route("/cookies", get(set_cookies))
+      .route("/forwarded", get(forwarded));
+    tokio::spawn(async move { axum::serve(listener, upstream).await.unwrap() });
+
+    let mut config = crate::app_state::test_config();
+    config.proxy_routes = vec![
+      ProxyRouteConfig {
+        path_prefix: Some("/api/payments".to_string()),
+        upstream_url: Some(format!("http://{addr}/v1")),
+        ..Default::default()
+      },
+      ProxyRouteConfig {
+        path_prefix: Some("/api/private".to_string()),
+        upstream_url: Some(format!("http://{addr}/v1")),
+        require_auth: Some(true),
+        ..Default::default()
+      },
+      ProxyRouteConfig {
+        path_prefix: Some("/api/keys".to_string()),
+        upstream_url: Some(format!("http://{addr}/v1")),
+        require_auth: Some(true),
+        allow_api_keys: Some(true),
+        ..Default::default()
+      },
+      ProxyRouteConfig {
+        path_prefix: Some("/api/cookies".to_string()),
+        upstream_url: Some(format!("http://{addr}/cookies")),
+        ..Default::default()
+      },
+      ProxyRouteConfig {
+        path_prefix: Some("/api/forwarded".to_string()),
+        upstream_url: Some(format!("http://{addr}/forwarded")),
+        ..Default::default()
+      },
+      ProxyRouteConfig {
+        path_prefix: Some("/api/down".to_string()),
+        // Port 9 (discard) is expected to refuse connections.
+        upstream_url: Some("http://127.0.0.1:9".to_string()),
+        ..Default::default()
+      },
+    ];
+
+    let state = test_state(Some(TestStateOptions {
+      config: Some(config.clone()),
+      ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    let email = "user@test.org";
+    let password = "Secret!1!!";
+    let user_id = create_user_for_test(&state, email, password).await.unwrap();
+    let tokens =
+      login_with_password_for_test(&state, UserIdentifier::Email(email.to_string()), password)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let router = router(&config.proxy_routes).with_state(state.clone());
+    let send = async |req: axum::http::request::Builder, body: &str| -> (StatusCode, String) {
+      let response = router
+        .clone()
+        .oneshot(req.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+      let status = response.status();
+      let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+      return (status, String::from_utf8(body.to_vec()).unwrap());
+    };
+
+    // Anonymous requests pass through w/o claims, even if the client tries to spoof them.
+    assert_eq!(
+      send(
+        Request::post("/api/payments/charges?id=1").header("x-trailbase-user-id", "spoofed"),
+        "payload"
+      )
+      .await,
+      (
+        StatusCode::OK,
+        "/charges id=- email=- auth=- body=payload".to_string()
+      )
+    );
+
+    assert_eq!(
+      send(Request::get("/api/private/charges"), "").await.0,
+      StatusCode::UNAUTHORIZED
+    );
+
+    let (status, body) = send(
+      Request::get("/api/private/charges").header(
+        header::AUTHORIZATION,
+        format!("Bearer {}", tokens.auth_token),
+      ),
+      "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+      body,
+      format!(
+        "/charges id={} email={email} auth=- body=",
+        crate::util::id_to_b64(user_id.as_bytes())
+      )
+    );
+
+    // Requests must not escape the upstream's base path.
+    for path in [
+      "/api/payments/../admin",
+      "/api/payments/%2e%2e/admin",
+      "/api/payments/.%2E/admin",
+      "/api/payments/a%2Fb",
+    ] {
+      assert_eq!(
+        send(Request::get(path), "").await.0,
+        StatusCode::BAD_REQUEST,
+        "{path}"
+      );
+    }
+
+    // API keys are rejected on routes requiring auth unless the route opts in, and never
+    // forwarded as user claims.
+    let secret = crate::auth::api_key::new_api_key_secret();
+    state
+      .conn()
+      .execute(
+        format!(
+          "INSERT INTO '{}' (name, secret_hash, record_apis, permissions) \
+           VALUES ('key', $1, '[\"x\"]', 1)",
+          crate::constants::API_KEY_TABLE
+        ),
+        trailbase_sqlite::params!(crate::auth::api_key::hash_api_key_secret(&secret)),
+      )
+      .await
+      .unwrap();
+    let with_key =
+      |path: &str| Request::get(path).header(header::AUTHORIZATION, format!("Bearer {secret}"));
+
+    assert_eq!(
+      send(with_key("/api/private/charges"), "").await.0,
+      StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+      send(with_key("/api/keys/charges"), "").await,
+      (
+        StatusCode::OK,
+        "/charges id=- email=- auth=- body=".to_string()
+      )
+    );
+    assert_eq!(
+      send(with_key("/api/payments/charges"), "").await,
+      (
+        StatusCode::OK,
+        "/charges id=- email=- auth=- body=".to_string()
+      )
+    );
+
+    // Upstreams must not be able to set TrailBase's cookies.
+    let response = router
+      .clone()
+      .oneshot(Request::get("/api/cookies").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookies: Vec<_> = response
+      .headers()
+      .get_all(header::SET_COOKIE)
+      .iter()
+      .map(|v| v.to_str().unwrap())
+      .collect();
+    assert_eq!(cookies, vec!["session=upstream; HttpOnly"]);
+
+    // The client's address is appended to the chain of proxies. The scheme falls back to the
+    // site URL's, i.e. "https://test.org", unless set by a reverse proxy in front of us.
+    let peer = ConnectInfo(SocketAddr::new(
+      IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+      1234,
+    ));
+    assert_eq!(
+      send(
+        Request::get("/api/forwarded")
+          .header(header::HOST, "example.com")
+          .extension(peer),
+        ""
+      )
+      .await,
+      (
+        StatusCode::OK,
+        "for=10.0.0.1 proto=https host=example.com".to_string()
+      )
+    );
+    assert_eq!(
+      send(
+        Request::get("/api/forwarded")
+          .header("x-forwarded-for", "203.0.113.7")
+          .header("x-forwarded-proto", "http")
+          .extension(peer),
+        ""
+      )
+      .await,
+      (
+        StatusCode::OK,
+        "for=203.0.113.7, 10.0.0.1 proto=http host=-".to_string()
+      )
+    );
+
+    assert_eq!(
+      send(Request::get("/api/down/charges"), "").await.0,
+      StatusCode::BAD_GATEWAY
+    );
+  }
+}
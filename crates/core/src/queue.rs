@@ -0,0 +1,377 @@
+//! Durable queue of background tasks.
+//!
+//! Tasks are enqueued into the `_task_queue` table, e.g. by Rust embedders via
+//! [TaskQueue::enqueue], through the admin API or by WASM components inserting rows directly.
+//! Each task belongs to a named queue, which determines its [TaskHandler]. A worker claims due
+//! tasks with bounded concurrency, reschedules failing tasks with exponential backoff and moves
+//! tasks that ran out of attempts to the dead-letter state, from where they can be requeued.
+
+use async_trait::async_trait;
+use const_format::formatcp;
+use log::*;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinSet;
+use trailbase_sqlite::{Connection, params};
+
+use crate::app_state::AppState;
+use crate::config::proto::QueueConfig;
+use crate::constants::TASK_QUEUE_TABLE;
+
+pub type TaskError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Processes the tasks of a queue. Returning an error schedules a retry.
+#[async_trait]
+pub trait TaskHandler: Send + Sync {
+  async fn handle(&self, payload: serde_json::Value) -> Result<(), TaskError>;
+}
+
+#[repr(i64)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TaskStatus {
+  Pending = 0,
+  Failed = 2,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EnqueueOptions {
+  /// Delay before the first attempt.
+  pub delay: Option<Duration>,
+  /// Overrides the configured maximum number of attempts.
+  pub max_attempts: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ClaimedTask {
+  id: i64,
+  queue: String,
+  payload: String,
+  attempts: i64,
+  max_attempts: Option<i64>,
+}
+
+pub struct TaskQueue {
+  conn: Connection,
+  handlers: RwLock<HashMap<String, Arc<dyn TaskHandler>>>,
+  /// Wakes up the worker, e.g. after enqueuing.
+  notify: Notify,
+}
+
+impl TaskQueue {
+  pub(crate) fn new(conn: Connection) -> Self {
+    return Self {
+      conn,
+      handlers: RwLock::new(HashMap::new()),
+      notify: Notify::new(),
+    };
+  }
+
+  /// Registers the handler for tasks of the given queue, replacing any previous one.
+  pub fn register_handler(&self, queue: impl Into<String>, handler: Arc<dyn TaskHandler>) {
+    self.handlers.write().insert(queue.into(), handler);
+    self.notify.notify_one();
+  }
+
+  /// Enqueues a task and returns its id.
+  pub async fn enqueue(
+    &self,
+    queue: &str,
+    payload: serde_json::Value,
+    options: EnqueueOptions,
+  ) -> Result<i64, trailbase_sqlite::Error> {
+    const QUERY: &str = formatcp!(
+      "INSERT INTO '{TASK_QUEUE_TABLE}' (queue, payload, max_attempts, next_attempt) VALUES ($1, $2, $3, UNIXEPOCH() + $4) RETURNING id"
+    );
+
+    let id: i64 = self
+      .conn
+      .write_query_row_get(
+        QUERY,
+        params!(
+          queue.to_string(),
+          payload.to_string(),
+          options.max_attempts.map(|m| m as i64),
+          options.delay.map_or(0, |d| d.as_secs() as i64),
+        ),
+        0,
+      )
+      .await?
+      .ok_or_else(|| trailbase_sqlite::Error::Other("missing id".into()))?;
+
+    self.notify.notify_one();
+    return Ok(id);
+  }
+
+  /// Resets a task, e.g. a dead-lettered one, for immediate processing. Returns false if no
+  /// such task exists.
+  pub(crate) async fn requeue(&self, id: i64) -> Result<bool, trailbase_sqlite::Error> {
+    const QUERY: &str = formatcp!(
+      "UPDATE '{TASK_QUEUE_TABLE}' SET status = $2, attempts = 0, next_attempt = UNIXEPOCH() WHERE id = $1"
+    );
+
+    let rows_affected = self
+      .conn
+      .execute(QUERY, params!(id, TaskStatus::Pending as i64))
+      .await?;
+
+    self.notify.notify_one();
+    return Ok(rows_affected > 0);
+  }
+
+  /// Claims up to `limit` due tasks of queues with a registered handler.
+  async fn claim(&self, limit: usize) -> Result<Vec<ClaimedTask>, trailbase_sqlite::Error> {
+    // Claiming bumps `next_attempt`, which keeps tasks from being claimed twice. Tasks left over
+    // from a crash are picked up again once the lease expires.
+    const QUERY: &str = formatcp!(
+      "\
+        UPDATE '{TASK_QUEUE_TABLE}' \
+        SET attempts = attempts + 1, next_attempt = UNIXEPOCH() + {LEASE_SEC} \
+        WHERE id IN ( \
+          SELECT id FROM '{TASK_QUEUE_TABLE}' \
+          WHERE status = {pending} AND next_attempt <= UNIXEPOCH() \
+            AND queue IN (SELECT value FROM json_each($1)) \
+          ORDER BY id LIMIT $2 \
+        ) \
+        RETURNING id, queue, payload, attempts, max_attempts \
+      ",
+      pending = TaskStatus::Pending as i64,
+    );
+
+    let queues =
+      serde_json::to_string(&self.handlers.read().keys().collect::<Vec<_>>()).unwrap_or_default();
+    if limit == 0 || queues == "[]" {
+      return Ok(vec![]);
+    }
+
+    return self
+      .conn
+      .write_query_values(QUERY, params!(queues, limit as i64))
+      .await;
+  }
+
+  /// Runs a claimed task and records the outcome.
+  async fn run(
+    &self,
+    task: ClaimedTask,
+    default_max_attempts: i64,
+  ) -> Result<(), trailbase_sqlite::Error> {
+    let handler = self.handlers.read().get(&task.queue).cloned();
+    let result = match (handler, serde_json::from_str(&task.payload)) {
+      (Some(handler), Ok(payload)) => handler.handle(payload).await,
+      (None, _) => Err(format!("No handler for queue '{}'", task.queue).into()),
+      (_, Err(err)) => Err(err.into()),
+    };
+
+    let max_attempts = task.max_attempts.unwrap_or(default_max_attempts);
+    match result {
+      Ok(()) => {
+        const QUERY: &str = formatcp!("DELETE FROM '{TASK_QUEUE_TABLE}' WHERE id = $1");
+        self.conn.execute(QUERY, params!(task.id)).await?;
+      }
+      Err(err) if task.attempts < max_attempts => {
+        const QUERY: &str = formatcp!(
+          "UPDATE '{TASK_QUEUE_TABLE}' SET next_attempt = UNIXEPOCH() + $2, last_error = $3 WHERE id = $1"
+        );
+        let backoff = INITIAL_BACKOFF_SEC << (task.attempts - 1).clamp(0, 16);
+        self
+          .conn
+          .execute(QUERY, params!(task.id, backoff, err.to_string()))
+          .await?;
+      }
+      Err(err) => {
+        warn!(
+          "Moving task {} of queue '{}' to dead letters after {} attempts: {err}",
+          task.id, task.queue, task.attempts
+        );
+
+        const QUERY: &str =
+          formatcp!("UPDATE '{TASK_QUEUE_TABLE}' SET status = $2, last_error = $3 WHERE id = $1");
+        self
+          .conn
+          .execute(
+            QUERY,
+            params!(task.id, TaskStatus::Failed as i64, err.to_string()),
+          )
+          .await?;
+      }
+    }
+
+    return Ok(());
+  }
+
+  /// Claims and runs up to `limit` due tasks. Returns the number of claimed tasks.
+  #[cfg(test)]
+  async fn run_due(&self, limit: usize) -> Result<usize, trailbase_sqlite::Error> {
+    let tasks = self.claim(limit).await?;
+    let count = tasks.len();
+    for task in tasks {
+      self.run(task, DEFAULT_MAX_ATTEMPTS).await?;
+    }
+    return Ok(count);
+  }
+}
+
+fn limits(config: Option<&QueueConfig>) -> (usize, i64) {
+  return (
+    config
+      .and_then(|c| c.concurrency)
+      .map_or(DEFAULT_CONCURRENCY, |c| c as usize)
+      .max(1),
+    config
+      .and_then(|c| c.max_attempts)
+      .map_or(DEFAULT_MAX_ATTEMPTS, |m| m as i64)
+      .max(1),
+  );
+}
+
+/// Spawns the worker processing due tasks with the configured concurrency.
+pub(crate) fn spawn_task_worker(state: AppState) -> tokio::task::JoinHandle<()> {
+  return tokio::spawn(async move {
+    let queue = state.task_queue();
+    let mut running = JoinSet::new();
+
+    loop {
+      let (concurrency, max_attempts) = state.access_config(|c| limits(c.queue.as_ref()));
+
+      match queue.claim(concurrency.saturating_sub(running.len())).await {
+        Ok(tasks) => {
+          for task in tasks {
+            let queue = queue.clone();
+            running.spawn(async move {
+              if let Err(err) = queue.run(task, max_attempts).await {
+                warn!("Failed to record task outcome: {err}");
+              }
+            });
+          }
+        }
+        Err(err) => warn!("Failed to claim tasks: {err}"),
+      }
+
+      tokio::select! {
+        _ = queue.notify.notified() => {}
+        _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        Some(_) = running.join_next(), if !running.is_empty() => {}
+      }
+    }
+  });
+}
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_MAX_ATTEMPTS: i64 = 5;
+/// Backoff after the first failed attempt in seconds. Doubles with every further attempt.
+const INITIAL_BACKOFF_SEC: i64 = 10;
+/// Claimed tasks are considered abandoned, e.g. due to a crash, once the lease expires.
+const LEASE_SEC: i64 = 600;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct TestHandler {
+    received: Arc<parking_lot::Mutex<Vec<serde_json::Value>>>,
+  }
+
+  #[async_trait]
+  impl TaskHandler for TestHandler {
+    async fn handle(&self, payload: serde_json::Value) -> Result<(), TaskError> {
+      self.received.lock().push(payload.clone());
+      if payload["fail"] == true {
+        return Err("failed".into());
+      }
+      return Ok(());
+    }
+  }
+
+  async fn statuses(queue: &TaskQueue) -> Vec<(i64, i64, Option<String>)> {
+    return queue
+      .conn
+      .read_query_rows(
+        formatcp!("SELECT status, attempts, last_error FROM '{TASK_QUEUE_TABLE}' ORDER BY id"),
+        (),
+      )
+      .await
+      .unwrap()
+      .iter()
+      .map(|row| {
+        (
+          row.get(0).unwrap(),
+          row.get(1).unwrap(),
+          row.get(2).unwrap(),
+        )
+      })
+      .collect();
+  }
+
+  #[tokio::test]
+  async fn test_task_queue() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let queue = TaskQueue::new(state.conn().clone());
+
+    queue
+      .enqueue("emails", serde_json::json!({"to": "a"}), Default::default())
+      .await
+      .unwrap();
+    let failing = queue
+      .enqueue(
+        "emails",
+        serde_json::json!({"fail": true}),
+        EnqueueOptions {
+          max_attempts: Some(2),
+          ..Default::default()
+        },
+      )
+      .await
+      .unwrap();
+
+    // Tasks w/o handler aren't claimed.
+    assert_eq!(queue.run_due(10).await.unwrap(), 0);
+
+    let received = Arc::new(parking_lot::Mutex::new(vec![]));
+    queue.register_handler(
+      "emails",
+      Arc::new(TestHandler {
+        received: received.clone(),
+      }),
+    );
+
+    assert_eq!(queue.run_due(10).await.unwrap(), 2);
+    assert_eq!(received.lock().len(), 2);
+    assert_eq!(
+      statuses(&queue).await,
+      vec![(TaskStatus::Pending as i64, 1, Some("failed".to_string()))]
+    );
+
+    // Not yet due due to backoff.
+    assert_eq!(queue.run_due(10).await.unwrap(), 0);
+
+    // Second and last attempt moves the task to dead letters.
+    queue
+      .conn
+      .execute(
+        formatcp!("UPDATE '{TASK_QUEUE_TABLE}' SET next_attempt = 0"),
+        (),
+      )
+      .await
+      .unwrap();
+    assert_eq!(queue.run_due(10).await.unwrap(), 1);
+    assert_eq!(
+      statuses(&queue).await,
+      vec![(TaskStatus::Failed as i64, 2, Some("failed".to_string()))]
+    );
+    assert_eq!(queue.run_due(10).await.unwrap(), 0);
+
+    // Requeueing resets attempts.
+    assert!(queue.requeue(failing).await.unwrap());
+    assert!(!queue.requeue(failing + 1).await.unwrap());
+    assert_eq!(queue.run_due(10).await.unwrap(), 1);
+    assert_eq!(
+      statuses(&queue).await,
+      vec![(TaskStatus::Pending as i64, 1, Some("failed".to_string()))]
+    );
+  }
+}
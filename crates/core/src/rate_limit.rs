@@ -0,0 +1,165 @@
+use axum::RequestExt;
+use axum::extract::{MatchedPath, RawPathParams, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use governor::{DefaultKeyedRateLimiter, Quota};
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::User;
+use crate::config::proto::RateLimitConfig;
+use crate::constants::AUTH_API_PATH;
+use crate::extract::ip::{extract_ip, ipv6_privacy_mask};
+
+/// Identity a request is accounted against.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum RateLimitKey {
+  User(Uuid),
+  Ip(IpAddr),
+  /// Requests w/o user or discernible client IP share a single bucket.
+  Unknown,
+}
+
+impl RateLimitKey {
+  fn from_ip(req: &Request) -> Self {
+    return extract_ip(req).map_or(Self::Unknown, |ip| Self::Ip(ipv6_privacy_mask(ip)));
+  }
+}
+
+/// Keyed token bucket rate limiter built from a [RateLimitConfig].
+pub(crate) struct RateLimiter {
+  limiter: DefaultKeyedRateLimiter<RateLimitKey>,
+  checks: AtomicUsize,
+}
+
+impl RateLimiter {
+  /// Returns `None` if the config doesn't specify a burst size, i.e. rate limiting is disabled.
+  pub(crate) fn new(config: &RateLimitConfig) -> Option<Self> {
+    let burst_size = NonZeroU32::new(config.burst_size.unwrap_or(0))?;
+    let period = Duration::from_secs(config.replenish_period_sec.unwrap_or(1).max(1) as u64);
+
+    return Some(Self {
+      limiter: DefaultKeyedRateLimiter::keyed(Quota::with_period(period)?.allow_burst(burst_size)),
+      checks: AtomicUsize::new(0),
+    });
+  }
+
+  /// Consumes a token for the given key. Returns false if the key has exhausted its budget.
+  pub(crate) fn check(&self, key: &RateLimitKey) -> bool {
+    // Periodically drop state for keys whose buckets have been fully replenished.
+    if self.checks.fetch_add(1, Ordering::Relaxed) % GC_INTERVAL == GC_INTERVAL - 1 {
+      self.limiter.retain_recent();
+    }
+
+    return self.limiter.check_key(key).is_ok();
+  }
+}
+
+impl std::fmt::Debug for RateLimiter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return f
+      .debug_struct("RateLimiter")
+      .field("keys", &self.limiter.len())
+      .finish();
+  }
+}
+
+/// Enforces the per-API rate limit of Record APIs. Requests are keyed by the authenticated user or,
+/// for anonymous requests, by client IP.
+pub(crate) async fn record_api_rate_limit(
+  State(state): State<AppState>,
+  mut req: Request,
+  next: Next,
+) -> Response {
+  let api_name = req
+    .extract_parts::<RawPathParams>()
+    .await
+    .ok()
+    .and_then(|params| {
+      params
+        .iter()
+        .find_map(|(key, value)| (key == "name").then(|| value.to_string()))
+    });
+
+  if let Some(api) = api_name.and_then(|name| state.lookup_record_api(&name))
+    && let Some(limiter) = api.rate_limiter()
+  {
+    // Invalid credentials are rejected by the handler, here we merely fall back to the IP.
+    let key = match req
+      .extract_parts_with_state::<Option<User>, _>(&state)
+      .await
+    {
+      Ok(Some(user)) => RateLimitKey::User(user.uuid),
+      _ => RateLimitKey::from_ip(&req),
+    };
+
+    if !limiter.check(&key) {
+      return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+  }
+
+  return next.run(req).await;
+}
+
+/// Enforces the configured per-endpoint rate limits of auth APIs keyed by client IP.
+pub(crate) async fn auth_rate_limit(
+  State(state): State<AppState>,
+  req: Request,
+  next: Next,
+) -> Response {
+  let limiters = state.auth_rate_limiters();
+  if !limiters.is_empty() {
+    let endpoint = req
+      .extensions()
+      .get::<MatchedPath>()
+      .and_then(|path| path.as_str().strip_prefix(&format!("/{AUTH_API_PATH}/")));
+
+    if let Some(limiter) = endpoint.and_then(|endpoint| limiters.get(endpoint))
+      && !limiter.check(&RateLimitKey::from_ip(&req))
+    {
+      return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+  }
+
+  return next.run(req).await;
+}
+
+const GC_INTERVAL: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rate_limiter() {
+    assert!(RateLimiter::new(&RateLimitConfig::default()).is_none());
+    assert!(
+      RateLimiter::new(&RateLimitConfig {
+        burst_size: Some(0),
+        ..Default::default()
+      })
+      .is_none()
+    );
+
+    let limiter = RateLimiter::new(&RateLimitConfig {
+      burst_size: Some(2),
+      replenish_period_sec: Some(3600),
+    })
+    .unwrap();
+
+    let alice = RateLimitKey::User(Uuid::now_v7());
+    let bob = RateLimitKey::User(Uuid::now_v7());
+
+    assert!(limiter.check(&alice));
+    assert!(limiter.check(&alice));
+    assert!(!limiter.check(&alice));
+
+    assert!(limiter.check(&bob));
+    assert!(limiter.check(&RateLimitKey::Unknown));
+  }
+}
@@ -0,0 +1,374 @@
+use mini_moka::sync::Cache;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Weak};
+use std::time::Duration;
+use trailbase_schema::QualifiedName;
+use trailbase_sqlite::{Connection, Value};
+
+use crate::config::proto::RecordCacheConfig;
+use crate::records::RecordError;
+use crate::records::list_records::ListResponse;
+use crate::records::subscribe::hook::{
+  HookListener, PreupdateHookEvent, TransactionOutcome, add_listener, add_transaction_listener,
+};
+
+/// Write counters of a database, bumped whenever a transaction writing to a table has committed.
+///
+/// Cache entries are tagged with the counter observed before reading from the database and are
+/// only served as long as the counter hasn't changed since. This way any write on the connection
+/// invalidates the cache, whether it goes through a record API, the admin SQL console, the WASM
+/// runtime or an import.
+///
+/// Counters are only bumped once committed changes are visible, i.e. after the write has returned
+/// or the write lock was released, see `Connection::add_post_write_listener`. Bumping from the
+/// commit hook instead would allow racing reads to cache the previous state under the new version.
+#[derive(Default)]
+pub(crate) struct WriteCounters {
+  /// Bumped on writes to any table.
+  any: AtomicU64,
+  tables: Mutex<HashMap<QualifiedName, u64>>,
+}
+
+impl WriteCounters {
+  fn get(&self, table_name: Option<&QualifiedName>) -> u64 {
+    return match table_name {
+      Some(table_name) => self.tables.lock().get(table_name).copied().unwrap_or(0),
+      None => self.any.load(Ordering::Acquire),
+    };
+  }
+
+  fn bump(&self, table_name: &QualifiedName) {
+    *self.tables.lock().entry(table_name.clone()).or_default() += 1;
+    self.any.fetch_add(1, Ordering::AcqRel);
+  }
+}
+
+/// Write counters by connection id.
+static WRITE_COUNTERS: LazyLock<Mutex<HashMap<usize, Weak<WriteCounters>>>> =
+  LazyLock::new(Default::default);
+
+/// Returns the write counters for the given connection, installing the change hooks on first use.
+pub(crate) fn write_counters(conn: &Connection) -> Result<Arc<WriteCounters>, RecordError> {
+  let mut registry = WRITE_COUNTERS.lock();
+  if let Some(counters) = registry.get(&conn.id()).and_then(Weak::upgrade) {
+    return Ok(counters);
+  }
+
+  let counters = Arc::new(WriteCounters::default());
+  // Tables written by the current transaction.
+  let pending: Arc<Mutex<HashSet<QualifiedName>>> = Default::default();
+  // Tables written by committed transactions, which may not be visible yet.
+  let committed: Arc<Mutex<HashSet<QualifiedName>>> = Default::default();
+
+  add_listener(
+    conn,
+    HookListener::Cache,
+    Box::new({
+      let counters = Arc::downgrade(&counters);
+      let pending = pending.clone();
+      move |event: &PreupdateHookEvent| {
+        pending.lock().insert(event.table_name.clone());
+        return counters.strong_count() > 0;
+      }
+    }),
+  )?;
+
+  add_transaction_listener(
+    conn,
+    HookListener::Cache,
+    Box::new({
+      let counters = Arc::downgrade(&counters);
+      let committed = committed.clone();
+      move |outcome: TransactionOutcome| {
+        let tables = std::mem::take(&mut *pending.lock());
+        if outcome == TransactionOutcome::Commit {
+          committed.lock().extend(tables);
+        }
+        return counters.strong_count() > 0;
+      }
+    }),
+  )?;
+
+  conn
+    .add_post_write_listener(Box::new({
+      let counters = Arc::downgrade(&counters);
+      move || -> bool {
+        let Some(counters) = counters.upgrade() else {
+          return false;
+        };
+
+        for table_name in std::mem::take(&mut *committed.lock()) {
+          counters.bump(&table_name);
+        }
+        return true;
+      }
+    }))
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  registry.insert(conn.id(), Arc::downgrade(&counters));
+
+  return Ok(counters);
+}
+
+/// The write counter a cache's entries are versioned by.
+#[derive(Clone)]
+pub(crate) struct CacheVersion {
+  counters: Arc<WriteCounters>,
+  /// The TABLE the cached data is derived from. `None` for data derived from multiple tables, e.g.
+  /// VIEWs or expanded foreign records, which are invalidated by writes to any table.
+  table_name: Option<QualifiedName>,
+}
+
+impl CacheVersion {
+  pub(crate) fn new(counters: Arc<WriteCounters>, table_name: Option<QualifiedName>) -> Self {
+    return Self {
+      counters,
+      table_name,
+    };
+  }
+
+  /// Current version. Has to be observed *before* reading the data to be cached.
+  pub(crate) fn current(&self) -> u64 {
+    return self.counters.get(self.table_name.as_ref());
+  }
+}
+
+/// Hashable representation of a record's primary key.
+///
+/// Record APIs require integer or UUID primary keys, we still support TEXT for good measure.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum RecordKey {
+  Integer(i64),
+  Text(String),
+  Blob(Vec<u8>),
+}
+
+impl RecordKey {
+  #[inline]
+  fn from_value(value: &Value) -> Option<Self> {
+    return match value {
+      Value::Integer(i) => Some(Self::Integer(*i)),
+      Value::Text(t) => Some(Self::Text(t.clone())),
+      Value::Blob(b) => Some(Self::Blob(b.clone())),
+      Value::Null | Value::Real(_) => None,
+    };
+  }
+}
+
+/// In-process read-through cache for reading records by id.
+///
+/// Entries are the final JSON representation of a record as returned by the read handler. Access
+/// checks are still applied on every read, i.e. the cache only saves the record lookup.
+#[derive(Clone)]
+pub(crate) struct RecordCache {
+  cache: Cache<RecordKey, (u64, serde_json::Value)>,
+  version: CacheVersion,
+}
+
+impl RecordCache {
+  pub(crate) fn new(config: &RecordCacheConfig, version: CacheVersion) -> Self {
+    return Self {
+      version,
+      cache: Cache::builder()
+        .time_to_live(Duration::from_secs(
+          config.ttl_sec.unwrap_or(DEFAULT_TTL_SEC),
+        ))
+        .max_capacity(config.capacity.unwrap_or(DEFAULT_CAPACITY))
+        .build(),
+    };
+  }
+
+  /// Version to pass to [RecordCache::insert] for data read after this call.
+  pub(crate) fn version(&self) -> u64 {
+    return self.version.current();
+  }
+
+  pub(crate) fn get(&self, record_id: &Value) -> Option<serde_json::Value> {
+    let (version, record) = self.cache.get(&RecordKey::from_value(record_id)?)?;
+    return (version == self.version.current()).then_some(record);
+  }
+
+  pub(crate) fn insert(&self, record_id: &Value, version: u64, record: serde_json::Value) {
+    if version != self.version.current() {
+      return;
+    }
+    if let Some(key) = RecordKey::from_value(record_id) {
+      self.cache.insert(key, (version, record));
+    }
+  }
+}
+
+/// Key for cached listings.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct ListKey {
+  /// Raw URL query, i.e. filters, ordering, pagination, ... .
+  query: Option<String>,
+  /// User scope. Only set when the result depends on the user, i.e. there's a read access rule.
+  user: Option<[u8; 16]>,
+}
+
+impl ListKey {
+  pub(crate) fn new(query: Option<String>, user: Option<[u8; 16]>) -> Self {
+    return Self { query, user };
+  }
+}
+
+/// In-process cache for listing records.
+///
+/// Since any write may affect any listing, all entries are invalidated by writes to the underlying
+/// TABLE. Access rules are part of the cached queries, thus entries are scoped to the user.
+#[derive(Clone)]
+pub(crate) struct ListCache {
+  cache: Cache<ListKey, (u64, ListResponse)>,
+  version: CacheVersion,
+}
+
+impl ListCache {
+  pub(crate) fn new(config: &RecordCacheConfig, version: CacheVersion) -> Self {
+    return Self {
+      version,
+      cache: Cache::builder()
+        .time_to_live(Duration::from_secs(
+          config.ttl_sec.unwrap_or(DEFAULT_TTL_SEC),
+        ))
+        .max_capacity(config.capacity.unwrap_or(DEFAULT_CAPACITY))
+        .build(),
+    };
+  }
+
+  /// Version to pass to [ListCache::insert] for data read after this call.
+  pub(crate) fn version(&self) -> u64 {
+    return self.version.current();
+  }
+
+  pub(crate) fn get(&self, key: &ListKey) -> Option<ListResponse> {
+    let (version, response) = self.cache.get(key)?;
+    return (version == self.version.current()).then_some(response);
+  }
+
+  pub(crate) fn insert(&self, key: ListKey, version: u64, response: ListResponse) {
+    if version == self.version.current() {
+      self.cache.insert(key, (version, response));
+    }
+  }
+}
+
+const DEFAULT_CAPACITY: u64 = 1024;
+const DEFAULT_TTL_SEC: u64 = 60;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_record_cache() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn
+      .execute_batch(
+        "
+          CREATE TABLE a (id INTEGER PRIMARY KEY) STRICT;
+          CREATE TABLE b (id INTEGER PRIMARY KEY) STRICT;
+        ",
+      )
+      .await
+      .unwrap();
+
+    let table_name = QualifiedName::parse("a").unwrap();
+    let cache = RecordCache::new(
+      &RecordCacheConfig::default(),
+      CacheVersion::new(write_counters(&conn).unwrap(), Some(table_name.clone())),
+    );
+
+    let id = Value::Integer(5);
+    assert!(cache.get(&id).is_none());
+
+    cache.insert(&id, cache.version(), serde_json::json!({"id": 5}));
+    assert_eq!(Some(serde_json::json!({"id": 5})), cache.get(&id));
+    assert!(cache.get(&Value::Integer(6)).is_none());
+
+    // Writes to other tables don't invalidate the cache.
+    conn
+      .execute("INSERT INTO b (id) VALUES (1)", ())
+      .await
+      .unwrap();
+    assert!(cache.get(&id).is_some());
+
+    // Committed writes to the table do, even if they don't go through a record API.
+    conn
+      .execute("INSERT INTO a (id) VALUES (1)", ())
+      .await
+      .unwrap();
+    assert!(cache.get(&id).is_none());
+
+    // Reads racing with a write don't populate the cache.
+    let version = cache.version();
+    conn
+      .execute("INSERT INTO a (id) VALUES (2)", ())
+      .await
+      .unwrap();
+    cache.insert(&id, version, serde_json::json!({"id": 5}));
+    assert!(cache.get(&id).is_none());
+
+    // Rolled back writes don't invalidate the cache.
+    cache.insert(&id, cache.version(), serde_json::json!({"id": 5}));
+    conn
+      .execute_batch("BEGIN; INSERT INTO a (id) VALUES (3); ROLLBACK;")
+      .await
+      .unwrap();
+    assert!(cache.get(&id).is_some());
+
+    // Writes only invalidate the cache once visible, e.g. when the write lock is released.
+    {
+      let lock = conn.write_lock().unwrap();
+      lock.execute("INSERT INTO a (id) VALUES (4)", ()).unwrap();
+      assert!(cache.get(&id).is_some());
+    }
+    assert!(cache.get(&id).is_none());
+
+    // Non-key types are never cached.
+    cache.insert(&Value::Real(5.0), cache.version(), serde_json::json!({}));
+    assert!(cache.get(&Value::Real(5.0)).is_none());
+  }
+
+  #[tokio::test]
+  async fn test_list_cache() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn
+      .execute_batch("CREATE TABLE a (id INTEGER PRIMARY KEY) STRICT;")
+      .await
+      .unwrap();
+
+    // Not bound to a specific table, e.g. for VIEWs.
+    let cache = ListCache::new(
+      &RecordCacheConfig::default(),
+      CacheVersion::new(write_counters(&conn).unwrap(), None),
+    );
+
+    let key = ListKey::new(Some("limit=5".to_string()), None);
+    assert!(cache.get(&key).is_none());
+
+    cache.insert(
+      key.clone(),
+      cache.version(),
+      ListResponse {
+        cursor: None,
+        total_count: Some(1),
+        records: vec![serde_json::json!({"id": 5})],
+      },
+    );
+    assert_eq!(Some(1), cache.get(&key).and_then(|r| r.total_count));
+    assert!(
+      cache
+        .get(&ListKey::new(Some("limit=5".to_string()), Some([0; 16])))
+        .is_none()
+    );
+
+    conn
+      .execute("INSERT INTO a (id) VALUES (1)", ())
+      .await
+      .unwrap();
+    assert!(cache.get(&key).is_none());
+  }
+}
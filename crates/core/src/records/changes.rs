@@ -0,0 +1,405 @@
+//! Change tracking for record APIs with `enable_change_tracking`.
+//!
+//! Triggers on the tracked tables append every creation, update and deletion to
+//! `_record_changes`. Clients request the changes since their last cursor to incrementally refresh
+//! cached records instead of re-listing everything.
+
+use axum::{
+  Json,
+  extract::{Path, Query, State},
+};
+use const_format::formatcp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use trailbase_sqlite::{ConnectionType, params};
+use ts_rs::TS;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::constants::RECORD_CHANGES_TABLE;
+use crate::records::record_api::RecordApi;
+use crate::records::transaction::extract_record_id;
+use crate::records::{Permission, RecordError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum RecordChangeOp {
+  Create,
+  Update,
+  Delete,
+}
+
+impl RecordChangeOp {
+  fn from_name(name: &str) -> Option<Self> {
+    return match name {
+      "create" => Some(Self::Create),
+      "update" => Some(Self::Update),
+      "delete" => Some(Self::Delete),
+      _ => None,
+    };
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct RecordChange {
+  pub op: RecordChangeOp,
+  /// Record id, i.e. an integer or url-safe base64 encoded UUID.
+  pub id: String,
+  /// Seconds since epoch.
+  pub created: i64,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct ChangesQuery {
+  /// Cursor returned by a previous request. If absent, only the current cursor is returned, e.g.
+  /// to start tracking changes after an initial listing.
+  pub since: Option<i64>,
+  pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct ChangesResponse {
+  /// Changes in the order they happened.
+  pub changes: Vec<RecordChange>,
+  /// Cursor to pass as `since` to the next request.
+  pub cursor: i64,
+  /// Whether there are further changes.
+  pub has_more: bool,
+  /// The cursor predates the retained changes, i.e. clients have to re-list all records.
+  pub expired: bool,
+}
+
+/// List changes since the given cursor.
+#[utoipa::path(
+  get,
+  path = "/{name}/changes",
+  tag = "records",
+  params(
+    ("name" = String, Path, description = "Name of the record API."),
+    ChangesQuery,
+  ),
+  responses(
+    (status = 200, description = "Changes since the cursor.", body = ChangesResponse),
+  )
+)]
+pub async fn list_changes_handler(
+  State(state): State<AppState>,
+  Path(api_name): Path<String>,
+  Query(query): Query<ChangesQuery>,
+  user: Option<User>,
+) -> Result<Json<ChangesResponse>, RecordError> {
+  const CURSOR_QUERY: &str = formatcp!(
+    "SELECT COALESCE((SELECT seq FROM sqlite_sequence WHERE name = '{RECORD_CHANGES_TABLE}'), 0)"
+  );
+  // NOTE: Entries are only ever removed by age and sequence numbers are never reused, i.e. a gap
+  // between the cursor and the oldest entry means changes got pruned.
+  const EXPIRED_QUERY: &str =
+    formatcp!("SELECT COALESCE($1 + 1 < (SELECT MIN(seq) FROM '{RECORD_CHANGES_TABLE}'), FALSE)");
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT seq, record_id, op, created FROM '{RECORD_CHANGES_TABLE}' \
+      WHERE table_name = $1 AND seq > $2 \
+      ORDER BY seq LIMIT $3 \
+    "
+  );
+
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+  if !api.enable_change_tracking() {
+    return Err(RecordError::ApiNotFound);
+  }
+  api.check_table_level_access(Permission::Read, user.as_ref())?;
+
+  let conn = state.conn();
+  let Some(since) = query.since else {
+    let cursor = conn
+      .read_query_row_get::<i64>(CURSOR_QUERY, (), 0)
+      .await?
+      .unwrap_or(0);
+    return Ok(Json(ChangesResponse {
+      changes: vec![],
+      cursor,
+      has_more: false,
+      expired: false,
+    }));
+  };
+
+  if conn
+    .read_query_row_get::<bool>(EXPIRED_QUERY, params!(since), 0)
+    .await?
+    .unwrap_or(false)
+  {
+    return Ok(Json(ChangesResponse {
+      changes: vec![],
+      cursor: since,
+      has_more: false,
+      expired: true,
+    }));
+  }
+
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  let rows = conn
+    .read_query_rows(
+      QUERY,
+      params!(api.qualified_name().name.clone(), since, limit as i64),
+    )
+    .await?;
+
+  let mut changes: Vec<RecordChange> = Vec::with_capacity(rows.len());
+  let mut cursor = since;
+  for row in rows.iter() {
+    cursor = row.get(0)?;
+    let record_id: trailbase_sqlite::Value = row.get(1)?;
+    let op: String = row.get(2)?;
+    let Some(op) = RecordChangeOp::from_name(&op) else {
+      return Err(RecordError::Internal(format!("invalid op: {op}").into()));
+    };
+
+    // Only report changes of records the user can currently read. Access to deleted records
+    // cannot be checked anymore.
+    if op != RecordChangeOp::Delete && !can_read(&api, &record_id, user.as_ref()).await? {
+      continue;
+    }
+
+    changes.push(RecordChange {
+      op,
+      id: extract_record_id(record_id).map_err(|err| RecordError::Internal(err.into()))?,
+      created: row.get(3)?,
+    });
+  }
+
+  return Ok(Json(ChangesResponse {
+    changes,
+    cursor,
+    has_more: rows.len() == limit,
+    expired: false,
+  }));
+}
+
+async fn can_read(
+  api: &RecordApi,
+  record_id: &trailbase_sqlite::Value,
+  user: Option<&User>,
+) -> Result<bool, RecordError> {
+  return match api
+    .check_record_level_access(Permission::Read, Some(record_id), None, user)
+    .await
+  {
+    Ok(()) => Ok(true),
+    Err(RecordError::Forbidden) => Ok(false),
+    Err(err) => Err(err),
+  };
+}
+
+/// Installs the triggers recording changes of tables with change tracking enabled and removes
+/// them from tables without.
+pub(crate) async fn update_change_tracking_triggers(
+  conn: &trailbase_sqlite::Connection,
+  apis: &HashMap<String, RecordApi>,
+) -> Result<(), trailbase_sqlite::Error> {
+  const QUERY: &str = "SELECT name, tbl_name FROM main.sqlite_schema WHERE type = 'trigger' AND name GLOB '__*__record_changes_*'";
+
+  if !matches!(conn.connection_type(), ConnectionType::Sqlite) {
+    return Ok(());
+  }
+
+  // Table name to primary key column.
+  let tracked: HashMap<String, String> = apis
+    .values()
+    .filter(|api| api.enable_change_tracking() && !api.tenant_scoped())
+    .map(|api| {
+      return (
+        api.qualified_name().name.clone(),
+        api.record_pk_column().column.name.clone(),
+      );
+    })
+    .collect();
+
+  let mut statements: Vec<String> = vec![];
+  for row in conn.read_query_rows(QUERY, ()).await?.iter() {
+    let trigger: String = row.get(0)?;
+    let table_name: String = row.get(1)?;
+    if !tracked.contains_key(&table_name) {
+      statements.push(format!(
+        "DROP TRIGGER IF EXISTS main.\"{}\";",
+        escape_identifier(&trigger)
+      ));
+    }
+  }
+
+  for (table_name, pk_column) in &tracked {
+    statements.push(build_triggers(table_name, pk_column));
+  }
+
+  if statements.is_empty() {
+    return Ok(());
+  }
+  return conn.execute_batch(statements.join("\n")).await;
+}
+
+/// Triggers for the given table. Changes of the primary key are recorded as deletion and creation.
+///
+/// NOTE: SQLite requires unqualified names within trigger bodies, which resolve to the trigger's
+/// database, i.e. main.
+fn build_triggers(table_name: &str, pk_column: &str) -> String {
+  let name = escape_identifier(table_name);
+  let literal = table_name.replace('\'', "''");
+  let pk = escape_identifier(pk_column);
+
+  return format!(
+    "\
+      CREATE TRIGGER IF NOT EXISTS main.\"__{name}__record_changes_insert\" AFTER INSERT ON \"{name}\" \
+      BEGIN \
+        INSERT INTO {RECORD_CHANGES_TABLE} (table_name, record_id, op) VALUES ('{literal}', NEW.\"{pk}\", 'create'); \
+      END; \
+      \
+      CREATE TRIGGER IF NOT EXISTS main.\"__{name}__record_changes_update\" AFTER UPDATE ON \"{name}\" \
+      BEGIN \
+        INSERT INTO {RECORD_CHANGES_TABLE} (table_name, record_id, op) \
+          SELECT '{literal}', OLD.\"{pk}\", 'delete' WHERE OLD.\"{pk}\" IS NOT NEW.\"{pk}\"; \
+        INSERT INTO {RECORD_CHANGES_TABLE} (table_name, record_id, op) VALUES ('{literal}', NEW.\"{pk}\", \
+          CASE WHEN OLD.\"{pk}\" IS NEW.\"{pk}\" THEN 'update' ELSE 'create' END); \
+      END; \
+      \
+      CREATE TRIGGER IF NOT EXISTS main.\"__{name}__record_changes_delete\" AFTER DELETE ON \"{name}\" \
+      BEGIN \
+        INSERT INTO {RECORD_CHANGES_TABLE} (table_name, record_id, op) VALUES ('{literal}', OLD.\"{pk}\", 'delete'); \
+      END; \
+    "
+  );
+}
+
+#[inline]
+fn escape_identifier(name: &str) -> String {
+  return name.replace('"', "\"\"");
+}
+
+const DEFAULT_LIMIT: usize = 256;
+const MAX_LIMIT: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::test_utils::add_record_api_config;
+
+  async fn list_changes(state: &AppState, since: Option<i64>) -> ChangesResponse {
+    return list_changes_handler(
+      State(state.clone()),
+      Path("items".to_string()),
+      Query(ChangesQuery { since, limit: None }),
+      None,
+    )
+    .await
+    .unwrap()
+    .0;
+  }
+
+  #[tokio::test]
+  async fn test_record_changes() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+    conn
+      .execute_batch(
+        "\
+          CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT) STRICT; \
+          INSERT INTO items (id, name) VALUES (1, 'before'); \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    let config = RecordApiConfig {
+      name: Some("items".to_string()),
+      table_name: Some("items".to_string()),
+      acl_world: [PermissionFlag::Read as i32].into(),
+      enable_change_tracking: Some(true),
+      ..Default::default()
+    };
+    add_record_api_config(&state, config).await.unwrap();
+
+    let initial = list_changes(&state, None).await;
+    assert!(initial.changes.is_empty());
+
+    conn
+      .execute_batch(
+        "\
+          INSERT INTO items (id, name) VALUES (2, 'a'); \
+          UPDATE items SET name = 'b' WHERE id = 1; \
+          UPDATE items SET id = 3 WHERE id = 2; \
+          DELETE FROM items WHERE id = 1; \
+        ",
+      )
+      .await
+      .unwrap();
+
+    let response = list_changes(&state, Some(initial.cursor)).await;
+    let changes: Vec<_> = response
+      .changes
+      .iter()
+      .map(|c| (c.op, c.id.as_str()))
+      .collect();
+    assert_eq!(
+      changes,
+      [
+        (RecordChangeOp::Create, "2"),
+        (RecordChangeOp::Update, "1"),
+        (RecordChangeOp::Delete, "2"),
+        (RecordChangeOp::Create, "3"),
+        (RecordChangeOp::Delete, "1"),
+      ]
+    );
+    assert!(!response.has_more);
+    assert!(!response.expired);
+
+    // Nothing new.
+    let next = list_changes(&state, Some(response.cursor)).await;
+    assert!(next.changes.is_empty());
+    assert_eq!(next.cursor, response.cursor);
+
+    // Pruned changes expire older cursors.
+    conn
+      .execute(
+        formatcp!("DELETE FROM '{RECORD_CHANGES_TABLE}' WHERE seq <= $1"),
+        params!(response.cursor - 1),
+      )
+      .await
+      .unwrap();
+    assert!(list_changes(&state, Some(initial.cursor)).await.expired);
+    assert!(
+      !list_changes(&state, Some(response.cursor - 1))
+        .await
+        .expired
+    );
+
+    // Disabling change tracking removes the triggers.
+    let mut next_config = (*state.get_config()).clone();
+    for api in &mut next_config.record_apis {
+      if api.name() == "items" {
+        api.enable_change_tracking = Some(false);
+      }
+    }
+    state
+      .validate_and_update_config(next_config, None)
+      .await
+      .unwrap();
+
+    let triggers: i64 = conn
+      .read_query_row_get(
+        "SELECT COUNT(*) FROM sqlite_schema WHERE type = 'trigger' AND tbl_name = 'items'",
+        (),
+        0,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(triggers, 0);
+  }
+}
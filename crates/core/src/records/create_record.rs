@@ -1,16 +1,25 @@
 use axum::extract::{Json, Path, Query, State};
+use axum::http::StatusCode;
 use axum::response::{IntoResponse, Redirect, Response};
 use base64::prelude::*;
 use serde::{Deserialize, Serialize};
 use trailbase_schema::{FileUploadInput, QualifiedNameEscaped};
+use trailbase_wasm_common::RecordHookOperation;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
 use crate::extract::Either;
-use crate::records::params::{JsonRow, LazyParams, Params};
-use crate::records::write_queries::{WriteQuery, run_insert_or_replace_query, run_queries};
-use crate::records::{Permission, RecordError};
+use crate::records::file_encryption::encrypt_files;
+use crate::records::hooks::{run_async_record_hooks, run_before_create_hooks, run_on_create_hooks};
+use crate::records::idempotency::{IdempotencyKey, IdempotentRequest, Reservation, fingerprint};
+use crate::records::params::{JsonRow, LazyParams, Params, check_column_write_access};
+use crate::records::scanner::scan_files;
+use crate::records::webhooks::{RecordOperation, enqueue_record_event};
+use crate::records::write_queries::{
+  WriteQuery, dry_run_queries, run_insert_or_replace_query, run_queries,
+};
+use crate::records::{Permission, RecordApi, RecordError};
 use crate::util::uuid_to_b64;
 
 #[derive(Clone, Debug, Default, Deserialize, IntoParams)]
@@ -20,6 +29,10 @@ pub struct CreateRecordQuery {
   ///
   /// We may want to have a different on-error redirect to better support the static HTML use-case.
   pub redirect_uri: Option<String>,
+
+  /// Validate the request, i.e. run access checks and apply the insert within a transaction that
+  /// is rolled back, without creating any records.
+  pub dry_run: Option<bool>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
@@ -73,10 +86,14 @@ fn extract_records(value: serde_json::Value) -> Result<Vec<RecordAndFiles>, Reco
   post,
   path = "/{name}",
   tag = "records",
-  params(CreateRecordQuery),
+  params(
+    CreateRecordQuery,
+    ("Idempotency-Key" = Option<String>, Header, description = "Key for safely retrying the request."),
+  ),
   request_body = serde_json::Value,
   responses(
     (status = 200, description = "Ids of successfully created records.", body = CreateRecordResponse),
+    (status = 204, description = "Successful dry run."),
   )
 )]
 pub async fn create_record_handler(
@@ -84,6 +101,7 @@ pub async fn create_record_handler(
   Path(api_name): Path<String>,
   Query(create_record_query): Query<CreateRecordQuery>,
   user: Option<User>,
+  idempotency_key: Option<IdempotencyKey>,
   either_request: Either<serde_json::Value>,
 ) -> Result<Response, RecordError> {
   let Some(api) = state.lookup_record_api(&api_name) else {
@@ -93,6 +111,56 @@ pub async fn create_record_handler(
     return Err(RecordError::ApiRequiresTable);
   }
 
+  if create_record_query.dry_run.unwrap_or(false) {
+    create_records(&state, &api, user.as_ref(), either_request, true).await?;
+    return Ok(StatusCode::NO_CONTENT.into_response());
+  }
+
+  let response = match idempotency_key {
+    Some(key) => {
+      let fingerprint = match either_request {
+        Either::Json(ref value) | Either::Form(ref value) => fingerprint(value, None),
+        Either::Multipart(ref value, ref files) => fingerprint(value, Some(files.as_slice())),
+      };
+
+      let request = IdempotentRequest::new(&api_name, user.as_ref(), key);
+      match request.reserve(state.session_conn(), fingerprint).await? {
+        Reservation::Completed(response) => response,
+        Reservation::Reserved => {
+          match create_records(&state, &api, user.as_ref(), either_request, false).await {
+            Ok(record_ids) => {
+              let response = CreateRecordResponse { ids: record_ids };
+              request.complete(state.session_conn(), &response).await?;
+              response
+            }
+            Err(err) => {
+              request.release(state.session_conn()).await?;
+              return Err(err);
+            }
+          }
+        }
+      }
+    }
+    None => CreateRecordResponse {
+      ids: create_records(&state, &api, user.as_ref(), either_request, false).await?,
+    },
+  };
+
+  if let Some(redirect_uri) = create_record_query.redirect_uri {
+    return Ok(Redirect::to(&redirect_uri).into_response());
+  }
+
+  return Ok(Json(response).into_response());
+}
+
+/// Creates one or more records and returns their url-safe base64 encoded ids.
+async fn create_records(
+  state: &AppState,
+  api: &RecordApi,
+  user: Option<&User>,
+  either_request: Either<serde_json::Value>,
+  dry_run: bool,
+) -> Result<Vec<String>, RecordError> {
   let records_and_files: Vec<RecordAndFiles> = match either_request {
     Either::Json(value) => extract_records(value)?,
     Either::Multipart(value, files) => vec![(extract_record(value)?, Some(files))],
@@ -101,8 +169,10 @@ pub async fn create_record_handler(
 
   let mut params_list: Vec<Params> = Vec::with_capacity(records_and_files.len());
   for (mut record, files) in records_and_files {
+    check_column_write_access(state, api, &record, files.as_deref(), false, user).await?;
+
     if api.insert_autofill_missing_user_id_columns()
-      && let Some(ref user) = user
+      && let Some(user) = user
     {
       for column_index in api.user_id_columns() {
         let col_name = &api.columns()[*column_index].column.name;
@@ -115,34 +185,46 @@ pub async fn create_record_handler(
       }
     }
 
+    for (column_name, field) in api.fill_on_create() {
+      record.insert(column_name.clone(), field.value(user));
+    }
+
+    run_before_create_hooks(state, api.api_name(), &mut record, user)?;
+    run_async_record_hooks(
+      state,
+      api.api_name(),
+      RecordHookOperation::Insert,
+      None,
+      Some(&mut record),
+      user,
+    )
+    .await?;
+
     #[cfg(debug_assertions)]
     crate::records::json_schema::validate_api_json_schema(
-      &state,
-      &api,
+      state,
+      api,
       trailbase_schema::json_schema::JsonSchemaMode::Insert,
       &serde_json::Value::Object(record.clone()),
     )
     .map_err(|_err| RecordError::BadRequest("Invalid Parameters"))?;
 
     let mut lazy_params =
-      LazyParams::for_insert(&api, state.json_schema_registry().clone(), record, files);
+      LazyParams::for_insert(api, state.json_schema_registry().clone(), record, files);
 
     // NOTE: We're currently serializing the async checks, we could parallelize them however it's
     // unclear if this would be much faster.
     api
-      .check_record_level_access(
-        Permission::Create,
-        None,
-        Some(&mut lazy_params),
-        user.as_ref(),
-      )
+      .check_record_level_access(Permission::Create, None, Some(&mut lazy_params), user)
       .await?;
 
-    params_list.push(
-      lazy_params
-        .consume()
-        .map_err(|_err| RecordError::BadRequest("Invalid Parameters"))?,
-    );
+    let params = lazy_params
+      .consume()
+      .map_err(|err| err.to_record_error("Invalid Parameters"))?;
+
+    run_on_create_hooks(state, api, params.named_params(), user)?;
+
+    params_list.push(params);
   }
 
   let pk_meta = api.record_pk_column();
@@ -150,6 +232,35 @@ pub async fn create_record_handler(
     .insert_conflict_resolution_strategy()
     .unwrap_or(crate::config::proto::ConflictResolutionStrategy::Undefined);
 
+  if dry_run && !params_list.is_empty() {
+    let queries = params_list
+      .into_iter()
+      .map(|params| {
+        return WriteQuery::new_insert_or_replace(
+          api.conn().connection_type(),
+          api.table_name(),
+          api.columns(),
+          &pk_meta.column.name,
+          conflict_resolution_strategy,
+          params,
+        )
+        .map(|(query, _files)| query);
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    dry_run_queries(api.conn(), queries).await?;
+    return Ok(vec![]);
+  }
+
+  for params in &mut params_list {
+    scan_files(state, params)
+      .await
+      .map_err(|err| err.into_record_error())?;
+    encrypt_files(state, params)
+      .await
+      .map_err(|err| RecordError::Internal(err.into()))?;
+  }
+
   let record_ids: Vec<String> = match params_list.len() {
     0 => {
       return Err(RecordError::BadRequest("no values provided"));
@@ -157,7 +268,7 @@ pub async fn create_record_handler(
     1 => {
       let record_id = run_insert_or_replace_query(
         api.conn(),
-        state.objectstore(),
+        &state.objectstore(),
         api.table_name(),
         api.columns(),
         conflict_resolution_strategy,
@@ -166,6 +277,14 @@ pub async fn create_record_handler(
       )
       .await?;
 
+      enqueue_record_event(
+        state,
+        api.qualified_name(),
+        RecordOperation::Insert,
+        &record_id,
+      )
+      .await;
+
       vec![extract_record_id(record_id)?]
     }
     _ => {
@@ -188,20 +307,28 @@ pub async fn create_record_handler(
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-      run_queries(conn, state.objectstore(), queries)
+      let record_ids = run_queries(conn, &state.objectstore(), queries)
         .await
-        .map_err(|err| RecordError::Internal(err.into()))?
+        .map_err(|err| RecordError::Internal(err.into()))?;
+
+      for record_id in &record_ids {
+        enqueue_record_event(
+          state,
+          api.qualified_name(),
+          RecordOperation::Insert,
+          record_id,
+        )
+        .await;
+      }
+
+      record_ids
         .into_iter()
         .map(extract_record_id)
         .collect::<Result<Vec<_>, _>>()?
     }
   };
 
-  if let Some(redirect_uri) = create_record_query.redirect_uri {
-    return Ok(Redirect::to(&redirect_uri).into_response());
-  }
-
-  return Ok(Json(CreateRecordResponse { ids: record_ids }).into_response());
+  return Ok(record_ids);
 }
 
 #[inline]
@@ -222,13 +349,16 @@ mod test {
   use crate::admin::user::*;
   use crate::app_state::*;
   use crate::auth::util::login_with_password;
-  use crate::config::proto::{ConflictResolutionStrategy, PermissionFlag, RecordApiConfig};
+  use crate::config::proto::{
+    ConflictResolutionStrategy, FileColumnPolicy, PermissionFlag, RecordApiConfig,
+  };
   use crate::records::test_utils::*;
   use crate::records::*;
   use crate::test::unpack_json_response;
   use crate::util::{id_to_b64, uuid_to_b64};
 
   use serde_json::json;
+  use trailbase_schema::FileUploadData;
   use trailbase_sqlite::params;
 
   #[tokio::test]
@@ -283,6 +413,7 @@ mod test {
         Path("simple_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        None,
         Either::Json(
           json_row_from_value(json!({
             "owner": id_to_b64(&user_x),
@@ -316,6 +447,7 @@ mod test {
         Path("simple_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        None,
         Either::Json(
           json_row_from_value(json!({
             "owner": uuid_to_b64(&uuid::Uuid::new_v4()),
@@ -391,6 +523,7 @@ mod test {
         Path("messages_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        None,
         Either::Json(json_row_from_value(json).unwrap().into()),
       )
       .await;
@@ -416,6 +549,7 @@ mod test {
         Path("messages_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        None,
         Either::Json(serde_json::Value::Array(vec![json(0), json(1)])),
       )
       .await;
@@ -438,6 +572,7 @@ mod test {
         Path("messages_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        None,
         Either::Json(json_row_from_value(json).unwrap().into()),
       )
       .await;
@@ -467,6 +602,7 @@ mod test {
         Path("messages_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        None,
         Either::Json(serde_json::Value::Array(vec![json(&user_x), json(&user_y)])),
       )
       .await;
@@ -493,6 +629,7 @@ mod test {
         Path("messages_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_y_token.auth_token),
+        None,
         Either::Json(json_row_from_value(json).unwrap().into()),
       )
       .await;
@@ -552,6 +689,7 @@ mod test {
         Path("messages_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        None,
         Either::Json(json_row_from_value(json).unwrap().into()),
       )
       .await;
@@ -559,4 +697,322 @@ mod test {
       assert!(response.is_ok(), "{response:?}");
     }
   }
+
+  #[tokio::test]
+  async fn test_record_api_create_idempotency_key() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE items (
+            id      INTEGER PRIMARY KEY,
+            value   TEXT NOT NULL
+          ) {strict};
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("items_api".to_string()),
+        table_name: Some("items".to_string()),
+        acl_world: [PermissionFlag::Create as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let create = async |key: &str, record: serde_json::Value| {
+      return create_record_handler(
+        State(state.clone()),
+        Path("items_api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        Some(IdempotencyKey(key.to_string())),
+        Either::Json(record),
+      )
+      .await;
+    };
+
+    let first: CreateRecordResponse =
+      unpack_json_response(create("key0", json!({"value": "a"})).await.unwrap())
+        .await
+        .unwrap();
+    let retry: CreateRecordResponse =
+      unpack_json_response(create("key0", json!({"value": "a"})).await.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(first.ids, retry.ids);
+
+    assert!(matches!(
+      create("key0", json!({"value": "b"})).await,
+      Err(RecordError::BadRequest(_))
+    ));
+
+    // Failed requests release their key.
+    assert!(create("key1", json!({})).await.is_err());
+    create("key1", json!({"value": "b"})).await.unwrap();
+
+    let count: i64 = conn
+      .read_query_row_get("SELECT COUNT(*) FROM items", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(2, count);
+  }
+
+  #[tokio::test]
+  async fn test_record_api_create_dry_run() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE items (
+            id      INTEGER PRIMARY KEY,
+            value   TEXT NOT NULL CHECK(LENGTH(value) < 5)
+          ) {strict};
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("items_api".to_string()),
+        table_name: Some("items".to_string()),
+        acl_world: [PermissionFlag::Create as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let dry_run = async |record: serde_json::Value| {
+      return create_record_handler(
+        State(state.clone()),
+        Path("items_api".to_string()),
+        Query(CreateRecordQuery {
+          dry_run: Some(true),
+          ..Default::default()
+        }),
+        None,
+        None,
+        Either::Json(record),
+      )
+      .await;
+    };
+
+    let response = dry_run(json!({"value": "a"})).await.unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+
+    assert!(dry_run(json!({"value": "too long"})).await.is_err());
+    assert!(
+      dry_run(json!([{"value": "a"}, {"value": "too long"}]))
+        .await
+        .is_err()
+    );
+
+    let count: i64 = conn
+      .read_query_row_get("SELECT COUNT(*) FROM items", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(0, count);
+  }
+
+  #[tokio::test]
+  async fn test_record_api_create_fill_on_create() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE posts (
+            id      INTEGER PRIMARY KEY,
+            owner   {uuid} REFERENCES _user,
+            email   TEXT,
+            body    TEXT
+          ) {strict};
+        "#,
+        strict = strict(conn),
+        uuid = uuid_column(conn),
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("posts_api".to_string()),
+        table_name: Some("posts".to_string()),
+        acl_world: [PermissionFlag::Create as i32].into(),
+        fill_on_create: [
+          ("owner".to_string(), "_USER_.id".to_string()),
+          ("email".to_string(), "_USER_.email".to_string()),
+        ]
+        .into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let password = "Secret!1!!";
+    let user_email = "user@test.com";
+    let user_id = create_user_for_test(&state, user_email, password)
+      .await
+      .unwrap();
+    let user_token = login_with_password(&state, user_email, password)
+      .await
+      .unwrap();
+
+    let create = async |user: Option<User>, record: serde_json::Value| {
+      return create_record_handler(
+        State(state.clone()),
+        Path("posts_api".to_string()),
+        Query(CreateRecordQuery::default()),
+        user,
+        None,
+        Either::Json(record),
+      )
+      .await;
+    };
+
+    // Client-provided values are overridden.
+    create(
+      User::from_auth_token(&state, &user_token.auth_token),
+      json!({"id": 1, "owner": uuid_to_b64(&uuid::Uuid::now_v7()), "body": "first"}),
+    )
+    .await
+    .unwrap();
+    create(None, json!({"id": 2, "email": "fake@test.com"}))
+      .await
+      .unwrap();
+
+    let rows = conn
+      .read_query_rows("SELECT owner, email FROM posts ORDER BY id", ())
+      .await
+      .unwrap();
+    assert_eq!(2, rows.len());
+    assert_eq!(
+      user_id.into_bytes().to_vec(),
+      rows[0].get::<Vec<u8>>(0).unwrap()
+    );
+    assert_eq!(user_email, rows[0].get::<String>(1).unwrap());
+    assert_eq!(None, rows[1].get::<Option<Vec<u8>>>(0).unwrap());
+    assert_eq!(None, rows[1].get::<Option<String>>(1).unwrap());
+  }
+
+  #[tokio::test]
+  async fn test_record_api_create_file_policies() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE uploads (
+            id      INTEGER PRIMARY KEY,
+            image   {json} CHECK(jsonschema('std.FileUpload', image)),
+            files   {json} CHECK(jsonschema('std.FileUploads', files))
+          ) {strict};
+        "#,
+        strict = strict(conn),
+        json = json_column(conn),
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("uploads_api".to_string()),
+        table_name: Some("uploads".to_string()),
+        acl_world: [PermissionFlag::Create as i32].into(),
+        file_policies: [
+          (
+            "image".to_string(),
+            FileColumnPolicy {
+              allowed_mime_types: vec!["image/*".to_string()],
+              max_file_size: Some(16),
+              ..Default::default()
+            },
+          ),
+          (
+            "files".to_string(),
+            FileColumnPolicy {
+              max_file_count: Some(1),
+              ..Default::default()
+            },
+          ),
+        ]
+        .into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let create = async |record: serde_json::Value| {
+      return create_record_handler(
+        State(state.clone()),
+        Path("uploads_api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(record),
+      )
+      .await;
+    };
+    let file = |data: &[u8]| {
+      return FileUploadInput {
+        name: None,
+        filename: Some("file".to_string()),
+        content_type: Some("text/plain".to_string()),
+        data: FileUploadData::Bytes(data.to_vec()),
+      };
+    };
+
+    const PNG_MAGIC: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    create(json!({"id": 1, "image": file(PNG_MAGIC)}))
+      .await
+      .unwrap();
+
+    assert!(matches!(
+      create(json!({"id": 2, "image": file(b"text")})).await,
+      Err(RecordError::BadRequest("File type not allowed"))
+    ));
+    assert!(matches!(
+      create(json!({"id": 2, "image": file(&[PNG_MAGIC, &[0; 16]].concat())})).await,
+      Err(RecordError::BadRequest("File too large"))
+    ));
+
+    create(json!({"id": 2, "files": [file(b"text")]}))
+      .await
+      .unwrap();
+    assert!(matches!(
+      create(json!({"id": 3, "files": [file(b"a"), file(b"b")]})).await,
+      Err(RecordError::BadRequest("Too many files"))
+    ));
+  }
 }
@@ -3,9 +3,12 @@ use axum::{
   http::StatusCode,
   response::{IntoResponse, Response},
 };
+use trailbase_wasm_common::RecordHookOperation;
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
+use crate::records::hooks::{run_async_record_hooks, run_on_delete_hooks};
+use crate::records::webhooks::{RecordOperation, enqueue_record_event};
 use crate::records::write_queries::run_delete_query;
 use crate::records::{Permission, RecordError};
 
@@ -30,24 +33,43 @@ pub async fn delete_record_handler(
     return Err(RecordError::ApiRequiresTable);
   }
 
-  let record_id = api.primary_key_to_value(record)?;
+  let record_id = api.primary_key_to_value(record.clone())?;
 
   api
     .check_record_level_access(Permission::Delete, Some(&record_id), None, user.as_ref())
     .await?;
 
+  run_async_record_hooks(
+    &state,
+    &api_name,
+    RecordHookOperation::Delete,
+    Some(&record),
+    None,
+    user.as_ref(),
+  )
+  .await?;
+  run_on_delete_hooks(&state, &api, &record_id, user.as_ref())?;
+
   let pk_meta = api.record_pk_column();
 
   run_delete_query(
     api.conn(),
-    state.objectstore(),
+    &state.objectstore(),
     api.table_name(),
     &pk_meta.column.name,
-    record_id,
+    record_id.clone(),
     api.has_file_columns(),
   )
   .await?;
 
+  enqueue_record_event(
+    &state,
+    api.qualified_name(),
+    RecordOperation::Delete,
+    &record_id,
+  )
+  .await;
+
   return Ok((StatusCode::OK, "deleted").into_response());
 }
 
@@ -181,6 +203,7 @@ mod test {
       Path("messages_api".to_string()),
       Query(CreateRecordQuery::default()),
       User::from_auth_token(state, auth_token),
+      None,
       Either::Json(json_row_from_value(create_json).unwrap().into()),
     )
     .await;
@@ -3,6 +3,8 @@ use axum::http::{StatusCode, header::CONTENT_TYPE};
 use axum::response::{IntoResponse, Response};
 use thiserror::Error;
 
+use crate::logging::with_error_fields;
+
 /// Publicly visible errors of record APIs.
 ///
 /// This error is deliberately opaque and kept very close to HTTP error codes to avoid the leaking
@@ -20,6 +22,8 @@ pub enum RecordError {
   Forbidden,
   #[error("Bad request: {0}")]
   BadRequest(&'static str),
+  #[error("Conflict: {0}")]
+  Conflict(&'static str),
   #[error("Internal: {0}")]
   Internal(Box<dyn std::error::Error + Send + Sync>),
 }
@@ -50,6 +54,8 @@ impl From<trailbase_sqlite::Error> for RecordError {
 
               Self::BadRequest("rejected")
             }
+            // Interrupted, e.g. due to exceeding the query timeout.
+            9 => Self::BadRequest("query timeout"),
             275 => Self::BadRequest("db constraint: check"),
             531 => Self::BadRequest("db constraint: commit hook"),
             3091 => Self::BadRequest("db constraint: data type"),
@@ -87,29 +93,41 @@ impl From<object_store::Error> for RecordError {
 
 impl IntoResponse for RecordError {
   fn into_response(self) -> Response {
+    // Internal errors aren't exposed to clients in release builds but should still be logged.
+    let error = match self {
+      Self::Internal(ref err) => Some(err.to_string()),
+      _ => None,
+    };
+
     let (status, body) = match self {
       Self::ApiNotFound => (StatusCode::METHOD_NOT_ALLOWED, None),
       Self::ApiRequiresTable => (StatusCode::METHOD_NOT_ALLOWED, None),
       Self::RecordNotFound => (StatusCode::NOT_FOUND, None),
       Self::Forbidden => (StatusCode::FORBIDDEN, None),
       Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, Some(msg.to_string())),
+      Self::Conflict(msg) => (StatusCode::CONFLICT, Some(msg.to_string())),
       Self::Internal(err) if cfg!(debug_assertions) => {
         (StatusCode::INTERNAL_SERVER_ERROR, Some(err.to_string()))
       }
       Self::Internal(_err) => (StatusCode::INTERNAL_SERVER_ERROR, None),
     };
 
-    if let Some(body) = body {
-      return Response::builder()
+    let response = if let Some(body) = body {
+      Response::builder()
         .status(status)
         .header(CONTENT_TYPE, "text/plain")
         .body(Body::new(body))
-        .unwrap_or_default();
-    }
+        .unwrap_or_default()
+    } else {
+      Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap_or_default()
+    };
 
-    return Response::builder()
-      .status(status)
-      .body(Body::empty())
-      .unwrap_or_default();
+    return match error {
+      Some(err) => with_error_fields(response, err),
+      None => response,
+    };
   }
 }
@@ -1,10 +1,10 @@
 use itertools::Itertools;
 use std::collections::HashMap;
 use thiserror::Error;
-use trailbase_schema::QualifiedName;
 use trailbase_schema::json::value_to_flat_json;
 use trailbase_schema::metadata::ColumnMetadata;
 use trailbase_schema::sqlite::ColumnOption;
+use trailbase_schema::{QualifiedName, QualifiedNameEscaped};
 
 use crate::records::RecordError;
 use crate::records::record_api::RecordApi;
@@ -168,6 +168,8 @@ pub(crate) struct ExpandedTable<'a> {
   pub local_column_name: String,
   pub num_columns: usize,
 
+  /// Fully qualified foreign table, i.e. in the same database as the API's table.
+  pub foreign_table: QualifiedNameEscaped,
   pub foreign_table_name: String,
   pub foreign_column_name: String,
 }
@@ -232,6 +234,7 @@ pub(crate) fn expand_tables<'s, T: AsRef<str>>(
       metadata: foreign_table,
       local_column_name: col_name.to_string(),
       num_columns,
+      foreign_table: QualifiedNameEscaped::new(&fq_foreign_table_name),
       foreign_table_name,
       foreign_column_name,
     });
@@ -0,0 +1,444 @@
+use axum::{
+  Json,
+  body::Body,
+  extract::{Path, Query, RawQuery, State},
+  http::{HeaderMap, header},
+  response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use trailbase_qs::Query as ListQuery;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::auth::util::is_admin;
+use crate::listing::limit_or_default;
+use crate::records::list_records::{
+  ListOrGeoJSONResponse, ListRecordsQuery, list_records, list_records_handler, parse_list_query,
+};
+use crate::records::protobuf::accepts_protobuf;
+use crate::records::{Permission, RecordApi, RecordError};
+
+/// Streaming export formats for listing records, negotiated via the `Accept` header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ExportFormat {
+  NdJson,
+  Csv,
+}
+
+impl ExportFormat {
+  pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+    let accept = headers.get(header::ACCEPT)?.to_str().ok()?;
+
+    for media_type in accept.split(',') {
+      let media_type = media_type.split(';').next().unwrap_or_default().trim();
+      if media_type.eq_ignore_ascii_case("application/x-ndjson") {
+        return Some(Self::NdJson);
+      } else if media_type.eq_ignore_ascii_case("text/csv") {
+        return Some(Self::Csv);
+      }
+    }
+
+    return None;
+  }
+
+  fn content_type(&self) -> &'static str {
+    return match self {
+      Self::NdJson => "application/x-ndjson",
+      Self::Csv => "text/csv; charset=utf-8",
+    };
+  }
+}
+
+/// Lists records or, if requested via the `Accept` header, encodes them as protobuf or streams
+/// them as NDJSON or CSV.
+pub async fn list_or_export_records_handler(
+  State(state): State<AppState>,
+  Path(api_name): Path<String>,
+  Query(query): Query<ListRecordsQuery>,
+  RawQuery(raw_url_query): RawQuery,
+  headers: HeaderMap,
+  user: Option<User>,
+) -> Result<Response, RecordError> {
+  if accepts_protobuf(&headers) {
+    let Some(api) = state.lookup_record_api(&api_name) else {
+      return Err(RecordError::ApiNotFound);
+    };
+    if query.geojson.is_some() {
+      return Err(RecordError::BadRequest(
+        "GeoJSON cannot be encoded as protobuf",
+      ));
+    }
+
+    let Json(response) = list_records_handler(
+      State(state),
+      Path(api_name),
+      Query(query),
+      RawQuery(raw_url_query),
+      user,
+    )
+    .await?;
+
+    return match response {
+      ListOrGeoJSONResponse::List(list) => api.protobuf_descriptors()?.encode_list(&list),
+      #[cfg(any(feature = "geos", feature = "geos-static"))]
+      ListOrGeoJSONResponse::GeoJSON(_) => Err(RecordError::Internal("Unexpected GeoJSON".into())),
+    };
+  }
+
+  let Some(format) = ExportFormat::from_headers(&headers) else {
+    return list_records_handler(
+      State(state),
+      Path(api_name),
+      Query(query),
+      RawQuery(raw_url_query),
+      user,
+    )
+    .await
+    .map(IntoResponse::into_response);
+  };
+
+  return export_records(state, api_name, query, raw_url_query, user, format).await;
+}
+
+async fn export_records(
+  state: AppState,
+  api_name: String,
+  query: ListRecordsQuery,
+  raw_url_query: Option<String>,
+  user: Option<User>,
+  format: ExportFormat,
+) -> Result<Response, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  api.check_table_level_access(Permission::Read, user.as_ref())?;
+
+  if query.geojson.is_some() {
+    return Err(RecordError::BadRequest("GeoJSON cannot be exported"));
+  }
+
+  let mut qs_query = parse_list_query(raw_url_query.as_deref())?;
+  // Only records are streamed.
+  qs_query.count = None;
+
+  // Exporting entire tables via `limit=none` is reserved to admins.
+  let remaining = match qs_query.limit {
+    Some(ListQuery::NO_LIMIT) => {
+      let Some(ref user) = user else {
+        return Err(RecordError::Forbidden);
+      };
+      if !is_admin(&state, &user.uuid).await {
+        return Err(RecordError::Forbidden);
+      }
+      None
+    }
+    limit => Some(
+      limit_or_default(limit.or(api.default_limit()), api.listing_hard_limit())
+        .map_err(RecordError::BadRequest)?,
+    ),
+  };
+
+  let columns: Vec<String> = api
+    .readable_columns(
+      qs_query
+        .select
+        .as_ref()
+        .map(|select| select.columns.as_slice()),
+      user.as_ref(),
+    )
+    .ok_or(RecordError::BadRequest("Invalid select"))?
+    .iter()
+    .map(|meta| meta.column.name.clone())
+    .filter(|name| !name.starts_with("_"))
+    .collect();
+
+  let header = match format {
+    ExportFormat::NdJson => None,
+    ExportFormat::Csv => Some(csv_rows(&columns, &[], true)?),
+  };
+
+  let batch_size = api
+    .listing_hard_limit()
+    .map_or(EXPORT_BATCH_SIZE, |l| l.min(EXPORT_BATCH_SIZE));
+
+  let stream = futures_util::stream::try_unfold(
+    Some(ExportState {
+      state,
+      api,
+      api_name,
+      qs_query,
+      user,
+      format,
+      columns,
+      header,
+      batch_size,
+      remaining,
+    }),
+    |export_state| async move {
+      let Some(mut export_state) = export_state else {
+        return Ok(None);
+      };
+      let (chunk, done) = export_state.next_page().await?;
+      return Ok(Some((chunk, (!done).then_some(export_state))));
+    },
+  );
+
+  return Ok(
+    (
+      [(header::CONTENT_TYPE, format.content_type())],
+      Body::from_stream(stream),
+    )
+      .into_response(),
+  );
+}
+
+struct ExportState {
+  state: AppState,
+  api: RecordApi,
+  api_name: String,
+  qs_query: ListQuery,
+  user: Option<User>,
+
+  format: ExportFormat,
+  columns: Vec<String>,
+  /// CSV header to be emitted with the first chunk.
+  header: Option<Bytes>,
+
+  batch_size: usize,
+  /// Remaining number of records to export. None if unlimited.
+  remaining: Option<usize>,
+}
+
+impl ExportState {
+  /// Fetches and serializes the next page. Returns true if this was the last page.
+  ///
+  /// NOTE: Paging keeps memory bounded, however the export isn't a consistent snapshot.
+  async fn next_page(&mut self) -> Result<(Bytes, bool), RecordError> {
+    let limit = self
+      .remaining
+      .map_or(self.batch_size, |r| r.min(self.batch_size));
+
+    let records = if limit == 0 {
+      vec![]
+    } else {
+      let mut qs_query = self.qs_query.clone();
+      qs_query.limit = Some(limit);
+
+      let Json(response) = list_records(
+        self.state.clone(),
+        &self.api,
+        self.api_name.clone(),
+        ListRecordsQuery::default(),
+        qs_query,
+        self.user.clone(),
+      )
+      .await?;
+
+      let list = match response {
+        ListOrGeoJSONResponse::List(list) => list,
+        #[cfg(any(feature = "geos", feature = "geos-static"))]
+        ListOrGeoJSONResponse::GeoJSON(_) => {
+          return Err(RecordError::Internal("Unexpected GeoJSON".into()));
+        }
+      };
+
+      // Prefer cursors over offsets, when available.
+      if let Some(cursor) = list.cursor {
+        self.qs_query.cursor = Some(cursor);
+        self.qs_query.offset = None;
+      } else {
+        self.qs_query.offset = Some(self.qs_query.offset.unwrap_or(0) + list.records.len());
+      }
+
+      list.records
+    };
+
+    if let Some(ref mut remaining) = self.remaining {
+      *remaining -= records.len();
+    }
+    let done = records.len() < limit || self.remaining == Some(0);
+
+    let mut chunk: Vec<u8> = self.header.take().map(Vec::from).unwrap_or_default();
+    match self.format {
+      ExportFormat::NdJson => {
+        for record in &records {
+          serde_json::to_writer(&mut chunk, record)
+            .map_err(|err| RecordError::Internal(err.into()))?;
+          chunk.push(b'\n');
+        }
+      }
+      ExportFormat::Csv => {
+        chunk.extend_from_slice(&csv_rows(&self.columns, &records, false)?);
+      }
+    };
+
+    return Ok((Bytes::from(chunk), done));
+  }
+}
+
+/// Serializes records as CSV rows, optionally preceded by a header row.
+fn csv_rows(
+  columns: &[String],
+  records: &[serde_json::Value],
+  header: bool,
+) -> Result<Bytes, RecordError> {
+  let mut writer = csv::Writer::from_writer(vec![]);
+
+  if header {
+    writer
+      .write_record(columns)
+      .map_err(|err| RecordError::Internal(err.into()))?;
+  }
+
+  for record in records {
+    writer
+      .write_record(columns.iter().map(|name| {
+        return match record.get(name) {
+          None | Some(serde_json::Value::Null) => String::new(),
+          Some(serde_json::Value::String(s)) => s.clone(),
+          // Numbers, bools and expanded records.
+          Some(value) => value.to_string(),
+        };
+      }))
+      .map_err(|err| RecordError::Internal(err.into()))?;
+  }
+
+  return writer
+    .into_inner()
+    .map(Bytes::from)
+    .map_err(|err| RecordError::Internal(err.to_string().into()));
+}
+
+const EXPORT_BATCH_SIZE: usize = 256;
+
+#[cfg(test)]
+mod tests {
+  use axum::http::HeaderValue;
+  use http_body_util::BodyExt;
+
+  use super::*;
+  use crate::admin::user::create_user_for_test;
+  use crate::app_state::test_state;
+  use crate::auth::util::login_with_password;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::test_utils::*;
+
+  #[test]
+  fn test_export_format() {
+    let headers = |accept: &'static str| {
+      return HeaderMap::from_iter([(header::ACCEPT, HeaderValue::from_static(accept))]);
+    };
+
+    assert_eq!(None, ExportFormat::from_headers(&HeaderMap::new()));
+    assert_eq!(
+      None,
+      ExportFormat::from_headers(&headers("application/json"))
+    );
+    assert_eq!(
+      Some(ExportFormat::NdJson),
+      ExportFormat::from_headers(&headers("application/x-ndjson"))
+    );
+    assert_eq!(
+      Some(ExportFormat::Csv),
+      ExportFormat::from_headers(&headers("text/html;q=0.9, text/csv; charset=utf-8"))
+    );
+  }
+
+  #[tokio::test]
+  async fn test_export_records() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE export (
+            id      INTEGER PRIMARY KEY,
+            value   TEXT
+          ) {strict};
+
+          INSERT INTO export (id, value) VALUES (1, 'a,b'), (2, NULL), (3, 'c');
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("export".to_string()),
+        acl_world: [PermissionFlag::Read as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let export = async |query: &str, format: ExportFormat, user: Option<User>| {
+      let response = export_records(
+        state.clone(),
+        "api".to_string(),
+        ListRecordsQuery::default(),
+        Some(query.to_string()),
+        user,
+        format,
+      )
+      .await?;
+
+      let body = response.into_body().collect().await.unwrap().to_bytes();
+      return Ok::<_, RecordError>(String::from_utf8(body.to_vec()).unwrap());
+    };
+
+    assert_eq!(
+      "{\"id\":1,\"value\":\"a,b\"}\n{\"id\":2,\"value\":null}\n",
+      export("order=id&limit=2", ExportFormat::NdJson, None)
+        .await
+        .unwrap()
+    );
+    assert_eq!(
+      "id,value\n3,c\n2,\n1,\"a,b\"\n",
+      export("", ExportFormat::Csv, None).await.unwrap()
+    );
+    assert_eq!(
+      "value\nc\n",
+      export("select=value&filter[id]=3", ExportFormat::Csv, None)
+        .await
+        .unwrap()
+    );
+
+    // Unlimited exports require admin privileges.
+    assert!(matches!(
+      export("limit=none", ExportFormat::NdJson, None).await,
+      Err(RecordError::Forbidden)
+    ));
+
+    let password = "Secret!1!!";
+    create_user_for_test(&state, "admin@test.com", password)
+      .await
+      .unwrap();
+    crate::auth::cli::promote_user_to_admin(
+      state.user_conn(),
+      crate::auth::cli::UserReference::Email("admin@test.com".to_string()),
+    )
+    .await
+    .unwrap();
+    let tokens = login_with_password(&state, "admin@test.com", password)
+      .await
+      .unwrap();
+    let admin = User::from_auth_token(&state, &tokens.auth_token);
+
+    assert_eq!(
+      3,
+      export("limit=none", ExportFormat::NdJson, admin)
+        .await
+        .unwrap()
+        .lines()
+        .count()
+    );
+  }
+}
@@ -0,0 +1,592 @@
+//! Envelope encryption of uploaded files at rest.
+//!
+//! Every file is encrypted with its own random data key. The data key is wrapped by a
+//! [FileKeyProvider], e.g. using the master key from the config or an external KMS, and stored as
+//! part of the file's metadata. Contents are split into fixed-size segments, which are sealed
+//! individually so that files can be streamed in and out of the object store w/o ever being
+//! materialized in memory.
+
+use async_trait::async_trait;
+use base64::prelude::*;
+use bytes::Bytes;
+use futures_util::stream::BoxStream;
+use futures_util::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use trailbase_schema::{FileEncryption, FileUpload, FileUploadData, FileUploads, SpooledFile};
+use trailbase_sqlite::Value;
+
+use crate::app_state::AppState;
+use crate::config::proto::FileEncryptionConfig;
+use crate::encryption::{KeyType, decrypt, encrypt, generate_random_key};
+use crate::records::params::Params;
+
+#[derive(Debug, Error)]
+pub enum FileEncryptionError {
+  #[error("No key provider configured")]
+  MissingKeyProvider,
+  #[error("Invalid key: {0}")]
+  InvalidKey(&'static str),
+  #[error("Crypto error: {0}")]
+  Crypto(&'static str),
+  #[error("Key provider error: {0}")]
+  Provider(Box<dyn std::error::Error + Send + Sync>),
+  #[error("IO error: {0}")]
+  IO(#[from] std::io::Error),
+  #[error("Storage error: {0}")]
+  Storage(#[from] object_store::Error),
+  #[error("Json serialization error: {0}")]
+  JsonSerialization(#[from] serde_json::Error),
+}
+
+/// A data key wrapped, i.e. encrypted, by a [FileKeyProvider].
+#[derive(Clone, Debug, PartialEq)]
+pub struct WrappedKey {
+  /// Identifies the key-encryption key, e.g. a KMS key id, to support key rotation.
+  pub key_id: Option<String>,
+  pub key: Vec<u8>,
+}
+
+/// Wraps and unwraps per-file data keys, e.g. by calling out to an external KMS.
+///
+/// A custom provider can be registered via [crate::ServerOptions::file_key_provider] or
+/// [AppState::register_file_key_provider]. Otherwise, the `server.file_encryption.master_key`
+/// from the config is used.
+#[async_trait]
+pub trait FileKeyProvider: Send + Sync {
+  /// Wraps a freshly generated data key before it's stored alongside the file's metadata.
+  async fn wrap_key(&self, data_key: &[u8]) -> Result<WrappedKey, FileEncryptionError>;
+
+  /// Unwraps a data key previously wrapped by [FileKeyProvider::wrap_key].
+  async fn unwrap_key(&self, wrapped_key: &WrappedKey) -> Result<Vec<u8>, FileEncryptionError>;
+}
+
+impl std::fmt::Debug for dyn FileKeyProvider {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return f.write_str("FileKeyProvider");
+  }
+}
+
+/// Wraps data keys with a static master key from the config.
+pub(crate) struct MasterKeyProvider {
+  key: KeyType,
+  /// Fingerprint of the master key to detect mismatches, e.g. after rotation.
+  key_id: String,
+}
+
+impl MasterKeyProvider {
+  pub(crate) fn from_base64(master_key: &str) -> Result<Self, FileEncryptionError> {
+    let key = BASE64_STANDARD
+      .decode(master_key)
+      .or_else(|_| BASE64_URL_SAFE.decode(master_key))
+      .map_err(|_| FileEncryptionError::InvalidKey("master key is not base64"))?;
+    if key.len() != KEY_LENGTH {
+      return Err(FileEncryptionError::InvalidKey(
+        "master key must be 256 bits",
+      ));
+    }
+
+    return Ok(Self {
+      key_id: BASE64_URL_SAFE_NO_PAD.encode(&Sha256::digest(&key)[..8]),
+      key: KeyType::clone_from_slice(&key),
+    });
+  }
+}
+
+#[async_trait]
+impl FileKeyProvider for MasterKeyProvider {
+  async fn wrap_key(&self, data_key: &[u8]) -> Result<WrappedKey, FileEncryptionError> {
+    return Ok(WrappedKey {
+      key_id: Some(self.key_id.clone()),
+      key: encrypt(&self.key, WRAPPED_KEY_AAD, data_key).map_err(FileEncryptionError::Crypto)?,
+    });
+  }
+
+  async fn unwrap_key(&self, wrapped_key: &WrappedKey) -> Result<Vec<u8>, FileEncryptionError> {
+    if wrapped_key
+      .key_id
+      .as_ref()
+      .is_some_and(|id| *id != self.key_id)
+    {
+      return Err(FileEncryptionError::InvalidKey("unknown master key"));
+    }
+
+    return decrypt(&self.key, WRAPPED_KEY_AAD, &wrapped_key.key)
+      .map_err(FileEncryptionError::Crypto);
+  }
+}
+
+pub(crate) fn build_file_key_provider(
+  config: Option<&FileEncryptionConfig>,
+) -> Option<Arc<dyn FileKeyProvider>> {
+  let master_key = config?.master_key.as_ref()?;
+  return match MasterKeyProvider::from_base64(master_key) {
+    Ok(provider) => Some(Arc::new(provider)),
+    Err(err) => {
+      log::error!("Failed to load file encryption master key: {err}");
+      None
+    }
+  };
+}
+
+/// Encrypts the contents of all files about to be written, if file encryption is enabled, and
+/// records their wrapped data keys in the files' metadata.
+pub(crate) async fn encrypt_files(
+  state: &AppState,
+  params: &mut Params,
+) -> Result<(), FileEncryptionError> {
+  let (named_params, files) = params.named_params_and_files_mut();
+  if files.iter().all(|(_, contents)| contents.is_none()) || !state.file_encryption_enabled() {
+    return Ok(());
+  }
+
+  let mut encrypted = HashMap::<String, FileEncryption>::new();
+  for (metadata, contents) in files.iter_mut() {
+    let Some(data) = contents.take() else {
+      continue;
+    };
+
+    let (encryption, data_key) = new_data_key(state).await?;
+    *contents = Some(encrypt_contents(data_key, data).await?);
+
+    metadata.set_encryption(Some(encryption.clone()));
+    encrypted.insert(metadata.objectstore_id().to_string(), encryption);
+  }
+
+  // The files' metadata has already been serialized into the query parameters, amend it.
+  for (_name, value) in named_params.iter_mut() {
+    let Value::Text(json) = value else {
+      continue;
+    };
+
+    if let Ok(mut file) = serde_json::from_str::<FileUpload>(json) {
+      if let Some(encryption) = encrypted.get(file.objectstore_id()) {
+        file.set_encryption(Some(encryption.clone()));
+        *json = serde_json::to_string(&file)?;
+      }
+    } else if let Ok(mut files) = serde_json::from_str::<FileUploads>(json) {
+      let mut amended = false;
+      for file in &mut files.0 {
+        if let Some(encryption) = encrypted.get(file.objectstore_id()) {
+          file.set_encryption(Some(encryption.clone()));
+          amended = true;
+        }
+      }
+
+      if amended {
+        *json = serde_json::to_string(&files)?;
+      }
+    }
+  }
+
+  return Ok(());
+}
+
+/// Generates a new data key and wraps it using the configured [FileKeyProvider].
+pub(crate) async fn new_data_key(
+  state: &AppState,
+) -> Result<(FileEncryption, KeyType), FileEncryptionError> {
+  let Some(provider) = state.file_key_provider() else {
+    return Err(FileEncryptionError::MissingKeyProvider);
+  };
+
+  let data_key = generate_random_key();
+  let WrappedKey { key_id, key } = provider.wrap_key(&data_key).await?;
+
+  return Ok((
+    FileEncryption {
+      key_id,
+      wrapped_key: BASE64_URL_SAFE.encode(key),
+    },
+    data_key,
+  ));
+}
+
+/// Unwraps the data key of an encrypted file using the configured [FileKeyProvider].
+pub(crate) async fn unwrap_data_key(
+  state: &AppState,
+  encryption: &FileEncryption,
+) -> Result<KeyType, FileEncryptionError> {
+  let Some(provider) = state.file_key_provider() else {
+    return Err(FileEncryptionError::MissingKeyProvider);
+  };
+
+  let key = BASE64_URL_SAFE
+    .decode(&encryption.wrapped_key)
+    .map_err(|_| FileEncryptionError::InvalidKey("wrapped key is not base64"))?;
+  let data_key = provider
+    .unwrap_key(&WrappedKey {
+      key_id: encryption.key_id.clone(),
+      key,
+    })
+    .await?;
+  if data_key.len() != KEY_LENGTH {
+    return Err(FileEncryptionError::InvalidKey("data key must be 256 bits"));
+  }
+
+  return Ok(KeyType::clone_from_slice(&data_key));
+}
+
+async fn encrypt_contents(
+  data_key: KeyType,
+  data: FileUploadData,
+) -> Result<FileUploadData, FileEncryptionError> {
+  let mut encryptor = SegmentEncryptor::new(data_key);
+
+  return match data {
+    FileUploadData::Bytes(bytes) => {
+      let mut sealed = encryptor.update(&bytes)?;
+      sealed.extend(encryptor.finish()?);
+      Ok(FileUploadData::Bytes(sealed))
+    }
+    FileUploadData::Spooled(spooled) => {
+      // Set up first to make sure the file gets removed on error.
+      let mut encrypted = SpooledFile {
+        path: spooled.path.with_extension("enc"),
+        len: 0,
+        head: spooled.head.clone(),
+        digest: spooled.digest.clone(),
+      };
+
+      let mut input = tokio::fs::File::open(&spooled.path).await?;
+      let mut output = tokio::io::BufWriter::new(tokio::fs::File::create(&encrypted.path).await?);
+      let mut buffer = vec![0; SEGMENT_SIZE];
+      loop {
+        let n = input.read(&mut buffer).await?;
+        if n == 0 {
+          break;
+        }
+
+        let sealed = encryptor.update(&buffer[..n])?;
+        output.write_all(&sealed).await?;
+        encrypted.len += sealed.len() as u64;
+      }
+
+      let sealed = encryptor.finish()?;
+      output.write_all(&sealed).await?;
+      output.flush().await?;
+      encrypted.len += sealed.len() as u64;
+
+      Ok(FileUploadData::Spooled(Arc::new(encrypted)))
+    }
+  };
+}
+
+/// Decrypts a stream of sealed segments, e.g. an encrypted file read from the object store.
+pub(crate) fn decrypt_stream(
+  data_key: KeyType,
+  input: BoxStream<'static, Result<Bytes, object_store::Error>>,
+) -> impl Stream<Item = Result<Bytes, FileEncryptionError>> + Send + 'static {
+  return futures_util::stream::try_unfold(
+    (input, Some(SegmentDecryptor::new(data_key))),
+    |(mut input, decryptor)| async move {
+      let Some(mut decryptor) = decryptor else {
+        return Ok(None);
+      };
+
+      loop {
+        match input.next().await {
+          Some(chunk) => {
+            let opened = decryptor.update(&chunk?)?;
+            if !opened.is_empty() {
+              return Ok(Some((Bytes::from(opened), (input, Some(decryptor)))));
+            }
+          }
+          None => {
+            let opened = decryptor.finish()?;
+            return Ok(Some((Bytes::from(opened), (input, None))));
+          }
+        }
+      }
+    },
+  );
+}
+
+/// Decrypts contents in memory, e.g. to derive thumbnails.
+pub(crate) fn decrypt_bytes(
+  data_key: KeyType,
+  sealed: &[u8],
+) -> Result<Vec<u8>, FileEncryptionError> {
+  let mut decryptor = SegmentDecryptor::new(data_key);
+  let mut opened = decryptor.update(sealed)?;
+  opened.extend(decryptor.finish()?);
+  return Ok(opened);
+}
+
+/// Seals contents incrementally segment by segment.
+///
+/// Each segment is authenticated together with its index and whether it's the last one, which
+/// prevents reordering and truncation.
+pub(crate) struct SegmentEncryptor {
+  key: KeyType,
+  index: u64,
+  pending: Vec<u8>,
+}
+
+impl SegmentEncryptor {
+  pub(crate) fn new(key: KeyType) -> Self {
+    return Self {
+      key,
+      index: 0,
+      pending: vec![],
+    };
+  }
+
+  /// Buffers `data` and returns all segments sealed so far. The last segment is held back until
+  /// [SegmentEncryptor::finish].
+  pub(crate) fn update(&mut self, data: &[u8]) -> Result<Vec<u8>, FileEncryptionError> {
+    self.pending.extend_from_slice(data);
+
+    let mut sealed = vec![];
+    while self.pending.len() > SEGMENT_SIZE {
+      let rest = self.pending.split_off(SEGMENT_SIZE);
+      let segment = std::mem::replace(&mut self.pending, rest);
+      sealed.extend(self.seal(&segment, false)?);
+    }
+    return Ok(sealed);
+  }
+
+  pub(crate) fn finish(mut self) -> Result<Vec<u8>, FileEncryptionError> {
+    let segment = std::mem::take(&mut self.pending);
+    return self.seal(&segment, true);
+  }
+
+  fn seal(&mut self, segment: &[u8], last: bool) -> Result<Vec<u8>, FileEncryptionError> {
+    let sealed = encrypt(&self.key, &segment_aad(self.index, last), segment)
+      .map_err(FileEncryptionError::Crypto)?;
+    self.index += 1;
+    return Ok(sealed);
+  }
+}
+
+struct SegmentDecryptor {
+  key: KeyType,
+  index: u64,
+  pending: Vec<u8>,
+}
+
+impl SegmentDecryptor {
+  fn new(key: KeyType) -> Self {
+    return Self {
+      key,
+      index: 0,
+      pending: vec![],
+    };
+  }
+
+  fn update(&mut self, data: &[u8]) -> Result<Vec<u8>, FileEncryptionError> {
+    self.pending.extend_from_slice(data);
+
+    let mut opened = vec![];
+    while self.pending.len() > SEALED_SEGMENT_SIZE {
+      let rest = self.pending.split_off(SEALED_SEGMENT_SIZE);
+      let segment = std::mem::replace(&mut self.pending, rest);
+      opened.extend(self.open(&segment, false)?);
+    }
+    return Ok(opened);
+  }
+
+  fn finish(mut self) -> Result<Vec<u8>, FileEncryptionError> {
+    let segment = std::mem::take(&mut self.pending);
+    return self.open(&segment, true);
+  }
+
+  fn open(&mut self, segment: &[u8], last: bool) -> Result<Vec<u8>, FileEncryptionError> {
+    let opened = decrypt(&self.key, &segment_aad(self.index, last), segment)
+      .map_err(FileEncryptionError::Crypto)?;
+    self.index += 1;
+    return Ok(opened);
+  }
+}
+
+fn segment_aad(index: u64, last: bool) -> [u8; 9] {
+  let mut aad = [0; 9];
+  aad[..8].copy_from_slice(&index.to_be_bytes());
+  aad[8] = last as u8;
+  return aad;
+}
+
+const KEY_LENGTH: usize = 32;
+const SEGMENT_SIZE: usize = 64 * 1024;
+// Every sealed segment is prefixed by a 12 byte nonce and followed by a 16 byte tag.
+const SEALED_SEGMENT_SIZE: usize = SEGMENT_SIZE + 12 + 16;
+const WRAPPED_KEY_AAD: &[u8] = b"trailbase-file-data-key";
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::{Path, Query, State};
+  use axum::http::HeaderMap;
+  use object_store::ObjectStoreExt;
+  use serde_json::json;
+  use trailbase_schema::FileUploadInput;
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::extract::Either;
+  use crate::records::create_record::{
+    CreateRecordQuery, CreateRecordResponse, create_record_handler,
+  };
+  use crate::records::files::SignedFileQuery;
+  use crate::records::read_record::get_uploaded_file_from_record_handler;
+  use crate::records::test_utils::*;
+  use crate::records::thumbnail::ThumbnailQuery;
+  use crate::test::unpack_json_response;
+
+  #[test]
+  fn test_segment_encryption() {
+    let key = generate_random_key();
+
+    for len in [0, 1, SEGMENT_SIZE - 1, SEGMENT_SIZE, 3 * SEGMENT_SIZE + 7] {
+      let contents: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+
+      let mut encryptor = SegmentEncryptor::new(key);
+      let mut sealed = vec![];
+      // Feed in odd-sized chunks.
+      for chunk in contents.chunks(10_000) {
+        sealed.extend(encryptor.update(chunk).unwrap());
+      }
+      sealed.extend(encryptor.finish().unwrap());
+      assert_ne!(contents, sealed);
+
+      assert_eq!(contents, decrypt_bytes(key, &sealed).unwrap());
+
+      // Truncation is detected.
+      if len > SEGMENT_SIZE {
+        assert!(decrypt_bytes(key, &sealed[..SEALED_SEGMENT_SIZE]).is_err());
+      }
+      assert!(decrypt_bytes(generate_random_key(), &sealed).is_err());
+    }
+  }
+
+  #[tokio::test]
+  async fn test_master_key_provider() {
+    let master_key = BASE64_STANDARD.encode(generate_random_key());
+    let provider = MasterKeyProvider::from_base64(&master_key).unwrap();
+
+    let data_key = generate_random_key();
+    let wrapped = provider.wrap_key(&data_key).await.unwrap();
+    assert_eq!(
+      data_key.to_vec(),
+      provider.unwrap_key(&wrapped).await.unwrap()
+    );
+
+    let other =
+      MasterKeyProvider::from_base64(&BASE64_STANDARD.encode(generate_random_key())).unwrap();
+    assert!(other.unwrap_key(&wrapped).await.is_err());
+
+    assert!(MasterKeyProvider::from_base64("short").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_encrypted_file_round_trip() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE encrypted (
+            id      INTEGER PRIMARY KEY,
+            file    {json} CHECK(jsonschema('std.FileUpload', file))
+          ) {strict};
+        "#,
+        strict = strict(conn),
+        json = json_column(conn),
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("encrypted".to_string()),
+        acl_world: [PermissionFlag::Create as i32, PermissionFlag::Read as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let mut config = (*state.get_config()).clone();
+    config.server.file_encryption = Some(FileEncryptionConfig {
+      enabled: Some(true),
+      master_key: Some(BASE64_STANDARD.encode(generate_random_key())),
+    });
+    state
+      .validate_and_update_config(config, None)
+      .await
+      .unwrap();
+
+    let contents = b"top secret".to_vec();
+    let response: CreateRecordResponse = unpack_json_response(
+      create_record_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(json!({
+          "id": 1,
+          "file": FileUploadInput {
+            name: None,
+            filename: Some("secret.txt".to_string()),
+            content_type: Some("text/plain".to_string()),
+            data: FileUploadData::Bytes(contents.clone()),
+          },
+        })),
+      )
+      .await
+      .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let json: String = conn
+      .read_query_row_get("SELECT file FROM encrypted WHERE id = 1", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    let file: FileUpload = serde_json::from_str(&json).unwrap();
+    assert!(file.encryption().is_some());
+
+    let stored = state
+      .objectstore()
+      .get(&object_store::path::Path::from(file.objectstore_id()))
+      .await
+      .unwrap()
+      .bytes()
+      .await
+      .unwrap();
+    assert!(
+      !stored
+        .windows(contents.len())
+        .any(|w| w == contents.as_slice())
+    );
+
+    let read = get_uploaded_file_from_record_handler(
+      State(state.clone()),
+      Path((
+        "api".to_string(),
+        response.ids[0].clone(),
+        "file".to_string(),
+      )),
+      Query(SignedFileQuery::default()),
+      Query(ThumbnailQuery::default()),
+      None,
+      HeaderMap::new(),
+    )
+    .await
+    .unwrap();
+    let body = axum::body::to_bytes(read.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert_eq!(contents, body.to_vec());
+  }
+}
@@ -1,18 +1,30 @@
 use axum::body::Body;
-use axum::http::header;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
 use axum::response::{IntoResponse, Response};
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
 use itertools::Itertools;
 use log::*;
-use object_store::{ObjectStore, ObjectStoreExt};
+use object_store::{GetOptions, GetRange, ObjectMeta, ObjectStore, ObjectStoreExt, WriteMultipart};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use std::collections::HashSet;
 use std::sync::Arc;
 use thiserror::Error;
-use trailbase_schema::{FileUpload, FileUploads, QualifiedName, QualifiedNameEscaped};
+use tokio::io::AsyncReadExt;
+use trailbase_schema::{
+  FileUpload, FileUploadData, FileUploads, QualifiedName, QualifiedNameEscaped, SpooledFile,
+};
 use trailbase_sqlite::params;
+use utoipa::IntoParams;
 
 use crate::app_state::AppState;
+use crate::auth::jwt::JwtHelper;
+use crate::constants::RECORD_API_PATH;
+use crate::metrics::{ObjectStoreOp, record_object_store_op};
+use crate::records::file_encryption::{FileEncryptionError, decrypt_stream, unwrap_data_key};
 use crate::records::params::FileMetadataContents;
+use crate::records::thumbnail::delete_thumbnails;
 
 #[derive(Debug, Error)]
 pub enum FileError {
@@ -24,43 +36,275 @@ pub enum FileError {
   JsonSerialization(#[from] serde_json::Error),
   #[error("SQL error: {0}")]
   Sql(#[from] trailbase_sqlite::Error),
+  #[error("Encryption error: {0}")]
+  Encryption(#[from] FileEncryptionError),
 }
 
+/// Serves the file's contents honoring conditional (`If-None-Match`, `If-Modified-Since`) and
+/// single-range requests, e.g. for browser caching and media playback.
 pub(crate) async fn read_file_into_response(
   state: &AppState,
   file_upload: FileUpload,
+  request_headers: &HeaderMap,
 ) -> Result<Response, FileError> {
-  let result = state
-    .objectstore()
-    .get(&object_store::path::Path::from(
-      file_upload.objectstore_id(),
-    ))
+  let store = state.objectstore();
+  let path = object_store::path::Path::from(file_upload.objectstore_id());
+  // Ranges would need to be mapped onto sealed segments, simply serve encrypted files in full.
+  let encrypted = file_upload.encryption().is_some();
+
+  let range_header = request_headers
+    .get(header::RANGE)
+    .and_then(|v| v.to_str().ok())
+    .filter(|_| !encrypted);
+
+  let mut range: Option<std::ops::Range<u64>> = None;
+  if range_header.is_some()
+    || request_headers.contains_key(header::IF_NONE_MATCH)
+    || request_headers.contains_key(header::IF_MODIFIED_SINCE)
+  {
+    record_object_store_op(ObjectStoreOp::Head);
+    let meta = store.head(&path).await?;
+    if !is_modified(request_headers, &meta) {
+      return Ok(
+        (
+          StatusCode::NOT_MODIFIED,
+          validator_headers(&meta, encrypted),
+        )
+          .into_response(),
+      );
+    }
+
+    match range_header.and_then(|v| parse_range(v, meta.size)) {
+      Some(Ok(r)) => range = Some(r),
+      Some(Err(())) => {
+        return Ok(
+          (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(header::CONTENT_RANGE, format!("bytes */{}", meta.size))],
+          )
+            .into_response(),
+        );
+      }
+      None => {}
+    };
+  }
+
+  record_object_store_op(ObjectStoreOp::Get);
+  let result = store
+    .get_opts(
+      &path,
+      GetOptions {
+        range: range.clone().map(GetRange::Bounded),
+        ..Default::default()
+      },
+    )
     .await?;
 
-  let headers = || {
-    return [
-      (
-        header::CONTENT_TYPE,
-        file_upload.content_type().map_or_else(
-          || "text/plain; charset=utf-8".to_string(),
-          |c| c.to_string(),
-        ),
-      ),
-      (header::CONTENT_DISPOSITION, "attachment".to_string()),
-    ];
-  };
+  let mut headers = validator_headers(&result.meta, encrypted);
+  headers.insert(
+    header::CONTENT_TYPE,
+    HeaderValue::from_str(
+      file_upload
+        .content_type()
+        .unwrap_or("text/plain; charset=utf-8"),
+    )
+    .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+  );
+  headers.insert(
+    header::CONTENT_DISPOSITION,
+    HeaderValue::from_static("attachment"),
+  );
 
-  return match result.payload {
-    object_store::GetResultPayload::File(_file, path) => {
-      let contents = tokio::fs::read(path).await?;
-      Ok((headers(), Body::from(contents)).into_response())
+  if let Some(encryption) = file_upload.encryption() {
+    let data_key = unwrap_data_key(state, encryption).await?;
+    let stream = decrypt_stream(data_key, result.into_stream());
+    return Ok((headers, Body::from_stream(stream)).into_response());
+  }
+
+  let size = result.meta.size;
+  let status = match range {
+    Some(ref r) => {
+      headers.insert(
+        header::CONTENT_RANGE,
+        HeaderValue::from_str(&format!("bytes {}-{}/{size}", r.start, r.end - 1)).expect("ascii"),
+      );
+      headers.insert(header::CONTENT_LENGTH, HeaderValue::from(r.end - r.start));
+      StatusCode::PARTIAL_CONTENT
+    }
+    None => {
+      headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+      StatusCode::OK
     }
-    object_store::GetResultPayload::Stream(stream) => {
-      Ok((headers(), Body::from_stream(stream)).into_response())
+  };
+
+  return Ok((status, headers, Body::from_stream(result.into_stream())).into_response());
+}
+
+/// `ETag` and `Last-Modified` validators as well as range support for the given object.
+fn validator_headers(meta: &ObjectMeta, encrypted: bool) -> HeaderMap {
+  let mut headers = HeaderMap::new();
+  if let Some(etag) = meta.e_tag.as_deref().map(quote_etag)
+    && let Ok(value) = HeaderValue::from_str(&etag)
+  {
+    headers.insert(header::ETAG, value);
+  }
+  if let Ok(value) = HeaderValue::from_str(&http_date(&meta.last_modified)) {
+    headers.insert(header::LAST_MODIFIED, value);
+  }
+  headers.insert(
+    header::ACCEPT_RANGES,
+    HeaderValue::from_static(if encrypted { "none" } else { "bytes" }),
+  );
+  return headers;
+}
+
+/// Evaluates the request's preconditions. Following RFC 9110, `If-Modified-Since` is ignored if
+/// `If-None-Match` is present.
+fn is_modified(request_headers: &HeaderMap, meta: &ObjectMeta) -> bool {
+  if let Some(if_none_match) = request_headers
+    .get(header::IF_NONE_MATCH)
+    .and_then(|v| v.to_str().ok())
+  {
+    let Some(etag) = meta.e_tag.as_deref().map(quote_etag) else {
+      return true;
+    };
+
+    // Weak comparison, i.e. ignore "W/" prefixes.
+    let etag = etag.trim_start_matches("W/");
+    return !if_none_match
+      .split(',')
+      .map(|tag| tag.trim())
+      .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+  }
+
+  if let Some(since) = request_headers
+    .get(header::IF_MODIFIED_SINCE)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+  {
+    // HTTP dates have second precision.
+    return meta.last_modified.timestamp() > since.timestamp();
+  }
+
+  return true;
+}
+
+/// Parses a single-range `Range` header. Returns `None` for anything unsupported, e.g. multiple
+/// ranges, in which case the entire file is served, and an error if the range cannot be
+/// satisfied.
+fn parse_range(value: &str, size: u64) -> Option<Result<std::ops::Range<u64>, ()>> {
+  let spec = value.trim().strip_prefix("bytes=")?;
+  if spec.contains(',') {
+    return None;
+  }
+
+  let (start, end) = spec.split_once('-')?;
+  let (start, end) = (start.trim(), end.trim());
+
+  if start.is_empty() {
+    // Suffix range, i.e. the last N bytes.
+    let suffix: u64 = end.parse().ok()?;
+    if suffix == 0 || size == 0 {
+      return Some(Err(()));
     }
+    return Some(Ok(size.saturating_sub(suffix)..size));
+  }
+
+  let start: u64 = start.parse().ok()?;
+  let end: Option<u64> = match end {
+    "" => None,
+    end => Some(end.parse().ok()?),
+  };
+  if end.is_some_and(|end| end < start) {
+    return None;
+  }
+  if start >= size {
+    return Some(Err(()));
+  }
+
+  return Some(Ok(start..end.map_or(size, |end| (end + 1).min(size))));
+}
+
+fn quote_etag(etag: &str) -> String {
+  if etag.starts_with('"') || etag.starts_with("W/") {
+    return etag.to_string();
+  }
+  return format!("\"{etag}\"");
+}
+
+fn http_date(date: &chrono::DateTime<chrono::Utc>) -> String {
+  return date.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+}
+
+/// Query parameters of file URLs signed via [signed_file_url], which grant access to the file
+/// until they expire w/o requiring an `Authorization` header.
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct SignedFileQuery {
+  /// Expiration as UNIX timestamp in seconds.
+  pub expires: Option<i64>,
+  /// URL-safe base64 encoded HMAC of the file's path and expiration.
+  pub signature: Option<String>,
+}
+
+/// Builds a relative, signed URL for the file located at the given path segments relative to the
+/// Record API, e.g. `[<api>, <record>, "file", <column>]`.
+pub(crate) fn signed_file_url(jwt: &JwtHelper, segments: &[&str], expires: i64) -> String {
+  let mut url = url::Url::parse("http://localhost").expect("invariant");
+  url
+    .path_segments_mut()
+    .expect("invariant")
+    .extend(RECORD_API_PATH.split('/'))
+    .extend(segments);
+  url
+    .query_pairs_mut()
+    .append_pair("expires", &expires.to_string())
+    .append_pair(
+      "signature",
+      &BASE64_URL_SAFE_NO_PAD.encode(file_url_mac(jwt, segments, expires).finalize().into_bytes()),
+    );
+
+  return url[url::Position::BeforePath..].to_string();
+}
+
+/// Whether `query` carries a valid, non-expired signature for the file at the given path
+/// segments.
+pub(crate) fn verify_signed_file_url(
+  jwt: &JwtHelper,
+  segments: &[&str],
+  query: &SignedFileQuery,
+) -> bool {
+  let (Some(expires), Some(signature)) = (query.expires, &query.signature) else {
+    return false;
   };
+
+  if expires < chrono::Utc::now().timestamp() {
+    return false;
+  }
+
+  let Ok(signature) = BASE64_URL_SAFE_NO_PAD.decode(signature) else {
+    return false;
+  };
+
+  return file_url_mac(jwt, segments, expires)
+    .verify_slice(&signature)
+    .is_ok();
 }
 
+fn file_url_mac(jwt: &JwtHelper, segments: &[&str], expires: i64) -> Hmac<Sha256> {
+  // Derived from the persisted JWT private key, i.e. signed URLs survive restarts and are
+  // invalidated by rotating the keys.
+  let key = jwt.derive_key(FILE_URL_SIGNING_KEY_PURPOSE);
+  let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("any key length");
+  for segment in segments {
+    mac.update(segment.as_bytes());
+    mac.update(&[0]);
+  }
+  mac.update(&expires.to_be_bytes());
+  return mac;
+}
+
+const FILE_URL_SIGNING_KEY_PURPOSE: &str = "file-url-signing";
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub(crate) struct FileDeletionsDb {
   id: i64,
@@ -192,6 +436,7 @@ pub(crate) async fn delete_pending_files_impl(
 
   let mut errors: Vec<FileDeletionsDb> = vec![];
   let mut delete = async |row: &FileDeletionsDb, file: FileUpload| {
+    record_object_store_op(ObjectStoreOp::Delete);
     let result = store
       .delete(&object_store::path::Path::from(file.objectstore_id()))
       .await;
@@ -210,7 +455,9 @@ pub(crate) async fn delete_pending_files_impl(
           warn!("Abandoning deletion of {file:?} after {ATTEMPTS_LIMIT} attempts: {err}");
         }
       }
-      Ok(_) => {}
+      Ok(_) => {
+        delete_thumbnails(store, &file).await;
+      }
     };
   };
 
@@ -298,9 +545,17 @@ impl FileManager {
         // TODO: We could write files in parallel.
         let path = object_store::path::Path::from(metadata.objectstore_id());
 
-        let mut writer = store.put_multipart(&path).await?;
-        writer.put_part(contents.into()).await?;
-        writer.complete().await?;
+        match contents {
+          FileUploadData::Bytes(bytes) => {
+            record_object_store_op(ObjectStoreOp::Put);
+            let mut writer = store.put_multipart(&path).await?;
+            writer.put_part(bytes.into()).await?;
+            writer.complete().await?;
+          }
+          FileUploadData::Spooled(spooled) => {
+            write_spooled_file(store, &path, &spooled).await?;
+          }
+        }
 
         written_files.push(metadata);
       }
@@ -314,6 +569,7 @@ impl FileManager {
         tokio::spawn(async move {
           for file in written_files {
             let path = object_store::path::Path::from(file.objectstore_id());
+            record_object_store_op(ObjectStoreOp::Delete);
             if let Err(err) = store.delete(&path).await {
               warn!("Failed to cleanup just written file: {err}");
             }
@@ -330,6 +586,57 @@ impl FileManager {
   }
 }
 
+/// Streams a spooled file into the object store chunk by chunk rather than reading it into
+/// memory all at once.
+async fn write_spooled_file(
+  store: &Arc<dyn ObjectStore>,
+  path: &object_store::path::Path,
+  spooled: &SpooledFile,
+) -> Result<(), object_store::Error> {
+  record_object_store_op(ObjectStoreOp::Put);
+  let mut writer =
+    WriteMultipart::new_with_chunk_size(store.put_multipart(path).await?, SPOOLED_FILE_CHUNK_SIZE);
+
+  let result: Result<(), object_store::Error> = async {
+    let mut file = tokio::fs::File::open(&spooled.path)
+      .await
+      .map_err(spool_error)?;
+    let mut buffer = vec![0; SPOOLED_FILE_CHUNK_SIZE];
+    loop {
+      let n = file.read(&mut buffer).await.map_err(spool_error)?;
+      if n == 0 {
+        return Ok(());
+      }
+
+      writer
+        .wait_for_capacity(MAX_CONCURRENT_SPOOLED_FILE_PARTS)
+        .await?;
+      writer.write(&buffer[..n]);
+    }
+  }
+  .await;
+
+  if let Err(err) = result {
+    if let Err(err) = writer.abort().await {
+      warn!("Failed to abort multipart write: {err}");
+    }
+    return Err(err);
+  }
+
+  writer.finish().await?;
+  return Ok(());
+}
+
+fn spool_error(err: std::io::Error) -> object_store::Error {
+  return object_store::Error::Generic {
+    store: "spool",
+    source: err.into(),
+  };
+}
+
+const SPOOLED_FILE_CHUNK_SIZE: usize = 5 * 1024 * 1024;
+const MAX_CONCURRENT_SPOOLED_FILE_PARTS: usize = 4;
+
 impl Drop for FileManager {
   fn drop(&mut self) {
     if let Some(f) = std::mem::take(&mut self.cleanup) {
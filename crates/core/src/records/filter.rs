@@ -79,6 +79,19 @@ pub(crate) fn qs_value_to_sql_with_constraints(
   };
 }
 
+/// Whether `op` can be applied to the given column, e.g. `LIKE` requires a textual column and
+/// spatial operators require a geometry column.
+pub(crate) fn is_supported_op(meta: &ColumnMetadata, op: CompareOp) -> bool {
+  return match op {
+    CompareOp::Like | CompareOp::Regexp => matches!(
+      meta.column.data_type,
+      ColumnDataType::Text | ColumnDataType::Any
+    ),
+    CompareOp::StWithin | CompareOp::StIntersects | CompareOp::StContains => meta.is_geometry,
+    _ => true,
+  };
+}
+
 pub(crate) fn qs_filter_to_record_filter(
   column_metadata: &[ColumnMetadata],
   filter: trailbase_qs::ValueOrComposite,
@@ -90,6 +103,10 @@ pub(crate) fn qs_filter_to_record_filter(
         .find(|meta| meta.column.name == col_op_value.column)
         .ok_or_else(|| RecordError::BadRequest("Invalid query"))?;
 
+      if !is_supported_op(meta, col_op_value.op) {
+        return Err(RecordError::BadRequest("Invalid query"));
+      }
+
       Ok(ValueOrComposite::Value(ColumnOpValue {
         column: col_op_value.column,
         op: col_op_value.op,
@@ -0,0 +1,535 @@
+use async_trait::async_trait;
+use trailbase_sqlite::{NamedParams, Value};
+use trailbase_wasm_common::{RecordHookOperation, RecordHookRequest};
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::records::{RecordApi, RecordError};
+
+/// Hooks into the Record API lifecycle, e.g. to normalize input before it's validated and
+/// written, to enforce custom invariants or to redact fields before records are returned.
+///
+/// Hooks are registered via [crate::ServerOptions::record_hooks] or
+/// [AppState::register_record_hooks] and apply to all Record APIs. They run synchronously on the
+/// request path and should therefore be cheap. Returning an error aborts the request.
+///
+/// NOTE: Hooks only apply to the create, read, update, delete and list endpoints. Transactions
+/// and subscriptions are not affected.
+pub trait RecordHooks: Send + Sync {
+  /// Called with the parsed record before it's converted into query parameters and before access
+  /// checks run.
+  fn before_create(
+    &self,
+    _api_name: &str,
+    _record: &mut serde_json::Map<String, serde_json::Value>,
+    _user: Option<&User>,
+  ) -> Result<(), RecordError> {
+    return Ok(());
+  }
+
+  /// Called with the parsed partial record before it's converted into query parameters and before
+  /// access checks run. For increments, the record holds the deltas.
+  fn before_update(
+    &self,
+    _api_name: &str,
+    _record: &mut serde_json::Map<String, serde_json::Value>,
+    _user: Option<&User>,
+  ) -> Result<(), RecordError> {
+    return Ok(());
+  }
+
+  /// Called for every record read or listed before it's serialized.
+  fn after_read(&self, _api_name: &str, _record: &mut serde_json::Value, _user: Option<&User>) {}
+
+  /// Called with the converted parameters after access checks passed and right before the record
+  /// is inserted.
+  fn on_create(
+    &self,
+    _state: &AppState,
+    _api: &RecordApi,
+    _params: &NamedParams,
+    _user: Option<&User>,
+  ) -> Result<(), RecordError> {
+    return Ok(());
+  }
+
+  /// Called with the converted parameters after access checks passed and right before the record
+  /// is updated.
+  fn on_update(
+    &self,
+    _state: &AppState,
+    _api: &RecordApi,
+    _record_id: &Value,
+    _params: &NamedParams,
+    _user: Option<&User>,
+  ) -> Result<(), RecordError> {
+    return Ok(());
+  }
+
+  /// Called after access checks passed and right before the record is deleted.
+  fn on_delete(
+    &self,
+    _state: &AppState,
+    _api: &RecordApi,
+    _record_id: &Value,
+    _user: Option<&User>,
+  ) -> Result<(), RecordError> {
+    return Ok(());
+  }
+}
+
+/// Asynchronous hooks bound to a specific Record API and operation, e.g. implemented by WASM
+/// components.
+///
+/// They run after the synchronous [RecordHooks::before_create] and [RecordHooks::before_update]
+/// hooks for inserts and updates, and after access checks for deletions.
+#[async_trait]
+pub(crate) trait AsyncRecordHook: Send + Sync {
+  /// Returns a replacement for the record of inserts and updates or `None` to keep it unchanged.
+  async fn call(
+    &self,
+    request: &RecordHookRequest,
+    user: Option<&User>,
+  ) -> Result<Option<serde_json::Map<String, serde_json::Value>>, RecordError>;
+}
+
+impl std::fmt::Debug for dyn RecordHooks {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return f.write_str("RecordHooks");
+  }
+}
+
+pub(crate) fn run_before_create_hooks(
+  state: &AppState,
+  api_name: &str,
+  record: &mut serde_json::Map<String, serde_json::Value>,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  for hooks in state.record_hooks().iter() {
+    hooks.before_create(api_name, record, user)?;
+  }
+  return Ok(());
+}
+
+pub(crate) fn run_before_update_hooks(
+  state: &AppState,
+  api_name: &str,
+  record: &mut serde_json::Map<String, serde_json::Value>,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  for hooks in state.record_hooks().iter() {
+    hooks.before_update(api_name, record, user)?;
+  }
+  return Ok(());
+}
+
+pub(crate) async fn run_async_record_hooks(
+  state: &AppState,
+  api_name: &str,
+  operation: RecordHookOperation,
+  record_id: Option<&str>,
+  record: Option<&mut serde_json::Map<String, serde_json::Value>>,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  let Some(hooks) = state.async_record_hooks(api_name, operation) else {
+    return Ok(());
+  };
+
+  let mut request = RecordHookRequest {
+    api_name: api_name.to_string(),
+    operation,
+    record_id: record_id.map(|id| id.to_string()),
+    record: record.as_deref().cloned(),
+  };
+  for hook in hooks.iter() {
+    if let Some(replacement) = hook.call(&request, user).await?
+      && request.record.is_some()
+    {
+      request.record = Some(replacement);
+    }
+  }
+
+  if let (Some(record), Some(replacement)) = (record, request.record) {
+    *record = replacement;
+  }
+  return Ok(());
+}
+
+pub(crate) fn run_after_read_hooks(
+  state: &AppState,
+  api_name: &str,
+  records: &mut [serde_json::Value],
+  user: Option<&User>,
+) {
+  let hooks = state.record_hooks();
+  if hooks.is_empty() {
+    return;
+  }
+
+  for record in records {
+    for hooks in hooks.iter() {
+      hooks.after_read(api_name, record, user);
+    }
+  }
+}
+
+pub(crate) fn run_on_create_hooks(
+  state: &AppState,
+  api: &RecordApi,
+  params: &NamedParams,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  for hooks in state.record_hooks().iter() {
+    hooks.on_create(state, api, params, user)?;
+  }
+  return Ok(());
+}
+
+pub(crate) fn run_on_update_hooks(
+  state: &AppState,
+  api: &RecordApi,
+  record_id: &Value,
+  params: &NamedParams,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  for hooks in state.record_hooks().iter() {
+    hooks.on_update(state, api, record_id, params, user)?;
+  }
+  return Ok(());
+}
+
+pub(crate) fn run_on_delete_hooks(
+  state: &AppState,
+  api: &RecordApi,
+  record_id: &Value,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  for hooks in state.record_hooks().iter() {
+    hooks.on_delete(state, api, record_id, user)?;
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::Json;
+  use axum::extract::{Path, Query, RawQuery, State};
+  use serde_json::json;
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::extract::Either;
+  use crate::records::create_record::{CreateRecordQuery, create_record_handler};
+  use crate::records::delete_record::delete_record_handler;
+  use crate::records::list_records::{
+    ListOrGeoJSONResponse, ListRecordsQuery, list_records_handler,
+  };
+  use crate::records::read_record::{ReadRecordQuery, read_record_handler};
+  use crate::records::test_utils::*;
+
+  struct TestHooks;
+
+  impl RecordHooks for TestHooks {
+    fn before_create(
+      &self,
+      _api_name: &str,
+      record: &mut serde_json::Map<String, serde_json::Value>,
+      _user: Option<&User>,
+    ) -> Result<(), RecordError> {
+      let Some(serde_json::Value::String(name)) = record.get("name") else {
+        return Err(RecordError::BadRequest("missing name"));
+      };
+      let normalized = name.trim().to_lowercase();
+      record.insert("name".to_string(), serde_json::Value::String(normalized));
+      return Ok(());
+    }
+
+    fn after_read(&self, _api_name: &str, record: &mut serde_json::Value, _user: Option<&User>) {
+      if let Some(obj) = record.as_object_mut() {
+        obj.remove("secret");
+      }
+    }
+  }
+
+  #[tokio::test]
+  async fn test_record_hooks() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE hooked (
+            id      INTEGER PRIMARY KEY,
+            name    TEXT NOT NULL,
+            secret  TEXT
+          ) {strict};
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("hooked".to_string()),
+        acl_world: [PermissionFlag::Create as i32, PermissionFlag::Read as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    state.register_record_hooks(Arc::new(TestHooks));
+
+    let create = async |record: serde_json::Value| {
+      return create_record_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(record),
+      )
+      .await;
+    };
+
+    create(json!({"id": 1, "name": "  Alice ", "secret": "s3cr3t"}))
+      .await
+      .unwrap();
+    assert!(matches!(
+      create(json!({"id": 2, "secret": "s3cr3t"})).await,
+      Err(RecordError::BadRequest("missing name"))
+    ));
+
+    let Json(record) = read_record_handler(
+      State(state.clone()),
+      Path(("api".to_string(), "1".to_string())),
+      Query(ReadRecordQuery::default()),
+      None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(json!({"id": 1, "name": "alice"}), record);
+
+    let ListOrGeoJSONResponse::List(list) = list_records_handler(
+      State(state.clone()),
+      Path("api".to_string()),
+      Query(ListRecordsQuery::default()),
+      RawQuery(None),
+      None,
+    )
+    .await
+    .unwrap()
+    .0
+    else {
+      panic!("not a list");
+    };
+    assert_eq!(vec![json!({"id": 1, "name": "alice"})], list.records);
+  }
+
+  struct ProtectedHooks;
+
+  impl RecordHooks for ProtectedHooks {
+    fn on_create(
+      &self,
+      _state: &AppState,
+      api: &RecordApi,
+      params: &NamedParams,
+      _user: Option<&User>,
+    ) -> Result<(), RecordError> {
+      assert_eq!("api", api.api_name());
+      if params
+        .iter()
+        .any(|(name, value)| name == ":name" && *value == Value::Text("root".to_string()))
+      {
+        return Err(RecordError::Forbidden);
+      }
+      return Ok(());
+    }
+
+    fn on_delete(
+      &self,
+      _state: &AppState,
+      _api: &RecordApi,
+      record_id: &Value,
+      _user: Option<&User>,
+    ) -> Result<(), RecordError> {
+      if *record_id == Value::Integer(1) {
+        return Err(RecordError::BadRequest("protected"));
+      }
+      return Ok(());
+    }
+  }
+
+  #[tokio::test]
+  async fn test_record_lifecycle_hooks() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE protected (
+            id      INTEGER PRIMARY KEY,
+            name    TEXT NOT NULL
+          ) {strict};
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("protected".to_string()),
+        acl_world: [PermissionFlag::Create as i32, PermissionFlag::Delete as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    state.register_record_hooks(Arc::new(ProtectedHooks));
+
+    let create = async |record: serde_json::Value| {
+      return create_record_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(record),
+      )
+      .await;
+    };
+    let delete = async |id: &str| {
+      return delete_record_handler(
+        State(state.clone()),
+        Path(("api".to_string(), id.to_string())),
+        None,
+      )
+      .await;
+    };
+
+    assert!(matches!(
+      create(json!({"id": 1, "name": "root"})).await,
+      Err(RecordError::Forbidden)
+    ));
+    create(json!({"id": 1, "name": "alice"})).await.unwrap();
+    create(json!({"id": 2, "name": "bob"})).await.unwrap();
+
+    assert!(matches!(
+      delete("1").await,
+      Err(RecordError::BadRequest("protected"))
+    ));
+    delete("2").await.unwrap();
+
+    let count: i64 = conn
+      .read_query_row_get("SELECT COUNT(*) FROM protected", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(1, count);
+  }
+
+  struct TaggingHook;
+
+  #[async_trait]
+  impl AsyncRecordHook for TaggingHook {
+    async fn call(
+      &self,
+      request: &RecordHookRequest,
+      _user: Option<&User>,
+    ) -> Result<Option<serde_json::Map<String, serde_json::Value>>, RecordError> {
+      if request.operation == RecordHookOperation::Delete {
+        return match request.record_id.as_deref() {
+          Some("1") => Err(RecordError::Forbidden),
+          _ => Ok(None),
+        };
+      }
+
+      let mut record = request.record.clone().unwrap_or_default();
+      record.insert("name".to_string(), json!("tagged"));
+      return Ok(Some(record));
+    }
+  }
+
+  #[tokio::test]
+  async fn test_async_record_hooks() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE tagged (
+            id      INTEGER PRIMARY KEY,
+            name    TEXT NOT NULL
+          ) {strict};
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("tagged".to_string()),
+        acl_world: [PermissionFlag::Create as i32, PermissionFlag::Delete as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    for operation in [RecordHookOperation::Insert, RecordHookOperation::Delete] {
+      state.register_async_record_hook("api", operation, Arc::new(TaggingHook));
+    }
+
+    for id in [1, 2] {
+      create_record_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(json!({"id": id, "name": "alice"})),
+      )
+      .await
+      .unwrap();
+    }
+
+    let delete = async |id: &str| {
+      return delete_record_handler(
+        State(state.clone()),
+        Path(("api".to_string(), id.to_string())),
+        None,
+      )
+      .await;
+    };
+    assert!(matches!(delete("1").await, Err(RecordError::Forbidden)));
+    delete("2").await.unwrap();
+
+    let names: String = conn
+      .read_query_row_get("SELECT group_concat(name) FROM tagged", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!("tagged", names);
+  }
+}
@@ -0,0 +1,204 @@
+use axum::extract::OptionalFromRequestParts;
+use axum::http::request::Parts;
+use base64::prelude::*;
+use chrono::{Duration, Utc};
+use const_format::formatcp;
+use sha2::{Digest, Sha256};
+use trailbase_schema::{FileUploadData, FileUploadInput};
+use trailbase_sqlite::{Connection, Value, params};
+
+use crate::auth::user::User;
+use crate::constants::IDEMPOTENCY_TABLE;
+use crate::records::RecordError;
+use crate::records::create_record::CreateRecordResponse;
+
+pub(crate) const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Client-chosen key identifying retries of the same record creation request, provided via the
+/// `Idempotency-Key` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdempotencyKey(pub String);
+
+impl<S> OptionalFromRequestParts<S> for IdempotencyKey
+where
+  S: Send + Sync,
+{
+  type Rejection = RecordError;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    _state: &S,
+  ) -> Result<Option<Self>, Self::Rejection> {
+    let Some(value) = parts.headers.get(IDEMPOTENCY_KEY_HEADER) else {
+      return Ok(None);
+    };
+
+    let key = value
+      .to_str()
+      .map_err(|_err| RecordError::BadRequest("Invalid Idempotency-Key"))?
+      .trim();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+      return Err(RecordError::BadRequest("Invalid Idempotency-Key"));
+    }
+
+    return Ok(Some(Self(key.to_string())));
+  }
+}
+
+pub(crate) enum Reservation {
+  /// First time the key is seen. The caller is responsible for either completing or releasing the
+  /// request.
+  Reserved,
+  /// A previous request with the same key completed successfully.
+  Completed(CreateRecordResponse),
+}
+
+/// An idempotent request scoped by Record API and user.
+pub(crate) struct IdempotentRequest {
+  api: String,
+  user: Value,
+  key: String,
+}
+
+impl IdempotentRequest {
+  pub(crate) fn new(api_name: &str, user: Option<&User>, key: IdempotencyKey) -> Self {
+    return Self {
+      api: api_name.to_string(),
+      user: user.map_or(Value::Null, |user| {
+        Value::Blob(user.uuid.into_bytes().to_vec())
+      }),
+      key: key.0,
+    };
+  }
+
+  /// Reserves the key for the request identified by `fingerprint` unless it has been seen before.
+  pub(crate) async fn reserve(
+    &self,
+    conn: &Connection,
+    fingerprint: String,
+  ) -> Result<Reservation, RecordError> {
+    const DELETE_EXPIRED_QUERY: &str = formatcp!(
+      "\
+        DELETE FROM '{IDEMPOTENCY_TABLE}' \
+        WHERE {SCOPE} AND expires < UNIXEPOCH() \
+      "
+    );
+
+    conn
+      .execute(
+        DELETE_EXPIRED_QUERY,
+        params!(self.api.clone(), self.user.clone(), self.key.clone()),
+      )
+      .await?;
+
+    const INSERT_QUERY: &str = formatcp!(
+      "\
+        INSERT INTO '{IDEMPOTENCY_TABLE}' (api, user, idempotency_key, fingerprint, expires) \
+        VALUES ($1, $2, $3, $4, $5) \
+        ON CONFLICT DO NOTHING \
+      "
+    );
+
+    let rows_affected = conn
+      .execute(
+        INSERT_QUERY,
+        params!(
+          self.api.clone(),
+          self.user.clone(),
+          self.key.clone(),
+          fingerprint.clone(),
+          (Utc::now() + IDEMPOTENCY_TTL).timestamp(),
+        ),
+      )
+      .await?;
+
+    if rows_affected > 0 {
+      return Ok(Reservation::Reserved);
+    }
+
+    const SELECT_QUERY: &str =
+      formatcp!("SELECT fingerprint, response FROM '{IDEMPOTENCY_TABLE}' WHERE {SCOPE}");
+
+    let Some(row) = conn
+      .read_query_row(
+        SELECT_QUERY,
+        params!(self.api.clone(), self.user.clone(), self.key.clone()),
+      )
+      .await?
+    else {
+      // Lost a race with a concurrent release. Let the client retry.
+      return Err(RecordError::Conflict("Idempotency-Key in use"));
+    };
+
+    if row.get::<String>(0)? != fingerprint {
+      return Err(RecordError::BadRequest(
+        "Idempotency-Key reused with different request",
+      ));
+    }
+
+    let Some(response) = row.get::<Option<String>>(1)? else {
+      return Err(RecordError::Conflict("Idempotency-Key in use"));
+    };
+
+    return Ok(Reservation::Completed(
+      serde_json::from_str(&response).map_err(|err| RecordError::Internal(err.into()))?,
+    ));
+  }
+
+  /// Stores the response to be replayed for retries.
+  pub(crate) async fn complete(
+    &self,
+    conn: &Connection,
+    response: &CreateRecordResponse,
+  ) -> Result<(), RecordError> {
+    const UPDATE_QUERY: &str =
+      formatcp!("UPDATE '{IDEMPOTENCY_TABLE}' SET response = $4 WHERE {SCOPE}");
+
+    conn
+      .execute(
+        UPDATE_QUERY,
+        params!(
+          self.api.clone(),
+          self.user.clone(),
+          self.key.clone(),
+          serde_json::to_string(response).map_err(|err| RecordError::Internal(err.into()))?,
+        ),
+      )
+      .await?;
+
+    return Ok(());
+  }
+
+  /// Releases the key after a failed request, so that it can be retried.
+  pub(crate) async fn release(&self, conn: &Connection) -> Result<(), RecordError> {
+    const DELETE_QUERY: &str = formatcp!("DELETE FROM '{IDEMPOTENCY_TABLE}' WHERE {SCOPE}");
+
+    conn
+      .execute(
+        DELETE_QUERY,
+        params!(self.api.clone(), self.user.clone(), self.key.clone()),
+      )
+      .await?;
+
+    return Ok(());
+  }
+}
+
+/// Hash of the request body used to detect reuse of keys across different requests.
+pub(crate) fn fingerprint(record: &serde_json::Value, files: Option<&[FileUploadInput]>) -> String {
+  let mut sha = Sha256::new();
+  sha.update(record.to_string());
+  for file in files.unwrap_or_default() {
+    sha.update(file.filename.as_deref().unwrap_or_default());
+    sha.update(file.content_type.as_deref().unwrap_or_default());
+    match &file.data {
+      FileUploadData::Bytes(bytes) => sha.update(bytes),
+      FileUploadData::Spooled(spooled) => sha.update(&spooled.digest),
+    }
+  }
+  return BASE64_URL_SAFE_NO_PAD.encode(sha.finalize());
+}
+
+const SCOPE: &str = "api = $1 AND IFNULL(user, X'') = IFNULL($2, X'') AND idempotency_key = $3";
+const MAX_KEY_LENGTH: usize = 255;
+const IDEMPOTENCY_TTL: Duration = Duration::hours(24);
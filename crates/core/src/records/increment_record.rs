@@ -0,0 +1,353 @@
+use axum::{
+  Json,
+  extract::{Path, State},
+};
+use trailbase_schema::json::value_to_flat_json;
+use trailbase_schema::sqlite::ColumnDataType;
+use trailbase_wasm_common::RecordHookOperation;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::records::hooks::{run_async_record_hooks, run_before_update_hooks, run_on_update_hooks};
+use crate::records::params::{JsonRow, LazyParams, check_column_write_access};
+use crate::records::webhooks::{RecordOperation, enqueue_record_event};
+use crate::records::write_queries::run_increment_query;
+use crate::records::{Permission, RecordApi, RecordError};
+
+/// Atomically increment numeric columns of an existing record.
+///
+/// Expects `{column: delta}` pairs, which are applied as `SET column = column + delta`, i.e.
+/// concurrent increments don't race. Negative deltas decrement. Responds with the updated values.
+///
+/// Runs the same hooks and access checks as regular updates. Both see the deltas as the request,
+/// e.g. as `_REQ_.<column>` in access rules.
+#[utoipa::path(
+  patch,
+  path = "/{name}/{record}/increment",
+  tag = "records",
+  request_body = serde_json::Value,
+  responses(
+    (status = 200, description = "Updated values.", body = serde_json::Value)
+  )
+)]
+pub async fn increment_record_handler(
+  State(state): State<AppState>,
+  Path((api_name, record)): Path<(String, String)>,
+  user: Option<User>,
+  Json(mut request): Json<JsonRow>,
+) -> Result<Json<JsonRow>, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  if !api.is_table() {
+    return Err(RecordError::ApiRequiresTable);
+  }
+
+  let record_id = api.primary_key_to_value(record.clone())?;
+
+  check_column_write_access(&state, &api, &request, None, true, user.as_ref()).await?;
+
+  run_before_update_hooks(&state, &api_name, &mut request, user.as_ref())?;
+  run_async_record_hooks(
+    &state,
+    &api_name,
+    RecordHookOperation::Update,
+    Some(&record),
+    Some(&mut request),
+    user.as_ref(),
+  )
+  .await?;
+
+  // NOTE: Validate after running the hooks, since they may alter the request.
+  validate_deltas(&api, &request)?;
+
+  let column_names: Vec<String> = request.keys().cloned().collect();
+  let mut lazy_params = LazyParams::for_update(
+    &api,
+    state.json_schema_registry().clone(),
+    request,
+    None,
+    api.record_pk_column().column.name.clone(),
+    record_id.clone(),
+  );
+
+  api
+    .check_record_level_access(
+      Permission::Update,
+      Some(&record_id),
+      Some(&mut lazy_params),
+      user.as_ref(),
+    )
+    .await?;
+
+  let params = lazy_params
+    .consume()
+    .map_err(|err| err.to_record_error("Invalid Parameters"))?;
+
+  run_on_update_hooks(
+    &state,
+    &api,
+    &record_id,
+    params.named_params(),
+    user.as_ref(),
+  )?;
+
+  let Some(values) = run_increment_query(api.conn(), api.table_name(), params).await? else {
+    return Err(RecordError::RecordNotFound);
+  };
+
+  enqueue_record_event(
+    &state,
+    api.qualified_name(),
+    RecordOperation::Update,
+    &record_id,
+  )
+  .await;
+
+  return Ok(Json(
+    column_names
+      .into_iter()
+      .zip(values.iter())
+      .map(|(name, value)| {
+        let value = value_to_flat_json(value).map_err(|err| RecordError::Internal(err.into()))?;
+        return Ok((name, value));
+      })
+      .collect::<Result<JsonRow, RecordError>>()?,
+  ));
+}
+
+/// Only numeric deltas for numeric columns are permitted.
+fn validate_deltas(api: &RecordApi, request: &JsonRow) -> Result<(), RecordError> {
+  if request.is_empty() {
+    return Err(RecordError::BadRequest("No columns to increment"));
+  }
+
+  let pk_column_name = &api.record_pk_column().column.name;
+  for (key, delta) in request {
+    // Unlike regular updates, unknown columns are rejected rather than skipped to avoid silently
+    // dropping increments.
+    let Some(meta) = api.column_metadata_by_name(key) else {
+      return Err(RecordError::BadRequest("Unknown column"));
+    };
+    if key == pk_column_name || meta.is_file || meta.json.is_some() {
+      return Err(RecordError::BadRequest("Column not numeric"));
+    }
+
+    let serde_json::Value::Number(delta) = delta else {
+      return Err(RecordError::BadRequest("Delta not numeric"));
+    };
+    match meta.column.data_type {
+      ColumnDataType::Integer if delta.is_i64() => {}
+      ColumnDataType::Integer => {
+        return Err(RecordError::BadRequest("Delta not an integer"));
+      }
+      ColumnDataType::Real => {}
+      _ => {
+        return Err(RecordError::BadRequest("Column not numeric"));
+      }
+    };
+  }
+
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+  use trailbase_sqlite::params;
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::test_utils::add_record_api_config;
+
+  async fn increment(
+    state: &AppState,
+    id: i64,
+    request: serde_json::Value,
+  ) -> Result<JsonRow, RecordError> {
+    let serde_json::Value::Object(request) = request else {
+      panic!("not an object");
+    };
+    return increment_record_handler(
+      State(state.clone()),
+      Path(("counters".to_string(), id.to_string())),
+      None,
+      Json(request),
+    )
+    .await
+    .map(|response| response.0);
+  }
+
+  #[tokio::test]
+  async fn test_increment_record() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE counters ( \
+            id     INTEGER PRIMARY KEY, \
+            count  INTEGER NOT NULL DEFAULT 0, \
+            score  REAL NOT NULL DEFAULT 0, \
+            name   TEXT \
+          ) STRICT; \
+          INSERT INTO counters (id, name) VALUES (1, 'a'); \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("counters".to_string()),
+        table_name: Some("counters".to_string()),
+        acl_world: [PermissionFlag::Read as i32, PermissionFlag::Update as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let response = increment(&state, 1, json!({"count": 2, "score": 0.5}))
+      .await
+      .unwrap();
+    assert_eq!(
+      serde_json::Value::Object(response),
+      json!({"count": 2, "score": 0.5})
+    );
+
+    // Concurrent increments don't race.
+    let futures = (0..20).map(|_| increment(&state, 1, json!({"count": 1})));
+    for result in futures_util::future::join_all(futures).await {
+      result.unwrap();
+    }
+    let count: i64 = state
+      .conn()
+      .read_query_row_get(
+        "SELECT count FROM counters WHERE id = $1",
+        params!(1_i64),
+        0,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(count, 22);
+
+    let response = increment(&state, 1, json!({"count": -30})).await.unwrap();
+    assert_eq!(response["count"], json!(-8));
+
+    // Type checks.
+    assert!(matches!(
+      increment(&state, 1, json!({"name": 1})).await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      increment(&state, 1, json!({"count": 1.5})).await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      increment(&state, 1, json!({"count": "1"})).await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      increment(&state, 1, json!({"id": 1})).await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      increment(&state, 1, json!({"missing": 1})).await,
+      Err(RecordError::BadRequest(_))
+    ));
+
+    assert!(matches!(
+      increment(&state, 2, json!({"count": 1})).await,
+      Err(RecordError::RecordNotFound)
+    ));
+  }
+
+  struct DoublingHooks;
+
+  impl crate::records::RecordHooks for DoublingHooks {
+    fn before_update(
+      &self,
+      _api_name: &str,
+      record: &mut serde_json::Map<String, serde_json::Value>,
+      _user: Option<&User>,
+    ) -> Result<(), RecordError> {
+      for delta in record.values_mut() {
+        if let Some(d) = delta.as_i64() {
+          *delta = json!(2 * d);
+        }
+      }
+      return Ok(());
+    }
+
+    fn on_update(
+      &self,
+      _state: &AppState,
+      _api: &RecordApi,
+      _record_id: &trailbase_sqlite::Value,
+      params: &trailbase_sqlite::NamedParams,
+      _user: Option<&User>,
+    ) -> Result<(), RecordError> {
+      if params
+        .iter()
+        .any(|(_name, value)| *value == trailbase_sqlite::Value::Integer(200))
+      {
+        return Err(RecordError::Forbidden);
+      }
+      return Ok(());
+    }
+  }
+
+  #[tokio::test]
+  async fn test_increment_record_hooks() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE counters (id INTEGER PRIMARY KEY, count INTEGER NOT NULL DEFAULT 0) STRICT; \
+          INSERT INTO counters (id) VALUES (1); \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("counters".to_string()),
+        table_name: Some("counters".to_string()),
+        acl_world: [PermissionFlag::Update as i32].into(),
+        // Access rules see the deltas.
+        update_access_rule: Some("_REQ_.count < 1000".to_string()),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    state.register_record_hooks(std::sync::Arc::new(DoublingHooks));
+
+    // Hooks see and may alter the deltas.
+    let response = increment(&state, 1, json!({"count": 2})).await.unwrap();
+    assert_eq!(response["count"], json!(4));
+
+    // Access rules are evaluated on the altered deltas.
+    assert!(matches!(
+      increment(&state, 1, json!({"count": 600})).await,
+      Err(RecordError::Forbidden)
+    ));
+
+    // As are `on_update` hooks.
+    assert!(matches!(
+      increment(&state, 1, json!({"count": 100})).await,
+      Err(RecordError::Forbidden)
+    ));
+  }
+}
@@ -3,6 +3,7 @@ use serde::Deserialize;
 use trailbase_schema::json_schema::{
   Expand, JsonSchemaMode, build_json_schema, build_json_schema_expanded,
 };
+use trailbase_schema::metadata::ColumnMetadata;
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
@@ -36,12 +37,37 @@ pub async fn json_schema_handler(
     .check_record_level_access(Permission::Schema, None, None, user.as_ref())
     .await?;
 
-  return Ok(Json(build_api_json_schema(&state, &api, request.mode)?));
+  return Ok(Json(build_user_api_json_schema(
+    &state,
+    &api,
+    request.mode.unwrap_or(JsonSchemaMode::Insert),
+    user.as_ref(),
+  )?));
+}
+
+/// Builds the API's JSON schema as seen by `user`, i.e. columns excluded from reads for the user
+/// are also omitted from the schema of read records.
+pub(crate) fn build_user_api_json_schema(
+  state: &AppState,
+  api: &RecordApi,
+  mode: JsonSchemaMode,
+  user: Option<&User>,
+) -> Result<serde_json::Value, RecordError> {
+  if mode == JsonSchemaMode::Select && api.has_read_excluded_columns() {
+    let columns = api
+      .readable_columns(None, user)
+      .ok_or(RecordError::BadRequest("Invalid select"))?;
+    let (_validator, json) = build_api_json_schema_internal(state, api, &columns, mode)?;
+    return Ok(json);
+  }
+
+  return build_api_json_schema(state, api, Some(mode));
 }
 
 fn build_api_json_schema_internal(
   state: &AppState,
   api: &RecordApi,
+  columns: &[ColumnMetadata],
   mode: JsonSchemaMode,
 ) -> Result<(jsonschema::Validator, serde_json::Value), RecordError> {
   if let (Some(config_expand), JsonSchemaMode::Select) = (api.expand(), mode) {
@@ -56,7 +82,7 @@ fn build_api_json_schema_internal(
     return build_json_schema_expanded(
       &state.json_schema_registry().read(),
       api.api_name(),
-      api.columns(),
+      columns,
       mode,
       Some(expand),
     )
@@ -66,7 +92,7 @@ fn build_api_json_schema_internal(
   return build_json_schema(
     &state.json_schema_registry().read(),
     api.api_name(),
-    api.columns(),
+    columns,
     mode,
   )
   .map_err(|err| RecordError::Internal(err.into()));
@@ -79,7 +105,7 @@ pub fn validate_api_json_schema(
   mode: JsonSchemaMode,
   value: &serde_json::Value,
 ) -> Result<(), RecordError> {
-  let (validator, json_schema) = build_api_json_schema_internal(state, api, mode)?;
+  let (validator, json_schema) = build_api_json_schema_internal(state, api, api.columns(), mode)?;
 
   let result = validator.evaluate(value);
   let errors: Vec<_> = result.iter_errors().collect();
@@ -97,7 +123,11 @@ pub fn build_api_json_schema(
   api: &RecordApi,
   mode: Option<JsonSchemaMode>,
 ) -> Result<serde_json::Value, RecordError> {
-  let (_validator, json) =
-    build_api_json_schema_internal(state, api, mode.unwrap_or(JsonSchemaMode::Insert))?;
+  let (_validator, json) = build_api_json_schema_internal(
+    state,
+    api,
+    api.columns(),
+    mode.unwrap_or(JsonSchemaMode::Insert),
+  )?;
   return Ok(json);
 }
@@ -16,13 +16,17 @@ use trailbase_sqlite::{ConnectionType, Value};
 use crate::app_state::AppState;
 use crate::auth::user::User;
 use crate::encryption::{KeyType, decrypt, encrypt, generate_random_key};
-use crate::listing::{WhereClause, build_filter_where_clause, limit_or_default};
+use crate::listing::{WhereClause, WhereClauseError, build_filter_where_clause, limit_or_default};
+use crate::records::RecordApi;
+use crate::records::cache::ListKey;
 use crate::records::expand::{ExpandedTable, JsonError, expand_tables, row_to_json_expand};
+use crate::records::hooks::run_after_read_hooks;
+use crate::records::record_api::user_roles_param;
 use crate::records::{Permission, RecordError};
 use crate::util::row_id_column;
 
 /// JSON response containing the listed records.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct ListResponse {
   /// Encrypted cursor for pagination - Round-trip to get the next page.
   #[serde(skip_serializing_if = "Option::is_none")]
@@ -95,7 +99,57 @@ pub async fn list_records_handler(
   // on the table, i.e. no access -> empty results.
   api.check_table_level_access(Permission::Read, user.as_ref())?;
 
-  let conn = api.conn();
+  let qs_query = parse_list_query(raw_url_query.as_deref())?;
+
+  let Some(cache) = api.list_cache() else {
+    return list_records(state, &api, api_name, query, qs_query, user).await;
+  };
+
+  // NOTE: Results only depend on the user if there's a read access rule, hooks or read-excluded
+  // columns.
+  let user_scoped = api.read_access_rule().is_some()
+    || !state.record_hooks().is_empty()
+    || api.has_read_excluded_columns();
+  let key = ListKey::new(
+    raw_url_query.clone(),
+    user
+      .as_ref()
+      .filter(|_| user_scoped)
+      .map(|u| u.uuid.into_bytes()),
+  );
+  if let Some(cached) = cache.get(&key) {
+    return Ok(Json(ListOrGeoJSONResponse::List(cached)));
+  }
+
+  let version = cache.version();
+  let response = list_records(state, &api, api_name, query, qs_query, user).await?;
+  if let ListOrGeoJSONResponse::List(ref list) = response.0 {
+    cache.insert(key, version, list.clone());
+  }
+
+  return Ok(response);
+}
+
+pub(crate) fn parse_list_query(
+  raw_url_query: Option<&str>,
+) -> Result<trailbase_qs::Query, RecordError> {
+  return raw_url_query
+    .map_or_else(|| Ok(Default::default()), trailbase_qs::Query::parse)
+    .map_err(|_err| {
+      return RecordError::BadRequest("Invalid query");
+    });
+}
+
+/// Lists records for an already access-checked API.
+pub(crate) async fn list_records(
+  state: AppState,
+  api: &RecordApi,
+  api_name: String,
+  query: ListRecordsQuery,
+  qs_query: trailbase_qs::Query,
+  user: Option<User>,
+) -> Result<Json<ListOrGeoJSONResponse>, RecordError> {
+  let conn = api.read_conn();
   let table_name = api.table_name();
   let pk_meta = api.record_pk_column();
   let pk_column = &pk_meta.column;
@@ -120,29 +174,55 @@ pub async fn list_records_handler(
     cursor,
     count,
     expand: query_expand,
+    select,
     order,
     filter: filter_params,
     offset,
-  } = raw_url_query
-    .as_ref()
-    .map_or_else(
-      || Ok(Default::default()),
-      |query| trailbase_qs::Query::parse(query),
-    )
-    .map_err(|_err| {
-      return RecordError::BadRequest("Invalid query");
-    })?;
+  } = qs_query;
+
+  // Columns excluded from reads for the user can neither be filtered nor sorted by, since that
+  // would reveal their values.
+  let excluded_columns = api.read_excluded_columns(user.as_ref());
+  if let Some(ref order) = order
+    && order
+      .columns
+      .iter()
+      .any(|(name, _)| excluded_columns.contains(&name.as_str()))
+  {
+    return Err(RecordError::BadRequest("Invalid order"));
+  }
+  let readable_columns = api
+    .readable_columns(None, user.as_ref())
+    .ok_or(RecordError::BadRequest("Invalid select"))?;
 
   // Where clause contains column filters and cursor depending on what's present.
   // NOTE: This will also drop any filters for unknown columns, thus avoiding SQL injections.
   let WhereClause {
     clause: filter_clause,
     mut params,
-  } = build_filter_where_clause("_ROW_", api.columns(), filter_params)
-    .map_err(|_err| RecordError::BadRequest("Invalid filter params"))?;
-
-  let limit: usize =
-    limit_or_default(limit, api.listing_hard_limit()).map_err(RecordError::BadRequest)?;
+  } = build_filter_where_clause("_ROW_", &readable_columns, filter_params).map_err(|err| {
+    return match err {
+      WhereClauseError::UnknownColumn(_) => RecordError::BadRequest("Filter on unknown column"),
+      WhereClauseError::UnsupportedOperator(_) => {
+        RecordError::BadRequest("Filter operator not supported for column type")
+      }
+      _ => RecordError::BadRequest("Invalid filter params"),
+    };
+  })?;
+
+  let limit: usize = limit_or_default(limit.or(api.default_limit()), api.listing_hard_limit())
+    .map_err(RecordError::BadRequest)?;
+  // Fall back to the API's default order, if configured, to guarantee deterministic pagination.
+  let order = order.or_else(|| api.default_order().cloned());
+
+  // Optional projection onto a subset of columns. Excluded, hidden and columns excluded from
+  // reads for the user cannot be selected.
+  let selected_columns: Cow<'_, [trailbase_schema::metadata::ColumnMetadata]> = match select {
+    Some(select) => api
+      .readable_columns(Some(&select.columns), user.as_ref())
+      .ok_or(RecordError::BadRequest("Invalid select"))?,
+    None => readable_columns.clone(),
+  };
 
   // User properties
   params.extend_from_slice(&[
@@ -154,7 +234,13 @@ pub async fn list_records_handler(
     ),
     (
       Cow::Borrowed(":__user_id"),
-      user.map_or(Value::Null, |u| Value::Blob(u.uuid.into())),
+      user
+        .as_ref()
+        .map_or(Value::Null, |u| Value::Blob(u.uuid.into())),
+    ),
+    (
+      Cow::Borrowed(":__user_roles"),
+      user_roles_param(user.as_ref()),
     ),
   ]);
 
@@ -228,14 +314,26 @@ pub async fn list_records_handler(
         return Err(RecordError::BadRequest("Invalid expansion"));
       };
 
+      if api
+        .max_joins()
+        .is_some_and(|max| expand.columns.len() > max)
+      {
+        return Err(RecordError::BadRequest("Too many expansions"));
+      }
+
       // NOTE: This will drop any unknown expand column, thus avoiding SQL injections.
       for col_name in &expand.columns {
         if !config_expand.contains_key(col_name) {
           return Err(RecordError::BadRequest("Invalid expansion"));
         }
+
+        // Expanded columns must be part of the projection.
+        if !selected_columns.iter().any(|m| m.column.name == *col_name) {
+          return Err(RecordError::BadRequest("Invalid expansion"));
+        }
       }
 
-      expand_tables(&api, metadata, &expand.columns)?
+      expand_tables(api, metadata, &expand.columns)?
     }
     None => vec![],
   };
@@ -245,7 +343,7 @@ pub async fn list_records_handler(
   let list_query = match conn.connection_type() {
     ConnectionType::Pg => ListRecordQueryTemplatePg {
       table_name,
-      column_metadata: api.columns(),
+      column_metadata: &selected_columns,
       // NOTE: We're using the read access rule to filter accessible rows as opposed to blocking
       // access early as we do for READs.
       read_access_clause: api.read_access_rule().unwrap_or("TRUE"),
@@ -260,7 +358,7 @@ pub async fn list_records_handler(
     .render(),
     ConnectionType::Sqlite => ListRecordQueryTemplateSqlite {
       table_name,
-      column_metadata: api.columns(),
+      column_metadata: &selected_columns,
       // NOTE: We're using the read access rule to filter accessible rows as opposed to blocking
       // access early as we do for READs.
       read_access_clause: api.read_access_rule().unwrap_or("TRUE"),
@@ -277,7 +375,9 @@ pub async fn list_records_handler(
   .map_err(|err| RecordError::Internal(err.into()))?;
 
   // Execute the query.
-  let rows = conn.read_query_rows(list_query, params).await?;
+  let rows = conn
+    .read_query_rows_with_timeout(list_query, params, api.query_timeout())
+    .await?;
 
   let Some(last_row) = rows.last() else {
     // Query result is empty:
@@ -327,10 +427,10 @@ pub async fn list_records_handler(
     None
   };
 
-  let records = if expanded_tables.is_empty() {
+  let mut records = if expanded_tables.is_empty() {
     rows
       .into_iter()
-      .map(|row| row_to_json_expand(api.columns(), &row, column_filter, api.expand()))
+      .map(|row| row_to_json_expand(&selected_columns, &row, column_filter, api.expand()))
       .collect::<Result<Vec<_>, JsonError>>()
       .map_err(|err| RecordError::Internal(err.into()))?
   } else {
@@ -344,7 +444,7 @@ pub async fn list_records_handler(
           ));
         };
 
-        let mut curr = row.split_off(api.columns().len());
+        let mut curr = row.split_off(selected_columns.len());
 
         for expanded in &expanded_tables {
           let next = curr.split_off(expanded.num_columns);
@@ -363,7 +463,7 @@ pub async fn list_records_handler(
           curr = next;
         }
 
-        return row_to_json_expand(api.columns(), &row, column_filter, Some(&expand))
+        return row_to_json_expand(&selected_columns, &row, column_filter, Some(&expand))
           .map_err(|err| RecordError::Internal(err.into()));
       })
       .collect::<Result<Vec<_>, RecordError>>()?
@@ -371,21 +471,36 @@ pub async fn list_records_handler(
 
   #[cfg(any(feature = "geos", feature = "geos-static"))]
   if let Some(meta) = geojson_geometry_column {
+    run_after_read_hooks(&state, &api_name, &mut records, user.as_ref());
+
+    // Features require both, the record id and the geometry.
+    if [&pk_column.name, &meta.column.name]
+      .into_iter()
+      .any(|name| !selected_columns.iter().any(|m| m.column.name == *name))
+    {
+      return Err(RecordError::BadRequest("Invalid select"));
+    }
+
     return Ok(Json(ListOrGeoJSONResponse::GeoJSON(
       build_feature_collection(meta, &pk_column.name, cursor, total_count, records)?,
     )));
   }
 
+  // NOTE: Projected records are missing required properties by design.
   #[cfg(debug_assertions)]
-  for record in &records {
-    crate::records::json_schema::validate_api_json_schema(
-      &state,
-      &api,
-      trailbase_schema::json_schema::JsonSchemaMode::Select,
-      record,
-    )?;
+  if selected_columns.len() == api.columns().len() {
+    for record in &records {
+      crate::records::json_schema::validate_api_json_schema(
+        &state,
+        api,
+        trailbase_schema::json_schema::JsonSchemaMode::Select,
+        record,
+      )?;
+    }
   }
 
+  run_after_read_hooks(&state, &api_name, &mut records, user.as_ref());
+
   return Ok(Json(ListOrGeoJSONResponse::List(ListResponse {
     cursor,
     total_count,
@@ -545,8 +660,10 @@ mod tests {
   use crate::auth::util::login_with_password;
   use crate::config::proto::PermissionFlag;
   use crate::connection::ConnectionEntry;
+  use crate::extract::Either;
   use crate::records::RecordError;
   use crate::records::test_utils::*;
+  use crate::records::update_record::{UpdateRecordQuery, update_record_handler};
   use crate::util::id_to_b64;
   use crate::util::urlencode;
 
@@ -864,6 +981,267 @@ mod tests {
       panic!("not a list");
     };
     assert_eq!(1, not_null_response.records.len());
+
+    let list = async |query: &str| {
+      return list_records_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(ListRecordsQuery::default()),
+        RawQuery(Some(query.to_string())),
+        None,
+      )
+      .await
+      .map(|response| {
+        let ListOrGeoJSONResponse::List(list) = response.0 else {
+          panic!("not a list");
+        };
+        return list.records.len();
+      });
+    };
+
+    assert_eq!(2, list("filter[id][in]=1,3").await.unwrap());
+    assert_eq!(2, list("filter[id][gte]=2").await.unwrap());
+    assert_eq!(1, list("filter[index][like]=2%").await.unwrap());
+    assert_eq!(2, list("filter[nullable][is]=null").await.unwrap());
+
+    // Boolean filter expressions.
+    assert_eq!(2, list("filter=id=1||id=2").await.unwrap());
+    assert_eq!(
+      1,
+      list("filter=(id=1||id=3)%26%26nullable[is]=!null")
+        .await
+        .unwrap()
+    );
+    assert!(matches!(
+      list("filter=(id=1||missing=3)").await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      list("filter=id=1&&nullable[is]=!null").await,
+      Err(RecordError::BadRequest(_))
+    ));
+
+    assert!(matches!(
+      list("filter[nullable][$like]=1%").await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      list("filter[missing][$in]=1,2").await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      list("filter[missing][$is]=NULL").await,
+      Err(RecordError::BadRequest(_))
+    ));
+
+    // Column projection.
+    let ListOrGeoJSONResponse::List(select_response) = list_records_handler(
+      State(state.clone()),
+      Path("api".to_string()),
+      Query(ListRecordsQuery::default()),
+      RawQuery(Some("select=nullable,id&filter[id]=1".to_string())),
+      None,
+    )
+    .await
+    .unwrap()
+    .0
+    else {
+      panic!("not a list");
+    };
+    assert_eq!(
+      vec![serde_json::json!({"id": 1, "nullable": 1})],
+      select_response.records
+    );
+
+    assert!(matches!(
+      list("select=id,missing").await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      list("select=_rowid_").await,
+      Err(RecordError::BadRequest(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_record_api_list_defaults() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE "table" (
+            id         INTEGER PRIMARY KEY,
+            rank       INTEGER NOT NULL
+          ) {strict};
+
+          INSERT INTO "table" (id, rank) VALUES (1, 3), (2, 1), (3, 2), (4, 5), (5, 4);
+        "#,
+        strict = strict(conn),
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("table".to_string()),
+        acl_world: [PermissionFlag::Read as i32].into(),
+        listing_hard_limit: Some(3),
+        default_limit: Some(2),
+        default_order_by: Some("-rank".to_string()),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let list = async |query: Option<&str>| {
+      return list_records_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(ListRecordsQuery::default()),
+        RawQuery(query.map(|q| q.to_string())),
+        None,
+      )
+      .await
+      .map(|response| {
+        let ListOrGeoJSONResponse::List(list) = response.0 else {
+          panic!("not a list");
+        };
+        return list
+          .records
+          .into_iter()
+          .map(|record| record["id"].as_i64().unwrap())
+          .collect::<Vec<_>>();
+      });
+    };
+
+    assert_eq!(vec![4, 5], list(None).await.unwrap());
+    assert_eq!(vec![4, 5, 1], list(Some("limit=3")).await.unwrap());
+    assert_eq!(vec![1, 2], list(Some("order=id")).await.unwrap());
+    assert!(matches!(
+      list(Some("limit=4")).await,
+      Err(RecordError::BadRequest(_))
+    ));
+
+    // Default limits must not exceed the hard limit.
+    assert!(
+      add_record_api_config(
+        &state,
+        RecordApiConfig {
+          name: Some("api2".to_string()),
+          table_name: Some("table".to_string()),
+          acl_world: [PermissionFlag::Read as i32].into(),
+          listing_hard_limit: Some(3),
+          default_limit: Some(4),
+          ..Default::default()
+        },
+      )
+      .await
+      .is_err()
+    );
+    assert!(
+      add_record_api_config(
+        &state,
+        RecordApiConfig {
+          name: Some("api2".to_string()),
+          table_name: Some("table".to_string()),
+          acl_world: [PermissionFlag::Read as i32].into(),
+          default_order_by: Some("-missing".to_string()),
+          ..Default::default()
+        },
+      )
+      .await
+      .is_err()
+    );
+  }
+
+  #[tokio::test]
+  async fn test_record_api_list_cache() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE cached (
+            id      INTEGER PRIMARY KEY,
+            value   TEXT NOT NULL
+          ) {strict};
+
+          INSERT INTO cached (id, value) VALUES (1, 'initial');
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("cached".to_string()),
+        acl_world: [PermissionFlag::Read as i32, PermissionFlag::Update as i32].into(),
+        list_cache: Some(crate::config::proto::RecordCacheConfig::default()),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let list = async |query: &str| -> Vec<serde_json::Value> {
+      let ListOrGeoJSONResponse::List(response) = list_records_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(ListRecordsQuery::default()),
+        RawQuery(Some(query.to_string())),
+        None,
+      )
+      .await
+      .unwrap()
+      .0
+      else {
+        panic!("not a list");
+      };
+      return response.records;
+    };
+
+    assert_eq!(
+      vec![serde_json::json!({"id": 1, "value": "initial"})],
+      list("limit=5").await
+    );
+
+    // Writes bypassing the Record API invalidate all entries as well.
+    conn
+      .execute("UPDATE cached SET value = 'raw' WHERE id = 1", ())
+      .await
+      .unwrap();
+    assert_eq!(
+      vec![serde_json::json!({"id": 1, "value": "raw"})],
+      list("limit=5").await
+    );
+
+    // Writes through the Record API invalidate all entries.
+    update_record_handler(
+      State(state.clone()),
+      Path(("api".to_string(), "1".to_string())),
+      Query(UpdateRecordQuery::default()),
+      None,
+      Either::Json(json_row_from_value(serde_json::json!({"value": "updated"})).unwrap()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+      vec![serde_json::json!({"id": 1, "value": "updated"})],
+      list("limit=5").await
+    );
   }
 
   #[tokio::test]
@@ -1,21 +1,36 @@
 use axum::{
   Router,
-  routing::{delete, get, patch, post},
+  routing::{delete, get, head, patch, post},
 };
 use trailbase_sqlite::ConnectionType;
 use utoipa::OpenApi;
 
+pub(crate) mod cache;
+pub(crate) mod changes;
 pub(crate) mod create_record;
 pub(crate) mod delete_record;
+pub(crate) mod export_records;
+pub(crate) mod file_encryption;
 pub(crate) mod files;
 pub(crate) mod filter;
+pub(crate) mod hooks;
+pub(crate) mod idempotency;
+pub(crate) mod increment_record;
 pub(crate) mod json_schema;
 pub(crate) mod list_records;
 pub(crate) mod params;
+pub(crate) mod protobuf;
 pub(crate) mod read_queries;
 pub(crate) mod read_record;
+pub(crate) mod saved_queries;
+pub(crate) mod scanner;
 pub(crate) mod subscribe;
+pub(crate) mod sync;
+pub(crate) mod thumbnail;
+pub(crate) mod update_record;
+pub(crate) mod upload;
 pub(crate) mod util;
+pub(crate) mod webhooks;
 pub(crate) mod write_queries;
 
 #[cfg(test)]
@@ -25,27 +40,38 @@ mod error;
 mod expand;
 mod record_api;
 mod transaction;
-mod update_record;
 mod validate;
 
-pub(crate) use error::RecordError;
+pub use error::RecordError;
+pub use file_encryption::{FileEncryptionError, FileKeyProvider, WrappedKey};
+pub use hooks::RecordHooks;
 pub use record_api::RecordApi;
-pub(crate) use validate::validate_record_api_config;
+pub use scanner::{ScanStream, ScanVerdict, UploadScanError, UploadScanner};
+pub(crate) use validate::{
+  referenced_columns, validate_record_api_config, validate_saved_query_config,
+};
 
 use crate::AppState;
 use crate::config::proto::PermissionFlag;
-use crate::constants::{RECORD_API_PATH, TRANSACTION_API_PATH};
+use crate::constants::{QUERY_API_PATH, RECORD_API_PATH, SYNC_API_PATH, TRANSACTION_API_PATH};
 
 #[derive(OpenApi)]
 #[openapi(paths(
   read_record::read_record_handler,
   read_record::get_uploaded_file_from_record_handler,
   read_record::get_uploaded_files_from_record_handler,
+  read_record::sign_file_url_handler,
+  upload::create_upload_handler,
+  upload::upload_offset_handler,
+  upload::append_upload_handler,
+  upload::abort_upload_handler,
   list_records::list_records_handler,
   create_record::create_record_handler,
   update_record::update_record_handler,
+  increment_record::increment_record_handler,
   delete_record::delete_record_handler,
   json_schema::json_schema_handler,
+  changes::list_changes_handler,
   subscribe::handler::add_subscription_sse_and_ws_handler,
 ))]
 pub(super) struct RecordOpenApi;
@@ -57,7 +83,7 @@ pub(crate) fn router(
   let mut router = Router::new()
     .route(
       &format!("/{RECORD_API_PATH}/{{name}}/{{record}}"),
-      get(read_record::read_record_handler),
+      get(protobuf::read_record_or_protobuf_handler),
     )
     .route(
       &format!("/{RECORD_API_PATH}/{{name}}"),
@@ -67,13 +93,17 @@ pub(crate) fn router(
       &format!("/{RECORD_API_PATH}/{{name}}/{{record}}"),
       patch(update_record::update_record_handler),
     )
+    .route(
+      &format!("/{RECORD_API_PATH}/{{name}}/{{record}}/increment"),
+      patch(increment_record::increment_record_handler),
+    )
     .route(
       &format!("/{RECORD_API_PATH}/{{name}}/{{record}}"),
       delete(delete_record::delete_record_handler),
     )
     .route(
       &format!("/{RECORD_API_PATH}/{{name}}"),
-      get(list_records::list_records_handler),
+      get(export_records::list_or_export_records_handler),
     )
     .route(
       &format!("/{RECORD_API_PATH}/{{name}}/{{record}}/file/{{column_name}}"),
@@ -83,16 +113,47 @@ pub(crate) fn router(
       &format!("/{RECORD_API_PATH}/{{name}}/{{record}}/files/{{column_name}}/{{file_name}}"),
       get(read_record::get_uploaded_files_from_record_handler),
     )
+    .route(
+      &format!("/{RECORD_API_PATH}/{{name}}/{{record}}/files/{{column_name}}/sign"),
+      get(read_record::sign_file_url_handler),
+    )
+    .route(
+      &format!("/{RECORD_API_PATH}/{{name}}/{{record}}/upload/{{column_name}}"),
+      post(upload::create_upload_handler),
+    )
+    .route(
+      &format!("/{RECORD_API_PATH}/{{name}}/upload/{{upload_id}}"),
+      head(upload::upload_offset_handler)
+        .patch(upload::append_upload_handler)
+        .delete(upload::abort_upload_handler),
+    )
     .route(
       &format!("/{RECORD_API_PATH}/{{name}}/schema"),
       get(json_schema::json_schema_handler),
+    )
+    .route(
+      &format!("/{RECORD_API_PATH}/{{name}}/changes"),
+      get(changes::list_changes_handler),
+    )
+    .route(
+      &format!("/{QUERY_API_PATH}/{{name}}"),
+      get(saved_queries::saved_query_handler),
     );
 
   if matches!(connection_type, ConnectionType::Sqlite) {
-    router = router.route(
-      &format!("/{RECORD_API_PATH}/{{name}}/subscribe/{{record}}"),
-      get(subscribe::handler::add_subscription_sse_and_ws_handler),
-    );
+    router = router
+      .route(
+        &format!("/{RECORD_API_PATH}/{{name}}/subscribe/{{record}}"),
+        get(subscribe::handler::add_subscription_sse_and_ws_handler),
+      )
+      .route(
+        &format!("/{SYNC_API_PATH}/{{name}}/pull"),
+        get(sync::sync_pull_handler),
+      )
+      .route(
+        &format!("/{SYNC_API_PATH}/{{name}}/push"),
+        post(sync::sync_push_handler),
+      );
   }
 
   if enable_transactions {
@@ -108,7 +169,8 @@ pub(crate) fn router(
 // Since this is for APIs access control, we'll use the API- space CRUD terminology instead of
 // database terminology.
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize, ts_rs::TS)]
+#[serde(rename_all = "lowercase")]
 pub enum Permission {
   // TODO: Should there be a separate "list records" permission or is "read" enough?
   Create = 1,  // ~ DB insert
@@ -1,17 +1,21 @@
 use parking_lot::RwLock;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use trailbase_schema::json::flat_json_to_value;
 use trailbase_schema::metadata::ColumnMetadata;
 use trailbase_schema::registry::JsonSchemaRegistry;
 use trailbase_schema::sqlite::{Column, ColumnDataType};
-use trailbase_schema::{FileUpload, FileUploadInput, FileUploads};
+use trailbase_schema::{FileUpload, FileUploadData, FileUploadInput, FileUploads};
 use trailbase_sqlite::{NamedParams, Value};
 use trailbase_sqlvalue::SqlValue;
 
-use crate::records::RecordApi;
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::auth::util::is_admin;
+use crate::config::proto::FileColumnPolicy;
 use crate::records::util::named_placeholder;
+use crate::records::{RecordApi, RecordError};
 use crate::schema_metadata::{self, JsonColumnMetadata, TableMetadata};
 
 #[derive(Debug, Clone, thiserror::Error)]
@@ -47,6 +51,25 @@ pub enum ParamsError {
   #[cfg(any(feature = "geos", feature = "geos-static"))]
   #[error("Geos: {0}")]
   Geos(#[from] geos::Error),
+  #[error("File type not allowed: {0}")]
+  FileType(String),
+  #[error("File too large: {0} bytes exceeds limit of {1}")]
+  FileSize(u64, u64),
+  #[error("Too many files: {0} exceeds limit of {1}")]
+  FileCount(usize, u64),
+}
+
+impl ParamsError {
+  /// Converts to a [RecordError], retaining the reason for violated file policies, which clients
+  /// can act upon.
+  pub(crate) fn to_record_error(&self, fallback: &'static str) -> RecordError {
+    return match self {
+      Self::FileType(_) => RecordError::BadRequest("File type not allowed"),
+      Self::FileSize(..) => RecordError::BadRequest("File too large"),
+      Self::FileCount(..) => RecordError::BadRequest("Too many files"),
+      _ => RecordError::BadRequest(fallback),
+    };
+  }
 }
 
 impl From<serde_json::Error> for ParamsError {
@@ -78,12 +101,17 @@ impl From<trailbase_schema::json::JsonError> for ParamsError {
 }
 
 // Contains Metadata (i.e. column contents) and file contents.
-pub(crate) type FileMetadataContents = Vec<(FileUpload, Option<Vec<u8>>)>;
+pub(crate) type FileMetadataContents = Vec<(FileUpload, Option<FileUploadData>)>;
 
 pub(crate) type JsonRow = serde_json::Map<String, serde_json::Value>;
 
 pub trait ColumnAccessor {
   fn column_by_name(&self, field_name: &str) -> Option<&ColumnMetadata>;
+
+  /// Restrictions on files uploaded to the given column, if any.
+  fn file_policy(&self, _column_name: &str) -> Option<&FileColumnPolicy> {
+    return None;
+  }
 }
 
 /// Implementation to build insert/update Params for admin APIs.
@@ -100,6 +128,11 @@ impl ColumnAccessor for RecordApi {
   fn column_by_name(&self, field_name: &str) -> Option<&ColumnMetadata> {
     return self.column_metadata_by_name(field_name);
   }
+
+  #[inline]
+  fn file_policy(&self, column_name: &str) -> Option<&FileColumnPolicy> {
+    return self.file_policy(column_name);
+  }
 }
 
 /// Represents a record provided by the user via request, i.e. a create or update record request.
@@ -137,6 +170,40 @@ pub enum Params {
 }
 
 impl Params {
+  /// Named SQL parameters, i.e. '(":col_name": Value::Text("hi"))'.
+  pub fn named_params(&self) -> &NamedParams {
+    return match self {
+      Self::Insert { named_params, .. } => named_params,
+      Self::Update { named_params, .. } => named_params,
+    };
+  }
+
+  /// Files and their contents to be written.
+  pub(crate) fn files(&self) -> &FileMetadataContents {
+    return match self {
+      Self::Insert { files, .. } => files,
+      Self::Update { files, .. } => files,
+    };
+  }
+
+  /// Named SQL parameters and files to be written, e.g. to amend the files' metadata.
+  pub(crate) fn named_params_and_files_mut(
+    &mut self,
+  ) -> (&mut NamedParams, &mut FileMetadataContents) {
+    return match self {
+      Self::Insert {
+        named_params,
+        files,
+        ..
+      } => (named_params, files),
+      Self::Update {
+        named_params,
+        files,
+        ..
+      } => (named_params, files),
+    };
+  }
+
   /// Converts a Json object + optional MultiPart files into trailbase_sqlite::Values and extracted
   /// files.
   pub fn for_insert<S: ColumnAccessor>(
@@ -171,6 +238,7 @@ impl Params {
         column,
         json.as_ref(),
         *is_geometry,
+        accessor.file_policy(&key),
         value,
       )?;
       if let Some(json_files) = json_files {
@@ -273,6 +341,7 @@ impl Params {
         column,
         json.as_ref(),
         *is_geometry,
+        accessor.file_policy(&key),
         value,
       )?;
       if let Some(json_files) = json_files {
@@ -356,6 +425,7 @@ impl Params {
                   column,
                   json.as_ref(),
                   *is_geometry,
+                  accessor.file_policy(&key),
                   serde_json::from_str(&text)?,
                 )?;
                 if let Some(json_files) = json_files {
@@ -480,6 +550,35 @@ impl<'a> LazyParams<'a> {
   }
 }
 
+/// Rejects requests writing admin-only or, for updates, immutable columns unless the user is an
+/// admin.
+pub(crate) async fn check_column_write_access(
+  state: &AppState,
+  api: &RecordApi,
+  row: &JsonRow,
+  multipart_files: Option<&[FileUploadInput]>,
+  update: bool,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  let fields = row.keys().map(String::as_str).chain(
+    multipart_files
+      .unwrap_or_default()
+      .iter()
+      .filter_map(|file| file.name.as_deref()),
+  );
+  if !api.writes_admin_only_columns(fields, update) {
+    return Ok(());
+  }
+
+  if let Some(user) = user
+    && is_admin(state, &user.uuid).await
+  {
+    return Ok(());
+  }
+
+  return Err(RecordError::Forbidden);
+}
+
 fn extract_files_from_multipart<S: ColumnAccessor>(
   accessor: &S,
   multipart_files: Vec<FileUploadInput>,
@@ -487,7 +586,7 @@ fn extract_files_from_multipart<S: ColumnAccessor>(
   column_names: &mut Vec<String>,
   column_indexes: &mut Vec<usize>,
 ) -> Result<FileMetadataContents, ParamsError> {
-  let files: Vec<(String, FileUpload, Option<Vec<u8>>)> = multipart_files
+  let files: Vec<(String, FileUpload, Option<FileUploadData>)> = multipart_files
     .into_iter()
     .map(|file| {
       let (col_name, file_metadata, content) = file.consume()?;
@@ -496,13 +595,14 @@ fn extract_files_from_multipart<S: ColumnAccessor>(
           "Multipart form upload missing name property",
         ));
       };
-      return Ok((col_name, file_metadata, Some(content.0)));
+      return Ok((col_name, file_metadata, Some(content)));
     })
     .collect::<Result<_, ParamsError>>()?;
 
   // Validate and organize by type;
   let mut uploaded_files = HashSet::<&'static str>::new();
-  for (field_name, file_metadata, _content) in &files {
+  let mut file_counts = HashMap::<&str, usize>::new();
+  for (field_name, file_metadata, content) in &files {
     // We simply skip unknown columns, this could simply be malformed input or version skew. This
     // is similar in spirit to protobuf's unknown fields behavior.
     let Some(ColumnMetadata {
@@ -520,6 +620,14 @@ fn extract_files_from_multipart<S: ColumnAccessor>(
       return Err(ParamsError::Column("Expected json file column"));
     };
 
+    if let Some(policy) = accessor.file_policy(&column.name) {
+      check_file_policy(policy, file_metadata, content.as_ref())?;
+
+      let count = file_counts.entry(&column.name).or_default();
+      *count += 1;
+      check_file_count(policy, *count)?;
+    }
+
     match schema_name.as_str() {
       "std.FileUpload" => {
         if !uploaded_files.insert(&column.name) {
@@ -557,11 +665,68 @@ fn extract_files_from_multipart<S: ColumnAccessor>(
   );
 }
 
+/// Validates a file's type and size against the column's policy. Round-tripped metadata w/o
+/// contents was validated when originally uploaded.
+fn check_file_policy(
+  policy: &FileColumnPolicy,
+  metadata: &FileUpload,
+  contents: Option<&FileUploadData>,
+) -> Result<(), ParamsError> {
+  let Some(contents) = contents else {
+    return Ok(());
+  };
+
+  if let Some(max_file_size) = policy.max_file_size
+    && contents.size() > max_file_size
+  {
+    return Err(ParamsError::FileSize(contents.size(), max_file_size));
+  }
+
+  return check_mime_type(policy, metadata.content_type());
+}
+
+pub(crate) fn check_mime_type(
+  policy: &FileColumnPolicy,
+  mime_type: Option<&str>,
+) -> Result<(), ParamsError> {
+  if policy.allowed_mime_types.is_empty() {
+    return Ok(());
+  }
+
+  let Some(mime_type) = mime_type else {
+    return Err(ParamsError::FileType("unknown".to_string()));
+  };
+
+  let allowed = policy.allowed_mime_types.iter().any(|allowed| {
+    return match allowed.strip_suffix("/*") {
+      Some(prefix) => mime_type
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.starts_with('/')),
+      None => allowed == mime_type,
+    };
+  });
+
+  if !allowed {
+    return Err(ParamsError::FileType(mime_type.to_string()));
+  }
+  return Ok(());
+}
+
+fn check_file_count(policy: &FileColumnPolicy, count: usize) -> Result<(), ParamsError> {
+  if let Some(max_file_count) = policy.max_file_count
+    && count as u64 > max_file_count
+  {
+    return Err(ParamsError::FileCount(count, max_file_count));
+  }
+  return Ok(());
+}
+
 fn extract_params_and_files_from_json(
   json_schema_registry: &JsonSchemaRegistry,
   col: &Column,
   json_metadata: Option<&JsonColumnMetadata>,
   #[allow(unused)] is_geometry: bool,
+  file_policy: Option<&FileColumnPolicy>,
   value: serde_json::Value,
 ) -> Result<(Value, Option<FileMetadataContents>), ParamsError> {
   // If this is *not* a JSON column convert the value trivially.
@@ -598,6 +763,10 @@ fn extract_params_and_files_from_json(
   match json_metadata {
     JsonColumnMetadata::SchemaName(name) if name == "std.FileUpload" => {
       let (metadata, contents) = extract_param_and_file_from_json_value(value)?;
+      if let Some(policy) = file_policy {
+        check_file_policy(policy, &metadata, contents.as_ref())?;
+      }
+
       let param = Value::Text(serde_json::to_string(&metadata)?);
       return Ok((param, Some(vec![(metadata, contents)])));
     }
@@ -614,6 +783,13 @@ fn extract_params_and_files_from_json(
         })
         .collect::<Result<Vec<_>, ParamsError>>()?;
 
+      if let Some(policy) = file_policy {
+        check_file_count(policy, uploads.len())?;
+        for (metadata, contents) in &uploads {
+          check_file_policy(policy, metadata, contents.as_ref())?;
+        }
+      }
+
       let param = Value::Text(serde_json::to_string(&FileUploads(
         uploads
           .iter()
@@ -650,7 +826,7 @@ fn extract_params_and_files_from_json(
 
 fn extract_param_and_file_from_json_value(
   value: serde_json::Value,
-) -> Result<(FileUpload, Option<Vec<u8>>), ParamsError> {
+) -> Result<(FileUpload, Option<FileUploadData>), ParamsError> {
   // We allow inputs to either be "fresh" inputs containing actual data bytes or metadata
   // round-tripped from prior reads (where the data is already stored). The latter is useful for
   // updates and partial deletions.
@@ -666,7 +842,7 @@ fn extract_param_and_file_from_json_value(
   return match serde_json::from_value::<InputOrMetadata>(value)? {
     InputOrMetadata::Input(file_upload_input) => {
       let (_col_name, metadata, content) = file_upload_input.consume()?;
-      Ok((metadata, Some(content.0)))
+      Ok((metadata, Some(content)))
     }
     InputOrMetadata::Metadata(metadata) => Ok((metadata, None)),
   };
@@ -0,0 +1,344 @@
+use axum::{
+  Json,
+  extract::{Path, Query, State},
+  http::{HeaderMap, header},
+  response::{IntoResponse, Response},
+};
+use base64::prelude::*;
+use prost_reflect::prost::Message;
+use prost_reflect::prost_types::{
+  DescriptorProto, FieldDescriptorProto, FileDescriptorProto, field_descriptor_proto,
+};
+use prost_reflect::{DynamicMessage, MessageDescriptor, Value};
+use trailbase_schema::metadata::ColumnMetadata;
+use trailbase_schema::sqlite::ColumnDataType;
+
+use crate::DESCRIPTOR_POOL;
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::records::list_records::ListResponse;
+use crate::records::read_record::{ReadRecordQuery, read_record_handler};
+use crate::records::{RecordApi, RecordError};
+
+pub(crate) const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Whether the client asked for protobuf-encoded responses via the `Accept` header.
+pub(crate) fn accepts_protobuf(headers: &HeaderMap) -> bool {
+  let Some(accept) = headers.get(header::ACCEPT).and_then(|h| h.to_str().ok()) else {
+    return false;
+  };
+
+  return accept.split(',').any(|media_type| {
+    media_type
+      .split(';')
+      .next()
+      .unwrap_or_default()
+      .trim()
+      .eq_ignore_ascii_case(PROTOBUF_CONTENT_TYPE)
+  });
+}
+
+/// Dynamic protobuf messages derived from a Record API's schema.
+///
+/// Fields are numbered by their column position starting at 1 and named after their columns.
+/// JSON, file, geometry and expandable foreign key columns are encoded as JSON strings.
+#[derive(Debug)]
+pub(crate) struct RecordDescriptors {
+  record: MessageDescriptor,
+  list: MessageDescriptor,
+}
+
+impl RecordDescriptors {
+  pub(crate) fn build(api: &RecordApi) -> Result<Self, String> {
+    let package = "records.dynamic";
+    let prefix = api
+      .api_name()
+      .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    let record_name = format!("{prefix}_Record");
+    let list_name = format!("{prefix}_List");
+
+    let optional = |name: &str, number: i32, r#type: field_descriptor_proto::Type| {
+      return FieldDescriptorProto {
+        name: Some(name.to_string()),
+        number: Some(number),
+        label: Some(field_descriptor_proto::Label::Optional as i32),
+        r#type: Some(r#type as i32),
+        ..Default::default()
+      };
+    };
+
+    let record = DescriptorProto {
+      name: Some(record_name.clone()),
+      field: api
+        .columns()
+        .iter()
+        .enumerate()
+        .filter(|(_, meta)| !meta.column.name.starts_with("_"))
+        .map(|(index, meta)| optional(&meta.column.name, index as i32 + 1, field_type(api, meta)))
+        .collect(),
+      ..Default::default()
+    };
+
+    let list = DescriptorProto {
+      name: Some(list_name.clone()),
+      field: vec![
+        FieldDescriptorProto {
+          label: Some(field_descriptor_proto::Label::Repeated as i32),
+          type_name: Some(format!(".{package}.{record_name}")),
+          ..optional("records", 1, field_descriptor_proto::Type::Message)
+        },
+        optional("cursor", 2, field_descriptor_proto::Type::String),
+        optional("total_count", 3, field_descriptor_proto::Type::Int64),
+      ],
+      ..Default::default()
+    };
+
+    let mut pool = DESCRIPTOR_POOL.clone();
+    pool
+      .add_file_descriptor_proto(FileDescriptorProto {
+        name: Some(format!("records/dynamic/{prefix}.proto")),
+        package: Some(package.to_string()),
+        message_type: vec![record, list],
+        syntax: Some("proto2".to_string()),
+        ..Default::default()
+      })
+      .map_err(|err| err.to_string())?;
+
+    return Ok(Self {
+      record: pool
+        .get_message_by_name(&format!("{package}.{record_name}"))
+        .ok_or("missing record message")?,
+      list: pool
+        .get_message_by_name(&format!("{package}.{list_name}"))
+        .ok_or("missing list message")?,
+    });
+  }
+
+  fn record_message(&self, record: &serde_json::Value) -> Result<DynamicMessage, RecordError> {
+    let serde_json::Value::Object(record) = record else {
+      return Err(RecordError::Internal("expected record".into()));
+    };
+
+    let mut message = DynamicMessage::new(self.record.clone());
+    for (name, value) in record {
+      let Some(field) = self.record.get_field_by_name(name) else {
+        continue;
+      };
+
+      let value = match (field.kind(), value) {
+        (_, serde_json::Value::Null) => continue,
+        (prost_reflect::Kind::Int64, serde_json::Value::Number(n)) => n.as_i64().map(Value::I64),
+        (prost_reflect::Kind::Double, serde_json::Value::Number(n)) => n.as_f64().map(Value::F64),
+        (prost_reflect::Kind::Bytes, serde_json::Value::String(s)) => BASE64_URL_SAFE
+          .decode(s)
+          .ok()
+          .map(|b| Value::Bytes(b.into())),
+        (prost_reflect::Kind::String, serde_json::Value::String(s)) => {
+          Some(Value::String(s.clone()))
+        }
+        (prost_reflect::Kind::String, value) => Some(Value::String(value.to_string())),
+        _ => None,
+      };
+
+      let Some(value) = value else {
+        return Err(RecordError::Internal(
+          format!("'{name}' not representable as protobuf").into(),
+        ));
+      };
+      message.set_field(&field, value);
+    }
+
+    return Ok(message);
+  }
+
+  pub(crate) fn encode_record(&self, record: &serde_json::Value) -> Result<Response, RecordError> {
+    return Ok(protobuf_response(
+      self.record_message(record)?.encode_to_vec(),
+    ));
+  }
+
+  pub(crate) fn encode_list(&self, list: &ListResponse) -> Result<Response, RecordError> {
+    let mut message = DynamicMessage::new(self.list.clone());
+    message.set_field_by_name(
+      "records",
+      Value::List(
+        list
+          .records
+          .iter()
+          .map(|record| Ok(Value::Message(self.record_message(record)?)))
+          .collect::<Result<Vec<_>, RecordError>>()?,
+      ),
+    );
+    if let Some(ref cursor) = list.cursor {
+      message.set_field_by_name("cursor", Value::String(cursor.clone()));
+    }
+    if let Some(total_count) = list.total_count {
+      message.set_field_by_name("total_count", Value::I64(total_count as i64));
+    }
+
+    return Ok(protobuf_response(message.encode_to_vec()));
+  }
+}
+
+fn field_type(api: &RecordApi, meta: &ColumnMetadata) -> field_descriptor_proto::Type {
+  let expandable = api
+    .expand()
+    .is_some_and(|e| e.contains_key(&meta.column.name));
+  if meta.json.is_some() || meta.is_geometry || expandable {
+    return field_descriptor_proto::Type::String;
+  }
+
+  return match meta.column.data_type {
+    ColumnDataType::Integer => field_descriptor_proto::Type::Int64,
+    ColumnDataType::Real => field_descriptor_proto::Type::Double,
+    ColumnDataType::Blob => field_descriptor_proto::Type::Bytes,
+    ColumnDataType::Text | ColumnDataType::Any => field_descriptor_proto::Type::String,
+  };
+}
+
+fn protobuf_response(body: Vec<u8>) -> Response {
+  return ([(header::CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)], body).into_response();
+}
+
+/// Reads a record as JSON or, if requested via the `Accept` header, as protobuf.
+pub async fn read_record_or_protobuf_handler(
+  State(state): State<AppState>,
+  Path((api_name, record)): Path<(String, String)>,
+  Query(query): Query<ReadRecordQuery>,
+  headers: HeaderMap,
+  user: Option<User>,
+) -> Result<Response, RecordError> {
+  if !accepts_protobuf(&headers) {
+    return read_record_handler(State(state), Path((api_name, record)), Query(query), user)
+      .await
+      .map(IntoResponse::into_response);
+  }
+
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  let Json(record) =
+    read_record_handler(State(state), Path((api_name, record)), Query(query), user).await?;
+
+  return api.protobuf_descriptors()?.encode_record(&record);
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::HeaderValue;
+  use http_body_util::BodyExt;
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::test_utils::*;
+
+  #[test]
+  fn test_accepts_protobuf() {
+    let headers = |accept: &'static str| {
+      return HeaderMap::from_iter([(header::ACCEPT, HeaderValue::from_static(accept))]);
+    };
+
+    assert!(!accepts_protobuf(&HeaderMap::new()));
+    assert!(!accepts_protobuf(&headers("application/json")));
+    assert!(accepts_protobuf(&headers(
+      "application/json;q=0.5, application/x-protobuf"
+    )));
+  }
+
+  #[tokio::test]
+  async fn test_protobuf_record() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE proto (
+            id      INTEGER PRIMARY KEY,
+            name    TEXT,
+            score   REAL,
+            data    BLOB,
+            _hidden TEXT
+          ) {strict};
+
+          INSERT INTO proto (id, name, score, data) VALUES (1, 'first', 1.5, X'0102');
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("proto".to_string()),
+        acl_world: [PermissionFlag::Read as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let response = read_record_or_protobuf_handler(
+      State(state.clone()),
+      Path(("api".to_string(), "1".to_string())),
+      Query(ReadRecordQuery::default()),
+      HeaderMap::from_iter([(
+        header::ACCEPT,
+        HeaderValue::from_static(PROTOBUF_CONTENT_TYPE),
+      )]),
+      None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+      PROTOBUF_CONTENT_TYPE,
+      response.headers().get(header::CONTENT_TYPE).unwrap()
+    );
+
+    let api = state.lookup_record_api("api").unwrap();
+    let descriptors = api.protobuf_descriptors().unwrap();
+    assert!(descriptors.record.get_field_by_name("_hidden").is_none());
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let message = DynamicMessage::decode(descriptors.record.clone(), body).unwrap();
+
+    assert_eq!(Value::I64(1), *message.get_field_by_name("id").unwrap());
+    assert_eq!(
+      Value::String("first".to_string()),
+      *message.get_field_by_name("name").unwrap()
+    );
+    assert_eq!(
+      Value::F64(1.5),
+      *message.get_field_by_name("score").unwrap()
+    );
+    assert_eq!(
+      Value::Bytes(vec![1, 2].into()),
+      *message.get_field_by_name("data").unwrap()
+    );
+
+    let list = descriptors
+      .encode_list(&ListResponse {
+        cursor: None,
+        total_count: Some(1),
+        records: vec![serde_json::json!({"id": 1, "name": "first"})],
+      })
+      .unwrap();
+    let body = list.into_body().collect().await.unwrap().to_bytes();
+    let message = DynamicMessage::decode(descriptors.list.clone(), body).unwrap();
+    assert_eq!(
+      Value::I64(1),
+      *message.get_field_by_name("total_count").unwrap()
+    );
+    let records = message.get_field_by_name("records").unwrap();
+    let Value::List(records) = records.as_ref() else {
+      panic!("expected list");
+    };
+    assert_eq!(1, records.len());
+  }
+}
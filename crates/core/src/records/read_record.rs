@@ -1,20 +1,29 @@
 use axum::{
   Json,
   extract::{Path, Query, State},
+  http::HeaderMap,
   response::Response,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::slice::from_mut;
 use trailbase_schema::FileUploads;
+use trailbase_schema::metadata::JsonColumnMetadata;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
 use crate::records::expand::expand_tables;
 use crate::records::expand::row_to_json_expand;
-use crate::records::files::read_file_into_response;
+use crate::records::files::{
+  SignedFileQuery, read_file_into_response, signed_file_url, verify_signed_file_url,
+};
+use crate::records::hooks::run_after_read_hooks;
 use crate::records::read_queries::{
   ExpandedSelectQueryResult, run_expanded_select_query, run_get_file_query, run_get_files_query,
   run_select_query,
 };
+use crate::records::thumbnail::{ThumbnailQuery, read_thumbnail_into_response};
 use crate::records::{Permission, RecordError};
 
 #[derive(Debug, Default, Deserialize)]
@@ -23,6 +32,9 @@ pub struct ReadRecordQuery {
   ///
   /// Requires the API's configuration to explicitly allow expanding said columns.
   pub expand: Option<String>,
+
+  /// Comma separated list of column names to return. All columns by default.
+  pub select: Option<String>,
 }
 
 /// Read record.
@@ -51,9 +63,19 @@ pub async fn read_record_handler(
     .await?;
 
   let pk_meta = api.record_pk_column();
+
+  // Optional projection onto a subset of columns. Excluded, hidden and columns excluded from
+  // reads for the user cannot be selected.
+  let names: Option<Vec<String>> = query
+    .select
+    .map(|select| select.split(",").map(|name| name.to_string()).collect());
+  let selected_columns: Cow<'_, [trailbase_schema::metadata::ColumnMetadata]> = api
+    .readable_columns(names.as_deref(), user.as_ref())
+    .ok_or(RecordError::BadRequest("Invalid select"))?;
+  let is_projected = selected_columns.len() != api.columns().len();
+
   let column_names = || {
-    api
-      .columns()
+    selected_columns
       .iter()
       .map(|meta| meta.column.name.as_str())
       .collect::<Vec<_>>()
@@ -68,17 +90,25 @@ pub async fn read_record_handler(
 
     // Input validation, i.e. only accept columns that are also configured.
     let query_expand: Vec<_> = query_expand.split(",").collect();
+    if api.max_joins().is_some_and(|max| query_expand.len() > max) {
+      return Err(RecordError::BadRequest("Too many expansions"));
+    }
     for col_name in &query_expand {
       if !query_expand.contains(col_name) {
         return Err(RecordError::BadRequest("Invalid expansion"));
       }
+
+      // Expanded columns must be part of the projection.
+      if !selected_columns.iter().any(|m| m.column.name == *col_name) {
+        return Err(RecordError::BadRequest("Invalid expansion"));
+      }
     }
 
     let metadata = api.connection_metadata();
     let expanded_tables = expand_tables(&api, metadata, &query_expand)?;
 
     let Some(ExpandedSelectQueryResult { root, foreign_rows }) = run_expanded_select_query(
-      api.conn(),
+      api.read_conn(),
       api.table_name(),
       &column_names(),
       &pk_meta.column.name,
@@ -102,34 +132,59 @@ pub async fn read_record_handler(
       debug_assert!(result.is_some(), "{col_name} duplicate");
     }
 
-    return Ok(Json(
-      row_to_json_expand(api.columns(), &root, prefix_filter, Some(&expand))
-        .map_err(|err| RecordError::Internal(err.into()))?,
-    ));
+    let mut record = row_to_json_expand(&selected_columns, &root, prefix_filter, Some(&expand))
+      .map_err(|err| RecordError::Internal(err.into()))?;
+    run_after_read_hooks(&state, &api_name, from_mut(&mut record), user.as_ref());
+
+    return Ok(Json(record));
   }
 
+  // NOTE: The cache only holds full records.
+  let cache = api.read_cache().filter(|_| !is_projected);
+  if let Some(mut cached) = cache.and_then(|cache| cache.get(&record_id)) {
+    run_after_read_hooks(&state, &api_name, from_mut(&mut cached), user.as_ref());
+    return Ok(Json(cached));
+  }
+
+  // NOTE: The version has to be observed before reading, see `WriteCounters`.
+  let version = cache.map(|cache| cache.version());
   let Some(row) = run_select_query(
-    api.conn(),
+    api.read_conn(),
     api.table_name(),
     &column_names(),
     &pk_meta.column.name,
-    record_id,
+    record_id.clone(),
   )
   .await?
   else {
     return Err(RecordError::RecordNotFound);
   };
 
-  let json_response = row_to_json_expand(api.columns(), &row, prefix_filter, api.expand())
+  let mut json_response = row_to_json_expand(&selected_columns, &row, prefix_filter, api.expand())
     .map_err(|err| RecordError::Internal(err.into()))?;
 
+  if let (Some(cache), Some(version)) = (cache, version) {
+    cache.insert(&record_id, version, json_response.clone());
+  }
+
+  // NOTE: Projected records are missing required properties by design.
   #[cfg(debug_assertions)]
-  crate::records::json_schema::validate_api_json_schema(
+  if !is_projected {
+    crate::records::json_schema::validate_api_json_schema(
+      &state,
+      &api,
+      trailbase_schema::json_schema::JsonSchemaMode::Select,
+      &json_response,
+    )?;
+  }
+
+  // NOTE: Hooks may depend on the user, thus run them after caching.
+  run_after_read_hooks(
     &state,
-    &api,
-    trailbase_schema::json_schema::JsonSchemaMode::Select,
-    &json_response,
-  )?;
+    &api_name,
+    from_mut(&mut json_response),
+    user.as_ref(),
+  );
 
   return Ok(Json(json_response));
 }
@@ -148,6 +203,7 @@ type GetUploadedFileFromRecordPath = Path<(
   get,
   path = "/{name}/{record}/file/{column_name}",
   tag = "records",
+  params(SignedFileQuery, ThumbnailQuery),
   responses(
     (status = 200, description = "File contents.")
   )
@@ -155,20 +211,31 @@ type GetUploadedFileFromRecordPath = Path<(
 pub async fn get_uploaded_file_from_record_handler(
   state: State<AppState>,
   Path((api_name, record, column_name)): GetUploadedFileFromRecordPath,
+  Query(signed_file_query): Query<SignedFileQuery>,
+  Query(thumbnail_query): Query<ThumbnailQuery>,
   user: Option<User>,
+  headers: HeaderMap,
 ) -> Result<Response, RecordError> {
   let Some(api) = state.lookup_record_api(&api_name) else {
     return Err(RecordError::ApiNotFound);
   };
 
+  let signed = verify_signed_file_url(
+    state.jwt(),
+    &[&api_name, &record, "file", &column_name],
+    &signed_file_query,
+  );
+
   let record_id = api.primary_key_to_value(record)?;
 
-  let Ok(()) = api
-    .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
-    .await
-  else {
-    return Err(RecordError::Forbidden);
-  };
+  if !signed {
+    let Ok(()) = api
+      .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
+      .await
+    else {
+      return Err(RecordError::Forbidden);
+    };
+  }
 
   let pk_meta = api.record_pk_column();
 
@@ -177,7 +244,7 @@ pub async fn get_uploaded_file_from_record_handler(
   };
 
   let file_upload = run_get_file_query(
-    api.conn(),
+    api.read_conn(),
     api.table_name(),
     column_metadata,
     &pk_meta.column.name,
@@ -185,7 +252,11 @@ pub async fn get_uploaded_file_from_record_handler(
   )
   .await?;
 
-  return read_file_into_response(&state, file_upload)
+  if thumbnail_query.thumb.is_some() {
+    return read_thumbnail_into_response(&state, &api, file_upload, &thumbnail_query).await;
+  }
+
+  return read_file_into_response(&state, file_upload, &headers)
     .await
     .map_err(|err| RecordError::Internal(err.into()));
 }
@@ -204,6 +275,7 @@ type GetUploadedFilesFromRecordPath = Path<(
   get,
   path = "/{name}/{record}/files/{column_name}/{file_name}",
   tag = "records",
+  params(SignedFileQuery, ThumbnailQuery),
   responses(
     (status = 200, description = "File contents.")
   )
@@ -211,22 +283,33 @@ type GetUploadedFilesFromRecordPath = Path<(
 pub async fn get_uploaded_files_from_record_handler(
   State(state): State<AppState>,
   Path((api_name, record, column_name, file_name)): GetUploadedFilesFromRecordPath,
+  Query(signed_file_query): Query<SignedFileQuery>,
+  Query(thumbnail_query): Query<ThumbnailQuery>,
   user: Option<User>,
+  headers: HeaderMap,
 ) -> Result<Response, RecordError> {
   let Some(api) = state.lookup_record_api(&api_name) else {
     return Err(RecordError::ApiNotFound);
   };
 
+  let signed = verify_signed_file_url(
+    state.jwt(),
+    &[&api_name, &record, "files", &column_name, &file_name],
+    &signed_file_query,
+  );
+
   let record_id = api.primary_key_to_value(record)?;
-  api
-    .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
-    .await?;
+  if !signed {
+    api
+      .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
+      .await?;
+  }
 
   let Some(column_metadata) = api.column_metadata_by_name(&column_name) else {
     return Err(RecordError::BadRequest("Invalid field/column name"));
   };
   let FileUploads(file_uploads) = run_get_files_query(
-    api.conn(),
+    api.read_conn(),
     api.table_name(),
     column_metadata,
     &api.record_pk_column().column.name,
@@ -239,11 +322,91 @@ pub async fn get_uploaded_files_from_record_handler(
     .find(|f| f.filename() == file_name)
     .ok_or_else(|| RecordError::RecordNotFound)?;
 
-  return read_file_into_response(&state, file_upload)
+  if thumbnail_query.thumb.is_some() {
+    return read_thumbnail_into_response(&state, &api, file_upload, &thumbnail_query).await;
+  }
+
+  return read_file_into_response(&state, file_upload, &headers)
     .await
     .map_err(|err| RecordError::Internal(err.into()));
 }
 
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct SignFileUrlQuery {
+  /// Name of the file to sign. Required for columns holding multiple files.
+  pub file_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct SignedFileUrlResponse {
+  /// Relative URL granting read access to the file w/o further authorization.
+  pub url: String,
+  /// Expiration of the URL as UNIX timestamp in seconds.
+  pub expires: i64,
+}
+
+/// Sign file URL.
+///
+/// Returns a short-lived URL to the file, which can be fetched w/o `Authorization` header, e.g.
+/// by a browser's `<img src>`. Requires read access to the record.
+#[utoipa::path(
+  get,
+  path = "/{name}/{record}/files/{column_name}/sign",
+  tag = "records",
+  params(SignFileUrlQuery),
+  responses(
+    (status = 200, description = "Signed file URL.", body = SignedFileUrlResponse)
+  )
+)]
+pub async fn sign_file_url_handler(
+  State(state): State<AppState>,
+  Path((api_name, record, column_name)): GetUploadedFileFromRecordPath,
+  Query(sign_file_url_query): Query<SignFileUrlQuery>,
+  user: Option<User>,
+) -> Result<Json<SignedFileUrlResponse>, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  let record_id = api.primary_key_to_value(record.clone())?;
+  api
+    .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
+    .await?;
+
+  // Columns excluded from reads for the user cannot be signed either.
+  let Some(columns) = api.readable_columns(Some(&[column_name.clone()]), user.as_ref()) else {
+    return Err(RecordError::BadRequest("Invalid field/column name"));
+  };
+  let Some(column_metadata) = columns.first() else {
+    return Err(RecordError::BadRequest("Invalid field/column name"));
+  };
+
+  let expires = (chrono::Utc::now() + SIGNED_FILE_URL_TTL).timestamp();
+  let url = match (&column_metadata.json, sign_file_url_query.file_name) {
+    (Some(JsonColumnMetadata::SchemaName(name)), None) if name == "std.FileUpload" => {
+      signed_file_url(
+        state.jwt(),
+        &[&api_name, &record, "file", &column_name],
+        expires,
+      )
+    }
+    (Some(JsonColumnMetadata::SchemaName(name)), Some(file_name)) if name == "std.FileUploads" => {
+      signed_file_url(
+        state.jwt(),
+        &[&api_name, &record, "files", &column_name, &file_name],
+        expires,
+      )
+    }
+    _ => {
+      return Err(RecordError::BadRequest("Invalid file column or file name"));
+    }
+  };
+
+  return Ok(Json(SignedFileUrlResponse { url, expires }));
+}
+
+const SIGNED_FILE_URL_TTL: chrono::Duration = chrono::Duration::hours(1);
+
 #[inline]
 fn prefix_filter(col_name: &str) -> bool {
   return !col_name.starts_with("_");
@@ -274,7 +437,7 @@ mod test {
   use crate::records::delete_record::delete_record_handler;
   use crate::records::params::JsonRow;
   use crate::records::test_utils::*;
-  use crate::records::update_record::update_record_handler;
+  use crate::records::update_record::{UpdateRecordQuery, update_record_handler};
   use crate::test::unpack_json_response;
   use crate::util::id_to_b64;
 
@@ -484,6 +647,7 @@ mod test {
         Path(API_NAME.to_string()),
         Query(CreateRecordQuery::default()),
         None,
+        None,
         Either::Json(JsonRow::new().into()),
       )
       .await
@@ -517,6 +681,7 @@ mod test {
         Path(API_NAME.to_string()),
         Query(CreateRecordQuery::default()),
         None,
+        None,
         Either::Json(json!({
           "index": column_value.to_string(),
           "test 😍": column_value.to_string(),
@@ -568,13 +733,14 @@ mod test {
         Path(API_NAME.to_string()),
         Query(CreateRecordQuery::default()),
         None,
+        None,
         Either::Json(
           json_row_from_value(json!({
             file_column: FileUploadInput {
               name: Some("name".to_string()),
               filename: Some("bar".to_string()),
               content_type: Some("baz".to_string()),
-              data: FileUploadData(bytes.clone()),
+              data: FileUploadData::Bytes(bytes.clone()),
             },
           }))
           .unwrap()
@@ -619,7 +785,10 @@ mod test {
     let read_response = get_uploaded_file_from_record_handler(
       State(state.clone()),
       Path(record_file_path.clone()),
+      Query(SignedFileQuery::default()),
+      Query(ThumbnailQuery::default()),
       None,
+      HeaderMap::new(),
     )
     .await
     .unwrap();
@@ -645,11 +814,299 @@ mod test {
       get_uploaded_file_from_record_handler(
         State(state.clone()),
         Path(record_file_path.clone()),
+        Query(SignedFileQuery::default()),
+        Query(ThumbnailQuery::default()),
+        None,
+        HeaderMap::new(),
+      )
+      .await
+      .is_err()
+    );
+  }
+
+  #[tokio::test]
+  async fn test_file_range_and_conditional_requests() {
+    use axum::http::{StatusCode, header};
+
+    let state = test_state(None).await.unwrap();
+    const API_NAME: &str = "test_api";
+    create_test_record_api(&state, API_NAME).await;
+
+    let bytes: Vec<u8> = (0..100).collect();
+    let create_response: CreateRecordResponse = unpack_json_response(
+      create_record_handler(
+        State(state.clone()),
+        Path(API_NAME.to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(
+          json_row_from_value(json!({
+            "file": FileUploadInput {
+              name: None,
+              filename: Some("video.mp4".to_string()),
+              content_type: Some("video/mp4".to_string()),
+              data: FileUploadData::Bytes(bytes.clone()),
+            },
+          }))
+          .unwrap()
+          .into(),
+        ),
+      )
+      .await
+      .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let read_file = async |headers: &[(header::HeaderName, &str)]| {
+      let mut header_map = HeaderMap::new();
+      for (name, value) in headers {
+        header_map.insert(name.clone(), value.parse().unwrap());
+      }
+
+      return get_uploaded_file_from_record_handler(
+        State(state.clone()),
+        Path((
+          API_NAME.to_string(),
+          create_response.ids[0].clone(),
+          "file".to_string(),
+        )),
+        Query(SignedFileQuery::default()),
+        Query(ThumbnailQuery::default()),
+        None,
+        header_map,
+      )
+      .await
+      .unwrap();
+    };
+
+    let response = read_file(&[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+    let etag = response.headers()[header::ETAG]
+      .to_str()
+      .unwrap()
+      .to_string();
+    let last_modified = response.headers()[header::LAST_MODIFIED]
+      .to_str()
+      .unwrap()
+      .to_string();
+
+    // Ranges.
+    let response = read_file(&[(header::RANGE, "bytes=10-19")]).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 10-19/100");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert_eq!(body.to_vec(), bytes[10..20]);
+
+    let response = read_file(&[(header::RANGE, "bytes=-5")]).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert_eq!(body.to_vec(), bytes[95..]);
+
+    let response = read_file(&[(header::RANGE, "bytes=200-")]).await;
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */100");
+
+    // Conditionals.
+    let response = read_file(&[(header::IF_NONE_MATCH, &etag)]).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let response = read_file(&[(header::IF_NONE_MATCH, "\"other\"")]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = read_file(&[(header::IF_MODIFIED_SINCE, &last_modified)]).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let response = read_file(&[(header::IF_MODIFIED_SINCE, "Wed, 21 Oct 2015 07:28:00 GMT")]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn test_signed_file_url() {
+    let state = test_state(None).await.unwrap();
+    const API_NAME: &str = "test_api";
+    create_test_record_api(&state, API_NAME).await;
+
+    // Same table but w/o world access.
+    const PRIVATE_API_NAME: &str = "private_api";
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some(PRIVATE_API_NAME.to_string()),
+        table_name: Some("table 😍".to_string()),
+        acl_authenticated: [PermissionFlag::Read as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let bytes: Vec<u8> = vec![1, 2, 3];
+    let create_response: CreateRecordResponse = unpack_json_response(
+      create_record_handler(
+        State(state.clone()),
+        Path(API_NAME.to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(
+          json_row_from_value(json!({
+            "file": FileUploadInput {
+              name: None,
+              filename: Some("img.png".to_string()),
+              content_type: Some("image/png".to_string()),
+              data: FileUploadData::Bytes(bytes.clone()),
+            },
+          }))
+          .unwrap()
+          .into(),
+        ),
+      )
+      .await
+      .unwrap(),
+    )
+    .await
+    .unwrap();
+    let record_id = create_response.ids[0].clone();
+
+    let read_file = async |api_name: &str, query: SignedFileQuery| {
+      return get_uploaded_file_from_record_handler(
+        State(state.clone()),
+        Path((api_name.to_string(), record_id.clone(), "file".to_string())),
+        Query(query),
+        Query(ThumbnailQuery::default()),
+        None,
+        HeaderMap::new(),
+      )
+      .await;
+    };
+
+    assert!(
+      read_file(PRIVATE_API_NAME, SignedFileQuery::default())
+        .await
+        .is_err()
+    );
+
+    // Signing requires read access.
+    assert!(
+      sign_file_url_handler(
+        State(state.clone()),
+        Path((
+          PRIVATE_API_NAME.to_string(),
+          record_id.clone(),
+          "file".to_string()
+        )),
+        Query(SignFileUrlQuery::default()),
         None,
       )
       .await
       .is_err()
     );
+
+    let Json(signed) = sign_file_url_handler(
+      State(state.clone()),
+      Path((API_NAME.to_string(), record_id.clone(), "file".to_string())),
+      Query(SignFileUrlQuery::default()),
+      None,
+    )
+    .await
+    .unwrap();
+    assert!(signed.url.starts_with("/api/records/v1/test_api/"));
+
+    let query: SignedFileQuery =
+      serde_urlencoded::from_str(signed.url.split_once('?').unwrap().1).unwrap();
+    assert_eq!(Some(signed.expires), query.expires);
+
+    let response = read_file(API_NAME, query.clone()).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert_eq!(body.to_vec(), bytes);
+
+    // Signatures are bound to the API and path.
+    assert!(read_file(PRIVATE_API_NAME, query).await.is_err());
+
+    let private_path = [PRIVATE_API_NAME, &record_id, "file", "file"];
+    let expired: SignedFileQuery = serde_urlencoded::from_str(
+      signed_file_url(
+        state.jwt(),
+        &private_path,
+        chrono::Utc::now().timestamp() - 1,
+      )
+      .split_once('?')
+      .unwrap()
+      .1,
+    )
+    .unwrap();
+    assert!(read_file(PRIVATE_API_NAME, expired).await.is_err());
+
+    let valid: SignedFileQuery = serde_urlencoded::from_str(
+      signed_file_url(
+        state.jwt(),
+        &private_path,
+        chrono::Utc::now().timestamp() + 60,
+      )
+      .split_once('?')
+      .unwrap()
+      .1,
+    )
+    .unwrap();
+    read_file(PRIVATE_API_NAME, valid.clone()).await.unwrap();
+
+    // ... as well as the expiration.
+    assert!(
+      read_file(
+        PRIVATE_API_NAME,
+        SignedFileQuery {
+          expires: valid.expires.map(|e| e + 60),
+          ..valid
+        }
+      )
+      .await
+      .is_err()
+    );
+
+    // Columns excluded from reads cannot be signed.
+    const EXCLUDED_API_NAME: &str = "excluded_api";
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some(EXCLUDED_API_NAME.to_string()),
+        table_name: Some("table 😍".to_string()),
+        acl_world: [PermissionFlag::Read as i32].into(),
+        read_excluded_columns: [(
+          "world".to_string(),
+          crate::config::proto::ColumnList {
+            columns: vec!["file".to_string()],
+          },
+        )]
+        .into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    assert!(matches!(
+      sign_file_url_handler(
+        State(state.clone()),
+        Path((
+          EXCLUDED_API_NAME.to_string(),
+          record_id.clone(),
+          "file".to_string()
+        )),
+        Query(SignFileUrlQuery::default()),
+        None,
+      )
+      .await,
+      Err(RecordError::BadRequest(_))
+    ));
   }
 
   async fn read_objectstore_file(
@@ -683,20 +1140,20 @@ mod test {
         name: Some("foo0".to_string()),
         filename: Some("bar0".to_string()),
         content_type: Some("baz0".to_string()),
-        data: FileUploadData(bytes0.clone()),
+        data: FileUploadData::Bytes(bytes0.clone()),
       },
       "files": vec![
           FileUploadInput {
             name: Some("foo1".to_string()),
             filename: Some("bar1".to_string()),
             content_type: Some("baz1".to_string()),
-            data: FileUploadData(bytes1.clone()),
+            data: FileUploadData::Bytes(bytes1.clone()),
           },
           FileUploadInput {
             name: Some("foo2".to_string()),
             filename: Some("bar2".to_string()),
             content_type: Some("baz2".to_string()),
-            data: FileUploadData(bytes2.clone()),
+            data: FileUploadData::Bytes(bytes2.clone()),
           },
       ],
     });
@@ -707,6 +1164,7 @@ mod test {
         Path(API_NAME.to_string()),
         Query(CreateRecordQuery::default()),
         None,
+        None,
         Either::Json(json_row_from_value(request.clone()).unwrap().into()),
       )
       .await
@@ -729,7 +1187,7 @@ mod test {
         let file_path = object_store::path::Path::from(f.objectstore_id());
         assert_eq!(
           *expected,
-          read_objectstore_file(state.objectstore(), &file_path).await
+          read_objectstore_file(&state.objectstore(), &file_path).await
         );
 
         let response = read().await;
@@ -764,7 +1222,10 @@ mod test {
           return get_uploaded_file_from_record_handler(
             State(state.clone()),
             Path((API_NAME.to_string(), record_id.clone(), "file".to_string())),
+            Query(SignedFileQuery::default()),
+            Query(ThumbnailQuery::default()),
             None,
+            HeaderMap::new(),
           )
           .await
           .unwrap();
@@ -779,7 +1240,10 @@ mod test {
               "files".to_string(),
               files[0].filename().to_string(),
             )),
+            Query(SignedFileQuery::default()),
+            Query(ThumbnailQuery::default()),
             None,
+            HeaderMap::new(),
           )
           .await
           .unwrap();
@@ -794,7 +1258,10 @@ mod test {
               "files".to_string(),
               files[1].filename().to_string(),
             )),
+            Query(SignedFileQuery::default()),
+            Query(ThumbnailQuery::default()),
             None,
+            HeaderMap::new(),
           )
           .await
           .unwrap();
@@ -812,6 +1279,7 @@ mod test {
         Path(API_NAME.to_string()),
         Query(CreateRecordQuery::default()),
         None,
+        None,
         Either::Json(serde_json::Value::Array(vec![
           request.clone(),
           request.clone(),
@@ -836,6 +1304,7 @@ mod test {
     let _ = update_record_handler(
       State(state.clone()),
       Path((API_NAME.to_string(), resp0.ids[0].clone())),
+      Query(UpdateRecordQuery::default()),
       None,
       Either::Json(json_row_from_value(request.clone()).unwrap().into()),
     )
@@ -981,6 +1450,7 @@ mod test {
         Path(API_NAME.to_string()),
         Query(CreateRecordQuery::default()),
         None,
+        None,
         Either::Json(json_row_from_value(value.clone()).unwrap().into()),
       )
       .await
@@ -1002,12 +1472,42 @@ mod test {
 
     assert_eq!(json, value);
 
+    // Project onto a subset of columns.
+    let Json(json) = read_record_handler(
+      State(state.clone()),
+      Path((API_NAME.to_string(), create_response.ids[0].clone())),
+      Query(ReadRecordQuery {
+        select: Some("drop".to_string()),
+        ..Default::default()
+      }),
+      None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(json, json!({"drop": "foo"}));
+
+    // Excluded columns cannot be selected.
+    assert!(matches!(
+      read_record_handler(
+        State(state.clone()),
+        Path((API_NAME.to_string(), create_response.ids[0].clone())),
+        Query(ReadRecordQuery {
+          select: Some("pid,index".to_string()),
+          ..Default::default()
+        }),
+        None,
+      )
+      .await,
+      Err(RecordError::BadRequest(_))
+    ));
+
     // Providing a value for the hidden column should be ignored
     create_record_handler(
       State(state.clone()),
       Path(API_NAME.to_string()),
       Query(CreateRecordQuery::default()),
       None,
+      None,
       Either::Json(
         json_row_from_value(json!({
           "pid": 2,
@@ -1029,6 +1529,108 @@ mod test {
     assert_eq!(index, "");
   }
 
+  #[tokio::test]
+  async fn test_record_api_with_read_excluded_columns() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE "people" (
+            id       INTEGER PRIMARY KEY,
+            name     TEXT NOT NULL,
+            email    TEXT NOT NULL
+          ) {strict};
+
+          INSERT INTO "people" (id, name, email) VALUES (1, 'alice', 'alice@localhost');
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("people".to_string()),
+        table_name: Some("people".to_string()),
+        acl_world: [PermissionFlag::Read as i32].into(),
+        acl_authenticated: [PermissionFlag::Read as i32].into(),
+        read_excluded_columns: [
+          ("world", vec!["email"]),
+          ("authenticated", vec!["email"]),
+          ("support", vec![]),
+        ]
+        .into_iter()
+        .map(|(audience, columns)| {
+          return (
+            audience.to_string(),
+            crate::config::proto::ColumnList {
+              columns: columns.into_iter().map(str::to_string).collect(),
+            },
+          );
+        })
+        .collect(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let user = |roles: &[&str]| {
+      let uuid = uuid::Uuid::now_v7();
+      return User {
+        id: crate::util::uuid_to_b64(&uuid),
+        email: Some("user@localhost".to_string()),
+        username: None,
+        uuid,
+        csrf_token: "csrf".to_string(),
+        roles: roles.iter().map(|r| r.to_string()).collect(),
+        api_key: None,
+      };
+    };
+
+    let read = async |user: Option<User>, select: Option<&str>| {
+      return read_record_handler(
+        State(state.clone()),
+        Path(("people".to_string(), "1".to_string())),
+        Query(ReadRecordQuery {
+          select: select.map(|s| s.to_string()),
+          ..Default::default()
+        }),
+        user,
+      )
+      .await
+      .map(|response| response.0);
+    };
+
+    assert_eq!(
+      read(None, None).await.unwrap(),
+      json!({"id": 1, "name": "alice"})
+    );
+    assert_eq!(
+      read(Some(user(&["editor"])), None).await.unwrap(),
+      json!({"id": 1, "name": "alice"})
+    );
+    assert_eq!(
+      read(Some(user(&["support"])), None).await.unwrap(),
+      json!({"id": 1, "name": "alice", "email": "alice@localhost"})
+    );
+
+    // Hidden columns cannot be selected explicitly.
+    assert!(matches!(
+      read(None, Some("id,email")).await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert_eq!(
+      read(Some(user(&["support"])), Some("email")).await.unwrap(),
+      json!({"email": "alice@localhost"})
+    );
+  }
+
   #[tokio::test]
   async fn test_field_presence_acls() {
     const TABLE_NAME: &str = "table";
@@ -1086,6 +1688,7 @@ mod test {
       Path(API_NAME.to_string()),
       Query(CreateRecordQuery::default()),
       None,
+      None,
       Either::Json(
         json_row_from_value(json!({
           "col1": "value".to_string(),
@@ -1104,6 +1707,7 @@ mod test {
         Path(API_NAME.to_string()),
         Query(CreateRecordQuery::default()),
         None,
+        None,
         Either::Json(
           json_row_from_value(json!({
             "col0": "value".to_string(),
@@ -1287,6 +1891,7 @@ mod test {
       Path(name.clone()),
       Query(CreateRecordQuery::default()),
       None,
+      None,
       Either::Json(record.clone()),
     )
     .await;
@@ -1394,6 +1999,7 @@ mod test {
         Path(name.clone()),
         Query(CreateRecordQuery::default()),
         None,
+        None,
         Either::Json(record.clone()),
       )
       .await
@@ -1413,4 +2019,74 @@ mod test {
 
     assert_eq!(read_response, record);
   }
+
+  #[tokio::test]
+  async fn test_read_cache() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE cached (
+            id      INTEGER PRIMARY KEY,
+            value   TEXT NOT NULL
+          ) {strict};
+
+          INSERT INTO cached (id, value) VALUES (1, 'initial');
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    const API_NAME: &str = "cached_api";
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some(API_NAME.to_string()),
+        table_name: Some("cached".to_string()),
+        acl_world: [PermissionFlag::Read as i32, PermissionFlag::Update as i32].into(),
+        read_cache: Some(crate::config::proto::RecordCacheConfig::default()),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let read = async || -> serde_json::Value {
+      let Json(value) = read_record_handler(
+        State(state.clone()),
+        Path((API_NAME.to_string(), "1".to_string())),
+        Query(ReadRecordQuery::default()),
+        None,
+      )
+      .await
+      .unwrap();
+      return value;
+    };
+
+    assert_eq!(json!({"id": 1, "value": "initial"}), read().await);
+
+    // Writes bypassing the Record API invalidate the cache as well.
+    conn
+      .execute("UPDATE cached SET value = 'raw' WHERE id = 1", ())
+      .await
+      .unwrap();
+    assert_eq!(json!({"id": 1, "value": "raw"}), read().await);
+
+    // Writes through the Record API invalidate the cached entry.
+    update_record_handler(
+      State(state.clone()),
+      Path((API_NAME.to_string(), "1".to_string())),
+      Query(UpdateRecordQuery::default()),
+      None,
+      Either::Json(json_row_from_value(json!({"value": "updated"})).unwrap()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(json!({"id": 1, "value": "updated"}), read().await);
+  }
 }
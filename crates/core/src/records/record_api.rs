@@ -1,7 +1,9 @@
 use askama::Template;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::time::Duration;
+use trailbase_qs::Order;
 use trailbase_schema::metadata::{
   ColumnMetadata, ConnectionMetadata, TableMetadata, ViewMetadata, find_file_column_indexes,
   find_user_id_foreign_key_columns,
@@ -10,11 +12,20 @@ use trailbase_schema::{QualifiedName, QualifiedNameEscaped};
 use trailbase_sqlite::{Connection, ConnectionType, NamedParams, SyncConnectionTrait, Value};
 
 use crate::auth::user::User;
-use crate::config::proto::{ConflictResolutionStrategy, RecordApiConfig};
+use crate::config::proto::{
+  ConflictResolutionStrategy, FileColumnPolicy, RecordApiConfig, SyncConflictPolicy,
+};
 use crate::constants::USER_TABLE;
+use crate::rate_limit::RateLimiter;
+use crate::records::cache::{CacheVersion, ListCache, RecordCache, write_counters};
 use crate::records::params::{LazyParams, Params};
+use crate::records::protobuf::RecordDescriptors;
+use crate::records::thumbnail::ThumbnailSize;
 use crate::records::util::named_placeholder;
 use crate::records::{Permission, RecordError};
+use crate::util::uuid_to_b64;
+
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Debug)]
 pub(crate) struct RecordApiSchema {
@@ -75,7 +86,7 @@ impl RecordApiSchema {
     return Ok(Self {
       qualified_name: table_metadata.schema.name.clone(),
       table_name: QualifiedNameEscaped::new(&table_metadata.schema.name),
-      attached_databases: config.attached_databases.clone(),
+      attached_databases: config.databases_to_attach(),
       is_table: true,
       record_pk_column: record_pk_column.clone(),
       column_metadata,
@@ -113,7 +124,7 @@ impl RecordApiSchema {
     return Ok(Self {
       qualified_name: view_metadata.schema.name.clone(),
       table_name: QualifiedNameEscaped::new(&view_metadata.schema.name),
-      attached_databases: config.attached_databases.clone(),
+      attached_databases: config.databases_to_attach(),
       is_table: false,
       record_pk_column: record_pk_column.clone(),
       column_metadata: column_metadata.clone(),
@@ -125,6 +136,37 @@ impl RecordApiSchema {
   }
 }
 
+/// Property of the authenticated user, which can be injected into records on creation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AuthContextField {
+  Id,
+  Email,
+  Username,
+}
+
+impl AuthContextField {
+  pub(crate) fn parse(field: &str) -> Option<Self> {
+    return match field {
+      "_USER_.id" => Some(Self::Id),
+      "_USER_.email" => Some(Self::Email),
+      "_USER_.username" => Some(Self::Username),
+      _ => None,
+    };
+  }
+
+  pub(crate) fn value(&self, user: Option<&User>) -> serde_json::Value {
+    let Some(user) = user else {
+      return serde_json::Value::Null;
+    };
+
+    return match self {
+      Self::Id => serde_json::Value::String(uuid_to_b64(&user.uuid)),
+      Self::Email => user.email.clone().into(),
+      Self::Username => user.username.clone().into(),
+    };
+  }
+}
+
 #[derive(Clone)]
 pub struct RecordApi {
   state: Arc<RecordApiState>,
@@ -133,6 +175,9 @@ pub struct RecordApi {
 struct RecordApiState {
   /// Cached connection for access checks and subscription state construction.
   conn: Arc<trailbase_sqlite::Connection>,
+  /// Connection for read-only queries, i.e. reads and listings. Same as `conn` unless a read
+  /// replica is configured.
+  read_conn: Arc<trailbase_sqlite::Connection>,
   /// Cached connection metadata.
   metadata: Arc<ConnectionMetadata>,
 
@@ -145,11 +190,46 @@ struct RecordApiState {
   insert_conflict_resolution_strategy: Option<ConflictResolutionStrategy>,
   insert_autofill_missing_user_id_columns: bool,
   enable_subscriptions: bool,
+  enable_change_tracking: bool,
+  /// Conflict policy of sync-enabled APIs.
+  sync_conflict_policy: Option<SyncConflictPolicy>,
+  /// Served from the database of the tenant a request is routed to.
+  tenant_scoped: bool,
 
   // Foreign key expansion configuration. Affects schema.
   expand: Option<HashMap<String, serde_json::Value>>,
 
   listing_hard_limit: Option<usize>,
+  /// Page size for listings w/o explicit limit.
+  default_limit: Option<usize>,
+  /// Order for listings w/o explicit order.
+  default_order: Option<Order>,
+  /// Execution time limit for list queries.
+  query_timeout: Duration,
+  /// Maximum number of expansions per request.
+  max_joins: Option<usize>,
+
+  /// Columns only admins may write.
+  admin_only_columns: Vec<String>,
+  /// Columns only admins may update.
+  immutable_columns: Vec<String>,
+  /// Columns filled from the authentication context on creation.
+  fill_on_create: Vec<(String, AuthContextField)>,
+  /// Allowed sizes for image thumbnails.
+  thumbnail_sizes: Vec<ThumbnailSize>,
+  /// Restrictions on uploaded files by column name.
+  file_policies: HashMap<String, FileColumnPolicy>,
+  /// Columns hidden from read responses by audience, i.e. "world", "authenticated" or role.
+  read_excluded_columns: HashMap<String, Vec<String>>,
+
+  /// Optional read-through cache for reads by id.
+  read_cache: Option<RecordCache>,
+  /// Optional cache for listings.
+  list_cache: Option<ListCache>,
+  /// Optional per-API rate limiter.
+  rate_limiter: Option<RateLimiter>,
+  /// Lazily built protobuf message descriptors.
+  protobuf_descriptors: OnceLock<Result<RecordDescriptors, String>>,
 
   // Open question: right now the read_access rule is also used for listing. It might be nice to
   // allow different permissions, however there's a risk of listing records w/o read access.
@@ -168,6 +248,7 @@ struct RecordApiState {
 impl RecordApiState {
   pub(crate) fn from_table(
     conn: Arc<Connection>,
+    read_conn: Arc<Connection>,
     metadata: Arc<ConnectionMetadata>,
     table_metadata: &TableMetadata,
     config: RecordApiConfig,
@@ -176,6 +257,7 @@ impl RecordApiState {
 
     return Self::from_impl(
       conn,
+      read_conn,
       metadata,
       RecordApiSchema::from_table(table_metadata, &config)?,
       config,
@@ -184,6 +266,7 @@ impl RecordApiState {
 
   pub(crate) fn from_view(
     conn: Arc<Connection>,
+    read_conn: Arc<Connection>,
     metadata: Arc<ConnectionMetadata>,
     view_metadata: &ViewMetadata,
     config: RecordApiConfig,
@@ -192,6 +275,7 @@ impl RecordApiState {
 
     return Self::from_impl(
       conn,
+      read_conn,
       metadata,
       RecordApiSchema::from_view(view_metadata, &config)?,
       config,
@@ -200,14 +284,28 @@ impl RecordApiState {
 
   fn from_impl(
     conn: Arc<Connection>,
+    read_conn: Arc<Connection>,
     metadata: Arc<ConnectionMetadata>,
     schema: RecordApiSchema,
-    config: RecordApiConfig,
+    mut config: RecordApiConfig,
   ) -> Result<Self, String> {
     let Some(api_name) = config.name.clone() else {
       return Err(format!("RecordApi misses name: {config:?}"));
     };
 
+    for rule in [
+      &mut config.create_access_rule,
+      &mut config.read_access_rule,
+      &mut config.update_access_rule,
+      &mut config.delete_access_rule,
+      &mut config.schema_access_rule,
+    ]
+    .into_iter()
+    .flatten()
+    {
+      *rule = rewrite_role_checks(conn.connection_type(), rule);
+    }
+
     let (read_access_query, subscription_read_access_query) = match &config.read_access_rule {
       Some(rule) => {
         let read_access_query = build_read_delete_schema_query(
@@ -289,8 +387,25 @@ impl RecordApiState {
       None => None,
     };
 
+    // Cached data derived from multiple tables, i.e. from VIEWs or including expanded foreign
+    // records, is invalidated by writes to any table.
+    //
+    // NOTE: Caching is disabled for read replicas, since their state may lag behind the writes the
+    // cache is versioned by.
+    let has_read_replica = read_conn.id() != conn.id();
+    let cache_version =
+      if (config.read_cache.is_some() || config.list_cache.is_some()) && !has_read_replica {
+        Some(CacheVersion::new(
+          write_counters(&conn).map_err(|err| err.to_string())?,
+          (schema.is_table && config.expand.is_empty()).then(|| schema.qualified_name.clone()),
+        ))
+      } else {
+        None
+      };
+
     return Ok(RecordApiState {
       conn,
+      read_conn,
       metadata,
 
       schema,
@@ -306,6 +421,14 @@ impl RecordApiState {
         .autofill_missing_user_id_columns
         .unwrap_or(false),
       enable_subscriptions: config.enable_subscriptions.unwrap_or(false),
+      enable_change_tracking: config.enable_change_tracking.unwrap_or(false),
+      sync_conflict_policy: config
+        .enable_sync()
+        .then(|| match config.sync_conflict_policy() {
+          SyncConflictPolicy::Undefined => SyncConflictPolicy::ServerWins,
+          policy => policy,
+        }),
+      tenant_scoped: config.tenant_scoped.unwrap_or(false),
 
       expand: if config.expand.is_empty() {
         None
@@ -320,6 +443,47 @@ impl RecordApiState {
       },
 
       listing_hard_limit: config.listing_hard_limit.map(|l| l as usize),
+      default_limit: config.default_limit.map(|l| l as usize),
+      default_order: config
+        .default_order_by
+        .as_ref()
+        .and_then(|order| order.parse().ok()),
+      query_timeout: config
+        .query_timeout_ms
+        .map_or(DEFAULT_QUERY_TIMEOUT, Duration::from_millis),
+      max_joins: config.max_joins.map(|j| j as usize),
+      admin_only_columns: config.admin_only_columns.clone(),
+      immutable_columns: config.immutable_columns.clone(),
+      fill_on_create: config
+        .fill_on_create
+        .iter()
+        .filter_map(|(column, field)| {
+          return Some((column.clone(), AuthContextField::parse(field)?));
+        })
+        .collect(),
+      thumbnail_sizes: config
+        .thumbnail_sizes
+        .iter()
+        .filter_map(|size| ThumbnailSize::parse(size))
+        .collect(),
+      file_policies: config.file_policies.clone(),
+      read_excluded_columns: config
+        .read_excluded_columns
+        .iter()
+        .map(|(audience, list)| (audience.clone(), list.columns.clone()))
+        .collect(),
+      read_cache: config
+        .read_cache
+        .as_ref()
+        .zip(cache_version.clone())
+        .map(|(config, version)| RecordCache::new(config, version)),
+      list_cache: config
+        .list_cache
+        .as_ref()
+        .zip(cache_version)
+        .map(|(config, version)| ListCache::new(config, version)),
+      rate_limiter: config.rate_limit.as_ref().and_then(RateLimiter::new),
+      protobuf_descriptors: OnceLock::new(),
 
       // Access control lists.
       acl: [
@@ -357,15 +521,19 @@ impl RecordApiState {
 impl RecordApi {
   pub(crate) fn build(
     conn: Arc<trailbase_sqlite::Connection>,
+    read_conn: Arc<trailbase_sqlite::Connection>,
     metadata: Arc<trailbase_schema::metadata::ConnectionMetadata>,
     config: RecordApiConfig,
   ) -> Result<Self, String> {
-    let table_name = QualifiedName::parse(config.table_name()).map_err(|err| err.to_string())?;
+    let table_name = config
+      .qualified_table_name()
+      .map_err(|err| err.to_string())?;
 
     if let Some(table_metadata) = metadata.get_table(&table_name) {
       return Ok(Self {
         state: Arc::new(RecordApiState::from_table(
           conn,
+          read_conn,
           metadata.clone(),
           table_metadata,
           config,
@@ -375,6 +543,7 @@ impl RecordApi {
       return Ok(Self {
         state: Arc::new(RecordApiState::from_view(
           conn,
+          read_conn,
           metadata.clone(),
           view_metadata,
           config,
@@ -410,6 +579,11 @@ impl RecordApi {
     return &self.state.conn;
   }
 
+  /// Connection for read-only queries, e.g. reads and listings. Is a read replica, if configured.
+  pub fn read_conn(&self) -> &Arc<trailbase_sqlite::Connection> {
+    return &self.state.read_conn;
+  }
+
   // NOTE: We use this for expansions when we follow FKs (read, list, json schema) as well as
   // constructing per-connection subscription state (though this could probably be untangled).
   pub(crate) fn connection_metadata(&self) -> &Arc<ConnectionMetadata> {
@@ -456,6 +630,83 @@ impl RecordApi {
     return Some(&self.state.schema.column_metadata[self.column_index_by_name(name)?]);
   }
 
+  /// Returns the subset of the API's columns selected by `names` in column order. Returns `None`
+  /// if any name doesn't refer to a visible column, e.g. excluded or hidden columns.
+  pub(crate) fn select_columns(&self, names: &[String]) -> Option<Vec<ColumnMetadata>> {
+    for name in names {
+      if name.starts_with("_") || self.column_index_by_name(name).is_none() {
+        return None;
+      }
+    }
+
+    return Some(
+      self
+        .columns()
+        .iter()
+        .filter(|meta| names.contains(&meta.column.name))
+        .cloned()
+        .collect(),
+    );
+  }
+
+  #[inline]
+  pub(crate) fn has_read_excluded_columns(&self) -> bool {
+    return !self.state.read_excluded_columns.is_empty();
+  }
+
+  /// Returns the columns hidden from `user` in read responses. Audiences are additive, i.e. a
+  /// column is only hidden if all entries matching the user hide it.
+  pub(crate) fn read_excluded_columns(&self, user: Option<&User>) -> Vec<&str> {
+    let by_audience = &self.state.read_excluded_columns;
+    if by_audience.is_empty() {
+      return vec![];
+    }
+
+    let matching: Vec<&Vec<String>> = match user {
+      None => by_audience.get("world").into_iter().collect(),
+      Some(user) => std::iter::once("authenticated")
+        .chain(user.roles.iter().map(String::as_str))
+        .filter_map(|audience| by_audience.get(audience))
+        .collect(),
+    };
+    let Some((first, rest)) = matching.split_first() else {
+      return vec![];
+    };
+
+    return first
+      .iter()
+      .filter(|column| rest.iter().all(|columns| columns.contains(column)))
+      .map(String::as_str)
+      .collect();
+  }
+
+  /// Returns the API's columns readable by `user`, optionally projected onto `names`. Returns
+  /// `None` if any name doesn't refer to a readable column.
+  pub(crate) fn readable_columns(
+    &self,
+    names: Option<&[String]>,
+    user: Option<&User>,
+  ) -> Option<Cow<'_, [ColumnMetadata]>> {
+    let excluded = self.read_excluded_columns(user);
+    return match names {
+      Some(names) => {
+        if names.iter().any(|name| excluded.contains(&name.as_str())) {
+          return None;
+        }
+        Some(Cow::Owned(self.select_columns(names)?))
+      }
+      None if excluded.is_empty() => Some(Cow::Borrowed(self.columns())),
+      None => Some(Cow::Owned(
+        self
+          .columns()
+          .iter()
+          .filter(|meta| !excluded.contains(&meta.column.name.as_str()))
+          .cloned()
+          .collect(),
+      )),
+    };
+  }
+
   pub fn primary_key_to_value(&self, pk: String) -> Result<Value, RecordError> {
     // NOTE: loosly parse - will convert STRING to INT/REAL.
     return trailbase_schema::json::parse_string_to_sqlite_value(
@@ -475,6 +726,76 @@ impl RecordApi {
     return self.state.listing_hard_limit;
   }
 
+  #[inline]
+  pub(crate) fn default_limit(&self) -> Option<usize> {
+    return self.state.default_limit;
+  }
+
+  #[inline]
+  pub(crate) fn default_order(&self) -> Option<&Order> {
+    return self.state.default_order.as_ref();
+  }
+
+  pub(crate) fn query_timeout(&self) -> Duration {
+    return self.state.query_timeout;
+  }
+
+  pub(crate) fn max_joins(&self) -> Option<usize> {
+    return self.state.max_joins;
+  }
+
+  #[inline]
+  pub(crate) fn fill_on_create(&self) -> &[(String, AuthContextField)] {
+    return &self.state.fill_on_create;
+  }
+
+  #[inline]
+  pub(crate) fn thumbnail_sizes(&self) -> &[ThumbnailSize] {
+    return &self.state.thumbnail_sizes;
+  }
+
+  #[inline]
+  pub(crate) fn file_policy(&self, column_name: &str) -> Option<&FileColumnPolicy> {
+    return self.state.file_policies.get(column_name);
+  }
+
+  /// Whether any of the given fields may only be written by admins.
+  pub(crate) fn writes_admin_only_columns<'a>(
+    &self,
+    mut fields: impl Iterator<Item = &'a str>,
+    update: bool,
+  ) -> bool {
+    let state = &self.state;
+    return fields.any(|field| {
+      return state.admin_only_columns.iter().any(|c| c == field)
+        || (update && state.immutable_columns.iter().any(|c| c == field));
+    });
+  }
+
+  #[inline]
+  pub(crate) fn read_cache(&self) -> Option<&RecordCache> {
+    return self.state.read_cache.as_ref();
+  }
+
+  #[inline]
+  pub(crate) fn rate_limiter(&self) -> Option<&RateLimiter> {
+    return self.state.rate_limiter.as_ref();
+  }
+
+  pub(crate) fn protobuf_descriptors(&self) -> Result<&RecordDescriptors, RecordError> {
+    return self
+      .state
+      .protobuf_descriptors
+      .get_or_init(|| RecordDescriptors::build(self))
+      .as_ref()
+      .map_err(|err| RecordError::Internal(err.clone().into()));
+  }
+
+  #[inline]
+  pub(crate) fn list_cache(&self) -> Option<&ListCache> {
+    return self.state.list_cache.as_ref();
+  }
+
   #[inline]
   pub fn insert_autofill_missing_user_id_columns(&self) -> bool {
     return self.state.insert_autofill_missing_user_id_columns;
@@ -485,6 +806,28 @@ impl RecordApi {
     return self.state.enable_subscriptions;
   }
 
+  #[inline]
+  pub fn enable_change_tracking(&self) -> bool {
+    return self.state.enable_change_tracking;
+  }
+
+  /// Conflict policy if sync is enabled for this API.
+  #[inline]
+  pub(crate) fn sync_conflict_policy(&self) -> Option<SyncConflictPolicy> {
+    return self.state.sync_conflict_policy;
+  }
+
+  #[inline]
+  pub fn tenant_scoped(&self) -> bool {
+    return self.state.tenant_scoped;
+  }
+
+  /// Whether both refer to the same instance, i.e. the API hasn't been rebuilt in between.
+  #[inline]
+  pub(crate) fn ptr_eq(&self, other: &RecordApi) -> bool {
+    return Arc::ptr_eq(&self.state, &other.state);
+  }
+
   #[inline]
   pub fn insert_conflict_resolution_strategy(&self) -> Option<ConflictResolutionStrategy> {
     return self.state.insert_conflict_resolution_strategy;
@@ -608,6 +951,13 @@ impl RecordApi {
     p: Permission,
     user: Option<&User>,
   ) -> Result<(), RecordError> {
+    // API keys are further restricted to their scope.
+    if let Some(scope) = user.and_then(|u| u.api_key.as_ref())
+      && !scope.allows(self.api_name(), p)
+    {
+      return Err(RecordError::Forbidden);
+    }
+
     if (user.is_some() && self.has_access(Entity::Authenticated, p))
       || self.has_access(Entity::World, p)
     {
@@ -646,7 +996,7 @@ impl RecordApi {
         let (named_params, column_names, column_indexes) = match request_params
           .ok_or_else(|| RecordError::Internal("missing insert params".into()))?
           .params()
-          .map_err(|_| RecordError::BadRequest("invalid params"))?
+          .map_err(|err| err.to_record_error("invalid params"))?
         {
           Params::Insert {
             named_params,
@@ -694,6 +1044,7 @@ impl RecordApi {
       Cow::Borrowed(":__user_id"),
       user.map_or(Value::Null, |u| Value::Blob(u.uuid.into())),
     ));
+    params.push((Cow::Borrowed(":__user_roles"), user_roles_param(user)));
     params.push((
       Cow::Borrowed(":__record_id"),
       record_id.map_or(Value::Null, |id| id.clone()),
@@ -719,6 +1070,10 @@ impl trailbase_sqlite::Params for SubscriptionAclParams {
       };
     }
 
+    if let Some(idx) = stmt.parameter_index(":__user_roles")? {
+      stmt.bind_parameter(idx, user_roles_param(self.user.as_ref()).into())?;
+    }
+
     if let Some(user) = self.user
       && let Some(idx) = stmt.parameter_index(":__user_id")?
     {
@@ -729,6 +1084,34 @@ impl trailbase_sqlite::Params for SubscriptionAclParams {
   }
 }
 
+/// Rewrites `_USER_.has_role('<role>')` in access rules to a lookup in the `:__user_roles`
+/// parameter, i.e. the JSON array of roles embedded in the user's auth token.
+pub(crate) fn rewrite_role_checks(connection_type: ConnectionType, access_rule: &str) -> String {
+  static HAS_ROLE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"_USER_\.has_role\(\s*('(?:[^']|'')*')\s*\)").expect("covered by tests")
+  });
+
+  return HAS_ROLE
+    .replace_all(access_rule, |captures: &regex::Captures| {
+      let role = &captures[1];
+      return match connection_type {
+        ConnectionType::Pg => format!("jsonb_exists(CAST(:__user_roles AS jsonb), {role})"),
+        ConnectionType::Sqlite => {
+          format!("EXISTS(SELECT 1 FROM json_each(:__user_roles) WHERE value = {role})")
+        }
+      };
+    })
+    .into_owned();
+}
+
+/// Binds the user's roles as JSON array, empty for unauthenticated requests.
+pub(crate) fn user_roles_param(user: Option<&User>) -> Value {
+  return Value::Text(match user {
+    Some(user) => serde_json::to_string(&user.roles).expect("json array"),
+    None => "[]".to_string(),
+  });
+}
+
 #[derive(Template)]
 #[template(
   escape = "none",
@@ -921,17 +1304,7 @@ fn filter_excluded_columns(
 fn assert_name(config: &RecordApiConfig, name: &QualifiedName) {
   // QUESTION: Should this be disabled in prod? This can only trigger during start and config
   // reload.
-  match name.database_schema.as_deref() {
-    Some(db) if db != "main" && db != "public" => {
-      assert_eq!(
-        config.table_name.as_deref().unwrap_or_default(),
-        format!("{db}.{}", name.name)
-      );
-    }
-    _ => {
-      assert_eq!(config.table_name.as_deref().unwrap_or_default(), &name.name);
-    }
-  }
+  assert_eq!(config.qualified_table_name().ok().as_ref(), Some(name));
 }
 
 #[cfg(test)]
@@ -1074,6 +1447,37 @@ mod tests {
     }
   }
 
+  #[test]
+  fn test_rewrite_role_checks() {
+    let rule = rewrite_role_checks(
+      ConnectionType::Sqlite,
+      "_USER_.has_role('editor') OR _USER_.has_role( 'o''brien' ) OR _USER_.id = _ROW_.owner",
+    );
+    assert_eq!(
+      rule,
+      "EXISTS(SELECT 1 FROM json_each(:__user_roles) WHERE value = 'editor') OR \
+       EXISTS(SELECT 1 FROM json_each(:__user_roles) WHERE value = 'o''brien') OR \
+       _USER_.id = _ROW_.owner"
+    );
+
+    sanitize_template(&build_read_delete_schema_query(
+      ConnectionType::Sqlite,
+      &QualifiedName::parse("table").unwrap().into(),
+      "index",
+      &rule,
+    ));
+
+    assert_eq!(
+      rewrite_role_checks(ConnectionType::Pg, "_USER_.has_role('admin')"),
+      "jsonb_exists(CAST(:__user_roles AS jsonb), 'admin')"
+    );
+    // Non-literal arguments are left alone.
+    assert_eq!(
+      rewrite_role_checks(ConnectionType::Sqlite, "_USER_.has_role(_ROW_.role)"),
+      "_USER_.has_role(_ROW_.role)"
+    );
+  }
+
   fn has_access(flags: u8, p: Permission) -> bool {
     return (flags & (p as u8)) > 0;
   }
@@ -0,0 +1,321 @@
+use axum::{
+  Json,
+  extract::{Path, Query, State},
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use trailbase_schema::json::value_to_flat_json;
+use trailbase_sqlite::{ConnectionType, Value};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::config::proto::{PermissionFlag, SavedQueryConfig};
+use crate::records::record_api::{rewrite_role_checks, user_roles_param};
+use crate::records::{Permission, RecordError, referenced_columns};
+
+#[derive(OpenApi)]
+#[openapi(paths(saved_query_handler))]
+pub(crate) struct SavedQueryOpenApi;
+
+/// JSON response containing the rows returned by a saved query.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct SavedQueryResponse {
+  pub records: Vec<serde_json::Value>,
+}
+
+/// Execute a saved query.
+///
+/// URL query parameters are bound to the query's named parameters.
+#[utoipa::path(
+  get,
+  path = "/{name}",
+  tag = "queries",
+  params(
+    ("name" = String, Path, description = "Name of the saved query."),
+  ),
+  responses(
+    (status = 200, description = "Resulting rows.", body = SavedQueryResponse)
+  )
+)]
+pub async fn saved_query_handler(
+  State(state): State<AppState>,
+  Path(name): Path<String>,
+  Query(params): Query<HashMap<String, String>>,
+  user: Option<User>,
+) -> Result<Json<SavedQueryResponse>, RecordError> {
+  let Some(config) = state.access_config(|c| {
+    return c
+      .saved_queries
+      .iter()
+      .find(|q| q.name.as_deref() == Some(name.as_str()))
+      .cloned();
+  }) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  check_access(&state, &config, &params, user.as_ref()).await?;
+
+  let Some(query) = config.query else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  let rows = state
+    .read_conn()
+    .read_query_rows(
+      query,
+      params
+        .into_iter()
+        .map(|(name, value)| (format!(":{name}"), parse_param(value)))
+        .collect::<Vec<_>>(),
+    )
+    .await?;
+
+  let records = rows
+    .iter()
+    .map(|row| {
+      return (0..row.column_count())
+        .map(|i| {
+          let name = row.column_name(i).unwrap_or_default().to_string();
+          let value = match row.get_value(i) {
+            Some(value) => {
+              value_to_flat_json(value).map_err(|err| RecordError::Internal(err.into()))?
+            }
+            None => serde_json::Value::Null,
+          };
+          return Ok((name, value));
+        })
+        .collect::<Result<serde_json::Map<_, _>, RecordError>>()
+        .map(serde_json::Value::Object);
+    })
+    .collect::<Result<Vec<_>, RecordError>>()?;
+
+  return Ok(Json(SavedQueryResponse { records }));
+}
+
+async fn check_access(
+  state: &AppState,
+  config: &SavedQueryConfig,
+  params: &HashMap<String, String>,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  let name = config.name();
+
+  // API keys are further restricted to their scope.
+  if let Some(scope) = user.and_then(|u| u.api_key.as_ref())
+    && !scope.allows(name, Permission::Read)
+  {
+    return Err(RecordError::Forbidden);
+  }
+
+  let allows_read = |acl: &[i32]| acl.contains(&(PermissionFlag::Read as i32));
+  if !allows_read(&config.acl_world) && !(user.is_some() && allows_read(&config.acl_authenticated))
+  {
+    return Err(RecordError::Forbidden);
+  }
+
+  let Some(ref rule) = config.access_rule else {
+    return Ok(());
+  };
+
+  // Only the request parameters referenced by the rule are exposed as _REQ_. Absent ones are NULL.
+  let req_columns = referenced_columns(rule, "_REQ_");
+  let req_table = if req_columns.is_empty() {
+    "".to_string()
+  } else {
+    format!(
+      ", (SELECT {}) AS _REQ_",
+      req_columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!(":__req{i} AS \"{column}\""))
+        .collect::<Vec<_>>()
+        .join(", ")
+    )
+  };
+
+  let query = format!(
+    "SELECT CAST(({rule}) AS INTEGER) FROM (SELECT :__user_id AS id) AS _USER_{req_table}",
+    rule = rewrite_role_checks(ConnectionType::Sqlite, rule),
+  );
+
+  let mut named_params: Vec<(String, Value)> = vec![
+    (
+      ":__user_id".to_string(),
+      user.map_or(Value::Null, |u| Value::Blob(u.uuid.into_bytes().to_vec())),
+    ),
+    (":__user_roles".to_string(), user_roles_param(user)),
+  ];
+  for (i, column) in req_columns.iter().enumerate() {
+    named_params.push((
+      format!(":__req{i}"),
+      params
+        .get(column)
+        .map_or(Value::Null, |v| parse_param(v.clone())),
+    ));
+  }
+
+  // Fail closed, e.g. when the rule cannot be evaluated.
+  let allowed: Option<i64> = state
+    .conn()
+    .read_query_row_get(query, named_params, 0)
+    .await
+    .map_err(|_err| RecordError::Forbidden)?;
+
+  return match allowed {
+    Some(1) => Ok(()),
+    _ => Err(RecordError::Forbidden),
+  };
+}
+
+/// URL query parameters are untyped, bind numbers as such so they behave in comparisons and
+/// LIMIT clauses.
+fn parse_param(value: String) -> Value {
+  if let Ok(i) = value.parse::<i64>() {
+    return Value::Integer(i);
+  }
+  if let Ok(f) = value.parse::<f64>()
+    && f.is_finite()
+  {
+    return Value::Real(f);
+  }
+  return Value::Text(value);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::validate_config;
+
+  async fn add_saved_query(state: &AppState, saved_query: SavedQueryConfig) {
+    let mut config = (*state.get_config()).clone();
+    config.saved_queries.push(saved_query);
+    state
+      .validate_and_update_config(config, None)
+      .await
+      .unwrap();
+  }
+
+  async fn run(
+    state: &AppState,
+    name: &str,
+    params: &[(&str, &str)],
+    user: Option<&User>,
+  ) -> Result<Vec<serde_json::Value>, RecordError> {
+    return saved_query_handler(
+      State(state.clone()),
+      Path(name.to_string()),
+      Query(
+        params
+          .iter()
+          .map(|(k, v)| (k.to_string(), v.to_string()))
+          .collect(),
+      ),
+      user.cloned(),
+    )
+    .await
+    .map(|response| response.0.records);
+  }
+
+  #[tokio::test]
+  async fn test_saved_queries() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE sale (id INTEGER PRIMARY KEY, region TEXT, amount INTEGER) STRICT; \
+          INSERT INTO sale (region, amount) VALUES ('eu', 10), ('eu', 5), ('us', 7); \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    // Validated against the schema.
+    let mut config = (*state.get_config()).clone();
+    config.saved_queries.push(SavedQueryConfig {
+      name: Some("broken".to_string()),
+      query: Some("SELECT missing FROM sale".to_string()),
+      ..Default::default()
+    });
+    assert!(
+      validate_config(&state.connection_manager(), &config)
+        .await
+        .is_err()
+    );
+
+    // Only SELECTs are allowed.
+    config.saved_queries[0].query = Some("DELETE FROM sale".to_string());
+    assert!(
+      validate_config(&state.connection_manager(), &config)
+        .await
+        .is_err()
+    );
+
+    add_saved_query(
+      &state,
+      SavedQueryConfig {
+        name: Some("totals".to_string()),
+        query: Some(
+          "SELECT region, SUM(amount) AS total FROM sale WHERE region = :region GROUP BY region"
+            .to_string(),
+        ),
+        acl_world: vec![PermissionFlag::Read as i32],
+        access_rule: Some("_REQ_.region <> 'us'".to_string()),
+        ..Default::default()
+      },
+    )
+    .await;
+
+    let records = run(&state, "totals", &[("region", "eu")], None)
+      .await
+      .unwrap();
+    assert_eq!(
+      records,
+      vec![serde_json::json!({"region": "eu", "total": 15})]
+    );
+
+    assert!(matches!(
+      run(&state, "totals", &[("region", "us")], None).await,
+      Err(RecordError::Forbidden)
+    ));
+    assert!(matches!(
+      run(&state, "missing", &[], None).await,
+      Err(RecordError::ApiNotFound)
+    ));
+
+    add_saved_query(
+      &state,
+      SavedQueryConfig {
+        name: Some("top".to_string()),
+        query: Some("SELECT id FROM sale ORDER BY amount DESC LIMIT :limit".to_string()),
+        acl_authenticated: vec![PermissionFlag::Read as i32],
+        ..Default::default()
+      },
+    )
+    .await;
+
+    // Requires authentication.
+    assert!(matches!(
+      run(&state, "top", &[("limit", "1")], None).await,
+      Err(RecordError::Forbidden)
+    ));
+
+    let uuid = uuid::Uuid::now_v7();
+    let user = User {
+      id: crate::util::uuid_to_b64(&uuid),
+      email: Some("user@localhost".to_string()),
+      username: None,
+      uuid,
+      csrf_token: "csrf".to_string(),
+      roles: vec![],
+      api_key: None,
+    };
+    let records = run(&state, "top", &[("limit", "1")], Some(&user))
+      .await
+      .unwrap();
+    assert_eq!(records, vec![serde_json::json!({"id": 1})]);
+  }
+}
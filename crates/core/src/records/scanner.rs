@@ -0,0 +1,382 @@
+//! Scanning of uploaded files, e.g. for malware or otherwise disallowed content, before they're
+//! written to the object store.
+//!
+//! Scanners are invoked after the files have been extracted from the request and validated
+//! against the column's file policy, but before they're encrypted and written. Any scanner
+//! rejecting a file fails the entire request.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use futures_util::stream::BoxStream;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use trailbase_schema::{FileUpload, FileUploadData, SpooledFile};
+
+use crate::app_state::AppState;
+use crate::config::proto::UploadScanConfig;
+use crate::metrics::{ObjectStoreOp, record_object_store_op};
+use crate::records::RecordError;
+use crate::records::params::Params;
+
+/// A file's contents streamed to a [UploadScanner].
+pub type ScanStream = BoxStream<'static, Result<Bytes, std::io::Error>>;
+
+#[derive(Debug, Error)]
+pub enum UploadScanError {
+  #[error("File '{filename}' rejected: {reason}")]
+  Rejected { filename: String, reason: String },
+  #[error("Scanner error: {0}")]
+  Scanner(Box<dyn std::error::Error + Send + Sync>),
+  #[error("IO error: {0}")]
+  IO(#[from] std::io::Error),
+}
+
+impl UploadScanError {
+  pub(crate) fn into_record_error(self) -> RecordError {
+    return match self {
+      Self::Rejected { .. } => {
+        log::info!("{self}");
+        RecordError::BadRequest("File rejected")
+      }
+      err => RecordError::Internal(err.into()),
+    };
+  }
+}
+
+/// Outcome of scanning a single file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScanVerdict {
+  Clean,
+  /// The file must not be stored. The reason is logged but not returned to the client.
+  Rejected(String),
+}
+
+/// Scans uploaded files before they're stored, e.g. to reject malware.
+///
+/// Scanners are registered via [crate::ServerOptions::upload_scanners] or
+/// [AppState::register_upload_scanner]. Additionally, a ClamAV scanner can be configured using
+/// `server.upload_scan.clamav_address` and WASM components can provide scanners. All scanners
+/// apply to all file uploads including avatars. Scanner errors fail the upload, i.e. uploads
+/// fail closed.
+#[async_trait]
+pub trait UploadScanner: Send + Sync {
+  async fn scan(
+    &self,
+    file: &FileUpload,
+    contents: ScanStream,
+  ) -> Result<ScanVerdict, UploadScanError>;
+}
+
+impl std::fmt::Debug for dyn UploadScanner {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return f.write_str("UploadScanner");
+  }
+}
+
+pub(crate) fn build_upload_scanner(
+  config: Option<&UploadScanConfig>,
+) -> Option<Arc<dyn UploadScanner>> {
+  let _address = config?.clamav_address.as_ref()?;
+
+  #[cfg(feature = "clamav")]
+  return Some(Arc::new(clamav::ClamAvScanner::new(_address.clone())));
+
+  #[cfg(not(feature = "clamav"))]
+  {
+    log::error!("ClamAV address configured but built w/o 'clamav' feature");
+    return None;
+  }
+}
+
+/// Runs all registered scanners over the files about to be written.
+pub(crate) async fn scan_files(state: &AppState, params: &Params) -> Result<(), UploadScanError> {
+  let scanners = state.upload_scanners();
+  if scanners.is_empty() {
+    return Ok(());
+  }
+
+  for (metadata, contents) in params.files() {
+    let Some(contents) = contents else {
+      continue;
+    };
+
+    for scanner in scanners.iter() {
+      let stream = match contents {
+        FileUploadData::Bytes(bytes) => bytes_stream(Bytes::from(bytes.clone())),
+        FileUploadData::Spooled(spooled) => spooled_stream(spooled).await?,
+      };
+
+      check_verdict(metadata, scanner.scan(metadata, stream).await?)?;
+    }
+  }
+
+  return Ok(());
+}
+
+/// Runs all registered scanners over a file, which has already been written to the object
+/// store, e.g. by a resumable upload.
+pub(crate) async fn scan_stored_file(
+  state: &AppState,
+  file: &FileUpload,
+) -> Result<(), UploadScanError> {
+  use crate::records::file_encryption::{decrypt_stream, unwrap_data_key};
+  use object_store::ObjectStoreExt;
+
+  let scanners = state.upload_scanners();
+  if scanners.is_empty() {
+    return Ok(());
+  }
+
+  let path = object_store::path::Path::from(file.objectstore_id());
+  for scanner in scanners.iter() {
+    record_object_store_op(ObjectStoreOp::Get);
+    let result = state
+      .objectstore()
+      .get(&path)
+      .await
+      .map_err(|err| UploadScanError::Scanner(err.into()))?;
+
+    let stream: ScanStream = match file.encryption() {
+      Some(encryption) => {
+        let data_key = unwrap_data_key(state, encryption)
+          .await
+          .map_err(|err| UploadScanError::Scanner(err.into()))?;
+        decrypt_stream(data_key, result.into_stream())
+          .map(|r| r.map_err(std::io::Error::other))
+          .boxed()
+      }
+      None => result
+        .into_stream()
+        .map(|r| r.map_err(std::io::Error::other))
+        .boxed(),
+    };
+
+    check_verdict(file, scanner.scan(file, stream).await?)?;
+  }
+
+  return Ok(());
+}
+
+fn check_verdict(file: &FileUpload, verdict: ScanVerdict) -> Result<(), UploadScanError> {
+  return match verdict {
+    ScanVerdict::Clean => Ok(()),
+    ScanVerdict::Rejected(reason) => Err(UploadScanError::Rejected {
+      filename: file
+        .original_filename()
+        .unwrap_or(file.filename())
+        .to_string(),
+      reason,
+    }),
+  };
+}
+
+fn bytes_stream(bytes: Bytes) -> ScanStream {
+  return futures_util::stream::once(async move { Ok(bytes) }).boxed();
+}
+
+async fn spooled_stream(spooled: &SpooledFile) -> Result<ScanStream, std::io::Error> {
+  const CHUNK_SIZE: usize = 256 * 1024;
+
+  let file = tokio::fs::File::open(&spooled.path).await?;
+  return Ok(
+    futures_util::stream::try_unfold(file, |mut file| async move {
+      let mut buffer = vec![0; CHUNK_SIZE];
+      let n = file.read(&mut buffer).await?;
+      if n == 0 {
+        return Ok::<_, std::io::Error>(None);
+      }
+      buffer.truncate(n);
+      return Ok(Some((Bytes::from(buffer), file)));
+    })
+    .boxed(),
+  );
+}
+
+#[cfg(feature = "clamav")]
+mod clamav {
+  use super::*;
+  use tokio::io::AsyncWriteExt;
+  use tokio::net::TcpStream;
+
+  /// clamd's default `StreamMaxLength` is 25MB and chunks must not exceed it. Keep them small.
+  const CHUNK_SIZE: usize = 64 * 1024;
+
+  /// Scans files using clamd's INSTREAM command over TCP.
+  ///
+  /// See: https://docs.clamav.net/manual/Usage/Scanning.html#clamd
+  pub(crate) struct ClamAvScanner {
+    address: String,
+  }
+
+  impl ClamAvScanner {
+    pub(crate) fn new(address: String) -> Self {
+      return Self { address };
+    }
+  }
+
+  #[async_trait]
+  impl UploadScanner for ClamAvScanner {
+    async fn scan(
+      &self,
+      _file: &FileUpload,
+      mut contents: ScanStream,
+    ) -> Result<ScanVerdict, UploadScanError> {
+      let mut conn = TcpStream::connect(&self.address).await?;
+
+      // The "z" prefix means null-terminated commands and responses.
+      conn.write_all(b"zINSTREAM\0").await?;
+      while let Some(chunk) = contents.next().await {
+        for part in chunk?.chunks(CHUNK_SIZE) {
+          conn.write_all(&(part.len() as u32).to_be_bytes()).await?;
+          conn.write_all(part).await?;
+        }
+      }
+      // A zero-length chunk terminates the stream.
+      conn.write_all(&0u32.to_be_bytes()).await?;
+      conn.flush().await?;
+
+      let mut response = vec![];
+      conn.read_to_end(&mut response).await?;
+
+      return parse_response(&String::from_utf8_lossy(&response));
+    }
+  }
+
+  fn parse_response(response: &str) -> Result<ScanVerdict, UploadScanError> {
+    // Responses look like: "stream: OK", "stream: Eicar-Signature FOUND" or
+    // "INSTREAM size limit exceeded. ERROR".
+    let response = response.trim_end_matches('\0').trim();
+    let result = response.strip_prefix("stream: ").unwrap_or(response);
+
+    if result == "OK" {
+      return Ok(ScanVerdict::Clean);
+    }
+    if let Some(signature) = result.strip_suffix(" FOUND") {
+      return Ok(ScanVerdict::Rejected(signature.to_string()));
+    }
+    return Err(UploadScanError::Scanner(
+      format!("clamd: {response}").into(),
+    ));
+  }
+
+  #[cfg(test)]
+  mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clamd_response() {
+      assert_eq!(parse_response("stream: OK\0").unwrap(), ScanVerdict::Clean);
+      assert_eq!(
+        parse_response("stream: Eicar-Signature FOUND\0").unwrap(),
+        ScanVerdict::Rejected("Eicar-Signature".to_string())
+      );
+      assert!(parse_response("INSTREAM size limit exceeded. ERROR\0").is_err());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::{Path, Query, State};
+  use serde_json::json;
+  use trailbase_schema::FileUploadInput;
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::extract::Either;
+  use crate::records::create_record::{CreateRecordQuery, create_record_handler};
+  use crate::records::test_utils::*;
+
+  struct DenyListScanner;
+
+  #[async_trait]
+  impl UploadScanner for DenyListScanner {
+    async fn scan(
+      &self,
+      _file: &FileUpload,
+      mut contents: ScanStream,
+    ) -> Result<ScanVerdict, UploadScanError> {
+      let mut data = vec![];
+      while let Some(chunk) = contents.next().await {
+        data.extend_from_slice(&chunk?);
+      }
+
+      if data.windows(7).any(|w| w == b"malware") {
+        return Ok(ScanVerdict::Rejected("malware".to_string()));
+      }
+      return Ok(ScanVerdict::Clean);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_upload_scanner() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE scanned (
+            id      INTEGER PRIMARY KEY,
+            file    {json} CHECK(jsonschema('std.FileUpload', file))
+          ) {strict};
+        "#,
+        strict = strict(conn),
+        json = json_column(conn),
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("scanned".to_string()),
+        acl_world: [PermissionFlag::Create as i32, PermissionFlag::Read as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    state.register_upload_scanner(Arc::new(DenyListScanner));
+
+    let create = async |id: i64, contents: &[u8]| {
+      return create_record_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(json!({
+          "id": id,
+          "file": FileUploadInput {
+            name: None,
+            filename: Some("file.txt".to_string()),
+            content_type: Some("text/plain".to_string()),
+            data: FileUploadData::Bytes(contents.to_vec()),
+          },
+        })),
+      )
+      .await;
+    };
+
+    assert!(create(1, b"harmless").await.is_ok());
+    assert!(matches!(
+      create(2, b"some malware inside").await,
+      Err(RecordError::BadRequest(_))
+    ));
+
+    let count: i64 = conn
+      .read_query_row_get("SELECT COUNT(*) FROM scanned", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(1, count);
+  }
+}
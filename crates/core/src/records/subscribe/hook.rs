@@ -1,5 +1,8 @@
 use log::*;
+use parking_lot::Mutex;
 use rusqlite::hooks::{Action, PreUpdateCase};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
 use trailbase_schema::QualifiedName;
 use trailbase_sqlite::{
   Connection, Value,
@@ -24,35 +27,108 @@ pub struct PreupdateHookEvent {
   pub record: Vec<Value>,
 }
 
-pub fn uninstall_hook(conn: &Connection) -> Result<(), RecordError> {
-  let lock = conn
-    .write_lock()
-    .map_err(|err| RecordError::Internal(err.into()))?;
+/// Consumers of preupdate hook events. SQLite only supports a single preupdate hook per
+/// connection, which is thus shared.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum HookListener {
+  Subscriptions,
+  Cdc,
+  Sync,
+  Cache,
+}
 
-  return lock.preupdate_hook(NO_HOOK).map_err(|err| {
-    // If we were able to install a hook, we should also be able to uninstall it.
-    log::error!("Failed to uninstall SQLite preupdate hook: {err}");
-    RecordError::Internal(err.into())
-  });
+/// Called for every change. Returning false unregisters the listener.
+pub type ListenerFn = Box<dyn FnMut(&PreupdateHookEvent) -> bool + Send>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransactionOutcome {
+  Commit,
+  Rollback,
 }
 
-pub fn install_hook(
+/// Called whenever a transaction commits or rolls back. Returning false unregisters the listener.
+///
+/// NOTE: Commit listeners are invoked before the commit becomes visible to other connections.
+pub type TransactionListenerFn = Box<dyn FnMut(TransactionOutcome) -> bool + Send>;
+
+#[derive(Default)]
+struct Listeners {
+  preupdate: HashMap<HookListener, ListenerFn>,
+  transaction: HashMap<HookListener, TransactionListenerFn>,
+}
+
+impl Listeners {
+  fn is_empty(&self) -> bool {
+    return self.preupdate.is_empty() && self.transaction.is_empty();
+  }
+
+  fn notify_transaction(&mut self, outcome: TransactionOutcome) {
+    self.transaction.retain(|_kind, listener| listener(outcome));
+  }
+}
+
+type SharedListeners = Arc<Mutex<Listeners>>;
+
+/// Listeners by connection id.
+static LISTENERS: LazyLock<Mutex<HashMap<usize, SharedListeners>>> =
+  LazyLock::new(Default::default);
+
+/// Registers a listener, installing the actual hook on first use. Replaces any existing listener
+/// of the same kind.
+pub fn add_listener(
   conn: &Connection,
-) -> Result<flume::Receiver<(usize, PreupdateHookEvent)>, RecordError> {
-  let (sender, receiver) = flume::bounded(CAPACITY);
+  kind: HookListener,
+  listener: ListenerFn,
+) -> Result<(), RecordError> {
+  let mut registry = LISTENERS.lock();
+  get_or_install_hooks(&mut registry, conn)?
+    .lock()
+    .preupdate
+    .insert(kind, listener);
+  return Ok(());
+}
+
+/// Registers a listener for transaction commits and rollbacks, installing the actual hooks on first
+/// use. Replaces any existing transaction listener of the same kind.
+pub fn add_transaction_listener(
+  conn: &Connection,
+  kind: HookListener,
+  listener: TransactionListenerFn,
+) -> Result<(), RecordError> {
+  let mut registry = LISTENERS.lock();
+  get_or_install_hooks(&mut registry, conn)?
+    .lock()
+    .transaction
+    .insert(kind, listener);
+  return Ok(());
+}
+
+fn get_or_install_hooks(
+  registry: &mut HashMap<usize, SharedListeners>,
+  conn: &Connection,
+) -> Result<SharedListeners, RecordError> {
+  if let Some(listeners) = registry.get(&conn.id()) {
+    return Ok(listeners.clone());
+  }
+
+  let listeners: SharedListeners = Default::default();
   let lock = conn
     .write_lock()
     .map_err(|err| RecordError::Internal(err.into()))?;
 
   lock
     .preupdate_hook({
-      let conn = conn.clone();
-      let mut cnt = 0;
+      let listeners = listeners.clone();
 
       Some(
         move |action: Action, db: &str, table_name: &str, case: &PreUpdateCase| {
+          let mut listeners = listeners.lock();
+          if listeners.preupdate.is_empty() {
+            return;
+          }
+
           // NOTE: We should do here as little work as possible. Specifially we don't do any
-          // filtering here. This should be done by the receiver.
+          // filtering here. This should be done by the listeners' receivers.
           let action = match action {
             Action::SQLITE_UPDATE => RecordAction::Update,
             Action::SQLITE_INSERT => RecordAction::Insert,
@@ -87,30 +163,113 @@ pub fn install_hook(
             record,
           };
 
-          cnt += 1;
-
-          match sender.try_send((cnt, event)) {
-            Ok(()) => {}
-            Err(flume::TrySendError::Full(_)) => {
-              warn!("Channel full. Failed to forward preupdate event.");
-            }
-            Err(flume::TrySendError::Disconnected(_)) => {
-              // QUESTION: Should it self-uninstall? This may be racy if a new hook
-              // is being installed while one is already installed. In principle this
-              // should not happen.
-              let _ = uninstall_hook(&conn);
-            }
-          };
+          // NOTE: Listeners, which are gone, are removed but the hook stays installed until the
+          // next `remove_listener` since we cannot re-acquire the write lock from within the hook.
+          listeners
+            .preupdate
+            .retain(|_kind, listener| listener(&event));
         },
       )
     })
     .map_err(|err| RecordError::Internal(err.into()))?;
 
+  lock
+    .commit_hook(Some({
+      let listeners = listeners.clone();
+      move || -> bool {
+        listeners
+          .lock()
+          .notify_transaction(TransactionOutcome::Commit);
+        // Don't veto the commit.
+        return false;
+      }
+    }))
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  lock
+    .rollback_hook(Some({
+      let listeners = listeners.clone();
+      move || {
+        listeners
+          .lock()
+          .notify_transaction(TransactionOutcome::Rollback);
+      }
+    }))
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  registry.insert(conn.id(), listeners.clone());
+
+  return Ok(listeners);
+}
+
+/// Unregisters a listener, uninstalling the actual hooks once no listeners are left.
+pub fn remove_listener(conn: &Connection, kind: HookListener) -> Result<(), RecordError> {
+  let mut registry = LISTENERS.lock();
+  let Some(listeners) = registry.get(&conn.id()) else {
+    return Ok(());
+  };
+
+  {
+    let mut listeners = listeners.lock();
+    listeners.preupdate.remove(&kind);
+    listeners.transaction.remove(&kind);
+    if !listeners.is_empty() {
+      return Ok(());
+    }
+  }
+  registry.remove(&conn.id());
+
+  let lock = conn
+    .write_lock()
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  // If we were able to install the hooks, we should also be able to uninstall them.
+  let uninstall = || -> rusqlite::Result<()> {
+    lock.preupdate_hook(NO_HOOK)?;
+    lock.commit_hook(NO_COMMIT_HOOK)?;
+    lock.rollback_hook(NO_ROLLBACK_HOOK)?;
+    return Ok(());
+  };
+  return uninstall().map_err(|err| {
+    log::error!("Failed to uninstall SQLite hooks: {err}");
+    RecordError::Internal(err.into())
+  });
+}
+
+pub fn uninstall_hook(conn: &Connection) -> Result<(), RecordError> {
+  return remove_listener(conn, HookListener::Subscriptions);
+}
+
+pub fn install_hook(
+  conn: &Connection,
+) -> Result<flume::Receiver<(usize, PreupdateHookEvent)>, RecordError> {
+  let (sender, receiver) = flume::bounded(CAPACITY);
+  let mut cnt = 0;
+
+  add_listener(
+    conn,
+    HookListener::Subscriptions,
+    Box::new(move |event: &PreupdateHookEvent| {
+      cnt += 1;
+
+      return match sender.try_send((cnt, event.clone())) {
+        Ok(()) => true,
+        Err(flume::TrySendError::Full(_)) => {
+          warn!("Channel full. Failed to forward preupdate event.");
+          true
+        }
+        Err(flume::TrySendError::Disconnected(_)) => false,
+      };
+    }),
+  )?;
+
   return Ok(receiver);
 }
 
 const CAPACITY: usize = 16 * 1024;
 const NO_HOOK: Option<fn(Action, &str, &str, &PreUpdateCase)> = None;
+const NO_COMMIT_HOOK: Option<fn() -> bool> = None;
+const NO_ROLLBACK_HOOK: Option<fn()> = None;
 
 #[cfg(test)]
 mod tests {
@@ -384,17 +384,21 @@ impl Drop for PerConnectionState {
 fn broker_subscriptions(
   subs: &[Arc<Subscription>],
   record: &Arc<indexmap::IndexMap<String, trailbase_sqlite::Value>>,
-  event: &Arc<EventPayload>,
+  payload: &mut impl FnMut(&Subscription) -> Option<Arc<EventPayload>>,
 ) -> Vec<usize> {
   return subs
     .iter()
     .enumerate()
     .filter_map(|(idx, sub)| {
+      let Some(event) = payload(sub) else {
+        return None;
+      };
+
       // Cloning the event. It's important that we use a try_send here to not block other
       // subscriptions if a subscriber is slow and their channel fills up.
       if let Err(err) = sub.sender.try_send(EventCandidate {
         record: Some(record.clone()),
-        payload: event.clone(),
+        payload: event,
         seq: sub.candidate_seq.fetch_add(1, Ordering::SeqCst),
       }) {
         match err {
@@ -425,7 +429,9 @@ fn broker(
     record,
   } = event;
 
-  let mut state = state.state.lock();
+  let mut guard = state.state.lock();
+  // Reborrow to allow for disjoint field borrows below.
+  let state = &mut *guard;
 
   // If table_metadata is missing, the config/schema must have changed, thus removing the
   // subscriptions.
@@ -458,10 +464,27 @@ fn broker(
       .collect(),
   );
 
-  // Build a JSON-encoded SQLite event (insert, update, delete).
-  let event: Arc<EventPayload> = {
+  // Build JSON-encoded SQLite events (insert, update, delete) containing only the columns
+  // readable by the respective subscriber. Events are shared between subscribers seeing the same
+  // columns.
+  let record_apis = &state.record_apis;
+  let mut events: Vec<(Vec<String>, Arc<EventPayload>)> = vec![];
+  let mut payload = |sub: &Subscription| -> Option<Arc<EventPayload>> {
+    // The API may have been removed by a config change. Validation will drop the event anyway.
+    let api = record_apis.get(&sub.record_api_name)?;
+    let readable: Vec<String> = api
+      .readable_columns(None, sub.user.as_ref())?
+      .iter()
+      .map(|meta| meta.column.name.clone())
+      .collect();
+
+    if let Some((_, event)) = events.iter().find(|(columns, _)| *columns == readable) {
+      return Some(event.clone());
+    }
+
     let json_obj = record
       .iter()
+      .filter(|(name, _)| readable.contains(*name))
       .filter_map(|(name, value)| {
         return value_to_flat_json(value)
           .ok()
@@ -469,16 +492,18 @@ fn broker(
       })
       .collect();
 
-    Arc::new(EventPayload::from(&match action {
+    let event = Arc::new(EventPayload::from(&match action {
       RecordAction::Delete => JsonEventPayload::Delete { value: json_obj },
       RecordAction::Insert => JsonEventPayload::Insert { value: json_obj },
       RecordAction::Update => JsonEventPayload::Update { value: json_obj },
-    }))
+    }));
+    events.push((readable, event.clone()));
+    return Some(event);
   };
 
   // First broker record subscriptions.
   if let Some(record_subscriptions) = subscriptions.record.get_mut(&row_id) {
-    let dead = broker_subscriptions(record_subscriptions, &record, &event);
+    let dead = broker_subscriptions(record_subscriptions, &record, &mut payload);
 
     for idx in dead.iter().rev() {
       record_subscriptions.remove(*idx);
@@ -486,7 +511,7 @@ fn broker(
   }
 
   // Then broker table subscriptions.
-  let dead = broker_subscriptions(&subscriptions.table, &record, &event);
+  let dead = broker_subscriptions(&subscriptions.table, &record, &mut payload);
   for idx in dead.iter().rev() {
     subscriptions.table.remove(*idx);
   }
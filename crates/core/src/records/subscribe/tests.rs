@@ -712,3 +712,98 @@ async fn subscription_filter_test() {
 
   assert_eq!(0, manager.num_table_subscriptions());
 }
+
+#[tokio::test]
+async fn subscription_read_excluded_columns_test() {
+  let state = test_state(None).await.unwrap();
+  let conn = state.conn().clone();
+
+  conn
+    .execute(
+      "CREATE TABLE test (id INTEGER PRIMARY KEY, text TEXT, secret TEXT) STRICT",
+      (),
+    )
+    .await
+    .unwrap();
+
+  state.rebuild_connection_metadata().await.unwrap();
+
+  add_record_api_config(
+    &state,
+    RecordApiConfig {
+      name: Some("api_name".to_string()),
+      table_name: Some("test".to_string()),
+      enable_subscriptions: Some(true),
+      acl_world: [PermissionFlag::Read as i32].into(),
+      acl_authenticated: [PermissionFlag::Read as i32].into(),
+      read_excluded_columns: [("world", vec!["secret"]), ("support", vec![])]
+        .into_iter()
+        .map(|(audience, columns)| {
+          return (
+            audience.to_string(),
+            crate::config::proto::ColumnList {
+              columns: columns.into_iter().map(str::to_string).collect(),
+            },
+          );
+        })
+        .collect(),
+      ..Default::default()
+    },
+  )
+  .await
+  .unwrap();
+
+  let uuid = uuid::Uuid::now_v7();
+  let support = User {
+    id: uuid_to_b64(&uuid),
+    email: Some("support@localhost".to_string()),
+    username: None,
+    uuid,
+    csrf_token: "csrf".to_string(),
+    roles: vec!["support".to_string()],
+    api_key: None,
+  };
+
+  let api = state.lookup_record_api("api_name").unwrap();
+  let mut world_stream = subscribe_to_records(state.clone(), api.clone(), "*", None, None).await;
+  let mut support_stream = subscribe_to_records(state.clone(), api, "*", Some(support), None).await;
+
+  for stream in [&mut world_stream, &mut support_stream] {
+    assert!(matches!(
+      stream.next().await.unwrap().event,
+      TestJsonEventPayload::Ping
+    ));
+  }
+
+  conn
+    .execute(
+      "INSERT INTO test (id, text, secret) VALUES (1, 'foo', 'hidden')",
+      (),
+    )
+    .await
+    .unwrap();
+
+  match world_stream.next().await.unwrap().event {
+    TestJsonEventPayload::Insert(obj) => {
+      assert_eq!(
+        Value::Object(obj),
+        serde_json::json!({"id": 1, "text": "foo"})
+      );
+    }
+    x => {
+      panic!("Expected insert, got: {x:?}");
+    }
+  };
+
+  match support_stream.next().await.unwrap().event {
+    TestJsonEventPayload::Insert(obj) => {
+      assert_eq!(
+        Value::Object(obj),
+        serde_json::json!({"id": 1, "text": "foo", "secret": "hidden"})
+      );
+    }
+    x => {
+      panic!("Expected insert, got: {x:?}");
+    }
+  };
+}
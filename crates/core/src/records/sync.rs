@@ -0,0 +1,670 @@
+//! Offline sync for record APIs with `enable_sync`.
+//!
+//! Changes to synced tables are captured via SQLite's preupdate hook and compacted into
+//! `_sync_changes`, i.e. one entry per record holding a monotonically increasing sequence number
+//! and whether the record was upserted or deleted. Clients pull changes with a sequence number
+//! greater than their cursor and push mutations of records with client-generated UUIDv7 ids.
+//! Mutations of records that changed since the client's cursor are resolved according to the
+//! API's `SyncConflictPolicy`.
+
+use axum::{
+  Json,
+  extract::{Path, Query, State},
+};
+use base64::prelude::*;
+use const_format::formatcp;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::slice::from_mut;
+use trailbase_sqlite::params;
+use trailbase_sqlite::traits::SyncConnection;
+use ts_rs::TS;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::config::proto::SyncConflictPolicy;
+use crate::constants::SYNC_CHANGES_TABLE;
+use crate::records::expand::row_to_json_expand;
+use crate::records::hooks::run_after_read_hooks;
+use crate::records::read_queries::run_select_query;
+use crate::records::record_api::RecordApi;
+use crate::records::subscribe::hook::{
+  HookListener, PreupdateHookEvent, RecordAction, add_listener,
+};
+use crate::records::transaction::{ModifiedRecord, Operation, apply_ops, notify_modified};
+use crate::records::{Permission, RecordError};
+
+#[derive(OpenApi)]
+#[openapi(paths(sync_pull_handler, sync_push_handler))]
+pub(crate) struct SyncOpenApi;
+
+/// Starts tracking changes of tables exposed by sync-enabled record APIs, if any.
+///
+/// NOTE: Config changes only take effect after a restart.
+pub(crate) fn start(state: &AppState) -> Result<(), RecordError> {
+  let tables: HashSet<String> = state
+    .get_config()
+    .record_apis
+    .iter()
+    .filter(|config| config.enable_sync())
+    .filter_map(|config| config.qualified_table_name().ok())
+    .map(|name| name.name)
+    .collect();
+  if tables.is_empty() {
+    return Ok(());
+  }
+
+  let (sender, receiver) = flume::unbounded::<PreupdateHookEvent>();
+  add_listener(
+    state.conn(),
+    HookListener::Sync,
+    Box::new(move |event: &PreupdateHookEvent| {
+      if event.table_name.database_schema.is_none() && tables.contains(&event.table_name.name) {
+        return sender.send(event.clone()).is_ok();
+      }
+      return !sender.is_disconnected();
+    }),
+  )?;
+
+  tokio::spawn(write_changes(state.clone(), receiver));
+
+  return Ok(());
+}
+
+/// Records the latest change per record. Replacing existing entries bumps their sequence number.
+///
+/// NOTE: Changes are recorded as they happen, i.e. also for transactions that are later rolled
+/// back. This is benign, since pulls always return the records' current state.
+async fn write_changes(state: AppState, receiver: flume::Receiver<PreupdateHookEvent>) {
+  const QUERY: &str = formatcp!(
+    "REPLACE INTO '{SYNC_CHANGES_TABLE}' (table_name, record_id, op) VALUES ($1, $2, $3)"
+  );
+
+  while let Ok(event) = receiver.recv_async().await {
+    let metadata = state.connection_manager().main_entry().metadata;
+    let Some(record_id) = metadata
+      .get_table(&event.table_name)
+      .and_then(|table| table.record_pk_column)
+      .and_then(|index| event.record.get(index).cloned())
+    else {
+      warn!("Sync: missing record id for {:?}", event.table_name);
+      continue;
+    };
+
+    // For deletes the record holds the old values.
+    let op = match event.action {
+      RecordAction::Insert | RecordAction::Update => "upsert",
+      RecordAction::Delete => "delete",
+    };
+
+    if let Err(err) = state
+      .conn()
+      .execute(
+        QUERY,
+        params!(event.table_name.name, record_id, op.to_string()),
+      )
+      .await
+    {
+      error!("Failed to record sync change: {err}");
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum SyncOp {
+  Upsert,
+  Delete,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct SyncPullQuery {
+  /// Cursor returned by the previous pull. Pulls all records if absent.
+  pub cursor: Option<i64>,
+  pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SyncChange {
+  pub op: SyncOp,
+  /// Url-safe base64 encoded id of the record.
+  pub id: String,
+  /// Current state of upserted records.
+  pub record: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SyncPullResponse {
+  /// Changes in the order they happened. Records the user is not allowed to read (any longer) are
+  /// reported as deleted.
+  pub changes: Vec<SyncChange>,
+  /// Cursor to pass to the next pull and push.
+  pub cursor: i64,
+  /// Whether there are further changes to pull.
+  pub has_more: bool,
+}
+
+/// Pull changes since the given cursor.
+#[utoipa::path(
+  get,
+  path = "/{name}/pull",
+  tag = "sync",
+  params(
+    ("name" = String, Path, description = "Name of the sync-enabled record API."),
+    SyncPullQuery,
+  ),
+  responses(
+    (status = 200, description = "Changes since the cursor.", body = SyncPullResponse),
+  )
+)]
+pub async fn sync_pull_handler(
+  State(state): State<AppState>,
+  Path(api_name): Path<String>,
+  Query(query): Query<SyncPullQuery>,
+  user: Option<User>,
+) -> Result<Json<SyncPullResponse>, RecordError> {
+  const QUERY: &str = formatcp!(
+    "\
+      SELECT seq, record_id, op FROM '{SYNC_CHANGES_TABLE}' \
+      WHERE table_name = $1 AND seq > $2 \
+      ORDER BY seq LIMIT $3 \
+    "
+  );
+
+  let (api, _policy) = get_sync_api(&state, &api_name)?;
+  api.check_table_level_access(Permission::Read, user.as_ref())?;
+
+  let cursor = query.cursor.unwrap_or(0);
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  let rows = state
+    .conn()
+    .read_query_rows(
+      QUERY,
+      params!(api.qualified_name().name.clone(), cursor, limit as i64),
+    )
+    .await?;
+
+  let mut changes: Vec<SyncChange> = Vec::with_capacity(rows.len());
+  let mut next_cursor = cursor;
+  for row in rows.iter() {
+    next_cursor = row.get(0)?;
+    let record_id: Vec<u8> = row.get(1)?;
+    let op: String = row.get(2)?;
+
+    let id = BASE64_URL_SAFE.encode(&record_id);
+    let record = match op.as_str() {
+      "upsert" => {
+        read_record(
+          &state,
+          &api,
+          user.as_ref(),
+          trailbase_sqlite::Value::Blob(record_id),
+        )
+        .await?
+      }
+      _ => None,
+    };
+
+    changes.push(SyncChange {
+      op: if record.is_some() {
+        SyncOp::Upsert
+      } else {
+        SyncOp::Delete
+      },
+      id,
+      record,
+    });
+  }
+
+  return Ok(Json(SyncPullResponse {
+    has_more: rows.len() == limit,
+    changes,
+    cursor: next_cursor,
+  }));
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SyncMutation {
+  pub op: SyncOp,
+  /// Url-safe base64 encoded, client-generated UUIDv7 id of the record.
+  pub id: String,
+  /// Record contents for upserts. Partial records are merged into existing ones.
+  pub record: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SyncPushRequest {
+  /// Cursor of the client's last pull. Mutations of records changed since are conflicts.
+  pub cursor: Option<i64>,
+  /// Mutations in the order they were made offline.
+  pub mutations: Vec<SyncMutation>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum SyncMutationStatus {
+  Applied,
+  Conflict,
+  Rejected,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SyncMutationResult {
+  pub id: String,
+  pub status: SyncMutationStatus,
+  /// Server's version of the record in case of a conflict, absent if it was deleted.
+  pub record: Option<serde_json::Value>,
+  /// Reason a mutation was rejected, e.g. insufficient permissions.
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, TS)]
+#[ts(export)]
+pub struct SyncPushResponse {
+  /// A result per mutation in request order.
+  pub results: Vec<SyncMutationResult>,
+}
+
+/// Push offline mutations.
+///
+/// Mutations are applied one by one, i.e. a rejected mutation doesn't affect others. Clients
+/// should pull afterwards to advance their cursor.
+#[utoipa::path(
+  post,
+  path = "/{name}/push",
+  tag = "sync",
+  params(
+    ("name" = String, Path, description = "Name of the sync-enabled record API."),
+  ),
+  request_body = SyncPushRequest,
+  responses(
+    (status = 200, description = "Per-mutation results.", body = SyncPushResponse),
+  )
+)]
+pub async fn sync_push_handler(
+  State(state): State<AppState>,
+  Path(api_name): Path<String>,
+  user: Option<User>,
+  Json(request): Json<SyncPushRequest>,
+) -> Result<Json<SyncPushResponse>, RecordError> {
+  let (api, policy) = get_sync_api(&state, &api_name)?;
+  if request.mutations.len() > MAX_MUTATIONS {
+    return Err(RecordError::BadRequest("Too many mutations"));
+  }
+
+  let cursor = request.cursor.unwrap_or(0);
+  let mut results: Vec<SyncMutationResult> = Vec::with_capacity(request.mutations.len());
+  for mutation in request.mutations {
+    let id = mutation.id.clone();
+
+    let result = match apply_mutation(&state, &api, user.as_ref(), policy, cursor, mutation).await {
+      Ok(Some(modified)) => {
+        notify_modified(&state, modified).await;
+        SyncMutationResult {
+          id,
+          status: SyncMutationStatus::Applied,
+          record: None,
+          error: None,
+        }
+      }
+      Ok(None) => {
+        let record = read_record(
+          &state,
+          &api,
+          user.as_ref(),
+          api.primary_key_to_value(id.clone())?,
+        )
+        .await?;
+        SyncMutationResult {
+          id,
+          status: SyncMutationStatus::Conflict,
+          record,
+          error: None,
+        }
+      }
+      Err(err) => SyncMutationResult {
+        id,
+        status: SyncMutationStatus::Rejected,
+        record: None,
+        error: Some(match err {
+          RecordError::Internal(err) => {
+            warn!("Sync mutation failed: {err}");
+            "Internal".to_string()
+          }
+          err => err.to_string(),
+        }),
+      },
+    };
+    results.push(result);
+  }
+
+  return Ok(Json(SyncPushResponse { results }));
+}
+
+/// Applies a single mutation. Returns None on conflict.
+async fn apply_mutation(
+  state: &AppState,
+  api: &RecordApi,
+  user: Option<&User>,
+  policy: SyncConflictPolicy,
+  cursor: i64,
+  mutation: SyncMutation,
+) -> Result<Option<Vec<ModifiedRecord>>, RecordError> {
+  const CONFLICT_QUERY: &str = formatcp!(
+    "SELECT EXISTS(SELECT 1 FROM '{SYNC_CHANGES_TABLE}' WHERE table_name = $1 AND record_id = $2 AND seq > $3)"
+  );
+
+  let record_id = api.primary_key_to_value(mutation.id.clone())?;
+  let pk_name = api.record_pk_column().column.name.clone();
+  let record = match (mutation.op, mutation.record) {
+    (SyncOp::Upsert, Some(serde_json::Value::Object(mut record))) => {
+      // The mutation's id is authoritative.
+      record.remove(&pk_name);
+      Some(record)
+    }
+    (SyncOp::Upsert, _) => {
+      return Err(RecordError::BadRequest("Missing record"));
+    }
+    (SyncOp::Delete, _) => None,
+  };
+
+  let exists_query = format!(
+    "SELECT EXISTS(SELECT 1 FROM {table} WHERE \"{pk_name}\" = $1)",
+    table = api.table_name()
+  );
+
+  return api
+    .conn()
+    .call_writer({
+      let state = state.clone();
+      let api = api.clone();
+      let user = user.cloned();
+      move |mut conn| -> Result<Result<Option<Vec<ModifiedRecord>>, RecordError>, trailbase_sqlite::Error> {
+        if policy == SyncConflictPolicy::ServerWins {
+          let changed = conn
+            .query_row(
+              CONFLICT_QUERY,
+              params!(api.qualified_name().name.clone(), record_id.clone(), cursor),
+            )?
+            .and_then(|row| row.get::<bool>(0).ok())
+            .unwrap_or(false);
+          if changed {
+            return Ok(Ok(None));
+          }
+        }
+
+        let exists = conn
+          .query_row(exists_query, params!(record_id))?
+          .and_then(|row| row.get::<bool>(0).ok())
+          .unwrap_or(false);
+
+        let api_name = api.api_name().to_string();
+        let op = match (record, exists) {
+          (Some(mut record), false) => {
+            record.insert(pk_name, serde_json::Value::String(mutation.id));
+            Operation::Create {
+              api_name,
+              value: serde_json::Value::Object(record),
+            }
+          }
+          (Some(record), true) => Operation::Update {
+            api_name,
+            record_id: mutation.id,
+            value: serde_json::Value::Object(record),
+          },
+          (None, true) => Operation::Delete {
+            api_name,
+            record_id: mutation.id,
+          },
+          // Already deleted.
+          (None, false) => return Ok(Ok(Some(vec![]))),
+        };
+
+        return Ok(
+          apply_ops(&state, &mut conn, user.as_ref(), &api, vec![op])
+            .map(|(_ids, modified)| Some(modified)),
+        );
+      }
+    })
+    .await?;
+}
+
+/// Reads the current state of a record. Returns None if it's gone or not readable by the user.
+async fn read_record(
+  state: &AppState,
+  api: &RecordApi,
+  user: Option<&User>,
+  record_id: trailbase_sqlite::Value,
+) -> Result<Option<serde_json::Value>, RecordError> {
+  match api
+    .check_record_level_access(Permission::Read, Some(&record_id), None, user)
+    .await
+  {
+    Ok(()) => {}
+    Err(RecordError::Forbidden) => return Ok(None),
+    Err(err) => return Err(err),
+  };
+
+  let Some(columns) = api.readable_columns(None, user) else {
+    return Ok(None);
+  };
+  let column_names: Vec<&str> = columns
+    .iter()
+    .map(|meta| meta.column.name.as_str())
+    .collect();
+  let Some(row) = run_select_query(
+    api.read_conn(),
+    api.table_name(),
+    &column_names,
+    &api.record_pk_column().column.name,
+    record_id,
+  )
+  .await?
+  else {
+    return Ok(None);
+  };
+
+  let mut record = row_to_json_expand(&columns, &row, prefix_filter, None)
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  run_after_read_hooks(state, api.api_name(), from_mut(&mut record), user);
+
+  return Ok(Some(record));
+}
+
+fn get_sync_api(
+  state: &AppState,
+  api_name: &str,
+) -> Result<(RecordApi, SyncConflictPolicy), RecordError> {
+  let api = state
+    .lookup_record_api(api_name)
+    .ok_or(RecordError::ApiNotFound)?;
+  let Some(policy) = api.sync_conflict_policy() else {
+    return Err(RecordError::ApiNotFound);
+  };
+  return Ok((api, policy));
+}
+
+#[inline]
+fn prefix_filter(col_name: &str) -> bool {
+  return !col_name.starts_with("_");
+}
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 256;
+const MAX_MUTATIONS: usize = 128;
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::test_utils::add_record_api_config;
+  use crate::util::{b64_to_id, uuid_to_b64};
+
+  async fn pull(state: &AppState, cursor: i64, expected: usize) -> SyncPullResponse {
+    // Changes are recorded asynchronously.
+    for _ in 0..100 {
+      let response = sync_pull_handler(
+        State(state.clone()),
+        Path("notes".to_string()),
+        Query(SyncPullQuery {
+          cursor: Some(cursor),
+          limit: None,
+        }),
+        None,
+      )
+      .await
+      .unwrap()
+      .0;
+      if response.changes.len() >= expected {
+        return response;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("missing changes after cursor {cursor}");
+  }
+
+  async fn push(state: &AppState, cursor: i64, mutations: Vec<SyncMutation>) -> SyncPushResponse {
+    return sync_push_handler(
+      State(state.clone()),
+      Path("notes".to_string()),
+      None,
+      Json(SyncPushRequest {
+        cursor: Some(cursor),
+        mutations,
+      }),
+    )
+    .await
+    .unwrap()
+    .0;
+  }
+
+  fn upsert(id: &str, text: &str) -> SyncMutation {
+    return SyncMutation {
+      op: SyncOp::Upsert,
+      id: id.to_string(),
+      record: Some(serde_json::json!({ "text": text })),
+    };
+  }
+
+  #[tokio::test]
+  async fn test_sync_pull_and_push() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(
+        "\
+          CREATE TABLE notes ( \
+            id    BLOB PRIMARY KEY NOT NULL CHECK(is_uuid_v7(id)) DEFAULT (uuid_v7()), \
+            text  TEXT \
+          ) STRICT; \
+        ",
+      )
+      .await
+      .unwrap();
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("notes".to_string()),
+        table_name: Some("notes".to_string()),
+        acl_world: [
+          PermissionFlag::Create as i32,
+          PermissionFlag::Read as i32,
+          PermissionFlag::Update as i32,
+          PermissionFlag::Delete as i32,
+        ]
+        .into(),
+        enable_sync: Some(true),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+    start(&state).unwrap();
+
+    // Client-generated ids.
+    let id0 = uuid_to_b64(&uuid::Uuid::now_v7());
+    let id1 = uuid_to_b64(&uuid::Uuid::now_v7());
+
+    let response = push(&state, 0, vec![upsert(&id0, "a"), upsert(&id1, "b")]).await;
+    assert!(
+      response
+        .results
+        .iter()
+        .all(|r| r.status == SyncMutationStatus::Applied),
+      "{:?}",
+      response.results
+    );
+
+    let pulled = pull(&state, 0, 2).await;
+    assert_eq!(pulled.changes.len(), 2, "{:?}", pulled.changes);
+    assert_eq!(pulled.changes[0].id, id0);
+    assert_eq!(pulled.changes[0].op, SyncOp::Upsert);
+    assert_eq!(pulled.changes[0].record.as_ref().unwrap()["text"], "a");
+    assert!(!pulled.has_more);
+    let cursor = pulled.cursor;
+
+    // Server-side change after the client's pull.
+    state
+      .conn()
+      .execute(
+        "UPDATE notes SET text = 'server' WHERE id = $1",
+        params!(b64_to_id(&id0).unwrap()),
+      )
+      .await
+      .unwrap();
+    let pulled = pull(&state, cursor, 1).await;
+    assert_eq!(pulled.changes.len(), 1);
+
+    // Server wins by default.
+    let response = push(&state, cursor, vec![upsert(&id0, "client")]).await;
+    assert_eq!(response.results[0].status, SyncMutationStatus::Conflict);
+    assert_eq!(
+      response.results[0].record.as_ref().unwrap()["text"],
+      "server"
+    );
+
+    // Deletes are pulled as tombstones.
+    let response = push(
+      &state,
+      pulled.cursor,
+      vec![SyncMutation {
+        op: SyncOp::Delete,
+        id: id1.clone(),
+        record: None,
+      }],
+    )
+    .await;
+    assert_eq!(response.results[0].status, SyncMutationStatus::Applied);
+
+    let pulled = pull(&state, pulled.cursor, 1).await;
+    assert_eq!(pulled.changes.len(), 1);
+    assert_eq!(pulled.changes[0].id, id1);
+    assert_eq!(pulled.changes[0].op, SyncOp::Delete);
+    assert!(pulled.changes[0].record.is_none());
+
+    // Invalid ids are rejected w/o affecting other mutations.
+    let id2 = uuid_to_b64(&uuid::Uuid::now_v7());
+    let response = push(
+      &state,
+      pulled.cursor,
+      vec![upsert("invalid", "x"), upsert(&id2, "c")],
+    )
+    .await;
+    assert_eq!(response.results[0].status, SyncMutationStatus::Rejected);
+    assert_eq!(response.results[1].status, SyncMutationStatus::Applied);
+  }
+}
@@ -22,6 +22,7 @@ pub(crate) async fn add_record_api(
     name: Some(api_name.to_string()),
     table_name: Some(table_name.to_string()),
     attached_databases: vec![],
+    database: None,
 
     acl_world: acls.world.into_iter().map(|f| f as i32).collect(),
     acl_authenticated: acls.authenticated.into_iter().map(|f| f as i32).collect(),
@@ -36,6 +37,23 @@ pub(crate) async fn add_record_api(
     schema_access_rule: access_rules.schema,
     expand: vec![],
     listing_hard_limit: None,
+    read_cache: None,
+    list_cache: None,
+    rate_limit: None,
+    admin_only_columns: vec![],
+    immutable_columns: vec![],
+    fill_on_create: Default::default(),
+    thumbnail_sizes: vec![],
+    file_policies: Default::default(),
+    query_timeout_ms: None,
+    max_joins: None,
+    tenant_scoped: None,
+    enable_sync: None,
+    sync_conflict_policy: None,
+    enable_change_tracking: None,
+    default_limit: None,
+    default_order_by: None,
+    read_excluded_columns: Default::default(),
   });
 
   return state.validate_and_update_config(config, None).await;
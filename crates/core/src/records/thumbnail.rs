@@ -0,0 +1,403 @@
+use axum::body::Body;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use futures_util::StreamExt;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use log::*;
+use object_store::{ObjectStore, ObjectStoreExt};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::sync::Arc;
+use trailbase_schema::FileUpload;
+use utoipa::IntoParams;
+
+use crate::app_state::AppState;
+use crate::encryption::KeyType;
+use crate::metrics::{ObjectStoreOp, record_object_store_op};
+use crate::records::file_encryption::{decrypt_bytes, unwrap_data_key};
+use crate::records::{RecordApi, RecordError};
+
+/// Query parameters for requesting resized variants of image files.
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct ThumbnailQuery {
+  /// Thumbnail size, e.g. "200x200". Must be one of the sizes configured for the Record API. The
+  /// image is scaled to fit within the given bounds preserving its aspect ratio.
+  pub thumb: Option<String>,
+  /// Thumbnail format: "webp" (default), "png" or "jpeg".
+  pub format: Option<String>,
+}
+
+/// Bounding box for thumbnails, parsed from `<width>x<height>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ThumbnailSize {
+  pub width: u32,
+  pub height: u32,
+}
+
+impl ThumbnailSize {
+  pub(crate) fn parse(size: &str) -> Option<Self> {
+    let (width, height) = size.split_once('x')?;
+    let width: u32 = width.parse().ok()?;
+    let height: u32 = height.parse().ok()?;
+
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+      return None;
+    }
+
+    return Some(Self { width, height });
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ThumbnailFormat {
+  Webp,
+  Png,
+  Jpeg,
+}
+
+impl ThumbnailFormat {
+  fn parse(format: Option<&str>) -> Option<Self> {
+    return match format {
+      None | Some("webp") => Some(Self::Webp),
+      Some("png") => Some(Self::Png),
+      Some("jpeg") | Some("jpg") => Some(Self::Jpeg),
+      _ => None,
+    };
+  }
+
+  fn extension(&self) -> &'static str {
+    return match self {
+      Self::Webp => "webp",
+      Self::Png => "png",
+      Self::Jpeg => "jpeg",
+    };
+  }
+
+  fn image_format(&self) -> ImageFormat {
+    return match self {
+      Self::Webp => ImageFormat::WebP,
+      Self::Png => ImageFormat::Png,
+      Self::Jpeg => ImageFormat::Jpeg,
+    };
+  }
+}
+
+/// Responds with a resized variant of the given image file. Variants are generated on first
+/// request and cached in the object store under a path derived from the original's.
+pub(crate) async fn read_thumbnail_into_response(
+  state: &AppState,
+  api: &RecordApi,
+  file_upload: FileUpload,
+  query: &ThumbnailQuery,
+) -> Result<Response, RecordError> {
+  let Some(size) = query
+    .thumb
+    .as_deref()
+    .and_then(ThumbnailSize::parse)
+    .filter(|size| api.thumbnail_sizes().contains(size))
+  else {
+    return Err(RecordError::BadRequest("Unsupported thumbnail size"));
+  };
+  let Some(format) = ThumbnailFormat::parse(query.format.as_deref()) else {
+    return Err(RecordError::BadRequest("Unsupported thumbnail format"));
+  };
+
+  if !file_upload
+    .content_type()
+    .is_some_and(|t| t.starts_with("image/"))
+  {
+    return Err(RecordError::BadRequest("Not an image"));
+  }
+
+  let store = state.objectstore();
+
+  // Thumbnails of encrypted files aren't cached to avoid storing derived plaintext.
+  if let Some(encryption) = file_upload.encryption() {
+    let data_key = unwrap_data_key(state, encryption)
+      .await
+      .map_err(|err| RecordError::Internal(err.into()))?;
+    let contents = build_thumbnail(&store, &file_upload, Some(data_key), size, format).await?;
+    return Ok(thumbnail_response(format, contents));
+  }
+
+  let path = thumbnail_path(&file_upload, size, format);
+
+  record_object_store_op(ObjectStoreOp::Get);
+  let contents: Vec<u8> = match store.get(&path).await {
+    Ok(result) => result
+      .bytes()
+      .await
+      .map_err(|err| RecordError::Internal(err.into()))?
+      .into(),
+    Err(object_store::Error::NotFound { .. }) => {
+      let contents = build_thumbnail(&store, &file_upload, None, size, format).await?;
+      record_object_store_op(ObjectStoreOp::Put);
+      if let Err(err) = store.put(&path, contents.clone().into()).await {
+        warn!("Failed to cache thumbnail: {err}");
+      }
+      contents
+    }
+    Err(err) => {
+      return Err(RecordError::Internal(err.into()));
+    }
+  };
+
+  return Ok(thumbnail_response(format, contents));
+}
+
+fn thumbnail_response(format: ThumbnailFormat, contents: Vec<u8>) -> Response {
+  return (
+    [(
+      header::CONTENT_TYPE,
+      format.image_format().to_mime_type().to_string(),
+    )],
+    Body::from(contents),
+  )
+    .into_response();
+}
+
+async fn build_thumbnail(
+  store: &Arc<dyn ObjectStore>,
+  file_upload: &FileUpload,
+  data_key: Option<KeyType>,
+  size: ThumbnailSize,
+  format: ThumbnailFormat,
+) -> Result<Vec<u8>, RecordError> {
+  record_object_store_op(ObjectStoreOp::Get);
+  let result = store
+    .get(&object_store::path::Path::from(
+      file_upload.objectstore_id(),
+    ))
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  if result.meta.size > MAX_SOURCE_SIZE {
+    return Err(RecordError::BadRequest("Image too large"));
+  }
+
+  let original = result
+    .bytes()
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  // Decoding and encoding is CPU-bound, keep it off the async executor.
+  return tokio::task::spawn_blocking(move || -> Result<Vec<u8>, RecordError> {
+    let original: Vec<u8> = match data_key {
+      Some(data_key) => {
+        decrypt_bytes(data_key, &original).map_err(|err| RecordError::Internal(err.into()))?
+      }
+      None => original.into(),
+    };
+
+    let image = ImageReader::new(Cursor::new(original))
+      .with_guessed_format()
+      .map_err(|err| RecordError::Internal(err.into()))?
+      .decode()
+      .map_err(|_| RecordError::BadRequest("Unsupported image"))?;
+
+    let thumbnail = image.thumbnail(size.width, size.height);
+    // Not every encoder supports every color type, e.g. JPEG has no alpha channel.
+    let thumbnail = match format {
+      ThumbnailFormat::Jpeg => DynamicImage::ImageRgb8(thumbnail.to_rgb8()),
+      ThumbnailFormat::Webp | ThumbnailFormat::Png => {
+        DynamicImage::ImageRgba8(thumbnail.to_rgba8())
+      }
+    };
+
+    let mut buffer = Cursor::new(Vec::<u8>::new());
+    thumbnail
+      .write_to(&mut buffer, format.image_format())
+      .map_err(|err| RecordError::Internal(err.into()))?;
+    return Ok(buffer.into_inner());
+  })
+  .await
+  .map_err(|err| RecordError::Internal(err.into()))?;
+}
+
+fn thumbnail_path(
+  file_upload: &FileUpload,
+  size: ThumbnailSize,
+  format: ThumbnailFormat,
+) -> object_store::path::Path {
+  return thumbnail_prefix(file_upload).child(format!(
+    "{}x{}.{}",
+    size.width,
+    size.height,
+    format.extension()
+  ));
+}
+
+fn thumbnail_prefix(file_upload: &FileUpload) -> object_store::path::Path {
+  return object_store::path::Path::from_iter([THUMBNAILS_PREFIX, file_upload.objectstore_id()]);
+}
+
+/// Deletes all cached thumbnails of the given file.
+pub(crate) async fn delete_thumbnails(store: &Arc<dyn ObjectStore>, file_upload: &FileUpload) {
+  let prefix = thumbnail_prefix(file_upload);
+  let mut thumbnails = store.list(Some(&prefix));
+  while let Some(meta) = thumbnails.next().await {
+    let result = match meta {
+      Ok(meta) => {
+        record_object_store_op(ObjectStoreOp::Delete);
+        store.delete(&meta.location).await
+      }
+      Err(err) => Err(err),
+    };
+
+    if let Err(err) = result {
+      warn!("Failed to delete thumbnail of {file_upload:?}: {err}");
+    }
+  }
+}
+
+const THUMBNAILS_PREFIX: &str = "thumbnails";
+const MAX_DIMENSION: u32 = 4096;
+const MAX_SOURCE_SIZE: u64 = 50 * 1024 * 1024;
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::{Path, Query, State};
+  use axum::http::HeaderMap;
+  use serde_json::json;
+  use trailbase_schema::{FileUploadData, FileUploadInput};
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::extract::Either;
+  use crate::records::create_record::{
+    CreateRecordQuery, CreateRecordResponse, create_record_handler,
+  };
+  use crate::records::files::SignedFileQuery;
+  use crate::records::read_record::get_uploaded_file_from_record_handler;
+  use crate::records::test_utils::*;
+  use crate::test::unpack_json_response;
+
+  #[test]
+  fn test_thumbnail_size_parse() {
+    assert_eq!(
+      Some(ThumbnailSize {
+        width: 200,
+        height: 100
+      }),
+      ThumbnailSize::parse("200x100")
+    );
+    assert_eq!(None, ThumbnailSize::parse("200"));
+    assert_eq!(None, ThumbnailSize::parse("0x100"));
+    assert_eq!(None, ThumbnailSize::parse("-1x100"));
+    assert_eq!(None, ThumbnailSize::parse("100000x100"));
+  }
+
+  #[tokio::test]
+  async fn test_thumbnails() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE photos (
+            id      INTEGER PRIMARY KEY,
+            file    {json} CHECK(jsonschema('std.FileUpload', file))
+          ) {strict};
+        "#,
+        strict = strict(conn),
+        json = json_column(conn),
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("api".to_string()),
+        table_name: Some("photos".to_string()),
+        acl_world: [PermissionFlag::Create as i32, PermissionFlag::Read as i32].into(),
+        thumbnail_sizes: vec!["16x16".to_string()],
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let mut png = Cursor::new(Vec::<u8>::new());
+    DynamicImage::new_rgb8(64, 32)
+      .write_to(&mut png, ImageFormat::Png)
+      .unwrap();
+
+    let response: CreateRecordResponse = unpack_json_response(
+      create_record_handler(
+        State(state.clone()),
+        Path("api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        None,
+        Either::Json(json!({
+          "id": 1,
+          "file": FileUploadInput {
+            name: None,
+            filename: Some("image.png".to_string()),
+            content_type: Some("image/png".to_string()),
+            data: FileUploadData::Bytes(png.into_inner()),
+          },
+        })),
+      )
+      .await
+      .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let read_thumbnail = async |thumb: &str, format: Option<&str>| {
+      return get_uploaded_file_from_record_handler(
+        State(state.clone()),
+        Path((
+          "api".to_string(),
+          response.ids[0].clone(),
+          "file".to_string(),
+        )),
+        Query(SignedFileQuery::default()),
+        Query(ThumbnailQuery {
+          thumb: Some(thumb.to_string()),
+          format: format.map(|f| f.to_string()),
+        }),
+        None,
+        HeaderMap::new(),
+      )
+      .await;
+    };
+
+    for _ in 0..2 {
+      let thumbnail = read_thumbnail("16x16", Some("png")).await.unwrap();
+      assert_eq!(
+        "image/png",
+        thumbnail.headers().get(header::CONTENT_TYPE).unwrap()
+      );
+
+      let body = axum::body::to_bytes(thumbnail.into_body(), usize::MAX)
+        .await
+        .unwrap();
+      let image = image::load_from_memory(&body).unwrap();
+      // Preserves aspect ratio.
+      assert_eq!((16, 8), (image.width(), image.height()));
+    }
+
+    let cached: Vec<_> = state
+      .objectstore()
+      .list(Some(&object_store::path::Path::from(THUMBNAILS_PREFIX)))
+      .collect()
+      .await;
+    assert_eq!(1, cached.len());
+
+    assert!(matches!(
+      read_thumbnail("32x32", None).await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      read_thumbnail("16x16", Some("bmp")).await,
+      Err(RecordError::BadRequest(_))
+    ));
+  }
+}
@@ -10,6 +10,7 @@ use crate::auth::user::User;
 use crate::config::proto::ConflictResolutionStrategy;
 use crate::records::params::LazyParams;
 use crate::records::record_api::RecordApi;
+use crate::records::webhooks::{RecordOperation, enqueue_record_event};
 use crate::records::write_queries::WriteQuery;
 use crate::records::{Permission, RecordError};
 use crate::util::uuid_to_b64;
@@ -84,11 +85,12 @@ pub async fn record_transactions_handler(
   };
 
   let conn = first_api.conn().clone();
-  let ids = if request.transaction.unwrap_or(false) {
+  let (ids, modified) = if request.transaction.unwrap_or(false) {
     conn
       .transaction({
-        move |mut tx| -> Result<Vec<String>, trailbase_sqlite::Error> {
-          let ids: Vec<String> = apply_ops(
+        let state = state.clone();
+        move |mut tx| -> Result<(Vec<String>, Vec<ModifiedRecord>), trailbase_sqlite::Error> {
+          let result = apply_ops(
             &state,
             &mut tx,
             user.as_ref(),
@@ -99,34 +101,47 @@ pub async fn record_transactions_handler(
 
           tx.commit()?;
 
-          return Ok(ids);
+          return Ok(result);
         }
       })
       .await?
   } else {
     conn
-      .call_writer(
-        move |mut conn| -> Result<Vec<String>, trailbase_sqlite::Error> {
-          let ids: Vec<String> = apply_ops(
+      .call_writer({
+        let state = state.clone();
+        move |mut conn| -> Result<(Vec<String>, Vec<ModifiedRecord>), trailbase_sqlite::Error> {
+          return apply_ops(
             &state,
             &mut conn,
             user.as_ref(),
             &first_api,
             request.operations,
           )
-          .map_err(|err| trailbase_sqlite::Error::Other(err.into()))?;
-
-          return Ok(ids);
-        },
-      )
+          .map_err(|err| trailbase_sqlite::Error::Other(err.into()));
+        }
+      })
       .await?
   };
 
+  notify_modified(&state, modified).await;
+
   return Ok(Json(TransactionResponse { ids }));
 }
 
+/// Table, primary key and kind of change of a record touched by an operation.
+pub(crate) type ModifiedRecord = (QualifiedName, trailbase_sqlite::Value, RecordOperation);
+
+/// Enqueues webhook events for records modified by [apply_ops]. Must only be called after commit.
+pub(crate) async fn notify_modified(state: &AppState, modified: Vec<ModifiedRecord>) {
+  for (table_name, record_id, operation) in modified {
+    enqueue_record_event(state, &table_name, operation, &record_id).await;
+  }
+}
+
 #[inline]
-fn extract_record_id(value: trailbase_sqlite::Value) -> Result<String, trailbase_sqlite::Error> {
+pub(crate) fn extract_record_id(
+  value: trailbase_sqlite::Value,
+) -> Result<String, trailbase_sqlite::Error> {
   return match value {
     trailbase_sqlite::Value::Blob(blob) => Ok(BASE64_URL_SAFE.encode(blob)),
     trailbase_sqlite::Value::Text(text) => Ok(text),
@@ -164,14 +179,15 @@ fn get_api(state: &AppState, api_name: &str) -> Result<RecordApi, RecordError> {
   return Ok(api);
 }
 
-fn apply_ops<T: SyncConnection>(
+pub(crate) fn apply_ops<T: SyncConnection>(
   state: &AppState,
   conn: &mut T,
   user: Option<&User>,
   api: &RecordApi,
   ops: Vec<Operation>,
-) -> Result<Vec<String>, RecordError> {
+) -> Result<(Vec<String>, Vec<ModifiedRecord>), RecordError> {
   let expected_db_name = get_db_name(api.qualified_name());
+  let mut modified: Vec<ModifiedRecord> = Vec::with_capacity(ops.len());
 
   let ids: Vec<String> = ops
     .into_iter()
@@ -221,15 +237,23 @@ fn apply_ops<T: SyncConnection>(
             conflict_resolution_strategy,
             lazy_params
               .consume()
-              .map_err(|_| RecordError::BadRequest("Invalid Parameters"))?,
+              .map_err(|err| err.to_record_error("Invalid Parameters"))?,
           )
           .map_err(|err| RecordError::Internal(err.into()))?;
 
           match query.apply_sync(conn) {
-            Ok(result) => Ok(Some(
-              extract_record_id(result.pk_value.expect("insert"))
-                .map_err(|err| RecordError::Internal(err.into()))?,
-            )),
+            Ok(result) => {
+              let record_id = result.pk_value.expect("insert");
+              modified.push((
+                api.qualified_name().clone(),
+                record_id.clone(),
+                RecordOperation::Insert,
+              ));
+
+              Ok(Some(
+                extract_record_id(record_id).map_err(|err| RecordError::Internal(err.into()))?,
+              ))
+            }
             // Skip over errors for `Ignore` conflict strategy.
             Err(err)
               if conflict_resolution_strategy == ConflictResolutionStrategy::Ignore
@@ -276,7 +300,7 @@ fn apply_ops<T: SyncConnection>(
             api.table_name(),
             lazy_params
               .consume()
-              .map_err(|_| RecordError::BadRequest("Invalid Parameters"))?,
+              .map_err(|err| err.to_record_error("Invalid Parameters"))?,
           )
           .map_err(|err| RecordError::Internal(err.into()))?;
 
@@ -284,6 +308,12 @@ fn apply_ops<T: SyncConnection>(
             .apply_sync(conn)
             .map_err(|err| RecordError::Internal(err.into()))?;
 
+          modified.push((
+            api.qualified_name().clone(),
+            record_id,
+            RecordOperation::Update,
+          ));
+
           Ok(None)
         }
         Operation::Delete {
@@ -303,7 +333,7 @@ fn apply_ops<T: SyncConnection>(
             conn.connection_type(),
             api.table_name(),
             &api.record_pk_column().column.name,
-            record_id,
+            record_id.clone(),
           )
           .map_err(|err| RecordError::Internal(err.into()))?;
 
@@ -311,6 +341,12 @@ fn apply_ops<T: SyncConnection>(
             .apply_sync(conn)
             .map_err(|err| RecordError::Internal(err.into()))?;
 
+          modified.push((
+            api.qualified_name().clone(),
+            record_id,
+            RecordOperation::Delete,
+          ));
+
           Ok(None)
         }
       };
@@ -320,7 +356,7 @@ fn apply_ops<T: SyncConnection>(
     .flatten()
     .collect();
 
-  return Ok(ids);
+  return Ok((ids, modified));
 }
 
 #[cfg(test)]
@@ -1,17 +1,32 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
+use serde::Deserialize;
+use trailbase_wasm_common::RecordHookOperation;
+use utoipa::IntoParams;
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
 use crate::extract::Either;
-use crate::records::params::{JsonRow, LazyParams};
-use crate::records::write_queries::run_update_query;
+use crate::records::file_encryption::encrypt_files;
+use crate::records::hooks::{run_async_record_hooks, run_before_update_hooks, run_on_update_hooks};
+use crate::records::params::{JsonRow, LazyParams, check_column_write_access};
+use crate::records::scanner::scan_files;
+use crate::records::webhooks::{RecordOperation, enqueue_record_event};
+use crate::records::write_queries::{WriteQuery, dry_run_queries, run_update_query};
 use crate::records::{Permission, RecordError};
 
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct UpdateRecordQuery {
+  /// Validate the request, i.e. run access checks and apply the update within a transaction that
+  /// is rolled back, without updating the record.
+  pub dry_run: Option<bool>,
+}
+
 /// Update existing record.
 #[utoipa::path(
   patch,
   path = "/{name}/{record}",
   tag = "records",
+  params(UpdateRecordQuery),
   request_body = serde_json::Value,
   responses(
     (status = 200, description = "Successful update.")
@@ -20,6 +35,7 @@ use crate::records::{Permission, RecordError};
 pub async fn update_record_handler(
   State(state): State<AppState>,
   Path((api_name, record)): Path<(String, String)>,
+  Query(update_record_query): Query<UpdateRecordQuery>,
   user: Option<User>,
   either_request: Either<JsonRow>,
 ) -> Result<(), RecordError> {
@@ -31,13 +47,34 @@ pub async fn update_record_handler(
     return Err(RecordError::ApiRequiresTable);
   }
 
-  let (request, multipart_files) = match either_request {
+  let (mut request, multipart_files) = match either_request {
     Either::Json(value) => (value, None),
     Either::Multipart(value, files) => (value, Some(files)),
     Either::Form(value) => (value, None),
   };
 
-  let record_id = api.primary_key_to_value(record)?;
+  let record_id = api.primary_key_to_value(record.clone())?;
+
+  check_column_write_access(
+    &state,
+    &api,
+    &request,
+    multipart_files.as_deref(),
+    true,
+    user.as_ref(),
+  )
+  .await?;
+
+  run_before_update_hooks(&state, &api_name, &mut request, user.as_ref())?;
+  run_async_record_hooks(
+    &state,
+    &api_name,
+    RecordHookOperation::Update,
+    Some(&record),
+    Some(&mut request),
+    user.as_ref(),
+  )
+  .await?;
 
   #[cfg(debug_assertions)]
   crate::records::json_schema::validate_api_json_schema(
@@ -66,16 +103,42 @@ pub async fn update_record_handler(
     )
     .await?;
 
-  run_update_query(
-    api.conn(),
-    state.objectstore(),
-    api.table_name(),
-    lazy_params
-      .consume()
-      .map_err(|_err| RecordError::BadRequest("Invalid Parameters"))?,
+  let mut params = lazy_params
+    .consume()
+    .map_err(|err| err.to_record_error("Invalid Parameters"))?;
+
+  run_on_update_hooks(
+    &state,
+    &api,
+    &record_id,
+    params.named_params(),
+    user.as_ref(),
+  )?;
+
+  if update_record_query.dry_run.unwrap_or(false) {
+    let (query, _files) =
+      WriteQuery::new_update(api.conn().connection_type(), api.table_name(), params)?;
+    return dry_run_queries(api.conn(), vec![query]).await;
+  }
+
+  scan_files(&state, &params)
+    .await
+    .map_err(|err| err.into_record_error())?;
+  encrypt_files(&state, &mut params)
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  run_update_query(api.conn(), &state.objectstore(), api.table_name(), params)
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  enqueue_record_event(
+    &state,
+    api.qualified_name(),
+    RecordOperation::Update,
+    &record_id,
   )
-  .await
-  .map_err(|err| RecordError::Internal(err.into()))?;
+  .await;
 
   return Ok(());
 }
@@ -146,6 +209,7 @@ mod test {
       Path("update_api".to_string()),
       Query(CreateRecordQuery::default()),
       None,
+      None,
       Either::Json(
         json_row_from_value(json!({
           "id": 1,
@@ -161,6 +225,7 @@ mod test {
     let _ = update_record_handler(
       State(state.clone()),
       Path(("update_api".to_string(), "1".to_string())),
+      Query(UpdateRecordQuery::default()),
       None,
       Either::Json(
         json_row_from_value(json!({
@@ -186,6 +251,7 @@ mod test {
     let response = update_record_handler(
       State(state.clone()),
       Path(("update_api".to_string(), "1".to_string())),
+      Query(UpdateRecordQuery::default()),
       None,
       Either::Json(
         json_row_from_value(json!({
@@ -273,6 +339,7 @@ mod test {
         Path("messages_api".to_string()),
         Query(CreateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        None,
         Either::Json(json_row_from_value(create_json).unwrap().into()),
       )
       .await
@@ -293,6 +360,7 @@ mod test {
       let update_response = update_record_handler(
         State(state.clone()),
         Path(("messages_api".to_string(), b64_id.clone())),
+        Query(UpdateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
         Either::Json(json_row_from_value(update_json).unwrap().into()),
       )
@@ -319,6 +387,7 @@ mod test {
       let update_response = update_record_handler(
         State(state.clone()),
         Path(("messages_api".to_string(), b64_id.clone())),
+        Query(UpdateRecordQuery::default()),
         User::from_auth_token(&state, &user_y_token.auth_token),
         Either::Json(json_row_from_value(update_json).unwrap().into()),
       )
@@ -384,6 +453,7 @@ mod test {
     let _ = update_record_handler(
       State(state.clone()),
       Path(("test_api".to_string(), BASE64_URL_SAFE.encode(&user_x))),
+      Query(UpdateRecordQuery::default()),
       User::from_auth_token(&state, &user_x_token.auth_token),
       Either::Json(
         json_row_from_value(json!({
@@ -402,6 +472,7 @@ mod test {
       update_record_handler(
         State(state.clone()),
         Path(("test_api".to_string(), BASE64_URL_SAFE.encode(&user_x))),
+        Query(UpdateRecordQuery::default()),
         User::from_auth_token(&state, &user_x_token.auth_token),
         Either::Json(
           json_row_from_value(json!({
@@ -416,4 +487,115 @@ mod test {
       .is_err()
     );
   }
+
+  #[tokio::test]
+  async fn test_admin_only_and_immutable_columns() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE accounts (
+            id        INTEGER PRIMARY KEY,
+            name      TEXT,
+            handle    TEXT,
+            role      TEXT
+          ) {strict};
+        "#,
+        strict = strict(conn)
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("accounts_api".to_string()),
+        table_name: Some("accounts".to_string()),
+        acl_authenticated: [PermissionFlag::Create as i32, PermissionFlag::Update as i32].into(),
+        admin_only_columns: vec!["role".to_string()],
+        immutable_columns: vec!["handle".to_string()],
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let password = "Secret!1!!";
+    create_user_for_test(&state, "user@test.com", password)
+      .await
+      .unwrap();
+    let user_token = login_with_password(&state, "user@test.com", password)
+      .await
+      .unwrap();
+    let user = User::from_auth_token(&state, &user_token.auth_token);
+
+    create_user_for_test(&state, "admin@test.com", password)
+      .await
+      .unwrap();
+    crate::auth::cli::promote_user_to_admin(
+      state.user_conn(),
+      crate::auth::cli::UserReference::Email("admin@test.com".to_string()),
+    )
+    .await
+    .unwrap();
+    let admin_token = login_with_password(&state, "admin@test.com", password)
+      .await
+      .unwrap();
+    let admin = User::from_auth_token(&state, &admin_token.auth_token);
+
+    let create = async |user: Option<User>, record: serde_json::Value| {
+      return create_record_handler(
+        State(state.clone()),
+        Path("accounts_api".to_string()),
+        Query(CreateRecordQuery::default()),
+        user,
+        None,
+        Either::Json(record),
+      )
+      .await;
+    };
+    let update = async |user: Option<User>, record: serde_json::Value| {
+      return update_record_handler(
+        State(state.clone()),
+        Path(("accounts_api".to_string(), "1".to_string())),
+        Query(UpdateRecordQuery::default()),
+        user,
+        Either::Json(json_row_from_value(record).unwrap()),
+      )
+      .await;
+    };
+
+    assert!(matches!(
+      create(user.clone(), json!({"id": 1, "role": "admin"})).await,
+      Err(RecordError::Forbidden)
+    ));
+    create(user.clone(), json!({"id": 1, "name": "a", "handle": "h"}))
+      .await
+      .unwrap();
+
+    update(user.clone(), json!({"name": "b"})).await.unwrap();
+    assert!(matches!(
+      update(user.clone(), json!({"handle": "other"})).await,
+      Err(RecordError::Forbidden)
+    ));
+    assert!(matches!(
+      update(user.clone(), json!({"role": "admin"})).await,
+      Err(RecordError::Forbidden)
+    ));
+
+    update(admin.clone(), json!({"handle": "other", "role": "admin"}))
+      .await
+      .unwrap();
+
+    let role: String = conn
+      .read_query_row_get("SELECT role FROM accounts WHERE id = 1", (), 0)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!("admin", role);
+  }
 }
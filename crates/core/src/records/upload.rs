@@ -0,0 +1,710 @@
+//! Resumable file uploads following the core protocol and the creation and termination extensions
+//! of [tus](https://tus.io/protocols/resumable-upload).
+//!
+//! Uploads target a single-file column of an existing record. Chunks are streamed straight into a
+//! multipart upload of the object store and the record's column is only updated once the upload
+//! has completed.
+
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, HeaderName, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use base64::prelude::*;
+use futures_util::StreamExt;
+use log::*;
+use object_store::{ObjectStoreExt, WriteMultipart};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use trailbase_schema::metadata::JsonColumnMetadata;
+use trailbase_schema::{FileEncryption, FileUpload};
+use trailbase_sqlite::Value;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::constants::RECORD_API_PATH;
+use crate::metrics::{ObjectStoreOp, record_object_store_op};
+use crate::records::file_encryption::{SegmentEncryptor, new_data_key};
+use crate::records::files::delete_files_marked_for_deletion;
+use crate::records::params::{JsonRow, Params, check_column_write_access, check_mime_type};
+use crate::records::scanner::scan_stored_file;
+use crate::records::util::named_placeholder;
+use crate::records::write_queries::WriteQuery;
+use crate::records::{Permission, RecordApi, RecordError};
+
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+
+const TUS_VERSION: &str = "1.0.0";
+const OFFSET_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+struct UploadSession {
+  api_name: String,
+  record_id: Value,
+  column_name: String,
+  user: Option<Uuid>,
+
+  original_filename: Option<String>,
+  content_type: Option<String>,
+  /// Leading bytes of the file used to infer its mime type on completion.
+  prefix: Vec<u8>,
+  /// Set if the file is encrypted at rest.
+  encryption: Option<(FileEncryption, SegmentEncryptor)>,
+
+  length: usize,
+  offset: usize,
+  /// `None` once the upload has been finalized or aborted.
+  writer: Option<WriteMultipart>,
+  last_active: Instant,
+}
+
+impl UploadSession {
+  fn accessible_by(&self, api_name: &str, user: Option<&User>) -> bool {
+    return self.api_name == api_name && self.user == user.map(|u| u.uuid);
+  }
+}
+
+/// In-flight uploads keyed by their id, which doubles as their objectstore id.
+///
+/// NOTE: Uploads are kept in memory and thus cannot be resumed across TB restarts.
+static UPLOADS: LazyLock<Mutex<HashMap<Uuid, SharedUploadSession>>> =
+  LazyLock::new(|| Mutex::new(HashMap::new()));
+
+type SharedUploadSession = Arc<tokio::sync::Mutex<UploadSession>>;
+
+/// Create resumable upload.
+///
+/// Expects the file's size in bytes via the `Upload-Length` header and optionally its filename
+/// and content type via the `Upload-Metadata` header, e.g. `filename <b64>,filetype <b64>`.
+/// Responds with the upload's URL in the `Location` header.
+#[utoipa::path(
+  post,
+  path = "/{name}/{record}/upload/{column_name}",
+  tag = "records",
+  responses(
+    (status = 201, description = "Upload created.")
+  )
+)]
+pub async fn create_upload_handler(
+  State(state): State<AppState>,
+  Path((api_name, record, column_name)): Path<(String, String, String)>,
+  user: Option<User>,
+  headers: HeaderMap,
+) -> Result<Response, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+  if !api.is_table() {
+    return Err(RecordError::ApiRequiresTable);
+  }
+
+  let is_file_column = api
+    .column_metadata_by_name(&column_name)
+    .and_then(|meta| meta.json.as_ref())
+    .is_some_and(
+      |json| matches!(json, JsonColumnMetadata::SchemaName(name) if name == "std.FileUpload"),
+    );
+  if !is_file_column {
+    return Err(RecordError::BadRequest("Not a single-file column"));
+  }
+
+  let record_id = api.primary_key_to_value(record)?;
+  check_upload_access(&state, &api, &record_id, &column_name, user.as_ref()).await?;
+
+  let length: usize = header_value(&headers, &UPLOAD_LENGTH)?
+    .ok_or(RecordError::BadRequest("Missing Upload-Length"))?;
+  if length > MAX_UPLOAD_LENGTH {
+    return Err(RecordError::BadRequest("Upload too large"));
+  }
+  if let Some(max_file_size) = api
+    .file_policy(&column_name)
+    .and_then(|policy| policy.max_file_size)
+    && length as u64 > max_file_size
+  {
+    return Err(RecordError::BadRequest("File too large"));
+  }
+
+  let (original_filename, content_type) = parse_upload_metadata(&headers)?;
+
+  abort_expired_uploads().await;
+
+  let encryption = if state.file_encryption_enabled() {
+    let (encryption, data_key) = new_data_key(&state)
+      .await
+      .map_err(|err| RecordError::Internal(err.into()))?;
+    Some((encryption, SegmentEncryptor::new(data_key)))
+  } else {
+    None
+  };
+
+  let id = Uuid::new_v4();
+  record_object_store_op(ObjectStoreOp::Put);
+  let upload = state
+    .objectstore()
+    .put_multipart(&object_store::path::Path::from(id.to_string()))
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  UPLOADS.lock().insert(
+    id,
+    Arc::new(tokio::sync::Mutex::new(UploadSession {
+      api_name: api_name.clone(),
+      record_id,
+      column_name,
+      user: user.map(|u| u.uuid),
+      original_filename,
+      content_type,
+      prefix: vec![],
+      encryption,
+      length,
+      offset: 0,
+      writer: Some(WriteMultipart::new(upload)),
+      last_active: Instant::now(),
+    })),
+  );
+
+  return Ok(
+    (
+      StatusCode::CREATED,
+      [
+        (
+          header::LOCATION,
+          format!("/{RECORD_API_PATH}/{api_name}/upload/{id}"),
+        ),
+        (TUS_RESUMABLE, TUS_VERSION.to_string()),
+        (UPLOAD_OFFSET, "0".to_string()),
+      ],
+    )
+      .into_response(),
+  );
+}
+
+/// Get upload offset.
+#[utoipa::path(
+  head,
+  path = "/{name}/upload/{upload_id}",
+  tag = "records",
+  responses(
+    (status = 200, description = "Current offset in the `Upload-Offset` header.")
+  )
+)]
+pub async fn upload_offset_handler(
+  Path((api_name, upload_id)): Path<(String, Uuid)>,
+  user: Option<User>,
+) -> Result<Response, RecordError> {
+  let session = lookup_upload(upload_id)?.lock_owned().await;
+  if !session.accessible_by(&api_name, user.as_ref()) {
+    return Err(RecordError::RecordNotFound);
+  }
+
+  return Ok(
+    ([
+      (TUS_RESUMABLE, TUS_VERSION.to_string()),
+      (UPLOAD_OFFSET, session.offset.to_string()),
+      (UPLOAD_LENGTH, session.length.to_string()),
+      (header::CACHE_CONTROL, "no-store".to_string()),
+    ],)
+      .into_response(),
+  );
+}
+
+/// Append to upload.
+///
+/// Appends the request body at the offset given by the `Upload-Offset` header, which must match
+/// the upload's current offset. Once all bytes have been received, the record is updated to
+/// reference the uploaded file.
+#[utoipa::path(
+  patch,
+  path = "/{name}/upload/{upload_id}",
+  tag = "records",
+  responses(
+    (status = 204, description = "Chunk appended. New offset in the `Upload-Offset` header.")
+  )
+)]
+pub async fn append_upload_handler(
+  State(state): State<AppState>,
+  Path((api_name, upload_id)): Path<(String, Uuid)>,
+  user: Option<User>,
+  headers: HeaderMap,
+  body: Body,
+) -> Result<Response, RecordError> {
+  if headers
+    .get(header::CONTENT_TYPE)
+    .is_none_or(|content_type| content_type != OFFSET_CONTENT_TYPE)
+  {
+    return Err(RecordError::BadRequest("Invalid Content-Type"));
+  }
+
+  let Ok(mut session) = lookup_upload(upload_id)?.try_lock_owned() else {
+    return Err(RecordError::Conflict("Concurrent upload"));
+  };
+  if !session.accessible_by(&api_name, user.as_ref()) {
+    return Err(RecordError::RecordNotFound);
+  }
+
+  let offset: Option<usize> = header_value(&headers, &UPLOAD_OFFSET)?;
+  if offset != Some(session.offset) {
+    return Err(RecordError::Conflict("Upload-Offset mismatch"));
+  }
+
+  let mut stream = body.into_data_stream();
+  while let Some(chunk) = stream.next().await {
+    let Ok(chunk) = chunk else {
+      // Client disconnected. Keep what we've got so far, so the client can resume.
+      break;
+    };
+
+    if session.offset + chunk.len() > session.length {
+      return Err(RecordError::BadRequest("Exceeds Upload-Length"));
+    }
+
+    if session.prefix.len() < PREFIX_LENGTH {
+      let n = (PREFIX_LENGTH - session.prefix.len()).min(chunk.len());
+      session.prefix.extend_from_slice(&chunk[..n]);
+    }
+
+    let session = &mut *session;
+    let Some(ref mut writer) = session.writer else {
+      return Err(RecordError::RecordNotFound);
+    };
+    writer
+      .wait_for_capacity(MAX_CONCURRENT_PARTS)
+      .await
+      .map_err(|err| RecordError::Internal(err.into()))?;
+    match session.encryption {
+      Some((_, ref mut encryptor)) => writer.write(
+        &encryptor
+          .update(&chunk)
+          .map_err(|err| RecordError::Internal(err.into()))?,
+      ),
+      None => writer.write(&chunk),
+    };
+
+    session.offset += chunk.len();
+    session.last_active = Instant::now();
+  }
+
+  if session.offset == session.length {
+    UPLOADS.lock().remove(&upload_id);
+    complete_upload(&state, upload_id, &mut session, user.as_ref()).await?;
+  }
+
+  return Ok(
+    (
+      StatusCode::NO_CONTENT,
+      [
+        (TUS_RESUMABLE, TUS_VERSION.to_string()),
+        (UPLOAD_OFFSET, session.offset.to_string()),
+      ],
+    )
+      .into_response(),
+  );
+}
+
+/// Abort upload.
+#[utoipa::path(
+  delete,
+  path = "/{name}/upload/{upload_id}",
+  tag = "records",
+  responses(
+    (status = 204, description = "Upload aborted.")
+  )
+)]
+pub async fn abort_upload_handler(
+  Path((api_name, upload_id)): Path<(String, Uuid)>,
+  user: Option<User>,
+) -> Result<Response, RecordError> {
+  let mut session = lookup_upload(upload_id)?.lock_owned().await;
+  if !session.accessible_by(&api_name, user.as_ref()) {
+    return Err(RecordError::RecordNotFound);
+  }
+  UPLOADS.lock().remove(&upload_id);
+
+  if let Some(writer) = session.writer.take()
+    && let Err(err) = writer.abort().await
+  {
+    warn!("Failed to abort upload: {err}");
+  }
+
+  return Ok((StatusCode::NO_CONTENT, [(TUS_RESUMABLE, TUS_VERSION)]).into_response());
+}
+
+async fn check_upload_access(
+  state: &AppState,
+  api: &RecordApi,
+  record_id: &Value,
+  column_name: &str,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  api
+    .check_record_level_access(Permission::Update, Some(record_id), None, user)
+    .await?;
+
+  let row = JsonRow::from_iter([(column_name.to_string(), serde_json::Value::Null)]);
+  return check_column_write_access(state, api, &row, None, true, user).await;
+}
+
+/// Finalizes the multipart upload and points the record's column to the new file.
+async fn complete_upload(
+  state: &AppState,
+  upload_id: Uuid,
+  session: &mut UploadSession,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  let Some(mut writer) = session.writer.take() else {
+    return Err(RecordError::RecordNotFound);
+  };
+
+  // Seal the remaining, buffered contents.
+  let encryption = match session.encryption.take() {
+    Some((encryption, encryptor)) => match encryptor.finish() {
+      Ok(sealed) => {
+        writer.write(&sealed);
+        Some(encryption)
+      }
+      Err(err) => {
+        let _ = writer.abort().await;
+        return Err(RecordError::Internal(err.into()));
+      }
+    },
+    None => None,
+  };
+
+  let Some(api) = state.lookup_record_api(&session.api_name) else {
+    let _ = writer.abort().await;
+    return Err(RecordError::ApiNotFound);
+  };
+
+  // Access may have been revoked, e.g. by another update of the record, since the upload started.
+  if let Err(err) =
+    check_upload_access(state, &api, &session.record_id, &session.column_name, user).await
+  {
+    let _ = writer.abort().await;
+    return Err(err);
+  }
+
+  writer
+    .finish()
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+  let mut file_upload = FileUpload::with_inferred_mime_type(
+    upload_id,
+    session.original_filename.clone(),
+    session.content_type.clone(),
+    &session.prefix,
+  );
+  file_upload.set_encryption(encryption);
+
+  let result = async {
+    // The type is only known once the leading bytes have been received.
+    if let Some(policy) = api.file_policy(&session.column_name) {
+      check_mime_type(policy, file_upload.content_type())
+        .map_err(|err| err.to_record_error("Invalid file"))?;
+    }
+    scan_stored_file(state, &file_upload)
+      .await
+      .map_err(|err| err.into_record_error())?;
+    return update_file_column(state, &api, session, &file_upload).await;
+  }
+  .await;
+  if result.is_err() {
+    let path = object_store::path::Path::from(file_upload.objectstore_id());
+    record_object_store_op(ObjectStoreOp::Delete);
+    if let Err(err) = state.objectstore().delete(&path).await {
+      warn!("Failed to cleanup upload: {err}");
+    }
+  }
+
+  return result;
+}
+
+async fn update_file_column(
+  state: &AppState,
+  api: &RecordApi,
+  session: &UploadSession,
+  file_upload: &FileUpload,
+) -> Result<(), RecordError> {
+  let Some(meta) = api.column_metadata_by_name(&session.column_name) else {
+    return Err(RecordError::BadRequest("Invalid field/column name"));
+  };
+
+  let json = serde_json::to_string(file_upload).map_err(|err| RecordError::Internal(err.into()))?;
+  let (query, _files) = WriteQuery::new_update(
+    api.conn().connection_type(),
+    api.table_name(),
+    Params::Update {
+      named_params: vec![
+        (
+          named_placeholder(&session.column_name).into(),
+          Value::Text(json),
+        ),
+        (":__pk_value".into(), session.record_id.clone()),
+      ],
+      files: vec![],
+      column_names: vec![session.column_name.clone()],
+      column_indexes: vec![meta.index],
+      pk_column_name: api.record_pk_column().column.name.clone(),
+    },
+  )?;
+
+  let result = query.apply_async(api.conn()).await?;
+
+  // Clean up the file previously referenced by the column, if any.
+  delete_files_marked_for_deletion(
+    api.conn(),
+    &state.objectstore(),
+    api.table_name(),
+    &[result.rowid],
+  )
+  .await
+  .map_err(|err| RecordError::Internal(err.into()))?;
+
+  return Ok(());
+}
+
+fn lookup_upload(upload_id: Uuid) -> Result<SharedUploadSession, RecordError> {
+  return UPLOADS
+    .lock()
+    .get(&upload_id)
+    .cloned()
+    .ok_or(RecordError::RecordNotFound);
+}
+
+async fn abort_expired_uploads() {
+  let expired: Vec<_> = {
+    let mut uploads = UPLOADS.lock();
+    let expired_ids: Vec<Uuid> = uploads
+      .iter()
+      .filter_map(|(id, session)| {
+        let session = session.try_lock().ok()?;
+        return (session.last_active.elapsed() > UPLOAD_TTL).then_some(*id);
+      })
+      .collect();
+
+    expired_ids
+      .into_iter()
+      .filter_map(|id| uploads.remove(&id))
+      .collect()
+  };
+
+  for session in expired {
+    if let Some(writer) = session.lock().await.writer.take()
+      && let Err(err) = writer.abort().await
+    {
+      warn!("Failed to abort expired upload: {err}");
+    }
+  }
+}
+
+fn header_value<T: std::str::FromStr>(
+  headers: &HeaderMap,
+  name: &HeaderName,
+) -> Result<Option<T>, RecordError> {
+  let Some(value) = headers.get(name) else {
+    return Ok(None);
+  };
+
+  return value
+    .to_str()
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .map(Some)
+    .ok_or(RecordError::BadRequest("Invalid header"));
+}
+
+/// Parses the `filename` and `filetype` entries of the `Upload-Metadata` header.
+fn parse_upload_metadata(
+  headers: &HeaderMap,
+) -> Result<(Option<String>, Option<String>), RecordError> {
+  let Some(metadata) = headers.get(UPLOAD_METADATA) else {
+    return Ok((None, None));
+  };
+  let metadata = metadata
+    .to_str()
+    .map_err(|_err| RecordError::BadRequest("Invalid Upload-Metadata"))?;
+
+  let mut filename = None;
+  let mut filetype = None;
+  for pair in metadata.split(',') {
+    let mut parts = pair.trim().splitn(2, ' ');
+    let key = parts.next().unwrap_or_default();
+    let value = match parts.next() {
+      Some(value) => Some(
+        BASE64_STANDARD
+          .decode(value)
+          .ok()
+          .and_then(|v| String::from_utf8(v).ok())
+          .ok_or(RecordError::BadRequest("Invalid Upload-Metadata"))?,
+      ),
+      None => None,
+    };
+
+    match key {
+      "filename" => filename = value,
+      "filetype" => filetype = value,
+      _ => {}
+    }
+  }
+
+  return Ok((filename, filetype));
+}
+
+const MAX_UPLOAD_LENGTH: usize = 10 * 1024 * 1024 * 1024;
+const MAX_CONCURRENT_PARTS: usize = 4;
+const PREFIX_LENGTH: usize = 8192;
+const UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::Query;
+  use axum::http::HeaderValue;
+
+  use super::*;
+  use crate::app_state::test_state;
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::records::read_record::{ReadRecordQuery, read_record_handler};
+  use crate::records::test_utils::*;
+
+  #[tokio::test]
+  async fn test_resumable_upload() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    conn
+      .execute_batch(format!(
+        r#"
+          CREATE TABLE docs (
+            id      INTEGER PRIMARY KEY,
+            file    {json} CHECK(jsonschema('std.FileUpload', file))
+          ) {strict};
+
+          INSERT INTO docs (id) VALUES (1);
+        "#,
+        strict = strict(conn),
+        json = json_column(conn),
+      ))
+      .await
+      .unwrap();
+
+    state.rebuild_connection_metadata().await.unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("docs_api".to_string()),
+        table_name: Some("docs".to_string()),
+        acl_world: [PermissionFlag::Read as i32, PermissionFlag::Update as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    let contents: Vec<u8> = (0..100).map(|i| i as u8).collect();
+
+    let response = create_upload_handler(
+      State(state.clone()),
+      Path(("docs_api".to_string(), "1".to_string(), "file".to_string())),
+      None,
+      HeaderMap::from_iter([
+        (UPLOAD_LENGTH, HeaderValue::from(contents.len())),
+        (
+          UPLOAD_METADATA,
+          HeaderValue::from_str(&format!("filename {}", BASE64_STANDARD.encode("doc.bin")))
+            .unwrap(),
+        ),
+      ]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+
+    let location = response
+      .headers()
+      .get(header::LOCATION)
+      .unwrap()
+      .to_str()
+      .unwrap();
+    let upload_id: Uuid = location.rsplit('/').next().unwrap().parse().unwrap();
+
+    let append = async |offset: usize, chunk: &[u8]| {
+      return append_upload_handler(
+        State(state.clone()),
+        Path(("docs_api".to_string(), upload_id)),
+        None,
+        HeaderMap::from_iter([
+          (
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(OFFSET_CONTENT_TYPE),
+          ),
+          (UPLOAD_OFFSET, HeaderValue::from(offset)),
+        ]),
+        Body::from(chunk.to_vec()),
+      )
+      .await;
+    };
+
+    let response = append(0, &contents[..40]).await.unwrap();
+    assert_eq!("40", response.headers().get(UPLOAD_OFFSET).unwrap());
+
+    // Mismatching offsets are rejected.
+    assert!(matches!(
+      append(0, &contents[40..]).await,
+      Err(RecordError::Conflict(_))
+    ));
+
+    let response = upload_offset_handler(Path(("docs_api".to_string(), upload_id)), None)
+      .await
+      .unwrap();
+    assert_eq!("40", response.headers().get(UPLOAD_OFFSET).unwrap());
+
+    append(40, &contents[40..]).await.unwrap();
+
+    // Completed uploads are gone.
+    assert!(
+      upload_offset_handler(Path(("docs_api".to_string(), upload_id)), None)
+        .await
+        .is_err()
+    );
+
+    let axum::Json(record) = read_record_handler(
+      State(state.clone()),
+      Path(("docs_api".to_string(), "1".to_string())),
+      Query(ReadRecordQuery::default()),
+      None,
+    )
+    .await
+    .unwrap();
+
+    let file: FileUpload = serde_json::from_value(record["file"].clone()).unwrap();
+    assert_eq!(Some("doc.bin"), file.original_filename());
+    assert_eq!(upload_id.to_string(), file.objectstore_id());
+
+    let stored = state
+      .objectstore()
+      .get(&object_store::path::Path::from(file.objectstore_id()))
+      .await
+      .unwrap()
+      .bytes()
+      .await
+      .unwrap();
+    assert_eq!(contents, stored.to_vec());
+
+    // Only single-file columns are supported.
+    assert!(
+      create_upload_handler(
+        State(state.clone()),
+        Path(("docs_api".to_string(), "1".to_string(), "id".to_string())),
+        None,
+        HeaderMap::from_iter([(UPLOAD_LENGTH, HeaderValue::from(1))]),
+      )
+      .await
+      .is_err()
+    );
+  }
+}
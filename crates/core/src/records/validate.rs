@@ -1,12 +1,14 @@
 use itertools::Itertools;
 use trailbase_schema::QualifiedName;
 use trailbase_schema::metadata::TableOrViewMetadata;
-use trailbase_schema::parse::parse_into_statement;
-use trailbase_schema::sqlite::ColumnOption;
+use trailbase_schema::parse::{parse_into_statement, parse_into_statements};
+use trailbase_schema::sqlite::{ColumnDataType, ColumnOption};
 use trailbase_sqlite::ConnectionType;
 
 use crate::config::{ConfigError, proto};
 use crate::connection::{ConnectionEntry, ConnectionManager};
+use crate::records::record_api::{AuthContextField, rewrite_role_checks};
+use crate::records::thumbnail::ThumbnailSize;
 
 fn validate_record_api_name(name: &str) -> Result<(), ConfigError> {
   if name.is_empty() {
@@ -36,6 +38,14 @@ pub(crate) async fn validate_record_api_config(
     if api_config.enable_subscriptions() {
       return Err(invalid("PG doesn't (yet) support realtime subscriptions"));
     }
+
+    if api_config.enable_sync() {
+      return Err(invalid("PG doesn't (yet) support sync"));
+    }
+
+    if api_config.enable_change_tracking() {
+      return Err(invalid("PG doesn't (yet) support change tracking"));
+    }
   }
 
   let Some(ref api_name) = api_config.name else {
@@ -43,18 +53,16 @@ pub(crate) async fn validate_record_api_config(
   };
   validate_record_api_name(api_name)?;
 
-  let table_name = {
-    let Some(ref table_name) = api_config.table_name else {
-      return Err(invalid(format!(
-        "Missing TABLE/VIEW reference for API '{api_name}'"
-      )));
-    };
-    QualifiedName::parse(table_name)?
-  };
+  if api_config.table_name.is_none() {
+    return Err(invalid(format!(
+      "Missing TABLE/VIEW reference for API '{api_name}'"
+    )));
+  }
+  let table_name = api_config.qualified_table_name()?;
 
   let dangling_database_references: Vec<_> = api_config
-    .attached_databases
-    .iter()
+    .databases_to_attach()
+    .into_iter()
     .filter(|adb| !databases.iter().any(|db| db.name() == adb.as_str()))
     .collect();
   if !dangling_database_references.is_empty() {
@@ -81,12 +89,31 @@ pub(crate) async fn validate_record_api_config(
     entity: Entity::Unknown,
   };
 
-  let ConnectionEntry { metadata, .. } = connection_manager
-    .get_entry_for_qn(&table_name)
-    .await
-    .map_err(|err| {
-      return invalid_prefixed(&prefix, err);
-    })?;
+  let tenant_scoped = api_config.tenant_scoped.unwrap_or(false);
+  if tenant_scoped {
+    if api_config.database.is_some() {
+      return Err(invalid_prefixed(
+        &prefix,
+        "Tenant-scoped APIs cannot reference other databases.",
+      ));
+    }
+    if api_config.enable_subscriptions() {
+      return Err(invalid_prefixed(
+        &prefix,
+        "Tenant-scoped APIs don't (yet) support realtime subscriptions.",
+      ));
+    }
+  }
+
+  // Tenant-scoped APIs are validated against the tenant migrations.
+  let ConnectionEntry { metadata, .. } = if tenant_scoped {
+    connection_manager.tenant_template_entry().await
+  } else {
+    connection_manager.get_entry_for_qn(&table_name).await
+  }
+  .map_err(|err| {
+    return invalid_prefixed(&prefix, err);
+  })?;
 
   let Some(table_or_view) = metadata.get_table_or_view(&table_name) else {
     return Err(invalid_prefixed(&prefix, "not found."));
@@ -130,6 +157,45 @@ pub(crate) async fn validate_record_api_config(
     ));
   };
 
+  if api_config.enable_sync() {
+    // Changes are tracked on the main connection and clients create records offline, i.e. pick
+    // their own ids.
+    if !matches!(prefix.entity, Entity::Table)
+      || tenant_scoped
+      || table_name
+        .database_schema
+        .as_ref()
+        .is_some_and(|db| db != "main")
+      || !api_config.attached_databases.is_empty()
+    {
+      return Err(invalid_prefixed(
+        &prefix,
+        "Sync requires a TABLE in the main database w/o attached databases.",
+      ));
+    }
+    if pk_meta.column.data_type != ColumnDataType::Blob {
+      return Err(invalid_prefixed(
+        &prefix,
+        "Sync requires a UUID PRIMARY KEY for client-generated ids.",
+      ));
+    }
+  }
+
+  if api_config.enable_change_tracking()
+    && (!matches!(prefix.entity, Entity::Table)
+      || tenant_scoped
+      || table_name
+        .database_schema
+        .as_ref()
+        .is_some_and(|db| db != "main"))
+  {
+    // Changes are recorded into `_record_changes` of the main database.
+    return Err(invalid_prefixed(
+      &prefix,
+      "Change tracking requires a TABLE in the main database.",
+    ));
+  }
+
   for excluded_column_name in &api_config.excluded_columns {
     let Some(excluded_index) = columns
       .iter()
@@ -159,6 +225,109 @@ pub(crate) async fn validate_record_api_config(
     }
   }
 
+  for column_name in api_config
+    .admin_only_columns
+    .iter()
+    .chain(&api_config.immutable_columns)
+  {
+    if !columns.iter().any(|meta| meta.column.name == *column_name) {
+      return Err(invalid_prefixed(
+        &prefix,
+        format!("Write-protected column '{column_name}' not found."),
+      ));
+    }
+  }
+
+  for (column_name, field) in &api_config.fill_on_create {
+    if !columns.iter().any(|meta| meta.column.name == *column_name) {
+      return Err(invalid_prefixed(
+        &prefix,
+        format!("Filled column '{column_name}' not found."),
+      ));
+    }
+    if AuthContextField::parse(field).is_none() {
+      return Err(invalid_prefixed(
+        &prefix,
+        format!("Column '{column_name}' filled with unsupported value '{field}'."),
+      ));
+    }
+  }
+
+  for (column_name, policy) in &api_config.file_policies {
+    if !columns
+      .iter()
+      .any(|meta| meta.column.name == *column_name && meta.is_file)
+    {
+      return Err(invalid_prefixed(
+        &prefix,
+        format!("File policy for unknown or non-file column '{column_name}'."),
+      ));
+    }
+    if let Some(mime_type) = policy
+      .allowed_mime_types
+      .iter()
+      .find(|mime_type| !mime_type.contains('/'))
+    {
+      return Err(invalid_prefixed(
+        &prefix,
+        format!("Invalid MIME type '{mime_type}' in file policy for '{column_name}'."),
+      ));
+    }
+  }
+
+  for size in &api_config.thumbnail_sizes {
+    if ThumbnailSize::parse(size).is_none() {
+      return Err(invalid_prefixed(
+        &prefix,
+        format!("Invalid thumbnail size '{size}', expected '<width>x<height>'."),
+      ));
+    }
+  }
+
+  if let Some(default_limit) = api_config.default_limit {
+    let hard_limit = api_config.listing_hard_limit.unwrap_or(1024);
+    if default_limit == 0 || default_limit > hard_limit {
+      return Err(invalid_prefixed(
+        &prefix,
+        format!("Default limit must be in [1, {hard_limit}]."),
+      ));
+    }
+  }
+
+  if let Some(ref default_order_by) = api_config.default_order_by {
+    let order: trailbase_qs::Order = default_order_by.parse().map_err(|err| {
+      return invalid_prefixed(&prefix, format!("Invalid default order: {err}"));
+    })?;
+    for (column_name, _) in &order.columns {
+      if column_name.starts_with("_")
+        || api_config.excluded_columns.contains(column_name)
+        || !columns.iter().any(|meta| meta.column.name == *column_name)
+      {
+        return Err(invalid_prefixed(
+          &prefix,
+          format!("Default order by unknown column '{column_name}'."),
+        ));
+      }
+    }
+  }
+
+  for (audience, list) in &api_config.read_excluded_columns {
+    for column_name in &list.columns {
+      if !columns.iter().any(|meta| meta.column.name == *column_name) {
+        return Err(invalid_prefixed(
+          &prefix,
+          format!("Column '{column_name}' excluded from reads for '{audience}' not found."),
+        ));
+      }
+      if *column_name == pk_meta.column.name {
+        return Err(invalid_prefixed(
+          &prefix,
+          format!("PK column '{column_name}' must not be excluded from reads."),
+        ));
+      }
+    }
+  }
+
   for expand in &api_config.expand {
     if expand.starts_with("_") {
       return Err(invalid_prefixed(
@@ -197,7 +366,11 @@ pub(crate) async fn validate_record_api_config(
       ));
     }
 
-    let fq_foreign_table_name = QualifiedName::parse(foreign_table_name)?;
+    // Foreign keys can only reference tables within the same database.
+    let fq_foreign_table_name = QualifiedName {
+      name: QualifiedName::parse(foreign_table_name)?.name,
+      database_schema: table_name.database_schema.clone(),
+    };
     let Some(foreign_table) = metadata.get_table(&fq_foreign_table_name) else {
       return Err(invalid_prefixed(
         &prefix,
@@ -233,15 +406,116 @@ pub(crate) async fn validate_record_api_config(
     };
   }
 
+  // Make sure rules only reference existing columns to surface typos early rather than at request
+  // time.
+  for rule in [
+    &api_config.create_access_rule,
+    &api_config.read_access_rule,
+    &api_config.update_access_rule,
+    &api_config.delete_access_rule,
+    &api_config.schema_access_rule,
+  ]
+  .into_iter()
+  .flatten()
+  {
+    for column_name in referenced_columns(rule, "_ROW_")
+      .into_iter()
+      .chain(referenced_columns(rule, "_REQ_"))
+    {
+      if !columns.iter().any(|meta| meta.column.name == column_name) {
+        return Err(invalid_prefixed(
+          &prefix,
+          format!("Access rule '{rule}' references unknown column '{column_name}'."),
+        ));
+      }
+    }
+
+    // Besides `id`, only role checks with literal role names, e.g. `_USER_.has_role('editor')`,
+    // are supported.
+    for field in referenced_columns(&rewrite_role_checks(ConnectionType::Sqlite, rule), "_USER_") {
+      if field != "id" {
+        return Err(invalid_prefixed(
+          &prefix,
+          format!("Access rule '{rule}' references unknown user field '{field}'."),
+        ));
+      }
+    }
+  }
+
+  for (name, cache) in [
+    ("read", &api_config.read_cache),
+    ("list", &api_config.list_cache),
+  ] {
+    if let Some(cache) = cache
+      && (cache.capacity == Some(0) || cache.ttl_sec == Some(0))
+    {
+      return Err(invalid(format!(
+        "API '{api_name}': {name} cache capacity and TTL must be positive."
+      )));
+    }
+  }
+
   return Ok(api_name.to_owned());
 }
 
+/// Validates a saved query, i.e. its access rule and that it's a single read-only statement,
+/// which is valid against the current schema.
+pub(crate) async fn validate_saved_query_config(
+  connection_manager: &ConnectionManager,
+  config: &proto::SavedQueryConfig,
+) -> Result<String, ConfigError> {
+  let Some(ref name) = config.name else {
+    return Err(invalid("Found saved query config entry w/o name."));
+  };
+  validate_record_api_name(name)?;
+
+  let ConnectionEntry { connection, .. } = connection_manager.main_entry();
+  if matches!(connection.connection_type(), ConnectionType::Pg) {
+    return Err(invalid("PG doesn't (yet) support saved queries"));
+  }
+
+  let Some(ref query) = config.query else {
+    return Err(invalid(format!("Saved query '{name}': missing query.")));
+  };
+  let statements =
+    parse_into_statements(query).map_err(|err| invalid(format!("Saved query '{name}': {err}")))?;
+  if !matches!(
+    statements.as_slice(),
+    [sqlite3_parser::ast::Stmt::Select(_)]
+  ) {
+    return Err(invalid(format!(
+      "Saved query '{name}': expected a single SELECT statement."
+    )));
+  }
+
+  // Compiles the query without running it, which catches unknown tables, columns, ... .
+  connection
+    .read_query_rows(format!("EXPLAIN {query}"), ())
+    .await
+    .map_err(|err| invalid(format!("Saved query '{name}': {err}")))?;
+
+  if let Some(ref rule) = config.access_rule {
+    validate_rule(AccessKind::Query, rule)?;
+
+    for field in referenced_columns(&rewrite_role_checks(ConnectionType::Sqlite, rule), "_USER_") {
+      if field != "id" {
+        return Err(invalid(format!(
+          "Saved query '{name}': access rule '{rule}' references unknown user field '{field}'."
+        )));
+      }
+    }
+  }
+
+  return Ok(name.clone());
+}
+
 enum AccessKind {
   Create,
   Read,
   Update,
   Delete,
   Schema,
+  Query,
 }
 
 fn validate_rule(kind: AccessKind, rule: &str) -> Result<(), ConfigError> {
@@ -281,10 +555,21 @@ fn validate_rule(kind: AccessKind, rule: &str) -> Result<(), ConfigError> {
         return Err(invalid("Schema rule cannot reference _REQ_"));
       }
     }
+    AccessKind::Query => {
+      if rule.contains("_ROW_") {
+        return Err(invalid("Query rule cannot reference _ROW_"));
+      }
+      if rule.contains("_REQ_FIELDS_") {
+        return Err(invalid("Query rule cannot reference _REQ_FIELDS_"));
+      }
+    }
   }
 
-  let stmt = parse_into_statement(&format!("SELECT {rule}"))
-    .map_err(|err| invalid(format!("'{rule}' not a valid SQL expression: {err}")))?;
+  let stmt = parse_into_statement(&format!(
+    "SELECT {}",
+    rewrite_role_checks(ConnectionType::Sqlite, rule)
+  ))
+  .map_err(|err| invalid(format!("'{rule}' not a valid SQL expression: {err}")))?;
 
   let Some(sqlite3_parser::ast::Stmt::Select(select)) = stmt else {
     return Err(invalid(format!(
@@ -311,6 +596,30 @@ fn validate_rule(kind: AccessKind, rule: &str) -> Result<(), ConfigError> {
   return Ok(());
 }
 
+/// Returns the names referenced as `<magic>.<name>` in `rule`, e.g. "owner" for `_ROW_.owner`.
+pub(crate) fn referenced_columns(rule: &str, magic: &str) -> Vec<String> {
+  let is_ident_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+  let prefix = format!("{magic}.");
+
+  return rule
+    .match_indices(&prefix)
+    .filter(|(index, _)| !rule[..*index].ends_with(is_ident_char))
+    .filter_map(|(index, _)| {
+      let rest = rule[index + prefix.len()..].trim_start();
+      return match rest.chars().next()? {
+        quote @ ('"' | '`' | '[') => {
+          let end = if quote == '[' { ']' } else { quote };
+          rest[1..].split(end).next().map(|name| name.to_string())
+        }
+        _ => {
+          let len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+          (len > 0).then(|| rest[..len].to_string())
+        }
+      };
+    })
+    .collect();
+}
+
 fn validate_expr_recursively(expr: &sqlite3_parser::ast::Expr) -> Result<(), ConfigError> {
   use sqlite3_parser::ast;
 
@@ -407,7 +716,33 @@ mod tests {
     )
     .unwrap();
 
+    validate_rule(
+      AccessKind::Read,
+      "_USER_.has_role('editor') OR _ROW_.userid = _USER_.id",
+    )
+    .unwrap();
+
     assert!(validate_rule(AccessKind::Update, "'field' IN _REQ_FIELDS_").is_ok());
     assert!(validate_rule(AccessKind::Update, "field IN _REQ_FIELDS_").is_err());
   }
+
+  #[test]
+  fn test_referenced_columns() {
+    assert_eq!(
+      vec!["owner", "room"],
+      referenced_columns(
+        r#"_ROW_.owner = _USER_.id AND EXISTS(SELECT 1 FROM m WHERE m.room = _ROW_."room")"#,
+        "_ROW_"
+      )
+    );
+    assert_eq!(
+      vec!["id"],
+      referenced_columns("_USER_.id IS NOT NULL", "_USER_")
+    );
+    assert_eq!(
+      vec!["index"],
+      referenced_columns("'a' IN _REQ_FIELDS_ AND _REQ_.[index] > 0", "_REQ_")
+    );
+    assert!(referenced_columns("MY_ROW_.owner = 1", "_ROW_").is_empty());
+  }
 }
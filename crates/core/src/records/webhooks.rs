@@ -0,0 +1,388 @@
+use base64::prelude::*;
+use const_format::formatcp;
+use log::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use trailbase_schema::QualifiedName;
+use trailbase_sqlite::{Connection, Value, params};
+
+use crate::app_state::AppState;
+use crate::auth::webhooks::post_signed;
+use crate::config::proto::WebhookConfig;
+use crate::constants::WEBHOOK_QUEUE_TABLE;
+
+/// Record changes, which can be delivered to webhooks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RecordOperation {
+  Insert,
+  Update,
+  Delete,
+}
+
+impl RecordOperation {
+  pub(crate) const ALL: [RecordOperation; 3] = [
+    RecordOperation::Insert,
+    RecordOperation::Update,
+    RecordOperation::Delete,
+  ];
+
+  pub(crate) fn name(self) -> &'static str {
+    return match self {
+      Self::Insert => "insert",
+      Self::Update => "update",
+      Self::Delete => "delete",
+    };
+  }
+}
+
+#[repr(i64)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum QueueStatus {
+  Pending = 0,
+  Failed = 2,
+}
+
+const MAX_ATTEMPTS: i64 = 8;
+/// Backoff after the first failed attempt in seconds. Doubles with every further attempt.
+const INITIAL_BACKOFF_SEC: i64 = 10;
+/// Claimed events are considered abandoned, e.g. due to a restart, once the lease expires.
+const LEASE_SEC: i64 = 120;
+const BATCH_SIZE: i64 = 64;
+
+/// Enqueues a record change for all subscribed webhooks and kicks off delivery in the background.
+///
+/// Called after the change has been applied. Failing to enqueue is only logged, i.e. it never
+/// fails the triggering request.
+pub(crate) async fn enqueue_record_event(
+  state: &AppState,
+  table: &QualifiedName,
+  operation: RecordOperation,
+  record_id: &Value,
+) {
+  let table_name = match table.database_schema {
+    Some(ref db) if db != "main" => format!("{db}.{}", table.name),
+    _ => table.name.clone(),
+  };
+
+  let webhooks: HashMap<String, WebhookConfig> = state.access_config(|c| {
+    return c
+      .webhooks
+      .iter()
+      .filter(|(_, webhook)| subscribed(webhook, &table_name, operation))
+      .map(|(name, webhook)| (name.clone(), webhook.clone()))
+      .collect();
+  });
+  if webhooks.is_empty() {
+    return;
+  }
+
+  let event = format!("record.{}", operation.name());
+  let payload = serde_json::json!({
+    "event": event,
+    "table": table_name,
+    "record_id": record_id_to_json(record_id),
+    "timestamp": chrono::Utc::now().timestamp(),
+  })
+  .to_string();
+
+  const QUERY: &str =
+    formatcp!("INSERT INTO '{WEBHOOK_QUEUE_TABLE}' (webhook, event, payload) VALUES ($1, $2, $3)");
+  for name in webhooks.keys() {
+    if let Err(err) = state
+      .user_conn()
+      .execute(QUERY, params!(name.clone(), event.clone(), payload.clone()))
+      .await
+    {
+      warn!("Failed to enqueue '{event}' for webhook {name}: {err}");
+    }
+  }
+
+  let conn = state.user_conn().clone();
+  tokio::spawn(async move {
+    if let Err(err) = deliver_pending(&conn, &webhooks).await {
+      warn!("Webhook delivery failed: {err}");
+    }
+  });
+}
+
+#[derive(Debug, Deserialize)]
+struct QueuedEvent {
+  id: i64,
+  webhook: String,
+  event: String,
+  payload: String,
+  attempts: i64,
+}
+
+/// Claims and delivers all due events. Events whose delivery fails are rescheduled with
+/// exponential backoff until they run out of attempts.
+///
+/// Invoked right after enqueuing and periodically by the `WEBHOOK_DELIVERIES` system job, which
+/// also picks up retries and events left over from before a restart. Returns the number of
+/// claimed events.
+pub(crate) async fn deliver_pending(
+  conn: &Connection,
+  webhooks: &HashMap<String, WebhookConfig>,
+) -> Result<usize, trailbase_sqlite::Error> {
+  // Claiming bumps `next_attempt`, which keeps concurrent runs from delivering the same event.
+  const CLAIM_QUERY: &str = formatcp!(
+    "\
+      UPDATE '{WEBHOOK_QUEUE_TABLE}' \
+      SET attempts = attempts + 1, next_attempt = UNIXEPOCH() + {LEASE_SEC} \
+      WHERE id IN ( \
+        SELECT id FROM '{WEBHOOK_QUEUE_TABLE}' \
+        WHERE status = {pending} AND next_attempt <= UNIXEPOCH() \
+        ORDER BY id LIMIT {BATCH_SIZE} \
+      ) \
+      RETURNING id, webhook, event, payload, attempts \
+    ",
+    pending = QueueStatus::Pending as i64,
+  );
+
+  let events: Vec<QueuedEvent> = conn.write_query_values(CLAIM_QUERY, ()).await?;
+  let count = events.len();
+
+  let results = futures_util::future::join_all(events.into_iter().map(|event| async move {
+    let result = match webhooks.get(&event.webhook) {
+      Some(config) => {
+        post_signed(
+          config.url.as_deref(),
+          config.secret.as_deref(),
+          &event.event,
+          &event.payload,
+        )
+        .await
+      }
+      None => Err(format!("Webhook '{}' no longer configured", event.webhook)),
+    };
+    return (event, result);
+  }))
+  .await;
+
+  for (event, result) in results {
+    match result {
+      Ok(()) => {
+        const QUERY: &str = formatcp!("DELETE FROM '{WEBHOOK_QUEUE_TABLE}' WHERE id = $1");
+        conn.execute(QUERY, params!(event.id)).await?;
+      }
+      Err(err) if event.attempts < MAX_ATTEMPTS && webhooks.contains_key(&event.webhook) => {
+        const QUERY: &str = formatcp!(
+          "UPDATE '{WEBHOOK_QUEUE_TABLE}' SET next_attempt = UNIXEPOCH() + $2, last_error = $3 WHERE id = $1"
+        );
+        let backoff = INITIAL_BACKOFF_SEC << (event.attempts - 1).clamp(0, 16);
+        conn.execute(QUERY, params!(event.id, backoff, err)).await?;
+      }
+      Err(err) => {
+        warn!(
+          "Giving up on webhook delivery {} to '{}' after {} attempts: {err}",
+          event.id, event.webhook, event.attempts
+        );
+
+        const QUERY: &str = formatcp!(
+          "UPDATE '{WEBHOOK_QUEUE_TABLE}' SET status = $2, last_error = $3 WHERE id = $1"
+        );
+        conn
+          .execute(QUERY, params!(event.id, QueueStatus::Failed as i64, err))
+          .await?;
+      }
+    }
+  }
+
+  return Ok(count);
+}
+
+/// Resets a queued event, e.g. a failed one, for immediate redelivery. Returns false if no such
+/// event exists.
+pub(crate) async fn retry(state: &AppState, id: i64) -> Result<bool, trailbase_sqlite::Error> {
+  const QUERY: &str = formatcp!(
+    "UPDATE '{WEBHOOK_QUEUE_TABLE}' SET status = $2, attempts = 0, next_attempt = UNIXEPOCH() WHERE id = $1"
+  );
+
+  let rows_affected = state
+    .user_conn()
+    .execute(QUERY, params!(id, QueueStatus::Pending as i64))
+    .await?;
+  if rows_affected == 0 {
+    return Ok(false);
+  }
+
+  let conn = state.user_conn().clone();
+  let webhooks = state.access_config(|c| c.webhooks.clone());
+  tokio::spawn(async move {
+    if let Err(err) = deliver_pending(&conn, &webhooks).await {
+      warn!("Webhook delivery failed: {err}");
+    }
+  });
+
+  return Ok(true);
+}
+
+fn subscribed(webhook: &WebhookConfig, table_name: &str, operation: RecordOperation) -> bool {
+  return (webhook.tables.is_empty() || webhook.tables.iter().any(|t| t == table_name))
+    && (webhook.operations.is_empty() || webhook.operations.iter().any(|o| o == operation.name()));
+}
+
+/// Renders record ids the same way the Record APIs do, i.e. UUIDs as url-safe base64.
+fn record_id_to_json(value: &Value) -> serde_json::Value {
+  return match value {
+    Value::Integer(i) => serde_json::Value::from(*i),
+    Value::Text(text) => serde_json::Value::String(text.clone()),
+    Value::Blob(blob) => serde_json::Value::String(BASE64_URL_SAFE.encode(blob)),
+    Value::Real(_) | Value::Null => serde_json::Value::Null,
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::{HeaderMap, StatusCode};
+  use axum::{Router, extract::State, routing::post};
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::app_state::{TestStateOptions, test_state};
+
+  type Received = Arc<parking_lot::Mutex<Vec<(HeaderMap, String)>>>;
+
+  async fn receive(
+    State(received): State<Received>,
+    headers: HeaderMap,
+    body: String,
+  ) -> StatusCode {
+    received.lock().push((headers, body));
+    return StatusCode::OK;
+  }
+
+  async fn queued(state: &AppState) -> Vec<(i64, i64, Option<String>)> {
+    return state
+      .user_conn()
+      .read_query_rows(
+        format!("SELECT id, status, last_error FROM '{WEBHOOK_QUEUE_TABLE}' ORDER BY id"),
+        (),
+      )
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|row| {
+        (
+          row.get(0).unwrap(),
+          row.get(1).unwrap(),
+          row.get(2).unwrap(),
+        )
+      })
+      .collect();
+  }
+
+  async fn wait_for<F: AsyncFn() -> bool>(f: F) {
+    for _ in 0..500 {
+      if f().await {
+        return;
+      }
+      tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("timed out");
+  }
+
+  #[tokio::test]
+  async fn test_record_webhooks() {
+    let received = Received::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = Router::new()
+      .route("/hook", post(receive))
+      .with_state(received.clone());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let mut config = crate::app_state::test_config();
+    config.webhooks.insert(
+      "sync".to_string(),
+      WebhookConfig {
+        url: Some(format!("http://{addr}/hook")),
+        secret: Some("secret".to_string()),
+        tables: vec!["article".to_string()],
+        operations: vec!["insert".to_string()],
+      },
+    );
+    config.webhooks.insert(
+      "unreachable".to_string(),
+      WebhookConfig {
+        // Port 9 (discard) is expected to refuse connections.
+        url: Some("http://127.0.0.1:9/hook".to_string()),
+        operations: vec!["delete".to_string()],
+        ..Default::default()
+      },
+    );
+
+    let state = test_state(Some(TestStateOptions {
+      config: Some(config),
+      ..Default::default()
+    }))
+    .await
+    .unwrap();
+
+    let article = QualifiedName::parse("article").unwrap();
+
+    // Delivered right away and removed from the queue.
+    enqueue_record_event(
+      &state,
+      &article,
+      RecordOperation::Insert,
+      &Value::Integer(5),
+    )
+    .await;
+    wait_for(async || !received.lock().is_empty()).await;
+    wait_for(async || queued(&state).await.is_empty()).await;
+
+    let (headers, body) = received.lock().pop().unwrap();
+    assert_eq!(headers.get("X-Webhook-Event").unwrap(), "record.insert");
+    assert!(headers.get("X-Webhook-Signature").is_some());
+    let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(payload["table"], "article");
+    assert_eq!(payload["record_id"], 5);
+
+    // Not subscribed.
+    enqueue_record_event(
+      &state,
+      &article,
+      RecordOperation::Update,
+      &Value::Integer(5),
+    )
+    .await;
+    assert!(queued(&state).await.is_empty());
+
+    // Failed deliveries are rescheduled until they run out of attempts.
+    enqueue_record_event(
+      &state,
+      &article,
+      RecordOperation::Delete,
+      &Value::Integer(5),
+    )
+    .await;
+    wait_for(async || {
+      let queued = queued(&state).await;
+      return queued.len() == 1 && queued[0].2.is_some();
+    })
+    .await;
+    let id = queued(&state).await[0].0;
+
+    state
+      .user_conn()
+      .execute(
+        format!(
+          "UPDATE '{WEBHOOK_QUEUE_TABLE}' SET attempts = {}, next_attempt = 0",
+          MAX_ATTEMPTS - 1
+        ),
+        (),
+      )
+      .await
+      .unwrap();
+    let webhooks = state.access_config(|c| c.webhooks.clone());
+    assert_eq!(
+      deliver_pending(state.user_conn(), &webhooks).await.unwrap(),
+      1
+    );
+    assert_eq!(queued(&state).await[0].1, QueueStatus::Failed as i64);
+
+    assert!(retry(&state, id).await.unwrap());
+    assert!(!retry(&state, id + 100).await.unwrap());
+  }
+}
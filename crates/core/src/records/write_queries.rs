@@ -99,6 +99,7 @@ impl WriteQuery {
       table_name,
       column_names: &column_names,
       pk_column_name: &pk_column_name,
+      increment: false,
       returning: Some(row_id_column2(connection_type)),
     }
     .render()
@@ -113,6 +114,44 @@ impl WriteQuery {
     ));
   }
 
+  /// Atomically adds the given params to the current column values, i.e. `SET col = col + :col`.
+  /// Returns the row id followed by the updated values.
+  pub fn new_increment(
+    connection_type: ConnectionType,
+    table_name: &QualifiedNameEscaped,
+    params: Params,
+  ) -> Result<Self, RecordError> {
+    let Params::Update {
+      named_params,
+      files,
+      column_names,
+      column_indexes: _,
+      pk_column_name,
+    } = params
+    else {
+      return Err(RecordError::Internal("not an update".into()));
+    };
+
+    if !files.is_empty() {
+      return Err(RecordError::BadRequest("Cannot increment files"));
+    }
+
+    let query = UpdateRecordQueryTemplate {
+      table_name,
+      column_names: &column_names,
+      pk_column_name: &pk_column_name,
+      increment: true,
+      returning: Some(row_id_column2(connection_type)),
+    }
+    .render()
+    .map_err(|err| RecordError::Internal(err.into()))?;
+
+    return Ok(Self::Update {
+      query,
+      named_params,
+    });
+  }
+
   pub fn new_delete(
     connection_type: ConnectionType,
     table_name: &QualifiedNameEscaped,
@@ -128,7 +167,7 @@ impl WriteQuery {
     });
   }
 
-  async fn apply_async(
+  pub(super) async fn apply_async(
     self,
     conn: &trailbase_sqlite::Connection,
   ) -> Result<WriteQueryResult, trailbase_sqlite::Error> {
@@ -287,6 +326,24 @@ pub(crate) async fn run_queries(
   return Ok(result.into_iter().filter_map(|r| r.pk_value).collect());
 }
 
+/// Applies the given queries within a transaction, which is rolled back afterwards. Useful for
+/// validating writes against the database's constraints without persisting them.
+pub(crate) async fn dry_run_queries(
+  conn: &Connection,
+  queries: Vec<WriteQuery>,
+) -> Result<(), RecordError> {
+  conn
+    .transaction(move |mut tx| -> Result<_, trailbase_sqlite::Error> {
+      for query in queries {
+        query.apply_sync(&mut tx)?;
+      }
+      return tx.rollback();
+    })
+    .await?;
+
+  return Ok(());
+}
+
 pub(crate) async fn run_insert_or_replace_query(
   conn: &Connection,
   objectstore: &Arc<dyn ObjectStore>,
@@ -361,6 +418,27 @@ pub(crate) async fn run_update_query(
   return Ok(());
 }
 
+/// Returns the updated values in param order or None if the record doesn't exist.
+pub(crate) async fn run_increment_query(
+  conn: &Connection,
+  table_name: &QualifiedNameEscaped,
+  params: Params,
+) -> Result<Option<Vec<Value>>, RecordError> {
+  let WriteQuery::Update {
+    query,
+    named_params,
+  } = WriteQuery::new_increment(conn.connection_type(), table_name, params)?
+  else {
+    return Err(RecordError::Internal("not an update".into()));
+  };
+
+  let Some(row) = conn.write_query_row(query, named_params).await? else {
+    return Ok(None);
+  };
+
+  return Ok(Some(row.0.into_iter().skip(1).collect()));
+}
+
 pub(crate) async fn run_delete_query(
   conn: &Connection,
   objectstore: &Arc<dyn ObjectStore>,
@@ -388,6 +466,7 @@ struct UpdateRecordQueryTemplate<'a> {
   table_name: &'a QualifiedNameEscaped,
   column_names: &'a [String],
   pk_column_name: &'a str,
+  increment: bool,
   returning: Option<&'a str>,
 }
 
@@ -465,4 +544,31 @@ mod tests {
       sanitize_template(&query);
     }
   }
+
+  #[test]
+  fn test_update_record_template() {
+    for increment in [false, true] {
+      let query = UpdateRecordQueryTemplate {
+        table_name: &QualifiedName::parse("table").unwrap().into(),
+        column_names: &["index".to_string(), "count".to_string()],
+        pk_column_name: "id",
+        increment,
+        returning: Some("_rowid_"),
+      }
+      .render()
+      .unwrap();
+
+      sanitize_template(&query);
+      assert_eq!(
+        increment,
+        query.contains(r#""count" = "count" + :count"#),
+        "{query}"
+      );
+      assert_eq!(
+        increment,
+        query.ends_with(r#"RETURNING "_rowid_", "index", "count""#),
+        "{query}"
+      );
+    }
+  }
 }
@@ -0,0 +1,589 @@
+//! Continuous replication of the main database to the object store, similar to Litestream.
+//!
+//! Replication happens in generations. Each generation starts with a snapshot of the database
+//! followed by segments of WAL frames, which are shipped as transactions get committed. A new
+//! generation is started periodically or whenever continuity is lost, e.g. when the WAL was
+//! checkpointed by another process. To guarantee continuity, auto-checkpointing is disabled on
+//! the writer connection and the replicator checkpoints the WAL itself after reading it.
+//!
+//! Layout in the object store:
+//!
+//!   <prefix>/generations/<generation>/snapshot-<unix ms>.db
+//!   <prefix>/generations/<generation>/wal/<index>-<unix ms>.wal
+
+mod wal;
+
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use log::*;
+use object_store::ObjectStore;
+use object_store::path::Path as ObjectPath;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use trailbase_sqlite::{Connection, ConnectionType};
+
+use crate::app_state::AppState;
+use crate::config::proto::ReplicationConfig;
+use crate::metrics::{ObjectStoreOp, record_object_store_op};
+use wal::{WalPosition, WalRead, apply_segment, read_wal};
+
+#[derive(Debug, Error)]
+pub enum ReplicationError {
+  #[error("IO: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("Rusqlite: {0}")]
+  Rusqlite(#[from] rusqlite::Error),
+  #[error("Lock: {0}")]
+  Lock(#[from] trailbase_sqlite::LockError),
+  #[error("ObjectStore: {0}")]
+  ObjectStore(#[from] object_store::Error),
+  #[error("Join: {0}")]
+  Join(#[from] tokio::task::JoinError),
+  #[error("Not supported: {0}")]
+  NotSupported(&'static str),
+  #[error("Not found: {0}")]
+  NotFound(String),
+}
+
+const DEFAULT_PREFIX: &str = "replica";
+const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+const DEFAULT_RETENTION: usize = 2;
+const DEFAULT_CHECKPOINT_PAGES: usize = 1000;
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Starts replicating the main database, if configured.
+///
+/// NOTE: Config changes only take effect after a restart.
+pub(crate) fn start(state: &AppState) -> Result<(), ReplicationError> {
+  let Some(config) = state.get_config().replication else {
+    return Ok(());
+  };
+  if !matches!(state.conn().connection_type(), ConnectionType::Sqlite) {
+    return Err(ReplicationError::NotSupported(
+      "replication requires SQLite",
+    ));
+  }
+
+  let replicator = Replicator::new(
+    state.conn().clone(),
+    &state.data_dir().main_db_path(),
+    state.objectstore(),
+    Options::from(&config),
+  );
+  tokio::spawn(replicator.run());
+
+  return Ok(());
+}
+
+struct Options {
+  prefix: ObjectPath,
+  sync_interval: Duration,
+  snapshot_interval: Duration,
+  retention: usize,
+  checkpoint_pages: usize,
+}
+
+impl From<&ReplicationConfig> for Options {
+  fn from(config: &ReplicationConfig) -> Self {
+    return Self {
+      prefix: ObjectPath::from(config.prefix.as_deref().unwrap_or(DEFAULT_PREFIX)),
+      sync_interval: config
+        .sync_interval_ms
+        .map_or(DEFAULT_SYNC_INTERVAL, Duration::from_millis),
+      snapshot_interval: config
+        .snapshot_interval_sec
+        .map_or(DEFAULT_SNAPSHOT_INTERVAL, Duration::from_secs),
+      retention: config
+        .retention
+        .map_or(DEFAULT_RETENTION, |r| r as usize)
+        .max(1),
+      checkpoint_pages: config
+        .checkpoint_pages
+        .map_or(DEFAULT_CHECKPOINT_PAGES, |p| p as usize),
+    };
+  }
+}
+
+struct Generation {
+  id: String,
+  started: Instant,
+  /// Position in the WAL up to which frames have been read. `None` after the WAL was truncated.
+  position: Option<WalPosition>,
+  next_index: u64,
+  /// Segments read but not yet uploaded, in order.
+  pending: VecDeque<(ObjectPath, Vec<u8>)>,
+}
+
+struct Replicator {
+  conn: Connection,
+  wal_path: PathBuf,
+  snapshot_path: PathBuf,
+  store: Arc<dyn ObjectStore>,
+  options: Options,
+  generation: Option<Generation>,
+}
+
+impl Replicator {
+  fn new(conn: Connection, db_path: &Path, store: Arc<dyn ObjectStore>, options: Options) -> Self {
+    let with_suffix = |suffix: &str| {
+      let mut path = db_path.as_os_str().to_owned();
+      path.push(suffix);
+      return PathBuf::from(path);
+    };
+
+    return Self {
+      conn,
+      wal_path: with_suffix("-wal"),
+      snapshot_path: with_suffix("-replica-snapshot"),
+      store,
+      options,
+      generation: None,
+    };
+  }
+
+  async fn run(mut self) {
+    loop {
+      if let Err(err) = self.sync().await {
+        warn!("Replication failed: {err}");
+      }
+      tokio::time::sleep(self.options.sync_interval).await;
+    }
+  }
+
+  /// Ships newly committed WAL frames, starting a new generation first if needed.
+  async fn sync(&mut self) -> Result<(), ReplicationError> {
+    let expired = self
+      .generation
+      .as_ref()
+      .is_none_or(|g| g.started.elapsed() >= self.options.snapshot_interval);
+    if expired {
+      self.start_generation().await?;
+    }
+
+    let Some(generation) = self.generation.as_mut() else {
+      return Ok(());
+    };
+
+    let conn = self.conn.clone();
+    let wal_path = self.wal_path.clone();
+    let position = generation.position;
+    let checkpoint_pages = self.options.checkpoint_pages;
+
+    // Reading and checkpointing happens under the write lock, i.e. no transactions are committed
+    // in-between.
+    let (read, truncated) =
+      tokio::task::spawn_blocking(move || -> Result<(WalRead, bool), ReplicationError> {
+        let lock = conn.try_write_arc_lock_for(LOCK_TIMEOUT)?;
+        let read = read_wal(&wal_path, position.as_ref())?;
+
+        let truncated = match read {
+          WalRead::Frames { ref position, .. } if position.frames() >= checkpoint_pages => {
+            let busy: i64 = lock.query_row("PRAGMA wal_checkpoint(TRUNCATE)", (), |row| {
+              return row.get(0);
+            })?;
+            busy == 0
+          }
+          _ => false,
+        };
+
+        return Ok((read, truncated));
+      })
+      .await??;
+
+    match read {
+      WalRead::Reset => {
+        info!("Replication continuity lost. Starting new generation");
+        self.generation = None;
+        return Ok(());
+      }
+      WalRead::Empty => {}
+      WalRead::Frames {
+        header,
+        frames,
+        position,
+      } => {
+        if !frames.is_empty() {
+          let mut segment = header.bytes().to_vec();
+          segment.extend_from_slice(&frames);
+
+          let path = generation_path(&self.options.prefix, &generation.id)
+            .child("wal")
+            .child(format!(
+              "{:016x}-{}.wal",
+              generation.next_index,
+              Utc::now().timestamp_millis()
+            ));
+          generation.pending.push_back((path, segment));
+          generation.next_index += 1;
+        }
+
+        generation.position = if truncated { None } else { Some(position) };
+      }
+    }
+
+    while let Some((path, segment)) = generation.pending.front() {
+      record_object_store_op(ObjectStoreOp::Put);
+      self.store.put(path, segment.clone().into()).await?;
+      generation.pending.pop_front();
+    }
+
+    return Ok(());
+  }
+
+  /// Uploads a snapshot consistent with the current WAL position.
+  async fn start_generation(&mut self) -> Result<(), ReplicationError> {
+    let conn = self.conn.clone();
+    let wal_path = self.wal_path.clone();
+    let snapshot_path = self.snapshot_path.clone();
+
+    let position =
+      tokio::task::spawn_blocking(move || -> Result<Option<WalPosition>, ReplicationError> {
+        let lock = conn.try_write_arc_lock_for(LOCK_TIMEOUT)?;
+
+        // Continuity requires the replicator to be in control of checkpointing.
+        lock.query_row("PRAGMA wal_autocheckpoint = 0", (), |_row| Ok(()))?;
+
+        if snapshot_path.exists() {
+          std::fs::remove_file(&snapshot_path)?;
+        }
+        lock.backup(rusqlite::MAIN_DB, &snapshot_path, None)?;
+
+        return Ok(match read_wal(&wal_path, None)? {
+          WalRead::Frames { position, .. } => Some(position),
+          WalRead::Empty | WalRead::Reset => None,
+        });
+      })
+      .await??;
+
+    let id = uuid::Uuid::now_v7().simple().to_string();
+    let snapshot = tokio::fs::read(&self.snapshot_path).await?;
+    let _ = tokio::fs::remove_file(&self.snapshot_path).await;
+
+    self
+      .store
+      .put(
+        &generation_path(&self.options.prefix, &id)
+          .child(format!("snapshot-{}.db", Utc::now().timestamp_millis())),
+        snapshot.into(),
+      )
+      .await?;
+
+    info!("Started replication generation: {id}");
+    self.generation = Some(Generation {
+      id,
+      started: Instant::now(),
+      position,
+      next_index: 0,
+      pending: VecDeque::new(),
+    });
+
+    if let Err(err) = self.apply_retention().await {
+      warn!("Failed to delete expired replication generations: {err}");
+    }
+
+    return Ok(());
+  }
+
+  async fn apply_retention(&self) -> Result<(), ReplicationError> {
+    let generations = list_generations_impl(&self.store, &self.options.prefix).await?;
+    for generation in generations.iter().rev().skip(self.options.retention) {
+      let prefix = generation_path(&self.options.prefix, &generation.id);
+      let mut objects = self.store.list(Some(&prefix));
+      while let Some(meta) = objects.next().await {
+        record_object_store_op(ObjectStoreOp::Delete);
+        self.store.delete(&meta?.location).await?;
+      }
+      debug!("Deleted replication generation: {}", generation.id);
+    }
+    return Ok(());
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplicaGeneration {
+  pub id: String,
+  /// Unix timestamp in milliseconds of the generation's snapshot, if any.
+  pub snapshot: Option<i64>,
+  /// Unix timestamp in milliseconds of the latest WAL segment, if any.
+  pub latest: Option<i64>,
+  pub segments: usize,
+}
+
+#[derive(Debug)]
+pub struct RestoredReplica {
+  pub generation: String,
+  /// Unix timestamp in milliseconds of the latest applied snapshot or WAL segment.
+  pub timestamp: i64,
+}
+
+#[derive(Default)]
+struct GenerationObjects {
+  snapshot: Option<(i64, ObjectPath)>,
+  segments: BTreeMap<u64, (i64, ObjectPath)>,
+}
+
+/// Lists replication generations, oldest first.
+pub async fn list_replica_generations(
+  state: &AppState,
+) -> Result<Vec<ReplicaGeneration>, ReplicationError> {
+  let options = Options::from(&state.get_config().replication.unwrap_or_default());
+  return list_generations_impl(&state.objectstore(), &options.prefix).await;
+}
+
+/// Restores the replica to `output` as of the given point in time or the latest available state.
+///
+/// NOTE: WAL segments are shipped periodically, thus transactions committed shortly before the
+/// given point in time may not be included.
+pub async fn restore_replica(
+  state: &AppState,
+  timestamp: Option<DateTime<Utc>>,
+  output: &Path,
+) -> Result<RestoredReplica, ReplicationError> {
+  let options = Options::from(&state.get_config().replication.unwrap_or_default());
+  return restore_replica_impl(&state.objectstore(), &options.prefix, timestamp, output).await;
+}
+
+async fn list_generations_impl(
+  store: &Arc<dyn ObjectStore>,
+  prefix: &ObjectPath,
+) -> Result<Vec<ReplicaGeneration>, ReplicationError> {
+  return Ok(
+    list_generation_objects(store, prefix)
+      .await?
+      .into_iter()
+      .map(|(id, objects)| ReplicaGeneration {
+        id,
+        snapshot: objects.snapshot.as_ref().map(|(ts, _)| *ts),
+        latest: objects.segments.values().map(|(ts, _)| *ts).max(),
+        segments: objects.segments.len(),
+      })
+      .collect(),
+  );
+}
+
+async fn list_generation_objects(
+  store: &Arc<dyn ObjectStore>,
+  prefix: &ObjectPath,
+) -> Result<BTreeMap<String, GenerationObjects>, ReplicationError> {
+  let generations_prefix = prefix.child("generations");
+  let mut generations: BTreeMap<String, GenerationObjects> = BTreeMap::new();
+
+  let mut objects = store.list(Some(&generations_prefix));
+  while let Some(meta) = objects.next().await {
+    let location = meta?.location;
+    let Some(parts) = location.prefix_match(&generations_prefix) else {
+      continue;
+    };
+    let parts: Vec<String> = parts.map(|p| p.as_ref().to_string()).collect();
+
+    match parts.as_slice() {
+      [id, name] => {
+        if let Some(ts) = name
+          .strip_prefix("snapshot-")
+          .and_then(|n| n.strip_suffix(".db"))
+          .and_then(|ts| ts.parse::<i64>().ok())
+        {
+          generations.entry(id.clone()).or_default().snapshot = Some((ts, location.clone()));
+        }
+      }
+      [id, dir, name] if dir == "wal" => {
+        let Some((index, ts)) = name.strip_suffix(".wal").and_then(|n| n.split_once('-')) else {
+          continue;
+        };
+        if let (Ok(index), Ok(ts)) = (u64::from_str_radix(index, 16), ts.parse::<i64>()) {
+          generations
+            .entry(id.clone())
+            .or_default()
+            .segments
+            .insert(index, (ts, location.clone()));
+        }
+      }
+      _ => {}
+    }
+  }
+
+  return Ok(generations);
+}
+
+async fn restore_replica_impl(
+  store: &Arc<dyn ObjectStore>,
+  prefix: &ObjectPath,
+  timestamp: Option<DateTime<Utc>>,
+  output: &Path,
+) -> Result<RestoredReplica, ReplicationError> {
+  if tokio::fs::try_exists(output).await? {
+    return Err(ReplicationError::Io(std::io::Error::new(
+      std::io::ErrorKind::AlreadyExists,
+      format!("{output:?} already exists"),
+    )));
+  }
+
+  let target = timestamp.map_or(i64::MAX, |ts| ts.timestamp_millis());
+
+  // Latest generation with a snapshot taken before the target.
+  let Some((id, objects)) = list_generation_objects(store, prefix)
+    .await?
+    .into_iter()
+    .rev()
+    .find(|(_, objects)| matches!(objects.snapshot, Some((ts, _)) if ts <= target))
+  else {
+    return Err(ReplicationError::NotFound(
+      "no snapshot before the given point in time".to_string(),
+    ));
+  };
+
+  let Some((mut restored, snapshot_path)) = objects.snapshot else {
+    unreachable!("filtered above");
+  };
+  record_object_store_op(ObjectStoreOp::Get);
+  let snapshot = store.get(&snapshot_path).await?.bytes().await?;
+
+  let mut segments: Vec<Vec<u8>> = vec![];
+  for (expected, (index, (ts, path))) in objects.segments.into_iter().enumerate() {
+    if ts > target {
+      break;
+    }
+    if index != expected as u64 {
+      warn!("Missing WAL segment {expected} in generation {id}. Stopping restore early");
+      break;
+    }
+    record_object_store_op(ObjectStoreOp::Get);
+    segments.push(store.get(&path).await?.bytes().await?.to_vec());
+    restored = ts;
+  }
+
+  let output = output.to_path_buf();
+  tokio::task::spawn_blocking(move || -> Result<(), ReplicationError> {
+    std::fs::write(&output, &snapshot)?;
+
+    let mut db = std::fs::OpenOptions::new().write(true).open(&output)?;
+    for segment in segments {
+      apply_segment(&mut db, &segment)?;
+    }
+    db.sync_all()?;
+    return Ok(());
+  })
+  .await??;
+
+  return Ok(RestoredReplica {
+    generation: id,
+    timestamp: restored,
+  });
+}
+
+fn generation_path(prefix: &ObjectPath, id: &str) -> ObjectPath {
+  return prefix.child("generations").child(id);
+}
+
+#[cfg(test)]
+mod tests {
+  use object_store::memory::InMemory;
+
+  use super::*;
+
+  fn open(path: &Path) -> Connection {
+    let path = path.to_path_buf();
+    return Connection::with_opts(
+      move || -> Result<_, trailbase_sqlite::Error> {
+        let conn = rusqlite::Connection::open(&path)?;
+        conn.query_row("PRAGMA journal_mode = WAL", (), |_row| Ok(()))?;
+        return Ok(conn);
+      },
+      Default::default(),
+    )
+    .unwrap();
+  }
+
+  fn count(path: &Path) -> i64 {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    return conn
+      .query_row("SELECT COUNT(*) FROM item", (), |row| row.get(0))
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_replicate_and_restore() {
+    let temp_dir = temp_dir::TempDir::new().unwrap();
+    let db_path = temp_dir.child("main.db");
+    let conn = open(&db_path);
+    conn
+      .execute_batch("CREATE TABLE item (id INTEGER PRIMARY KEY, data BLOB) STRICT")
+      .await
+      .unwrap();
+
+    let insert = async |n: usize| {
+      for _ in 0..n {
+        conn
+          .execute("INSERT INTO item (data) VALUES (randomblob(2000))", ())
+          .await
+          .unwrap();
+      }
+    };
+
+    let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+    let prefix = ObjectPath::from("replica");
+    let mut replicator = Replicator::new(
+      conn.clone(),
+      &db_path,
+      store.clone(),
+      Options {
+        prefix: prefix.clone(),
+        sync_interval: Duration::from_millis(10),
+        snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+        retention: 1,
+        checkpoint_pages: 20,
+      },
+    );
+
+    insert(3).await;
+    replicator.sync().await.unwrap();
+
+    insert(5).await;
+    replicator.sync().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let point_in_time = Utc::now();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    // Enough to trigger a checkpoint, after which shipping continues in a fresh WAL.
+    insert(20).await;
+    replicator.sync().await.unwrap();
+    assert_eq!(None, replicator.generation.as_ref().unwrap().position);
+    insert(2).await;
+    replicator.sync().await.unwrap();
+
+    let generations = list_generations_impl(&store, &prefix).await.unwrap();
+    assert_eq!(generations.len(), 1);
+    assert_eq!(generations[0].segments, 3);
+
+    let latest = temp_dir.child("latest.db");
+    restore_replica_impl(&store, &prefix, None, &latest)
+      .await
+      .unwrap();
+    assert_eq!(count(&latest), 30);
+
+    let earlier = temp_dir.child("earlier.db");
+    restore_replica_impl(&store, &prefix, Some(point_in_time), &earlier)
+      .await
+      .unwrap();
+    assert_eq!(count(&earlier), 8);
+
+    // Restoring never overwrites existing files.
+    assert!(
+      restore_replica_impl(&store, &prefix, None, &latest)
+        .await
+        .is_err()
+    );
+
+    // Starting a new generation expires the previous one.
+    replicator.generation = None;
+    replicator.sync().await.unwrap();
+    let generations = list_generations_impl(&store, &prefix).await.unwrap();
+    assert_eq!(generations.len(), 1);
+    assert_eq!(generations[0].segments, 0);
+  }
+}
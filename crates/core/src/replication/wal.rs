@@ -0,0 +1,206 @@
+//! Minimal reader for SQLite's write-ahead log (WAL).
+//!
+//! See: https://www.sqlite.org/fileformat2.html#walformat
+
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+pub(crate) const WAL_HEADER_SIZE: usize = 32;
+const FRAME_HEADER_SIZE: usize = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct WalHeader {
+  bytes: [u8; WAL_HEADER_SIZE],
+  big_endian: bool,
+  page_size: usize,
+  salt: [u32; 2],
+  checksum: [u32; 2],
+}
+
+impl WalHeader {
+  /// Parses and validates a WAL header. Returns `None` for invalid or missing headers.
+  pub(crate) fn parse(data: &[u8]) -> Option<Self> {
+    let bytes: [u8; WAL_HEADER_SIZE] = data.get(..WAL_HEADER_SIZE)?.try_into().ok()?;
+    let big_endian = match u32_at(&bytes, 0) {
+      0x377f0682 => false,
+      0x377f0683 => true,
+      _ => return None,
+    };
+
+    let checksum = wal_checksum(big_endian, &bytes[..24], [0, 0]);
+    if checksum != [u32_at(&bytes, 24), u32_at(&bytes, 28)] {
+      return None;
+    }
+
+    return Some(Self {
+      bytes,
+      big_endian,
+      page_size: u32_at(&bytes, 8) as usize,
+      salt: [u32_at(&bytes, 16), u32_at(&bytes, 20)],
+      checksum,
+    });
+  }
+
+  pub(crate) fn bytes(&self) -> &[u8] {
+    return &self.bytes;
+  }
+
+  fn frame_size(&self) -> usize {
+    return FRAME_HEADER_SIZE + self.page_size;
+  }
+}
+
+/// Position in the WAL up to which committed frames have been read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct WalPosition {
+  header: WalHeader,
+  /// Byte offset past the last read commit frame.
+  offset: usize,
+  /// Running checksum at `offset`.
+  checksum: [u32; 2],
+}
+
+impl WalPosition {
+  /// Number of frames in the WAL up to this position.
+  pub(crate) fn frames(&self) -> usize {
+    return (self.offset - WAL_HEADER_SIZE) / self.header.frame_size();
+  }
+}
+
+#[derive(Debug)]
+pub(crate) enum WalRead {
+  /// Committed frames following the previous position, possibly none.
+  Frames {
+    header: WalHeader,
+    frames: Vec<u8>,
+    position: WalPosition,
+  },
+  /// The WAL was restarted since the previous position, e.g. due to a checkpoint. Frames may have
+  /// been missed.
+  Reset,
+  /// There's no WAL content.
+  Empty,
+}
+
+/// Reads the committed frames following `position` or from the start, if `None`.
+///
+/// Frames are only returned up to the last commit frame, i.e. never partial transactions.
+pub(crate) fn read_wal(path: &Path, position: Option<&WalPosition>) -> std::io::Result<WalRead> {
+  let data = match std::fs::read(path) {
+    Ok(data) => data,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+      return Ok(match position {
+        Some(_) => WalRead::Reset,
+        None => WalRead::Empty,
+      });
+    }
+    Err(err) => return Err(err),
+  };
+
+  let Some(header) = WalHeader::parse(&data) else {
+    return Ok(match position {
+      Some(_) => WalRead::Reset,
+      None => WalRead::Empty,
+    });
+  };
+
+  let (start, checksum) = match position {
+    Some(position) if position.header == header => (position.offset, position.checksum),
+    Some(_) => return Ok(WalRead::Reset),
+    None => (WAL_HEADER_SIZE, header.checksum),
+  };
+
+  let frame_size = header.frame_size();
+  let mut offset = start;
+  let mut checksum = checksum;
+  let mut committed = (start, checksum);
+
+  while offset + frame_size <= data.len() {
+    let frame = &data[offset..offset + frame_size];
+
+    // Frames left over from before the WAL was restarted have different salts.
+    if [u32_at(frame, 8), u32_at(frame, 12)] != header.salt {
+      break;
+    }
+
+    checksum = wal_checksum(header.big_endian, &frame[..8], checksum);
+    checksum = wal_checksum(header.big_endian, &frame[FRAME_HEADER_SIZE..], checksum);
+    if checksum != [u32_at(frame, 16), u32_at(frame, 20)] {
+      break;
+    }
+
+    offset += frame_size;
+    if u32_at(frame, 4) != 0 {
+      committed = (offset, checksum);
+    }
+  }
+
+  return Ok(WalRead::Frames {
+    header,
+    frames: data[start..committed.0].to_vec(),
+    position: WalPosition {
+      header,
+      offset: committed.0,
+      checksum: committed.1,
+    },
+  });
+}
+
+/// Applies a segment, i.e. a WAL header followed by frames, directly to a database file.
+pub(crate) fn apply_segment(db: &mut std::fs::File, segment: &[u8]) -> std::io::Result<()> {
+  let Some(header) = WalHeader::parse(segment) else {
+    return Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      "invalid WAL segment header",
+    ));
+  };
+
+  let page_size = header.page_size as u64;
+  for frame in segment[WAL_HEADER_SIZE..].chunks_exact(header.frame_size()) {
+    let page_number = u32_at(frame, 0) as u64;
+    if page_number == 0 {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "invalid WAL frame",
+      ));
+    }
+
+    db.seek(SeekFrom::Start((page_number - 1) * page_size))?;
+    db.write_all(&frame[FRAME_HEADER_SIZE..])?;
+
+    // Commit frames carry the size of the database in pages, e.g. after a VACUUM.
+    let db_size = u32_at(frame, 4) as u64;
+    if db_size != 0 {
+      db.set_len(db_size * page_size)?;
+    }
+  }
+
+  return Ok(());
+}
+
+fn wal_checksum(big_endian: bool, data: &[u8], checksum: [u32; 2]) -> [u32; 2] {
+  let [mut s0, mut s1] = checksum;
+  for chunk in data.chunks_exact(8) {
+    let (x0, x1) = if big_endian {
+      (u32_at(chunk, 0), u32_at(chunk, 4))
+    } else {
+      (
+        u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+        u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+      )
+    };
+    s0 = s0.wrapping_add(x0).wrapping_add(s1);
+    s1 = s1.wrapping_add(x1).wrapping_add(s0);
+  }
+  return [s0, s1];
+}
+
+#[inline]
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+  return u32::from_be_bytes([
+    data[offset],
+    data[offset + 1],
+    data[offset + 2],
+    data[offset + 3],
+  ]);
+}
@@ -16,13 +16,16 @@ use trailbase_schema::{QualifiedName, QualifiedNameEscaped};
 use trailbase_sqlite::{Connection, named_params, params};
 
 use crate::DataDir;
+use crate::backup::{BackupError, create_backups};
 use crate::config::proto::{Config, SystemJob, SystemJobId};
 use crate::connection::{BuildOptions, ConnectionManager};
 use crate::constants::{
-  AUTHORIZATION_CODE_TABLE, DEFAULT_ANONYMOUS_REFRESH_TOKEN_TTL, LOGS_RETENTION_DEFAULT,
-  OTP_CODE_TABLE, SESSION_TABLE, USER_TABLE,
+  AUTHORIZATION_CODE_TABLE, DEFAULT_ANONYMOUS_REFRESH_TOKEN_TTL, IDEMPOTENCY_TABLE, JOB_RUNS_TABLE,
+  JOBS_TABLE, LOGS_RETENTION_DEFAULT, MAGIC_LINK_TABLE, OTP_CODE_TABLE, RECORD_CHANGES_RETENTION,
+  RECORD_CHANGES_TABLE, SAML_REQUEST_TABLE, SESSION_TABLE, USER_TABLE,
 };
 use crate::records::files::{FileDeletionsDb, FileError, delete_pending_files_impl};
+use crate::records::webhooks::deliver_pending;
 
 type CallbackError = Box<dyn std::error::Error + Sync + Send>;
 type CallbackFunction = dyn Fn() -> BoxFuture<'static, Result<(), CallbackError>> + Sync + Send;
@@ -35,6 +38,9 @@ pub struct ExecutionResult {
 
 static JOB_ID_COUNTER: AtomicI32 = AtomicI32::new(1024);
 
+/// Number of runs retained per job in the run history.
+const JOB_RUNS_RETENTION: i64 = 100;
+
 pub trait CallbackResultTrait {
   fn into_result(self) -> Result<(), CallbackError>;
 }
@@ -53,6 +59,8 @@ impl<T: Into<CallbackError>> CallbackResultTrait for Result<(), T> {
 
 struct JobState {
   name: String,
+  /// SQL executed by user-defined jobs.
+  query: Option<String>,
 
   schedule: Schedule,
   callback: Arc<CallbackFunction>,
@@ -65,14 +73,27 @@ struct JobState {
 pub struct Job {
   pub id: i32,
   state: Arc<Mutex<JobState>>,
+  /// Connection for recording runs in the run history, if any.
+  history: Option<Connection>,
+  /// Held for reading by in-flight runs, which lets shutdown wait for runs to complete.
+  runs: Arc<tokio::sync::RwLock<()>>,
 }
 
 impl Job {
-  fn new(id: i32, name: String, schedule: Schedule, callback: Box<CallbackFunction>) -> Self {
+  fn new(
+    id: i32,
+    name: String,
+    schedule: Schedule,
+    callback: Box<CallbackFunction>,
+    history: Option<Connection>,
+  ) -> Self {
     return Job {
       id,
+      history,
+      runs: Default::default(),
       state: Arc::new(Mutex::new(JobState {
         name,
+        query: None,
         schedule,
         callback: callback.into(),
         handle: None,
@@ -113,6 +134,7 @@ impl Job {
   }
 
   async fn run_now(&self) -> Result<(), String> {
+    let _run = self.runs.read().await;
     let callback = self.state.lock().callback.clone();
 
     let start_time = Utc::now();
@@ -120,6 +142,15 @@ impl Job {
     let end_time = Utc::now();
 
     let result_str = result.as_ref().map_err(|err| err.to_string()).copied();
+    if let Some(ref conn) = self.history {
+      let name = self.name();
+      if let Err(err) =
+        record_run(conn, &name, start_time, end_time, result_str.as_ref().err()).await
+      {
+        warn!("Failed to record run of job '{name}': {err}");
+      }
+    }
+
     self.state.lock().latest = Some(ExecutionResult {
       start_time,
       end_time,
@@ -136,7 +167,7 @@ impl Job {
     return None;
   }
 
-  fn stop(&self) {
+  pub(crate) fn stop(&self) {
     let mut lock = self.state.lock();
     if let Some(ref handle) = lock.handle {
       handle.abort();
@@ -144,6 +175,13 @@ impl Job {
     lock.handle = None;
   }
 
+  /// Stops the job at a safe point, i.e. waits for in-flight runs to complete first. No new runs
+  /// are started while waiting.
+  async fn quiesce(&self) {
+    let _runs = self.runs.write().await;
+    self.stop();
+  }
+
   pub fn running(&self) -> bool {
     return self.state.lock().handle.is_some();
   }
@@ -166,16 +204,30 @@ impl Job {
   pub fn schedule(&self) -> Schedule {
     return self.state.lock().schedule.clone();
   }
+
+  pub fn query(&self) -> Option<String> {
+    return self.state.lock().query.clone();
+  }
 }
 
 pub struct JobRegistry {
   pub(crate) jobs: Mutex<HashMap<i32, Job>>,
+  /// Connection to the main database, where job definitions and run history are persisted.
+  conn: Option<Connection>,
 }
 
 impl JobRegistry {
   pub fn new() -> Self {
     return JobRegistry {
       jobs: Mutex::new(HashMap::new()),
+      conn: None,
+    };
+  }
+
+  pub(crate) fn with_history(conn: Connection) -> Self {
+    return JobRegistry {
+      jobs: Mutex::new(HashMap::new()),
+      conn: Some(conn),
     };
   }
 
@@ -191,7 +243,13 @@ impl JobRegistry {
       Entry::Occupied(_) => None,
       Entry::Vacant(entry) => Some(
         entry
-          .insert(Job::new(id, name.into(), schedule, callback))
+          .insert(Job::new(
+            id,
+            name.into(),
+            schedule,
+            callback,
+            self.conn.clone(),
+          ))
           .clone(),
       ),
     };
@@ -206,6 +264,290 @@ impl JobRegistry {
     debug!("Running job {id}: {}", job.name());
     return Some(job.run_now().await);
   }
+
+  /// Stops all jobs without cutting in-flight runs short. Runs, which haven't completed within
+  /// `timeout`, are aborted.
+  pub async fn shutdown(&self, timeout: std::time::Duration) {
+    let jobs: Vec<Job> = self.jobs.lock().values().cloned().collect();
+
+    let quiesce = futures_util::future::join_all(jobs.iter().map(|job| job.quiesce()));
+    if tokio::time::timeout(timeout, quiesce).await.is_err() {
+      warn!("Jobs didn't complete within {timeout:?}, aborting");
+    }
+
+    for job in jobs {
+      job.stop();
+    }
+  }
+
+  pub(crate) fn get_job(&self, id: i32) -> Option<Job> {
+    return self.jobs.lock().get(&id).cloned();
+  }
+
+  pub(crate) fn find_job(&self, name: &str) -> Option<Job> {
+    return self
+      .jobs
+      .lock()
+      .values()
+      .find(|j| j.name() == name)
+      .cloned();
+  }
+
+  /// Registers and starts a user-defined job executing `query` against the main database.
+  pub(crate) fn new_query_job(
+    &self,
+    name: String,
+    schedule: Schedule,
+    query: String,
+  ) -> Option<Job> {
+    let conn = self.conn.clone()?;
+    let job = self.new_job(
+      None,
+      name,
+      schedule,
+      build_callback({
+        let query = query.clone();
+        move || {
+          let conn = conn.clone();
+          let query = query.clone();
+          return async move { conn.execute_batch(query).await };
+        }
+      }),
+    )?;
+    job.state.lock().query = Some(query);
+    return Some(job);
+  }
+
+  /// Persists, registers and starts a new user-defined job. Returns `None` if a job with the
+  /// same name already exists.
+  pub(crate) async fn create_query_job(
+    &self,
+    name: String,
+    schedule: Schedule,
+    query: String,
+  ) -> Result<Option<Job>, trailbase_sqlite::Error> {
+    let Some(ref conn) = self.conn else {
+      return Ok(None);
+    };
+    if self.find_job(&name).is_some() {
+      return Ok(None);
+    }
+
+    let inserted = conn
+      .execute(
+        formatcp!(
+          "INSERT INTO {JOBS_TABLE} (name, schedule, query) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING"
+        ),
+        params!(name.clone(), schedule.to_string(), query.clone()),
+      )
+      .await?;
+    if inserted == 0 {
+      return Ok(None);
+    }
+
+    let Some(job) = self.new_query_job(name, schedule, query) else {
+      return Ok(None);
+    };
+    job.start();
+    return Ok(Some(job));
+  }
+
+  /// Stops and deletes a user-defined job. Returns `None` if the job doesn't exist or isn't
+  /// user-defined.
+  pub(crate) async fn delete_query_job(
+    &self,
+    id: i32,
+  ) -> Result<Option<Job>, trailbase_sqlite::Error> {
+    let Some(ref conn) = self.conn else {
+      return Ok(None);
+    };
+    let Some(job) = self.get_job(id) else {
+      return Ok(None);
+    };
+
+    let deleted = conn
+      .execute(
+        formatcp!("DELETE FROM {JOBS_TABLE} WHERE name = $1 AND query IS NOT NULL"),
+        params!(job.name()),
+      )
+      .await?;
+    if deleted == 0 {
+      return Ok(None);
+    }
+
+    job.stop();
+    self.jobs.lock().remove(&id);
+    return Ok(Some(job));
+  }
+
+  /// Persists the job's definition and starts it, unless it has been paused.
+  pub(crate) async fn start_unless_paused(&self, job: &Job) -> Result<(), trailbase_sqlite::Error> {
+    if let Some(ref conn) = self.conn {
+      persist_runtime_job(conn, job).await?;
+      if is_paused(conn, &job.name()).await? {
+        return Ok(());
+      }
+    }
+
+    job.start();
+    return Ok(());
+  }
+
+  /// Pauses or resumes the given job and persists the state.
+  pub(crate) async fn set_paused(
+    &self,
+    job: &Job,
+    paused: bool,
+  ) -> Result<(), trailbase_sqlite::Error> {
+    if let Some(ref conn) = self.conn {
+      persist_runtime_job(conn, job).await?;
+      conn
+        .execute(
+          formatcp!("UPDATE {JOBS_TABLE} SET paused = $1, updated = UNIXEPOCH() WHERE name = $2"),
+          params!(paused, job.name()),
+        )
+        .await?;
+    }
+
+    if paused {
+      job.stop();
+    } else {
+      job.start();
+    }
+    return Ok(());
+  }
+}
+
+/// Persists the definition of a job registered at runtime, i.e. a system or WASM job, if it
+/// doesn't exist yet. Doesn't alter the paused state of existing jobs.
+async fn persist_runtime_job(conn: &Connection, job: &Job) -> Result<(), trailbase_sqlite::Error> {
+  const QUERY: &str = formatcp!(
+    "\
+      INSERT INTO {JOBS_TABLE} (name, schedule) VALUES ($1, $2) \
+        ON CONFLICT (name) DO UPDATE SET schedule = excluded.schedule, updated = UNIXEPOCH() \
+        WHERE query IS NULL AND schedule != excluded.schedule \
+    "
+  );
+
+  conn
+    .execute(QUERY, params!(job.name(), job.schedule().to_string()))
+    .await?;
+  return Ok(());
+}
+
+async fn is_paused(conn: &Connection, name: &str) -> Result<bool, trailbase_sqlite::Error> {
+  return Ok(
+    conn
+      .read_query_row_get(
+        formatcp!("SELECT paused FROM {JOBS_TABLE} WHERE name = $1"),
+        params!(name.to_string()),
+        0,
+      )
+      .await?
+      .unwrap_or(false),
+  );
+}
+
+async fn record_run(
+  conn: &Connection,
+  name: &str,
+  start_time: DateTime<Utc>,
+  end_time: DateTime<Utc>,
+  error: Option<&String>,
+) -> Result<(), trailbase_sqlite::Error> {
+  const INSERT_QUERY: &str = formatcp!(
+    "INSERT INTO {JOB_RUNS_TABLE} (job, started, duration_ms, success, output) VALUES ($1, $2, $3, $4, $5)"
+  );
+  const RETENTION_QUERY: &str = formatcp!(
+    "\
+      DELETE FROM {JOB_RUNS_TABLE} WHERE job = $1 AND id NOT IN ( \
+        SELECT id FROM {JOB_RUNS_TABLE} WHERE job = $1 ORDER BY id DESC LIMIT $2 \
+      ) \
+    "
+  );
+
+  conn
+    .execute(
+      INSERT_QUERY,
+      params!(
+        name.to_string(),
+        start_time.timestamp(),
+        (end_time - start_time).num_milliseconds(),
+        error.is_none(),
+        error.cloned(),
+      ),
+    )
+    .await?;
+  conn
+    .execute(
+      RETENTION_QUERY,
+      params!(name.to_string(), JOB_RUNS_RETENTION),
+    )
+    .await?;
+
+  return Ok(());
+}
+
+/// Restores persisted state: registers user-defined jobs and pauses paused jobs.
+pub(crate) async fn restore_persisted_jobs(
+  registry: Arc<JobRegistry>,
+) -> Result<(), trailbase_sqlite::Error> {
+  let Some(ref conn) = registry.conn else {
+    return Ok(());
+  };
+
+  let rows = conn
+    .read_query_rows(
+      formatcp!("SELECT name, schedule, query, paused FROM {JOBS_TABLE}"),
+      (),
+    )
+    .await?;
+
+  for row in rows.iter() {
+    let name: String = row.get(0)?;
+    let query: Option<String> = row.get(2)?;
+    let paused: bool = row.get(3)?;
+
+    let job = match query {
+      Some(query) if registry.find_job(&name).is_none() => {
+        let schedule: String = row.get(1)?;
+        let schedule = match Schedule::from_str(&schedule) {
+          Ok(schedule) => schedule,
+          Err(err) => {
+            error!("Invalid time spec for '{name}': {err}");
+            continue;
+          }
+        };
+
+        let Some(job) = registry.new_query_job(name.clone(), schedule, query) else {
+          error!("Duplicate job definition for '{name}'");
+          continue;
+        };
+        if !paused {
+          job.start();
+        }
+        job
+      }
+      _ => {
+        let Some(job) = registry.find_job(&name) else {
+          continue;
+        };
+        job
+      }
+    };
+
+    if paused {
+      job.stop();
+    }
+  }
+
+  // Persist definitions of the system jobs.
+  let jobs: Vec<Job> = registry.jobs.lock().values().cloned().collect();
+  for job in jobs {
+    persist_runtime_job(conn, &job).await?;
+  }
+
+  return Ok(());
 }
 
 impl Drop for JobRegistry {
@@ -260,8 +602,10 @@ fn build_job(
       }),
     },
     SystemJobId::Backup => {
-      let backup_file = data_dir.backup_path().join("backup.db");
+      let data_dir = data_dir.clone();
       let main_conn = connection_manager.main_entry().connection.clone();
+      let logs_conn = logs_conn.clone();
+      let backup_config = config.backups.clone().unwrap_or_default();
 
       DefaultSystemJob {
         name: "Backup",
@@ -271,11 +615,27 @@ fn build_job(
           disabled: Some(true),
         },
         callback: build_callback(move || {
-          let conn = main_conn.clone();
-          let path = backup_file.clone();
+          let data_dir = data_dir.clone();
+          let main_conn = main_conn.clone();
+          let logs_conn = logs_conn.clone();
+          let object_store = object_store.clone();
+          let backup_config = backup_config.clone();
+
           return async move {
-            conn.backup(path).await?;
-            return Ok::<(), trailbase_sqlite::Error>(());
+            create_backups(
+              &data_dir,
+              &main_conn,
+              &logs_conn,
+              &object_store,
+              &backup_config,
+            )
+            .await
+            .map_err(|err| {
+              warn!("Periodic backup failed: {err}");
+              return err;
+            })?;
+
+            Ok::<(), BackupError>(())
           };
         }),
       }
@@ -311,13 +671,20 @@ fn build_job(
 
           return async move {
             let timestamp = (Utc::now() - retention).timestamp();
-            logs_conn
-              .execute("DELETE FROM _logs WHERE created < $1", params!(timestamp))
-              .await
-              .map_err(|err| {
-                warn!("Periodic logs cleanup failed: {err}");
-                err
-              })?;
+            for query in [
+              "DELETE FROM _logs WHERE created < $1",
+              "DELETE FROM _slow_queries WHERE created < $1",
+              "DELETE FROM _email_log WHERE created < $1",
+              "DELETE FROM _push_deliveries WHERE created < $1",
+            ] {
+              logs_conn
+                .execute(query, params!(timestamp))
+                .await
+                .map_err(|err| {
+                  warn!("Periodic logs cleanup failed: {err}");
+                  err
+                })?;
+            }
 
             Ok::<(), trailbase_sqlite::Error>(())
           };
@@ -342,6 +709,9 @@ fn build_job(
               DELETE FROM '{SESSION_TABLE}' WHERE expires < (UNIXEPOCH() - 60); \
               DELETE FROM '{AUTHORIZATION_CODE_TABLE}' WHERE expires < (UNIXEPOCH() - 60); \
               DELETE FROM '{OTP_CODE_TABLE}' WHERE expires < (UNIXEPOCH() - 60); \
+              DELETE FROM '{MAGIC_LINK_TABLE}' WHERE expires < (UNIXEPOCH() - 60); \
+              DELETE FROM '{SAML_REQUEST_TABLE}' WHERE expires < (UNIXEPOCH() - 60); \
+              DELETE FROM '{IDEMPOTENCY_TABLE}' WHERE expires < (UNIXEPOCH() - 60); \
             "
           );
 
@@ -451,6 +821,65 @@ fn build_job(
         }),
       }
     }
+    SystemJobId::WebhookDeliveries => {
+      let main_conn = connection_manager.main_entry().connection.clone();
+      let webhooks = config.webhooks.clone();
+
+      DefaultSystemJob {
+        name: "Webhook Deliveries",
+        default: SystemJob {
+          id: Some(id as i32),
+          // sec   min   hour   day of month   month   day of week   year
+          schedule: Some("*/15 * * * * * *".into()),
+          disabled: Some(false),
+        },
+        callback: build_callback(move || {
+          let main_conn = main_conn.clone();
+          let webhooks = webhooks.clone();
+
+          return async move {
+            deliver_pending(&main_conn, &webhooks)
+              .await
+              .map_err(|err| {
+                warn!("Periodic webhook delivery failed: {err}");
+                return err;
+              })?;
+
+            Ok::<(), trailbase_sqlite::Error>(())
+          };
+        }),
+      }
+    }
+    SystemJobId::RecordChangesCleaner => {
+      let main_conn = connection_manager.main_entry().connection.clone();
+
+      DefaultSystemJob {
+        name: "Record Changes Cleanup",
+        default: SystemJob {
+          id: Some(id as i32),
+          schedule: Some("@daily".into()),
+          disabled: Some(false),
+        },
+        callback: build_callback(move || {
+          let main_conn = main_conn.clone();
+
+          const QUERY: &str = formatcp!("DELETE FROM '{RECORD_CHANGES_TABLE}' WHERE created < $1");
+
+          return async move {
+            let timestamp = (Utc::now() - RECORD_CHANGES_RETENTION).timestamp();
+            main_conn
+              .execute(QUERY, params!(timestamp))
+              .await
+              .map_err(|err| {
+                warn!("Periodic record changes cleanup failed: {err}");
+                err
+              })?;
+
+            Ok::<(), trailbase_sqlite::Error>(())
+          };
+        }),
+      }
+    }
   };
 }
 
@@ -500,9 +929,11 @@ pub fn build_job_registry_from_config(
     SystemJobId::AuthCleaner,
     SystemJobId::QueryOptimizer,
     SystemJobId::FileDeletions,
+    SystemJobId::WebhookDeliveries,
+    SystemJobId::RecordChangesCleaner,
   ];
 
-  let jobs = JobRegistry::new();
+  let jobs = JobRegistry::with_history((*connection_manager.main_entry().connection).clone());
   for job_id in job_ids {
     let DefaultSystemJob {
       name,
@@ -581,6 +1012,7 @@ pub(crate) async fn cleanup_anonymous_users(
 mod tests {
   use super::*;
   use cron::TimeUnitSpec;
+  use std::sync::atomic::AtomicBool;
 
   #[test]
   fn test_cron() {
@@ -646,11 +1078,53 @@ mod tests {
     assert_eq!(err_string, Some("result".to_string()));
   }
 
+  #[tokio::test]
+  async fn test_shutdown_completes_in_flight_runs() {
+    let registry = Arc::new(JobRegistry::new());
+
+    let (started_sender, started) = flume::unbounded::<()>();
+    let completed = Arc::new(AtomicBool::new(false));
+    let job = registry
+      .new_job(
+        None,
+        "Slow Task",
+        Schedule::from_str("0 0 0 1 1 * *").unwrap(),
+        build_callback({
+          let completed = completed.clone();
+          move || {
+            let started_sender = started_sender.clone();
+            let completed = completed.clone();
+            return async move {
+              started_sender.send_async(()).await.unwrap();
+              tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+              completed.store(true, Ordering::SeqCst);
+            };
+          }
+        }),
+      )
+      .unwrap();
+    job.start();
+
+    let run = tokio::spawn({
+      let registry = registry.clone();
+      let id = job.id;
+      async move { registry.run_job(id).await }
+    });
+    started.recv_async().await.unwrap();
+
+    registry.shutdown(std::time::Duration::from_secs(5)).await;
+
+    // The in-flight run wasn't cut short and the job is stopped.
+    assert!(completed.load(Ordering::SeqCst));
+    assert!(!job.running());
+    assert_eq!(run.await.unwrap(), Some(Ok(())));
+  }
+
   #[tokio::test]
   async fn test_delete_pending_files_job() {
     let state = crate::app_state::test_state(None).await.unwrap();
 
-    delete_pending_files_job(state.conn(), state.objectstore(), None)
+    delete_pending_files_job(state.conn(), &state.objectstore(), None)
       .await
       .unwrap();
   }
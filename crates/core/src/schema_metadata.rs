@@ -219,6 +219,10 @@ fn setup_file_deletion_triggers(
               WHEN (OLD.\"{column_name}\" IS NOT NULL) \
               EXECUTE FUNCTION \"__{unqualified_name}__{column_name}__trigger_fun\"(); \
           "),
+          // NOTE: SQLite disallows qualified names within trigger bodies. Instead, unqualified
+          // names resolve to the trigger's database, i.e. deletions are recorded in the
+          // `_file_deletions` table of the database the table lives in, which is created by the
+          // base migrations for every attached database.
           ConnectionType::Sqlite => format!(
             "\
             DROP TRIGGER IF EXISTS \"{db}\".\"__{unqualified_name}__{column_name}__update_trigger\"; \
@@ -606,6 +610,7 @@ mod tests {
         Path(("test_table_api".to_string(), "1".to_string())),
         Query(ReadRecordQuery {
           expand: Some("UNKNOWN".to_string()),
+          select: None,
         }),
         None,
       )
@@ -636,7 +641,7 @@ mod tests {
       let Json(value) = read_record_handler(
         State(state.clone()),
         Path(("test_table_api".to_string(), "1".to_string())),
-        Query(ReadRecordQuery { expand: None }),
+        Query(ReadRecordQuery::default()),
         None,
       )
       .await
@@ -681,6 +686,7 @@ mod tests {
         Path(("test_table_api".to_string(), "1".to_string())),
         Query(ReadRecordQuery {
           expand: Some("fk".to_string()),
+          select: None,
         }),
         None,
       )
@@ -782,7 +788,7 @@ mod tests {
       let Json(value) = read_record_handler(
         State(state.clone()),
         Path(("test_table_api".to_string(), "1".to_string())),
-        Query(ReadRecordQuery { expand: None }),
+        Query(ReadRecordQuery::default()),
         None,
       )
       .await
@@ -833,6 +839,7 @@ mod tests {
         Path(("test_table_api".to_string(), "1".to_string())),
         Query(ReadRecordQuery {
           expand: Some("fk1".to_string()),
+          select: None,
         }),
         None,
       )
@@ -882,6 +889,7 @@ mod tests {
         Path(("test_table_api".to_string(), "1".to_string())),
         Query(ReadRecordQuery {
           expand: Some("fk0,fk1".to_string()),
+          select: None,
         }),
         None,
       )
@@ -0,0 +1,222 @@
+//! Seed data, e.g. reference data like countries or roles.
+//!
+//! Seeds are `*.sql` files in `<traildepot>/seeds/`, which are applied to the main database in
+//! lexicographic order of their file names after migrations. Unlike migrations, seeds aren't part
+//! of the schema's evolution: every file is applied once per environment and tracked by name in
+//! `_seed_history`. In dev mode seeds can be re-run, thus they should ideally be idempotent, e.g.
+//! using `INSERT OR REPLACE`.
+
+use base64::prelude::*;
+use const_format::formatcp;
+use log::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+use trailbase_sqlite::params;
+use trailbase_sqlite::traits::{SyncConnection, SyncTransaction};
+
+const SEED_TABLE_NAME: &str = "_seed_history";
+
+#[derive(Debug, Error)]
+pub enum SeedError {
+  #[error("IO: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("TrailbaseSqlite: {0}")]
+  TrailbaseSqlite(#[from] trailbase_sqlite::Error),
+}
+
+struct Seed {
+  name: String,
+  sql: String,
+  checksum: String,
+}
+
+/// Applies seeds from `seeds_path` that haven't been applied yet or, if `rerun` is set, all seeds.
+///
+/// All seeds are applied in a single transaction. Returns the names of the applied seeds.
+pub(crate) async fn apply_seeds(
+  conn: &trailbase_sqlite::Connection,
+  seeds_path: impl AsRef<Path>,
+  rerun: bool,
+) -> Result<Vec<String>, SeedError> {
+  const CREATE_TABLE_QUERY: &str = formatcp!(
+    "\
+      CREATE TABLE IF NOT EXISTS {SEED_TABLE_NAME} ( \
+        name       TEXT PRIMARY KEY NOT NULL, \
+        checksum   TEXT NOT NULL, \
+        applied    INTEGER NOT NULL \
+      ) STRICT \
+    "
+  );
+  const UPSERT_QUERY: &str = formatcp!(
+    "\
+      INSERT INTO {SEED_TABLE_NAME} (name, checksum, applied) VALUES ($1, $2, $3) \
+        ON CONFLICT (name) DO UPDATE SET checksum = excluded.checksum, applied = excluded.applied \
+    "
+  );
+
+  let seeds = load_seeds(seeds_path.as_ref()).await?;
+  if seeds.is_empty() {
+    return Ok(vec![]);
+  }
+
+  let applied = conn
+    .transaction(move |mut tx| -> Result<Vec<String>, trailbase_sqlite::Error> {
+      tx.execute_batch(CREATE_TABLE_QUERY)?;
+
+      let mut history = HashMap::<String, String>::new();
+      for row in tx
+        .query_rows(formatcp!("SELECT name, checksum FROM {SEED_TABLE_NAME}"), ())?
+        .iter()
+      {
+        history.insert(row.get(0)?, row.get(1)?);
+      }
+
+      let now = chrono::Utc::now().timestamp();
+      let mut applied = vec![];
+      for seed in seeds {
+        if !rerun && let Some(checksum) = history.get(&seed.name) {
+          if *checksum != seed.checksum {
+            warn!(
+              "Seed '{}' changed after it was applied. Changes are only applied when re-running seeds in dev mode.",
+              seed.name
+            );
+          }
+          continue;
+        }
+
+        tx.execute_batch(&seed.sql)?;
+        tx.execute(UPSERT_QUERY, params!(seed.name.clone(), seed.checksum, now))?;
+        applied.push(seed.name);
+      }
+
+      tx.commit()?;
+      return Ok(applied);
+    })
+    .await?;
+
+  if !applied.is_empty() {
+    info!("Applied seeds: {applied:?}");
+  }
+
+  return Ok(applied);
+}
+
+/// Loads `*.sql` files from `seeds_path` ordered by name. A missing directory has no seeds.
+async fn load_seeds(seeds_path: &Path) -> Result<Vec<Seed>, SeedError> {
+  let mut entries = match tokio::fs::read_dir(seeds_path).await {
+    Ok(entries) => entries,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+      return Ok(vec![]);
+    }
+    Err(err) => {
+      return Err(err.into());
+    }
+  };
+
+  let mut seeds = vec![];
+  while let Some(entry) = entries.next_entry().await? {
+    let path = entry.path();
+    if !entry.file_type().await?.is_file() || path.extension().is_none_or(|ext| ext != "sql") {
+      continue;
+    }
+
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+      warn!("Skipping seed with non-UTF8 name: {path:?}");
+      continue;
+    };
+
+    let sql = tokio::fs::read_to_string(&path).await?;
+    seeds.push(Seed {
+      name: name.to_string(),
+      checksum: BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(sql.as_bytes())),
+      sql,
+    });
+  }
+
+  seeds.sort_by(|a, b| a.name.cmp(&b.name));
+
+  return Ok(seeds);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_apply_seeds() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let conn = state.conn();
+    let seeds_path = state.data_dir().seeds_path();
+
+    // Missing seeds directory.
+    assert!(
+      apply_seeds(conn, &seeds_path, false)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+
+    conn
+      .execute_batch("CREATE TABLE country (code TEXT PRIMARY KEY NOT NULL, name TEXT) STRICT")
+      .await
+      .unwrap();
+
+    std::fs::create_dir_all(&seeds_path).unwrap();
+    std::fs::write(
+      seeds_path.join("01_countries.sql"),
+      "INSERT OR REPLACE INTO country (code, name) VALUES ('CH', 'Switzerland');",
+    )
+    .unwrap();
+    std::fs::write(
+      seeds_path.join("02_more_countries.sql"),
+      "INSERT OR REPLACE INTO country (code, name) VALUES ('DE', 'Germany');",
+    )
+    .unwrap();
+    std::fs::write(seeds_path.join("README.md"), "not a seed").unwrap();
+
+    assert_eq!(
+      apply_seeds(conn, &seeds_path, false).await.unwrap(),
+      vec!["01_countries.sql", "02_more_countries.sql"]
+    );
+
+    let count = async || -> i64 {
+      return conn
+        .read_query_row_get("SELECT COUNT(*) FROM country", (), 0)
+        .await
+        .unwrap()
+        .unwrap();
+    };
+    assert_eq!(count().await, 2);
+
+    // Seeds are only applied once, even when changed.
+    conn.execute_batch("DELETE FROM country").await.unwrap();
+    std::fs::write(
+      seeds_path.join("01_countries.sql"),
+      "INSERT OR REPLACE INTO country (code, name) VALUES ('FR', 'France');",
+    )
+    .unwrap();
+    assert!(
+      apply_seeds(conn, &seeds_path, false)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    assert_eq!(count().await, 0);
+
+    // Unless re-run.
+    assert_eq!(apply_seeds(conn, &seeds_path, true).await.unwrap().len(), 2);
+    assert_eq!(count().await, 2);
+
+    // A failing seed rolls back all seeds.
+    std::fs::write(
+      seeds_path.join("03_broken.sql"),
+      "INSERT INTO missing VALUES (1);",
+    )
+    .unwrap();
+    conn.execute_batch("DELETE FROM country").await.unwrap();
+    assert!(apply_seeds(conn, &seeds_path, true).await.is_err());
+    assert_eq!(count().await, 0);
+  }
+}
@@ -0,0 +1,228 @@
+//! Automatic provisioning and renewal of TLS certificates via ACME, e.g. Let's Encrypt.
+
+use std::path::PathBuf;
+
+/// Options for provisioning TLS certificates via ACME. Certificates are requested on startup and
+/// renewed in the background before they expire.
+#[derive(Clone, Debug, Default)]
+pub struct AcmeOptions {
+  /// Domains to request a certificate for, e.g. "example.com". The server must be reachable
+  /// under all of them.
+  pub domains: Vec<String>,
+  /// Contact emails, e.g. for expiry notices.
+  pub contact: Vec<String>,
+  /// Whether to use Let's Encrypt's production rather than its staging environment. Staging
+  /// certificates aren't trusted by browsers but are subject to less strict rate limits.
+  pub production: bool,
+  /// Custom ACME directory, e.g. of another CA. Takes precedence over `production`.
+  pub directory_url: Option<String>,
+  /// When set, challenges are answered via HTTP-01 on a plain HTTP listener bound to this
+  /// address, e.g. "0.0.0.0:80", which redirects all other requests to HTTPS. Otherwise,
+  /// challenges are answered via TLS-ALPN-01 on the HTTPS listener itself, which then has to be
+  /// reachable on port 443.
+  pub http01_address: Option<String>,
+  /// Directory to cache the account key and certificates in. Default: `<data_dir>/secrets/acme`.
+  pub cache_dir: Option<PathBuf>,
+}
+
+impl AcmeOptions {
+  /// Rejects incomplete options upfront rather than failing on the first certificate request.
+  pub(crate) fn validate(&self) -> Result<(), String> {
+    use validator::{ValidateEmail, ValidateUrl};
+
+    if self.domains.is_empty() {
+      return Err("ACME requires at least one domain".to_string());
+    }
+    if let Some(domain) = self.domains.iter().find(|d| d.trim().is_empty()) {
+      return Err(format!("Invalid ACME domain: {domain:?}"));
+    }
+
+    if self.contact.is_empty() {
+      return Err("ACME requires at least one contact email".to_string());
+    }
+    if let Some(email) = self.contact.iter().find(|e| !e.validate_email()) {
+      return Err(format!("Invalid ACME contact email: {email:?}"));
+    }
+
+    if let Some(ref url) = self.directory_url
+      && !url.validate_url()
+    {
+      return Err(format!("Invalid ACME directory URL: {url:?}"));
+    }
+
+    return Ok(());
+  }
+}
+
+#[cfg(feature = "acme")]
+pub(crate) use imp::{AcmeTls, start};
+
+#[cfg(feature = "acme")]
+mod imp {
+  use axum::Router;
+  use axum::http::{HeaderMap, StatusCode, Uri, header};
+  use axum::response::{IntoResponse, Redirect, Response};
+  use futures_util::StreamExt;
+  use log::*;
+  use rustls_acme::caches::DirCache;
+  use rustls_acme::{AcmeConfig, UseChallenge};
+  use std::sync::Arc;
+  use tokio_rustls::rustls::ServerConfig;
+
+  use super::AcmeOptions;
+  use crate::data_dir::DataDir;
+  use crate::server::{ALPN_PROTOCOLS, InitError};
+
+  pub(crate) struct AcmeTls {
+    /// Serves the certificates provisioned via ACME.
+    pub(crate) config: Arc<ServerConfig>,
+    /// Answers TLS-ALPN-01 challenges.
+    pub(crate) challenge_config: Arc<ServerConfig>,
+    /// Plain HTTP listener answering HTTP-01 challenges, if enabled.
+    pub(crate) http01_router: Option<(String, Router)>,
+  }
+
+  /// Sets up certificate provisioning and spawns the background task, which requests and renews
+  /// certificates.
+  pub(crate) fn start(data_dir: &DataDir, options: &AcmeOptions) -> Result<AcmeTls, InitError> {
+    options.validate().map_err(InitError::Tls)?;
+
+    let cache_dir = options
+      .cache_dir
+      .clone()
+      .unwrap_or_else(|| data_dir.secrets_path().join("acme"));
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let config = AcmeConfig::new(options.domains.clone())
+      .contact(
+        options
+          .contact
+          .iter()
+          .map(|email| format!("mailto:{email}")),
+      )
+      .cache(DirCache::new(cache_dir));
+    let config = match options.directory_url {
+      Some(ref url) => config.directory(url),
+      None => config.directory_lets_encrypt(options.production),
+    };
+    let config = if options.http01_address.is_some() {
+      config.challenge_type(UseChallenge::Http01)
+    } else {
+      config
+    };
+
+    let mut state = config.state();
+
+    let mut server_config = ServerConfig::builder()
+      .with_no_client_auth()
+      .with_cert_resolver(state.resolver());
+    server_config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+
+    let tls = AcmeTls {
+      config: Arc::new(server_config),
+      challenge_config: state.challenge_rustls_config(),
+      http01_router: options.http01_address.clone().map(|address| {
+        let router = Router::new()
+          .route_service(
+            "/.well-known/acme-challenge/{challenge_token}",
+            state.http01_challenge_tower_service(),
+          )
+          .fallback(redirect_to_https);
+        (address, router)
+      }),
+    };
+
+    tokio::spawn(async move {
+      while let Some(event) = state.next().await {
+        match event {
+          Ok(event) => info!("ACME: {event:?}"),
+          Err(err) => error!("ACME: {err:?}"),
+        }
+      }
+    });
+
+    return Ok(tls);
+  }
+
+  async fn redirect_to_https(uri: Uri, headers: HeaderMap) -> Response {
+    let Some(host) = headers.get(header::HOST).and_then(|h| h.to_str().ok()) else {
+      return StatusCode::BAD_REQUEST.into_response();
+    };
+    // Strip the plain HTTP listener's port, if any.
+    let host = host.rsplit_once(':').map_or(host, |(host, _port)| host);
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+
+    return Redirect::permanent(&format!("https://{host}{path}")).into_response();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn options() -> AcmeOptions {
+    return AcmeOptions {
+      domains: vec!["example.com".to_string()],
+      contact: vec!["admin@example.com".to_string()],
+      ..Default::default()
+    };
+  }
+
+  #[test]
+  fn test_validate_acme_options() {
+    assert!(options().validate().is_ok());
+    assert!(
+      AcmeOptions {
+        directory_url: Some("https://acme.example.com/directory".to_string()),
+        ..options()
+      }
+      .validate()
+      .is_ok()
+    );
+
+    // Missing or empty domains.
+    assert!(
+      AcmeOptions {
+        domains: vec![],
+        ..options()
+      }
+      .validate()
+      .is_err()
+    );
+    assert!(
+      AcmeOptions {
+        domains: vec!["example.com".to_string(), " ".to_string()],
+        ..options()
+      }
+      .validate()
+      .is_err()
+    );
+
+    // Missing or invalid contact.
+    assert!(
+      AcmeOptions {
+        contact: vec![],
+        ..options()
+      }
+      .validate()
+      .is_err()
+    );
+    assert!(
+      AcmeOptions {
+        contact: vec!["admin".to_string()],
+        ..options()
+      }
+      .validate()
+      .is_err()
+    );
+
+    assert!(
+      AcmeOptions {
+        directory_url: Some("not a url".to_string()),
+        ..options()
+      }
+      .validate()
+      .is_err()
+    );
+  }
+}
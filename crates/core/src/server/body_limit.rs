@@ -0,0 +1,197 @@
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http_body_util::Limited;
+use std::sync::Arc;
+use tower::{Layer, ServiceExt};
+use tower_http::limit::RequestBodyLimitLayer;
+
+use crate::config::proto::ServerConfig;
+use crate::constants::{ADMIN_API_PATH, RECORD_API_PATH, TRANSACTION_API_PATH};
+
+const DEFAULT_LIMIT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Request body size limits by endpoint class.
+#[derive(Debug)]
+pub(super) struct BodyLimits {
+  default: usize,
+  record_write: usize,
+  file_upload: usize,
+  admin_import: usize,
+}
+
+impl BodyLimits {
+  pub(super) fn new(config: &ServerConfig) -> Self {
+    let default = config
+      .request_size_limit_bytes
+      .map_or(DEFAULT_LIMIT_BYTES, |limit| limit as usize);
+    let limits = config.request_body_limits.clone().unwrap_or_default();
+    let or_default = |limit: Option<u64>| limit.map_or(default, |limit| limit as usize);
+
+    return BodyLimits {
+      default,
+      record_write: or_default(limits.record_write_bytes),
+      file_upload: or_default(limits.file_upload_bytes),
+      admin_import: or_default(limits.admin_import_bytes),
+    };
+  }
+
+  fn for_request(&self, path: &str, headers: &HeaderMap) -> usize {
+    let Some(path) = path.strip_prefix('/') else {
+      return self.default;
+    };
+
+    if let Some(admin_path) = path.strip_prefix(ADMIN_API_PATH) {
+      let is_import = admin_path == "/database/restore"
+        || admin_path.ends_with("/import")
+        || admin_path.ends_with("/import/preview");
+      return if is_import {
+        self.admin_import
+      } else {
+        self.default
+      };
+    }
+
+    if let Some(record_path) = path
+      .strip_prefix(RECORD_API_PATH)
+      .or_else(|| path.strip_prefix(TRANSACTION_API_PATH))
+    {
+      let is_multipart = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"));
+      // Resumable uploads, i.e. ".../upload/{column}" and ".../upload/{upload_id}".
+      let is_upload = record_path.contains("/upload/");
+
+      return if is_multipart || is_upload {
+        self.file_upload
+      } else {
+        self.record_write
+      };
+    }
+
+    return self.default;
+  }
+}
+
+/// Limits the request body size depending on the endpoint class. Exceeding the limit results in
+/// a "413 Payload Too Large" response stating the applicable limit.
+pub(super) async fn body_limit_middleware(
+  State(limits): State<Arc<BodyLimits>>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let limit = limits.for_request(request.uri().path(), request.headers());
+
+  let service = RequestBodyLimitLayer::new(limit)
+    .layer(next.map_request(|request: axum::http::Request<Limited<Body>>| request.map(Body::new)));
+  let response = match service.oneshot(request).await {
+    Ok(response) => response,
+    Err(err) => match err {},
+  };
+
+  if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+    return (
+      StatusCode::PAYLOAD_TOO_LARGE,
+      format!("Request body exceeds limit of {limit} bytes"),
+    )
+      .into_response();
+  }
+
+  return response.map(Body::new);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::Router;
+  use axum::body::Bytes;
+  use axum::routing::post;
+
+  use crate::config::proto::RequestBodyLimits;
+
+  #[tokio::test]
+  async fn test_body_limits() {
+    let limits = Arc::new(BodyLimits::new(&ServerConfig {
+      request_size_limit_bytes: Some(100),
+      request_body_limits: Some(RequestBodyLimits {
+        record_write_bytes: Some(10),
+        file_upload_bytes: Some(1000),
+        admin_import_bytes: None,
+      }),
+      ..Default::default()
+    }));
+
+    async fn handler(body: Bytes) -> String {
+      return body.len().to_string();
+    }
+
+    let router = Router::new()
+      .route("/api/records/v1/{name}", post(handler))
+      .route("/api/_admin/table/{name}/import", post(handler))
+      .route("/custom", post(handler))
+      .layer(axum::middleware::from_fn_with_state(
+        limits,
+        body_limit_middleware,
+      ));
+
+    let send = async |path: &str, content_type: &str, len: usize| -> (StatusCode, String) {
+      let response = router
+        .clone()
+        .oneshot(
+          axum::http::Request::post(path)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_LENGTH, len)
+            .body(Body::from(vec![b'x'; len]))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+      let status = response.status();
+      let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+      return (status, String::from_utf8(body.to_vec()).unwrap());
+    };
+
+    let records = "/api/records/v1/movies";
+    assert_eq!(
+      send(records, "application/json", 10).await.0,
+      StatusCode::OK
+    );
+    assert_eq!(
+      send(records, "application/json", 11).await,
+      (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "Request body exceeds limit of 10 bytes".to_string()
+      )
+    );
+    assert_eq!(
+      send(records, "multipart/form-data; boundary=x", 1000)
+        .await
+        .0,
+      StatusCode::OK
+    );
+    assert_eq!(
+      send(records, "multipart/form-data; boundary=x", 1001)
+        .await
+        .0,
+      StatusCode::PAYLOAD_TOO_LARGE
+    );
+
+    // Unset limits fall back to `request_size_limit_bytes`.
+    let import = "/api/_admin/table/movies/import";
+    assert_eq!(send(import, "text/csv", 100).await.0, StatusCode::OK);
+    assert_eq!(
+      send(import, "text/csv", 101).await.1,
+      "Request body exceeds limit of 100 bytes"
+    );
+    assert_eq!(
+      send("/custom", "text/plain", 101).await.0,
+      StatusCode::PAYLOAD_TOO_LARGE
+    );
+  }
+}
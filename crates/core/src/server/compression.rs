@@ -0,0 +1,148 @@
+use axum::Router;
+use axum::body::HttpBody;
+use axum::http::{Response, header};
+use std::sync::Arc;
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+
+use crate::app_state::AppState;
+use crate::config::proto::CompressionConfig;
+
+const DEFAULT_MIN_SIZE_BYTES: u32 = 1024;
+const DEFAULT_CONTENT_TYPES: &[&str] = &[
+  "application/json",
+  "application/javascript",
+  "image/svg+xml",
+  "text/",
+];
+
+/// Compresses responses using brotli, zstd or gzip depending on what the client accepts.
+pub(super) fn with_compression(
+  router: Router<AppState>,
+  config: Option<&CompressionConfig>,
+) -> Router<AppState> {
+  let config = config.cloned().unwrap_or_default();
+  if !config.enabled.unwrap_or(true) {
+    return router;
+  }
+
+  let content_types = if config.content_types.is_empty() {
+    DEFAULT_CONTENT_TYPES
+      .iter()
+      .map(|t| t.to_string())
+      .collect()
+  } else {
+    config.content_types
+  };
+  let min_size = config.min_size_bytes.unwrap_or(DEFAULT_MIN_SIZE_BYTES);
+
+  return router.layer(
+    CompressionLayer::new().compress_when(
+      SizeAbove::new(min_size.min(u16::MAX as u32) as u16)
+        // Compressing streams would buffer events, e.g. of realtime subscriptions.
+        .and(NotForContentType::const_new("text/event-stream"))
+        .and(ForContentTypes(Arc::new(content_types))),
+    ),
+  );
+}
+
+/// Only compresses responses with a content type matching one of the given prefixes.
+#[derive(Clone)]
+struct ForContentTypes(Arc<Vec<String>>);
+
+impl Predicate for ForContentTypes {
+  fn should_compress<B>(&self, response: &Response<B>) -> bool
+  where
+    B: HttpBody,
+  {
+    let Some(content_type) = response
+      .headers()
+      .get(header::CONTENT_TYPE)
+      .and_then(|v| v.to_str().ok())
+    else {
+      return false;
+    };
+
+    return self
+      .0
+      .iter()
+      .any(|prefix| content_type.starts_with(prefix.as_str()));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::body::Body;
+  use axum::routing::get;
+  use tower::ServiceExt;
+
+  async fn get_encoding(router: Router<()>, path: &str, accept: &str) -> Option<String> {
+    let response = router
+      .oneshot(
+        axum::http::Request::get(path)
+          .header(header::ACCEPT_ENCODING, accept)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+
+    return response
+      .headers()
+      .get(header::CONTENT_ENCODING)
+      .map(|v| v.to_str().unwrap().to_string());
+  }
+
+  #[tokio::test]
+  async fn test_compression() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+
+    let router = |config: Option<CompressionConfig>| {
+      let router = Router::new()
+        .route("/large", get(|| async { axum::Json(vec!["value"; 1024]) }))
+        .route("/small", get(|| async { axum::Json("value") }))
+        .route(
+          "/binary",
+          get(|| async {
+            (
+              [(header::CONTENT_TYPE, "application/octet-stream")],
+              vec![0u8; 4096],
+            )
+          }),
+        );
+      return with_compression(router, config.as_ref()).with_state(state.clone());
+    };
+
+    assert_eq!(
+      get_encoding(router(None), "/large", "br").await.as_deref(),
+      Some("br")
+    );
+    assert_eq!(
+      get_encoding(router(None), "/large", "zstd")
+        .await
+        .as_deref(),
+      Some("zstd")
+    );
+    assert_eq!(get_encoding(router(None), "/large", "").await, None);
+    assert_eq!(get_encoding(router(None), "/small", "br").await, None);
+    assert_eq!(get_encoding(router(None), "/binary", "br").await, None);
+
+    let disabled = Some(CompressionConfig {
+      enabled: Some(false),
+      ..Default::default()
+    });
+    assert_eq!(get_encoding(router(disabled), "/large", "br").await, None);
+
+    let binary = Some(CompressionConfig {
+      content_types: vec!["application/octet-stream".to_string()],
+      ..Default::default()
+    });
+    assert_eq!(
+      get_encoding(router(binary), "/binary", "gzip")
+        .await
+        .as_deref(),
+      Some("gzip")
+    );
+  }
+}
@@ -0,0 +1,273 @@
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+use log::*;
+use std::sync::Arc;
+use tower::{Layer, ServiceExt};
+use tower_http::cors::{self, CorsLayer};
+
+use crate::config::proto::{CorsConfig, CorsPolicy};
+use crate::constants::{ADMIN_API_PATH, AUTH_API_PATH, RECORD_API_PATH, TRANSACTION_API_PATH};
+
+/// CORS policies by route group.
+pub(super) struct CorsPolicies {
+  fallback: CorsLayer,
+  records: Option<CorsLayer>,
+  auth: Option<CorsLayer>,
+  admin: Option<CorsLayer>,
+}
+
+impl CorsPolicies {
+  pub(super) fn new(dev: bool, allowed_origins: &[String], config: Option<&CorsConfig>) -> Self {
+    if dev {
+      return CorsPolicies {
+        fallback: CorsLayer::very_permissive(),
+        records: None,
+        auth: None,
+        admin: None,
+      };
+    }
+
+    let config = config.cloned().unwrap_or_default();
+    return CorsPolicies {
+      fallback: config
+        .fallback
+        .as_ref()
+        .map_or_else(|| build_default_cors(allowed_origins), build_cors),
+      records: config.records.as_ref().map(build_cors),
+      auth: config.auth.as_ref().map(build_cors),
+      admin: config.admin.as_ref().map(build_cors),
+    };
+  }
+
+  fn for_path(&self, path: &str) -> &CorsLayer {
+    let group = if has_prefix(path, RECORD_API_PATH) || has_prefix(path, TRANSACTION_API_PATH) {
+      &self.records
+    } else if has_prefix(path, AUTH_API_PATH) || has_prefix(path, "_/auth") {
+      &self.auth
+    } else if has_prefix(path, ADMIN_API_PATH) || has_prefix(path, "_/admin") {
+      &self.admin
+    } else {
+      &None
+    };
+
+    return group.as_ref().unwrap_or(&self.fallback);
+  }
+}
+
+/// Matches "/<prefix>" and "/<prefix>/..." but not "/<prefix>foo".
+#[inline]
+fn has_prefix(path: &str, prefix: &str) -> bool {
+  return path
+    .strip_prefix('/')
+    .and_then(|p| p.strip_prefix(prefix))
+    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+}
+
+/// Applies the CORS policy of the request's route group.
+pub(super) async fn cors_middleware(
+  State(policies): State<Arc<CorsPolicies>>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let cors = policies.for_path(request.uri().path()).layer(next);
+  return match cors.oneshot(request).await {
+    Ok(response) => response,
+    Err(err) => match err {},
+  };
+}
+
+/// Policy derived from the `--cors-allowed-origins` command line flag.
+fn build_default_cors(allowed_origins: &[String]) -> CorsLayer {
+  let wildcard = allowed_origins.iter().any(|s| s == "*");
+
+  let origins = if wildcard {
+    info!("CORS: allow any origin");
+    // cors::AllowOrigin::any()
+    cors::AllowOrigin::mirror_request()
+  } else {
+    cors::AllowOrigin::list(allowed_origins.iter().filter_map(|o| {
+      match HeaderValue::from_str(o.as_str()) {
+        Ok(value) => Some(value),
+        Err(err) => {
+          error!("Invalid CORS origin {o}: {err}");
+          None
+        }
+      }
+    }))
+  };
+
+  // Cannot combine `Access-Control-Allow-Credentials: true` with `Access-Control-Allow-Methods: *`
+  return CorsLayer::new()
+    .allow_methods(cors::Any)
+    .allow_headers(cors::Any)
+    .allow_origin(origins);
+}
+
+fn build_cors(policy: &CorsPolicy) -> CorsLayer {
+  let credentials = policy.allow_credentials.unwrap_or(false);
+
+  // NOTE: Wildcards, i.e. `cors::Any`, cannot be combined with credentials. Mirroring the request
+  // is equivalent but permitted.
+  let origins = if policy.allowed_origins.iter().any(|o| o == "*") {
+    cors::AllowOrigin::mirror_request()
+  } else {
+    let patterns: Vec<regex::Regex> = policy
+      .allowed_origins
+      .iter()
+      .filter_map(|origin| match origin_pattern(origin) {
+        Ok(pattern) => Some(pattern),
+        Err(err) => {
+          error!("Invalid CORS origin {origin}: {err}");
+          None
+        }
+      })
+      .collect();
+
+    cors::AllowOrigin::predicate(move |origin: &HeaderValue, _parts| {
+      return origin
+        .to_str()
+        .is_ok_and(|origin| patterns.iter().any(|p| p.is_match(origin)));
+    })
+  };
+
+  let headers = if policy.allowed_headers.is_empty() {
+    cors::AllowHeaders::mirror_request()
+  } else {
+    cors::AllowHeaders::list(
+      policy
+        .allowed_headers
+        .iter()
+        .filter_map(|h| HeaderName::try_from(h.as_str()).ok()),
+    )
+  };
+
+  let methods = if policy.allowed_methods.is_empty() {
+    cors::AllowMethods::mirror_request()
+  } else {
+    cors::AllowMethods::list(
+      policy
+        .allowed_methods
+        .iter()
+        .filter_map(|m| Method::try_from(m.as_str()).ok()),
+    )
+  };
+
+  let mut layer = CorsLayer::new()
+    .allow_origin(origins)
+    .allow_headers(headers)
+    .allow_methods(methods)
+    .allow_credentials(credentials);
+  if let Some(max_age) = policy.max_age_sec {
+    layer = layer.max_age(std::time::Duration::from_secs(max_age));
+  }
+  return layer;
+}
+
+/// Compiles an origin, which may contain "*" wildcards, e.g. "https://*.example.com", into an
+/// anchored pattern.
+fn origin_pattern(origin: &str) -> Result<regex::Regex, regex::Error> {
+  let pattern = origin
+    .split('*')
+    .map(regex::escape)
+    .collect::<Vec<_>>()
+    .join("[^/]*");
+  return regex::Regex::new(&format!("^{pattern}$"));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::Router;
+  use axum::body::Body;
+  use axum::http::header;
+  use axum::routing::get;
+
+  async fn allowed_origin(router: &Router, path: &str, origin: &str) -> Option<String> {
+    let response = router
+      .clone()
+      .oneshot(
+        axum::http::Request::get(path)
+          .header(header::ORIGIN, origin)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+
+    return response
+      .headers()
+      .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+      .map(|v| v.to_str().unwrap().to_string());
+  }
+
+  #[tokio::test]
+  async fn test_cors_policies() {
+    let config = CorsConfig {
+      records: Some(CorsPolicy {
+        allowed_origins: vec!["https://*.example.com".to_string()],
+        allow_credentials: Some(true),
+        ..Default::default()
+      }),
+      admin: Some(CorsPolicy::default()),
+      ..Default::default()
+    };
+    let policies = Arc::new(CorsPolicies::new(
+      false,
+      &["https://other.org".to_string()],
+      Some(&config),
+    ));
+
+    let router = Router::new()
+      .route("/api/records/v1/{name}", get(|| async { "records" }))
+      .route("/api/_admin/tables", get(|| async { "admin" }))
+      .route("/custom", get(|| async { "custom" }))
+      .layer(axum::middleware::from_fn_with_state(
+        policies,
+        cors_middleware,
+      ));
+
+    let records = "/api/records/v1/movies";
+    assert_eq!(
+      allowed_origin(&router, records, "https://app.example.com")
+        .await
+        .as_deref(),
+      Some("https://app.example.com")
+    );
+    assert_eq!(
+      allowed_origin(&router, records, "https://example.com.evil.org").await,
+      None
+    );
+    assert_eq!(
+      allowed_origin(&router, records, "https://other.org").await,
+      None
+    );
+
+    // Admin routes don't allow any cross-origin requests.
+    assert_eq!(
+      allowed_origin(&router, "/api/_admin/tables", "https://other.org").await,
+      None
+    );
+
+    // Other routes fall back to the command line flag.
+    assert_eq!(
+      allowed_origin(&router, "/custom", "https://other.org")
+        .await
+        .as_deref(),
+      Some("https://other.org")
+    );
+  }
+
+  #[test]
+  fn test_origin_pattern() {
+    let pattern = origin_pattern("https://*.example.com").unwrap();
+    assert!(pattern.is_match("https://app.example.com"));
+    assert!(!pattern.is_match("https://example.com"));
+    assert!(!pattern.is_match("http://app.example.com"));
+    assert!(!pattern.is_match("https://evil.org/.example.com"));
+
+    assert!(has_prefix("/api/records/v1/movies", RECORD_API_PATH));
+    assert!(!has_prefix("/api/records/v10", RECORD_API_PATH));
+  }
+}
@@ -3,11 +3,14 @@ use parking_lot::RwLock;
 use std::path::PathBuf;
 use std::sync::Arc;
 use thiserror::Error;
+use trailbase_extension::SqliteFunctionRegistrar;
 
-use crate::app_state::{AppState, AppStateArgs, build_objectstore, update_json_schema_registry};
+use crate::app_state::{
+  AppState, AppStateArgs, build_objectstore, objectstore_config, update_json_schema_registry,
+};
 use crate::auth::jwt::{JwtHelper, JwtHelperError};
 use crate::config::load_or_init_config_textproto;
-use crate::connection::ConnectionManager;
+use crate::connection::{ConnectionManager, ReadReplicaOptions};
 use crate::constants::USER_TABLE;
 use crate::metadata::load_or_init_metadata_textproto;
 use crate::rand::random_alphanumeric;
@@ -39,6 +42,12 @@ pub enum InitError {
   ObjectStore(#[from] object_store::Error),
   #[error("Auth error: {0}")]
   Auth(#[from] crate::auth::AuthError),
+  #[error("Seed error: {0}")]
+  Seed(#[from] crate::seeds::SeedError),
+  #[error("Tracing error: {0}")]
+  Tracing(String),
+  #[error("TLS error: {0}")]
+  Tls(String),
 }
 
 #[derive(Default)]
@@ -53,6 +62,8 @@ pub struct InitArgs {
   pub dev: bool,
   pub demo: bool,
   pub wasm_tokio_runtime: Option<tokio::runtime::Handle>,
+  pub read_replica: Option<ReadReplicaOptions>,
+  pub sqlite_functions: Vec<SqliteFunctionRegistrar>,
 
   #[cfg(feature = "pg")]
   pub pg_uri: Option<String>,
@@ -63,7 +74,7 @@ pub async fn init_app_state(args: InitArgs) -> Result<(bool, AppState), InitErro
   args.data_dir.ensure_directory_structure().await?;
 
   // Then open or init new databases.
-  let logs_conn = crate::connection::init_logs_db(Some(&args.data_dir))?;
+  let logs_conn = crate::connection::init_logs_db(Some(&args.data_dir), &args.sqlite_functions)?;
   let session_conn = crate::connection::init_session_db(Some(&args.data_dir))?;
 
   let json_schema_registry = Arc::new(RwLock::new(
@@ -86,21 +97,31 @@ pub async fn init_app_state(args: InitArgs) -> Result<(bool, AppState), InitErro
     data_dir: args.data_dir.clone(),
     json_schema_registry: json_schema_registry.clone(),
     sqlite_function_runtimes: sync_wasm_runtimes,
+    sqlite_functions: args.sqlite_functions,
     // TODO: Wire up from config, if/when PG is supported.
     pg_uri: cfg_select! {
         feature = "pg" => args.pg_uri,
         _ => None,
     },
+    read_replica: args.read_replica,
   })
   .await?;
 
+  // Seeds are applied after migrations, since they depend on the schema.
+  crate::seeds::apply_seeds(
+    &connection_manager.main_entry().connection,
+    args.data_dir.seeds_path(),
+    false,
+  )
+  .await?;
+
   // Read config or write default one. Ensures config is validated.
   let config = load_or_init_config_textproto(&args.data_dir, &connection_manager).await?;
 
   // Load the `<depot>/metadata.textproto`.
   let _metadata = load_or_init_metadata_textproto(&args.data_dir).await?;
 
-  let jwt = JwtHelper::init_from_path(&args.data_dir).await?;
+  let jwt = JwtHelper::init_from_path(&args.data_dir, config.auth.jwt_algorithm().into()).await?;
 
   // Init geoip if present.
   let geoip_db_path = args
@@ -110,7 +131,8 @@ pub async fn init_app_state(args: InitArgs) -> Result<(bool, AppState), InitErro
     debug!("Failed to load maxmind geoip DB '{geoip_db_path:?}': {err}");
   }
 
-  let object_store = build_objectstore(&args.data_dir, config.server.s3_storage_config.as_ref())?;
+  let object_store =
+    build_objectstore(&args.data_dir, objectstore_config(&config.server).as_ref())?;
 
   let app_state = AppState::new(AppStateArgs {
     data_dir: args.data_dir.clone(),
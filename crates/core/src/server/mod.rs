@@ -1,35 +1,37 @@
+mod acme;
+mod body_limit;
+mod compression;
+mod cors;
 mod init;
+mod otel;
 mod serve;
+mod static_site;
 
 use axum::body::Body;
 use axum::extract::{DefaultBodyLimit, Request, State};
-use axum::handler::HandlerWithoutStateExt;
-use axum::http::{HeaderValue, StatusCode};
+use axum::http::StatusCode;
 use axum::middleware::{self, Next};
 use axum::response::{IntoResponse, Response};
 use axum::routing::get;
 use axum::{RequestExt, Router};
 use bytes::Bytes;
-use http_body_util::BodyExt;
-use http_body_util::combinators::UnsyncBoxBody;
 use log::*;
 use std::borrow::Cow;
 use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::{Arc, LazyLock};
+use std::sync::Arc;
 use tokio::signal;
 use tokio::task::JoinSet;
 use tokio_rustls::TlsAcceptor;
 use tokio_rustls::rustls::ServerConfig;
 use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
-use tower::Service;
 use tower_cookies::CookieManagerLayer;
 use tower_governor::GovernorLayer;
 use tower_governor::governor::GovernorConfigBuilder;
-use tower_http::services::fs::{ServeDir, ServeFile};
-use tower_http::{cors, limit::RequestBodyLimitLayer, trace::TraceLayer};
+use tower_http::trace::TraceLayer;
 use tracing_subscriber::{filter, prelude::*};
-use trailbase_assets::AssetService;
+use trailbase_assets::{AssetService, DirSource, StaticSiteService};
+use trailbase_extension::SqliteFunctionRegistrar;
 
 use crate::admin;
 use crate::app_state::AppState;
@@ -40,9 +42,15 @@ use crate::constants::{ADMIN_API_PATH, HEADER_CSRF_TOKEN};
 use crate::data_dir::DataDir;
 use crate::extract::ip::RealIpKeyExtractor;
 use crate::logging;
+use crate::notifications;
+use crate::rate_limit;
 use crate::records;
+use crate::tenants;
 
+pub use crate::connection::ReadReplicaOptions;
+pub use acme::AcmeOptions;
 pub use init::{InitArgs, InitError, init_app_state};
+pub use otel::{OtelOptions, OtelProtocol};
 
 /// A set of options to configure serving behaviors. Changing any of these options
 /// requires a server restart, which makes them a natural fit for being exposed as command line
@@ -67,6 +75,10 @@ pub struct ServerOptions {
   /// Enable SPA fallback mode for public_dir.
   pub public_dir_spa: bool,
 
+  /// Optional prefix of the configured object store to serve static assets from at the HTTP
+  /// root, e.g. "public/". Ignored if `public_dir` is set.
+  pub public_object_store_prefix: Option<String>,
+
   /// Optional path to sandboxed FS root for WASM runtime.
   pub runtime_root_fs: Option<PathBuf>,
 
@@ -95,8 +107,52 @@ pub struct ServerOptions {
   /// TLS key path.
   pub tls_key: Option<Arc<PrivateKeyDer<'static>>>,
 
+  /// Optional automatic provisioning of TLS certificates via ACME, e.g. Let's Encrypt. Mutually
+  /// exclusive with `tls_cert` and `tls_key`. Is ignored in default builds. ACME support is
+  /// optional.
+  pub acme: Option<AcmeOptions>,
+
   /// Postgres connection URI. Is ignored in default builds. PG support is optional.
   pub pg_uri: Option<String>,
+
+  /// Optional pool of read-only connections to serve read-only queries from, e.g. record reads
+  /// and listings. Keeps heavy read traffic from contending with writes.
+  pub read_replica: Option<ReadReplicaOptions>,
+
+  /// Optional address to serve the gRPC Record and Auth services on. Is ignored in default
+  /// builds. gRPC support is optional.
+  pub grpc_address: Option<String>,
+
+  /// Optional address to serve Prometheus metrics on under `/metrics`. Metrics are only served
+  /// on this dedicated listener.
+  pub metrics_address: Option<String>,
+
+  /// Optional export of request traces via OTLP. Is ignored in default builds. OpenTelemetry
+  /// support is optional.
+  pub otel: Option<OtelOptions>,
+
+  /// Record lifecycle hooks, e.g. for users embedding TrailBase as a library.
+  pub record_hooks: Vec<Arc<dyn records::RecordHooks>>,
+
+  /// Custom provider for wrapping the data keys of encrypted files, e.g. backed by an external
+  /// KMS. Takes precedence over `server.file_encryption.master_key`.
+  pub file_key_provider: Option<Arc<dyn records::FileKeyProvider>>,
+
+  /// Scanners for uploaded files, e.g. to reject malware, in addition to the configured ones.
+  pub upload_scanners: Vec<Arc<dyn records::UploadScanner>>,
+
+  /// Custom routes served alongside TrailBase's own APIs, e.g. for users embedding TrailBase as a
+  /// library. Handlers can extract `State<AppState>` and the authenticated `User`, and requests
+  /// are logged like any other.
+  pub custom_routers: Vec<Router<AppState>>,
+
+  /// Custom SQL functions registered on every database connection, e.g. for users embedding
+  /// TrailBase as a library. Available to queries, views, triggers and migrations alike.
+  pub sqlite_functions: Vec<SqliteFunctionRegistrar>,
+
+  /// Watch the config and vault files and apply validated changes at runtime. Invalid edits are
+  /// rejected and logged.
+  pub watch_config: bool,
 }
 
 pub struct Server {
@@ -105,9 +161,13 @@ pub struct Server {
   // Routers.
   pub main_router: (String, Router),
   pub admin_router: Option<(String, Router)>,
+  pub grpc_router: Option<(String, Router)>,
+  pub metrics_router: Option<(String, Router)>,
 
   // TLS/SSL
   pub tls: Option<(CertificateDer<'static>, PrivateKeyDer<'static>)>,
+  #[cfg(feature = "acme")]
+  acme: Option<Arc<acme::AcmeTls>>,
 }
 
 impl Server {
@@ -119,9 +179,9 @@ impl Server {
   /// Initializes the server in a more customizable manner. Will create a new data directory on
   /// first start.
   ///
-  /// The `custom_routes` will be registered with the http server and `on_first_init` will be
-  /// called only when a new data directory and therefore databases are created. This hook can
-  /// be used to customize the setup in a simple manner, e.g. create tables, etc.
+  /// `on_first_init` will be called only when a new data directory and therefore databases are
+  /// created. This hook can be used to customize the setup in a simple manner, e.g. create
+  /// tables, etc.
   /// Note, however, that for a multi-stage deployment (dev, test, staging, prod, ...) or prod
   /// setups migrations are a more robust approach to consistent and continuous management of
   /// schemas.
@@ -139,6 +199,7 @@ impl Server {
     validate_path(opts.public_dir.as_ref())?;
     validate_path(opts.runtime_root_fs.as_ref())?;
     validate_path(opts.geoip_db_path.as_ref())?;
+    validate_tls(&opts)?;
 
     let (new_data_dir, state) = init::init_app_state(InitArgs {
       data_dir: opts.data_dir.clone(),
@@ -150,13 +211,39 @@ impl Server {
       dev: opts.dev,
       demo: opts.demo,
       wasm_tokio_runtime: opts.wasm_tokio_runtime.clone(),
+      read_replica: opts.read_replica.clone(),
+      sqlite_functions: opts.sqlite_functions.clone(),
 
       #[cfg(feature = "pg")]
       pg_uri: opts.pg_uri.clone(),
     })
     .await?;
 
-    Self::build_tracing(&state, opts.log_responses).init();
+    Self::init_tracing(&state, opts)?;
+
+    for hooks in &opts.record_hooks {
+      state.register_record_hooks(hooks.clone());
+    }
+
+    if let Some(ref provider) = opts.file_key_provider {
+      state.register_file_key_provider(provider.clone());
+    }
+
+    for scanner in &opts.upload_scanners {
+      state.register_upload_scanner(scanner.clone());
+    }
+
+    if let Err(err) = crate::cdc::start(&state) {
+      error!("Failed to start change data capture: {err}");
+    }
+
+    if let Err(err) = crate::records::sync::start(&state) {
+      error!("Failed to start sync change tracking: {err}");
+    }
+
+    if let Err(err) = crate::replication::start(&state) {
+      error!("Failed to start replication: {err}");
+    }
 
     if new_data_dir {
       on_first_init(state.clone())
@@ -164,7 +251,7 @@ impl Server {
         .map_err(|err| InitError::CustomInit(err.to_string()))?;
     }
 
-    let mut custom_routers: Vec<Router<AppState>> = vec![];
+    let mut custom_routers: Vec<Router<AppState>> = opts.custom_routers.clone();
 
     for rt in state.wasm_runtimes() {
       if let Some(wasm_router) = crate::wasm::install_routes_and_jobs(&state, rt.clone())
@@ -219,6 +306,18 @@ impl Server {
       None
     };
 
+    if opts.watch_config {
+      crate::config_watcher::spawn_config_watcher(state.clone());
+    }
+    crate::config::secrets::spawn_secrets_refresher(state.clone());
+    crate::queue::spawn_task_worker(state.clone());
+    crate::email::spawn_email_worker(state.clone());
+
+    #[cfg(not(feature = "acme"))]
+    if opts.acme.is_some() {
+      warn!("Ignoring ACME options. Requires the 'acme' feature.");
+    }
+
     let build_independent_admin_router = opts
       .admin_address
       .as_ref()
@@ -243,14 +342,36 @@ impl Server {
       } else {
         None
       },
+      grpc_router: Self::build_grpc_router(&state, &opts),
+      metrics_router: opts
+        .metrics_address
+        .clone()
+        .map(|address| (address, crate::metrics::router().with_state(state.clone()))),
       tls: Self::load_tls(&opts),
+      #[cfg(feature = "acme")]
+      acme: opts
+        .acme
+        .as_ref()
+        .map(|options| acme::start(&opts.data_dir, options).map(Arc::new))
+        .transpose()?,
     })
   }
 
-  fn build_tracing(
-    state: &AppState,
-    log_responses: bool,
-  ) -> impl tracing_subscriber::layer::SubscriberExt {
+  #[allow(unused_variables)]
+  fn build_grpc_router(state: &AppState, opts: &ServerOptions) -> Option<(String, Router)> {
+    let address = opts.grpc_address.clone()?;
+
+    #[cfg(feature = "grpc")]
+    return Some((address, crate::grpc::router(state.clone())));
+
+    #[cfg(not(feature = "grpc"))]
+    {
+      warn!("Ignoring gRPC address {address:?}. Requires the 'grpc' feature.");
+      return None;
+    }
+  }
+
+  fn init_tracing(state: &AppState, opts: &ServerOptions) -> Result<(), InitError> {
     // Initialize tracing subscribers/layers.
     //
     // A few notes in case initialization below panics. The `log` and `tracing` crates/systems are
@@ -272,24 +393,61 @@ impl Server {
     //
     // Response log events are emitted at the INFO level, see `logging.rs`
     #[cfg(not(feature = "otel"))]
-    let subscriber = tracing_subscriber::Registry::default();
+    {
+      if opts.otel.is_some() {
+        warn!("Ignoring OTLP trace export. Requires the 'otel' feature.");
+      }
+
+      Self::with_log_layers(
+        tracing_subscriber::Registry::default(),
+        state,
+        opts.log_responses,
+      )
+      .init();
+    }
 
     #[cfg(feature = "otel")]
-    let subscriber = {
-      let (subscriber, otel_guard) =
-        init_tracing_opentelemetry::tracing_subscriber_ext::regiter_otel_layers(
-          tracing_subscriber::Registry::default(),
+    match opts.otel {
+      Some(ref options) => {
+        let layer =
+          otel::build_layer(options).map_err(|err| InitError::Tracing(err.to_string()))?;
+
+        Self::with_log_layers(
+          tracing_subscriber::Registry::default().with(layer),
+          state,
+          opts.log_responses,
         )
-        .expect("startup");
+        .init();
+      }
+      None => {
+        // Configured via the standard `OTEL_*` environment variables.
+        let (subscriber, otel_guard) =
+          init_tracing_opentelemetry::tracing_subscriber_ext::regiter_otel_layers(
+            tracing_subscriber::Registry::default(),
+          )
+          .map_err(|err| InitError::Tracing(err.to_string()))?;
 
-      // TODO: We have to keep this alive. Let's find something better than a singleton.
-      use std::sync::OnceLock;
-      static SINGLETON: OnceLock<init_tracing_opentelemetry::Guard> = OnceLock::new();
-      SINGLETON.get_or_init(move || init_tracing_opentelemetry::Guard::global(Some(otel_guard)));
+        // TODO: We have to keep this alive. Let's find something better than a singleton.
+        use std::sync::OnceLock;
+        static SINGLETON: OnceLock<init_tracing_opentelemetry::Guard> = OnceLock::new();
+        SINGLETON.get_or_init(move || init_tracing_opentelemetry::Guard::global(Some(otel_guard)));
 
-      subscriber
-    };
+        Self::with_log_layers(subscriber, state, opts.log_responses).init();
+      }
+    }
 
+    return Ok(());
+  }
+
+  fn with_log_layers<S>(
+    subscriber: S,
+    state: &AppState,
+    log_responses: bool,
+  ) -> impl tracing_subscriber::layer::SubscriberExt + use<S>
+  where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+    S: Send + Sync + 'static,
+  {
     let filter_layer = filter::Targets::new()
       .with_default(filter::LevelFilter::OFF)
       .with_target(crate::logging::EVENT_TARGET, crate::logging::LEVEL);
@@ -348,6 +506,12 @@ impl Server {
             }
           }
 
+          if let Err(err) =
+            crate::seeds::apply_seeds(&conn, state.data_dir().seeds_path(), false).await
+          {
+            error!("Failed to apply seeds: {err}");
+          }
+
           // NOTE: we're always invalidating: simple & safe. We could also avoid invalidation
           // when no new migrations were applied :shrug:.
           if let Err(err) = state.rebuild_connection_metadata().await {
@@ -374,30 +538,79 @@ impl Server {
       });
     }
 
+    let state = self.state.clone();
+    let tls = self.build_tls();
     let (cleanup_sender, cleanup_receiver) = tokio::sync::oneshot::channel::<()>();
+    let (drain_sender, drain_receiver) = tokio::sync::oneshot::channel::<()>();
 
     tokio::spawn(async move {
       if cleanup_receiver.await.is_ok() {
         log::debug!("cleanup started");
 
         self.state.subscription_manager().shutdown();
+        let _ = drain_sender.send(());
       }
     });
 
+    if let Some((addr, router)) = self.grpc_router {
+      let tls = tls.clone();
+
+      info!("Serving gRPC on {addr}");
+      tokio::spawn(async move { start_listen(&addr, router, tls, None).await });
+    }
+
+    #[cfg(feature = "acme")]
+    if let Some((addr, router)) = self.acme.as_ref().and_then(|a| a.http01_router.clone()) {
+      info!("Serving ACME HTTP-01 challenges on {addr}");
+      tokio::spawn(async move { start_listen(&addr, router, None, None).await });
+    }
+
+    if let Some((addr, router)) = self.metrics_router {
+      info!("Serving metrics on {addr}");
+      tokio::spawn(async move { start_listen(&addr, router, None, None).await });
+    }
+
     // Finally start serving.
     //
-    // NOTE: This will only return when graceful shutdown succeeded.
-    serve_impl(
-      self.main_router,
-      self.admin_router,
-      self.tls,
-      cleanup_sender,
-    )
-    .await?;
+    // NOTE: This will only return once shutdown was initiated and in-flight requests completed or
+    // the drain deadline passed.
+    tokio::select! {
+      result = serve_routers(
+        self.main_router,
+        self.admin_router,
+        tls,
+        cleanup_sender,
+      ) => result?,
+      _ = async {
+        match drain_receiver.await {
+          Ok(_) => tokio::time::sleep(DRAIN_TIMEOUT).await,
+          Err(_) => std::future::pending().await,
+        }
+      } => {
+        warn!("In-flight requests didn't complete within {DRAIN_TIMEOUT:?}");
+      }
+    };
+
+    // No more requests are served. Wind down background work, e.g. jobs and log writers, before
+    // exiting.
+    state.shutdown(QUIESCE_TIMEOUT).await;
 
     return Ok(());
   }
 
+  /// TLS used by the listeners, either provisioned via ACME or static, see `validate_tls`.
+  fn build_tls(&self) -> Option<Tls> {
+    #[cfg(feature = "acme")]
+    if let Some(ref acme) = self.acme {
+      return Some(Tls::Acme(acme.clone()));
+    }
+
+    return self
+      .tls
+      .as_ref()
+      .map(|(cert, key)| Tls::new_static(cert.clone(), key.clone_key()));
+  }
+
   pub fn load_tls(
     opts: &ServerOptions,
   ) -> Option<(CertificateDer<'static>, PrivateKeyDer<'static>)> {
@@ -501,17 +714,39 @@ impl Server {
 
     let mut router = Router::new()
       // Public, stable and versioned APIs.
-      .merge(records::router(conn.connection_type(), enable_transactions))
-      .merge(install_auth_rate_limiter.map_or_else(
-        || auth::router(&state.get_config()),
-        |inst| inst(auth::router(&state.get_config())),
-      ))
+      .merge(
+        records::router(conn.connection_type(), enable_transactions)
+          .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::record_api_rate_limit,
+          ))
+          // NOTE: Outermost, so that rate limiting already sees the routed tenant.
+          .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            tenants::tenant_routing,
+          )),
+      )
+      .merge(
+        install_auth_rate_limiter
+          .map_or_else(
+            || auth::router(&state.get_config()),
+            |inst| inst(auth::router(&state.get_config())),
+          )
+          .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::auth_rate_limit,
+          )),
+      )
+      .merge(notifications::router())
+      .route("/api/openapi.json", get(crate::openapi::openapi_handler))
       .route("/api/healthcheck", get(healthcheck_handler));
 
     if build_admin_router {
       router = router.merge(Self::build_admin_router(state));
     }
 
+    router = router.merge(crate::proxy::router(&state.get_config().proxy_routes));
+
     for custom_router in custom_routers {
       router = router.merge(custom_router);
     }
@@ -521,44 +756,22 @@ impl Server {
         panic!("--public_dir={public_dir:?} path does not exist.")
       }
 
-      const NOT_FOUND: &[u8] = b"Not found";
-      async fn handle_404() -> (StatusCode, &'static [u8]) {
-        (StatusCode::NOT_FOUND, NOT_FOUND)
+      if opts.public_dir_spa
+        && !tokio::fs::try_exists(public_dir.join("index.html"))
+          .await
+          .unwrap_or(false)
+      {
+        warn!("--spa specified but index.html not found");
       }
 
-      router = if opts.public_dir_spa {
-        let spa_fallback = public_dir.join("index.html");
-        if tokio::fs::try_exists(&spa_fallback).await.unwrap_or(false) {
-          let mut index_file = ServeFile::new(spa_fallback);
-          let fallback = async move |req: Request| {
-            static SUFFIX_RE: LazyLock<regex::Regex> =
-              LazyLock::new(|| regex::Regex::new(r"[.]\w+$").expect("const"));
-
-            // Return NOT_FOUND when the requested path ends in a suffix. Not sure if this is ideal
-            // but we definitely don't want to return an "index.html" on a favicon request.
-            if SUFFIX_RE.is_match(req.uri().path()) {
-              return Ok(
-                Response::builder()
-                  .status(StatusCode::NOT_FOUND)
-                  .body(axum::body::Body::from(NOT_FOUND).boxed_unsync())
-                  .expect("infallible"),
-              );
-            }
-
-            return index_file.call(req).await.map(|response| {
-              response.map(|body| UnsyncBoxBody::new(body.map_err(axum::Error::new)))
-            });
-          };
-
-          router.fallback_service(ServeDir::new(public_dir).fallback(fallback.into_service()))
-        } else {
-          warn!("--spa specified but index.html not found");
-          router.fallback_service(ServeDir::new(public_dir).fallback(handle_404.into_service()))
-        }
-      } else {
-        router
-          .fallback_service(ServeDir::new(public_dir).not_found_service(handle_404.into_service()))
-      };
+      router = router.fallback_service(
+        StaticSiteService::new(DirSource::new(public_dir)).with_spa_fallback(opts.public_dir_spa),
+      );
+    } else if let Some(prefix) = &opts.public_object_store_prefix {
+      router = router.fallback_service(
+        StaticSiteService::new(static_site::ObjectStoreSource::new(state.clone(), prefix))
+          .with_spa_fallback(opts.public_dir_spa),
+      );
     }
 
     return Ok((
@@ -572,14 +785,22 @@ impl Server {
     opts: &ServerOptions,
     router: Router<AppState>,
   ) -> Router<()> {
+    let router =
+      compression::with_compression(router, state.get_config().server.compression.as_ref());
+
     #[cfg(feature = "otel")]
-    let router = router
-      .layer(axum_tracing_opentelemetry::middleware::OtelInResponseLayer)
-      .layer(axum_tracing_opentelemetry::middleware::OtelAxumLayer::default());
+    let router = router.layer(axum_tracing_opentelemetry::middleware::OtelInResponseLayer);
 
     return router
       .layer(CookieManagerLayer::new())
-      .layer(build_cors(opts))
+      .layer(middleware::from_fn_with_state(
+        Arc::new(cors::CorsPolicies::new(
+          opts.dev,
+          &opts.cors_allowed_origins,
+          state.get_config().server.cors.as_ref(),
+        )),
+        cors::cors_middleware,
+      ))
       .layer(
         // This declares: **what information** is logged at what level in to events and spans.
         TraceLayer::new_for_http()
@@ -587,15 +808,12 @@ impl Server {
           .on_request(logging::sqlite_logger_on_request)
           .on_response(logging::sqlite_logger_on_response),
       )
-      // Default request size limit is only 2MB Increase to 10MB by default if no explicit user
-      // limit is provided.
+      // Axum's default request size limit is only 2MB. Instead, apply configurable limits by
+      // endpoint class, 10MB by default.
       .layer(DefaultBodyLimit::disable())
-      .layer(RequestBodyLimitLayer::new(
-        state
-          .get_config()
-          .server
-          .request_size_limit_bytes
-          .map_or(10 * 1024 * 1024, |limit| limit as usize),
+      .layer(middleware::from_fn_with_state(
+        Arc::new(body_limit::BodyLimits::new(&state.get_config().server)),
+        body_limit::body_limit_middleware,
       ))
       .with_state(state.clone());
   }
@@ -637,36 +855,10 @@ async fn assert_admin_api_access(
   return Ok(next.run(req).await);
 }
 
-fn build_cors(opts: &ServerOptions) -> cors::CorsLayer {
-  if opts.dev {
-    return cors::CorsLayer::very_permissive();
-  }
-
-  let origin_strs = &opts.cors_allowed_origins;
-  let wildcard = origin_strs.iter().any(|s| s == "*");
-
-  let origins = if wildcard {
-    info!("CORS: allow any origin");
-    // cors::AllowOrigin::any()
-    cors::AllowOrigin::mirror_request()
-  } else {
-    cors::AllowOrigin::list(origin_strs.iter().filter_map(|o| {
-      match HeaderValue::from_str(o.as_str()) {
-        Ok(value) => Some(value),
-        Err(err) => {
-          error!("Invalid CORS origin {o}: {err}");
-          None
-        }
-      }
-    }))
-  };
-
-  // Cannot combine `Access-Control-Allow-Credentials: true` with `Access-Control-Allow-Methods: *`
-  return cors::CorsLayer::new()
-    .allow_methods(cors::Any)
-    .allow_headers(cors::Any)
-    .allow_origin(origins);
-}
+/// Deadline for in-flight requests to complete after shutdown was initiated.
+const DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// Deadline for jobs to complete and logs to be flushed once requests have been drained.
+const QUIESCE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
 
 async fn shutdown_signal() {
   let ctrl_c = async {
@@ -701,6 +893,7 @@ async fn shutdown_signal() {
           handle.metrics().num_alive_tasks()
         );
 
+        // NOTE: Leaves room for draining requests and quiescing background work in `serve`.
         const SECONDS: usize = 10;
 
         for remaining in (0..SECONDS).rev() {
@@ -743,11 +936,49 @@ async fn shutdown_signal() {
   // Ready to shut down.
 }
 
+/// Protocols negotiated via ALPN in order of preference, i.e. HTTP/2 if supported by the client.
+pub(crate) const ALPN_PROTOCOLS: &[&[u8]] = &[b"h2", b"http/1.1"];
+
+/// How listeners terminate TLS.
+#[derive(Clone)]
+enum Tls {
+  Static(Arc<ServerConfig>),
+  #[cfg(feature = "acme")]
+  Acme(Arc<acme::AcmeTls>),
+}
+
+impl Tls {
+  fn new_static(cert: CertificateDer<'static>, key: PrivateKeyDer<'static>) -> Self {
+    let mut config = ServerConfig::builder()
+      .with_no_client_auth()
+      .with_single_cert(vec![cert], key)
+      .expect("Failed to build server config");
+    config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+
+    return Tls::Static(Arc::new(config));
+  }
+}
+
 pub async fn serve_impl(
   main_router: (String, Router),
   admin_router: Option<(String, Router)>,
   tls: Option<(CertificateDer<'static>, PrivateKeyDer<'static>)>,
   cleanup_sender: tokio::sync::oneshot::Sender<()>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  return serve_routers(
+    main_router,
+    admin_router,
+    tls.map(|(cert, key)| Tls::new_static(cert, key)),
+    cleanup_sender,
+  )
+  .await;
+}
+
+async fn serve_routers(
+  main_router: (String, Router),
+  admin_router: Option<(String, Router)>,
+  tls: Option<Tls>,
+  cleanup_sender: tokio::sync::oneshot::Sender<()>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   // Make sure TLS provider is installed (both for incoming and outgoing traffic, including traffic
   // from WASM components).
@@ -771,9 +1002,7 @@ pub async fn serve_impl(
 
   if let Some((addr, router)) = admin_router {
     set.spawn({
-      let tls = tls
-        .as_ref()
-        .map(|(cert, key)| (cert.clone(), key.clone_key()));
+      let tls = tls.clone();
       async move { start_listen(&addr, router, tls, None).await }
     });
   }
@@ -798,7 +1027,7 @@ pub async fn serve_impl(
 async fn start_listen(
   addr: &str,
   router: Router<()>,
-  tls: Option<(CertificateDer<'static>, PrivateKeyDer<'static>)>,
+  tls: Option<Tls>,
   cleanup_sender: Option<tokio::sync::oneshot::Sender<()>>,
 ) {
   let tcp_listener = match tokio::net::TcpListener::bind(addr).await {
@@ -809,43 +1038,48 @@ async fn start_listen(
     }
   };
 
+  let shutdown = async {
+    shutdown_signal().await;
+
+    if let Some(cleanup) = cleanup_sender {
+      let _ = cleanup.send(());
+    }
+  };
+
   if let Err(err) = match tls {
-    Some((cert, key)) => {
+    Some(Tls::Static(config)) => {
       info!("TLS enabled");
 
       serve::serve(
         serve::TlsListener {
           listener: tcp_listener,
-          acceptor: TlsAcceptor::from(Arc::new({
-            ServerConfig::builder()
-              .with_no_client_auth()
-              .with_single_cert(vec![cert], key)
-              .expect("Failed to build server config")
-          })),
+          acceptor: TlsAcceptor::from(config),
         },
         router.into_make_service_with_connect_info::<SocketAddr>(),
       )
-      .with_graceful_shutdown(async {
-        shutdown_signal().await;
+      .with_graceful_shutdown(shutdown)
+      .await
+    }
+    #[cfg(feature = "acme")]
+    Some(Tls::Acme(tls)) => {
+      info!("TLS enabled, certificates provisioned via ACME");
 
-        if let Some(cleanup) = cleanup_sender {
-          let _ = cleanup.send(());
-        }
-      })
+      serve::serve(
+        serve::AcmeTlsListener {
+          listener: tcp_listener,
+          tls,
+        },
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+      )
+      .with_graceful_shutdown(shutdown)
       .await
     }
-    _ => {
+    None => {
       serve::serve(
         tcp_listener,
         router.into_make_service_with_connect_info::<SocketAddr>(),
       )
-      .with_graceful_shutdown(async {
-        shutdown_signal().await;
-
-        if let Some(cleanup) = cleanup_sender {
-          let _ = cleanup.send(());
-        }
-      })
+      .with_graceful_shutdown(shutdown)
       .await
     }
   } {
@@ -863,6 +1097,28 @@ fn validate_path(path: Option<&PathBuf>) -> Result<(), InitError> {
   return Ok(());
 }
 
+/// Certificates are either provisioned via ACME or provided manually, not both.
+fn validate_tls(opts: &ServerOptions) -> Result<(), InitError> {
+  let Some(ref acme) = opts.acme else {
+    return Ok(());
+  };
+
+  acme.validate().map_err(InitError::Tls)?;
+
+  let certs_path = opts.data_dir.secrets_path().join("certs");
+  if opts.tls_cert.is_some()
+    || opts.tls_key.is_some()
+    || std::fs::exists(certs_path.join("cert.pem"))?
+    || std::fs::exists(certs_path.join("key.pem"))?
+  {
+    return Err(InitError::Tls(format!(
+      "ACME is mutually exclusive with a manual TLS cert/key, remove it or {certs_path:?}"
+    )));
+  }
+
+  return Ok(());
+}
+
 fn cow_to_bytes(cow: Cow<'static, [u8]>) -> Bytes {
   match cow {
     Cow::Borrowed(x) => Bytes::from(x),
@@ -0,0 +1,177 @@
+/// Transport used to export traces to an OTLP collector.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OtelProtocol {
+  #[default]
+  Grpc,
+  HttpProtobuf,
+}
+
+/// Options for exporting request traces via OTLP. Unset options fall back to the standard
+/// `OTEL_*` environment variables, e.g. `OTEL_EXPORTER_OTLP_ENDPOINT`.
+#[derive(Clone, Debug, Default)]
+pub struct OtelOptions {
+  /// Collector endpoint, e.g. "http://localhost:4317" for gRPC or
+  /// "http://localhost:4318/v1/traces" for HTTP.
+  pub endpoint: Option<String>,
+  pub protocol: OtelProtocol,
+  /// Ratio of root traces to sample in [0, 1]. Requests carrying a `traceparent` header follow
+  /// the caller's sampling decision. Defaults to sampling all traces.
+  pub sample_ratio: Option<f64>,
+  /// Additional resource attributes, e.g. ("deployment.environment", "prod").
+  pub resource_attributes: Vec<(String, String)>,
+}
+
+/// Builds a tracing layer exporting spans, e.g. the request spans of `logging.rs`, and installs a
+/// W3C trace context propagator, which is used to continue incoming `traceparent`s.
+#[cfg(feature = "otel")]
+pub(super) fn build_layer<S>(
+  options: &OtelOptions,
+) -> Result<
+  tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>,
+  opentelemetry_otlp::ExporterBuildError,
+>
+where
+  S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+  use opentelemetry::KeyValue;
+  use opentelemetry::trace::TracerProvider;
+  use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+  use opentelemetry_sdk::Resource;
+  use opentelemetry_sdk::propagation::TraceContextPropagator;
+  use opentelemetry_sdk::trace::SdkTracerProvider;
+
+  let exporter = match options.protocol {
+    OtelProtocol::Grpc => {
+      let builder = SpanExporter::builder().with_tonic();
+      match options.endpoint {
+        Some(ref endpoint) => builder.with_endpoint(endpoint).build()?,
+        None => builder.build()?,
+      }
+    }
+    OtelProtocol::HttpProtobuf => {
+      let builder = SpanExporter::builder().with_http();
+      match options.endpoint {
+        Some(ref endpoint) => builder.with_endpoint(endpoint).build()?,
+        None => builder.build()?,
+      }
+    }
+  };
+
+  let mut resource = Resource::builder();
+  // NOTE: Only fall back to our own name if users didn't name the service explicitly.
+  if std::env::var_os("OTEL_SERVICE_NAME").is_none()
+    && !options
+      .resource_attributes
+      .iter()
+      .any(|(key, _)| key == "service.name")
+  {
+    resource = resource.with_service_name("trailbase");
+  }
+  let resource = resource
+    .with_attributes(
+      options
+        .resource_attributes
+        .iter()
+        .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+    )
+    .build();
+
+  let provider = SdkTracerProvider::builder()
+    .with_batch_exporter(exporter)
+    .with_sampler(build_sampler(options.sample_ratio))
+    .with_resource(resource)
+    .build();
+
+  // NOTE: The global provider keeps the exporter alive for the lifetime of the process.
+  opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+  opentelemetry::global::set_tracer_provider(provider.clone());
+
+  return Ok(tracing_opentelemetry::layer().with_tracer(provider.tracer("trailbase")));
+}
+
+/// Samples root traces by ratio, while requests carrying a `traceparent` follow the caller's
+/// sampling decision.
+#[cfg(feature = "otel")]
+fn build_sampler(sample_ratio: Option<f64>) -> opentelemetry_sdk::trace::Sampler {
+  use opentelemetry_sdk::trace::Sampler;
+
+  let root = match sample_ratio {
+    Some(ratio) => Sampler::TraceIdRatioBased(ratio.clamp(0.0, 1.0)),
+    None => Sampler::AlwaysOn,
+  };
+
+  return Sampler::ParentBased(Box::new(root));
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+  use axum::body::Body;
+  use axum::http::Request;
+  use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceId, TracerProvider};
+  use opentelemetry_sdk::propagation::TraceContextPropagator;
+  use opentelemetry_sdk::trace::SdkTracerProvider;
+  use tracing_opentelemetry::OpenTelemetrySpanExt;
+  use tracing_subscriber::layer::SubscriberExt;
+
+  use super::*;
+  use crate::logging::sqlite_logger_make_span;
+
+  const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+  const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+  fn request_span_context(sample_ratio: Option<f64>, traceparent: Option<&str>) -> SpanContext {
+    let provider = SdkTracerProvider::builder()
+      .with_sampler(build_sampler(sample_ratio))
+      .build();
+    let subscriber = tracing_subscriber::Registry::default()
+      .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+    return tracing::subscriber::with_default(subscriber, || {
+      let mut builder = Request::builder().uri("/api/healthcheck");
+      if let Some(traceparent) = traceparent {
+        builder = builder.header("traceparent", traceparent);
+      }
+      let span = sqlite_logger_make_span(&builder.body(Body::empty()).unwrap());
+
+      return span.context().span().span_context().clone();
+    });
+  }
+
+  #[test]
+  fn test_traceparent_propagation() {
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let trace_id = TraceId::from_hex(TRACE_ID).unwrap();
+    let parent_span_id = SpanId::from_hex(PARENT_SPAN_ID).unwrap();
+
+    // Incoming traces are continued with a new span, even if we wouldn't sample root traces.
+    let sampled = request_span_context(
+      Some(0.0),
+      Some(&format!("00-{TRACE_ID}-{PARENT_SPAN_ID}-01")),
+    );
+    assert!(sampled.is_valid());
+    assert_eq!(trace_id, sampled.trace_id());
+    assert_ne!(parent_span_id, sampled.span_id());
+    assert_ne!(SpanId::INVALID, sampled.span_id());
+    assert!(sampled.is_sampled());
+
+    // The caller's decision not to sample is honored, even if we'd sample all root traces.
+    let unsampled = request_span_context(None, Some(&format!("00-{TRACE_ID}-{PARENT_SPAN_ID}-00")));
+    assert_eq!(trace_id, unsampled.trace_id());
+    assert_ne!(parent_span_id, unsampled.span_id());
+    assert!(!unsampled.is_sampled());
+
+    // Root traces follow the configured ratio.
+    let root = request_span_context(Some(1.0), None);
+    assert!(root.is_valid());
+    assert_ne!(trace_id, root.trace_id());
+    assert!(root.is_sampled());
+
+    assert!(!request_span_context(Some(0.0), None).is_sampled());
+
+    // Malformed headers start a new root trace rather than being continued.
+    let malformed = request_span_context(Some(1.0), Some("00-invalid-01"));
+    assert_ne!(trace_id, malformed.trace_id());
+    assert!(malformed.is_sampled());
+  }
+}
@@ -142,6 +142,65 @@ impl axum::extract::connect_info::Connected<IncomingStream<'_, TlsListener>> for
   }
 }
 
+/// Like `TlsListener` but additionally answers ACME TLS-ALPN-01 challenges.
+#[cfg(feature = "acme")]
+pub(crate) struct AcmeTlsListener {
+  pub(crate) tls: Arc<super::acme::AcmeTls>,
+  pub(crate) listener: TcpListener,
+}
+
+#[cfg(feature = "acme")]
+impl Listener for AcmeTlsListener {
+  type Io = tokio_rustls::server::TlsStream<TcpStream>;
+  type Addr = std::net::SocketAddr;
+
+  async fn accept(&mut self) -> io::Result<(Self::Io, Self::Addr)> {
+    use tokio::io::AsyncWriteExt;
+    use tokio_rustls::LazyConfigAcceptor;
+
+    let mut cnt = 0;
+    loop {
+      let (stream, remote_addr) = match self.listener.accept().await {
+        Ok(tup) => tup,
+        Err(err) => {
+          if cnt >= 3 {
+            return Err(err);
+          }
+          cnt += 1;
+
+          handle_accept_error(err).await;
+          continue;
+        }
+      };
+
+      let start = LazyConfigAcceptor::new(Default::default(), stream).await?;
+      if rustls_acme::is_tls_alpn_challenge(&start.client_hello()) {
+        // Challenge connections only complete the handshake.
+        let mut stream = start.into_stream(self.tls.challenge_config.clone()).await?;
+        let _ = stream.shutdown().await;
+        continue;
+      }
+
+      return Ok((
+        start.into_stream(self.tls.config.clone()).await?,
+        remote_addr,
+      ));
+    }
+  }
+
+  #[inline]
+  fn local_addr(&self) -> io::Result<Self::Addr> {
+    self.listener.local_addr()
+  }
+}
+
+#[cfg(feature = "acme")]
+impl axum::extract::connect_info::Connected<IncomingStream<'_, AcmeTlsListener>> for SocketAddr {
+  fn connect_info(stream: IncomingStream<'_, AcmeTlsListener>) -> Self {
+    *stream.remote_addr()
+  }
+}
+
 /// Serve the service with the supplied listener.
 ///
 /// This method of running a service is intentionally simple and doesn't support any configuration.
@@ -0,0 +1,75 @@
+use axum::body::Body;
+use object_store::{GetOptions, GetRange, ObjectStore, ObjectStoreExt};
+use std::ops::Range;
+use trailbase_assets::{OpenFuture, ReadFuture, StaticFile, StaticSource};
+
+use crate::app_state::AppState;
+
+/// Serves static files from a prefix of the configured object store, e.g. "public/".
+pub(super) struct ObjectStoreSource {
+  state: AppState,
+  prefix: String,
+}
+
+impl ObjectStoreSource {
+  pub(super) fn new(state: AppState, prefix: &str) -> Self {
+    return Self {
+      state,
+      prefix: prefix.trim_matches('/').to_string(),
+    };
+  }
+}
+
+impl StaticSource for ObjectStoreSource {
+  fn open<'a>(&'a self, path: &'a str) -> OpenFuture<'a> {
+    return Box::pin(async move {
+      let location = object_store::path::Path::from_iter(
+        self
+          .prefix
+          .split('/')
+          .chain(path.split('/'))
+          .filter(|segment| !segment.is_empty()),
+      );
+
+      // NOTE: Look up the store on every request, since it may change with the config.
+      let store = self.state.objectstore();
+      let meta = match store.head(&location).await {
+        Ok(meta) => meta,
+        Err(object_store::Error::NotFound { .. }) => return Ok(None),
+        Err(err) => return Err(std::io::Error::other(err)),
+      };
+
+      let etag = meta
+        .e_tag
+        .as_ref()
+        .map(|etag| format!("\"{}\"", etag.trim_matches('"')))
+        .or_else(|| {
+          Some(format!(
+            "\"{:x}-{:x}\"",
+            meta.last_modified.timestamp(),
+            meta.size
+          ))
+        });
+
+      return Ok(Some(StaticFile {
+        len: meta.size,
+        etag,
+        read: Box::new(move |range: Range<u64>| -> ReadFuture {
+          return Box::pin(async move {
+            let result = store
+              .get_opts(
+                &location,
+                GetOptions {
+                  range: Some(GetRange::Bounded(range)),
+                  ..Default::default()
+                },
+              )
+              .await
+              .map_err(std::io::Error::other)?;
+            return Ok(Body::from_stream(result.into_stream()));
+          });
+        }),
+      }));
+    });
+  }
+}
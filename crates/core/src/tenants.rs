@@ -0,0 +1,534 @@
+//! Tenant-per-database multi-tenancy.
+//!
+//! Tenants are registered in the `_tenant` catalog in the main database and each tenant's data
+//! lives in its own database, `<traildepot>/data/tenants/<name>.db`, which is created on demand
+//! and migrated using the tenant migrations in `<traildepot>/migrations/tenants/`. Requests are
+//! routed to a tenant by [tenant_routing] and record APIs marked `tenant_scoped` are then served
+//! from the routed tenant's database.
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode, header, request::Parts};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use const_format::formatcp;
+use log::*;
+use thiserror::Error;
+use trailbase_sqlite::params;
+
+use crate::app_state::AppState;
+use crate::auth::AuthError;
+use crate::auth::api_key::{extract_api_key_from_headers, lookup_api_key};
+use crate::auth::tokens::extract_tokens_from_request_parts;
+use crate::auth::util::get_user_tenant;
+use crate::config::proto::{TenancyConfig, TenantRouting};
+use crate::connection::{ConnectionEntry, ConnectionError};
+use crate::constants::TENANT_TABLE;
+use crate::util::b64_to_id;
+
+tokio::task_local! {
+  static CURRENT_TENANT: Tenant;
+}
+
+/// Tenant the current request has been routed to.
+#[derive(Clone)]
+pub(crate) struct Tenant {
+  pub name: String,
+  pub entry: ConnectionEntry,
+}
+
+/// Returns the tenant the current request has been routed to, if any.
+pub(crate) fn current_tenant() -> Option<Tenant> {
+  return CURRENT_TENANT.try_with(|tenant| tenant.clone()).ok();
+}
+
+/// Runs the given future in the scope of the given tenant.
+pub(crate) async fn with_tenant<F: Future>(tenant: Tenant, f: F) -> F::Output {
+  return CURRENT_TENANT.scope(tenant, f).await;
+}
+
+#[derive(Debug, Error)]
+pub enum TenantError {
+  #[error("Invalid tenant name: {0}")]
+  InvalidName(&'static str),
+  #[error("Tenant not found")]
+  NotFound,
+  #[error("Tenant already exists")]
+  AlreadyExists,
+  #[error("TrailBase SQLite: {0}")]
+  TrailbaseSqlite(#[from] trailbase_sqlite::Error),
+  #[error("Connection: {0}")]
+  Connection(#[from] ConnectionError),
+}
+
+/// Tenant names are used as database file names and subdomains, we therefore only permit lowercase
+/// alphanumeric characters, '_' and '-'.
+pub(crate) fn validate_tenant_name(name: &str) -> Result<(), TenantError> {
+  if name.is_empty() || name.len() > 63 {
+    return Err(TenantError::InvalidName("length"));
+  }
+  if name.starts_with(['_', '-']) {
+    return Err(TenantError::InvalidName("leading '_' or '-'"));
+  }
+  if !name
+    .chars()
+    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+  {
+    return Err(TenantError::InvalidName(
+      "may only contain lowercase alphanumeric characters, '_' and '-'",
+    ));
+  }
+  return Ok(());
+}
+
+/// Registers a new tenant and creates its database.
+pub(crate) async fn register_tenant(state: &AppState, name: &str) -> Result<(), TenantError> {
+  validate_tenant_name(name)?;
+
+  const QUERY: &str =
+    formatcp!("INSERT INTO '{TENANT_TABLE}' (name) VALUES ($1) ON CONFLICT DO NOTHING");
+  let rows_affected = state
+    .conn()
+    .execute(QUERY, params!(name.to_string()))
+    .await?;
+  if rows_affected == 0 {
+    return Err(TenantError::AlreadyExists);
+  }
+
+  // Eagerly create and migrate the database to surface migration errors early.
+  state.connection_manager().get_tenant_entry(name).await?;
+
+  return Ok(());
+}
+
+/// Removes the tenant from the catalog and closes its database.
+///
+/// NOTE: The tenant's database file is kept around and has to be removed manually.
+pub(crate) async fn unregister_tenant(state: &AppState, name: &str) -> Result<(), TenantError> {
+  const QUERY: &str = formatcp!("DELETE FROM '{TENANT_TABLE}' WHERE name = $1");
+  let rows_affected = state
+    .conn()
+    .execute(QUERY, params!(name.to_string()))
+    .await?;
+
+  state.connection_manager().evict_tenant(name);
+  state.invalidate_tenant_record_apis(name);
+
+  if rows_affected == 0 {
+    return Err(TenantError::NotFound);
+  }
+  return Ok(());
+}
+
+/// Whether a tenant with the given name is registered.
+pub(crate) async fn tenant_exists(state: &AppState, name: &str) -> Result<bool, TenantError> {
+  const QUERY: &str = formatcp!("SELECT EXISTS(SELECT 1 FROM '{TENANT_TABLE}' WHERE name = $1)");
+  return Ok(
+    state
+      .conn()
+      .read_query_row_get::<bool>(QUERY, params!(name.to_string()), 0)
+      .await?
+      .unwrap_or(false),
+  );
+}
+
+/// Looks up a registered tenant and opens its database.
+pub(crate) async fn lookup_tenant(state: &AppState, name: &str) -> Result<Tenant, TenantError> {
+  validate_tenant_name(name)?;
+
+  if !tenant_exists(state, name).await? {
+    return Err(TenantError::NotFound);
+  }
+
+  return Ok(Tenant {
+    name: name.to_string(),
+    entry: state.connection_manager().get_tenant_entry(name).await?,
+  });
+}
+
+/// Determines the name of the tenant a request is for based on the configured routing.
+fn resolve_tenant_name(
+  config: &TenancyConfig,
+  headers: &HeaderMap,
+  claim: Option<&str>,
+) -> Option<String> {
+  return match config.routing() {
+    TenantRouting::Subdomain => {
+      let host = headers.get(header::HOST)?.to_str().ok()?;
+      subdomain(host).map(|s| s.to_ascii_lowercase())
+    }
+    TenantRouting::Claim => claim.map(|c| c.to_string()),
+    TenantRouting::Header | TenantRouting::Undefined => {
+      let name = config.header.as_deref().unwrap_or(DEFAULT_TENANT_HEADER);
+      let value = headers.get(name)?.to_str().ok()?.trim();
+      (!value.is_empty()).then(|| value.to_string())
+    }
+  };
+}
+
+/// Leftmost label of a host with at least three labels, e.g. "acme" for "acme.example.com", or of
+/// a "*.localhost" host for local development.
+fn subdomain(host: &str) -> Option<&str> {
+  // Strip the port, if any. IPv6 addresses are bracketed and have no subdomain anyway.
+  if host.starts_with('[') {
+    return None;
+  }
+  let host = host.rsplit_once(':').map_or(host, |(host, _port)| host);
+  if host.parse::<std::net::IpAddr>().is_ok() {
+    return None;
+  }
+
+  let labels: Vec<&str> = host.split('.').collect();
+  let is_localhost = labels.len() == 2 && labels[1] == "localhost";
+  if labels.len() < 3 && !is_localhost {
+    return None;
+  }
+  return labels.first().copied().filter(|label| !label.is_empty());
+}
+
+/// Tenant membership of the request's principal. `None` for anonymous requests, otherwise the
+/// tenant the authenticated user or API key belongs to, if any.
+///
+/// Users' membership is looked up rather than taken from their auth token, so that reassignments
+/// take effect immediately.
+async fn principal_tenant(
+  state: &AppState,
+  parts: &Parts,
+) -> Result<Option<Option<String>>, AuthError> {
+  if let Some(secret) = extract_api_key_from_headers(&parts.headers) {
+    let (_key_id, scope) = lookup_api_key(state, secret).await?;
+    return Ok(Some(scope.tenant));
+  }
+
+  let Ok(tokens) = extract_tokens_from_request_parts(state, parts).await else {
+    return Ok(None);
+  };
+  let user_id = b64_to_id(&tokens.auth_token_claims.sub).map_err(|_| AuthError::Unauthorized)?;
+  return Ok(Some(get_user_tenant(state.user_conn(), &user_id).await?));
+}
+
+/// Routes record API requests to the configured tenant. Requests w/o a discernible tenant are
+/// passed through and will be rejected by tenant-scoped APIs.
+///
+/// Authenticated users and API keys may only access the tenant they belong to, anonymous requests
+/// are subject to the tenant-scoped APIs' ACLs.
+pub(crate) async fn tenant_routing(
+  State(state): State<AppState>,
+  req: Request,
+  next: Next,
+) -> Response {
+  let Some(config) = state.access_config(|c| c.tenancy.clone()) else {
+    return next.run(req).await;
+  };
+  if !config.enabled.unwrap_or(false) {
+    return next.run(req).await;
+  }
+
+  let (parts, body) = req.into_parts();
+  let membership = match principal_tenant(&state, &parts).await {
+    Ok(membership) => membership,
+    Err(err) => return err.into_response(),
+  };
+
+  let Some(name) = resolve_tenant_name(
+    &config,
+    &parts.headers,
+    membership.as_ref().and_then(|m| m.as_deref()),
+  ) else {
+    return next.run(Request::from_parts(parts, body)).await;
+  };
+
+  if let Some(member_of) = membership
+    && member_of.as_deref() != Some(name.as_str())
+  {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let tenant = match lookup_tenant(&state, &name).await {
+    Ok(tenant) => tenant,
+    Err(TenantError::NotFound | TenantError::InvalidName(_)) => {
+      return StatusCode::NOT_FOUND.into_response();
+    }
+    Err(err) => {
+      warn!("Failed to open tenant '{name}': {err}");
+      return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+  };
+
+  return with_tenant(tenant, next.run(Request::from_parts(parts, body))).await;
+}
+
+const DEFAULT_TENANT_HEADER: &str = "X-Tenant";
+
+#[cfg(test)]
+mod tests {
+  use axum::Router;
+  use axum::body::Body;
+  use axum::http::HeaderValue;
+  use axum::routing::get;
+  use std::sync::Arc;
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::admin::user::create_user_for_test;
+  use crate::auth::util::{UserIdentifier, login_with_password_for_test};
+  use crate::config::proto::{PermissionFlag, RecordApiConfig};
+  use crate::constants::{API_KEY_TABLE, TENANT_USER_TABLE};
+  use crate::records::test_utils::add_record_api_config;
+
+  #[test]
+  fn test_resolve_tenant_name() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      header::HOST,
+      HeaderValue::from_static("acme.example.com:4000"),
+    );
+    headers.insert("X-Tenant", HeaderValue::from_static("globex"));
+
+    let subdomain_config = TenancyConfig {
+      enabled: Some(true),
+      routing: Some(TenantRouting::Subdomain as i32),
+      ..Default::default()
+    };
+    assert_eq!(
+      resolve_tenant_name(&subdomain_config, &headers, None).as_deref(),
+      Some("acme")
+    );
+
+    let header_config = TenancyConfig {
+      enabled: Some(true),
+      ..Default::default()
+    };
+    assert_eq!(
+      resolve_tenant_name(&header_config, &headers, None).as_deref(),
+      Some("globex")
+    );
+
+    let claim_config = TenancyConfig {
+      enabled: Some(true),
+      routing: Some(TenantRouting::Claim as i32),
+      ..Default::default()
+    };
+    assert_eq!(
+      resolve_tenant_name(&claim_config, &headers, Some("initech")).as_deref(),
+      Some("initech")
+    );
+    assert_eq!(resolve_tenant_name(&claim_config, &headers, None), None);
+
+    assert_eq!(subdomain("acme.localhost:4000"), Some("acme"));
+    assert_eq!(subdomain("example.com"), None);
+    assert_eq!(subdomain("localhost:4000"), None);
+    assert_eq!(subdomain("127.0.0.1:4000"), None);
+    assert_eq!(subdomain("[::1]:4000"), None);
+
+    assert!(validate_tenant_name("acme-1").is_ok());
+    assert!(validate_tenant_name("Acme").is_err());
+    assert!(validate_tenant_name("_acme").is_err());
+    assert!(validate_tenant_name("../acme").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_tenant_scoped_record_api() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+
+    let tenant_migrations = state.data_dir().migrations_path().join("tenants");
+    std::fs::create_dir_all(&tenant_migrations).unwrap();
+    std::fs::write(
+      tenant_migrations.join("U1700000000__notes.sql"),
+      "CREATE TABLE note (id INTEGER PRIMARY KEY, text TEXT NOT NULL) STRICT;",
+    )
+    .unwrap();
+
+    register_tenant(&state, "acme").await.unwrap();
+    register_tenant(&state, "globex").await.unwrap();
+    assert!(matches!(
+      register_tenant(&state, "acme").await,
+      Err(TenantError::AlreadyExists)
+    ));
+
+    let mut config = state.get_config();
+    Arc::make_mut(&mut config).tenancy = Some(TenancyConfig {
+      enabled: Some(true),
+      ..Default::default()
+    });
+    state
+      .validate_and_update_config((*config).clone(), None)
+      .await
+      .unwrap();
+
+    add_record_api_config(
+      &state,
+      RecordApiConfig {
+        name: Some("note".to_string()),
+        table_name: Some("note".to_string()),
+        tenant_scoped: Some(true),
+        acl_world: [PermissionFlag::Read as i32].into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+    // Tenant-scoped APIs aren't available outside of a tenant's scope.
+    assert!(state.lookup_record_api("note").is_none());
+
+    let acme = lookup_tenant(&state, "acme").await.unwrap();
+    acme
+      .entry
+      .connection
+      .execute("INSERT INTO note (text) VALUES ('acme')", ())
+      .await
+      .unwrap();
+    let globex = lookup_tenant(&state, "globex").await.unwrap();
+    assert!(matches!(
+      lookup_tenant(&state, "initech").await,
+      Err(TenantError::NotFound)
+    ));
+
+    let count = |tenant: Tenant| {
+      let state = state.clone();
+      with_tenant(tenant, async move {
+        let api = state.lookup_record_api("note").unwrap();
+        return api
+          .conn()
+          .read_query_row_get::<i64>("SELECT COUNT(*) FROM note", (), 0)
+          .await
+          .unwrap()
+          .unwrap();
+      })
+    };
+
+    assert_eq!(count(acme).await, 1);
+    assert_eq!(count(globex).await, 0);
+
+    unregister_tenant(&state, "globex").await.unwrap();
+    assert!(matches!(
+      lookup_tenant(&state, "globex").await,
+      Err(TenantError::NotFound)
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_tenant_routing_membership() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+
+    register_tenant(&state, "acme").await.unwrap();
+    register_tenant(&state, "globex").await.unwrap();
+
+    let mut config = state.get_config();
+    Arc::make_mut(&mut config).tenancy = Some(TenancyConfig {
+      enabled: Some(true),
+      ..Default::default()
+    });
+    state
+      .validate_and_update_config((*config).clone(), None)
+      .await
+      .unwrap();
+
+    let email = "member@test.org";
+    let password = "Secret!1!!";
+    let user_id = create_user_for_test(&state, email, password).await.unwrap();
+    let auth_token =
+      login_with_password_for_test(&state, UserIdentifier::Email(email.to_string()), password)
+        .await
+        .unwrap()
+        .unwrap()
+        .auth_token;
+
+    let insert_key = async |tenant: Option<&str>| -> String {
+      let secret = crate::auth::api_key::new_api_key_secret();
+      state
+        .conn()
+        .execute(
+          formatcp!(
+            "INSERT INTO '{API_KEY_TABLE}' (name, secret_hash, record_apis, permissions, tenant) \
+             VALUES ('key', $1, '[\"x\"]', 1, $2)"
+          ),
+          params!(
+            crate::auth::api_key::hash_api_key_secret(&secret),
+            tenant.map(|t| t.to_string())
+          ),
+        )
+        .await
+        .unwrap();
+      return secret;
+    };
+    let bound_key = insert_key(Some("acme")).await;
+    let unbound_key = insert_key(None).await;
+
+    let router = Router::new()
+      .route(
+        "/",
+        get(async || current_tenant().map_or("-".to_string(), |t| t.name)),
+      )
+      .layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        tenant_routing,
+      ))
+      .layer(tower_cookies::CookieManagerLayer::new());
+
+    let send = async |tenant: &str, bearer: Option<&str>| -> (StatusCode, String) {
+      let mut request = axum::http::Request::get("/").header("X-Tenant", tenant);
+      if let Some(bearer) = bearer {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {bearer}"));
+      }
+      let response = router
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+      let status = response.status();
+      let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+      return (status, String::from_utf8(body.to_vec()).unwrap());
+    };
+
+    // Anonymous requests are left to the APIs' ACLs.
+    assert_eq!(
+      send("acme", None).await,
+      (StatusCode::OK, "acme".to_string())
+    );
+
+    // Users not assigned to any tenant may not access tenants.
+    assert_eq!(
+      send("acme", Some(&auth_token)).await.0,
+      StatusCode::FORBIDDEN
+    );
+
+    // Assignments take effect w/o having to refresh the auth token.
+    state
+      .user_conn()
+      .execute(
+        formatcp!("INSERT INTO '{TENANT_USER_TABLE}' (user, tenant) VALUES ($1, 'acme')"),
+        params!(user_id.into_bytes()),
+      )
+      .await
+      .unwrap();
+    assert_eq!(
+      send("acme", Some(&auth_token)).await,
+      (StatusCode::OK, "acme".to_string())
+    );
+    assert_eq!(
+      send("globex", Some(&auth_token)).await.0,
+      StatusCode::FORBIDDEN
+    );
+
+    // API keys may only access the tenant they're bound to.
+    assert_eq!(
+      send("acme", Some(&bound_key)).await,
+      (StatusCode::OK, "acme".to_string())
+    );
+    assert_eq!(
+      send("globex", Some(&bound_key)).await.0,
+      StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+      send("acme", Some(&unbound_key)).await.0,
+      StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+      send("acme", Some("tb_invalid")).await.0,
+      StatusCode::UNAUTHORIZED
+    );
+  }
+}
@@ -4,20 +4,28 @@ use std::{
   path::{Path, PathBuf},
 };
 use thiserror::Error;
+use trailbase_schema::parse::parse_into_statement;
+use trailbase_schema::sqlite::Table;
+use trailbase_sqlite::ConnectionType;
 use trailbase_sqlite::traits::{SyncConnection, SyncTransaction};
 
+use crate::constants::SQLITE_SCHEMA_TABLE;
 use crate::migrations;
 
 #[derive(Debug, Error)]
 pub enum TransactionError {
   #[error("SQLite error: {0}")]
   Sqlite(#[from] trailbase_sqlite::Error),
+  #[error("FromSql error: {0}")]
+  FromSql(#[from] trailbase_sqlite::from_sql::FromSqlError),
   #[error("IO error: {0}")]
   IO(#[from] std::io::Error),
   #[error("Migration error: {0}")]
   Migration(#[from] trailbase_refinery::Error),
   #[error("File error: {0}")]
   File(String),
+  #[error("Rollback error: {0}")]
+  Rollback(String),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -28,32 +36,22 @@ enum QueryType {
 
 pub struct TransactionLog {
   log: Vec<(QueryType, String)>,
+  /// Statements reverting the schema changes, if they could be derived.
+  down: Option<Vec<String>>,
 }
 
 impl TransactionLog {
   pub(crate) fn build_sql(&self) -> String {
-    let sql_string: String = self
-      .log
-      .iter()
-      .filter_map(|(_, stmt)| match stmt.as_str() {
-        "" => None,
-        x if x.ends_with(";") => Some(stmt.clone()),
-        x => Some(format!("{x};")),
-      })
-      .collect::<Vec<String>>()
-      .join("\n");
-
-    return sqlformat::format(
-      &sql_string,
-      &sqlformat::QueryParams::None,
-      &sqlformat::FormatOptions {
-        ignore_case_convert: None,
-        indent: sqlformat::Indent::Spaces(4),
-        uppercase: Some(true),
-        lines_between_queries: 2,
-        ..Default::default()
-      },
-    );
+    return format_sql(self.log.iter().map(|(_, stmt)| stmt.as_str()));
+  }
+
+  /// SQL reverting the schema changes of this transaction. Note that this only restores the
+  /// schema, e.g. the contents of dropped columns or tables are lost.
+  pub(crate) fn build_down_sql(&self) -> Option<String> {
+    return self
+      .down
+      .as_ref()
+      .map(|down| format_sql(down.iter().map(|stmt| stmt.as_str())));
   }
 
   /// Commit previously recorded transaction log on provided connection.
@@ -72,7 +70,10 @@ impl TransactionLog {
     let path = migration_path.as_ref().join(filename);
 
     let sql = self.build_sql();
-    let migrations = vec![trailbase_refinery::Migration::unapplied(&stem, &sql)?];
+    let down_sql = self.build_down_sql();
+    let migration = trailbase_refinery::Migration::unapplied(&stem, &sql)?;
+    let downs = migrations::DownMigrations::from([(migration.version(), down_sql.clone())]);
+    let migrations = vec![migration];
     let runner = migrations::new_migration_runner(&migrations).set_abort_missing(false);
 
     let mut conn = conn.clone();
@@ -80,8 +81,12 @@ impl TransactionLog {
       error!("Migration aborted with: {err} for {sql}");
       err
     })?;
+    migrations::record_migration_batch_async(&conn, report.applied_migrations(), downs).await?;
 
-    write_migration_file(path, &sql)?;
+    write_migration_file(path.clone(), &sql)?;
+    if let Some(down_sql) = down_sql {
+      write_migration_file(migrations::down_migration_path(&path), &down_sql)?;
+    }
 
     return Ok(report);
   }
@@ -115,14 +120,29 @@ impl TransactionLog {
 }
 
 /// A recorder for table migrations, i.e.: create, alter, drop, as opposed to data migrations.
+///
+/// Also snapshots the schema before the first statement to derive statements reverting the
+/// recorded schema changes.
 pub struct TransactionRecorder<'a> {
   tx: trailbase_sqlite::Transaction<'a>,
   log: Vec<(QueryType, String)>,
+  schema_before: Option<Vec<SchemaEntry>>,
 }
 
 impl<'a> TransactionRecorder<'a> {
   pub fn new(tx: trailbase_sqlite::Transaction<'a>) -> Self {
-    return Self { tx, log: vec![] };
+    return Self {
+      tx,
+      log: vec![],
+      schema_before: None,
+    };
+  }
+
+  fn maybe_snapshot_schema(&mut self) -> Result<(), trailbase_sqlite::Error> {
+    if self.schema_before.is_none() && self.tx.connection_type() == ConnectionType::Sqlite {
+      self.schema_before = Some(snapshot_schema(&mut self.tx)?);
+    }
+    return Ok(());
   }
 
   // Note that we cannot take any sql params for recording purposes.
@@ -139,6 +159,7 @@ impl<'a> TransactionRecorder<'a> {
       ));
     };
 
+    self.maybe_snapshot_schema()?;
     self.tx.query_row(sql, params)?;
 
     self.log.push((QueryType::Query, expanded_sql));
@@ -158,6 +179,7 @@ impl<'a> TransactionRecorder<'a> {
       ));
     };
 
+    self.maybe_snapshot_schema()?;
     let rows_affected = self.tx.execute(sql, params)?;
 
     self.log.push((QueryType::Execute, expanded_sql));
@@ -168,14 +190,178 @@ impl<'a> TransactionRecorder<'a> {
   /// Consume this transaction and rollback.
   #[allow(unused)]
   pub fn rollback(mut self) -> Result<Option<TransactionLog>, TransactionError> {
+    let down = match self.schema_before {
+      Some(ref before) => build_down_statements(before, &snapshot_schema(&mut self.tx)?),
+      None => None,
+    };
+
     self.tx.rollback()?;
 
     if self.log.is_empty() {
       return Ok(None);
     }
 
-    return Ok(Some(TransactionLog { log: self.log }));
+    return Ok(Some(TransactionLog {
+      log: self.log,
+      down,
+    }));
+  }
+}
+
+fn format_sql<'a>(statements: impl Iterator<Item = &'a str>) -> String {
+  let sql_string: String = statements
+    .filter_map(|stmt| match stmt {
+      "" => None,
+      x if x.ends_with(";") => Some(x.to_string()),
+      x => Some(format!("{x};")),
+    })
+    .collect::<Vec<String>>()
+    .join("\n");
+
+  return sqlformat::format(
+    &sql_string,
+    &sqlformat::QueryParams::None,
+    &sqlformat::FormatOptions {
+      ignore_case_convert: None,
+      indent: sqlformat::Indent::Spaces(4),
+      uppercase: Some(true),
+      lines_between_queries: 2,
+      ..Default::default()
+    },
+  );
+}
+
+/// An entry of the `sqlite_schema` table.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SchemaEntry {
+  pub r#type: String,
+  pub name: String,
+  pub tbl_name: String,
+  pub sql: String,
+}
+
+pub(crate) fn snapshot_schema(
+  tx: &mut trailbase_sqlite::Transaction<'_>,
+) -> Result<Vec<SchemaEntry>, trailbase_sqlite::Error> {
+  let rows = tx.query_rows(
+    format!(
+      "SELECT type, name, tbl_name, sql FROM main.{SQLITE_SCHEMA_TABLE} WHERE sql IS NOT NULL"
+    ),
+    (),
+  )?;
+
+  return rows
+    .iter()
+    .map(|row| -> Result<SchemaEntry, trailbase_sqlite::Error> {
+      return Ok(SchemaEntry {
+        r#type: row.get(0)?,
+        name: row.get(1)?,
+        tbl_name: row.get(2)?,
+        sql: row.get(3)?,
+      });
+    })
+    .collect();
+}
+
+/// Derives statements reverting the schema from `after` back to `before`. Changed tables are
+/// recreated with their prior definition, retaining the data of columns present in both. Returns
+/// `None`, if the schema didn't change, e.g. for data migrations, or the changes cannot be
+/// reverted.
+fn build_down_statements(before: &[SchemaEntry], after: &[SchemaEntry]) -> Option<Vec<String>> {
+  fn find<'b>(entries: &'b [SchemaEntry], entry: &SchemaEntry) -> Option<&'b SchemaEntry> {
+    return entries
+      .iter()
+      .find(|e| e.r#type == entry.r#type && e.name == entry.name);
+  }
+
+  fn parse_table(sql: &str) -> Option<Table> {
+    return parse_into_statement(sql).ok()??.try_into().ok();
+  }
+
+  if before == after {
+    return None;
+  }
+
+  let is_table = |e: &&SchemaEntry| e.r#type == "table";
+  let changed_tables: Vec<(&SchemaEntry, &SchemaEntry)> = before
+    .iter()
+    .filter(is_table)
+    .filter_map(|b| find(after, b).filter(|a| a.sql != b.sql).map(|a| (b, a)))
+    .collect();
+  let recreated = |tbl_name: &str| changed_tables.iter().any(|(b, _)| b.name == tbl_name);
+
+  let mut down: Vec<String> = vec![];
+  if !changed_tables.is_empty() {
+    down.push("PRAGMA defer_foreign_keys = ON".to_string());
+  }
+
+  // Drop created and changed views, triggers and indexes first.
+  for entry in after.iter().filter(|e| !is_table(e)) {
+    if find(before, entry).is_none_or(|b| b.sql != entry.sql) {
+      down.push(format!(
+        "DROP {} IF EXISTS \"{}\"",
+        entry.r#type.to_uppercase(),
+        entry.name
+      ));
+    }
+  }
+
+  // Drop created tables.
+  for entry in after.iter().filter(is_table) {
+    if find(before, entry).is_none() {
+      down.push(format!("DROP TABLE \"{}\"", entry.name));
+    }
+  }
+
+  // Recreate changed tables, like `alter_table_handler` does.
+  for (b, a) in &changed_tables {
+    let mut table = parse_table(&b.sql)?;
+    let current = parse_table(&a.sql)?;
+    let columns = table
+      .columns
+      .iter()
+      .filter(|c| current.columns.iter().any(|o| o.name == c.name))
+      .map(|c| format!("\"{}\"", c.name))
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    let name = &b.name;
+    table.name.database_schema = None;
+    table.name.name = format!("__down_{name}");
+    down.push(table.create_table_statement());
+    down.push(format!(
+      "INSERT INTO \"__down_{name}\" ({columns}) SELECT {columns} FROM \"{name}\""
+    ));
+    down.push(format!("DROP TABLE \"{name}\""));
+    down.push("PRAGMA legacy_alter_table = ON".to_string());
+    down.push(format!(
+      "ALTER TABLE \"__down_{name}\" RENAME TO \"{name}\""
+    ));
+    down.push("PRAGMA legacy_alter_table = OFF".to_string());
+  }
+
+  // Create dropped tables.
+  for entry in before.iter().filter(is_table) {
+    if find(after, entry).is_none() {
+      down.push(entry.sql.clone());
+    }
+  }
+
+  // Create dropped and changed indexes and triggers as well as the ones of recreated tables,
+  // followed by views.
+  let mut views: Vec<String> = vec![];
+  for entry in before.iter().filter(|e| !is_table(e)) {
+    if find(after, entry).is_none_or(|a| a.sql != entry.sql) || recreated(&entry.tbl_name) {
+      if entry.r#type == "view" {
+        views.push(entry.sql.clone());
+      } else {
+        down.push(entry.sql.clone());
+      }
+    }
   }
+  down.extend(views);
+
+  return Some(down);
 }
 
 fn write_migration_file(path: PathBuf, sql: &str) -> std::io::Result<()> {
@@ -1,17 +1,30 @@
 use axum::Router;
 use axum::extract::{RawPathParams, Request};
 use bytes::Bytes;
+use futures_util::StreamExt;
 use http_body_util::{BodyExt, combinators::UnsyncBoxBody};
 use hyper::StatusCode;
 use log::*;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
-use trailbase_wasm_common::{HttpContext, HttpContextKind, HttpContextUser};
-use trailbase_wasm_runtime_host::{InitArgs, RuntimeOptions, find_wasm_components};
+use trailbase_reactive::Reactive;
+use trailbase_schema::FileUpload;
+use trailbase_wasm_common::{
+  HttpContext, HttpContextKind, HttpContextUser, RECORD_HOOK_PATH_PREFIX, RecordHookOperation,
+  RecordHookRequest,
+};
+use trailbase_wasm_runtime_host::{
+  EgressPolicy, InitArgs, ResourceLimits, RuntimeOptions, find_wasm_components,
+};
 
 use crate::User;
+use crate::config::proto::Config;
+use crate::queue::{TaskError, TaskHandler};
+use crate::records::hooks::AsyncRecordHook;
+use crate::records::{RecordError, ScanStream, ScanVerdict, UploadScanError, UploadScanner};
 use crate::util::urlencode;
 use crate::{AppState, DataDir};
 
@@ -30,6 +43,7 @@ pub(crate) async fn build_sync_wasm_runtimes_for_components(
     conn: None,
     kv_store: KvStore::new(),
     fs_root_path: None,
+    egress_policy: Default::default(),
   });
 
   let mut sync_runtimes: Vec<(SqliteStore, SqliteFunctions)> = vec![];
@@ -74,6 +88,7 @@ pub(crate) fn wasm_runtimes_builder(
   rt: Option<tokio::runtime::Handle>,
   runtime_root_fs: Option<std::path::PathBuf>,
   shared_kv_store: Option<KvStore>,
+  config: &Reactive<Config>,
   dev: bool,
 ) -> Result<WasmRuntimeBuilder, AnyError> {
   let components_path = data_dir.root().join("wasm");
@@ -82,9 +97,21 @@ pub(crate) fn wasm_runtimes_builder(
     conn: Some(conn),
     kv_store: shared_kv_store.unwrap_or_default(),
     fs_root_path: runtime_root_fs.clone(),
+    egress_policy: parking_lot::RwLock::new(build_egress_policy(&config.ptr())),
   });
 
+  {
+    // Apply egress policy changes to already running components.
+    let shared_state = shared_state.clone();
+    config.add_observer(move |c| {
+      *shared_state.egress_policy.write() = build_egress_policy(c);
+    });
+  }
+
+  let config = config.clone();
   return Ok(Box::new(move || {
+    let limits = config.ptr().wasm_limits.clone().unwrap_or_default();
+
     let components = find_wasm_components(&components_path);
     if components.is_empty() {
       debug!("No WASM component found in {components_path:?}");
@@ -106,6 +133,10 @@ pub(crate) fn wasm_runtimes_builder(
               dev
             },
             tokio_runtime: rt.clone(),
+            limits: ResourceLimits {
+              max_memory_bytes: limits.max_memory_bytes.map(|b| b as usize),
+              deadline: limits.deadline_ms.map(Duration::from_millis),
+            },
           },
         );
       })
@@ -115,12 +146,30 @@ pub(crate) fn wasm_runtimes_builder(
   }));
 }
 
+fn build_egress_policy(config: &Config) -> EgressPolicy {
+  const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+  const DEFAULT_MAX_RESPONSE_BYTES: u64 = 10 * 1024 * 1024;
+
+  let egress = config.egress.clone().unwrap_or_default();
+  return EgressPolicy {
+    allowed_hosts: (!egress.allowed_hosts.is_empty()).then_some(egress.allowed_hosts),
+    timeout: Some(
+      egress
+        .timeout_sec
+        .map_or(DEFAULT_TIMEOUT, Duration::from_secs),
+    ),
+    max_response_bytes: Some(
+      egress
+        .max_response_size_bytes
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES) as usize,
+    ),
+  };
+}
+
 pub(crate) async fn install_routes_and_jobs(
   state: &AppState,
   runtime: Arc<RwLock<Runtime>>,
 ) -> Result<Option<Router<AppState>>, AnyError> {
-  use trailbase_wasm_runtime_host::Error as WasmError;
-
   let init_result = {
     let store = HttpStore::new(&*runtime.read().await).await?;
     store
@@ -131,53 +180,57 @@ pub(crate) async fn install_routes_and_jobs(
   };
 
   for (name, spec) in init_result.job_handlers {
-    let schedule = cron::Schedule::from_str(&spec)?;
     let store = HttpStore::new(&*runtime.read().await).await?;
-
-    let Some(job) = state.jobs().new_job(
-      None,
-      name.clone(),
-      schedule,
-      crate::scheduler::build_callback(move || {
-        let name = name.clone();
-        let store = store.clone();
-
-        return async move {
-          let uri = hyper::http::Uri::from_str(&format!("http://__job/?name={}", urlencode(&name)))
-            .map_err(|err| WasmError::Other(format!("Job URI: {err}")))?;
-
-          let request = hyper::Request::builder()
-            // NOTE: We cannot use a custom-scheme, since the wasi http
-            // implementation rejects everything but http and https.
-            .uri(uri)
-            .header(
-              "__context",
-              to_header_value(&HttpContext {
-                kind: HttpContextKind::Job,
-                registered_path: name,
-                path_params: vec![],
-                user: None,
-              })?,
-            )
-            .body(empty())
-            .map_err(|err| WasmError::Other(err.to_string()))?;
-
-          store.call_incoming_http_handler(request).await?;
-
-          Ok::<_, AnyError>(())
-        };
-      }),
-    ) else {
-      return Err("Failed to add job".into());
-    };
-
-    job.start();
+    install_job(state, store, name, &spec).await?;
   }
 
   debug!("Got {} WASM routes", init_result.http_handlers.len());
 
   let mut router = Router::<AppState>::new();
   for (method, path) in init_result.http_handlers {
+    if path.starts_with(UPLOAD_SCANNER_PATH_PREFIX) {
+      debug!("Installing WASM upload scanner: {path}");
+
+      state.register_upload_scanner(Arc::new(WasmUploadScanner {
+        store: HttpStore::new(&*runtime.read().await).await?,
+        registered_path: path,
+      }));
+      continue;
+    }
+
+    if let Some(queue) = path.strip_prefix(TASK_HANDLER_PATH_PREFIX) {
+      debug!("Installing WASM task handler: {path}");
+
+      state.task_queue().register_handler(
+        queue,
+        Arc::new(WasmTaskHandler {
+          store: HttpStore::new(&*runtime.read().await).await?,
+          registered_path: path.clone(),
+        }),
+      );
+      continue;
+    }
+
+    if let Some(hook) = path.strip_prefix(RECORD_HOOK_PATH_PREFIX) {
+      let Some((api_name, operation)) = hook
+        .rsplit_once('/')
+        .and_then(|(api_name, op)| Some((api_name, RecordHookOperation::from_name(op)?)))
+      else {
+        return Err(format!("Invalid record hook path: {path}").into());
+      };
+      debug!("Installing WASM record hook: {path}");
+
+      state.register_async_record_hook(
+        api_name,
+        operation,
+        Arc::new(WasmRecordHook {
+          store: HttpStore::new(&*runtime.read().await).await?,
+          registered_path: path.clone(),
+        }),
+      );
+      continue;
+    }
+
     debug!("Installing WASM route: {method:?}: {path}");
 
     // let runtime = runtime.clone();
@@ -254,6 +307,272 @@ pub(crate) async fn install_routes_and_jobs(
   return Ok(Some(router));
 }
 
+/// Registers and starts a job, which invokes the component's job handler `name` on `spec`.
+async fn install_job(
+  state: &AppState,
+  store: HttpStore,
+  name: String,
+  spec: &str,
+) -> Result<crate::scheduler::Job, AnyError> {
+  use trailbase_wasm_runtime_host::Error as WasmError;
+
+  let schedule = cron::Schedule::from_str(spec)?;
+  let Some(job) = state.jobs().new_job(
+    None,
+    name.clone(),
+    schedule,
+    crate::scheduler::build_callback(move || {
+      let name = name.clone();
+      let store = store.clone();
+
+      return async move {
+        let uri = hyper::http::Uri::from_str(&format!("http://__job/?name={}", urlencode(&name)))
+          .map_err(|err| WasmError::Other(format!("Job URI: {err}")))?;
+
+        let request = hyper::Request::builder()
+          // NOTE: We cannot use a custom-scheme, since the wasi http
+          // implementation rejects everything but http and https.
+          .uri(uri)
+          .header(
+            "__context",
+            to_header_value(&HttpContext {
+              kind: HttpContextKind::Job,
+              registered_path: name,
+              path_params: vec![],
+              user: None,
+            })?,
+          )
+          .body(empty())
+          .map_err(|err| WasmError::Other(err.to_string()))?;
+
+        // Surface failed runs, e.g. uncaught exceptions, in the job history.
+        let response = store.call_incoming_http_handler(request).await?;
+        check_response_status(response, "WASM job").await?;
+
+        Ok::<_, AnyError>(())
+      };
+    }),
+  ) else {
+    return Err("Failed to add job".into());
+  };
+
+  state.jobs().start_unless_paused(&job).await?;
+
+  return Ok(job);
+}
+
+/// Registered HTTP handlers with this prefix are installed as upload scanners rather than routes.
+const UPLOAD_SCANNER_PATH_PREFIX: &str = "/__upload_scanner/";
+
+/// Forwards files to a WASM component's upload scanner. Responses with a 2xx status code mean
+/// clean, a 4xx status rejects the file with the response body as reason.
+struct WasmUploadScanner {
+  store: HttpStore,
+  registered_path: String,
+}
+
+#[async_trait::async_trait]
+impl UploadScanner for WasmUploadScanner {
+  async fn scan(
+    &self,
+    file: &FileUpload,
+    mut contents: ScanStream,
+  ) -> Result<ScanVerdict, UploadScanError> {
+    // NOTE: Like request bodies, contents are buffered since there's no streaming into WASM yet.
+    let mut data = vec![];
+    while let Some(chunk) = contents.next().await {
+      data.extend_from_slice(&chunk?);
+    }
+
+    let mut builder = hyper::Request::builder()
+      .method(hyper::Method::POST)
+      .uri(format!("http://__upload_scanner{}", self.registered_path))
+      .header(
+        "__context",
+        to_header_value(&HttpContext {
+          kind: HttpContextKind::Http,
+          registered_path: self.registered_path.clone(),
+          path_params: vec![],
+          user: None,
+        })
+        .map_err(|err| UploadScanError::Scanner(err.into()))?,
+      );
+    if let Some(filename) = file.original_filename() {
+      builder = builder.header("x-filename", urlencode(filename));
+    }
+    if let Some(content_type) = file.content_type() {
+      builder = builder.header(hyper::header::CONTENT_TYPE, content_type);
+    }
+
+    let request = builder
+      .body(UnsyncBoxBody::new(
+        http_body_util::Full::new(Bytes::from(data)).map_err(|_| unreachable!()),
+      ))
+      .map_err(|err| UploadScanError::Scanner(err.into()))?;
+
+    let response = self
+      .store
+      .call_incoming_http_handler(request)
+      .await
+      .map_err(|err| UploadScanError::Scanner(err.into()))?;
+
+    let status = response.status();
+    if status.is_success() {
+      return Ok(ScanVerdict::Clean);
+    }
+    if status.is_client_error() {
+      let reason = match response.into_body().collect().await {
+        Ok(body) => String::from_utf8_lossy(&body.to_bytes()).to_string(),
+        Err(_) => status.to_string(),
+      };
+      return Ok(ScanVerdict::Rejected(reason));
+    }
+
+    return Err(UploadScanError::Scanner(
+      format!("WASM upload scanner responded: {status}").into(),
+    ));
+  }
+}
+
+/// Registered HTTP handlers with this prefix are installed as handlers for the task queue named
+/// by the remainder of the path rather than routes.
+const TASK_HANDLER_PATH_PREFIX: &str = "/__queue/";
+
+/// Forwards task payloads to a WASM component. Responses with a 2xx status code mean success,
+/// otherwise the task is retried.
+struct WasmTaskHandler {
+  store: HttpStore,
+  registered_path: String,
+}
+
+#[async_trait::async_trait]
+impl TaskHandler for WasmTaskHandler {
+  async fn handle(&self, payload: serde_json::Value) -> Result<(), TaskError> {
+    let request = hyper::Request::builder()
+      .method(hyper::Method::POST)
+      .uri(format!("http://__queue{}", self.registered_path))
+      .header(
+        "__context",
+        to_header_value(&HttpContext {
+          kind: HttpContextKind::Http,
+          registered_path: self.registered_path.clone(),
+          path_params: vec![],
+          user: None,
+        })?,
+      )
+      .header(hyper::header::CONTENT_TYPE, "application/json")
+      .body(UnsyncBoxBody::new(
+        http_body_util::Full::new(Bytes::from(payload.to_string())).map_err(|_| unreachable!()),
+      ))?;
+
+    let response = self.store.call_incoming_http_handler(request).await?;
+    return check_response_status(response, "WASM task handler").await;
+  }
+}
+
+/// Forwards Record API writes to a WASM component's record hook. Responses with a 2xx status code
+/// and a JSON object body replace the record, 4xx responses reject the request.
+struct WasmRecordHook {
+  store: HttpStore,
+  registered_path: String,
+}
+
+#[async_trait::async_trait]
+impl AsyncRecordHook for WasmRecordHook {
+  async fn call(
+    &self,
+    request: &RecordHookRequest,
+    user: Option<&User>,
+  ) -> Result<Option<serde_json::Map<String, serde_json::Value>>, RecordError> {
+    let body = serde_json::to_vec(request).map_err(|err| RecordError::Internal(err.into()))?;
+    let request = hyper::Request::builder()
+      .method(hyper::Method::POST)
+      .uri(format!("http://__record_hook{}", self.registered_path))
+      .header(
+        "__context",
+        to_header_value(&HttpContext {
+          kind: HttpContextKind::Http,
+          registered_path: self.registered_path.clone(),
+          path_params: vec![],
+          user: user.map(|u| HttpContextUser {
+            id: u.id.clone(),
+            email: u.email.clone(),
+            username: u.username.clone(),
+            csrf_token: u.csrf_token.clone(),
+          }),
+        })
+        .map_err(|err| RecordError::Internal(err.into()))?,
+      )
+      .header(hyper::header::CONTENT_TYPE, "application/json")
+      .body(UnsyncBoxBody::new(
+        http_body_util::Full::new(Bytes::from(body)).map_err(|_| unreachable!()),
+      ))
+      .map_err(|err| RecordError::Internal(err.into()))?;
+
+    let response = self
+      .store
+      .call_incoming_http_handler(request)
+      .await
+      .map_err(|err| RecordError::Internal(err.into()))?;
+
+    let status = response.status();
+    if status == StatusCode::FORBIDDEN {
+      return Err(RecordError::Forbidden);
+    }
+    if status.is_client_error() {
+      return Err(RecordError::BadRequest("Rejected by record hook"));
+    }
+
+    let body = response
+      .into_body()
+      .collect()
+      .await
+      .map_err(|_err| RecordError::Internal("Failed to read record hook response".into()))?
+      .to_bytes();
+    if !status.is_success() {
+      return Err(RecordError::Internal(
+        format!(
+          "WASM record hook responded {status}: {}",
+          String::from_utf8_lossy(&body)
+        )
+        .into(),
+      ));
+    }
+
+    if body.is_empty() {
+      return Ok(None);
+    }
+    return match serde_json::from_slice::<serde_json::Value>(&body) {
+      Ok(serde_json::Value::Object(record)) => Ok(Some(record)),
+      Ok(serde_json::Value::Null) => Ok(None),
+      _ => Err(RecordError::Internal(
+        format!(
+          "WASM record hook {} returned invalid record",
+          self.registered_path
+        )
+        .into(),
+      )),
+    };
+  }
+}
+
+/// Maps non-2xx responses of internal handler invocations, e.g. jobs, to errors.
+async fn check_response_status<B: hyper::body::Body>(
+  response: hyper::Response<B>,
+  context: &str,
+) -> Result<(), AnyError> {
+  let status = response.status();
+  if status.is_success() {
+    return Ok(());
+  }
+
+  let message = match response.into_body().collect().await {
+    Ok(body) => String::from_utf8_lossy(&body.to_bytes()).to_string(),
+    Err(_) => String::new(),
+  };
+  return Err(format!("{context} responded {status}: {message}").into());
+}
+
 #[inline]
 fn axum_method(method: trailbase_wasm_runtime_host::HttpMethodType) -> axum::routing::MethodFilter {
   use trailbase_wasm_runtime_host::HttpMethodType;
@@ -288,3 +607,48 @@ fn to_header_value(
   return hyper::http::HeaderValue::from_bytes(&serde_json::to_vec(&context).unwrap_or_default())
     .map_err(|_err| trailbase_wasm_runtime_host::Error::Encoding);
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+
+  const WASM_COMPONENT_PATH: &str = "../../client/testfixture/wasm/wasm_guest_testfixture.wasm";
+
+  #[tokio::test]
+  async fn test_install_jobs() {
+    let state = test_state(None).await.unwrap();
+    let runtime = Runtime::init(
+      WASM_COMPONENT_PATH.into(),
+      Arc::new(SharedState {
+        conn: Some(state.conn().clone()),
+        kv_store: KvStore::new(),
+        fs_root_path: None,
+        egress_policy: Default::default(),
+      }),
+      RuntimeOptions::default(),
+    )
+    .unwrap();
+    let runtime = Arc::new(RwLock::new(runtime));
+
+    install_routes_and_jobs(&state, runtime.clone())
+      .await
+      .unwrap();
+
+    // The job registered by the component shows up in the registry and runs.
+    let job = state.jobs().find_job("WASM-registered Job").unwrap();
+    assert!(job.next_run().is_some());
+    assert_eq!(state.jobs().run_job(job.id).await, Some(Ok(())));
+    let (_start, _duration, error) = job.latest().unwrap();
+    assert_eq!(error, None);
+
+    // Invalid cron specs are rejected.
+    let store = HttpStore::new(&*runtime.read().await).await.unwrap();
+    assert!(
+      install_job(&state, store, "invalid".to_string(), "* *")
+        .await
+        .is_err()
+    );
+    assert!(state.jobs().find_job("invalid").is_none());
+  }
+}
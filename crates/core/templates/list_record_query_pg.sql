@@ -26,7 +26,7 @@ FROM
 {%- endif %}
   {{ table_name }} AS _ROW_
 {%- for expanded in expanded_tables %}
-    LEFT JOIN "{{ expanded.foreign_table_name }}" AS F{{ loop.index0 }} ON _ROW_."{{ expanded.local_column_name }}" = F{{ loop.index0 }}."{{ expanded.foreign_column_name }}"
+    LEFT JOIN {{ expanded.foreign_table }} AS F{{ loop.index0 }} ON _ROW_."{{ expanded.local_column_name }}" = F{{ loop.index0 }}."{{ expanded.foreign_column_name }}"
 {%- endfor %}
 WHERE
   ({{ read_access_clause }}) AND ({{ filter_clause }})
@@ -17,6 +17,8 @@ fn test_admin_permissions() {
       state: _,
       main_router,
       admin_router,
+      grpc_router: _,
+      metrics_router: _,
       tls,
     } = Server::init(ServerOptions {
       data_dir: DataDir(data_dir.path().to_path_buf()),
@@ -0,0 +1,52 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Extension, Router};
+use axum_test::TestServer;
+
+use trailbase::{AppState, DataDir, Server, ServerOptions, User};
+
+async fn hello_handler(
+  State(state): State<AppState>,
+  Extension(greeting): Extension<&'static str>,
+  user: Option<User>,
+) -> String {
+  let app_name = state
+    .get_config()
+    .server
+    .application_name
+    .unwrap_or_default();
+  let subject = user
+    .and_then(|u| u.email)
+    .unwrap_or_else(|| "anonymous".to_string());
+  return format!("{greeting} {subject} from {app_name}");
+}
+
+#[tokio::test]
+async fn test_custom_routers() {
+  let data_dir = temp_dir::TempDir::new().unwrap();
+
+  let options = ServerOptions {
+    data_dir: DataDir(data_dir.path().to_path_buf()),
+    address: "localhost:4053".to_string(),
+    custom_routers: vec![
+      Router::new()
+        .route("/hello", get(hello_handler))
+        .layer(Extension("Hi")),
+    ],
+    ..Default::default()
+  };
+
+  let Server { main_router, .. } = Server::init(options).await.unwrap();
+
+  let (_address, router) = main_router;
+  let server = TestServer::new(router);
+
+  let response = server.get("/hello").await;
+  assert_eq!(response.status_code(), StatusCode::OK);
+  assert_eq!(response.text(), "Hi anonymous from TrailBase");
+
+  // Built-in routes are still served.
+  let response = server.get("/api/healthcheck").await;
+  assert_eq!(response.status_code(), StatusCode::OK);
+}
@@ -79,6 +79,8 @@ async fn test_record_apis() {
     state,
     main_router,
     admin_router,
+    grpc_router: _,
+    metrics_router: _,
     tls,
   } = Server::init(options.clone()).await.unwrap();
 
@@ -56,6 +56,15 @@ async fn test_spa_fallback() {
   assert_eq!(response.status_code(), StatusCode::OK);
   assert!(response.text().contains("SPA Index"));
 
+  // Unhashed files are revalidated using ETags.
+  assert_eq!(response.header("cache-control"), "no-cache");
+  let etag = response.header("etag");
+  let response = server
+    .get("/index.html")
+    .add_header("if-none-match", etag)
+    .await;
+  assert_eq!(response.status_code(), StatusCode::NOT_MODIFIED);
+
   // Existing CSS file should be served
   let response = server.get("/assets/style.css").await;
   assert_eq!(response.status_code(), StatusCode::OK);
@@ -127,7 +136,7 @@ async fn test_spa_fallback() {
   let response = server.get("/assets/missing.html").await;
   assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
 
-  // ServeDir behavior: redirect
+  // Directories requested w/o trailing slash redirect.
   let response = server.get("/docs").await;
   assert_eq!(response.status_code(), StatusCode::TEMPORARY_REDIRECT);
   assert_eq!(response.header("location"), "/docs/");
@@ -0,0 +1,50 @@
+use rusqlite::functions::FunctionFlags;
+
+use trailbase::{DataDir, Server, ServerOptions, SqliteFunctionRegistrar};
+
+#[tokio::test]
+async fn test_custom_sqlite_functions() {
+  let data_dir = temp_dir::TempDir::new().unwrap();
+
+  let options = ServerOptions {
+    data_dir: DataDir(data_dir.path().to_path_buf()),
+    address: "localhost:4054".to_string(),
+    sqlite_functions: vec![SqliteFunctionRegistrar::new(|conn| {
+      return conn.create_scalar_function(
+        "slugify",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |context| {
+          let s: String = context.get(0)?;
+          return Ok(s.to_lowercase().replace(' ', "-"));
+        },
+      );
+    })],
+    ..Default::default()
+  };
+
+  let Server { state, .. } = Server::init(options).await.unwrap();
+
+  // Custom functions can be used in schemas, e.g. generated columns.
+  state
+    .conn()
+    .execute_batch(
+      r#"
+        CREATE TABLE posts (
+          id    INTEGER PRIMARY KEY,
+          title TEXT NOT NULL,
+          slug  TEXT GENERATED ALWAYS AS (slugify(title)) VIRTUAL
+        ) STRICT;
+        INSERT INTO posts (title) VALUES ('Hello World');
+      "#,
+    )
+    .await
+    .unwrap();
+
+  let slug: Option<String> = state
+    .conn()
+    .read_query_value("SELECT slug FROM posts", ())
+    .await
+    .unwrap();
+  assert_eq!(slug.as_deref(), Some("hello-world"));
+}
@@ -2,7 +2,7 @@ use rcgen::{CertifiedKey, generate_simple_self_signed};
 use std::sync::Arc;
 use tokio_rustls::rustls::pki_types::{PrivateKeyDer, pem::PemObject};
 use tracing::*;
-use trailbase::{DataDir, Server, ServerOptions};
+use trailbase::{AcmeOptions, DataDir, InitError, Server, ServerOptions};
 
 #[test]
 fn test_https_serving() {
@@ -71,3 +71,73 @@ fn test_https_serving() {
     }
   });
 }
+
+#[tokio::test]
+async fn test_acme_options_validation() {
+  let data_dir = temp_dir::TempDir::new().unwrap();
+
+  let init = async |acme: AcmeOptions, manual_cert: bool| {
+    let (tls_cert, tls_key) = if manual_cert {
+      let CertifiedKey { cert, signing_key } =
+        generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+      let tls_key = PrivateKeyDer::from_pem_slice(signing_key.serialize_pem().as_bytes()).unwrap();
+      (Some(cert.der().clone()), Some(Arc::new(tls_key)))
+    } else {
+      (None, None)
+    };
+
+    return Server::init(ServerOptions {
+      data_dir: DataDir(data_dir.path().to_path_buf()),
+      address: "127.0.0.1:4026".to_string(),
+      tls_cert,
+      tls_key,
+      acme: Some(acme),
+      ..Default::default()
+    })
+    .await;
+  };
+
+  let acme = AcmeOptions {
+    domains: vec!["example.com".to_string()],
+    contact: vec!["admin@example.com".to_string()],
+    ..Default::default()
+  };
+
+  // ACME without domain.
+  assert!(matches!(
+    init(
+      AcmeOptions {
+        domains: vec![],
+        ..acme.clone()
+      },
+      false
+    )
+    .await,
+    Err(InitError::Tls(_))
+  ));
+
+  // ACME without contact.
+  assert!(matches!(
+    init(
+      AcmeOptions {
+        contact: vec![],
+        ..acme.clone()
+      },
+      false
+    )
+    .await,
+    Err(InitError::Tls(_))
+  ));
+
+  // ACME and manual certs are mutually exclusive.
+  assert!(matches!(
+    init(acme.clone(), true).await,
+    Err(InitError::Tls(_))
+  ));
+
+  // Same for certs in the data directory.
+  let certs_path = data_dir.path().join("secrets").join("certs");
+  std::fs::create_dir_all(&certs_path).unwrap();
+  std::fs::write(certs_path.join("cert.pem"), "").unwrap();
+  assert!(matches!(init(acme, false).await, Err(InitError::Tls(_))));
+}
@@ -0,0 +1,382 @@
+use aes_gcm_siv::aead::{Aead, AeadCore, KeyInit, OsRng, Payload, generic_array::GenericArray};
+use aes_gcm_siv::{Aes256GcmSiv, Key};
+use arc_swap::ArcSwap;
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use rusqlite::Error;
+use rusqlite::functions::{Context, FunctionFlags};
+use rusqlite::types::{Value, ValueRef};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use crate::password::{PasswordError, verify_password};
+
+const KEY_LENGTH: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+static ENCRYPTION_KEY: LazyLock<ArcSwap<Option<EncryptionKey>>> =
+  LazyLock::new(|| ArcSwap::from_pointee(None));
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum CryptoError {
+  #[error("Key is not base64")]
+  InvalidEncoding,
+  #[error("Key must be 256 bits")]
+  InvalidKeyLength,
+}
+
+/// 256-bit key used by the `encrypt()` and `decrypt()` SQL functions.
+#[derive(Clone)]
+pub struct EncryptionKey(Key<Aes256GcmSiv>);
+
+impl EncryptionKey {
+  pub fn from_base64(key: &str) -> Result<Self, CryptoError> {
+    let key = BASE64_STANDARD
+      .decode(key)
+      .or_else(|_| BASE64_URL_SAFE.decode(key))
+      .map_err(|_| CryptoError::InvalidEncoding)?;
+    if key.len() != KEY_LENGTH {
+      return Err(CryptoError::InvalidKeyLength);
+    }
+    return Ok(Self(Key::<Aes256GcmSiv>::clone_from_slice(&key)));
+  }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return f.write_str("EncryptionKey(<redacted>)");
+  }
+}
+
+/// Sets the process-wide key used by `encrypt()` and `decrypt()`. Unsetting it makes both fail.
+pub fn set_encryption_key(key: Option<EncryptionKey>) {
+  ENCRYPTION_KEY.swap(key.into());
+}
+
+/// Returns the raw bytes of TEXT and BLOB arguments.
+fn get_bytes<'a>(context: &'a Context, idx: usize) -> Result<Option<&'a [u8]>, Error> {
+  return match context.get_raw(idx) {
+    ValueRef::Null => Ok(None),
+    ValueRef::Text(bytes) | ValueRef::Blob(bytes) => Ok(Some(bytes)),
+    v => Err(Error::InvalidFunctionParameterType(idx, v.data_type())),
+  };
+}
+
+/// SHA-256 digest of a TEXT or BLOB value, e.g. `hex(sha256(col))`.
+fn sha256(context: &Context) -> Result<Value, Error> {
+  if context.len() != 1 {
+    return Err(Error::InvalidParameterCount(context.len(), 1));
+  }
+
+  return Ok(match get_bytes(context, 0)? {
+    Some(data) => Value::Blob(Sha256::digest(data).to_vec()),
+    None => Value::Null,
+  });
+}
+
+/// HMAC-SHA-256 of a message: `hmac_sha256(key, message)`.
+fn hmac_sha256(context: &Context) -> Result<Value, Error> {
+  if context.len() != 2 {
+    return Err(Error::InvalidParameterCount(context.len(), 2));
+  }
+
+  let (Some(key), Some(message)) = (get_bytes(context, 0)?, get_bytes(context, 1)?) else {
+    return Ok(Value::Null);
+  };
+
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length");
+  mac.update(message);
+  return Ok(Value::Blob(mac.finalize().into_bytes().to_vec()));
+}
+
+/// Checks a password against an Argon2 (or imported bcrypt) hash: `argon2_verify(password, hash)`.
+fn argon2_verify(context: &Context) -> Result<Value, Error> {
+  if context.len() != 2 {
+    return Err(Error::InvalidParameterCount(context.len(), 2));
+  }
+
+  let (Some(password), Some(hash)) = (get_bytes(context, 0)?, context.get_raw(1).as_str_or_null()?)
+  else {
+    return Ok(Value::Null);
+  };
+
+  return match verify_password(password, hash) {
+    Ok(()) => Ok(Value::Integer(1)),
+    Err(PasswordError::InvalidPassword) => Ok(Value::Integer(0)),
+    Err(err) => Err(Error::UserFunctionError(format!("Argon2: {err}").into())),
+  };
+}
+
+fn with_key<T>(f: impl FnOnce(&Aes256GcmSiv) -> Result<T, Error>) -> Result<T, Error> {
+  let key = ENCRYPTION_KEY.load();
+  let Some(ref key) = **key else {
+    return Err(Error::UserFunctionError(
+      "No encryption key configured".into(),
+    ));
+  };
+  return f(&Aes256GcmSiv::new(&key.0));
+}
+
+/// Optional associated data, e.g. a record's id, binding the cipher text to its context.
+fn associated_data<'a>(context: &'a Context) -> Result<Cow<'a, [u8]>, Error> {
+  if context.len() < 2 {
+    return Ok(Cow::Borrowed(&[]));
+  }
+  return match context.get_raw(1) {
+    ValueRef::Integer(i) => Ok(Cow::Owned(i.to_string().into_bytes())),
+    _ => Ok(Cow::Borrowed(get_bytes(context, 1)?.unwrap_or_default())),
+  };
+}
+
+/// Authenticated encryption of a TEXT or BLOB value with the configured key:
+/// `encrypt(plaintext[, associated_data])`. Returns a BLOB of [nonce | enc(payload) | tag].
+fn encrypt(context: &Context) -> Result<Value, Error> {
+  if context.is_empty() || context.len() > 2 {
+    return Err(Error::InvalidParameterCount(context.len(), 1));
+  }
+
+  let Some(plaintext) = get_bytes(context, 0)? else {
+    return Ok(Value::Null);
+  };
+  let aad = associated_data(context)?;
+
+  return with_key(|cipher| {
+    let nonce = Aes256GcmSiv::generate_nonce(&mut OsRng);
+    let sealed = cipher
+      .encrypt(
+        &nonce,
+        Payload {
+          msg: plaintext,
+          aad: &aad,
+        },
+      )
+      .map_err(|_| Error::UserFunctionError("Encryption failed".into()))?;
+
+    let mut buffer = Vec::with_capacity(NONCE_LEN + sealed.len());
+    buffer.extend_from_slice(&nonce);
+    buffer.extend_from_slice(&sealed);
+    return Ok(Value::Blob(buffer));
+  });
+}
+
+/// Inverse of [encrypt]: `decrypt(ciphertext[, associated_data])`. Returns a BLOB, i.e. use
+/// `CAST(decrypt(col) AS TEXT)` for text.
+fn decrypt(context: &Context) -> Result<Value, Error> {
+  if context.is_empty() || context.len() > 2 {
+    return Err(Error::InvalidParameterCount(context.len(), 1));
+  }
+
+  let Some(ciphertext) = get_bytes(context, 0)? else {
+    return Ok(Value::Null);
+  };
+  if ciphertext.len() < NONCE_LEN + TAG_LEN {
+    return Err(Error::UserFunctionError("Cipher text too short".into()));
+  }
+  let aad = associated_data(context)?;
+
+  return with_key(|cipher| {
+    let (nonce, msg) = ciphertext.split_at(NONCE_LEN);
+    let plaintext = cipher
+      .decrypt(GenericArray::from_slice(nonce), Payload { msg, aad: &aad })
+      .map_err(|_| Error::UserFunctionError("Invalid key or cipher text".into()))?;
+    return Ok(Value::Blob(plaintext));
+  });
+}
+
+pub(crate) fn register_extension_functions(db: &rusqlite::Connection) -> Result<(), Error> {
+  // WARN: Be careful with declaring INNOCUOUS. It allows "user-defined functions" to run
+  // when "trusted_schema=OFF", which means as part of: VIEWs, TRIGGERs, CHECK, DEFAULT,
+  // GENERATED cols, ... as opposed to just top-level SELECTs.
+
+  db.create_scalar_function(
+    "sha256",
+    1,
+    FunctionFlags::SQLITE_UTF8
+      | FunctionFlags::SQLITE_DETERMINISTIC
+      | FunctionFlags::SQLITE_INNOCUOUS,
+    sha256,
+  )?;
+
+  db.create_scalar_function(
+    "hmac_sha256",
+    2,
+    FunctionFlags::SQLITE_UTF8
+      | FunctionFlags::SQLITE_DETERMINISTIC
+      | FunctionFlags::SQLITE_INNOCUOUS,
+    hmac_sha256,
+  )?;
+
+  // NOTE: Not innocuous, since argon2 is deliberately expensive and must not be triggerable from
+  // untrusted schema objects.
+  db.create_scalar_function(
+    "argon2_verify",
+    2,
+    FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+    argon2_verify,
+  )?;
+
+  // NOTE: encrypt() uses a random nonce and is therefore not deterministic. It is innocuous to
+  // allow encrypting in TRIGGERs and DEFAULTs. decrypt(), on the other hand, is restricted to
+  // top-level queries, since any VIEW could otherwise reveal plaintext using the global key.
+  for n_args in [1, 2] {
+    db.create_scalar_function(
+      "encrypt",
+      n_args,
+      FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_INNOCUOUS,
+      encrypt,
+    )?;
+
+    db.create_scalar_function(
+      "decrypt",
+      n_args,
+      FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+      decrypt,
+    )?;
+  }
+
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hex(conn: &rusqlite::Connection, query: &str) -> String {
+    return conn
+      .query_row(&format!("SELECT hex({query})"), (), |row| row.get(0))
+      .unwrap();
+  }
+
+  #[test]
+  fn test_hashing() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    register_extension_functions(&conn).unwrap();
+
+    assert_eq!(
+      hex(&conn, "sha256('abc')"),
+      "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD"
+    );
+    // TEXT and BLOB inputs hash the same bytes.
+    assert_eq!(hex(&conn, "sha256('abc')"), hex(&conn, "sha256(X'616263')"));
+
+    // RFC 4231, test case 2.
+    assert_eq!(
+      hex(&conn, "hmac_sha256('Jefe', 'what do ya want for nothing?')"),
+      "5BDCC146BF60754E6A042426089575C75A003F089D2739839DEC58B964EC3843"
+    );
+
+    let null: Option<Vec<u8>> = conn
+      .query_row("SELECT sha256(NULL)", (), |row| row.get(0))
+      .unwrap();
+    assert_eq!(null, None);
+
+    let hash = "$argon2id$v=19$m=19456,t=2,p=1$QU8j9dRSzZ2tn/1e3BwgMg$yfperWEmAfO/7UJipZ3C7OEl4dYkjvRfr2CH4UgdE5E";
+    let verify = |password: &str| -> Result<bool, rusqlite::Error> {
+      return conn.query_row("SELECT argon2_verify($1, $2)", (password, hash), |row| {
+        row.get(0)
+      });
+    };
+    assert!(verify("secret").unwrap());
+    assert!(!verify("wrong").unwrap());
+    assert!(
+      conn
+        .query_row("SELECT argon2_verify('secret', 'invalid')", (), |_row| Ok(
+          ()
+        ))
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn test_encryption() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    register_extension_functions(&conn).unwrap();
+
+    set_encryption_key(None);
+    assert!(
+      conn
+        .query_row("SELECT encrypt('secret')", (), |_row| Ok(()))
+        .is_err()
+    );
+
+    assert_eq!(
+      EncryptionKey::from_base64("c2hvcnQ=").err(),
+      Some(CryptoError::InvalidKeyLength)
+    );
+    set_encryption_key(Some(
+      EncryptionKey::from_base64(&BASE64_STANDARD.encode([7u8; KEY_LENGTH])).unwrap(),
+    ));
+
+    let ciphertext: Vec<u8> = conn
+      .query_row("SELECT encrypt('secret', 'record-1')", (), |row| row.get(0))
+      .unwrap();
+    assert_eq!(ciphertext.len(), NONCE_LEN + "secret".len() + TAG_LEN);
+
+    let plaintext: String = conn
+      .query_row(
+        "SELECT CAST(decrypt($1, 'record-1') AS TEXT)",
+        [&ciphertext],
+        |row| row.get(0),
+      )
+      .unwrap();
+    assert_eq!(plaintext, "secret");
+
+    // Mismatching associated data.
+    assert!(
+      conn
+        .query_row("SELECT decrypt($1, 'record-2')", [&ciphertext], |_row| Ok(
+          ()
+        ))
+        .is_err()
+    );
+
+    // Integer associated data, e.g. ids, are bound by their textual representation.
+    let plaintext: String = conn
+      .query_row(
+        "SELECT CAST(decrypt(encrypt('secret', 1), '1') AS TEXT)",
+        (),
+        |row| row.get(0),
+      )
+      .unwrap();
+    assert_eq!(plaintext, "secret");
+
+    // Nonces are random.
+    let other: Vec<u8> = conn
+      .query_row("SELECT encrypt('secret', 'record-1')", (), |row| row.get(0))
+      .unwrap();
+    assert_ne!(ciphertext, other);
+  }
+
+  #[test]
+  fn test_untrusted_schema() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    register_extension_functions(&conn).unwrap();
+    conn.pragma_update(None, "trusted_schema", "OFF").unwrap();
+
+    conn
+      .execute_batch(
+        r#"
+          CREATE VIEW hashed AS SELECT sha256('secret') AS value;
+          CREATE VIEW decrypted AS SELECT decrypt(X'00') AS value;
+          CREATE VIEW verified AS SELECT argon2_verify('secret', 'hash') AS value;
+        "#,
+      )
+      .unwrap();
+
+    assert!(
+      conn
+        .query_row("SELECT value FROM hashed", (), |_row| Ok(()))
+        .is_ok()
+    );
+
+    // decrypt() and argon2_verify() must not run as part of untrusted schema objects.
+    for view in ["decrypted", "verified"] {
+      let err = conn
+        .query_row(&format!("SELECT value FROM {view}"), (), |_row| Ok(()))
+        .unwrap_err();
+      assert!(err.to_string().contains("unsafe use"), "{view}: {err}");
+    }
+  }
+}
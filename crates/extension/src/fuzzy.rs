@@ -0,0 +1,208 @@
+use rusqlite::Error;
+use rusqlite::functions::{Context, FunctionFlags};
+use std::collections::HashSet;
+
+/// Upper bound on the length of `levenshtein()` inputs in characters, since the distance is
+/// computed in O(n*m).
+const MAX_LEVENSHTEIN_LEN: usize = 1024;
+/// Upper bound on the length of `trigram_similarity()` inputs in bytes.
+const MAX_TRIGRAM_INPUT_LEN: usize = 64 * 1024;
+
+/// Edit distance between two strings in characters: `levenshtein(a, b)`.
+fn levenshtein(context: &Context) -> Result<Option<i64>, Error> {
+  if context.len() != 2 {
+    return Err(Error::InvalidParameterCount(context.len(), 2));
+  }
+
+  let (Some(a), Some(b)) = (
+    context.get_raw(0).as_str_or_null()?,
+    context.get_raw(1).as_str_or_null()?,
+  ) else {
+    return Ok(None);
+  };
+
+  let a: Vec<char> = a.chars().collect();
+  let b: Vec<char> = b.chars().collect();
+  if a.len() > MAX_LEVENSHTEIN_LEN || b.len() > MAX_LEVENSHTEIN_LEN {
+    return Err(Error::UserFunctionError(
+      format!("levenshtein: input exceeds {MAX_LEVENSHTEIN_LEN} characters").into(),
+    ));
+  }
+
+  return Ok(Some(levenshtein_impl(&a, &b) as i64));
+}
+
+fn levenshtein_impl(a: &[char], b: &[char]) -> usize {
+  let mut prev: Vec<usize> = (0..=b.len()).collect();
+  let mut curr = vec![0; b.len() + 1];
+
+  for (i, ca) in a.iter().enumerate() {
+    curr[0] = i + 1;
+    for (j, cb) in b.iter().enumerate() {
+      let substitution = prev[j] + usize::from(ca != cb);
+      curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+    }
+    std::mem::swap(&mut prev, &mut curr);
+  }
+
+  return prev[b.len()];
+}
+
+/// Similarity of two strings in [0, 1] based on shared trigrams, similar to Postgres' `pg_trgm`:
+/// `trigram_similarity(a, b)`.
+fn trigram_similarity(context: &Context) -> Result<Option<f64>, Error> {
+  if context.len() != 2 {
+    return Err(Error::InvalidParameterCount(context.len(), 2));
+  }
+
+  let (Some(a), Some(b)) = (
+    context.get_raw(0).as_str_or_null()?,
+    context.get_raw(1).as_str_or_null()?,
+  ) else {
+    return Ok(None);
+  };
+
+  if a.len() > MAX_TRIGRAM_INPUT_LEN || b.len() > MAX_TRIGRAM_INPUT_LEN {
+    return Err(Error::UserFunctionError(
+      format!("trigram_similarity: input exceeds {MAX_TRIGRAM_INPUT_LEN} bytes").into(),
+    ));
+  }
+
+  return Ok(Some(trigram_similarity_impl(a, b)));
+}
+
+fn trigram_similarity_impl(a: &str, b: &str) -> f64 {
+  let a = trigrams(a);
+  let b = trigrams(b);
+
+  let union = a.union(&b).count();
+  if union == 0 {
+    return 0.0;
+  }
+  return a.intersection(&b).count() as f64 / union as f64;
+}
+
+/// Trigrams of the lower-cased, alphanumeric words of `s`. Like `pg_trgm`, words are padded with
+/// two leading and one trailing space.
+fn trigrams(s: &str) -> HashSet<[char; 3]> {
+  let mut trigrams = HashSet::new();
+  for word in s.split(|c: char| !c.is_alphanumeric()) {
+    if word.is_empty() {
+      continue;
+    }
+
+    let padded: Vec<char> = "  "
+      .chars()
+      .chain(word.chars().flat_map(char::to_lowercase))
+      .chain(std::iter::once(' '))
+      .collect();
+    for window in padded.windows(3) {
+      trigrams.insert([window[0], window[1], window[2]]);
+    }
+  }
+  return trigrams;
+}
+
+pub(crate) fn register_extension_functions(db: &rusqlite::Connection) -> Result<(), Error> {
+  // WARN: Be careful with declaring INNOCUOUS. It allows "user-defined functions" to run
+  // when "trusted_schema=OFF", which means as part of: VIEWs, TRIGGERs, CHECK, DEFAULT,
+  // GENERATED cols, ... as opposed to just top-level SELECTs.
+
+  db.create_scalar_function(
+    "levenshtein",
+    2,
+    FunctionFlags::SQLITE_UTF8
+      | FunctionFlags::SQLITE_DETERMINISTIC
+      | FunctionFlags::SQLITE_INNOCUOUS,
+    levenshtein,
+  )?;
+
+  db.create_scalar_function(
+    "trigram_similarity",
+    2,
+    FunctionFlags::SQLITE_UTF8
+      | FunctionFlags::SQLITE_DETERMINISTIC
+      | FunctionFlags::SQLITE_INNOCUOUS,
+    trigram_similarity,
+  )?;
+
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_levenshtein() {
+    let distance = |a: &str, b: &str| -> usize {
+      return levenshtein_impl(
+        &a.chars().collect::<Vec<_>>(),
+        &b.chars().collect::<Vec<_>>(),
+      );
+    };
+
+    assert_eq!(distance("", ""), 0);
+    assert_eq!(distance("abc", ""), 3);
+    assert_eq!(distance("kitten", "sitting"), 3);
+    assert_eq!(distance("flaw", "lawn"), 2);
+    // Characters rather than bytes.
+    assert_eq!(distance("über", "uber"), 1);
+  }
+
+  #[test]
+  fn test_trigram_similarity() {
+    assert_eq!(trigram_similarity_impl("word", "word"), 1.0);
+    assert_eq!(trigram_similarity_impl("Word", "word"), 1.0);
+    assert_eq!(trigram_similarity_impl("abc", "xyz"), 0.0);
+    assert_eq!(trigram_similarity_impl("", ""), 0.0);
+
+    // Shares "  w", " wo", "wor" and "ord" out of 7 distinct trigrams.
+    let similarity = trigram_similarity_impl("word", "words");
+    assert!((similarity - 4.0 / 7.0).abs() < 1e-9, "{similarity}");
+  }
+
+  #[test]
+  fn test_sql_functions() {
+    let conn = crate::connect_sqlite(None, None).unwrap();
+
+    let distance: i64 = conn
+      .query_row("SELECT levenshtein('kitten', 'sitting')", (), |row| {
+        row.get(0)
+      })
+      .unwrap();
+    assert_eq!(distance, 3);
+
+    let null: Option<i64> = conn
+      .query_row("SELECT levenshtein(NULL, 'sitting')", (), |row| row.get(0))
+      .unwrap();
+    assert_eq!(null, None);
+
+    let too_long = "a".repeat(MAX_LEVENSHTEIN_LEN + 1);
+    assert!(
+      conn
+        .query_row("SELECT levenshtein($1, 'a')", [&too_long], |_row| Ok(()))
+        .is_err()
+    );
+
+    conn
+      .execute_batch(
+        r#"
+          CREATE TABLE names (name TEXT NOT NULL) STRICT;
+          INSERT INTO names (name) VALUES ('Jonathan'), ('Johnathan'), ('Margaret');
+        "#,
+      )
+      .unwrap();
+    let mut stmt = conn
+      .prepare(
+        "SELECT name FROM names WHERE trigram_similarity(name, 'jonathon') > 0.2 ORDER BY name",
+      )
+      .unwrap();
+    let names: Vec<String> = stmt
+      .query_map((), |row| row.get(0))
+      .unwrap()
+      .collect::<Result<_, _>>()
+      .unwrap();
+    assert_eq!(names, vec!["Johnathan", "Jonathan"]);
+  }
+}
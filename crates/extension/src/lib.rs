@@ -6,11 +6,13 @@ use rusqlite::Connection;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+pub mod crypto;
 pub mod geoip;
 pub mod jsonschema;
 pub mod password;
 
 mod base64;
+mod fuzzy;
 mod regex;
 mod uuid;
 mod validators;
@@ -50,9 +52,44 @@ pub fn apply_default_pragmas(conn: &Connection) -> Result<(), rusqlite::Error> {
   return Ok(());
 }
 
+/// Registers additional application-defined SQL functions, e.g. scalar, aggregate or window
+/// functions, on a connection. Allows embedders to install domain-specific SQL helpers alongside
+/// the built-in ones without patching this crate.
+#[derive(Clone)]
+pub struct SqliteFunctionRegistrar(
+  Arc<dyn Fn(&Connection) -> Result<(), rusqlite::Error> + Send + Sync>,
+);
+
+impl SqliteFunctionRegistrar {
+  pub fn new(
+    f: impl Fn(&Connection) -> Result<(), rusqlite::Error> + Send + Sync + 'static,
+  ) -> Self {
+    return Self(Arc::new(f));
+  }
+
+  pub fn register(&self, conn: &Connection) -> Result<(), rusqlite::Error> {
+    return (self.0)(conn);
+  }
+}
+
+impl std::fmt::Debug for SqliteFunctionRegistrar {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return f.write_str("SqliteFunctionRegistrar");
+  }
+}
+
 pub fn connect_sqlite(
   path: Option<PathBuf>,
   registry: Option<Arc<RwLock<JsonSchemaRegistry>>>,
+) -> Result<Connection, Error> {
+  return connect_sqlite_with_functions(path, registry, &[]);
+}
+
+/// Like [connect_sqlite] but additionally registers the given custom functions.
+pub fn connect_sqlite_with_functions(
+  path: Option<PathBuf>,
+  registry: Option<Arc<RwLock<JsonSchemaRegistry>>>,
+  functions: &[SqliteFunctionRegistrar],
 ) -> Result<Connection, Error> {
   // NOTE: We used to initialize C extensions here as well, such as sqlean and sqlite-vec, however
   // this has now been moved to the top-level CLI.
@@ -70,6 +107,9 @@ pub fn connect_sqlite(
   };
 
   register_all_extension_functions(&conn, registry)?;
+  for registrar in functions {
+    registrar.register(&conn)?;
+  }
 
   apply_default_pragmas(&conn)?;
 
@@ -89,10 +129,12 @@ pub fn register_all_extension_functions(
 
   uuid::register_extension_functions(db)?;
   password::register_extension_functions(db)?;
+  crypto::register_extension_functions(db)?;
   jsonschema::register_extension_functions(db, registry)?;
   geoip::register_extension_functions(db)?;
   base64::register_extension_functions(db)?;
   regex::register_extension_functions(db)?;
+  fuzzy::register_extension_functions(db)?;
   validators::register_extension_functions(db)?;
 
   return Ok(());
@@ -119,6 +161,30 @@ mod test {
     assert_eq!(uuid.get_version_num(), 7);
   }
 
+  #[test]
+  fn test_custom_functions() {
+    let registrar = SqliteFunctionRegistrar::new(|conn| {
+      return conn.create_scalar_function(
+        "twice",
+        1,
+        rusqlite::functions::FunctionFlags::SQLITE_UTF8
+          | rusqlite::functions::FunctionFlags::SQLITE_DETERMINISTIC,
+        |context| Ok(2 * context.get::<i64>(0)?),
+      );
+    });
+    let conn = connect_sqlite_with_functions(None, None, &[registrar]).unwrap();
+
+    let value: i64 = conn
+      .query_row("SELECT twice(21)", (), |row| row.get(0))
+      .unwrap();
+    assert_eq!(value, 42);
+
+    // Built-in functions are still available.
+    conn
+      .query_row("SELECT uuid_v7()", (), |_row| Ok(()))
+      .unwrap();
+  }
+
   #[test]
   fn test_uuids() {
     let conn = connect_sqlite(None, None).unwrap();
@@ -1,12 +1,21 @@
 use quick_cache::sync::Cache;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use rusqlite::Error;
 use rusqlite::functions::{Context, FunctionFlags};
+use rusqlite::types::ValueRef;
 use std::sync::LazyLock;
 
 // NOTE: Regexps are using Arcs internally and are cheap to clone.
 static CACHE: LazyLock<Cache<String, Regex>> = LazyLock::new(|| Cache::new(256));
 
+/// Upper bound on the length of patterns in bytes.
+const MAX_PATTERN_LEN: usize = 4 * 1024;
+/// Upper bound on the heap size of compiled patterns, e.g. guarding against nested repetitions
+/// like `(\w{1000}){1000}` blowing up.
+const MAX_COMPILED_SIZE: usize = 1024 * 1024;
+/// Upper bound on the length of matched inputs in bytes.
+const MAX_INPUT_LEN: usize = 16 * 1024 * 1024;
+
 /// Custom regexp function.
 ///
 /// NOTE: Sqlite supports `col REGEXP pattern` in expression, which requires a custom
@@ -28,20 +37,82 @@ fn regexp_impl(re: &str, contents: Option<&str>) -> Result<bool, Error> {
     return Ok(true);
   };
 
-  let re = re.to_string();
+  let pattern = compile(re)?;
+  check_input_len(contents)?;
+  return Ok(pattern.is_match(contents));
+}
+
+/// Extracts the first match or capture group: `regexp_extract(contents, pattern[, group])`, where
+/// group is either a capture group's index or name. Returns NULL if there's no match.
+fn regexp_extract(context: &Context) -> Result<Option<String>, Error> {
+  if context.len() != 2 && context.len() != 3 {
+    return Err(Error::InvalidParameterCount(context.len(), 2));
+  }
 
-  return match CACHE.get(&re) {
-    Some(pattern) => Ok(pattern.is_match(contents)),
-    None => {
-      let pattern =
-        Regex::new(&re).map_err(|err| Error::UserFunctionError(format!("Regex: {err}").into()))?;
+  let Some(contents) = context.get_raw(0).as_str_or_null()? else {
+    return Ok(None);
+  };
+  let pattern = compile(context.get_raw(1).as_str()?)?;
+  check_input_len(contents)?;
 
-      let valid = pattern.is_match(contents);
-      CACHE.insert(re.to_string(), pattern);
+  let Some(captures) = pattern.captures(contents) else {
+    return Ok(None);
+  };
 
-      Ok(valid)
+  let group = (context.len() == 3).then(|| context.get_raw(2));
+  let m = match group {
+    None => captures.get(0),
+    Some(ValueRef::Integer(index)) => {
+      let index = usize::try_from(index)
+        .ok()
+        .filter(|index| *index < pattern.captures_len())
+        .ok_or_else(|| Error::UserFunctionError(format!("Regex: invalid group {index}").into()))?;
+      captures.get(index)
+    }
+    Some(ValueRef::Text(name)) => {
+      let name = std::str::from_utf8(name).map_err(|err| Error::UserFunctionError(err.into()))?;
+      if !pattern.capture_names().any(|n| n == Some(name)) {
+        return Err(Error::UserFunctionError(
+          format!("Regex: invalid group '{name}'").into(),
+        ));
+      }
+      captures.name(name)
     }
+    Some(v) => return Err(Error::InvalidFunctionParameterType(2, v.data_type())),
   };
+
+  return Ok(m.map(|m| m.as_str().to_string()));
+}
+
+/// Compiles the given pattern or returns a cached, previously compiled one.
+fn compile(re: &str) -> Result<Regex, Error> {
+  if let Some(pattern) = CACHE.get(re) {
+    return Ok(pattern);
+  }
+
+  if re.len() > MAX_PATTERN_LEN {
+    return Err(Error::UserFunctionError(
+      format!("Regex: pattern exceeds {MAX_PATTERN_LEN} bytes").into(),
+    ));
+  }
+
+  let pattern = RegexBuilder::new(re)
+    .size_limit(MAX_COMPILED_SIZE)
+    .build()
+    .map_err(|err| Error::UserFunctionError(format!("Regex: {err}").into()))?;
+
+  CACHE.insert(re.to_string(), pattern.clone());
+
+  return Ok(pattern);
+}
+
+fn check_input_len(contents: &str) -> Result<(), Error> {
+  if contents.len() > MAX_INPUT_LEN {
+    return Err(Error::UserFunctionError(
+      format!("Regex: input exceeds {MAX_INPUT_LEN} bytes").into(),
+    ));
+  }
+  return Ok(());
 }
 
 pub(crate) fn register_extension_functions(db: &rusqlite::Connection) -> Result<(), Error> {
@@ -60,6 +131,17 @@ pub(crate) fn register_extension_functions(db: &rusqlite::Connection) -> Result<
     regexp,
   )?;
 
+  for n_args in [2, 3] {
+    db.create_scalar_function(
+      "regexp_extract",
+      n_args,
+      FunctionFlags::SQLITE_UTF8
+        | FunctionFlags::SQLITE_DETERMINISTIC
+        | FunctionFlags::SQLITE_INNOCUOUS,
+      regexp_extract,
+    )?;
+  }
+
   return Ok(());
 }
 
@@ -127,4 +209,39 @@ mod tests {
       assert_eq!(cnt, 2);
     }
   }
+
+  #[test]
+  fn test_regexp_extract() {
+    let conn = crate::connect_sqlite(None, None).unwrap();
+    let extract = |query: &str| -> Result<Option<String>, rusqlite::Error> {
+      return conn.query_row(query, (), |row| row.get(0));
+    };
+
+    assert_eq!(
+      extract(r#"SELECT regexp_extract('order-1234', '\d+')"#).unwrap(),
+      Some("1234".to_string())
+    );
+    assert_eq!(
+      extract(r#"SELECT regexp_extract('a@b.org', '@(\w+)\.', 1)"#).unwrap(),
+      Some("b".to_string())
+    );
+    assert_eq!(
+      extract(r#"SELECT regexp_extract('a@b.org', '\.(?<tld>\w+)$', 'tld')"#).unwrap(),
+      Some("org".to_string())
+    );
+    assert_eq!(extract("SELECT regexp_extract('abc', 'x')").unwrap(), None);
+    assert_eq!(extract("SELECT regexp_extract(NULL, 'x')").unwrap(), None);
+
+    // Invalid groups.
+    assert!(extract("SELECT regexp_extract('abc', 'b', 1)").is_err());
+    assert!(extract("SELECT regexp_extract('abc', 'b', 'missing')").is_err());
+  }
+
+  #[test]
+  fn test_limits() {
+    assert!(regexp_impl(&"a".repeat(MAX_PATTERN_LEN + 1), Some("a")).is_err());
+    // Compiles to a program exceeding the size limit.
+    assert!(regexp_impl(r"(\w{1000}){1000}", Some("a")).is_err());
+    assert!(regexp_impl("a", Some(&"a".repeat(MAX_INPUT_LEN + 1))).is_err());
+  }
 }
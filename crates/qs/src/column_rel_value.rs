@@ -23,17 +23,19 @@ pub enum CompareOp {
 }
 
 impl CompareOp {
+  /// Parses an operator qualifier. The "$" prefix is optional, i.e. `[$gte]` and `[gte]` are
+  /// equivalent.
   pub fn from(qualifier: &str) -> Option<Self> {
-    return match qualifier {
-      "$eq" => Some(Self::Equal),
-      "$ne" => Some(Self::NotEqual),
-      "$gte" => Some(Self::GreaterThanEqual),
-      "$gt" => Some(Self::GreaterThan),
-      "$lte" => Some(Self::LessThanEqual),
-      "$lt" => Some(Self::LessThan),
-      "$is" => Some(Self::Is),
-      "$like" => Some(Self::Like),
-      "$re" => Some(Self::Regexp),
+    return match qualifier.strip_prefix('$').unwrap_or(qualifier) {
+      "eq" => Some(Self::Equal),
+      "ne" => Some(Self::NotEqual),
+      "gte" => Some(Self::GreaterThanEqual),
+      "gt" => Some(Self::GreaterThan),
+      "lte" => Some(Self::LessThanEqual),
+      "lt" => Some(Self::LessThan),
+      "is" => Some(Self::Is),
+      "like" => Some(Self::Like),
+      "re" => Some(Self::Regexp),
       // Spatial Types:
       "@within" => Some(Self::StWithin),
       "@intersects" => Some(Self::StIntersects),
@@ -97,8 +99,10 @@ where
 
   return match op {
     CompareOp::Is => match value {
-      serde_value::Value::String(value) if value == "NULL" => Ok(Value::String("NULL".to_string())),
-      serde_value::Value::String(value) if value == "!NULL" => {
+      serde_value::Value::String(value) if value.eq_ignore_ascii_case("NULL") => {
+        Ok(Value::String("NULL".to_string()))
+      }
+      serde_value::Value::String(value) if value.eq_ignore_ascii_case("!NULL") => {
         Ok(Value::String("NOT NULL".to_string()))
       }
       _ => Err(Error::invalid_type(unexpected(&value), &"NULL or !NULL")),
@@ -130,6 +134,46 @@ where
   };
 }
 
+/// Parses the operand of the list operator `[column][$in]=a,b,c`. Explicitly indexed lists,
+/// i.e. `[column][$in][0]=a&[column][$in][1]=b`, are accepted as well.
+pub(crate) fn parse_value_list<'de, D>(value: serde_value::Value) -> Result<Vec<Value>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  use crate::util::unexpected;
+
+  let values: Vec<Value> = match value {
+    serde_value::Value::String(list) => list
+      .split(',')
+      .map(|v| Value::unparse(v.to_string()))
+      .collect(),
+    serde_value::Value::Seq(seq) => seq
+      .into_iter()
+      .map(|v| parse_value::<D>(CompareOp::Equal, v))
+      .collect::<Result<Vec<_>, _>>()?,
+    v => {
+      return Err(Error::invalid_type(
+        unexpected(&v),
+        &"comma-separated list of values",
+      ));
+    }
+  };
+
+  if values.is_empty() || values.len() > MAX_LIST_LEN {
+    return Err(Error::invalid_length(
+      values.len(),
+      &"between 1 and 128 values",
+    ));
+  }
+
+  return Ok(values);
+}
+
+#[inline]
+pub(crate) fn is_list_op(qualifier: &str) -> bool {
+  return qualifier == "$in" || qualifier == "in";
+}
+
 #[inline]
 fn validate_wkt(s: &str) -> bool {
   if s.chars().all(|c| c != ';' && c != '\'') {
@@ -187,3 +231,4 @@ where
 }
 
 const OP_ERR: &str = "one of [$eq, $ne, $lt, ...]";
+const MAX_LIST_LEN: usize = 128;
@@ -0,0 +1,261 @@
+/// Parser for boolean filter expressions, e.g.:
+///
+///   filter=(status=active||status=pending)&&age[gt]=18
+///
+/// `&&` binds stronger than `||` and parentheses can be used for grouping. Each comparison has
+/// the same `column[op]=value` shape as the bracketed filter syntax, i.e. supports the same
+/// operators. Values extend until the next `)`, `&&` or `||` unless double-quoted.
+///
+/// NOTE: Within a query string, "&" needs to be percent-encoded as "%26". Unencoded `&&` following
+/// a `filter=` expression are rejected rather than silently dropping the remainder.
+use crate::filter::{Combiner, ValueOrComposite, serde_value_to_column_filter};
+
+pub(crate) fn parse_filter_expression<'de, D>(expr: &str) -> Result<ValueOrComposite, D::Error>
+where
+  D: serde::de::Deserializer<'de>,
+{
+  use serde::de::Error;
+
+  let mut parser = Parser {
+    input: expr,
+    pos: 0,
+  };
+  let result = parser.parse_or::<D>(0)?;
+
+  parser.skip_whitespace();
+  if parser.pos != expr.len() {
+    return Err(Error::custom(format!(
+      "unexpected input at {}: {}",
+      parser.pos,
+      &expr[parser.pos..]
+    )));
+  }
+
+  return Ok(result);
+}
+
+struct Parser<'a> {
+  input: &'a str,
+  pos: usize,
+}
+
+impl Parser<'_> {
+  fn rest(&self) -> &str {
+    return &self.input[self.pos..];
+  }
+
+  fn skip_whitespace(&mut self) {
+    let rest = self.rest();
+    self.pos += rest.len() - rest.trim_start().len();
+  }
+
+  fn consume(&mut self, token: &str) -> bool {
+    self.skip_whitespace();
+    if self.rest().starts_with(token) {
+      self.pos += token.len();
+      return true;
+    }
+    return false;
+  }
+
+  fn parse_or<'de, D>(&mut self, depth: usize) -> Result<ValueOrComposite, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    let mut operands = vec![self.parse_and::<D>(depth)?];
+    while self.consume("||") {
+      operands.push(self.parse_and::<D>(depth)?);
+    }
+    return Ok(combine(Combiner::Or, operands));
+  }
+
+  fn parse_and<'de, D>(&mut self, depth: usize) -> Result<ValueOrComposite, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    let mut operands = vec![self.parse_primary::<D>(depth)?];
+    while self.consume("&&") {
+      operands.push(self.parse_primary::<D>(depth)?);
+    }
+    return Ok(combine(Combiner::And, operands));
+  }
+
+  fn parse_primary<'de, D>(&mut self, depth: usize) -> Result<ValueOrComposite, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    use serde::de::Error;
+
+    if self.consume("(") {
+      // Limit recursion depth consistent with the bracketed syntax.
+      if depth >= 5 {
+        return Err(Error::custom("Recursion limit exceeded"));
+      }
+
+      let inner = self.parse_or::<D>(depth + 1)?;
+      if !self.consume(")") {
+        return Err(Error::custom(format!("expected ')' at {}", self.pos)));
+      }
+      return Ok(inner);
+    }
+
+    return self.parse_comparison::<D>();
+  }
+
+  fn parse_comparison<'de, D>(&mut self) -> Result<ValueOrComposite, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    use serde::de::Error;
+    use serde_value::Value;
+
+    self.skip_whitespace();
+    let rest = self.rest();
+    let column_len = rest.find(['[', '=']).unwrap_or(rest.len());
+    let column = rest[..column_len].trim().to_string();
+    if column.is_empty() {
+      return Err(Error::custom(format!("expected column at {}", self.pos)));
+    }
+    self.pos += column_len;
+
+    let op: Option<String> = if self.rest().starts_with('[') {
+      let Some(len) = self.rest().find(']') else {
+        return Err(Error::custom(format!("expected ']' at {}", self.pos)));
+      };
+      let op = self.rest()[1..len].to_string();
+      self.pos += len + 1;
+      Some(op)
+    } else {
+      None
+    };
+
+    if !self.rest().starts_with('=') {
+      return Err(Error::custom(format!("expected '=' at {}", self.pos)));
+    }
+    self.pos += 1;
+
+    let value = self.parse_value::<D>()?;
+
+    return serde_value_to_column_filter::<D>(
+      column,
+      match op {
+        Some(op) => Value::Map([(Value::String(op), Value::String(value))].into()),
+        None => Value::String(value),
+      },
+    );
+  }
+
+  fn parse_value<'de, D>(&mut self) -> Result<String, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    use serde::de::Error;
+
+    let rest = self.rest();
+    if let Some(quoted) = rest.strip_prefix('"') {
+      let Some(len) = quoted.find('"') else {
+        return Err(Error::custom(format!("unterminated quote at {}", self.pos)));
+      };
+      let value = quoted[..len].to_string();
+      self.pos += len + 2;
+      return Ok(value);
+    }
+
+    let len = [rest.find(')'), rest.find("&&"), rest.find("||")]
+      .into_iter()
+      .flatten()
+      .min()
+      .unwrap_or(rest.len());
+    let value = rest[..len].trim_end().to_string();
+    self.pos += len;
+    return Ok(value);
+  }
+}
+
+fn combine(combiner: Combiner, mut operands: Vec<ValueOrComposite>) -> ValueOrComposite {
+  if operands.len() == 1 {
+    return operands.pop().expect("len == 1");
+  }
+  return ValueOrComposite::Composite(combiner, operands);
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::column_rel_value::{ColumnOpValue, CompareOp};
+  use crate::filter::{Combiner, ValueOrComposite};
+  use crate::query::Query;
+  use crate::value::Value;
+
+  fn cov(column: &str, op: CompareOp, value: Value) -> ValueOrComposite {
+    return ValueOrComposite::Value(ColumnOpValue {
+      column: column.to_string(),
+      op,
+      value,
+    });
+  }
+
+  /// Parses the literal expression as part of a full query string.
+  fn parse(expr: &str) -> Result<ValueOrComposite, serde_qs::Error> {
+    let expr = expr.replace('&', "%26").replace(' ', "%20");
+    return Query::parse(&format!("limit=5&filter={expr}&count=true")).map(|q| {
+      assert_eq!(q.limit, Some(5));
+      assert_eq!(q.count, Some(true));
+      return q.filter.expect("filter");
+    });
+  }
+
+  #[test]
+  fn test_filter_expression() {
+    assert_eq!(
+      parse("(status=active||status=pending)&&age[gt]=18").unwrap(),
+      ValueOrComposite::Composite(
+        Combiner::And,
+        vec![
+          ValueOrComposite::Composite(
+            Combiner::Or,
+            vec![
+              cov("status", CompareOp::Equal, Value::String("active".into())),
+              cov("status", CompareOp::Equal, Value::String("pending".into())),
+            ]
+          ),
+          cov("age", CompareOp::GreaterThan, Value::Integer(18)),
+        ]
+      )
+    );
+
+    // && binds stronger than ||.
+    assert_eq!(
+      parse("a=1||b=2&&c[$is]=NULL").unwrap(),
+      ValueOrComposite::Composite(
+        Combiner::Or,
+        vec![
+          cov("a", CompareOp::Equal, Value::Integer(1)),
+          ValueOrComposite::Composite(
+            Combiner::And,
+            vec![
+              cov("b", CompareOp::Equal, Value::Integer(2)),
+              cov("c", CompareOp::Is, Value::String("NULL".into())),
+            ]
+          ),
+        ]
+      )
+    );
+
+    // Quoted values may contain reserved characters.
+    assert_eq!(
+      parse(r#"name="a && (b)""#).unwrap(),
+      cov("name", CompareOp::Equal, Value::String("a && (b)".into()))
+    );
+
+    assert!(parse("").is_err());
+    assert!(parse("(a=1").is_err());
+    assert!(parse("a=1)").is_err());
+    assert!(parse("a=1&&").is_err());
+    assert!(parse("a[foo]=1").is_err());
+    assert!(parse("((((((a=1))))))").is_err());
+
+    // An unencoded `&&` would split the query string and must not silently drop `b=2`.
+    assert!(Query::parse("filter=a=1&&b=2").is_err());
+    assert!(Query::parse("filter=(a=1||b=2)&&c=3&limit=5").is_err());
+  }
+}
@@ -10,7 +10,9 @@ use itertools::Itertools;
 /// filters[and][0][or][0][column0]=value0&[and][0][or][1][column1]=value1
 use std::collections::BTreeMap;
 
-use crate::column_rel_value::{ColumnOpValue, CompareOp, serde_value_to_single_column_rel_value};
+use crate::column_rel_value::{
+  ColumnOpValue, CompareOp, is_list_op, parse_value_list, serde_value_to_single_column_rel_value,
+};
 use crate::value::Value;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -156,7 +158,14 @@ where
     return Err(Error::custom("Recursion limit exceeded"));
   }
 
-  // We always expect [key] = value, i.e. a Map[key] = value.
+  // Top-level boolean expression, e.g. `filter=(a=1||a=2)&&b[gt]=3`.
+  if let Value::String(ref expr) = value
+    && depth == 0
+  {
+    return crate::expression::parse_filter_expression::<D>(expr);
+  }
+
+  // Otherwise, we always expect [key] = value, i.e. a Map[key] = value.
   let Value::Map(mut m) = value else {
     return Err(Error::invalid_type(
       crate::util::unexpected(&value),
@@ -192,19 +201,15 @@ where
           Combiner::And,
           m.into_iter()
             .map(|(key, value)| {
-              return Ok(ValueOrComposite::Value(
-                serde_value_to_single_column_rel_value::<D>(
-                  col_name.to_string(),
-                  Value::Map(BTreeMap::from([(key, value)])),
-                )?,
-              ));
+              return serde_value_to_column_filter::<D>(
+                col_name.to_string(),
+                Value::Map(BTreeMap::from([(key, value)])),
+              );
             })
             .collect::<Result<Vec<_>, _>>()?,
         )),
         // For any other string-type key, turn into a single value.
-        (_key, v) => Ok(ValueOrComposite::Value(
-          serde_value_to_single_column_rel_value::<D>(key, v)?,
-        )),
+        (_key, v) => serde_value_to_column_filter::<D>(key, v),
       }
     }
     // Multiple different keys on the same same level, i.e. no explicit grouping by a single key
@@ -218,6 +223,52 @@ where
   };
 }
 
+/// Parses the filter for a single column.
+///
+/// List operators, i.e. `[column][$in]=a,b,c`, are expanded into an OR-composite of equality
+/// filters. This way they're parameterized like any other filter and work for subscriptions alike.
+pub(crate) fn serde_value_to_column_filter<'de, D>(
+  column: String,
+  value: serde_value::Value,
+) -> Result<ValueOrComposite, D::Error>
+where
+  D: serde::de::Deserializer<'de>,
+{
+  use serde::de::Error;
+  use serde_value::Value;
+
+  return match value {
+    Value::Map(mut m)
+      if m.len() == 1
+        && matches!(m.first_key_value(), Some((Value::String(op), _)) if is_list_op(op)) =>
+    {
+      if !crate::util::sanitize_column_name(&column) {
+        return Err(Error::custom(format!(
+          "invalid column name for filter: {column}"
+        )));
+      }
+
+      let (_op, list) = m.pop_first().expect("len() == 1");
+      Ok(ValueOrComposite::Composite(
+        Combiner::Or,
+        parse_value_list::<D>(list)?
+          .into_iter()
+          .map(|value| {
+            return ValueOrComposite::Value(ColumnOpValue {
+              column: column.clone(),
+              op: CompareOp::Equal,
+              value,
+            });
+          })
+          .collect(),
+      ))
+    }
+    v => Ok(ValueOrComposite::Value(
+      serde_value_to_single_column_rel_value::<D>(column, v)?,
+    )),
+  };
+}
+
 /// Recursively combine nested filter expressions.
 fn combine<'de, D>(
   combiner: Combiner,
@@ -399,6 +450,94 @@ mod tests {
     assert_eq!(q2_explicit, f2.to_query());
   }
 
+  #[test]
+  fn test_filter_operators() {
+    let qs = Config::new();
+
+    // Operators can be given w/o "$" prefix.
+    let f0 = qs
+      .deserialize_str::<Query>("filter[col0][gte]=5")
+      .unwrap()
+      .filter
+      .unwrap();
+    assert_eq!(
+      f0,
+      ValueOrComposite::Value(ColumnOpValue {
+        column: "col0".to_string(),
+        op: CompareOp::GreaterThanEqual,
+        value: Value::Integer(5),
+      })
+    );
+    assert_eq!("filter[col0][$gte]=5", f0.to_query());
+
+    let f1 = qs
+      .deserialize_str::<Query>("filter[col0][is]=null")
+      .unwrap()
+      .filter
+      .unwrap();
+    assert_eq!(
+      f1,
+      ValueOrComposite::Value(ColumnOpValue {
+        column: "col0".to_string(),
+        op: CompareOp::Is,
+        value: Value::String("NULL".to_string()),
+      })
+    );
+
+    assert!(
+      qs.deserialize_str::<Query>("filter[col0][$unknown]=5")
+        .is_err()
+    );
+    assert!(qs.deserialize_str::<Query>("filter[col0][is]=foo").is_err());
+
+    // List operator is expanded into an OR-composite.
+    let f2 = qs
+      .deserialize_str::<Query>("filter[col0][in]=a,2")
+      .unwrap()
+      .filter
+      .unwrap();
+    let expected = ValueOrComposite::Composite(
+      Combiner::Or,
+      vec![
+        ValueOrComposite::Value(ColumnOpValue {
+          column: "col0".to_string(),
+          op: CompareOp::Equal,
+          value: Value::String("a".to_string()),
+        }),
+        ValueOrComposite::Value(ColumnOpValue {
+          column: "col0".to_string(),
+          op: CompareOp::Equal,
+          value: Value::Integer(2),
+        }),
+      ],
+    );
+    assert_eq!(f2, expected);
+
+    let f3 = qs
+      .deserialize_str::<Query>("filter[col0][$in][0]=a&filter[col0][$in][1]=2")
+      .unwrap()
+      .filter
+      .unwrap();
+    assert_eq!(f3, expected);
+
+    // Combined with other operators on the same column.
+    let f4 = qs
+      .deserialize_str::<Query>("filter[col0][$in]=a,b&filter[col0][$ne]=c")
+      .unwrap()
+      .filter
+      .unwrap();
+    let ValueOrComposite::Composite(Combiner::And, v) = f4 else {
+      panic!("{f4:?}");
+    };
+    assert_eq!(v.len(), 2);
+
+    let too_long = (0..129).map(|i| i.to_string()).join(",");
+    assert!(
+      qs.deserialize_str::<Query>(&format!("filter[col0][$in]={too_long}"))
+        .is_err()
+    );
+  }
+
   #[test]
   fn test_filter_to_sql() {
     let v0 = ValueOrComposite::Value(ColumnOpValue {
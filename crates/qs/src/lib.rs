@@ -3,6 +3,7 @@
 #![warn(clippy::await_holding_lock, clippy::inefficient_to_string)]
 
 mod column_rel_value;
+mod expression;
 mod filter;
 mod query;
 mod util;
@@ -10,5 +11,5 @@ mod value;
 
 pub use column_rel_value::{ColumnOpValue, CompareOp};
 pub use filter::{Combiner, ValueOrComposite};
-pub use query::{Cursor, CursorType, Expand, FilterQuery, Order, OrderPrecedent, Query};
+pub use query::{Cursor, CursorType, Expand, FilterQuery, Order, OrderPrecedent, Query, Select};
 pub use value::Value;
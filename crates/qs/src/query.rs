@@ -3,7 +3,7 @@ use itertools::Itertools;
 use serde::Deserialize;
 
 use crate::filter::ValueOrComposite;
-use crate::util::deserialize_bool;
+use crate::util::{deserialize_bool, deserialize_limit};
 
 pub type Error = serde_qs::Error;
 
@@ -59,22 +59,12 @@ pub struct Order {
   pub columns: Vec<(String, OrderPrecedent)>,
 }
 
-impl<'de> serde::de::Deserialize<'de> for Order {
-  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-  where
-    D: serde::de::Deserializer<'de>,
-  {
-    use serde::de::Error;
-    use serde_value::Value;
-
-    let value = Value::deserialize(deserializer)?;
-    let Value::String(str) = value else {
-      return Err(Error::invalid_type(
-        crate::util::unexpected(&value),
-        &"comma separated column names to order by",
-      ));
-    };
+impl std::str::FromStr for Order {
+  type Err = String;
 
+  /// Parses comma separated column names, optionally prefixed with "+" (ascending, default) or "-"
+  /// (descending).
+  fn from_str(str: &str) -> Result<Self, Self::Err> {
     let columns = str
       .split(",")
       .map(|v| {
@@ -85,10 +75,7 @@ impl<'de> serde::de::Deserialize<'de> for Order {
         };
 
         if !crate::util::sanitize_column_name(&col_order.0) {
-          return Err(Error::custom(format!(
-            "invalid column name for order: {}",
-            col_order.0
-          )));
+          return Err(format!("invalid column name for order: {}", col_order.0));
         }
 
         return Ok(col_order);
@@ -96,16 +83,33 @@ impl<'de> serde::de::Deserialize<'de> for Order {
       .collect::<Result<Vec<_>, _>>()?;
 
     if columns.len() > 5 {
-      return Err(Error::invalid_length(
-        5,
-        &"more more than 5 order dimension",
-      ));
+      return Err("more than 5 order dimensions".to_string());
     }
 
     return Ok(Order { columns });
   }
 }
 
+impl<'de> serde::de::Deserialize<'de> for Order {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    use serde::de::Error;
+    use serde_value::Value;
+
+    let value = Value::deserialize(deserializer)?;
+    let Value::String(str) = value else {
+      return Err(Error::invalid_type(
+        crate::util::unexpected(&value),
+        &"comma separated column names to order by",
+      ));
+    };
+
+    return str.parse().map_err(Error::custom);
+  }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Expand {
   pub columns: Vec<String>,
@@ -151,11 +155,50 @@ impl<'de> serde::de::Deserialize<'de> for Expand {
   }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Select {
+  pub columns: Vec<String>,
+}
+
+impl<'de> serde::de::Deserialize<'de> for Select {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    use serde::de::Error;
+    use serde_value::Value;
+
+    let value = Value::deserialize(deserializer)?;
+    let Value::String(str) = value else {
+      return Err(Error::invalid_type(
+        crate::util::unexpected(&value),
+        &"comma separated column names to select",
+      ));
+    };
+
+    let columns = str
+      .split(",")
+      .map(|column_name| {
+        if column_name.is_empty() || !crate::util::sanitize_column_name(column_name) {
+          return Err(Error::custom(format!(
+            "invalid column name for select: {column_name}",
+          )));
+        }
+
+        return Ok(column_name.to_string());
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    return Ok(Select { columns });
+  }
+}
+
 #[derive(Clone, Default, Debug, PartialEq, Deserialize)]
 pub struct Query {
   /// Pagination parameters:
   ///
-  /// Max number of elements returned per page.
+  /// Max number of elements returned per page. `limit=none` yields [Query::NO_LIMIT].
+  #[serde(default, deserialize_with = "deserialize_limit")]
   pub limit: Option<usize>,
   /// Cursor to page.
   pub cursor: Option<String>,
@@ -169,6 +212,9 @@ pub struct Query {
   /// Which foreign key columns to expand (only when allowed by configuration).
   pub expand: Option<Expand>,
 
+  /// Which columns to return. All by default.
+  pub select: Option<Select>,
+
   /// Ordering. It's a vector for &order=-col0,+col1,col2
   pub order: Option<Order>,
 
@@ -178,7 +224,13 @@ pub struct Query {
 }
 
 impl Query {
+  /// Limit representing `limit=none`, i.e. a request for all elements. It's up to the caller to
+  /// decide whether that's permissible.
+  pub const NO_LIMIT: usize = usize::MAX;
+
   pub fn parse(query: &str) -> Result<Query, Error> {
+    reject_split_filter_expression(query)?;
+
     // NOTE: We rely on non-strict mode to parse `filter[col0]=a&b%filter[col1]=c`.
     let qs = serde_qs::Config::new().max_depth(9).use_form_encoding(true);
     return qs.deserialize_bytes::<Query>(query.as_bytes());
@@ -188,8 +240,10 @@ impl Query {
   pub fn to_query(&self) -> String {
     let mut pairs: Vec<String> = vec![];
 
-    if let Some(limit) = self.limit {
-      pairs.push(format!("limit={limit}"));
+    match self.limit {
+      Some(Self::NO_LIMIT) => pairs.push("limit=none".to_string()),
+      Some(limit) => pairs.push(format!("limit={limit}")),
+      None => {}
     }
 
     if let Some(ref cursor) = self.cursor {
@@ -211,6 +265,13 @@ impl Query {
       pairs.push(format!("expand={s}"));
     }
 
+    if let Some(ref select) = self.select
+      && !select.columns.is_empty()
+    {
+      let s = select.columns.join(",");
+      pairs.push(format!("select={s}"));
+    }
+
     if let Some(ref order) = self.order {
       let s = order
         .columns
@@ -241,6 +302,8 @@ pub struct FilterQuery {
 
 impl FilterQuery {
   pub fn parse(query: &str) -> Result<FilterQuery, Error> {
+    reject_split_filter_expression(query)?;
+
     // NOTE: We rely on non-strict mode to parse `filter[col0]=a&b%filter[col1]=c`.
     let qs = serde_qs::Config::new().max_depth(9).use_form_encoding(true);
     return qs.deserialize_bytes::<FilterQuery>(query.as_bytes());
@@ -255,6 +318,21 @@ impl FilterQuery {
   }
 }
 
+/// Rejects `filter=` expressions followed by an empty parameter, e.g. `filter=a=1&&b=2`. An
+/// unencoded `&&` would otherwise split the query string and silently drop the remainder of the
+/// expression. Empty parameters elsewhere are ignored as before.
+fn reject_split_filter_expression(query: &str) -> Result<(), Error> {
+  let params: Vec<&str> = query.split('&').collect();
+  for (index, param) in params.iter().enumerate() {
+    if param.starts_with("filter=") && matches!(params.get(index + 1..index + 3), Some(["", _])) {
+      return Err(<Error as serde::de::Error>::custom(
+        "'&&' in filter expressions must be percent-encoded as '%26%26'",
+      ));
+    }
+  }
+  return Ok(());
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -270,6 +348,14 @@ mod tests {
   fn test_query_basic_parsing() {
     assert_eq!(Query::parse("").unwrap(), Query::default());
     assert_eq!(Query::parse("unknown=foo").unwrap(), Query::default());
+    assert_eq!(
+      Query::parse("limit=5&&count=true&").unwrap(),
+      Query {
+        limit: Some(5),
+        count: Some(true),
+        ..Default::default()
+      }
+    );
 
     // NOTE: The filter value contains a '&', which will not parse if we're using query instead
     // of form encoding. Test explicitly that we properly allow '&'s.
@@ -338,6 +424,17 @@ mod tests {
       }
     );
     assert!(Query::parse("offset=-1").is_err());
+    assert!(Query::parse("limit=-1").is_err());
+    assert_eq!(
+      Query::parse("limit=none").unwrap().limit,
+      Some(Query::NO_LIMIT)
+    );
+    assert_eq!(
+      Query::parse(&Query::parse("limit=none").unwrap().to_query())
+        .unwrap()
+        .limit,
+      Some(Query::NO_LIMIT)
+    );
 
     // Make sure, unknown query paramms are simply ignored.
     assert_eq!(
@@ -359,6 +456,9 @@ mod tests {
       expand: Some(Expand {
         columns: vec!["a".to_string(), "b".to_string()],
       }),
+      select: Some(Select {
+        columns: vec!["a".to_string(), "c".to_string()],
+      }),
       order: Some(Order {
         columns: vec![
           ("a".to_string(), OrderPrecedent::Ascending),
@@ -375,6 +475,7 @@ mod tests {
     assert!(s.contains("offset=2"));
     assert!(s.contains("count=true"));
     assert!(s.contains("expand=a,b"));
+    assert!(s.contains("select=a,c"));
     assert!(s.contains("order=a,-b"));
   }
 
@@ -412,6 +513,23 @@ mod tests {
     assert!(qs.deserialize_str::<Query>("expand=a,b,c,d,e,f").is_err());
   }
 
+  #[test]
+  fn test_query_select_parsing() {
+    let qs = Config::new();
+
+    assert!(qs.deserialize_str::<Query>("select=").is_err());
+    assert!(qs.deserialize_str::<Query>("select=a,,b").is_err());
+    assert!(qs.deserialize_str::<Query>("select=a\"").is_err());
+    assert_eq!(
+      qs.deserialize_str::<Query>("select=a,b")
+        .unwrap()
+        .select
+        .unwrap()
+        .columns,
+      vec!["a".to_string(), "b".to_string()]
+    );
+  }
+
   #[test]
   fn test_query_filter_parsing() {
     let qs = Config::new();
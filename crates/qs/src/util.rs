@@ -41,6 +41,30 @@ where
   ));
 }
 
+/// Deserializes a limit, where "none" requests all elements, see [crate::Query::NO_LIMIT].
+pub(crate) fn deserialize_limit<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+  D: serde::de::Deserializer<'de>,
+{
+  use serde::de::Error;
+  use serde_value::Value;
+
+  let value = Value::deserialize(deserializer)?;
+  if let Value::String(ref str) = value {
+    if str.eq_ignore_ascii_case("none") {
+      return Ok(Some(crate::Query::NO_LIMIT));
+    }
+    if let Ok(limit) = str.parse::<usize>() {
+      return Ok(Some(limit));
+    }
+  }
+
+  return Err(Error::invalid_type(
+    crate::util::unexpected(&value),
+    &"non-negative integer or none",
+  ));
+}
+
 pub(crate) fn unexpected(value: &'_ serde_value::Value) -> Unexpected<'_> {
   use serde_value::Value;
 
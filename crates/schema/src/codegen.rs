@@ -0,0 +1,664 @@
+//! Generates client model types for Dart, Kotlin and Swift from JSON schemas, e.g. the ones built
+//! for Record APIs by [crate::json_schema::build_json_schema]. TypeScript is handled by
+//! [crate::typescript].
+//!
+//! Unlike TypeScript, these languages have no structural unions. Columns are therefore mapped to
+//! primitives, lists of primitives or, for anything more complex like JSON columns or files, to the
+//! respective language's dynamic JSON type.
+
+use serde_json::{Map, Value};
+
+pub use crate::typescript::to_type_name;
+
+/// Number of nested references after which they aren't resolved anymore, e.g. for recursive
+/// schemas.
+const MAX_REFS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+  Dart,
+  Kotlin,
+  Swift,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum FieldType {
+  Integer,
+  Number,
+  String,
+  Boolean,
+  Array(Box<FieldType>),
+  Json,
+}
+
+#[derive(Clone, Debug)]
+struct Field {
+  /// Name of the JSON property, i.e. the column name.
+  json_name: String,
+  /// Sanitized identifier.
+  name: String,
+  field_type: FieldType,
+  /// Field may be absent or null.
+  optional: bool,
+}
+
+/// Builds a model type named `type_name` from the given JSON object schema, including
+/// (de-)serialization support, i.e. `fromJson`/`toJson` for Dart, `@Serializable` for Kotlin and
+/// `Codable` for Swift.
+pub fn json_schema_to_model(language: Language, type_name: &str, schema: &Value) -> String {
+  let fields = object_fields(language, schema);
+  return match language {
+    Language::Dart => dart_model(type_name, &fields),
+    Language::Kotlin => kotlin_model(type_name, &fields),
+    Language::Swift => swift_model(type_name, &fields),
+  };
+}
+
+/// Dynamic JSON type used by Swift models, which unlike Dart and Kotlin has no built-in one.
+pub const SWIFT_JSON_VALUE: &str = r#"import Foundation
+
+public enum JSONValue: Codable, Sendable, Equatable {
+  case null
+  case bool(Bool)
+  case number(Double)
+  case string(String)
+  case array([JSONValue])
+  case object([String: JSONValue])
+
+  public init(from decoder: Decoder) throws {
+    let container = try decoder.singleValueContainer()
+    if container.decodeNil() {
+      self = .null
+    } else if let value = try? container.decode(Bool.self) {
+      self = .bool(value)
+    } else if let value = try? container.decode(Double.self) {
+      self = .number(value)
+    } else if let value = try? container.decode(String.self) {
+      self = .string(value)
+    } else if let value = try? container.decode([JSONValue].self) {
+      self = .array(value)
+    } else {
+      self = .object(try container.decode([String: JSONValue].self))
+    }
+  }
+
+  public func encode(to encoder: Encoder) throws {
+    var container = encoder.singleValueContainer()
+    switch self {
+    case .null: try container.encodeNil()
+    case .bool(let value): try container.encode(value)
+    case .number(let value): try container.encode(value)
+    case .string(let value): try container.encode(value)
+    case .array(let value): try container.encode(value)
+    case .object(let value): try container.encode(value)
+    }
+  }
+}
+"#;
+
+fn object_fields(language: Language, schema: &Value) -> Vec<Field> {
+  let empty = Map::new();
+  let defs = schema
+    .get("$defs")
+    .and_then(|d| d.as_object())
+    .unwrap_or(&empty);
+
+  let Some(obj) = resolve(schema, defs, 0) else {
+    return vec![];
+  };
+  let required: Vec<&str> = match obj.get("required") {
+    Some(Value::Array(required)) => required.iter().filter_map(|r| r.as_str()).collect(),
+    _ => vec![],
+  };
+  let Some(Value::Object(properties)) = obj.get("properties") else {
+    return vec![];
+  };
+
+  return properties
+    .iter()
+    .map(|(name, property)| {
+      let (field_type, nullable) = field_type(property, defs, 0);
+      return Field {
+        json_name: name.clone(),
+        name: identifier(language, name),
+        field_type,
+        optional: nullable || !required.contains(&name.as_str()),
+      };
+    })
+    .collect();
+}
+
+fn resolve<'a>(
+  schema: &'a Value,
+  defs: &'a Map<String, Value>,
+  refs: usize,
+) -> Option<&'a Map<String, Value>> {
+  let obj = schema.as_object()?;
+  if let Some(reference) = obj.get("$ref").and_then(|r| r.as_str()) {
+    if refs >= MAX_REFS {
+      return None;
+    }
+    let def = defs.get(reference.strip_prefix("#/$defs/")?)?;
+    return resolve(def, defs, refs + 1);
+  }
+  return Some(obj);
+}
+
+/// Returns the field's type and whether it's nullable.
+fn field_type(schema: &Value, defs: &Map<String, Value>, refs: usize) -> (FieldType, bool) {
+  let Some(obj) = resolve(schema, defs, refs) else {
+    return (FieldType::Json, true);
+  };
+
+  if let Some(value) = obj.get("const") {
+    return (literal_type(value), value.is_null());
+  }
+
+  if let Some(Value::Array(values)) = obj.get("enum") {
+    let nullable = values.iter().any(|v| v.is_null());
+    return (
+      single(values.iter().filter(|v| !v.is_null()).map(literal_type)),
+      nullable,
+    );
+  }
+
+  for key in ["oneOf", "anyOf"] {
+    if let Some(Value::Array(schemas)) = obj.get(key) {
+      let mut nullable = false;
+      let mut types = vec![];
+      for schema in schemas {
+        if schema.get("type").and_then(|t| t.as_str()) == Some("null") {
+          nullable = true;
+          continue;
+        }
+        let (t, n) = field_type(schema, defs, refs + 1);
+        nullable |= n;
+        types.push(t);
+      }
+      return (single(types.into_iter()), nullable);
+    }
+  }
+
+  return match obj.get("type") {
+    Some(Value::String(t)) => (primitive_type(t, obj, defs, refs), false),
+    Some(Value::Array(types)) => {
+      let types: Vec<&str> = types.iter().filter_map(|t| t.as_str()).collect();
+      let nullable = types.contains(&"null");
+      (
+        single(
+          types
+            .into_iter()
+            .filter(|t| *t != "null")
+            .map(|t| primitive_type(t, obj, defs, refs)),
+        ),
+        nullable,
+      )
+    }
+    _ => (FieldType::Json, true),
+  };
+}
+
+fn primitive_type(
+  t: &str,
+  obj: &Map<String, Value>,
+  defs: &Map<String, Value>,
+  refs: usize,
+) -> FieldType {
+  return match t {
+    "string" => FieldType::String,
+    "integer" => FieldType::Integer,
+    "number" => FieldType::Number,
+    "boolean" => FieldType::Boolean,
+    "array" => match obj
+      .get("items")
+      .map(|items| field_type(items, defs, refs + 1))
+    {
+      Some((FieldType::Json, _)) | Some((_, true)) | None => FieldType::Json,
+      Some((item, false)) => FieldType::Array(Box::new(item)),
+    },
+    _ => FieldType::Json,
+  };
+}
+
+fn literal_type(value: &Value) -> FieldType {
+  return match value {
+    Value::String(_) => FieldType::String,
+    Value::Bool(_) => FieldType::Boolean,
+    Value::Number(n) if n.is_i64() => FieldType::Integer,
+    Value::Number(_) => FieldType::Number,
+    _ => FieldType::Json,
+  };
+}
+
+/// Collapses alternatives into a single type, falling back to JSON for actual unions.
+fn single(mut types: impl Iterator<Item = FieldType>) -> FieldType {
+  let Some(first) = types.next() else {
+    return FieldType::Json;
+  };
+  let mut result = first;
+  for t in types {
+    result = match (result, t) {
+      (a, b) if a == b => a,
+      (FieldType::Integer, FieldType::Number) | (FieldType::Number, FieldType::Integer) => {
+        FieldType::Number
+      }
+      _ => FieldType::Json,
+    };
+  }
+  return result;
+}
+
+const DART_KEYWORDS: &[&str] = &[
+  "assert", "break", "case", "catch", "class", "const", "continue", "default", "do", "else",
+  "enum", "extends", "false", "final", "finally", "for", "if", "in", "is", "new", "null",
+  "rethrow", "return", "super", "switch", "this", "throw", "true", "try", "var", "void", "while",
+  "with",
+];
+
+const KOTLIN_KEYWORDS: &[&str] = &[
+  "as",
+  "break",
+  "class",
+  "continue",
+  "do",
+  "else",
+  "false",
+  "for",
+  "fun",
+  "if",
+  "in",
+  "interface",
+  "is",
+  "null",
+  "object",
+  "package",
+  "return",
+  "super",
+  "this",
+  "throw",
+  "true",
+  "try",
+  "typealias",
+  "typeof",
+  "val",
+  "var",
+  "when",
+  "while",
+];
+
+const SWIFT_KEYWORDS: &[&str] = &[
+  "as",
+  "associatedtype",
+  "break",
+  "case",
+  "catch",
+  "class",
+  "continue",
+  "default",
+  "defer",
+  "deinit",
+  "do",
+  "else",
+  "enum",
+  "extension",
+  "fallthrough",
+  "false",
+  "fileprivate",
+  "for",
+  "func",
+  "guard",
+  "if",
+  "import",
+  "in",
+  "init",
+  "inout",
+  "internal",
+  "is",
+  "let",
+  "nil",
+  "open",
+  "operator",
+  "private",
+  "protocol",
+  "public",
+  "repeat",
+  "rethrows",
+  "return",
+  "self",
+  "Self",
+  "static",
+  "struct",
+  "subscript",
+  "super",
+  "switch",
+  "throw",
+  "throws",
+  "true",
+  "try",
+  "typealias",
+  "var",
+  "where",
+  "while",
+];
+
+/// Converts a property name to a valid identifier. Keywords get an underscore suffix.
+fn identifier(language: Language, name: &str) -> String {
+  let mut ident: String = name
+    .chars()
+    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+    .collect();
+  // NOTE: Leading underscores make Dart identifiers private, which aren't allowed as named
+  // parameters.
+  if ident.is_empty()
+    || ident.starts_with(|c: char| c.is_ascii_digit())
+    || (language == Language::Dart && ident.starts_with('_'))
+  {
+    ident.insert(0, 'c');
+  }
+
+  let keywords = match language {
+    Language::Dart => DART_KEYWORDS,
+    Language::Kotlin => KOTLIN_KEYWORDS,
+    Language::Swift => SWIFT_KEYWORDS,
+  };
+  if keywords.contains(&ident.as_str()) {
+    ident.push('_');
+  }
+  return ident;
+}
+
+/// Double-quoted string literal. Dart and Kotlin additionally interpolate `$`.
+fn string_literal(language: Language, s: &str) -> String {
+  let literal = Value::String(s.to_string()).to_string();
+  return match language {
+    Language::Dart | Language::Kotlin => literal.replace('$', "\\$"),
+    Language::Swift => literal,
+  };
+}
+
+fn dart_type(t: &FieldType) -> String {
+  return match t {
+    FieldType::Integer => "int".to_string(),
+    FieldType::Number => "double".to_string(),
+    FieldType::String => "String".to_string(),
+    FieldType::Boolean => "bool".to_string(),
+    FieldType::Array(item) => format!("List<{}>", dart_type(item)),
+    FieldType::Json => "Object".to_string(),
+  };
+}
+
+/// Dart expression converting the decoded JSON `expr` to the field's type.
+fn dart_from_json(t: &FieldType, expr: &str, optional: bool) -> String {
+  let q = if optional { "?" } else { "" };
+  return match t {
+    FieldType::Number => format!("({expr} as num{q}){q}.toDouble()"),
+    FieldType::Array(item) => format!(
+      "({expr} as List<dynamic>{q}){q}.map((e) => {}).toList()",
+      dart_from_json(item, "e", false)
+    ),
+    FieldType::Json => expr.to_string(),
+    t => format!("{expr} as {}{q}", dart_type(t)),
+  };
+}
+
+fn dart_model(type_name: &str, fields: &[Field]) -> String {
+  let field_type = |f: &Field| -> String {
+    return match (&f.field_type, f.optional) {
+      (FieldType::Json, _) | (_, true) => format!("{}?", dart_type(&f.field_type)),
+      (t, false) => dart_type(t),
+    };
+  };
+
+  let mut out = format!("class {type_name} {{\n");
+  for f in fields {
+    out.push_str(&format!("  final {} {};\n", field_type(f), f.name));
+  }
+
+  out.push_str(&format!("\n  const {type_name}({{"));
+  if !fields.is_empty() {
+    out.push('\n');
+    for f in fields {
+      let required = if f.optional { "" } else { "required " };
+      out.push_str(&format!("    {required}this.{},\n", f.name));
+    }
+    out.push_str("  ");
+  }
+  out.push_str("});\n");
+
+  out.push_str(&format!(
+    "\n  factory {type_name}.fromJson(Map<String, dynamic> json) => {type_name}(\n"
+  ));
+  for f in fields {
+    let expr = format!("json[{}]", string_literal(Language::Dart, &f.json_name));
+    out.push_str(&format!(
+      "        {}: {},\n",
+      f.name,
+      dart_from_json(&f.field_type, &expr, f.optional)
+    ));
+  }
+  out.push_str("      );\n");
+
+  out.push_str("\n  Map<String, dynamic> toJson() => {\n");
+  for f in fields {
+    let key = string_literal(Language::Dart, &f.json_name);
+    if f.optional {
+      out.push_str(&format!("        if ({0} != null) {key}: {0},\n", f.name));
+    } else {
+      out.push_str(&format!("        {key}: {},\n", f.name));
+    }
+  }
+  out.push_str("      };\n}\n");
+  return out;
+}
+
+fn kotlin_type(t: &FieldType) -> String {
+  return match t {
+    FieldType::Integer => "Long".to_string(),
+    FieldType::Number => "Double".to_string(),
+    FieldType::String => "String".to_string(),
+    FieldType::Boolean => "Boolean".to_string(),
+    FieldType::Array(item) => format!("List<{}>", kotlin_type(item)),
+    FieldType::Json => "JsonElement".to_string(),
+  };
+}
+
+fn kotlin_model(type_name: &str, fields: &[Field]) -> String {
+  let mut out = format!("@Serializable\ndata class {type_name}(\n");
+  for f in fields {
+    out.push_str("    ");
+    if f.name != f.json_name {
+      out.push_str(&format!(
+        "@SerialName({}) ",
+        string_literal(Language::Kotlin, &f.json_name)
+      ));
+    }
+    let t = kotlin_type(&f.field_type);
+    if f.optional {
+      out.push_str(&format!("val {}: {t}? = null,\n", f.name));
+    } else {
+      out.push_str(&format!("val {}: {t},\n", f.name));
+    }
+  }
+  out.push_str(")\n");
+  return out;
+}
+
+fn swift_type(t: &FieldType) -> String {
+  return match t {
+    FieldType::Integer => "Int64".to_string(),
+    FieldType::Number => "Double".to_string(),
+    FieldType::String => "String".to_string(),
+    FieldType::Boolean => "Bool".to_string(),
+    FieldType::Array(item) => format!("[{}]", swift_type(item)),
+    FieldType::Json => "JSONValue".to_string(),
+  };
+}
+
+fn swift_model(type_name: &str, fields: &[Field]) -> String {
+  let field_type = |f: &Field| -> String {
+    let t = swift_type(&f.field_type);
+    return if f.optional { format!("{t}?") } else { t };
+  };
+
+  let mut out = format!("public struct {type_name}: Codable, Sendable {{\n");
+  for f in fields {
+    out.push_str(&format!("  public var {}: {}\n", f.name, field_type(f)));
+  }
+
+  // Memberwise initializers of public structs are internal.
+  let params: Vec<String> = fields
+    .iter()
+    .map(|f| {
+      let default = if f.optional { " = nil" } else { "" };
+      return format!("{}: {}{default}", f.name, field_type(f));
+    })
+    .collect();
+  out.push_str(&format!("\n  public init({}) {{\n", params.join(", ")));
+  for f in fields {
+    out.push_str(&format!("    self.{0} = {0}\n", f.name));
+  }
+  out.push_str("  }\n");
+
+  if fields.iter().any(|f| f.name != f.json_name) {
+    out.push_str("\n  enum CodingKeys: String, CodingKey {\n");
+    for f in fields {
+      if f.name == f.json_name {
+        out.push_str(&format!("    case {}\n", f.name));
+      } else {
+        out.push_str(&format!(
+          "    case {} = {}\n",
+          f.name,
+          string_literal(Language::Swift, &f.json_name)
+        ));
+      }
+    }
+    out.push_str("  }\n");
+  }
+
+  out.push_str("}\n");
+  return out;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn schema() -> Value {
+    return serde_json::json!({
+      "title": "movies",
+      "type": "object",
+      "properties": {
+        "id": { "type": "integer" },
+        "name": { "type": ["null", "string"] },
+        "data": { "$ref": "#/$defs/data" },
+        "rating": { "type": "number" },
+        "tags": { "type": "array", "items": { "type": "string" } },
+        "my column": { "type": "boolean" }
+      },
+      "required": ["id", "data", "rating"],
+      "$defs": {
+        "data": {
+          "type": "object",
+          "properties": {
+            "tags": { "type": "array", "items": { "type": "string" } }
+          }
+        }
+      }
+    });
+  }
+
+  #[test]
+  fn test_dart_model() {
+    assert_eq!(
+      json_schema_to_model(Language::Dart, "Movies", &schema()),
+      r#"class Movies {
+  final Object? data;
+  final int id;
+  final bool? my_column;
+  final String? name;
+  final double rating;
+  final List<String>? tags;
+
+  const Movies({
+    required this.data,
+    required this.id,
+    this.my_column,
+    this.name,
+    required this.rating,
+    this.tags,
+  });
+
+  factory Movies.fromJson(Map<String, dynamic> json) => Movies(
+        data: json["data"],
+        id: json["id"] as int,
+        my_column: json["my column"] as bool?,
+        name: json["name"] as String?,
+        rating: (json["rating"] as num).toDouble(),
+        tags: (json["tags"] as List<dynamic>?)?.map((e) => e as String).toList(),
+      );
+
+  Map<String, dynamic> toJson() => {
+        "data": data,
+        "id": id,
+        if (my_column != null) "my column": my_column,
+        if (name != null) "name": name,
+        "rating": rating,
+        if (tags != null) "tags": tags,
+      };
+}
+"#
+    );
+  }
+
+  #[test]
+  fn test_kotlin_model() {
+    assert_eq!(
+      json_schema_to_model(Language::Kotlin, "Movies", &schema()),
+      r#"@Serializable
+data class Movies(
+    val data: JsonElement,
+    val id: Long,
+    @SerialName("my column") val my_column: Boolean? = null,
+    val name: String? = null,
+    val rating: Double,
+    val tags: List<String>? = null,
+)
+"#
+    );
+  }
+
+  #[test]
+  fn test_swift_model() {
+    let model = json_schema_to_model(Language::Swift, "Movies", &schema());
+    assert!(
+      model.starts_with(
+        r#"public struct Movies: Codable, Sendable {
+  public var data: JSONValue
+  public var id: Int64
+  public var my_column: Bool?
+  public var name: String?
+  public var rating: Double
+  public var tags: [String]?
+"#
+      ),
+      "{model}"
+    );
+    assert!(
+      model.contains("  public init(data: JSONValue, id: Int64, my_column: Bool? = nil, "),
+      "{model}"
+    );
+    assert!(
+      model.contains("    case my_column = \"my column\"\n"),
+      "{model}"
+    );
+  }
+
+  #[test]
+  fn test_identifier() {
+    assert_eq!(identifier(Language::Dart, "class"), "class_");
+    assert_eq!(identifier(Language::Kotlin, "2fa"), "c2fa");
+    assert_eq!(identifier(Language::Dart, "_secret"), "c_secret");
+    assert_eq!(identifier(Language::Kotlin, "_secret"), "_secret");
+    assert_eq!(identifier(Language::Swift, "a-b"), "a_b");
+  }
+}
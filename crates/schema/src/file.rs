@@ -1,5 +1,7 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::error::Error;
@@ -22,12 +24,83 @@ pub struct FileUploadInput {
   /// The file's actual byte data
   ///
   /// Note that we're using a custom serializer to support denser base64 encoding for JSON when
-  /// Vec<u8>, would otherwise be serialized as `"data": [0, 1, 1]`.
+  /// Vec<u8>, would otherwise be serialized as `"data": [0, 1, 1]`. Spooled contents cannot be
+  /// serialized.
   pub data: FileUploadData,
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
-pub struct FileUploadData(#[serde(with = "bytes_or_base64")] pub Vec<u8>);
+/// A file's contents, either held in memory or spooled to disk.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileUploadData {
+  Bytes(Vec<u8>),
+  /// Large contents, e.g. from multipart uploads, are spooled to a temporary file first so that
+  /// they can be streamed into the object store w/o ever being materialized in memory.
+  Spooled(Arc<SpooledFile>),
+}
+
+impl FileUploadData {
+  /// Size of the contents in bytes.
+  pub fn size(&self) -> u64 {
+    return match self {
+      Self::Bytes(bytes) => bytes.len() as u64,
+      Self::Spooled(spooled) => spooled.len,
+    };
+  }
+
+  /// Leading bytes of the contents, e.g. to infer the mime type.
+  pub fn head(&self) -> &[u8] {
+    return match self {
+      Self::Bytes(bytes) => bytes,
+      Self::Spooled(spooled) => &spooled.head,
+    };
+  }
+}
+
+impl Default for FileUploadData {
+  fn default() -> Self {
+    return Self::Bytes(vec![]);
+  }
+}
+
+impl Serialize for FileUploadData {
+  fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    return match self {
+      Self::Bytes(bytes) => bytes_or_base64::serialize(bytes, s),
+      Self::Spooled(_) => Err(serde::ser::Error::custom(
+        "spooled file contents cannot be serialized",
+      )),
+    };
+  }
+}
+
+impl<'de> Deserialize<'de> for FileUploadData {
+  fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    return Ok(Self::Bytes(bytes_or_base64::deserialize(d)?));
+  }
+}
+
+/// File contents spooled to a temporary file on disk. The file is removed once the last reference
+/// is dropped.
+#[derive(Debug, PartialEq)]
+pub struct SpooledFile {
+  pub path: PathBuf,
+  /// The file's size in bytes.
+  pub len: u64,
+  /// Leading bytes of the file, e.g. to infer the mime type.
+  pub head: Vec<u8>,
+  /// Digest of the file's contents, e.g. to compare uploads w/o reading them back.
+  pub digest: Vec<u8>,
+}
+
+impl Drop for SpooledFile {
+  fn drop(&mut self) {
+    if let Err(err) = std::fs::remove_file(&self.path)
+      && err.kind() != std::io::ErrorKind::NotFound
+    {
+      log::warn!("Failed to remove spooled file {:?}: {err}", self.path);
+    }
+  }
+}
 
 mod bytes_or_base64 {
   use base64::prelude::*;
@@ -73,7 +146,7 @@ impl FileUploadInput {
     } = self;
 
     // We don't trust user provided type, we check ourselves.
-    let mime_type = infer::get(&data.0).map(|t| t.mime_type().to_string());
+    let mime_type = infer::get(data.head()).map(|t| t.mime_type().to_string());
 
     return Ok((
       name,
@@ -107,6 +180,23 @@ pub struct FileUpload {
   /// The file's inferred mime type. Not user provided.
   #[serde(skip_serializing_if = "Option::is_none")]
   mime_type: Option<String>,
+
+  /// Present if the file's contents are encrypted at rest.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  encryption: Option<FileEncryption>,
+}
+
+/// Envelope encryption metadata of a file. The contents are encrypted with a per-file data key,
+/// which itself is stored wrapped, i.e. encrypted, by a key-encryption key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FileEncryption {
+  /// Identifies the key-encryption key used to wrap the data key, e.g. to support rotation.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub key_id: Option<String>,
+
+  /// The wrapped data key, URL-safe base64 encoded.
+  pub wrapped_key: String,
 }
 
 impl FileUpload {
@@ -122,9 +212,22 @@ impl FileUpload {
       original_filename,
       content_type,
       mime_type,
+      encryption: None,
     };
   }
 
+  /// Like [FileUpload::new] but infers the mime type from the file's leading bytes, e.g. for
+  /// uploads whose contents aren't available all at once.
+  pub fn with_inferred_mime_type(
+    id: Uuid,
+    original_filename: Option<String>,
+    content_type: Option<String>,
+    prefix: &[u8],
+  ) -> Self {
+    let mime_type = infer::get(prefix).map(|t| t.mime_type().to_string());
+    return Self::new(id, original_filename, content_type, mime_type);
+  }
+
   pub fn objectstore_id(&self) -> &str {
     return &self.id;
   }
@@ -140,6 +243,14 @@ impl FileUpload {
   pub fn original_filename(&self) -> Option<&str> {
     return self.original_filename.as_deref();
   }
+
+  pub fn encryption(&self) -> Option<&FileEncryption> {
+    return self.encryption.as_ref();
+  }
+
+  pub fn set_encryption(&mut self, encryption: Option<FileEncryption>) {
+    self.encryption = encryption;
+  }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
@@ -2,6 +2,7 @@
 #![allow(clippy::needless_return)]
 #![warn(clippy::await_holding_lock, clippy::inefficient_to_string)]
 
+pub mod codegen;
 pub mod error;
 pub mod file;
 pub mod json;
@@ -10,9 +11,12 @@ pub mod metadata;
 pub mod parse;
 pub mod registry;
 pub mod sqlite;
+pub mod typescript;
 
 pub use error::Error;
-pub use file::{FileUpload, FileUploadData, FileUploadInput, FileUploads};
+pub use file::{
+  FileEncryption, FileUpload, FileUploadData, FileUploadInput, FileUploads, SpooledFile,
+};
 pub use sqlite::QualifiedName;
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
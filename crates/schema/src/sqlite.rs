@@ -124,9 +124,13 @@ impl UniqueConstraint {
 
 #[derive(Clone, Debug, Serialize, Deserialize, TS, PartialEq)]
 pub struct ColumnOrder {
+  /// Column name or, for expression indexes, the SQL expression.
   pub column_name: String,
   pub ascending: Option<bool>,
   pub nulls_first: Option<bool>,
+  /// Whether `column_name` is an expression, e.g. `lower(email)`, rather than a column name.
+  #[serde(default)]
+  pub expression: bool,
 }
 
 /// Conflict resolution types
@@ -820,8 +824,12 @@ impl TableIndex {
       .iter()
       .map(|c| {
         format!(
-          "\"{name}\" {order}",
-          name = c.column_name,
+          "{name} {order}",
+          name = if c.expression {
+            c.column_name.clone()
+          } else {
+            format!("\"{}\"", c.column_name)
+          },
           order = c
             .ascending
             .map_or("", |asc| if asc { "ASC" } else { "DESC" })
@@ -865,6 +873,12 @@ impl TryFrom<sqlite3_parser::ast::Stmt> for TableIndex {
         columns: columns
           .into_iter()
           .map(|order_expr| ColumnOrder {
+            expression: !matches!(
+              order_expr.expr,
+              sqlite3_parser::ast::Expr::Id(_)
+                | sqlite3_parser::ast::Expr::Name(_)
+                | sqlite3_parser::ast::Expr::Literal(sqlite3_parser::ast::Literal::String(_))
+            ),
             column_name: unquote_expr(&order_expr.expr),
             ascending: order_expr
               .order
@@ -1845,6 +1859,25 @@ mod tests {
     assert_eq!(index, index1, "Parsed: {sql1}");
   }
 
+  #[test]
+  fn test_parse_create_expression_index() {
+    let sql = r#"CREATE INDEX "lower_email" ON "user" (lower(email), "id" DESC)"#;
+    let index: TableIndex = parse_into_statement(sql)
+      .unwrap()
+      .unwrap()
+      .try_into()
+      .unwrap();
+
+    assert!(index.columns[0].expression);
+    assert!(!index.columns[1].expression);
+
+    let sql1 = index.create_index_statement();
+    let stmt1 = parse_into_statement(&sql1).unwrap().unwrap();
+    let index1: TableIndex = stmt1.try_into().unwrap();
+
+    assert_eq!(index, index1, "Parsed: {sql1}");
+  }
+
   fn parse_into_select(sql: &str) -> sqlite3_parser::ast::Select {
     let sqlite3_parser::ast::Stmt::Select(select) = parse_into_statement(sql).unwrap().unwrap()
     else {
@@ -0,0 +1,269 @@
+//! Generates TypeScript type definitions from JSON schemas, e.g. the ones built for tables by
+//! [crate::json_schema::build_json_schema].
+
+use serde_json::{Map, Value};
+
+/// Number of nested references after which they aren't resolved anymore, e.g. for recursive
+/// schemas.
+const MAX_REFS: usize = 16;
+
+#[derive(Clone, Copy)]
+struct Context<'a> {
+  defs: &'a Map<String, Value>,
+  /// Nesting level of objects for indentation.
+  indent: usize,
+  refs: usize,
+}
+
+impl Context<'_> {
+  fn nested(&self) -> Self {
+    return Context {
+      indent: self.indent + 1,
+      ..*self
+    };
+  }
+}
+
+/// Converts a name, e.g. a table name, to a PascalCase TypeScript identifier.
+pub fn to_type_name(name: &str) -> String {
+  let mut type_name = String::with_capacity(name.len());
+  for part in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+    let mut chars = part.chars();
+    if let Some(first) = chars.next() {
+      type_name.push(first.to_ascii_uppercase());
+      type_name.extend(chars);
+    }
+  }
+
+  if type_name.is_empty() || type_name.starts_with(|c: char| c.is_ascii_digit()) {
+    type_name.insert(0, 'T');
+  }
+  return type_name;
+}
+
+/// Builds an exported TypeScript type declaration named `type_name` from the given JSON schema.
+/// References to `#/$defs/<name>` are inlined.
+pub fn json_schema_to_typescript(type_name: &str, schema: &Value) -> String {
+  let empty = Map::new();
+  let defs = schema
+    .get("$defs")
+    .and_then(|d| d.as_object())
+    .unwrap_or(&empty);
+
+  let mut out = String::new();
+  if let Some(title) = schema.get("title").and_then(|t| t.as_str()) {
+    out.push_str(&format!("/** {title} */\n"));
+  }
+  out.push_str(&format!(
+    "export type {type_name} = {};\n",
+    to_type(
+      schema,
+      &Context {
+        defs,
+        indent: 0,
+        refs: 0,
+      }
+    )
+  ));
+  return out;
+}
+
+fn to_type(schema: &Value, cx: &Context) -> String {
+  let obj = match schema {
+    Value::Bool(true) => return "unknown".to_string(),
+    Value::Bool(false) => return "never".to_string(),
+    Value::Object(obj) => obj,
+    _ => return "unknown".to_string(),
+  };
+
+  if let Some(reference) = obj.get("$ref").and_then(|r| r.as_str()) {
+    return match reference
+      .strip_prefix("#/$defs/")
+      .and_then(|name| cx.defs.get(name))
+    {
+      Some(def) if cx.refs < MAX_REFS => to_type(
+        def,
+        &Context {
+          refs: cx.refs + 1,
+          ..*cx
+        },
+      ),
+      _ => "unknown".to_string(),
+    };
+  }
+
+  if let Some(value) = obj.get("const") {
+    return value.to_string();
+  }
+
+  if let Some(Value::Array(values)) = obj.get("enum") {
+    return union(values.iter().map(|v| v.to_string()));
+  }
+
+  for key in ["oneOf", "anyOf"] {
+    if let Some(Value::Array(schemas)) = obj.get(key) {
+      return union(schemas.iter().map(|s| to_type(s, cx)));
+    }
+  }
+
+  if let Some(Value::Array(schemas)) = obj.get("allOf") {
+    let types: Vec<_> = schemas.iter().map(|s| wrap(to_type(s, cx))).collect();
+    return types.join(" & ");
+  }
+
+  return match obj.get("type") {
+    Some(Value::String(t)) => primitive_type(t, obj, cx),
+    Some(Value::Array(types)) => union(
+      types
+        .iter()
+        .filter_map(|t| t.as_str())
+        .map(|t| primitive_type(t, obj, cx)),
+    ),
+    _ if obj.contains_key("properties") => object_type(obj, cx),
+    _ => "unknown".to_string(),
+  };
+}
+
+fn primitive_type(t: &str, obj: &Map<String, Value>, cx: &Context) -> String {
+  return match t {
+    "string" => "string".to_string(),
+    "integer" | "number" => "number".to_string(),
+    "boolean" => "boolean".to_string(),
+    "null" => "null".to_string(),
+    "array" => match obj.get("items") {
+      Some(items) => format!("Array<{}>", to_type(items, cx)),
+      None => "Array<unknown>".to_string(),
+    },
+    "object" => object_type(obj, cx),
+    _ => "unknown".to_string(),
+  };
+}
+
+fn object_type(obj: &Map<String, Value>, cx: &Context) -> String {
+  let required: Vec<&str> = match obj.get("required") {
+    Some(Value::Array(required)) => required.iter().filter_map(|r| r.as_str()).collect(),
+    _ => vec![],
+  };
+
+  let Some(Value::Object(properties)) = obj.get("properties") else {
+    return match obj.get("additionalProperties") {
+      Some(Value::Object(_)) => format!(
+        "Record<string, {}>",
+        to_type(&obj["additionalProperties"], cx)
+      ),
+      _ => "Record<string, unknown>".to_string(),
+    };
+  };
+
+  if properties.is_empty() {
+    return "Record<string, never>".to_string();
+  }
+
+  let nested = cx.nested();
+  let indent = "  ".repeat(nested.indent);
+  let mut out = "{\n".to_string();
+  for (name, property) in properties {
+    let optional = if required.contains(&name.as_str()) {
+      ""
+    } else {
+      "?"
+    };
+    out.push_str(&format!(
+      "{indent}{}{optional}: {};\n",
+      property_name(name),
+      to_type(property, &nested)
+    ));
+  }
+  out.push_str(&"  ".repeat(cx.indent));
+  out.push('}');
+  return out;
+}
+
+fn property_name(name: &str) -> String {
+  let is_identifier = name
+    .chars()
+    .next()
+    .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+  if is_identifier {
+    return name.to_string();
+  }
+  return Value::String(name.to_string()).to_string();
+}
+
+fn union(types: impl Iterator<Item = String>) -> String {
+  let mut unique: Vec<String> = vec![];
+  for t in types {
+    if !unique.contains(&t) {
+      unique.push(t);
+    }
+  }
+
+  return match unique.len() {
+    0 => "never".to_string(),
+    1 => unique.remove(0),
+    _ => unique.into_iter().map(wrap).collect::<Vec<_>>().join(" | "),
+  };
+}
+
+/// Parenthesizes unions and intersections to keep precedence when nested.
+fn wrap(t: String) -> String {
+  if !t.starts_with('{') && (t.contains(" | ") || t.contains(" & ")) {
+    return format!("({t})");
+  }
+  return t;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_type_name() {
+    assert_eq!(to_type_name("movies"), "Movies");
+    assert_eq!(to_type_name("user_profiles"), "UserProfiles");
+    assert_eq!(to_type_name("2fa-codes"), "T2faCodes");
+    assert_eq!(to_type_name("_"), "T");
+  }
+
+  #[test]
+  fn test_json_schema_to_typescript() {
+    let schema = serde_json::json!({
+      "title": "movies",
+      "type": "object",
+      "properties": {
+        "id": { "type": "integer" },
+        "name": { "type": ["null", "string"] },
+        "data": { "$ref": "#/$defs/data" },
+        "rating": { "enum": ["good", "bad"] },
+        "my column": { "type": "boolean" }
+      },
+      "required": ["id", "data"],
+      "$defs": {
+        "data": {
+          "type": "object",
+          "properties": {
+            "tags": { "type": "array", "items": { "type": "string" } }
+          }
+        }
+      }
+    });
+
+    assert_eq!(
+      json_schema_to_typescript("Movies", &schema),
+      r#"/** movies */
+export type Movies = {
+  data: {
+    tags?: Array<string>;
+  };
+  id: number;
+  "my column"?: boolean;
+  name?: null | string;
+  rating?: "good" | "bad";
+};
+"#
+    );
+  }
+}
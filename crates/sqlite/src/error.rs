@@ -1,3 +1,13 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static BUSY_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Process-wide number of operations across all connections, which failed with `SQLITE_BUSY`, e.g.
+/// because the busy timeout expired while waiting for a lock.
+pub fn busy_error_count() -> u64 {
+  return BUSY_ERRORS.load(Ordering::Relaxed);
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
   #[error("ConnectionClosed")]
@@ -28,7 +38,7 @@ pub enum Error {
   // QUESTION: This is leaky. How often do downstream users have to introspect on this
   // rusqlite::Error. Otherwise, should/could this be more opaue.
   #[error("Rusqlite: {0}")]
-  Rusqlite(#[from] rusqlite::Error),
+  Rusqlite(#[source] rusqlite::Error),
 
   // QUESTION: This is leaky. How often do downstream users have to introspect on this
   // rusqlite::Error. Otherwise, should/could this be more opaue.
@@ -48,6 +58,15 @@ pub enum Error {
   Other(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
 }
 
+impl From<rusqlite::Error> for Error {
+  fn from(err: rusqlite::Error) -> Self {
+    if err.sqlite_error_code() == Some(rusqlite::ErrorCode::DatabaseBusy) {
+      BUSY_ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+    return Error::Rusqlite(err);
+  }
+}
+
 pub fn unpack_other_error<T>(err: Error) -> Result<T, Error>
 where
   T: std::error::Error + Send + Sync + 'static,
@@ -27,7 +27,7 @@ use crate::traits::{
 use crate::r#type::ConnectionType;
 
 // NOTE: We should probably decouple from the impl.
-pub use crate::sqlite::executor::{ArcLockGuard, LockError, LockGuard};
+pub use crate::sqlite::executor::{ArcLockGuard, LockError, LockGuard, PostWriteListener};
 pub use crate::sqlite::transaction::OwnedTx;
 
 #[derive(Clone, Debug)]
@@ -167,6 +167,20 @@ impl Connection {
     };
   }
 
+  pub fn add_post_write_listener(&self, listener: PostWriteListener) -> Result<(), LockError> {
+    return match self.exec {
+      Executor::Sqlite(ref exec) => {
+        exec.add_post_write_listener(listener);
+        Ok(())
+      }
+      Executor::Pg(_) => {
+        log::error!("Not supported: PG post-write listeners");
+
+        Err(LockError::NotSupported)
+      }
+    };
+  }
+
   #[inline]
   pub fn try_write_arc_lock_for(
     &self,
@@ -247,6 +261,25 @@ impl Connection {
     };
   }
 
+  /// Query SQL statement, which is interrupted after `timeout` or when the future is dropped.
+  ///
+  /// NOTE: The timeout is currently not enforced for Postgres.
+  pub async fn read_query_rows_with_timeout(
+    &self,
+    sql: impl AsRef<str> + Send + 'static,
+    params: impl Params + Send + 'static,
+    timeout: std::time::Duration,
+  ) -> Result<Rows, Error> {
+    return match self.exec {
+      Executor::Sqlite(ref exec) => {
+        exec
+          .read_query_rows_f_with_timeout(sql, params, timeout, sqlite_from_rows)
+          .await
+      }
+      Executor::Pg(ref exec) => exec.query_rows_f(sql, params, pg_from_rows).await,
+    };
+  }
+
   pub async fn read_query_row(
     &self,
     sql: impl AsRef<str> + Send + 'static,
@@ -551,6 +584,26 @@ impl Connection {
     };
   }
 
+  /// Replaces the main database's content with the database at the given path.
+  pub async fn restore(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+    return match self.exec {
+      Executor::Sqlite(ref exec) => {
+        let src =
+          rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        exec
+          .call_writer(move |dst_conn| -> Result<(), Error> {
+            return crate::sqlite::util::backup(&src, dst_conn);
+          })
+          .await
+      }
+      Executor::Pg(_) => {
+        log::error!("Not implemented: restore");
+
+        Err(Error::NotImplemented)
+      }
+    };
+  }
+
   pub async fn list_databases(&self) -> Result<Vec<Database>, Error> {
     return match self.exec {
       Executor::Sqlite(ref exec) => exec.call_reader(crate::sqlite::util::list_databases).await,
@@ -31,7 +31,9 @@ pub mod generic;
 #[cfg(not(feature = "generic"))]
 mod connection_imports {
   pub use super::sqlite::batch::execute_batch;
-  pub use super::sqlite::connection::{ArcLockGuard, Connection, LockError, LockGuard, Options};
+  pub use super::sqlite::connection::{
+    ArcLockGuard, Connection, LockError, LockGuard, Options, PostWriteListener,
+  };
   pub use super::sqlite::sync::SyncConnection;
   pub use super::sqlite::transaction::{OwnedTx, Transaction};
   pub use super::r#type::ConnectionType;
@@ -40,16 +42,22 @@ mod connection_imports {
 #[cfg(feature = "generic")]
 mod connection_imports {
   pub use super::generic::{Connection, OwnedTx, SyncConnection, Transaction, execute_batch};
-  pub use super::sqlite::connection::{ArcLockGuard, LockError, LockGuard, Options};
+  pub use super::sqlite::connection::{
+    ArcLockGuard, LockError, LockGuard, Options, PostWriteListener,
+  };
   pub use super::r#type::ConnectionType;
 }
 
 pub use connection_imports::*;
 
 pub use database::Database;
-pub use error::{Error, unpack_other_error};
+pub use error::{Error, busy_error_count, unpack_other_error};
 pub use params::{NamedParamRef, NamedParams, NamedParamsRef, Params};
 pub use rows::{Row, Rows, ValueType};
+pub use sqlite::{
+  SlowQuery, StatementCacheStats, clear_slow_query_handler, set_slow_query_handler,
+  statement_cache_stats,
+};
 pub use statement::Statement;
 pub use traits::SyncConnection as SyncConnectionTrait;
 pub use value::{Value, ValueRef};
@@ -16,7 +16,7 @@ use crate::traits::SyncConnection as SyncConnectionTrait;
 use crate::r#type::ConnectionType;
 
 // NOTE: We should probably decouple from the impl.
-pub use crate::sqlite::executor::{ArcLockGuard, LockError, LockGuard, Options};
+pub use crate::sqlite::executor::{ArcLockGuard, LockError, LockGuard, Options, PostWriteListener};
 
 /// A handle to call functions in background thread.
 #[derive(Clone)]
@@ -88,6 +88,15 @@ impl Connection {
     return self.exec.write_lock();
   }
 
+  /// Registers a listener invoked once writes have returned or write locks have been released,
+  /// i.e. when committed changes have become visible to other connections.
+  ///
+  /// NOTE: Unlike SQLite's commit hook, which fires before the commit is visible.
+  pub fn add_post_write_listener(&self, listener: PostWriteListener) -> Result<(), LockError> {
+    self.exec.add_post_write_listener(listener);
+    return Ok(());
+  }
+
   /// Acquire a ref-counted write lock on the connections.
   ///
   /// NOTE: Current use cases:
@@ -156,6 +165,19 @@ impl Connection {
     return self.exec.read_query_rows_f(sql, params, from_rows).await;
   }
 
+  /// Query SQL statement, which is interrupted after `timeout` or when the future is dropped.
+  pub async fn read_query_rows_with_timeout(
+    &self,
+    sql: impl AsRef<str> + Send + 'static,
+    params: impl Params + Send + 'static,
+    timeout: std::time::Duration,
+  ) -> Result<Rows, Error> {
+    return self
+      .exec
+      .read_query_rows_f_with_timeout(sql, params, timeout, from_rows)
+      .await;
+  }
+
   pub async fn read_query_row(
     &self,
     sql: impl AsRef<str> + Send + 'static,
@@ -342,6 +364,18 @@ impl Connection {
       .await;
   }
 
+  /// Replaces the main database's content with the database at the given path.
+  pub async fn restore(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+    let src =
+      rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    return self
+      .exec
+      .call_writer(move |dst_conn| -> Result<(), Error> {
+        return crate::sqlite::util::backup(&src, dst_conn);
+      })
+      .await;
+  }
+
   pub async fn list_databases(&self) -> Result<Vec<Database>, Error> {
     return self
       .exec
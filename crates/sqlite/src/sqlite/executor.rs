@@ -1,10 +1,13 @@
 use crossfire;
 use log::*;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::error::Error;
 use crate::params::Params;
+use crate::sqlite::slow_query::instrument;
+use crate::sqlite::statement_cache::prepare_cached;
 
 pub use crate::sqlite::lock::{ArcLockGuard, LockError, LockGuard};
 
@@ -26,6 +29,20 @@ enum WriterMessage {
   RunMut(Box<dyn FnOnce(&mut rusqlite::Connection) + Send>),
 }
 
+/// Called after every call on the writer connection and whenever a write lock is released, i.e.
+/// once changes committed in the meantime are visible to other connections. Returning false
+/// unregisters the listener.
+pub type PostWriteListener = Box<dyn FnMut() -> bool + Send>;
+
+#[derive(Clone, Default)]
+pub(crate) struct PostWriteListeners(Arc<Mutex<Vec<PostWriteListener>>>);
+
+impl PostWriteListeners {
+  pub(crate) fn notify(&self) {
+    self.0.lock().retain_mut(|listener| listener());
+  }
+}
+
 #[derive(Clone, Default)]
 pub struct Options {
   pub busy_timeout: Option<std::time::Duration>,
@@ -44,6 +61,7 @@ pub(crate) struct Executor {
   // NOTE: Is shared across reader and writer worker threads.
   // NOTE: Only needs to be an to get parking_lot's owned ArcLocks.
   conns: Arc<RwLock<ConnectionVec>>,
+  post_write: PostWriteListeners,
 }
 
 impl Drop for Executor {
@@ -158,6 +176,7 @@ impl Executor {
       reader: shared_read_sender,
       writer: shared_write_sender,
       conns,
+      post_write: PostWriteListeners::default(),
     };
 
     assert_eq!(num_threads, conn.threads());
@@ -171,7 +190,11 @@ impl Executor {
 
   #[inline]
   pub fn write_lock(&self) -> Result<LockGuard<'_>, LockError> {
-    return Ok(LockGuard::new(self.conns.write()));
+    return Ok(LockGuard::new(self.conns.write(), &self.post_write));
+  }
+
+  pub fn add_post_write_listener(&self, listener: PostWriteListener) {
+    self.post_write.0.lock().push(listener);
   }
 
   #[inline]
@@ -184,6 +207,7 @@ impl Executor {
         .conns
         .try_write_arc_for(duration)
         .ok_or(LockError::Timeout)?,
+      post_write: self.post_write.clone(),
     });
   }
 
@@ -208,11 +232,15 @@ impl Executor {
     Error: From<E>,
   {
     let (sender, receiver) = crossfire::oneshot::oneshot::<Result<R, E>>();
+    let post_write = self.post_write.clone();
 
     self
       .writer
       .send(WriterMessage::RunMut(Box::new(move |conn| {
-        sender.send(function(conn));
+        let result = function(conn);
+        // Notify before responding, so callers observe the effects of their own writes.
+        post_write.notify();
+        sender.send(result);
       })))
       .map_err(|_| Error::ConnectionClosed)?;
 
@@ -251,11 +279,11 @@ impl Executor {
   {
     return self
       .call_writer(move |conn: &mut rusqlite::Connection| {
-        let mut stmt = conn.prepare_cached(sql.as_ref())?;
+        let mut stmt = prepare_cached(conn, sql.as_ref())?;
 
         params.bind(&mut stmt)?;
 
-        return f(stmt.raw_query());
+        return instrument(conn, sql.as_ref(), || f(stmt.raw_query()));
       })
       .await;
   }
@@ -272,12 +300,66 @@ impl Executor {
   {
     return self
       .call_reader(move |conn: &rusqlite::Connection| {
-        let mut stmt = conn.prepare_cached(sql.as_ref())?;
+        let mut stmt = prepare_cached(conn, sql.as_ref())?;
         assert!(stmt.readonly());
 
         params.bind(&mut stmt)?;
 
-        return f(stmt.raw_query());
+        return instrument(conn, sql.as_ref(), || f(stmt.raw_query()));
+      })
+      .await;
+  }
+
+  /// Like `read_query_rows_f` but interrupts the query once `timeout` has elapsed or the returned
+  /// future is dropped, e.g. because the client disconnected.
+  pub async fn read_query_rows_f_with_timeout<T>(
+    &self,
+    sql: impl AsRef<str> + Send + 'static,
+    params: impl Params + Send + 'static,
+    timeout: std::time::Duration,
+    f: impl (FnOnce(rusqlite::Rows<'_>) -> Result<T, Error>) + Send + 'static,
+  ) -> Result<T, Error>
+  where
+    T: Send + 'static,
+  {
+    /// Number of virtual machine instructions between checks.
+    const PROGRESS_STEPS: std::ffi::c_int = 1000;
+
+    struct CancelOnDrop(Arc<AtomicBool>);
+
+    impl Drop for CancelOnDrop {
+      fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+      }
+    }
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _guard = CancelOnDrop(cancelled.clone());
+    let deadline = std::time::Instant::now() + timeout;
+
+    return self
+      .call_reader(move |conn: &rusqlite::Connection| {
+        conn.progress_handler(
+          PROGRESS_STEPS,
+          Some(move || {
+            return cancelled.load(Ordering::Relaxed) || std::time::Instant::now() > deadline;
+          }),
+        )?;
+
+        let result = prepare_cached(conn, sql.as_ref())
+          .map_err(Error::from)
+          .and_then(|mut stmt| {
+            assert!(stmt.readonly());
+
+            params.bind(&mut stmt)?;
+
+            return instrument(conn, sql.as_ref(), || f(stmt.raw_query()));
+          });
+
+        // Connections are shared, remove the handler for subsequent queries.
+        conn.progress_handler(0, None::<fn() -> bool>)?;
+
+        return result;
       })
       .await;
   }
@@ -1,6 +1,6 @@
 use std::ops::{Deref, DerefMut};
 
-use crate::sqlite::executor::ConnectionVec;
+use crate::sqlite::executor::{ConnectionVec, PostWriteListeners};
 
 #[derive(thiserror::Error, Debug)]
 pub enum LockError {
@@ -10,11 +10,23 @@ pub enum LockError {
   NotSupported,
 }
 
-pub struct LockGuard<'a>(parking_lot::RwLockWriteGuard<'a, ConnectionVec>);
+pub struct LockGuard<'a>(
+  parking_lot::RwLockWriteGuard<'a, ConnectionVec>,
+  &'a PostWriteListeners,
+);
 
 impl<'a> LockGuard<'a> {
-  pub(super) fn new(guard: parking_lot::RwLockWriteGuard<'a, ConnectionVec>) -> Self {
-    return Self(guard);
+  pub(super) fn new(
+    guard: parking_lot::RwLockWriteGuard<'a, ConnectionVec>,
+    post_write: &'a PostWriteListeners,
+  ) -> Self {
+    return Self(guard, post_write);
+  }
+}
+
+impl Drop for LockGuard<'_> {
+  fn drop(&mut self) {
+    self.1.notify();
   }
 }
 
@@ -36,6 +48,13 @@ impl DerefMut for LockGuard<'_> {
 
 pub struct ArcLockGuard {
   pub(super) guard: parking_lot::ArcRwLockWriteGuard<parking_lot::RawRwLock, ConnectionVec>,
+  pub(super) post_write: PostWriteListeners,
+}
+
+impl Drop for ArcLockGuard {
+  fn drop(&mut self) {
+    self.post_write.notify();
+  }
 }
 
 impl Deref for ArcLockGuard {
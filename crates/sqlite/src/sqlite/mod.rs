@@ -2,9 +2,13 @@ pub(super) mod batch;
 pub(super) mod connection;
 pub(super) mod executor;
 mod lock;
+pub(super) mod slow_query;
+pub(super) mod statement_cache;
 pub(super) mod sync;
 pub(super) mod transaction;
 pub(super) mod util;
 
 pub use batch::execute_batch;
+pub use slow_query::{SlowQuery, clear_slow_query_handler, set_slow_query_handler};
+pub use statement_cache::{StatementCacheStats, statement_cache_stats};
 pub use util::{extract_record_values, extract_row_id, from_rows};
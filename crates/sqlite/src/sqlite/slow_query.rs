@@ -0,0 +1,96 @@
+//! Detection of slow statements across all connections, e.g. to record them for performance
+//! debugging in production.
+//!
+//! Detection is disabled by default and only costs an atomic load per statement unless enabled.
+
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A statement, which took longer than the configured threshold.
+#[derive(Clone, Debug)]
+pub struct SlowQuery {
+  pub sql: String,
+  pub duration: Duration,
+  /// Path of the connection's main database, `None` for in-memory databases.
+  pub database: Option<String>,
+  /// Output of `EXPLAIN QUERY PLAN`, one line per step indented by depth. Empty if the statement
+  /// couldn't be explained.
+  pub query_plan: String,
+}
+
+type SlowQueryHandler = Arc<dyn Fn(SlowQuery) + Send + Sync>;
+
+/// Threshold in microseconds, zero if disabled.
+static THRESHOLD_MICROS: AtomicU64 = AtomicU64::new(0);
+static HANDLER: RwLock<Option<SlowQueryHandler>> = parking_lot::const_rwlock(None);
+
+/// Installs a process-wide `handler`, which is called for every statement taking longer than
+/// `threshold`. The handler is invoked on the connection's thread and thus shouldn't block.
+pub fn set_slow_query_handler(
+  threshold: Duration,
+  handler: impl Fn(SlowQuery) + Send + Sync + 'static,
+) {
+  *HANDLER.write() = Some(Arc::new(handler));
+  THRESHOLD_MICROS.store((threshold.as_micros() as u64).max(1), Ordering::Relaxed);
+}
+
+/// Disables slow statement detection.
+pub fn clear_slow_query_handler() {
+  THRESHOLD_MICROS.store(0, Ordering::Relaxed);
+  *HANDLER.write() = None;
+}
+
+/// Runs `f`, i.e. steps the prepared `sql` statement, and reports it if it exceeds the threshold.
+#[inline]
+pub(crate) fn instrument<T>(conn: &rusqlite::Connection, sql: &str, f: impl FnOnce() -> T) -> T {
+  let threshold = THRESHOLD_MICROS.load(Ordering::Relaxed);
+  if threshold == 0 {
+    return f();
+  }
+
+  let start = Instant::now();
+  let result = f();
+  let duration = start.elapsed();
+
+  if duration.as_micros() as u64 >= threshold {
+    let handler = HANDLER.read().clone();
+    if let Some(handler) = handler {
+      handler(SlowQuery {
+        sql: sql.to_string(),
+        duration,
+        database: conn.path().filter(|p| !p.is_empty()).map(|p| p.to_string()),
+        query_plan: explain_query_plan(conn, sql).unwrap_or_default(),
+      });
+    }
+  }
+
+  return result;
+}
+
+/// Renders the `EXPLAIN QUERY PLAN` output of `sql` as an indented tree. Parameters don't need to
+/// be bound, since unbound parameters are treated as NULL.
+fn explain_query_plan(conn: &rusqlite::Connection, sql: &str) -> Result<String, rusqlite::Error> {
+  let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {sql}"))?;
+  let mut rows = stmt.query([])?;
+
+  // Maps plan step ids to their depth.
+  let mut depths: Vec<(i64, usize)> = vec![];
+  let mut lines: Vec<String> = vec![];
+  while let Some(row) = rows.next()? {
+    let id: i64 = row.get(0)?;
+    let parent: i64 = row.get(1)?;
+    let detail: String = row.get(3)?;
+
+    let depth = depths
+      .iter()
+      .rev()
+      .find(|(id, _)| *id == parent)
+      .map_or(0, |(_, depth)| depth + 1);
+    depths.push((id, depth));
+    lines.push(format!("{}{detail}", "  ".repeat(depth)));
+  }
+
+  return Ok(lines.join("\n"));
+}
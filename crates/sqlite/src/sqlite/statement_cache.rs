@@ -0,0 +1,53 @@
+//! Instrumentation for rusqlite's per-connection prepared statement cache.
+//!
+//! Statements are cached by their SQL text, thus repeatedly executed queries, e.g. built by record
+//! APIs, skip SQLite's relatively expensive parsing and planning.
+
+use rusqlite::StatementStatus;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Process-wide prepared statement cache statistics across all connections.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StatementCacheStats {
+  pub hits: u64,
+  pub misses: u64,
+}
+
+impl StatementCacheStats {
+  /// Ratio of cache hits to lookups. `None` if there weren't any lookups yet.
+  pub fn hit_rate(&self) -> Option<f64> {
+    let lookups = self.hits + self.misses;
+    if lookups == 0 {
+      return None;
+    }
+    return Some(self.hits as f64 / lookups as f64);
+  }
+}
+
+pub fn statement_cache_stats() -> StatementCacheStats {
+  return StatementCacheStats {
+    hits: HITS.load(Ordering::Relaxed),
+    misses: MISSES.load(Ordering::Relaxed),
+  };
+}
+
+/// Prepares `sql` using the connection's statement cache and records whether it was a hit.
+#[inline]
+pub(crate) fn prepare_cached<'a>(
+  conn: &'a rusqlite::Connection,
+  sql: &str,
+) -> Result<rusqlite::CachedStatement<'a>, rusqlite::Error> {
+  let stmt = conn.prepare_cached(sql)?;
+
+  // Statements taken from the cache have run before, freshly prepared ones haven't.
+  if stmt.get_status(StatementStatus::Run) > 0 {
+    HITS.fetch_add(1, Ordering::Relaxed);
+  } else {
+    MISSES.fetch_add(1, Ordering::Relaxed);
+  }
+
+  return Ok(stmt);
+}
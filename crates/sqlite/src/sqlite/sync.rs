@@ -3,6 +3,8 @@ use std::sync::Arc;
 use crate::error::Error;
 use crate::params::Params;
 use crate::rows::{Row, Rows};
+use crate::sqlite::slow_query::instrument;
+use crate::sqlite::statement_cache::prepare_cached;
 use crate::sqlite::util::{columns, from_row, from_rows};
 use crate::traits::SyncConnection as SyncConnectionTrait;
 use crate::r#type::ConnectionType;
@@ -62,13 +64,15 @@ pub(super) fn query_row(
   sql: impl AsRef<str>,
   params: impl Params,
 ) -> Result<Option<Row>, Error> {
-  let mut stmt = conn.prepare_cached(sql.as_ref())?;
+  let mut stmt = prepare_cached(conn, sql.as_ref())?;
   params.bind(&mut stmt)?;
 
-  if let Some(row) = stmt.raw_query().next()? {
-    return Ok(Some(from_row(row, Arc::new(columns(row.as_ref())))?));
-  }
-  return Ok(None);
+  return instrument(conn, sql.as_ref(), || -> Result<Option<Row>, Error> {
+    if let Some(row) = stmt.raw_query().next()? {
+      return Ok(Some(from_row(row, Arc::new(columns(row.as_ref())))?));
+    }
+    return Ok(None);
+  });
 }
 
 #[inline]
@@ -77,9 +81,9 @@ pub(super) fn query_rows(
   sql: impl AsRef<str>,
   params: impl Params,
 ) -> Result<Rows, Error> {
-  let mut stmt = conn.prepare_cached(sql.as_ref())?;
+  let mut stmt = prepare_cached(conn, sql.as_ref())?;
   params.bind(&mut stmt)?;
-  return from_rows(stmt.raw_query());
+  return instrument(conn, sql.as_ref(), || from_rows(stmt.raw_query()));
 }
 
 #[inline]
@@ -88,10 +92,10 @@ pub(super) fn execute(
   sql: impl AsRef<str>,
   params: impl Params,
 ) -> Result<usize, Error> {
-  let mut stmt = conn.prepare_cached(sql.as_ref())?;
+  let mut stmt = prepare_cached(conn, sql.as_ref())?;
   params.bind(&mut stmt)?;
 
-  return match stmt.raw_execute() {
+  return match instrument(conn, sql.as_ref(), || stmt.raw_execute()) {
     Err(rusqlite::Error::ExecuteReturnedResults) => Err(Error::ExecuteReturnedResults),
     r => Ok(r?),
   };
@@ -186,6 +186,16 @@ async fn test_error_source() {
   );
 }
 
+#[test]
+fn test_busy_error_count() {
+  let before = crate::busy_error_count();
+
+  let _: Error = rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_BUSY), None).into();
+  let _: Error = rusqlite::Error::InvalidQuery.into();
+
+  assert!(crate::busy_error_count() > before);
+}
+
 fn failable_func(_: &rusqlite::Connection) -> std::result::Result<(), MyError> {
   Err(MyError::MySpecificError)
 }
@@ -659,3 +669,79 @@ async fn test_execute_returning_rows() {
   let c = rusqlite::Connection::open_in_memory().unwrap();
   c.execute_batch("SELECT 4;").unwrap();
 }
+
+#[tokio::test]
+async fn test_read_query_rows_with_timeout() {
+  let conn = Connection::open_in_memory().unwrap();
+
+  let rows = conn
+    .read_query_rows_with_timeout("SELECT 5", (), std::time::Duration::from_secs(10))
+    .await
+    .unwrap();
+  assert_eq!(rows.len(), 1);
+
+  // Pathological query, which would run practically forever.
+  let err = conn
+    .read_query_rows_with_timeout(
+      "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT MAX(x) FROM c",
+      (),
+      std::time::Duration::from_millis(50),
+    )
+    .await
+    .unwrap_err();
+  assert!(
+    matches!(&err, Error::Rusqlite(err) if err.sqlite_error_code() == Some(ErrorCode::OperationInterrupted)),
+    "{err}"
+  );
+
+  // The progress handler is removed afterwards.
+  let rows = conn.read_query_rows("SELECT 6", ()).await.unwrap();
+  assert_eq!(rows.len(), 1);
+}
+
+#[tokio::test]
+async fn test_statement_cache_stats() {
+  let conn = Connection::open_in_memory().unwrap();
+  let query = "SELECT 'statement_cache_stats'";
+
+  let before = crate::statement_cache_stats();
+  for _ in 0..3 {
+    conn.read_query_rows(query, ()).await.unwrap();
+  }
+  let after = crate::statement_cache_stats();
+
+  // NOTE: Stats are process-wide and other tests may run concurrently.
+  assert!(after.misses > before.misses);
+  assert!(after.hits >= before.hits + 2);
+  assert!(after.hit_rate().is_some());
+}
+
+#[tokio::test]
+async fn test_slow_query_handler() {
+  let conn = Connection::open_in_memory().unwrap();
+  conn
+    .execute("CREATE TABLE slow (id INTEGER PRIMARY KEY, value TEXT)", ())
+    .await
+    .unwrap();
+
+  let query = "SELECT * FROM slow WHERE value = 'slow_query_handler'";
+  let (sender, receiver) = std::sync::mpsc::channel::<crate::SlowQuery>();
+  let sender = parking_lot::Mutex::new(sender);
+  crate::set_slow_query_handler(std::time::Duration::ZERO, move |slow_query| {
+    // NOTE: The handler is process-wide and other tests may run concurrently.
+    if slow_query.sql == query {
+      sender.lock().send(slow_query).unwrap();
+    }
+  });
+
+  conn.read_query_rows(query, ()).await.unwrap();
+  crate::clear_slow_query_handler();
+
+  let slow_query = receiver.recv().unwrap();
+  assert_eq!(slow_query.database, None);
+  assert!(
+    slow_query.query_plan.contains("SCAN slow"),
+    "{}",
+    slow_query.query_plan
+  );
+}
@@ -53,3 +53,57 @@ pub struct HttpContextUser {
   /// The "expected" CSRF token as included in the auth token claims [User] was constructed from.
   pub csrf_token: String,
 }
+
+/// Registered HTTP handlers with this prefix followed by `<api_name>/<operation>` are installed as
+/// Record API hooks by the host rather than as public routes, see [RecordHookRequest].
+pub const RECORD_HOOK_PATH_PREFIX: &str = "/__record_hook/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize, TS)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordHookOperation {
+  Insert,
+  Update,
+  Delete,
+}
+
+impl RecordHookOperation {
+  pub fn name(self) -> &'static str {
+    return match self {
+      Self::Insert => "insert",
+      Self::Update => "update",
+      Self::Delete => "delete",
+    };
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    return match name {
+      "insert" => Some(Self::Insert),
+      "update" => Some(Self::Update),
+      "delete" => Some(Self::Delete),
+      _ => None,
+    };
+  }
+}
+
+/// Path under which a record hook for the given Record API and operation is registered.
+pub fn record_hook_path(api_name: &str, operation: RecordHookOperation) -> String {
+  return format!("{RECORD_HOOK_PATH_PREFIX}{api_name}/{}", operation.name());
+}
+
+/// JSON body of requests to record hooks, which are sent as `POST` with the acting user in the
+/// "__context" header.
+///
+/// Hooks run before a record is inserted, updated or deleted. They may respond with a JSON object
+/// replacing the record of inserts and updates, with an empty body to leave it unchanged, or with
+/// a 4xx status code to reject the request.
+#[derive(Clone, Debug, Deserialize, Serialize, TS)]
+#[ts(export)]
+pub struct RecordHookRequest {
+  pub api_name: String,
+  pub operation: RecordHookOperation,
+  /// Id of the record as used in Record API paths. Absent for inserts.
+  pub record_id: Option<String>,
+  /// The record for inserts or the partial record for updates. Absent for deletions.
+  #[ts(type = "{ [key: string]: unknown } | null")]
+  pub record: Option<serde_json::Map<String, serde_json::Value>>,
+}
@@ -0,0 +1,35 @@
+use trailbase_wasm_common::record_hook_path;
+
+use crate::http::{HttpError, HttpRoute, Json, Method, Request, StatusCode, User};
+
+pub use trailbase_wasm_common::{RecordHookOperation, RecordHookRequest};
+
+pub type Record = serde_json::Map<String, serde_json::Value>;
+
+/// Builds a hook, which is called before records of the given Record API are inserted, updated or
+/// deleted. Returning a record replaces the one being inserted or updated, returning an error
+/// rejects the request.
+///
+/// Hooks are registered alongside HTTP handlers, i.e. returned from [crate::Guest::http_handlers].
+pub fn record_hook<F>(api_name: &str, operation: RecordHookOperation, f: F) -> HttpRoute
+where
+  F: (AsyncFn(RecordHookRequest, Option<User>) -> Result<Option<Record>, HttpError>)
+    + Send
+    + Sync
+    + 'static,
+{
+  return HttpRoute::new(
+    Method::POST,
+    record_hook_path(api_name, operation),
+    async move |mut req: Request| -> Result<Json<Option<Record>>, HttpError> {
+      let request = req
+        .body()
+        .json::<RecordHookRequest>()
+        .await
+        .map_err(|err| HttpError::message(StatusCode::BAD_REQUEST, err))?;
+      let user = req.user().cloned();
+
+      return Ok(Json(f(request, user).await?));
+    },
+  );
+}
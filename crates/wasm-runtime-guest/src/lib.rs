@@ -29,6 +29,7 @@ pub mod wit {
 pub mod db;
 pub mod fetch;
 pub mod fs;
+pub mod hook;
 pub mod http;
 pub mod job;
 pub mod kv;
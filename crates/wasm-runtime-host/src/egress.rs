@@ -0,0 +1,152 @@
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::time::Duration;
+use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::p2::body::{HyperIncomingBody, HyperOutgoingBody};
+use wasmtime_wasi_http::p2::types::{IncomingResponse, OutgoingRequestConfig};
+
+/// Restricts outgoing HTTP requests of guests, e.g. `fetch()` calls to third-party APIs.
+#[derive(Clone, Debug, Default)]
+pub struct EgressPolicy {
+  /// Hosts guests may send requests to, e.g. "api.stripe.com", or "*.slack.com" for all
+  /// sub-domains. Any host is allowed if `None`.
+  pub allowed_hosts: Option<Vec<String>>,
+  /// Timeout for connecting and receiving the response headers as well as between response body
+  /// chunks.
+  pub timeout: Option<Duration>,
+  /// Maximum size of response bodies in bytes.
+  pub max_response_bytes: Option<usize>,
+}
+
+impl EgressPolicy {
+  pub fn is_allowed(&self, host: &str) -> bool {
+    let Some(ref allowed_hosts) = self.allowed_hosts else {
+      return true;
+    };
+
+    let host = host.to_ascii_lowercase();
+    return allowed_hosts.iter().any(|allowed| {
+      let allowed = allowed.to_ascii_lowercase();
+      return match allowed.strip_prefix("*.") {
+        Some(domain) => host
+          .strip_suffix(domain)
+          .is_some_and(|prefix| prefix.ends_with('.')),
+        None => host == allowed,
+      };
+    });
+  }
+}
+
+pub(crate) async fn send_request(
+  policy: EgressPolicy,
+  request: hyper::Request<HyperOutgoingBody>,
+  mut config: OutgoingRequestConfig,
+) -> Result<IncomingResponse, ErrorCode> {
+  let host = request.uri().host().unwrap_or_default();
+  if !policy.is_allowed(host) {
+    log::debug!("WASM egress to '{host}' denied by policy");
+    return Err(ErrorCode::HttpRequestDenied);
+  }
+
+  if let Some(timeout) = policy.timeout {
+    config.connect_timeout = config.connect_timeout.min(timeout);
+    config.first_byte_timeout = config.first_byte_timeout.min(timeout);
+    config.between_bytes_timeout = config.between_bytes_timeout.min(timeout);
+  }
+
+  let mut response = wasmtime_wasi_http::p2::default_send_request_handler(request, config).await?;
+
+  if let Some(limit) = policy.max_response_bytes {
+    let content_length = response
+      .resp
+      .headers()
+      .get(hyper::header::CONTENT_LENGTH)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len > limit as u64) {
+      return Err(ErrorCode::HttpResponseBodySize(content_length));
+    }
+
+    response.resp = response.resp.map(|body| {
+      use http_body_util::BodyExt;
+
+      return LimitedBody {
+        inner: body,
+        remaining: limit,
+        limit,
+      }
+      .boxed();
+    });
+  }
+
+  return Ok(response);
+}
+
+/// Response body failing once more than `limit` bytes have been received.
+struct LimitedBody {
+  inner: HyperIncomingBody,
+  remaining: usize,
+  limit: usize,
+}
+
+impl Body for LimitedBody {
+  type Data = Bytes;
+  type Error = ErrorCode;
+
+  fn poll_frame(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+    let this = &mut *self;
+    return match Pin::new(&mut this.inner).poll_frame(cx) {
+      Poll::Ready(Some(Ok(frame))) => {
+        if let Some(data) = frame.data_ref() {
+          let Some(remaining) = this.remaining.checked_sub(data.len()) else {
+            return Poll::Ready(Some(Err(ErrorCode::HttpResponseBodySize(Some(
+              this.limit as u64,
+            )))));
+          };
+          this.remaining = remaining;
+        }
+        Poll::Ready(Some(Ok(frame)))
+      }
+      poll => poll,
+    };
+  }
+
+  fn is_end_stream(&self) -> bool {
+    return self.inner.is_end_stream();
+  }
+
+  fn size_hint(&self) -> SizeHint {
+    return self.inner.size_hint();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_egress_allowlist() {
+    let policy = EgressPolicy::default();
+    assert!(policy.is_allowed("example.com"));
+
+    let policy = EgressPolicy {
+      allowed_hosts: Some(vec![
+        "api.stripe.com".to_string(),
+        "*.slack.com".to_string(),
+      ]),
+      ..Default::default()
+    };
+    assert!(policy.is_allowed("api.stripe.com"));
+    assert!(policy.is_allowed("API.Stripe.com"));
+    assert!(!policy.is_allowed("stripe.com"));
+    assert!(!policy.is_allowed("api.stripe.com.evil.org"));
+    assert!(policy.is_allowed("hooks.slack.com"));
+    assert!(!policy.is_allowed("slack.com"));
+    assert!(!policy.is_allowed("evilslack.com"));
+  }
+}
@@ -64,6 +64,8 @@ pub struct SharedState {
   pub conn: Option<trailbase_sqlite::Connection>,
   pub kv_store: trailbase_wasi_keyvalue::Store,
   pub fs_root_path: Option<PathBuf>,
+  /// Policy for outgoing HTTP requests, which can be updated at runtime.
+  pub egress_policy: parking_lot::RwLock<crate::EgressPolicy>,
 }
 
 /// State for one runtime instance.
@@ -73,6 +75,7 @@ pub struct State {
   pub(crate) http_ctx: WasiHttpCtx,
   pub(crate) hooks: Hooks,
   pub(crate) kv: WasiKeyValueCtx,
+  pub(crate) limits: wasmtime::StoreLimits,
 
   // A mutex of a DB lock.
   #[deprecated = "Used by deprecated `tx-*` free functions. Will be removed in favor of the `TransactionImpl` resource."]
@@ -130,9 +133,16 @@ impl WasiHttpHooks for Hooks {
           ),
         )
       }
-      _ => Ok(wasmtime_wasi_http::p2::default_send_request(
-        request, config,
-      )),
+      _ => {
+        let policy = self.shared.egress_policy.read().clone();
+        Ok(
+          wasmtime_wasi_http::p2::types::HostFutureIncomingResponse::pending(
+            wasmtime_wasi::runtime::spawn(async move {
+              Ok(crate::egress::send_request(policy, request, config).await)
+            }),
+          ),
+        )
+      }
     };
   }
 }
@@ -2,6 +2,7 @@
 #![allow(clippy::needless_return)]
 #![warn(clippy::await_holding_lock, clippy::inefficient_to_string)]
 
+mod egress;
 pub mod functions;
 mod host;
 mod sqlite;
@@ -11,12 +12,12 @@ use core::future::Future;
 use http_body_util::combinators::UnsyncBoxBody;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
 use tokio::task::JoinError;
 use trailbase_wasi_keyvalue::WasiKeyValueCtx;
 use wasmtime::component::{Component, Linker, ResourceTable};
-use wasmtime::{AsContextMut, Config, Engine, Result, Store};
+use wasmtime::{AsContextMut, Config, Engine, Result, Store, StoreLimitsBuilder};
 use wasmtime_wasi::{DirPerms, FilePerms, WasiCtxBuilder};
 use wasmtime_wasi_http::WasiHttpCtx;
 use wasmtime_wasi_http::p2::WasiHttpView;
@@ -25,6 +26,7 @@ use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
 use crate::host::TransactionImpl;
 use crate::host::exports::trailbase::component::init_endpoint::Arguments;
 
+pub use crate::egress::EgressPolicy;
 pub use crate::host::exports::trailbase::component::init_endpoint::HttpMethodType;
 pub use crate::host::{SharedState, State};
 pub use trailbase_wasi_keyvalue::Store as KvStore;
@@ -41,6 +43,10 @@ pub enum Error {
   HttpErrorCode(ErrorCode),
   #[error("Encoding")]
   Encoding,
+  #[error("Component disabled")]
+  Disabled,
+  #[error("Deadline exceeded")]
+  DeadlineExceeded,
   #[error("Other: {0}")]
   Other(String),
 }
@@ -55,8 +61,33 @@ pub struct RuntimeOptions {
 
   /// Which tokio runtime handle to execute on.
   pub tokio_runtime: Option<tokio::runtime::Handle>,
+
+  /// Per-component resource limits.
+  pub limits: ResourceLimits,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct ResourceLimits {
+  /// Maximum linear memory of a component instance in bytes.
+  pub max_memory_bytes: Option<usize>,
+
+  /// Maximum wall-clock time of a single invocation, e.g. an HTTP request or a job run.
+  pub deadline: Option<Duration>,
 }
 
+/// Invocation counters of a component since it has been loaded.
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeMetrics {
+  pub in_flight: usize,
+  pub calls: u64,
+  pub errors: u64,
+  pub deadlines_exceeded: u64,
+}
+
+/// Interval at which guests yield back to the host executor, which ensures that long-running
+/// guest code doesn't stall other tasks and that deadlines are enforced.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
 pub trait StoreBuilder<S> {
   fn new_store(&self, engine: &Engine) -> Result<Store<S>, Error>;
 }
@@ -74,6 +105,42 @@ struct RuntimeInternal<T: StoreBuilder<State>> {
 
   rt_handle: tokio::runtime::Handle,
   local_in_flight: AtomicUsize,
+
+  limits: ResourceLimits,
+  enabled: AtomicBool,
+  /// Notified when the component gets disabled to abort in-flight invocations.
+  killed: tokio::sync::Notify,
+  calls: AtomicU64,
+  errors: AtomicU64,
+  deadlines_exceeded: AtomicU64,
+}
+
+impl<T: StoreBuilder<State>> RuntimeInternal<T> {
+  fn new_store(&self) -> Result<Store<State>, Error> {
+    let mut store = self.store_builder.new_store(&self.engine)?;
+
+    if let Some(max_memory_bytes) = self.limits.max_memory_bytes {
+      store.data_mut().limits = StoreLimitsBuilder::new()
+        .memory_size(max_memory_bytes)
+        .build();
+    }
+    store.limiter(|state| &mut state.limits);
+
+    // Yield on every tick. Deadlines themselves are enforced by the caller.
+    store.epoch_deadline_async_yield_and_update(1);
+
+    return Ok(store);
+  }
+
+  /// Resolves once the component is disabled, see [RuntimeT::set_enabled].
+  async fn disabled(&self) {
+    // NOTE: Register before checking the flag to not miss concurrent notifications.
+    let notified = self.killed.notified();
+    if !self.enabled.load(Ordering::Relaxed) {
+      return;
+    }
+    notified.await;
+  }
 }
 
 #[derive(Clone)]
@@ -93,7 +160,19 @@ impl<T: StoreBuilder<State>> RuntimeT<T> {
       let cache = wasmtime::Cache::new(wasmtime::CacheConfig::default())?;
       let config = build_config(Some(cache), opts.use_winch);
 
-      Engine::new(&config)?
+      let engine = Engine::new(&config)?;
+
+      // Drive epoch-based interruption for as long as the engine is alive.
+      let weak = engine.weak();
+      std::thread::spawn(move || {
+        while let Some(engine) = weak.upgrade() {
+          engine.increment_epoch();
+          drop(engine);
+          std::thread::sleep(EPOCH_TICK);
+        }
+      });
+
+      engine
     };
 
     // Load the component - a very expensive operation generating code. Compilation happens in
@@ -151,6 +230,12 @@ impl<T: StoreBuilder<State>> RuntimeT<T> {
       store_builder,
       rt_handle,
       local_in_flight: AtomicUsize::new(0),
+      limits: opts.limits,
+      enabled: AtomicBool::new(true),
+      killed: tokio::sync::Notify::new(),
+      calls: AtomicU64::new(0),
+      errors: AtomicU64::new(0),
+      deadlines_exceeded: AtomicU64::new(0),
     });
 
     return Ok(Self { state });
@@ -160,8 +245,30 @@ impl<T: StoreBuilder<State>> RuntimeT<T> {
     return &self.state.component_path;
   }
 
+  pub fn metrics(&self) -> RuntimeMetrics {
+    return RuntimeMetrics {
+      in_flight: self.state.local_in_flight.load(Ordering::Relaxed),
+      calls: self.state.calls.load(Ordering::Relaxed),
+      errors: self.state.errors.load(Ordering::Relaxed),
+      deadlines_exceeded: self.state.deadlines_exceeded.load(Ordering::Relaxed),
+    };
+  }
+
+  pub fn enabled(&self) -> bool {
+    return self.state.enabled.load(Ordering::Relaxed);
+  }
+
+  /// Kill switch: invocations of a disabled component fail with [Error::Disabled]. In-flight
+  /// invocations are aborted.
+  pub fn set_enabled(&self, enabled: bool) {
+    self.state.enabled.store(enabled, Ordering::Relaxed);
+    if !enabled {
+      self.state.killed.notify_waiters();
+    }
+  }
+
   async fn new_bindings(&self) -> Result<(Store<State>, crate::host::Interfaces), Error> {
-    let mut store = self.state.store_builder.new_store(&self.state.engine)?;
+    let mut store = self.state.new_store()?;
 
     let bindings = crate::host::Interfaces::instantiate_async(
       &mut store,
@@ -222,6 +329,7 @@ impl StoreBuilder<State> for Arc<SharedState> {
           shared: self.clone(),
         },
         kv: WasiKeyValueCtx::new(self.kv_store.clone()),
+        limits: Default::default(),
         #[allow(deprecated)]
         tx: tokio::sync::Mutex::new(TransactionImpl::default()),
         shared: self.clone(),
@@ -290,8 +398,7 @@ impl HttpStore {
         })
         .await?
     })
-    .await
-    .map_err(|join_err| Error::Other(join_err.to_string()))?;
+    .await;
   }
 
   pub async fn call_incoming_http_handler(
@@ -317,13 +424,11 @@ impl HttpStore {
       // In the current setup, if the listening side hangs-up the they call may not be aborted.
       // Depends on what the implementation does when the streaming body's receiving end gets
       // out of scope.
-      let handle = tokio::spawn(async move {
+      let runtime_state = state.runtime_state.clone();
+      let deadline = runtime_state.limits.deadline;
+      let call = with_deadline(deadline, async move {
         // Instantiate a store per request, see FIXME below.
-        let mut lock = state
-          .rt
-          .state
-          .store_builder
-          .new_store(&state.rt.state.engine)?;
+        let mut lock = state.rt.state.new_store()?;
         // let (mut lock, _bindings) = state.rt.new_bindings().await?;
 
         let proxy_bindings = wasmtime_wasi_http::p2::bindings::Proxy::instantiate_async(
@@ -349,8 +454,11 @@ impl HttpStore {
         proxy_bindings
           .wasi_http_incoming_handler()
           .call_handle(lock.as_context_mut(), req, out)
-          .await
+          .await?;
+
+        Ok(())
       });
+      let handle = tokio::spawn(with_kill_switch(runtime_state, call));
 
       match receiver.await {
         Ok(Ok(resp)) => {
@@ -373,19 +481,18 @@ impl HttpStore {
         }
       }
     })
-    .await
-    .map_err(|join_err| Error::Other(join_err.to_string()))?;
+    .await;
   }
 
-  fn call<F>(
-    rt: &Arc<RuntimeInternal<Arc<SharedState>>>,
-    f: F,
-  ) -> impl Future<Output = Result<F::Output, JoinError>>
+  async fn call<F, O>(rt: &Arc<RuntimeInternal<Arc<SharedState>>>, f: F) -> Result<O, Error>
   where
-    F: Future + Send + 'static,
-    F::Output: Send + 'static,
+    F: Future<Output = Result<O, Error>> + Send + 'static,
+    O: Send + 'static,
   {
     let state = rt.clone();
+    if !state.enabled.load(Ordering::Relaxed) {
+      return Err(Error::Disabled);
+    }
 
     #[cfg(debug_assertions)]
     log::debug!(
@@ -398,17 +505,65 @@ impl HttpStore {
     state.local_in_flight.fetch_add(1, Ordering::Relaxed);
     IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
 
-    return rt.rt_handle.spawn(async move {
-      let r = f.await;
-
-      IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
-      state.local_in_flight.fetch_sub(1, Ordering::Relaxed);
+    let deadline = state.limits.deadline;
+    let result = rt
+      .rt_handle
+      .spawn(async move {
+        let r = with_kill_switch(state.clone(), with_deadline(deadline, f)).await;
+
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+        state.local_in_flight.fetch_sub(1, Ordering::Relaxed);
+
+        state.calls.fetch_add(1, Ordering::Relaxed);
+        match r {
+          Ok(_) => {}
+          Err(Error::DeadlineExceeded) => {
+            log::warn!(
+              "WASM component {path:?} exceeded its deadline",
+              path = state.component_path
+            );
+            state.errors.fetch_add(1, Ordering::Relaxed);
+            state.deadlines_exceeded.fetch_add(1, Ordering::Relaxed);
+          }
+          Err(_) => {
+            state.errors.fetch_add(1, Ordering::Relaxed);
+          }
+        };
+
+        r
+      })
+      .await;
 
-      r
-    });
+    return result.map_err(|join_err: JoinError| Error::Other(join_err.to_string()))?;
   }
 }
 
+/// Aborts `f`, e.g. a guest stuck in an infinite loop, once `deadline` has elapsed. This works
+/// since guests yield back to the executor periodically, see [EPOCH_TICK].
+async fn with_deadline<O>(
+  deadline: Option<Duration>,
+  f: impl Future<Output = Result<O, Error>>,
+) -> Result<O, Error> {
+  let Some(deadline) = deadline else {
+    return f.await;
+  };
+  return tokio::time::timeout(deadline, f)
+    .await
+    .map_err(|_| Error::DeadlineExceeded)?;
+}
+
+/// Aborts `f`, once the component gets disabled via the kill switch. Like deadlines, this works
+/// for busy guests, since they yield back to the executor periodically.
+async fn with_kill_switch<T: StoreBuilder<State>, O>(
+  rt: Arc<RuntimeInternal<T>>,
+  f: impl Future<Output = Result<O, Error>>,
+) -> Result<O, Error> {
+  return tokio::select! {
+    result = f => result,
+    _ = rt.disabled() => Err(Error::Disabled),
+  };
+}
+
 pub fn find_wasm_components(components_path: impl AsRef<std::path::Path>) -> Vec<PathBuf> {
   let Ok(dir) = std::fs::read_dir(components_path.as_ref()) else {
     return vec![];
@@ -441,7 +596,7 @@ fn build_config(cache: Option<wasmtime::Cache>, use_winch: bool) -> Config {
   let mut config = Config::new();
 
   // Execution settings:
-  config.epoch_interruption(false);
+  config.epoch_interruption(true);
   config.memory_reservation(64 * 1024 * 1024 /* bytes */);
   config.wasm_component_model(true);
   // config.wasm_backtrace_details(wasmtime::WasmBacktraceDetails::Enable);
@@ -500,16 +655,25 @@ mod tests {
   const WASM_COMPONENT_PATH: &str = "../../client/testfixture/wasm/wasm_guest_testfixture.wasm";
 
   fn init_runtime(conn: Option<trailbase_sqlite::Connection>) -> Runtime {
+    return init_runtime_with_limits(conn, ResourceLimits::default());
+  }
+
+  fn init_runtime_with_limits(
+    conn: Option<trailbase_sqlite::Connection>,
+    limits: ResourceLimits,
+  ) -> Runtime {
     let shared_state = Arc::new(SharedState {
       conn,
       kv_store: KvStore::new(),
       fs_root_path: None,
+      egress_policy: Default::default(),
     });
 
     return Runtime::init(
       WASM_COMPONENT_PATH.into(),
       shared_state,
       RuntimeOptions {
+        limits,
         ..Default::default()
       },
     )
@@ -633,6 +797,101 @@ mod tests {
     }
   }
 
+  #[tokio::test]
+  async fn test_deadline() {
+    let runtime = init_runtime_with_limits(
+      None,
+      ResourceLimits {
+        deadline: Some(Duration::from_millis(100)),
+        ..Default::default()
+      },
+    );
+
+    // A busy guest gets terminated.
+    let result = send_http_request(
+      &runtime,
+      "http://localhost:4000/fibonacci?n=100",
+      "/fibonacci",
+    )
+    .await;
+    assert!(matches!(result, Err(Error::DeadlineExceeded)), "{result:?}");
+
+    let metrics = runtime.metrics();
+    assert_eq!(metrics.in_flight, 0);
+    assert_eq!(metrics.errors, 1);
+    assert_eq!(metrics.deadlines_exceeded, 1);
+
+    // Invocations within the deadline are unaffected.
+    let response = send_http_request(
+      &runtime,
+      "http://localhost:4000/fibonacci?n=10",
+      "/fibonacci",
+    )
+    .await
+    .unwrap();
+    assert_eq!(55, response_to_i64(response).await);
+  }
+
+  #[tokio::test]
+  async fn test_memory_limit() {
+    let runtime = init_runtime_with_limits(
+      None,
+      ResourceLimits {
+        max_memory_bytes: Some(64 * 1024),
+        ..Default::default()
+      },
+    );
+
+    // The component's linear memory exceeds the limit.
+    assert!(
+      send_http_request(&runtime, "http://localhost:4000/method", "/method")
+        .await
+        .is_err()
+    );
+
+    let metrics = runtime.metrics();
+    assert_eq!(metrics.in_flight, 0);
+    assert_eq!(metrics.errors, 1);
+  }
+
+  #[tokio::test]
+  async fn test_kill_switch() {
+    let runtime = Arc::new(init_runtime(None));
+
+    let busy = {
+      let runtime = runtime.clone();
+      tokio::spawn(async move {
+        return send_http_request(
+          &runtime,
+          "http://localhost:4000/fibonacci?n=100",
+          "/fibonacci",
+        )
+        .await;
+      })
+    };
+
+    while runtime.metrics().in_flight == 0 {
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // Disabling the component aborts the in-flight invocation and rejects new ones.
+    runtime.set_enabled(false);
+    let result = busy.await.unwrap();
+    assert!(matches!(result, Err(Error::Disabled)), "{result:?}");
+    assert_eq!(runtime.metrics().in_flight, 0);
+
+    assert!(matches!(
+      send_http_request(&runtime, "http://localhost:4000/method", "/method").await,
+      Err(Error::Disabled)
+    ));
+
+    runtime.set_enabled(true);
+    let response = send_http_request(&runtime, "http://localhost:4000/method", "/method")
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+  }
+
   async fn send_http_request(
     runtime: &Runtime,
     uri: &str,
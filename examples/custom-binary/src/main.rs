@@ -1,24 +1,18 @@
 use axum::{
-  extract::{FromRef, State},
+  Extension,
   response::{Html, IntoResponse, Response},
   routing::{Router, get},
 };
 use trailbase::{AppState, DataDir, Server, ServerOptions, User};
 
 #[derive(Clone)]
-struct CustomState {
-  state: AppState,
-  greeting: Option<String>,
-}
-
-impl FromRef<CustomState> for AppState {
-  fn from_ref(s: &CustomState) -> Self {
-    s.state.clone()
-  }
-}
+struct Greeting(Option<String>);
 
-async fn hello_world_handler(State(state): State<CustomState>, user: Option<User>) -> Response {
-  let greeting = state.greeting.as_deref().unwrap_or("Hello");
+async fn hello_world_handler(
+  Extension(greeting): Extension<Greeting>,
+  user: Option<User>,
+) -> Response {
+  let greeting = greeting.0.as_deref().unwrap_or("Hello");
   let subject = match user {
     Some(ref user) => user
       .email
@@ -45,12 +39,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     .install_default()
     .expect("Failed to install rustls crypto");
 
-  let Server {
-    state,
-    main_router,
-    admin_router,
-    tls,
-  } = Server::init_with_custom_initializer(
+  let app = Server::init_with_custom_initializer(
     ServerOptions {
       data_dir: DataDir::default(),
       address: "localhost:4004".to_string(),
@@ -59,6 +48,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
       log_responses: true,
       dev: false,
       cors_allowed_origins: vec![],
+      custom_routers: vec![
+        Router::new()
+          .route("/", get(hello_world_handler))
+          .layer(Extension(Greeting(Some("Hi".to_string())))),
+      ],
       ..Default::default()
     },
     |state: AppState| async move {
@@ -68,20 +62,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   )
   .await?;
 
-  let router = {
-    let custom_router = Router::new()
-      .route("/", get(hello_world_handler))
-      .with_state(CustomState {
-        state,
-        greeting: Some("Hi".to_string()),
-      })
-      .merge(main_router.1);
-
-    (main_router.0, custom_router)
-  };
-
-  let (cleanup_sender, _cleanup_receiver) = tokio::sync::oneshot::channel::<()>();
-  trailbase::api::serve(router, admin_router, tls, cleanup_sender).await?;
+  app.serve().await?;
 
   Ok(())
 }
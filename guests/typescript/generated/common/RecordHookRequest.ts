@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RecordHookOperation } from "./RecordHookOperation";
+
+/**
+ * JSON body of requests to record hooks, which are sent as `POST` with the acting user in the
+ * "__context" header.
+ *
+ * Hooks run before a record is inserted, updated or deleted. They may respond with a JSON object
+ * replacing the record of inserts and updates, with an empty body to leave it unchanged, or with
+ * a 4xx status code to reject the request.
+ */
+export type RecordHookRequest = { api_name: string, operation: RecordHookOperation, 
+/**
+ * Id of the record as used in Record API paths. Absent for inserts.
+ */
+record_id: string | null, 
+/**
+ * The record for inserts or the partial record for updates. Absent for deletions.
+ */
+record: { [key: string]: unknown } | null, };
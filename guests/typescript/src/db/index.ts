@@ -4,8 +4,10 @@ import { Transaction as WasiTransaction } from "trailbase:database/sqlite@0.1.1"
 
 import type { SqliteRequest } from "@common/SqliteRequest";
 import type { Value } from "./value";
+import type { Params } from "./params";
 
 import { SqlValue } from "@common/SqlValue";
+import { bindParams } from "./params";
 import {
   fromJsonSqlValue,
   fromWitValue,
@@ -14,6 +16,7 @@ import {
 } from "./value";
 
 export type { Value } from "trailbase:database/sqlite@0.1.1";
+export type { Params } from "./params";
 export { escape } from "./value";
 
 export class Transaction {
@@ -23,14 +26,16 @@ export class Transaction {
     this.tx = new WasiTransaction();
   }
 
-  query(query: string, params: Value[]): Value[][] {
+  query(query: string, params: Params): Value[][] {
+    const [sql, values] = bindParams(query, params);
     return this.tx
-      .query(query, params.map(toWitValue))
+      .query(sql, values.map(toWitValue))
       .map((row) => row.map(fromWitValue));
   }
 
-  execute(query: string, params: Value[]): number {
-    return Number(this.tx.execute(query, params.map(toWitValue)));
+  execute(query: string, params: Params): number {
+    const [sql, values] = bindParams(query, params);
+    return Number(this.tx.execute(sql, values.map(toWitValue)));
   }
 
   commit(): void {
@@ -41,13 +46,30 @@ export class Transaction {
   }
 }
 
+/// Runs `fn` within a transaction, which is committed if `fn` returns and rolled
+/// back if it throws.
+export async function transaction<T>(
+  fn: (tx: Transaction) => T | Promise<T>,
+): Promise<T> {
+  const tx = new Transaction();
+  try {
+    const result = await fn(tx);
+    tx.commit();
+    return result;
+  } catch (err) {
+    tx.rollback();
+    throw err;
+  }
+}
+
 export async function query(
   query: string,
-  params: Value[],
+  params: Params,
 ): Promise<Value[][]> {
+  const [sql, values] = bindParams(query, params);
   const body: SqliteRequest = {
-    query,
-    params: params.map(toJsonSqlValue),
+    query: sql,
+    params: values.map(toJsonSqlValue),
   };
   const reply = await fetch("http://__sqlite/query", {
     method: "POST",
@@ -71,10 +93,14 @@ export async function query(
   }
 }
 
-export async function execute(query: string, params: Value[]): Promise<number> {
+export async function execute(
+  query: string,
+  params: Params,
+): Promise<number> {
+  const [sql, values] = bindParams(query, params);
   const body: SqliteRequest = {
-    query,
-    params: params.map(toJsonSqlValue),
+    query: sql,
+    params: values.map(toJsonSqlValue),
   };
   const reply = await fetch("http://__sqlite/execute", {
     method: "POST",
@@ -0,0 +1,84 @@
+import type { Value } from "./value";
+
+/// Query parameters: either positional, e.g. `?1`, or named, e.g. `:id`, `@id` or
+/// `$id`, where names are given w/o their prefix.
+export type Params = Value[] | Record<string, Value>;
+
+/// Rewrites named parameters to positional ones, since the host only binds
+/// positional parameters. Quoted strings, identifiers and comments are skipped.
+export function bindParams(query: string, params: Params): [string, Value[]] {
+  if (Array.isArray(params)) {
+    return [query, params];
+  }
+
+  const indexes = new Map<string, number>();
+  const values: Value[] = [];
+  let result = "";
+
+  let i = 0;
+  while (i < query.length) {
+    const c = query[i];
+
+    const skipUntil = (end: string, from: number): number => {
+      const idx = query.indexOf(end, from);
+      return idx < 0 ? query.length : idx + end.length;
+    };
+
+    let next: number;
+    switch (c) {
+      case "'":
+      case '"':
+      case "`":
+        next = skipUntil(c, i + 1);
+        break;
+      case "[":
+        next = skipUntil("]", i + 1);
+        break;
+      case "-":
+        next = query[i + 1] === "-" ? skipUntil("\n", i) : i + 1;
+        break;
+      case "/":
+        next = query[i + 1] === "*" ? skipUntil("*/", i + 2) : i + 1;
+        break;
+      case "?":
+        throw new Error(`Positional parameter in query with named params`);
+      case ":":
+      case "@":
+      case "$": {
+        const match = /^[A-Za-z0-9_]+/.exec(query.slice(i + 1));
+        if (!match) {
+          next = i + 1;
+          break;
+        }
+
+        const name = match[0];
+        if (!(name in params)) {
+          throw new Error(`Missing named parameter: ${c}${name}`);
+        }
+
+        let index = indexes.get(name);
+        if (index === undefined) {
+          values.push(params[name]);
+          index = values.length;
+          indexes.set(name, index);
+        }
+
+        result += `?${index}`;
+        i += name.length + 1;
+        continue;
+      }
+      default:
+        next = i + 1;
+    }
+
+    result += query.slice(i, next);
+    i = next;
+  }
+
+  const unused = Object.keys(params).filter((name) => !indexes.has(name));
+  if (unused.length > 0) {
+    throw new Error(`Unused named parameters: ${unused.join(", ")}`);
+  }
+
+  return [result, values];
+}
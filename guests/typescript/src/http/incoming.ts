@@ -10,7 +10,12 @@ import type { HttpHandlerInterface, ResponseType } from "./index";
 import { StatusCode } from "./index";
 import { HttpError, HttpResponse, buildResponse } from "./response";
 import { type Method, HttpRequestImpl } from "./request";
-import { JobHandlerInterface } from "../job";
+import { JobHandlerInterface, scheduledJobHandlers } from "../job";
+import {
+  UploadScannerInterface,
+  buildUploadScannerHttpHandler,
+  uploadScannerPath,
+} from "../upload";
 import { awaitPendingTimers } from "../timer";
 
 type IncomingHandler = (
@@ -29,13 +34,19 @@ function httpKey(path: string, method: Method): string {
 export function buildIncomingHttpHandler(args: {
   httpHandlers?: HttpHandlerInterface[];
   jobHandlers?: JobHandlerInterface[];
+  uploadScanners?: UploadScannerInterface[];
 }): IncomingHandler {
-  const httpHandlers = Object.fromEntries(
-    (args.httpHandlers ?? []).map((h) => [
+  const httpHandlers = Object.fromEntries([
+    ...(args.httpHandlers ?? []).map((h) => [
       httpKey(h.path, h.method),
       h.handler,
     ]),
-  );
+    // Upload scanners are dispatched like regular HTTP handlers.
+    ...(args.uploadScanners ?? []).map((s) => [
+      httpKey(uploadScannerPath(s.name), "post"),
+      buildUploadScannerHttpHandler(s.handler),
+    ]),
+  ]);
   const jobHandlers = Object.fromEntries(
     (args.jobHandlers ?? []).map((h) => [h.name, h.handler]),
   );
@@ -51,7 +62,10 @@ export function buildIncomingHttpHandler(args: {
     );
 
     if (context.kind === "Job") {
-      const handler = jobHandlers[context.registered_path];
+      const handler =
+        jobHandlers[context.registered_path] ??
+        scheduledJobHandlers().find((h) => h.name === context.registered_path)
+          ?.handler;
       if (!handler) {
         throw new HttpError(StatusCode.NOT_FOUND, "impl not found");
       }
@@ -10,11 +10,15 @@ import type {
   Error as SqliteError,
   dispatchScalarFunction,
 } from "trailbase:component/sqlite-function-endpoint@0.1.1";
-import type { HttpHandlerInterface } from "./http";
+import type { HttpHandlerInterface, Method } from "./http";
 import type { JobHandlerInterface } from "./job";
+import { scheduledJobHandlers } from "./job";
+import type { UploadScannerInterface } from "./upload";
+import { uploadScannerPath } from "./upload";
 import { buildIncomingHttpHandler } from "./http/incoming";
 
 export { addPeriodicCallback } from "./timer";
+export { scheduleJob } from "./job";
 
 export * from "./util";
 
@@ -43,6 +47,7 @@ export function defineConfig(opts: {
   init?: (args: InitArgs) => void;
   httpHandlers?: HttpHandlerInterface[];
   jobHandlers?: JobHandlerInterface[];
+  uploadScanners?: UploadScannerInterface[];
 }): Config {
   return {
     incomingHandler: {
@@ -55,7 +60,14 @@ export function defineConfig(opts: {
         });
 
         return {
-          handlers: (opts.httpHandlers ?? []).map((h) => [h.method, h.path]),
+          handlers: [
+            ...(opts.httpHandlers ?? []).map(
+              (h) => [h.method, h.path] as [Method, string],
+            ),
+            ...(opts.uploadScanners ?? []).map(
+              (s) => ["post", uploadScannerPath(s.name)] as [Method, string],
+            ),
+          ],
         };
       },
       initJobHandlers: function (args: Arguments): JobHandlers {
@@ -64,7 +76,10 @@ export function defineConfig(opts: {
         });
 
         return {
-          handlers: (opts.jobHandlers ?? []).map((h) => [h.name, h.spec]),
+          handlers: [
+            ...(opts.jobHandlers ?? []),
+            ...scheduledJobHandlers(),
+          ].map((h) => [h.name, h.spec]),
         };
       },
       initSqliteFunctions: function (args: Arguments): SqliteFunctions {
@@ -25,6 +25,28 @@ export class JobHandler implements JobHandlerInterface {
   }
 }
 
+const scheduledJobs: JobHandlerInterface[] = [];
+
+/// Registers a job running `handler` on the given cron `spec`, e.g. "0 0 3 * * *",
+/// alongside jobs passed to `defineConfig`. Must be called at module load time.
+export function scheduleJob(
+  name: string,
+  spec: string,
+  handler: JobHandlerType,
+): JobHandler {
+  if (scheduledJobs.some((job) => job.name === name)) {
+    throw new Error(`Job already scheduled: ${name}`);
+  }
+
+  const job = new JobHandler(name, spec, handler);
+  scheduledJobs.push(job);
+  return job;
+}
+
+export function scheduledJobHandlers(): JobHandlerInterface[] {
+  return scheduledJobs;
+}
+
 function validateSpec(spec: string) {
   switch (spec) {
     case "@hourly":
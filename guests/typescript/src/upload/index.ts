@@ -0,0 +1,60 @@
+import type { HttpHandlerCallback, HttpRequest } from "../http";
+import { HttpError, StatusCode } from "../http";
+
+/// Registered HTTP handlers with this prefix are installed as upload scanners
+/// by the host rather than as public routes.
+export const UPLOAD_SCANNER_PATH_PREFIX = "/__upload_scanner/";
+
+export type UploadedFile = {
+  filename: string | undefined;
+  contentType: string | undefined;
+  contents: Uint8Array;
+};
+
+/// Returns a reason to reject the file or nothing if the file is clean.
+export type UploadScannerCallback = (
+  file: UploadedFile,
+) => string | void | Promise<string | void>;
+
+export interface UploadScannerInterface {
+  name: string;
+  handler: UploadScannerCallback;
+}
+
+/// Scans every file before it's written to the object store, e.g. to reject
+/// disallowed content. Applies to all file uploads including avatars.
+export class UploadScanner implements UploadScannerInterface {
+  constructor(
+    public readonly name: string,
+    public readonly handler: UploadScannerCallback,
+  ) {}
+}
+
+export function uploadScannerPath(name: string): string {
+  return `${UPLOAD_SCANNER_PATH_PREFIX}${name}`;
+}
+
+export function buildUploadScannerHttpHandler(
+  handler: UploadScannerCallback,
+): HttpHandlerCallback {
+  return async (req: HttpRequest) => {
+    const filename = header(req, "x-filename");
+    const reason = await handler({
+      filename:
+        filename !== undefined
+          ? decodeURIComponent(filename.replace(/\+/g, " "))
+          : undefined,
+      contentType: header(req, "content-type"),
+      contents: req.body() ?? new Uint8Array(),
+    });
+
+    if (typeof reason === "string") {
+      throw new HttpError(StatusCode.UNPROCESSABLE_ENTITY, reason);
+    }
+  };
+}
+
+function header(req: HttpRequest, name: string): string | undefined {
+  const value = req.headers().find(([k]) => k.toLowerCase() === name)?.[1];
+  return value !== undefined ? new TextDecoder().decode(value) : undefined;
+}
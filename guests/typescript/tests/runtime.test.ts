@@ -1,4 +1,4 @@
-import { test } from "vitest";
+import { test, vi } from "vitest";
 import {
   escape,
   fromJsonSqlValue,
@@ -6,8 +6,32 @@ import {
   toJsonSqlValue,
   toWitValue,
 } from "../src/db/value";
+import { bindParams } from "../src/db/params";
+import { transaction } from "../src/db";
+import { scheduleJob, scheduledJobHandlers } from "../src/job";
 import { urlSafeBase64Encode, urlSafeBase64Decode } from "../src/util";
 
+// Records calls to the host's transaction API.
+const txLog = vi.hoisted(() => [] as string[]);
+vi.mock("trailbase:database/sqlite@0.1.1", () => ({
+  Transaction: class {
+    query(query: string): unknown[][] {
+      txLog.push(`query: ${query}`);
+      return [];
+    }
+    execute(query: string): bigint {
+      txLog.push(`execute: ${query}`);
+      return 1n;
+    }
+    commit() {
+      txLog.push("commit");
+    }
+    rollback() {
+      txLog.push("rollback");
+    }
+  },
+}));
+
 test("base64", ({ expect }) => {
   expect(
     urlSafeBase64Decode(urlSafeBase64Encode(Uint8Array.from([0]))),
@@ -60,3 +84,60 @@ test("SQL escape", ({ expect }) => {
   expect(escape("foo'")).toEqual("'foo'''");
   expect(escape("foo\0")).toEqual("'foo\\0'");
 });
+
+test("Schedule jobs", ({ expect }) => {
+  const handler = () => {};
+  scheduleJob("cleanup", "0 0 3 * * *", handler);
+
+  expect(scheduledJobHandlers().map((h) => [h.name, h.spec])).toEqual([
+    ["cleanup", "0 0 3 * * *"],
+  ]);
+  expect(() => scheduleJob("cleanup", "@daily", handler)).toThrow();
+  expect(() => scheduleJob("other", "* *", handler)).toThrow();
+});
+
+test("Named params", ({ expect }) => {
+  expect(bindParams("SELECT ?1", [5])).toEqual(["SELECT ?1", [5]]);
+  expect(
+    bindParams("SELECT * FROM t WHERE a = :a AND b = @b OR c = :a", {
+      a: 1,
+      b: "x",
+    }),
+  ).toEqual(["SELECT * FROM t WHERE a = ?1 AND b = ?2 OR c = ?1", [1, "x"]]);
+  expect(
+    bindParams("SELECT ':a', \"$b\" -- :c\n FROM t WHERE x = $d", { d: null }),
+  ).toEqual(["SELECT ':a', \"$b\" -- :c\n FROM t WHERE x = ?1", [null]]);
+
+  expect(() => bindParams("SELECT :missing", {})).toThrow(
+    "Missing named parameter: :missing",
+  );
+  expect(() => bindParams("SELECT :a", { a: 1, extra: 2 })).toThrow(
+    "Unused named parameters: extra",
+  );
+  expect(() => bindParams("SELECT ?", { a: 1 })).toThrow();
+});
+
+test("Transactions", async ({ expect }) => {
+  txLog.length = 0;
+  expect(
+    await transaction((tx) => tx.execute("UPDATE t SET a = :a", { a: 1 })),
+  ).toEqual(1);
+  expect(txLog).toEqual(["execute: UPDATE t SET a = ?1", "commit"]);
+
+  // Throwing rolls back.
+  txLog.length = 0;
+  await expect(
+    transaction(async (tx) => {
+      tx.execute("UPDATE t SET a = 1", []);
+      throw new Error("abort");
+    }),
+  ).rejects.toThrow("abort");
+  expect(txLog).toEqual(["execute: UPDATE t SET a = 1", "rollback"]);
+
+  // ... as do binding errors.
+  txLog.length = 0;
+  await expect(
+    transaction((tx) => tx.query("SELECT :a", { a: 1, b: 2 })),
+  ).rejects.toThrow("Unused named parameters: b");
+  expect(txLog).toEqual(["rollback"]);
+});
@@ -9,6 +9,7 @@ const entryPoints = {
   "http": resolve(__dirname, 'src/http/index.ts'),
   "job": resolve(__dirname, 'src/job/index.ts'),
   "kv": resolve(__dirname, 'src/kv/index.ts'),
+  "upload": resolve(__dirname, 'src/upload/index.ts'),
 };
 
 export default defineConfig({